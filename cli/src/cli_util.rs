@@ -13,6 +13,7 @@
 // limitations under the License.
 
 use core::fmt;
+use std::borrow::Cow;
 use std::collections::{HashMap, HashSet};
 use std::env::{self, ArgsOs, VarError};
 use std::ffi::{OsStr, OsString};
@@ -31,10 +32,14 @@ use clap::builder::{NonEmptyStringValueParser, TypedValueParser, ValueParserFact
 use clap::{Arg, ArgAction, ArgMatches, Command, FromArgMatches};
 use indexmap::{IndexMap, IndexSet};
 use itertools::Itertools;
-use jj_lib::backend::{BackendError, ChangeId, CommitId, MergedTreeId};
+use jj_lib::absorb::AbsorbError;
+use jj_lib::backend::{BackendError, ChangeId, CommitId, MergedTreeId, Timestamp};
 use jj_lib::commit::Commit;
+use jj_lib::content_hash::blake2b_hash;
+use jj_lib::format_version::FormatVersionError;
 use jj_lib::git::{GitConfigParseError, GitExportError, GitImportError, GitRemoteManagementError};
-use jj_lib::git_backend::GitBackend;
+use jj_lib::git_backend::{GitBackend, GitBackendError};
+use jj_lib::gitattributes::GitAttributes;
 use jj_lib::gitignore::GitIgnoreFile;
 use jj_lib::hex_util::to_reverse_hex;
 use jj_lib::id_prefix::IdPrefixContext;
@@ -42,35 +47,42 @@ use jj_lib::matchers::{EverythingMatcher, Matcher, PrefixMatcher};
 use jj_lib::merged_tree::MergedTree;
 use jj_lib::object_id::ObjectId;
 use jj_lib::op_heads_store::{self, OpHeadResolutionError};
-use jj_lib::op_store::{OpStoreError, OperationId, WorkspaceId};
+use jj_lib::op_store::{OpStoreError, OperationId, RefTarget, WorkspaceId};
 use jj_lib::op_walk::OpsetEvaluationError;
 use jj_lib::operation::Operation;
 use jj_lib::repo::{
-    CheckOutCommitError, EditCommitError, MutableRepo, ReadonlyRepo, Repo, RepoLoader,
-    RepoLoaderError, RewriteRootCommit, StoreFactories, StoreLoadError,
+    check_view_commits, AbandonOnCheckout, CheckOutCommitError, EditCommitError, MutableRepo,
+    ReadonlyRepo, Repo, RepoLoader, RepoLoaderError, RepoReadOnly, RewriteRootCommit,
+    StoreFactories, StoreLoadError,
 };
-use jj_lib::repo_path::{FsPathParseError, RepoPath, RepoPathBuf};
+use jj_lib::repo_path::{FsPathParseError, RepoPath, RepoPathBuf, RepoPathUiConverter};
+use jj_lib::repo_scope::{RepoScope, RepoScopeError, WorkspaceScope};
 use jj_lib::revset::{
     DefaultSymbolResolver, Revset, RevsetAliasesMap, RevsetCommitRef, RevsetEvaluationError,
     RevsetExpression, RevsetFilterPredicate, RevsetIteratorExt, RevsetParseContext,
-    RevsetParseError, RevsetParseErrorKind, RevsetResolutionError, RevsetWorkspaceContext,
+    RevsetParseError, RevsetParseErrorKind, RevsetResolutionError, RevsetSymbolKind,
+    RevsetWorkspaceContext,
 };
 use jj_lib::rewrite::restore_tree;
 use jj_lib::settings::{ConfigResultExt as _, UserSettings};
 use jj_lib::signing::SignInitError;
 use jj_lib::str_util::{StringPattern, StringPatternParseError};
-use jj_lib::transaction::Transaction;
+use jj_lib::transaction::{
+    self, ImmutableCommitsValidator, OperationDescription, Transaction, TransactionCommitError,
+    TransactionValidationError, TransactionValidator,
+};
 use jj_lib::tree::TreeMergeError;
 use jj_lib::view::View;
 use jj_lib::working_copy::{
-    CheckoutStats, LockedWorkingCopy, ResetError, SnapshotError, SnapshotOptions, WorkingCopy,
-    WorkingCopyFactory, WorkingCopyStateError,
+    CheckoutError, CheckoutStats, LockedWorkingCopy, ResetError, SnapshotChangeCallback,
+    SnapshotError, SnapshotOptions, SnapshotStats, WorkingCopy, WorkingCopyFactory,
+    WorkingCopyStateError,
 };
 use jj_lib::workspace::{
     default_working_copy_factories, LockedWorkspace, Workspace, WorkspaceInitError,
     WorkspaceLoadError, WorkspaceLoader,
 };
-use jj_lib::{dag_walk, file_util, git, op_walk, revset};
+use jj_lib::{dag_walk, git, op_walk, revset};
 use once_cell::unsync::OnceCell;
 use thiserror::Error;
 use toml_edit;
@@ -78,8 +90,10 @@ use tracing::instrument;
 use tracing_chrome::ChromeLayerBuilder;
 use tracing_subscriber::prelude::*;
 
+use crate::commit_templater::SignatureBatch;
 use crate::config::{
-    new_config_path, AnnotatedValue, CommandNameAndArgs, ConfigSource, LayeredConfigs,
+    check_env_timestamps, new_config_path, AnnotatedValue, CommandNameAndArgs, ConfigSource,
+    LayeredConfigs,
 };
 use crate::formatter::{FormatRecorder, Formatter, PlainTextFormatter};
 use crate::git_util::{
@@ -88,8 +102,8 @@ use crate::git_util::{
 use crate::merge_tools::{ConflictResolveError, DiffEditError, DiffGenerateError};
 use crate::template_parser::{TemplateAliasesMap, TemplateParseError};
 use crate::templater::Template;
-use crate::ui::{ColorChoice, Ui};
-use crate::{commit_templater, text_util};
+use crate::ui::{ColorChoice, Ui, UiWarning, WarningCode, WarningsFormat};
+use crate::{commit_templater, diff_util, text_util, time_util};
 
 #[derive(Clone, Debug)]
 pub enum CommandError {
@@ -101,9 +115,15 @@ pub enum CommandError {
     /// Invalid command line
     CliError(String),
     /// Invalid command line detected by clap
-    ClapCliError(Arc<clap::Error>),
+    ClapCliError {
+        err: Arc<clap::Error>,
+        hint: Option<String>,
+    },
     BrokenPipe,
     InternalError(Arc<dyn std::error::Error + Send + Sync>),
+    /// Not an error. The command wants to report its result through the given
+    /// exit status without printing anything else.
+    ExitStatus(u8),
 }
 
 /// Wraps error with user-visible message.
@@ -243,6 +263,14 @@ impl From<CheckOutCommitError> for CommandError {
 
 impl From<BackendError> for CommandError {
     fn from(err: BackendError) -> Self {
+        if let BackendError::ObjectNotFound { source, .. } = &err {
+            if let Some(GitBackendError::MissingGitObject { hint, .. }) =
+                source.downcast_ref::<GitBackendError>()
+            {
+                let hint = hint.clone();
+                return user_error_with_hint(err, hint);
+            }
+        }
         internal_error_with_message("Unexpected error from backend", err)
     }
 }
@@ -273,6 +301,10 @@ impl From<WorkspaceInitError> for CommandError {
             }
             WorkspaceInitError::SignInit(err @ SignInitError::UnknownBackend(_)) => user_error(err),
             WorkspaceInitError::SignInit(err) => internal_error(err),
+            WorkspaceInitError::MergeDrivers(err) => {
+                user_error_with_message("Invalid merge driver config", err)
+            }
+            WorkspaceInitError::TransactionCommit(err) => err.into(),
         }
     }
 }
@@ -306,17 +338,53 @@ impl From<SnapshotError> for CommandError {
 want this file to be snapshotted. Otherwise add it to your `.gitignore` file."#,
                 err,
             ),
+            SnapshotError::ReservedPath { .. } => {
+                user_error_with_message("Failed to snapshot the working copy", err)
+            }
             err => internal_error_with_message("Failed to snapshot the working copy", err),
         }
     }
 }
 
+impl From<TransactionValidationError> for CommandError {
+    fn from(err: TransactionValidationError) -> Self {
+        match err {
+            TransactionValidationError::Rejected(_) => {
+                user_error_with_message("Refusing to commit the transaction", err)
+            }
+            err => internal_error_with_message("Failed to validate the transaction", err),
+        }
+    }
+}
+
+impl From<TransactionCommitError> for CommandError {
+    fn from(err: TransactionCommitError) -> Self {
+        match err {
+            TransactionCommitError::ReadOnly(err) => err.into(),
+            TransactionCommitError::Validation(err) => err.into(),
+            err @ (TransactionCommitError::Backend(_) | TransactionCommitError::IndexWrite(_)) => {
+                internal_error(err)
+            }
+        }
+    }
+}
+
 impl From<TreeMergeError> for CommandError {
     fn from(err: TreeMergeError) -> Self {
         internal_error_with_message("Merge failed", err)
     }
 }
 
+impl From<AbsorbError> for CommandError {
+    fn from(err: AbsorbError) -> Self {
+        match err {
+            AbsorbError::Backend(err) => err.into(),
+            AbsorbError::TreeMerge(err) => err.into(),
+            AbsorbError::RevsetEvaluation(err) => err.into(),
+        }
+    }
+}
+
 impl From<OpStoreError> for CommandError {
     fn from(err: OpStoreError) -> Self {
         internal_error_with_message("Failed to load an operation", err)
@@ -325,7 +393,24 @@ impl From<OpStoreError> for CommandError {
 
 impl From<RepoLoaderError> for CommandError {
     fn from(err: RepoLoaderError) -> Self {
-        internal_error_with_message("Failed to load the repo", err)
+        match err {
+            RepoLoaderError::ReadOnly(err) => err.into(),
+            RepoLoaderError::TransactionCommit(err) => err.into(),
+            err @ RepoLoaderError::MissingObjectForHead { .. } => user_error_with_hint(
+                err,
+                "Run `jj debug recover-heads` to remove the references to missing commits.",
+            ),
+            err => internal_error_with_message("Failed to load the repo", err),
+        }
+    }
+}
+
+impl From<RepoReadOnly> for CommandError {
+    fn from(err: RepoReadOnly) -> Self {
+        user_error_with_hint(
+            err,
+            "Commands that modify the repo or the working copy need write access to it.",
+        )
     }
 }
 
@@ -435,6 +520,14 @@ impl From<RevsetResolutionError> for CommandError {
                 name: _,
                 candidates,
             } => format_similarity_hint(candidates),
+            RevsetResolutionError::AmbiguousSymbol { name, kinds } => {
+                format_qualified_symbol_hint(name, kinds)
+            }
+            RevsetResolutionError::AmbiguousRefNameCase {
+                name: _,
+                kind: _,
+                candidates,
+            } => format_similarity_hint(candidates),
             RevsetResolutionError::EmptyString
             | RevsetResolutionError::WorkspaceMissingWorkingCopy { .. }
             | RevsetResolutionError::AmbiguousCommitIdPrefix(_)
@@ -445,6 +538,15 @@ impl From<RevsetResolutionError> for CommandError {
     }
 }
 
+fn format_qualified_symbol_hint(name: &str, kinds: &[RevsetSymbolKind]) -> Option<String> {
+    let choices = kinds
+        .iter()
+        .filter_map(|kind| kind.qualifier())
+        .map(|qualifier| format!("`{qualifier}:{name}`"))
+        .collect_vec();
+    (!choices.is_empty()).then(|| format!("Use {} to pick one.", choices.join(" or ")))
+}
+
 impl From<TemplateParseError> for CommandError {
     fn from(err: TemplateParseError) -> Self {
         let message = iter::successors(Some(&err), |e| e.origin()).join("\n");
@@ -460,7 +562,10 @@ impl From<FsPathParseError> for CommandError {
 
 impl From<clap::Error> for CommandError {
     fn from(err: clap::Error) -> Self {
-        CommandError::ClapCliError(Arc::new(err))
+        CommandError::ClapCliError {
+            err: Arc::new(err),
+            hint: None,
+        }
     }
 }
 
@@ -476,6 +581,12 @@ impl From<WorkingCopyStateError> for CommandError {
     }
 }
 
+impl From<RepoScopeError> for CommandError {
+    fn from(err: RepoScopeError) -> Self {
+        internal_error_with_message("Failed to access the workspace scope", err)
+    }
+}
+
 #[derive(Clone)]
 struct ChromeTracingFlushGuard {
     _inner: Option<Rc<tracing_chrome::FlushGuard>>,
@@ -574,6 +685,7 @@ pub struct CommandHelper {
     maybe_workspace_loader: Result<WorkspaceLoader, CommandError>,
     store_factories: StoreFactories,
     working_copy_factories: HashMap<String, Box<dyn WorkingCopyFactory>>,
+    transaction_validators: Vec<Arc<dyn TransactionValidator>>,
 }
 
 impl CommandHelper {
@@ -589,6 +701,7 @@ impl CommandHelper {
         maybe_workspace_loader: Result<WorkspaceLoader, CommandError>,
         store_factories: StoreFactories,
         working_copy_factories: HashMap<String, Box<dyn WorkingCopyFactory>>,
+        transaction_validators: Vec<Arc<dyn TransactionValidator>>,
     ) -> Self {
         // `cwd` is canonicalized for consistency with `Workspace::workspace_root()` and
         // to easily compute relative paths between them.
@@ -605,6 +718,7 @@ impl CommandHelper {
             maybe_workspace_loader,
             store_factories,
             working_copy_factories,
+            transaction_validators,
         }
     }
 
@@ -624,6 +738,18 @@ impl CommandHelper {
         &self.matches
     }
 
+    /// The name of the command being run, including the names of its parent
+    /// commands, such as "git push".
+    pub fn command_name(&self) -> String {
+        let mut names = vec![];
+        let mut matches = &self.matches;
+        while let Some((name, sub_matches)) = matches.subcommand() {
+            names.push(name);
+            matches = sub_matches;
+        }
+        names.join(" ")
+    }
+
     pub fn global_args(&self) -> &GlobalArgs {
         &self.global_args
     }
@@ -669,6 +795,9 @@ impl CommandHelper {
         let workspace = self.load_workspace()?;
         let op_head = self.resolve_operation(ui, workspace.repo_loader())?;
         let repo = workspace.repo_loader().load_at(&op_head)?;
+        if self.global_args.at_operation == "@" {
+            check_view_commits(repo.as_ref())?;
+        }
         self.for_loaded_repo(ui, workspace, repo)
     }
 
@@ -687,22 +816,49 @@ impl CommandHelper {
     #[instrument(skip_all)]
     pub fn load_workspace(&self) -> Result<Workspace, CommandError> {
         let loader = self.workspace_loader()?;
+        let settings = self.settings_for_workspace(loader.workspace_root())?;
         loader
             .load(
-                &self.settings,
+                &settings,
                 &self.store_factories,
                 &self.working_copy_factories,
             )
             .map_err(|err| map_workspace_load_error(err, self.global_args.repository.as_deref()))
     }
 
+    /// The default log revset of a scoped workspace filters commits by the
+    /// paths they touch, so its index keeps changed-path filters even if they
+    /// aren't enabled in the config.
+    fn settings_for_workspace(
+        &self,
+        workspace_root: &Path,
+    ) -> Result<Cow<'_, UserSettings>, CommandError> {
+        let is_scoped = WorkspaceScope::load(workspace_root)?.map_or(false, |workspace_scope| {
+            !workspace_scope.scope.is_everything()
+        });
+        if !is_scoped || self.settings.index_changed_path_filters() {
+            return Ok(Cow::Borrowed(&self.settings));
+        }
+        let config = config::Config::builder()
+            .add_source(self.settings.config().clone())
+            .set_override("index.changed-path-filters", true)?
+            .build()?;
+        Ok(Cow::Owned(UserSettings::from_config(config)))
+    }
+
     #[instrument(skip_all)]
     pub fn resolve_operation(
         &self,
         ui: &mut Ui,
         repo_loader: &RepoLoader,
     ) -> Result<Operation, CommandError> {
-        if self.global_args.at_operation == "@" {
+        if self.global_args.at_operation == "@" && repo_loader.is_read_only() {
+            op_heads_store::resolve_op_heads_read_only(
+                repo_loader.op_heads_store().as_ref(),
+                repo_loader.op_store(),
+                repo_loader.repo_path(),
+            )
+        } else if self.global_args.at_operation == "@" {
             op_heads_store::resolve_op_heads(
                 repo_loader.op_heads_store().as_ref(),
                 repo_loader.op_store(),
@@ -713,8 +869,12 @@ impl CommandHelper {
                     )?;
                     let base_repo = repo_loader.load_at(&op_heads[0])?;
                     // TODO: It may be helpful to print each operation we're merging here
-                    let mut tx =
-                        start_repo_transaction(&base_repo, &self.settings, &self.string_args);
+                    let mut tx = start_repo_transaction(
+                        &base_repo,
+                        &self.settings,
+                        &self.string_args,
+                        &self.command_name(),
+                    );
                     for other_op_head in op_heads.into_iter().skip(1) {
                         tx.merge_operation(other_op_head)?;
                         let num_rebased = tx.mut_repo().rebase_descendants(&self.settings)?;
@@ -727,7 +887,7 @@ impl CommandHelper {
                         }
                     }
                     Ok(tx
-                        .write("resolve concurrent operations")
+                        .write("resolve concurrent operations")?
                         .leave_unpublished()
                         .operation()
                         .clone())
@@ -776,14 +936,32 @@ impl ReadonlyUserRepo {
 pub struct WorkspaceCommandHelper {
     cwd: PathBuf,
     string_args: Vec<String>,
+    command_name: String,
     global_args: GlobalArgs,
     settings: UserSettings,
     workspace: Workspace,
     user_repo: ReadonlyUserRepo,
     revset_aliases_map: RevsetAliasesMap,
     template_aliases_map: TemplateAliasesMap,
-    may_update_working_copy: bool,
+    signature_batch: Rc<SignatureBatch>,
+    working_copy_mode: WorkingCopyMode,
     working_copy_shared_with_git: bool,
+    transaction_validators: Vec<Arc<dyn TransactionValidator>>,
+    path_converter: RepoPathUiConverter,
+    stale_working_copy: Option<StaleWorkingCopy>,
+    snapshot_stats: SnapshotStats,
+    max_template_diff_size: u64,
+}
+
+/// How a command interacts with the working copy on disk.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum WorkingCopyMode {
+    /// Snapshot the working copy before running the command, and update it
+    /// if the command changes the working-copy commit.
+    Snapshot,
+    /// Neither read nor write the working copy on disk. The command operates
+    /// on the last recorded working-copy commit, which may be stale.
+    ReadOnly,
 }
 
 impl WorkspaceCommandHelper {
@@ -804,23 +982,54 @@ impl WorkspaceCommandHelper {
             repo.as_ref(),
             workspace.workspace_id(),
             &id_prefix_context,
+            None,
             &template_aliases_map,
             &command.settings,
         )?;
+        // Likewise, report an invalid abandon-on-checkout policy before any
+        // transaction ends up silently using the default.
+        command.settings.abandon_on_checkout()?;
+        let max_template_diff_size = diff_util::max_template_diff_size(&command.settings)?;
         let loaded_at_head = command.global_args.at_operation == "@";
-        let may_update_working_copy = loaded_at_head && !command.global_args.ignore_working_copy;
+        let working_copy_mode = if loaded_at_head
+            && !repo.is_read_only()
+            && !command.global_args.ignore_working_copy
+            && command.settings.auto_snapshot()
+        {
+            WorkingCopyMode::Snapshot
+        } else {
+            WorkingCopyMode::ReadOnly
+        };
         let working_copy_shared_with_git = is_colocated_git_workspace(&workspace, &repo);
+        let transaction_validators = command
+            .transaction_validators
+            .iter()
+            .cloned()
+            .chain(transaction::builtin_validators(&command.settings)?)
+            .collect();
+        let path_converter = RepoPathUiConverter::new(
+            command.cwd.clone(),
+            workspace.workspace_root().clone(),
+            command.settings.relative_paths() && !command.global_args.repo_paths,
+        );
         let helper = Self {
             cwd: command.cwd.clone(),
             string_args: command.string_args.clone(),
+            command_name: command.command_name(),
             global_args: command.global_args.clone(),
             settings: command.settings.clone(),
             workspace,
             user_repo: ReadonlyUserRepo::new(repo),
             revset_aliases_map,
             template_aliases_map,
-            may_update_working_copy,
+            signature_batch: Rc::default(),
+            working_copy_mode,
             working_copy_shared_with_git,
+            transaction_validators,
+            path_converter,
+            stale_working_copy: None,
+            snapshot_stats: SnapshotStats::default(),
+            max_template_diff_size,
         };
         // Parse short-prefixes revset early to report error before starting mutable
         // operation.
@@ -832,14 +1041,39 @@ impl WorkspaceCommandHelper {
         self.user_repo.git_backend()
     }
 
+    /// Whether this command snapshots and updates the working copy.
+    pub fn working_copy_mode(&self) -> WorkingCopyMode {
+        self.working_copy_mode
+    }
+
+    /// Stats about the snapshot taken when this command started. Empty if no
+    /// snapshot was taken.
+    pub fn snapshot_stats(&self) -> &SnapshotStats {
+        &self.snapshot_stats
+    }
+
+    /// Returns an error if the working copy is stale, and so no operation can
+    /// be recorded. Commands that ask for input, e.g. in an editor, should
+    /// check this first so the input isn't lost.
+    pub fn check_working_copy_fresh(&self) -> Result<(), CommandError> {
+        match &self.stale_working_copy {
+            Some(stale) => Err(stale.to_command_error()),
+            None => Ok(()),
+        }
+    }
+
     pub fn check_working_copy_writable(&self) -> Result<(), CommandError> {
-        if self.may_update_working_copy {
+        self.check_working_copy_fresh()?;
+        if self.working_copy_mode == WorkingCopyMode::Snapshot {
             Ok(())
         } else {
+            self.repo().check_writable()?;
             let hint = if self.global_args.ignore_working_copy {
                 "Don't use --ignore-working-copy."
-            } else {
+            } else if self.global_args.at_operation != "@" {
                 "Don't use --at-op."
+            } else {
+                "Set `snapshot.auto = true` in your config."
             };
             Err(user_error_with_hint(
                 "This command must be able to update the working copy.",
@@ -852,23 +1086,82 @@ impl WorkspaceCommandHelper {
     /// copy is collocated with Git.
     #[instrument(skip_all)]
     pub fn maybe_snapshot(&mut self, ui: &mut Ui) -> Result<(), CommandError> {
-        if self.may_update_working_copy {
+        self.maybe_snapshot_with_callback(ui, |_| Ok(None))
+    }
+
+    /// Snapshots the working copy like `maybe_snapshot()`, reporting changes
+    /// to the callback returned by `make_on_change` as soon as they're found.
+    /// The callback is created after the Git HEAD is imported, so it sees the
+    /// working-copy commit that's about to be snapshotted. No progress bar is
+    /// shown if there's a callback.
+    pub fn maybe_snapshot_with_callback<'a>(
+        &mut self,
+        ui: &mut Ui,
+        make_on_change: impl FnOnce(
+            &Self,
+        )
+            -> Result<Option<Box<SnapshotChangeCallback<'a>>>, CommandError>,
+    ) -> Result<(), CommandError> {
+        if self.working_copy_mode == WorkingCopyMode::Snapshot {
             if self.working_copy_shared_with_git {
                 self.import_git_head(ui)?;
             }
+            let on_change = make_on_change(self)?;
             // Because the Git refs (except HEAD) aren't imported yet, the ref
             // pointing to the new working-copy commit might not be exported.
             // In that situation, the ref would be conflicted anyway, so export
             // failure is okay.
-            self.snapshot_working_copy(ui)?;
-            // import_git_refs() can rebase the working-copy commit.
-            if self.working_copy_shared_with_git {
+            self.snapshot_working_copy(ui, on_change.as_deref())?;
+            // import_git_refs() can rebase the working-copy commit. It would
+            // also record an operation, which isn't allowed if the working
+            // copy is stale.
+            if self.working_copy_shared_with_git && self.stale_working_copy.is_none() {
                 self.import_git_refs(ui)?;
             }
         }
         Ok(())
     }
 
+    /// Snapshots the working copy like `maybe_snapshot()`, unless the
+    /// `expression` provably doesn't refer to a working-copy commit. Commands
+    /// that only show such revisions can then skip walking the working copy.
+    ///
+    /// If the working copy is colocated with Git, the snapshot also imports the
+    /// Git refs, which any revset may depend on, so it's never skipped. It's
+    /// also not skipped if a local ref would be moved by the snapshot.
+    pub fn maybe_snapshot_for_revset(
+        &mut self,
+        ui: &mut Ui,
+        expression: &RevsetExpression,
+    ) -> Result<(), CommandError> {
+        if self.working_copy_shared_with_git
+            || expression.references_working_copy()
+            || self.local_refs_follow_working_copy()?
+        {
+            self.maybe_snapshot(ui)?;
+        }
+        Ok(())
+    }
+
+    /// Returns true if a local ref points to the working-copy commit or one of
+    /// its descendants. Snapshotting rewrites these commits and moves the refs
+    /// along with them.
+    fn local_refs_follow_working_copy(&self) -> Result<bool, CommandError> {
+        let Some(wc_commit_id) = self.get_wc_commit_id() else {
+            return Ok(false);
+        };
+        let local_refs = RevsetExpression::union_all(&[
+            RevsetExpression::branches(StringPattern::everything()),
+            RevsetExpression::tags(StringPattern::everything()),
+            RevsetExpression::git_refs(),
+        ]);
+        let revset = RevsetExpression::commits(vec![wc_commit_id.clone()])
+            .descendants()
+            .intersection(&local_refs)
+            .evaluate_programmatic(self.repo().as_ref())?;
+        Ok(!revset.is_empty())
+    }
+
     /// Imports new HEAD from the colocated Git repo.
     ///
     /// If the Git HEAD has changed, this function abandons our old checkout and
@@ -876,7 +1169,7 @@ impl WorkspaceCommandHelper {
     /// point to the new Git HEAD. The working-copy contents won't be updated.
     #[instrument(skip_all)]
     fn import_git_head(&mut self, ui: &mut Ui) -> Result<(), CommandError> {
-        assert!(self.may_update_working_copy);
+        assert_eq!(self.working_copy_mode, WorkingCopyMode::Snapshot);
         let mut tx = self.start_transaction();
         git::import_head(tx.mut_repo())?;
         if !tx.mut_repo().has_changes() {
@@ -911,7 +1204,7 @@ impl WorkspaceCommandHelper {
             // state to it without updating working copy files.
             locked_ws.locked_wc().reset(&new_git_head_commit)?;
             tx.mut_repo().rebase_descendants(&self.settings)?;
-            self.user_repo = ReadonlyUserRepo::new(tx.commit("import git head"));
+            self.user_repo = ReadonlyUserRepo::new(tx.commit("import git head")?);
             locked_ws.finish(self.user_repo.repo.op_id().clone())?;
             if old_git_head.is_present() {
                 writeln!(
@@ -966,6 +1259,10 @@ impl WorkspaceCommandHelper {
         Ok(())
     }
 
+    pub fn settings(&self) -> &UserSettings {
+        &self.settings
+    }
+
     pub fn repo(&self) -> &Arc<ReadonlyRepo> {
         &self.user_repo.repo
     }
@@ -1015,17 +1312,36 @@ impl WorkspaceCommandHelper {
         self.working_copy_shared_with_git
     }
 
+    /// Formats a path for display, relative to the cwd or to the workspace
+    /// root depending on `ui.relative-paths` and `--repo-paths`.
     pub fn format_file_path(&self, file: &RepoPath) -> String {
-        file_util::relative_path(&self.cwd, &file.to_fs_path(self.workspace_root()))
-            .to_str()
-            .unwrap()
-            .to_owned()
+        self.path_converter.format_file_path(file)
+    }
+
+    pub fn path_converter(&self) -> &RepoPathUiConverter {
+        &self.path_converter
+    }
+
+    /// Writes a formatted path with the directory part and the file name
+    /// labeled separately, so they can be styled differently.
+    pub fn write_file_path(
+        &self,
+        formatter: &mut dyn Formatter,
+        file: &RepoPath,
+    ) -> io::Result<()> {
+        write_formatted_file_path(formatter, &self.format_file_path(file))
     }
 
     /// Parses a path relative to cwd into a RepoPath, which is relative to the
     /// workspace root.
     pub fn parse_file_path(&self, input: &str) -> Result<RepoPathBuf, FsPathParseError> {
-        RepoPathBuf::parse_fs_path(&self.cwd, self.workspace_root(), input)
+        self.path_converter.parse_file_path(input)
+    }
+
+    /// Path prefixes the view of this workspace is restricted to.
+    pub fn repo_scope(&self) -> Result<RepoScope, CommandError> {
+        let workspace_scope = WorkspaceScope::load(self.workspace_root())?;
+        Ok(workspace_scope.map(|ws| ws.scope).unwrap_or_default())
     }
 
     pub fn matcher_from_values(&self, values: &[String]) -> Result<Box<dyn Matcher>, CommandError> {
@@ -1080,6 +1396,25 @@ impl WorkspaceCommandHelper {
         git_ignores
     }
 
+    /// Attributes of paths as declared by the `.gitattributes` files in the
+    /// working-copy commit and by the `info/attributes` file of the backing Git
+    /// repo.
+    pub fn git_attributes(&self) -> Result<GitAttributes, CommandError> {
+        let mut attributes = match self.get_wc_commit_id() {
+            Some(commit_id) => {
+                GitAttributes::new(self.repo().store().get_commit(commit_id)?.tree()?)
+            }
+            None => GitAttributes::empty(),
+        };
+        if let Some(git_backend) = self.git_backend() {
+            let info_path = git_backend.git_repo_path().join("info").join("attributes");
+            if let Ok(content) = fs::read(info_path) {
+                attributes = attributes.with_info_attributes(&content);
+            }
+        }
+        Ok(attributes)
+    }
+
     pub fn resolve_single_op(&self, op_str: &str) -> Result<Operation, OpsetEvaluationError> {
         op_walk::resolve_op_with_repo(self.repo(), op_str)
     }
@@ -1217,6 +1552,11 @@ Set which revision the branch points to with `jj branch set {branch_name} -r <RE
                     RevsetExpression::Latest {
                         candidates,
                         count: _,
+                        by: _,
+                    } => has_legacy_rule(candidates),
+                    RevsetExpression::Limit {
+                        candidates,
+                        count: _,
                     } => has_legacy_rule(candidates),
                     RevsetExpression::Filter(_) => false,
                     RevsetExpression::AsFilter(expression) => has_legacy_rule(expression),
@@ -1234,16 +1574,131 @@ Set which revision the branch points to with `jj branch set {branch_name} -r <RE
                 }
             }
             if has_legacy_rule(&expression) {
-                writeln!(
-                    ui.warning(),
-                    "The `:` revset operator is deprecated. Please switch to `::`."
-                )
+                ui.write_warning(&UiWarning::new(
+                    WarningCode::DeprecatedRevsetOperator,
+                    "The `:` revset operator is deprecated. Please switch to `::`.",
+                ))
                 .ok();
             }
+            if !self.strict_symbols() {
+                self.warn_ambiguous_symbols(ui, &expression);
+            }
         }
         Ok(revset::optimize(expression))
     }
 
+    /// Refuses to write an operation on top of the one loaded by `--at-op`
+    /// unless it's an op head, since that would silently create an operation
+    /// concurrent with the later ones. The operation commands, which are meant
+    /// to deal with old operations, are exempt.
+    fn check_at_op_writable(&self, base_repo: &ReadonlyRepo) -> Result<(), CommandError> {
+        if self.global_args.at_operation == "@"
+            || self.is_operation_command()
+            || self
+                .settings
+                .config()
+                .get_bool("operation.allow-at-op-mutations")
+                .unwrap_or(false)
+        {
+            return Ok(());
+        }
+        let op_heads = base_repo.op_heads_store().get_op_heads();
+        if op_heads.is_empty() {
+            return Err(OpHeadResolutionError::NoHeads.into());
+        }
+        if op_heads.contains(base_repo.op_id()) {
+            return Ok(());
+        }
+        Err(user_error_with_hint(
+            format!(
+                "Cannot modify the repo at operation {}, which is not the latest operation",
+                short_operation_hash(base_repo.op_id())
+            ),
+            "Run the command without --at-op, or set `operation.allow-at-op-mutations = true` to \
+             create an operation concurrent with the later ones.",
+        ))
+    }
+
+    /// Whether the command is one of the `jj op` commands, or the `jj undo`
+    /// shortcut.
+    fn is_operation_command(&self) -> bool {
+        self.command_name.starts_with("operation ") || self.command_name == "undo"
+    }
+
+    /// Rejects rewriting the commits that are immutable in the current repo.
+    /// The operation commands are exempt, since restoring an old operation
+    /// may bring back earlier versions of immutable commits.
+    fn immutable_commits_validator(
+        &self,
+    ) -> Result<Option<ImmutableCommitsValidator>, CommandError> {
+        if self.is_operation_command() {
+            return Ok(None);
+        }
+        let immutable_heads = self
+            .evaluate_revset(self.immutable_heads_expression()?)?
+            .iter()
+            .collect();
+        Ok(Some(ImmutableCommitsValidator::new(immutable_heads)))
+    }
+
+    fn strict_symbols(&self) -> bool {
+        self.settings
+            .config()
+            .get_bool("revsets.strict-symbols")
+            .unwrap_or(false)
+    }
+
+    /// Whether branch and tag names may be looked up ignoring case.
+    pub(crate) fn case_insensitive_refs(&self) -> bool {
+        self.settings
+            .config()
+            .get_bool("ui.case-insensitive-refs")
+            .unwrap_or(false)
+    }
+
+    /// Warns about symbols that match more than one of a tag, a branch, a git
+    /// ref, a commit id, and a change id. Errors are left to the evaluation.
+    fn warn_ambiguous_symbols(&self, ui: &Ui, expression: &Rc<RevsetExpression>) {
+        let symbols = expression.symbols();
+        if symbols.is_empty() {
+            return;
+        }
+        let Ok(symbol_resolver) = self.revset_symbol_resolver() else {
+            return;
+        };
+        // Single-character names like `a` shadow an id prefix in most small
+        // repos, so warning about them would be mostly noise.
+        for symbol in symbols.iter().unique().filter(|symbol| symbol.len() > 1) {
+            let Ok(matches) = symbol_resolver.resolve_symbol_matches(symbol) else {
+                continue;
+            };
+            if matches.len() < 2 {
+                continue;
+            }
+            let mut warning = UiWarning::new(
+                WarningCode::AmbiguousRevision,
+                format!(
+                    r#"Revision "{symbol}" is ambiguous; using the {}. It matches:"#,
+                    matches[0].0
+                ),
+            );
+            for (kind, ids) in &matches {
+                for id in ids {
+                    let summary = match self.repo().store().get_commit(id) {
+                        Ok(commit) => self.format_commit_summary(&commit),
+                        Err(_) => short_commit_hash(id),
+                    };
+                    warning = warning.with_detail(format!("{kind}: {summary}"));
+                }
+            }
+            let kinds = matches.iter().map(|(kind, _)| *kind).collect_vec();
+            if let Some(hint) = format_qualified_symbol_hint(symbol, &kinds) {
+                warning = warning.with_hint(hint);
+            }
+            ui.write_warning(&warning).ok();
+        }
+    }
+
     pub fn evaluate_revset<'repo>(
         &'repo self,
         revset_expression: Rc<RevsetExpression>,
@@ -1275,7 +1730,9 @@ Set which revision the branch points to with `jj branch set {branch_name} -r <RE
             Box::new(|repo, prefix| id_prefix_context.resolve_change_prefix(repo, prefix));
         let symbol_resolver = DefaultSymbolResolver::new(self.repo().as_ref())
             .with_commit_id_resolver(commit_id_resolver)
-            .with_change_id_resolver(change_id_resolver);
+            .with_change_id_resolver(change_id_resolver)
+            .with_strict(self.strict_symbols())
+            .with_case_insensitive_refs(self.case_insensitive_refs());
         Ok(symbol_resolver)
     }
 
@@ -1313,12 +1770,26 @@ Set which revision the branch points to with `jj branch set {branch_name} -r <RE
             self.repo().as_ref(),
             self.workspace_id(),
             id_prefix_context,
+            self.signature_batch.clone(),
+            Some(self),
             template_text,
             &self.template_aliases_map,
         )?;
         Ok(template)
     }
 
+    /// The size in bytes after which a diff rendered by a commit template is
+    /// truncated.
+    pub fn max_template_diff_size(&self) -> u64 {
+        self.max_template_diff_size
+    }
+
+    /// Commits whose signatures will be verified in one batch when a template
+    /// parsed by `parse_commit_template()` first needs one of them.
+    pub fn signature_batch(&self) -> &SignatureBatch {
+        &self.signature_batch
+    }
+
     /// Returns one-line summary of the given `commit`.
     pub fn format_commit_summary(&self, commit: &Commit) -> String {
         let mut output = Vec::new();
@@ -1341,6 +1812,7 @@ Set which revision the branch points to with `jj branch set {branch_name} -r <RE
             self.repo().as_ref(),
             self.workspace_id(),
             id_prefix_context,
+            Some(self),
             &self.template_aliases_map,
             &self.settings,
         )
@@ -1349,16 +1821,7 @@ Set which revision the branch points to with `jj branch set {branch_name} -r <RE
         Ok(())
     }
 
-    pub fn check_rewritable<'a>(
-        &self,
-        commits: impl IntoIterator<Item = &'a Commit>,
-    ) -> Result<(), CommandError> {
-        let to_rewrite_revset = RevsetExpression::commits(
-            commits
-                .into_iter()
-                .map(|commit| commit.id().clone())
-                .collect(),
-        );
+    fn immutable_heads_expression(&self) -> Result<Rc<RevsetExpression>, CommandError> {
         let (params, immutable_heads_str) = self
             .revset_aliases_map
             .get_function("immutable_heads")
@@ -1368,12 +1831,37 @@ Set which revision the branch points to with `jj branch set {branch_name} -r <RE
                 r#"The `revset-aliases.immutable_heads()` function must be declared without arguments."#,
             ));
         }
-        let immutable_heads_revset = self.parse_revset(immutable_heads_str, None)?;
-        let immutable_revset = immutable_heads_revset
+        self.parse_revset(immutable_heads_str, None)
+    }
+
+    /// Returns the expression of the commits that can't be rewritten: the
+    /// ancestors of `immutable_heads()`, and the root commit.
+    pub fn immutable_expression(&self) -> Result<Rc<RevsetExpression>, CommandError> {
+        Ok(self
+            .immutable_heads_expression()?
             .ancestors()
             .union(&RevsetExpression::commit(
                 self.repo().store().root_commit_id().clone(),
-            ));
+            )))
+    }
+
+    /// Fails early if any of the `commits` is immutable, before the command
+    /// does any work. Transactions are also checked by an
+    /// `ImmutableCommitsValidator` when they're committed.
+    pub fn check_rewritable<'a>(
+        &self,
+        commits: impl IntoIterator<Item = &'a Commit>,
+    ) -> Result<(), CommandError> {
+        // Rewriting records an operation, which fails if the working copy is
+        // stale. Failing before the command asks for any input is friendlier.
+        self.check_working_copy_fresh()?;
+        let to_rewrite_revset = RevsetExpression::commits(
+            commits
+                .into_iter()
+                .map(|commit| commit.id().clone())
+                .collect(),
+        );
+        let immutable_revset = self.immutable_expression()?;
         let revset = self.evaluate_revset(to_rewrite_revset.intersection(&immutable_revset))?;
         if let Some(commit) = revset.iter().commits(self.repo().store()).next() {
             let commit = commit?;
@@ -1395,6 +1883,46 @@ Set which revision the branch points to with `jj branch set {branch_name} -r <RE
         Ok(())
     }
 
+    /// Returns the local branches pointing at `from` that should follow a new
+    /// child commit, per the `experimental-advance-branches` settings.
+    ///
+    /// Callers should only advance branches to a commit with a single parent,
+    /// so branches never move across merges.
+    pub fn get_advanceable_branches(
+        &self,
+        from: &CommitId,
+    ) -> Result<Vec<AdvanceableBranch>, CommandError> {
+        let ab_settings = AdvanceBranchesSettings::from_config(self.settings.config())?;
+        if ab_settings.enabled_branches.is_empty() {
+            return Ok(vec![]);
+        }
+        let branches = self
+            .repo()
+            .view()
+            .local_branches()
+            .filter(|(name, target)| {
+                target.as_normal() == Some(from) && ab_settings.branch_is_eligible(name)
+            })
+            .map(|(name, _)| AdvanceableBranch {
+                name: name.to_owned(),
+                old_commit_id: from.clone(),
+            })
+            .collect();
+        Ok(branches)
+    }
+
+    /// Whether `jj new` on top of the working-copy commit `wc_commit` should
+    /// advance the branches on its parent to it, per
+    /// `experimental-advance-branches.on-new`.
+    pub fn advances_branches_on_new(&self, wc_commit: &Commit) -> Result<bool, CommandError> {
+        let ab_settings = AdvanceBranchesSettings::from_config(self.settings.config())?;
+        Ok(match ab_settings.on_new {
+            AdvanceOnNew::NonEmpty => !wc_commit.is_discardable(),
+            AdvanceOnNew::Described => !wc_commit.description().is_empty(),
+            AdvanceOnNew::Never => false,
+        })
+    }
+
     pub fn check_non_empty(&self, commits: &[Commit]) -> Result<(), CommandError> {
         if commits.is_empty() {
             return Err(user_error("Empty revision set"));
@@ -1403,7 +1931,11 @@ Set which revision the branch points to with `jj branch set {branch_name} -r <RE
     }
 
     #[instrument(skip_all)]
-    fn snapshot_working_copy(&mut self, ui: &mut Ui) -> Result<(), CommandError> {
+    fn snapshot_working_copy(
+        &mut self,
+        ui: &mut Ui,
+        on_change: Option<&SnapshotChangeCallback>,
+    ) -> Result<(), CommandError> {
         let workspace_id = self.workspace_id().to_owned();
         let get_wc_commit = |repo: &ReadonlyRepo| -> Result<Option<_>, _> {
             repo.view()
@@ -1436,15 +1968,21 @@ Set which revision the branch points to with `jj branch set {branch_name} -r <RE
                     (repo, wc_commit)
                 }
                 Ok(WorkingCopyFreshness::WorkingCopyStale) => {
-                    return Err(user_error_with_hint(
+                    // Read-only commands can still show the repo, but commands
+                    // that would record a new operation are refused.
+                    let stale = StaleWorkingCopy::new(&repo, &workspace_id, old_op_id, &wc_commit);
+                    let warning = UiWarning::new(
+                        WarningCode::StaleWorkingCopy,
                         format!(
-                            "The working copy is stale (not updated since operation {}).",
-                            short_operation_hash(&old_op_id)
+                            "The working copy is stale (not updated since operation {}), so it \
+                             wasn't snapshotted.",
+                            short_operation_hash(&stale.operation_id)
                         ),
-                        "Run `jj workspace update-stale` to update it.
-See https://github.com/martinvonz/jj/blob/main/docs/working-copy.md#stale-working-copy \
-                         for more information.",
-                    ));
+                    )
+                    .with_hint("Run `jj workspace update-stale` to update it.");
+                    ui.write_warning(&warning)?;
+                    self.stale_working_copy = Some(stale);
+                    return Ok(());
                 }
                 Ok(WorkingCopyFreshness::SiblingOperation) => {
                     return Err(internal_error(format!(
@@ -1465,23 +2003,33 @@ See https://github.com/martinvonz/jj/blob/main/docs/working-copy.md#stale-workin
                 Err(e) => return Err(e.into()),
             };
         self.user_repo = ReadonlyUserRepo::new(repo);
-        let progress = crate::progress::snapshot_progress(ui);
+        let progress = on_change
+            .is_none()
+            .then(|| crate::progress::snapshot_progress(ui))
+            .flatten();
         let new_tree_id = locked_ws.locked_wc().snapshot(SnapshotOptions {
             base_ignores,
             fsmonitor_kind: self.settings.fsmonitor_kind()?,
             progress: progress.as_ref().map(|x| x as _),
+            on_change,
+            cancel: None,
             max_new_file_size: self.settings.max_new_file_size()?,
         })?;
         drop(progress);
+        self.snapshot_stats = locked_ws.locked_wc().snapshot_stats();
         if new_tree_id != *wc_commit.tree_id() {
-            let mut tx =
-                start_repo_transaction(&self.user_repo.repo, &self.settings, &self.string_args);
+            let mut tx = start_repo_transaction(
+                &self.user_repo.repo,
+                &self.settings,
+                &self.string_args,
+                &self.command_name,
+            );
             let mut_repo = tx.mut_repo();
             let commit = mut_repo
                 .rewrite_commit(&self.settings, &wc_commit)
                 .set_tree_id(new_tree_id)
                 .write()?;
-            mut_repo.set_wc_commit(workspace_id, commit.id().clone())?;
+            mut_repo.set_wc_commit(workspace_id.clone(), commit.id().clone())?;
 
             // Rebase descendants
             let num_rebased = mut_repo.rebase_descendants(&self.settings)?;
@@ -1497,7 +2045,19 @@ See https://github.com/martinvonz/jj/blob/main/docs/working-copy.md#stale-workin
                 print_failed_git_export(ui, &failed_branches)?;
             }
 
-            self.user_repo = ReadonlyUserRepo::new(tx.commit("snapshot working copy"));
+            // Other workspaces may have been updated to the replaced operation,
+            // so it's only replaced if their working-copy commits are unchanged.
+            if num_rebased == 0
+                && is_coalescable_snapshot(
+                    self.user_repo.repo.operation(),
+                    &workspace_id,
+                    &self.settings,
+                )?
+            {
+                tx.replace_base_operation()?;
+            }
+            self.user_repo = ReadonlyUserRepo::new(tx.commit(SNAPSHOT_DESCRIPTION)?);
+            warn_about_clock_skew(ui, self.user_repo.repo.operation(), &self.settings)?;
         }
         locked_ws.finish(self.user_repo.repo.op_id().clone())?;
         Ok(())
@@ -1509,7 +2069,7 @@ See https://github.com/martinvonz/jj/blob/main/docs/working-copy.md#stale-workin
         maybe_old_commit: Option<&Commit>,
         new_commit: &Commit,
     ) -> Result<(), CommandError> {
-        assert!(self.may_update_working_copy);
+        assert_eq!(self.working_copy_mode, WorkingCopyMode::Snapshot);
         let stats = update_working_copy(
             &self.user_repo.repo,
             &mut self.workspace,
@@ -1536,7 +2096,15 @@ See https://github.com/martinvonz/jj/blob/main/docs/working-copy.md#stale-workin
     }
 
     pub fn start_transaction(&mut self) -> WorkspaceCommandTransaction {
-        let tx = start_repo_transaction(self.repo(), &self.settings, &self.string_args);
+        let mut tx = start_repo_transaction(
+            self.repo(),
+            &self.settings,
+            &self.string_args,
+            &self.command_name,
+        );
+        for validator in &self.transaction_validators {
+            tx.add_validator(validator.clone());
+        }
         WorkspaceCommandTransaction { helper: self, tx }
     }
 
@@ -1550,10 +2118,44 @@ See https://github.com/martinvonz/jj/blob/main/docs/working-copy.md#stale-workin
             writeln!(ui.stderr(), "Nothing changed.")?;
             return Ok(());
         }
+        self.check_working_copy_fresh()?;
+        tx.base_repo().check_writable()?;
+        self.check_at_op_writable(tx.base_repo())?;
+        if let Some(validator) = self.immutable_commits_validator()? {
+            tx.add_validator(Arc::new(validator));
+        }
+        // With the default policy, only empty undescribed working-copy commits
+        // are abandoned, which has always happened silently. Otherwise, every
+        // abandoned commit is listed so it can be recovered from the op log.
+        if self.settings.abandon_on_checkout()? != AbandonOnCheckout::default() {
+            for commit_id in tx.mut_repo().abandoned_on_checkout() {
+                let commit = tx.repo().store().get_commit(commit_id)?;
+                writeln!(
+                    ui.stderr(),
+                    "Abandoned empty working-copy commit {}",
+                    short_change_hash(commit.change_id())
+                )?;
+            }
+        }
         let num_rebased = tx.mut_repo().rebase_descendants(&self.settings)?;
         if num_rebased > 0 {
             writeln!(ui.stderr(), "Rebased {num_rebased} descendant commits")?;
         }
+        for conflict in tx.mut_repo().collapsed_conflicts() {
+            ui.write_warning(&UiWarning::new(
+                WarningCode::CollapsedConflict,
+                format!(
+                    "The conflict in {} had more than {} sides, and was collapsed into a 2-sided \
+                     conflict in commit {}",
+                    self.format_file_path(&conflict.path),
+                    self.settings.max_conflict_sides(),
+                    short_commit_hash(&conflict.commit_id),
+                ),
+            ))?;
+        }
+        // Validate before exporting to Git, so nothing is exported if the
+        // transaction is rejected
+        let validated = tx.validate()?;
 
         let old_repo = tx.base_repo().clone();
 
@@ -1576,25 +2178,44 @@ See https://github.com/martinvonz/jj/blob/main/docs/working-copy.md#stale-workin
             let failed_branches = git::export_refs(tx.mut_repo())?;
             print_failed_git_export(ui, &failed_branches)?;
         }
-        self.user_repo = ReadonlyUserRepo::new(tx.commit(description));
+        self.user_repo = ReadonlyUserRepo::new(
+            tx.commit_validated(OperationDescription::new(description), validated)?,
+        );
+        warn_about_clock_skew(ui, self.user_repo.repo.operation(), &self.settings)?;
         self.report_repo_changes(ui, &old_repo)?;
 
-        if self.may_update_working_copy {
-            if let Some(new_commit) = &maybe_new_wc_commit {
+        match (self.working_copy_mode, &maybe_new_wc_commit) {
+            (WorkingCopyMode::Snapshot, Some(new_commit)) => {
                 self.update_working_copy(ui, maybe_old_wc_commit.as_ref(), new_commit)?;
-            } else {
+            }
+            (WorkingCopyMode::ReadOnly, Some(new_commit))
+                if self.global_args.at_operation == "@"
+                    && maybe_old_wc_commit.as_ref() != Some(new_commit) =>
+            {
+                // The files on disk are left alone, so tell the user which
+                // commit they should be at.
+                write!(ui.stderr(), "Working copy not updated, should be at: ")?;
+                ui.stderr_formatter().with_label("working_copy", |fmt| {
+                    self.write_commit_summary(fmt, new_commit)
+                })?;
+                writeln!(ui.stderr())?;
+            }
+            (_, Some(_)) => {}
+            (_, None) => {
                 // It seems the workspace was deleted, so we shouldn't try to
                 // update it.
             }
         }
         let settings = &self.settings;
         if settings.user_name().is_empty() || settings.user_email().is_empty() {
-            writeln!(
-                ui.warning(),
-                r#"Name and email not configured. Until configured, your commits will be created with the empty identity, and can't be pushed to remotes. To configure, run:
-  jj config set --user user.name "Some One"
-  jj config set --user user.email "someone@example.com""#
-            )?;
+            let warning = UiWarning::new(
+                WarningCode::MissingIdentity,
+                "Name and email not configured. Until configured, your commits will be created \
+                 with the empty identity, and can't be pushed to remotes. To configure, run:",
+            )
+            .with_detail(r#"jj config set --user user.name "Some One""#)
+            .with_detail(r#"jj config set --user user.email "someone@example.com""#);
+            ui.write_warning(&warning)?;
         }
         Ok(())
     }
@@ -1738,6 +2359,78 @@ Then run `jj squash` to move the resolution into the conflicted commit."#,
     }
 }
 
+/// A local branch that may follow a new child of the commit it points to.
+#[derive(Clone, Debug)]
+pub struct AdvanceableBranch {
+    name: String,
+    old_commit_id: CommitId,
+}
+
+/// Which working-copy commits `jj new` advances branches to, per
+/// `experimental-advance-branches.on-new`.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum AdvanceOnNew {
+    /// Commits that aren't both empty and undescribed.
+    NonEmpty,
+    /// Commits with a description.
+    Described,
+    /// No commits; only `jj commit` advances branches.
+    Never,
+}
+
+/// The `experimental-advance-branches` settings.
+struct AdvanceBranchesSettings {
+    enabled_branches: Vec<StringPattern>,
+    on_new: AdvanceOnNew,
+}
+
+impl AdvanceBranchesSettings {
+    fn from_config(config: &config::Config) -> Result<Self, CommandError> {
+        let patterns: Vec<String> = config
+            .get("experimental-advance-branches.enabled-branches")
+            .optional()?
+            .unwrap_or_default();
+        let enabled_branches = patterns
+            .iter()
+            .map(|pattern| {
+                StringPattern::glob(pattern).map_err(|err| {
+                    user_error_with_message(
+                        format!(
+                            "Invalid pattern '{pattern}' in \
+                             experimental-advance-branches.enabled-branches"
+                        ),
+                        err,
+                    )
+                })
+            })
+            .try_collect()?;
+        let on_new: Option<String> = config
+            .get("experimental-advance-branches.on-new")
+            .optional()?;
+        let on_new = match on_new.as_deref() {
+            None | Some("non-empty") => AdvanceOnNew::NonEmpty,
+            Some("described") => AdvanceOnNew::Described,
+            Some("never") => AdvanceOnNew::Never,
+            Some(other) => {
+                return Err(user_error(format!(
+                    "Invalid experimental-advance-branches.on-new value '{other}'; expected \
+                     'non-empty', 'described', or 'never'"
+                )))
+            }
+        };
+        Ok(AdvanceBranchesSettings {
+            enabled_branches,
+            on_new,
+        })
+    }
+
+    fn branch_is_eligible(&self, name: &str) -> bool {
+        self.enabled_branches
+            .iter()
+            .any(|pattern| pattern.matches(name))
+    }
+}
+
 #[must_use]
 pub struct WorkspaceCommandTransaction<'a> {
     helper: &'a mut WorkspaceCommandHelper,
@@ -1773,6 +2466,31 @@ impl WorkspaceCommandTransaction<'_> {
         self.tx.mut_repo().edit(workspace_id, commit)
     }
 
+    /// Moves the given branches to `move_to`, unless they were moved by
+    /// something else in this transaction.
+    pub fn advance_branches(
+        &mut self,
+        ui: &Ui,
+        branches: Vec<AdvanceableBranch>,
+        move_to: &CommitId,
+    ) -> Result<(), CommandError> {
+        for branch in branches {
+            let old_target = RefTarget::normal(branch.old_commit_id);
+            if self.repo().get_local_branch(&branch.name) != old_target {
+                continue;
+            }
+            self.mut_repo()
+                .set_local_branch_target(&branch.name, RefTarget::normal(move_to.clone()));
+            writeln!(
+                ui.stderr(),
+                "Advanced branch {} to {}",
+                branch.name,
+                short_commit_hash(move_to)
+            )?;
+        }
+        Ok(())
+    }
+
     pub fn run_mergetool(
         &self,
         ui: &Ui,
@@ -1787,7 +2505,7 @@ impl WorkspaceCommandTransaction<'_> {
 
     pub fn edit_diff(
         &self,
-        ui: &Ui,
+        ui: &mut Ui,
         left_tree: &MergedTree,
         right_tree: &MergedTree,
         matcher: &dyn Matcher,
@@ -1808,7 +2526,7 @@ impl WorkspaceCommandTransaction<'_> {
 
     pub fn select_diff(
         &self,
-        ui: &Ui,
+        ui: &mut Ui,
         left_tree: &MergedTree,
         right_tree: &MergedTree,
         matcher: &dyn Matcher,
@@ -1823,6 +2541,24 @@ impl WorkspaceCommandTransaction<'_> {
         }
     }
 
+    /// Selects the changes listed in a `--hunks-from` file.
+    pub fn select_hunks_from_file(
+        &self,
+        left_tree: &MergedTree,
+        right_tree: &MergedTree,
+        matcher: &dyn Matcher,
+        selection_path: &Path,
+    ) -> Result<MergedTreeId, CommandError> {
+        let text = fs::read_to_string(selection_path).map_err(|err| {
+            user_error_with_message(format!("Failed to read {}", selection_path.display()), err)
+        })?;
+        let select = || {
+            let selection = crate::merge_tools::HunkSelection::parse(&text)?;
+            crate::merge_tools::apply_hunk_selection(left_tree, right_tree, matcher, &selection)
+        };
+        select().map_err(|err| user_error_with_message("Failed to select changes", err))
+    }
+
     pub fn format_commit_summary(&self, commit: &Commit) -> String {
         let mut output = Vec::new();
         self.write_commit_summary(&mut PlainTextFormatter::new(&mut output), commit)
@@ -1841,6 +2577,7 @@ impl WorkspaceCommandTransaction<'_> {
             self.tx.repo(),
             self.helper.workspace_id(),
             &id_prefix_context,
+            Some(&self.helper),
             &self.helper.template_aliases_map,
             &self.helper.settings,
         )
@@ -1852,12 +2589,91 @@ impl WorkspaceCommandTransaction<'_> {
         self.helper.finish_transaction(ui, self.tx, description)
     }
 
+    /// Prints the changes the transaction would make, then discards it. The
+    /// operation log and the working copy are left alone.
+    pub fn finish_dry_run(mut self, ui: &mut Ui) -> Result<(), CommandError> {
+        if !self.tx.mut_repo().has_changes() {
+            writeln!(ui.stderr(), "Nothing changed.")?;
+            return Ok(());
+        }
+        self.tx
+            .mut_repo()
+            .rebase_descendants(&self.helper.settings)?;
+        let pending_changes = self.tx.pending_changes();
+        let changes = pending_changes.commit_changes()?;
+        writeln!(ui.stderr(), "Changes that would be made:")?;
+        for commit in &changes.abandoned {
+            write!(ui.stderr(), "  Abandon commit ")?;
+            self.helper
+                .write_commit_summary(ui.stderr_formatter().as_mut(), commit)?;
+            writeln!(ui.stderr())?;
+        }
+        for (old_commit, new_commit) in &changes.rewritten {
+            write!(
+                ui.stderr(),
+                "  Rewrite commit {} as ",
+                short_commit_hash(old_commit.id())
+            )?;
+            self.write_commit_summary(ui.stderr_formatter().as_mut(), new_commit)?;
+            writeln!(ui.stderr())?;
+        }
+        for commit in &changes.created {
+            write!(ui.stderr(), "  Create commit ")?;
+            self.write_commit_summary(ui.stderr_formatter().as_mut(), commit)?;
+            writeln!(ui.stderr())?;
+        }
+        let format_target =
+            |target: &RefTarget| target.added_ids().map(short_commit_hash).join(", ");
+        for (name, old_target, new_target) in pending_changes.changed_local_branches() {
+            match (old_target.is_present(), new_target.is_present()) {
+                (true, true) => writeln!(
+                    ui.stderr(),
+                    "  Move branch {name} from {} to {}",
+                    format_target(old_target),
+                    format_target(new_target)
+                )?,
+                (true, false) => writeln!(
+                    ui.stderr(),
+                    "  Delete branch {name} from {}",
+                    format_target(old_target)
+                )?,
+                (false, true) => writeln!(
+                    ui.stderr(),
+                    "  Add branch {name} to {}",
+                    format_target(new_target)
+                )?,
+                (false, false) => {}
+            }
+        }
+        let new_commits = changes.created.iter().map(|commit| (None, commit)).chain(
+            changes
+                .rewritten
+                .iter()
+                .map(|(old_commit, new_commit)| (Some(old_commit), new_commit)),
+        );
+        for (old_commit, new_commit) in new_commits {
+            let had_conflict = match old_commit {
+                Some(commit) => commit.has_conflict()?,
+                None => false,
+            };
+            if !had_conflict && new_commit.has_conflict()? {
+                writeln!(
+                    ui.stderr(),
+                    "  Introduce conflicts in commit {}",
+                    short_commit_hash(new_commit.id())
+                )?;
+            }
+        }
+        writeln!(ui.stderr(), "Dry-run requested, no changes were made.")?;
+        Ok(())
+    }
+
     pub fn into_inner(self) -> Transaction {
         self.tx
     }
 }
 
-fn find_workspace_dir(cwd: &Path) -> &Path {
+pub fn find_workspace_dir(cwd: &Path) -> &Path {
     cwd.ancestors()
         .find(|path| path.join(".jj").is_dir())
         .unwrap_or(cwd)
@@ -1881,10 +2697,14 @@ jj git init --git-repo=.",
                 user_error(message)
             }
         }
-        WorkspaceLoadError::RepoDoesNotExist(repo_dir) => user_error(format!(
-            "The repository directory at {} is missing. Was it moved?",
-            repo_dir.display(),
-        )),
+        err @ WorkspaceLoadError::RepoDoesNotExist { .. } => user_error_with_hint(
+            err,
+            "If the repo was moved, run `jj debug relink --repo PATH` with its new path.",
+        ),
+        err @ WorkspaceLoadError::GitRepoDoesNotExist { .. } => user_error_with_hint(
+            err,
+            "If the Git repo was moved, run `jj debug relink --git-repo PATH` with its new path.",
+        ),
         WorkspaceLoadError::StoreLoadError(err @ StoreLoadError::UnsupportedType { .. }) => {
             internal_error_with_message(
                 "This version of the jj binary doesn't support this type of repo",
@@ -1897,15 +2717,113 @@ jj git init --git-repo=.",
         WorkspaceLoadError::StoreLoadError(StoreLoadError::Signing(
             err @ SignInitError::UnknownBackend(_),
         )) => user_error(err),
+        WorkspaceLoadError::StoreLoadError(StoreLoadError::MergeDrivers(err)) => {
+            user_error_with_message("Invalid merge driver config", err)
+        }
+        WorkspaceLoadError::StoreLoadError(StoreLoadError::FormatVersion(
+            err @ FormatVersionError::TooOld { .. },
+        )) => user_error_with_hint(err, "Run `jj debug upgrade-repo` to upgrade it."),
+        WorkspaceLoadError::StoreLoadError(StoreLoadError::FormatVersion(
+            err @ FormatVersionError::TooNew { .. },
+        )) => user_error_with_hint(
+            err,
+            "The repo was written by a newer version of jj. Use that version instead.",
+        ),
         WorkspaceLoadError::StoreLoadError(err) => internal_error(err),
         WorkspaceLoadError::NonUnicodePath | WorkspaceLoadError::Path(_) => user_error(err),
     }
 }
 
+/// Reports the files which were updated because the repo was moved.
+fn print_repaired_paths(ui: &Ui, loader: &WorkspaceLoader) -> io::Result<()> {
+    for repaired in loader.repaired_paths() {
+        ui.write_warning(&UiWarning::new(
+            WarningCode::RepairedPath,
+            format!(
+                "Updated {} to point to {} instead of {}, which no longer exists",
+                repaired.file.display(),
+                repaired.new_target.display(),
+                repaired.old_target.display(),
+            ),
+        ))?;
+    }
+    Ok(())
+}
+
+const SNAPSHOT_DESCRIPTION: &str = "snapshot working copy";
+
+/// Returns true if `op` snapshotted the working copy of `workspace_id` and was
+/// recorded by the same user and host recently enough that a new snapshot can
+/// replace it instead of being recorded on top of it.
+fn is_coalescable_snapshot(
+    op: &Operation,
+    workspace_id: &WorkspaceId,
+    settings: &UserSettings,
+) -> Result<bool, CommandError> {
+    let window = settings.snapshot_coalesce_window()?;
+    let metadata = &op.store_operation().metadata;
+    if window.is_zero()
+        || metadata.description != SNAPSHOT_DESCRIPTION
+        || metadata.hostname != settings.operation_hostname()
+        || metadata.username != settings.operation_username()
+        || op.parent_ids().len() != 1
+    {
+        return Ok(false);
+    }
+    let now = settings
+        .operation_timestamp()
+        .unwrap_or_else(Timestamp::now);
+    let elapsed_millis = now.timestamp.0 - metadata.end_time.timestamp.0;
+    if !(0..=i64::try_from(window.as_millis()).unwrap_or(i64::MAX)).contains(&elapsed_millis) {
+        return Ok(false);
+    }
+    let parent_op = op.parents().next().unwrap()?;
+    let old_wc_commit_id = parent_op.view()?.get_wc_commit_id(workspace_id).cloned();
+    let new_wc_commit_id = op.view()?.get_wc_commit_id(workspace_id).cloned();
+    Ok(old_wc_commit_id != new_wc_commit_id)
+}
+
+/// Warns if the end time of `op` had to be moved forward to the end time of its
+/// parent by more than `timestamps.clock-skew-warning-secs`, which probably
+/// means that the clock is wrong.
+fn warn_about_clock_skew(
+    ui: &Ui,
+    op: &Operation,
+    settings: &UserSettings,
+) -> Result<(), CommandError> {
+    let metadata = &op.store_operation().metadata;
+    let Some(wall_clock_time) = transaction::wall_clock_end_time(metadata) else {
+        return Ok(());
+    };
+    let threshold = settings.clock_skew_warning_threshold()?;
+    let skew_millis = metadata.end_time.timestamp.0 - wall_clock_time.timestamp.0;
+    if skew_millis <= i64::try_from(threshold.as_millis()).unwrap_or(i64::MAX) {
+        return Ok(());
+    }
+    ui.write_warning(
+        &UiWarning::new(
+            WarningCode::ClockSkew,
+            format!(
+                "The clock is behind the previous operation. The time of this operation was \
+                 moved forward from {} to {}.",
+                time_util::format_absolute_timestamp(&wall_clock_time),
+                time_util::format_absolute_timestamp(&metadata.end_time),
+            ),
+        )
+        .with_hint("Check that the system clock is set correctly."),
+    )?;
+    Ok(())
+}
+
+/// Starts a transaction tagged with the command line that started it. Unless
+/// `operation.command-tags` is disabled, the operation is also tagged with the
+/// command name and a hash of its arguments, so that the operations of a given
+/// command or invocation can be found with `jj op log --tag`.
 pub fn start_repo_transaction(
     repo: &Arc<ReadonlyRepo>,
     settings: &UserSettings,
     string_args: &[String],
+    command_name: &str,
 ) -> Transaction {
     let mut tx = repo.start_transaction(settings);
     // TODO: Either do better shell-escaping here or store the values in some list
@@ -1933,9 +2851,78 @@ pub fn start_repo_transaction(
     let mut quoted_strings = vec!["jj".to_string()];
     quoted_strings.extend(string_args.iter().skip(1).map(shell_escape));
     tx.set_tag("args".to_string(), quoted_strings.join(" "));
+    if settings
+        .config()
+        .get_bool("operation.command-tags")
+        .unwrap_or(true)
+    {
+        tx.set_tag("command".to_string(), command_name.to_owned());
+        let args_hash = blake2b_hash(string_args.get(1..).unwrap_or_default());
+        tx.set_tag("args-hash".to_string(), hex::encode(&args_hash[..8]));
+    }
     tx
 }
 
+/// A working copy that wasn't updated when another operation changed the
+/// workspace's working-copy commit.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct StaleWorkingCopy {
+    /// The operation the working copy was last updated at.
+    pub operation_id: OperationId,
+    /// The working-copy commit as of that operation, if it could be read.
+    pub recorded: Option<CommitId>,
+    /// The workspace's working-copy commit as of the loaded operation.
+    pub current: CommitId,
+}
+
+impl StaleWorkingCopy {
+    fn new(
+        repo: &ReadonlyRepo,
+        workspace_id: &WorkspaceId,
+        operation_id: OperationId,
+        current_wc_commit: &Commit,
+    ) -> Self {
+        let recorded = repo
+            .op_store()
+            .read_operation(&operation_id)
+            .ok()
+            .and_then(|data| {
+                let op = Operation::new(repo.op_store().clone(), operation_id.clone(), data);
+                let view = op.view().ok()?;
+                view.get_wc_commit_id(workspace_id).cloned()
+            });
+        StaleWorkingCopy {
+            operation_id,
+            recorded,
+            current: current_wc_commit.id().clone(),
+        }
+    }
+
+    fn to_command_error(&self) -> CommandError {
+        let mut hint = String::new();
+        if let Some(recorded) = &self.recorded {
+            hint.push_str(&format!(
+                "The working copy is at commit {}, but the workspace's working-copy commit is \
+                 now {}.\n",
+                short_commit_hash(recorded),
+                short_commit_hash(&self.current)
+            ));
+        }
+        hint.push_str(
+            "Run `jj workspace update-stale` to update it.
+See https://github.com/martinvonz/jj/blob/main/docs/working-copy.md#stale-working-copy \
+             for more information.",
+        );
+        user_error_with_hint(
+            format!(
+                "The working copy is stale (not updated since operation {}).",
+                short_operation_hash(&self.operation_id)
+            ),
+            hint,
+        )
+    }
+}
+
 /// Whether the working copy is stale or not.
 #[derive(Clone, Debug, Eq, PartialEq)]
 pub enum WorkingCopyFreshness {
@@ -2007,19 +2994,21 @@ pub fn print_checkout_stats(
         )?;
     }
     if stats.skipped_files != 0 {
-        writeln!(
-            ui.warning(),
-            "{} of those updates were skipped because there were conflicting changes in the \
-             working copy.",
-            stats.skipped_files
-        )?;
-        writeln!(
-            ui.hint(),
-            "Hint: Inspect the changes compared to the intended target with `jj diff --from {}`.
+        let warning = UiWarning::new(
+            WarningCode::SkippedConflictingUpdates,
+            format!(
+                "{} of those updates were skipped because there were conflicting changes in \
+                 the working copy.",
+                stats.skipped_files
+            ),
+        )
+        .with_hint(format!(
+            "Inspect the changes compared to the intended target with `jj diff --from {}`.
 Discard the conflicting changes with `jj restore --from {}`.",
             short_commit_hash(new_commit.id()),
             short_commit_hash(new_commit.id())
-        )?;
+        ));
+        ui.write_warning(&warning)?;
     }
     Ok(())
 }
@@ -2103,7 +3092,10 @@ fn load_revset_aliases(
                 .map_err(|e| e.to_string())
                 .and_then(|v| aliases_map.insert(&decl, v).map_err(|e| e.to_string()));
             if let Err(s) = r {
-                writeln!(ui.warning(), r#"Failed to load "{TABLE_KEY}.{decl}": {s}"#)?;
+                ui.write_warning(&UiWarning::new(
+                    WarningCode::InvalidAlias,
+                    format!(r#"Failed to load "{TABLE_KEY}.{decl}": {s}"#),
+                ))?;
             }
         }
     }
@@ -2157,11 +3149,15 @@ pub fn update_working_copy(
         // warning for most commands (but be an error for the checkout command)
         let stats = workspace
             .check_out(repo.op_id().clone(), old_tree_id.as_ref(), new_commit)
-            .map_err(|err| {
-                internal_error_with_message(
+            .map_err(|err| match err {
+                CheckoutError::ReservedPath { .. } => user_error_with_hint(
+                    err,
+                    "Run `jj debug strip-reserved-paths` to remove such paths from the history",
+                ),
+                err => internal_error_with_message(
                     format!("Failed to check out commit {}", new_commit.id().hex()),
                     err,
-                )
+                ),
             })?;
         Some(stats)
     } else {
@@ -2193,7 +3189,10 @@ fn load_template_aliases(
                 .map_err(|e| e.to_string())
                 .and_then(|v| aliases_map.insert(&decl, v).map_err(|e| e.to_string()));
             if let Err(s) = r {
-                writeln!(ui.warning(), r#"Failed to load "{TABLE_KEY}.{decl}": {s}"#)?;
+                ui.write_warning(&UiWarning::new(
+                    WarningCode::InvalidAlias,
+                    format!(r#"Failed to load "{TABLE_KEY}.{decl}": {s}"#),
+                ))?;
             }
         }
     }
@@ -2205,6 +3204,7 @@ fn parse_commit_summary_template<'a>(
     repo: &'a dyn Repo,
     workspace_id: &WorkspaceId,
     id_prefix_context: &'a IdPrefixContext,
+    diff_renderer: Option<&'a WorkspaceCommandHelper>,
     aliases_map: &TemplateAliasesMap,
     settings: &UserSettings,
 ) -> Result<Box<dyn Template<Commit> + 'a>, CommandError> {
@@ -2213,6 +3213,8 @@ fn parse_commit_summary_template<'a>(
         repo,
         workspace_id,
         id_prefix_context,
+        Rc::default(),
+        diff_renderer,
         &template_text,
         aliases_map,
     )?)
@@ -2528,7 +3530,9 @@ pub struct GlobalArgs {
     /// Path to repository to operate on
     ///
     /// By default, Jujutsu searches for the closest .jj/ directory in an
-    /// ancestor of the current working directory.
+    /// ancestor of the current working directory. The path may point to the
+    /// workspace root or to its .jj/ directory. Paths given to other arguments
+    /// are still interpreted relative to the current working directory.
     #[arg(long, short = 'R', global = true, value_hint = clap::ValueHint::DirPath)]
     pub repository: Option<String>,
     /// Don't snapshot the working copy, and don't update it
@@ -2559,12 +3563,21 @@ pub struct GlobalArgs {
     /// When loading the repo at an earlier operation, the working copy will be
     /// ignored, as if `--ignore-working-copy` had been specified.
     ///
-    /// It is possible to run mutating commands when loading the repo at an
-    /// earlier operation. Doing that is equivalent to having run concurrent
-    /// commands starting at the earlier operation. There's rarely a reason to
-    /// do that, but it is possible.
+    /// Mutating commands (other than the `jj op` commands) refuse to run when
+    /// the repo is loaded at an earlier operation, since that is equivalent
+    /// to having run concurrent commands starting at the earlier operation.
+    /// There's rarely a reason to do that, but it is possible if
+    /// `operation.allow-at-op-mutations` is set to `true`.
     #[arg(long, visible_alias = "at-op", global = true, default_value = "@")]
     pub at_operation: String,
+    /// Show paths relative to the workspace root
+    ///
+    /// By default, paths are shown relative to the current directory, unless
+    /// `ui.relative-paths` is set to `false`. This flag is useful for scripts
+    /// that run in any directory of the workspace. Paths given as arguments are
+    /// still relative to the current directory.
+    #[arg(long, global = true)]
+    pub repo_paths: bool,
     /// Enable verbose logging
     #[arg(long, short = 'v', global = true)]
     pub verbose: bool,
@@ -2583,6 +3596,9 @@ pub struct EarlyArgs {
     // Parsing with ignore_errors will crash if this is bool, so use
     // Option<bool>.
     pub no_pager: Option<bool>,
+    /// How to print warnings to stderr (text, json)
+    #[arg(long, value_name = "FORMAT", global = true)]
+    pub warnings: Option<WarningsFormat>,
     /// Additional configuration options (can be repeated)
     //  TODO: Introduce a `--config` option with simpler syntax for simple
     //  cases, designed so that `--config ui.color=auto` works
@@ -2590,6 +3606,35 @@ pub struct EarlyArgs {
     pub config_toml: Vec<String>,
 }
 
+/// Writes a path formatted by `RepoPathUiConverter` with the directory part
+/// and the file name labeled separately.
+pub fn write_formatted_file_path(formatter: &mut dyn Formatter, path: &str) -> io::Result<()> {
+    let basename_start = path.rfind(std::path::is_separator).map_or(0, |pos| pos + 1);
+    let (dirname, basename) = path.split_at(basename_start);
+    if !dirname.is_empty() {
+        write!(formatter.labeled("dirname"), "{dirname}")?;
+    }
+    write!(formatter.labeled("basename"), "{basename}")
+}
+
+/// Writes a record of the output that `-z` selects for scripts: the fields,
+/// each followed by a tab, then the path and a NUL byte.
+///
+/// Nothing is quoted or escaped. The path is relative to the workspace root
+/// and separated by `/`, regardless of `ui.relative-paths` and the platform.
+/// Since it's the last field, it may contain tabs and newlines.
+pub fn write_nul_record(
+    formatter: &mut dyn Formatter,
+    fields: &[&str],
+    path: &RepoPath,
+) -> io::Result<()> {
+    for field in fields {
+        write!(formatter, "{field}\t")?;
+    }
+    formatter.write_all(path.as_internal_bytes())?;
+    formatter.write_all(b"\0")
+}
+
 /// Create a description from a list of paragraphs.
 ///
 /// Based on the Git CLI behavior. See `opt_parse_m()` and `cleanup_mode` in
@@ -2683,6 +3728,7 @@ fn resolve_default_command(
 }
 
 fn resolve_aliases(
+    ui: &Ui,
     config: &config::Config,
     app: &Command,
     mut string_args: Vec<String>,
@@ -2707,11 +3753,23 @@ fn resolve_aliases(
             real_commands.insert(alias.to_string());
         }
     }
+    // Expanded aliases and their definitions, in order of expansion
+    let mut expansions: Vec<(String, Vec<String>)> = vec![];
     loop {
         let app_clone = app.clone().allow_external_subcommands(true);
         let matches = app_clone.try_get_matches_from(&string_args).ok();
         if let Some((command_name, submatches)) = matches.as_ref().and_then(|m| m.subcommand()) {
-            if !real_commands.contains(command_name) {
+            if real_commands.contains(command_name) {
+                if aliases_map.contains_key(command_name) {
+                    ui.write_warning(&UiWarning::new(
+                        WarningCode::InvalidAlias,
+                        format!(
+                            "Cannot define an alias that overrides the built-in command \"{}\"",
+                            command_name
+                        ),
+                    ))?;
+                }
+            } else {
                 let alias_name = command_name.to_string();
                 let alias_args = submatches
                     .get_many::<OsString>("")
@@ -2727,9 +3785,10 @@ fn resolve_aliases(
                     if let Ok(alias_definition) = value.try_deserialize::<Vec<String>>() {
                         assert!(string_args.ends_with(&alias_args));
                         string_args.truncate(string_args.len() - 1 - alias_args.len());
-                        string_args.extend(alias_definition);
+                        string_args.extend_from_slice(&alias_definition);
                         string_args.extend_from_slice(&alias_args);
                         resolved_aliases.insert(alias_name.clone());
+                        expansions.push((alias_name, alias_definition));
                         continue;
                     } else {
                         return Err(user_error(format!(
@@ -2738,13 +3797,41 @@ fn resolve_aliases(
                     }
                 } else {
                     // Not a real command and not an alias, so return what we've resolved so far
-                    return Ok(string_args);
+                    break;
                 }
             }
         }
         // No more alias commands, or hit unknown option
-        return Ok(string_args);
+        break;
     }
+
+    if !expansions.is_empty() {
+        // Report errors in the expanded command line along with the aliases it
+        // came from, since the user didn't type most of it.
+        if let Err(err) = app
+            .clone()
+            .arg_required_else_help(true)
+            .subcommand_required(true)
+            .try_get_matches_from(&string_args)
+        {
+            if !matches!(
+                err.kind(),
+                clap::error::ErrorKind::DisplayHelp
+                    | clap::error::ErrorKind::DisplayHelpOnMissingArgumentOrSubcommand
+                    | clap::error::ErrorKind::DisplayVersion
+            ) {
+                let definitions = expansions
+                    .iter()
+                    .map(|(name, definition)| format!("{name} = {definition:?}"))
+                    .join(", ");
+                return Err(CommandError::ClapCliError {
+                    err: Arc::new(err),
+                    hint: Some(format!("The command was expanded from alias {definitions}")),
+                });
+            }
+        }
+    }
+    Ok(string_args)
 }
 
 /// Parse args that must be interpreted early, e.g. before printing help.
@@ -2770,6 +3857,9 @@ fn handle_early_args(
     if args.no_pager.unwrap_or_default() {
         args.config_toml.push(r#"ui.paginate="never""#.to_owned());
     }
+    if let Some(format) = args.warnings {
+        args.config_toml.push(format!(r#"ui.warnings="{format}""#));
+    }
     if !args.config_toml.is_empty() {
         layered_configs.parse_config_args(&args.config_toml)?;
         ui.reset(&layered_configs.merge())?;
@@ -2793,7 +3883,7 @@ pub fn expand_args(
     }
 
     let string_args = resolve_default_command(ui, config, app, string_args)?;
-    resolve_aliases(config, app, string_args)
+    resolve_aliases(ui, config, app, string_args)
 }
 
 pub fn parse_args(
@@ -2847,7 +3937,7 @@ pub fn handle_command_result(
             writeln!(ui.error(), "Error: {message}")?;
             Ok(ExitCode::from(2))
         }
-        Err(CommandError::ClapCliError(inner)) => {
+        Err(CommandError::ClapCliError { err: inner, hint }) => {
             let clap_str = if ui.color() {
                 inner.render().ansi().to_string()
             } else {
@@ -2870,6 +3960,9 @@ pub fn handle_command_result(
                 }
                 _ => {
                     write!(ui.stderr(), "{clap_str}")?;
+                    if let Some(hint) = hint {
+                        writeln!(ui.hint(), "Hint: {hint}")?;
+                    }
                     Ok(ExitCode::from(2))
                 }
             }
@@ -2883,6 +3976,7 @@ pub fn handle_command_result(
             print_error_sources(ui, err.source())?;
             Ok(ExitCode::from(255))
         }
+        Err(CommandError::ExitStatus(code)) => Ok(ExitCode::from(*code)),
     }
 }
 
@@ -2894,6 +3988,7 @@ pub struct CliRunner {
     extra_configs: Option<config::Config>,
     store_factories: Option<StoreFactories>,
     working_copy_factories: Option<HashMap<String, Box<dyn WorkingCopyFactory>>>,
+    transaction_validators: Vec<Arc<dyn TransactionValidator>>,
     dispatch_fn: CliDispatchFn,
     start_hook_fns: Vec<CliDispatchFn>,
     process_global_args_fns: Vec<ProcessGlobalArgsFn>,
@@ -2915,6 +4010,7 @@ impl CliRunner {
             extra_configs: None,
             store_factories: None,
             working_copy_factories: None,
+            transaction_validators: vec![],
             dispatch_fn: Box::new(crate::commands::run_command),
             start_hook_fns: vec![],
             process_global_args_fns: vec![],
@@ -2948,6 +4044,13 @@ impl CliRunner {
         self
     }
 
+    /// Registers a validator to run before committing transactions started
+    /// by commands.
+    pub fn add_transaction_validator(mut self, validator: Arc<dyn TransactionValidator>) -> Self {
+        self.transaction_validators.push(validator);
+        self
+    }
+
     pub fn add_start_hook(mut self, start_hook_fn: CliDispatchFn) -> Self {
         self.start_hook_fns.push(start_hook_fn);
         self
@@ -3005,7 +4108,11 @@ impl CliRunner {
         // than the path resolution.
         let maybe_cwd_workspace_loader = WorkspaceLoader::init(find_workspace_dir(&cwd))
             .map_err(|err| map_workspace_load_error(err, None));
+        if let Ok(loader) = &maybe_cwd_workspace_loader {
+            print_repaired_paths(ui, loader)?;
+        }
         layered_configs.read_user_config()?;
+        check_env_timestamps()?;
         if let Ok(loader) = &maybe_cwd_workspace_loader {
             layered_configs.read_repo_config(loader.repo_path())?;
         }
@@ -3028,6 +4135,7 @@ impl CliRunner {
             // Invalid -R path is an error. No need to proceed.
             let loader = WorkspaceLoader::init(&cwd.join(path))
                 .map_err(|err| map_workspace_load_error(err, Some(path)))?;
+            print_repaired_paths(ui, &loader)?;
             layered_configs.read_repo_config(loader.repo_path())?;
             Ok(loader)
         } else {
@@ -3043,10 +4151,10 @@ impl CliRunner {
         if args.global_args.repository.is_some() {
             let new_string_args = expand_args(ui, &self.app, env::args_os(), &config).ok();
             if new_string_args.as_ref() != Some(&string_args) {
-                writeln!(
-                    ui.warning(),
-                    "Command aliases cannot be loaded from -R/--repository path"
-                )?;
+                ui.write_warning(&UiWarning::new(
+                    WarningCode::InvalidAlias,
+                    "Command aliases cannot be loaded from -R/--repository path",
+                ))?;
             }
         }
 
@@ -3065,6 +4173,7 @@ impl CliRunner {
             maybe_workspace_loader,
             self.store_factories.unwrap_or_default(),
             working_copy_factories,
+            self.transaction_validators,
         );
         for start_hook_fn in self.start_hook_fns {
             start_hook_fn(ui, &command_helper)?;
@@ -35,6 +35,9 @@ pub(crate) struct AbandonArgs {
     /// Do not print every abandoned commit on a separate line
     #[arg(long, short)]
     summary: bool,
+    /// Only print the changes that would be made, without making them
+    #[arg(long)]
+    dry_run: bool,
     /// Ignored (but lets you pass `-r` for consistency with other commands)
     #[arg(short = 'r', hide = true)]
     unused_revision: bool,
@@ -54,6 +57,9 @@ pub(crate) fn cmd_abandon(
         tx.mut_repo().record_abandoned_commit(commit.id().clone());
     }
     let num_rebased = tx.mut_repo().rebase_descendants(command.settings())?;
+    if args.dry_run {
+        return tx.finish_dry_run(ui);
+    }
 
     if to_abandon.len() == 1 {
         write!(ui.stderr(), "Abandoned commit ")?;
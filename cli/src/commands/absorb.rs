@@ -0,0 +1,166 @@
+// Copyright 2024 The Jujutsu Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::io::Write;
+use std::ops::Range;
+
+use itertools::Itertools;
+use jj_lib::absorb::{
+    apply_absorb, drop_conflicting_hunks, plan_absorb, AbsorbPlan, AbsorbSkipReason,
+};
+use jj_lib::backend::CommitId;
+use jj_lib::commit::Commit;
+use jj_lib::object_id::ObjectId;
+use jj_lib::repo::Repo;
+use jj_lib::revset::RevsetExpression;
+use tracing::instrument;
+
+use crate::cli_util::{CommandError, CommandHelper, RevisionArg, WorkspaceCommandHelper};
+use crate::ui::Ui;
+
+/// Move changes from a revision into the ancestors that last changed the same
+/// lines
+///
+/// Each hunk of the changes in the source revision is moved into the closest
+/// mutable ancestor that last changed the lines the hunk replaces. Lines added
+/// between two lines go into the commit that last changed both of them.
+///
+/// Hunks whose lines were last changed in more than one commit or in an
+/// immutable commit are left in the source revision, as are changes to added,
+/// deleted, or conflicted files, and hunks that would create a conflict when
+/// moved. The changes in a merge commit are relative to the merge of its
+/// parents.
+#[derive(clap::Args, Clone, Debug)]
+pub(crate) struct AbsorbArgs {
+    /// Source revision to absorb changes from
+    #[arg(long, short, default_value = "@")]
+    from: RevisionArg,
+    /// Only print the changes that would be made, without making them
+    #[arg(long)]
+    dry_run: bool,
+    /// Move only changes to these paths (instead of all paths)
+    #[arg(value_hint = clap::ValueHint::AnyPath)]
+    paths: Vec<String>,
+}
+
+#[instrument(skip_all)]
+pub(crate) fn cmd_absorb(
+    ui: &mut Ui,
+    command: &CommandHelper,
+    args: &AbsorbArgs,
+) -> Result<(), CommandError> {
+    let mut workspace_command = command.workspace_helper(ui)?;
+    let source = workspace_command.resolve_single_rev(&args.from, ui)?;
+    workspace_command.check_rewritable([&source])?;
+    let matcher = workspace_command.matcher_from_values(&args.paths)?;
+    let destinations_expression = RevsetExpression::commits(source.parent_ids().to_vec())
+        .ancestors()
+        .minus(&workspace_command.immutable_expression()?);
+    let destinations: Vec<CommitId> = workspace_command
+        .evaluate_revset(destinations_expression)?
+        .iter()
+        .collect();
+    let repo = workspace_command.repo().clone();
+    let mut plan = plan_absorb(repo.as_ref(), &source, &destinations, matcher.as_ref())?;
+    drop_conflicting_hunks(repo.as_ref(), &source, &mut plan)?;
+    print_absorb_plan(ui, &workspace_command, &source, &plan, args.dry_run)?;
+    if plan.hunks.is_empty() {
+        writeln!(ui.stderr(), "Nothing changed.")?;
+        return Ok(());
+    }
+
+    let mut tx = workspace_command.start_transaction();
+    apply_absorb(tx.mut_repo(), command.settings(), &source, &plan.hunks)?;
+    if args.dry_run {
+        return tx.finish_dry_run(ui);
+    }
+    let num_destinations = plan.hunks.iter().map(|(id, _)| id).unique().count();
+    tx.finish(
+        ui,
+        format!(
+            "absorb changes from commit {} into {num_destinations} commits",
+            source.id().hex()
+        ),
+    )?;
+    Ok(())
+}
+
+fn print_absorb_plan(
+    ui: &Ui,
+    workspace_command: &WorkspaceCommandHelper,
+    source: &Commit,
+    plan: &AbsorbPlan,
+    dry_run: bool,
+) -> Result<(), CommandError> {
+    let store = workspace_command.repo().store();
+    let mut formatter = ui.stderr_formatter();
+    let hunks_by_destination = plan.hunks.iter().into_group_map_by(|(id, _)| id);
+    // Print the destinations in the order the hunks were found
+    for destination_id in plan.hunks.iter().map(|(id, _)| id).unique() {
+        let destination = store.get_commit(destination_id)?;
+        write!(
+            formatter,
+            "{} into ",
+            if dry_run { "Would absorb" } else { "Absorbed" }
+        )?;
+        workspace_command.write_commit_summary(formatter.as_mut(), &destination)?;
+        writeln!(formatter, ":")?;
+        for (_, hunk) in &hunks_by_destination[destination_id] {
+            writeln!(
+                formatter,
+                "  {}: {}",
+                workspace_command.format_file_path(&hunk.path),
+                format_lines(&hunk.parent_lines)
+            )?;
+        }
+    }
+    if !plan.skipped.is_empty() {
+        write!(
+            formatter,
+            "{} in ",
+            if dry_run { "Would leave" } else { "Left" }
+        )?;
+        workspace_command.write_commit_summary(formatter.as_mut(), source)?;
+        writeln!(formatter, ":")?;
+        for skipped in &plan.skipped {
+            let reason = match skipped.reason {
+                AbsorbSkipReason::Ambiguous => "the lines were last changed in different commits",
+                AbsorbSkipReason::Immutable => "the lines were last changed in an immutable commit",
+                AbsorbSkipReason::UnsupportedPath => "not a modified regular file",
+                AbsorbSkipReason::Conflict => "moving it would create a conflict",
+            };
+            let path = workspace_command.format_file_path(&skipped.path);
+            match &skipped.parent_lines {
+                Some(lines) => {
+                    writeln!(formatter, "  {path}: {} ({reason})", format_lines(lines))?;
+                }
+                None => {
+                    writeln!(formatter, "  {path} ({reason})")?;
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Formats a 0-based range of lines for humans. An empty range is an
+/// insertion point.
+fn format_lines(lines: &Range<usize>) -> String {
+    match lines.len() {
+        0 if lines.start == 0 => "before line 1".to_owned(),
+        0 => format!("after line {}", lines.start),
+        1 => format!("line {}", lines.start + 1),
+        _ => format!("lines {}-{}", lines.start + 1, lines.end),
+    }
+}
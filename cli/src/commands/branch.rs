@@ -21,18 +21,19 @@ use itertools::Itertools;
 use jj_lib::backend::CommitId;
 use jj_lib::git;
 use jj_lib::object_id::ObjectId;
-use jj_lib::op_store::{RefTarget, RemoteRef};
+use jj_lib::op_store::{BranchTarget, RefTarget, RemoteRef};
+use jj_lib::refs::{self, CommitCount};
 use jj_lib::repo::Repo;
 use jj_lib::revset::{self, RevsetExpression};
 use jj_lib::str_util::StringPattern;
 use jj_lib::view::View;
 
 use crate::cli_util::{
-    parse_string_pattern, user_error, user_error_with_hint, CommandError, CommandHelper,
-    RemoteBranchName, RemoteBranchNamePattern, RevisionArg,
+    parse_string_pattern, user_error, user_error_with_hint, user_error_with_message, CommandError,
+    CommandHelper, RemoteBranchName, RemoteBranchNamePattern, RevisionArg,
 };
 use crate::formatter::Formatter;
-use crate::ui::Ui;
+use crate::ui::{Ui, UiWarning, WarningCode};
 
 /// Manage branches.
 ///
@@ -50,6 +51,7 @@ pub enum BranchCommand {
     List(BranchListArgs),
     #[command(visible_alias("r"))]
     Rename(BranchRenameArgs),
+    Resolve(BranchResolveArgs),
     #[command(visible_alias("s"))]
     Set(BranchSetArgs),
     #[command(visible_alias("t"))]
@@ -117,6 +119,20 @@ pub struct BranchListArgs {
     /// wouldn't have a local target.
     #[arg(long, short)]
     revisions: Vec<RevisionArg>,
+
+    /// Print each branch as a JSON object on its own line
+    ///
+    /// The objects include how far the local branch is from its tracking
+    /// remote branches.
+    #[arg(long)]
+    json: bool,
+
+    /// Show only local branches whose targets are conflicted
+    ///
+    /// The candidate targets of each branch are listed in a stable order. Use
+    /// `jj branch resolve` to point the branch to one of them.
+    #[arg(long, conflicts_with_all = ["all", "json"])]
+    conflicted: bool,
 }
 
 /// Forget everything about a branch, including its local and remote
@@ -152,6 +168,24 @@ pub struct BranchRenameArgs {
     pub new: String,
 }
 
+/// Resolve a conflicted branch by pointing it to a certain commit
+///
+/// The revision must be one of the branch's candidate targets, as listed by
+/// `jj branch list --conflicted`, or a descendant of one of them.
+#[derive(clap::Args, Clone, Debug)]
+pub struct BranchResolveArgs {
+    /// The conflicted branch to resolve
+    pub name: String,
+
+    /// The branch's new target revision
+    #[arg(long, short, required = true)]
+    pub revision: RevisionArg,
+
+    /// Allow resolving to a revision unrelated to the candidate targets
+    #[arg(long)]
+    pub force: bool,
+}
+
 /// Update an existing branch to point to a certain commit.
 #[derive(clap::Args, Clone, Debug)]
 pub struct BranchSetArgs {
@@ -219,6 +253,7 @@ pub fn cmd_branch(
         BranchCommand::Create(sub_args) => cmd_branch_create(ui, command, sub_args),
         BranchCommand::Rename(sub_args) => cmd_branch_rename(ui, command, sub_args),
         BranchCommand::Set(sub_args) => cmd_branch_set(ui, command, sub_args),
+        BranchCommand::Resolve(sub_args) => cmd_branch_resolve(ui, command, sub_args),
         BranchCommand::Delete(sub_args) => cmd_branch_delete(ui, command, sub_args),
         BranchCommand::Forget(sub_args) => cmd_branch_forget(ui, command, sub_args),
         BranchCommand::Track(sub_args) => cmd_branch_track(ui, command, sub_args),
@@ -227,6 +262,28 @@ pub fn cmd_branch(
     }
 }
 
+fn validate_branch_name(name: &str) -> Result<(), CommandError> {
+    refs::validate_branch_name(name)
+        .map_err(|err| user_error_with_message(format!("Invalid branch name: {name:?}"), err))
+}
+
+/// Checks that a new branch can be named `name`, and that it wouldn't collide
+/// with any of the `other_names` when exported to Git.
+fn validate_new_branch_name<'a>(
+    name: &str,
+    other_names: impl IntoIterator<Item = &'a str>,
+) -> Result<(), CommandError> {
+    validate_branch_name(name)?;
+    if let Some(other) = refs::find_colliding_branch_name(name, other_names) {
+        return Err(user_error_with_hint(
+            format!("Branch name {name} collides with branch {other}"),
+            "Branch names that differ only in letter case or Unicode normalization can't be told \
+             apart on some file systems.",
+        ));
+    }
+    Ok(())
+}
+
 fn cmd_branch_create(
     ui: &mut Ui,
     command: &CommandHelper,
@@ -246,13 +303,19 @@ fn cmd_branch_create(
             "Use `jj branch set` to update it.",
         ));
     }
+    for (i, name) in branch_names.iter().enumerate() {
+        let other_names = view.local_branches().map(|(name, _)| name);
+        validate_new_branch_name(
+            name,
+            other_names.chain(branch_names[..i].iter().map(String::as_str)),
+        )?;
+    }
 
     if branch_names.len() > 1 {
-        writeln!(
-            ui.warning(),
-            "warning: Creating multiple branches: {}",
-            branch_names.join(", "),
-        )?;
+        ui.write_warning(&UiWarning::new(
+            WarningCode::MultipleBranches,
+            format!("Creating multiple branches: {}", branch_names.join(", ")),
+        ))?;
     }
 
     let mut tx = workspace_command.start_transaction();
@@ -288,6 +351,11 @@ fn cmd_branch_rename(
     if view.get_local_branch(new_branch).is_present() {
         return Err(user_error(format!("Branch already exists: {new_branch}")));
     }
+    let other_names = view
+        .local_branches()
+        .map(|(name, _)| name)
+        .filter(|&name| name != old_branch);
+    validate_new_branch_name(new_branch, other_names)?;
 
     let mut tx = workspace_command.start_transaction();
     tx.mut_repo()
@@ -311,16 +379,16 @@ fn cmd_branch_rename(
         )
         .any(|(_, remote_ref)| remote_ref.is_tracking())
     {
-        writeln!(
-            ui.warning(),
-            "Warning: Branch {old_branch} has tracking remote branches which were not renamed."
-        )?;
-        writeln!(
-            ui.hint(),
-            "Hint: to rename the branch on the remote, you can `jj git push --branch \
-             {old_branch}` first (to delete it on the remote), and then `jj git push --branch \
-             {new_branch}`. `jj git push --all` would also be sufficient."
-        )?;
+        let warning = UiWarning::new(
+            WarningCode::RemoteBranchesNotRenamed,
+            format!("Branch {old_branch} has tracking remote branches which were not renamed."),
+        )
+        .with_hint(format!(
+            "to rename the branch on the remote, you can `jj git push --branch {old_branch}` \
+             first (to delete it on the remote), and then `jj git push --branch {new_branch}`. \
+             `jj git push --all` would also be sufficient."
+        ));
+        ui.write_warning(&warning)?;
     }
 
     Ok(())
@@ -344,6 +412,7 @@ fn cmd_branch_set(
     };
     let branch_names = &args.names;
     for name in branch_names {
+        validate_branch_name(name)?;
         let old_target = repo.view().get_local_branch(name);
         if old_target.is_absent() {
             return Err(user_error_with_hint(
@@ -360,11 +429,10 @@ fn cmd_branch_set(
     }
 
     if branch_names.len() > 1 {
-        writeln!(
-            ui.warning(),
-            "warning: Updating multiple branches: {}",
-            branch_names.join(", "),
-        )?;
+        ui.write_warning(&UiWarning::new(
+            WarningCode::MultipleBranches,
+            format!("Updating multiple branches: {}", branch_names.join(", ")),
+        ))?;
     }
 
     let mut tx = workspace_command.start_transaction();
@@ -383,6 +451,52 @@ fn cmd_branch_set(
     Ok(())
 }
 
+fn cmd_branch_resolve(
+    ui: &mut Ui,
+    command: &CommandHelper,
+    args: &BranchResolveArgs,
+) -> Result<(), CommandError> {
+    let mut workspace_command = command.workspace_helper(ui)?;
+    let target_commit = workspace_command.resolve_single_rev(&args.revision, ui)?;
+    let repo = workspace_command.repo().as_ref();
+    let name = &args.name;
+    let Some(candidates) = repo
+        .view()
+        .local_branch_conflict_candidates(name, repo.index())
+    else {
+        return Err(if repo.view().get_local_branch(name).is_absent() {
+            user_error(format!("No such branch: {name}"))
+        } else {
+            user_error_with_hint(
+                format!("Branch {name} is not conflicted"),
+                "Use `jj branch set` to move it.",
+            )
+        });
+    };
+    let is_candidate_or_descendant = candidates
+        .iter()
+        .any(|id| repo.index().is_ancestor(id, target_commit.id()));
+    if !args.force && !is_candidate_or_descendant {
+        return Err(user_error_with_hint(
+            format!("Refusing to resolve branch {name} to an unrelated commit"),
+            "The revision must be a candidate target or a descendant of one. Use --force to \
+             allow it.",
+        ));
+    }
+
+    let mut tx = workspace_command.start_transaction();
+    tx.mut_repo()
+        .resolve_branch_conflict(name, target_commit.id().clone());
+    tx.finish(
+        ui,
+        format!(
+            "resolve conflicted branch {name} to commit {}",
+            target_commit.id().hex()
+        ),
+    )?;
+    Ok(())
+}
+
 fn find_local_branches(
     view: &View,
     name_patterns: &[StringPattern],
@@ -477,10 +591,10 @@ fn cmd_branch_delete(
     let mut workspace_command = command.workspace_helper(ui)?;
     let view = workspace_command.repo().view();
     if !args.glob.is_empty() {
-        writeln!(
-            ui.warning(),
-            "--glob has been deprecated. Please prefix the pattern with `glob:` instead."
-        )?;
+        ui.write_warning(&UiWarning::new(
+            WarningCode::DeprecatedOption,
+            "--glob has been deprecated. Please prefix the pattern with `glob:` instead.",
+        ))?;
     }
     let name_patterns = [&args.names[..], &args.glob[..]].concat();
     let names = find_local_branches(view, &name_patterns)?;
@@ -504,10 +618,10 @@ fn cmd_branch_forget(
     let mut workspace_command = command.workspace_helper(ui)?;
     let view = workspace_command.repo().view();
     if !args.glob.is_empty() {
-        writeln!(
-            ui.warning(),
-            "--glob has been deprecated. Please prefix the pattern with `glob:` instead."
-        )?;
+        ui.write_warning(&UiWarning::new(
+            WarningCode::DeprecatedOption,
+            "--glob has been deprecated. Please prefix the pattern with `glob:` instead.",
+        ))?;
     }
     let name_patterns = [&args.names[..], &args.glob[..]].concat();
     let names = find_forgettable_branches(view, &name_patterns)?;
@@ -532,7 +646,10 @@ fn cmd_branch_track(
     let mut names = Vec::new();
     for (name, remote_ref) in find_remote_branches(view, &args.names)? {
         if remote_ref.is_tracking() {
-            writeln!(ui.warning(), "Remote branch already tracked: {name}")?;
+            ui.write_warning(&UiWarning::new(
+                WarningCode::BranchTrackingUnchanged,
+                format!("Remote branch already tracked: {name}"),
+            ))?;
         } else {
             names.push(name);
         }
@@ -564,12 +681,15 @@ fn cmd_branch_untrack(
     for (name, remote_ref) in find_remote_branches(view, &args.names)? {
         if name.remote == git::REMOTE_NAME_FOR_LOCAL_GIT_REPO {
             // This restriction can be lifted if we want to support untracked @git branches.
-            writeln!(
-                ui.warning(),
-                "Git-tracking branch cannot be untracked: {name}"
-            )?;
+            ui.write_warning(&UiWarning::new(
+                WarningCode::BranchTrackingUnchanged,
+                format!("Git-tracking branch cannot be untracked: {name}"),
+            ))?;
         } else if !remote_ref.is_tracking() {
-            writeln!(ui.warning(), "Remote branch not tracked yet: {name}")?;
+            ui.write_warning(&UiWarning::new(
+                WarningCode::BranchTrackingUnchanged,
+                format!("Remote branch not tracked yet: {name}"),
+            ))?;
         } else {
             names.push(name);
         }
@@ -590,6 +710,9 @@ fn cmd_branch_untrack(
     Ok(())
 }
 
+/// Commits counted at most when comparing a local branch with a remote branch
+const MAX_AHEAD_BEHIND_COUNT: usize = 1000;
+
 fn cmd_branch_list(
     ui: &mut Ui,
     command: &CommandHelper,
@@ -673,11 +796,48 @@ fn cmd_branch_list(
     let mut formatter = ui.stdout_formatter();
     let formatter = formatter.as_mut();
 
+    if args.conflicted {
+        let conflicted_names = view.conflicted_local_branches().filter(|&(name, _)| {
+            branch_names_to_list
+                .as_ref()
+                .map_or(true, |branch_names| branch_names.contains(name))
+        });
+        for (name, _) in conflicted_names {
+            let candidates = view
+                .local_branch_conflict_candidates(name, repo.index())
+                .unwrap();
+            write!(formatter.labeled("branch"), "{name}")?;
+            write!(formatter, " ")?;
+            write!(formatter.labeled("conflict"), "(conflicted)")?;
+            writeln!(formatter, ":")?;
+            for (prefix, ids) in [("-", &candidates.removed_ids), ("+", &candidates.added_ids)] {
+                for id in ids {
+                    let commit = repo.store().get_commit(id)?;
+                    write!(formatter, "  {prefix} ")?;
+                    no_branches_template.format(&commit, formatter)?;
+                    writeln!(formatter)?;
+                }
+            }
+        }
+        return Ok(());
+    }
+
     let branches_to_list = view.branches().filter(|&(name, _)| {
         branch_names_to_list
             .as_ref()
             .map_or(true, |branch_names| branch_names.contains(name))
     });
+    if args.json {
+        for (name, branch_target) in branches_to_list {
+            let branch = branch_to_json(repo.as_ref(), name, &branch_target, args.all)?;
+            // Like in the text output, untracked remote branches are only
+            // shown with --all.
+            if branch.target.is_some() || !branch.remotes.is_empty() {
+                writeln!(formatter, "{}", serde_json::to_string(&branch).unwrap())?;
+            }
+        }
+        return Ok(());
+    }
     for (name, branch_target) in branches_to_list {
         let (tracking_remote_refs, untracked_remote_refs) =
             branch_target
@@ -685,10 +845,17 @@ fn cmd_branch_list(
                 .into_iter()
                 .partition::<Vec<_>, _>(|&(_, remote_ref)| remote_ref.is_tracking());
 
+        // A deleted local branch which is still tracking remote branches is a
+        // tombstone. The deletion will be propagated on the next push/export.
+        let found_non_git_remote = tracking_remote_refs
+            .iter()
+            .any(|&(remote, _)| remote != git::REMOTE_NAME_FOR_LOCAL_GIT_REPO);
         if branch_target.local_target.is_present() || !tracking_remote_refs.is_empty() {
             write!(formatter.labeled("branch"), "{name}")?;
             if branch_target.local_target.is_present() {
                 print_branch_target(formatter, branch_target.local_target)?;
+            } else if found_non_git_remote {
+                writeln!(formatter, " (deleted, will be pushed)")?;
             } else {
                 writeln!(formatter, " (deleted)")?;
             }
@@ -703,45 +870,39 @@ fn cmd_branch_list(
             write!(formatter.labeled("branch"), "@{remote}")?;
             let local_target = branch_target.local_target;
             if local_target.is_present() && !synced {
-                let remote_added_ids = remote_ref.target.added_ids().cloned().collect_vec();
-                let local_added_ids = local_target.added_ids().cloned().collect_vec();
-                let (remote_ahead_lower, remote_ahead_upper) =
-                    revset::walk_revs(repo.as_ref(), &remote_added_ids, &local_added_ids)?
-                        .count_estimate();
-                let (local_ahead_lower, local_ahead_upper) =
-                    revset::walk_revs(repo.as_ref(), &local_added_ids, &remote_added_ids)?
-                        .count_estimate();
-                let remote_ahead_message = match remote_ahead_upper {
-                    Some(0) => None,
-                    Some(upper) if upper == remote_ahead_lower => {
-                        Some(format!("ahead by {remote_ahead_lower} commits"))
-                    }
-                    _ => Some(format!("ahead by at least {remote_ahead_lower} commits")),
-                };
-                let local_ahead_message = match local_ahead_upper {
-                    Some(0) => None,
-                    Some(upper) if upper == local_ahead_lower => {
-                        Some(format!("behind by {local_ahead_lower} commits"))
-                    }
-                    _ => Some(format!("behind by at least {local_ahead_lower} commits")),
-                };
-                match (remote_ahead_message, local_ahead_message) {
-                    (Some(rm), Some(lm)) => {
-                        write!(formatter, " ({rm}, {lm})")?;
-                    }
-                    (Some(m), None) | (None, Some(m)) => {
-                        write!(formatter, " ({m})")?;
+                let counts = refs::count_ahead_behind(
+                    repo.as_ref(),
+                    local_target,
+                    &remote_ref.target,
+                    MAX_AHEAD_BEHIND_COUNT,
+                )?;
+                // The counts are shown from the remote branch's point of view.
+                let messages = [
+                    ("ahead", "ahead by", counts.behind),
+                    ("behind", "behind by", counts.ahead),
+                ]
+                .into_iter()
+                .filter(|(_, _, count)| !count.is_zero())
+                .collect_vec();
+                if !messages.is_empty() {
+                    write!(formatter, " (")?;
+                    for (i, (label, message, count)) in messages.into_iter().enumerate() {
+                        if i > 0 {
+                            write!(formatter, ", ")?;
+                        }
+                        write!(
+                            formatter.labeled(label),
+                            "{message} {}",
+                            format_commit_count(count)
+                        )?;
                     }
-                    (None, None) => { /* do nothing */ }
+                    write!(formatter, ")")?;
                 }
             }
             print_branch_target(formatter, &remote_ref.target)?;
         }
 
         if branch_target.local_target.is_absent() && !tracking_remote_refs.is_empty() {
-            let found_non_git_remote = tracking_remote_refs
-                .iter()
-                .any(|&(remote, _)| remote != git::REMOTE_NAME_FOR_LOCAL_GIT_REPO);
             if found_non_git_remote {
                 writeln!(
                     formatter.labeled("hint"),
@@ -767,3 +928,110 @@ fn cmd_branch_list(
 
     Ok(())
 }
+
+fn format_commit_count(count: CommitCount) -> String {
+    match count {
+        CommitCount::Exact(n) => format!("{n} commits"),
+        CommitCount::AtLeast(n) => format!("{n}+ commits"),
+    }
+}
+
+#[derive(serde::Serialize)]
+struct BranchJson<'a> {
+    name: &'a str,
+    /// `None` if the local branch is deleted.
+    target: Option<RefTargetJson>,
+    remotes: Vec<RemoteBranchJson<'a>>,
+}
+
+#[derive(serde::Serialize)]
+struct RemoteBranchJson<'a> {
+    remote: &'a str,
+    tracking: bool,
+    target: Option<RefTargetJson>,
+    /// Commits in the remote branch which aren't in the local branch.
+    ahead_by: Option<CommitCountJson>,
+    /// Commits in the local branch which aren't in the remote branch.
+    behind_by: Option<CommitCountJson>,
+}
+
+#[derive(serde::Serialize)]
+struct RefTargetJson {
+    conflict: bool,
+    added_ids: Vec<String>,
+    removed_ids: Vec<String>,
+}
+
+#[derive(serde::Serialize)]
+struct CommitCountJson {
+    count: usize,
+    /// Whether there are more commits than `count`.
+    capped: bool,
+}
+
+impl RefTargetJson {
+    fn new(target: &RefTarget) -> Option<Self> {
+        target.is_present().then(|| RefTargetJson {
+            conflict: target.has_conflict(),
+            added_ids: target.added_ids().map(|id| id.hex()).collect(),
+            removed_ids: target.removed_ids().map(|id| id.hex()).collect(),
+        })
+    }
+}
+
+impl From<CommitCount> for CommitCountJson {
+    fn from(count: CommitCount) -> Self {
+        match count {
+            CommitCount::Exact(count) => CommitCountJson {
+                count,
+                capped: false,
+            },
+            CommitCount::AtLeast(count) => CommitCountJson {
+                count,
+                capped: true,
+            },
+        }
+    }
+}
+
+/// Describes the branch like `jj branch list` would print it. The ahead/behind
+/// counts are only computed for the remote branches which are included.
+fn branch_to_json<'a>(
+    repo: &dyn Repo,
+    name: &'a str,
+    branch_target: &BranchTarget<'a>,
+    all: bool,
+) -> Result<BranchJson<'a>, CommandError> {
+    let local_target = branch_target.local_target;
+    let mut remotes = vec![];
+    for &(remote, remote_ref) in &branch_target.remote_refs {
+        let tracking = remote_ref.is_tracking();
+        let synced = remote_ref.target == *local_target;
+        if !all && (!tracking || synced) {
+            continue;
+        }
+        let (ahead_by, behind_by) = if tracking && local_target.is_present() && !synced {
+            let counts = refs::count_ahead_behind(
+                repo,
+                local_target,
+                &remote_ref.target,
+                MAX_AHEAD_BEHIND_COUNT,
+            )?;
+            (Some(counts.behind.into()), Some(counts.ahead.into()))
+        } else {
+            (None, None)
+        };
+        remotes.push(RemoteBranchJson {
+            remote,
+            tracking,
+            target: RefTargetJson::new(&remote_ref.target),
+            ahead_by,
+            behind_by,
+        });
+    }
+    Ok(BranchJson {
+        name,
+        target: RefTargetJson::new(local_target),
+        remotes,
+    })
+}
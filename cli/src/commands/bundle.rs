@@ -0,0 +1,141 @@
+// Copyright 2024 The Jujutsu Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Write};
+use std::path::PathBuf;
+
+use clap::Subcommand;
+use itertools::Itertools;
+use jj_lib::bundle;
+use jj_lib::repo::Repo;
+use tracing::instrument;
+
+use crate::cli_util::{
+    resolve_multiple_nonempty_revsets, user_error_with_message, CommandError, CommandHelper,
+    RevisionArg,
+};
+use crate::ui::Ui;
+
+/// Move commits between repos with a file
+///
+/// A bundle holds a set of commits and everything needed to recreate them in
+/// another repo, which doesn't need to share a remote with this one. Parents
+/// of the bundled commits must already exist in the repo the bundle is applied
+/// to, unless they're bundled too.
+#[derive(Subcommand, Clone, Debug)]
+pub(crate) enum BundleCommand {
+    Create(BundleCreateArgs),
+    Apply(BundleApplyArgs),
+}
+
+/// Write commits to a bundle file
+#[derive(clap::Args, Clone, Debug)]
+pub(crate) struct BundleCreateArgs {
+    /// The commits to include
+    #[arg(long, short, required = true)]
+    revisions: Vec<RevisionArg>,
+    /// The file to write the bundle to
+    #[arg(long, short, value_hint = clap::ValueHint::FilePath)]
+    output: PathBuf,
+}
+
+/// Import the commits from a bundle file
+///
+/// The head commits of the bundle become visible in this repo. Objects the
+/// repo already has are skipped.
+#[derive(clap::Args, Clone, Debug)]
+pub(crate) struct BundleApplyArgs {
+    /// The bundle file
+    #[arg(value_hint = clap::ValueHint::FilePath)]
+    file: PathBuf,
+}
+
+#[instrument(skip_all)]
+pub(crate) fn cmd_bundle(
+    ui: &mut Ui,
+    command: &CommandHelper,
+    subcommand: &BundleCommand,
+) -> Result<(), CommandError> {
+    match subcommand {
+        BundleCommand::Create(sub_args) => cmd_bundle_create(ui, command, sub_args),
+        BundleCommand::Apply(sub_args) => cmd_bundle_apply(ui, command, sub_args),
+    }
+}
+
+#[instrument(skip_all)]
+fn cmd_bundle_create(
+    ui: &mut Ui,
+    command: &CommandHelper,
+    args: &BundleCreateArgs,
+) -> Result<(), CommandError> {
+    let workspace_command = command.workspace_helper(ui)?;
+    let commits = resolve_multiple_nonempty_revsets(&args.revisions, &workspace_command, ui)?
+        .into_iter()
+        .collect_vec();
+    let write_error = |err: bundle::BundleError| {
+        user_error_with_message(
+            format!("Failed to write bundle to {}", args.output.display()),
+            err,
+        )
+    };
+    let file = File::create(&args.output).map_err(|err| write_error(err.into()))?;
+    let stats = bundle::write_bundle(
+        workspace_command.repo().store(),
+        &commits,
+        &mut BufWriter::new(file),
+    )
+    .map_err(write_error)?;
+    writeln!(
+        ui.stderr(),
+        "Wrote {} commits ({} objects) to {}",
+        stats.num_commits,
+        stats.num_objects,
+        args.output.display()
+    )?;
+    Ok(())
+}
+
+#[instrument(skip_all)]
+fn cmd_bundle_apply(
+    ui: &mut Ui,
+    command: &CommandHelper,
+    args: &BundleApplyArgs,
+) -> Result<(), CommandError> {
+    let mut workspace_command = command.workspace_helper(ui)?;
+    let apply_error = |err: bundle::BundleError| {
+        user_error_with_message(
+            format!("Failed to apply bundle {}", args.file.display()),
+            err,
+        )
+    };
+    let file = File::open(&args.file).map_err(|err| apply_error(err.into()))?;
+    let mut tx = workspace_command.start_transaction();
+    let applied =
+        bundle::apply_bundle(tx.mut_repo(), &mut BufReader::new(file)).map_err(apply_error)?;
+    writeln!(
+        ui.stderr(),
+        "Applied {} commits ({} objects, {} already present)",
+        applied.stats.num_commits,
+        applied.stats.num_objects,
+        applied.stats.num_existing_objects
+    )?;
+    for head in &applied.heads {
+        write!(ui.stderr(), "Added head ")?;
+        tx.write_commit_summary(ui.stderr_formatter().as_mut(), head)?;
+        writeln!(ui.stderr())?;
+    }
+    tx.finish(ui, format!("apply bundle {}", args.file.display()))?;
+    Ok(())
+}
@@ -14,7 +14,7 @@
 
 use std::io::Write;
 
-use jj_lib::conflicts::{materialize_tree_value, MaterializedTreeValue};
+use jj_lib::conflicts::{materialize_tree_value_with_labels, MaterializedTreeValue};
 use jj_lib::repo::Repo;
 use pollster::FutureExt;
 use tracing::instrument;
@@ -45,10 +45,16 @@ pub(crate) fn cmd_cat(
     let path = workspace_command.parse_file_path(&args.path)?;
     let repo = workspace_command.repo();
     let value = tree.path_value(&path);
-    let materialized = materialize_tree_value(repo.store(), &path, value).block_on()?;
+    let labels = commit.conflict_labels();
+    let materialized =
+        materialize_tree_value_with_labels(repo.store(), &path, value, labels.as_ref())
+            .block_on()?;
     match materialized {
         MaterializedTreeValue::Absent => {
-            return Err(user_error("No such path"));
+            return Err(user_error(format!(
+                "No such path: {}",
+                workspace_command.format_file_path(&path)
+            )));
         }
         MaterializedTreeValue::File { mut reader, .. } => {
             ui.request_pager();
@@ -16,7 +16,7 @@ use jj_lib::object_id::ObjectId;
 use tracing::instrument;
 
 use crate::cli_util::{join_message_paragraphs, CommandError, CommandHelper, RevisionArg};
-use crate::ui::Ui;
+use crate::ui::{Ui, UiWarning, WarningCode};
 
 /// Create a new, empty change and edit it in the working copy
 ///
@@ -41,14 +41,12 @@ pub(crate) fn cmd_checkout(
     command: &CommandHelper,
     args: &CheckoutArgs,
 ) -> Result<(), CommandError> {
-    writeln!(
-        ui.warning(),
-        "warning: `jj checkout` is deprecated; use `jj new` instead, which is equivalent"
-    )?;
-    writeln!(
-        ui.warning(),
-        "warning: `jj checkout` will be removed in a future version, and this will be a hard error"
-    )?;
+    let warning = UiWarning::new(
+        WarningCode::DeprecatedCommand,
+        "`jj checkout` is deprecated, and will be removed in a future version",
+    )
+    .with_hint("Use `jj new` instead, which is equivalent");
+    ui.write_warning(&warning)?;
     let mut workspace_command = command.workspace_helper(ui)?;
     let target = workspace_command.resolve_single_rev(&args.revision, ui)?;
     let mut tx = workspace_command.start_transaction();
@@ -48,9 +48,25 @@ pub(crate) fn cmd_commit(
         .get_wc_commit_id()
         .ok_or_else(|| user_error("This command requires a working copy"))?;
     let commit = workspace_command.repo().store().get_commit(commit_id)?;
+    workspace_command.check_working_copy_fresh()?;
     let matcher = workspace_command.matcher_from_values(&args.paths)?;
+    let advanceable_branches = match commit.parent_ids() {
+        [parent_id] => workspace_command.get_advanceable_branches(parent_id)?,
+        _ => vec![],
+    };
     let mut tx = workspace_command.start_transaction();
     let base_tree = merge_commit_trees(tx.repo(), &commit.parents())?;
+    if !args.paths.is_empty()
+        && base_tree
+            .diff(&commit.tree()?, matcher.as_ref())
+            .next()
+            .is_none()
+    {
+        return Err(user_error(format!(
+            "The given paths do not match any changed file: {}",
+            args.paths.join(" ")
+        )));
+    }
     let instructions = format!(
         "\
 You are splitting the working-copy commit: {}
@@ -70,13 +86,6 @@ new working-copy commit.
         args.interactive,
     )?;
     let middle_tree = tx.repo().store().get_root_tree(&tree_id)?;
-    if !args.paths.is_empty() && middle_tree.id() == base_tree.id() {
-        writeln!(
-            ui.warning(),
-            "The given paths do not match any file: {}",
-            args.paths.join(" ")
-        )?;
-    }
 
     let template = description_template_for_commit(
         ui,
@@ -100,6 +109,7 @@ new working-copy commit.
         .set_tree_id(tree_id)
         .set_description(description)
         .write()?;
+    tx.advance_branches(ui, advanceable_branches, new_commit.id())?;
     let workspace_ids = tx
         .mut_repo()
         .view()
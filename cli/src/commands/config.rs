@@ -23,7 +23,7 @@ use crate::cli_util::{
     write_config_value_to_file, CommandError, CommandHelper,
 };
 use crate::config::{AnnotatedValue, ConfigSource};
-use crate::ui::Ui;
+use crate::ui::{Ui, UiWarning, WarningCode};
 
 #[derive(clap::Args, Clone, Debug)]
 #[command(group = clap::ArgGroup::new("config_level").multiple(false).required(true))]
@@ -216,9 +216,15 @@ pub(crate) fn cmd_config_list(
     if !wrote_values {
         // Note to stderr explaining why output is empty.
         if let Some(name) = &args.name {
-            writeln!(ui.warning(), "No matching config key for {name}")?;
+            ui.write_warning(&UiWarning::new(
+                WarningCode::NoMatchingConfig,
+                format!("No matching config key for {name}"),
+            ))?;
         } else {
-            writeln!(ui.warning(), "No config to list")?;
+            ui.write_warning(&UiWarning::new(
+                WarningCode::NoMatchingConfig,
+                "No config to list",
+            ))?;
         }
     }
     Ok(())
@@ -0,0 +1,147 @@
+// Copyright 2024 The Jujutsu Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::fs;
+use std::io::Write;
+
+use jj_lib::backend::TreeValue;
+use jj_lib::merge::Merge;
+use jj_lib::merged_tree::MergedTreeBuilder;
+use jj_lib::repo::Repo;
+use jj_lib::working_copy::SnapshotOptions;
+use tracing::instrument;
+
+use crate::cli_util::{user_error, CommandError, CommandHelper};
+use crate::ui::Ui;
+
+/// Copy a file in the working copy and record where it was copied from
+///
+/// Diffs of the working-copy commit then show the new file as a copy of the
+/// source instead of as an added file. The recorded source is kept when the
+/// commit is rewritten, as long as the copy still exists.
+#[derive(clap::Args, Clone, Debug)]
+pub(crate) struct CpArgs {
+    /// The file to copy
+    #[arg(value_hint = clap::ValueHint::FilePath)]
+    source: String,
+    /// The path of the copy, which must not exist yet
+    #[arg(value_hint = clap::ValueHint::FilePath)]
+    destination: String,
+}
+
+#[instrument(skip_all)]
+pub(crate) fn cmd_cp(
+    ui: &mut Ui,
+    command: &CommandHelper,
+    args: &CpArgs,
+) -> Result<(), CommandError> {
+    copy_file(ui, command, &args.source, &args.destination, false)
+}
+
+/// Copies or moves (if `remove_source` is true) a file in the working copy,
+/// and records the source in the working-copy commit.
+pub(crate) fn copy_file(
+    ui: &mut Ui,
+    command: &CommandHelper,
+    source: &str,
+    destination: &str,
+    remove_source: bool,
+) -> Result<(), CommandError> {
+    let mut workspace_command = command.workspace_helper(ui)?;
+    let store = workspace_command.repo().store().clone();
+    let source = workspace_command.parse_file_path(source)?;
+    let destination = workspace_command.parse_file_path(destination)?;
+    let source_ui_path = workspace_command.format_file_path(&source);
+    let destination_ui_path = workspace_command.format_file_path(&destination);
+
+    let mut tx = workspace_command.start_transaction().into_inner();
+    let base_ignores = workspace_command.base_ignores();
+    let workspace_root = workspace_command.workspace_root().clone();
+    let (mut locked_ws, wc_commit) = workspace_command.start_working_copy_mutation()?;
+    let wc_tree = wc_commit.tree()?;
+    let source_value = wc_tree.path_value(&source);
+    if source_value.is_absent() {
+        return Err(user_error(format!("No such file: {source_ui_path}")));
+    }
+    if !matches!(
+        source_value.as_resolved(),
+        Some(Some(TreeValue::File { .. }))
+    ) {
+        return Err(user_error(format!("Not a regular file: {source_ui_path}")));
+    }
+    let source_disk_path = source.to_fs_path(&workspace_root);
+    let destination_disk_path = destination.to_fs_path(&workspace_root);
+    if wc_tree.path_value(&destination).is_present()
+        || destination_disk_path.symlink_metadata().is_ok()
+    {
+        return Err(user_error(format!(
+            "Destination already exists: {destination_ui_path}"
+        )));
+    }
+
+    if let Some(parent) = destination_disk_path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    if remove_source {
+        fs::rename(&source_disk_path, &destination_disk_path)?;
+    } else {
+        fs::copy(&source_disk_path, &destination_disk_path)?;
+    }
+
+    let mut tree_builder = MergedTreeBuilder::new(wc_commit.tree_id().clone());
+    tree_builder.set_or_remove(destination.clone(), source_value);
+    let mut copy_sources = wc_commit.copy_sources();
+    let recorded_source = if remove_source {
+        tree_builder.set_or_remove(source.clone(), Merge::absent());
+        // Moving a copy keeps the source it was copied from
+        copy_sources.remove(&source).unwrap_or(source)
+    } else {
+        source
+    };
+    copy_sources.insert(destination, recorded_source);
+    let new_tree_id = tree_builder.write_tree(&store)?;
+    let new_commit = tx
+        .mut_repo()
+        .rewrite_commit(command.settings(), &wc_commit)
+        .set_tree_id(new_tree_id)
+        .set_copy_sources(&copy_sources)
+        .write()?;
+    // Update the working copy's file states to match the new tree
+    locked_ws.locked_wc().reset(&new_commit)?;
+    let wc_tree_id = locked_ws.locked_wc().snapshot(SnapshotOptions {
+        base_ignores,
+        fsmonitor_kind: command.settings().fsmonitor_kind()?,
+        progress: None,
+        on_change: None,
+        cancel: None,
+        max_new_file_size: command.settings().max_new_file_size()?,
+    })?;
+    if wc_tree_id != *new_commit.tree_id() {
+        // There were concurrent changes in the working copy. Leave them to be
+        // snapshotted by the next command.
+        locked_ws.locked_wc().reset(&new_commit)?;
+    }
+    let num_rebased = tx.mut_repo().rebase_descendants(command.settings())?;
+    if num_rebased > 0 {
+        writeln!(ui.stderr(), "Rebased {num_rebased} descendant commits")?;
+    }
+    let description = if remove_source {
+        format!("move {source_ui_path} to {destination_ui_path}")
+    } else {
+        format!("copy {source_ui_path} to {destination_ui_path}")
+    };
+    let repo = tx.commit(description)?;
+    locked_ws.finish(repo.op_id().clone())?;
+    Ok(())
+}
@@ -13,17 +13,38 @@
 // limitations under the License.
 
 use std::any::Any;
+use std::collections::{HashMap, HashSet};
 use std::fmt::Debug;
-use std::io::Write as _;
+use std::io::{self, Read as _, Write as _};
+use std::sync::Arc;
 
 use clap::Subcommand;
+use itertools::Itertools;
+use jj_lib::backend::{
+    self, CommitId, ConflictId, FileId, MergedTreeId, ObjectKind, SymlinkId, TreeId, TreeValue,
+};
 use jj_lib::default_index::{AsCompositeIndex as _, DefaultIndexStore, DefaultReadonlyIndex};
-use jj_lib::local_working_copy::LocalWorkingCopy;
-use jj_lib::object_id::ObjectId;
+use jj_lib::format_version::{self, SupportedVersions, UpgradeError};
+use jj_lib::local_backend::LocalBackend;
+use jj_lib::local_working_copy::{probe_exec_bit_supported, LocalWorkingCopy};
+use jj_lib::merge::Merge;
+use jj_lib::merged_tree::MergedTreeBuilder;
+use jj_lib::object_id::{HexPrefix, ObjectId};
+use jj_lib::repo::{find_missing_commits, ConsistencyCheck};
+use jj_lib::repo_path::RepoPath;
+use jj_lib::repo_stats::{collect_repo_stats, ObjectStats, RepoStatsError};
+use jj_lib::revset::{RevsetExpression, RevsetIteratorExt as _};
+use jj_lib::rewrite::transform_descendants;
+use jj_lib::store::Store;
 use jj_lib::working_copy::WorkingCopy;
+use jj_lib::workspace;
 use jj_lib::{op_walk, revset};
 
-use crate::cli_util::{internal_error, user_error, CommandError, CommandHelper, RevisionArg};
+use crate::cli_util::{
+    find_workspace_dir, internal_error, short_commit_hash, start_repo_transaction, user_error,
+    user_error_with_hint, CommandError, CommandHelper, RevisionArg,
+};
+use crate::formatter::Formatter;
 use crate::template_parser;
 use crate::ui::Ui;
 
@@ -34,6 +55,7 @@ pub enum DebugCommand {
     Revset(DebugRevsetArgs),
     #[command(name = "workingcopy")]
     WorkingCopy(DebugWorkingCopyArgs),
+    FilesystemProbe(DebugFilesystemProbeArgs),
     Template(DebugTemplateArgs),
     Index(DebugIndexArgs),
     #[command(name = "reindex")]
@@ -42,6 +64,17 @@ pub enum DebugCommand {
     Operation(DebugOperationArgs),
     Tree(DebugTreeArgs),
     #[command(subcommand)]
+    Object(DebugObjectCommand),
+    NormalizeTrees(DebugNormalizeTreesArgs),
+    StripReservedPaths(DebugStripReservedPathsArgs),
+    FilterPaths(DebugFilterPathsArgs),
+    Chunks(DebugChunksArgs),
+    Relink(DebugRelinkArgs),
+    Check(DebugCheckArgs),
+    RecoverHeads(DebugRecoverHeadsArgs),
+    UpgradeRepo(DebugUpgradeRepoArgs),
+    Stats(DebugStatsArgs),
+    #[command(subcommand)]
     Watchman(DebugWatchmanSubcommand),
 }
 
@@ -55,6 +88,13 @@ pub struct DebugRevsetArgs {
 #[derive(clap::Args, Clone, Debug)]
 pub struct DebugWorkingCopyArgs {}
 
+/// Show which features the working copy's filesystem supports
+///
+/// Prints both what's detected now and what was recorded when the working
+/// copy was initialized. The recorded value is the one that's used.
+#[derive(clap::Args, Clone, Debug)]
+pub struct DebugFilesystemProbeArgs {}
+
 /// Parse a template
 #[derive(clap::Args, Clone, Debug)]
 pub struct DebugTemplateArgs {
@@ -99,6 +139,150 @@ pub struct DebugTreeArgs {
     // TODO: Add an option to include trees that are ancestors of the matched paths
 }
 
+/// Inspect objects in the backend's store
+#[derive(Subcommand, Clone, Debug)]
+pub enum DebugObjectCommand {
+    Show(DebugObjectShowArgs),
+}
+
+/// Print an object from the backend's store
+///
+/// The object is found by a unique prefix of its id. Commits, trees, and
+/// conflicts are printed as their parsed fields, and files and symlinks as
+/// their size and target, respectively.
+#[derive(clap::Args, Clone, Debug)]
+pub struct DebugObjectShowArgs {
+    /// The id of the object, or a unique prefix of it
+    id: String,
+    /// The kind of object to look for (default: any kind)
+    #[arg(long = "type", value_enum)]
+    kind: Option<DebugObjectKind>,
+    /// Print the object as it's stored by the backend
+    #[arg(long)]
+    raw: bool,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, clap::ValueEnum)]
+pub enum DebugObjectKind {
+    Commit,
+    Tree,
+    File,
+    Symlink,
+    Conflict,
+}
+
+impl From<DebugObjectKind> for ObjectKind {
+    fn from(kind: DebugObjectKind) -> Self {
+        match kind {
+            DebugObjectKind::Commit => ObjectKind::Commit,
+            DebugObjectKind::Tree => ObjectKind::Tree,
+            DebugObjectKind::File => ObjectKind::File,
+            DebugObjectKind::Symlink => ObjectKind::Symlink,
+            DebugObjectKind::Conflict => ObjectKind::Conflict,
+        }
+    }
+}
+
+/// Rewrite commits whose trees have entries pointing at the empty tree
+///
+/// Such trees can only come from outside jj, e.g. from Git. They have the same
+/// contents as the normalized trees, but different ids. Descendants of the
+/// rewritten commits are rebased.
+#[derive(clap::Args, Clone, Debug)]
+pub struct DebugNormalizeTreesArgs {}
+
+/// Remove `.jj` and `.git` entries from the trees of commits
+///
+/// Such entries can be left by older versions that snapshotted the contents of
+/// these directories. Descendants of the rewritten commits are rebased.
+#[derive(clap::Args, Clone, Debug)]
+pub struct DebugStripReservedPathsArgs {
+    /// The revisions to check
+    #[arg(long, short, default_value = "all()")]
+    revisions: Vec<RevisionArg>,
+}
+
+/// Remove paths from the trees of commits
+///
+/// The paths are removed from the selected commits that contain them, and the
+/// descendants of these commits are rebased. Conflicts at the removed paths are
+/// removed too. Commits that don't need to be rewritten keep their ids. The old
+/// and new ids of the rewritten commits are printed.
+#[derive(clap::Args, Clone, Debug)]
+pub struct DebugFilterPathsArgs {
+    /// The revisions to remove the paths from
+    #[arg(long, short, default_value = "all()")]
+    revisions: Vec<RevisionArg>,
+    /// The paths to remove
+    #[arg(long, required = true, value_hint = clap::ValueHint::AnyPath)]
+    remove: Vec<String>,
+}
+
+/// Show how much space large files in the native backend share
+///
+/// Large files are stored in content-defined chunks, so successive versions
+/// of a file can share the chunks that didn't change.
+#[derive(clap::Args, Clone, Debug)]
+pub struct DebugChunksArgs {}
+
+/// Point the workspace at a repo or Git repo that was moved
+///
+/// This works even if the workspace can't be loaded because the repo is
+/// missing.
+#[derive(clap::Args, Clone, Debug)]
+#[command(group(clap::ArgGroup::new("target").args(&["repo", "git_repo"]).required(true).multiple(true)))]
+pub struct DebugRelinkArgs {
+    /// The new path of the repo (the `.jj/repo` directory of the workspace
+    /// that contains it)
+    #[arg(long, value_hint = clap::ValueHint::DirPath)]
+    repo: Option<String>,
+    /// The new path of the Git repo backing the repo
+    #[arg(long, value_hint = clap::ValueHint::DirPath)]
+    git_repo: Option<String>,
+}
+
+/// Check that the commits the repo refers to exist in the store
+///
+/// Every commit referenced by the view and every ancestor of the visible heads
+/// is checked. Commands only check the visible heads and the working-copy
+/// commits when loading the repo.
+#[derive(clap::Args, Clone, Debug)]
+pub struct DebugCheckArgs {}
+
+/// Remove references to commits that are missing from the store
+///
+/// This makes the repo usable again after commit objects were lost, e.g. by
+/// deleting files from the store by hand. Missing visible heads are replaced by
+/// their nearest ancestors that still exist. A workspace whose working-copy
+/// commit is missing gets a new working-copy commit on top of the root commit;
+/// in the current workspace, it keeps the files in the working copy.
+#[derive(clap::Args, Clone, Debug)]
+pub struct DebugRecoverHeadsArgs {}
+
+/// Upgrade the repo and working copy to the formats of this version of jj
+///
+/// Repos in formats older than the current ones can't be loaded until they're
+/// upgraded. Older versions of jj may not be able to use the repo afterwards.
+#[derive(clap::Args, Clone, Debug)]
+pub struct DebugUpgradeRepoArgs {}
+
+/// Show statistics about the objects in the repo
+///
+/// Counts the commits, trees, files, symlinks and conflicts reachable from
+/// all commits in the index, including hidden ones, and lists the largest
+/// files with the commits that introduced them. Also shows the sizes of the
+/// index segments, the number of operations, and the number of entries in
+/// the working copy's file state cache.
+#[derive(clap::Args, Clone, Debug)]
+pub struct DebugStatsArgs {
+    /// Number of largest files to list
+    #[arg(long, default_value_t = 10)]
+    top: usize,
+    /// Print the statistics as JSON
+    #[arg(long)]
+    json: bool,
+}
+
 #[derive(Subcommand, Clone, Debug)]
 pub enum DebugWatchmanSubcommand {
     QueryClock,
@@ -114,11 +298,24 @@ pub fn cmd_debug(
     match subcommand {
         DebugCommand::Revset(args) => cmd_debug_revset(ui, command, args),
         DebugCommand::WorkingCopy(args) => cmd_debug_working_copy(ui, command, args),
+        DebugCommand::FilesystemProbe(args) => cmd_debug_filesystem_probe(ui, command, args),
         DebugCommand::Template(args) => cmd_debug_template(ui, command, args),
         DebugCommand::Index(args) => cmd_debug_index(ui, command, args),
         DebugCommand::ReIndex(args) => cmd_debug_reindex(ui, command, args),
         DebugCommand::Operation(args) => cmd_debug_operation(ui, command, args),
         DebugCommand::Tree(args) => cmd_debug_tree(ui, command, args),
+        DebugCommand::Object(DebugObjectCommand::Show(args)) => {
+            cmd_debug_object_show(ui, command, args)
+        }
+        DebugCommand::NormalizeTrees(args) => cmd_debug_normalize_trees(ui, command, args),
+        DebugCommand::StripReservedPaths(args) => cmd_debug_strip_reserved_paths(ui, command, args),
+        DebugCommand::FilterPaths(args) => cmd_debug_filter_paths(ui, command, args),
+        DebugCommand::Chunks(args) => cmd_debug_chunks(ui, command, args),
+        DebugCommand::Relink(args) => cmd_debug_relink(ui, command, args),
+        DebugCommand::Check(args) => cmd_debug_check(ui, command, args),
+        DebugCommand::RecoverHeads(args) => cmd_debug_recover_heads(ui, command, args),
+        DebugCommand::UpgradeRepo(args) => cmd_debug_upgrade_repo(ui, command, args),
+        DebugCommand::Stats(args) => cmd_debug_stats(ui, command, args),
         DebugCommand::Watchman(args) => cmd_debug_watchman(ui, command, args),
     }
 }
@@ -182,6 +379,29 @@ fn cmd_debug_working_copy(
     Ok(())
 }
 
+fn cmd_debug_filesystem_probe(
+    ui: &mut Ui,
+    command: &CommandHelper,
+    _args: &DebugFilesystemProbeArgs,
+) -> Result<(), CommandError> {
+    let workspace_command = command.workspace_helper_no_snapshot(ui)?;
+    let wc = check_local_disk_wc(workspace_command.working_copy().as_any())?;
+    let describe = |supported: bool| {
+        if supported {
+            "supported"
+        } else {
+            "not supported"
+        }
+    };
+    writeln!(
+        ui.stdout(),
+        "Executable bit: {} (recorded: {})",
+        describe(probe_exec_bit_supported(workspace_command.workspace_root())),
+        describe(wc.exec_bit_supported()?)
+    )?;
+    Ok(())
+}
+
 fn cmd_debug_template(
     ui: &mut Ui,
     _command: &CommandHelper,
@@ -221,6 +441,13 @@ fn cmd_debug_index(
         for (i, level) in stats.levels.iter().enumerate() {
             writeln!(ui.stdout(), "  Level {i}:")?;
             writeln!(ui.stdout(), "    Number of commits: {}", level.num_commits)?;
+            if level.num_changed_path_filters > 0 {
+                writeln!(
+                    ui.stdout(),
+                    "    Number of changed-path filters: {}",
+                    level.num_changed_path_filters
+                )?;
+            }
             writeln!(ui.stdout(), "    Name: {}", level.name.as_ref().unwrap())?;
         }
     } else {
@@ -302,6 +529,523 @@ fn cmd_debug_tree(
     Ok(())
 }
 
+fn cmd_debug_object_show(
+    ui: &mut Ui,
+    command: &CommandHelper,
+    args: &DebugObjectShowArgs,
+) -> Result<(), CommandError> {
+    let workspace_command = command.workspace_helper(ui)?;
+    let store = workspace_command.repo().store();
+    let prefix = HexPrefix::new(&args.id)
+        .ok_or_else(|| user_error(format!("Invalid object id: {:?}", args.id)))?;
+    let matches = store.find_objects_by_prefix(&prefix, args.kind.map(ObjectKind::from))?;
+    let (kind, id) = match matches.as_slice() {
+        [] => {
+            return Err(user_error(format!(
+                "No object found with id prefix {}",
+                prefix.hex()
+            )))
+        }
+        [(kind, id)] => (*kind, id),
+        _ => {
+            let hint = matches
+                .iter()
+                .map(|(kind, id)| format!("  {kind} {}", hex::encode(id)))
+                .join("\n");
+            return Err(user_error_with_hint(
+                format!("Object id prefix {} is ambiguous", prefix.hex()),
+                format!("The prefix matches these objects:\n{hint}"),
+            ));
+        }
+    };
+
+    if args.raw {
+        let data = store.read_raw_object(kind, id)?;
+        ui.stdout_formatter().write_all(&data)?;
+        return Ok(());
+    }
+    let mut formatter = ui.stdout_formatter();
+    let formatter = formatter.as_mut();
+    writeln!(formatter, "{kind} {}", hex::encode(id))?;
+    let path = RepoPath::root();
+    match kind {
+        ObjectKind::Commit => {
+            let commit = store.get_commit(&CommitId::from_bytes(id))?;
+            write_store_commit(formatter, commit.store_commit())?;
+        }
+        ObjectKind::Tree => {
+            let tree = store.get_tree(path, &TreeId::from_bytes(id))?;
+            for entry in tree.entries_non_recursive() {
+                writeln!(
+                    formatter,
+                    "{} {}",
+                    format_tree_value(entry.value()),
+                    entry.name().to_string_lossy()
+                )?;
+            }
+        }
+        ObjectKind::File => {
+            let mut contents = vec![];
+            store
+                .read_file(path, &FileId::from_bytes(id))?
+                .read_to_end(&mut contents)?;
+            writeln!(formatter, "size {}", contents.len())?;
+        }
+        ObjectKind::Symlink => {
+            let target = store.read_symlink(path, &SymlinkId::from_bytes(id))?;
+            writeln!(formatter, "target {target}")?;
+        }
+        ObjectKind::Conflict => {
+            let conflict = store.read_conflict(path, &ConflictId::from_bytes(id))?;
+            for value in conflict.removes() {
+                writeln!(formatter, "remove {}", format_conflict_term(value))?;
+            }
+            for value in conflict.adds() {
+                writeln!(formatter, "add {}", format_conflict_term(value))?;
+            }
+        }
+    }
+    Ok(())
+}
+
+fn write_store_commit(
+    formatter: &mut dyn Formatter,
+    commit: &backend::Commit,
+) -> std::io::Result<()> {
+    match &commit.root_tree {
+        MergedTreeId::Legacy(tree_id) => writeln!(formatter, "tree {}", tree_id.hex())?,
+        MergedTreeId::Merge(tree_ids) => {
+            for tree_id in tree_ids.removes() {
+                writeln!(formatter, "tree-remove {}", tree_id.hex())?;
+            }
+            for tree_id in tree_ids.adds() {
+                writeln!(formatter, "tree {}", tree_id.hex())?;
+            }
+        }
+    }
+    for parent_id in &commit.parents {
+        writeln!(formatter, "parent {}", parent_id.hex())?;
+    }
+    for predecessor_id in &commit.predecessors {
+        writeln!(formatter, "predecessor {}", predecessor_id.hex())?;
+    }
+    writeln!(formatter, "change-id {}", commit.change_id.hex())?;
+    writeln!(formatter, "author {}", format_signature(&commit.author))?;
+    writeln!(
+        formatter,
+        "committer {}",
+        format_signature(&commit.committer)
+    )?;
+    if let Some(sig) = &commit.secure_sig {
+        writeln!(formatter, "signature {} bytes", sig.sig.len())?;
+    }
+    writeln!(formatter)?;
+    for line in commit.description.lines() {
+        writeln!(formatter, "    {line}")?;
+    }
+    Ok(())
+}
+
+fn format_signature(signature: &backend::Signature) -> String {
+    let timestamp = &signature.timestamp;
+    let tz_sign = if timestamp.tz_offset < 0 { '-' } else { '+' };
+    let tz_minutes = timestamp.tz_offset.abs();
+    format!(
+        "{} <{}> {} {tz_sign}{:02}{:02}",
+        signature.name,
+        signature.email,
+        timestamp.timestamp.0,
+        tz_minutes / 60,
+        tz_minutes % 60
+    )
+}
+
+fn format_tree_value(value: &TreeValue) -> String {
+    match value {
+        TreeValue::File {
+            id,
+            executable: false,
+        } => format!("file {}", id.hex()),
+        TreeValue::File {
+            id,
+            executable: true,
+        } => format!("executable-file {}", id.hex()),
+        TreeValue::Symlink(id) => format!("symlink {}", id.hex()),
+        TreeValue::Tree(id) => format!("tree {}", id.hex()),
+        TreeValue::GitSubmodule(id) => format!("submodule {}", id.hex()),
+        TreeValue::Conflict(id) => format!("conflict {}", id.hex()),
+    }
+}
+
+fn format_conflict_term(value: &Option<TreeValue>) -> String {
+    match value {
+        Some(value) => format_tree_value(value),
+        None => "absent".to_owned(),
+    }
+}
+
+fn cmd_debug_normalize_trees(
+    ui: &mut Ui,
+    command: &CommandHelper,
+    _args: &DebugNormalizeTreesArgs,
+) -> Result<(), CommandError> {
+    let mut workspace_command = command.workspace_helper(ui)?;
+    let root_commit_id = workspace_command.repo().store().root_commit_id().clone();
+    // Visit parents before children. A commit is rewritten if its tree changes
+    // or if any of its parents is rewritten. Since normalization doesn't change
+    // the contents, the rewritten commits can keep their (normalized) trees.
+    let commits = workspace_command.resolve_revset("all()", ui)?;
+    let mut new_tree_ids = HashMap::new();
+    let mut to_rewrite = vec![];
+    for commit in commits.into_iter().rev() {
+        if commit.id() == &root_commit_id {
+            continue;
+        }
+        let tree = commit.tree()?;
+        let new_tree_id = tree.normalize()?.id();
+        let parent_rewritten = commit
+            .parent_ids()
+            .iter()
+            .any(|id| new_tree_ids.contains_key(id));
+        if new_tree_id != tree.id() || parent_rewritten {
+            new_tree_ids.insert(commit.id().clone(), new_tree_id);
+            to_rewrite.push(commit);
+        }
+    }
+    if to_rewrite.is_empty() {
+        writeln!(ui.stderr(), "Nothing changed.")?;
+        return Ok(());
+    }
+    workspace_command.check_rewritable(&to_rewrite)?;
+
+    let mut tx = workspace_command.start_transaction();
+    let mut rewritten: HashMap<CommitId, CommitId> = HashMap::new();
+    for commit in &to_rewrite {
+        let new_parent_ids = commit
+            .parent_ids()
+            .iter()
+            .map(|id| rewritten.get(id).unwrap_or(id).clone())
+            .collect();
+        let new_commit = tx
+            .mut_repo()
+            .rewrite_commit(command.settings(), commit)
+            .set_parents(new_parent_ids)
+            .set_tree_id(new_tree_ids[commit.id()].clone())
+            .write()?;
+        rewritten.insert(commit.id().clone(), new_commit.id().clone());
+    }
+    tx.mut_repo().rebase_descendants(command.settings())?;
+    writeln!(ui.stderr(), "Rewrote {} commits", to_rewrite.len())?;
+    tx.finish(ui, "normalize trees")?;
+    Ok(())
+}
+
+fn cmd_debug_strip_reserved_paths(
+    ui: &mut Ui,
+    command: &CommandHelper,
+    args: &DebugStripReservedPathsArgs,
+) -> Result<(), CommandError> {
+    let mut workspace_command = command.workspace_helper(ui)?;
+    let root_commit_id = workspace_command.repo().store().root_commit_id().clone();
+    let mut to_strip = vec![];
+    for revision in &args.revisions {
+        for commit in workspace_command.resolve_revset(revision, ui)? {
+            if commit.id() != &root_commit_id
+                && commit.tree()?.without_reserved_paths()?.id() != *commit.tree_id()
+            {
+                to_strip.push(commit);
+            }
+        }
+    }
+    if to_strip.is_empty() {
+        writeln!(ui.stderr(), "Nothing changed.")?;
+        return Ok(());
+    }
+    workspace_command.check_rewritable(&to_strip)?;
+
+    let mut tx = workspace_command.start_transaction();
+    let to_strip_ids: HashSet<_> = to_strip.iter().map(|commit| commit.id().clone()).collect();
+    // Rebased descendants may also inherit reserved entries from the stripped
+    // commits, so every visited tree is checked again.
+    let rewritten = transform_descendants(
+        command.settings(),
+        tx.mut_repo(),
+        to_strip_ids.into_iter().collect(),
+        |context| {
+            if !context.parents_changed()
+                && context.old_commit().tree()?.without_reserved_paths()?.id()
+                    == *context.old_commit().tree_id()
+            {
+                return Ok::<_, CommandError>(None);
+            }
+            let store = context.repo().store().clone();
+            let builder = context.rebase()?;
+            let new_tree = store
+                .get_root_tree(builder.tree_id())?
+                .without_reserved_paths()?;
+            Ok(Some(builder.set_tree_id(new_tree.id())))
+        },
+    )?;
+    writeln!(ui.stderr(), "Rewrote {} commits", rewritten.len())?;
+    tx.finish(ui, "strip reserved paths")?;
+    Ok(())
+}
+
+fn cmd_debug_filter_paths(
+    ui: &mut Ui,
+    command: &CommandHelper,
+    args: &DebugFilterPathsArgs,
+) -> Result<(), CommandError> {
+    let mut workspace_command = command.workspace_helper(ui)?;
+    let matcher = workspace_command.matcher_from_values(&args.remove)?;
+    let store = workspace_command.repo().store().clone();
+    let mut to_filter = vec![];
+    for revision in &args.revisions {
+        for commit in workspace_command.resolve_revset(revision, ui)? {
+            if commit.id() != store.root_commit_id()
+                && commit
+                    .tree()?
+                    .entries_matching(matcher.as_ref())
+                    .next()
+                    .is_some()
+            {
+                to_filter.push(commit);
+            }
+        }
+    }
+    if to_filter.is_empty() {
+        writeln!(ui.stderr(), "Nothing changed.")?;
+        return Ok(());
+    }
+    workspace_command.check_rewritable(&to_filter)?;
+
+    // The contents of the removed files, which may no longer be reachable
+    // once the commits are rewritten
+    let mut removed_files = HashMap::new();
+    for commit in &to_filter {
+        for (path, value) in commit.tree()?.entries_matching(matcher.as_ref()) {
+            for value in value.iter().flatten() {
+                for_each_file_id(&store, &path, value, &mut |id, path| {
+                    removed_files
+                        .entry(id.clone())
+                        .or_insert_with(|| path.to_owned());
+                })?;
+            }
+        }
+    }
+
+    let mut tx = workspace_command.start_transaction();
+    let to_filter_ids: HashSet<_> = to_filter.iter().map(|commit| commit.id().clone()).collect();
+    let mut visited = vec![];
+    let rewritten = transform_descendants(
+        command.settings(),
+        tx.mut_repo(),
+        to_filter_ids.iter().cloned().collect(),
+        |context| {
+            visited.push(context.old_commit().id().clone());
+            let filter = to_filter_ids.contains(context.old_commit().id());
+            if !filter && !context.parents_changed() {
+                return Ok::<_, CommandError>(None);
+            }
+            let builder = context.rebase()?;
+            if !filter {
+                return Ok(Some(builder));
+            }
+            let tree = store.get_root_tree(builder.tree_id())?;
+            let mut tree_builder = MergedTreeBuilder::new(tree.id());
+            for (path, _) in tree.entries_matching(matcher.as_ref()) {
+                tree_builder.set_or_remove(path, Merge::absent());
+            }
+            let new_tree_id = tree_builder.write_tree(&store)?;
+            Ok(Some(builder.set_tree_id(new_tree_id)))
+        },
+    )?;
+    for old_id in &visited {
+        if let Some(new_id) = rewritten.get(old_id) {
+            writeln!(ui.stdout(), "{} -> {}", old_id.hex(), new_id.hex())?;
+        }
+    }
+    writeln!(ui.stderr(), "Rewrote {} commits", rewritten.len())?;
+
+    // Files may still be reachable from other paths or from commits that
+    // weren't selected
+    {
+        let mut seen_trees = HashSet::new();
+        let visible_revset = RevsetExpression::all().evaluate_programmatic(tx.repo())?;
+        for commit in visible_revset.iter().commits(&store) {
+            if removed_files.is_empty() {
+                break;
+            }
+            for tree_id in commit?.tree_id().to_merge().iter() {
+                for_each_reachable_file_id(
+                    &store,
+                    RepoPath::root(),
+                    tree_id,
+                    &mut seen_trees,
+                    &mut |id, _path| {
+                        removed_files.remove(id);
+                    },
+                )?;
+            }
+        }
+    }
+    let mut unreachable_size = 0;
+    for (id, path) in &removed_files {
+        let mut reader = store.read_file(path, id)?;
+        unreachable_size += io::copy(&mut reader, &mut io::sink())?;
+    }
+    writeln!(
+        ui.stderr(),
+        "{} files ({} bytes) are no longer reachable",
+        removed_files.len(),
+        unreachable_size
+    )?;
+    tx.finish(ui, "filter paths")?;
+    Ok(())
+}
+
+/// Calls `f` with the id of each file in `value`, including the terms of
+/// legacy conflict objects.
+fn for_each_file_id(
+    store: &Store,
+    path: &RepoPath,
+    value: &TreeValue,
+    f: &mut impl FnMut(&FileId, &RepoPath),
+) -> Result<(), backend::BackendError> {
+    match value {
+        TreeValue::File { id, .. } => f(id, path),
+        TreeValue::Conflict(id) => {
+            let conflict = store.read_conflict(path, id)?;
+            for value in conflict.iter().flatten() {
+                for_each_file_id(store, path, value, f)?;
+            }
+        }
+        TreeValue::Symlink(_) | TreeValue::Tree(_) | TreeValue::GitSubmodule(_) => {}
+    }
+    Ok(())
+}
+
+/// Calls `f` with the id of each file reachable from the tree, skipping trees
+/// in `seen_trees`.
+fn for_each_reachable_file_id(
+    store: &Arc<Store>,
+    dir: &RepoPath,
+    id: &TreeId,
+    seen_trees: &mut HashSet<TreeId>,
+    f: &mut impl FnMut(&FileId, &RepoPath),
+) -> Result<(), backend::BackendError> {
+    if !seen_trees.insert(id.clone()) {
+        return Ok(());
+    }
+    let tree = store.get_tree(dir, id)?;
+    for entry in tree.entries_non_recursive() {
+        let path = dir.join(entry.name());
+        match entry.value() {
+            TreeValue::Tree(id) => for_each_reachable_file_id(store, &path, id, seen_trees, f)?,
+            value => for_each_file_id(store, &path, value, f)?,
+        }
+    }
+    Ok(())
+}
+
+fn cmd_debug_chunks(
+    ui: &mut Ui,
+    command: &CommandHelper,
+    _args: &DebugChunksArgs,
+) -> Result<(), CommandError> {
+    let workspace_command = command.workspace_helper(ui)?;
+    let store = workspace_command.repo().store();
+    let Some(local_backend) = store.backend_impl().downcast_ref::<LocalBackend>() else {
+        return Err(user_error(
+            "Chunk stats are only available for the native backend",
+        ));
+    };
+    let stats = local_backend.chunk_stats()?;
+    writeln!(ui.stdout(), "Number of chunked files: {}", stats.num_files)?;
+    writeln!(ui.stdout(), "Total size: {}", stats.total_size)?;
+    writeln!(ui.stdout(), "Number of chunks: {}", stats.num_chunk_refs)?;
+    writeln!(
+        ui.stdout(),
+        "Number of unique chunks: {}",
+        stats.num_unique_chunks
+    )?;
+    writeln!(ui.stdout(), "Size of unique chunks: {}", stats.unique_size)?;
+    Ok(())
+}
+
+fn cmd_debug_check(
+    ui: &mut Ui,
+    command: &CommandHelper,
+    _args: &DebugCheckArgs,
+) -> Result<(), CommandError> {
+    // Load the repo without the usual check, which would fail on the first
+    // missing commit.
+    let workspace = command.load_workspace()?;
+    let repo_loader = workspace.repo_loader();
+    let op = command.resolve_operation(ui, repo_loader)?;
+    let repo = repo_loader.load_at(&op)?;
+    let missing = find_missing_commits(repo.as_ref(), ConsistencyCheck::Thorough)?;
+    for (id, reference) in &missing.view_references {
+        writeln!(
+            ui.stdout(),
+            "Commit {} referenced by {reference} is missing",
+            id.hex()
+        )?;
+    }
+    for id in &missing.ancestors {
+        writeln!(ui.stdout(), "Ancestor commit {} is missing", id.hex())?;
+    }
+    if missing.is_empty() {
+        writeln!(ui.stderr(), "No missing commits found")?;
+        Ok(())
+    } else if missing.view_references.is_empty() {
+        Err(user_error("The repo has missing commits"))
+    } else {
+        Err(user_error_with_hint(
+            "The repo has missing commits",
+            "Run `jj debug recover-heads` to remove the references to them.",
+        ))
+    }
+}
+
+fn cmd_debug_recover_heads(
+    ui: &mut Ui,
+    command: &CommandHelper,
+    _args: &DebugRecoverHeadsArgs,
+) -> Result<(), CommandError> {
+    let workspace = command.load_workspace()?;
+    let repo_loader = workspace.repo_loader();
+    let op = command.resolve_operation(ui, repo_loader)?;
+    let repo = repo_loader.load_at(&op)?;
+    // Keep the files in the working copy if its commit is replaced
+    let mut wc_tree_ids = HashMap::new();
+    if let Ok(tree_id) = workspace.working_copy().tree_id() {
+        wc_tree_ids.insert(workspace.workspace_id().clone(), tree_id.clone());
+    }
+    let mut tx = start_repo_transaction(
+        &repo,
+        command.settings(),
+        command.string_args(),
+        &command.command_name(),
+    );
+    let removed = tx
+        .mut_repo()
+        .remove_missing_view_commits(command.settings(), &wc_tree_ids)?;
+    if removed.is_empty() {
+        writeln!(ui.stderr(), "Nothing to recover")?;
+        return Ok(());
+    }
+    for (id, reference) in &removed {
+        writeln!(
+            ui.stderr(),
+            "Dropped {reference} (missing commit {})",
+            short_commit_hash(id)
+        )?;
+    }
+    tx.commit("recover missing heads")?;
+    Ok(())
+}
+
 #[cfg(feature = "watchman")]
 fn cmd_debug_watchman(
     ui: &mut Ui,
@@ -355,3 +1099,231 @@ fn check_local_disk_wc(x: &dyn Any) -> Result<&LocalWorkingCopy, CommandError> {
     x.downcast_ref()
         .ok_or_else(|| user_error("This command requires a standard local-disk working copy"))
 }
+
+fn cmd_debug_upgrade_repo(
+    ui: &mut Ui,
+    command: &CommandHelper,
+    _args: &DebugUpgradeRepoArgs,
+) -> Result<(), CommandError> {
+    // The repo isn't loaded, since that fails if it needs to be upgraded.
+    let loader = command.workspace_loader()?;
+    let applied = format_version::upgrade(
+        loader.repo_path(),
+        Some(loader.working_copy_state_path()),
+        &SupportedVersions::current(),
+        &format_version::builtin_migrations(),
+    )
+    .map_err(|err| match err {
+        UpgradeError::FormatVersion(_) | UpgradeError::MissingMigration { .. } => user_error(err),
+        UpgradeError::Migration { .. } | UpgradeError::Path(_) => internal_error(err),
+    })?;
+    if applied.is_empty() {
+        writeln!(ui.stderr(), "Nothing to upgrade")?;
+    }
+    for migration in &applied {
+        writeln!(
+            ui.stderr(),
+            "Upgraded the {} from format {} to {}: {}",
+            migration.component,
+            migration.from_version,
+            migration.from_version + 1,
+            migration.description
+        )?;
+    }
+    Ok(())
+}
+
+#[derive(serde::Serialize)]
+struct StatsJson {
+    objects: ObjectsJson,
+    largest_files: Vec<LargeFileJson>,
+    /// `None` if the index isn't the default one.
+    index_segments: Option<Vec<IndexSegmentJson>>,
+    operations: u64,
+    /// `None` if the working copy isn't a local-disk one.
+    working_copy_file_states: Option<usize>,
+}
+
+#[derive(serde::Serialize)]
+struct ObjectsJson {
+    commits: ObjectStatsJson,
+    trees: ObjectStatsJson,
+    files: ObjectStatsJson,
+    symlinks: ObjectStatsJson,
+    conflicts: ObjectStatsJson,
+}
+
+#[derive(serde::Serialize)]
+struct ObjectStatsJson {
+    count: u64,
+    bytes: u64,
+}
+
+#[derive(serde::Serialize)]
+struct LargeFileJson {
+    path: String,
+    bytes: u64,
+    commit_id: String,
+}
+
+#[derive(serde::Serialize)]
+struct IndexSegmentJson {
+    name: Option<String>,
+    commits: u32,
+    bytes: usize,
+}
+
+impl From<&ObjectStats> for ObjectStatsJson {
+    fn from(stats: &ObjectStats) -> Self {
+        ObjectStatsJson {
+            count: stats.count,
+            bytes: stats.num_bytes,
+        }
+    }
+}
+
+fn cmd_debug_stats(
+    ui: &mut Ui,
+    command: &CommandHelper,
+    args: &DebugStatsArgs,
+) -> Result<(), CommandError> {
+    let workspace_command = command.workspace_helper_no_snapshot(ui)?;
+    let repo = workspace_command.repo();
+    let stats = collect_repo_stats(repo, args.top).map_err(|err| match err {
+        RepoStatsError::Backend(err) => err.into(),
+        RepoStatsError::OpStore(err) => err.into(),
+        RepoStatsError::AllHeadsUnsupported(err) => user_error(err),
+    })?;
+    let index_segments = repo
+        .readonly_index()
+        .as_any()
+        .downcast_ref::<DefaultReadonlyIndex>()
+        .map(|index| {
+            index
+                .as_composite()
+                .stats()
+                .levels
+                .into_iter()
+                .map(|level| IndexSegmentJson {
+                    name: level.name,
+                    commits: level.num_commits,
+                    bytes: level.num_bytes,
+                })
+                .collect_vec()
+        });
+    let working_copy_file_states = match workspace_command
+        .working_copy()
+        .as_any()
+        .downcast_ref::<LocalWorkingCopy>()
+    {
+        Some(wc) => Some(wc.file_states()?.into_iter().count()),
+        None => None,
+    };
+    let stats = StatsJson {
+        objects: ObjectsJson {
+            commits: (&stats.commits).into(),
+            trees: (&stats.trees).into(),
+            files: (&stats.files).into(),
+            symlinks: (&stats.symlinks).into(),
+            conflicts: (&stats.conflicts).into(),
+        },
+        largest_files: stats
+            .largest_files
+            .iter()
+            .map(|file| LargeFileJson {
+                path: file.path.as_internal_file_string().into_owned(),
+                bytes: file.size,
+                commit_id: file.commit_id.hex(),
+            })
+            .collect(),
+        index_segments,
+        operations: stats.num_operations,
+        working_copy_file_states,
+    };
+
+    let mut formatter = ui.stdout_formatter();
+    if args.json {
+        writeln!(
+            formatter,
+            "{}",
+            serde_json::to_string_pretty(&stats).unwrap()
+        )?;
+        return Ok(());
+    }
+    writeln!(
+        formatter,
+        "{:<10} {:>10} {:>14}",
+        "Objects", "Count", "Bytes"
+    )?;
+    for (kind, object_stats) in [
+        ("Commits", &stats.objects.commits),
+        ("Trees", &stats.objects.trees),
+        ("Files", &stats.objects.files),
+        ("Symlinks", &stats.objects.symlinks),
+        ("Conflicts", &stats.objects.conflicts),
+    ] {
+        writeln!(
+            formatter,
+            "{kind:<10} {:>10} {:>14}",
+            object_stats.count, object_stats.bytes
+        )?;
+    }
+    writeln!(formatter)?;
+    writeln!(formatter, "Largest files:")?;
+    for file in &stats.largest_files {
+        writeln!(
+            formatter,
+            "  {:>14}  {}  (introduced in commit {})",
+            file.bytes,
+            workspace_command.format_file_path(RepoPath::from_internal_string(&file.path)),
+            &file.commit_id[..12]
+        )?;
+    }
+    writeln!(formatter)?;
+    if let Some(segments) = &stats.index_segments {
+        writeln!(formatter, "Index segments:")?;
+        for segment in segments {
+            writeln!(
+                formatter,
+                "  {}: {} commits, {} bytes",
+                segment.name.as_deref().unwrap_or("(not written)"),
+                segment.commits,
+                segment.bytes
+            )?;
+        }
+    }
+    writeln!(formatter, "Operations: {}", stats.operations)?;
+    if let Some(num_file_states) = stats.working_copy_file_states {
+        writeln!(formatter, "Working-copy file states: {num_file_states}")?;
+    }
+    Ok(())
+}
+
+fn cmd_debug_relink(
+    ui: &mut Ui,
+    command: &CommandHelper,
+    args: &DebugRelinkArgs,
+) -> Result<(), CommandError> {
+    // The workspace is located without loading it, since that fails if the
+    // repo can't be found.
+    let workspace_root = match &command.global_args().repository {
+        Some(path) => command.cwd().join(path),
+        None => find_workspace_dir(command.cwd()).to_owned(),
+    };
+    if let Some(repo_path) = &args.repo {
+        if workspace_root.join(".jj").join("repo").is_dir() {
+            return Err(user_error(
+                "The repo is stored in this workspace, so it can't be relinked",
+            ));
+        }
+        workspace::relink_repo(&workspace_root, &command.cwd().join(repo_path))
+            .map_err(user_error)?;
+        writeln!(ui.stderr(), "Updated the path to the repo")?;
+    }
+    if let Some(git_repo_path) = &args.git_repo {
+        workspace::relink_git_repo(&workspace_root, &command.cwd().join(git_repo_path))
+            .map_err(user_error)?;
+        writeln!(ui.stderr(), "Updated the path to the Git repo")?;
+    }
+    Ok(())
+}
@@ -12,11 +12,16 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use jj_lib::rewrite::merge_commit_trees;
+use jj_lib::gitignore::GitIgnoreFile;
+use jj_lib::local_working_copy::snapshot_directory;
+use jj_lib::repo::Repo;
+use jj_lib::working_copy::SnapshotOptions;
 use tracing::instrument;
 
-use crate::cli_util::{CommandError, CommandHelper, RevisionArg};
-use crate::diff_util::{diff_formats_for, show_diff, DiffFormatArgs};
+use crate::cli_util::{user_error, CommandError, CommandHelper, RevisionArg};
+use crate::diff_util::{
+    diff_formats_for, show_diff, show_nul_terminated_diff, show_patch, DiffFormatArgs,
+};
 use crate::ui::Ui;
 
 /// Compare file contents between two commits
@@ -35,11 +40,31 @@ pub(crate) struct DiffArgs {
     /// Show changes to this revision
     #[arg(long, conflicts_with = "revision")]
     to: Option<RevisionArg>,
+    /// Show changes to the files in this directory
+    ///
+    /// The directory is compared as if it were the workspace root, and
+    /// doesn't have to be part of a workspace. Files ignored by `.gitignore`
+    /// files in the directory are skipped. The files are written to a
+    /// temporary store, not to the repo, which requires the Git backend.
+    #[arg(
+        long,
+        conflicts_with_all = ["revision", "to"],
+        value_name = "DIR",
+        value_hint = clap::ValueHint::DirPath,
+    )]
+    to_path: Option<String>,
     /// Restrict the diff to these paths
     #[arg(value_hint = clap::ValueHint::AnyPath)]
     paths: Vec<String>,
     #[command(flatten)]
     format: DiffFormatArgs,
+    /// Print each path as a NUL-terminated record, for scripts
+    ///
+    /// Only works with `--summary`, `--types`, and `--name-only`, which write
+    /// their status or types, then a tab, before the path. Paths are relative
+    /// to the workspace root and never quoted.
+    #[arg(short = 'z')]
+    null_terminated: bool,
 }
 
 #[instrument(skip_all)]
@@ -49,31 +74,84 @@ pub(crate) fn cmd_diff(
     args: &DiffArgs,
 ) -> Result<(), CommandError> {
     let workspace_command = command.workspace_helper(ui)?;
-    let from_tree;
-    let to_tree;
-    if args.from.is_some() || args.to.is_some() {
+    if args.from.is_some() || args.to.is_some() || args.to_path.is_some() {
         let from = workspace_command.resolve_single_rev(args.from.as_deref().unwrap_or("@"), ui)?;
-        from_tree = from.tree()?;
-        let to = workspace_command.resolve_single_rev(args.to.as_deref().unwrap_or("@"), ui)?;
-        to_tree = to.tree()?;
+        let mut from_tree = from.tree()?;
+        let snapshot;
+        let to_tree = if let Some(to_path) = &args.to_path {
+            let dir = command.cwd().join(to_path);
+            if !dir.is_dir() {
+                return Err(user_error(format!("{to_path} is not a directory")));
+            }
+            let options = SnapshotOptions {
+                base_ignores: GitIgnoreFile::empty(),
+                fsmonitor_kind: None,
+                progress: None,
+                on_change: None,
+                cancel: None,
+                max_new_file_size: u64::MAX,
+            };
+            snapshot = snapshot_directory(
+                command.settings(),
+                workspace_command.repo().store(),
+                &dir,
+                options,
+            )?;
+            // Load the tree to compare with from the temporary store, so the
+            // contents of both trees can be read from the same store
+            let temp_store = snapshot.tree().store();
+            from_tree = temp_store.get_root_tree(&from_tree.id())?;
+            snapshot.tree().clone()
+        } else {
+            let to = workspace_command.resolve_single_rev(args.to.as_deref().unwrap_or("@"), ui)?;
+            to.tree()?
+        };
+        let matcher = workspace_command.matcher_from_values(&args.paths)?;
+        let diff_formats = diff_formats_for(command.settings(), &args.format)?;
+        ui.request_pager();
+        if args.null_terminated {
+            show_nul_terminated_diff(
+                ui.stdout_formatter().as_mut(),
+                &from_tree,
+                &to_tree,
+                matcher.as_ref(),
+                &diff_formats,
+            )?;
+        } else {
+            show_diff(
+                ui,
+                ui.stdout_formatter().as_mut(),
+                &workspace_command,
+                &from_tree,
+                &to_tree,
+                matcher.as_ref(),
+                &diff_formats,
+            )?;
+        }
     } else {
         let commit =
             workspace_command.resolve_single_rev(args.revision.as_deref().unwrap_or("@"), ui)?;
-        let parents = commit.parents();
-        from_tree = merge_commit_trees(workspace_command.repo().as_ref(), &parents)?;
-        to_tree = commit.tree()?
+        let matcher = workspace_command.matcher_from_values(&args.paths)?;
+        let diff_formats = diff_formats_for(command.settings(), &args.format)?;
+        ui.request_pager();
+        if args.null_terminated {
+            show_nul_terminated_diff(
+                ui.stdout_formatter().as_mut(),
+                &workspace_command.repo().merged_parent_tree(&commit)?,
+                &commit.tree()?,
+                matcher.as_ref(),
+                &diff_formats,
+            )?;
+        } else {
+            show_patch(
+                ui,
+                ui.stdout_formatter().as_mut(),
+                &workspace_command,
+                &commit,
+                matcher.as_ref(),
+                &diff_formats,
+            )?;
+        }
     }
-    let matcher = workspace_command.matcher_from_values(&args.paths)?;
-    let diff_formats = diff_formats_for(command.settings(), &args.format)?;
-    ui.request_pager();
-    show_diff(
-        ui,
-        ui.stdout_formatter().as_mut(),
-        &workspace_command,
-        &from_tree,
-        &to_tree,
-        matcher.as_ref(),
-        &diff_formats,
-    )?;
     Ok(())
 }
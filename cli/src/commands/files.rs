@@ -16,7 +16,7 @@ use std::io::Write;
 
 use tracing::instrument;
 
-use crate::cli_util::{CommandError, CommandHelper, RevisionArg};
+use crate::cli_util::{write_nul_record, CommandError, CommandHelper, RevisionArg};
 use crate::ui::Ui;
 
 /// List files in a revision
@@ -28,6 +28,11 @@ pub(crate) struct FilesArgs {
     /// Only list files matching these prefixes (instead of all files)
     #[arg(value_hint = clap::ValueHint::AnyPath)]
     paths: Vec<String>,
+    /// End each path with a NUL byte instead of a newline, for scripts
+    ///
+    /// Paths are relative to the workspace root and never quoted.
+    #[arg(short = 'z')]
+    null_terminated: bool,
 }
 
 #[instrument(skip_all)]
@@ -41,12 +46,14 @@ pub(crate) fn cmd_files(
     let tree = commit.tree()?;
     let matcher = workspace_command.matcher_from_values(&args.paths)?;
     ui.request_pager();
+    let mut formatter = ui.stdout_formatter();
     for (name, _value) in tree.entries_matching(matcher.as_ref()) {
-        writeln!(
-            ui.stdout(),
-            "{}",
-            &workspace_command.format_file_path(&name)
-        )?;
+        if args.null_terminated {
+            write_nul_record(formatter.as_mut(), &[], &name)?;
+        } else {
+            workspace_command.write_file_path(formatter.as_mut(), &name)?;
+            writeln!(formatter)?;
+        }
     }
     Ok(())
 }
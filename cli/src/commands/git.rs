@@ -46,11 +46,12 @@ use crate::cli_util::{
     user_error_with_hint_opt, user_error_with_message, CommandError, CommandHelper, RevisionArg,
     WorkspaceCommandHelper,
 };
+use crate::commands::init::create_workspace_dir;
 use crate::git_util::{
     get_git_repo, is_colocated_git_workspace, print_failed_git_export, print_git_import_stats,
     with_remote_git_callbacks,
 };
-use crate::ui::Ui;
+use crate::ui::{Ui, UiWarning, WarningCode};
 
 /// Commands for working with the underlying Git repo
 ///
@@ -128,9 +129,9 @@ pub struct GitInitArgs {
     /// This is done by placing the backing git repo into a `.git` directory
     /// in the root of the `jj` repo along with the `.jj` directory.
     ///
-    /// This option is only valid when creating new repos. To
-    /// reuse an existing `.git` directory in an existing git
-    /// repo, see the `--git-repo` param below.
+    /// If the destination already has a `.git` directory, that git repo
+    /// is reused, with or without this option. To reuse a git repo
+    /// elsewhere, see the `--git-repo` param below.
     ///
     /// This option is mutually exclusive with `--git-repo`.
     #[arg(long, conflicts_with = "git_repo")]
@@ -147,6 +148,10 @@ pub struct GitInitArgs {
     /// This option is mutually exclusive with `--colocate`.
     #[arg(long, conflicts_with = "colocate", value_hint = clap::ValueHint::DirPath)]
     git_repo: Option<String>,
+
+    /// Create the repo even if the destination is inside another jj repo
+    #[arg(long)]
+    force_nested: bool,
 }
 
 /// Fetch from a Git remote
@@ -371,7 +376,7 @@ pub fn git_init(
     command: &CommandHelper,
     workspace_root: &Path,
     colocate: bool,
-    git_repo: Option<&str>,
+    git_repo: Option<&Path>,
 ) -> Result<(), CommandError> {
     let cwd = command.cwd().canonicalize().unwrap();
     let relative_wc_path = file_util::relative_path(&cwd, workspace_root);
@@ -383,14 +388,14 @@ pub fn git_init(
         return Ok(());
     }
 
-    if let Some(git_store_str) = git_repo {
-        let git_store_path = cwd.join(git_store_str);
-        let (workspace, repo) =
+    if let Some(git_store_path) = git_repo {
+        let git_store_path = cwd.join(git_store_path);
+        let (mut workspace, repo) =
             Workspace::init_external_git(command.settings(), workspace_root, &git_store_path)?;
         // Import refs first so all the reachable commits are indexed in
         // chronological order.
         let colocated = is_colocated_git_workspace(&workspace, &repo);
-        let repo = init_git_refs(ui, command, repo, colocated)?;
+        let repo = init_git_refs(ui, command, &mut workspace, repo, colocated)?;
         let mut workspace_command = command.for_loaded_repo(ui, workspace, repo)?;
         maybe_add_gitignore(&workspace_command)?;
         workspace_command.maybe_snapshot(ui)?;
@@ -427,14 +432,22 @@ pub fn git_init(
 /// the repo is colocated.
 ///
 /// This is similar to `WorkspaceCommandHelper::import_git_refs()`, but never
-/// moves the Git HEAD to the working copy parent.
+/// moves the Git HEAD to the working copy parent. The import replaces the
+/// operation that added the workspace, so the repo starts out with a single
+/// operation.
 fn init_git_refs(
     ui: &mut Ui,
     command: &CommandHelper,
+    workspace: &mut Workspace,
     repo: Arc<ReadonlyRepo>,
     colocated: bool,
 ) -> Result<Arc<ReadonlyRepo>, CommandError> {
-    let mut tx = start_repo_transaction(&repo, command.settings(), command.string_args());
+    let mut tx = start_repo_transaction(
+        &repo,
+        command.settings(),
+        command.string_args(),
+        &command.command_name(),
+    );
     // There should be no old refs to abandon, but enforce it.
     let mut git_settings = command.settings().git_settings();
     git_settings.abandon_unreachable_commits = false;
@@ -454,7 +467,14 @@ fn init_git_refs(
         let failed_branches = git::export_refs(tx.mut_repo())?;
         print_failed_git_export(ui, &failed_branches)?;
     }
-    let repo = tx.commit("import git refs");
+    tx.replace_base_operation()?;
+    let repo = tx.commit(format!(
+        "add workspace '{}' and import git refs",
+        workspace.workspace_id().as_str()
+    ))?;
+    // The working copy was recorded at the replaced operation
+    let locked_ws = workspace.start_working_copy_mutation()?;
+    locked_ws.finish(repo.op_id().clone())?;
     writeln!(
         ui.stderr(),
         "Done importing changes from the underlying Git repo."
@@ -468,18 +488,24 @@ fn cmd_git_init(
     args: &GitInitArgs,
 ) -> Result<(), CommandError> {
     let cwd = command.cwd().canonicalize().unwrap();
-    let wc_path = cwd.join(&args.destination);
-    let wc_path = file_util::create_or_reuse_dir(&wc_path)
-        .and_then(|_| wc_path.canonicalize())
-        .map_err(|e| user_error_with_message("Failed to create workspace", e))?;
+    let wc_path = create_workspace_dir(command, &args.destination, args.force_nested)?;
 
-    git_init(
-        ui,
-        command,
-        &wc_path,
-        args.colocate,
-        args.git_repo.as_deref(),
-    )?;
+    // Reuse an existing Git repo in the destination instead of failing to
+    // create a new one. It's colocated unless it's a linked worktree.
+    let existing_git_repo =
+        (args.git_repo.is_none() && wc_path.join(".git").exists()).then(|| wc_path.clone());
+    if let Some(path) = &existing_git_repo {
+        writeln!(
+            ui.stderr(),
+            r#"Using the existing Git repo in "{}" as the backing repo"#,
+            file_util::relative_path(&cwd, path).display()
+        )?;
+    }
+    let git_repo = existing_git_repo
+        .as_deref()
+        .or(args.git_repo.as_deref().map(Path::new));
+    let colocate = args.colocate && existing_git_repo.is_none();
+    git_init(ui, command, &wc_path, colocate, git_repo)?;
 
     let relative_wc_path = file_util::relative_path(&cwd, &wc_path);
     writeln!(
@@ -676,12 +702,14 @@ fn cmd_git_clone(
             Ok(())
         };
         if let Err(err) = clean_up_dirs() {
-            writeln!(
-                ui.warning(),
-                "Failed to clean up {}: {}",
-                canonical_wc_path.display(),
-                err
-            )
+            ui.write_warning(&UiWarning::new(
+                WarningCode::FailedCleanup,
+                format!(
+                    "Failed to clean up {}: {}",
+                    canonical_wc_path.display(),
+                    err
+                ),
+            ))
             .ok();
         }
     }
@@ -750,6 +778,9 @@ fn do_git_clone(
         GitFetchError::InvalidBranchPattern => {
             unreachable!("we didn't provide any globs")
         }
+        GitFetchError::Cancelled(_) => {
+            unreachable!("we didn't provide a cancellation token")
+        }
     })?;
     print_git_import_stats(ui, &stats.import_stats)?;
     fetch_tx.finish(ui, "fetch from git remote into empty repo")?;
@@ -924,16 +955,18 @@ fn cmd_git_push(
         }
         if branches_targeted.is_empty() {
             if use_default_revset {
-                writeln!(
-                    ui.warning(),
-                    "No branches found in the default push revset, \
-                     `remote_branches(remote={remote})..@`."
-                )?;
+                ui.write_warning(&UiWarning::new(
+                    WarningCode::NoBranchesToPush,
+                    format!(
+                        "No branches found in the default push revset, \
+                         `remote_branches(remote={remote})..@`."
+                    ),
+                ))?;
             } else if !args.revisions.is_empty() {
-                writeln!(
-                    ui.warning(),
-                    "No branches point to the specified revisions."
-                )?;
+                ui.write_warning(&UiWarning::new(
+                    WarningCode::NoBranchesToPush,
+                    "No branches point to the specified revisions.",
+                ))?;
             } else { /* A plain "Nothing changed" message will suffice */
             }
         }
@@ -1103,11 +1136,11 @@ struct RejectedBranchUpdateReason {
 
 impl RejectedBranchUpdateReason {
     fn print(&self, ui: &Ui) -> io::Result<()> {
-        writeln!(ui.warning(), "{}", self.message)?;
+        let mut warning = UiWarning::new(WarningCode::RejectedBranchUpdate, &self.message);
         if let Some(hint) = &self.hint {
-            writeln!(ui.hint(), "Hint: {hint}")?;
+            warning = warning.with_hint(hint);
         }
-        Ok(())
+        ui.write_warning(&warning)
     }
 }
 
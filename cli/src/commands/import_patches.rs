@@ -0,0 +1,204 @@
+// Copyright 2024 The Jujutsu Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+use itertools::Itertools;
+use jj_lib::patch::{self, FileRejects, PatchApplyError};
+use jj_lib::repo::Repo;
+use tracing::instrument;
+
+use crate::cli_util::{
+    user_error, user_error_with_message, CommandError, CommandHelper, RevisionArg,
+};
+use crate::ui::Ui;
+
+/// Apply a directory of patch files as a stack of commits
+///
+/// Each patch becomes a commit on top of the one created from the previous
+/// patch, with the text before the patch's diffs as its description. Patches
+/// from `git format-patch`, Mercurial, and quilt are supported, as are plain
+/// unified diffs. The first component of the paths in the diffs is stripped,
+/// like with `patch -p1`.
+///
+/// A series file lists the patches by name, one per line, like the series
+/// files of quilt and Mercurial queues. Empty lines and lines starting with
+/// `#` are ignored.
+///
+/// A hunk applies where its context and removed lines are found in the file,
+/// even if the lines have moved. If a patch doesn't apply, the import stops
+/// there. The commits created from the patches before it are kept, and the
+/// hunks that didn't apply are written to `.rej` files in the reject
+/// directory.
+#[derive(clap::Args, Clone, Debug)]
+pub(crate) struct ImportPatchesArgs {
+    /// The directory with the patches
+    #[arg(value_hint = clap::ValueHint::DirPath)]
+    dir: PathBuf,
+    /// A file listing the patches in the directory to apply, in order
+    /// (default: all `.patch` and `.diff` files, ordered by name)
+    #[arg(long, value_hint = clap::ValueHint::FilePath)]
+    series: Option<PathBuf>,
+    /// The revision to apply the first patch on top of
+    #[arg(long, short, default_value = "@")]
+    destination: RevisionArg,
+    /// How many lines of context at the start and end of a hunk may be ignored
+    /// when the hunk doesn't apply otherwise
+    #[arg(long, default_value_t = 0)]
+    fuzz: usize,
+    /// The directory to write rejected hunks to (default: `rejects` in the
+    /// patch directory)
+    #[arg(long, value_hint = clap::ValueHint::DirPath)]
+    reject_dir: Option<PathBuf>,
+}
+
+#[instrument(skip_all)]
+pub(crate) fn cmd_import_patches(
+    ui: &mut Ui,
+    command: &CommandHelper,
+    args: &ImportPatchesArgs,
+) -> Result<(), CommandError> {
+    let mut workspace_command = command.workspace_helper(ui)?;
+    let destination = workspace_command.resolve_single_rev(&args.destination, ui)?;
+    let patch_names = match &args.series {
+        Some(series) => read_series(series)?,
+        None => find_patches(&args.dir)?,
+    };
+    if patch_names.is_empty() {
+        return Err(user_error(format!(
+            "No patches found in {}",
+            args.dir.display()
+        )));
+    }
+
+    let mut tx = workspace_command.start_transaction();
+    let store = tx.repo().store().clone();
+    let mut parent = destination;
+    let mut num_applied = 0;
+    let mut failure = None;
+    for name in &patch_names {
+        let path = args.dir.join(name);
+        let text = fs::read(&path).map_err(|err| {
+            user_error_with_message(format!("Failed to read {}", path.display()), err)
+        })?;
+        let patch = match patch::parse_patch(&text) {
+            Ok(patch) => patch,
+            Err(err) => {
+                failure = Some(user_error_with_message(
+                    format!("Failed to parse patch {name}"),
+                    err,
+                ));
+                break;
+            }
+        };
+        let tree_id = match patch::apply_patch(&store, &parent.tree()?, &patch, args.fuzz) {
+            Ok(tree_id) => tree_id,
+            Err(PatchApplyError::Backend(err)) => return Err(err.into()),
+            Err(err) => {
+                if let PatchApplyError::Rejected(rejects) = &err {
+                    let reject_dir = match &args.reject_dir {
+                        Some(reject_dir) => reject_dir.clone(),
+                        None => args.dir.join("rejects"),
+                    };
+                    write_rejects(ui, &reject_dir, rejects)?;
+                }
+                failure = Some(user_error_with_message(
+                    format!("Failed to apply patch {name}"),
+                    err,
+                ));
+                break;
+            }
+        };
+        let commit = tx
+            .mut_repo()
+            .new_commit(command.settings(), vec![parent.id().clone()], tree_id)
+            .set_description(patch.description)
+            .write()?;
+        write!(ui.stderr(), "Applied {name}: ")?;
+        tx.write_commit_summary(ui.stderr_formatter().as_mut(), &commit)?;
+        writeln!(ui.stderr())?;
+        num_applied += 1;
+        parent = commit;
+    }
+    if num_applied > 0 {
+        tx.finish(
+            ui,
+            format!(
+                "import {num_applied} of {} patches from {}",
+                patch_names.len(),
+                args.dir.display()
+            ),
+        )?;
+    }
+    match failure {
+        Some(err) => Err(err),
+        None => Ok(()),
+    }
+}
+
+/// Reads the patch names from a series file.
+fn read_series(series: &Path) -> Result<Vec<String>, CommandError> {
+    let text = fs::read_to_string(series).map_err(|err| {
+        user_error_with_message(format!("Failed to read {}", series.display()), err)
+    })?;
+    // Quilt allows options like `-p0` after the name, which are ignored
+    Ok(text
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .filter_map(|line| line.split_whitespace().next())
+        .map(str::to_owned)
+        .collect())
+}
+
+/// Finds the `.patch` and `.diff` files in the directory, sorted by name.
+fn find_patches(dir: &Path) -> Result<Vec<String>, CommandError> {
+    let read_error = |err: io::Error| {
+        user_error_with_message(format!("Failed to read directory {}", dir.display()), err)
+    };
+    let mut names = vec![];
+    for entry in fs::read_dir(dir).map_err(read_error)? {
+        let entry = entry.map_err(read_error)?;
+        let path = entry.path();
+        let is_patch = path
+            .extension()
+            .is_some_and(|extension| extension == "patch" || extension == "diff");
+        if is_patch && path.is_file() {
+            names.push(entry.file_name().to_string_lossy().into_owned());
+        }
+    }
+    Ok(names.into_iter().sorted().collect())
+}
+
+fn write_rejects(ui: &Ui, reject_dir: &Path, rejects: &[FileRejects]) -> Result<(), CommandError> {
+    for file_rejects in rejects {
+        let mut path = file_rejects.path().to_fs_path(reject_dir).into_os_string();
+        path.push(".rej");
+        let path = PathBuf::from(path);
+        let write_error = |err: io::Error| {
+            user_error_with_message(format!("Failed to write {}", path.display()), err)
+        };
+        fs::create_dir_all(path.parent().unwrap()).map_err(write_error)?;
+        fs::write(&path, file_rejects.to_bytes()).map_err(write_error)?;
+        writeln!(
+            ui.stderr(),
+            "Wrote {} rejected hunks to {}",
+            file_rejects.hunks.len(),
+            path.display()
+        )?;
+    }
+    Ok(())
+}
@@ -13,6 +13,7 @@
 // limitations under the License.
 
 use std::io::Write;
+use std::path::{Path, PathBuf};
 
 use clap::ArgGroup;
 use jj_lib::file_util;
@@ -21,7 +22,7 @@ use tracing::instrument;
 
 use super::git;
 use crate::cli_util::{user_error_with_hint, user_error_with_message, CommandError, CommandHelper};
-use crate::ui::Ui;
+use crate::ui::{Ui, UiWarning, WarningCode};
 
 /// Create a new repo in the given directory
 ///
@@ -41,6 +42,9 @@ pub(crate) struct InitArgs {
     /// Path to a git repo the jj repo will be backed by
     #[arg(long, hide = true, value_hint = clap::ValueHint::DirPath)]
     git_repo: Option<String>,
+    /// Create the repo even if the destination is inside another jj repo
+    #[arg(long)]
+    force_nested: bool,
 }
 
 #[instrument(skip_all)]
@@ -50,20 +54,20 @@ pub(crate) fn cmd_init(
     args: &InitArgs,
 ) -> Result<(), CommandError> {
     let cwd = command.cwd().canonicalize().unwrap();
-    let wc_path = cwd.join(&args.destination);
-    let wc_path = file_util::create_or_reuse_dir(&wc_path)
-        .and_then(|_| wc_path.canonicalize())
-        .map_err(|e| user_error_with_message("Failed to create workspace", e))?;
+    let wc_path = create_workspace_dir(command, &args.destination, args.force_nested)?;
 
     // Preserve existing behaviour where `jj init` is not able to create
     // a colocated repo.
     let colocate = false;
     if args.git || args.git_repo.is_some() {
-        git::git_init(ui, command, &wc_path, colocate, args.git_repo.as_deref())?;
-        writeln!(
-            ui.warning(),
-            "warning: `--git` and `--git-repo` are deprecated.
-Use `jj git init` instead"
+        let git_repo = args.git_repo.as_deref().map(Path::new);
+        git::git_init(ui, command, &wc_path, colocate, git_repo)?;
+        ui.write_warning(
+            &UiWarning::new(
+                WarningCode::DeprecatedOption,
+                "`--git` and `--git-repo` are deprecated.",
+            )
+            .with_hint("Use `jj git init` instead"),
         )?;
     } else {
         if !command.settings().allow_native_backend() {
@@ -84,3 +88,44 @@ Set `ui.allow-init-native` to allow initializing a repo with the native backend.
     )?;
     Ok(())
 }
+
+/// Creates the directory for a new workspace if it doesn't exist, and returns
+/// its canonical path.
+///
+/// Fails if the directory is inside an existing workspace, unless
+/// `force_nested` is set. The check doesn't depend on the current directory,
+/// only on where the new workspace would be.
+pub(crate) fn create_workspace_dir(
+    command: &CommandHelper,
+    destination: &str,
+    force_nested: bool,
+) -> Result<PathBuf, CommandError> {
+    let cwd = command.cwd().canonicalize().unwrap();
+    let wc_path = cwd.join(destination);
+    let wc_path_existed = wc_path.exists();
+    let wc_path = file_util::create_or_reuse_dir(&wc_path)
+        .and_then(|_| wc_path.canonicalize())
+        .map_err(|e| user_error_with_message("Failed to create workspace", e))?;
+    if force_nested {
+        return Ok(wc_path);
+    }
+    let Some(enclosing_root) = wc_path
+        .ancestors()
+        .skip(1)
+        .find(|path| path.join(".jj").is_dir())
+    else {
+        return Ok(wc_path);
+    };
+    let err = user_error_with_hint(
+        format!(
+            r#"Did not create a jj repo because "{}" is inside the existing jj repo in "{}""#,
+            file_util::relative_path(&cwd, &wc_path).display(),
+            file_util::relative_path(&cwd, enclosing_root).display()
+        ),
+        "Pass `--force-nested` to create a nested repo anyway.",
+    );
+    if !wc_path_existed {
+        std::fs::remove_dir(&wc_path).ok();
+    }
+    Err(err)
+}
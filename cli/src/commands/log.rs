@@ -13,7 +13,8 @@
 // limitations under the License.
 
 use itertools::Itertools;
-use jj_lib::backend::CommitId;
+use jj_lib::backend::BackendResult;
+use jj_lib::commit::Commit;
 use jj_lib::repo::Repo;
 use jj_lib::revset::{self, RevsetExpression, RevsetFilterPredicate, RevsetIteratorExt};
 use jj_lib::revset_graph::{
@@ -24,14 +25,19 @@ use tracing::instrument;
 use crate::cli_util::{CommandError, CommandHelper, LogContentFormat, RevisionArg};
 use crate::diff_util::{self, DiffFormatArgs};
 use crate::graphlog::{get_graphlog, Edge};
-use crate::ui::Ui;
+use crate::ui::{Ui, UiWarning, WarningCode};
+
+/// Number of commits loaded ahead of rendering them, so their signatures can be
+/// verified in one batch if the template shows them.
+const COMMIT_BATCH_SIZE: usize = 100;
 
 /// Show commit history
 #[derive(clap::Args, Clone, Debug)]
 pub(crate) struct LogArgs {
     /// Which revisions to show. Defaults to the `revsets.log` setting, or
     /// `@ | ancestors(immutable_heads().., 2) | heads(immutable_heads())` if
-    /// it is not set.
+    /// it is not set. The default is limited to commits in the repo scope (see
+    /// `jj scope`).
     #[arg(long, short)]
     revisions: Vec<RevisionArg>,
     /// Show commits modifying the given paths
@@ -48,6 +54,12 @@ pub(crate) struct LogArgs {
     /// Don't show the graph, show a flat list of revisions
     #[arg(long)]
     no_graph: bool,
+    /// Sort revisions by the given timestamp, newest first
+    ///
+    /// By default, revisions are shown in topological order. Sorting by date
+    /// requires `--no-graph`.
+    #[arg(long, value_enum, requires = "no_graph")]
+    sort_by: Option<LogSortBy>,
     /// Render each revision using the given template
     ///
     /// For the syntax, see https://github.com/martinvonz/jj/blob/main/docs/templates.md
@@ -60,17 +72,36 @@ pub(crate) struct LogArgs {
     diff_format: DiffFormatArgs,
 }
 
+#[derive(clap::ValueEnum, Clone, Copy, Debug, Eq, PartialEq)]
+enum LogSortBy {
+    /// The author timestamp
+    Author,
+    /// The committer timestamp
+    Committer,
+}
+
 #[instrument(skip_all)]
 pub(crate) fn cmd_log(
     ui: &mut Ui,
     command: &CommandHelper,
     args: &LogArgs,
 ) -> Result<(), CommandError> {
-    let workspace_command = command.workspace_helper(ui)?;
+    // Parse the revset first so the snapshot can be skipped if the working copy
+    // isn't involved.
+    let mut workspace_command = command.workspace_helper_no_snapshot(ui)?;
 
     let revset_expression = {
         let mut expression = if args.revisions.is_empty() {
-            workspace_command.parse_revset(&command.settings().default_revset(), Some(ui))?
+            let expression =
+                workspace_command.parse_revset(&command.settings().default_revset(), Some(ui))?;
+            // Only show commits in the repo scope, but keep the working copy so
+            // the graph isn't empty after checking out something outside it.
+            match workspace_command.repo_scope()?.to_revset_expression() {
+                Some(scope_expression) => expression.intersection(&scope_expression.union(
+                    &RevsetExpression::working_copy(workspace_command.workspace_id().clone()),
+                )),
+                None => expression,
+            }
         } else {
             let expressions: Vec<_> = args
                 .revisions
@@ -91,6 +122,7 @@ pub(crate) fn cmd_log(
         }
         revset::optimize(expression)
     };
+    workspace_command.maybe_snapshot_for_revset(ui, &revset_expression)?;
     let repo = workspace_command.repo();
     let wc_commit_id = workspace_command.get_wc_commit_id();
     let matcher = workspace_command.matcher_from_values(&args.paths)?;
@@ -121,83 +153,115 @@ pub(crate) fn cmd_log(
             } else {
                 Box::new(forward_iter)
             };
-            for (commit_id, edges) in iter.take(args.limit.unwrap_or(usize::MAX)) {
-                let mut graphlog_edges = vec![];
-                // TODO: Should we update RevsetGraphIterator to yield this flag instead of all
-                // the missing edges since we don't care about where they point here
-                // anyway?
-                let mut has_missing = false;
-                for edge in edges {
-                    match edge.edge_type {
-                        RevsetGraphEdgeType::Missing => {
-                            has_missing = true;
+            let chunks = iter
+                .take(args.limit.unwrap_or(usize::MAX))
+                .chunks(COMMIT_BATCH_SIZE);
+            for chunk in &chunks {
+                let nodes = chunk.collect_vec();
+                let commits: Vec<_> = nodes
+                    .iter()
+                    .map(|(commit_id, _)| store.get_commit(commit_id))
+                    .try_collect()?;
+                workspace_command.signature_batch().extend(&commits);
+                for ((commit_id, edges), commit) in nodes.into_iter().zip(commits) {
+                    let mut graphlog_edges = vec![];
+                    // TODO: Should we update RevsetGraphIterator to yield this flag instead of all
+                    // the missing edges since we don't care about where they point here
+                    // anyway?
+                    let mut has_missing = false;
+                    for edge in edges {
+                        match edge.edge_type {
+                            RevsetGraphEdgeType::Missing => {
+                                has_missing = true;
+                            }
+                            RevsetGraphEdgeType::Direct => graphlog_edges.push(Edge::Present {
+                                direct: true,
+                                target: edge.target,
+                            }),
+                            RevsetGraphEdgeType::Indirect => graphlog_edges.push(Edge::Present {
+                                direct: false,
+                                target: edge.target,
+                            }),
                         }
-                        RevsetGraphEdgeType::Direct => graphlog_edges.push(Edge::Present {
-                            direct: true,
-                            target: edge.target,
-                        }),
-                        RevsetGraphEdgeType::Indirect => graphlog_edges.push(Edge::Present {
-                            direct: false,
-                            target: edge.target,
-                        }),
                     }
-                }
-                if has_missing {
-                    graphlog_edges.push(Edge::Missing);
-                }
-                let mut buffer = vec![];
-                let commit = store.get_commit(&commit_id)?;
-                with_content_format.write_graph_text(
-                    ui.new_formatter(&mut buffer).as_mut(),
-                    |formatter| template.format(&commit, formatter),
-                    || graph.width(&commit_id, &graphlog_edges),
-                )?;
-                if !buffer.ends_with(b"\n") {
-                    buffer.push(b'\n');
-                }
-                if !diff_formats.is_empty() {
-                    let mut formatter = ui.new_formatter(&mut buffer);
-                    diff_util::show_patch(
-                        ui,
-                        formatter.as_mut(),
-                        &workspace_command,
-                        &commit,
-                        matcher.as_ref(),
-                        &diff_formats,
+                    if has_missing {
+                        graphlog_edges.push(Edge::Missing);
+                    }
+                    let mut buffer = vec![];
+                    with_content_format.write_graph_text(
+                        ui.new_formatter(&mut buffer).as_mut(),
+                        |formatter| template.format(&commit, formatter),
+                        || graph.width(&commit_id, &graphlog_edges),
                     )?;
-                }
-                let node_symbol = if Some(&commit_id) == wc_commit_id {
-                    "@"
-                } else {
-                    &default_node_symbol
-                };
+                    if !buffer.ends_with(b"\n") {
+                        buffer.push(b'\n');
+                    }
+                    if !diff_formats.is_empty() {
+                        let mut formatter = ui.new_formatter(&mut buffer);
+                        diff_util::show_patch(
+                            ui,
+                            formatter.as_mut(),
+                            &workspace_command,
+                            &commit,
+                            matcher.as_ref(),
+                            &diff_formats,
+                        )?;
+                    }
+                    let node_symbol = if Some(&commit_id) == wc_commit_id {
+                        "@"
+                    } else {
+                        &default_node_symbol
+                    };
 
-                graph.add_node(
-                    &commit_id,
-                    &graphlog_edges,
-                    node_symbol,
-                    &String::from_utf8_lossy(&buffer),
-                )?;
+                    graph.add_node(
+                        &commit_id,
+                        &graphlog_edges,
+                        node_symbol,
+                        &String::from_utf8_lossy(&buffer),
+                    )?;
+                }
             }
         } else {
-            let iter: Box<dyn Iterator<Item = CommitId>> = if args.reversed {
-                Box::new(revset.iter().reversed())
-            } else {
-                Box::new(revset.iter())
-            };
-            for commit_or_error in iter.commits(store).take(args.limit.unwrap_or(usize::MAX)) {
-                let commit = commit_or_error?;
-                with_content_format
-                    .write(formatter, |formatter| template.format(&commit, formatter))?;
-                if !diff_formats.is_empty() {
-                    diff_util::show_patch(
-                        ui,
-                        formatter,
-                        &workspace_command,
-                        &commit,
-                        matcher.as_ref(),
-                        &diff_formats,
-                    )?;
+            let iter: Box<dyn Iterator<Item = BackendResult<Commit>>> =
+                if let Some(sort_by) = args.sort_by {
+                    // Sorting needs all the commits. The sort is stable, so commits
+                    // with the same timestamp stay in topological order.
+                    let mut commits: Vec<Commit> = revset.iter().commits(store).try_collect()?;
+                    commits.sort_by(|a, b| {
+                        let timestamp = |commit: &Commit| match sort_by {
+                            LogSortBy::Author => commit.author().timestamp.timestamp,
+                            LogSortBy::Committer => commit.committer().timestamp.timestamp,
+                        };
+                        timestamp(b).cmp(&timestamp(a))
+                    });
+                    if args.reversed {
+                        commits.reverse();
+                    }
+                    Box::new(commits.into_iter().map(Ok))
+                } else if args.reversed {
+                    Box::new(revset.iter().reversed().commits(store))
+                } else {
+                    Box::new(revset.iter().commits(store))
+                };
+            let chunks = iter
+                .take(args.limit.unwrap_or(usize::MAX))
+                .chunks(COMMIT_BATCH_SIZE);
+            for chunk in &chunks {
+                let commits: Vec<_> = chunk.try_collect()?;
+                workspace_command.signature_batch().extend(&commits);
+                for commit in &commits {
+                    with_content_format
+                        .write(formatter, |formatter| template.format(commit, formatter))?;
+                    if !diff_formats.is_empty() {
+                        diff_util::show_patch(
+                            ui,
+                            formatter,
+                            &workspace_command,
+                            commit,
+                            matcher.as_ref(),
+                            &diff_formats,
+                        )?;
+                    }
                 }
             }
         }
@@ -208,20 +272,24 @@ pub(crate) fn cmd_log(
     if let ([], [only_path]) = (args.revisions.as_slice(), args.paths.as_slice()) {
         if only_path == "." && workspace_command.parse_file_path(only_path)?.is_root() {
             // For users of e.g. Mercurial, where `.` indicates the current commit.
-            writeln!(
-                ui.warning(),
-                "warning: The argument {only_path:?} is being interpreted as a path, but this is \
-                 often not useful because all non-empty commits touch '.'.  If you meant to show \
-                 the working copy commit, pass -r '@' instead."
-            )?;
+            ui.write_warning(&UiWarning::new(
+                WarningCode::AmbiguousPathArgument,
+                format!(
+                    "The argument {only_path:?} is being interpreted as a path, but this is \
+                     often not useful because all non-empty commits touch '.'.  If you meant to \
+                     show the working copy commit, pass -r '@' instead."
+                ),
+            ))?;
         } else if revset.is_empty()
             && revset::parse(only_path, &workspace_command.revset_parse_context()).is_ok()
         {
-            writeln!(
-                ui.warning(),
-                "warning: The argument {only_path:?} is being interpreted as a path. To specify a \
-                 revset, pass -r {only_path:?} instead."
-            )?;
+            ui.write_warning(&UiWarning::new(
+                WarningCode::AmbiguousPathArgument,
+                format!(
+                    "The argument {only_path:?} is being interpreted as a path. To specify a \
+                     revset, pass -r {only_path:?} instead."
+                ),
+            ))?;
         }
     }
 
@@ -16,7 +16,7 @@ use tracing::instrument;
 
 use super::new;
 use crate::cli_util::{CommandError, CommandHelper};
-use crate::ui::Ui;
+use crate::ui::{Ui, UiWarning, WarningCode};
 
 #[instrument(skip_all)]
 pub(crate) fn cmd_merge(
@@ -24,14 +24,12 @@ pub(crate) fn cmd_merge(
     command: &CommandHelper,
     args: &new::NewArgs,
 ) -> Result<(), CommandError> {
-    writeln!(
-        ui.warning(),
-        "warning: `jj merge` is deprecated; use `jj new` instead, which is equivalent"
-    )?;
-    writeln!(
-        ui.warning(),
-        "warning: `jj merge` will be removed in a future version, and this will be a hard error"
-    )?;
+    let warning = UiWarning::new(
+        WarningCode::DeprecatedCommand,
+        "`jj merge` is deprecated, and will be removed in a future version",
+    )
+    .with_hint("Use `jj new` instead, which is equivalent");
+    ui.write_warning(&warning)?;
     if args.revisions.len() < 2 {
         return Err(CommandError::CliError(String::from(
             "Merge requires at least two revisions",
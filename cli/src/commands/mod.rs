@@ -13,15 +13,18 @@
 // limitations under the License.
 
 mod abandon;
+mod absorb;
 mod backout;
 #[cfg(feature = "bench")]
 mod bench;
 mod branch;
+mod bundle;
 mod cat;
 mod checkout;
 mod chmod;
 mod commit;
 mod config;
+mod cp;
 mod debug;
 mod describe;
 mod diff;
@@ -30,21 +33,26 @@ mod duplicate;
 mod edit;
 mod files;
 mod git;
+mod import_patches;
 mod init;
 mod interdiff;
 mod log;
 mod merge;
 mod r#move;
+mod mv;
 mod new;
 mod next;
 mod obslog;
 mod operation;
 mod prev;
+mod range_diff;
+mod reauthor;
 mod rebase;
 mod resolve;
 mod restore;
 mod root;
 mod run;
+mod scope;
 mod show;
 mod sparse;
 mod split;
@@ -68,12 +76,15 @@ use crate::ui::Ui;
 #[derive(clap::Parser, Clone, Debug)]
 enum Command {
     Abandon(abandon::AbandonArgs),
+    Absorb(absorb::AbsorbArgs),
     Backout(backout::BackoutArgs),
     #[cfg(feature = "bench")]
     #[command(subcommand)]
     Bench(bench::BenchCommand),
     #[command(subcommand)]
     Branch(branch::BranchCommand),
+    #[command(subcommand)]
+    Bundle(bundle::BundleCommand),
     #[command(alias = "print")]
     Cat(cat::CatArgs),
     #[command(hide = true)]
@@ -82,6 +93,7 @@ enum Command {
     Commit(commit::CommitArgs),
     #[command(subcommand)]
     Config(config::ConfigCommand),
+    Cp(cp::CpArgs),
     #[command(subcommand)]
     Debug(debug::DebugCommand),
     Describe(describe::DescribeArgs),
@@ -92,6 +104,7 @@ enum Command {
     Files(files::FilesArgs),
     #[command(subcommand)]
     Git(git::GitCommand),
+    ImportPatches(import_patches::ImportPatchesArgs),
     Init(init::InitArgs),
     Interdiff(interdiff::InterdiffArgs),
     Log(log::LogArgs),
@@ -107,6 +120,7 @@ enum Command {
     #[command(hide = true)]
     Merge(new::NewArgs),
     Move(r#move::MoveArgs),
+    Mv(mv::MvArgs),
     New(new::NewArgs),
     Next(next::NextArgs),
     Obslog(obslog::ObslogArgs),
@@ -114,6 +128,8 @@ enum Command {
     #[command(visible_alias = "op")]
     Operation(operation::OperationCommand),
     Prev(prev::PrevArgs),
+    RangeDiff(range_diff::RangeDiffArgs),
+    Reauthor(reauthor::ReauthorArgs),
     Rebase(rebase::RebaseArgs),
     Resolve(resolve::ResolveArgs),
     Restore(restore::RestoreArgs),
@@ -126,6 +142,8 @@ enum Command {
     #[command(hide = true)]
     // TODO: Flesh out.
     Run(run::RunArgs),
+    #[command(subcommand)]
+    Scope(scope::ScopeCommand),
     Show(show::ShowArgs),
     #[command(subcommand)]
     Sparse(sparse::SparseArgs),
@@ -172,16 +190,20 @@ pub fn run_command(ui: &mut Ui, command_helper: &CommandHelper) -> Result<(), Co
         Command::Status(sub_args) => status::cmd_status(ui, command_helper, sub_args),
         Command::Log(sub_args) => log::cmd_log(ui, command_helper, sub_args),
         Command::Interdiff(sub_args) => interdiff::cmd_interdiff(ui, command_helper, sub_args),
+        Command::RangeDiff(sub_args) => range_diff::cmd_range_diff(ui, command_helper, sub_args),
         Command::Obslog(sub_args) => obslog::cmd_obslog(ui, command_helper, sub_args),
         Command::Describe(sub_args) => describe::cmd_describe(ui, command_helper, sub_args),
         Command::Commit(sub_args) => commit::cmd_commit(ui, command_helper, sub_args),
         Command::Duplicate(sub_args) => duplicate::cmd_duplicate(ui, command_helper, sub_args),
         Command::Abandon(sub_args) => abandon::cmd_abandon(ui, command_helper, sub_args),
+        Command::Absorb(sub_args) => absorb::cmd_absorb(ui, command_helper, sub_args),
         Command::Edit(sub_args) => edit::cmd_edit(ui, command_helper, sub_args),
         Command::Next(sub_args) => next::cmd_next(ui, command_helper, sub_args),
         Command::Prev(sub_args) => prev::cmd_prev(ui, command_helper, sub_args),
         Command::New(sub_args) => new::cmd_new(ui, command_helper, sub_args),
         Command::Move(sub_args) => r#move::cmd_move(ui, command_helper, sub_args),
+        Command::Cp(sub_args) => cp::cmd_cp(ui, command_helper, sub_args),
+        Command::Mv(sub_args) => mv::cmd_mv(ui, command_helper, sub_args),
         Command::Squash(sub_args) => squash::cmd_squash(ui, command_helper, sub_args),
         Command::Unsquash(sub_args) => unsquash::cmd_unsquash(ui, command_helper, sub_args),
         Command::Restore(sub_args) => restore::cmd_restore(ui, command_helper, sub_args),
@@ -191,14 +213,20 @@ pub fn run_command(ui: &mut Ui, command_helper: &CommandHelper) -> Result<(), Co
         Command::Diffedit(sub_args) => diffedit::cmd_diffedit(ui, command_helper, sub_args),
         Command::Split(sub_args) => split::cmd_split(ui, command_helper, sub_args),
         Command::Merge(sub_args) => merge::cmd_merge(ui, command_helper, sub_args),
+        Command::Reauthor(sub_args) => reauthor::cmd_reauthor(ui, command_helper, sub_args),
         Command::Rebase(sub_args) => rebase::cmd_rebase(ui, command_helper, sub_args),
         Command::Backout(sub_args) => backout::cmd_backout(ui, command_helper, sub_args),
         Command::Resolve(sub_args) => resolve::cmd_resolve(ui, command_helper, sub_args),
         Command::Branch(sub_args) => branch::cmd_branch(ui, command_helper, sub_args),
+        Command::Bundle(sub_args) => bundle::cmd_bundle(ui, command_helper, sub_args),
+        Command::ImportPatches(sub_args) => {
+            import_patches::cmd_import_patches(ui, command_helper, sub_args)
+        }
         Command::Undo(sub_args) => operation::cmd_op_undo(ui, command_helper, sub_args),
         Command::Operation(sub_args) => operation::cmd_operation(ui, command_helper, sub_args),
         Command::Workspace(sub_args) => workspace::cmd_workspace(ui, command_helper, sub_args),
         Command::Sparse(sub_args) => sparse::cmd_sparse(ui, command_helper, sub_args),
+        Command::Scope(sub_args) => scope::cmd_scope(ui, command_helper, sub_args),
         Command::Tag(sub_args) => tag::cmd_tag(ui, command_helper, sub_args),
         Command::Chmod(sub_args) => chmod::cmd_chmod(ui, command_helper, sub_args),
         Command::Git(sub_args) => git::cmd_git(ui, command_helper, sub_args),
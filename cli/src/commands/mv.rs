@@ -0,0 +1,42 @@
+// Copyright 2024 The Jujutsu Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use tracing::instrument;
+
+use crate::cli_util::{CommandError, CommandHelper};
+use crate::commands::cp::copy_file;
+use crate::ui::Ui;
+
+/// Rename a file in the working copy and record where it was moved from
+///
+/// Diffs of the working-copy commit then show the file as renamed instead of
+/// as removed and added. To move changes between revisions, use `jj move`.
+#[derive(clap::Args, Clone, Debug)]
+pub(crate) struct MvArgs {
+    /// The file to rename
+    #[arg(value_hint = clap::ValueHint::FilePath)]
+    source: String,
+    /// The new path of the file, which must not exist yet
+    #[arg(value_hint = clap::ValueHint::FilePath)]
+    destination: String,
+}
+
+#[instrument(skip_all)]
+pub(crate) fn cmd_mv(
+    ui: &mut Ui,
+    command: &CommandHelper,
+    args: &MvArgs,
+) -> Result<(), CommandError> {
+    copy_file(ui, command, &args.source, &args.destination, true)
+}
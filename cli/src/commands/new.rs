@@ -102,6 +102,22 @@ Please use `jj new 'all:x|y'` instead of `jj new --allow-large-revsets x y`.",
         .into_iter()
         .collect_vec();
     let target_ids = target_commits.iter().map(|c| c.id().clone()).collect_vec();
+    // Starting a new change on top of the working-copy commit is like committing
+    // it, so branches on its parent may follow it, depending on the policy.
+    let advanceable_branches = match &*target_commits {
+        [target]
+            if !args.insert_before
+                && !args.insert_after
+                && workspace_command.get_wc_commit_id() == Some(target.id())
+                && workspace_command.advances_branches_on_new(target)? =>
+        {
+            match target.parent_ids() {
+                [parent_id] => workspace_command.get_advanceable_branches(parent_id)?,
+                _ => vec![],
+            }
+        }
+        _ => vec![],
+    };
     let mut tx = workspace_command.start_transaction();
     let mut num_rebased;
     let new_commit;
@@ -195,6 +211,9 @@ Please use `jj new 'all:x|y'` instead of `jj new --allow-large-revsets x y`.",
         }
     }
     num_rebased += tx.mut_repo().rebase_descendants(command.settings())?;
+    if !advanceable_branches.is_empty() {
+        tx.advance_branches(ui, advanceable_branches, &target_ids[0])?;
+    }
     if args.no_edit {
         write!(ui.stderr(), "Created new commit ")?;
         tx.write_commit_summary(ui.stderr_formatter().as_mut(), &new_commit)?;
@@ -20,7 +20,8 @@ use jj_lib::repo::Repo;
 use jj_lib::revset::{RevsetExpression, RevsetIteratorExt};
 
 use crate::cli_util::{
-    short_commit_hash, user_error, CommandError, CommandHelper, WorkspaceCommandHelper,
+    short_commit_hash, user_error, user_error_with_hint, CommandError, CommandHelper,
+    WorkspaceCommandHelper,
 };
 use crate::ui::Ui;
 
@@ -61,9 +62,41 @@ pub(crate) struct NextArgs {
     /// edit`).
     #[arg(long)]
     edit: bool,
+    /// Prompt for the commit to move to if there are several candidates,
+    /// instead of failing
+    #[arg(long)]
+    prompt: bool,
+}
+
+/// Picks the target among several candidates, by prompting if `prompt` is set.
+/// Otherwise fails with an error listing the candidates.
+pub fn resolve_ambiguous_target<'a>(
+    ui: &mut Ui,
+    workspace_command: &WorkspaceCommandHelper,
+    cmd: &str,
+    commits: &'a [Commit],
+    edit: bool,
+    prompt: bool,
+) -> Result<&'a Commit, CommandError> {
+    if prompt {
+        return choose_commit(ui, workspace_command, cmd, commits);
+    }
+    let mut message = format!("The {cmd} commit is ambiguous, it could be any of:");
+    for commit in commits {
+        message.push_str("\n  ");
+        message.push_str(&workspace_command.format_commit_summary(commit));
+    }
+    Err(user_error_with_hint(
+        message,
+        format!(
+            "Use `jj {cmd} --prompt` to choose one, or `jj {}` with the change id of one of \
+             them.",
+            if edit { "edit" } else { "new" }
+        ),
+    ))
 }
 
-pub fn choose_commit<'a>(
+fn choose_commit<'a>(
     ui: &mut Ui,
     workspace_command: &WorkspaceCommandHelper,
     cmd: &str,
@@ -127,7 +160,6 @@ pub(crate) fn cmd_next(
         .evaluate_programmatic(workspace_command.repo().as_ref())?
         .iter()
         .commits(workspace_command.repo().store())
-        .take(2)
         .try_collect()?;
     let target = match targets.as_slice() {
         [target] => target,
@@ -138,7 +170,9 @@ pub(crate) fn cmd_next(
                 if amount > 1 { "s" } else { "" }
             )));
         }
-        commits => choose_commit(ui, &workspace_command, "next", commits)?,
+        commits => {
+            resolve_ambiguous_target(ui, &workspace_command, "next", commits, edit, args.prompt)?
+        }
     };
     let target_short = short_commit_hash(target.id());
     // We're editing, just move to the target commit.
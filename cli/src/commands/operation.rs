@@ -12,25 +12,33 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+use std::collections::HashMap;
 use std::io::Write as _;
 use std::slice;
 
 use clap::Subcommand;
 use itertools::Itertools as _;
-use jj_lib::object_id::ObjectId;
-use jj_lib::op_store::OperationId;
-use jj_lib::op_walk;
+use jj_lib::backend::CommitId;
+use jj_lib::hex_util::to_forward_hex;
+use jj_lib::matchers::EverythingMatcher;
+use jj_lib::object_id::{HexPrefix, ObjectId, PrefixResolution};
+use jj_lib::op_store::{OpStoreResult, OperationId, RefTarget};
+use jj_lib::op_walk::{self, OperationTagFilter};
 use jj_lib::operation::Operation;
-use jj_lib::repo::Repo;
+use jj_lib::repo::{ReadonlyRepo, Repo};
+use jj_lib::view::ViewDiff;
 
 use crate::cli_util::{
     short_operation_hash, user_error, user_error_with_hint, CommandError, CommandHelper,
-    LogContentFormat,
+    LogContentFormat, WorkspaceCommandHelper,
 };
+use crate::diff_util::{self, DiffFormat, DiffFormatArgs};
+use crate::formatter::Formatter;
 use crate::graphlog::{get_graphlog, Edge};
 use crate::operation_templater;
 use crate::templater::Template as _;
-use crate::ui::Ui;
+use crate::time_util::format_absolute_timestamp;
+use crate::ui::{Ui, UiWarning, WarningCode};
 
 /// Commands for working with the operation log
 ///
@@ -39,9 +47,11 @@ use crate::ui::Ui;
 #[derive(Subcommand, Clone, Debug)]
 pub enum OperationCommand {
     Abandon(OperationAbandonArgs),
+    Diff(OperationDiffArgs),
     Log(OperationLogArgs),
     Undo(OperationUndoArgs),
     Restore(OperationRestoreArgs),
+    RestoreCommit(OperationRestoreCommitArgs),
 }
 
 /// Show the operation log
@@ -58,6 +68,40 @@ pub struct OperationLogArgs {
     /// For the syntax, see https://github.com/martinvonz/jj/blob/main/docs/templates.md
     #[arg(long, short = 'T')]
     template: Option<String>,
+    /// Only show operations with the given tag, e.g. `--tag command=describe`
+    ///
+    /// Can be repeated to only show operations with all the given tags.
+    /// Operations are connected to their closest shown ancestors in the graph.
+    #[arg(long, value_name = "KEY=VALUE", value_parser = parse_tag_arg)]
+    tag: Vec<(String, String)>,
+}
+
+fn parse_tag_arg(arg: &str) -> Result<(String, String), String> {
+    match arg.split_once('=') {
+        Some((key, value)) if !key.is_empty() => Ok((key.to_owned(), value.to_owned())),
+        _ => Err(format!("Expected KEY=VALUE, got {arg:?}")),
+    }
+}
+
+/// Compare the repo state between two operations
+///
+/// Shows the changes to working-copy commits, head commits, branches, tags,
+/// and Git refs. By default, shows the changes made by the current operation.
+#[derive(clap::Args, Clone, Debug)]
+pub struct OperationDiffArgs {
+    /// Show changes from this operation
+    ///
+    /// Defaults to the parent of the `--to` operation.
+    #[arg(long)]
+    from: Option<String>,
+    /// Show changes to this operation
+    #[arg(long, default_value = "@")]
+    to: String,
+    /// Show patch of the changes to working-copy commits
+    #[arg(long, short = 'p')]
+    patch: bool,
+    #[command(flatten)]
+    diff_format: DiffFormatArgs,
 }
 
 /// Create a new operation that restores the repo to an earlier state
@@ -80,6 +124,26 @@ pub struct OperationRestoreArgs {
     what: Vec<UndoWhatToRestore>,
 }
 
+/// Make an abandoned or otherwise hidden commit visible again
+///
+/// Searches the operation log, newest operation first, for the last operation
+/// in which the commit, or a commit with the given change ID, was visible. That
+/// commit is made visible again in a new operation. Unlike `jj op restore`,
+/// this leaves everything else, including branches and working-copy commits,
+/// as it is.
+///
+/// If several commits with the change ID were visible in that operation, they
+/// are listed, and the commit ID of one of them has to be passed instead.
+#[derive(clap::Args, Clone, Debug)]
+pub struct OperationRestoreCommitArgs {
+    /// Change ID or commit ID of the commit to restore
+    #[arg(value_name = "CHANGE_ID_OR_COMMIT_ID")]
+    id: String,
+    /// Maximum number of operations to search
+    #[arg(long, default_value_t = 1000)]
+    limit: usize,
+}
+
 /// Create a new operation that undoes an earlier operation
 ///
 /// This undoes an individual operation by applying the inverse of the
@@ -176,16 +240,36 @@ fn cmd_op_log(
     ui.request_pager();
     let mut formatter = ui.stdout_formatter();
     let formatter = formatter.as_mut();
-    let iter = op_walk::walk_ancestors(&head_ops).take(args.limit.unwrap_or(usize::MAX));
+    let tag_filter = args
+        .tag
+        .iter()
+        .fold(OperationTagFilter::new(), |filter, (key, value)| {
+            filter.with_tag(key, value)
+        });
+    let ops_with_edges: Box<dyn Iterator<Item = OpStoreResult<OperationWithEdges>> + '_> =
+        if tag_filter.is_empty() {
+            Box::new(op_walk::walk_ancestors(&head_ops).map_ok(|op| {
+                let edges = op
+                    .parent_ids()
+                    .iter()
+                    .cloned()
+                    .map(Edge::direct)
+                    .collect_vec();
+                (op, edges)
+            }))
+        } else {
+            Box::new(
+                filter_ops_by_tags(&head_ops, &tag_filter)?
+                    .into_iter()
+                    .map(Ok),
+            )
+        };
+    let iter = ops_with_edges.take(args.limit.unwrap_or(usize::MAX));
     if !args.no_graph {
         let mut graph = get_graphlog(command.settings(), formatter.raw());
         let default_node_symbol = graph.default_node_symbol().to_owned();
-        for op in iter {
-            let op = op?;
-            let mut edges = vec![];
-            for id in op.parent_ids() {
-                edges.push(Edge::direct(id.clone()));
-            }
+        for op_and_edges in iter {
+            let (op, edges) = op_and_edges?;
             let is_current_op = Some(op.id()) == current_op_id;
             let mut buffer = vec![];
             with_content_format.write_graph_text(
@@ -211,8 +295,8 @@ fn cmd_op_log(
             )?;
         }
     } else {
-        for op in iter {
-            let op = op?;
+        for op_and_edges in iter {
+            let (op, _edges) = op_and_edges?;
             with_content_format.write(formatter, |formatter| {
                 formatter.with_label("op_log", |formatter| template.format(&op, formatter))
             })?;
@@ -222,6 +306,258 @@ fn cmd_op_log(
     Ok(())
 }
 
+type OperationWithEdges = (Operation, Vec<Edge<OperationId>>);
+
+/// Walks the ancestors of `head_ops`, keeping the operations matching `filter`.
+/// Each kept operation has direct edges to its kept parents and indirect edges
+/// to the closest kept ancestors of its other parents.
+fn filter_ops_by_tags(
+    head_ops: &[Operation],
+    filter: &OperationTagFilter,
+) -> OpStoreResult<Vec<OperationWithEdges>> {
+    let all_ops: Vec<Operation> = op_walk::walk_ancestors(head_ops).try_collect()?;
+    // The closest kept operations at or below each visited operation
+    let mut closest_kept: HashMap<OperationId, Vec<OperationId>> = HashMap::new();
+    let mut kept = vec![];
+    // Visit parents before their children
+    for op in all_ops.into_iter().rev() {
+        let mut edges = vec![];
+        for parent_id in op.parent_ids() {
+            match closest_kept.get(parent_id).map(Vec::as_slice) {
+                Some([id]) if id == parent_id => edges.push(Edge::direct(id.clone())),
+                Some(ids) => edges.extend(ids.iter().cloned().map(Edge::indirect)),
+                None => {}
+            }
+        }
+        let edges = edges
+            .into_iter()
+            .unique_by(|edge| match edge {
+                Edge::Present { target, .. } => Some(target.clone()),
+                Edge::Missing => None,
+            })
+            .collect_vec();
+        if filter.matches(&op) {
+            closest_kept.insert(op.id().clone(), vec![op.id().clone()]);
+            kept.push((op, edges));
+        } else {
+            let ids = edges
+                .into_iter()
+                .filter_map(|edge| match edge {
+                    Edge::Present { target, .. } => Some(target),
+                    Edge::Missing => None,
+                })
+                .collect();
+            closest_kept.insert(op.id().clone(), ids);
+        }
+    }
+    kept.reverse();
+    Ok(kept)
+}
+
+fn cmd_op_diff(
+    ui: &mut Ui,
+    command: &CommandHelper,
+    args: &OperationDiffArgs,
+) -> Result<(), CommandError> {
+    let workspace_command = command.workspace_helper(ui)?;
+    let to_op = workspace_command.resolve_single_op(&args.to)?;
+    let from_op = if let Some(from_str) = &args.from {
+        workspace_command.resolve_single_op(from_str)?
+    } else {
+        let mut parent_ops = to_op.parents();
+        let Some(parent_op) = parent_ops.next().transpose()? else {
+            return Err(user_error("Cannot diff the root operation"));
+        };
+        if parent_ops.next().is_some() {
+            return Err(user_error_with_hint(
+                "Cannot diff a merge operation",
+                "Use --from to specify the operation to compare with",
+            ));
+        }
+        parent_op
+    };
+    let from_view = from_op.view()?;
+    let to_view = to_op.view()?;
+    let diff = from_view.diff(&to_view);
+    let diff_formats =
+        diff_util::diff_formats_for_log(command.settings(), &args.diff_format, args.patch)?;
+
+    ui.request_pager();
+    let mut formatter = ui.stdout_formatter();
+    let formatter = formatter.as_mut();
+    formatter.push_label("op_diff")?;
+    show_op_diff(
+        ui,
+        formatter,
+        &workspace_command,
+        &from_op,
+        &to_op,
+        &diff,
+        &diff_formats,
+    )?;
+    formatter.pop_label()?;
+    Ok(())
+}
+
+fn show_op_diff(
+    ui: &Ui,
+    formatter: &mut dyn Formatter,
+    workspace_command: &WorkspaceCommandHelper,
+    from_op: &Operation,
+    to_op: &Operation,
+    diff: &ViewDiff,
+    diff_formats: &[DiffFormat],
+) -> Result<(), CommandError> {
+    write_op_summary(formatter, "From", from_op)?;
+    write_op_summary(formatter, "  To", to_op)?;
+    if diff.is_empty() {
+        writeln!(formatter)?;
+        writeln!(formatter, "No changes.")?;
+        return Ok(());
+    }
+
+    if !diff.wc_commits.is_empty() {
+        writeln!(formatter)?;
+        writeln!(formatter, "Changed working-copy commits:")?;
+        for (workspace_id, (old_id, new_id)) in &diff.wc_commits {
+            writeln!(formatter, "{}@:", workspace_id.as_str())?;
+            write_commit_changes(formatter, workspace_command, *old_id, *new_id)?;
+            if let (Some(old_id), Some(new_id), false) = (old_id, new_id, diff_formats.is_empty()) {
+                let store = workspace_command.repo().store();
+                diff_util::show_diff(
+                    ui,
+                    formatter,
+                    workspace_command,
+                    &store.get_commit(old_id)?.tree()?,
+                    &store.get_commit(new_id)?.tree()?,
+                    &EverythingMatcher,
+                    diff_formats,
+                )?;
+            }
+        }
+    }
+
+    if !diff.removed_heads.is_empty() || !diff.added_heads.is_empty() {
+        writeln!(formatter)?;
+        writeln!(formatter, "Changed head commits:")?;
+        write_commit_changes(
+            formatter,
+            workspace_command,
+            diff.removed_heads.iter().copied(),
+            diff.added_heads.iter().copied(),
+        )?;
+    }
+
+    write_ref_changes(
+        formatter,
+        workspace_command,
+        "Changed local branches:",
+        &diff.local_branches,
+    )?;
+    if !diff.remote_branches.is_empty() {
+        writeln!(formatter)?;
+        writeln!(formatter, "Changed remote branches:")?;
+        for ((name, remote_name), (old_ref, new_ref)) in &diff.remote_branches {
+            write!(formatter, "{name}@{remote_name}:")?;
+            if old_ref.is_tracking() != new_ref.is_tracking() && new_ref.is_present() {
+                if new_ref.is_tracking() {
+                    write!(formatter, " (tracked)")?;
+                } else {
+                    write!(formatter, " (untracked)")?;
+                }
+            }
+            writeln!(formatter)?;
+            write_ref_target_changes(
+                formatter,
+                workspace_command,
+                &old_ref.target,
+                &new_ref.target,
+            )?;
+        }
+    }
+    write_ref_changes(formatter, workspace_command, "Changed tags:", &diff.tags)?;
+    write_ref_changes(
+        formatter,
+        workspace_command,
+        "Changed Git refs:",
+        &diff.git_refs,
+    )?;
+    if let Some((old_target, new_target)) = diff.git_head {
+        writeln!(formatter)?;
+        writeln!(formatter, "Changed Git HEAD:")?;
+        write_ref_target_changes(formatter, workspace_command, old_target, new_target)?;
+    }
+    Ok(())
+}
+
+fn write_op_summary(
+    formatter: &mut dyn Formatter,
+    prefix: &str,
+    op: &Operation,
+) -> Result<(), CommandError> {
+    write!(formatter, "{prefix} operation ")?;
+    formatter.with_label("id", |formatter| {
+        write!(formatter, "{}", short_operation_hash(op.id()))
+    })?;
+    writeln!(formatter, ": {}", op.store_operation().metadata.description)?;
+    Ok(())
+}
+
+/// Writes the removed commits prefixed by "-" and the added commits prefixed
+/// by "+".
+fn write_commit_changes<'a>(
+    formatter: &mut dyn Formatter,
+    workspace_command: &WorkspaceCommandHelper,
+    removed_ids: impl IntoIterator<Item = &'a CommitId>,
+    added_ids: impl IntoIterator<Item = &'a CommitId>,
+) -> Result<(), CommandError> {
+    let store = workspace_command.repo().store();
+    for (label, sigil, ids) in [
+        ("removed", "-", removed_ids.into_iter().collect_vec()),
+        ("added", "+", added_ids.into_iter().collect_vec()),
+    ] {
+        for id in ids {
+            let commit = store.get_commit(id)?;
+            formatter.with_label(label, |formatter| write!(formatter, "  {sigil} "))?;
+            workspace_command.write_commit_summary(formatter, &commit)?;
+            writeln!(formatter)?;
+        }
+    }
+    Ok(())
+}
+
+fn write_ref_target_changes(
+    formatter: &mut dyn Formatter,
+    workspace_command: &WorkspaceCommandHelper,
+    old_target: &RefTarget,
+    new_target: &RefTarget,
+) -> Result<(), CommandError> {
+    write_commit_changes(
+        formatter,
+        workspace_command,
+        old_target.added_ids(),
+        new_target.added_ids(),
+    )
+}
+
+fn write_ref_changes(
+    formatter: &mut dyn Formatter,
+    workspace_command: &WorkspaceCommandHelper,
+    heading: &str,
+    changes: &[(&str, (&RefTarget, &RefTarget))],
+) -> Result<(), CommandError> {
+    if changes.is_empty() {
+        return Ok(());
+    }
+    writeln!(formatter)?;
+    writeln!(formatter, "{heading}")?;
+    for (name, (old_target, new_target)) in changes {
+        writeln!(formatter, "{name}:")?;
+        write_ref_target_changes(formatter, workspace_command, old_target, new_target)?;
+    }
+    Ok(())
+}
+
 /// Restore only the portions of the view specified by the `what` argument
 fn view_with_desired_portions_restored(
     view_being_restored: &jj_lib::op_store::View,
@@ -299,6 +635,121 @@ fn cmd_op_restore(
     Ok(())
 }
 
+fn cmd_op_restore_commit(
+    ui: &mut Ui,
+    command: &CommandHelper,
+    args: &OperationRestoreCommitArgs,
+) -> Result<(), CommandError> {
+    let mut workspace_command = command.workspace_helper(ui)?;
+    let repo = workspace_command.repo().clone();
+    let (kind, candidate_ids) = resolve_hidden_commit_ids(repo.as_ref(), &args.id)?;
+    let found = op_walk::find_op_with_visible_commits(
+        slice::from_ref(repo.operation()),
+        repo.index(),
+        &candidate_ids,
+        args.limit,
+    )?;
+    let Some((found_op, found_ids)) = found else {
+        return Err(user_error_with_hint(
+            format!(
+                "No {kind} with ID \"{}\" was visible in the last {} operations",
+                args.id, args.limit
+            ),
+            "Use `--limit` to search more operations.",
+        ));
+    };
+    if found_op.id() == repo.op_id() {
+        return Err(user_error(format!(
+            "The {kind} with ID \"{}\" is already visible",
+            args.id
+        )));
+    }
+    let commits: Vec<_> = found_ids
+        .iter()
+        .map(|id| repo.store().get_commit(id))
+        .try_collect()?;
+    if let [commit] = &*commits {
+        let mut tx = workspace_command.start_transaction();
+        tx.mut_repo().add_head(commit)?;
+        tx.finish(ui, format!("restore commit {}", commit.id().hex()))?;
+        let mut formatter = ui.stderr_formatter();
+        write!(formatter, "Restored commit ")?;
+        workspace_command.write_commit_summary(formatter.as_mut(), commit)?;
+        writeln!(formatter)?;
+        writeln!(
+            formatter,
+            "Found in operation {}: {}",
+            short_operation_hash(found_op.id()),
+            found_op.store_operation().metadata.description
+        )?;
+        Ok(())
+    } else {
+        let mut formatter = ui.stderr_formatter();
+        writeln!(
+            formatter,
+            "Commits visible in operation {}:",
+            short_operation_hash(found_op.id())
+        )?;
+        for commit in &commits {
+            write!(formatter, "  ")?;
+            workspace_command.write_commit_summary(formatter.as_mut(), commit)?;
+            writeln!(
+                formatter,
+                " (committed {})",
+                format_absolute_timestamp(&commit.committer().timestamp)
+            )?;
+        }
+        Err(user_error_with_hint(
+            format!(
+                "Change ID \"{}\" matches {} commits",
+                args.id,
+                commits.len()
+            ),
+            "Pass the commit ID of the one to restore.",
+        ))
+    }
+}
+
+/// Resolves `id` to a commit, or to all the commits with a change ID, including
+/// hidden ones. Returns whether it's a commit ID or a change ID, for messages.
+fn resolve_hidden_commit_ids(
+    repo: &ReadonlyRepo,
+    id: &str,
+) -> Result<(&'static str, Vec<CommitId>), CommandError> {
+    let index = repo.readonly_index();
+    if let Some(prefix) = HexPrefix::new(id) {
+        match index.as_index().resolve_commit_id_prefix(&prefix) {
+            PrefixResolution::SingleMatch(commit_id) => return Ok(("commit", vec![commit_id])),
+            PrefixResolution::AmbiguousMatch => {
+                return Err(user_error(format!(
+                    "Commit ID prefix \"{id}\" is ambiguous"
+                )));
+            }
+            PrefixResolution::NoMatch => {}
+        }
+    }
+    if let Some(prefix) = to_forward_hex(id).as_deref().and_then(HexPrefix::new) {
+        let heads = index
+            .as_index()
+            .all_heads_for_gc()
+            .map_err(user_error)?
+            .collect_vec();
+        match index
+            .change_id_index(&mut heads.iter())
+            .resolve_prefix(&prefix)
+        {
+            PrefixResolution::SingleMatch(commit_ids) => return Ok(("change", commit_ids)),
+            PrefixResolution::AmbiguousMatch => {
+                return Err(user_error(format!(
+                    "Change ID prefix \"{id}\" is ambiguous"
+                )));
+            }
+            PrefixResolution::NoMatch => {}
+        }
+    }
+    Err(user_error(format!("No commit or change with ID \"{id}\"")))
+}
+
 fn cmd_op_abandon(
     ui: &mut Ui,
     command: &CommandHelper,
@@ -368,21 +819,27 @@ fn cmd_op_abandon(
         stats.unreachable_count,
         stats.rewritten_count,
     )?;
-    repo_loader
-        .op_heads_store()
-        .update_op_heads(slice::from_ref(current_head_op.id()), &new_head_id);
+    {
+        let _lock = repo_loader.op_heads_store().lock();
+        repo_loader
+            .op_heads_store()
+            .update_op_heads(slice::from_ref(current_head_op.id()), &new_head_id);
+    }
     // Remap the operation id of the current workspace. If there were any
     // concurrent operations, user will need to re-abandon their ancestors.
     if !command.global_args().ignore_working_copy {
         let mut locked_ws = workspace.start_working_copy_mutation()?;
         let old_op_id = locked_ws.locked_wc().old_operation_id();
         if old_op_id != current_head_op.id() {
-            writeln!(
-                ui.warning(),
-                "The working copy operation {} is not updated because it differs from the repo {}.",
-                short_operation_hash(old_op_id),
-                short_operation_hash(current_head_op.id()),
-            )?;
+            ui.write_warning(&UiWarning::new(
+                WarningCode::StaleWorkingCopy,
+                format!(
+                    "The working copy operation {} is not updated because it differs from the \
+                     repo {}.",
+                    short_operation_hash(old_op_id),
+                    short_operation_hash(current_head_op.id()),
+                ),
+            ))?;
         } else {
             locked_ws.finish(new_head_id)?
         }
@@ -397,8 +854,10 @@ pub fn cmd_operation(
 ) -> Result<(), CommandError> {
     match subcommand {
         OperationCommand::Abandon(args) => cmd_op_abandon(ui, command, args),
+        OperationCommand::Diff(args) => cmd_op_diff(ui, command, args),
         OperationCommand::Log(args) => cmd_op_log(ui, command, args),
         OperationCommand::Restore(args) => cmd_op_restore(ui, command, args),
+        OperationCommand::RestoreCommit(args) => cmd_op_restore_commit(ui, command, args),
         OperationCommand::Undo(args) => cmd_op_undo(ui, command, args),
     }
 }
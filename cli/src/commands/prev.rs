@@ -17,7 +17,7 @@ use jj_lib::repo::Repo;
 use jj_lib::revset::{RevsetExpression, RevsetIteratorExt};
 
 use crate::cli_util::{short_commit_hash, user_error, CommandError, CommandHelper};
-use crate::commands::next::choose_commit;
+use crate::commands::next::resolve_ambiguous_target;
 use crate::ui::Ui;
 
 /// Move the working copy commit to the parent of the current revision.
@@ -55,6 +55,10 @@ pub(crate) struct PrevArgs {
     /// Edit the parent directly, instead of moving the working-copy commit.
     #[arg(long)]
     edit: bool,
+    /// Prompt for the commit to move to if there are several candidates,
+    /// instead of failing
+    #[arg(long)]
+    prompt: bool,
 }
 
 pub(crate) fn cmd_prev(
@@ -93,7 +97,6 @@ pub(crate) fn cmd_prev(
         .evaluate_programmatic(workspace_command.repo().as_ref())?
         .iter()
         .commits(workspace_command.repo().store())
-        .take(2)
         .try_collect()?;
     let target = match targets.as_slice() {
         [target] => target,
@@ -103,7 +106,9 @@ pub(crate) fn cmd_prev(
                 if amount > 1 { "s" } else { "" }
             )))
         }
-        commits => choose_commit(ui, &workspace_command, "prev", commits)?,
+        commits => {
+            resolve_ambiguous_target(ui, &workspace_command, "prev", commits, edit, args.prompt)?
+        }
     };
     // Generate a short commit hash, to make it readable in the op log.
     let target_short = short_commit_hash(target.id());
@@ -0,0 +1,212 @@
+// Copyright 2024 The Jujutsu Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::collections::HashSet;
+use std::io::Write;
+
+use jj_lib::commit::Commit;
+use jj_lib::matchers::EverythingMatcher;
+use jj_lib::rewrite::rebase_to_dest_parent;
+use tracing::instrument;
+
+use crate::cli_util::{
+    user_error, CommandError, CommandHelper, RevisionArg, WorkspaceCommandHelper,
+};
+use crate::diff_util::{self, DiffFormat, DiffFormatArgs};
+use crate::formatter::Formatter;
+use crate::ui::Ui;
+
+/// Descriptions must share at least this fraction of their words for commits
+/// with different change ids to be paired up.
+const MIN_DESCRIPTION_SIMILARITY: f64 = 0.5;
+
+/// Compare two versions of a series of commits
+///
+/// The commits of the old and the new version are paired up by change id, or
+/// by description for commits whose change id isn't in the other version. Each
+/// commit is listed with a marker: `=` if its changes are the same in both
+/// versions, `!` if they differ, `+` if it's only in the new version, and `-`
+/// if it's only in the old version. For changed commits, the differences are
+/// shown as with `jj interdiff`.
+#[derive(clap::Args, Clone, Debug)]
+pub(crate) struct RangeDiffArgs {
+    /// The old and the new version of the commits (specify it twice, e.g. `-r
+    /// OLD -r NEW`)
+    #[arg(long, short, required = true)]
+    revisions: Vec<RevisionArg>,
+    #[command(flatten)]
+    format: DiffFormatArgs,
+}
+
+#[instrument(skip_all)]
+pub(crate) fn cmd_range_diff(
+    ui: &mut Ui,
+    command: &CommandHelper,
+    args: &RangeDiffArgs,
+) -> Result<(), CommandError> {
+    let [old_revision, new_revision] = args.revisions.as_slice() else {
+        return Err(user_error(
+            "Expected two revsets: the old and the new version of the commits",
+        ));
+    };
+    let workspace_command = command.workspace_helper(ui)?;
+    // Revsets are in reverse topological order
+    let mut old_commits = workspace_command.resolve_revset(old_revision, ui)?;
+    old_commits.reverse();
+    let mut new_commits = workspace_command.resolve_revset(new_revision, ui)?;
+    new_commits.reverse();
+    let pairs = pair_commits(&old_commits, &new_commits);
+    let mut old_is_paired = vec![false; old_commits.len()];
+    for &old_index in pairs.iter().flatten() {
+        old_is_paired[old_index] = true;
+    }
+
+    let diff_formats = diff_util::diff_formats_for(command.settings(), &args.format)?;
+    ui.request_pager();
+    let mut formatter = ui.stdout_formatter();
+    let formatter = formatter.as_mut();
+    // Removed commits are listed before the first pair that comes after them
+    // in the old version
+    let mut next_old_index = 0;
+    for (new_commit, old_index) in new_commits.iter().zip(pairs) {
+        let Some(old_index) = old_index else {
+            write_commit_line(formatter, &workspace_command, "+", new_commit)?;
+            continue;
+        };
+        for old_index in next_old_index..old_index {
+            if !old_is_paired[old_index] {
+                write_commit_line(formatter, &workspace_command, "-", &old_commits[old_index])?;
+            }
+        }
+        next_old_index = next_old_index.max(old_index + 1);
+        write_pair(
+            ui,
+            formatter,
+            &workspace_command,
+            &old_commits[old_index],
+            new_commit,
+            &diff_formats,
+        )?;
+    }
+    for old_index in next_old_index..old_commits.len() {
+        if !old_is_paired[old_index] {
+            write_commit_line(formatter, &workspace_command, "-", &old_commits[old_index])?;
+        }
+    }
+    Ok(())
+}
+
+/// Returns the index of the old commit paired with each new commit. Commits
+/// with the same change id are paired first, then the remaining commits with
+/// the most similar descriptions.
+fn pair_commits(old_commits: &[Commit], new_commits: &[Commit]) -> Vec<Option<usize>> {
+    let mut old_is_paired = vec![false; old_commits.len()];
+    let mut pairs = vec![None; new_commits.len()];
+    for (new_index, new_commit) in new_commits.iter().enumerate() {
+        let old_index = (0..old_commits.len()).find(|&old_index| {
+            !old_is_paired[old_index]
+                && old_commits[old_index].change_id() == new_commit.change_id()
+        });
+        if let Some(old_index) = old_index {
+            old_is_paired[old_index] = true;
+            pairs[new_index] = Some(old_index);
+        }
+    }
+    for (new_index, new_commit) in new_commits.iter().enumerate() {
+        if pairs[new_index].is_some() {
+            continue;
+        }
+        let best_match = (0..old_commits.len())
+            .filter(|&old_index| !old_is_paired[old_index])
+            .map(|old_index| {
+                let similarity = description_similarity(
+                    old_commits[old_index].description(),
+                    new_commit.description(),
+                );
+                (old_index, similarity)
+            })
+            .filter(|&(_, similarity)| similarity >= MIN_DESCRIPTION_SIMILARITY)
+            // Prefer the earliest of equally similar commits
+            .max_by(|(index1, similarity1), (index2, similarity2)| {
+                similarity1.total_cmp(similarity2).then(index2.cmp(index1))
+            });
+        if let Some((old_index, _)) = best_match {
+            old_is_paired[old_index] = true;
+            pairs[new_index] = Some(old_index);
+        }
+    }
+    pairs
+}
+
+/// The fraction of the words of two descriptions that they have in common.
+fn description_similarity(description1: &str, description2: &str) -> f64 {
+    let words = |description: &str| -> HashSet<String> {
+        description
+            .split_whitespace()
+            .map(|word| word.to_lowercase())
+            .collect()
+    };
+    let words1 = words(description1);
+    let words2 = words(description2);
+    let num_words = words1.union(&words2).count();
+    if num_words == 0 {
+        return 0.0;
+    }
+    words1.intersection(&words2).count() as f64 / num_words as f64
+}
+
+fn write_commit_line(
+    formatter: &mut dyn Formatter,
+    workspace_command: &WorkspaceCommandHelper,
+    marker: &str,
+    commit: &Commit,
+) -> std::io::Result<()> {
+    write!(formatter, "{marker} ")?;
+    workspace_command.write_commit_summary(formatter, commit)?;
+    writeln!(formatter)
+}
+
+fn write_pair(
+    ui: &Ui,
+    formatter: &mut dyn Formatter,
+    workspace_command: &WorkspaceCommandHelper,
+    old_commit: &Commit,
+    new_commit: &Commit,
+    diff_formats: &[DiffFormat],
+) -> Result<(), CommandError> {
+    // The old commit's changes are rebased onto the new commit's parents, so
+    // only the differences in the changes themselves are shown. Conflicts from
+    // that rebase show up in the diff.
+    let old_tree =
+        rebase_to_dest_parent(workspace_command.repo().as_ref(), old_commit, new_commit)?;
+    let new_tree = new_commit.tree()?;
+    let same_description = old_commit.description() == new_commit.description();
+    if old_tree.id() == new_tree.id() && same_description {
+        write_commit_line(formatter, workspace_command, "=", new_commit)?;
+        return Ok(());
+    }
+    write_commit_line(formatter, workspace_command, "!", new_commit)?;
+    if !same_description {
+        writeln!(formatter, "Description changed")?;
+    }
+    diff_util::show_diff(
+        ui,
+        formatter,
+        workspace_command,
+        &old_tree,
+        &new_tree,
+        &EverythingMatcher,
+        diff_formats,
+    )
+}
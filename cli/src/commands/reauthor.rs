@@ -0,0 +1,116 @@
+// Copyright 2024 The Jujutsu Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::collections::HashSet;
+use std::io::Write;
+
+use itertools::Itertools;
+use jj_lib::backend::{CommitId, Signature};
+use jj_lib::object_id::ObjectId;
+use jj_lib::rewrite::transform_descendants;
+use tracing::instrument;
+
+use crate::cli_util::{
+    resolve_multiple_nonempty_revsets, CommandError, CommandHelper, RevisionArg,
+};
+use crate::ui::Ui;
+
+/// Change the author of revisions
+///
+/// The author name and email of the given revisions are set to the configured
+/// user, unless `--name` or `--email` is given. The author timestamp is kept.
+/// Descendants of the revisions are rebased onto the rewritten revisions.
+#[derive(clap::Args, Clone, Debug)]
+pub(crate) struct ReauthorArgs {
+    /// The revision(s) to change the author of
+    #[arg(long, short, default_value = "@")]
+    revisions: Vec<RevisionArg>,
+    /// The new author name (defaults to the configured `user.name`)
+    #[arg(long)]
+    name: Option<String>,
+    /// The new author email (defaults to the configured `user.email`)
+    #[arg(long)]
+    email: Option<String>,
+}
+
+#[instrument(skip_all)]
+pub(crate) fn cmd_reauthor(
+    ui: &mut Ui,
+    command: &CommandHelper,
+    args: &ReauthorArgs,
+) -> Result<(), CommandError> {
+    let mut workspace_command = command.workspace_helper(ui)?;
+    let commits = resolve_multiple_nonempty_revsets(&args.revisions, &workspace_command, ui)?;
+    workspace_command.check_rewritable(commits.iter())?;
+    let name = args
+        .name
+        .clone()
+        .unwrap_or_else(|| command.settings().user_name());
+    let email = args
+        .email
+        .clone()
+        .unwrap_or_else(|| command.settings().user_email());
+    let to_reauthor: HashSet<&CommitId> = commits.iter().map(|commit| commit.id()).collect();
+
+    let mut tx = workspace_command.start_transaction();
+    let mut num_reauthored = 0;
+    let mut num_rebased = 0;
+    transform_descendants(
+        command.settings(),
+        tx.mut_repo(),
+        commits
+            .iter()
+            .map(|commit| commit.id().clone())
+            .collect_vec(),
+        |context| {
+            let old_author = context.old_commit().author();
+            if to_reauthor.contains(context.old_commit().id())
+                && (old_author.name != name || old_author.email != email)
+            {
+                let new_author = Signature {
+                    name: name.clone(),
+                    email: email.clone(),
+                    timestamp: old_author.timestamp.clone(),
+                };
+                num_reauthored += 1;
+                Ok::<_, CommandError>(Some(context.rebase()?.set_author(new_author)))
+            } else {
+                if context.parents_changed() {
+                    num_rebased += 1;
+                }
+                Ok(None)
+            }
+        },
+    )?;
+
+    if num_reauthored == 0 {
+        writeln!(ui.stderr(), "Nothing changed.")?;
+        return Ok(());
+    }
+    writeln!(ui.stderr(), "Reauthored {num_reauthored} commits")?;
+    if num_rebased > 0 {
+        writeln!(ui.stderr(), "Rebased {num_rebased} descendant commits")?;
+    }
+    let transaction_description = if commits.len() == 1 {
+        format!("reauthor commit {}", commits[0].id().hex())
+    } else {
+        format!(
+            "reauthor commit {} and {} more",
+            commits[0].id().hex(),
+            commits.len() - 1
+        )
+    };
+    tx.finish(ui, transaction_description)?;
+    Ok(())
+}
@@ -158,9 +158,21 @@ pub(crate) struct RebaseArgs {
     /// skipped.
     /// Will never skip merge commits with multiple non-empty parents.
     /// Will never skip the working commit.
-    #[arg(long, conflicts_with = "revision")]
+    ///
+    /// Without this flag or `--keep-empty`, the `rebase.empty` setting
+    /// decides what happens to commits that become empty.
+    #[arg(long, conflicts_with_all = ["revision", "keep_empty"])]
     skip_empty: bool,
 
+    /// Keep commits that become empty, regardless of the `rebase.empty`
+    /// setting
+    #[arg(long, conflicts_with = "revision")]
+    keep_empty: bool,
+
+    /// Only print the changes that would be made, without making them
+    #[arg(long)]
+    dry_run: bool,
+
     /// Deprecated. Please prefix the revset with `all:` instead.
     #[arg(long, short = 'L', hide = true)]
     allow_large_revsets: bool,
@@ -180,10 +192,14 @@ Please use `jj rebase -d 'all:x|y'` instead of `jj rebase --allow-large-revsets
     }
 
     let rebase_options = RebaseOptions {
-        empty: match args.skip_empty {
-            true => EmptyBehaviour::AbandonAllEmpty,
-            false => EmptyBehaviour::Keep,
+        empty: if args.skip_empty {
+            EmptyBehaviour::AbandonAllEmpty
+        } else if args.keep_empty || args.revision.is_some() {
+            EmptyBehaviour::Keep
+        } else {
+            empty_behaviour_from_settings(command.settings())?
         },
+        cancel: None,
     };
     let mut workspace_command = command.workspace_helper(ui)?;
     let new_parents = cli_util::resolve_all_revs(&workspace_command, ui, &args.destination)?
@@ -211,6 +227,7 @@ Please use `jj rebase -d 'all:x|y'` instead of `jj rebase --allow-large-revsets
             &mut workspace_command,
             &new_parents,
             rev_str,
+            args.dry_run,
         )?;
     } else if !args.source.is_empty() {
         let source_commits =
@@ -222,6 +239,7 @@ Please use `jj rebase -d 'all:x|y'` instead of `jj rebase --allow-large-revsets
             &new_parents,
             &source_commits,
             rebase_options,
+            args.dry_run,
         )?;
     } else {
         let branch_commits = if args.branch.is_empty() {
@@ -236,6 +254,7 @@ Please use `jj rebase -d 'all:x|y'` instead of `jj rebase --allow-large-revsets
             &new_parents,
             &branch_commits,
             rebase_options,
+            args.dry_run,
         )?;
     }
     Ok(())
@@ -248,6 +267,7 @@ fn rebase_branch(
     new_parents: &[Commit],
     branch_commits: &IndexSet<Commit>,
     rebase_options: RebaseOptions,
+    dry_run: bool,
 ) -> Result<(), CommandError> {
     let parent_ids = new_parents
         .iter()
@@ -273,6 +293,7 @@ fn rebase_branch(
         new_parents,
         &root_commits,
         rebase_options,
+        dry_run,
     )
 }
 
@@ -283,6 +304,7 @@ fn rebase_descendants(
     new_parents: &[Commit],
     old_commits: &IndexSet<Commit>,
     rebase_options: RebaseOptions,
+    dry_run: bool,
 ) -> Result<(), CommandError> {
     workspace_command.check_rewritable(old_commits)?;
     for old_commit in old_commits.iter() {
@@ -291,19 +313,49 @@ fn rebase_descendants(
     let mut tx = workspace_command.start_transaction();
     // `rebase_descendants` takes care of sorting in reverse topological order, so
     // no need to do it here.
+    let mut abandoned_commits = vec![];
     for old_commit in old_commits {
-        rebase_commit_with_options(
+        let new_commit = rebase_commit_with_options(
             settings,
             tx.mut_repo(),
             old_commit,
             new_parents,
             &rebase_options,
         )?;
+        if new_commit.change_id() != old_commit.change_id() {
+            abandoned_commits.push(old_commit.clone());
+        }
+    }
+    let rebased_descendants = tx
+        .mut_repo()
+        .rebase_descendants_with_options_return_map(settings, rebase_options)?;
+    // An abandoned commit is mapped to its parent, which has a different change id.
+    // See `MutableRepo::rebase_descendants_with_options_return_map()`.
+    for (old_commit_id, new_commit_id) in &rebased_descendants {
+        let old_commit = tx.repo().store().get_commit(old_commit_id)?;
+        let new_commit = tx.repo().store().get_commit(new_commit_id)?;
+        if new_commit.change_id() != old_commit.change_id() {
+            abandoned_commits.push(old_commit);
+        }
+    }
+    if dry_run {
+        return tx.finish_dry_run(ui);
     }
-    let num_rebased = old_commits.len()
-        + tx.mut_repo()
-            .rebase_descendants_with_options(settings, rebase_options)?;
+    let num_rebased = old_commits.len() + rebased_descendants.len() - abandoned_commits.len();
     writeln!(ui.stderr(), "Rebased {num_rebased} commits")?;
+    if !abandoned_commits.is_empty() {
+        writeln!(
+            ui.stderr(),
+            "Abandoned {} commits that became empty:",
+            abandoned_commits.len()
+        )?;
+        for commit in &abandoned_commits {
+            write!(ui.stderr(), "  ")?;
+            tx.base_workspace_helper()
+                .write_commit_summary(ui.stderr_formatter().as_mut(), commit)?;
+            writeln!(ui.stderr())?;
+        }
+    }
     let tx_message = if old_commits.len() == 1 {
         format!(
             "rebase commit {} and descendants",
@@ -322,6 +374,7 @@ fn rebase_revision(
     workspace_command: &mut WorkspaceCommandHelper,
     new_parents: &[Commit],
     rev_str: &str,
+    dry_run: bool,
 ) -> Result<(), CommandError> {
     let old_commit = workspace_command.resolve_single_rev(rev_str, ui)?;
     workspace_command.check_rewritable([&old_commit])?;
@@ -432,6 +485,9 @@ fn rebase_revision(
     // abandoned.
     rebase_commit(settings, tx.mut_repo(), &old_commit, &new_parents)?;
     debug_assert_eq!(tx.mut_repo().rebase_descendants(settings)?, 0);
+    if dry_run {
+        return tx.finish_dry_run(ui);
+    }
 
     if num_rebased_descendants > 0 {
         writeln!(
@@ -444,6 +500,20 @@ fn rebase_revision(
     Ok(())
 }
 
+fn empty_behaviour_from_settings(
+    settings: &UserSettings,
+) -> Result<EmptyBehaviour, config::ConfigError> {
+    let name = settings.config().get_string("rebase.empty")?;
+    match name.as_ref() {
+        "keep" => Ok(EmptyBehaviour::Keep),
+        "abandon-newly-empty" => Ok(EmptyBehaviour::AbandonNewlyEmpty),
+        "abandon-all-empty" => Ok(EmptyBehaviour::AbandonAllEmpty),
+        _ => Err(config::ConfigError::Message(format!(
+            "invalid rebase.empty setting: {name}"
+        ))),
+    }
+}
+
 fn check_rebase_destinations(
     repo: &Arc<ReadonlyRepo>,
     new_parents: &[Commit],
@@ -15,14 +15,16 @@
 use std::collections::BTreeMap;
 use std::io::Write;
 
+use clap::ArgGroup;
 use itertools::Itertools;
 use jj_lib::backend::TreeValue;
-use jj_lib::merge::MergedTreeValue;
+use jj_lib::merge::{MergedTreeValue, MetadataResolution};
+use jj_lib::merged_tree::MergedTreeBuilder;
 use jj_lib::object_id::ObjectId;
 use jj_lib::repo_path::RepoPathBuf;
 use tracing::instrument;
 
-use crate::cli_util::{CommandError, CommandHelper, WorkspaceCommandHelper};
+use crate::cli_util::{user_error, CommandError, CommandHelper, WorkspaceCommandHelper};
 use crate::formatter::Formatter;
 use crate::ui::Ui;
 
@@ -34,6 +36,10 @@ use crate::ui::Ui;
 /// Note that conflicts can also be resolved without using this command. You may
 /// edit the conflict markers in the conflicted file directly with a text
 /// editor.
+///
+/// Conflicts in the executable bit, and conflicts between a symlink and a
+/// regular file, can't be represented in the working copy. Resolve them with
+/// `--set-exec`, `--clear-exec`, `--use-symlink`, or `--use-file` instead.
 //  TODOs:
 //   - `jj resolve --editor` to resolve a conflict in the default text editor. Should work for
 //     conflicts with 3+ adds. Useful to resolve conflicts in a commit other than the current one.
@@ -42,6 +48,12 @@ use crate::ui::Ui;
 //     point to existing commits with simpler conflicts where resolving those conflicts would help
 //     simplify the present one.
 #[derive(clap::Args, Clone, Debug)]
+#[command(group(
+    ArgGroup::new("metadata")
+        .args(&["set_exec", "clear_exec", "use_symlink", "use_file"])
+        .conflicts_with("list")
+        .requires("paths")
+))]
 pub(crate) struct ResolveArgs {
     #[arg(long, short, default_value = "@")]
     revision: String,
@@ -54,6 +66,22 @@ pub(crate) struct ResolveArgs {
     /// conflict
     #[arg(long, short, conflicts_with = "list")]
     quiet: bool,
+    /// Resolve executable-bit conflicts at the given paths by making the
+    /// files executable
+    #[arg(long)]
+    set_exec: bool,
+    /// Resolve executable-bit conflicts at the given paths by making the
+    /// files non-executable
+    #[arg(long)]
+    clear_exec: bool,
+    /// Resolve symlink-vs-file conflicts at the given paths by keeping the
+    /// symlink
+    #[arg(long)]
+    use_symlink: bool,
+    /// Resolve symlink-vs-file conflicts at the given paths by keeping the
+    /// regular file
+    #[arg(long)]
+    use_file: bool,
     /// Restrict to these paths when searching for a conflict to resolve. We
     /// will attempt to resolve the first conflict we can find. You can use
     /// the `--list` argument to find paths to use here.
@@ -94,15 +122,35 @@ pub(crate) fn cmd_resolve(
         );
     };
 
-    let (repo_path, _) = conflicts.first().unwrap();
     workspace_command.check_rewritable([&commit])?;
-    writeln!(
-        ui.stderr(),
-        "Resolving conflicts in: {}",
-        workspace_command.format_file_path(repo_path)
-    )?;
     let mut tx = workspace_command.start_transaction();
-    let new_tree_id = tx.run_mergetool(ui, &tree, repo_path)?;
+    let new_tree_id = if let Some(resolution) = metadata_resolution(args) {
+        let mut tree_builder = MergedTreeBuilder::new(commit.tree_id().clone());
+        for (repo_path, value) in &conflicts {
+            let new_value = value.resolve_metadata(resolution).ok_or_else(|| {
+                user_error(format!(
+                    "Conflict at {} can't be resolved with {}",
+                    tx.base_workspace_helper().format_file_path(repo_path),
+                    metadata_resolution_flag(resolution)
+                ))
+            })?;
+            writeln!(
+                ui.stderr(),
+                "Resolving conflicts in: {}",
+                tx.base_workspace_helper().format_file_path(repo_path)
+            )?;
+            tree_builder.set_or_remove(repo_path.clone(), new_value);
+        }
+        tree_builder.write_tree(commit.store())?
+    } else {
+        let (repo_path, _) = conflicts.first().unwrap();
+        writeln!(
+            ui.stderr(),
+            "Resolving conflicts in: {}",
+            tx.base_workspace_helper().format_file_path(repo_path)
+        )?;
+        tx.run_mergetool(ui, &tree, repo_path)?
+    };
     let new_commit = tx
         .mut_repo()
         .rewrite_commit(command.settings(), &commit)
@@ -131,6 +179,29 @@ pub(crate) fn cmd_resolve(
     Ok(())
 }
 
+fn metadata_resolution(args: &ResolveArgs) -> Option<MetadataResolution> {
+    if args.set_exec {
+        Some(MetadataResolution::Executable(true))
+    } else if args.clear_exec {
+        Some(MetadataResolution::Executable(false))
+    } else if args.use_symlink {
+        Some(MetadataResolution::UseSymlink)
+    } else if args.use_file {
+        Some(MetadataResolution::UseFile)
+    } else {
+        None
+    }
+}
+
+fn metadata_resolution_flag(resolution: MetadataResolution) -> &'static str {
+    match resolution {
+        MetadataResolution::Executable(true) => "--set-exec",
+        MetadataResolution::Executable(false) => "--clear-exec",
+        MetadataResolution::UseSymlink => "--use-symlink",
+        MetadataResolution::UseFile => "--use-file",
+    }
+}
+
 #[instrument(skip_all)]
 pub(crate) fn print_conflicted_paths(
     conflicts: &[(RepoPathBuf, MergedTreeValue)],
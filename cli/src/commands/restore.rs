@@ -58,6 +58,9 @@ pub(crate) struct RestoreArgs {
     /// --changes-in @`.
     #[arg(long, short, value_name="REVISION", conflicts_with_all=["to", "from"])]
     changes_in: Option<RevisionArg>,
+    /// Only print the changes that would be made, without making them
+    #[arg(long)]
+    dry_run: bool,
     /// Prints an error. DO NOT USE.
     ///
     /// If we followed the pattern of `jj diff` and `jj diffedit`, we would use
@@ -111,6 +114,9 @@ pub(crate) fn cmd_restore(
         // rebase_descendants early; otherwise `new_commit` would always have
         // a conflicted change id at this point.
         let num_rebased = tx.mut_repo().rebase_descendants(command.settings())?;
+        if args.dry_run {
+            return tx.finish_dry_run(ui);
+        }
         write!(ui.stderr(), "Created ")?;
         tx.write_commit_summary(ui.stderr_formatter().as_mut(), &new_commit)?;
         writeln!(ui.stderr())?;
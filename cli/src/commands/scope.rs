@@ -0,0 +1,165 @@
+// Copyright 2024 The Jujutsu Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::io::Write;
+
+use clap::Subcommand;
+use itertools::Itertools;
+use jj_lib::repo_path::RepoPathBuf;
+use jj_lib::repo_scope::{RepoScope, WorkspaceScope};
+use tracing::instrument;
+
+use crate::cli_util::{
+    internal_error_with_message, print_checkout_stats, CommandError, CommandHelper,
+    WorkspaceCommandHelper,
+};
+use crate::ui::Ui;
+
+/// Restrict the repo view to some paths
+///
+/// In a large repo, you may only care about a few directories. Setting a scope
+/// limits the default revset of `jj log` to commits touching the given paths
+/// and makes the working copy sparse on them. Commits outside the scope are
+/// still in the repo and can be selected explicitly, e.g. with `jj log -r`.
+///
+/// The scope is stored per workspace. Setting it restricts the sparse patterns
+/// of the working copy (see `jj sparse`) to the scope, and clearing it restores
+/// the patterns outside the scope. The index of a scoped workspace keeps
+/// filters of the paths changed by each commit, so the commits in the scope
+/// can be found without diffing their trees.
+#[derive(Subcommand, Clone, Debug)]
+pub(crate) enum ScopeCommand {
+    List(ScopeListArgs),
+    Set(ScopeSetArgs),
+    Clear(ScopeClearArgs),
+}
+
+/// List the paths in the repo scope
+///
+/// A scope covering the whole repo is rendered as `.` (a single period).
+#[derive(clap::Args, Clone, Debug)]
+pub(crate) struct ScopeListArgs {}
+
+/// Restrict the repo view to the given paths
+#[derive(clap::Args, Clone, Debug)]
+pub(crate) struct ScopeSetArgs {
+    /// Paths to include in the scope
+    #[arg(required = true, value_hint = clap::ValueHint::AnyPath)]
+    paths: Vec<String>,
+}
+
+/// Make the repo view cover the whole repo again
+#[derive(clap::Args, Clone, Debug)]
+pub(crate) struct ScopeClearArgs {}
+
+#[instrument(skip_all)]
+pub(crate) fn cmd_scope(
+    ui: &mut Ui,
+    command: &CommandHelper,
+    subcommand: &ScopeCommand,
+) -> Result<(), CommandError> {
+    match subcommand {
+        ScopeCommand::List(sub_args) => cmd_scope_list(ui, command, sub_args),
+        ScopeCommand::Set(sub_args) => cmd_scope_set(ui, command, sub_args),
+        ScopeCommand::Clear(sub_args) => cmd_scope_clear(ui, command, sub_args),
+    }
+}
+
+#[instrument(skip_all)]
+fn cmd_scope_list(
+    ui: &mut Ui,
+    command: &CommandHelper,
+    _args: &ScopeListArgs,
+) -> Result<(), CommandError> {
+    let workspace_command = command.workspace_helper(ui)?;
+    let scope = workspace_command.repo_scope()?;
+    if scope.is_everything() {
+        writeln!(
+            ui.stdout(),
+            "{}",
+            workspace_command.format_file_path(&RepoPathBuf::root())
+        )?;
+    } else {
+        for prefix in scope.prefixes() {
+            writeln!(
+                ui.stdout(),
+                "{}",
+                workspace_command.format_file_path(prefix)
+            )?;
+        }
+    }
+    Ok(())
+}
+
+#[instrument(skip_all)]
+fn cmd_scope_set(
+    ui: &mut Ui,
+    command: &CommandHelper,
+    args: &ScopeSetArgs,
+) -> Result<(), CommandError> {
+    let workspace_command = command.workspace_helper(ui)?;
+    let prefixes: Vec<_> = args
+        .paths
+        .iter()
+        .map(|path| workspace_command.parse_file_path(path))
+        .try_collect()?;
+    update_scope(ui, workspace_command, RepoScope::new(prefixes))
+}
+
+#[instrument(skip_all)]
+fn cmd_scope_clear(
+    ui: &mut Ui,
+    command: &CommandHelper,
+    _args: &ScopeClearArgs,
+) -> Result<(), CommandError> {
+    let workspace_command = command.workspace_helper(ui)?;
+    update_scope(ui, workspace_command, RepoScope::default())
+}
+
+/// Restricts the sparse patterns of the working copy to the scope and records
+/// the scope in the workspace.
+fn update_scope(
+    ui: &mut Ui,
+    mut workspace_command: WorkspaceCommandHelper,
+    scope: RepoScope,
+) -> Result<(), CommandError> {
+    let workspace_root = workspace_command.workspace_root().clone();
+    let old_workspace_scope = WorkspaceScope::load(&workspace_root)?;
+    let (mut locked_ws, wc_commit) = workspace_command.start_working_copy_mutation()?;
+    let current_patterns = locked_ws.locked_wc().sparse_patterns()?.to_vec();
+    // Patterns outside the old scope were only hidden by it, so they're
+    // restored before restricting the patterns to the new scope
+    let base_sparse_patterns = match &old_workspace_scope {
+        Some(workspace_scope) => workspace_scope.unscoped_sparse_patterns(&current_patterns),
+        None => current_patterns,
+    };
+    let stats = locked_ws
+        .locked_wc()
+        .set_sparse_patterns(scope.restrict_sparse_patterns(&base_sparse_patterns))
+        .map_err(|err| internal_error_with_message("Failed to update working copy paths", err))?;
+    let operation_id = locked_ws.locked_wc().old_operation_id().clone();
+    locked_ws.finish(operation_id)?;
+    print_checkout_stats(ui, stats, &wc_commit)?;
+
+    if scope.is_everything() {
+        WorkspaceScope::remove(&workspace_root)?;
+    } else {
+        let workspace_scope = WorkspaceScope {
+            scope,
+            base_sparse_patterns,
+        };
+        workspace_scope.save(&workspace_root)?;
+    }
+    Ok(())
+}
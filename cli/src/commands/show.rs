@@ -12,7 +12,6 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use jj_lib::matchers::EverythingMatcher;
 use tracing::instrument;
 
 use crate::cli_util::{CommandError, CommandHelper, RevisionArg};
@@ -23,8 +22,15 @@ use crate::ui::Ui;
 #[derive(clap::Args, Clone, Debug)]
 pub(crate) struct ShowArgs {
     /// Show changes in this revision, compared to its parent(s)
+    ///
+    /// If the revision is a merge commit, this shows changes *from* the
+    /// automatic merge of the contents of all of its parents *to* the contents
+    /// of the revision itself.
     #[arg(default_value = "@")]
     revision: RevisionArg,
+    /// Restrict the diff to these paths
+    #[arg(value_hint = clap::ValueHint::AnyPath)]
+    paths: Vec<String>,
     /// Ignored (but lets you pass `-r` for consistency with other commands)
     #[arg(short = 'r', hide = true)]
     unused_revision: bool,
@@ -50,6 +56,7 @@ pub(crate) fn cmd_show(
         None => command.settings().config().get_string("templates.show")?,
     };
     let template = workspace_command.parse_commit_template(&template_string)?;
+    let matcher = workspace_command.matcher_from_values(&args.paths)?;
     let diff_formats = diff_util::diff_formats_for(command.settings(), &args.format)?;
     ui.request_pager();
     let mut formatter = ui.stdout_formatter();
@@ -60,7 +67,7 @@ pub(crate) fn cmd_show(
         formatter,
         &workspace_command,
         &commit,
-        &EverythingMatcher,
+        matcher.as_ref(),
         &diff_formats,
     )?;
     Ok(())
@@ -12,6 +12,7 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 use std::io::Write;
+use std::path::PathBuf;
 
 use jj_lib::object_id::ObjectId;
 use jj_lib::repo::Repo;
@@ -20,7 +21,7 @@ use tracing::instrument;
 
 use crate::cli_util::{CommandError, CommandHelper, RevisionArg};
 use crate::description_util::{description_template_for_commit, edit_description};
-use crate::ui::Ui;
+use crate::ui::{Ui, UiWarning, WarningCode};
 
 /// Split a revision in two
 ///
@@ -30,6 +31,9 @@ use crate::ui::Ui;
 /// the previous revision. The remaining changes will be put in a new revision
 /// on top.
 ///
+/// To split without a diff editor, list the paths to put in the first revision,
+/// or the hunks with `--hunks-from`.
+///
 /// If the change you split had a description, you will be asked to enter a
 /// change description for each commit. If the change did not have a
 /// description, the second part will not get a description, and you will be
@@ -43,6 +47,14 @@ pub(crate) struct SplitArgs {
     /// The revision to split
     #[arg(long, short, default_value = "@")]
     revision: RevisionArg,
+    /// Put the files and hunks listed in this file in the first commit
+    ///
+    /// Each line has a path relative to the workspace root, optionally
+    /// followed by `:` and the numbers of the hunks to select, like
+    /// `src/main.rs:1,3`. The hunks are numbered from 1 in the order of the
+    /// diff, as in the `:checklist` diff editor.
+    #[arg(long, conflicts_with = "interactive", value_hint = clap::ValueHint::FilePath)]
+    hunks_from: Option<PathBuf>,
     /// Put these paths in the first commit
     #[arg(value_hint = clap::ValueHint::AnyPath)]
     paths: Vec<String>,
@@ -61,7 +73,7 @@ pub(crate) fn cmd_split(
     let mut tx = workspace_command.start_transaction();
     let end_tree = commit.tree()?;
     let base_tree = merge_commit_trees(tx.repo(), &commit.parents())?;
-    let interactive = args.interactive || args.paths.is_empty();
+    let interactive = args.interactive || (args.paths.is_empty() && args.hunks_from.is_none());
     let instructions = format!(
         "\
 You are splitting a commit in two: {}
@@ -76,26 +88,37 @@ don't make any changes, then the operation will be aborted.
     );
 
     // Prompt the user to select the changes they want for the first commit.
-    let selected_tree_id = tx.select_diff(
-        ui,
-        &base_tree,
-        &end_tree,
-        matcher.as_ref(),
-        &instructions,
-        interactive,
-    )?;
+    let selected_tree_id = if let Some(hunks_from) = &args.hunks_from {
+        tx.select_hunks_from_file(&base_tree, &end_tree, matcher.as_ref(), hunks_from)?
+    } else {
+        tx.select_diff(
+            ui,
+            &base_tree,
+            &end_tree,
+            matcher.as_ref(),
+            &instructions,
+            interactive,
+        )?
+    };
     if &selected_tree_id == commit.tree_id() && interactive {
         // The user selected everything from the original commit.
         writeln!(ui.stderr(), "Nothing changed.")?;
         return Ok(());
     }
-    if selected_tree_id == base_tree.id() {
+    if selected_tree_id == base_tree.id() && args.hunks_from.is_some() {
+        ui.write_warning(&UiWarning::new(
+            WarningCode::EmptySelection,
+            "No changes were selected",
+        ))?;
+    } else if selected_tree_id == base_tree.id() {
         // The user selected nothing, so the first commit will be empty.
-        writeln!(
-            ui.warning(),
-            "The given paths do not match any file: {}",
-            args.paths.join(" ")
-        )?;
+        ui.write_warning(&UiWarning::new(
+            WarningCode::EmptySelection,
+            format!(
+                "The given paths do not match any file: {}",
+                args.paths.join(" ")
+            ),
+        ))?;
     }
 
     // Create the first commit, which includes the changes selected by the user.
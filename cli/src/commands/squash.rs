@@ -12,6 +12,8 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+use std::path::PathBuf;
+
 use clap::parser::ValueSource;
 use jj_lib::object_id::ObjectId;
 use jj_lib::revset;
@@ -19,14 +21,15 @@ use tracing::instrument;
 
 use crate::cli_util::{self, user_error, CommandError, CommandHelper, RevisionArg};
 use crate::description_util::combine_messages;
-use crate::ui::Ui;
+use crate::ui::{Ui, UiWarning, WarningCode};
 
 /// Move changes from a revision into its parent
 ///
 /// After moving the changes into the parent, the child revision will have the
 /// same content state as before. If that means that the change is now empty
 /// compared to its parent, it will be abandoned.
-/// Without `--interactive`, the child change will always be empty.
+/// Without `--interactive` or `--hunks-from`, the child change will always be
+/// empty.
 ///
 /// If the source became empty and both the source and destination had a
 /// non-empty description, you will be asked for the combined description. If
@@ -42,6 +45,11 @@ pub(crate) struct SquashArgs {
     /// Interactively choose which parts to squash
     #[arg(long, short)]
     interactive: bool,
+    /// Move only the files and hunks listed in this file
+    ///
+    /// The file has the same format as for `jj split --hunks-from`.
+    #[arg(long, conflicts_with = "interactive", value_hint = clap::ValueHint::FilePath)]
+    hunks_from: Option<PathBuf>,
     /// Move only changes to these paths (instead of all paths)
     #[arg(conflicts_with = "interactive", value_hint = clap::ValueHint::AnyPath)]
     paths: Vec<String>,
@@ -82,16 +90,20 @@ from the source will be moved into the parent.
     );
     let parent_tree = parent.tree()?;
     let tree = commit.tree()?;
-    let new_parent_tree_id = tx.select_diff(
-        ui,
-        &parent_tree,
-        &tree,
-        matcher.as_ref(),
-        &instructions,
-        args.interactive,
-    )?;
+    let new_parent_tree_id = if let Some(hunks_from) = &args.hunks_from {
+        tx.select_hunks_from_file(&parent_tree, &tree, matcher.as_ref(), hunks_from)?
+    } else {
+        tx.select_diff(
+            ui,
+            &parent_tree,
+            &tree,
+            matcher.as_ref(),
+            &instructions,
+            args.interactive,
+        )?
+    };
     if &new_parent_tree_id == parent.tree_id() {
-        if args.interactive {
+        if args.interactive || args.hunks_from.is_some() {
             return Err(user_error("No changes selected"));
         }
 
@@ -104,16 +116,18 @@ from the source will be moved into the parent.
                 )
                 .is_ok()
             {
-                writeln!(
-                    ui.warning(),
-                    "warning: The argument {only_path:?} is being interpreted as a path. To \
-                     specify a revset, pass -r {only_path:?} instead."
-                )?;
+                ui.write_warning(&UiWarning::new(
+                    WarningCode::AmbiguousPathArgument,
+                    format!(
+                        "The argument {only_path:?} is being interpreted as a path. To \
+                         specify a revset, pass -r {only_path:?} instead."
+                    ),
+                ))?;
             }
         }
     }
     // Abandon the child if the parent now has all the content from the child
-    // (always the case in the non-interactive case).
+    // (always the case without --interactive or --hunks-from).
     let abandon_child = &new_parent_tree_id == commit.tree_id();
     let description = if !args.message_paragraphs.is_empty() {
         cli_util::join_message_paragraphs(&args.message_paragraphs)
@@ -12,16 +12,27 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+use std::collections::HashSet;
+use std::io::{self, IsTerminal as _};
+use std::sync::Mutex;
+
 use itertools::Itertools;
-use jj_lib::matchers::EverythingMatcher;
+use jj_lib::conflicts;
+use jj_lib::matchers::{DifferenceMatcher, EverythingMatcher, FilesMatcher};
+use jj_lib::merge::{MergedTreeValue, MetadataConflict, MetadataResolution};
 use jj_lib::repo::Repo;
-use jj_lib::rewrite::merge_commit_trees;
+use jj_lib::repo_path::{RepoPath, RepoPathBuf};
+use jj_lib::status::{RepoStatus, StatusLevel};
+use jj_lib::working_copy::{SnapshotChangeCallback, SnapshotSkipReason};
 use tracing::instrument;
 
 use super::resolve;
-use crate::cli_util::{CommandError, CommandHelper};
-use crate::diff_util;
-use crate::ui::Ui;
+use crate::cli_util::{
+    write_nul_record, CommandError, CommandHelper, WorkingCopyMode, WorkspaceCommandHelper,
+};
+use crate::diff_util::{self, DiffFormat};
+use crate::formatter::Formatter;
+use crate::ui::{Ui, UiWarning, WarningCode};
 
 /// Show high-level repo status
 ///
@@ -31,55 +42,277 @@ use crate::ui::Ui;
 ///    changes between them
 ///
 ///  * Conflicted branches (see https://github.com/martinvonz/jj/blob/main/docs/branches.md)
+///
+/// If stdout is a terminal, changes are printed as soon as the snapshot of the
+/// working copy finds them, and the output isn't paged. Otherwise, all changes
+/// are printed in sorted order once the snapshot is complete.
+///
+/// With `--quiet` or `--check`, the exit code reports the status. The first
+/// matching state in this list determines the exit code:
+///
+///  * 3: The working-copy commit is divergent, or there are conflicted
+///    branches
+///
+///  * 2: The working-copy commit has conflicts
+///
+///  * 1: The working-copy commit has changes
+///
+///  * 0: The working copy is clean
 #[derive(clap::Args, Clone, Debug)]
 #[command(visible_alias = "st")]
-pub(crate) struct StatusArgs {}
+pub(crate) struct StatusArgs {
+    /// Don't print anything, only report the status through the exit code
+    #[arg(long, short)]
+    quiet: bool,
+    /// Report the status through the exit code in addition to printing it
+    #[arg(long)]
+    check: bool,
+    /// Print only the changed and untracked paths, as NUL-terminated records
+    /// for scripts
+    ///
+    /// Each record is the `--summary` status of a changed path (`M`, `A`, or
+    /// `D`), or `?` for an untracked path that wasn't snapshotted, then a tab
+    /// and the path. Paths are relative to the workspace root and never
+    /// quoted.
+    #[arg(short = 'z', conflicts_with = "quiet")]
+    null_terminated: bool,
+}
+
+/// Exit code when the working copy has changes.
+const EXIT_CODE_CHANGES: u8 = 1;
+/// Exit code when the working copy has conflicts.
+const EXIT_CODE_CONFLICTS: u8 = 2;
+/// Exit code for repo-level problems such as divergence or conflicted
+/// branches.
+const EXIT_CODE_REPO_PROBLEMS: u8 = 3;
 
 #[instrument(skip_all)]
 pub(crate) fn cmd_status(
     ui: &mut Ui,
     command: &CommandHelper,
-    _args: &StatusArgs,
+    args: &StatusArgs,
 ) -> Result<(), CommandError> {
-    let workspace_command = command.workspace_helper(ui)?;
+    let stream = !args.quiet && !args.null_terminated && io::stdout().is_terminal();
+    let mut workspace_command = command.workspace_helper_no_snapshot(ui)?;
+    let streamed_paths = Mutex::new(HashSet::new());
+    if stream {
+        let formatter_factory = ui.formatter_factory().clone();
+        workspace_command.maybe_snapshot_with_callback(ui, |workspace_command| {
+            let repo = workspace_command.repo();
+            let Some(wc_commit_id) = workspace_command.get_wc_commit_id() else {
+                return Ok(None);
+            };
+            let wc_commit = repo.store().get_commit(wc_commit_id)?;
+            let parent_tree = repo.merged_parent_tree(&wc_commit)?;
+            let path_converter = workspace_command.path_converter().clone();
+            let streamed_paths = &streamed_paths;
+            let on_change: Box<SnapshotChangeCallback> =
+                Box::new(move |path: &RepoPath, value: &MergedTreeValue| {
+                    let before = parent_tree.path_value(path);
+                    if before == *value {
+                        return;
+                    }
+                    // The lock also keeps lines from different threads apart.
+                    let mut streamed_paths = streamed_paths.lock().unwrap();
+                    let mut formatter = formatter_factory.new_formatter(io::stdout().lock());
+                    if streamed_paths.is_empty() {
+                        formatter.write_str("Working copy changes:\n").ok();
+                    }
+                    streamed_paths.insert(path.to_owned());
+                    let path = path_converter.format_file_path(path);
+                    formatter
+                        .with_label("diff", |formatter| {
+                            diff_util::write_diff_summary_entry(formatter, &path, &before, value)
+                        })
+                        .ok();
+                });
+            Ok(Some(on_change))
+        })?;
+    } else {
+        workspace_command.maybe_snapshot(ui)?;
+    }
+    let streamed_paths = streamed_paths.into_inner().unwrap();
     let repo = workspace_command.repo();
     let maybe_wc_commit = workspace_command
         .get_wc_commit_id()
         .map(|id| repo.store().get_commit(id))
         .transpose()?;
-    ui.request_pager();
-    let mut formatter = ui.stdout_formatter();
-    let formatter = formatter.as_mut();
+    let status = RepoStatus::gather(repo.as_ref(), maybe_wc_commit)?;
 
-    if let Some(wc_commit) = &maybe_wc_commit {
-        let parent_tree = merge_commit_trees(repo.as_ref(), &wc_commit.parents())?;
-        let tree = wc_commit.tree()?;
-        if tree.id() == parent_tree.id() {
+    if !args.quiet {
+        if workspace_command.working_copy_mode() == WorkingCopyMode::ReadOnly {
+            ui.write_warning(&UiWarning::new(
+                WarningCode::UnsnapshottedWorkingCopy,
+                "The working copy was not snapshotted, so the status may be stale.",
+            ))?;
+        }
+        if !stream {
+            ui.request_pager();
+        }
+        let mut formatter = ui.stdout_formatter();
+        if args.null_terminated {
+            print_status_records(formatter.as_mut(), &workspace_command, &status)?;
+        } else {
+            print_status(
+                formatter.as_mut(),
+                &workspace_command,
+                &status,
+                &streamed_paths,
+            )?;
+        }
+    }
+
+    if args.quiet || args.check {
+        let exit_code = match status.level() {
+            StatusLevel::Clean => return Ok(()),
+            StatusLevel::Changes => EXIT_CODE_CHANGES,
+            StatusLevel::Conflicts => EXIT_CODE_CONFLICTS,
+            StatusLevel::RepoProblems => EXIT_CODE_REPO_PROBLEMS,
+        };
+        return Err(CommandError::ExitStatus(exit_code));
+    }
+    Ok(())
+}
+
+/// Prints the changed and untracked paths for `-z`.
+fn print_status_records(
+    formatter: &mut dyn Formatter,
+    workspace_command: &WorkspaceCommandHelper,
+    status: &RepoStatus,
+) -> Result<(), CommandError> {
+    if let Some(wc) = &status.wc_commit {
+        diff_util::show_nul_terminated_diff(
+            formatter,
+            &wc.parent_tree,
+            &wc.tree,
+            &EverythingMatcher,
+            &[DiffFormat::Summary],
+        )?;
+    }
+    for (path, _reason) in &workspace_command.snapshot_stats().skipped_paths {
+        write_nul_record(formatter, &["?"], path)?;
+    }
+    Ok(())
+}
+
+/// Prints the status. Changes to the `streamed_paths` were already printed
+/// while the working copy was snapshotted.
+fn print_status(
+    formatter: &mut dyn Formatter,
+    workspace_command: &WorkspaceCommandHelper,
+    status: &RepoStatus,
+    streamed_paths: &HashSet<RepoPathBuf>,
+) -> Result<(), CommandError> {
+    if let Some(wc) = &status.wc_commit {
+        if !wc.has_changes() {
             formatter.write_str("The working copy is clean\n")?;
         } else {
-            formatter.write_str("Working copy changes:\n")?;
+            if streamed_paths.is_empty() {
+                formatter.write_str("Working copy changes:\n")?;
+            }
+            let streamed_matcher = FilesMatcher::new(streamed_paths);
+            let matcher = DifferenceMatcher::new(&EverythingMatcher, &streamed_matcher);
             diff_util::show_diff_summary(
                 formatter,
-                &workspace_command,
-                parent_tree.diff_stream(&tree, &EverythingMatcher),
+                workspace_command,
+                wc.parent_tree.diff_stream(&wc.tree, &matcher),
             )?;
         }
 
-        let conflicts = wc_commit.tree()?.conflicts().collect_vec();
-        if !conflicts.is_empty() {
+        let (path_conflicts, content_conflicts): (Vec<_>, Vec<_>) = wc
+            .conflicts
+            .iter()
+            .cloned()
+            .partition(|(_, value)| value.is_path_conflict());
+        let metadata_conflicts = content_conflicts
+            .iter()
+            .filter(|(_, value)| value.metadata_conflict().is_some())
+            .cloned()
+            .collect_vec();
+        // An executable-bit conflict may also have conflicting contents
+        let content_conflicts = content_conflicts
+            .into_iter()
+            .filter(|(_, value)| match value.metadata_conflict() {
+                None => true,
+                Some(MetadataConflict::Executable) => value
+                    .resolve_metadata(MetadataResolution::Executable(false))
+                    .map_or(true, |value| !value.is_resolved()),
+                Some(MetadataConflict::SymlinkVsFile) => false,
+            })
+            .collect_vec();
+        if !content_conflicts.is_empty() {
             writeln!(
                 formatter.labeled("conflict"),
                 "There are unresolved conflicts at these paths:"
             )?;
-            resolve::print_conflicted_paths(&conflicts, formatter, &workspace_command)?
+            resolve::print_conflicted_paths(&content_conflicts, formatter, workspace_command)?
+        }
+        if !path_conflicts.is_empty() {
+            writeln!(
+                formatter.labeled("conflict"),
+                "There are path conflicts between files and directories at these paths:"
+            )?;
+            for (path, _) in &path_conflicts {
+                let file_path = conflicts::path_conflict_file_path(path);
+                writeln!(
+                    formatter,
+                    "  {}: the file is checked out as {}",
+                    workspace_command.format_file_path(path),
+                    workspace_command.format_file_path(&file_path)
+                )?;
+            }
+            writeln!(
+                formatter,
+                "  Delete the file to keep the directory, or replace the directory with the file \
+                 to keep the file."
+            )?;
+        }
+        if !metadata_conflicts.is_empty() {
+            writeln!(
+                formatter.labeled("conflict"),
+                "There are metadata conflicts at these paths:"
+            )?;
+            for (path, value) in &metadata_conflicts {
+                let description = match value.metadata_conflict().unwrap() {
+                    MetadataConflict::Executable => "the sides disagree about the executable bit",
+                    MetadataConflict::SymlinkVsFile => {
+                        "a symlink on some sides and a regular file on others (checked out as \
+                         the file)"
+                    }
+                };
+                writeln!(
+                    formatter,
+                    "  {}: {description}",
+                    workspace_command.format_file_path(path)
+                )?;
+            }
+            writeln!(
+                formatter,
+                "  Use `jj resolve --set-exec` or `--clear-exec`, or `jj resolve --use-symlink` \
+                 or `--use-file`, with the path to resolve."
+            )?;
+        }
+        let skipped_paths = &workspace_command.snapshot_stats().skipped_paths;
+        if !skipped_paths.is_empty() {
+            writeln!(formatter, "Untracked paths that weren't snapshotted:")?;
+            for (path, reason) in skipped_paths {
+                let reason = match reason {
+                    SnapshotSkipReason::SpecialFile => "special file",
+                };
+                writeln!(
+                    formatter,
+                    "  {}: {reason}",
+                    workspace_command.format_file_path(path)
+                )?;
+            }
         }
 
         formatter.write_str("Working copy : ")?;
         formatter.with_label("working_copy", |fmt| {
-            workspace_command.write_commit_summary(fmt, wc_commit)
+            workspace_command.write_commit_summary(fmt, &wc.commit)
         })?;
         formatter.write_str("\n")?;
-        for parent in wc_commit.parents() {
+        for parent in wc.commit.parents() {
             formatter.write_str("Parent commit: ")?;
             workspace_command.write_commit_summary(formatter, &parent)?;
             formatter.write_str("\n")?;
@@ -88,24 +321,12 @@ pub(crate) fn cmd_status(
         formatter.write_str("No working copy\n")?;
     }
 
-    let conflicted_local_branches = repo
-        .view()
-        .local_branches()
-        .filter(|(_, target)| target.has_conflict())
-        .map(|(branch_name, _)| branch_name)
-        .collect_vec();
-    let conflicted_remote_branches = repo
-        .view()
-        .all_remote_branches()
-        .filter(|(_, remote_ref)| remote_ref.target.has_conflict())
-        .map(|(full_name, _)| full_name)
-        .collect_vec();
-    if !conflicted_local_branches.is_empty() {
+    if !status.conflicted_local_branches.is_empty() {
         writeln!(
             formatter.labeled("conflict"),
             "These branches have conflicts:"
         )?;
-        for branch_name in conflicted_local_branches {
+        for branch_name in &status.conflicted_local_branches {
             write!(formatter, "  ")?;
             write!(formatter.labeled("branch"), "{branch_name}")?;
             writeln!(formatter)?;
@@ -116,12 +337,12 @@ pub(crate) fn cmd_status(
              resolve."
         )?;
     }
-    if !conflicted_remote_branches.is_empty() {
+    if !status.conflicted_remote_branches.is_empty() {
         writeln!(
             formatter.labeled("conflict"),
             "These remote branches have conflicts:"
         )?;
-        for (branch_name, remote_name) in conflicted_remote_branches {
+        for (branch_name, remote_name) in &status.conflicted_remote_branches {
             write!(formatter, "  ")?;
             write!(formatter.labeled("branch"), "{branch_name}@{remote_name}")?;
             writeln!(formatter)?;
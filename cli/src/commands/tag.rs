@@ -12,18 +12,61 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+use clap::builder::NonEmptyStringValueParser;
+use jj_lib::git;
+use jj_lib::object_id::ObjectId;
+use jj_lib::repo::{Repo, TagError};
 use jj_lib::str_util::StringPattern;
 
-use crate::cli_util::{parse_string_pattern, CommandError, CommandHelper};
+use crate::cli_util::{
+    parse_string_pattern, user_error, user_error_with_hint, CommandError, CommandHelper,
+    RevisionArg,
+};
 use crate::ui::Ui;
 
 /// Manage tags.
+///
+/// Tags are exported to Git as lightweight tags. Annotated tags imported from
+/// Git can only be moved or deleted with `--force`, since their tag objects
+/// would be lost.
 #[derive(clap::Subcommand, Clone, Debug)]
 pub enum TagCommand {
+    #[command(visible_alias("c"))]
+    Create(TagCreateArgs),
+    #[command(visible_alias("d"))]
+    Delete(TagDeleteArgs),
     #[command(visible_alias("l"))]
     List(TagListArgs),
 }
 
+/// Create a new tag.
+#[derive(clap::Args, Clone, Debug)]
+pub struct TagCreateArgs {
+    /// The tag's target revision.
+    #[arg(long, short)]
+    revision: Option<RevisionArg>,
+
+    /// Move the tag if it already exists, even if it's an annotated tag
+    #[arg(long)]
+    force: bool,
+
+    /// The tag to create.
+    #[arg(value_parser = NonEmptyStringValueParser::new())]
+    name: String,
+}
+
+/// Delete existing tags.
+#[derive(clap::Args, Clone, Debug)]
+pub struct TagDeleteArgs {
+    /// Delete annotated tags too
+    #[arg(long)]
+    force: bool,
+
+    /// The tags to delete.
+    #[arg(required = true, value_parser = NonEmptyStringValueParser::new())]
+    names: Vec<String>,
+}
+
 /// List tags.
 #[derive(clap::Args, Clone, Debug)]
 pub struct TagListArgs {
@@ -42,10 +85,89 @@ pub fn cmd_tag(
     subcommand: &TagCommand,
 ) -> Result<(), CommandError> {
     match subcommand {
+        TagCommand::Create(sub_args) => cmd_tag_create(ui, command, sub_args),
+        TagCommand::Delete(sub_args) => cmd_tag_delete(ui, command, sub_args),
         TagCommand::List(sub_args) => cmd_tag_list(ui, command, sub_args),
     }
 }
 
+fn annotated_tag_error(name: &str, hint: &str) -> CommandError {
+    user_error_with_hint(
+        format!("Tag {name} is an annotated tag imported from Git"),
+        hint,
+    )
+}
+
+fn cmd_tag_create(
+    ui: &mut Ui,
+    command: &CommandHelper,
+    args: &TagCreateArgs,
+) -> Result<(), CommandError> {
+    let mut workspace_command = command.workspace_helper(ui)?;
+    let target_commit =
+        workspace_command.resolve_single_rev(args.revision.as_deref().unwrap_or("@"), ui)?;
+    let repo = workspace_command.repo();
+    let name = &args.name;
+    if !args.force && repo.view().get_tag(name).is_present() {
+        if git::is_annotated_tag(repo.store(), name) {
+            return Err(annotated_tag_error(
+                name,
+                "Use --force to replace it with a lightweight tag.",
+            ));
+        }
+        return Err(user_error_with_hint(
+            format!("Tag already exists: {name}"),
+            "Use --force to move it.",
+        ));
+    }
+    if !args.force && workspace_command.case_insensitive_refs() {
+        if let Some(other) = repo.view().tag_names_ignoring_case(name).first() {
+            return Err(user_error_with_hint(
+                format!("Tag name {name} differs only by case from tag {other}"),
+                "Use --force to create it anyway.",
+            ));
+        }
+    }
+
+    let mut tx = workspace_command.start_transaction();
+    tx.mut_repo()
+        .create_tag(name, target_commit.id(), args.force)
+        .map_err(user_error)?;
+    tx.finish(
+        ui,
+        format!(
+            "create tag {name} pointing to commit {}",
+            target_commit.id().hex()
+        ),
+    )?;
+    Ok(())
+}
+
+fn cmd_tag_delete(
+    ui: &mut Ui,
+    command: &CommandHelper,
+    args: &TagDeleteArgs,
+) -> Result<(), CommandError> {
+    let mut workspace_command = command.workspace_helper(ui)?;
+    let repo = workspace_command.repo();
+    for name in &args.names {
+        if repo.view().get_tag(name).is_absent() {
+            return Err(user_error(TagError::NotFound(name.clone())));
+        }
+        if !args.force && git::is_annotated_tag(repo.store(), name) {
+            return Err(annotated_tag_error(name, "Use --force to delete it."));
+        }
+    }
+
+    let mut tx = workspace_command.start_transaction();
+    for name in &args.names {
+        tx.mut_repo().delete_tag(name).map_err(user_error)?;
+    }
+    let tag_term = if args.names.len() == 1 { "tag" } else { "tags" };
+    tx.finish(ui, format!("delete {tag_term} {}", args.names.join(", ")))?;
+    Ok(())
+}
+
 fn cmd_tag_list(
     ui: &mut Ui,
     command: &CommandHelper,
@@ -68,6 +68,8 @@ pub(crate) fn cmd_untrack(
         base_ignores,
         fsmonitor_kind: command.settings().fsmonitor_kind()?,
         progress: None,
+        on_change: None,
+        cancel: None,
         max_new_file_size: command.settings().max_new_file_size()?,
     })?;
     if wc_tree_id != *new_commit.tree_id() {
@@ -101,7 +103,7 @@ Make sure they're ignored, then try again.",
     if num_rebased > 0 {
         writeln!(ui.stderr(), "Rebased {num_rebased} descendant commits")?;
     }
-    let repo = tx.commit("untrack paths");
+    let repo = tx.commit("untrack paths")?;
     locked_ws.finish(repo.op_id().clone())?;
     Ok(())
 }
@@ -12,22 +12,27 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+use std::collections::HashSet;
 use std::io::Write;
 use std::slice;
 use std::time::{Duration, SystemTime};
 
 use clap::Subcommand;
 use clap_complete::Shell;
+use jj_lib::file_util;
 use jj_lib::repo::Repo;
+use jj_lib::revset::RevsetExpression;
 use tracing::instrument;
 
-use crate::cli_util::{user_error, CommandError, CommandHelper};
-use crate::ui::Ui;
+use crate::cli_util::{short_change_hash, user_error, CommandError, CommandHelper};
+use crate::ui::{Ui, UiWarning, WarningCode};
 
 /// Infrequently used commands such as for generating shell completions
 #[derive(Subcommand, Clone, Debug)]
 pub(crate) enum UtilCommand {
     Completion(UtilCompletionArgs),
+    #[command(hide = true)]
+    CompletionData(UtilCompletionDataArgs),
     Gc(UtilGcArgs),
     Mangen(UtilMangenArgs),
     MarkdownHelp(UtilMarkdownHelp),
@@ -64,6 +69,35 @@ pub(crate) struct UtilCompletionArgs {
     zsh: bool,
 }
 
+/// Print candidates for dynamic shell completion, one per line
+///
+/// This is called by the scripts printed by `jj util completion`. It never
+/// snapshots the working copy and only loads the parts of the repo needed for
+/// the requested kind of candidates.
+#[derive(clap::Args, Clone, Debug)]
+pub(crate) struct UtilCompletionDataArgs {
+    /// The kind of candidates to print
+    #[arg(long, value_enum)]
+    kind: CompletionDataKind,
+    /// Only print candidates starting with this string
+    #[arg(long, default_value = "")]
+    prefix: String,
+}
+
+#[derive(clap::ValueEnum, Clone, Copy, Debug, Eq, PartialEq)]
+enum CompletionDataKind {
+    /// Local branch names
+    Branches,
+    /// Local branch names and change ids of recent commits
+    Revisions,
+    /// Files in the working-copy commit
+    Paths,
+}
+
+/// Number of recent commits whose change ids are suggested as revisions. Only
+/// that many commits are walked, even if few of them match the prefix.
+const COMPLETION_DATA_MAX_COMMITS: usize = 100;
+
 /// Run backend-dependent garbage collection.
 #[derive(clap::Args, Clone, Debug)]
 pub(crate) struct UtilGcArgs {
@@ -77,6 +111,11 @@ pub(crate) struct UtilGcArgs {
     /// release.
     #[arg(long)]
     expire: Option<String>,
+    /// Only print the number of operations and views that would be pruned
+    ///
+    /// Objects in the commit backend aren't included.
+    #[arg(long)]
+    dry_run: bool,
 }
 
 /// Print a ROFF (manpage)
@@ -99,6 +138,7 @@ pub(crate) fn cmd_util(
 ) -> Result<(), CommandError> {
     match subcommand {
         UtilCommand::Completion(args) => cmd_util_completion(ui, command, args),
+        UtilCommand::CompletionData(args) => cmd_util_completion_data(ui, command, args),
         UtilCommand::Gc(args) => cmd_util_gc(ui, command, args),
         UtilCommand::Mangen(args) => cmd_util_mangen(ui, command, args),
         UtilCommand::MarkdownHelp(args) => cmd_util_markdownhelp(ui, command, args),
@@ -113,12 +153,15 @@ fn cmd_util_completion(
 ) -> Result<(), CommandError> {
     let mut app = command.app().clone();
     let warn = |shell| {
-        writeln!(
-            ui.warning(),
-            "Warning: `jj util completion --{shell}` will be removed in a future version, and \
-             this will be a hard error"
-        )?;
-        writeln!(ui.hint(), "Hint: Use `jj util completion {shell}` instead")
+        let warning = UiWarning::new(
+            WarningCode::DeprecatedCommand,
+            format!(
+                "`jj util completion --{shell}` will be removed in a future version, and this \
+                 will be a hard error"
+            ),
+        )
+        .with_hint(format!("Use `jj util completion {shell}` instead"));
+        ui.write_warning(&warning)
     };
     let mut buf = vec![];
     let shell = match (args.shell, args.fish, args.zsh, args.bash) {
@@ -144,10 +187,100 @@ fn cmd_util_completion(
         }
     };
     clap_complete::generate(shell, &mut app, "jj", &mut buf);
+    match shell {
+        Shell::Bash => buf.extend_from_slice(BASH_DYNAMIC_COMPLETION.as_bytes()),
+        Shell::Fish => buf.extend_from_slice(FISH_DYNAMIC_COMPLETION.as_bytes()),
+        _ => {}
+    }
     ui.stdout_formatter().write_all(&buf)?;
     Ok(())
 }
 
+/// Wraps the generated `_jj` function to complete revision arguments with
+/// `jj util completion-data`.
+const BASH_DYNAMIC_COMPLETION: &str = r#"
+_jj_dynamic() {
+    local cur="${COMP_WORDS[COMP_CWORD]}"
+    case "${COMP_WORDS[COMP_CWORD-1]}" in
+        -r|--revision|--revisions|-d|--destination)
+            local IFS=$'\n'
+            COMPREPLY=($(jj util completion-data --kind revisions --prefix "${cur}" 2>/dev/null))
+            ;;
+        *)
+            _jj "$@"
+            ;;
+    esac
+}
+
+complete -F _jj_dynamic -o bashdefault -o default jj
+"#;
+
+const FISH_DYNAMIC_COMPLETION: &str = r#"
+complete -c jj -s r -l revision -l revisions -x -a '(jj util completion-data --kind revisions --prefix (commandline -ct) 2>/dev/null)'
+complete -c jj -s d -l destination -x -a '(jj util completion-data --kind revisions --prefix (commandline -ct) 2>/dev/null)'
+"#;
+
+fn cmd_util_completion_data(
+    ui: &mut Ui,
+    command: &CommandHelper,
+    args: &UtilCompletionDataArgs,
+) -> Result<(), CommandError> {
+    // Skip WorkspaceCommandHelper: completion must be fast, so don't snapshot
+    // the working copy or parse templates and revset aliases.
+    let workspace = command.load_workspace()?;
+    let op = command.resolve_operation(ui, workspace.repo_loader())?;
+    let repo = workspace.repo_loader().load_at(&op)?;
+    let mut candidates = vec![];
+    if matches!(
+        args.kind,
+        CompletionDataKind::Branches | CompletionDataKind::Revisions
+    ) {
+        candidates.extend(
+            repo.view()
+                .local_branches()
+                .map(|(name, _)| name.to_owned()),
+        );
+    }
+    if args.kind == CompletionDataKind::Revisions {
+        // Don't evaluate the whole revset. Ancestors of the heads are yielded
+        // newest first, so the first few are the recent commits.
+        let expression = RevsetExpression::visible_heads()
+            .ancestors()
+            .minus(&RevsetExpression::root());
+        let revset = expression.evaluate_programmatic(repo.as_ref())?;
+        let mut seen = HashSet::new();
+        candidates.extend(
+            revset
+                .commit_change_ids()
+                .take(COMPLETION_DATA_MAX_COMMITS)
+                .map(|(_, change_id)| short_change_hash(&change_id))
+                .filter(|hash| seen.insert(hash.clone())),
+        );
+    }
+    if args.kind == CompletionDataKind::Paths {
+        if let Some(wc_commit_id) = repo.view().get_wc_commit_id(workspace.workspace_id()) {
+            let tree = repo.store().get_commit(wc_commit_id)?.tree()?;
+            // The completion scripts read the candidates as text, so paths that
+            // aren't valid UTF-8 are skipped
+            candidates.extend(tree.entries().filter_map(|(path, _)| {
+                file_util::relative_path(
+                    command.cwd(),
+                    &path.to_fs_path(workspace.workspace_root()),
+                )
+                .to_str()
+                .map(ToOwned::to_owned)
+            }));
+        }
+    }
+    let mut formatter = ui.stdout_formatter();
+    for candidate in candidates {
+        if candidate.starts_with(&args.prefix) {
+            writeln!(formatter, "{candidate}")?;
+        }
+    }
+    Ok(())
+}
+
 fn cmd_util_gc(
     ui: &mut Ui,
     command: &CommandHelper,
@@ -166,6 +299,19 @@ fn cmd_util_gc(
     let workspace_command = command.workspace_helper(ui)?;
 
     let repo = workspace_command.repo();
+    if args.dry_run {
+        let stats = repo
+            .op_store()
+            .gc_dry_run(slice::from_ref(repo.op_id()), keep_newer)?;
+        writeln!(
+            ui.stderr(),
+            "Would prune {} operations and {} views",
+            stats.num_operations,
+            stats.num_views
+        )?;
+        writeln!(ui.stderr(), "Dry-run requested, no changes were made.")?;
+        return Ok(());
+    }
     repo.op_store()
         .gc(slice::from_ref(repo.op_id()), keep_newer)?;
     repo.store().gc(repo.index(), keep_newer)?;
@@ -25,13 +25,13 @@ use jj_lib::op_store::{OpStoreError, WorkspaceId};
 use jj_lib::operation::Operation;
 use jj_lib::repo::{ReadonlyRepo, Repo};
 use jj_lib::rewrite::merge_commit_trees;
+use jj_lib::working_copy::SnapshotOptions;
 use jj_lib::workspace::Workspace;
 use tracing::instrument;
 
 use crate::cli_util::{
-    self, check_stale_working_copy, internal_error_with_message, print_checkout_stats,
-    short_commit_hash, user_error, CommandError, CommandHelper, RevisionArg, WorkingCopyFreshness,
-    WorkspaceCommandHelper,
+    self, internal_error_with_message, print_checkout_stats, short_commit_hash, user_error,
+    CommandError, CommandHelper, RevisionArg, WorkspaceCommandHelper,
 };
 use crate::ui::Ui;
 
@@ -300,7 +300,7 @@ fn create_and_check_out_recovery_commit(
         .new_commit(command.settings(), vec![commit_id.clone()], tree_id.clone())
         .write()?;
     mut_repo.set_wc_commit(workspace_id, new_commit.id().clone())?;
-    let repo = tx.commit("recovery commit");
+    let repo = tx.commit("recovery commit")?;
 
     locked_workspace.locked_wc().reset_to_empty()?;
     locked_workspace.finish(repo.op_id().clone())?;
@@ -352,62 +352,96 @@ fn cmd_workspace_update_stale(
     command: &CommandHelper,
     _args: &WorkspaceUpdateStaleArgs,
 ) -> Result<(), CommandError> {
-    // Snapshot the current working copy on top of the last known working-copy
-    // operation, then merge the concurrent operations. The wc_commit_id of the
-    // merged repo wouldn't change because the old one wins, but it's probably
-    // fine if we picked the new wc_commit_id.
-    let known_wc_commit = {
+    // The working-copy commit as of the last working-copy operation, which is
+    // what the files on disk are based on.
+    let recorded_wc_commit = {
         let (mut workspace_command, recovered) = for_stale_working_copy(ui, command)?;
-        workspace_command.maybe_snapshot(ui)?;
-
         if recovered {
             // We have already recovered from the situation that prompted the user to run
             // this command, and it is known that the workspace is not stale
             // (since we just updated it), so we can return early.
+            workspace_command.maybe_snapshot(ui)?;
             return Ok(());
         }
-
-        let wc_commit_id = workspace_command.get_wc_commit_id().unwrap();
+        let Some(wc_commit_id) = workspace_command.get_wc_commit_id() else {
+            return Err(user_error("Nothing checked out in this workspace"));
+        };
         workspace_command.repo().store().get_commit(wc_commit_id)?
     };
     let mut workspace_command = command.workspace_helper_no_snapshot(ui)?;
+    workspace_command.check_working_copy_writable()?;
+    let Some(target_wc_commit_id) = workspace_command.get_wc_commit_id() else {
+        return Err(user_error("Nothing checked out in this workspace"));
+    };
+    let target_wc_commit = workspace_command
+        .repo()
+        .store()
+        .get_commit(target_wc_commit_id)?;
+    if target_wc_commit.id() == recorded_wc_commit.id() {
+        workspace_command.maybe_snapshot(ui)?;
+        writeln!(
+            ui.stderr(),
+            "Nothing to do (the working copy is not stale)."
+        )?;
+        return Ok(());
+    }
 
-    let repo = workspace_command.repo().clone();
-    let (mut locked_ws, desired_wc_commit) =
-        workspace_command.unchecked_start_working_copy_mutation()?;
-    match check_stale_working_copy(locked_ws.locked_wc(), &desired_wc_commit, &repo)? {
-        WorkingCopyFreshness::Fresh | WorkingCopyFreshness::Updated(_) => {
-            writeln!(
-                ui.stderr(),
-                "Nothing to do (the working copy is not stale)."
-            )?;
-        }
-        WorkingCopyFreshness::WorkingCopyStale | WorkingCopyFreshness::SiblingOperation => {
-            // The same check as start_working_copy_mutation(), but with the stale
-            // working-copy commit.
-            if known_wc_commit.tree_id() != locked_ws.locked_wc().old_tree_id() {
-                return Err(user_error("Concurrent working copy operation. Try again."));
-            }
-            let stats = locked_ws
-                .locked_wc()
-                .check_out(&desired_wc_commit)
-                .map_err(|err| {
-                    internal_error_with_message(
-                        format!(
-                            "Failed to check out commit {}",
-                            desired_wc_commit.id().hex()
-                        ),
-                        err,
-                    )
-                })?;
-            locked_ws.finish(repo.op_id().clone())?;
-            write!(ui.stderr(), "Working copy now at: ")?;
-            ui.stderr_formatter().with_label("working_copy", |fmt| {
-                workspace_command.write_commit_summary(fmt, &desired_wc_commit)
-            })?;
-            writeln!(ui.stderr())?;
-            print_checkout_stats(ui, stats, &desired_wc_commit)?;
-        }
+    let base_ignores = workspace_command.base_ignores();
+    let workspace_id = workspace_command.workspace_id().clone();
+    let mut tx = workspace_command.start_transaction().into_inner();
+    let (mut locked_ws, _) = workspace_command.unchecked_start_working_copy_mutation()?;
+    // The same check as start_working_copy_mutation(), but with the stale
+    // working-copy commit.
+    if recorded_wc_commit.tree_id() != locked_ws.locked_wc().old_tree_id() {
+        return Err(user_error("Concurrent working copy operation. Try again."));
     }
+    // Snapshot the files on disk so no changes are lost.
+    let snapshot_tree_id = locked_ws.locked_wc().snapshot(SnapshotOptions {
+        base_ignores,
+        fsmonitor_kind: command.settings().fsmonitor_kind()?,
+        progress: None,
+        on_change: None,
+        cancel: None,
+        max_new_file_size: command.settings().max_new_file_size()?,
+    })?;
+    let new_wc_commit = if snapshot_tree_id == *recorded_wc_commit.tree_id() {
+        target_wc_commit
+    } else {
+        // Apply the changes made on disk to the commit the working copy should
+        // be at.
+        let snapshot_tree = tx.repo().store().get_root_tree(&snapshot_tree_id)?;
+        let new_tree = target_wc_commit
+            .tree()?
+            .merge(&recorded_wc_commit.tree()?, &snapshot_tree)?;
+        let mut_repo = tx.mut_repo();
+        let new_wc_commit = mut_repo
+            .rewrite_commit(command.settings(), &target_wc_commit)
+            .set_tree_id(new_tree.id())
+            .write()?;
+        mut_repo.set_wc_commit(workspace_id, new_wc_commit.id().clone())?;
+        mut_repo.rebase_descendants(command.settings())?;
+        new_wc_commit
+    };
+    let repo = if tx.mut_repo().has_changes() {
+        tx.commit("update stale working copy")?
+    } else {
+        tx.base_repo().clone()
+    };
+    let stats = locked_ws
+        .locked_wc()
+        .check_out(&new_wc_commit)
+        .map_err(|err| {
+            internal_error_with_message(
+                format!("Failed to check out commit {}", new_wc_commit.id().hex()),
+                err,
+            )
+        })?;
+    locked_ws.finish(repo.op_id().clone())?;
+    write!(ui.stderr(), "Working copy now at: ")?;
+    ui.stderr_formatter().with_label("working_copy", |fmt| {
+        workspace_command.write_commit_summary(fmt, &new_wc_commit)
+    })?;
+    writeln!(ui.stderr())?;
+    print_checkout_stats(ui, stats, &new_wc_commit)?;
     Ok(())
 }
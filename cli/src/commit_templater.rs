@@ -12,23 +12,31 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+use std::cell::RefCell;
 use std::cmp::max;
 use std::collections::HashMap;
-use std::io;
 use std::rc::Rc;
+use std::sync::Arc;
+use std::{io, mem};
 
 use itertools::Itertools as _;
 use jj_lib::backend::{ChangeId, CommitId};
 use jj_lib::commit::Commit;
+use jj_lib::git;
 use jj_lib::hex_util::to_reverse_hex;
 use jj_lib::id_prefix::IdPrefixContext;
+use jj_lib::matchers::FilesMatcher;
+use jj_lib::merged_tree::DiffSummary;
 use jj_lib::object_id::ObjectId as _;
 use jj_lib::op_store::{RefTarget, WorkspaceId};
 use jj_lib::repo::Repo;
-use jj_lib::{git, rewrite};
+use jj_lib::repo_path::RepoPathBuf;
+use jj_lib::signing::{SigStatus, SignResult, Verification};
 use once_cell::unsync::OnceCell;
 
-use crate::formatter::Formatter;
+use crate::cli_util::{CommandError, WorkspaceCommandHelper};
+use crate::diff_util::{self, TemplateDiffFormat};
+use crate::formatter::{FormatRecorder, Formatter};
 use crate::template_builder::{
     self, BuildContext, CoreTemplatePropertyKind, IntoTemplateProperty, TemplateLanguage,
 };
@@ -36,8 +44,8 @@ use crate::template_parser::{
     self, FunctionCallNode, TemplateAliasesMap, TemplateParseError, TemplateParseResult,
 };
 use crate::templater::{
-    self, IntoTemplate, PlainTextFormattedProperty, Template, TemplateFunction, TemplateProperty,
-    TemplatePropertyFn,
+    self, IntoTemplate, Literal, PlainTextFormattedProperty, Template, TemplateFunction,
+    TemplateProperty, TemplatePropertyFn,
 };
 use crate::text_util;
 
@@ -46,6 +54,8 @@ struct CommitTemplateLanguage<'repo, 'b> {
     workspace_id: &'b WorkspaceId,
     id_prefix_context: &'repo IdPrefixContext,
     keyword_cache: CommitKeywordCache,
+    signature_batch: Rc<SignatureBatch>,
+    diff_renderer: Option<&'repo WorkspaceCommandHelper>,
 }
 
 impl<'repo> TemplateLanguage<'repo> for CommitTemplateLanguage<'repo, '_> {
@@ -98,6 +108,21 @@ impl<'repo> TemplateLanguage<'repo> for CommitTemplateLanguage<'repo, '_> {
             CommitTemplatePropertyKind::ShortestIdPrefix(property) => {
                 build_shortest_id_prefix_method(self, build_ctx, property, function)
             }
+            CommitTemplatePropertyKind::CryptographicSignature(property) => {
+                build_cryptographic_signature_method(self, build_ctx, property, function)
+            }
+            CommitTemplatePropertyKind::TreeDiff(property) => {
+                build_tree_diff_method(self, build_ctx, property, function)
+            }
+            CommitTemplatePropertyKind::RepoPathList(property) => {
+                template_builder::build_formattable_list_method(
+                    self,
+                    build_ctx,
+                    property,
+                    function,
+                    |item| self.wrap_repo_path(item),
+                )
+            }
         }
     }
 }
@@ -146,6 +171,57 @@ impl<'repo> CommitTemplateLanguage<'repo, '_> {
     ) -> CommitTemplatePropertyKind<'repo> {
         CommitTemplatePropertyKind::ShortestIdPrefix(Box::new(property))
     }
+
+    fn wrap_cryptographic_signature(
+        &self,
+        property: impl TemplateProperty<Commit, Output = CryptographicSignature> + 'repo,
+    ) -> CommitTemplatePropertyKind<'repo> {
+        CommitTemplatePropertyKind::CryptographicSignature(Box::new(property))
+    }
+
+    fn wrap_tree_diff(
+        &self,
+        property: impl TemplateProperty<Commit, Output = TreeDiff> + 'repo,
+    ) -> CommitTemplatePropertyKind<'repo> {
+        CommitTemplatePropertyKind::TreeDiff(Box::new(property))
+    }
+
+    fn wrap_tree_diff_template(
+        &self,
+        diff_property: impl TemplateProperty<Commit, Output = TreeDiff> + 'repo,
+        format_property: impl TemplateProperty<Commit, Output = TemplateDiffFormat> + 'repo,
+    ) -> CommitTemplatePropertyKind<'repo> {
+        let repo = self.repo;
+        let renderer = self.diff_renderer;
+        let property: Box<dyn TemplateProperty<Commit, Output = TreeDiffTemplate<'repo>> + 'repo> =
+            Box::new(TemplateFunction::new(
+                (diff_property, format_property),
+                move |(diff, format)| TreeDiffTemplate {
+                    diff,
+                    format,
+                    repo,
+                    renderer,
+                },
+            ));
+        self.wrap_template(property.into_template())
+    }
+
+    fn wrap_repo_path(
+        &self,
+        property: impl TemplateProperty<Commit, Output = RepoPathBuf> + 'repo,
+    ) -> CommitTemplatePropertyKind<'repo> {
+        // There are no methods on paths yet, so render them as strings.
+        self.wrap_string(TemplateFunction::new(property, |path| {
+            path.as_internal_file_string().into_owned()
+        }))
+    }
+
+    fn wrap_repo_path_list(
+        &self,
+        property: impl TemplateProperty<Commit, Output = Vec<RepoPathBuf>> + 'repo,
+    ) -> CommitTemplatePropertyKind<'repo> {
+        CommitTemplatePropertyKind::RepoPathList(Box::new(property))
+    }
 }
 
 enum CommitTemplatePropertyKind<'repo> {
@@ -156,6 +232,11 @@ enum CommitTemplatePropertyKind<'repo> {
     RefNameList(Box<dyn TemplateProperty<Commit, Output = Vec<RefName>> + 'repo>),
     CommitOrChangeId(Box<dyn TemplateProperty<Commit, Output = CommitOrChangeId> + 'repo>),
     ShortestIdPrefix(Box<dyn TemplateProperty<Commit, Output = ShortestIdPrefix> + 'repo>),
+    CryptographicSignature(
+        Box<dyn TemplateProperty<Commit, Output = CryptographicSignature> + 'repo>,
+    ),
+    TreeDiff(Box<dyn TemplateProperty<Commit, Output = TreeDiff> + 'repo>),
+    RepoPathList(Box<dyn TemplateProperty<Commit, Output = Vec<RepoPathBuf>> + 'repo>),
 }
 
 impl<'repo> IntoTemplateProperty<'repo, Commit> for CommitTemplatePropertyKind<'repo> {
@@ -172,6 +253,15 @@ impl<'repo> IntoTemplateProperty<'repo, Commit> for CommitTemplatePropertyKind<'
             }
             CommitTemplatePropertyKind::CommitOrChangeId(_) => None,
             CommitTemplatePropertyKind::ShortestIdPrefix(_) => None,
+            CommitTemplatePropertyKind::CryptographicSignature(property) => {
+                Some(Box::new(TemplateFunction::new(property, |signature| {
+                    signature.commit.is_signed()
+                })))
+            }
+            CommitTemplatePropertyKind::TreeDiff(_) => None,
+            CommitTemplatePropertyKind::RepoPathList(property) => {
+                Some(Box::new(TemplateFunction::new(property, |l| !l.is_empty())))
+            }
         }
     }
 
@@ -207,6 +297,11 @@ impl<'repo> IntoTemplateProperty<'repo, Commit> for CommitTemplatePropertyKind<'
             CommitTemplatePropertyKind::ShortestIdPrefix(property) => {
                 Some(property.into_template())
             }
+            CommitTemplatePropertyKind::CryptographicSignature(property) => {
+                Some(property.into_template())
+            }
+            CommitTemplatePropertyKind::TreeDiff(_) => None,
+            CommitTemplatePropertyKind::RepoPathList(property) => Some(property.into_template()),
         }
     }
 }
@@ -253,11 +348,20 @@ fn build_commit_keyword<'repo>(
 
 fn build_commit_method<'repo>(
     language: &CommitTemplateLanguage<'repo, '_>,
-    _build_ctx: &BuildContext<CommitTemplatePropertyKind<'repo>>,
+    build_ctx: &BuildContext<CommitTemplatePropertyKind<'repo>>,
     self_property: impl TemplateProperty<Commit, Output = Commit> + 'repo,
     function: &FunctionCallNode,
 ) -> TemplateParseResult<CommitTemplatePropertyKind<'repo>> {
-    if let Some(property) = build_commit_keyword_opt(language, self_property, function.name) {
+    if function.name == "metadata" {
+        let [key_node] = template_parser::expect_exact_arguments(function)?;
+        let key_property =
+            template_builder::expect_plain_text_expression(language, build_ctx, key_node)?;
+        Ok(language.wrap_string(TemplateFunction::new(
+            (self_property, key_property),
+            |(commit, key)| commit.metadata().get(&key).cloned().unwrap_or_default(),
+        )))
+    } else if let Some(property) = build_commit_keyword_opt(language, self_property, function.name)
+    {
         template_parser::expect_no_arguments(function)?;
         Ok(property)
     } else {
@@ -287,6 +391,8 @@ fn build_commit_keyword_opt<'repo>(
     let repo = language.repo;
     let cache = &language.keyword_cache;
     let property = match name {
+        // The commit itself, so methods that take arguments can be called on it
+        "commit" => language.wrap_commit(property),
         "description" => language.wrap_string(wrap_fn(property, |commit| {
             text_util::complete_newline(commit.description())
         })),
@@ -372,12 +478,28 @@ fn build_commit_keyword_opt<'repo>(
             if let [parent] = &commit.parents()[..] {
                 return parent.tree_id() == commit.tree_id();
             }
-            let parent_tree = rewrite::merge_commit_trees(repo, &commit.parents()).unwrap();
+            let parent_tree = repo.merged_parent_tree(commit).unwrap();
             *commit.tree_id() == parent_tree.id()
         })),
         "root" => language.wrap_boolean(wrap_fn(property, move |commit| {
             commit.id() == repo.store().root_commit_id()
         })),
+        "diff" => language.wrap_tree_diff(wrap_fn(property, |commit| {
+            // TODO: propagate errors once the template language can
+            TreeDiff {
+                commit: commit.clone(),
+                summary: repo.diff_summary(commit).unwrap(),
+            }
+        })),
+        "signature" => {
+            let batch = language.signature_batch.clone();
+            language.wrap_cryptographic_signature(wrap_fn(property, move |commit| {
+                CryptographicSignature {
+                    commit: commit.clone(),
+                    batch: batch.clone(),
+                }
+            }))
+        }
         _ => return None,
     };
     Some(property)
@@ -710,10 +832,335 @@ fn build_shortest_id_prefix_method<'repo>(
     Ok(property)
 }
 
+/// Signed commits that are about to be rendered.
+///
+/// Verifying a signature can be slow (e.g. a `gpg` process has to be started),
+/// so when a template asks for the verification of a commit, the signatures of
+/// all pending commits are verified in one batch. Nothing is verified if the
+/// template doesn't use the signatures.
+#[derive(Debug, Default)]
+pub struct SignatureBatch {
+    pending: RefCell<Vec<Commit>>,
+}
+
+impl SignatureBatch {
+    /// Adds commits that will be rendered next.
+    pub fn extend<'a>(&self, commits: impl IntoIterator<Item = &'a Commit>) {
+        self.pending.borrow_mut().extend(
+            commits
+                .into_iter()
+                .filter(|commit| commit.is_signed())
+                .cloned(),
+        );
+    }
+
+    fn verification(&self, commit: &Commit) -> SignResult<Option<Verification>> {
+        if !commit.is_signed() {
+            return Ok(None);
+        }
+        let pending = mem::take(&mut *self.pending.borrow_mut());
+        if !pending.is_empty() {
+            let items = pending
+                .iter()
+                .filter_map(|commit| {
+                    let sig = commit.store_commit().secure_sig.as_ref()?;
+                    Some((commit.id(), sig.data.as_slice(), sig.sig.as_slice()))
+                })
+                .collect_vec();
+            // The results are cached by the signer. Errors aren't, so they'll
+            // be reported when the failed commits are rendered.
+            commit.store().signer().verify_batch(&items);
+        }
+        commit.verification()
+    }
+}
+
+/// Signature of a commit, verified when it's first needed.
+#[derive(Clone, Debug)]
+struct CryptographicSignature {
+    commit: Commit,
+    batch: Rc<SignatureBatch>,
+}
+
+impl CryptographicSignature {
+    fn verification(&self) -> Option<Verification> {
+        // TODO: propagate errors once the template language can
+        self.batch.verification(&self.commit).ok().flatten()
+    }
+
+    fn status(&self) -> &'static str {
+        if !self.commit.is_signed() {
+            return "none";
+        }
+        match self.verification().map(|verification| verification.status) {
+            Some(SigStatus::Good) => "good",
+            Some(SigStatus::Unknown) => "unknown",
+            Some(SigStatus::Bad) => "bad",
+            Some(SigStatus::Expired) => "expired",
+            None => "error",
+        }
+    }
+}
+
+impl Template<()> for CryptographicSignature {
+    fn format(&self, _: &(), formatter: &mut dyn Formatter) -> io::Result<()> {
+        let status = self.status();
+        write!(formatter.labeled(status), "{status}")
+    }
+}
+
+fn build_cryptographic_signature_method<'repo>(
+    language: &CommitTemplateLanguage<'repo, '_>,
+    _build_ctx: &BuildContext<CommitTemplatePropertyKind<'repo>>,
+    self_property: impl TemplateProperty<Commit, Output = CryptographicSignature> + 'repo,
+    function: &FunctionCallNode,
+) -> TemplateParseResult<CommitTemplatePropertyKind<'repo>> {
+    let property = match function.name {
+        "status" => {
+            template_parser::expect_no_arguments(function)?;
+            language.wrap_string(TemplateFunction::new(self_property, |signature| {
+                signature.status().to_owned()
+            }))
+        }
+        "key" => {
+            template_parser::expect_no_arguments(function)?;
+            language.wrap_string(TemplateFunction::new(self_property, |signature| {
+                signature
+                    .verification()
+                    .and_then(|verification| verification.key)
+                    .unwrap_or_default()
+            }))
+        }
+        "display" => {
+            template_parser::expect_no_arguments(function)?;
+            language.wrap_string(TemplateFunction::new(self_property, |signature| {
+                signature
+                    .verification()
+                    .and_then(|verification| verification.display)
+                    .unwrap_or_default()
+            }))
+        }
+        _ => {
+            return Err(TemplateParseError::no_such_method(
+                "CryptographicSignature",
+                function,
+            ))
+        }
+    };
+    Ok(property)
+}
+
+/// Changes made by a commit relative to its parents.
+#[derive(Clone, Debug)]
+struct TreeDiff {
+    commit: Commit,
+    summary: Arc<DiffSummary>,
+}
+
+impl TreeDiff {
+    fn render(
+        &self,
+        repo: &dyn Repo,
+        workspace_command: &WorkspaceCommandHelper,
+        format: TemplateDiffFormat,
+        formatter: &mut dyn Formatter,
+    ) -> Result<(), CommandError> {
+        let from_tree = repo.merged_parent_tree(&self.commit)?;
+        let to_tree = self.commit.tree()?;
+        // Only visit the paths that the cached summary says were changed
+        let matcher = FilesMatcher::new(self.summary.paths());
+        diff_util::show_template_diff(
+            formatter,
+            workspace_command,
+            &from_tree,
+            &to_tree,
+            &matcher,
+            &self.commit.copy_sources(),
+            format,
+        )
+    }
+}
+
+/// A `TreeDiff` rendered in one of the diff formats.
+struct TreeDiffTemplate<'repo> {
+    diff: TreeDiff,
+    format: TemplateDiffFormat,
+    repo: &'repo dyn Repo,
+    renderer: Option<&'repo WorkspaceCommandHelper>,
+}
+
+impl Template<()> for TreeDiffTemplate<'_> {
+    fn format(&self, _: &(), formatter: &mut dyn Formatter) -> io::Result<()> {
+        // Templates that are only parsed to be validated have no renderer, and
+        // are never formatted.
+        let Some(workspace_command) = self.renderer else {
+            return Ok(());
+        };
+        let max_size = workspace_command.max_template_diff_size();
+        let mut recorder = LimitedFormatRecorder::new(max_size);
+        let result = self
+            .diff
+            .render(self.repo, workspace_command, self.format, &mut recorder);
+        // Rendering is stopped by a write error once the diff doesn't fit
+        if !recorder.is_full() {
+            result.map_err(command_error_to_io)?;
+        }
+        write_truncated_diff(formatter, &recorder.recorder, max_size)
+    }
+}
+
+/// Records formatted output until it's larger than `max_size` bytes, after
+/// which writes fail, so a diff is only rendered up to where it's cut.
+struct LimitedFormatRecorder {
+    recorder: FormatRecorder,
+    max_size: usize,
+}
+
+impl LimitedFormatRecorder {
+    fn new(max_size: u64) -> Self {
+        LimitedFormatRecorder {
+            recorder: FormatRecorder::new(),
+            max_size: usize::try_from(max_size).unwrap_or(usize::MAX),
+        }
+    }
+
+    /// Whether more than `max_size` bytes have been recorded.
+    fn is_full(&self) -> bool {
+        self.recorder.data().len() > self.max_size
+    }
+}
+
+impl io::Write for LimitedFormatRecorder {
+    fn write(&mut self, data: &[u8]) -> io::Result<usize> {
+        if self.is_full() {
+            return Err(io::Error::other("the diff is larger than the limit"));
+        }
+        // Record one byte past the limit so the diff is known to be cut
+        let room = self.max_size.saturating_add(1) - self.recorder.data().len();
+        io::Write::write(&mut self.recorder, &data[..data.len().min(room)])
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl Formatter for LimitedFormatRecorder {
+    fn raw(&mut self) -> &mut dyn io::Write {
+        self.recorder.raw()
+    }
+
+    fn push_label(&mut self, label: &str) -> io::Result<()> {
+        self.recorder.push_label(label)
+    }
+
+    fn pop_label(&mut self) -> io::Result<()> {
+        self.recorder.pop_label()
+    }
+}
+
+fn command_error_to_io(err: CommandError) -> io::Error {
+    match err {
+        CommandError::BrokenPipe => io::ErrorKind::BrokenPipe.into(),
+        CommandError::UserError { err, .. } | CommandError::InternalError(err) => {
+            io::Error::other(err.to_string())
+        }
+        CommandError::ClapCliError { err, .. } => io::Error::other(err.to_string()),
+        CommandError::ConfigError(message) | CommandError::CliError(message) => {
+            io::Error::other(message)
+        }
+        CommandError::ExitStatus(status) => {
+            io::Error::other(format!("unexpected exit status {status}"))
+        }
+    }
+}
+
+/// Writes the recorded diff up to the last line that fits in `max_size`
+/// bytes. If some of it doesn't fit, a note saying so is written after it.
+fn write_truncated_diff(
+    formatter: &mut dyn Formatter,
+    recorded: &FormatRecorder,
+    max_size: u64,
+) -> io::Result<()> {
+    let data = recorded.data();
+    let max_size = usize::try_from(max_size).unwrap_or(usize::MAX);
+    if data.len() <= max_size {
+        return recorded.replay(formatter);
+    }
+    let end = data[..max_size]
+        .iter()
+        .rposition(|&b| b == b'\n')
+        .map_or(0, |pos| pos + 1);
+    // The labels after the end are still replayed, so they stay balanced
+    recorded.replay_with(formatter, |formatter, range| {
+        formatter.write_all(&data[range.start.min(end)..range.end.min(end)])
+    })?;
+    formatter.with_label("diff", |formatter| {
+        writeln!(
+            formatter.labeled("truncated"),
+            "... diff truncated, use jj show"
+        )
+    })
+}
+
+fn build_tree_diff_method<'repo>(
+    language: &CommitTemplateLanguage<'repo, '_>,
+    build_ctx: &BuildContext<CommitTemplatePropertyKind<'repo>>,
+    self_property: impl TemplateProperty<Commit, Output = TreeDiff> + 'repo,
+    function: &FunctionCallNode,
+) -> TemplateParseResult<CommitTemplatePropertyKind<'repo>> {
+    let property = match function.name {
+        "files" => {
+            template_parser::expect_no_arguments(function)?;
+            language.wrap_repo_path_list(TemplateFunction::new(self_property, |diff| {
+                diff.summary.paths().map(|path| path.to_owned()).collect()
+            }))
+        }
+        "summary" => {
+            template_parser::expect_no_arguments(function)?;
+            language.wrap_tree_diff_template(self_property, Literal(TemplateDiffFormat::Summary))
+        }
+        "stat" => {
+            let [width_node] = template_parser::expect_exact_arguments(function)?;
+            let width_property =
+                template_builder::expect_integer_expression(language, build_ctx, width_node)?;
+            let format_property =
+                TemplateFunction::new(width_property, |width| TemplateDiffFormat::Stat {
+                    width: usize::try_from(width).unwrap_or(0),
+                });
+            language.wrap_tree_diff_template(self_property, format_property)
+        }
+        "git" => {
+            template_parser::expect_no_arguments(function)?;
+            language.wrap_tree_diff_template(self_property, Literal(TemplateDiffFormat::Git))
+        }
+        "color_words" => {
+            template_parser::expect_no_arguments(function)?;
+            language.wrap_tree_diff_template(self_property, Literal(TemplateDiffFormat::ColorWords))
+        }
+        _ => return Err(TemplateParseError::no_such_method("TreeDiff", function)),
+    };
+    Ok(property)
+}
+
+impl Template<()> for RepoPathBuf {
+    fn format(&self, _: &(), formatter: &mut dyn Formatter) -> io::Result<()> {
+        write!(formatter, "{}", self.as_internal_file_string())
+    }
+}
+
+impl Template<()> for Vec<RepoPathBuf> {
+    fn format(&self, _: &(), formatter: &mut dyn Formatter) -> io::Result<()> {
+        templater::format_joined(&(), formatter, self, " ")
+    }
+}
+
 pub fn parse<'repo>(
     repo: &'repo dyn Repo,
     workspace_id: &WorkspaceId,
     id_prefix_context: &'repo IdPrefixContext,
+    signature_batch: Rc<SignatureBatch>,
+    diff_renderer: Option<&'repo WorkspaceCommandHelper>,
     template_text: &str,
     aliases_map: &TemplateAliasesMap,
 ) -> TemplateParseResult<Box<dyn Template<Commit> + 'repo>> {
@@ -722,6 +1169,8 @@ pub fn parse<'repo>(
         workspace_id,
         id_prefix_context,
         keyword_cache: CommitKeywordCache::default(),
+        signature_batch,
+        diff_renderer,
     };
     let node = template_parser::parse(template_text, aliases_map)?;
     template_builder::build(&language, &node)
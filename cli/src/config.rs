@@ -18,6 +18,7 @@ use std::path::{Path, PathBuf};
 use std::process::Command;
 use std::{env, fmt};
 
+use chrono::DateTime;
 use config::Source;
 use itertools::Itertools;
 use jj_lib::settings::ConfigResultExt as _;
@@ -32,6 +33,8 @@ pub enum ConfigError {
     AmbiguousSource(PathBuf, PathBuf),
     #[error(transparent)]
     ConfigCreateError(#[from] std::io::Error),
+    #[error("${name} is not a valid RFC 3339 timestamp: {value:?}")]
+    InvalidEnvTimestamp { name: &'static str, value: String },
 }
 
 #[derive(Clone, Debug, PartialEq, Eq)]
@@ -394,6 +397,19 @@ fn env_overrides() -> config::Config {
     builder.build().unwrap()
 }
 
+/// Checks that the timestamps set by environment variables can be parsed.
+/// Otherwise, they would silently be ignored in favor of the current time.
+pub fn check_env_timestamps() -> Result<(), ConfigError> {
+    for name in ["JJ_TIMESTAMP", "JJ_OP_TIMESTAMP"] {
+        if let Ok(value) = env::var(name) {
+            if DateTime::parse_from_rfc3339(&value).is_err() {
+                return Err(ConfigError::InvalidEnvTimestamp { name, value });
+            }
+        }
+    }
+    Ok(())
+}
+
 fn read_config_file(path: &Path) -> Result<config::Config, config::ConfigError> {
     config::Config::builder()
         .add_source(
@@ -13,31 +13,35 @@
 // limitations under the License.
 
 use std::cmp::max;
-use std::collections::VecDeque;
+use std::collections::{BTreeMap, HashMap, HashSet, VecDeque};
 use std::io;
 use std::ops::Range;
+use std::slice;
 
 use futures::{try_join, Stream, StreamExt};
 use itertools::Itertools;
 use jj_lib::backend::{BackendResult, TreeValue};
 use jj_lib::commit::Commit;
-use jj_lib::conflicts::{materialize_tree_value, MaterializedTreeValue};
-use jj_lib::diff::{Diff, DiffHunk};
+use jj_lib::conflicts::{self, materialize_tree_value, MaterializedTreeValue};
+use jj_lib::diff::{DiffHunk, WhitespaceMode};
 use jj_lib::files::DiffLine;
-use jj_lib::matchers::Matcher;
+use jj_lib::gitattributes::GitAttributes;
+use jj_lib::matchers::{EverythingMatcher, GlobsMatcher, Matcher, Visit, VisitFiles};
 use jj_lib::merge::MergedTreeValue;
 use jj_lib::merged_tree::{MergedTree, TreeDiffStream};
 use jj_lib::object_id::ObjectId;
 use jj_lib::repo::Repo;
 use jj_lib::repo_path::{RepoPath, RepoPathBuf};
-use jj_lib::settings::{ConfigResultExt as _, UserSettings};
+use jj_lib::settings::{ConfigResultExt as _, HumanByteSize, UserSettings};
 use jj_lib::store::Store;
-use jj_lib::{diff, files, rewrite};
+use jj_lib::{diff, files};
 use pollster::FutureExt;
 use tracing::instrument;
 use unicode_width::UnicodeWidthStr as _;
 
-use crate::cli_util::{CommandError, WorkspaceCommandHelper};
+use crate::cli_util::{
+    user_error, write_formatted_file_path, write_nul_record, CommandError, WorkspaceCommandHelper,
+};
 use crate::formatter::Formatter;
 use crate::merge_tools::{self, ExternalMergeTool, MergeTool};
 use crate::text_util;
@@ -45,7 +49,7 @@ use crate::ui::Ui;
 
 #[derive(clap::Args, Clone, Debug)]
 #[command(next_help_heading = "Diff Formatting Options")]
-#[command(group(clap::ArgGroup::new("short-format").args(&["summary", "stat", "types"])))]
+#[command(group(clap::ArgGroup::new("short-format").args(&["summary", "stat", "types", "name_only"])))]
 #[command(group(clap::ArgGroup::new("long-format").args(&["git", "color_words", "tool"])))]
 pub struct DiffFormatArgs {
     /// For each path, show only whether it was modified, added, or deleted
@@ -63,24 +67,79 @@ pub struct DiffFormatArgs {
     /// Git submodule.
     #[arg(long)]
     pub types: bool,
+    /// Show only the paths of the changed files
+    #[arg(long)]
+    pub name_only: bool,
     /// Show a Git-format diff
+    ///
+    /// Unlike the other formats, this shows the changes to files of any size.
     #[arg(long)]
     pub git: bool,
+    /// Include the contents of binary files in Git-format diffs
+    ///
+    /// The contents are encoded as a "GIT binary patch", which `git apply`
+    /// can apply.
+    #[arg(long, requires = "git")]
+    pub binary: bool,
     /// Show a word-level diff with changes indicated only by color
     #[arg(long)]
     pub color_words: bool,
     /// Generate diff by external command
     #[arg(long)]
     pub tool: Option<String>,
+    /// Show the contents of files matching `diff.skip-paths`
+    #[arg(long)]
+    pub include_generated: bool,
+    /// Ignore whitespace when comparing lines
+    ///
+    /// Files whose only changes are whitespace aren't shown, except by
+    /// `--stat`.
+    #[arg(long, short = 'w')]
+    pub ignore_all_space: bool,
+    /// Ignore changes in the amount of whitespace when comparing lines
+    ///
+    /// Whitespace at the end of lines is ignored, and other runs of
+    /// whitespace are considered equal.
+    #[arg(long, short = 'b')]
+    pub ignore_space_change: bool,
+    /// Ignore carriage returns at the end of lines when comparing lines
+    #[arg(long)]
+    pub ignore_cr_at_eol: bool,
+}
+
+impl DiffFormatArgs {
+    /// The whitespace handling requested by the flags. The flag ignoring the
+    /// most whitespace wins.
+    fn whitespace_mode(&self) -> WhitespaceMode {
+        if self.ignore_all_space {
+            WhitespaceMode::IgnoreAllSpace
+        } else if self.ignore_space_change {
+            WhitespaceMode::IgnoreSpaceChange
+        } else if self.ignore_cr_at_eol {
+            WhitespaceMode::IgnoreCrAtEol
+        } else {
+            WhitespaceMode::Exact
+        }
+    }
 }
 
 #[derive(Clone, Debug, Eq, PartialEq)]
 pub enum DiffFormat {
     Summary,
-    Stat,
+    Stat {
+        whitespace: WhitespaceMode,
+    },
     Types,
-    Git,
-    ColorWords,
+    NameOnly,
+    Git {
+        binary: bool,
+        include_generated: bool,
+        whitespace: WhitespaceMode,
+    },
+    ColorWords {
+        include_generated: bool,
+        whitespace: WhitespaceMode,
+    },
     Tool(Box<ExternalMergeTool>),
 }
 
@@ -91,7 +150,7 @@ pub fn diff_formats_for(
 ) -> Result<Vec<DiffFormat>, config::ConfigError> {
     let formats = diff_formats_from_args(settings, args)?;
     if formats.is_empty() {
-        Ok(vec![default_diff_format(settings)?])
+        Ok(vec![default_diff_format(settings, args)?])
     } else {
         Ok(formats)
     }
@@ -107,7 +166,7 @@ pub fn diff_formats_for_log(
     let mut formats = diff_formats_from_args(settings, args)?;
     // --patch implies default if no format other than --summary is specified
     if patch && matches!(formats.as_slice(), [] | [DiffFormat::Summary]) {
-        formats.push(default_diff_format(settings)?);
+        formats.push(default_diff_format(settings, args)?);
         formats.dedup();
     }
     Ok(formats)
@@ -117,12 +176,27 @@ fn diff_formats_from_args(
     settings: &UserSettings,
     args: &DiffFormatArgs,
 ) -> Result<Vec<DiffFormat>, config::ConfigError> {
+    let whitespace = args.whitespace_mode();
     let mut formats = [
         (args.summary, DiffFormat::Summary),
         (args.types, DiffFormat::Types),
-        (args.git, DiffFormat::Git),
-        (args.color_words, DiffFormat::ColorWords),
-        (args.stat, DiffFormat::Stat),
+        (args.name_only, DiffFormat::NameOnly),
+        (
+            args.git,
+            DiffFormat::Git {
+                binary: args.binary,
+                include_generated: args.include_generated,
+                whitespace,
+            },
+        ),
+        (
+            args.color_words,
+            DiffFormat::ColorWords {
+                include_generated: args.include_generated,
+                whitespace,
+            },
+        ),
+        (args.stat, DiffFormat::Stat { whitespace }),
     ]
     .into_iter()
     .filter_map(|(arg, format)| arg.then_some(format))
@@ -131,7 +205,7 @@ fn diff_formats_from_args(
         let tool = merge_tools::get_tool_config(settings, name)?
             .unwrap_or_else(|| MergeTool::External(ExternalMergeTool::with_program(name)));
         match tool {
-            MergeTool::Builtin => {}
+            MergeTool::Builtin | MergeTool::Checklist => {}
             MergeTool::External(tool) => {
                 formats.push(DiffFormat::Tool(Box::new(tool)));
             }
@@ -140,14 +214,19 @@ fn diff_formats_from_args(
     Ok(formats)
 }
 
-fn default_diff_format(settings: &UserSettings) -> Result<DiffFormat, config::ConfigError> {
+fn default_diff_format(
+    settings: &UserSettings,
+    args: &DiffFormatArgs,
+) -> Result<DiffFormat, config::ConfigError> {
+    let include_generated = args.include_generated;
+    let whitespace = args.whitespace_mode();
     let config = settings.config();
     if let Some(args) = config.get("ui.diff.tool").optional()? {
         // External "tool" overrides the internal "format" option.
         let tool = merge_tools::get_tool_config_from_args(settings, &args)?
             .unwrap_or_else(|| MergeTool::External(ExternalMergeTool::with_diff_args(&args)));
         match tool {
-            MergeTool::Builtin => {}
+            MergeTool::Builtin | MergeTool::Checklist => {}
             MergeTool::External(tool) => {
                 return Ok(DiffFormat::Tool(Box::new(tool)));
             }
@@ -163,9 +242,17 @@ fn default_diff_format(settings: &UserSettings) -> Result<DiffFormat, config::Co
     match name.as_ref() {
         "summary" => Ok(DiffFormat::Summary),
         "types" => Ok(DiffFormat::Types),
-        "git" => Ok(DiffFormat::Git),
-        "color-words" => Ok(DiffFormat::ColorWords),
-        "stat" => Ok(DiffFormat::Stat),
+        "name-only" => Ok(DiffFormat::NameOnly),
+        "git" => Ok(DiffFormat::Git {
+            binary: false,
+            include_generated,
+            whitespace,
+        }),
+        "color-words" => Ok(DiffFormat::ColorWords {
+            include_generated,
+            whitespace,
+        }),
+        "stat" => Ok(DiffFormat::Stat { whitespace }),
         _ => Err(config::ConfigError::Message(format!(
             "invalid diff format: {name}"
         ))),
@@ -180,6 +267,31 @@ pub fn show_diff(
     to_tree: &MergedTree,
     matcher: &dyn Matcher,
     formats: &[DiffFormat],
+) -> Result<(), CommandError> {
+    show_diff_with_copies(
+        ui,
+        formatter,
+        workspace_command,
+        from_tree,
+        to_tree,
+        matcher,
+        formats,
+        &BTreeMap::new(),
+    )
+}
+
+/// Like `show_diff()`, but shows the added files in `copy_sources` as copies
+/// of their source files.
+#[allow(clippy::too_many_arguments)]
+fn show_diff_with_copies(
+    ui: &Ui,
+    formatter: &mut dyn Formatter,
+    workspace_command: &WorkspaceCommandHelper,
+    from_tree: &MergedTree,
+    to_tree: &MergedTree,
+    matcher: &dyn Matcher,
+    formats: &[DiffFormat],
+    copy_sources: &BTreeMap<RepoPathBuf, RepoPathBuf>,
 ) -> Result<(), CommandError> {
     for format in formats {
         match format {
@@ -187,21 +299,60 @@ pub fn show_diff(
                 let tree_diff = from_tree.diff_stream(to_tree, matcher);
                 show_diff_summary(formatter, workspace_command, tree_diff)?;
             }
-            DiffFormat::Stat => {
+            DiffFormat::Stat { whitespace } => {
                 let tree_diff = from_tree.diff_stream(to_tree, matcher);
-                show_diff_stat(ui, formatter, workspace_command, tree_diff)?;
+                show_diff_stat(
+                    ui,
+                    formatter,
+                    workspace_command,
+                    to_tree.store(),
+                    tree_diff,
+                    *whitespace,
+                )?;
             }
             DiffFormat::Types => {
                 let tree_diff = from_tree.diff_stream(to_tree, matcher);
                 show_types(formatter, workspace_command, tree_diff)?;
             }
-            DiffFormat::Git => {
+            DiffFormat::NameOnly => {
+                let summary = from_tree.diff_summary(to_tree, matcher)?;
+                show_names(formatter, workspace_command, summary.paths())?;
+            }
+            DiffFormat::Git {
+                binary,
+                include_generated,
+                whitespace,
+            } => {
                 let tree_diff = from_tree.diff_stream(to_tree, matcher);
-                show_git_diff(formatter, workspace_command, tree_diff)?;
+                let skipped =
+                    SkippedFilesMatcher::new(workspace_command, matcher, *include_generated)?;
+                show_git_diff(
+                    formatter,
+                    workspace_command,
+                    to_tree.store(),
+                    tree_diff,
+                    *binary,
+                    &skipped,
+                    *whitespace,
+                )?;
             }
-            DiffFormat::ColorWords => {
+            DiffFormat::ColorWords {
+                include_generated,
+                whitespace,
+            } => {
                 let tree_diff = from_tree.diff_stream(to_tree, matcher);
-                show_color_words_diff(formatter, workspace_command, tree_diff)?;
+                let skipped =
+                    SkippedFilesMatcher::new(workspace_command, matcher, *include_generated)?;
+                let copies = CopiedFiles::resolve(copy_sources, from_tree, to_tree, matcher);
+                show_color_words_diff(
+                    formatter,
+                    workspace_command,
+                    to_tree.store(),
+                    tree_diff,
+                    &skipped,
+                    &copies,
+                    *whitespace,
+                )?;
             }
             DiffFormat::Tool(tool) => {
                 merge_tools::generate_diff(ui, formatter.raw(), from_tree, to_tree, matcher, tool)?;
@@ -211,6 +362,50 @@ pub fn show_diff(
     Ok(())
 }
 
+/// Shows the changes as records for `-z`, which are written by
+/// `write_nul_record()`. `--summary` adds the status letter and `--types` the
+/// types before and after as a field before the path. No other format is
+/// supported.
+pub fn show_nul_terminated_diff(
+    formatter: &mut dyn Formatter,
+    from_tree: &MergedTree,
+    to_tree: &MergedTree,
+    matcher: &dyn Matcher,
+    formats: &[DiffFormat],
+) -> Result<(), CommandError> {
+    let format = match formats {
+        [format @ (DiffFormat::Summary | DiffFormat::Types | DiffFormat::NameOnly)] => format,
+        _ => {
+            return Err(user_error(
+                "-z only works with one of --summary, --types, and --name-only",
+            ))
+        }
+    };
+    let mut tree_diff = from_tree.diff_stream(to_tree, matcher);
+    async {
+        while let Some((repo_path, diff)) = tree_diff.next().await {
+            let (before, after) = diff?;
+            match format {
+                DiffFormat::Summary => {
+                    let (_, status) = diff_summary_status(&before, &after);
+                    write_nul_record(formatter, &[status], &repo_path)?;
+                }
+                DiffFormat::Types => {
+                    let types = format!(
+                        "{}{}",
+                        diff_summary_char(&before),
+                        diff_summary_char(&after)
+                    );
+                    write_nul_record(formatter, &[&types], &repo_path)?;
+                }
+                _ => write_nul_record(formatter, &[], &repo_path)?,
+            }
+        }
+        Ok::<(), CommandError>(())
+    }
+    .block_on()
+}
+
 pub fn show_patch(
     ui: &Ui,
     formatter: &mut dyn Formatter,
@@ -219,23 +414,103 @@ pub fn show_patch(
     matcher: &dyn Matcher,
     formats: &[DiffFormat],
 ) -> Result<(), CommandError> {
-    let parents = commit.parents();
-    let from_tree = rewrite::merge_commit_trees(workspace_command.repo().as_ref(), &parents)?;
+    let from_tree = workspace_command.repo().merged_parent_tree(commit)?;
     let to_tree = commit.tree()?;
-    show_diff(
-        ui,
-        formatter,
-        workspace_command,
-        &from_tree,
-        &to_tree,
-        matcher,
-        formats,
-    )
+    let copy_sources = commit.copy_sources();
+    for format in formats {
+        if *format == DiffFormat::NameOnly {
+            // The changed paths are cached per commit, so reuse them instead of
+            // diffing the trees again.
+            let summary = workspace_command.repo().diff_summary(commit)?;
+            let paths = summary.paths().filter(|path| matcher.matches(path));
+            show_names(formatter, workspace_command, paths)?;
+        } else {
+            show_diff_with_copies(
+                ui,
+                formatter,
+                workspace_command,
+                &from_tree,
+                &to_tree,
+                matcher,
+                slice::from_ref(format),
+                &copy_sources,
+            )?;
+        }
+    }
+    Ok(())
+}
+
+/// A diff format that the `diff` object of commit templates can render.
+/// Unlike `DiffFormat`, it doesn't depend on the terminal.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum TemplateDiffFormat {
+    Summary,
+    Stat { width: usize },
+    Git,
+    ColorWords,
+}
+
+/// Shows the changes from `from_tree` to `to_tree` for a commit template.
+/// Whitespace is compared exactly, and files matching `diff.skip-paths` are
+/// replaced by a placeholder even if `matcher` names them.
+pub fn show_template_diff(
+    formatter: &mut dyn Formatter,
+    workspace_command: &WorkspaceCommandHelper,
+    from_tree: &MergedTree,
+    to_tree: &MergedTree,
+    matcher: &dyn Matcher,
+    copy_sources: &BTreeMap<RepoPathBuf, RepoPathBuf>,
+    format: TemplateDiffFormat,
+) -> Result<(), CommandError> {
+    let tree_diff = from_tree.diff_stream(to_tree, matcher);
+    let whitespace = WhitespaceMode::Exact;
+    match format {
+        TemplateDiffFormat::Summary => {
+            show_diff_summary(formatter, workspace_command, tree_diff)?;
+        }
+        TemplateDiffFormat::Stat { width } => {
+            show_diff_stat_with_width(
+                formatter,
+                workspace_command,
+                to_tree.store(),
+                tree_diff,
+                whitespace,
+                width,
+            )?;
+        }
+        TemplateDiffFormat::Git => {
+            let skipped = SkippedFilesMatcher::new(workspace_command, &EverythingMatcher, false)?;
+            show_git_diff(
+                formatter,
+                workspace_command,
+                to_tree.store(),
+                tree_diff,
+                false,
+                &skipped,
+                whitespace,
+            )?;
+        }
+        TemplateDiffFormat::ColorWords => {
+            let skipped = SkippedFilesMatcher::new(workspace_command, &EverythingMatcher, false)?;
+            let copies = CopiedFiles::resolve(copy_sources, from_tree, to_tree, matcher);
+            show_color_words_diff(
+                formatter,
+                workspace_command,
+                to_tree.store(),
+                tree_diff,
+                &skipped,
+                &copies,
+                whitespace,
+            )?;
+        }
+    }
+    Ok(())
 }
 
 fn show_color_words_diff_hunks(
     left: &[u8],
     right: &[u8],
+    whitespace: WhitespaceMode,
     formatter: &mut dyn Formatter,
 ) -> io::Result<()> {
     const SKIPPED_CONTEXT_LINE: &str = "    ...\n";
@@ -245,7 +520,7 @@ fn show_color_words_diff_hunks(
     let mut skipped_context = false;
     // Are the lines in `context` to be printed before the next modified line?
     let mut context_before = true;
-    for diff_line in files::diff(left, right) {
+    for diff_line in files::diff_with_whitespace(left, right, whitespace) {
         if diff_line.is_unmodified() {
             context.push_back(diff_line.clone());
             let mut start_skipping_context = false;
@@ -346,39 +621,258 @@ fn show_color_words_diff_line(
     Ok(())
 }
 
+/// Files larger than this are not diffed by default, since that can take a
+/// long time. Configurable by `ui.diff.max-file-size`.
+const DEFAULT_MAX_DIFF_FILE_SIZE: u64 = 10 * 1024 * 1024;
+
+/// Diffs rendered by commit templates are truncated after this many bytes by
+/// default, so a template can't produce huge output by accident.
+/// Configurable by `ui.diff.max-template-size`.
+const DEFAULT_MAX_TEMPLATE_DIFF_SIZE: u64 = 1024 * 1024;
+
+/// Returns the size in bytes after which a diff rendered by a commit template
+/// is truncated.
+pub fn max_template_diff_size(settings: &UserSettings) -> Result<u64, config::ConfigError> {
+    let size = match settings
+        .config()
+        .get::<HumanByteSize>("ui.diff.max-template-size")
+        .optional()?
+    {
+        Some(HumanByteSize(0)) => u64::MAX,
+        Some(HumanByteSize(size)) => size,
+        None => DEFAULT_MAX_TEMPLATE_DIFF_SIZE,
+    };
+    Ok(size)
+}
+
+/// Settings deciding which files are shown as text in diffs.
+struct DiffContentOptions {
+    max_file_size: u64,
+    /// Files with these extensions are considered binary regardless of their
+    /// contents.
+    binary_extensions: Vec<String>,
+    /// The `text` attribute overrides the extension and content heuristics.
+    attributes: GitAttributes,
+}
+
+impl DiffContentOptions {
+    fn for_workspace(workspace_command: &WorkspaceCommandHelper) -> Result<Self, CommandError> {
+        let config = workspace_command.settings().config();
+        let max_file_size = match config
+            .get::<HumanByteSize>("ui.diff.max-file-size")
+            .optional()?
+        {
+            Some(HumanByteSize(0)) => u64::MAX,
+            Some(HumanByteSize(size)) => size,
+            None => DEFAULT_MAX_DIFF_FILE_SIZE,
+        };
+        let binary_extensions = config
+            .get::<Vec<String>>("ui.diff.binary-extensions")
+            .optional()?
+            .unwrap_or_default();
+        Ok(DiffContentOptions {
+            max_file_size,
+            binary_extensions,
+            attributes: workspace_command.git_attributes()?,
+        })
+    }
+
+    fn has_binary_extension(&self, path: &RepoPath) -> bool {
+        let Some(name) = path.components().last() else {
+            return false;
+        };
+        let name = name.to_string_lossy();
+        let Some((_, extension)) = name.rsplit_once('.') else {
+            return false;
+        };
+        self.binary_extensions
+            .iter()
+            .any(|binary_extension| binary_extension.eq_ignore_ascii_case(extension))
+    }
+
+    fn is_binary(&self, path: &RepoPath, contents: &[u8]) -> BackendResult<bool> {
+        if let Some(text) = self.attributes.attributes_for(path)?.text {
+            return Ok(!text);
+        }
+        Ok(self.has_binary_extension(path) || is_binary_content(contents))
+    }
+}
+
+/// Matches the files whose diffs are replaced by a placeholder because they
+/// match `diff.skip-paths`. Files named explicitly by the path arguments are
+/// never skipped.
+struct SkippedFilesMatcher<'a> {
+    globs: Option<GlobsMatcher>,
+    paths: &'a dyn Matcher,
+}
+
+impl<'a> SkippedFilesMatcher<'a> {
+    fn new(
+        workspace_command: &WorkspaceCommandHelper,
+        paths: &'a dyn Matcher,
+        include_generated: bool,
+    ) -> Result<Self, CommandError> {
+        let patterns = if include_generated {
+            vec![]
+        } else {
+            workspace_command
+                .settings()
+                .config()
+                .get::<Vec<String>>("diff.skip-paths")
+                .optional()?
+                .unwrap_or_default()
+        };
+        let globs = if patterns.is_empty() {
+            None
+        } else {
+            let globs = GlobsMatcher::new(patterns)
+                .map_err(|err| CommandError::ConfigError(format!("diff.skip-paths: {err}")))?;
+            Some(globs)
+        };
+        Ok(SkippedFilesMatcher { globs, paths })
+    }
+
+    fn is_named_file(&self, file: &RepoPath) -> bool {
+        let Some((dir, name)) = file.split() else {
+            return false;
+        };
+        match self.paths.visit(dir) {
+            Visit::Specific {
+                files: VisitFiles::Set(files),
+                ..
+            } => files.contains(name),
+            _ => false,
+        }
+    }
+}
+
+impl Matcher for SkippedFilesMatcher<'_> {
+    fn matches(&self, file: &RepoPath) -> bool {
+        self.globs.as_ref().is_some_and(|globs| globs.matches(file)) && !self.is_named_file(file)
+    }
+
+    fn visit(&self, dir: &RepoPath) -> Visit {
+        match &self.globs {
+            Some(globs) => globs.visit(dir),
+            None => Visit::Nothing,
+        }
+    }
+}
+
+/// Shown instead of the diff of a file matched by `SkippedFilesMatcher`.
+const SKIPPED_FILE_PLACEHOLDER: &str =
+    "(diff suppressed for generated file; use --include-generated)";
+
+/// Files recorded as copied or moved in a commit, resolved against the trees
+/// being diffed.
+#[derive(Default)]
+pub struct CopiedFiles {
+    /// The source path and value of each added file that is a copy
+    sources: HashMap<RepoPathBuf, (RepoPathBuf, MergedTreeValue)>,
+    /// Sources that are removed by the diff, so their copies are renames
+    renamed: HashSet<RepoPathBuf>,
+}
+
+impl CopiedFiles {
+    fn resolve(
+        copy_sources: &BTreeMap<RepoPathBuf, RepoPathBuf>,
+        from_tree: &MergedTree,
+        to_tree: &MergedTree,
+        matcher: &dyn Matcher,
+    ) -> Self {
+        let mut copies = CopiedFiles::default();
+        for (dest, source) in copy_sources {
+            if !matcher.matches(dest)
+                || from_tree.path_value(dest).is_present()
+                || to_tree.path_value(dest).is_absent()
+            {
+                continue;
+            }
+            // The source may have been added in the same commit
+            let from_value = from_tree.path_value(source);
+            let is_removed = from_value.is_present() && to_tree.path_value(source).is_absent();
+            let source_value = if from_value.is_present() {
+                from_value
+            } else {
+                to_tree.path_value(source)
+            };
+            if !matches!(
+                source_value.as_resolved(),
+                Some(Some(TreeValue::File { .. }))
+            ) {
+                continue;
+            }
+            if is_removed {
+                copies.renamed.insert(source.clone());
+            }
+            copies
+                .sources
+                .insert(dest.clone(), (source.clone(), source_value));
+        }
+        copies
+    }
+}
+
 struct FileContent {
     /// false if this file is likely text; true if it is likely binary.
     is_binary: bool,
+    /// true if the file is larger than `ui.diff.max-file-size`, in which case
+    /// `contents` is empty.
+    is_too_large: bool,
+    size: u64,
     contents: Vec<u8>,
 }
 
 impl FileContent {
     fn empty() -> Self {
+        Self::text(vec![])
+    }
+
+    fn text(contents: Vec<u8>) -> Self {
         Self {
             is_binary: false,
-            contents: vec![],
+            is_too_large: false,
+            size: contents.len() as u64,
+            contents,
         }
     }
 
     pub(crate) fn is_empty(&self) -> bool {
-        self.contents.is_empty()
+        self.size == 0
     }
 }
 
-fn file_content_for_diff(reader: &mut dyn io::Read) -> io::Result<FileContent> {
-    // If this is a binary file, don't show the full contents.
-    // Determine whether it's binary by whether the first 8k bytes contain a null
-    // character; this is the same heuristic used by git as of writing: https://github.com/git/git/blob/eea0e59ffbed6e33d171ace5be13cde9faa41639/xdiff-interface.c#L192-L198
+// If this is a binary file, don't show the full contents.
+// Determine whether it's binary by whether the first 8k bytes contain a null
+// character; this is the same heuristic used by git as of writing: https://github.com/git/git/blob/eea0e59ffbed6e33d171ace5be13cde9faa41639/xdiff-interface.c#L192-L198
+fn is_binary_content(contents: &[u8]) -> bool {
     const PEEK_SIZE: usize = 8000;
+    contents[..PEEK_SIZE.min(contents.len())].contains(&b'\0')
+}
+
+fn file_content_for_diff(
+    path: &RepoPath,
+    reader: &mut dyn io::Read,
+    options: &DiffContentOptions,
+) -> Result<FileContent, CommandError> {
     // TODO: currently we look at the whole file, even though for binary files we
     // only need to know the file size. To change that we'd have to extend all
     // the data backends to support getting the length.
     let mut contents = vec![];
-    reader.read_to_end(&mut contents)?;
-
-    let start = &contents[..PEEK_SIZE.min(contents.len())];
+    io::Read::take(&mut *reader, options.max_file_size.saturating_add(1))
+        .read_to_end(&mut contents)?;
+    let mut size = contents.len() as u64;
+    let is_binary = options.is_binary(path, &contents)?;
+    let is_too_large = size > options.max_file_size;
+    if is_too_large {
+        // Only the size is needed
+        size += io::copy(reader, &mut io::sink())?;
+        contents = vec![];
+    }
     Ok(FileContent {
-        is_binary: start.contains(&b'\0'),
+        is_binary,
+        is_too_large,
+        size,
         contents,
     })
 }
@@ -386,32 +880,59 @@ fn file_content_for_diff(reader: &mut dyn io::Read) -> io::Result<FileContent> {
 fn diff_content(
     path: &RepoPath,
     value: MaterializedTreeValue,
+    options: &DiffContentOptions,
 ) -> Result<FileContent, CommandError> {
     match value {
         MaterializedTreeValue::Absent => Ok(FileContent::empty()),
         MaterializedTreeValue::File { mut reader, .. } => {
-            file_content_for_diff(&mut reader).map_err(Into::into)
+            file_content_for_diff(path, &mut reader, options)
         }
-        MaterializedTreeValue::Symlink { id: _, target } => Ok(FileContent {
-            // Unix file paths can't contain null bytes.
-            is_binary: false,
-            contents: target.into_bytes(),
-        }),
-        MaterializedTreeValue::GitSubmodule(id) => Ok(FileContent {
-            is_binary: false,
-            contents: format!("Git submodule checked out at {}", id.hex()).into_bytes(),
-        }),
+        // Unix file paths can't contain null bytes.
+        MaterializedTreeValue::Symlink { id: _, target } => {
+            Ok(FileContent::text(target.into_bytes()))
+        }
+        MaterializedTreeValue::GitSubmodule(id) => Ok(FileContent::text(
+            format!("Git submodule checked out at {}", id.hex()).into_bytes(),
+        )),
         // TODO: are we sure this is never binary?
-        MaterializedTreeValue::Conflict { id: _, contents } => Ok(FileContent {
-            is_binary: false,
-            contents,
-        }),
+        MaterializedTreeValue::Conflict { id: _, contents } => Ok(FileContent::text(contents)),
         MaterializedTreeValue::Tree(id) => {
             panic!("Unexpected tree with id {id:?} in diff at path {path:?}");
         }
     }
 }
 
+/// Writes a placeholder instead of the diff if either side is binary or too
+/// large to diff. Returns false if the contents should be diffed.
+fn show_content_placeholder(
+    formatter: &mut dyn Formatter,
+    left_content: Option<&FileContent>,
+    right_content: Option<&FileContent>,
+) -> io::Result<bool> {
+    let contents = [left_content, right_content];
+    let sizes = contents
+        .iter()
+        .flatten()
+        .map(|content| HumanByteSize(content.size).to_string())
+        .join(" → ");
+    if contents.iter().flatten().any(|content| content.is_binary) {
+        writeln!(formatter.labeled("binary"), "    (binary file, {sizes})")?;
+        Ok(true)
+    } else if contents
+        .iter()
+        .flatten()
+        .any(|content| content.is_too_large)
+    {
+        writeln!(
+            formatter.labeled("binary"),
+            "    (file too large to diff, use --git to force)"
+        )?;
+        Ok(true)
+    } else {
+        Ok(false)
+    }
+}
+
 fn basic_diff_file_type(value: &MaterializedTreeValue) -> &'static str {
     match value {
         MaterializedTreeValue::Absent => {
@@ -434,29 +955,95 @@ fn basic_diff_file_type(value: &MaterializedTreeValue) -> &'static str {
 pub fn show_color_words_diff(
     formatter: &mut dyn Formatter,
     workspace_command: &WorkspaceCommandHelper,
+    store: &Store,
     tree_diff: TreeDiffStream,
+    skipped: &dyn Matcher,
+    copies: &CopiedFiles,
+    whitespace: WhitespaceMode,
 ) -> Result<(), CommandError> {
+    let options = DiffContentOptions::for_workspace(workspace_command)?;
     formatter.push_label("diff")?;
-    let mut diff_stream = materialized_diff_stream(workspace_command.repo().store(), tree_diff);
+    let mut diff_stream = materialized_diff_stream(store, tree_diff);
     async {
         while let Some((path, diff)) = diff_stream.next().await {
             let ui_path = workspace_command.format_file_path(&path);
             let (left_value, right_value) = diff?;
-            if left_value.is_absent() {
+            if right_value.is_absent() && copies.renamed.contains(&path) {
+                // Shown with the file it was renamed to
+                continue;
+            }
+            if let Some((source, source_value)) = copies.sources.get(&path) {
+                let description = basic_diff_file_type(&right_value);
+                let action = if copies.renamed.contains(source) {
+                    "Renamed"
+                } else {
+                    "Copied"
+                };
+                let source_ui_path = workspace_command.format_file_path(source);
+                writeln!(
+                    formatter.labeled("header"),
+                    "{action} {description} {source_ui_path} to {ui_path}:"
+                )?;
+                if skipped.matches(&path) {
+                    writeln!(
+                        formatter.labeled("skipped"),
+                        "    {SKIPPED_FILE_PLACEHOLDER}"
+                    )?;
+                    continue;
+                }
+                let source_value =
+                    materialize_tree_value(store, source, source_value.clone()).await?;
+                let left_content = diff_content(source, source_value, &options)?;
+                let right_content = diff_content(&path, right_value, &options)?;
+                if !show_content_placeholder(formatter, Some(&left_content), Some(&right_content))?
+                    && left_content.contents != right_content.contents
+                {
+                    show_color_words_diff_hunks(
+                        &left_content.contents,
+                        &right_content.contents,
+                        whitespace,
+                        formatter,
+                    )?;
+                }
+            } else if left_value.is_absent() {
                 let description = basic_diff_file_type(&right_value);
                 writeln!(
                     formatter.labeled("header"),
                     "Added {description} {ui_path}:"
                 )?;
-                let right_content = diff_content(&path, right_value)?;
+                if skipped.matches(&path) {
+                    writeln!(
+                        formatter.labeled("skipped"),
+                        "    {SKIPPED_FILE_PLACEHOLDER}"
+                    )?;
+                    continue;
+                }
+                let right_content = diff_content(&path, right_value, &options)?;
                 if right_content.is_empty() {
                     writeln!(formatter.labeled("empty"), "    (empty)")?;
-                } else if right_content.is_binary {
-                    writeln!(formatter.labeled("binary"), "    (binary)")?;
-                } else {
-                    show_color_words_diff_hunks(&[], &right_content.contents, formatter)?;
+                } else if !show_content_placeholder(formatter, None, Some(&right_content))? {
+                    show_color_words_diff_hunks(
+                        &[],
+                        &right_content.contents,
+                        whitespace,
+                        formatter,
+                    )?;
                 }
             } else if right_value.is_present() {
+                let partial_resolution = match (&left_value, &right_value) {
+                    (
+                        MaterializedTreeValue::Conflict { id: left_id, .. },
+                        MaterializedTreeValue::Conflict { id: right_id, .. },
+                    ) => conflicts::resolved_conflict_hunks(store, &path, left_id, right_id).await,
+                    _ => None,
+                };
+                let same_file_mode = matches!(
+                    (&left_value, &right_value),
+                    (
+                        MaterializedTreeValue::File { executable: left_executable, .. },
+                        MaterializedTreeValue::File { executable: right_executable, .. },
+                    ) if left_executable == right_executable
+                );
                 let description = match (&left_value, &right_value) {
                     (
                         MaterializedTreeValue::File {
@@ -481,7 +1068,12 @@ pub fn show_color_words_diff(
                     (
                         MaterializedTreeValue::Conflict { .. },
                         MaterializedTreeValue::Conflict { .. },
-                    ) => "Modified conflict in".to_string(),
+                    ) => match partial_resolution {
+                        Some((resolved, total)) => {
+                            format!("Resolved {resolved} of {total} conflicts in")
+                        }
+                        None => "Modified conflict in".to_string(),
+                    },
                     (MaterializedTreeValue::Conflict { .. }, _) => {
                         "Resolved conflict in".to_string()
                     }
@@ -504,15 +1096,28 @@ pub fn show_color_words_diff(
                         )
                     }
                 };
-                let left_content = diff_content(&path, left_value)?;
-                let right_content = diff_content(&path, right_value)?;
+                if skipped.matches(&path) {
+                    writeln!(formatter.labeled("header"), "{description} {ui_path}:")?;
+                    writeln!(
+                        formatter.labeled("skipped"),
+                        "    {SKIPPED_FILE_PLACEHOLDER}"
+                    )?;
+                    continue;
+                }
+                let left_content = diff_content(&path, left_value, &options)?;
+                let right_content = diff_content(&path, right_value, &options)?;
+                if same_file_mode
+                    && is_whitespace_only_change(&left_content, &right_content, whitespace)
+                {
+                    continue;
+                }
                 writeln!(formatter.labeled("header"), "{description} {ui_path}:")?;
-                if left_content.is_binary || right_content.is_binary {
-                    writeln!(formatter.labeled("binary"), "    (binary)")?;
-                } else {
+                if !show_content_placeholder(formatter, Some(&left_content), Some(&right_content))?
+                {
                     show_color_words_diff_hunks(
                         &left_content.contents,
                         &right_content.contents,
+                        whitespace,
                         formatter,
                     )?;
                 }
@@ -522,13 +1127,23 @@ pub fn show_color_words_diff(
                     formatter.labeled("header"),
                     "Removed {description} {ui_path}:"
                 )?;
-                let left_content = diff_content(&path, left_value)?;
+                if skipped.matches(&path) {
+                    writeln!(
+                        formatter.labeled("skipped"),
+                        "    {SKIPPED_FILE_PLACEHOLDER}"
+                    )?;
+                    continue;
+                }
+                let left_content = diff_content(&path, left_value, &options)?;
                 if left_content.is_empty() {
                     writeln!(formatter.labeled("empty"), "    (empty)")?;
-                } else if left_content.is_binary {
-                    writeln!(formatter.labeled("binary"), "    (binary)")?;
-                } else {
-                    show_color_words_diff_hunks(&left_content.contents, &[], formatter)?;
+                } else if !show_content_placeholder(formatter, Some(&left_content), None)? {
+                    show_color_words_diff_hunks(
+                        &left_content.contents,
+                        &[],
+                        whitespace,
+                        formatter,
+                    )?;
                 }
             }
         }
@@ -539,19 +1154,47 @@ pub fn show_color_words_diff(
     Ok(())
 }
 
+/// Whether the only changes between the two contents are whitespace ignored
+/// by `whitespace`. Binary files and files too large to diff never count as
+/// whitespace-only changes.
+fn is_whitespace_only_change(
+    left_content: &FileContent,
+    right_content: &FileContent,
+    whitespace: WhitespaceMode,
+) -> bool {
+    whitespace != WhitespaceMode::Exact
+        && !left_content.is_binary
+        && !right_content.is_binary
+        && !left_content.is_too_large
+        && !right_content.is_too_large
+        && left_content.contents != right_content.contents
+        && whitespace.normalize(&left_content.contents)
+            == whitespace.normalize(&right_content.contents)
+}
+
 struct GitDiffPart {
     mode: String,
+    /// The full hash, which is only shown for binary patches.
     hash: String,
+    is_binary: bool,
     content: Vec<u8>,
 }
 
+impl GitDiffPart {
+    fn short_hash(&self) -> &str {
+        &self.hash[..10]
+    }
+}
+
 fn git_diff_part(
     path: &RepoPath,
     value: MaterializedTreeValue,
+    options: &DiffContentOptions,
 ) -> Result<GitDiffPart, CommandError> {
     let mode;
     let hash;
     let mut contents: Vec<u8>;
+    let mut is_binary = false;
     match value {
         MaterializedTreeValue::Absent => {
             panic!("Absent path {path:?} in diff should have been handled by caller");
@@ -567,9 +1210,10 @@ fn git_diff_part(
                 "100644".to_string()
             };
             hash = id.hex();
-            // TODO: use `file_content_for_diff` instead of showing binary
+            // The whole file is diffed regardless of `ui.diff.max-file-size`
             contents = vec![];
             reader.read_to_end(&mut contents)?;
+            is_binary = options.is_binary(path, &contents)?;
         }
         MaterializedTreeValue::Symlink { id, target } => {
             mode = "120000".to_string();
@@ -594,10 +1238,10 @@ fn git_diff_part(
             panic!("Unexpected tree in diff at path {path:?}");
         }
     }
-    let hash = hash[0..10].to_string();
     Ok(GitDiffPart {
         mode,
         hash,
+        is_binary,
         content: contents,
     })
 }
@@ -619,6 +1263,7 @@ fn unified_diff_hunks<'content>(
     left_content: &'content [u8],
     right_content: &'content [u8],
     num_context_lines: usize,
+    whitespace: WhitespaceMode,
 ) -> Vec<UnifiedDiffHunk<'content>> {
     let mut hunks = vec![];
     let mut current_hunk = UnifiedDiffHunk {
@@ -627,8 +1272,7 @@ fn unified_diff_hunks<'content>(
         lines: vec![],
     };
     let mut show_context_after = false;
-    let diff = Diff::for_tokenizer(&[left_content, right_content], &diff::find_line_ranges);
-    for hunk in diff.hunks() {
+    for hunk in diff::diff_lines(left_content, right_content, whitespace) {
         match hunk {
             DiffHunk::Matching(content) => {
                 let lines = content.split_inclusive(|b| *b == b'\n').collect_vec();
@@ -699,8 +1343,9 @@ fn show_unified_diff_hunks(
     formatter: &mut dyn Formatter,
     left_content: &[u8],
     right_content: &[u8],
+    whitespace: WhitespaceMode,
 ) -> Result<(), CommandError> {
-    for hunk in unified_diff_hunks(left_content, right_content, 3) {
+    for hunk in unified_diff_hunks(left_content, right_content, 3, whitespace) {
         writeln!(
             formatter.labeled("hunk_header"),
             "@@ -{},{} +{},{} @@",
@@ -765,61 +1410,147 @@ fn materialized_diff_stream<'a>(
 pub fn show_git_diff(
     formatter: &mut dyn Formatter,
     workspace_command: &WorkspaceCommandHelper,
+    store: &Store,
     tree_diff: TreeDiffStream,
+    binary: bool,
+    skipped: &dyn Matcher,
+    whitespace: WhitespaceMode,
 ) -> Result<(), CommandError> {
+    let options = DiffContentOptions::for_workspace(workspace_command)?;
     formatter.push_label("diff")?;
 
-    let mut diff_stream = materialized_diff_stream(workspace_command.repo().store(), tree_diff);
+    let mut diff_stream = materialized_diff_stream(store, tree_diff);
     async {
         while let Some((path, diff)) = diff_stream.next().await {
             let path_string = path.as_internal_file_string();
             let (left_value, right_value) = diff?;
-            if left_value.is_absent() {
-                let right_part = git_diff_part(&path, right_value)?;
-                formatter.with_label("file_header", |formatter| {
-                    writeln!(formatter, "diff --git a/{path_string} b/{path_string}")?;
-                    writeln!(formatter, "new file mode {}", &right_part.mode)?;
-                    writeln!(formatter, "index 0000000000..{}", &right_part.hash)?;
-                    writeln!(formatter, "--- /dev/null")?;
-                    writeln!(formatter, "+++ b/{path_string}")
-                })?;
-                show_unified_diff_hunks(formatter, &[], &right_part.content)?;
-            } else if right_value.is_present() {
-                let left_part = git_diff_part(&path, left_value)?;
-                let right_part = git_diff_part(&path, right_value)?;
-                formatter.with_label("file_header", |formatter| {
-                    writeln!(formatter, "diff --git a/{path_string} b/{path_string}")?;
-                    if left_part.mode != right_part.mode {
-                        writeln!(formatter, "old mode {}", &left_part.mode)?;
-                        writeln!(formatter, "new mode {}", &right_part.mode)?;
-                        if left_part.hash != right_part.hash {
-                            writeln!(formatter, "index {}...{}", &left_part.hash, right_part.hash)?;
+            let left_part = if left_value.is_present() {
+                Some(git_diff_part(&path, left_value, &options)?)
+            } else {
+                None
+            };
+            let right_part = if right_value.is_present() {
+                Some(git_diff_part(&path, right_value, &options)?)
+            } else {
+                None
+            };
+            let is_binary = left_part
+                .iter()
+                .chain(&right_part)
+                .any(|part| part.is_binary);
+            if let (Some(left_part), Some(right_part)) = (&left_part, &right_part) {
+                if !is_binary
+                    && left_part.mode == right_part.mode
+                    && !skipped.matches(&path)
+                    && left_part.content != right_part.content
+                    && whitespace != WhitespaceMode::Exact
+                    && whitespace.normalize(&left_part.content)
+                        == whitespace.normalize(&right_part.content)
+                {
+                    continue;
+                }
+            }
+            // `git apply` needs full hashes to apply binary patches
+            let full_index = is_binary && binary;
+            let hash = |part: &GitDiffPart| {
+                if full_index {
+                    part.hash.clone()
+                } else {
+                    part.short_hash().to_owned()
+                }
+            };
+            let zero_hash = "0".repeat(
+                left_part
+                    .iter()
+                    .chain(&right_part)
+                    .map(hash)
+                    .next()
+                    .unwrap()
+                    .len(),
+            );
+            formatter.with_label("file_header", |formatter| {
+                writeln!(formatter, "diff --git a/{path_string} b/{path_string}")?;
+                match (&left_part, &right_part) {
+                    (None, Some(right_part)) => {
+                        writeln!(formatter, "new file mode {}", &right_part.mode)?;
+                        writeln!(formatter, "index {zero_hash}..{}", hash(right_part))?;
+                    }
+                    (Some(left_part), Some(right_part)) => {
+                        if left_part.mode != right_part.mode {
+                            writeln!(formatter, "old mode {}", &left_part.mode)?;
+                            writeln!(formatter, "new mode {}", &right_part.mode)?;
+                            if left_part.hash != right_part.hash {
+                                writeln!(
+                                    formatter,
+                                    "index {}...{}",
+                                    hash(left_part),
+                                    hash(right_part)
+                                )?;
+                            }
+                        } else if left_part.hash != right_part.hash {
+                            writeln!(
+                                formatter,
+                                "index {}...{} {}",
+                                hash(left_part),
+                                hash(right_part),
+                                left_part.mode
+                            )?;
                         }
-                    } else if left_part.hash != right_part.hash {
-                        writeln!(
-                            formatter,
-                            "index {}...{} {}",
-                            &left_part.hash, right_part.hash, left_part.mode
-                        )?;
                     }
-                    if left_part.content != right_part.content {
-                        writeln!(formatter, "--- a/{path_string}")?;
-                        writeln!(formatter, "+++ b/{path_string}")?;
+                    (Some(left_part), None) => {
+                        writeln!(formatter, "deleted file mode {}", &left_part.mode)?;
+                        writeln!(formatter, "index {}..{zero_hash}", hash(left_part))?;
                     }
-                    Ok(())
-                })?;
-                show_unified_diff_hunks(formatter, &left_part.content, &right_part.content)?;
-            } else {
-                let left_part = git_diff_part(&path, left_value)?;
+                    (None, None) => panic!("Absent path {path:?} on both sides of diff"),
+                }
+                Ok(())
+            })?;
+            if skipped.matches(&path) {
+                writeln!(formatter.labeled("skipped"), "{SKIPPED_FILE_PLACEHOLDER}")?;
+                continue;
+            }
+            let left_content = left_part.as_ref().map_or(&[][..], |part| &part.content);
+            let right_content = right_part.as_ref().map_or(&[][..], |part| &part.content);
+            if is_binary {
+                if left_content == right_content {
+                    // Only the mode changed
+                } else if binary {
+                    writeln!(formatter, "GIT binary patch")?;
+                    write_git_binary_literal(formatter, right_content)?;
+                    write_git_binary_literal(formatter, left_content)?;
+                } else {
+                    let left_name = if left_part.is_some() {
+                        format!("a/{path_string}")
+                    } else {
+                        "/dev/null".to_owned()
+                    };
+                    let right_name = if right_part.is_some() {
+                        format!("b/{path_string}")
+                    } else {
+                        "/dev/null".to_owned()
+                    };
+                    writeln!(
+                        formatter,
+                        "Binary files {left_name} and {right_name} differ"
+                    )?;
+                }
+                continue;
+            }
+            if left_part.is_none() || right_part.is_none() || left_content != right_content {
                 formatter.with_label("file_header", |formatter| {
-                    writeln!(formatter, "diff --git a/{path_string} b/{path_string}")?;
-                    writeln!(formatter, "deleted file mode {}", &left_part.mode)?;
-                    writeln!(formatter, "index {}..0000000000", &left_part.hash)?;
-                    writeln!(formatter, "--- a/{path_string}")?;
-                    writeln!(formatter, "+++ /dev/null")
+                    if left_part.is_some() {
+                        writeln!(formatter, "--- a/{path_string}")?;
+                    } else {
+                        writeln!(formatter, "--- /dev/null")?;
+                    }
+                    if right_part.is_some() {
+                        writeln!(formatter, "+++ b/{path_string}")
+                    } else {
+                        writeln!(formatter, "+++ /dev/null")
+                    }
                 })?;
-                show_unified_diff_hunks(formatter, &left_part.content, &[])?;
             }
+            show_unified_diff_hunks(formatter, left_content, right_content, whitespace)?;
         }
         Ok::<(), CommandError>(())
     }
@@ -828,7 +1559,91 @@ pub fn show_git_diff(
     Ok(())
 }
 
+/// Writes `data` as a "literal" hunk of a Git binary patch. The data is
+/// zlib-encoded (without compression) and then base85-encoded in lines of up
+/// to 52 bytes, each prefixed by a character encoding its length.
+fn write_git_binary_literal(formatter: &mut dyn Formatter, data: &[u8]) -> io::Result<()> {
+    writeln!(formatter, "literal {}", data.len())?;
+    for line in zlib_stored(data).chunks(52) {
+        let length_char = if line.len() <= 26 {
+            b'A' + line.len() as u8 - 1
+        } else {
+            b'a' + line.len() as u8 - 27
+        };
+        formatter.write_all(&[length_char])?;
+        formatter.write_all(&git_base85(line))?;
+        writeln!(formatter)?;
+    }
+    writeln!(formatter)
+}
+
+/// Wraps `data` in a zlib stream made of uncompressed ("stored") blocks.
+fn zlib_stored(data: &[u8]) -> Vec<u8> {
+    const MAX_BLOCK_SIZE: usize = 0xffff;
+    let mut output = vec![0x78, 0x01];
+    let mut blocks = data.chunks(MAX_BLOCK_SIZE).peekable();
+    if blocks.peek().is_none() {
+        output.extend_from_slice(&[0x01, 0x00, 0x00, 0xff, 0xff]);
+    }
+    while let Some(block) = blocks.next() {
+        let is_final = blocks.peek().is_none();
+        let len = block.len() as u16;
+        output.push(u8::from(is_final));
+        output.extend_from_slice(&len.to_le_bytes());
+        output.extend_from_slice(&(!len).to_le_bytes());
+        output.extend_from_slice(block);
+    }
+    // Adler-32 checksum
+    let (mut a, mut b) = (1u32, 0u32);
+    for &byte in data {
+        a = (a + u32::from(byte)) % 65521;
+        b = (b + a) % 65521;
+    }
+    output.extend_from_slice(&((b << 16) | a).to_be_bytes());
+    output
+}
+
+/// Base85-encodes `data` using Git's alphabet, padding it to a multiple of 4
+/// bytes.
+fn git_base85(data: &[u8]) -> Vec<u8> {
+    const ALPHABET: &[u8; 85] =
+        b"0123456789ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz!#$%&()*+-;<=>?@^_`{|}~";
+    let mut output = vec![];
+    for group in data.chunks(4) {
+        let mut bytes = [0; 4];
+        bytes[..group.len()].copy_from_slice(group);
+        let mut value = u32::from_be_bytes(bytes);
+        let mut encoded = [0; 5];
+        for c in encoded.iter_mut().rev() {
+            *c = ALPHABET[(value % 85) as usize];
+            value /= 85;
+        }
+        output.extend_from_slice(&encoded);
+    }
+    output
+}
+
+/// Appended to the `--summary` and `--stat` lines of modified files that still
+/// have conflicts.
+const CONFLICT_FLAG: &str = " (conflict)";
+
+/// Appended to the `--stat` lines of files whose only changes are whitespace
+/// ignored by the diff options.
+const WHITESPACE_ONLY_FLAG: &str = " (whitespace only)";
+
 #[instrument(skip_all)]
+pub fn show_names<'a>(
+    formatter: &mut dyn Formatter,
+    workspace_command: &WorkspaceCommandHelper,
+    paths: impl IntoIterator<Item = &'a RepoPath>,
+) -> io::Result<()> {
+    for path in paths {
+        workspace_command.write_file_path(formatter, path)?;
+        writeln!(formatter)?;
+    }
+    Ok(())
+}
+
 pub fn show_diff_summary(
     formatter: &mut dyn Formatter,
     workspace_command: &WorkspaceCommandHelper,
@@ -838,25 +1653,8 @@ pub fn show_diff_summary(
         async {
             while let Some((repo_path, diff)) = tree_diff.next().await {
                 let (before, after) = diff.unwrap();
-                if before.is_present() && after.is_present() {
-                    writeln!(
-                        formatter.labeled("modified"),
-                        "M {}",
-                        workspace_command.format_file_path(&repo_path)
-                    )?;
-                } else if before.is_absent() {
-                    writeln!(
-                        formatter.labeled("added"),
-                        "A {}",
-                        workspace_command.format_file_path(&repo_path)
-                    )?;
-                } else {
-                    writeln!(
-                        formatter.labeled("removed"),
-                        "D {}", // `R` could be interpreted as "renamed"
-                        workspace_command.format_file_path(&repo_path)
-                    )?;
-                }
+                let path = workspace_command.format_file_path(&repo_path);
+                write_diff_summary_entry(formatter, &path, &before, &after)?;
             }
             Ok(())
         }
@@ -864,21 +1662,85 @@ pub fn show_diff_summary(
     })
 }
 
+/// Writes the line that `show_diff_summary()` shows for a path, given its
+/// formatted name and its values before and after. The caller should add the
+/// "diff" label.
+pub fn write_diff_summary_entry(
+    formatter: &mut dyn Formatter,
+    path: &str,
+    before: &MergedTreeValue,
+    after: &MergedTreeValue,
+) -> io::Result<()> {
+    let (label, status) = diff_summary_status(before, after);
+    formatter.with_label(label, |formatter| {
+        write!(formatter, "{status} ")?;
+        write_formatted_file_path(formatter, path)
+    })?;
+    // Conflicts are shown as modified files, since their
+    // contents can only be compared once materialized
+    if label == "modified" && !after.is_resolved() {
+        write!(formatter.labeled("conflict"), "{CONFLICT_FLAG}")?;
+    }
+    writeln!(formatter)
+}
+
+/// The label and the letter that `--summary` shows for a change.
+fn diff_summary_status(
+    before: &MergedTreeValue,
+    after: &MergedTreeValue,
+) -> (&'static str, &'static str) {
+    // `R` could be interpreted as "renamed"
+    if before.is_present() && after.is_present() {
+        ("modified", "M")
+    } else if before.is_absent() {
+        ("added", "A")
+    } else {
+        ("removed", "D")
+    }
+}
+
 struct DiffStat {
     path: String,
     added: usize,
     removed: usize,
+    /// The sizes before and after, if the file is binary or too large to diff.
+    /// Such files count as having no added or removed lines.
+    sizes: Option<(u64, u64)>,
+    /// Whether the file still has conflicts after the change.
+    is_conflict: bool,
+    /// Whether the file only has changes in ignored whitespace.
+    whitespace_only: bool,
 }
 
 fn get_diff_stat(
     path: String,
     left_content: &FileContent,
     right_content: &FileContent,
+    whitespace: WhitespaceMode,
 ) -> DiffStat {
+    if left_content.is_binary
+        || right_content.is_binary
+        || left_content.is_too_large
+        || right_content.is_too_large
+    {
+        return DiffStat {
+            path,
+            added: 0,
+            removed: 0,
+            sizes: Some((left_content.size, right_content.size)),
+            is_conflict: false,
+            whitespace_only: false,
+        };
+    }
     // TODO: this matches git's behavior, which is to count the number of newlines
     // in the file. but that behavior seems unhelpful; no one really cares how
     // many `0xa0` characters are in an image.
-    let hunks = unified_diff_hunks(&left_content.contents, &right_content.contents, 0);
+    let hunks = unified_diff_hunks(
+        &left_content.contents,
+        &right_content.contents,
+        0,
+        whitespace,
+    );
     let mut added = 0;
     let mut removed = 0;
     for hunk in hunks {
@@ -894,6 +1756,9 @@ fn get_diff_stat(
         path,
         added,
         removed,
+        sizes: None,
+        is_conflict: false,
+        whitespace_only: is_whitespace_only_change(left_content, right_content, whitespace),
     }
 }
 
@@ -901,21 +1766,51 @@ pub fn show_diff_stat(
     ui: &Ui,
     formatter: &mut dyn Formatter,
     workspace_command: &WorkspaceCommandHelper,
+    store: &Store,
     tree_diff: TreeDiffStream,
+    whitespace: WhitespaceMode,
 ) -> Result<(), CommandError> {
+    // 4 characters padding for the graph
+    let display_width = usize::from(ui.term_width().unwrap_or(80)).saturating_sub(4);
+    show_diff_stat_with_width(
+        formatter,
+        workspace_command,
+        store,
+        tree_diff,
+        whitespace,
+        display_width,
+    )
+}
+
+/// Like `show_diff_stat()`, but fits the lines in `display_width` columns
+/// instead of the terminal width.
+fn show_diff_stat_with_width(
+    formatter: &mut dyn Formatter,
+    workspace_command: &WorkspaceCommandHelper,
+    store: &Store,
+    tree_diff: TreeDiffStream,
+    whitespace: WhitespaceMode,
+    display_width: usize,
+) -> Result<(), CommandError> {
+    let options = DiffContentOptions::for_workspace(workspace_command)?;
     let mut stats: Vec<DiffStat> = vec![];
     let mut max_path_width = 0;
     let mut max_diffs = 0;
 
-    let mut diff_stream = materialized_diff_stream(workspace_command.repo().store(), tree_diff);
+    let mut diff_stream = materialized_diff_stream(store, tree_diff);
     async {
         while let Some((repo_path, diff)) = diff_stream.next().await {
             let (left, right) = diff?;
             let path = workspace_command.format_file_path(&repo_path);
-            let left_content = diff_content(&repo_path, left)?;
-            let right_content = diff_content(&repo_path, right)?;
+            let is_conflict =
+                left.is_present() && matches!(right, MaterializedTreeValue::Conflict { .. });
+            let left_content = diff_content(&repo_path, left, &options)?;
+            let right_content = diff_content(&repo_path, right, &options)?;
             max_path_width = max(max_path_width, path.width());
-            let stat = get_diff_stat(path, &left_content, &right_content);
+            let stat = DiffStat {
+                is_conflict,
+                ..get_diff_stat(path, &left_content, &right_content, whitespace)
+            };
             max_diffs = max(max_diffs, stat.added + stat.removed);
             stats.push(stat);
         }
@@ -924,9 +1819,13 @@ pub fn show_diff_stat(
     .block_on()?;
 
     let number_padding = max_diffs.to_string().len();
-    // 4 characters padding for the graph
+    let conflict_flag_width = if stats.iter().any(|stat| stat.is_conflict) {
+        CONFLICT_FLAG.len()
+    } else {
+        0
+    };
     let available_width =
-        usize::from(ui.term_width().unwrap_or(80)).saturating_sub(4 + " | ".len() + number_padding);
+        display_width.saturating_sub(" | ".len() + number_padding + conflict_flag_width);
     // Always give at least a tiny bit of room
     let available_width = max(available_width, 5);
     let max_path_width = max_path_width.clamp(3, (0.7 * available_width as f64) as usize);
@@ -949,6 +1848,14 @@ pub fn show_diff_stat(
             // replace start of path with ellipsis if the path is too long
             let (path, path_width) = text_util::elide_start(&stat.path, "...", max_path_width);
             let path_pad_width = max_path_width - path_width;
+            if let Some((left_size, right_size)) = stat.sizes {
+                writeln!(
+                    formatter,
+                    "{path}{:path_pad_width$} | Bin {left_size} -> {right_size} bytes",
+                    "", // pad to max_path_width
+                )?;
+                continue;
+            }
             write!(
                 formatter,
                 "{path}{:path_pad_width$} | {:>number_padding$}{}",
@@ -957,7 +1864,14 @@ pub fn show_diff_stat(
                 if bar_added + bar_removed > 0 { " " } else { "" },
             )?;
             write!(formatter.labeled("added"), "{}", "+".repeat(bar_added))?;
-            writeln!(formatter.labeled("removed"), "{}", "-".repeat(bar_removed))?;
+            write!(formatter.labeled("removed"), "{}", "-".repeat(bar_removed))?;
+            if stat.is_conflict {
+                write!(formatter.labeled("conflict"), "{CONFLICT_FLAG}")?;
+            }
+            if stat.whitespace_only {
+                write!(formatter, "{WHITESPACE_ONLY_FLAG}")?;
+            }
+            writeln!(formatter)?;
         }
         writeln!(
             formatter.labeled("stat-summary"),
@@ -14,6 +14,7 @@
 
 //! Git utilities shared by various commands.
 
+use std::fmt::Write as _;
 use std::io::{Read, Write};
 use std::path::{Path, PathBuf};
 use std::process::Stdio;
@@ -29,7 +30,7 @@ use jj_lib::workspace::Workspace;
 
 use crate::cli_util::{user_error, CommandError};
 use crate::progress::Progress;
-use crate::ui::Ui;
+use crate::ui::{Ui, UiWarning, WarningCode};
 
 pub fn get_git_repo(store: &Store) -> Result<git2::Repository, CommandError> {
     match store.backend_impl().downcast_ref::<GitBackend>() {
@@ -169,6 +170,21 @@ pub fn with_remote_git_callbacks<T>(
         Some((terminal_get_username(ui, url)?, terminal_get_pw(ui, url)?))
     };
     callbacks.get_username_password = Some(&mut get_user_pw);
+    let mut on_retry = |retry: &git::FetchRetry| {
+        let ui = ui.lock().unwrap();
+        ui.write_warning(&UiWarning::new(
+            WarningCode::FetchRetry,
+            format!(
+                "Fetch failed: {}. Retrying in {}s (attempt {} of {})",
+                retry.error.message(),
+                retry.delay.as_secs_f32(),
+                retry.attempt + 1,
+                retry.max_attempts
+            ),
+        ))
+        .ok();
+    };
+    callbacks.retry = Some(&mut on_retry);
     f(callbacks)
 }
 
@@ -188,28 +204,28 @@ pub fn print_failed_git_export(
     failed_branches: &[FailedRefExport],
 ) -> Result<(), std::io::Error> {
     if !failed_branches.is_empty() {
-        writeln!(ui.warning(), "Failed to export some branches:")?;
-        let mut formatter = ui.stderr_formatter();
+        let mut warning = UiWarning::new(
+            WarningCode::FailedGitExport,
+            "Failed to export some branches:",
+        );
         for FailedRefExport { name, reason } in failed_branches {
-            formatter.write_str("  ")?;
-            write!(formatter.labeled("branch"), "{name}")?;
+            let mut detail = name.to_string();
             for err in iter::successors(Some(reason as &dyn error::Error), |err| err.source()) {
-                write!(formatter, ": {err}")?;
+                write!(detail, ": {err}").unwrap();
             }
-            writeln!(formatter)?;
+            warning = warning.with_detail(detail);
         }
-        drop(formatter);
         if failed_branches
             .iter()
             .any(|failed| matches!(failed.reason, FailedRefExportReason::FailedToSet(_)))
         {
-            writeln!(
-                ui.hint(),
-                r#"Hint: Git doesn't allow a branch name that looks like a parent directory of
+            warning = warning.with_hint(
+                r#"Git doesn't allow a branch name that looks like a parent directory of
 another (e.g. `foo` and `foo/bar`). Try to rename the branches that failed to
 export or their "parent" branches."#,
-            )?;
+            );
         }
+        ui.write_warning(&warning)?;
     }
     Ok(())
 }
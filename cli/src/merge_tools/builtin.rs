@@ -405,17 +405,25 @@ pub fn apply_diff_builtin(
     Ok(tree_id)
 }
 
-pub fn edit_diff_builtin(
+pub fn collect_changed_files(
     left_tree: &MergedTree,
     right_tree: &MergedTree,
     matcher: &dyn Matcher,
-) -> Result<MergedTreeId, BuiltinToolError> {
-    let store = left_tree.store().clone();
-    let changed_files: Vec<_> = left_tree
+) -> Result<Vec<RepoPathBuf>, BackendError> {
+    left_tree
         .diff_stream(right_tree, matcher)
         .map(|(path, diff)| diff.map(|_| path))
         .try_collect()
-        .block_on()?;
+        .block_on()
+}
+
+pub fn edit_diff_builtin(
+    left_tree: &MergedTree,
+    right_tree: &MergedTree,
+    matcher: &dyn Matcher,
+) -> Result<MergedTreeId, BuiltinToolError> {
+    let store = left_tree.store().clone();
+    let changed_files = collect_changed_files(left_tree, right_tree, matcher)?;
     let files = make_diff_files(&store, left_tree, right_tree, &changed_files)?;
     let mut input = scm_record::helpers::CrosstermInput;
     let recorder = scm_record::Recorder::new(
@@ -0,0 +1,392 @@
+// Copyright 2024 The Jujutsu Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! A line-based diff editor, which lists the changes as a numbered checklist
+//! and reads the selection from stdin. Unlike the `:builtin` editor, it
+//! doesn't need a terminal, so it also works when the input is piped.
+
+use std::io::{self, Write as _};
+
+use jj_lib::backend::{BackendError, MergedTreeId};
+use jj_lib::matchers::Matcher;
+use jj_lib::merged_tree::MergedTree;
+use jj_lib::repo_path::RepoPathBuf;
+use thiserror::Error;
+
+use super::builtin::{
+    apply_diff_builtin, collect_changed_files, make_diff_files, BuiltinToolError,
+};
+use crate::ui::{Ui, UiWarning, WarningCode};
+
+const HELP_TEXT: &str = "\
+Enter the number of a file (like `1`) or of a hunk (like `1.2`) to toggle it.
+Only the selected changes are included in the result.
+Other commands: `all`, `none`, `list`, `done`, `quit`.";
+
+#[derive(Debug, Error)]
+pub enum ChecklistError {
+    #[error(transparent)]
+    Builtin(#[from] BuiltinToolError),
+    #[error(transparent)]
+    Backend(#[from] BackendError),
+    #[error("Failed to read the selection")]
+    Io(#[from] io::Error),
+    #[error("The selection was cancelled")]
+    Cancelled,
+    #[error("Invalid selection at line {line}: {message}")]
+    InvalidSelection { line: usize, message: String },
+    #[error("There are no changes to {0:?}")]
+    UnchangedPath(RepoPathBuf),
+    #[error("There is no hunk {hunk} in {path:?}, which has {num_hunks} hunks")]
+    NoSuchHunk {
+        path: RepoPathBuf,
+        hunk: usize,
+        num_hunks: usize,
+    },
+}
+
+/// Changes to select without prompting, as read from a `--hunks-from` file.
+///
+/// Each line has a path relative to the workspace root, optionally followed
+/// by `:` and a comma-separated list of hunk numbers, like `src/lib.rs:1,3`.
+/// The hunks are numbered from 1, in the same way as in the checklist. Empty
+/// lines and lines starting with `#` are ignored.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct HunkSelection {
+    /// The selected paths, with the numbers of the selected hunks, or `None`
+    /// if all hunks are selected.
+    pub paths: Vec<(RepoPathBuf, Option<Vec<usize>>)>,
+}
+
+impl HunkSelection {
+    pub fn parse(text: &str) -> Result<Self, ChecklistError> {
+        let mut paths = vec![];
+        for (index, line) in text.lines().enumerate() {
+            let invalid = |message: String| ChecklistError::InvalidSelection {
+                line: index + 1,
+                message,
+            };
+            if line.trim().is_empty() || line.starts_with('#') {
+                continue;
+            }
+            // A path may contain `:` itself, so only a suffix that looks like
+            // hunk numbers is taken as such
+            let (path, hunks) = match line.rsplit_once(':') {
+                Some((path, hunks))
+                    if !hunks.is_empty()
+                        && hunks.chars().all(|c| c.is_ascii_digit() || c == ',') =>
+                {
+                    let hunks = hunks
+                        .split(',')
+                        .map(|hunk| match hunk.parse::<usize>() {
+                            Ok(0) | Err(_) => Err(invalid(format!("Invalid hunk number {hunk:?}"))),
+                            Ok(hunk) => Ok(hunk),
+                        })
+                        .collect::<Result<Vec<usize>, _>>()?;
+                    (path, Some(hunks))
+                }
+                _ => (line, None),
+            };
+            let path = RepoPathBuf::from_relative_path(path)
+                .filter(|path| !path.is_root())
+                .ok_or_else(|| invalid(format!("Invalid path {path:?}")))?;
+            paths.push((path, hunks));
+        }
+        Ok(HunkSelection { paths })
+    }
+}
+
+/// Selects the changes between the trees that `selection` lists, and returns
+/// the resulting tree.
+pub fn apply_hunk_selection(
+    left_tree: &MergedTree,
+    right_tree: &MergedTree,
+    matcher: &dyn Matcher,
+    selection: &HunkSelection,
+) -> Result<MergedTreeId, ChecklistError> {
+    let store = left_tree.store().clone();
+    let changed_files = collect_changed_files(left_tree, right_tree, matcher)?;
+    let mut files = make_diff_files(&store, left_tree, right_tree, &changed_files)?;
+    for (path, hunks) in &selection.paths {
+        let Some(file_index) = changed_files.iter().position(|changed| changed == path) else {
+            return Err(ChecklistError::UnchangedPath(path.clone()));
+        };
+        let file = &mut files[file_index];
+        let Some(hunks) = hunks else {
+            set_file_checked(file, true);
+            continue;
+        };
+        let section_indices = hunk_indices(file);
+        for &hunk in hunks {
+            let Some(&section_index) = section_indices.get(hunk - 1) else {
+                return Err(ChecklistError::NoSuchHunk {
+                    path: path.clone(),
+                    hunk,
+                    num_hunks: section_indices.len(),
+                });
+            };
+            set_section_checked(&mut file.sections[section_index], true);
+        }
+    }
+    Ok(apply_diff_builtin(
+        store,
+        left_tree,
+        right_tree,
+        changed_files,
+        &files,
+    )?)
+}
+
+/// Prints the changes between the trees as a checklist and lets the user
+/// select some of them, then returns the tree with the selected changes.
+pub fn edit_diff_checklist(
+    ui: &mut Ui,
+    left_tree: &MergedTree,
+    right_tree: &MergedTree,
+    matcher: &dyn Matcher,
+) -> Result<MergedTreeId, ChecklistError> {
+    let store = left_tree.store().clone();
+    let changed_files = collect_changed_files(left_tree, right_tree, matcher)?;
+    let mut files = make_diff_files(&store, left_tree, right_tree, &changed_files)?;
+    write_checklist(ui, &changed_files, &files)?;
+    writeln!(ui.stdout(), "{HELP_TEXT}")?;
+    loop {
+        let input = match ui.prompt("Select") {
+            Ok(input) => input,
+            Err(err) if err.kind() == io::ErrorKind::UnexpectedEof => {
+                return Err(ChecklistError::Cancelled)
+            }
+            Err(err) => return Err(err.into()),
+        };
+        for word in input.split_whitespace() {
+            match word {
+                "d" | "done" => {
+                    return Ok(apply_diff_builtin(
+                        store,
+                        left_tree,
+                        right_tree,
+                        changed_files,
+                        &files,
+                    )?);
+                }
+                "q" | "quit" => return Err(ChecklistError::Cancelled),
+                "l" | "list" => write_checklist(ui, &changed_files, &files)?,
+                "a" | "all" | "n" | "none" => {
+                    let checked = word.starts_with('a');
+                    for file in &mut files {
+                        set_file_checked(file, checked);
+                    }
+                }
+                "?" | "h" | "help" => writeln!(ui.stdout(), "{HELP_TEXT}")?,
+                _ => match toggle(&changed_files, &mut files, word) {
+                    Some(message) => writeln!(ui.stdout(), "{message}")?,
+                    None => ui.write_warning(&UiWarning::new(
+                        WarningCode::UnrecognizedResponse,
+                        format!("Unrecognized response: {word}"),
+                    ))?,
+                },
+            }
+        }
+    }
+}
+
+/// Toggles the file or hunk with the number `word`, and returns a description
+/// of the new state, or `None` if there's no such file or hunk.
+fn toggle(
+    changed_files: &[RepoPathBuf],
+    files: &mut [scm_record::File],
+    word: &str,
+) -> Option<String> {
+    let (file_number, hunk_number) = match word.split_once('.') {
+        Some((file, hunk)) => (
+            file.parse::<usize>().ok()?,
+            Some(hunk.parse::<usize>().ok()?),
+        ),
+        None => (word.parse::<usize>().ok()?, None),
+    };
+    let file_index = file_number.checked_sub(1)?;
+    let path = changed_files.get(file_index)?.as_internal_file_string();
+    let file = files.get_mut(file_index)?;
+    match hunk_number {
+        None => {
+            let checked = file_state(file) != "[x]";
+            set_file_checked(file, checked);
+            Some(format!("{} {path}", selected_word(checked)))
+        }
+        Some(hunk_number) => {
+            let section_index = *hunk_indices(file).get(hunk_number.checked_sub(1)?)?;
+            let section = &mut file.sections[section_index];
+            let checked = !is_section_checked(section);
+            set_section_checked(section, checked);
+            Some(format!(
+                "{} hunk {hunk_number} of {path}",
+                selected_word(checked)
+            ))
+        }
+    }
+}
+
+fn selected_word(checked: bool) -> &'static str {
+    if checked {
+        "Selected"
+    } else {
+        "Deselected"
+    }
+}
+
+fn write_checklist(
+    ui: &Ui,
+    changed_files: &[RepoPathBuf],
+    files: &[scm_record::File],
+) -> io::Result<()> {
+    let mut stdout = ui.stdout();
+    if files.is_empty() {
+        writeln!(stdout, "There are no changes to select.")?;
+    }
+    for (file_index, (path, file)) in changed_files.iter().zip(files).enumerate() {
+        let file_number = file_index + 1;
+        writeln!(
+            stdout,
+            "{file_number}. {} {}",
+            file_state(file),
+            path.as_internal_file_string()
+        )?;
+        for (hunk_index, section_index) in hunk_indices(file).into_iter().enumerate() {
+            let section = &file.sections[section_index];
+            let state = if is_section_checked(section) {
+                "[x]"
+            } else {
+                "[ ]"
+            };
+            write!(stdout, "   {file_number}.{} {state}", hunk_index + 1)?;
+            match section {
+                scm_record::Section::Unchanged { .. } => {}
+                scm_record::Section::Changed { lines } => {
+                    writeln!(stdout)?;
+                    for line in lines {
+                        let sign = match line.change_type {
+                            scm_record::ChangeType::Added => '+',
+                            scm_record::ChangeType::Removed => '-',
+                        };
+                        let text: &str = &line.line;
+                        let text = text.strip_suffix('\n').unwrap_or(text);
+                        writeln!(stdout, "       {sign}{text}")?;
+                    }
+                }
+                scm_record::Section::FileMode { before, after, .. } => {
+                    writeln!(stdout, " mode {:o} -> {:o}", before.0, after.0)?;
+                }
+                scm_record::Section::Binary {
+                    old_description,
+                    new_description,
+                    ..
+                } => {
+                    writeln!(
+                        stdout,
+                        " binary {} -> {}",
+                        old_description.as_deref().unwrap_or("(absent)"),
+                        new_description.as_deref().unwrap_or("(absent)")
+                    )?;
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+/// The indices of the sections of the file that can be selected.
+fn hunk_indices(file: &scm_record::File) -> Vec<usize> {
+    file.sections
+        .iter()
+        .enumerate()
+        .filter(|(_, section)| !matches!(section, scm_record::Section::Unchanged { .. }))
+        .map(|(index, _)| index)
+        .collect()
+}
+
+fn file_state(file: &scm_record::File) -> &'static str {
+    let (mut any_checked, mut all_checked) = (false, true);
+    for index in hunk_indices(file) {
+        let checked = is_section_checked(&file.sections[index]);
+        any_checked |= checked;
+        all_checked &= checked;
+    }
+    if !any_checked {
+        "[ ]"
+    } else if all_checked {
+        "[x]"
+    } else {
+        "[~]"
+    }
+}
+
+fn is_section_checked(section: &scm_record::Section) -> bool {
+    match section {
+        scm_record::Section::Unchanged { .. } => false,
+        scm_record::Section::Changed { lines } => lines.iter().any(|line| line.is_checked),
+        scm_record::Section::FileMode { is_checked, .. }
+        | scm_record::Section::Binary { is_checked, .. } => *is_checked,
+    }
+}
+
+fn set_section_checked(section: &mut scm_record::Section, checked: bool) {
+    match section {
+        scm_record::Section::Unchanged { .. } => {}
+        scm_record::Section::Changed { lines } => {
+            for line in lines {
+                line.is_checked = checked;
+            }
+        }
+        scm_record::Section::FileMode { is_checked, .. }
+        | scm_record::Section::Binary { is_checked, .. } => *is_checked = checked,
+    }
+}
+
+fn set_file_checked(file: &mut scm_record::File, checked: bool) {
+    for section in &mut file.sections {
+        set_section_checked(section, checked);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_hunk_selection() {
+        let path = |value: &str| RepoPathBuf::from_internal_string(value);
+        let selection =
+            HunkSelection::parse("# comment\nfile\n\ndir/file:1,3\nnot:hunks\nwindows:2\r\n")
+                .unwrap();
+        assert_eq!(
+            selection.paths,
+            vec![
+                (path("file"), None),
+                (path("dir/file"), Some(vec![1, 3])),
+                (path("not:hunks"), None),
+                (path("windows"), Some(vec![2])),
+            ]
+        );
+
+        insta::assert_snapshot!(
+            HunkSelection::parse("file:0").unwrap_err(),
+            @r###"Invalid selection at line 1: Invalid hunk number "0""###);
+        insta::assert_snapshot!(
+            HunkSelection::parse("file\nfile:1,,2").unwrap_err(),
+            @r###"Invalid selection at line 2: Invalid hunk number """###);
+        insta::assert_snapshot!(
+            HunkSelection::parse("../file").unwrap_err(),
+            @r###"Invalid selection at line 1: Invalid path "../file""###);
+    }
+}
@@ -26,7 +26,7 @@ use thiserror::Error;
 
 use super::{ConflictResolveError, DiffEditError, DiffGenerateError};
 use crate::config::CommandNameAndArgs;
-use crate::ui::Ui;
+use crate::ui::{Ui, UiWarning, WarningCode};
 
 /// Merge/diff tool loaded from the settings.
 #[derive(Clone, Debug, Eq, PartialEq, serde::Deserialize)]
@@ -316,7 +316,7 @@ pub fn run_mergetool_external(
     let suffix = repo_path
         .components()
         .last()
-        .map(|filename| format!("_{}", filename.as_str()))
+        .map(|filename| format!("_{}", filename.to_string_lossy()))
         // The default case below should never actually trigger, but we support it just in case
         // resolving the root path ever makes sense.
         .unwrap_or_default();
@@ -523,6 +523,8 @@ diff editing in mind and be a little inaccurate.
         base_ignores,
         fsmonitor_kind: settings.fsmonitor_kind()?,
         progress: None,
+        on_change: None,
+        cancel: None,
         max_new_file_size: settings.max_new_file_size()?,
     })?;
     Ok(output_tree_state.current_tree_id().clone())
@@ -564,7 +566,11 @@ pub fn generate_diff(
     let exit_status = child.wait().map_err(ExternalToolError::Io)?;
     tracing::info!(?cmd, ?exit_status, "The external diff generator exited:");
     if !exit_status.success() {
-        writeln!(ui.warning(), "{}", format_tool_aborted(&exit_status)).ok();
+        ui.write_warning(&UiWarning::new(
+            WarningCode::ExternalToolFailed,
+            format_tool_aborted(&exit_status),
+        ))
+        .ok();
     }
     Ok(())
 }
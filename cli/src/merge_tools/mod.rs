@@ -13,8 +13,10 @@
 // limitations under the License.
 
 mod builtin;
+mod checklist;
 mod external;
 
+use std::io::{self, IsTerminal as _};
 use std::sync::Arc;
 
 use config::ConfigError;
@@ -30,18 +32,23 @@ use pollster::FutureExt;
 use thiserror::Error;
 
 use self::builtin::{edit_diff_builtin, edit_merge_builtin, BuiltinToolError};
+use self::checklist::edit_diff_checklist;
+pub use self::checklist::{apply_hunk_selection, ChecklistError, HunkSelection};
 use self::external::{edit_diff_external, DiffCheckoutError, ExternalToolError};
 pub use self::external::{generate_diff, ExternalMergeTool};
 use crate::config::CommandNameAndArgs;
 use crate::ui::Ui;
 
 const BUILTIN_EDITOR_NAME: &str = ":builtin";
+const CHECKLIST_EDITOR_NAME: &str = ":checklist";
 
 #[derive(Debug, Error)]
 pub enum DiffEditError {
     #[error(transparent)]
     InternalTool(#[from] Box<BuiltinToolError>),
     #[error(transparent)]
+    Checklist(#[from] Box<ChecklistError>),
+    #[error(transparent)]
     ExternalTool(#[from] ExternalToolError),
     #[error(transparent)]
     DiffCheckoutError(#[from] DiffCheckoutError),
@@ -74,6 +81,8 @@ pub enum ConflictResolveError {
          supported. Conflict summary for {0:?}:\n{1}"
     )]
     NotNormalFiles(RepoPathBuf, String),
+    #[error("The `{0}` editor can only be used to edit diffs")]
+    DiffEditorOnly(&'static str),
     #[error("The conflict at {path:?} has {sides} sides. At most 2 sides are supported.")]
     ConflictTooComplicated { path: RepoPathBuf, sides: usize },
     #[error(
@@ -121,6 +130,7 @@ pub fn run_mergetool(
             let tree_id = edit_merge_builtin(tree, repo_path, content).map_err(Box::new)?;
             Ok(tree_id)
         }
+        MergeTool::Checklist => Err(ConflictResolveError::DiffEditorOnly(CHECKLIST_EDITOR_NAME)),
         MergeTool::External(editor) => external::run_mergetool_external(
             &editor, file_merge, content, repo_path, conflict, tree,
         ),
@@ -128,7 +138,7 @@ pub fn run_mergetool(
 }
 
 pub fn edit_diff(
-    ui: &Ui,
+    ui: &mut Ui,
     left_tree: &MergedTree,
     right_tree: &MergedTree,
     matcher: &dyn Matcher,
@@ -136,13 +146,25 @@ pub fn edit_diff(
     base_ignores: Arc<GitIgnoreFile>,
     settings: &UserSettings,
 ) -> Result<MergedTreeId, DiffEditError> {
+    // The `:builtin` editor reads keys from a terminal, so it can't be driven
+    // by piped input the way the checklist can.
+    let default_editor = if io::stdin().is_terminal() {
+        BUILTIN_EDITOR_NAME
+    } else {
+        CHECKLIST_EDITOR_NAME
+    };
     // Start a diff editor on the two directories.
-    let editor = get_diff_editor_from_settings(ui, settings)?;
+    let editor = get_diff_editor_from_settings(ui, settings, default_editor)?;
     match editor {
         MergeTool::Builtin => {
             let tree_id = edit_diff_builtin(left_tree, right_tree, matcher).map_err(Box::new)?;
             Ok(tree_id)
         }
+        MergeTool::Checklist => {
+            let tree_id =
+                edit_diff_checklist(ui, left_tree, right_tree, matcher).map_err(Box::new)?;
+            Ok(tree_id)
+        }
         MergeTool::External(editor) => edit_diff_external(
             editor,
             left_tree,
@@ -158,6 +180,7 @@ pub fn edit_diff(
 #[derive(Clone, Debug, Eq, PartialEq)]
 pub enum MergeTool {
     Builtin,
+    Checklist,
     External(ExternalMergeTool),
 }
 
@@ -166,13 +189,13 @@ fn editor_args_from_settings(
     ui: &Ui,
     settings: &UserSettings,
     key: &str,
+    default_editor: &str,
 ) -> Result<CommandNameAndArgs, ExternalToolError> {
     // TODO: Make this configuration have a table of possible editors and detect the
     // best one here.
     if let Some(args) = settings.config().get(key).optional()? {
         Ok(args)
     } else {
-        let default_editor = BUILTIN_EDITOR_NAME;
         writeln!(
             ui.hint(),
             "Using default editor '{default_editor}'; you can change this by setting {key}"
@@ -190,6 +213,9 @@ pub fn get_tool_config(
     if name == BUILTIN_EDITOR_NAME {
         return Ok(Some(MergeTool::Builtin));
     }
+    if name == CHECKLIST_EDITOR_NAME {
+        return Ok(Some(MergeTool::Checklist));
+    }
 
     const TABLE_KEY: &str = "merge-tools";
     let tools_table = settings.config().get_table(TABLE_KEY)?;
@@ -224,8 +250,9 @@ pub fn get_tool_config_from_args(
 fn get_diff_editor_from_settings(
     ui: &Ui,
     settings: &UserSettings,
+    default_editor: &str,
 ) -> Result<MergeTool, ExternalToolError> {
-    let args = editor_args_from_settings(ui, settings, "ui.diff-editor")?;
+    let args = editor_args_from_settings(ui, settings, "ui.diff-editor", default_editor)?;
     let editor = get_tool_config_from_args(settings, &args)?
         .unwrap_or_else(|| MergeTool::External(ExternalMergeTool::with_edit_args(&args)));
     Ok(editor)
@@ -235,7 +262,7 @@ fn get_merge_tool_from_settings(
     ui: &Ui,
     settings: &UserSettings,
 ) -> Result<MergeTool, ExternalToolError> {
-    let args = editor_args_from_settings(ui, settings, "ui.merge-editor")?;
+    let args = editor_args_from_settings(ui, settings, "ui.merge-editor", BUILTIN_EDITOR_NAME)?;
     let mergetool = get_tool_config_from_args(settings, &args)?
         .unwrap_or_else(|| MergeTool::External(ExternalMergeTool::with_merge_args(&args)));
     match mergetool {
@@ -267,12 +294,15 @@ mod tests {
             let config = config_from_string(text);
             let ui = Ui::with_config(&config).unwrap();
             let settings = UserSettings::from_config(config);
-            get_diff_editor_from_settings(&ui, &settings)
+            get_diff_editor_from_settings(&ui, &settings, BUILTIN_EDITOR_NAME)
         };
 
         // Default
         insta::assert_debug_snapshot!(get("").unwrap(), @"Builtin");
 
+        // The checklist editor
+        insta::assert_debug_snapshot!(get(r#"ui.diff-editor = ":checklist""#).unwrap(), @"Checklist");
+
         // Just program name, edit_args are filled by default
         insta::assert_debug_snapshot!(get(r#"ui.diff-editor = "my-diff""#).unwrap(), @r###"
         External(
@@ -138,6 +138,7 @@ fn build_operation_keyword(
             metadata
                 .tags
                 .iter()
+                .sorted()
                 .map(|(key, value)| format!("{key}: {value}"))
                 .join("\n")
         })),
@@ -463,6 +463,37 @@ fn build_string_method<'a, L: TemplateLanguage<'a>>(
                 s.lines().next().unwrap_or_default().to_string()
             }))
         }
+        "subject" => {
+            template_parser::expect_no_arguments(function)?;
+            language.wrap_string(TemplateFunction::new(self_property, |s| {
+                text_util::split_description(&s).0.to_owned()
+            }))
+        }
+        "body" => {
+            template_parser::expect_no_arguments(function)?;
+            language.wrap_string(TemplateFunction::new(self_property, |s| {
+                text_util::split_description(&s).1.to_owned()
+            }))
+        }
+        "has_body" => {
+            template_parser::expect_no_arguments(function)?;
+            language.wrap_boolean(TemplateFunction::new(self_property, |s| {
+                !text_util::split_description(&s).1.is_empty()
+            }))
+        }
+        "summary" => {
+            let [max_width_node] = template_parser::expect_exact_arguments(function)?;
+            let max_width_property =
+                expect_integer_expression(language, build_ctx, max_width_node)?;
+            language.wrap_string(TemplateFunction::new(
+                (self_property, max_width_property),
+                |(s, max_width)| {
+                    let (subject, _) = text_util::split_description(&s);
+                    let max_width = usize::try_from(max_width).unwrap_or(0);
+                    text_util::elide_end(subject, "…", max_width).0.into_owned()
+                },
+            ))
+        }
         "lines" => {
             template_parser::expect_no_arguments(function)?;
             language.wrap_string_list(TemplateFunction::new(self_property, |s| {
@@ -1429,6 +1460,28 @@ mod tests {
         insta::assert_snapshot!(env.render_ok(r#""".lines()"#), @"");
         insta::assert_snapshot!(env.render_ok(r#""a\nb\nc\n".lines()"#), @"a b c");
 
+        insta::assert_snapshot!(env.render_ok(r#""".subject()"#), @"");
+        insta::assert_snapshot!(env.render_ok(r#""foo\n\nbar\n".subject()"#), @"foo");
+        insta::assert_snapshot!(env.render_ok(r#""foo\n\nbar\n\nbaz\n".body()"#), @r###"
+        bar
+
+        baz
+        "###);
+        // The body starts right after the subject if there's no blank line
+        insta::assert_snapshot!(env.render_ok(r#""foo\nbar\n".body()"#), @"bar");
+        insta::assert_snapshot!(env.render_ok(r#""foo\n".body()"#), @"");
+        insta::assert_snapshot!(env.render_ok(r#""foo\n".has_body()"#), @"false");
+        insta::assert_snapshot!(env.render_ok(r#""foo\n\n\n".has_body()"#), @"false");
+        insta::assert_snapshot!(env.render_ok(r#""foo\n\nbar\n".has_body()"#), @"true");
+
+        insta::assert_snapshot!(env.render_ok(r#""foo\n\nbar\n".summary(10)"#), @"foo");
+        insta::assert_snapshot!(env.render_ok(r#""abcdef".summary(5)"#), @"abcd…");
+        insta::assert_snapshot!(env.render_ok(r#""abc💩def".summary(5)"#), @"abc…");
+        insta::assert_snapshot!(env.render_ok(r#""一二三四".summary(5)"#), @"一二…");
+        insta::assert_snapshot!(env.render_ok(r#""一二三四".summary(8)"#), @"一二三四");
+        insta::assert_snapshot!(env.render_ok(r#""abcdef".summary(0)"#), @"");
+        insta::assert_snapshot!(env.render_ok(r#""abcdef".summary(-1)"#), @"");
+
         insta::assert_snapshot!(env.render_ok(r#""".starts_with("")"#), @"true");
         insta::assert_snapshot!(env.render_ok(r#""everything".starts_with("")"#), @"true");
         insta::assert_snapshot!(env.render_ok(r#""".starts_with("foo")"#), @"false");
@@ -103,6 +103,7 @@ pub struct Ui {
     pager_cmd: CommandNameAndArgs,
     paginate: PaginationChoice,
     progress_indicator: bool,
+    warnings_format: WarningsFormat,
     formatter_factory: FormatterFactory,
     output: UiOutput,
 }
@@ -173,6 +174,139 @@ fn pagination_setting(config: &config::Config) -> Result<PaginationChoice, Comma
         .map_err(|err| CommandError::ConfigError(format!("Invalid `ui.paginate`: {err}")))
 }
 
+/// How warnings are printed to stderr.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq, serde::Deserialize)]
+#[serde(rename_all(deserialize = "kebab-case"))]
+pub enum WarningsFormat {
+    /// Human-readable lines prefixed with `Warning:` and `Hint:`
+    #[default]
+    Text,
+    /// One JSON object per warning and line
+    Json,
+}
+
+impl FromStr for WarningsFormat {
+    type Err = &'static str;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "text" => Ok(WarningsFormat::Text),
+            "json" => Ok(WarningsFormat::Json),
+            _ => Err("must be one of text or json"),
+        }
+    }
+}
+
+impl fmt::Display for WarningsFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let s = match self {
+            WarningsFormat::Text => "text",
+            WarningsFormat::Json => "json",
+        };
+        write!(f, "{s}")
+    }
+}
+
+fn warnings_format_setting(config: &config::Config) -> Result<WarningsFormat, CommandError> {
+    config
+        .get::<WarningsFormat>("ui.warnings")
+        .map_err(|err| CommandError::ConfigError(format!("Invalid `ui.warnings`: {err}")))
+}
+
+/// Stable identifier of a kind of warning. Scripts can match on it when
+/// warnings are printed as JSON.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum WarningCode {
+    AmbiguousPathArgument,
+    AmbiguousRevision,
+    BranchTrackingUnchanged,
+    ClockSkew,
+    CollapsedConflict,
+    DeprecatedCommand,
+    DeprecatedOption,
+    DeprecatedRevsetOperator,
+    EmptySelection,
+    ExternalToolFailed,
+    FailedCleanup,
+    FailedGitExport,
+    FetchRetry,
+    InvalidAlias,
+    MissingIdentity,
+    MultipleBranches,
+    NoBranchesToPush,
+    NoMatchingConfig,
+    RejectedBranchUpdate,
+    RemoteBranchesNotRenamed,
+    RepairedPath,
+    SkippedConflictingUpdates,
+    StaleWorkingCopy,
+    UnrecognizedResponse,
+    UnsnapshottedWorkingCopy,
+}
+
+impl WarningCode {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            WarningCode::AmbiguousPathArgument => "ambiguous-path-argument",
+            WarningCode::AmbiguousRevision => "ambiguous-revision",
+            WarningCode::BranchTrackingUnchanged => "branch-tracking-unchanged",
+            WarningCode::ClockSkew => "clock-skew",
+            WarningCode::CollapsedConflict => "collapsed-conflict",
+            WarningCode::DeprecatedCommand => "deprecated-command",
+            WarningCode::DeprecatedOption => "deprecated-option",
+            WarningCode::DeprecatedRevsetOperator => "deprecated-revset-operator",
+            WarningCode::EmptySelection => "empty-selection",
+            WarningCode::ExternalToolFailed => "external-tool-failed",
+            WarningCode::FailedCleanup => "failed-cleanup",
+            WarningCode::FailedGitExport => "failed-git-export",
+            WarningCode::FetchRetry => "fetch-retry",
+            WarningCode::InvalidAlias => "invalid-alias",
+            WarningCode::MissingIdentity => "missing-identity",
+            WarningCode::MultipleBranches => "multiple-branches",
+            WarningCode::NoBranchesToPush => "no-branches-to-push",
+            WarningCode::NoMatchingConfig => "no-matching-config",
+            WarningCode::RejectedBranchUpdate => "rejected-branch-update",
+            WarningCode::RemoteBranchesNotRenamed => "remote-branches-not-renamed",
+            WarningCode::RepairedPath => "repaired-path",
+            WarningCode::SkippedConflictingUpdates => "skipped-conflicting-updates",
+            WarningCode::StaleWorkingCopy => "stale-working-copy",
+            WarningCode::UnrecognizedResponse => "unrecognized-response",
+            WarningCode::UnsnapshottedWorkingCopy => "unsnapshotted-working-copy",
+        }
+    }
+}
+
+/// Warning to be printed by `Ui::write_warning()`.
+#[derive(Clone, Debug)]
+pub struct UiWarning {
+    code: WarningCode,
+    message: String,
+    details: Vec<String>,
+    hints: Vec<String>,
+}
+
+impl UiWarning {
+    pub fn new(code: WarningCode, message: impl Into<String>) -> Self {
+        UiWarning {
+            code,
+            message: message.into(),
+            details: vec![],
+            hints: vec![],
+        }
+    }
+
+    /// Adds a line of detail, which is printed indented below the message.
+    pub fn with_detail(mut self, detail: impl Into<String>) -> Self {
+        self.details.push(detail.into());
+        self
+    }
+
+    pub fn with_hint(mut self, hint: impl Into<String>) -> Self {
+        self.hints.push(hint.into());
+        self
+    }
+}
+
 fn pager_setting(config: &config::Config) -> Result<CommandNameAndArgs, CommandError> {
     config
         .get::<CommandNameAndArgs>("ui.pager")
@@ -193,6 +327,7 @@ impl Ui {
             pager_cmd: pager_setting(config)?,
             paginate: pagination_setting(config)?,
             progress_indicator,
+            warnings_format: warnings_format_setting(config)?,
             output: UiOutput::new_terminal(),
         })
     }
@@ -202,6 +337,7 @@ impl Ui {
         self.paginate = pagination_setting(config)?;
         self.pager_cmd = pager_setting(config)?;
         self.progress_indicator = progress_indicator_setting(config);
+        self.warnings_format = warnings_format_setting(config)?;
         let sanitize = io::stdout().is_terminal();
         self.formatter_factory = FormatterFactory::prepare(config, self.color, sanitize)?;
         Ok(())
@@ -240,6 +376,10 @@ impl Ui {
         self.color
     }
 
+    pub fn formatter_factory(&self) -> &FormatterFactory {
+        &self.formatter_factory
+    }
+
     pub fn new_formatter<'output, W: Write + 'output>(
         &self,
         output: W,
@@ -311,6 +451,41 @@ impl Ui {
         LabeledWriter::new(self.stderr_formatter(), "error")
     }
 
+    /// Prints the `warning` to stderr, either as text or as a line of JSON
+    /// depending on `ui.warnings`. Nothing is ever written to stdout, so
+    /// warnings don't mix with the output of the command.
+    pub fn write_warning(&self, warning: &UiWarning) -> io::Result<()> {
+        match self.warnings_format {
+            WarningsFormat::Text => {
+                writeln!(self.warning(), "Warning: {}", warning.message)?;
+                for detail in &warning.details {
+                    writeln!(self.warning(), "  {detail}")?;
+                }
+                for hint in &warning.hints {
+                    writeln!(self.hint(), "Hint: {hint}")?;
+                }
+            }
+            WarningsFormat::Json => {
+                #[derive(serde::Serialize)]
+                struct JsonWarning<'a> {
+                    code: &'a str,
+                    message: &'a str,
+                    details: &'a [String],
+                    hints: &'a [String],
+                }
+                let json = serde_json::to_string(&JsonWarning {
+                    code: warning.code.as_str(),
+                    message: &warning.message,
+                    details: &warning.details,
+                    hints: &warning.hints,
+                })
+                .unwrap();
+                writeln!(self.stderr(), "{json}")?;
+            }
+        }
+        Ok(())
+    }
+
     /// Waits for the pager exits.
     #[instrument(skip_all)]
     pub fn finalize_pager(&mut self) {
@@ -0,0 +1,137 @@
+// Copyright 2024 The Jujutsu Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::fs::OpenOptions;
+use std::io::{self, Read, Write};
+use std::process::exit;
+use std::{env, fs};
+
+const PREFIX: &str = "-----BEGIN PGP SIGNATURE-----\n\n";
+const SUFFIX: &str = "\n-----END PGP SIGNATURE-----\n";
+/// Length of the literal data packet header, and of the literal data fields
+/// that precede the data.
+const LITERAL_HEADER_LEN: usize = 12;
+
+/// FNV-1a, which is good enough to detect modified data in tests
+fn checksum(data: &[u8]) -> String {
+    let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+    for &b in data {
+        hash ^= u64::from(b);
+        hash = hash.wrapping_mul(0x0100_0000_01b3);
+    }
+    format!("{hash:016x}")
+}
+
+fn encode_base64(data: &[u8]) -> String {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut encoded = String::new();
+    for chunk in data.chunks(3) {
+        let bytes = [
+            chunk[0],
+            *chunk.get(1).unwrap_or(&0),
+            *chunk.get(2).unwrap_or(&0),
+        ];
+        let n = u32::from_be_bytes([0, bytes[0], bytes[1], bytes[2]]);
+        for i in 0..4 {
+            if i <= chunk.len() {
+                encoded.push(ALPHABET[((n >> (18 - 6 * i)) & 0x3f) as usize] as char);
+            } else {
+                encoded.push('=');
+            }
+        }
+    }
+    encoded
+}
+
+fn read_stdin() -> Vec<u8> {
+    let mut data = vec![];
+    io::stdin().read_to_end(&mut data).unwrap();
+    data
+}
+
+/// Prints the status lines `gpg --status-fd=1` would print for the signed
+/// message, and returns the exit code.
+fn verify_message(message: &[u8]) -> i32 {
+    // The fake signature packet is the key and the checksum of the data,
+    // followed by the literal data packet
+    let parsed = message.iter().position(|&b| b == b'\n').and_then(|i| {
+        let key = std::str::from_utf8(&message[..i]).ok()?;
+        let sum = message.get(i + 1..i + 17)?;
+        let data = message.get(i + 17 + LITERAL_HEADER_LEN..)?;
+        Some((key, sum, data))
+    });
+    let Some((key, sum, data)) = parsed else {
+        println!("[GNUPG:] NODATA 1");
+        return 2;
+    };
+    let display = format!("Test User <{key}@example.com>");
+    if key == "unknown" {
+        println!("[GNUPG:] ERRSIG {key} 1 8 00 0 9 -");
+        println!("[GNUPG:] NO_PUBKEY {key}");
+        2
+    } else if sum != checksum(data).as_bytes() {
+        println!("[GNUPG:] BADSIG {key} {display}");
+        1
+    } else if key == "expired" {
+        println!("[GNUPG:] EXPKEYSIG {key} {display}");
+        0
+    } else {
+        println!("[GNUPG:] GOODSIG {key} {display}");
+        0
+    }
+}
+
+/// A fake `gpg`, useful for testing
+///
+/// `--detach-sign [--local-user KEY]` writes a signature of stdin by KEY
+/// (`default` if not given). `--verify-files FILE...` prints the status lines
+/// `gpg --status-fd=1` would print for each of the signed messages, which are
+/// made of a signature and a literal data packet. Signatures by the key
+/// `unknown` can't be verified, and signatures by the key `expired` are made by
+/// an expired key.
+///
+/// If `FAKE_GPG_LOG` is set, each invocation appends its command to that file.
+fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
+    if let Some(log_path) = env::var_os("FAKE_GPG_LOG") {
+        let mut log = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(log_path)
+            .unwrap();
+        writeln!(log, "{}", args.join(" ")).unwrap();
+    }
+
+    if args.iter().any(|arg| arg == "--detach-sign") {
+        let key = args
+            .iter()
+            .position(|arg| arg == "--local-user")
+            .map_or("default", |i| &args[i + 1]);
+        let data = read_stdin();
+        let body = format!("{key}\n{}", checksum(&data));
+        print!("{PREFIX}{}{SUFFIX}", encode_base64(body.as_bytes()));
+    } else if let Some(i) = args.iter().position(|arg| arg == "--verify-files") {
+        let mut exit_code = 0;
+        for path in &args[i + 1..] {
+            println!("[GNUPG:] FILE_START 1 {path}");
+            let message = fs::read(path).unwrap();
+            exit_code = exit_code.max(verify_message(&message));
+            println!("[GNUPG:] FILE_DONE");
+        }
+        exit(exit_code);
+    } else {
+        eprintln!("fake-gpg: unsupported arguments: {args:?}");
+        exit(2);
+    }
+}
@@ -70,11 +70,15 @@ impl Default for TestEnvironment {
             debug_allow_stderr: std::env::var("JJ_DEBUG_ALLOW_STDERR").is_ok(),
         };
         // Use absolute timestamps in the operation log to make tests independent of the
-        // current time.
+        // current time. Don't tag operations with the command name and arguments
+        // hash, which would change the ids of all operations in the snapshots.
         env.add_config(
             r#"
 [template-aliases]
 'format_time_range(time_range)' = 'time_range.start() ++ " - " ++ time_range.end()'
+
+[operation]
+command-tags = false
         "#,
         );
         env
@@ -313,6 +317,27 @@ impl TestEnvironment {
         edit_script
     }
 
+    /// Sets up the fake gpg as the signing backend, signing all commits. The
+    /// returned file logs the arguments of each invocation of the fake gpg.
+    pub fn set_up_fake_gpg(&mut self) -> PathBuf {
+        let gpg_path = assert_cmd::cargo::cargo_bin("fake-gpg");
+        assert!(gpg_path.is_file());
+        // Simplified TOML escaping, hoping that there are no '"' or control characters
+        // in it
+        let escaped_gpg_path = gpg_path.to_str().unwrap().replace('\\', r"\\");
+        self.add_config(&format!(
+            r###"
+            signing.backend = "gpg"
+            signing.sign-all = true
+            signing.backends.gpg.program = "{escaped_gpg_path}"
+            "###
+        ));
+        let log_path = self.env_root().join("fake_gpg_log");
+        std::fs::write(&log_path, "").unwrap();
+        self.add_env_var("FAKE_GPG_LOG", log_path.to_str().unwrap());
+        log_path
+    }
+
     pub fn normalize_output(&self, text: &str) -> String {
         let text = text.replace("jj.exe", "jj");
         let regex = Regex::new(&format!(
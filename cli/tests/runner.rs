@@ -9,8 +9,11 @@ fn test_no_forgotten_test_files() {
 }
 
 mod test_abandon_command;
+mod test_absorb_command;
+mod test_advance_branches;
 mod test_alias;
 mod test_branch_command;
+mod test_bundle_command;
 mod test_builtin_aliases;
 mod test_cat_command;
 mod test_checkout;
@@ -19,12 +22,14 @@ mod test_commit_command;
 mod test_commit_template;
 mod test_concurrent_operations;
 mod test_config_command;
+mod test_cp_command;
 mod test_debug_command;
 mod test_describe_command;
 mod test_diff_command;
 mod test_diffedit_command;
 mod test_duplicate_command;
 mod test_edit_command;
+mod test_files_command;
 mod test_generate_md_cli_help;
 mod test_git_clone;
 mod test_git_colocated;
@@ -37,6 +42,7 @@ mod test_git_submodule;
 mod test_gitignores;
 mod test_global_opts;
 mod test_immutable_commits;
+mod test_import_patches_command;
 mod test_init_command;
 mod test_interdiff_command;
 mod test_log_command;
@@ -45,14 +51,18 @@ mod test_new_command;
 mod test_next_prev_commands;
 mod test_obslog_command;
 mod test_operations;
+mod test_range_diff_command;
+mod test_reauthor_command;
 mod test_rebase_command;
 mod test_repo_change_report;
 mod test_resolve_command;
 mod test_restore_command;
 mod test_revset_output;
 mod test_root;
+mod test_scope_command;
 mod test_shell_completion;
 mod test_show_command;
+mod test_signing;
 mod test_sparse_command;
 mod test_split_command;
 mod test_squash_command;
@@ -0,0 +1,185 @@
+// Copyright 2024 The Jujutsu Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::path::Path;
+
+use crate::common::TestEnvironment;
+
+fn commit_file(test_env: &TestEnvironment, repo_path: &Path, name: &str, contents: &str) {
+    std::fs::write(repo_path.join("file"), contents).unwrap();
+    test_env.jj_cmd_ok(repo_path, &["commit", "-m", name]);
+}
+
+fn init_stack(test_env: &TestEnvironment) -> std::path::PathBuf {
+    test_env.jj_cmd_ok(test_env.env_root(), &["init", "repo", "--git"]);
+    let repo_path = test_env.env_root().join("repo");
+    // Print only the description in commit summaries so the output doesn't
+    // depend on commit ids
+    test_env.add_config(r#"templates.commit_summary = 'description.first_line()'"#);
+    commit_file(test_env, &repo_path, "a", "1\n2\n3\n4\n5\n");
+    commit_file(test_env, &repo_path, "b", "1\n2\n3b\n4\n5\n");
+    commit_file(test_env, &repo_path, "c", "1\n2\n3b\n4\n5c\n");
+    test_env.jj_cmd_ok(&repo_path, &["describe", "-m", "source"]);
+    repo_path
+}
+
+fn get_file_contents(test_env: &TestEnvironment, repo_path: &Path, revision: &str) -> String {
+    test_env.jj_cmd_success(repo_path, &["cat", "-r", revision, "file"])
+}
+
+#[test]
+fn test_absorb() {
+    let test_env = TestEnvironment::default();
+    let repo_path = init_stack(&test_env);
+    std::fs::write(repo_path.join("file"), "1a\n2\n3bb\n4\n5cc\n").unwrap();
+
+    let (stdout, stderr) = test_env.jj_cmd_ok(&repo_path, &["absorb"]);
+    insta::assert_snapshot!(stdout, @"");
+    insta::assert_snapshot!(stderr, @r###"
+    Absorbed into a:
+      file: line 1
+    Absorbed into b:
+      file: line 3
+    Absorbed into c:
+      file: line 5
+    Working copy now at: source
+    Parent commit      : c
+    "###);
+    insta::assert_snapshot!(get_file_contents(&test_env, &repo_path, "description(a)"), @r###"
+    1a
+    2
+    3
+    4
+    5
+    "###);
+    insta::assert_snapshot!(get_file_contents(&test_env, &repo_path, "description(b)"), @r###"
+    1a
+    2
+    3bb
+    4
+    5
+    "###);
+    insta::assert_snapshot!(get_file_contents(&test_env, &repo_path, "description(c)"), @r###"
+    1a
+    2
+    3bb
+    4
+    5cc
+    "###);
+    // Nothing is left in the source revision
+    let stdout = test_env.jj_cmd_success(&repo_path, &["diff", "--summary"]);
+    insta::assert_snapshot!(stdout, @"");
+
+    // Running it again has nothing to do
+    let (_stdout, stderr) = test_env.jj_cmd_ok(&repo_path, &["absorb"]);
+    insta::assert_snapshot!(stderr, @r###"
+    Nothing changed.
+    "###);
+}
+
+#[test]
+fn test_absorb_skipped_hunks() {
+    let test_env = TestEnvironment::default();
+    let repo_path = init_stack(&test_env);
+    test_env.add_config(r#"revset-aliases."immutable_heads()" = "description(a)""#);
+    // Line 1 was last changed in the immutable commit `a`, and lines 3 and 4
+    // were last changed in different commits
+    std::fs::write(repo_path.join("file"), "1a\n2\n3bb\n4x\n5cc\n").unwrap();
+    std::fs::write(repo_path.join("added"), "added\n").unwrap();
+
+    let (_stdout, stderr) = test_env.jj_cmd_ok(&repo_path, &["absorb"]);
+    insta::assert_snapshot!(stderr, @r###"
+    Absorbed into c:
+      file: line 5
+    Left in source:
+      added (not a modified regular file)
+      file: line 1 (the lines were last changed in an immutable commit)
+      file: lines 3-4 (the lines were last changed in different commits)
+    Working copy now at: source
+    Parent commit      : c
+    "###);
+    let stdout = test_env.jj_cmd_success(&repo_path, &["diff", "--summary"]);
+    insta::assert_snapshot!(stdout, @r###"
+    A added
+    M file
+    "###);
+}
+
+#[test]
+fn test_absorb_dry_run() {
+    let test_env = TestEnvironment::default();
+    let repo_path = init_stack(&test_env);
+    std::fs::write(repo_path.join("file"), "1\n2\n3bb\n4\n5c\n").unwrap();
+    // Snapshot the working copy first
+    test_env.jj_cmd_ok(&repo_path, &["status"]);
+    let op_log_before = test_env.jj_cmd_success(&repo_path, &["op", "log"]);
+
+    let (_stdout, stderr) = test_env.jj_cmd_ok(&repo_path, &["absorb", "--dry-run"]);
+    let (plan, changes) = stderr.split_once("Changes that would be made:\n").unwrap();
+    insta::assert_snapshot!(plan, @r###"
+    Would absorb into b:
+      file: line 3
+    "###);
+    assert!(changes.contains("Rewrite commit"));
+
+    let op_log_after = test_env.jj_cmd_success(&repo_path, &["op", "log"]);
+    assert_eq!(op_log_after, op_log_before);
+    insta::assert_snapshot!(get_file_contents(&test_env, &repo_path, "description(b)"), @r###"
+    1
+    2
+    3b
+    4
+    5
+    "###);
+}
+
+#[test]
+fn test_absorb_from_merge() {
+    let test_env = TestEnvironment::default();
+    let repo_path = init_stack(&test_env);
+    test_env.jj_cmd_ok(&repo_path, &["new", "root()", "-m", "other"]);
+    std::fs::write(repo_path.join("other"), "1\n2\n").unwrap();
+    test_env.jj_cmd_ok(
+        &repo_path,
+        &["new", "description(c)", "description(other)", "-m", "merge"],
+    );
+    // Each hunk goes into an ancestor of the parent that has the lines
+    std::fs::write(repo_path.join("file"), "1a\n2\n3b\n4\n5c\n").unwrap();
+    std::fs::write(repo_path.join("other"), "1\n2x\n").unwrap();
+
+    let (_stdout, stderr) = test_env.jj_cmd_ok(&repo_path, &["absorb"]);
+    insta::assert_snapshot!(stderr, @r###"
+    Absorbed into a:
+      file: line 1
+    Absorbed into other:
+      other: line 2
+    Working copy now at: merge
+    Parent commit      : c
+    Parent commit      : other
+    "###);
+    insta::assert_snapshot!(get_file_contents(&test_env, &repo_path, "description(a)"), @r###"
+    1a
+    2
+    3
+    4
+    5
+    "###);
+    let stdout = test_env.jj_cmd_success(&repo_path, &["cat", "-r", "description(other)", "other"]);
+    insta::assert_snapshot!(stdout, @r###"
+    1
+    2x
+    "###);
+    let stdout = test_env.jj_cmd_success(&repo_path, &["diff", "--summary"]);
+    insta::assert_snapshot!(stdout, @"");
+}
@@ -0,0 +1,159 @@
+// Copyright 2024 The Jujutsu Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::path::{Path, PathBuf};
+
+use crate::common::TestEnvironment;
+
+fn set_up(test_env: &TestEnvironment) -> PathBuf {
+    test_env
+        .add_config(r#"experimental-advance-branches.enabled-branches = ["main", "release-*"]"#);
+    test_env.jj_cmd_ok(test_env.env_root(), &["init", "repo", "--git"]);
+    test_env.env_root().join("repo")
+}
+
+fn get_log_output(test_env: &TestEnvironment, cwd: &Path) -> String {
+    let template = r#"separate(" ", description.first_line(), branches)"#;
+    test_env.jj_cmd_success(cwd, &["log", "-T", template])
+}
+
+#[test]
+fn test_advance_branches_on_commit() {
+    let test_env = TestEnvironment::default();
+    let repo_path = set_up(&test_env);
+
+    test_env.jj_cmd_ok(&repo_path, &["commit", "-m", "first"]);
+    test_env.jj_cmd_ok(&repo_path, &["branch", "create", "main", "-r", "@-"]);
+    test_env.jj_cmd_ok(&repo_path, &["branch", "create", "release-1", "-r", "@-"]);
+    let (_stdout, stderr) = test_env.jj_cmd_ok(&repo_path, &["commit", "-m", "second"]);
+    assert!(stderr.contains("Advanced branch main to "), "{stderr}");
+    assert!(stderr.contains("Advanced branch release-1 to "), "{stderr}");
+    insta::assert_snapshot!(get_log_output(&test_env, &repo_path), @r###"
+    @
+    ◉  second main release-1
+    ◉  first
+    ◉
+    "###);
+}
+
+#[test]
+fn test_advance_branches_pattern_mismatch() {
+    let test_env = TestEnvironment::default();
+    let repo_path = set_up(&test_env);
+
+    test_env.jj_cmd_ok(&repo_path, &["commit", "-m", "first"]);
+    test_env.jj_cmd_ok(&repo_path, &["branch", "create", "feature", "-r", "@-"]);
+    let (_stdout, stderr) = test_env.jj_cmd_ok(&repo_path, &["commit", "-m", "second"]);
+    assert!(!stderr.contains("Advanced branch"), "{stderr}");
+    insta::assert_snapshot!(get_log_output(&test_env, &repo_path), @r###"
+    @
+    ◉  second
+    ◉  first feature
+    ◉
+    "###);
+}
+
+#[test]
+fn test_advance_branches_not_across_merge() {
+    let test_env = TestEnvironment::default();
+    let repo_path = set_up(&test_env);
+
+    test_env.jj_cmd_ok(&repo_path, &["new", "root()", "-m", "A"]);
+    test_env.jj_cmd_ok(&repo_path, &["branch", "create", "main"]);
+    test_env.jj_cmd_ok(&repo_path, &["new", "root()", "-m", "B"]);
+    test_env.jj_cmd_ok(&repo_path, &["new", "main", "@"]);
+    let (_stdout, stderr) = test_env.jj_cmd_ok(&repo_path, &["commit", "-m", "merge"]);
+    assert!(!stderr.contains("Advanced branch"), "{stderr}");
+    insta::assert_snapshot!(get_log_output(&test_env, &repo_path), @r###"
+    @
+    ◉    merge
+    ├─╮
+    │ ◉  B
+    ◉ │  A main
+    ├─╯
+    ◉
+    "###);
+}
+
+#[test]
+fn test_advance_branches_on_new() {
+    let test_env = TestEnvironment::default();
+    let repo_path = set_up(&test_env);
+
+    test_env.jj_cmd_ok(&repo_path, &["commit", "-m", "first"]);
+    test_env.jj_cmd_ok(&repo_path, &["branch", "create", "main", "-r", "@-"]);
+
+    // The branch doesn't advance to an empty working-copy commit without a
+    // description
+    let (_stdout, stderr) = test_env.jj_cmd_ok(&repo_path, &["new"]);
+    assert!(!stderr.contains("Advanced branch"), "{stderr}");
+    insta::assert_snapshot!(get_log_output(&test_env, &repo_path), @r###"
+    @
+    ◉  first main
+    ◉
+    "###);
+
+    // It advances to the working-copy commit once it's described, and never to
+    // the new empty commit
+    test_env.jj_cmd_ok(&repo_path, &["describe", "-m", "second"]);
+    let (_stdout, stderr) = test_env.jj_cmd_ok(&repo_path, &["new"]);
+    assert!(stderr.contains("Advanced branch main to "), "{stderr}");
+    insta::assert_snapshot!(get_log_output(&test_env, &repo_path), @r###"
+    @
+    ◉  second main
+    ◉  first
+    ◉
+    "###);
+
+    // Starting a new change elsewhere doesn't advance anything
+    test_env.jj_cmd_ok(&repo_path, &["describe", "-m", "third"]);
+    let (_stdout, stderr) = test_env.jj_cmd_ok(&repo_path, &["new", "root()"]);
+    assert!(!stderr.contains("Advanced branch"), "{stderr}");
+}
+
+#[test]
+fn test_advance_branches_on_new_policy() {
+    let test_env = TestEnvironment::default();
+    let repo_path = set_up(&test_env);
+
+    test_env.jj_cmd_ok(&repo_path, &["commit", "-m", "first"]);
+    test_env.jj_cmd_ok(&repo_path, &["branch", "create", "main", "-r", "@-"]);
+
+    // With "described", a non-empty commit without a description isn't
+    // advanced to
+    test_env.add_config(r#"experimental-advance-branches.on-new = "described""#);
+    std::fs::write(repo_path.join("file"), "contents").unwrap();
+    let (_stdout, stderr) = test_env.jj_cmd_ok(&repo_path, &["new"]);
+    assert!(!stderr.contains("Advanced branch"), "{stderr}");
+    test_env.jj_cmd_ok(&repo_path, &["describe", "@-", "-m", "second"]);
+    test_env.jj_cmd_ok(&repo_path, &["edit", "@-"]);
+    let (_stdout, stderr) = test_env.jj_cmd_ok(&repo_path, &["new"]);
+    assert!(stderr.contains("Advanced branch main to "), "{stderr}");
+
+    // With "never", only `jj commit` advances branches
+    test_env.add_config(r#"experimental-advance-branches.on-new = "never""#);
+    test_env.jj_cmd_ok(&repo_path, &["describe", "-m", "third"]);
+    let (_stdout, stderr) = test_env.jj_cmd_ok(&repo_path, &["new"]);
+    assert!(!stderr.contains("Advanced branch"), "{stderr}");
+    test_env.jj_cmd_ok(&repo_path, &["edit", "@-"]);
+    let (_stdout, stderr) = test_env.jj_cmd_ok(&repo_path, &["commit", "-m", "third"]);
+    assert!(stderr.contains("Advanced branch main to "), "{stderr}");
+
+    test_env.add_config(r#"experimental-advance-branches.on-new = "sometimes""#);
+    let stderr = test_env.jj_cmd_failure(&repo_path, &["new"]);
+    assert!(
+        stderr.contains("Invalid experimental-advance-branches.on-new value 'sometimes'"),
+        "{stderr}"
+    );
+}
@@ -119,6 +119,7 @@ fn test_alias_calls_unknown_command() {
     Usage: jj [OPTIONS] <COMMAND>
 
     For more information, try '--help'.
+    Hint: The command was expanded from alias foo = ["nonexistent"]
     "###);
 }
 
@@ -138,6 +139,7 @@ fn test_alias_calls_command_with_invalid_option() {
     Usage: jj log [OPTIONS] [PATHS]...
 
     For more information, try '--help'.
+    Hint: The command was expanded from alias foo = ["log", "--nonexistent"]
     "###);
 }
 
@@ -165,10 +167,38 @@ fn test_alias_cannot_override_builtin() {
 
     test_env.add_config(r#"aliases.log = ["rebase"]"#);
     // Alias should be ignored
-    let stdout = test_env.jj_cmd_success(&repo_path, &["log", "-r", "root()"]);
+    let (stdout, stderr) = test_env.jj_cmd_ok(&repo_path, &["log", "-r", "root()"]);
     insta::assert_snapshot!(stdout, @r###"
     ◉  zzzzzzzz root() 00000000
     "###);
+    insta::assert_snapshot!(stderr, @r###"
+    Warning: Cannot define an alias that overrides the built-in command "log"
+    "###);
+}
+
+#[test]
+fn test_alias_nested() {
+    let test_env = TestEnvironment::default();
+    test_env.jj_cmd_ok(test_env.env_root(), &["init", "repo", "--git"]);
+    let repo_path = test_env.env_root().join("repo");
+
+    test_env.add_config(
+        r#"[aliases]
+    b = ["bl", "-r", "@"]
+    bl = ["log", "-T", "branches"]
+    "#,
+    );
+    test_env.jj_cmd_ok(&repo_path, &["branch", "create", "my-branch"]);
+    let stdout = test_env.jj_cmd_success(&repo_path, &["b"]);
+    insta::assert_snapshot!(stdout, @r###"
+    @  my-branch
+    │
+    ~
+    "###);
+
+    // Additional arguments are appended after the expansion
+    let stdout = test_env.jj_cmd_success(&repo_path, &["b", "--no-graph"]);
+    insta::assert_snapshot!(stdout, @"my-branch");
 }
 
 #[test]
@@ -303,7 +333,7 @@ fn test_alias_in_repo_config() {
     user alias
     "###);
     insta::assert_snapshot!(stderr, @r###"
-    Command aliases cannot be loaded from -R/--repository path
+    Warning: Command aliases cannot be loaded from -R/--repository path
     "###);
 
     // Aliases are loaded from the cwd-relative workspace even with -R.
@@ -313,7 +343,7 @@ fn test_alias_in_repo_config() {
     repo1 alias
     "###);
     insta::assert_snapshot!(stderr, @r###"
-    Command aliases cannot be loaded from -R/--repository path
+    Warning: Command aliases cannot be loaded from -R/--repository path
     "###);
 
     // No warning if the expanded command is identical.
@@ -25,7 +25,7 @@ fn test_branch_multiple_names() {
     let (stdout, stderr) = test_env.jj_cmd_ok(&repo_path, &["branch", "create", "foo", "bar"]);
     insta::assert_snapshot!(stdout, @"");
     insta::assert_snapshot!(stderr, @r###"
-    warning: Creating multiple branches: foo, bar
+    Warning: Creating multiple branches: foo, bar
     "###);
     insta::assert_snapshot!(get_log_output(&test_env, &repo_path), @r###"
     @  bar foo 230dd059e1b0
@@ -36,7 +36,7 @@ fn test_branch_multiple_names() {
     let (stdout, stderr) = test_env.jj_cmd_ok(&repo_path, &["branch", "set", "foo", "bar"]);
     insta::assert_snapshot!(stdout, @"");
     insta::assert_snapshot!(stderr, @r###"
-    warning: Updating multiple branches: foo, bar
+    Warning: Updating multiple branches: foo, bar
     "###);
     insta::assert_snapshot!(get_log_output(&test_env, &repo_path), @r###"
     @  bar foo 8bb159bc30a9
@@ -71,7 +71,7 @@ fn test_branch_at_root() {
     insta::assert_snapshot!(stdout, @"");
     insta::assert_snapshot!(stderr, @r###"
     Nothing changed.
-    Failed to export some branches:
+    Warning: Failed to export some branches:
       fred: Ref cannot point to the root commit in Git
     "###);
 }
@@ -90,6 +90,54 @@ fn test_branch_empty_name() {
     "###);
 }
 
+#[test]
+fn test_branch_invalid_name() {
+    let test_env = TestEnvironment::default();
+    test_env.jj_cmd_ok(test_env.env_root(), &["init", "repo", "--git"]);
+    let repo_path = test_env.env_root().join("repo");
+
+    let stderr = test_env.jj_cmd_failure(&repo_path, &["branch", "create", "foo..bar"]);
+    insta::assert_snapshot!(stderr, @r###"
+    Error: Invalid branch name: "foo..bar"
+    Caused by: Name contains ".."
+    "###);
+    let stderr = test_env.jj_cmd_failure(&repo_path, &["branch", "create", "--", "-foo"]);
+    insta::assert_snapshot!(stderr, @r###"
+    Error: Invalid branch name: "-foo"
+    Caused by: Name starts with '-'
+    "###);
+    let stderr = test_env.jj_cmd_failure(&repo_path, &["branch", "create", "foo/.bar"]);
+    insta::assert_snapshot!(stderr, @r###"
+    Error: Invalid branch name: "foo/.bar"
+    Caused by: Path component ".bar" starts with '.' or ends with ".lock"
+    "###);
+
+    // Names that differ only in case or Unicode normalization collide
+    test_env.jj_cmd_ok(&repo_path, &["branch", "create", "main", "caf\u{e9}"]);
+    let stderr = test_env.jj_cmd_failure(&repo_path, &["branch", "create", "Main"]);
+    insta::assert_snapshot!(stderr, @r###"
+    Error: Branch name Main collides with branch main
+    Hint: Branch names that differ only in letter case or Unicode normalization can't be told apart on some file systems.
+    "###);
+    let stderr = test_env.jj_cmd_failure(&repo_path, &["branch", "create", "cafe\u{301}"]);
+    assert!(
+        stderr.starts_with("Error: Branch name cafe\u{301} collides with branch caf\u{e9}\n"),
+        "{stderr}"
+    );
+    let stderr = test_env.jj_cmd_failure(&repo_path, &["branch", "create", "foo", "FOO"]);
+    insta::assert_snapshot!(stderr, @r###"
+    Error: Branch name FOO collides with branch foo
+    Hint: Branch names that differ only in letter case or Unicode normalization can't be told apart on some file systems.
+    "###);
+    let stderr = test_env.jj_cmd_failure(&repo_path, &["branch", "rename", "main", "CAF\u{c9}"]);
+    insta::assert_snapshot!(stderr, @r###"
+    Error: Branch name CAFÉ collides with branch café
+    Hint: Branch names that differ only in letter case or Unicode normalization can't be told apart on some file systems.
+    "###);
+    // A branch can be renamed to a different case of its own name
+    test_env.jj_cmd_ok(&repo_path, &["branch", "rename", "main", "Main"]);
+}
+
 #[test]
 fn test_branch_move() {
     let test_env = TestEnvironment::default();
@@ -133,6 +181,7 @@ fn test_branch_move_conflicting() {
     let test_env = TestEnvironment::default();
     test_env.jj_cmd_ok(test_env.env_root(), &["init", "repo", "--git"]);
     let repo_path = test_env.env_root().join("repo");
+    test_env.add_config("operation.allow-at-op-mutations = true");
     let get_log = || {
         let template = r#"separate(" ", description.first_line(), branches)"#;
         let (stdout, _stderr) = test_env.jj_cmd_ok(&repo_path, &["log", "-T", template]);
@@ -187,6 +236,117 @@ fn test_branch_move_conflicting() {
     "###);
 }
 
+#[test]
+fn test_branch_list_conflicted_and_resolve() {
+    let test_env = TestEnvironment::default();
+    test_env.jj_cmd_ok(test_env.env_root(), &["init", "repo", "--git"]);
+    let repo_path = test_env.env_root().join("repo");
+    test_env.add_config("operation.allow-at-op-mutations = true");
+    test_env.add_config(r#"templates.commit_summary_no_branches = "description.first_line()""#);
+    let get_log = || {
+        let template = r#"separate(" ", description.first_line(), branches)"#;
+        test_env.jj_cmd_success(&repo_path, &["log", "-T", template])
+    };
+
+    test_env.jj_cmd_ok(&repo_path, &["new", "root()", "-mA0"]);
+    test_env.jj_cmd_ok(&repo_path, &["new", "root()", "-mB0"]);
+    test_env.jj_cmd_ok(&repo_path, &["new", "root()", "-mC0"]);
+    test_env.jj_cmd_ok(&repo_path, &["new", "description(A0)", "-mA1"]);
+
+    // Set up conflicting branches.
+    test_env.jj_cmd_ok(
+        &repo_path,
+        &["branch", "create", "-rdescription(A0)", "foo", "bar"],
+    );
+    test_env.jj_cmd_ok(
+        &repo_path,
+        &[
+            "branch",
+            "create",
+            "--at-op=@-",
+            "-rdescription(B0)",
+            "foo",
+            "bar",
+        ],
+    );
+    let (stdout, _stderr) = test_env.jj_cmd_ok(&repo_path, &["branch", "list", "--conflicted"]);
+    insta::assert_snapshot!(stdout, @r###"
+    bar (conflicted):
+      + A0
+      + B0
+    foo (conflicted):
+      + A0
+      + B0
+    "###);
+    let stdout = test_env.jj_cmd_success(&repo_path, &["branch", "list", "--conflicted", "foo"]);
+    insta::assert_snapshot!(stdout, @r###"
+    foo (conflicted):
+      + A0
+      + B0
+    "###);
+
+    let stderr = test_env.jj_cmd_failure(
+        &repo_path,
+        &["branch", "resolve", "baz", "-rdescription(A0)"],
+    );
+    insta::assert_snapshot!(stderr, @r###"
+    Error: No such branch: baz
+    "###);
+
+    // Can't resolve the branch to C0 since it's unrelated to the candidates.
+    let stderr = test_env.jj_cmd_failure(
+        &repo_path,
+        &["branch", "resolve", "foo", "-rdescription(C0)"],
+    );
+    insta::assert_snapshot!(stderr, @r###"
+    Error: Refusing to resolve branch foo to an unrelated commit
+    Hint: The revision must be a candidate target or a descendant of one. Use --force to allow it.
+    "###);
+
+    // Can resolve the branch to A1 since it's descendant of A0.
+    let (_stdout, stderr) = test_env.jj_cmd_ok(
+        &repo_path,
+        &["branch", "resolve", "foo", "-rdescription(A1)"],
+    );
+    insta::assert_snapshot!(stderr, @"");
+    insta::assert_snapshot!(get_log(), @r###"
+    @  A1 foo
+    ◉  A0 bar??
+    │ ◉  C0
+    ├─╯
+    │ ◉  B0 bar??
+    ├─╯
+    ◉
+    "###);
+
+    let stderr = test_env.jj_cmd_failure(
+        &repo_path,
+        &["branch", "resolve", "foo", "-rdescription(A0)"],
+    );
+    insta::assert_snapshot!(stderr, @r###"
+    Error: Branch foo is not conflicted
+    Hint: Use `jj branch set` to move it.
+    "###);
+
+    // Can resolve the branch to an unrelated commit with --force.
+    let (_stdout, stderr) = test_env.jj_cmd_ok(
+        &repo_path,
+        &["branch", "resolve", "bar", "-rdescription(C0)", "--force"],
+    );
+    insta::assert_snapshot!(stderr, @"");
+    insta::assert_snapshot!(get_log(), @r###"
+    @  A1 foo
+    ◉  A0
+    │ ◉  C0 bar
+    ├─╯
+    │ ◉  B0
+    ├─╯
+    ◉
+    "###);
+    let stdout = test_env.jj_cmd_success(&repo_path, &["branch", "list", "--conflicted"]);
+    insta::assert_snapshot!(stdout, @"");
+}
+
 #[test]
 fn test_branch_rename() {
     let test_env = TestEnvironment::default();
@@ -251,7 +411,7 @@ fn test_branch_forget_glob() {
         test_env.jj_cmd_ok(&repo_path, &["branch", "forget", "--glob", "foo-[1-3]"]);
     insta::assert_snapshot!(stdout, @"");
     insta::assert_snapshot!(stderr, @r###"
-    --glob has been deprecated. Please prefix the pattern with `glob:` instead.
+    Warning: --glob has been deprecated. Please prefix the pattern with `glob:` instead.
     Forgot 2 branches.
     "###);
     test_env.jj_cmd_ok(&repo_path, &["undo"]);
@@ -273,7 +433,7 @@ fn test_branch_forget_glob() {
     );
     insta::assert_snapshot!(stdout, @"");
     insta::assert_snapshot!(stderr, @r###"
-    --glob has been deprecated. Please prefix the pattern with `glob:` instead.
+    Warning: --glob has been deprecated. Please prefix the pattern with `glob:` instead.
     "###);
     insta::assert_snapshot!(get_log_output(&test_env, &repo_path), @r###"
     @  bar-2 230dd059e1b0
@@ -294,7 +454,7 @@ fn test_branch_forget_glob() {
         &["branch", "forget", "glob:bar*", "glob:baz*", "--glob=boom*"],
     );
     insta::assert_snapshot!(stderr, @r###"
-    --glob has been deprecated. Please prefix the pattern with `glob:` instead.
+    Warning: --glob has been deprecated. Please prefix the pattern with `glob:` instead.
     Error: No matching branches for patterns: baz*, boom*
     "###);
 }
@@ -333,7 +493,7 @@ fn test_branch_delete_glob() {
         test_env.jj_cmd_ok(&repo_path, &["branch", "delete", "--glob", "foo-[1-3]"]);
     insta::assert_snapshot!(stdout, @"");
     insta::assert_snapshot!(stderr, @r###"
-    --glob has been deprecated. Please prefix the pattern with `glob:` instead.
+    Warning: --glob has been deprecated. Please prefix the pattern with `glob:` instead.
     Deleted 2 branches.
     "###);
     test_env.jj_cmd_ok(&repo_path, &["undo"]);
@@ -351,7 +511,7 @@ fn test_branch_delete_glob() {
     // forget`, it's not allowed to delete already deleted branches.
     let stderr = test_env.jj_cmd_failure(&repo_path, &["branch", "delete", "--glob=foo-[1-3]"]);
     insta::assert_snapshot!(stderr, @r###"
-    --glob has been deprecated. Please prefix the pattern with `glob:` instead.
+    Warning: --glob has been deprecated. Please prefix the pattern with `glob:` instead.
     Error: No matching branches for patterns: foo-[1-3]
     "###);
 
@@ -363,7 +523,7 @@ fn test_branch_delete_glob() {
     );
     insta::assert_snapshot!(stdout, @"");
     insta::assert_snapshot!(stderr, @r###"
-    --glob has been deprecated. Please prefix the pattern with `glob:` instead.
+    Warning: --glob has been deprecated. Please prefix the pattern with `glob:` instead.
     "###);
     insta::assert_snapshot!(get_log_output(&test_env, &repo_path), @r###"
     @  bar-2 foo-1@origin foo-3@origin foo-4@origin 6fbf398c2d59
@@ -374,13 +534,13 @@ fn test_branch_delete_glob() {
     insta::assert_snapshot!(get_branch_output(&test_env, &repo_path), @r###"
     bar-2: qpvuntsm 6fbf398c (empty) commit
       @origin: qpvuntsm 6fbf398c (empty) commit
-    foo-1 (deleted)
+    foo-1 (deleted, will be pushed)
       @origin: qpvuntsm 6fbf398c (empty) commit
       (this branch will be *deleted permanently* on the remote on the next `jj git push`. Use `jj branch forget` to prevent this)
-    foo-3 (deleted)
+    foo-3 (deleted, will be pushed)
       @origin: qpvuntsm 6fbf398c (empty) commit
       (this branch will be *deleted permanently* on the remote on the next `jj git push`. Use `jj branch forget` to prevent this)
-    foo-4 (deleted)
+    foo-4 (deleted, will be pushed)
       @origin: qpvuntsm 6fbf398c (empty) commit
       (this branch will be *deleted permanently* on the remote on the next `jj git push`. Use `jj branch forget` to prevent this)
     "###);
@@ -623,7 +783,7 @@ fn test_branch_forget_deleted_or_nonexistent_branch() {
     test_env.jj_cmd_ok(&repo_path, &["git", "fetch", "--remote=origin"]);
     test_env.jj_cmd_ok(&repo_path, &["branch", "delete", "feature1"]);
     insta::assert_snapshot!(get_branch_output(&test_env, &repo_path), @r###"
-    feature1 (deleted)
+    feature1 (deleted, will be pushed)
       @origin: mzyxwzks 9f01a0e0 message
       (this branch will be *deleted permanently* on the remote on the next `jj git push`. Use `jj branch forget` to prevent this)
     "###);
@@ -883,14 +1043,14 @@ fn test_branch_track_untrack_patterns() {
     test_env.jj_cmd_ok(&repo_path, &["branch", "track", "feature1@origin"]);
     let (_, stderr) = test_env.jj_cmd_ok(&repo_path, &["branch", "track", "feature1@origin"]);
     insta::assert_snapshot!(stderr, @r###"
-    Remote branch already tracked: feature1@origin
+    Warning: Remote branch already tracked: feature1@origin
     Nothing changed.
     "###);
 
     // Untrack non-tracking branch
     let (_, stderr) = test_env.jj_cmd_ok(&repo_path, &["branch", "untrack", "feature2@origin"]);
     insta::assert_snapshot!(stderr, @r###"
-    Remote branch not tracked yet: feature2@origin
+    Warning: Remote branch not tracked yet: feature2@origin
     Nothing changed.
     "###);
 
@@ -898,7 +1058,7 @@ fn test_branch_track_untrack_patterns() {
     test_env.jj_cmd_ok(&repo_path, &["git", "export"]);
     let (_, stderr) = test_env.jj_cmd_ok(&repo_path, &["branch", "untrack", "main@git"]);
     insta::assert_snapshot!(stderr, @r###"
-    Git-tracking branch cannot be untracked: main@git
+    Warning: Git-tracking branch cannot be untracked: main@git
     Nothing changed.
     "###);
     insta::assert_snapshot!(get_branch_output(&test_env, &repo_path), @r###"
@@ -913,9 +1073,9 @@ fn test_branch_track_untrack_patterns() {
     // Untrack by pattern
     let (_, stderr) = test_env.jj_cmd_ok(&repo_path, &["branch", "untrack", "glob:*@*"]);
     insta::assert_snapshot!(stderr, @r###"
-    Git-tracking branch cannot be untracked: feature1@git
-    Remote branch not tracked yet: feature2@origin
-    Git-tracking branch cannot be untracked: main@git
+    Warning: Git-tracking branch cannot be untracked: feature1@git
+    Warning: Remote branch not tracked yet: feature2@origin
+    Warning: Git-tracking branch cannot be untracked: main@git
     "###);
     insta::assert_snapshot!(get_branch_output(&test_env, &repo_path), @r###"
     feature1: omvolwpu 1336caed commit
@@ -987,7 +1147,7 @@ fn test_branch_list() {
     insta::assert_snapshot!(
         test_env.jj_cmd_success(&local_path, &["branch", "list"]), @r###"
     local-only: wqnwkozp 4e887f78 (empty) local-only
-    remote-delete (deleted)
+    remote-delete (deleted, will be pushed)
       @origin: mnmymoky 203e60eb (empty) remote-delete
       (this branch will be *deleted permanently* on the remote on the next `jj git push`. Use `jj branch forget` to prevent this)
     remote-sync: zwtyzrop c761c7ea (empty) remote-sync
@@ -998,7 +1158,7 @@ fn test_branch_list() {
     insta::assert_snapshot!(
         test_env.jj_cmd_success(&local_path, &["branch", "list", "--all"]), @r###"
     local-only: wqnwkozp 4e887f78 (empty) local-only
-    remote-delete (deleted)
+    remote-delete (deleted, will be pushed)
       @origin: mnmymoky 203e60eb (empty) remote-delete
       (this branch will be *deleted permanently* on the remote on the next `jj git push`. Use `jj branch forget` to prevent this)
     remote-sync: zwtyzrop c761c7ea (empty) remote-sync
@@ -1009,6 +1169,67 @@ fn test_branch_list() {
     "###);
 }
 
+#[test]
+fn test_branch_list_json() {
+    let test_env = TestEnvironment::default();
+    test_env.add_config("git.auto-local-branch = true");
+
+    // Initialize remote refs
+    test_env.jj_cmd_ok(test_env.env_root(), &["init", "remote", "--git"]);
+    let remote_path = test_env.env_root().join("remote");
+    for branch in ["remote-sync", "remote-unsync"] {
+        test_env.jj_cmd_ok(&remote_path, &["new", "root()", "-m", branch]);
+        test_env.jj_cmd_ok(&remote_path, &["branch", "create", branch]);
+    }
+    test_env.jj_cmd_ok(&remote_path, &["new"]);
+    test_env.jj_cmd_ok(&remote_path, &["git", "export"]);
+
+    // Initialize local refs
+    let mut remote_git_path = remote_path;
+    remote_git_path.extend([".jj", "repo", "store", "git"]);
+    test_env.jj_cmd_ok(
+        test_env.env_root(),
+        &["git", "clone", remote_git_path.to_str().unwrap(), "local"],
+    );
+    let local_path = test_env.env_root().join("local");
+    test_env.jj_cmd_ok(&local_path, &["new", "root()", "-m", "local-only"]);
+    test_env.jj_cmd_ok(&local_path, &["branch", "create", "local-only"]);
+    test_env.jj_cmd_ok(
+        &local_path,
+        &["branch", "set", "--allow-backwards", "remote-unsync"],
+    );
+
+    // Replace the commit ids with the descriptions to make the output stable
+    let mut stdout = test_env.jj_cmd_success(&local_path, &["branch", "list", "--all", "--json"]);
+    let commits = test_env.jj_cmd_success(
+        &local_path,
+        &[
+            "log",
+            "--no-graph",
+            "-r=all()",
+            "-T",
+            r#"commit_id ++ " " ++ description.first_line() ++ "\n""#,
+        ],
+    );
+    for line in commits.lines() {
+        let (commit_id, description) = line.split_once(' ').unwrap();
+        stdout = stdout.replace(commit_id, description);
+    }
+    // Each line is a JSON object
+    for line in stdout.lines() {
+        serde_json::from_str::<serde_json::Value>(line).unwrap();
+    }
+    insta::assert_snapshot!(stdout, @r###"
+    {"name":"local-only","target":{"conflict":false,"added_ids":["local-only"],"removed_ids":[]},"remotes":[]}
+    {"name":"remote-sync","target":{"conflict":false,"added_ids":["remote-sync"],"removed_ids":[]},"remotes":[{"remote":"origin","tracking":true,"target":{"conflict":false,"added_ids":["remote-sync"],"removed_ids":[]},"ahead_by":null,"behind_by":null}]}
+    {"name":"remote-unsync","target":{"conflict":false,"added_ids":["local-only"],"removed_ids":[]},"remotes":[{"remote":"origin","tracking":true,"target":{"conflict":false,"added_ids":["remote-unsync"],"removed_ids":[]},"ahead_by":{"count":1,"capped":false},"behind_by":{"count":1,"capped":false}}]}
+    "###);
+
+    // Synchronized remote branches are only included with --all
+    let stdout = test_env.jj_cmd_success(&local_path, &["branch", "list", "--json", "remote-sync"]);
+    assert!(stdout.contains(r#""remotes":[]"#), "{stdout}");
+}
+
 #[test]
 fn test_branch_list_filtered() {
     let test_env = TestEnvironment::default();
@@ -1062,7 +1283,7 @@ fn test_branch_list_filtered() {
     // All branches are listed by default.
     insta::assert_snapshot!(test_env.jj_cmd_success(&local_path, &["branch", "list"]), @r###"
     local-keep: kpqxywon c7b4c09c (empty) local-keep
-    remote-delete (deleted)
+    remote-delete (deleted, will be pushed)
       @origin: yxusvupt dad5f298 (empty) remote-delete
       (this branch will be *deleted permanently* on the remote on the next `jj git push`. Use `jj branch forget` to prevent this)
     remote-keep: nlwprzpn 911e9120 (empty) remote-keep
@@ -1105,7 +1326,7 @@ fn test_branch_list_filtered() {
 
     // Can select deleted branch by name pattern, but not by revset.
     insta::assert_snapshot!(query(&["remote-delete"]), @r###"
-    remote-delete (deleted)
+    remote-delete (deleted, will be pushed)
       @origin: yxusvupt dad5f298 (empty) remote-delete
       (this branch will be *deleted permanently* on the remote on the next `jj git push`. Use `jj branch forget` to prevent this)
     "###);
@@ -1119,7 +1340,7 @@ fn test_branch_list_filtered() {
     // Name patterns are OR-ed.
     insta::assert_snapshot!(query(&["glob:*-keep", "remote-delete"]), @r###"
     local-keep: kpqxywon c7b4c09c (empty) local-keep
-    remote-delete (deleted)
+    remote-delete (deleted, will be pushed)
       @origin: yxusvupt dad5f298 (empty) remote-delete
       (this branch will be *deleted permanently* on the remote on the next `jj git push`. Use `jj branch forget` to prevent this)
     remote-keep: nlwprzpn 911e9120 (empty) remote-keep
@@ -1178,7 +1399,55 @@ fn test_branch_list_much_remote_divergence() {
         test_env.jj_cmd_success(&local_path, &["branch", "list"]), @r###"
     local-only: zkyosouw 4ab3f751 (empty) local-only
     remote-unsync: zkyosouw 4ab3f751 (empty) local-only
-      @origin (ahead by at least 10 commits, behind by at least 10 commits): lxyktnks 19582022 (empty) remote-unsync
+      @origin (ahead by 16 commits, behind by 16 commits): lxyktnks 19582022 (empty) remote-unsync
+    "###);
+}
+
+#[test]
+fn test_branch_case_insensitive_lookup() {
+    let test_env = TestEnvironment::default();
+    test_env.jj_cmd_ok(test_env.env_root(), &["init", "repo", "--git"]);
+    let repo_path = test_env.env_root().join("repo");
+    test_env.add_config("ui.case-insensitive-refs = true");
+
+    test_env.jj_cmd_ok(&repo_path, &["describe", "-m", "login"]);
+    test_env.jj_cmd_ok(&repo_path, &["branch", "create", "Feature/Login"]);
+    test_env.jj_cmd_ok(&repo_path, &["new", "root()"]);
+
+    let stdout = test_env.jj_cmd_success(
+        &repo_path,
+        &[
+            "log",
+            "--no-graph",
+            "-r",
+            "feature/login",
+            "-T",
+            "description",
+        ],
+    );
+    insta::assert_snapshot!(stdout, @r###"
+    login
+    "###);
+    let stdout = test_env.jj_cmd_success(
+        &repo_path,
+        &[
+            "log",
+            "--no-graph",
+            "-r",
+            "branch:FEATURE/LOGIN",
+            "-T",
+            "description",
+        ],
+    );
+    insta::assert_snapshot!(stdout, @r###"
+    login
+    "###);
+
+    // A branch differing only by case can't be created
+    let stderr = test_env.jj_cmd_failure(&repo_path, &["branch", "create", "feature/login"]);
+    insta::assert_snapshot!(stderr, @r###"
+    Error: Branch name feature/login collides with branch Feature/Login
+    Hint: Branch names that differ only in letter case or Unicode normalization can't be told apart on some file systems.
     "###);
 }
 
@@ -0,0 +1,85 @@
+// Copyright 2024 The Jujutsu Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use crate::common::TestEnvironment;
+
+#[test]
+fn test_bundle_create_and_apply() {
+    let test_env = TestEnvironment::default();
+    test_env.jj_cmd_ok(test_env.env_root(), &["init", "source", "--git"]);
+    let source_path = test_env.env_root().join("source");
+    std::fs::write(source_path.join("file1"), "1\n").unwrap();
+    test_env.jj_cmd_ok(&source_path, &["commit", "-m", "first"]);
+    std::fs::write(source_path.join("file2"), "2\n").unwrap();
+    test_env.jj_cmd_ok(&source_path, &["describe", "-m", "second"]);
+
+    let (stdout, stderr) = test_env.jj_cmd_ok(
+        &source_path,
+        &[
+            "bundle",
+            "create",
+            "-r",
+            "root()..@",
+            "-o",
+            "../commits.bundle",
+        ],
+    );
+    insta::assert_snapshot!(stdout, @"");
+    insta::assert_snapshot!(stderr, @r###"
+    Wrote 2 commits (6 objects) to ../commits.bundle
+    "###);
+
+    test_env.jj_cmd_ok(test_env.env_root(), &["init", "dest", "--git"]);
+    let dest_path = test_env.env_root().join("dest");
+    let (stdout, stderr) =
+        test_env.jj_cmd_ok(&dest_path, &["bundle", "apply", "../commits.bundle"]);
+    insta::assert_snapshot!(stdout, @"");
+    let first_line = stderr.lines().next().unwrap();
+    insta::assert_snapshot!(first_line, @"Applied 2 commits (6 objects, 0 already present)");
+    let stdout = test_env.jj_cmd_success(
+        &dest_path,
+        &[
+            "log",
+            "--no-graph",
+            "-r",
+            "~@ & ~root()",
+            "-T",
+            "description",
+        ],
+    );
+    insta::assert_snapshot!(stdout, @r###"
+    second
+    first
+    "###);
+
+    // Applying the bundle again doesn't write anything
+    let (_stdout, stderr) =
+        test_env.jj_cmd_ok(&dest_path, &["bundle", "apply", "../commits.bundle"]);
+    let first_line = stderr.lines().next().unwrap();
+    insta::assert_snapshot!(first_line, @"Applied 2 commits (6 objects, 6 already present)");
+}
+
+#[test]
+fn test_bundle_apply_not_a_bundle() {
+    let test_env = TestEnvironment::default();
+    test_env.jj_cmd_ok(test_env.env_root(), &["init", "repo", "--git"]);
+    let repo_path = test_env.env_root().join("repo");
+    std::fs::write(test_env.env_root().join("garbage"), "garbage\n").unwrap();
+
+    let stderr = test_env.jj_cmd_failure(&repo_path, &["bundle", "apply", "../garbage"]);
+    insta::assert_snapshot!(stderr, @r###"
+    Error: Failed to apply bundle ../garbage
+    Caused by: Not a bundle file
+    "###);
+}
@@ -58,7 +58,7 @@ fn test_cat() {
     // Error if the path doesn't exist
     let stderr = test_env.jj_cmd_failure(&repo_path, &["cat", "nonexistent"]);
     insta::assert_snapshot!(stderr, @r###"
-    Error: No such path
+    Error: No such path: nonexistent
     "###);
 
     // Error if the path is not a file
@@ -74,10 +74,10 @@ fn test_cat() {
     let stdout = test_env.jj_cmd_success(&repo_path, &["cat", "file1"]);
     insta::assert_snapshot!(stdout, @r###"
     <<<<<<<
-    %%%%%%%
+    %%%%%%% diff from parents of rebased commit to rebase destination
     -b
     +a
-    +++++++
+    +++++++ rebased commit
     c
     >>>>>>>
     "###);
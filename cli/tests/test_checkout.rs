@@ -29,8 +29,8 @@ fn test_checkout() {
     let (stdout, stderr) = test_env.jj_cmd_ok(&repo_path, &["checkout", "@"]);
     insta::assert_snapshot!(stdout, @"");
     insta::assert_snapshot!(stderr, @r###"
-    warning: `jj checkout` is deprecated; use `jj new` instead, which is equivalent
-    warning: `jj checkout` will be removed in a future version, and this will be a hard error
+    Warning: `jj checkout` is deprecated, and will be removed in a future version
+    Hint: Use `jj new` instead, which is equivalent
     Working copy now at: zsuskuln 05ce7118 (empty) (no description set)
     Parent commit      : rlvkpnrz 5c52832c (empty) second
     "###);
@@ -66,8 +66,8 @@ fn test_checkout_not_single_rev() {
 
     let stderr = test_env.jj_cmd_failure(&repo_path, &["checkout", "root()..@"]);
     insta::assert_snapshot!(stderr, @r###"
-    warning: `jj checkout` is deprecated; use `jj new` instead, which is equivalent
-    warning: `jj checkout` will be removed in a future version, and this will be a hard error
+    Warning: `jj checkout` is deprecated, and will be removed in a future version
+    Hint: Use `jj new` instead, which is equivalent
     Error: Revset "root()..@" resolved to more than one revision
     Hint: The revset "root()..@" resolved to these revisions:
     royxmykx 2f859371 (empty) (no description set)
@@ -80,8 +80,8 @@ fn test_checkout_not_single_rev() {
 
     let stderr = test_env.jj_cmd_failure(&repo_path, &["checkout", "root()..@-"]);
     insta::assert_snapshot!(stderr, @r###"
-    warning: `jj checkout` is deprecated; use `jj new` instead, which is equivalent
-    warning: `jj checkout` will be removed in a future version, and this will be a hard error
+    Warning: `jj checkout` is deprecated, and will be removed in a future version
+    Hint: Use `jj new` instead, which is equivalent
     Error: Revset "root()..@-" resolved to more than one revision
     Hint: The revset "root()..@-" resolved to these revisions:
     mzvwutvl 5c1afd8b (empty) fifth
@@ -93,8 +93,8 @@ fn test_checkout_not_single_rev() {
 
     let stderr = test_env.jj_cmd_failure(&repo_path, &["checkout", "@-|@--"]);
     insta::assert_snapshot!(stderr, @r###"
-    warning: `jj checkout` is deprecated; use `jj new` instead, which is equivalent
-    warning: `jj checkout` will be removed in a future version, and this will be a hard error
+    Warning: `jj checkout` is deprecated, and will be removed in a future version
+    Hint: Use `jj new` instead, which is equivalent
     Error: Revset "@-|@--" resolved to more than one revision
     Hint: The revset "@-|@--" resolved to these revisions:
     mzvwutvl 5c1afd8b (empty) fifth
@@ -103,8 +103,8 @@ fn test_checkout_not_single_rev() {
 
     let stderr = test_env.jj_cmd_failure(&repo_path, &["checkout", "none()"]);
     insta::assert_snapshot!(stderr, @r###"
-    warning: `jj checkout` is deprecated; use `jj new` instead, which is equivalent
-    warning: `jj checkout` will be removed in a future version, and this will be a hard error
+    Warning: `jj checkout` is deprecated, and will be removed in a future version
+    Hint: Use `jj new` instead, which is equivalent
     Error: Revset "none()" didn't resolve to any revisions
     "###);
 }
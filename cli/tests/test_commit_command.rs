@@ -31,6 +31,26 @@ fn test_commit_with_description_from_cli() {
     "###);
 }
 
+#[test]
+fn test_commit_validation() {
+    let test_env = TestEnvironment::default();
+    test_env.jj_cmd_ok(test_env.env_root(), &["init", "repo", "--git"]);
+    let workspace_path = test_env.env_root().join("repo");
+    test_env.add_config(r#"validation.forbidden-paths = ["secret"]"#);
+    std::fs::write(workspace_path.join("secret"), "password\n").unwrap();
+
+    // The snapshot isn't validated, but committing the change is rejected
+    let stderr = test_env.jj_cmd_failure(&workspace_path, &["commit", "-m=first"]);
+    assert!(
+        stderr.starts_with("Error: Refusing to commit the transaction"),
+        "{stderr}"
+    );
+    assert!(
+        stderr.contains("changes the forbidden path secret"),
+        "{stderr}"
+    );
+}
+
 #[test]
 fn test_commit_with_editor() {
     let mut test_env = TestEnvironment::default();
@@ -171,7 +191,7 @@ fn test_commit_paths() {
 }
 
 #[test]
-fn test_commit_paths_warning() {
+fn test_commit_paths_no_match() {
     let test_env = TestEnvironment::default();
     test_env.jj_cmd_ok(test_env.env_root(), &["init", "repo", "--git"]);
     let workspace_path = test_env.env_root().join("repo");
@@ -179,13 +199,10 @@ fn test_commit_paths_warning() {
     std::fs::write(workspace_path.join("file1"), "foo\n").unwrap();
     std::fs::write(workspace_path.join("file2"), "bar\n").unwrap();
 
-    let (stdout, stderr) = test_env.jj_cmd_ok(&workspace_path, &["commit", "-m=first", "file3"]);
+    let stderr = test_env.jj_cmd_failure(&workspace_path, &["commit", "-m=first", "file3"]);
     insta::assert_snapshot!(stderr, @r###"
-    The given paths do not match any file: file3
-    Working copy now at: rlvkpnrz 67872820 (no description set)
-    Parent commit      : qpvuntsm 69542c19 (empty) first
+    Error: The given paths do not match any changed file: file3
     "###);
-    insta::assert_snapshot!(stdout, @"");
 
     let stdout = test_env.jj_cmd_success(&workspace_path, &["diff"]);
     insta::assert_snapshot!(stdout, @r###"
@@ -196,6 +213,65 @@ fn test_commit_paths_warning() {
     "###);
 }
 
+#[test]
+fn test_commit_paths_match_everything() {
+    let test_env = TestEnvironment::default();
+    test_env.jj_cmd_ok(test_env.env_root(), &["init", "repo", "--git"]);
+    let workspace_path = test_env.env_root().join("repo");
+
+    std::fs::write(workspace_path.join("file1"), "foo\n").unwrap();
+    std::fs::write(workspace_path.join("file2"), "bar\n").unwrap();
+
+    test_env.jj_cmd_ok(&workspace_path, &["commit", "-m=first", "file1", "file2"]);
+    let stdout = test_env.jj_cmd_success(&workspace_path, &["diff", "-r", "@-"]);
+    insta::assert_snapshot!(stdout, @r###"
+    Added regular file file1:
+            1: foo
+    Added regular file file2:
+            1: bar
+    "###);
+    let stdout = test_env.jj_cmd_success(&workspace_path, &["diff"]);
+    insta::assert_snapshot!(stdout, @"");
+}
+
+#[test]
+fn test_commit_paths_with_conflict() {
+    let test_env = TestEnvironment::default();
+    test_env.jj_cmd_ok(test_env.env_root(), &["init", "repo", "--git"]);
+    let workspace_path = test_env.env_root().join("repo");
+
+    test_env.jj_cmd_ok(&workspace_path, &["describe", "-m=base"]);
+    std::fs::write(workspace_path.join("file"), "base\n").unwrap();
+    test_env.jj_cmd_ok(&workspace_path, &["new", "-m=left"]);
+    std::fs::write(workspace_path.join("file"), "left\n").unwrap();
+    test_env.jj_cmd_ok(&workspace_path, &["new", "description(base)", "-m=right"]);
+    std::fs::write(workspace_path.join("file"), "right\n").unwrap();
+    test_env.jj_cmd_ok(
+        &workspace_path,
+        &["new", "description(left)", "description(right)"],
+    );
+    std::fs::write(workspace_path.join("file2"), "new\n").unwrap();
+
+    // The conflict in the working-copy commit is kept in the committed part
+    test_env.jj_cmd_ok(&workspace_path, &["commit", "-m=merged", "file2"]);
+    let template = r#"separate(" ", description.first_line(), if(conflict, "conflict"))"#;
+    let stdout = test_env.jj_cmd_success(&workspace_path, &["log", "-T", template]);
+    insta::assert_snapshot!(stdout, @r###"
+    @  conflict
+    ◉    merged conflict
+    ├─╮
+    │ ◉  right
+    ◉ │  left
+    ├─╯
+    ◉  base
+    ◉
+    "###);
+    let stdout = test_env.jj_cmd_success(&workspace_path, &["diff", "-r", "@-", "--summary"]);
+    insta::assert_snapshot!(stdout, @r###"
+    A file2
+    "###);
+}
+
 fn get_log_output(test_env: &TestEnvironment, cwd: &Path) -> String {
     let template = r#"commit_id.short() ++ " " ++ description"#;
     test_env.jj_cmd_success(cwd, &["log", "-T", template])
@@ -304,6 +304,7 @@ fn test_log_obslog_divergence() {
     let test_env = TestEnvironment::default();
     test_env.jj_cmd_ok(test_env.env_root(), &["init", "repo", "--git"]);
     let repo_path = test_env.env_root().join("repo");
+    test_env.add_config("operation.allow-at-op-mutations = true");
 
     std::fs::write(repo_path.join("file"), "foo\n").unwrap();
     test_env.jj_cmd_ok(&repo_path, &["describe", "-m", "description 1"]);
@@ -474,6 +475,237 @@ fn test_log_git_head() {
     "###);
 }
 
+#[test]
+fn test_log_commit_metadata() {
+    let test_env = TestEnvironment::default();
+    let repo_path = test_env.env_root().join("repo");
+    let git_repo = git2::Repository::init(&repo_path).unwrap();
+    let signature =
+        git2::Signature::new("Some One", "some.one@example.com", &git2::Time::new(0, 0)).unwrap();
+    let empty_tree_id = git_repo.treebuilder(None).unwrap().write().unwrap();
+    let empty_tree = git_repo.find_tree(empty_tree_id).unwrap();
+    git_repo
+        .commit(
+            Some("refs/heads/main"),
+            &signature,
+            &signature,
+            "from git",
+            &empty_tree,
+            &[],
+        )
+        .unwrap();
+    test_env.jj_cmd_ok(&repo_path, &["init", "--git-repo=."]);
+    test_env.jj_cmd_ok(&repo_path, &["describe", "-m=native"]);
+    test_env.jj_cmd_ok(&repo_path, &["backout", "-r=main"]);
+
+    let template = r#"description.first_line() ++ " via=" ++ commit.metadata("committed-via") ++ " generated=" ++ commit.metadata("generated-description") ++ "\n""#;
+    let stdout = test_env.jj_cmd_success(
+        &repo_path,
+        &["log", "--no-graph", "-r=all() ~ root()", "-T", template],
+    );
+    insta::assert_snapshot!(stdout, @r###"
+    backout of commit 186d1c6889cda9c60f4e36cb7708e102f1a37a9f via= generated=true
+    native via= generated=
+    from git via=git generated=
+    "###);
+
+    let stdout = test_env.jj_cmd_success(
+        &repo_path,
+        &[
+            "log",
+            "--no-graph",
+            r#"-r=has_metadata("committed-via")"#,
+            "-T",
+            r#"description.first_line() ++ "\n""#,
+        ],
+    );
+    insta::assert_snapshot!(stdout, @r###"
+    from git
+    "###);
+}
+
+#[test]
+fn test_log_diff_files() {
+    let test_env = TestEnvironment::default();
+    test_env.jj_cmd_ok(test_env.env_root(), &["init", "repo", "--git"]);
+    let repo_path = test_env.env_root().join("repo");
+    std::fs::create_dir(repo_path.join("dir")).unwrap();
+
+    test_env.jj_cmd_ok(&repo_path, &["describe", "-m=a"]);
+    std::fs::write(repo_path.join("file1"), "a\n").unwrap();
+    std::fs::write(repo_path.join("dir").join("file2"), "a\n").unwrap();
+    test_env.jj_cmd_ok(&repo_path, &["new", "-m=b"]);
+    std::fs::write(repo_path.join("file1"), "b\n").unwrap();
+    std::fs::remove_file(repo_path.join("dir").join("file2")).unwrap();
+    test_env.jj_cmd_ok(&repo_path, &["new", "root()", "-m=c"]);
+    std::fs::write(repo_path.join("file3"), "c\n").unwrap();
+    // The merge commit only lists the file it changed relative to the merge of
+    // its parents
+    test_env.jj_cmd_ok(
+        &repo_path,
+        &["new", "description(b)", "description(c)", "-m=merge"],
+    );
+    std::fs::write(repo_path.join("file4"), "merge\n").unwrap();
+
+    let template = r#"description.first_line() ++ ": " ++ diff.files().map(|path| "<" ++ path ++ ">").join(",") ++ "\n""#;
+    let stdout = test_env.jj_cmd_success(
+        &repo_path,
+        &["log", "--no-graph", "-r=all() ~ root()", "-T", template],
+    );
+    insta::assert_snapshot!(stdout, @r###"
+    merge: <file4>
+    c: <file3>
+    b: <dir/file2>,<file1>
+    a: <dir/file2>,<file1>
+    "###);
+
+    let template = r#"if(diff.files(), diff.files(), "(no files)") ++ "\n""#;
+    let stdout = test_env.jj_cmd_success(
+        &repo_path,
+        &[
+            "log",
+            "--no-graph",
+            "-r=description(b) | root()",
+            "-T",
+            template,
+        ],
+    );
+    insta::assert_snapshot!(stdout, @r###"
+    dir/file2 file1
+    (no files)
+    "###);
+}
+
+#[test]
+fn test_log_diff_rendered() {
+    let test_env = TestEnvironment::default();
+    test_env.jj_cmd_ok(test_env.env_root(), &["init", "repo", "--git"]);
+    let repo_path = test_env.env_root().join("repo");
+    std::fs::create_dir(repo_path.join("dir")).unwrap();
+
+    test_env.jj_cmd_ok(&repo_path, &["describe", "-m=a"]);
+    std::fs::write(repo_path.join("file1"), "a\n").unwrap();
+    std::fs::write(repo_path.join("dir").join("file2"), "a\n").unwrap();
+    test_env.jj_cmd_ok(&repo_path, &["new", "-m=b"]);
+    std::fs::write(repo_path.join("file1"), "b\n").unwrap();
+    std::fs::remove_file(repo_path.join("dir").join("file2")).unwrap();
+
+    let template = r#"description.first_line() ++ "\n" ++ diff.stat(40)"#;
+    let stdout = test_env.jj_cmd_success(&repo_path, &["log", "-r=::@ ~ root()", "-T", template]);
+    insta::assert_snapshot!(stdout, @r###"
+    @  b
+    │  dir/file2 | 1 -
+    │  file1     | 2 +-
+    │  2 files changed, 1 insertion(+), 2 deletions(-)
+    ◉  a
+    │  dir/file2 | 1 +
+    ~  file1     | 1 +
+       2 files changed, 2 insertions(+), 0 deletions(-)
+    "###);
+
+    // The diff labels are nested in the labels of the log output
+    let stdout = test_env.jj_cmd_success(
+        &repo_path,
+        &[
+            "--color=always",
+            "log",
+            "--no-graph",
+            "-r=description(a)",
+            "-T=diff.summary()",
+        ],
+    );
+    insta::assert_snapshot!(stdout, @r###"
+    [38;5;2mA dir/file2[39m
+    [38;5;2mA file1[39m
+    "###);
+
+    // Each diff is cut at the last line that fits in the limit
+    test_env.add_config(r#"ui.diff.max-template-size = "15B""#);
+    let stdout = test_env.jj_cmd_success(
+        &repo_path,
+        &[
+            "log",
+            "--no-graph",
+            "-r=description(a)",
+            "-T=diff.summary()",
+        ],
+    );
+    insta::assert_snapshot!(stdout, @r###"
+    A dir/file2
+    ... diff truncated, use jj show
+    "###);
+    let stdout = test_env.jj_cmd_success(
+        &repo_path,
+        &[
+            "log",
+            "--no-graph",
+            "-r=description(a)",
+            "-T=diff.summary()",
+            "--config-toml=ui.diff.max-template-size = 0",
+        ],
+    );
+    insta::assert_snapshot!(stdout, @r###"
+    A dir/file2
+    A file1
+    "###);
+}
+
+#[test]
+fn test_log_conflicts() {
+    let test_env = TestEnvironment::default();
+    test_env.jj_cmd_ok(test_env.env_root(), &["init", "repo", "--git"]);
+    let repo_path = test_env.env_root().join("repo");
+
+    // Create a stack where the middle commit becomes conflicted after rebasing
+    // it, and the commit on top of it resolves the conflict by reverting.
+    std::fs::write(repo_path.join("file"), "base\n").unwrap();
+    test_env.jj_cmd_ok(&repo_path, &["describe", "-m=base"]);
+    test_env.jj_cmd_ok(&repo_path, &["new", "-m=first"]);
+    std::fs::write(repo_path.join("file"), "first\n").unwrap();
+    test_env.jj_cmd_ok(&repo_path, &["new", "-m=second"]);
+    std::fs::write(repo_path.join("file"), "base\n").unwrap();
+    test_env.jj_cmd_ok(&repo_path, &["new", "description(base)", "-m=other"]);
+    std::fs::write(repo_path.join("file"), "other\n").unwrap();
+    test_env.jj_cmd_ok(
+        &repo_path,
+        &[
+            "rebase",
+            "-s",
+            "description(first)",
+            "-d",
+            "description(other)",
+        ],
+    );
+
+    let template = r#"description.first_line() ++ if(conflict, " (conflict)") ++ "\n""#;
+    let stdout = test_env.jj_cmd_success(
+        &repo_path,
+        &["log", "--no-graph", "-r", "all() ~ root()", "-T", template],
+    );
+    insta::assert_snapshot!(stdout, @r###"
+    second
+    first (conflict)
+    other
+    base
+    "###);
+
+    let template = r#"description.first_line() ++ "\n""#;
+    let stdout = test_env.jj_cmd_success(
+        &repo_path,
+        &[
+            "log",
+            "--no-graph",
+            "-r",
+            "conflicts() & descendants(@)",
+            "-T",
+            template,
+        ],
+    );
+    insta::assert_snapshot!(stdout, @r###"
+    first
+    "###);
+}
+
 #[test]
 fn test_log_customize_short_id() {
     let test_env = TestEnvironment::default();
@@ -23,6 +23,7 @@ fn test_concurrent_operation_divergence() {
     let test_env = TestEnvironment::default();
     test_env.jj_cmd_ok(test_env.env_root(), &["init", "repo", "--git"]);
     let repo_path = test_env.env_root().join("repo");
+    test_env.add_config("operation.allow-at-op-mutations = true");
 
     test_env.jj_cmd_ok(&repo_path, &["describe", "-m", "message 1"]);
     test_env.jj_cmd_ok(
@@ -64,6 +65,7 @@ fn test_concurrent_operations_auto_rebase() {
     let test_env = TestEnvironment::default();
     test_env.jj_cmd_ok(test_env.env_root(), &["init", "repo", "--git"]);
     let repo_path = test_env.env_root().join("repo");
+    test_env.add_config("operation.allow-at-op-mutations = true");
 
     std::fs::write(repo_path.join("file"), "contents").unwrap();
     test_env.jj_cmd_ok(&repo_path, &["describe", "-m", "initial"]);
@@ -107,6 +109,7 @@ fn test_concurrent_operations_wc_modified() {
     let test_env = TestEnvironment::default();
     test_env.jj_cmd_ok(test_env.env_root(), &["init", "repo", "--git"]);
     let repo_path = test_env.env_root().join("repo");
+    test_env.add_config("operation.allow-at-op-mutations = true");
 
     std::fs::write(repo_path.join("file"), "contents\n").unwrap();
     test_env.jj_cmd_ok(&repo_path, &["describe", "-m", "initial"]);
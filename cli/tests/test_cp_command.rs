@@ -0,0 +1,134 @@
+// Copyright 2024 The Jujutsu Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::path::Path;
+
+use crate::common::TestEnvironment;
+
+fn get_copy_sources(test_env: &TestEnvironment, repo_path: &Path) -> String {
+    test_env.jj_cmd_success(
+        repo_path,
+        &[
+            "log",
+            "--no-graph",
+            "-r",
+            "@",
+            "-T",
+            r#"commit.metadata("copy-sources")"#,
+        ],
+    )
+}
+
+#[test]
+fn test_cp() {
+    let test_env = TestEnvironment::default();
+    test_env.jj_cmd_ok(test_env.env_root(), &["init", "repo", "--git"]);
+    let repo_path = test_env.env_root().join("repo");
+    std::fs::write(repo_path.join("file1"), "a\nb\nc\n").unwrap();
+    test_env.jj_cmd_ok(&repo_path, &["new"]);
+
+    let (stdout, stderr) = test_env.jj_cmd_ok(&repo_path, &["cp", "file1", "file2"]);
+    insta::assert_snapshot!(stdout, @"");
+    insta::assert_snapshot!(stderr, @"");
+    assert_eq!(
+        std::fs::read_to_string(repo_path.join("file2")).unwrap(),
+        "a\nb\nc\n"
+    );
+    let copy_sources = get_copy_sources(&test_env, &repo_path);
+    insta::assert_snapshot!(copy_sources, @r###"{"file2":"file1"}"###);
+
+    // The copy is shown as such instead of as an added file
+    let stdout = test_env.jj_cmd_success(&repo_path, &["diff"]);
+    insta::assert_snapshot!(stdout, @r###"
+    Copied regular file file1 to file2:
+    "###);
+    let stdout = test_env.jj_cmd_success(&repo_path, &["diff", "-s"]);
+    insta::assert_snapshot!(stdout, @r###"
+    A file2
+    "###);
+
+    // The recorded source survives rewriting the commit
+    std::fs::write(repo_path.join("file2"), "a\nb\nc\nd\n").unwrap();
+    test_env.jj_cmd_ok(&repo_path, &["describe", "-m", "copy"]);
+    let stdout = test_env.jj_cmd_success(&repo_path, &["diff"]);
+    insta::assert_snapshot!(stdout, @r###"
+    Copied regular file file1 to file2:
+       1    1: a
+       2    2: b
+       3    3: c
+            4: d
+    "###);
+
+    // It's dropped once the copy is gone
+    std::fs::remove_file(repo_path.join("file2")).unwrap();
+    let copy_sources = get_copy_sources(&test_env, &repo_path);
+    insta::assert_snapshot!(copy_sources, @"");
+}
+
+#[test]
+fn test_mv() {
+    let test_env = TestEnvironment::default();
+    test_env.jj_cmd_ok(test_env.env_root(), &["init", "repo", "--git"]);
+    let repo_path = test_env.env_root().join("repo");
+    std::fs::write(repo_path.join("file1"), "a\n").unwrap();
+    test_env.jj_cmd_ok(&repo_path, &["new"]);
+
+    test_env.jj_cmd_ok(&repo_path, &["mv", "file1", "file2"]);
+    assert!(!repo_path.join("file1").exists());
+    let stdout = test_env.jj_cmd_success(&repo_path, &["diff"]);
+    insta::assert_snapshot!(stdout, @r###"
+    Renamed regular file file1 to file2:
+    "###);
+    let stdout = test_env.jj_cmd_success(&repo_path, &["diff", "-s"]);
+    insta::assert_snapshot!(stdout, @r###"
+    D file1
+    A file2
+    "###);
+
+    // Moving the file again keeps the original source
+    test_env.jj_cmd_ok(&repo_path, &["mv", "file2", "dir/file3"]);
+    assert_eq!(
+        std::fs::read_to_string(repo_path.join("dir").join("file3")).unwrap(),
+        "a\n"
+    );
+    let copy_sources = get_copy_sources(&test_env, &repo_path);
+    insta::assert_snapshot!(copy_sources, @r###"{"dir/file3":"file1"}"###);
+    let stdout = test_env.jj_cmd_success(&repo_path, &["diff"]);
+    insta::assert_snapshot!(stdout, @r###"
+    Renamed regular file file1 to dir/file3:
+    "###);
+}
+
+#[test]
+fn test_cp_bad_paths() {
+    let test_env = TestEnvironment::default();
+    test_env.jj_cmd_ok(test_env.env_root(), &["init", "repo", "--git"]);
+    let repo_path = test_env.env_root().join("repo");
+    std::fs::write(repo_path.join("file1"), "a\n").unwrap();
+    std::fs::create_dir(repo_path.join("dir")).unwrap();
+    std::fs::write(repo_path.join("dir").join("file2"), "b\n").unwrap();
+
+    let stderr = test_env.jj_cmd_failure(&repo_path, &["cp", "missing", "file3"]);
+    insta::assert_snapshot!(stderr, @r###"
+    Error: No such file: missing
+    "###);
+    let stderr = test_env.jj_cmd_failure(&repo_path, &["cp", "dir", "file3"]);
+    insta::assert_snapshot!(stderr, @r###"
+    Error: Not a regular file: dir
+    "###);
+    let stderr = test_env.jj_cmd_failure(&repo_path, &["mv", "file1", "dir/file2"]);
+    insta::assert_snapshot!(stderr, @r###"
+    Error: Destination already exists: dir/file2
+    "###);
+}
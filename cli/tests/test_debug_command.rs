@@ -15,7 +15,7 @@
 use insta::assert_snapshot;
 use regex::Regex;
 
-use crate::common::TestEnvironment;
+use crate::common::{get_stdout_string, TestEnvironment};
 
 #[test]
 fn test_debug_revset() {
@@ -54,6 +54,18 @@ fn test_debug_revset() {
     });
 }
 
+#[cfg(unix)]
+#[test]
+fn test_debug_filesystem_probe() {
+    let test_env = TestEnvironment::default();
+    test_env.jj_cmd_ok(test_env.env_root(), &["init", "repo", "--git"]);
+    let workspace_path = test_env.env_root().join("repo");
+    let stdout = test_env.jj_cmd_success(&workspace_path, &["debug", "filesystem-probe"]);
+    assert_snapshot!(stdout, @r###"
+    Executable bit: supported (recorded: supported)
+    "###);
+}
+
 #[test]
 fn test_debug_index() {
     let test_env = TestEnvironment::default();
@@ -117,6 +129,50 @@ fn test_debug_reindex() {
     );
 }
 
+#[test]
+fn test_debug_index_changed_path_filters() {
+    let test_env = TestEnvironment::default();
+    test_env.add_config("index.changed-path-filters = true");
+    test_env.jj_cmd_ok(test_env.env_root(), &["init", "repo", "--git"]);
+    let workspace_path = test_env.env_root().join("repo");
+    test_env.jj_cmd_ok(&workspace_path, &["new"]);
+    test_env.jj_cmd_ok(&workspace_path, &["new"]);
+    // The root commit has no filter
+    let stdout = test_env.jj_cmd_success(&workspace_path, &["debug", "index"]);
+    assert_snapshot!(filter_index_stats(&stdout), @r###"
+    Number of commits: 4
+    Number of merges: 0
+    Max generation number: 3
+    Number of heads: 1
+    Number of changes: 4
+    Stats per level:
+      Level 0:
+        Number of commits: 3
+        Number of changed-path filters: 2
+        Name: [hash]
+      Level 1:
+        Number of commits: 1
+        Number of changed-path filters: 1
+        Name: [hash]
+    "###
+    );
+    test_env.jj_cmd_ok(&workspace_path, &["debug", "reindex"]);
+    let stdout = test_env.jj_cmd_success(&workspace_path, &["debug", "index"]);
+    assert_snapshot!(filter_index_stats(&stdout), @r###"
+    Number of commits: 4
+    Number of merges: 0
+    Max generation number: 3
+    Number of heads: 1
+    Number of changes: 4
+    Stats per level:
+      Level 0:
+        Number of commits: 4
+        Number of changed-path filters: 3
+        Name: [hash]
+    "###
+    );
+}
+
 #[test]
 fn test_debug_operation_id() {
     let test_env = TestEnvironment::default();
@@ -130,6 +186,643 @@ fn test_debug_operation_id() {
     );
 }
 
+#[test]
+fn test_debug_normalize_trees() {
+    let test_env = TestEnvironment::default();
+    // The Git commits are newer than the fake timestamps. Don't let the commits
+    // created on top of them take their committer timestamps from them.
+    test_env.add_config("timestamps.monotonic-commits = false");
+    let git_repo_path = test_env.env_root().join("git-repo");
+    let git_repo = git2::Repository::init_bare(git_repo_path).unwrap();
+
+    // Create a commit with an empty directory in Git. jj never writes such trees.
+    let blob_oid = git_repo.blob(b"contents\n").unwrap();
+    let empty_tree_oid = git_repo.treebuilder(None).unwrap().write().unwrap();
+    let mut tree_builder = git_repo.treebuilder(None).unwrap();
+    tree_builder.insert("file", blob_oid, 0o100644).unwrap();
+    tree_builder
+        .insert("empty", empty_tree_oid, 0o040000)
+        .unwrap();
+    let tree_oid = tree_builder.write().unwrap();
+    let tree = git_repo.find_tree(tree_oid).unwrap();
+    let signature = git2::Signature::new(
+        "Someone",
+        "someone@example.com",
+        &git2::Time::new(1234567890, 60),
+    )
+    .unwrap();
+    let commit_oid = git_repo
+        .commit(
+            Some("refs/heads/master"),
+            &signature,
+            &signature,
+            "unnormalized",
+            &tree,
+            &[],
+        )
+        .unwrap();
+
+    test_env.jj_cmd_ok(
+        test_env.env_root(),
+        &["init", "repo", "--git-repo", "git-repo"],
+    );
+    let repo_path = test_env.env_root().join("repo");
+    test_env.jj_cmd_ok(&repo_path, &["new", "master"]);
+
+    let (stdout, stderr) = test_env.jj_cmd_ok(&repo_path, &["debug", "normalize-trees"]);
+    insta::assert_snapshot!(stdout, @"");
+    // The master commit and the working-copy commit on top of it
+    assert!(stderr.starts_with("Rewrote 2 commits\n"), "{stderr}");
+    let new_commit_id = test_env.jj_cmd_success(
+        &repo_path,
+        &["log", "--no-graph", "-r=master", "-T=commit_id"],
+    );
+    assert_ne!(new_commit_id, commit_oid.to_string());
+
+    // The contents didn't change
+    let stdout = test_env.jj_cmd_success(
+        &repo_path,
+        &["diff", "--from", &commit_oid.to_string(), "--to", "master"],
+    );
+    insta::assert_snapshot!(stdout, @"");
+    let stdout = test_env.jj_cmd_success(&repo_path, &["diff", "-r", "@"]);
+    insta::assert_snapshot!(stdout, @"");
+
+    // Running it again does nothing
+    let (_stdout, stderr) = test_env.jj_cmd_ok(&repo_path, &["debug", "normalize-trees"]);
+    insta::assert_snapshot!(stderr, @r###"
+    Nothing changed.
+    "###);
+}
+
+#[test]
+fn test_debug_strip_reserved_paths() {
+    let test_env = TestEnvironment::default();
+    // The Git commits are newer than the fake timestamps. Don't let the commits
+    // created on top of them take their committer timestamps from them.
+    test_env.add_config("timestamps.monotonic-commits = false");
+    let git_repo_path = test_env.env_root().join("git-repo");
+    let git_repo = git2::Repository::init_bare(git_repo_path).unwrap();
+
+    // Create a commit with a .jj directory in Git. jj never writes such trees.
+    let blob_oid = git_repo.blob(b"contents\n").unwrap();
+    let mut tree_builder = git_repo.treebuilder(None).unwrap();
+    tree_builder.insert("file", blob_oid, 0o100644).unwrap();
+    let dotjj_tree_oid = tree_builder.write().unwrap();
+    tree_builder
+        .insert(".jj", dotjj_tree_oid, 0o040000)
+        .unwrap();
+    let tree_oid = tree_builder.write().unwrap();
+    let tree = git_repo.find_tree(tree_oid).unwrap();
+    let signature = git2::Signature::new(
+        "Someone",
+        "someone@example.com",
+        &git2::Time::new(1234567890, 60),
+    )
+    .unwrap();
+    git_repo
+        .commit(
+            Some("refs/heads/reserved"),
+            &signature,
+            &signature,
+            "with .jj",
+            &tree,
+            &[],
+        )
+        .unwrap();
+
+    // The commit isn't at the Git HEAD, so it's not checked out
+    test_env.jj_cmd_ok(
+        test_env.env_root(),
+        &["init", "repo", "--git-repo", "git-repo"],
+    );
+    let repo_path = test_env.env_root().join("repo");
+    let stdout = test_env.jj_cmd_success(&repo_path, &["files", "-r", "reserved"]);
+    insta::assert_snapshot!(stdout, @r###"
+    .jj/file
+    file
+    "###);
+
+    // Such commits can still be rewritten, e.g. rebased onto a commit that
+    // needs a merged tree
+    test_env.jj_cmd_ok(&repo_path, &["new", "root()"]);
+    std::fs::write(repo_path.join("other"), "other\n").unwrap();
+    test_env.jj_cmd_ok(&repo_path, &["rebase", "-r", "reserved", "-d", "@"]);
+    let stdout = test_env.jj_cmd_success(&repo_path, &["files", "-r", "reserved"]);
+    insta::assert_snapshot!(stdout, @r###"
+    .jj/file
+    file
+    other
+    "###);
+
+    let (stdout, stderr) = test_env.jj_cmd_ok(&repo_path, &["debug", "strip-reserved-paths"]);
+    insta::assert_snapshot!(stdout, @"");
+    assert!(stderr.starts_with("Rewrote 1 commits\n"), "{stderr}");
+    let stdout = test_env.jj_cmd_success(&repo_path, &["files", "-r", "reserved"]);
+    insta::assert_snapshot!(stdout, @r###"
+    file
+    other
+    "###);
+
+    // Running it again does nothing
+    let (_stdout, stderr) = test_env.jj_cmd_ok(&repo_path, &["debug", "strip-reserved-paths"]);
+    insta::assert_snapshot!(stderr, @r###"
+    Nothing changed.
+    "###);
+}
+
+#[test]
+fn test_debug_filter_paths() {
+    let test_env = TestEnvironment::default();
+    test_env.jj_cmd_ok(test_env.env_root(), &["init", "repo", "--git"]);
+    let repo_path = test_env.env_root().join("repo");
+    std::fs::write(repo_path.join("file"), "1\n").unwrap();
+    test_env.jj_cmd_ok(&repo_path, &["commit", "-m=first"]);
+    std::fs::write(repo_path.join("file"), "2\n").unwrap();
+    test_env.jj_cmd_ok(&repo_path, &["commit", "-m=second"]);
+    std::fs::write(repo_path.join("secret"), "password\n").unwrap();
+    test_env.jj_cmd_ok(&repo_path, &["commit", "-m=third"]);
+    std::fs::write(repo_path.join("secret"), "password2\n").unwrap();
+    test_env.jj_cmd_ok(&repo_path, &["commit", "-m=fourth"]);
+    let get_commit_ids = || {
+        test_env.jj_cmd_success(
+            &repo_path,
+            &[
+                "log",
+                "--no-graph",
+                "-r=all() ~ root()",
+                "-T",
+                r#"description.first_line() ++ " " ++ commit_id ++ "\n""#,
+            ],
+        )
+    };
+    let old_commit_ids = get_commit_ids();
+
+    let (stdout, stderr) =
+        test_env.jj_cmd_ok(&repo_path, &["debug", "filter-paths", "--remove", "secret"]);
+    // The third and fourth commits and the working-copy commit
+    assert_eq!(stdout.lines().count(), 3, "{stdout}");
+    assert!(
+        stderr.starts_with("Rewrote 3 commits\n2 files (19 bytes) are no longer reachable\n"),
+        "{stderr}"
+    );
+    let new_commit_ids = get_commit_ids();
+    for (old_line, new_line) in old_commit_ids.lines().zip(new_commit_ids.lines()) {
+        let (description, old_id) = old_line.split_once(' ').unwrap();
+        let new_id = new_line.split_once(' ').unwrap().1;
+        // Commits that didn't contain the file keep their ids
+        if ["first", "second"].contains(&description) {
+            assert_eq!(old_id, new_id);
+            assert!(!stdout.contains(old_id), "{stdout}");
+        } else {
+            assert!(
+                stdout.contains(&format!("{old_id} -> {new_id}\n")),
+                "{stdout}"
+            );
+        }
+    }
+    for revision in ["description(third)", "description(fourth)", "@"] {
+        let stdout = test_env.jj_cmd_success(&repo_path, &["files", "-r", revision]);
+        assert_eq!(stdout, "file\n", "{revision}");
+    }
+    let stdout = test_env.jj_cmd_success(&repo_path, &["diff", "-r", "description(third)"]);
+    insta::assert_snapshot!(stdout, @"");
+    assert!(!repo_path.join("secret").exists());
+
+    // Running it again does nothing
+    let (_stdout, stderr) =
+        test_env.jj_cmd_ok(&repo_path, &["debug", "filter-paths", "--remove", "secret"]);
+    insta::assert_snapshot!(stderr, @r###"
+    Nothing changed.
+    "###);
+}
+
+#[test]
+fn test_debug_chunks() {
+    let test_env = TestEnvironment::default();
+    test_env.add_config(
+        r#"
+        ui.allow-init-native = true
+        snapshot.max-new-file-size = "10MiB"
+        "#,
+    );
+    test_env.jj_cmd_ok(test_env.env_root(), &["init", "repo"]);
+    let repo_path = test_env.env_root().join("repo");
+
+    let stdout = test_env.jj_cmd_success(&repo_path, &["debug", "chunks"]);
+    insta::assert_snapshot!(stdout, @r###"
+    Number of chunked files: 0
+    Total size: 0
+    Number of chunks: 0
+    Number of unique chunks: 0
+    Size of unique chunks: 0
+    "###);
+
+    // Two versions of a large file differing in one line share all but one
+    // chunk
+    let contents: String = (0..200_000).map(|i| format!("line {i}\n")).collect();
+    std::fs::write(repo_path.join("file"), &contents).unwrap();
+    test_env.jj_cmd_ok(&repo_path, &["new"]);
+    let contents = contents.replace("line 100000\n", "changed line\n");
+    std::fs::write(repo_path.join("file"), contents).unwrap();
+    let stdout = test_env.jj_cmd_success(&repo_path, &["debug", "chunks"]);
+    insta::assert_snapshot!(stdout, @r###"
+    Number of chunked files: 2
+    Total size: 4577781
+    Number of chunks: 70
+    Number of unique chunks: 36
+    Size of unique chunks: 2472721
+    "###);
+
+    test_env.jj_cmd_ok(test_env.env_root(), &["init", "git-repo", "--git"]);
+    let stderr =
+        test_env.jj_cmd_failure(&test_env.env_root().join("git-repo"), &["debug", "chunks"]);
+    insta::assert_snapshot!(stderr, @r###"
+    Error: Chunk stats are only available for the native backend
+    "###);
+}
+
+#[test]
+fn test_debug_object_show() {
+    let test_env = TestEnvironment::default();
+    test_env.jj_cmd_ok(test_env.env_root(), &["init", "repo", "--git"]);
+    let repo_path = test_env.env_root().join("repo");
+    std::fs::write(repo_path.join("file"), "a\n").unwrap();
+    std::fs::create_dir(repo_path.join("dir")).unwrap();
+    std::fs::write(repo_path.join("dir").join("sub"), "b\n").unwrap();
+    test_env.jj_cmd_ok(&repo_path, &["describe", "-m", "Some description"]);
+    let commit_id = test_env.jj_cmd_success(
+        &repo_path,
+        &["log", "--no-graph", "-r", "@", "-T", "commit_id"],
+    );
+
+    // Commits are found by a prefix of their id
+    let stdout =
+        test_env.jj_cmd_success(&repo_path, &["debug", "object", "show", &commit_id[..12]]);
+    insta::with_settings!({filters => vec![
+        (commit_id.as_str(), "[commit]"),
+        (r"predecessor [0-9a-f]{40}", "predecessor [hash]"),
+        (r"change-id [0-9a-f]{32}", "change-id [hash]"),
+        (r"> \d+ \+0700", "> [timestamp] +0700"),
+    ]}, {
+        assert_snapshot!(stdout, @r###"
+        commit [commit]
+        tree a49ed2342464f30f18a10226db3dbe1b282d2f85
+        parent 0000000000000000000000000000000000000000
+        predecessor [hash]
+        change-id [hash]
+        author Test User <test.user@example.com> [timestamp] +0700
+        committer Test User <test.user@example.com> [timestamp] +0700
+
+            Some description
+        "###);
+    });
+
+    let stdout = test_env.jj_cmd_success(
+        &repo_path,
+        &[
+            "debug",
+            "object",
+            "show",
+            "a49ed2342464f30f18a10226db3dbe1b282d2f85",
+        ],
+    );
+    insta::assert_snapshot!(stdout, @r###"
+    tree a49ed2342464f30f18a10226db3dbe1b282d2f85
+    tree 8a2b36a8b03e85da23f32aa6a15a235e4544b542 dir
+    file 78981922613b2afb6025042ff6bd878ac1994e85 file
+    "###);
+
+    let stdout = test_env.jj_cmd_success(&repo_path, &["debug", "object", "show", "7898"]);
+    insta::assert_snapshot!(stdout, @r###"
+    file 78981922613b2afb6025042ff6bd878ac1994e85
+    size 2
+    "###);
+
+    // The raw object is the Git blob's contents
+    let stdout = test_env.jj_cmd_success(&repo_path, &["debug", "object", "show", "7898", "--raw"]);
+    insta::assert_snapshot!(stdout, @r###"
+    a
+    "###);
+
+    // The kind of object to look for can be restricted
+    let stderr = test_env.jj_cmd_failure(
+        &repo_path,
+        &["debug", "object", "show", "7898", "--type", "tree"],
+    );
+    insta::assert_snapshot!(stderr, @r###"
+    Error: No object found with id prefix 7898
+    "###);
+
+    let stderr = test_env.jj_cmd_failure(&repo_path, &["debug", "object", "show", "xyz"]);
+    insta::assert_snapshot!(stderr, @r###"
+    Error: Invalid object id: "xyz"
+    "###);
+}
+
+#[test]
+fn test_debug_object_show_ambiguous() {
+    let test_env = TestEnvironment::default();
+    test_env.jj_cmd_ok(test_env.env_root(), &["init", "repo", "--git"]);
+    let repo_path = test_env.env_root().join("repo");
+    // The ids of these blobs share the prefix "6bb2f"
+    std::fs::write(repo_path.join("file1"), "195\n").unwrap();
+    std::fs::write(repo_path.join("file2"), "389\n").unwrap();
+    test_env.jj_cmd_ok(&repo_path, &["new"]);
+
+    let stderr = test_env.jj_cmd_failure(&repo_path, &["debug", "object", "show", "6bb2f"]);
+    insta::assert_snapshot!(stderr, @r###"
+    Error: Object id prefix 6bb2f is ambiguous
+    Hint: The prefix matches these objects:
+      file 6bb2f4ee89f3ff56785055f588c560ce557d0655
+      file 6bb2f98fb0227744dff2c9023c2a8d53cc721588
+    "###);
+
+    let stdout = test_env.jj_cmd_success(&repo_path, &["debug", "object", "show", "6bb2f9"]);
+    insta::assert_snapshot!(stdout, @r###"
+    file 6bb2f98fb0227744dff2c9023c2a8d53cc721588
+    size 4
+    "###);
+}
+
+#[test]
+fn test_debug_object_show_conflict() {
+    let test_env = TestEnvironment::default();
+    // Legacy conflicts are stored as separate objects
+    test_env.add_config("format.tree-level-conflicts = false");
+    test_env.jj_cmd_ok(test_env.env_root(), &["init", "repo", "--git"]);
+    let repo_path = test_env.env_root().join("repo");
+    std::fs::write(repo_path.join("file"), "base\n").unwrap();
+    test_env.jj_cmd_ok(&repo_path, &["branch", "create", "base"]);
+    test_env.jj_cmd_ok(&repo_path, &["new"]);
+    std::fs::write(repo_path.join("file"), "left\n").unwrap();
+    test_env.jj_cmd_ok(&repo_path, &["branch", "create", "left"]);
+    test_env.jj_cmd_ok(&repo_path, &["new", "base"]);
+    std::fs::write(repo_path.join("file"), "right\n").unwrap();
+    test_env.jj_cmd_ok(&repo_path, &["branch", "create", "right"]);
+    test_env.jj_cmd_ok(&repo_path, &["new", "left", "right"]);
+    let commit_id = test_env.jj_cmd_success(
+        &repo_path,
+        &["log", "--no-graph", "-r", "@", "-T", "commit_id"],
+    );
+
+    let stdout = test_env.jj_cmd_success(
+        &repo_path,
+        &["debug", "object", "show", &commit_id, "--type", "commit"],
+    );
+    let tree_id = find_object_id(&stdout, "tree ");
+    let stdout = test_env.jj_cmd_success(
+        &repo_path,
+        &["debug", "object", "show", &tree_id, "--type", "tree"],
+    );
+    let conflict_id = find_object_id(&stdout, "conflict ");
+    insta::with_settings!({filters => vec![
+        (tree_id.as_str(), "[tree]"),
+        (conflict_id.as_str(), "[conflict]"),
+    ]}, {
+        assert_snapshot!(stdout, @r###"
+        tree [tree]
+        conflict [conflict] file
+        "###);
+    });
+
+    let stdout = test_env.jj_cmd_success(
+        &repo_path,
+        &[
+            "debug",
+            "object",
+            "show",
+            &conflict_id,
+            "--type",
+            "conflict",
+        ],
+    );
+    insta::with_settings!({filters => vec![
+        (conflict_id.as_str(), "[conflict]"),
+    ]}, {
+        assert_snapshot!(stdout, @r###"
+        conflict [conflict]
+        remove file df967b96a579e45a18b8251732d16804b2e56a55
+        add file 45cf141ba67d59203f02a54f03162f3fcef57830
+        add file c376d892e8b105bd712d06ec5162b5f31ce949c3
+        "###);
+    });
+}
+
+#[test]
+fn test_debug_check_and_recover_heads() {
+    let test_env = TestEnvironment::default();
+    test_env.add_config("ui.allow-init-native = true");
+    test_env.jj_cmd_ok(test_env.env_root(), &["init", "repo"]);
+    let repo_path = test_env.env_root().join("repo");
+    std::fs::write(repo_path.join("file"), "contents\n").unwrap();
+    test_env.jj_cmd_ok(&repo_path, &["commit", "-m", "first"]);
+    std::fs::write(repo_path.join("file2"), "contents\n").unwrap();
+    let wc_commit_id = test_env.jj_cmd_success(
+        &repo_path,
+        &["log", "--no-graph", "-r", "@", "-T", "commit_id"],
+    );
+
+    let (stdout, stderr) = test_env.jj_cmd_ok(&repo_path, &["debug", "check"]);
+    assert_snapshot!(stdout, @"");
+    assert_snapshot!(stderr, @r###"
+    No missing commits found
+    "###);
+
+    // Lose the working-copy commit
+    std::fs::remove_file(
+        repo_path
+            .join(".jj")
+            .join("repo")
+            .join("store")
+            .join("commits")
+            .join(&wc_commit_id),
+    )
+    .unwrap();
+    insta::with_settings!({filters => vec![
+        (wc_commit_id.as_str(), "[wc commit]"),
+        (&wc_commit_id[..12], "[wc commit]"),
+    ]}, {
+        let stderr = test_env.jj_cmd_failure(&repo_path, &["status"]);
+        assert_snapshot!(stderr, @r###"
+        Error: Commit [wc commit] referenced by a visible head is missing from the store
+        Hint: Run `jj debug recover-heads` to remove the references to missing commits.
+        "###);
+
+        let assert = test_env
+            .jj_cmd(&repo_path, &["debug", "check"])
+            .assert()
+            .code(1);
+        assert_snapshot!(get_stdout_string(&assert), @r###"
+        Commit [wc commit] referenced by a visible head is missing
+        Commit [wc commit] referenced by the working-copy commit of workspace default is missing
+        "###);
+
+        let (stdout, stderr) = test_env.jj_cmd_ok(&repo_path, &["debug", "recover-heads"]);
+        assert_snapshot!(stdout, @"");
+        assert_snapshot!(stderr, @r###"
+        Dropped a visible head (missing commit [wc commit])
+        Dropped the working-copy commit of workspace default (missing commit [wc commit])
+        "###);
+    });
+
+    // The repo is usable again, and the files in the working copy are kept
+    let stdout = test_env.jj_cmd_success(
+        &repo_path,
+        &["log", "-T", r#"description.first_line() ++ " " ++ empty"#],
+    );
+    assert!(stdout.contains("first false"), "{stdout}");
+    assert!(repo_path.join("file2").exists());
+    let (_stdout, stderr) = test_env.jj_cmd_ok(&repo_path, &["debug", "check"]);
+    assert_snapshot!(stderr, @r###"
+    No missing commits found
+    "###);
+}
+
+#[test]
+fn test_missing_git_object() {
+    let test_env = TestEnvironment::default();
+    test_env.jj_cmd_ok(test_env.env_root(), &["init", "repo", "--git"]);
+    let repo_path = test_env.env_root().join("repo");
+    std::fs::write(repo_path.join("file"), "contents\n").unwrap();
+    test_env.jj_cmd_ok(&repo_path, &["commit", "-m", "first"]);
+    let commit_id = test_env.jj_cmd_success(
+        &repo_path,
+        &["log", "--no-graph", "-r", "@-", "-T", "commit_id"],
+    );
+
+    // Lose the parent commit as if it were pruned by an external `git gc`
+    std::fs::remove_file(
+        repo_path
+            .join(".jj")
+            .join("repo")
+            .join("store")
+            .join("git")
+            .join("objects")
+            .join(&commit_id[..2])
+            .join(&commit_id[2..]),
+    )
+    .unwrap();
+    let stderr = test_env.jj_cmd_failure(&repo_path, &["show", "@-"]);
+    assert!(
+        stderr.starts_with(&format!(
+            "Error: Object {commit_id} of type commit not found\nCaused by:\n1: Git object \
+             {commit_id} is missing from the backing Git repo\n"
+        )),
+        "{stderr}"
+    );
+    assert!(
+        stderr.ends_with(
+            "Hint: The object may have been pruned by `git gc` run outside of jj. Run `jj debug \
+             recover-heads` to remove the references to missing commits.\n"
+        ),
+        "{stderr}"
+    );
+}
+
+fn find_object_id(text: &str, line_prefix: &str) -> String {
+    let line = text
+        .lines()
+        .find(|line| line.starts_with(line_prefix))
+        .unwrap();
+    line[line_prefix.len()..]
+        .split(' ')
+        .next()
+        .unwrap()
+        .to_owned()
+}
+
+#[test]
+fn test_debug_upgrade_repo() {
+    let test_env = TestEnvironment::default();
+    test_env.jj_cmd_ok(test_env.env_root(), &["init", "repo", "--git"]);
+    let repo_path = test_env.env_root().join("repo");
+
+    let (stdout, stderr) = test_env.jj_cmd_ok(&repo_path, &["debug", "upgrade-repo"]);
+    assert_snapshot!(stdout, @"");
+    assert_snapshot!(stderr, @r###"
+    Nothing to upgrade
+    "###);
+
+    // A repo written by a newer version can't be loaded or upgraded
+    let index_path = repo_path.join(".jj").join("repo").join("index");
+    std::fs::write(index_path.join("format_version"), "3\n").unwrap();
+    let stderr = test_env.jj_cmd_failure(&repo_path, &["status"]);
+    assert_snapshot!(stderr, @r###"
+    Error: The index requires format 3, this binary supports up to 1
+    Hint: The repo was written by a newer version of jj. Use that version instead.
+    "###);
+    let stderr = test_env.jj_cmd_failure(&repo_path, &["debug", "upgrade-repo"]);
+    assert_snapshot!(stderr, @r###"
+    Error: The index requires format 3, this binary supports up to 1
+    "###);
+
+    // Unless the newer format is still readable, in which case it's read-only
+    std::fs::write(index_path.join("format_version"), "3 1\n").unwrap();
+    let stdout = test_env.jj_cmd_success(&repo_path, &["log", "--no-graph", "-r=@", "-T=empty"]);
+    assert_snapshot!(stdout, @"true");
+}
+
+#[test]
+fn test_debug_stats() {
+    let test_env = TestEnvironment::default();
+    test_env.jj_cmd_ok(test_env.env_root(), &["init", "repo", "--git"]);
+    let repo_path = test_env.env_root().join("repo");
+    std::fs::write(repo_path.join("big"), "b".repeat(100)).unwrap();
+    std::fs::write(repo_path.join("small"), "1\n").unwrap();
+    test_env.jj_cmd_ok(&repo_path, &["commit", "-m", "a"]);
+    std::fs::create_dir(repo_path.join("dir")).unwrap();
+    std::fs::write(repo_path.join("dir").join("medium"), "m".repeat(50)).unwrap();
+    test_env.jj_cmd_ok(&repo_path, &["commit", "-m", "b"]);
+    let commit_id = |description: &str| {
+        test_env.jj_cmd_success(
+            &repo_path,
+            &[
+                "log",
+                "--no-graph",
+                "-r",
+                &format!("description({description})"),
+                "-T",
+                "commit_id",
+            ],
+        )
+    };
+
+    let stdout = test_env.jj_cmd_success(&repo_path, &["debug", "stats", "--top=2", "--json"]);
+    let stats: serde_json::Value = serde_json::from_str(&stdout).unwrap();
+    assert_eq!(stats["objects"]["files"]["count"], 3);
+    assert_eq!(stats["objects"]["files"]["bytes"], 100 + 2 + 50);
+    assert_eq!(stats["objects"]["symlinks"]["count"], 0);
+    assert_eq!(stats["objects"]["conflicts"]["count"], 0);
+    // The files are attributed to the visible commits that introduced them,
+    // not to the hidden commits snapshotted before `jj commit` described them
+    assert_eq!(
+        stats["largest_files"],
+        serde_json::json!([
+            {"path": "big", "bytes": 100, "commit_id": commit_id("a")},
+            {"path": "dir/medium", "bytes": 50, "commit_id": commit_id("b")},
+        ])
+    );
+    assert_eq!(stats["working_copy_file_states"], 3);
+
+    let stdout = test_env.jj_cmd_success(&repo_path, &["debug", "stats", "--top=1"]);
+    assert!(
+        stdout.starts_with("Objects         Count          Bytes\n"),
+        "{stdout}"
+    );
+    assert!(
+        stdout.contains("Files               3            152\n"),
+        "{stdout}"
+    );
+    assert!(
+        stdout.contains(&format!(
+            "Largest files:\n             100  big  (introduced in commit {})\n\n",
+            &commit_id("a")[..12]
+        )),
+        "{stdout}"
+    );
+}
+
 fn filter_index_stats(text: &str) -> String {
     let regex = Regex::new(r"    Name: [0-9a-z]+").unwrap();
     regex.replace_all(text, "    Name: [hash]").to_string()
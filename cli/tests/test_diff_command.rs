@@ -144,6 +144,93 @@ fn test_diff_empty() {
     "###);
 }
 
+#[test]
+fn test_diff_null_terminated() {
+    let test_env = TestEnvironment::default();
+    test_env.jj_cmd_ok(test_env.env_root(), &["init", "repo", "--git"]);
+    let repo_path = test_env.env_root().join("repo");
+    std::fs::create_dir(repo_path.join("dir")).unwrap();
+
+    std::fs::write(repo_path.join("file1"), "foo\n").unwrap();
+    std::fs::write(repo_path.join("file2"), "foo\n").unwrap();
+    test_env.jj_cmd_ok(&repo_path, &["new"]);
+    std::fs::remove_file(repo_path.join("file1")).unwrap();
+    std::fs::write(repo_path.join("file2"), "foo\nbar\n").unwrap();
+    std::fs::write(repo_path.join("dir").join("file 3"), "foo\n").unwrap();
+
+    // Paths are relative to the workspace root even in a subdirectory
+    let sub_dir = repo_path.join("dir");
+    let stdout = test_env.jj_cmd_success(&sub_dir, &["diff", "--name-only", "-z"]);
+    assert_eq!(stdout, "dir/file 3\0file1\0file2\0");
+    let stdout = test_env.jj_cmd_success(&sub_dir, &["diff", "--summary", "-z"]);
+    assert_eq!(stdout, "A\tdir/file 3\0D\tfile1\0M\tfile2\0");
+    let stdout = test_env.jj_cmd_success(
+        &sub_dir,
+        &["diff", "--types", "-z", "--from=@-", "--to=@", "../file2"],
+    );
+    assert_eq!(stdout, "FF\tfile2\0");
+
+    let stderr = test_env.jj_cmd_failure(&repo_path, &["diff", "--git", "-z"]);
+    insta::assert_snapshot!(stderr, @r###"
+    Error: -z only works with one of --summary, --types, and --name-only
+    "###);
+}
+
+#[test]
+fn test_diff_name_only() {
+    let test_env = TestEnvironment::default();
+    test_env.jj_cmd_ok(test_env.env_root(), &["init", "repo", "--git"]);
+    let repo_path = test_env.env_root().join("repo");
+    std::fs::create_dir(repo_path.join("dir")).unwrap();
+
+    std::fs::write(repo_path.join("file1"), "foo\n").unwrap();
+    std::fs::write(repo_path.join("file2"), "foo\n").unwrap();
+    test_env.jj_cmd_ok(&repo_path, &["new"]);
+    std::fs::remove_file(repo_path.join("file1")).unwrap();
+    std::fs::write(repo_path.join("file2"), "foo\nbar\n").unwrap();
+    std::fs::write(repo_path.join("dir").join("file3"), "foo\n").unwrap();
+
+    let stdout = test_env.jj_cmd_success(&repo_path, &["diff", "--name-only"]);
+    insta::assert_snapshot!(stdout.replace('\\', "/"), @r###"
+    dir/file3
+    file1
+    file2
+    "###);
+
+    let stdout = test_env.jj_cmd_success(&repo_path, &["diff", "--name-only", "file2", "dir"]);
+    insta::assert_snapshot!(stdout.replace('\\', "/"), @r###"
+    dir/file3
+    file2
+    "###);
+
+    let stdout = test_env.jj_cmd_success(
+        &repo_path,
+        &["diff", "--name-only", "--from=root()", "--to=@-"],
+    );
+    insta::assert_snapshot!(stdout, @r###"
+    file1
+    file2
+    "###);
+
+    let stdout = test_env.jj_cmd_success(
+        &repo_path,
+        &["show", "--name-only", "-T=description", "-r=@-"],
+    );
+    insta::assert_snapshot!(stdout, @r###"
+    file1
+    file2
+    "###);
+
+    let stderr = test_env.jj_cmd_cli_error(&repo_path, &["diff", "--name-only", "--summary"]);
+    insta::assert_snapshot!(stderr, @r###"
+    error: the argument '--name-only' cannot be used with '--summary'
+
+    Usage: jj diff --name-only [PATHS]...
+
+    For more information, try '--help'.
+    "###);
+}
+
 #[test]
 fn test_diff_types() {
     let test_env = TestEnvironment::default();
@@ -213,6 +300,138 @@ fn test_diff_types() {
     }
 }
 
+#[test]
+fn test_diff_to_path() {
+    let test_env = TestEnvironment::default();
+    test_env.jj_cmd_ok(test_env.env_root(), &["init", "repo", "--git"]);
+    let repo_path = test_env.env_root().join("repo");
+    std::fs::write(repo_path.join("file1"), "foo\n").unwrap();
+    std::fs::write(repo_path.join("file2"), "foo\n").unwrap();
+    std::fs::write(repo_path.join("script"), "echo\n").unwrap();
+    test_env.jj_cmd_ok(&repo_path, &["commit", "-m", "base"]);
+
+    // A directory outside the workspace with a deleted, a modified, an added,
+    // and an ignored file
+    let scratch_path = test_env.env_root().join("scratch");
+    std::fs::create_dir(&scratch_path).unwrap();
+    std::fs::write(scratch_path.join("file2"), "foo\nbar\n").unwrap();
+    std::fs::write(scratch_path.join("file3"), "baz\n").unwrap();
+    std::fs::write(scratch_path.join("script"), "echo\n").unwrap();
+    std::fs::write(scratch_path.join(".gitignore"), "ignored\n").unwrap();
+    std::fs::write(scratch_path.join("ignored"), "ignored\n").unwrap();
+
+    let stdout = test_env.jj_cmd_success(&repo_path, &["diff", "--to-path=../scratch"]);
+    insta::assert_snapshot!(stdout, @r###"
+    Added regular file .gitignore:
+            1: ignored
+    Removed regular file file1:
+       1     : foo
+    Modified regular file file2:
+       1    1: foo
+            2: bar
+    Added regular file file3:
+            1: baz
+    "###);
+
+    // The snapshot isn't written to the repo
+    let git_repo = git2::Repository::open(repo_path.join(".jj/repo/store/git")).unwrap();
+    let blob_id = git2::Oid::hash_object(git2::ObjectType::Blob, b"baz\n").unwrap();
+    assert!(git_repo.find_blob(blob_id).is_err());
+
+    // Paths are restricted by the matcher
+    let stdout = test_env.jj_cmd_success(
+        &repo_path,
+        &[
+            "diff",
+            "-s",
+            "--from=root()",
+            "--to-path=../scratch",
+            "file2",
+        ],
+    );
+    insta::assert_snapshot!(stdout, @r###"
+    A file2
+    "###);
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+
+        std::fs::set_permissions(
+            scratch_path.join("script"),
+            std::fs::Permissions::from_mode(0o755),
+        )
+        .unwrap();
+        let stdout = test_env.jj_cmd_success(
+            &repo_path,
+            &["diff", "-s", "--to-path=../scratch", "script"],
+        );
+        insta::assert_snapshot!(stdout, @r###"
+        M script
+        "###);
+    }
+
+    // The working copy isn't affected
+    let stdout = test_env.jj_cmd_success(&repo_path, &["diff", "-s"]);
+    insta::assert_snapshot!(stdout, @"");
+
+    let stderr = test_env.jj_cmd_failure(&repo_path, &["diff", "--to-path=../missing"]);
+    insta::assert_snapshot!(stderr, @r###"
+    Error: ../missing is not a directory
+    "###);
+}
+
+#[test]
+fn test_diff_partially_resolved_conflict() {
+    let test_env = TestEnvironment::default();
+    test_env.jj_cmd_ok(test_env.env_root(), &["init", "repo", "--git"]);
+    let repo_path = test_env.env_root().join("repo");
+    let file_path = repo_path.join("file");
+
+    // Two conflicts in the same file, far enough apart not to share context
+    let lines = |first: &str, last: &str| format!("{first}\nb\nc\nd\ne\nf\ng\nh\n{last}\n");
+    std::fs::write(&file_path, lines("a", "i")).unwrap();
+    test_env.jj_cmd_ok(&repo_path, &["new", "-m=left"]);
+    std::fs::write(&file_path, lines("a left", "i left")).unwrap();
+    test_env.jj_cmd_ok(&repo_path, &["new", "@-", "-m=right"]);
+    std::fs::write(&file_path, lines("a right", "i right")).unwrap();
+    test_env.jj_cmd_ok(
+        &repo_path,
+        &["new", r#"description("left")"#, r#"description("right")"#],
+    );
+    test_env.jj_cmd_ok(&repo_path, &["new"]);
+
+    // Resolve the first conflict only
+    let contents = std::fs::read_to_string(&file_path).unwrap();
+    let start = contents.find("<<<<<<<").unwrap();
+    let end = contents.find(">>>>>>>\n").unwrap() + ">>>>>>>\n".len();
+    let resolved = format!("{}a resolved\n{}", &contents[..start], &contents[end..]);
+    std::fs::write(&file_path, resolved).unwrap();
+
+    let stdout = test_env.jj_cmd_success(&repo_path, &["diff"]);
+    assert!(
+        stdout.starts_with("Resolved 1 of 2 conflicts in file:\n"),
+        "{stdout}"
+    );
+    assert!(stdout.contains("a resolved"), "{stdout}");
+    assert!(!stdout.contains("i left"), "{stdout}");
+    let stdout = test_env.jj_cmd_success(&repo_path, &["diff", "--git"]);
+    assert!(stdout.contains("+a resolved"), "{stdout}");
+    assert!(stdout.contains("-<<<<<<<"), "{stdout}");
+    assert!(!stdout.contains("i left"), "{stdout}");
+
+    // The file still has conflicts
+    let stdout = test_env.jj_cmd_success(&repo_path, &["diff", "--summary"]);
+    insta::assert_snapshot!(stdout, @r###"
+    M file (conflict)
+    "###);
+    let stdout = test_env.jj_cmd_success(&repo_path, &["diff", "--stat"]);
+    assert!(
+        stdout.lines().next().unwrap().ends_with(" (conflict)"),
+        "{stdout}"
+    );
+}
+
 #[test]
 fn test_diff_bad_args() {
     let test_env = TestEnvironment::default();
@@ -369,6 +588,47 @@ fn test_diff_relative_paths() {
     ..\file1      | 2 +-
     4 files changed, 4 insertions(+), 4 deletions(-)
     "###);
+
+    // Paths relative to the workspace root
+    let stdout = test_env.jj_cmd_success(&repo_path.join("dir1"), &["diff", "-s", "--repo-paths"]);
+    #[cfg(unix)]
+    insta::assert_snapshot!(stdout, @r###"
+    M dir1/file2
+    M dir1/subdir1/file3
+    M dir2/file4
+    M file1
+    "###);
+    #[cfg(windows)]
+    insta::assert_snapshot!(stdout, @r###"
+    M dir1\file2
+    M dir1\subdir1\file3
+    M dir2\file4
+    M file1
+    "###);
+    test_env.add_config("ui.relative-paths = false");
+    assert_eq!(
+        test_env.jj_cmd_success(&repo_path.join("dir1"), &["diff", "-s"]),
+        stdout
+    );
+
+    // The directory part and the file name can be colored differently
+    let stdout = test_env.jj_cmd_success(
+        &repo_path.join("dir1"),
+        &[
+            "diff",
+            "-s",
+            "--color=always",
+            "--config-toml",
+            r#"colors.dirname = "blue""#,
+        ],
+    );
+    #[cfg(unix)]
+    insta::assert_snapshot!(stdout, @r###"
+    [38;5;6mM [38;5;4mdir1/[38;5;6mfile2[39m
+    [38;5;6mM [38;5;4mdir1/subdir1/[38;5;6mfile3[39m
+    [38;5;6mM [38;5;4mdir2/[38;5;6mfile4[39m
+    [38;5;6mM file1[39m
+    "###);
 }
 
 #[test]
@@ -751,7 +1011,7 @@ fn test_diff_external_tool() {
     diff
     "###);
     insta::assert_snapshot!(stderr, @r###"
-    Tool exited with a non-zero code (run with --verbose to see the exact invocation). Exit code: 1.
+    Warning: Tool exited with a non-zero code (run with --verbose to see the exact invocation). Exit code: 1.
     "###);
 }
 
@@ -959,21 +1219,306 @@ fn test_diff_binary() {
     let stdout = test_env.jj_cmd_success(&repo_path, &["diff"]);
     insta::assert_snapshot!(stdout, @r###"
     Removed regular file file1.png:
-        (binary)
+        (binary file, 16.0B)
     Modified regular file file2.png:
-        (binary)
+        (binary file, 16.0B → 8.0B)
     Added regular file file3.png:
-        (binary)
+        (binary file, 12.0B)
     Added regular file file4.png:
-        (binary)
+        (binary file, 3.0B)
+    "###);
+
+    let stdout = test_env.jj_cmd_success(&repo_path, &["diff", "--stat"]);
+    insta::assert_snapshot!(stdout, @r###"
+    file1.png | Bin 16 -> 0 bytes
+    file2.png | Bin 16 -> 8 bytes
+    file3.png | Bin 0 -> 12 bytes
+    file4.png | Bin 0 -> 3 bytes
+    4 files changed, 0 insertions(+), 0 deletions(-)
+    "###);
+
+    let stdout = test_env.jj_cmd_success(&repo_path, &["diff", "--summary"]);
+    insta::assert_snapshot!(stdout, @r###"
+    D file1.png
+    M file2.png
+    A file3.png
+    A file4.png
+    "###);
+
+    let stdout = test_env.jj_cmd_success(&repo_path, &["diff", "--git"]);
+    insta::assert_snapshot!(stdout, @r###"
+    diff --git a/file1.png b/file1.png
+    deleted file mode 100644
+    index 2b65b23c22..0000000000
+    Binary files a/file1.png and /dev/null differ
+    diff --git a/file2.png b/file2.png
+    index 7f036ce788...3bd1f0e297 100644
+    Binary files a/file2.png and b/file2.png differ
+    diff --git a/file3.png b/file3.png
+    new file mode 100644
+    index 0000000000..deacfbc286
+    Binary files /dev/null and b/file3.png differ
+    diff --git a/file4.png b/file4.png
+    new file mode 100644
+    index 0000000000..4227ca4e87
+    Binary files /dev/null and b/file4.png differ
+    "###);
+}
+
+#[test]
+fn test_diff_binary_extensions() {
+    let test_env = TestEnvironment::default();
+    test_env.jj_cmd_ok(test_env.env_root(), &["init", "repo", "--git"]);
+    let repo_path = test_env.env_root().join("repo");
+
+    std::fs::write(repo_path.join("file.txt"), "text\n").unwrap();
+    std::fs::write(repo_path.join("file.dat"), "text\n").unwrap();
+
+    let stdout = test_env.jj_cmd_success(
+        &repo_path,
+        &["diff", "--config-toml=ui.diff.binary-extensions=['dat']"],
+    );
+    insta::assert_snapshot!(stdout, @r###"
+    Added regular file file.dat:
+        (binary file, 5.0B)
+    Added regular file file.txt:
+            1: text
+    "###);
+}
+
+#[test]
+fn test_diff_binary_gitattributes() {
+    let test_env = TestEnvironment::default();
+    test_env.jj_cmd_ok(test_env.env_root(), &["init", "repo", "--git"]);
+    let repo_path = test_env.env_root().join("repo");
+    let git_info_path = repo_path.join(".jj/repo/store/git/info");
+    std::fs::create_dir_all(&git_info_path).unwrap();
+    std::fs::write(git_info_path.join("attributes"), "override.txt -text\n").unwrap();
+
+    std::fs::write(repo_path.join(".gitattributes"), "*.dat -text\n").unwrap();
+    std::fs::create_dir(repo_path.join("sub")).unwrap();
+    std::fs::write(
+        repo_path.join("sub").join(".gitattributes"),
+        "keep.dat text\n",
+    )
+    .unwrap();
+    for path in ["file.dat", "override.txt", "sub/file.dat", "sub/keep.dat"] {
+        std::fs::write(repo_path.join(path), "text\n").unwrap();
+    }
+
+    let stdout = test_env.jj_cmd_success(&repo_path, &["diff"]);
+    insta::assert_snapshot!(stdout, @r###"
+    Added regular file .gitattributes:
+            1: *.dat -text
+    Added regular file file.dat:
+        (binary file, 5.0B)
+    Added regular file override.txt:
+        (binary file, 5.0B)
+    Added regular file sub/.gitattributes:
+            1: keep.dat text
+    Added regular file sub/file.dat:
+        (binary file, 5.0B)
+    Added regular file sub/keep.dat:
+            1: text
+    "###);
+}
+
+#[test]
+fn test_diff_binary_git_patch() {
+    let test_env = TestEnvironment::default();
+    test_env.jj_cmd_ok(test_env.env_root(), &["init", "repo", "--git"]);
+    let repo_path = test_env.env_root().join("repo");
+
+    std::fs::write(repo_path.join("file.bin"), b"\0\x01\x02\x03").unwrap();
+    test_env.jj_cmd_ok(&repo_path, &["new"]);
+    std::fs::write(repo_path.join("file.bin"), b"\0\x01\x02\x03\x04").unwrap();
+
+    // The literal data is the zlib-wrapped contents, base85-encoded. The first
+    // literal is the new contents and the second one the old contents.
+    let stdout = test_env.jj_cmd_success(&repo_path, &["diff", "--git", "--binary"]);
+    insta::assert_snapshot!(stdout, @r###"
+    diff --git a/file.bin b/file.bin
+    index 85025d98693fe77b700bcf818dfd8fcd13c4e961...eaf36c1daccfdf325514461cd1a2ffbc139b5464 100644
+    GIT binary patch
+    literal 5
+    PcmV+g0Q&y`0RjUA02u%a
+
+    literal 4
+    OcmV+f0Q>&{0RjU64gd!L
+    "###);
+}
+
+#[test]
+fn test_diff_large_file() {
+    let test_env = TestEnvironment::default();
+    test_env.jj_cmd_ok(test_env.env_root(), &["init", "repo", "--git"]);
+    test_env.add_config(r#"ui.diff.max-file-size = "10B""#);
+    let repo_path = test_env.env_root().join("repo");
+
+    std::fs::write(repo_path.join("small"), "small\n").unwrap();
+    std::fs::write(repo_path.join("large"), "line1\nline2\nline3\n").unwrap();
+
+    let stdout = test_env.jj_cmd_success(&repo_path, &["diff"]);
+    insta::assert_snapshot!(stdout, @r###"
+    Added regular file large:
+        (file too large to diff, use --git to force)
+    Added regular file small:
+            1: small
+    "###);
+
+    let stdout = test_env.jj_cmd_success(&repo_path, &["diff", "--stat"]);
+    insta::assert_snapshot!(stdout, @r###"
+    large | Bin 0 -> 18 bytes
+    small | 1 +
+    2 files changed, 1 insertion(+), 0 deletions(-)
     "###);
 
+    // The Git format shows the whole diff regardless of the size
+    let stdout = test_env.jj_cmd_success(&repo_path, &["diff", "--git", "large"]);
+    insta::assert_snapshot!(stdout, @r###"
+    diff --git a/large b/large
+    new file mode 100644
+    index 0000000000..83db48f84e
+    --- /dev/null
+    +++ b/large
+    @@ -1,0 +1,3 @@
+    +line1
+    +line2
+    +line3
+    "###);
+
+    // A size of 0 means there's no limit
+    let stdout = test_env.jj_cmd_success(
+        &repo_path,
+        &["diff", "--config-toml=ui.diff.max-file-size=0", "large"],
+    );
+    insta::assert_snapshot!(stdout, @r###"
+    Added regular file large:
+            1: line1
+            2: line2
+            3: line3
+    "###);
+}
+
+#[test]
+fn test_diff_skip_paths() {
+    let test_env = TestEnvironment::default();
+    test_env.jj_cmd_ok(test_env.env_root(), &["init", "repo", "--git"]);
+    test_env.add_config(r#"diff.skip-paths = ["*.pb.go"]"#);
+    let repo_path = test_env.env_root().join("repo");
+
+    std::fs::create_dir(repo_path.join("gen")).unwrap();
+    std::fs::write(repo_path.join("gen").join("types.pb.go"), "gen\n").unwrap();
+    std::fs::write(repo_path.join("src.go"), "src\n").unwrap();
+
+    let stdout = test_env.jj_cmd_success(&repo_path, &["diff"]);
+    insta::assert_snapshot!(stdout, @r###"
+    Added regular file gen/types.pb.go:
+        (diff suppressed for generated file; use --include-generated)
+    Added regular file src.go:
+            1: src
+    "###);
+
+    let stdout = test_env.jj_cmd_success(&repo_path, &["diff", "--git"]);
+    insta::assert_snapshot!(stdout, @r###"
+    diff --git a/gen/types.pb.go b/gen/types.pb.go
+    new file mode 100644
+    index 0000000000..4f62b849d5
+    (diff suppressed for generated file; use --include-generated)
+    diff --git a/src.go b/src.go
+    new file mode 100644
+    index 0000000000..85de9cf933
+    --- /dev/null
+    +++ b/src.go
+    @@ -1,0 +1,1 @@
+    +src
+    "###);
+
+    // The summary formats still include the skipped files
     let stdout = test_env.jj_cmd_success(&repo_path, &["diff", "--stat"]);
     insta::assert_snapshot!(stdout, @r###"
-    file1.png | 3 ---
-    file2.png | 5 ++---
-    file3.png | 3 +++
-    file4.png | 1 +
-    4 files changed, 6 insertions(+), 6 deletions(-)
+    gen/types.pb.go | 1 +
+    src.go          | 1 +
+    2 files changed, 2 insertions(+), 0 deletions(-)
+    "###);
+
+    let stdout = test_env.jj_cmd_success(&repo_path, &["diff", "--include-generated"]);
+    insta::assert_snapshot!(stdout, @r###"
+    Added regular file gen/types.pb.go:
+            1: gen
+    Added regular file src.go:
+            1: src
+    "###);
+
+    // A file named explicitly is shown, but not a file in a named directory
+    let stdout = test_env.jj_cmd_success(&repo_path, &["diff", "gen/types.pb.go"]);
+    insta::assert_snapshot!(stdout, @r###"
+    Added regular file gen/types.pb.go:
+            1: gen
+    "###);
+    let stdout = test_env.jj_cmd_success(&repo_path, &["diff", "gen"]);
+    insta::assert_snapshot!(stdout, @r###"
+    Added regular file gen/types.pb.go:
+        (diff suppressed for generated file; use --include-generated)
+    "###);
+}
+
+#[test]
+fn test_diff_ignore_whitespace() {
+    let test_env = TestEnvironment::default();
+    test_env.jj_cmd_ok(test_env.env_root(), &["init", "repo", "--git"]);
+    let repo_path = test_env.env_root().join("repo");
+
+    std::fs::write(repo_path.join("file1"), "a b\nc\n").unwrap();
+    std::fs::write(repo_path.join("file2"), "x\ny\n").unwrap();
+    std::fs::write(repo_path.join("file3"), "foo\nbar\n").unwrap();
+    test_env.jj_cmd_ok(&repo_path, &["new"]);
+    // Only the amount of whitespace changes
+    std::fs::write(repo_path.join("file1"), "a  b \nc\n").unwrap();
+    // Only a carriage return is added
+    std::fs::write(repo_path.join("file2"), "x\r\ny\n").unwrap();
+    // Whitespace and real changes
+    std::fs::write(repo_path.join("file3"), "foo \nbaz\n").unwrap();
+
+    let stdout = test_env.jj_cmd_success(&repo_path, &["diff", "-w"]);
+    insta::assert_snapshot!(stdout, @r###"
+    Modified regular file file3:
+       1    1: foo
+       2    2: barbaz
+    "###);
+
+    let stdout = test_env.jj_cmd_success(&repo_path, &["diff", "-b"]);
+    insta::assert_snapshot!(stdout, @r###"
+    Modified regular file file3:
+       1    1: foo
+       2    2: barbaz
+    "###);
+
+    let stdout = test_env.jj_cmd_success(&repo_path, &["diff", "--git", "-w"]);
+    insta::assert_snapshot!(stdout, @r###"
+    diff --git a/file3 b/file3
+    index 3bd1f0e297...e34f7ff964 100644
+    --- a/file3
+    +++ b/file3
+    @@ -1,2 +1,2 @@
+     foo
+    -bar
+    +baz
+    "###);
+
+    let stdout = test_env.jj_cmd_success(&repo_path, &["diff", "--stat", "-w"]);
+    insta::assert_snapshot!(stdout, @r###"
+    file1 | 0 (whitespace only)
+    file2 | 0 (whitespace only)
+    file3 | 2 +-
+    3 files changed, 1 insertion(+), 1 deletion(-)
+    "###);
+
+    let stdout = test_env.jj_cmd_success(&repo_path, &["diff", "--stat", "--ignore-cr-at-eol"]);
+    insta::assert_snapshot!(stdout, @r###"
+    file1 | 2 +-
+    file2 | 0 (whitespace only)
+    file3 | 4 ++--
+    3 files changed, 3 insertions(+), 3 deletions(-)
     "###);
 }
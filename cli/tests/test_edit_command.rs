@@ -67,6 +67,76 @@ fn test_edit() {
     "###);
 }
 
+#[test]
+fn test_edit_mid_stack() {
+    let test_env = TestEnvironment::default();
+    test_env.jj_cmd_ok(test_env.env_root(), &["init", "repo", "--git"]);
+    let repo_path = test_env.env_root().join("repo");
+    std::fs::write(repo_path.join("file1"), "1").unwrap();
+    test_env.jj_cmd_ok(&repo_path, &["commit", "-m", "first"]);
+    std::fs::write(repo_path.join("file1"), "2").unwrap();
+    test_env.jj_cmd_ok(&repo_path, &["commit", "-m", "second"]);
+    test_env.jj_cmd_ok(&repo_path, &["branch", "create", "-r", "@-", "stack"]);
+    std::fs::write(repo_path.join("file1"), "3").unwrap();
+    test_env.jj_cmd_ok(&repo_path, &["describe", "-m", "third"]);
+    let template = r#"if(root, "root", separate(" ", description.first_line(), branches))"#;
+
+    // The working copy becomes the edited commit itself, not a new child
+    test_env.jj_cmd_ok(&repo_path, &["edit", "description(first)"]);
+    insta::assert_snapshot!(read_file(&repo_path.join("file1")), @"1");
+    let stdout = test_env.jj_cmd_success(&repo_path, &["log", "-T", template]);
+    insta::assert_snapshot!(stdout, @r###"
+    ◉  third
+    ◉  second stack
+    @  first
+    ◉  root
+    "###);
+
+    // Snapshots amend the commit, and the descendants and their branches
+    // follow the rewritten commit
+    std::fs::write(repo_path.join("file2"), "edited").unwrap();
+    let (stdout, stderr) = test_env.jj_cmd_ok(&repo_path, &["log", "-T", template]);
+    insta::assert_snapshot!(stdout, @r###"
+    ◉  third
+    ◉  second stack
+    @  first
+    ◉  root
+    "###);
+    insta::assert_snapshot!(stderr, @r###"
+    Rebased 2 descendant commits onto updated working copy
+    "###);
+    let stdout = test_env.jj_cmd_success(&repo_path, &["cat", "-r", "stack", "file2"]);
+    insta::assert_snapshot!(stdout, @"edited");
+
+    // `jj new` starts a new change on top, which later snapshots amend instead
+    test_env.jj_cmd_ok(&repo_path, &["new"]);
+    std::fs::write(repo_path.join("file3"), "new").unwrap();
+    let stdout = test_env.jj_cmd_success(
+        &repo_path,
+        &[
+            "log",
+            "--no-graph",
+            "-r",
+            "@-",
+            "-T",
+            r#"description ++ empty"#,
+        ],
+    );
+    insta::assert_snapshot!(stdout, @r###"
+    first
+    false
+    "###);
+    let stdout = test_env.jj_cmd_success(&repo_path, &["diff", "-r", "@-", "--summary"]);
+    insta::assert_snapshot!(stdout, @r###"
+    A file1
+    A file2
+    "###);
+    let stdout = test_env.jj_cmd_success(&repo_path, &["diff", "--summary"]);
+    insta::assert_snapshot!(stdout, @r###"
+    A file3
+    "###);
+}
+
 #[test]
 // Windows says "Access is denied" when trying to delete the object file.
 #[cfg(unix)]
@@ -106,7 +176,8 @@ fn test_edit_current_wc_commit_missing() {
     Caused by:
     1: Current working-copy commit not found
     2: Object 69542c1984c1f9d91f7c6c9c9e6941782c944bd9 of type commit not found
-    3: An object with id 69542c1984c1f9d91f7c6c9c9e6941782c944bd9 could not be found
+    3: Git object 69542c1984c1f9d91f7c6c9c9e6941782c944bd9 is missing from the backing Git repo
+    4: An object with id 69542c1984c1f9d91f7c6c9c9e6941782c944bd9 could not be found
     "###);
 }
 
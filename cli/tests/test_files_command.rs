@@ -0,0 +1,65 @@
+// Copyright 2024 The Jujutsu Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use crate::common::TestEnvironment;
+
+#[test]
+fn test_files() {
+    let test_env = TestEnvironment::default();
+    test_env.jj_cmd_ok(test_env.env_root(), &["init", "repo", "--git"]);
+    let repo_path = test_env.env_root().join("repo");
+    std::fs::create_dir(repo_path.join("dir")).unwrap();
+    std::fs::write(repo_path.join("dir").join("file2"), "").unwrap();
+    std::fs::write(repo_path.join("file1"), "").unwrap();
+
+    let stdout = test_env.jj_cmd_success(&repo_path, &["files"]);
+    insta::assert_snapshot!(stdout.replace('\\', "/"), @r###"
+    dir/file2
+    file1
+    "###);
+    // Paths are relative to the current directory unless -z is passed
+    let stdout = test_env.jj_cmd_success(&repo_path.join("dir"), &["files"]);
+    insta::assert_snapshot!(stdout.replace('\\', "/"), @r###"
+    file2
+    ../file1
+    "###);
+    let stdout = test_env.jj_cmd_success(&repo_path.join("dir"), &["files", "-z"]);
+    assert_eq!(stdout, "dir/file2\0file1\0");
+}
+
+#[cfg(unix)]
+#[test]
+fn test_files_null_terminated_round_trip() {
+    let test_env = TestEnvironment::default();
+    test_env.jj_cmd_ok(test_env.env_root(), &["init", "repo", "--git"]);
+    let repo_path = test_env.env_root().join("repo");
+    std::fs::create_dir(repo_path.join("dir")).unwrap();
+    let names = ["dir/with space", "new\nline", "plain", "tab\tname"];
+    for name in names {
+        std::fs::write(repo_path.join(name), name).unwrap();
+    }
+
+    let stdout = test_env.jj_cmd_success(&repo_path.join("dir"), &["files", "-z"]);
+    let paths: Vec<&str> = stdout.split_terminator('\0').collect();
+    assert_eq!(paths, names);
+    // A consumer can use the paths as they are
+    for path in paths {
+        let contents = std::fs::read_to_string(repo_path.join(path)).unwrap();
+        assert_eq!(contents, path);
+    }
+
+    // The newline makes the normal output ambiguous
+    let stdout = test_env.jj_cmd_success(&repo_path, &["files"]);
+    assert_eq!(stdout.lines().count(), 5);
+}
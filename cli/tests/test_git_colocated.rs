@@ -21,6 +21,9 @@ use crate::common::TestEnvironment;
 #[test]
 fn test_git_colocated() {
     let test_env = TestEnvironment::default();
+    // The Git commits are newer than the fake timestamps. Don't let the commits
+    // created on top of them take their committer timestamps from them.
+    test_env.add_config("timestamps.monotonic-commits = false");
     let workspace_root = test_env.env_root().join("repo");
     let git_repo = git2::Repository::init(&workspace_root).unwrap();
 
@@ -402,7 +405,7 @@ fn test_git_colocated_conflicting_git_refs() {
     insta::assert_snapshot!(stdout, @"");
     insta::with_settings!({filters => vec![(": The lock for resource.*", ": ...")]}, {
         insta::assert_snapshot!(stderr, @r###"
-        Failed to export some branches:
+        Warning: Failed to export some branches:
           main/sub: Failed to set: A lock could not be obtained for reference "refs/heads/main/sub": ...
         Hint: Git doesn't allow a branch name that looks like a parent directory of
         another (e.g. `foo` and `foo/bar`). Try to rename the branches that failed to
@@ -414,6 +417,9 @@ fn test_git_colocated_conflicting_git_refs() {
 #[test]
 fn test_git_colocated_checkout_non_empty_working_copy() {
     let test_env = TestEnvironment::default();
+    // The Git commits are newer than the fake timestamps. Don't let the commits
+    // created on top of them take their committer timestamps from them.
+    test_env.add_config("timestamps.monotonic-commits = false");
     let workspace_root = test_env.env_root().join("repo");
     let git_repo = git2::Repository::init(&workspace_root).unwrap();
     test_env.jj_cmd_ok(&workspace_root, &["init", "--git-repo", "."]);
@@ -553,7 +559,7 @@ fn test_git_colocated_rebase_dirty_working_copy() {
       Use `jj branch list` to see details. Use `jj branch set <name> -r <rev>` to resolve.
     "###);
     insta::assert_snapshot!(stderr, @r###"
-    Failed to export some branches:
+    Warning: Failed to export some branches:
       feature: Modified ref had been deleted in Git
     Done importing changes from the underlying Git repo.
     "###);
@@ -730,6 +736,9 @@ fn get_log_output_with_stderr(
 #[test]
 fn test_git_colocated_unreachable_commits() {
     let test_env = TestEnvironment::default();
+    // The Git commits are newer than the fake timestamps. Don't let the commits
+    // created on top of them take their committer timestamps from them.
+    test_env.add_config("timestamps.monotonic-commits = false");
     let workspace_root = test_env.env_root().join("repo");
     let git_repo = git2::Repository::init(&workspace_root).unwrap();
 
@@ -798,3 +807,45 @@ fn test_git_colocated_unreachable_commits() {
     Error: Revision "8e713ff77b54928dd4a82aaabeca44b1ae91722c" doesn't exist
     "###);
 }
+
+#[test]
+fn test_git_colocated_commit_after_newer_parent() {
+    let test_env = TestEnvironment::default();
+    let workspace_root = test_env.env_root().join("repo");
+    let git_repo = git2::Repository::init(&workspace_root).unwrap();
+
+    // Create a commit in Git that is newer than the fake timestamps
+    let empty_tree_oid = git_repo.treebuilder(None).unwrap().write().unwrap();
+    let tree = git_repo.find_tree(empty_tree_oid).unwrap();
+    let signature = git2::Signature::new(
+        "Someone",
+        "someone@example.com",
+        &git2::Time::new(1234567890, 60),
+    )
+    .unwrap();
+    git_repo
+        .commit(
+            Some("refs/heads/master"),
+            &signature,
+            &signature,
+            "initial",
+            &tree,
+            &[],
+        )
+        .unwrap();
+    test_env.jj_cmd_ok(&workspace_root, &["init", "--git-repo", "."]);
+    test_env.jj_cmd_ok(&workspace_root, &["new"]);
+
+    // The commits on top of it are committed at its committer time instead of
+    // going back in time, but are still authored at the time from the clock
+    let template = r#"author.timestamp() ++ " | " ++ committer.timestamp() ++ "\n""#;
+    let stdout = test_env.jj_cmd_success(
+        &workspace_root,
+        &["log", "--no-graph", "-r=::@ ~ root()", "-T", template],
+    );
+    insta::assert_snapshot!(stdout, @r###"
+    2001-02-03 04:05:08.000 +07:00 | 2009-02-14 06:31:30.000 +07:00
+    2001-02-03 04:05:07.000 +07:00 | 2009-02-14 06:31:30.000 +07:00
+    2009-02-14 00:31:30.000 +01:00 | 2009-02-14 00:31:30.000 +01:00
+    "###);
+}
@@ -901,7 +901,7 @@ fn test_fetch_undo_what() {
     insta::assert_snapshot!(stdout, @"");
     insta::assert_snapshot!(stderr, @"");
     insta::assert_snapshot!(get_branch_output(&test_env, &repo_path), @r###"
-    b (deleted)
+    b (deleted, will be pushed)
       @origin: vpupmnsl hidden c7d4bdcb descr_for_b
       (this branch will be *deleted permanently* on the remote on the next `jj git push`. Use `jj branch forget` to prevent this)
     "###);
@@ -910,7 +910,7 @@ fn test_fetch_undo_what() {
     // change our local repo state...
     test_env.jj_cmd_ok(&repo_path, &["branch", "c", "newbranch"]);
     insta::assert_snapshot!(get_branch_output(&test_env, &repo_path), @r###"
-    b (deleted)
+    b (deleted, will be pushed)
       @origin: vpupmnsl hidden c7d4bdcb descr_for_b
       (this branch will be *deleted permanently* on the remote on the next `jj git push`. Use `jj branch forget` to prevent this)
     newbranch: qpvuntsm 230dd059 (empty) (no description set)
@@ -1244,3 +1244,118 @@ fn test_git_fetch_remote_only_branch() {
     feature2@origin: mzyxwzks 9f01a0e0 message
     "###);
 }
+
+/// Creates a Git repo with `feature1` branch to be added as a remote
+fn init_git_repo_with_feature_branch(test_env: &TestEnvironment) -> git2::Repository {
+    let git_repo_path = test_env.env_root().join("git-repo");
+    let git_repo = git2::Repository::init(git_repo_path).unwrap();
+    let signature =
+        git2::Signature::new("Some One", "some.one@example.com", &git2::Time::new(0, 0)).unwrap();
+    let mut tree_builder = git_repo.treebuilder(None).unwrap();
+    let file_oid = git_repo.blob(b"content").unwrap();
+    tree_builder
+        .insert("file", file_oid, git2::FileMode::Blob.into())
+        .unwrap();
+    let tree_oid = tree_builder.write().unwrap();
+    let tree = git_repo.find_tree(tree_oid).unwrap();
+    git_repo
+        .commit(
+            Some("refs/heads/feature1"),
+            &signature,
+            &signature,
+            "message",
+            &tree,
+            &[],
+        )
+        .unwrap();
+    drop(tree);
+    git_repo
+}
+
+#[test]
+fn test_git_fetch_deleted_branch_stays_deleted() {
+    let test_env = TestEnvironment::default();
+    test_env.add_config("git.auto-local-branch = true");
+    test_env.jj_cmd_ok(test_env.env_root(), &["init", "repo", "--git"]);
+    let repo_path = test_env.env_root().join("repo");
+    let git_repo = init_git_repo_with_feature_branch(&test_env);
+    test_env.jj_cmd_ok(
+        &repo_path,
+        &["git", "remote", "add", "origin", "../git-repo"],
+    );
+    test_env.jj_cmd_ok(&repo_path, &["git", "fetch"]);
+
+    // Deleting the local branch leaves a tombstone behind
+    test_env.jj_cmd_ok(&repo_path, &["branch", "delete", "feature1"]);
+    insta::assert_snapshot!(get_branch_output(&test_env, &repo_path), @r###"
+    feature1 (deleted, will be pushed)
+      @origin: mzyxwzks 9f01a0e0 message
+      (this branch will be *deleted permanently* on the remote on the next `jj git push`. Use `jj branch forget` to prevent this)
+    "###);
+
+    // Fetching the unchanged remote branch doesn't resurrect the local branch
+    test_env.jj_cmd_ok(&repo_path, &["git", "fetch"]);
+    insta::assert_snapshot!(get_branch_output(&test_env, &repo_path), @r###"
+    feature1 (deleted, will be pushed)
+      @origin: mzyxwzks 9f01a0e0 message
+      (this branch will be *deleted permanently* on the remote on the next `jj git push`. Use `jj branch forget` to prevent this)
+    "###);
+
+    // Pushing deletes the remote branch
+    let (stdout, stderr) = test_env.jj_cmd_ok(&repo_path, &["git", "push", "--deleted"]);
+    insta::assert_snapshot!(stdout, @"");
+    insta::assert_snapshot!(stderr, @r###"
+    Branch changes to push to origin:
+      Delete branch feature1 from 9f01a0e04879
+    "###);
+    assert!(git_repo.find_reference("refs/heads/feature1").is_err());
+    insta::assert_snapshot!(get_branch_output(&test_env, &repo_path), @"");
+
+    // And fetching again doesn't bring it back
+    test_env.jj_cmd_ok(&repo_path, &["git", "fetch"]);
+    insta::assert_snapshot!(get_branch_output(&test_env, &repo_path), @"");
+}
+
+#[test]
+fn test_git_fetch_deleted_branch_moved_on_remote() {
+    let test_env = TestEnvironment::default();
+    test_env.add_config("git.auto-local-branch = true");
+    test_env.jj_cmd_ok(test_env.env_root(), &["init", "repo", "--git"]);
+    let repo_path = test_env.env_root().join("repo");
+    let git_repo = init_git_repo_with_feature_branch(&test_env);
+    test_env.jj_cmd_ok(
+        &repo_path,
+        &["git", "remote", "add", "origin", "../git-repo"],
+    );
+    test_env.jj_cmd_ok(&repo_path, &["git", "fetch"]);
+    test_env.jj_cmd_ok(&repo_path, &["branch", "delete", "feature1"]);
+
+    // Move the branch on the remote after it was deleted locally
+    let signature =
+        git2::Signature::new("Some One", "some.one@example.com", &git2::Time::new(0, 0)).unwrap();
+    let parent = git_repo
+        .find_reference("refs/heads/feature1")
+        .unwrap()
+        .peel_to_commit()
+        .unwrap();
+    git_repo
+        .commit(
+            Some("refs/heads/feature1"),
+            &signature,
+            &signature,
+            "moved",
+            &parent.tree().unwrap(),
+            &[&parent],
+        )
+        .unwrap();
+
+    // The deletion and the remote move conflict
+    test_env.jj_cmd_ok(&repo_path, &["git", "fetch"]);
+    insta::assert_snapshot!(get_log_output(&test_env, &repo_path), @r###"
+    ◉  c1de9117d4ad moved feature1?? feature1@origin
+    ◉  9f01a0e04879 message
+    │ @  230dd059e1b0
+    ├─╯
+    ◉  000000000000
+    "###);
+}
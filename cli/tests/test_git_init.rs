@@ -542,13 +542,15 @@ fn test_git_init_colocated_via_flag_git_dir_exists() {
     let workspace_root = test_env.env_root().join("repo");
     init_git_repo(&workspace_root, false);
 
-    let stderr = test_env.jj_cmd_failure(&workspace_root, &["git", "init", "--colocate"]);
+    // The existing Git repo is reused
+    let (stdout, stderr) = test_env.jj_cmd_ok(&workspace_root, &["git", "init", "--colocate"]);
+    insta::assert_snapshot!(stdout, @"");
     insta::assert_snapshot!(stderr, @r###"
-    Error: Failed to access the repository
-    Caused by:
-    1: Failed to initialize git repository
-    2: Refusing to initialize the existing '$TEST_ENV/repo/.git' directory
+    Using the existing Git repo in "." as the backing repo
+    Done importing changes from the underlying Git repo.
+    Initialized repo in "."
     "###);
+    insta::assert_snapshot!(read_git_target(&workspace_root), @"../../../.git");
 }
 
 #[test]
@@ -569,3 +571,222 @@ fn test_git_init_bad_wc_path() {
     let stderr = test_env.jj_cmd_failure(test_env.env_root(), &["git", "init", "existing-file"]);
     assert!(stderr.contains("Failed to create workspace"));
 }
+
+#[test]
+fn test_git_init_external_moved_with_repo() {
+    let test_env = TestEnvironment::default();
+    let parent_path = test_env.env_root().join("parent");
+    init_git_repo(&parent_path.join("git-repo"), false);
+    test_env.jj_cmd_ok(
+        &parent_path,
+        &["git", "init", "--git-repo=git-repo", "repo"],
+    );
+    insta::assert_snapshot!(
+        read_git_target(&parent_path.join("repo")).replace('\\', "/"),
+        @"../../../../git-repo/.git"
+    );
+
+    let renamed_path = test_env.env_root().join("renamed");
+    std::fs::rename(&parent_path, &renamed_path).unwrap();
+    let stdout = test_env.jj_cmd_success(
+        &renamed_path.join("repo"),
+        &["log", "--no-graph", "-r=@-", "-T=description"],
+    );
+    insta::assert_snapshot!(stdout, @"My commit message");
+}
+
+#[test]
+fn test_git_init_external_moved_with_legacy_absolute_path() {
+    let test_env = TestEnvironment::default();
+    let parent_path = test_env.env_root().join("parent");
+    let git_repo_path = parent_path.join("git-repo");
+    init_git_repo(&git_repo_path, false);
+    test_env.jj_cmd_ok(
+        &parent_path,
+        &["git", "init", "--git-repo=git-repo", "repo"],
+    );
+    let mut git_target_path = parent_path.join("repo");
+    git_target_path.extend([".jj", "repo", "store", "git_target"]);
+    std::fs::write(
+        &git_target_path,
+        git_repo_path.join(".git").to_str().unwrap(),
+    )
+    .unwrap();
+
+    let renamed_path = test_env.env_root().join("renamed");
+    std::fs::rename(&parent_path, &renamed_path).unwrap();
+    let workspace_root = renamed_path.join("repo");
+    let (stdout, stderr) = test_env.jj_cmd_ok(
+        &workspace_root,
+        &["log", "--no-graph", "-r=@-", "-T=description"],
+    );
+    insta::assert_snapshot!(stdout, @"My commit message");
+    insta::assert_snapshot!(stderr.replace('\\', "/"), @r###"
+    Warning: Updated $TEST_ENV/renamed/repo/.jj/repo/store/git_target to point to ../../../../git-repo/.git instead of $TEST_ENV/parent/git-repo/.git, which no longer exists
+    "###);
+    insta::assert_snapshot!(
+        read_git_target(&workspace_root).replace('\\', "/"),
+        @"../../../../git-repo/.git"
+    );
+}
+
+#[test]
+fn test_git_init_external_relink_moved_git_repo() {
+    let test_env = TestEnvironment::default();
+    init_git_repo(&test_env.env_root().join("git-repo"), false);
+    test_env.jj_cmd_ok(
+        test_env.env_root(),
+        &["git", "init", "--git-repo=git-repo", "repo"],
+    );
+    let workspace_root = test_env.env_root().join("repo");
+
+    std::fs::rename(
+        test_env.env_root().join("git-repo"),
+        test_env.env_root().join("moved"),
+    )
+    .unwrap();
+    let stderr = test_env.jj_cmd_failure(&workspace_root, &["log"]);
+    insta::assert_snapshot!(stderr.replace('\\', "/"), @r###"
+    Error: $TEST_ENV/repo/.jj/repo/store/git_target points to ../../../../git-repo/.git, which is not a Git repo
+    Hint: If the Git repo was moved, run `jj debug relink --git-repo PATH` with its new path.
+    "###);
+
+    let (stdout, stderr) = test_env.jj_cmd_ok(
+        &workspace_root,
+        &["debug", "relink", "--git-repo", "../moved"],
+    );
+    insta::assert_snapshot!(stdout, @"");
+    insta::assert_snapshot!(stderr, @r###"
+    Updated the path to the Git repo
+    "###);
+    insta::assert_snapshot!(
+        read_git_target(&workspace_root).replace('\\', "/"),
+        @"../../../../moved/.git"
+    );
+    let stdout = test_env.jj_cmd_success(
+        &workspace_root,
+        &["log", "--no-graph", "-r=@-", "-T=description"],
+    );
+    insta::assert_snapshot!(stdout, @"My commit message");
+}
+
+#[test]
+fn test_git_init_git_dir_exists() {
+    let test_env = TestEnvironment::default();
+    let workspace_root = test_env.env_root().join("repo");
+    init_git_repo(&workspace_root, false);
+
+    // Without any flags, the existing Git repo is colocated
+    let (stdout, stderr) = test_env.jj_cmd_ok(test_env.env_root(), &["git", "init", "repo"]);
+    insta::assert_snapshot!(stdout, @"");
+    insta::assert_snapshot!(stderr, @r###"
+    Using the existing Git repo in "repo" as the backing repo
+    Done importing changes from the underlying Git repo.
+    Initialized repo in "repo"
+    "###);
+    insta::assert_snapshot!(read_git_target(&workspace_root), @"../../../.git");
+    let stdout = test_env.jj_cmd_success(
+        &workspace_root,
+        &["log", "--no-graph", "-r=@-", "-T=description"],
+    );
+    insta::assert_snapshot!(stdout, @"My commit message");
+}
+
+#[test]
+fn test_git_init_imports_refs_in_initial_operation() {
+    let test_env = TestEnvironment::default();
+    let git_repo_path = test_env.env_root().join("git-repo");
+    init_git_repo(&git_repo_path, false);
+    test_env.jj_cmd_ok(
+        test_env.env_root(),
+        &["git", "init", "repo", "--git-repo=git-repo"],
+    );
+    let workspace_root = test_env.env_root().join("repo");
+
+    // The root operation has no description
+    let template = r#"if(description, description ++ "\n")"#;
+    let stdout = test_env.jj_cmd_success(
+        &workspace_root,
+        &["op", "log", "--no-graph", "-T", template],
+    );
+    insta::assert_snapshot!(stdout, @r###"
+    import git head
+    add workspace 'default' and import git refs
+    "###);
+}
+
+#[test]
+fn test_git_init_linked_worktree() {
+    let test_env = TestEnvironment::default();
+    let git_repo_path = test_env.env_root().join("git-repo");
+    let worktree_path = test_env.env_root().join("worktree");
+    let nested_worktree_path = test_env.env_root().join("nested-worktree");
+    let git_repo = init_git_repo(&git_repo_path, false);
+    git_repo.worktree("worktree", &worktree_path, None).unwrap();
+    // A worktree created from a linked worktree also belongs to the main repo
+    git2::Repository::open(&worktree_path)
+        .unwrap()
+        .worktree("nested-worktree", &nested_worktree_path, None)
+        .unwrap();
+    assert!(worktree_path.join(".git").is_file());
+
+    for (name, git_repo_arg) in [
+        ("repo1", worktree_path.to_str().unwrap()),
+        ("repo2", nested_worktree_path.to_str().unwrap()),
+    ] {
+        test_env.jj_cmd_ok(
+            test_env.env_root(),
+            &["git", "init", name, "--git-repo", git_repo_arg],
+        );
+        let workspace_root = test_env.env_root().join(name);
+        // The main repo backs the jj repo
+        insta::allow_duplicates! {
+            insta::assert_snapshot!(
+                read_git_target(&workspace_root).replace('\\', "/"),
+                @"../../../../git-repo/.git"
+            );
+        }
+        let stdout = test_env.jj_cmd_success(
+            &workspace_root,
+            &["log", "--no-graph", "-r=@-", "-T=description"],
+        );
+        insta::allow_duplicates! {
+            insta::assert_snapshot!(stdout, @"My commit message");
+        }
+    }
+}
+
+#[test]
+fn test_git_init_nested() {
+    let test_env = TestEnvironment::default();
+    test_env.jj_cmd_ok(test_env.env_root(), &["git", "init", "repo"]);
+    let workspace_root = test_env.env_root().join("repo");
+    std::fs::create_dir(workspace_root.join("dir")).unwrap();
+
+    // The enclosing repo is found no matter where the command is run from
+    let stderr = test_env.jj_cmd_failure(test_env.env_root(), &["git", "init", "repo/sub"]);
+    insta::assert_snapshot!(stderr, @r###"
+    Error: Did not create a jj repo because "repo/sub" is inside the existing jj repo in "repo"
+    Hint: Pass `--force-nested` to create a nested repo anyway.
+    "###);
+    assert!(!workspace_root.join("sub").exists());
+    let stderr = test_env.jj_cmd_failure(&workspace_root.join("dir"), &["git", "init", "."]);
+    insta::assert_snapshot!(stderr, @r###"
+    Error: Did not create a jj repo because "." is inside the existing jj repo in ".."
+    Hint: Pass `--force-nested` to create a nested repo anyway.
+    "###);
+    assert!(workspace_root.join("dir").is_dir());
+    let stderr = test_env.jj_cmd_failure(&workspace_root, &["init", "--git", "sub"]);
+    insta::assert_snapshot!(stderr, @r###"
+    Error: Did not create a jj repo because "sub" is inside the existing jj repo in "."
+    Hint: Pass `--force-nested` to create a nested repo anyway.
+    "###);
+
+    let (stdout, stderr) =
+        test_env.jj_cmd_ok(&workspace_root, &["git", "init", "--force-nested", "sub"]);
+    insta::assert_snapshot!(stdout, @"");
+    insta::assert_snapshot!(stderr, @r###"
+    Initialized repo in "sub"
+    "###);
+    assert!(workspace_root.join("sub").join(".jj").is_dir());
+}
@@ -141,7 +141,7 @@ fn test_git_push_no_matching_branch() {
     let (stdout, stderr) = test_env.jj_cmd_ok(&workspace_root, &["git", "push"]);
     insta::assert_snapshot!(stdout, @"");
     insta::assert_snapshot!(stderr, @r###"
-    No branches found in the default push revset, `remote_branches(remote=origin)..@`.
+    Warning: No branches found in the default push revset, `remote_branches(remote=origin)..@`.
     Nothing changed.
     "###);
 }
@@ -153,7 +153,7 @@ fn test_git_push_matching_branch_unchanged() {
     let (stdout, stderr) = test_env.jj_cmd_ok(&workspace_root, &["git", "push"]);
     insta::assert_snapshot!(stdout, @"");
     insta::assert_snapshot!(stderr, @r###"
-    No branches found in the default push revset, `remote_branches(remote=origin)..@`.
+    Warning: No branches found in the default push revset, `remote_branches(remote=origin)..@`.
     Nothing changed.
     "###);
 }
@@ -196,7 +196,7 @@ fn test_git_push_other_remote_has_branch() {
     let (stdout, stderr) = test_env.jj_cmd_ok(&workspace_root, &["git", "push"]);
     insta::assert_snapshot!(stdout, @"");
     insta::assert_snapshot!(stderr, @r###"
-    No branches found in the default push revset, `remote_branches(remote=origin)..@`.
+    Warning: No branches found in the default push revset, `remote_branches(remote=origin)..@`.
     Nothing changed.
     "###);
     // But it will still get pushed to another remote
@@ -285,7 +285,7 @@ fn test_git_push_multiple() {
     // Check the setup
     let stdout = test_env.jj_cmd_success(&workspace_root, &["branch", "list", "--all"]);
     insta::assert_snapshot!(stdout, @r###"
-    branch1 (deleted)
+    branch1 (deleted, will be pushed)
       @origin: lzmmnrxq 45a3aa29 (empty) description 1
       (this branch will be *deleted permanently* on the remote on the next `jj git push`. Use `jj branch forget` to prevent this)
     branch2: yqosqzyt 15dcdaa4 (empty) foo
@@ -462,7 +462,7 @@ fn test_git_push_revisions() {
     let (stdout, stderr) = test_env.jj_cmd_ok(&workspace_root, &["git", "push", "-r=@--"]);
     insta::assert_snapshot!(stdout, @"");
     insta::assert_snapshot!(stderr, @r###"
-    No branches point to the specified revisions.
+    Warning: No branches point to the specified revisions.
     Nothing changed.
     "###);
     // Push a revision with a single branch
@@ -790,7 +790,7 @@ fn test_git_push_tracked_vs_all() {
     insta::assert_snapshot!(stdout, @r###"
     branch1: vruxwmqv a25f24af (empty) moved branch1
     branch1@origin: lzmmnrxq 45a3aa29 (empty) description 1
-    branch2 (deleted)
+    branch2 (deleted, will be pushed)
       @origin: rlzusymt 8476341e (empty) description 2
       (this branch will be *deleted permanently* on the remote on the next `jj git push`. Use `jj branch forget` to prevent this)
     branch3: znkkpsqq 998d6a78 (empty) moved branch2
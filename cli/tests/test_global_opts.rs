@@ -134,6 +134,108 @@ fn test_ignore_working_copy() {
     "###);
 }
 
+#[test]
+fn test_snapshot_auto_disabled() {
+    let test_env = TestEnvironment::default();
+    test_env.jj_cmd_ok(test_env.env_root(), &["init", "repo", "--git"]);
+    let repo_path = test_env.env_root().join("repo");
+    test_env.add_config("snapshot.auto = false");
+    // The files recording the working-copy state, with their modification
+    // times and contents
+    let read_working_copy_state = || {
+        let state_dir = repo_path.join(".jj").join("working_copy");
+        let mut state = vec![];
+        for entry in std::fs::read_dir(state_dir).unwrap() {
+            let entry = entry.unwrap();
+            let metadata = entry.metadata().unwrap();
+            if metadata.is_file() {
+                let contents = std::fs::read(entry.path()).unwrap();
+                state.push((entry.file_name(), metadata.modified().unwrap(), contents));
+            }
+        }
+        state.sort();
+        state
+    };
+    let working_copy_state = read_working_copy_state();
+    assert!(working_copy_state
+        .iter()
+        .any(|(name, _, _)| name == "tree_state"));
+
+    // New files aren't picked up, and status warns that it may be stale
+    std::fs::write(repo_path.join("file"), "initial").unwrap();
+    let (stdout, stderr) = test_env.jj_cmd_ok(&repo_path, &["status"]);
+    insta::assert_snapshot!(stdout, @r###"
+    The working copy is clean
+    Working copy : qpvuntsm 230dd059 (empty) (no description set)
+    Parent commit: zzzzzzzz 00000000 (empty) (no description set)
+    "###);
+    insta::assert_snapshot!(stderr, @r###"
+    Warning: The working copy was not snapshotted, so the status may be stale.
+    "###);
+    // Not even the stat cache was updated
+    assert_eq!(read_working_copy_state(), working_copy_state);
+
+    // Mutations don't touch the files on disk, but report where the working
+    // copy should be
+    let (_stdout, stderr) = test_env.jj_cmd_ok(&repo_path, &["new", "-m", "child"]);
+    assert!(
+        stderr.contains("Working copy not updated, should be at: "),
+        "{stderr}"
+    );
+    assert_eq!(
+        std::fs::read_to_string(repo_path.join("file")).unwrap(),
+        "initial"
+    );
+    assert_eq!(read_working_copy_state(), working_copy_state);
+
+    // Commands that must update the working copy are rejected
+    let stderr = test_env.jj_cmd_failure(&repo_path, &["untrack", "file"]);
+    insta::assert_snapshot!(stderr, @r###"
+    Error: This command must be able to update the working copy.
+    Hint: Set `snapshot.auto = true` in your config.
+    "###);
+}
+
+#[test]
+fn test_warnings_format() {
+    let test_env = TestEnvironment::default();
+    test_env.jj_cmd_ok(test_env.env_root(), &["init", "repo", "--git"]);
+    let repo_path = test_env.env_root().join("repo");
+    test_env.add_config("snapshot.auto = false");
+
+    // The data is printed to stdout, and the warnings only to stderr
+    let (stdout, stderr) = test_env.jj_cmd_ok(&repo_path, &["status", "--warnings=json"]);
+    insta::assert_snapshot!(stdout, @r###"
+    The working copy is clean
+    Working copy : qpvuntsm 230dd059 (empty) (no description set)
+    Parent commit: zzzzzzzz 00000000 (empty) (no description set)
+    "###);
+    insta::assert_snapshot!(stderr, @r###"
+    {"code":"unsnapshotted-working-copy","message":"The working copy was not snapshotted, so the status may be stale.","details":[],"hints":[]}
+    "###);
+
+    // Hints are included in the warning they belong to
+    let (stdout, stderr) = test_env.jj_cmd_ok(
+        &repo_path,
+        &["util", "completion", "--bash", "--warnings", "json"],
+    );
+    assert!(stdout.contains("COMPREPLY"), "{stdout}");
+    insta::assert_snapshot!(stderr, @r###"
+    {"code":"deprecated-command","message":"`jj util completion --bash` will be removed in a future version, and this will be a hard error","details":[],"hints":["Use `jj util completion bash` instead"]}
+    "###);
+
+    // The format can also be set in the config
+    test_env.add_config(r#"ui.warnings = "json""#);
+    let (_stdout, stderr) = test_env.jj_cmd_ok(&repo_path, &["status"]);
+    insta::assert_snapshot!(stderr, @r###"
+    {"code":"unsnapshotted-working-copy","message":"The working copy was not snapshotted, so the status may be stale.","details":[],"hints":[]}
+    "###);
+    let (_stdout, stderr) = test_env.jj_cmd_ok(&repo_path, &["status", "--warnings=text"]);
+    insta::assert_snapshot!(stderr, @r###"
+    Warning: The working copy was not snapshotted, so the status may be stale.
+    "###);
+}
+
 #[test]
 fn test_repo_arg_with_init() {
     let test_env = TestEnvironment::default();
@@ -189,6 +291,37 @@ fn test_resolve_workspace_directory() {
     "###);
 }
 
+#[test]
+fn test_repository_arg_from_other_directory() {
+    let test_env = TestEnvironment::default();
+    test_env.jj_cmd_ok(test_env.env_root(), &["init", "repo", "--git"]);
+    let repo_path = test_env.env_root().join("repo");
+    std::fs::create_dir(repo_path.join("dir")).unwrap();
+    std::fs::write(repo_path.join("dir").join("file"), "contents").unwrap();
+    std::fs::write(repo_path.join("other"), "contents").unwrap();
+    let elsewhere = test_env.env_root().join("elsewhere");
+    std::fs::create_dir(&elsewhere).unwrap();
+
+    // Path arguments are relative to the cwd, not to the -R path
+    let stdout = test_env.jj_cmd_success(&elsewhere, &["files", "-R", "../repo", "../repo/dir"]);
+    insta::assert_snapshot!(stdout.replace('\\', "/"), @r###"
+    ../repo/dir/file
+    "###);
+
+    // The .jj directory can be specified instead of the workspace root
+    let stdout = test_env.jj_cmd_success(&elsewhere, &["files", "-R", "../repo/.jj"]);
+    insta::assert_snapshot!(stdout.replace('\\', "/"), @r###"
+    ../repo/dir/file
+    ../repo/other
+    "###);
+
+    // Paths outside the specified workspace are rejected
+    let stderr = test_env.jj_cmd_failure(&elsewhere, &["files", "-R", "../repo", "."]);
+    insta::assert_snapshot!(stderr, @r###"
+    Error: Path "." is not in the repo
+    "###);
+}
+
 #[test]
 fn test_no_workspace_directory() {
     let test_env = TestEnvironment::default();
@@ -406,7 +539,7 @@ fn test_no_user_configured() {
     insta::assert_snapshot!(get_stderr_string(&assert), @r###"
     Working copy now at: qpvuntsm 7a7d6016 (empty) without name
     Parent commit      : zzzzzzzz 00000000 (empty) (no description set)
-    Name and email not configured. Until configured, your commits will be created with the empty identity, and can't be pushed to remotes. To configure, run:
+    Warning: Name and email not configured. Until configured, your commits will be created with the empty identity, and can't be pushed to remotes. To configure, run:
       jj config set --user user.name "Some One"
       jj config set --user user.email "someone@example.com"
     "###);
@@ -418,7 +551,7 @@ fn test_no_user_configured() {
     insta::assert_snapshot!(get_stderr_string(&assert), @r###"
     Working copy now at: qpvuntsm 906f8b89 (empty) without email
     Parent commit      : zzzzzzzz 00000000 (empty) (no description set)
-    Name and email not configured. Until configured, your commits will be created with the empty identity, and can't be pushed to remotes. To configure, run:
+    Warning: Name and email not configured. Until configured, your commits will be created with the empty identity, and can't be pushed to remotes. To configure, run:
       jj config set --user user.name "Some One"
       jj config set --user user.email "someone@example.com"
     "###);
@@ -446,9 +579,11 @@ fn test_help() {
       -R, --repository <REPOSITORY>      Path to repository to operate on
           --ignore-working-copy          Don't snapshot the working copy, and don't update it
           --at-operation <AT_OPERATION>  Operation to load the repo at [default: @] [aliases: at-op]
+          --repo-paths                   Show paths relative to the workspace root
       -v, --verbose                      Enable verbose logging
           --color <WHEN>                 When to colorize output (always, never, auto)
           --no-pager                     Disable the pager
+          --warnings <FORMAT>            How to print warnings to stderr (text, json)
           --config-toml <TOML>           Additional configuration options (can be repeated)
     "###);
 }
@@ -477,3 +612,83 @@ fn strip_last_line(s: &str) -> &str {
         .rsplit_once('\n')
         .map_or(s, |(h, _)| &s[..h.len() + 1])
 }
+
+#[test]
+fn test_reproducible_ids_from_env() {
+    // Creates commits with the same metadata, no matter how many commands
+    // were run in the test environment before.
+    fn create_commits(test_env: &TestEnvironment) -> (String, String) {
+        let run = |current_dir: &std::path::Path, args: &[&str], seed: &str| {
+            test_env
+                .jj_cmd(current_dir, args)
+                .env("JJ_TIMESTAMP", "2020-02-03T04:05:06+07:00")
+                .env("JJ_OP_TIMESTAMP", "2020-02-03T04:05:06+07:00")
+                .env("JJ_RANDOMNESS_SEED", seed)
+                .assert()
+                .success();
+        };
+        run(test_env.env_root(), &["git", "init", "repo"], "1");
+        let repo_path = test_env.env_root().join("repo");
+        run(&repo_path, &["new", "-m", "first"], "2");
+        run(&repo_path, &["describe", "-m", "second"], "3");
+        let commit_ids = test_env.jj_cmd_success(
+            &repo_path,
+            &[
+                "log",
+                "--ignore-working-copy",
+                "--no-graph",
+                "-T",
+                r#"commit_id ++ " " ++ committer.timestamp() ++ "\n""#,
+            ],
+        );
+        let op_ids = test_env.jj_cmd_success(
+            &repo_path,
+            &[
+                "op",
+                "log",
+                "--ignore-working-copy",
+                "--no-graph",
+                "-T",
+                r#"id ++ "\n""#,
+            ],
+        );
+        (commit_ids, op_ids)
+    }
+
+    let test_env = TestEnvironment::default();
+    let (commit_ids, op_ids) = create_commits(&test_env);
+    assert!(commit_ids
+        .lines()
+        .next()
+        .unwrap()
+        .ends_with(" 2020-02-03 04:05:06.000 +07:00"));
+
+    // The default timestamps and randomness seed of the test environment
+    // depend on the number of commands run, so run one more.
+    let other_test_env = TestEnvironment::default();
+    other_test_env.jj_cmd_success(other_test_env.env_root(), &["version"]);
+    let (other_commit_ids, other_op_ids) = create_commits(&other_test_env);
+    assert_eq!(other_commit_ids, commit_ids);
+    assert_eq!(other_op_ids, op_ids);
+}
+
+#[test]
+fn test_invalid_timestamp_from_env() {
+    let test_env = TestEnvironment::default();
+    for name in ["JJ_TIMESTAMP", "JJ_OP_TIMESTAMP"] {
+        let assert = test_env
+            .jj_cmd(test_env.env_root(), &["log"])
+            .env(name, "yesterday")
+            .assert()
+            .code(1);
+        insta::allow_duplicates! {
+            insta::assert_snapshot!(
+                get_stderr_string(&assert).replace(name, "NAME"),
+                @r###"
+            Config error: $NAME is not a valid RFC 3339 timestamp: "yesterday"
+            For help, see https://github.com/martinvonz/jj/blob/main/docs/config.md.
+            "###
+            );
+        }
+    }
+}
@@ -0,0 +1,240 @@
+// Copyright 2024 The Jujutsu Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::path::PathBuf;
+
+use crate::common::TestEnvironment;
+
+fn init_repo(test_env: &TestEnvironment) -> PathBuf {
+    test_env.jj_cmd_ok(test_env.env_root(), &["init", "repo", "--git"]);
+    let repo_path = test_env.env_root().join("repo");
+    std::fs::write(repo_path.join("file"), "a\nb\nc\nd\n").unwrap();
+    test_env.jj_cmd_ok(&repo_path, &["commit", "-m", "base"]);
+    test_env.add_config(r#"templates.commit_summary = 'description.first_line()'"#);
+    std::fs::create_dir(test_env.env_root().join("patches")).unwrap();
+    repo_path
+}
+
+fn write_patch(test_env: &TestEnvironment, name: &str, contents: &str) {
+    std::fs::write(test_env.env_root().join("patches").join(name), contents).unwrap();
+}
+
+fn get_descriptions(test_env: &TestEnvironment, repo_path: &std::path::Path) -> String {
+    test_env.jj_cmd_success(
+        repo_path,
+        &[
+            "log",
+            "--no-graph",
+            "-r",
+            "@+::",
+            "-T",
+            r#"description.first_line() ++ "\n""#,
+        ],
+    )
+}
+
+#[test]
+fn test_import_patches_series() {
+    let test_env = TestEnvironment::default();
+    let repo_path = init_repo(&test_env);
+    write_patch(
+        &test_env,
+        "modify.patch",
+        "\
+From 1111111111111111111111111111111111111111 Mon Sep 17 00:00:00 2001
+From: Test User <test.user@example.com>
+Subject: [PATCH 1/3] Modify b
+
+Change the second line.
+---
+ file | 2 +-
+ 1 file changed, 1 insertion(+), 1 deletion(-)
+
+diff --git a/file b/file
+--- a/file
++++ b/file
+@@ -1,3 +1,3 @@
+ a
+-b
++B
+ c
+",
+    );
+    write_patch(
+        &test_env,
+        "add.patch",
+        "\
+Add a file
+
+Index: repo/new
+===================================================================
+--- /dev/null
++++ repo/new
+@@ -0,0 +1 @@
++new
+",
+    );
+    write_patch(
+        &test_env,
+        "conflict.patch",
+        "\
+Change a line that isn't there
+
+--- a/file
++++ b/file
+@@ -2,3 +2,3 @@
+ B
+-x
++X
+ d
+",
+    );
+    // The series isn't in the order of the names
+    std::fs::write(
+        test_env.env_root().join("series"),
+        "# The patches\nmodify.patch\nadd.patch -p1\n\nconflict.patch\n",
+    )
+    .unwrap();
+
+    let stderr = test_env.jj_cmd_failure(
+        &repo_path,
+        &["import-patches", "../patches", "--series", "../series"],
+    );
+    insta::assert_snapshot!(stderr.replace('\\', "/"), @r###"
+    Applied modify.patch: Modify b
+    Applied add.patch: Add a file
+    Wrote 1 rejected hunks to ../patches/rejects/file.rej
+    Error: Failed to apply patch conflict.patch
+    Caused by: 1 hunks failed to apply
+    "###);
+
+    // The patches before the failing one were imported
+    insta::assert_snapshot!(get_descriptions(&test_env, &repo_path), @r###"
+    Add a file
+    Modify b
+    "###);
+    let stdout = test_env.jj_cmd_success(
+        &repo_path,
+        &["log", "--no-graph", "-r", "@+", "-T", "description"],
+    );
+    insta::assert_snapshot!(stdout, @r###"
+    Modify b
+
+    Change the second line.
+    "###);
+    let stdout = test_env.jj_cmd_success(
+        &repo_path,
+        &["cat", "-r", "description(\"Add a file\")", "file"],
+    );
+    insta::assert_snapshot!(stdout, @r###"
+    a
+    B
+    c
+    d
+    "###);
+    let stdout = test_env.jj_cmd_success(
+        &repo_path,
+        &["cat", "-r", "description(\"Add a file\")", "new"],
+    );
+    insta::assert_snapshot!(stdout, @r###"
+    new
+    "###);
+    let stdout = test_env.jj_cmd_success(&repo_path, &["op", "log", "--limit=1", "-Tdescription"]);
+    insta::assert_snapshot!(stdout, @r###"
+    @  import 2 of 3 patches from ../patches
+    "###);
+
+    // The reject file has the hunk that didn't apply
+    let rejects =
+        std::fs::read_to_string(test_env.env_root().join("patches/rejects/file.rej")).unwrap();
+    insta::assert_snapshot!(rejects, @r###"
+    --- a/file
+    +++ b/file
+    @@ -2,3 +2,3 @@
+     B
+    -x
+    +X
+     d
+    "###);
+}
+
+#[test]
+fn test_import_patches_sorted_with_fuzz() {
+    let test_env = TestEnvironment::default();
+    let repo_path = init_repo(&test_env);
+    write_patch(
+        &test_env,
+        "1.diff",
+        "\
+First
+
+--- a/file
++++ b/file
+@@ -1,2 +1,2 @@
+-a
++A
+ b
+",
+    );
+    // The context line `x` doesn't match, but can be ignored with fuzz
+    write_patch(
+        &test_env,
+        "2.patch",
+        "\
+Second
+
+--- a/file
++++ b/file
+@@ -2,3 +2,3 @@
+ b
+-c
++C
+ x
+",
+    );
+    write_patch(&test_env, "notes.txt", "Not a patch\n");
+
+    let stderr = test_env.jj_cmd_failure(&repo_path, &["import-patches", "../patches"]);
+    insta::assert_snapshot!(stderr.replace('\\', "/"), @r###"
+    Applied 1.diff: First
+    Wrote 1 rejected hunks to ../patches/rejects/file.rej
+    Error: Failed to apply patch 2.patch
+    Caused by: 1 hunks failed to apply
+    "###);
+
+    test_env.jj_cmd_ok(&repo_path, &["abandon", "@+"]);
+    let (_stdout, stderr) =
+        test_env.jj_cmd_ok(&repo_path, &["import-patches", "../patches", "--fuzz", "1"]);
+    insta::assert_snapshot!(stderr, @r###"
+    Applied 1.diff: First
+    Applied 2.patch: Second
+    "###);
+    insta::assert_snapshot!(get_descriptions(&test_env, &repo_path), @r###"
+    Second
+    First
+    "###);
+    let stdout = test_env.jj_cmd_success(&repo_path, &["cat", "-r", "description(Second)", "file"]);
+    insta::assert_snapshot!(stdout, @r###"
+    A
+    b
+    C
+    d
+    "###);
+
+    // Nothing to import
+    let stderr = test_env.jj_cmd_failure(&repo_path, &["import-patches", "../repo"]);
+    insta::assert_snapshot!(stderr, @r###"
+    Error: No patches found in ../repo
+    "###);
+}
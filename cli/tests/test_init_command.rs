@@ -72,8 +72,8 @@ fn test_init_git_internal() {
     let (stdout, stderr) = test_env.jj_cmd_ok(test_env.env_root(), &["init", "repo", "--git"]);
     insta::assert_snapshot!(stdout, @"");
     insta::assert_snapshot!(stderr, @r###"
-    warning: `--git` and `--git-repo` are deprecated.
-    Use `jj git init` instead
+    Warning: `--git` and `--git-repo` are deprecated.
+    Hint: Use `jj git init` instead
     Initialized repo in "repo"
     "###);
 
@@ -113,8 +113,8 @@ fn test_init_git_external(bare: bool) {
         Working copy now at: sqpuoqvx f6950fc1 (empty) (no description set)
         Parent commit      : mwrttmos 8d698d4a my-branch | My commit message
         Added 1 files, modified 0 files, removed 0 files
-        warning: `--git` and `--git-repo` are deprecated.
-        Use `jj git init` instead
+        Warning: `--git` and `--git-repo` are deprecated.
+        Hint: Use `jj git init` instead
         Initialized repo in "repo"
         "###);
     }
@@ -185,8 +185,8 @@ fn test_init_git_colocated() {
     insta::assert_snapshot!(stdout, @"");
     insta::assert_snapshot!(stderr, @r###"
     Done importing changes from the underlying Git repo.
-    warning: `--git` and `--git-repo` are deprecated.
-    Use `jj git init` instead
+    Warning: `--git` and `--git-repo` are deprecated.
+    Hint: Use `jj git init` instead
     Initialized repo in "."
     "###);
 
@@ -235,8 +235,8 @@ fn test_init_git_colocated_gitlink() {
     insta::assert_snapshot!(stdout, @"");
     insta::assert_snapshot!(stderr, @r###"
     Done importing changes from the underlying Git repo.
-    warning: `--git` and `--git-repo` are deprecated.
-    Use `jj git init` instead
+    Warning: `--git` and `--git-repo` are deprecated.
+    Hint: Use `jj git init` instead
     Initialized repo in "."
     "###);
     insta::assert_snapshot!(read_git_target(&workspace_root), @"../../../.git");
@@ -273,8 +273,8 @@ fn test_init_git_colocated_symlink_directory() {
     insta::assert_snapshot!(stdout, @"");
     insta::assert_snapshot!(stderr, @r###"
     Done importing changes from the underlying Git repo.
-    warning: `--git` and `--git-repo` are deprecated.
-    Use `jj git init` instead
+    Warning: `--git` and `--git-repo` are deprecated.
+    Hint: Use `jj git init` instead
     Initialized repo in "."
     "###);
     insta::assert_snapshot!(read_git_target(&workspace_root), @"../../../.git");
@@ -314,8 +314,8 @@ fn test_init_git_colocated_symlink_directory_without_bare_config() {
     insta::assert_snapshot!(stdout, @"");
     insta::assert_snapshot!(stderr, @r###"
     Done importing changes from the underlying Git repo.
-    warning: `--git` and `--git-repo` are deprecated.
-    Use `jj git init` instead
+    Warning: `--git` and `--git-repo` are deprecated.
+    Hint: Use `jj git init` instead
     Initialized repo in "."
     "###);
     insta::assert_snapshot!(read_git_target(&workspace_root), @"../../../.git");
@@ -357,8 +357,8 @@ fn test_init_git_colocated_symlink_gitlink() {
     insta::assert_snapshot!(stdout, @"");
     insta::assert_snapshot!(stderr, @r###"
     Done importing changes from the underlying Git repo.
-    warning: `--git` and `--git-repo` are deprecated.
-    Use `jj git init` instead
+    Warning: `--git` and `--git-repo` are deprecated.
+    Hint: Use `jj git init` instead
     Initialized repo in "."
     "###);
     insta::assert_snapshot!(read_git_target(&workspace_root), @"../../../.git");
@@ -419,8 +419,8 @@ fn test_init_git_colocated_imported_refs() {
     let (_stdout, stderr) = test_env.jj_cmd_ok(&local_path, &["init", "--git-repo=."]);
     insta::assert_snapshot!(stderr, @r###"
     Done importing changes from the underlying Git repo.
-    warning: `--git` and `--git-repo` are deprecated.
-    Use `jj git init` instead
+    Warning: `--git` and `--git-repo` are deprecated.
+    Hint: Use `jj git init` instead
     Initialized repo in "."
     "###);
     insta::assert_snapshot!(get_branch_output(&test_env, &local_path), @r###"
@@ -442,8 +442,8 @@ fn test_init_git_colocated_imported_refs() {
     The following remote branches aren't associated with the existing local branches:
       local-remote@origin
     Hint: Run `jj branch track local-remote@origin` to keep local branches updated on future pulls.
-    warning: `--git` and `--git-repo` are deprecated.
-    Use `jj git init` instead
+    Warning: `--git` and `--git-repo` are deprecated.
+    Hint: Use `jj git init` instead
     Initialized repo in "."
     "###);
     insta::assert_snapshot!(get_branch_output(&test_env, &local_path), @r###"
@@ -467,8 +467,8 @@ fn test_init_git_external_but_git_dir_exists() {
     );
     insta::assert_snapshot!(stdout, @"");
     insta::assert_snapshot!(stderr, @r###"
-    warning: `--git` and `--git-repo` are deprecated.
-    Use `jj git init` instead
+    Warning: `--git` and `--git-repo` are deprecated.
+    Hint: Use `jj git init` instead
     Initialized repo in "."
     "###);
 
@@ -544,4 +544,6 @@ fn test_init_local() {
     assert!(store_path.join("files").is_dir());
     assert!(store_path.join("symlinks").is_dir());
     assert!(store_path.join("conflicts").is_dir());
+    assert!(store_path.join("chunked-files").is_dir());
+    assert!(store_path.join("chunks").is_dir());
 }
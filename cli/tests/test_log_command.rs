@@ -41,7 +41,7 @@ fn test_log_legacy_range_operator() {
     ~
     "###);
     insta::assert_snapshot!(stderr, @r###"
-    The `:` revset operator is deprecated. Please switch to `::`.
+    Warning: The `:` revset operator is deprecated. Please switch to `::`.
     "###);
     let (stdout, stderr) = test_env.jj_cmd_ok(&repo_path, &["log", "-r=:@"]);
     insta::assert_snapshot!(stdout, @r###"
@@ -50,7 +50,7 @@ fn test_log_legacy_range_operator() {
     ◉  zzzzzzzz root() 00000000
     "###);
     insta::assert_snapshot!(stderr, @r###"
-    The `:` revset operator is deprecated. Please switch to `::`.
+    Warning: The `:` revset operator is deprecated. Please switch to `::`.
     "###);
     let (stdout, stderr) = test_env.jj_cmd_ok(&repo_path, &["log", "-r=root():@"]);
     insta::assert_snapshot!(stdout, @r###"
@@ -59,7 +59,7 @@ fn test_log_legacy_range_operator() {
     ◉  zzzzzzzz root() 00000000
     "###);
     insta::assert_snapshot!(stderr, @r###"
-    The `:` revset operator is deprecated. Please switch to `::`.
+    Warning: The `:` revset operator is deprecated. Please switch to `::`.
     "###);
     let (stdout, stderr) = test_env.jj_cmd_ok(
         &repo_path,
@@ -71,7 +71,7 @@ fn test_log_legacy_range_operator() {
     ~
     "###);
     insta::assert_snapshot!(stderr, @r###"
-    The `:` revset operator is deprecated. Please switch to `::`.
+    Warning: The `:` revset operator is deprecated. Please switch to `::`.
     "###);
 }
 
@@ -743,6 +743,7 @@ fn test_log_divergence() {
     let test_env = TestEnvironment::default();
     test_env.jj_cmd_ok(test_env.env_root(), &["init", "repo", "--git"]);
     let repo_path = test_env.env_root().join("repo");
+    test_env.add_config("operation.allow-at-op-mutations = true");
     let template = r#"description.first_line() ++ if(divergent, " !divergence!")"#;
 
     std::fs::write(repo_path.join("file"), "foo\n").unwrap();
@@ -797,6 +798,53 @@ fn test_log_reversed() {
     "###);
 }
 
+#[test]
+fn test_log_sort_by() {
+    let test_env = TestEnvironment::default();
+    test_env.jj_cmd_ok(test_env.env_root(), &["init", "repo", "--git"]);
+    let repo_path = test_env.env_root().join("repo");
+
+    test_env.jj_cmd_ok(&repo_path, &["new", "root()", "-m", "first"]);
+    test_env.jj_cmd_ok(&repo_path, &["new", "root()", "-m", "second"]);
+    // Rewriting "first" updates its committer timestamp but not its author
+    // timestamp
+    test_env.jj_cmd_ok(
+        &repo_path,
+        &[
+            "describe",
+            "-r",
+            "description(first)",
+            "-m",
+            "first (edited)",
+        ],
+    );
+
+    let log = |args: &[&str]| {
+        let mut all_args = vec!["log", "-r", "~root()", "-T", "description", "--no-graph"];
+        all_args.extend_from_slice(args);
+        test_env.jj_cmd_success(&repo_path, &all_args)
+    };
+    insta::assert_snapshot!(log(&["--sort-by", "committer"]), @r###"
+    first (edited)
+    second
+    "###);
+    insta::assert_snapshot!(log(&["--sort-by", "author"]), @r###"
+    second
+    first (edited)
+    "###);
+    insta::assert_snapshot!(log(&["--sort-by", "author", "--reversed"]), @r###"
+    first (edited)
+    second
+    "###);
+    insta::assert_snapshot!(log(&["--sort-by", "author", "--limit", "1"]), @r###"
+    second
+    "###);
+
+    // The graph is always in topological order
+    let stderr = test_env.jj_cmd_cli_error(&repo_path, &["log", "--sort-by", "author"]);
+    assert!(stderr.contains("--no-graph"), "{stderr}");
+}
+
 #[test]
 fn test_log_filtered_by_path() {
     let test_env = TestEnvironment::default();
@@ -968,7 +1016,7 @@ fn test_log_warn_path_might_be_revset() {
     ~
     "###);
     insta::assert_snapshot!(stderr, @r###"
-    warning: The argument "." is being interpreted as a path, but this is often not useful because all non-empty commits touch '.'.  If you meant to show the working copy commit, pass -r '@' instead.
+    Warning: The argument "." is being interpreted as a path, but this is often not useful because all non-empty commits touch '.'.  If you meant to show the working copy commit, pass -r '@' instead.
     "###);
 
     // ...but checking `jj log .` makes sense in a subdirectory.
@@ -982,14 +1030,14 @@ fn test_log_warn_path_might_be_revset() {
     let (stdout, stderr) = test_env.jj_cmd_ok(&repo_path, &["log", "@", "-T", "description"]);
     insta::assert_snapshot!(stdout, @"");
     insta::assert_snapshot!(stderr, @r###"
-    warning: The argument "@" is being interpreted as a path. To specify a revset, pass -r "@" instead.
+    Warning: The argument "@" is being interpreted as a path. To specify a revset, pass -r "@" instead.
     "###);
 
     // Warn when there's no path with the provided name.
     let (stdout, stderr) = test_env.jj_cmd_ok(&repo_path, &["log", "file2", "-T", "description"]);
     insta::assert_snapshot!(stdout, @"");
     insta::assert_snapshot!(stderr, @r###"
-    warning: The argument "file2" is being interpreted as a path. To specify a revset, pass -r "file2" instead.
+    Warning: The argument "file2" is being interpreted as a path. To specify a revset, pass -r "file2" instead.
     "###);
 
     // If an explicit revision is provided, then suppress the warning.
@@ -1023,6 +1071,70 @@ fn test_default_revset() {
     );
 }
 
+#[test]
+fn test_log_skips_snapshot_without_working_copy() {
+    let test_env = TestEnvironment::default();
+    test_env.jj_cmd_ok(test_env.env_root(), &["init", "repo", "--git"]);
+    let repo_path = test_env.env_root().join("repo");
+    std::fs::write(repo_path.join("file1"), "foo\n").unwrap();
+    test_env.jj_cmd_ok(&repo_path, &["commit", "-m=first"]);
+    test_env.jj_cmd_ok(&repo_path, &["branch", "create", "-r=@-", "main"]);
+    // Shows the changes recorded in the working-copy commit without
+    // snapshotting
+    let recorded_changes = || {
+        test_env.jj_cmd_success(
+            &repo_path,
+            &["diff", "--ignore-working-copy", "--name-only"],
+        )
+    };
+
+    // Revsets only referring to branches don't need the working copy
+    std::fs::write(repo_path.join("file2"), "foo\n").unwrap();
+    let stdout = test_env.jj_cmd_success(
+        &repo_path,
+        &["log", "--no-graph", "-r=branches()", "-T=description"],
+    );
+    insta::assert_snapshot!(stdout, @r###"
+    first
+    "###);
+    insta::assert_snapshot!(recorded_changes(), @"");
+
+    // The working copy is snapshotted if the revset may include it
+    let stdout = test_env.jj_cmd_success(
+        &repo_path,
+        &["log", "--no-graph", "-r=@ | branches()", "-T=description"],
+    );
+    insta::assert_snapshot!(stdout, @r###"
+    first
+    "###);
+    insta::assert_snapshot!(recorded_changes(), @r###"
+    file2
+    "###);
+
+    // The working copy is snapshotted if a branch would be moved by the
+    // snapshot
+    test_env.jj_cmd_ok(&repo_path, &["branch", "create", "-r=@", "wip"]);
+    std::fs::write(repo_path.join("file3"), "foo\n").unwrap();
+    test_env.jj_cmd_ok(
+        &repo_path,
+        &["log", "--no-graph", "-r=branches()", "-T=description"],
+    );
+    insta::assert_snapshot!(recorded_changes(), @r###"
+    file2
+    file3
+    "###);
+    test_env.jj_cmd_ok(&repo_path, &["branch", "delete", "wip"]);
+
+    // The default revset includes the working copy
+    std::fs::write(repo_path.join("file4"), "foo\n").unwrap();
+    test_env.jj_cmd_ok(&repo_path, &["log"]);
+    insta::assert_snapshot!(recorded_changes(), @r###"
+    file2
+    file3
+    file4
+    "###);
+}
+
 #[test]
 fn test_default_revset_per_repo() {
     let test_env = TestEnvironment::default();
@@ -121,14 +121,14 @@ fn test_new_merge() {
     // `jj merge` with less than two arguments is an error
     let stderr = test_env.jj_cmd_cli_error(&repo_path, &["merge"]);
     insta::assert_snapshot!(stderr, @r###"
-    warning: `jj merge` is deprecated; use `jj new` instead, which is equivalent
-    warning: `jj merge` will be removed in a future version, and this will be a hard error
+    Warning: `jj merge` is deprecated, and will be removed in a future version
+    Hint: Use `jj new` instead, which is equivalent
     Error: Merge requires at least two revisions
     "###);
     let stderr = test_env.jj_cmd_cli_error(&repo_path, &["merge", "main"]);
     insta::assert_snapshot!(stderr, @r###"
-    warning: `jj merge` is deprecated; use `jj new` instead, which is equivalent
-    warning: `jj merge` will be removed in a future version, and this will be a hard error
+    Warning: `jj merge` is deprecated, and will be removed in a future version
+    Hint: Use `jj new` instead, which is equivalent
     Error: Merge requires at least two revisions
     "###);
 
@@ -471,6 +471,7 @@ fn test_new_conflicting_branches() {
     let test_env = TestEnvironment::default();
     test_env.jj_cmd_ok(test_env.env_root(), &["init", "repo", "--git"]);
     let repo_path = test_env.env_root().join("repo");
+    test_env.add_config("operation.allow-at-op-mutations = true");
 
     test_env.jj_cmd_ok(&repo_path, &["describe", "-m", "one"]);
     test_env.jj_cmd_ok(&repo_path, &["new", "-m", "two", "@-"]);
@@ -507,6 +508,7 @@ fn test_new_conflicting_change_ids() {
     let test_env = TestEnvironment::default();
     test_env.jj_cmd_ok(test_env.env_root(), &["init", "repo", "--git"]);
     let repo_path = test_env.env_root().join("repo");
+    test_env.add_config("operation.allow-at-op-mutations = true");
 
     test_env.jj_cmd_ok(&repo_path, &["describe", "-m", "one"]);
     test_env.jj_cmd_ok(&repo_path, &["--at-op=@-", "describe", "-m", "two"]);
@@ -550,3 +552,46 @@ fn get_short_log_output(test_env: &TestEnvironment, repo_path: &Path) -> String
     let template = r#"if(description, description, "root")"#;
     test_env.jj_cmd_success(repo_path, &["log", "-T", template])
 }
+
+#[test]
+fn test_new_abandon_on_checkout() {
+    let test_env = TestEnvironment::default();
+    test_env.jj_cmd_ok(test_env.env_root(), &["init", "repo", "--git"]);
+    let repo_path = test_env.env_root().join("repo");
+
+    // By default, an empty commit with a description is kept
+    test_env.jj_cmd_ok(&repo_path, &["describe", "-m", "first"]);
+    let (_stdout, stderr) = test_env.jj_cmd_ok(&repo_path, &["new", "root()"]);
+    assert!(!stderr.contains("Abandoned"));
+
+    // With "empty", it's abandoned and reported
+    test_env.add_config(r#"working-copy.abandon-on-checkout = "empty""#);
+    test_env.jj_cmd_ok(&repo_path, &["describe", "-m", "second"]);
+    let (_stdout, stderr) = test_env.jj_cmd_ok(&repo_path, &["new", "root()"]);
+    insta::assert_snapshot!(stderr.lines().next().unwrap(), @"Abandoned empty working-copy commit rlvkpnrzqnoo");
+    // Undescribed commits are reported too
+    let change_id =
+        test_env.jj_cmd_success(&repo_path, &["log", "--no-graph", "-r@", "-Tchange_id"]);
+    let (_stdout, stderr) = test_env.jj_cmd_ok(&repo_path, &["new", "root()"]);
+    assert!(
+        stderr.contains(&format!(
+            "Abandoned empty working-copy commit {}",
+            &change_id[..12]
+        )),
+        "{stderr}"
+    );
+
+    // A local branch keeps it regardless of the policy
+    test_env.jj_cmd_ok(&repo_path, &["describe", "-m", "third"]);
+    test_env.jj_cmd_ok(&repo_path, &["branch", "create", "keep"]);
+    let (_stdout, stderr) = test_env.jj_cmd_ok(&repo_path, &["new", "root()"]);
+    assert!(!stderr.contains("Abandoned"));
+
+    // An unknown policy is rejected
+    test_env.add_config(r#"working-copy.abandon-on-checkout = "always""#);
+    let stderr = test_env.jj_cmd_failure(&repo_path, &["new"]);
+    insta::assert_snapshot!(stderr, @r###"
+    Config error: unknown abandon-on-checkout policy: always
+    For help, see https://github.com/martinvonz/jj/blob/main/docs/config.md.
+    "###);
+}
@@ -132,6 +132,27 @@ fn test_next_fails_on_merge_commit() {
     "###);
 }
 
+#[test]
+fn test_next_fails_on_branching_children() {
+    let test_env = TestEnvironment::default();
+    test_env.jj_cmd_ok(test_env.env_root(), &["init", "repo", "--git"]);
+    let repo_path = test_env.env_root().join("repo");
+    test_env.jj_cmd_ok(&repo_path, &["commit", "-m", "first"]);
+    test_env.jj_cmd_ok(&repo_path, &["commit", "-m", "second"]);
+    test_env.jj_cmd_ok(&repo_path, &["co", "@--"]);
+    test_env.jj_cmd_ok(&repo_path, &["commit", "-m", "third"]);
+    test_env.jj_cmd_ok(&repo_path, &["co", "@--"]);
+
+    // The candidates are listed without prompting
+    let stderr = test_env.jj_cmd_failure(&repo_path, &["next"]);
+    insta::assert_snapshot!(stderr,@r###"
+    Error: The next commit is ambiguous, it could be any of:
+      zsuskuln 40a959a0 (empty) third
+      rlvkpnrz 5c52832c (empty) second
+    Hint: Use `jj next --prompt` to choose one, or `jj new` with the change id of one of them.
+    "###);
+}
+
 #[test]
 fn test_next_fails_on_branching_children_no_stdin() {
     let test_env = TestEnvironment::default();
@@ -144,7 +165,10 @@ fn test_next_fails_on_branching_children_no_stdin() {
     test_env.jj_cmd_ok(&repo_path, &["co", "@--"]);
 
     // Try to advance the working copy commit.
-    let assert = test_env.jj_cmd(&repo_path, &["next"]).assert().code(1);
+    let assert = test_env
+        .jj_cmd(&repo_path, &["next", "--prompt"])
+        .assert()
+        .code(1);
     let stderr = test_env.normalize_output(&get_stderr_string(&assert));
     insta::assert_snapshot!(stderr,@r###"
     Error: Cannot prompt for input since the output is not connected to a terminal
@@ -164,7 +188,7 @@ fn test_next_fails_on_branching_children_quit_prompt() {
 
     // Try to advance the working copy commit.
     let assert = test_env
-        .jj_cmd_stdin(&repo_path, &["next"], "q\n")
+        .jj_cmd_stdin(&repo_path, &["next", "--prompt"], "q\n")
         .assert()
         .code(1);
     let stdout = test_env.normalize_output(&get_stdout_string(&assert));
@@ -192,7 +216,7 @@ fn test_next_choose_branching_child() {
     test_env.jj_cmd_ok(&repo_path, &["commit", "-m", "third"]);
     test_env.jj_cmd_ok(&repo_path, &["co", "@--"]);
     // Advance the working copy commit.
-    let (stdout, stderr) = test_env.jj_cmd_stdin_ok(&repo_path, &["next"], "1\n");
+    let (stdout, stderr) = test_env.jj_cmd_stdin_ok(&repo_path, &["next", "--prompt"], "1\n");
     insta::assert_snapshot!(stdout,@r###"
     ambiguous next commit, choose one to target:
     1: zsuskuln 40a959a0 (empty) third
@@ -237,8 +261,16 @@ fn test_prev_fails_on_multiple_parents() {
     // Create a merge commit, which has two parents.
     test_env.jj_cmd_ok(&repo_path, &["new", "left", "right"]);
     test_env.jj_cmd_ok(&repo_path, &["commit", "-m", "merge"]);
+    // The candidates are listed without prompting
+    let stderr = test_env.jj_cmd_failure(&repo_path, &["prev"]);
+    insta::assert_snapshot!(stderr,@r###"
+    Error: The prev commit is ambiguous, it could be any of:
+      zsuskuln edad76e9 right | (empty) second
+      qpvuntsm 5ae1a6a5 left | (empty) first
+    Hint: Use `jj prev --prompt` to choose one, or `jj new` with the change id of one of them.
+    "###);
     // Advance the working copy commit.
-    let (stdout, stderr) = test_env.jj_cmd_stdin_ok(&repo_path, &["prev"], "2\n");
+    let (stdout, stderr) = test_env.jj_cmd_stdin_ok(&repo_path, &["prev", "--prompt"], "2\n");
     insta::assert_snapshot!(stdout,@r###"
     ambiguous prev commit, choose one to target:
     1: zsuskuln edad76e9 right | (empty) second
@@ -304,3 +336,52 @@ fn test_next_editing() {
     Parent commit      : kkmpptxz 3fa8931e (empty) third
     "###);
 }
+
+#[test]
+fn test_next_editing_fails_on_branching_children() {
+    let test_env = TestEnvironment::default();
+    test_env.jj_cmd_ok(test_env.env_root(), &["init", "repo", "--git"]);
+    let repo_path = test_env.env_root().join("repo");
+    test_env.jj_cmd_ok(&repo_path, &["commit", "-m", "first"]);
+    test_env.jj_cmd_ok(&repo_path, &["commit", "-m", "second"]);
+    test_env.jj_cmd_ok(&repo_path, &["co", "@--"]);
+    test_env.jj_cmd_ok(&repo_path, &["commit", "-m", "third"]);
+    test_env.jj_cmd_ok(&repo_path, &["edit", "description(first)"]);
+
+    let stderr = test_env.jj_cmd_failure(&repo_path, &["next", "--edit"]);
+    insta::assert_snapshot!(stderr,@r###"
+    Error: The next commit is ambiguous, it could be any of:
+      zsuskuln 40a959a0 (empty) third
+      rlvkpnrz 5c52832c (empty) second
+    Hint: Use `jj next --prompt` to choose one, or `jj edit` with the change id of one of them.
+    "###);
+}
+
+#[test]
+fn test_next_editing_at_head_fails() {
+    let test_env = TestEnvironment::default();
+    test_env.jj_cmd_ok(test_env.env_root(), &["init", "repo", "--git"]);
+    let repo_path = test_env.env_root().join("repo");
+    test_env.jj_cmd_ok(&repo_path, &["commit", "-m", "first"]);
+    test_env.jj_cmd_ok(&repo_path, &["edit", "description(first)"]);
+
+    // The working-copy commit isn't a child of "first" anymore since it was
+    // abandoned, so there's nothing to move to.
+    let stderr = test_env.jj_cmd_failure(&repo_path, &["next", "--edit"]);
+    insta::assert_snapshot!(stderr,@r###"
+    Error: No descendant found 1 commit forward
+    "###);
+}
+
+#[test]
+fn test_prev_editing_onto_root_fails() {
+    let test_env = TestEnvironment::default();
+    test_env.jj_cmd_ok(test_env.env_root(), &["init", "repo", "--git"]);
+    let repo_path = test_env.env_root().join("repo");
+
+    // The root commit can't be edited
+    let stderr = test_env.jj_cmd_failure(&repo_path, &["prev", "--edit"]);
+    insta::assert_snapshot!(stderr,@r###"
+    Error: The root commit 000000000000 is immutable
+    "###);
+}
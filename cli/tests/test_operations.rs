@@ -17,7 +17,7 @@ use std::path::Path;
 use itertools::Itertools;
 use regex::Regex;
 
-use crate::common::{get_stdout_string, TestEnvironment};
+use crate::common::{get_stderr_string, get_stdout_string, TestEnvironment};
 
 #[test]
 fn test_op_log() {
@@ -93,6 +93,78 @@ fn test_op_log() {
     "###);
 }
 
+#[test]
+fn test_log_at_op_shows_deleted_branch() {
+    let test_env = TestEnvironment::default();
+    test_env.jj_cmd_ok(test_env.env_root(), &["init", "repo", "--git"]);
+    let repo_path = test_env.env_root().join("repo");
+    test_env.jj_cmd_ok(&repo_path, &["branch", "create", "foo"]);
+    test_env.jj_cmd_ok(&repo_path, &["branch", "delete", "foo"]);
+    let get_branches = |op: &str| {
+        test_env.jj_cmd_success(
+            &repo_path,
+            &[
+                "log",
+                "--no-graph",
+                "-r",
+                "branches()",
+                "-T",
+                r#"branches ++ "\n""#,
+                "--at-op",
+                op,
+            ],
+        )
+    };
+
+    // The branch is gone at the head operation, but still visible when loading
+    // the repo at the operation before it was deleted.
+    insta::assert_snapshot!(get_branches("@"), @"");
+    insta::assert_snapshot!(get_branches("@-"), @r###"
+    foo
+    "###);
+}
+
+#[test]
+fn test_mutation_at_op() {
+    let test_env = TestEnvironment::default();
+    test_env.jj_cmd_ok(test_env.env_root(), &["init", "repo", "--git"]);
+    let repo_path = test_env.env_root().join("repo");
+    test_env.jj_cmd_ok(&repo_path, &["describe", "-m", "first"]);
+
+    // Mutations at an old operation are refused
+    let stderr =
+        test_env.jj_cmd_failure(&repo_path, &["describe", "-m", "second", "--at-op", "@-"]);
+    assert!(
+        stderr.starts_with("Error: Cannot modify the repo at operation "),
+        "{stderr}"
+    );
+    assert!(
+        stderr.contains("Hint: Run the command without --at-op"),
+        "{stderr}"
+    );
+
+    // The head operation can be named explicitly
+    let head_op_id =
+        test_env.jj_cmd_success(&repo_path, &["op", "log", "--no-graph", "-n1", "-T", "id"]);
+    test_env.jj_cmd_ok(
+        &repo_path,
+        &["describe", "-m", "second", "--at-op", &head_op_id],
+    );
+
+    // The operation commands are exempt
+    test_env.jj_cmd_ok(
+        &repo_path,
+        &["op", "restore", "--at-op", &head_op_id, &head_op_id],
+    );
+
+    // Concurrent operations can be allowed explicitly
+    test_env.add_config("operation.allow-at-op-mutations = true");
+    test_env.jj_cmd_ok(
+        &repo_path,
+        &["describe", "-m", "third", "--at-op", &head_op_id],
+    );
+}
+
 #[test]
 fn test_op_log_limit() {
     let test_env = TestEnvironment::default();
@@ -426,7 +498,7 @@ fn test_op_abandon_without_updating_working_copy() {
     let (_stdout, stderr) = test_env.jj_cmd_ok(&repo_path, &["op", "abandon", "@-"]);
     insta::assert_snapshot!(stderr, @r###"
     Abandoned 1 operations and reparented 1 descendant operations.
-    The working copy operation 0229bff5a524 is not updated because it differs from the repo 0173d6fbe6a2.
+    Warning: The working copy operation 0229bff5a524 is not updated because it differs from the repo 0173d6fbe6a2.
     "###);
     insta::assert_snapshot!(
         test_env.jj_cmd_success(&repo_path, &["debug", "workingcopy", "--ignore-working-copy"]), @r###"
@@ -441,9 +513,513 @@ fn test_op_abandon_without_updating_working_copy() {
     "###);
 }
 
+#[test]
+fn test_op_diff_branch_move() {
+    let test_env = TestEnvironment::default();
+    test_env.add_config(r#"templates.commit_summary = "description.first_line()""#);
+    test_env.jj_cmd_ok(test_env.env_root(), &["init", "repo", "--git"]);
+    let repo_path = test_env.env_root().join("repo");
+    test_env.jj_cmd_ok(&repo_path, &["describe", "-m=first"]);
+    test_env.jj_cmd_ok(&repo_path, &["branch", "create", "main"]);
+    test_env.jj_cmd_ok(&repo_path, &["new", "-m=second"]);
+    test_env.jj_cmd_ok(&repo_path, &["branch", "set", "main", "-r=@"]);
+
+    // By default, the changes made by the current operation are shown
+    insta::assert_snapshot!(get_op_diff_output(&test_env, &repo_path, &[]), @r###"
+    From operation <id>: new empty commit
+      To operation <id>: point branch main to commit <id>
+
+    Changed local branches:
+    main:
+      - first
+      + second
+    "###);
+
+    // Changes across multiple operations
+    insta::assert_snapshot!(
+        get_op_diff_output(&test_env, &repo_path, &["--from=@---", "--to=@"]), @r###"
+    From operation <id>: describe commit <id>
+      To operation <id>: point branch main to commit <id>
+
+    Changed working-copy commits:
+    default@:
+      - first
+      + second
+
+    Changed head commits:
+      - first
+      + second
+
+    Changed local branches:
+    main:
+      + second
+    "###);
+
+    // No changes between an operation and itself
+    insta::assert_snapshot!(
+        get_op_diff_output(&test_env, &repo_path, &["--from=@", "--to=@"]), @r###"
+    From operation <id>: point branch main to commit <id>
+      To operation <id>: point branch main to commit <id>
+
+    No changes.
+    "###);
+}
+
+#[test]
+fn test_op_diff_fetch() {
+    let test_env = TestEnvironment::default();
+    test_env.add_config(r#"templates.commit_summary = "description.first_line()""#);
+    test_env.add_config("git.auto-local-branch = false");
+    test_env.jj_cmd_ok(test_env.env_root(), &["init", "repo", "--git"]);
+    let repo_path = test_env.env_root().join("repo");
+
+    let git_repo = git2::Repository::init(test_env.env_root().join("remote")).unwrap();
+    let signature =
+        git2::Signature::new("Some One", "some.one@example.com", &git2::Time::new(0, 0)).unwrap();
+    let empty_tree_oid = git_repo.treebuilder(None).unwrap().write().unwrap();
+    let empty_tree = git_repo.find_tree(empty_tree_oid).unwrap();
+    git_repo
+        .commit(
+            Some("refs/heads/feature"),
+            &signature,
+            &signature,
+            "remote commit",
+            &empty_tree,
+            &[],
+        )
+        .unwrap();
+    test_env.jj_cmd_ok(&repo_path, &["git", "remote", "add", "origin", "../remote"]);
+    test_env.jj_cmd_ok(&repo_path, &["git", "fetch"]);
+
+    insta::assert_snapshot!(get_op_diff_output(&test_env, &repo_path, &[]), @r###"
+    From operation <id>: add workspace 'default'
+      To operation <id>: fetch from git remote(s) origin
+
+    Changed head commits:
+      + remote commit
+
+    Changed remote branches:
+    feature@origin:
+      + remote commit
+
+    Changed Git refs:
+    refs/remotes/origin/feature:
+      + remote commit
+    "###);
+}
+
+#[test]
+fn test_op_diff_undo() {
+    let test_env = TestEnvironment::default();
+    test_env.add_config(r#"templates.commit_summary = "description.first_line()""#);
+    test_env.jj_cmd_ok(test_env.env_root(), &["init", "repo", "--git"]);
+    let repo_path = test_env.env_root().join("repo");
+    test_env.jj_cmd_ok(&repo_path, &["describe", "-m=first"]);
+    test_env.jj_cmd_ok(&repo_path, &["branch", "create", "main"]);
+    test_env.jj_cmd_ok(&repo_path, &["new", "-m=second"]);
+    test_env.jj_cmd_ok(&repo_path, &["undo"]);
+
+    // The undo operation is the inverse of the undone one
+    insta::assert_snapshot!(get_op_diff_output(&test_env, &repo_path, &[]), @r###"
+    From operation <id>: new empty commit
+      To operation <id>: undo operation <id>
+
+    Changed working-copy commits:
+    default@:
+      - second
+      + first
+
+    Changed head commits:
+      - second
+      + first
+    "###);
+    insta::assert_snapshot!(get_op_diff_output(&test_env, &repo_path, &["--to=@-"]), @r###"
+    From operation <id>: create branch main pointing to commit <id>
+      To operation <id>: new empty commit
+
+    Changed working-copy commits:
+    default@:
+      - first
+      + second
+
+    Changed head commits:
+      - first
+      + second
+    "###);
+}
+
+fn get_op_diff_output(test_env: &TestEnvironment, repo_path: &Path, args: &[&str]) -> String {
+    let stdout = test_env.jj_cmd_success(repo_path, &[&["op", "diff"], args].concat());
+    // Operation ids and the commit ids in operation descriptions aren't stable
+    let id_re = Regex::new(r"[0-9a-f]{12,}").unwrap();
+    id_re.replace_all(&stdout, "<id>").into_owned()
+}
+
 fn get_log_output(test_env: &TestEnvironment, repo_path: &Path, op_id: &str) -> String {
     test_env.jj_cmd_success(
         repo_path,
         &["log", "-T", "commit_id", "--at-op", op_id, "-r", "all()"],
     )
 }
+
+#[test]
+fn test_op_log_tags() {
+    let test_env = TestEnvironment::default();
+    test_env.jj_cmd_ok(test_env.env_root(), &["init", "repo", "--git"]);
+    let repo_path = test_env.env_root().join("repo");
+    test_env.add_config("operation.command-tags = true");
+
+    test_env.jj_cmd_ok(&repo_path, &["new"]);
+    test_env.jj_cmd_ok(&repo_path, &["describe", "-m", "second"]);
+    test_env.jj_cmd_ok(&repo_path, &["new"]);
+
+    // Operations are tagged with the command name and a hash of the arguments
+    let stdout = test_env.jj_cmd_success(
+        &repo_path,
+        &["op", "log", "--no-graph", "-T", r#"tags ++ "\n""#, "--limit=2"],
+    );
+    insta::assert_snapshot!(stdout, @r###"
+    args: jj new
+    args-hash: 9e6aa893d863d4a5
+    command: new
+    args: jj describe -m second
+    args-hash: 730e71a1ca16f7d0
+    command: describe
+    "###);
+
+    let template = r#"description.first_line() ++ "\n""#;
+    let stdout = test_env.jj_cmd_success(
+        &repo_path,
+        &["op", "log", "--no-graph", "-T", template, "--tag", "command=new"],
+    );
+    insta::assert_snapshot!(stdout, @r###"
+    new empty commit
+    new empty commit
+    "###);
+
+    // All the tags must match
+    let stdout = test_env.jj_cmd_success(
+        &repo_path,
+        &[
+            "op",
+            "log",
+            "--no-graph",
+            "-T",
+            template,
+            "--tag",
+            "command=new",
+            "--tag",
+            "args-hash=730e71a1ca16f7d0",
+        ],
+    );
+    insta::assert_snapshot!(stdout, @"");
+
+    // Operations from before the tags were recorded don't match
+    let stdout = test_env.jj_cmd_success(
+        &repo_path,
+        &["op", "log", "--no-graph", "-T", template, "--tag", "command=init"],
+    );
+    insta::assert_snapshot!(stdout, @"");
+
+    let stderr = test_env.jj_cmd_cli_error(&repo_path, &["op", "log", "--tag", "command"]);
+    insta::assert_snapshot!(stderr, @r###"
+    error: invalid value 'command' for '--tag <KEY=VALUE>': Expected KEY=VALUE, got "command"
+
+    For more information, try '--help'.
+    "###);
+}
+
+fn get_op_descriptions(test_env: &TestEnvironment, repo_path: &Path) -> String {
+    // The root operation has no description
+    let template = r#"if(description, description.first_line() ++ "\n")"#;
+    test_env.jj_cmd_success(repo_path, &["op", "log", "--no-graph", "-T", template])
+}
+
+#[test]
+fn test_snapshot_noop_records_no_operation() {
+    let test_env = TestEnvironment::default();
+    test_env.jj_cmd_ok(test_env.env_root(), &["init", "repo", "--git"]);
+    let repo_path = test_env.env_root().join("repo");
+    std::fs::write(repo_path.join("file"), "contents\n").unwrap();
+    test_env.jj_cmd_ok(&repo_path, &["status"]);
+    let op_log = get_op_descriptions(&test_env, &repo_path);
+    insta::assert_snapshot!(op_log, @r###"
+    snapshot working copy
+    add workspace 'default'
+    initialize repo
+    "###);
+
+    // Commands that find nothing to snapshot don't record an operation, even
+    // if the file's modification time changed
+    for args in [&["status"][..], &["log"], &["diff"], &["show"], &["files"]] {
+        test_env.jj_cmd_ok(&repo_path, args);
+    }
+    std::fs::write(repo_path.join("file"), "contents\n").unwrap();
+    test_env.jj_cmd_ok(&repo_path, &["status"]);
+    assert_eq!(get_op_descriptions(&test_env, &repo_path), op_log);
+}
+
+#[test]
+fn test_snapshot_coalescing() {
+    let test_env = TestEnvironment::default();
+    test_env.jj_cmd_ok(test_env.env_root(), &["init", "repo", "--git"]);
+    let repo_path = test_env.env_root().join("repo");
+    test_env.add_config("snapshot.coalesce-window-secs = 60");
+
+    // Consecutive snapshots are coalesced into one operation
+    for contents in ["1\n", "2\n", "3\n"] {
+        std::fs::write(repo_path.join("file"), contents).unwrap();
+        test_env.jj_cmd_ok(&repo_path, &["status"]);
+    }
+    insta::assert_snapshot!(get_op_descriptions(&test_env, &repo_path), @r###"
+    snapshot working copy
+    add workspace 'default'
+    initialize repo
+    "###);
+    let stdout = test_env.jj_cmd_success(&repo_path, &["cat", "file"]);
+    insta::assert_snapshot!(stdout, @r###"
+    3
+    "###);
+
+    // Another operation in between starts a new snapshot operation
+    test_env.jj_cmd_ok(&repo_path, &["new"]);
+    for contents in ["4\n", "5\n"] {
+        std::fs::write(repo_path.join("file"), contents).unwrap();
+        test_env.jj_cmd_ok(&repo_path, &["status"]);
+    }
+    insta::assert_snapshot!(get_op_descriptions(&test_env, &repo_path), @r###"
+    snapshot working copy
+    new empty commit
+    snapshot working copy
+    add workspace 'default'
+    initialize repo
+    "###);
+
+    // Undoing the coalesced operation undoes all of its snapshots
+    test_env.jj_cmd_ok(&repo_path, &["undo"]);
+    let stdout = test_env.jj_cmd_success(&repo_path, &["cat", "file", "--ignore-working-copy"]);
+    insta::assert_snapshot!(stdout, @r###"
+    3
+    "###);
+
+    // Without a window, every snapshot is recorded
+    test_env.add_config("snapshot.coalesce-window-secs = 0");
+    test_env.jj_cmd_ok(&repo_path, &["new"]);
+    for contents in ["6\n", "7\n"] {
+        std::fs::write(repo_path.join("file"), contents).unwrap();
+        test_env.jj_cmd_ok(&repo_path, &["status"]);
+    }
+    let op_log = get_op_descriptions(&test_env, &repo_path);
+    assert!(op_log.starts_with("snapshot working copy\nsnapshot working copy\nnew empty commit\n"));
+}
+
+#[test]
+fn test_snapshot_not_coalesced_by_default() {
+    let test_env = TestEnvironment::default();
+    test_env.jj_cmd_ok(test_env.env_root(), &["init", "repo", "--git"]);
+    let repo_path = test_env.env_root().join("repo");
+
+    for contents in ["1\n", "2\n"] {
+        std::fs::write(repo_path.join("file"), contents).unwrap();
+        test_env.jj_cmd_ok(&repo_path, &["status"]);
+    }
+    insta::assert_snapshot!(get_op_descriptions(&test_env, &repo_path), @r###"
+    snapshot working copy
+    snapshot working copy
+    add workspace 'default'
+    initialize repo
+    "###);
+}
+
+#[test]
+fn test_op_clock_skew() {
+    let test_env = TestEnvironment::default();
+    test_env.jj_cmd_ok(test_env.env_root(), &["init", "repo", "--git"]);
+    let repo_path = test_env.env_root().join("repo");
+    test_env.add_config(r#"templates.commit_summary = 'description.first_line()'"#);
+    test_env.jj_cmd_ok(&repo_path, &["describe", "-m", "first"]);
+    let run_at = |args: &[&str], timestamp: &str| {
+        let assert = test_env
+            .jj_cmd(&repo_path, args)
+            .env("JJ_OP_TIMESTAMP", timestamp)
+            .assert()
+            .success();
+        get_stderr_string(&assert)
+    };
+
+    // A clock that is a few minutes behind isn't reported
+    let stderr = run_at(&["new", "-m", "second"], "2001-02-03T04:00:00+07:00");
+    insta::assert_snapshot!(stderr, @r###"
+    Working copy now at: second
+    Parent commit      : first
+    "###);
+
+    // A clock that is hours behind is probably wrong
+    let stderr = run_at(&["new", "-m", "third"], "2001-02-03T02:05:06+07:00");
+    insta::assert_snapshot!(stderr, @r###"
+    Warning: The clock is behind the previous operation. The time of this operation was moved forward from 2001-02-03 02:05:06.000 +07:00 to 2001-02-03 04:05:08.000 +07:00.
+    Hint: Check that the system clock is set correctly.
+    Working copy now at: third
+    Parent commit      : second
+    "###);
+
+    // The operations are recorded at the time of their parent, and the time
+    // from the clock is kept in a tag
+    let template = r#"description.first_line() ++ " " ++ time.end() ++ "\n" ++ tags ++ "\n""#;
+    let stdout = test_env.jj_cmd_success(
+        &repo_path,
+        &["op", "log", "--no-graph", "-T", template, "--limit=2"],
+    );
+    insta::assert_snapshot!(stdout, @r###"
+    new empty commit 2001-02-03 04:05:08.000 +07:00
+    args: jj new -m third
+    wall-clock-time: 2001-02-03T02:05:06+07:00
+    new empty commit 2001-02-03 04:05:08.000 +07:00
+    args: jj new -m second
+    wall-clock-time: 2001-02-03T04:00:00+07:00
+    "###);
+
+    // The threshold is configurable
+    test_env.add_config("timestamps.clock-skew-warning-secs = 60");
+    let stderr = run_at(&["new", "-m", "fourth"], "2001-02-03T04:00:00+07:00");
+    insta::assert_snapshot!(stderr, @r###"
+    Warning: The clock is behind the previous operation. The time of this operation was moved forward from 2001-02-03 04:00:00.000 +07:00 to 2001-02-03 04:05:08.000 +07:00.
+    Hint: Check that the system clock is set correctly.
+    Working copy now at: fourth
+    Parent commit      : third
+    "###);
+}
+
+#[test]
+fn test_op_restore_commit() {
+    let test_env = TestEnvironment::default();
+    test_env.jj_cmd_ok(test_env.env_root(), &["init", "repo", "--git"]);
+    let repo_path = test_env.env_root().join("repo");
+    test_env.add_config(r#"templates.commit_summary = 'description.first_line()'"#);
+    test_env.jj_cmd_ok(&repo_path, &["new", "-m", "lost"]);
+    test_env.jj_cmd_ok(&repo_path, &["new", "-m", "later"]);
+    let change_id = test_env.jj_cmd_success(
+        &repo_path,
+        &[
+            "log",
+            "--no-graph",
+            "-r",
+            "description(lost)",
+            "-T",
+            "change_id",
+        ],
+    );
+    test_env.jj_cmd_ok(&repo_path, &["abandon", "description(lost)"]);
+    test_env.jj_cmd_ok(&repo_path, &["describe", "-m", "later, edited"]);
+    let op_log_before = get_op_descriptions(&test_env, &repo_path);
+
+    let (stdout, stderr) = test_env.jj_cmd_ok(&repo_path, &["op", "restore-commit", &change_id]);
+    insta::assert_snapshot!(stdout, @"");
+    insta::assert_snapshot!(redact_op_hash(&stderr), @r###"
+    Restored commit lost
+    Found in operation [op]: new empty commit
+    "###);
+    let stdout = test_env.jj_cmd_success(
+        &repo_path,
+        &[
+            "log",
+            "-T",
+            r#"if(description, description.first_line(), "(no description)") ++ "\n""#,
+        ],
+    );
+    insta::assert_snapshot!(stdout, @r###"
+    @  later, edited
+    │ ◉  lost
+    ├─╯
+    ◉  (no description)
+    ◉  (no description)
+    "###);
+    // The recovery is its own operation, and nothing else was restored
+    let op_log_after = get_op_descriptions(&test_env, &repo_path);
+    let (restore_op, rest) = op_log_after.split_once('\n').unwrap();
+    assert!(restore_op.starts_with("restore commit "), "{restore_op}");
+    assert_eq!(rest, op_log_before);
+
+    let stderr = test_env.jj_cmd_failure(&repo_path, &["op", "restore-commit", &change_id]);
+    assert_eq!(
+        stderr,
+        format!("Error: The change with ID \"{change_id}\" is already visible\n")
+    );
+    let stderr = test_env.jj_cmd_failure(&repo_path, &["op", "restore-commit", "nonexistent"]);
+    insta::assert_snapshot!(stderr, @r###"
+    Error: No commit or change with ID "nonexistent"
+    "###);
+}
+
+#[test]
+fn test_op_restore_commit_divergent() {
+    let test_env = TestEnvironment::default();
+    test_env.jj_cmd_ok(test_env.env_root(), &["init", "repo", "--git"]);
+    let repo_path = test_env.env_root().join("repo");
+    test_env.add_config("operation.allow-at-op-mutations = true");
+    test_env.add_config(r#"templates.commit_summary = 'description.first_line()'"#);
+    test_env.jj_cmd_ok(&repo_path, &["new", "-m", "lost"]);
+    let change_id = test_env.jj_cmd_success(
+        &repo_path,
+        &["log", "--no-graph", "-r", "@", "-T", "change_id"],
+    );
+    test_env.jj_cmd_ok(&repo_path, &["new", "root()"]);
+    // Make the change divergent by describing it concurrently
+    test_env.jj_cmd_ok(&repo_path, &["describe", "-r", &change_id, "-m", "lost 1"]);
+    test_env.jj_cmd_ok(
+        &repo_path,
+        &[
+            "describe", "--at-op", "@-", "-r", &change_id, "-m", "lost 2",
+        ],
+    );
+    test_env.jj_cmd_ok(&repo_path, &["abandon", "description(lost)"]);
+
+    let stderr = test_env.jj_cmd_failure(&repo_path, &["op", "restore-commit", &change_id]);
+    let stderr = Regex::new(r"\(committed [^)]*\)")
+        .unwrap()
+        .replace_all(&redact_op_hash(&stderr), "(committed [timestamp])")
+        .into_owned();
+    let lines = stderr.lines().collect_vec();
+    assert_eq!(lines[0], "Commits visible in operation [op]:");
+    assert_eq!(
+        lines[1..3].iter().copied().sorted().collect_vec(),
+        [
+            "  lost 1 (committed [timestamp])",
+            "  lost 2 (committed [timestamp])"
+        ]
+    );
+    assert_eq!(
+        lines[3..],
+        [
+            format!("Error: Change ID \"{change_id}\" matches 2 commits").as_str(),
+            "Hint: Pass the commit ID of the one to restore."
+        ]
+    );
+
+    // Passing a commit ID restores that commit
+    let commit_id = test_env.jj_cmd_success(
+        &repo_path,
+        &[
+            "log",
+            "--no-graph",
+            "--at-op",
+            "@-",
+            "-r",
+            "description('lost 2')",
+            "-T",
+            "commit_id",
+        ],
+    );
+    let (_stdout, stderr) = test_env.jj_cmd_ok(&repo_path, &["op", "restore-commit", &commit_id]);
+    insta::assert_snapshot!(redact_op_hash(&stderr), @r###"
+    Restored commit lost 2
+    Found in operation [op]: resolve concurrent operations
+    "###);
+}
+
+fn redact_op_hash(text: &str) -> String {
+    Regex::new(r"operation [0-9a-f]{12}")
+        .unwrap()
+        .replace_all(text, "operation [op]")
+        .into_owned()
+}
@@ -0,0 +1,105 @@
+// Copyright 2024 The Jujutsu Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use crate::common::TestEnvironment;
+
+#[test]
+fn test_range_diff() {
+    let test_env = TestEnvironment::default();
+    test_env.jj_cmd_ok(test_env.env_root(), &["init", "repo", "--git"]);
+    let repo_path = test_env.env_root().join("repo");
+    test_env.add_config(r#"templates.commit_summary = "description.first_line()""#);
+
+    test_env.jj_cmd_ok(&repo_path, &["describe", "-m=first"]);
+    std::fs::write(repo_path.join("file1"), "1\n").unwrap();
+    test_env.jj_cmd_ok(&repo_path, &["new", "-m=second"]);
+    std::fs::write(repo_path.join("file2"), "2\n").unwrap();
+    test_env.jj_cmd_ok(&repo_path, &["new", "-m=third"]);
+    std::fs::write(repo_path.join("file3"), "3\n").unwrap();
+    let old_commit_ids = test_env.jj_cmd_success(
+        &repo_path,
+        &[
+            "log",
+            "--no-graph",
+            "-r=::@ ~ root()",
+            "-T",
+            r#"commit_id ++ "\n""#,
+        ],
+    );
+    let old_revset = old_commit_ids.lines().collect::<Vec<_>>().join(" | ");
+
+    // Rebase the stack onto a new commit, edit the second commit, and recreate
+    // the third commit with a new change id
+    test_env.jj_cmd_ok(&repo_path, &["new", "root()", "-m=base"]);
+    std::fs::write(repo_path.join("base"), "base\n").unwrap();
+    test_env.jj_cmd_ok(
+        &repo_path,
+        &["rebase", "-s=description(first)", "-d=description(base)"],
+    );
+    test_env.jj_cmd_ok(&repo_path, &["edit", "description(second)"]);
+    std::fs::write(repo_path.join("file2"), "2 edited\n").unwrap();
+    test_env.jj_cmd_ok(&repo_path, &["abandon", "description(third)"]);
+    test_env.jj_cmd_ok(&repo_path, &["new", "description(second)", "-m=third"]);
+    std::fs::write(repo_path.join("file3"), "3\n").unwrap();
+    test_env.jj_cmd_ok(&repo_path, &["new", "-m=fourth"]);
+    std::fs::write(repo_path.join("file4"), "4\n").unwrap();
+
+    let stdout = test_env.jj_cmd_success(
+        &repo_path,
+        &[
+            "range-diff",
+            "-r",
+            &old_revset,
+            "-r=description(base)..@",
+            "--summary",
+        ],
+    );
+    insta::assert_snapshot!(stdout, @r###"
+    = first
+    ! second
+    M file2
+    = third
+    + fourth
+    "###);
+
+    // Commits missing from the new version
+    let stdout = test_env.jj_cmd_success(
+        &repo_path,
+        &["range-diff", "-r", &old_revset, "-r=description(first)"],
+    );
+    insta::assert_snapshot!(stdout, @r###"
+    = first
+    - second
+    - third
+    "###);
+
+    // The old version of a commit can conflict with the new parents, which
+    // shows up in the diff
+    test_env.jj_cmd_ok(&repo_path, &["new", "-m=second"]);
+    std::fs::write(repo_path.join("file2"), "2 again\n").unwrap();
+    let old_second_id = old_commit_ids.lines().nth(1).unwrap();
+    let stdout = test_env.jj_cmd_success(
+        &repo_path,
+        &["range-diff", "-r", old_second_id, "-r=@", "--types"],
+    );
+    insta::assert_snapshot!(stdout, @r###"
+    ! second
+    CF file2
+    "###);
+
+    let stderr = test_env.jj_cmd_failure(&repo_path, &["range-diff", "-r=@"]);
+    insta::assert_snapshot!(stderr, @r###"
+    Error: Expected two revsets: the old and the new version of the commits
+    "###);
+}
@@ -0,0 +1,138 @@
+// Copyright 2024 The Jujutsu Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::path::Path;
+
+use crate::common::TestEnvironment;
+
+fn create_commit(test_env: &TestEnvironment, repo_path: &Path, name: &str, parents: &[&str]) {
+    if parents.is_empty() {
+        test_env.jj_cmd_ok(repo_path, &["new", "root()", "-m", name]);
+    } else {
+        let mut args = vec!["new", "-m", name];
+        args.extend(parents);
+        test_env.jj_cmd_ok(repo_path, &args);
+    }
+    std::fs::write(repo_path.join(name), format!("{name}\n")).unwrap();
+    test_env.jj_cmd_ok(repo_path, &["branch", "create", name]);
+}
+
+fn get_log_output(test_env: &TestEnvironment, repo_path: &Path) -> String {
+    test_env.jj_cmd_success(
+        repo_path,
+        &[
+            "log",
+            "--no-graph",
+            "-r=a::",
+            "-T",
+            r#"description.first_line() ++ ": " ++ author.name() ++ " <" ++ author.email() ++ ">\n""#,
+        ],
+    )
+}
+
+fn get_parents(test_env: &TestEnvironment, repo_path: &Path, revision: &str) -> String {
+    test_env.jj_cmd_success(
+        repo_path,
+        &[
+            "log",
+            "--no-graph",
+            &format!("-r=parents({revision})"),
+            "-T",
+            r#"description.first_line() ++ "\n""#,
+        ],
+    )
+}
+
+#[test]
+fn test_reauthor_stack_with_merge() {
+    let test_env = TestEnvironment::default();
+    test_env.jj_cmd_ok(test_env.env_root(), &["init", "repo", "--git"]);
+    let repo_path = test_env.env_root().join("repo");
+
+    create_commit(&test_env, &repo_path, "a", &[]);
+    create_commit(&test_env, &repo_path, "b", &["a"]);
+    create_commit(&test_env, &repo_path, "c", &["a"]);
+    create_commit(&test_env, &repo_path, "d", &["b", "c"]);
+    create_commit(&test_env, &repo_path, "e", &["d"]);
+    test_env.jj_cmd_ok(&repo_path, &["new", "root()"]);
+
+    let (stdout, stderr) = test_env.jj_cmd_ok(
+        &repo_path,
+        &[
+            "reauthor",
+            "-r=a::",
+            "--name=New Author",
+            "--email=new.author@example.com",
+        ],
+    );
+    insta::assert_snapshot!(stdout, @"");
+    insta::assert_snapshot!(stderr, @r###"
+    Reauthored 5 commits
+    "###);
+    insta::assert_snapshot!(get_log_output(&test_env, &repo_path), @r###"
+    e: New Author <new.author@example.com>
+    d: New Author <new.author@example.com>
+    c: New Author <new.author@example.com>
+    b: New Author <new.author@example.com>
+    a: New Author <new.author@example.com>
+    "###);
+    // The merge commit is still a merge of the rewritten parents
+    insta::assert_snapshot!(get_parents(&test_env, &repo_path, "d"), @r###"
+    c
+    b
+    "###);
+}
+
+#[test]
+fn test_reauthor_rebases_descendants() {
+    let test_env = TestEnvironment::default();
+    test_env.jj_cmd_ok(test_env.env_root(), &["init", "repo", "--git"]);
+    let repo_path = test_env.env_root().join("repo");
+
+    create_commit(&test_env, &repo_path, "a", &[]);
+    create_commit(&test_env, &repo_path, "b", &["a"]);
+    create_commit(&test_env, &repo_path, "c", &["a"]);
+    create_commit(&test_env, &repo_path, "d", &["b", "c"]);
+    create_commit(&test_env, &repo_path, "e", &["d"]);
+    test_env.jj_cmd_ok(&repo_path, &["new", "root()"]);
+
+    // The name defaults to the configured user
+    let (stdout, stderr) = test_env.jj_cmd_ok(
+        &repo_path,
+        &["reauthor", "-r=b", "--email=other@example.com"],
+    );
+    insta::assert_snapshot!(stdout, @"");
+    insta::assert_snapshot!(stderr, @r###"
+    Reauthored 1 commits
+    Rebased 2 descendant commits
+    "###);
+    insta::assert_snapshot!(get_log_output(&test_env, &repo_path), @r###"
+    e: Test User <test.user@example.com>
+    d: Test User <test.user@example.com>
+    b: Test User <other@example.com>
+    c: Test User <test.user@example.com>
+    a: Test User <test.user@example.com>
+    "###);
+    insta::assert_snapshot!(get_parents(&test_env, &repo_path, "d"), @r###"
+    b
+    c
+    "###);
+
+    // Commits that already have the author aren't rewritten
+    let (stdout, stderr) = test_env.jj_cmd_ok(&repo_path, &["reauthor", "-r=a"]);
+    insta::assert_snapshot!(stdout, @"");
+    insta::assert_snapshot!(stderr, @r###"
+    Nothing changed.
+    "###);
+}
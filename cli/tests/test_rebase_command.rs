@@ -626,6 +626,124 @@ fn test_rebase_with_descendants() {
     "###);
 }
 
+#[test]
+fn test_rebase_abandon_newly_empty() {
+    let test_env = TestEnvironment::default();
+    test_env.jj_cmd_ok(test_env.env_root(), &["init", "repo", "--git"]);
+    let repo_path = test_env.env_root().join("repo");
+
+    create_commit(&test_env, &repo_path, "base", &[]);
+    create_commit(&test_env, &repo_path, "upstream", &["base"]);
+    // `x` makes the same change as `upstream`, so it becomes empty when rebased
+    // onto it
+    test_env.jj_cmd_ok(&repo_path, &["new", "base", "-m", "x"]);
+    std::fs::write(repo_path.join("upstream"), "upstream\n").unwrap();
+    test_env.jj_cmd_ok(&repo_path, &["branch", "create", "x"]);
+    // `empty` was empty to begin with
+    test_env.jj_cmd_ok(&repo_path, &["new", "x", "-m", "empty"]);
+    test_env.jj_cmd_ok(&repo_path, &["branch", "create", "empty"]);
+    create_commit(&test_env, &repo_path, "y", &["empty"]);
+    // Test the setup
+    insta::assert_snapshot!(get_log_output(&test_env, &repo_path), @r###"
+    @  y
+    ◉  empty
+    ◉  x
+    │ ◉  upstream
+    ├─╯
+    ◉  base
+    ◉
+    "###);
+
+    let (stdout, stderr) = test_env.jj_cmd_ok(
+        &repo_path,
+        &[
+            "rebase",
+            "-s=x",
+            "-d=upstream",
+            "--config-toml=rebase.empty='abandon-newly-empty'",
+        ],
+    );
+    insta::assert_snapshot!(stdout, @"");
+    let mut stderr_lines = stderr.lines();
+    assert_eq!(stderr_lines.next(), Some("Rebased 2 commits"));
+    assert_eq!(
+        stderr_lines.next(),
+        Some("Abandoned 1 commits that became empty:")
+    );
+    assert!(stderr_lines.next().unwrap().ends_with(" x"));
+    assert!(stderr_lines
+        .next()
+        .unwrap()
+        .starts_with("Working copy now at:"));
+    insta::assert_snapshot!(get_log_output(&test_env, &repo_path), @r###"
+    @  y
+    ◉  empty
+    ◉  upstream x
+    ◉  base
+    ◉
+    "###);
+
+    // `--keep-empty` overrides the setting
+    test_env.jj_cmd_ok(&repo_path, &["undo"]);
+    let (_stdout, stderr) = test_env.jj_cmd_ok(
+        &repo_path,
+        &[
+            "rebase",
+            "-s=x",
+            "-d=upstream",
+            "--keep-empty",
+            "--config-toml=rebase.empty='abandon-newly-empty'",
+        ],
+    );
+    assert!(stderr.starts_with("Rebased 3 commits\nWorking copy now at:"));
+    insta::assert_snapshot!(get_log_output(&test_env, &repo_path), @r###"
+    @  y
+    ◉  empty
+    ◉  x
+    ◉  upstream
+    ◉  base
+    ◉
+    "###);
+
+    // The working-copy commit is never abandoned
+    test_env.jj_cmd_ok(&repo_path, &["undo"]);
+    test_env.jj_cmd_ok(&repo_path, &["edit", "x"]);
+    let (_stdout, stderr) = test_env.jj_cmd_ok(
+        &repo_path,
+        &[
+            "rebase",
+            "-s=x",
+            "-d=upstream",
+            "--config-toml=rebase.empty='abandon-newly-empty'",
+        ],
+    );
+    assert!(stderr.starts_with("Rebased 3 commits\nWorking copy now at:"));
+    insta::assert_snapshot!(get_log_output(&test_env, &repo_path), @r###"
+    ◉  y
+    ◉  empty
+    @  x
+    ◉  upstream
+    ◉  base
+    ◉
+    "###);
+
+    // Invalid setting
+    test_env.jj_cmd_ok(&repo_path, &["undo"]);
+    let stderr = test_env.jj_cmd_failure(
+        &repo_path,
+        &[
+            "rebase",
+            "-s=x",
+            "-d=upstream",
+            "--config-toml=rebase.empty='bogus'",
+        ],
+    );
+    insta::assert_snapshot!(stderr, @r###"
+    Config error: invalid rebase.empty setting: bogus
+    For help, see https://github.com/martinvonz/jj/blob/main/docs/config.md.
+    "###);
+}
+
 fn get_log_output(test_env: &TestEnvironment, repo_path: &Path) -> String {
     test_env.jj_cmd_success(repo_path, &["log", "-T", "branches"])
 }
@@ -1017,3 +1135,79 @@ fn test_rebase_with_child_and_descendant_bug_2600() {
     ◉
     "###);
 }
+
+#[test]
+fn test_rebase_dry_run() {
+    let test_env = TestEnvironment::default();
+    test_env.jj_cmd_ok(test_env.env_root(), &["init", "repo", "--git"]);
+    let repo_path = test_env.env_root().join("repo");
+    let file_path = repo_path.join("file");
+
+    std::fs::write(&file_path, "base\n").unwrap();
+    test_env.jj_cmd_ok(&repo_path, &["commit", "-m", "base"]);
+    std::fs::write(&file_path, "a\n").unwrap();
+    test_env.jj_cmd_ok(&repo_path, &["describe", "-m", "a"]);
+    test_env.jj_cmd_ok(&repo_path, &["branch", "create", "a"]);
+    test_env.jj_cmd_ok(&repo_path, &["new", "description(base)", "-m", "b"]);
+    std::fs::write(&file_path, "b\n").unwrap();
+    test_env.jj_cmd_ok(&repo_path, &["branch", "create", "b"]);
+    test_env.jj_cmd_ok(&repo_path, &["new", "-m", "c"]);
+    test_env.jj_cmd_ok(&repo_path, &["branch", "create", "c"]);
+    let commit_id = |rev: &str| {
+        test_env
+            .jj_cmd_success(
+                &repo_path,
+                &["log", "--no-graph", "-r", rev, "-T", "commit_id.short()"],
+            )
+            .trim_end()
+            .to_owned()
+    };
+    let old_b = commit_id("b");
+    let old_c = commit_id("c");
+    let op_id_before = test_env.current_operation_id(&repo_path);
+
+    // Rebasing b onto a conflicts, but nothing is changed in a dry run. Use the
+    // same commit timestamp for both runs so the commit ids match.
+    let rebase_args = [
+        "rebase",
+        "-s",
+        "b",
+        "-d",
+        "a",
+        r#"--config-toml=debug.commit-timestamp="2001-02-03T04:05:30+07:00""#,
+    ];
+    let (stdout, dry_run_stderr) =
+        test_env.jj_cmd_ok(&repo_path, &[&rebase_args[..], &["--dry-run"]].concat());
+    insta::assert_snapshot!(stdout, @"");
+    assert_eq!(test_env.current_operation_id(&repo_path), op_id_before);
+    assert_eq!(std::fs::read_to_string(&file_path).unwrap(), "b\n");
+    assert_eq!(commit_id("b"), old_b);
+
+    // The report matches the effects of actually running the command
+    test_env.jj_cmd_ok(&repo_path, &rebase_args);
+    let new_b = commit_id("b");
+    let new_c = commit_id("c");
+    assert_ne!(new_b, old_b);
+    let mut report_lines = dry_run_stderr.lines();
+    assert_eq!(report_lines.next(), Some("Changes that would be made:"));
+    assert_eq!(
+        report_lines.next_back(),
+        Some("Dry-run requested, no changes were made.")
+    );
+    let report_lines: Vec<_> = report_lines.collect();
+    assert_eq!(report_lines.len(), 6, "{dry_run_stderr}");
+    for (old_id, new_id) in [(&old_b, &new_b), (&old_c, &new_c)] {
+        assert!(report_lines.iter().any(|line| line
+            .starts_with(&format!("  Rewrite commit {old_id} as "))
+            && line.contains(&new_id[..8])));
+        assert!(
+            report_lines.contains(&format!("  Introduce conflicts in commit {new_id}").as_str())
+        );
+    }
+    assert!(report_lines.contains(&format!("  Move branch b from {old_b} to {new_b}").as_str()));
+    assert!(report_lines.contains(&format!("  Move branch c from {old_c} to {new_c}").as_str()));
+    let stdout = test_env.jj_cmd_success(&repo_path, &["resolve", "--list", "-r", "b"]);
+    insta::assert_snapshot!(stdout, @r###"
+    file    2-sided conflict
+    "###);
+}
@@ -101,6 +101,7 @@ fn test_report_conflicts_with_divergent_commits() {
     let test_env = TestEnvironment::default();
     test_env.jj_cmd_ok(test_env.env_root(), &["init", "repo", "--git"]);
     let repo_path = test_env.env_root().join("repo");
+    test_env.add_config("operation.allow-at-op-mutations = true");
 
     test_env.jj_cmd_ok(&repo_path, &["describe", "-m=A"]);
     std::fs::write(repo_path.join("file"), "A\n").unwrap();
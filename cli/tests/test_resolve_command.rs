@@ -763,3 +763,130 @@ fn test_multiple_conflicts() {
     Error: No conflicts found at this revision
     "###);
 }
+
+/// Returns the "metadata conflicts" section of `jj status`.
+#[cfg(unix)]
+fn get_metadata_conflicts_status(test_env: &TestEnvironment, repo_path: &Path) -> String {
+    let stdout = test_env.jj_cmd_success(repo_path, &["status"]);
+    let mut lines = stdout
+        .lines()
+        .skip_while(|line| !line.starts_with("There are metadata conflicts"));
+    let Some(header) = lines.next() else {
+        return String::new();
+    };
+    std::iter::once(header)
+        .chain(lines.take_while(|line| line.starts_with("  ")))
+        .map(|line| format!("{line}\n"))
+        .collect()
+}
+
+#[cfg(unix)]
+#[test]
+fn test_executable_bit_conflict() {
+    use std::os::unix::fs::PermissionsExt as _;
+
+    let test_env = TestEnvironment::default();
+    test_env.jj_cmd_ok(test_env.env_root(), &["init", "repo", "--git"]);
+    let repo_path = test_env.env_root().join("repo");
+    let file_path = repo_path.join("file");
+    let mode = || file_path.metadata().unwrap().permissions().mode();
+
+    create_commit(&test_env, &repo_path, "a", &[], &[("file", "contents\n")]);
+    test_env.jj_cmd_ok(&repo_path, &["chmod", "x", "file"]);
+    create_commit(&test_env, &repo_path, "b", &[], &[("file", "contents\n")]);
+    create_commit(&test_env, &repo_path, "merge", &["a", "b"], &[]);
+    insta::assert_snapshot!(test_env.jj_cmd_success(&repo_path, &["resolve", "--list"]),
+    @r###"
+    file    2-sided conflict including an executable
+    "###);
+
+    // The file is checked out as non-executable, and status explains how to
+    // resolve the conflict
+    assert_eq!(mode() & 0o111, 0);
+    insta::assert_snapshot!(get_metadata_conflicts_status(&test_env, &repo_path), @r###"
+    There are metadata conflicts at these paths:
+      file: the sides disagree about the executable bit
+      Use `jj resolve --set-exec` or `--clear-exec`, or `jj resolve --use-symlink` or `--use-file`, with the path to resolve.
+    "###);
+    let stdout = test_env.jj_cmd_success(&repo_path, &["status"]);
+    assert!(!stdout.contains("unresolved conflicts"));
+
+    // Making the file executable on disk doesn't resolve the conflict
+    std::fs::set_permissions(&file_path, std::fs::Permissions::from_mode(0o755)).unwrap();
+    insta::assert_snapshot!(test_env.jj_cmd_success(&repo_path, &["resolve", "--list"]),
+    @r###"
+    file    2-sided conflict including an executable
+    "###);
+
+    insta::assert_snapshot!(test_env.jj_cmd_failure(&repo_path, &["resolve", "--use-file", "file"]), @r###"
+    Error: Conflict at file can't be resolved with --use-file
+    "###);
+    test_env.jj_cmd_ok(&repo_path, &["resolve", "--set-exec", "file"]);
+    insta::assert_snapshot!(test_env.jj_cmd_cli_error(&repo_path, &["resolve", "--list"]),
+    @r###"
+    Error: No conflicts found at this revision
+    "###);
+    assert_ne!(mode() & 0o111, 0);
+    assert!(get_metadata_conflicts_status(&test_env, &repo_path).is_empty());
+
+    // The resolved file is snapshotted like any other file
+    test_env.jj_cmd_ok(&repo_path, &["new"]);
+    std::fs::set_permissions(&file_path, std::fs::Permissions::from_mode(0o644)).unwrap();
+    insta::assert_snapshot!(test_env.jj_cmd_success(&repo_path, &["diff", "--summary"]), @r###"
+    M file
+    "###);
+}
+
+#[cfg(unix)]
+#[test]
+fn test_symlink_vs_file_conflict() {
+    let test_env = TestEnvironment::default();
+    test_env.jj_cmd_ok(test_env.env_root(), &["init", "repo", "--git"]);
+    let repo_path = test_env.env_root().join("repo");
+    let link_path = repo_path.join("link");
+
+    create_commit(&test_env, &repo_path, "a", &[], &[]);
+    std::os::unix::fs::symlink("target", &link_path).unwrap();
+    create_commit(
+        &test_env,
+        &repo_path,
+        "b",
+        &[],
+        &[("link", "file contents\n")],
+    );
+    create_commit(&test_env, &repo_path, "merge", &["a", "b"], &[]);
+    insta::assert_snapshot!(test_env.jj_cmd_success(&repo_path, &["resolve", "--list"]),
+    @r###"
+    link    2-sided conflict including a symlink
+    "###);
+
+    // The regular file is checked out
+    assert!(link_path.symlink_metadata().unwrap().is_file());
+    assert_eq!(
+        std::fs::read_to_string(&link_path).unwrap(),
+        "file contents\n"
+    );
+    insta::assert_snapshot!(get_metadata_conflicts_status(&test_env, &repo_path), @r###"
+    There are metadata conflicts at these paths:
+      link: a symlink on some sides and a regular file on others (checked out as the file)
+      Use `jj resolve --set-exec` or `--clear-exec`, or `jj resolve --use-symlink` or `--use-file`, with the path to resolve.
+    "###);
+
+    // Editing the file doesn't resolve the conflict
+    std::fs::write(&link_path, "edited\n").unwrap();
+    insta::assert_snapshot!(test_env.jj_cmd_success(&repo_path, &["resolve", "--list"]),
+    @r###"
+    link    2-sided conflict including a symlink
+    "###);
+
+    insta::assert_snapshot!(test_env.jj_cmd_failure(&repo_path, &["resolve", "--set-exec", "link"]), @r###"
+    Error: Conflict at link can't be resolved with --set-exec
+    "###);
+    test_env.jj_cmd_ok(&repo_path, &["resolve", "--use-symlink", "link"]);
+    insta::assert_snapshot!(test_env.jj_cmd_cli_error(&repo_path, &["resolve", "--list"]),
+    @r###"
+    Error: No conflicts found at this revision
+    "###);
+    assert_eq!(std::fs::read_link(&link_path).unwrap(), Path::new("target"));
+    assert!(get_metadata_conflicts_status(&test_env, &repo_path).is_empty());
+}
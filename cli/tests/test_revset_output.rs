@@ -149,6 +149,50 @@ fn test_bad_function_call() {
       = Invalid arguments to revset function "branches": Invalid string pattern kind "bad"
     "###);
 
+    let stderr =
+        test_env.jj_cmd_failure(&repo_path, &["log", "-r", r#"author_date(after:"soon")"#]);
+    insta::assert_snapshot!(stderr, @r###"
+    Error: Failed to parse revset:  --> 1:13
+      |
+    1 | author_date(after:"soon")
+      |             ^----------^
+      |
+      = Invalid arguments to revset function "author_date": Invalid date "soon", expected e.g. "2024-01-31", "2024-01-31 13:00", or "2 days ago"
+    "###);
+
+    let stderr = test_env.jj_cmd_failure(
+        &repo_path,
+        &["log", "-r", r#"committer_date(on:"2024-01-31")"#],
+    );
+    insta::assert_snapshot!(stderr, @r###"
+    Error: Failed to parse revset:  --> 1:16
+      |
+    1 | committer_date(on:"2024-01-31")
+      |                ^-------------^
+      |
+      = Invalid arguments to revset function "committer_date": Invalid date pattern kind "on", expected "after" or "before"
+    "###);
+
+    let stderr = test_env.jj_cmd_failure(&repo_path, &["log", "-r", "author_date(2024)"]);
+    insta::assert_snapshot!(stderr, @r###"
+    Error: Failed to parse revset:  --> 1:13
+      |
+    1 | author_date(2024)
+      |             ^--^
+      |
+      = Invalid arguments to revset function "author_date": Expected function argument of date pattern, e.g. after:"2024-01-31"
+    "###);
+
+    let stderr = test_env.jj_cmd_failure(&repo_path, &["log", "-r", "latest(all(), by=date)"]);
+    insta::assert_snapshot!(stderr, @r###"
+    Error: Failed to parse revset:  --> 1:18
+      |
+    1 | latest(all(), by=date)
+      |                  ^--^
+      |
+      = Invalid arguments to revset function "latest": Expected "author" or "committer"
+    "###);
+
     let stderr = test_env.jj_cmd_failure(&repo_path, &["log", "-r", "root()::whatever()"]);
     insta::assert_snapshot!(stderr, @r###"
     Error: Failed to parse revset:  --> 1:9
@@ -394,13 +438,13 @@ fn test_bad_alias_decl() {
     ◉  zzzzzzzz root() 00000000
     "###);
     insta::assert_snapshot!(stderr, @r###"
-    Failed to load "revset-aliases."bad"":  --> 1:1
+    Warning: Failed to load "revset-aliases."bad"":  --> 1:1
       |
     1 | "bad"
       | ^---
       |
       = expected <identifier> or <function_name>
-    Failed to load "revset-aliases.badfn(a, a)":  --> 1:7
+    Warning: Failed to load "revset-aliases.badfn(a, a)":  --> 1:7
       |
     1 | badfn(a, a)
       |       ^--^
@@ -408,3 +452,76 @@ fn test_bad_alias_decl() {
       = Redefinition of function parameter
     "###);
 }
+
+#[test]
+fn test_ambiguous_symbol() {
+    let test_env = TestEnvironment::default();
+    test_env.jj_cmd_ok(test_env.env_root(), &["init", "repo", "--git"]);
+    let repo_path = test_env.env_root().join("repo");
+    // Branches named like the change id and the commit id of the root commit
+    test_env.jj_cmd_ok(&repo_path, &["branch", "create", "zzz", "000"]);
+    let template = r#"change_id.shortest(8) ++ "\n""#;
+
+    // The branch takes precedence, with a warning
+    let (stdout, stderr) = test_env.jj_cmd_ok(
+        &repo_path,
+        &["log", "--no-graph", "-T", template, "-r", "zzz"],
+    );
+    insta::assert_snapshot!(stdout, @r###"
+    qpvuntsm
+    "###);
+    insta::assert_snapshot!(stderr, @r###"
+    Warning: Revision "zzz" is ambiguous; using the branch. It matches:
+      branch: qpvuntsm 230dd059 000 zzz | (empty) (no description set)
+      change id: zzzzzzzz 00000000 (empty) (no description set)
+    Hint: Use `branch:zzz` or `change:zzz` to pick one.
+    "###);
+    let (stdout, stderr) = test_env.jj_cmd_ok(
+        &repo_path,
+        &["log", "--no-graph", "-T", template, "-r", "000"],
+    );
+    insta::assert_snapshot!(stdout, @r###"
+    qpvuntsm
+    "###);
+    insta::assert_snapshot!(stderr, @r###"
+    Warning: Revision "000" is ambiguous; using the branch. It matches:
+      branch: qpvuntsm 230dd059 000 zzz | (empty) (no description set)
+      commit id: zzzzzzzz 00000000 (empty) (no description set)
+    Hint: Use `branch:000` or `commit:000` to pick one.
+    "###);
+
+    // Strict mode makes it an error
+    let stderr = test_env.jj_cmd_failure(
+        &repo_path,
+        &[
+            "log",
+            "-r",
+            "zzz",
+            "--config-toml",
+            "revsets.strict-symbols = true",
+        ],
+    );
+    insta::assert_snapshot!(stderr, @r###"
+    Error: Revision "zzz" is ambiguous
+    Hint: Use `branch:zzz` or `change:zzz` to pick one.
+    "###);
+
+    // Explicit namespaces don't warn
+    for (revset, expected) in [
+        ("branch:zzz", "qpvuntsm\n"),
+        ("change:zzz", "zzzzzzzz\n"),
+        ("branch:000", "qpvuntsm\n"),
+        ("commit:000", "zzzzzzzz\n"),
+    ] {
+        let (stdout, stderr) = test_env.jj_cmd_ok(
+            &repo_path,
+            &["log", "--no-graph", "-T", template, "-r", revset],
+        );
+        assert_eq!(stdout, expected, "{revset}");
+        assert_eq!(stderr, "", "{revset}");
+    }
+    let stderr = test_env.jj_cmd_failure(&repo_path, &["log", "-r", "change:000"]);
+    insta::assert_snapshot!(stderr, @r###"
+    Error: Revision "change:000" doesn't exist
+    "###);
+}
@@ -0,0 +1,159 @@
+// Copyright 2024 The Jujutsu Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::path::Path;
+
+use crate::common::TestEnvironment;
+
+fn get_log_output(test_env: &TestEnvironment, repo_path: &Path, args: &[&str]) -> String {
+    let template = r#"
+        if(current_working_copy, "@", if(root, "root", description.first_line())) ++ "\n"
+    "#;
+    let mut full_args = vec!["log", "--no-graph", "-T", template];
+    full_args.extend_from_slice(args);
+    test_env.jj_cmd_success(repo_path, &full_args)
+}
+
+#[test]
+fn test_scope_to_directory() {
+    let test_env = TestEnvironment::default();
+    test_env.jj_cmd_ok(test_env.env_root(), &["init", "repo", "--git"]);
+    let repo_path = test_env.env_root().join("repo");
+    std::fs::create_dir(repo_path.join("a")).unwrap();
+    std::fs::write(repo_path.join("a").join("file"), "a\n").unwrap();
+    test_env.jj_cmd_ok(&repo_path, &["commit", "-m", "change a"]);
+    std::fs::create_dir(repo_path.join("b")).unwrap();
+    std::fs::write(repo_path.join("b").join("file"), "b\n").unwrap();
+    test_env.jj_cmd_ok(&repo_path, &["commit", "-m", "change b"]);
+
+    // By default, the scope is the whole repo
+    let stdout = test_env.jj_cmd_success(&repo_path, &["scope", "list"]);
+    insta::assert_snapshot!(stdout, @r###"
+    .
+    "###);
+    insta::assert_snapshot!(get_log_output(&test_env, &repo_path, &[]), @r###"
+    @
+    change b
+    change a
+    root
+    "###);
+
+    // Scoping to a directory makes the working copy sparse on it
+    let (stdout, stderr) = test_env.jj_cmd_ok(&repo_path, &["scope", "set", "a"]);
+    insta::assert_snapshot!(stdout, @"");
+    insta::assert_snapshot!(stderr, @r###"
+    Added 0 files, modified 0 files, removed 1 files
+    "###);
+    assert!(repo_path.join("a").join("file").exists());
+    assert!(!repo_path.join("b").exists());
+    let stdout = test_env.jj_cmd_success(&repo_path, &["scope", "list"]);
+    insta::assert_snapshot!(stdout, @r###"
+    a
+    "###);
+    let stdout = test_env.jj_cmd_success(&repo_path, &["sparse", "list"]);
+    insta::assert_snapshot!(stdout, @r###"
+    a
+    "###);
+
+    // The default log only shows commits touching the scope
+    insta::assert_snapshot!(get_log_output(&test_env, &repo_path, &[]), @r###"
+    @
+    change a
+    "###);
+    // Commits outside the scope can still be selected explicitly
+    insta::assert_snapshot!(
+        get_log_output(&test_env, &repo_path, &["-r", "description(\"change b\")"]), @r###"
+    change b
+    "###);
+
+    // Clearing the scope brings everything back
+    let (stdout, stderr) = test_env.jj_cmd_ok(&repo_path, &["scope", "clear"]);
+    insta::assert_snapshot!(stdout, @"");
+    insta::assert_snapshot!(stderr, @r###"
+    Added 1 files, modified 0 files, removed 0 files
+    "###);
+    assert!(repo_path.join("b").join("file").exists());
+    let stdout = test_env.jj_cmd_success(&repo_path, &["scope", "list"]);
+    insta::assert_snapshot!(stdout, @r###"
+    .
+    "###);
+    insta::assert_snapshot!(get_log_output(&test_env, &repo_path, &[]), @r###"
+    @
+    change b
+    change a
+    root
+    "###);
+}
+
+#[test]
+fn test_scope_merges_sparse_patterns() {
+    let test_env = TestEnvironment::default();
+    test_env.jj_cmd_ok(test_env.env_root(), &["init", "repo", "--git"]);
+    let repo_path = test_env.env_root().join("repo");
+    for dir in ["a/x", "a/y", "b", "c"] {
+        std::fs::create_dir_all(repo_path.join(dir)).unwrap();
+        std::fs::write(repo_path.join(dir).join("file"), "contents\n").unwrap();
+    }
+    test_env.jj_cmd_ok(&repo_path, &["commit", "-m", "add files"]);
+    test_env.jj_cmd_ok(
+        &repo_path,
+        &["sparse", "set", "--clear", "--add", "a/x", "--add", "b"],
+    );
+
+    // The existing sparse patterns are restricted to the scope
+    let (_stdout, stderr) = test_env.jj_cmd_ok(&repo_path, &["scope", "set", "a"]);
+    insta::assert_snapshot!(stderr, @r###"
+    Added 0 files, modified 0 files, removed 1 files
+    "###);
+    let stdout = test_env.jj_cmd_success(&repo_path, &["sparse", "list"]);
+    insta::assert_snapshot!(stdout, @r###"
+    a/x
+    "###);
+    assert!(!repo_path.join("a").join("y").exists());
+
+    // Clearing the scope restores the patterns outside it
+    let (_stdout, stderr) = test_env.jj_cmd_ok(&repo_path, &["scope", "clear"]);
+    insta::assert_snapshot!(stderr, @r###"
+    Added 1 files, modified 0 files, removed 0 files
+    "###);
+    let stdout = test_env.jj_cmd_success(&repo_path, &["sparse", "list"]);
+    insta::assert_snapshot!(stdout, @r###"
+    a/x
+    b
+    "###);
+    assert!(!repo_path.join("c").exists());
+}
+
+#[test]
+fn test_scope_per_workspace() {
+    let test_env = TestEnvironment::default();
+    test_env.jj_cmd_ok(test_env.env_root(), &["init", "repo", "--git"]);
+    let repo_path = test_env.env_root().join("repo");
+    let secondary_path = test_env.env_root().join("secondary");
+    test_env.jj_cmd_ok(
+        &repo_path,
+        &["workspace", "add", "--name", "second", "../secondary"],
+    );
+
+    // Scoping one workspace doesn't affect the others
+    test_env.jj_cmd_ok(&repo_path, &["scope", "set", "a"]);
+    let stdout = test_env.jj_cmd_success(&repo_path, &["scope", "list"]);
+    insta::assert_snapshot!(stdout, @r###"
+    a
+    "###);
+    let stdout = test_env.jj_cmd_success(&secondary_path, &["scope", "list"]);
+    insta::assert_snapshot!(stdout, @r###"
+    .
+    "###);
+}
@@ -30,3 +30,107 @@ fn test_deprecated_flags() {
     );
     assert!(stdout.contains("COMPREPLY"));
 }
+
+#[test]
+fn test_completion_script_calls_completion_data() {
+    let test_env = TestEnvironment::default();
+    let stdout = test_env.jj_cmd_success(test_env.env_root(), &["util", "completion", "bash"]);
+    assert!(stdout.contains("jj util completion-data --kind revisions"));
+    let stdout = test_env.jj_cmd_success(test_env.env_root(), &["util", "completion", "fish"]);
+    assert!(stdout.contains("jj util completion-data --kind revisions"));
+}
+
+#[test]
+fn test_completion_data() {
+    let test_env = TestEnvironment::default();
+    test_env.jj_cmd_ok(test_env.env_root(), &["init", "repo", "--git"]);
+    let repo_path = test_env.env_root().join("repo");
+    std::fs::create_dir(repo_path.join("dir")).unwrap();
+    std::fs::write(repo_path.join("dir").join("file1"), "").unwrap();
+    std::fs::write(repo_path.join("file2"), "").unwrap();
+    test_env.jj_cmd_ok(&repo_path, &["commit", "-m", "first"]);
+    test_env.jj_cmd_ok(&repo_path, &["branch", "create", "main", "-r", "@-"]);
+    test_env.jj_cmd_ok(&repo_path, &["branch", "create", "feature"]);
+
+    let stdout = test_env.jj_cmd_success(
+        &repo_path,
+        &["util", "completion-data", "--kind", "branches"],
+    );
+    assert_snapshot!(stdout, @r###"
+    feature
+    main
+    "###);
+    let stdout = test_env.jj_cmd_success(
+        &repo_path,
+        &[
+            "util",
+            "completion-data",
+            "--kind",
+            "branches",
+            "--prefix",
+            "ma",
+        ],
+    );
+    assert_snapshot!(stdout, @r###"
+    main
+    "###);
+
+    // Revisions are the branches followed by the change ids of recent commits
+    let change_ids = test_env.jj_cmd_success(
+        &repo_path,
+        &[
+            "log",
+            "--no-graph",
+            "-r",
+            "all() ~ root()",
+            "-T",
+            r#"change_id.short() ++ "\n""#,
+        ],
+    );
+    let stdout = test_env.jj_cmd_success(
+        &repo_path,
+        &["util", "completion-data", "--kind", "revisions"],
+    );
+    assert_eq!(stdout, format!("feature\nmain\n{change_ids}"));
+
+    let stdout =
+        test_env.jj_cmd_success(&repo_path, &["util", "completion-data", "--kind", "paths"]);
+    assert_snapshot!(stdout.replace('\\', "/"), @r###"
+    dir/file1
+    file2
+    "###);
+    let stdout = test_env.jj_cmd_success(
+        &repo_path.join("dir"),
+        &[
+            "util",
+            "completion-data",
+            "--kind",
+            "paths",
+            "--prefix",
+            "fi",
+        ],
+    );
+    assert_snapshot!(stdout, @r###"
+    file1
+    "###);
+}
+
+#[test]
+fn test_completion_data_does_not_snapshot() {
+    let test_env = TestEnvironment::default();
+    test_env.jj_cmd_ok(test_env.env_root(), &["init", "repo", "--git"]);
+    let repo_path = test_env.env_root().join("repo");
+    std::fs::write(repo_path.join("file1"), "").unwrap();
+    test_env.jj_cmd_ok(&repo_path, &["status"]);
+    let op_id = test_env.current_operation_id(&repo_path);
+
+    // A file added since the last snapshot isn't picked up, and no operation
+    // is recorded
+    std::fs::write(repo_path.join("file2"), "").unwrap();
+    let stdout =
+        test_env.jj_cmd_success(&repo_path, &["util", "completion-data", "--kind", "paths"]);
+    assert_snapshot!(stdout, @r###"
+    file1
+    "###);
+    assert_eq!(test_env.current_operation_id(&repo_path), op_id);
+}
@@ -76,3 +76,61 @@ fn test_show_relative_timestamps() {
         (no description set)
     "###);
 }
+
+#[test]
+fn test_show_stat_and_paths() {
+    let test_env = TestEnvironment::default();
+    test_env.jj_cmd_ok(test_env.env_root(), &["init", "repo", "--git"]);
+    let repo_path = test_env.env_root().join("repo");
+    std::fs::write(repo_path.join("file1"), "foo\n").unwrap();
+    std::fs::write(repo_path.join("file2"), "bar\n").unwrap();
+    test_env.jj_cmd_ok(&repo_path, &["describe", "-m", "first"]);
+    test_env.jj_cmd_ok(&repo_path, &["new", "-m", "second"]);
+    std::fs::write(repo_path.join("file1"), "foo\nbaz\n").unwrap();
+    std::fs::write(repo_path.join("file2"), "qux\n").unwrap();
+
+    let stdout = test_env.jj_cmd_success(&repo_path, &["show", "-T", "description", "--stat"]);
+    insta::assert_snapshot!(stdout, @r###"
+    second
+    file1 | 1 +
+    file2 | 2 +-
+    2 files changed, 2 insertions(+), 1 deletion(-)
+    "###);
+
+    let stdout = test_env.jj_cmd_success(
+        &repo_path,
+        &["show", "-T", "description", "--summary", "@", "file2"],
+    );
+    insta::assert_snapshot!(stdout, @r###"
+    second
+    M file2
+    "###);
+}
+
+#[test]
+fn test_show_merge_with_resolved_conflict() {
+    let test_env = TestEnvironment::default();
+    test_env.jj_cmd_ok(test_env.env_root(), &["init", "repo", "--git"]);
+    let repo_path = test_env.env_root().join("repo");
+    std::fs::write(repo_path.join("file"), "base\n").unwrap();
+    std::fs::write(repo_path.join("other"), "base\n").unwrap();
+    test_env.jj_cmd_ok(&repo_path, &["describe", "-m", "base"]);
+    test_env.jj_cmd_ok(&repo_path, &["branch", "create", "base"]);
+    test_env.jj_cmd_ok(&repo_path, &["new", "-m", "left"]);
+    std::fs::write(repo_path.join("file"), "left\n").unwrap();
+    std::fs::write(repo_path.join("other"), "changed\n").unwrap();
+    test_env.jj_cmd_ok(&repo_path, &["branch", "create", "left"]);
+    test_env.jj_cmd_ok(&repo_path, &["new", "-m", "right", "base"]);
+    std::fs::write(repo_path.join("file"), "right\n").unwrap();
+    test_env.jj_cmd_ok(&repo_path, &["branch", "create", "right"]);
+    test_env.jj_cmd_ok(&repo_path, &["new", "-m", "merge", "left", "right"]);
+    std::fs::write(repo_path.join("file"), "resolved\n").unwrap();
+
+    // Only the manual conflict resolution is shown, not the changes that were
+    // merged cleanly from the parents.
+    let stdout = test_env.jj_cmd_success(&repo_path, &["show", "-T", "description", "--summary"]);
+    insta::assert_snapshot!(stdout, @r###"
+    merge
+    M file
+    "###);
+}
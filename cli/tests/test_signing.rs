@@ -0,0 +1,154 @@
+// Copyright 2024 The Jujutsu Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+#![cfg(feature = "gpg")]
+
+use std::path::Path;
+
+use crate::common::TestEnvironment;
+
+const SIGNATURE_TEMPLATE: &str = r#"
+separate(" ",
+  description.first_line(),
+  signature,
+  signature.key(),
+  signature.display(),
+) ++ "\n"
+"#;
+
+/// Creates commits signed by a good, an expired and an unknown key, and an
+/// unsigned one.
+fn create_signed_commits(test_env: &TestEnvironment, repo_path: &Path) {
+    test_env.jj_cmd_ok(repo_path, &["describe", "-m", "good"]);
+    test_env.jj_cmd_ok(
+        repo_path,
+        &[
+            "new",
+            "-m",
+            "expired",
+            "--config-toml=signing.key='expired'",
+        ],
+    );
+    test_env.jj_cmd_ok(
+        repo_path,
+        &[
+            "new",
+            "-m",
+            "unknown",
+            "--config-toml=signing.key='unknown'",
+        ],
+    );
+    test_env.jj_cmd_ok(
+        repo_path,
+        &[
+            "new",
+            "root()",
+            "-m",
+            "unsigned",
+            "--config-toml=signing.sign-all=false",
+        ],
+    );
+}
+
+fn count_verifications(log_path: &Path) -> usize {
+    let log = std::fs::read_to_string(log_path).unwrap();
+    log.lines().filter(|line| line.contains("--verify")).count()
+}
+
+#[test]
+fn test_signature_templates() {
+    let mut test_env = TestEnvironment::default();
+    test_env.set_up_fake_gpg();
+    test_env.jj_cmd_ok(test_env.env_root(), &["init", "repo", "--git"]);
+    let repo_path = test_env.env_root().join("repo");
+    create_signed_commits(&test_env, &repo_path);
+
+    let stdout = test_env.jj_cmd_success(
+        &repo_path,
+        &["log", "--no-graph", "-r=~root()", "-T", SIGNATURE_TEMPLATE],
+    );
+    insta::assert_snapshot!(stdout, @r###"
+    unsigned none
+    unknown unknown unknown
+    expired expired expired Test User <expired@example.com>
+    good good default Test User <default@example.com>
+    "###);
+
+    // Signed commits are true in boolean context
+    let stdout = test_env.jj_cmd_success(
+        &repo_path,
+        &[
+            "log",
+            "--no-graph",
+            "-r=~root()",
+            "-T",
+            r#"if(signature, "signed", "unsigned") ++ " " ++ signature.status() ++ "\n""#,
+        ],
+    );
+    insta::assert_snapshot!(stdout, @r###"
+    unsigned none
+    signed unknown
+    signed expired
+    signed good
+    "###);
+
+    // The signature is labeled with its status
+    let stdout = test_env.jj_cmd_success(
+        &repo_path,
+        &[
+            "log",
+            "--no-graph",
+            "-r=description(expired)",
+            "--color=always",
+            "-T",
+            "signature",
+        ],
+    );
+    insta::assert_snapshot!(stdout, @"[38;5;3mexpired[39m");
+}
+
+#[test]
+fn test_signature_verified_lazily() {
+    let mut test_env = TestEnvironment::default();
+    let log_path = test_env.set_up_fake_gpg();
+    test_env.jj_cmd_ok(test_env.env_root(), &["init", "repo", "--git"]);
+    let repo_path = test_env.env_root().join("repo");
+    create_signed_commits(&test_env, &repo_path);
+
+    // Nothing is verified if the template doesn't show signatures
+    std::fs::write(&log_path, "").unwrap();
+    test_env.jj_cmd_success(&repo_path, &["log", "-r=all()", "-T", "description"]);
+    assert_eq!(count_verifications(&log_path), 0);
+
+    // Only the rendered commits are verified
+    test_env.jj_cmd_success(
+        &repo_path,
+        &["log", "-r=description(good)", "-T", "signature"],
+    );
+    assert_eq!(count_verifications(&log_path), 1);
+
+    // Each signature is verified once, even if it's shown several times, and
+    // the signatures of the rendered commits are verified by a single process
+    std::fs::write(&log_path, "").unwrap();
+    test_env.jj_cmd_success(
+        &repo_path,
+        &[
+            "log",
+            "-r=all()",
+            "-T",
+            "signature ++ signature.status() ++ signature.display()",
+        ],
+    );
+    assert_eq!(count_verifications(&log_path), 1);
+}
@@ -14,7 +14,7 @@
 
 use std::path::Path;
 
-use crate::common::TestEnvironment;
+use crate::common::{get_stderr_string, TestEnvironment};
 
 #[test]
 fn test_split_by_paths() {
@@ -119,7 +119,7 @@ fn test_split_by_paths() {
     let (stdout, stderr) = test_env.jj_cmd_ok(&repo_path, &["split", "-r", "@-", "nonexistent"]);
     insta::assert_snapshot!(stdout, @"");
     insta::assert_snapshot!(stderr, @r###"
-    The given paths do not match any file: nonexistent
+    Warning: The given paths do not match any file: nonexistent
     Rebased 1 descendant commits
     First part: qpvuntsm 7086b0bc (empty) (no description set)
     Second part: lylxulpl 2252ed18 (no description set)
@@ -231,6 +231,183 @@ JJ: Lines starting with "JJ: " (like this one) will be removed.
     "###);
 }
 
+fn init_hunks_repo(test_env: &mut TestEnvironment) -> std::path::PathBuf {
+    test_env.jj_cmd_ok(test_env.env_root(), &["init", "repo", "--git"]);
+    let repo_path = test_env.env_root().join("repo");
+    test_env.add_config(r#"templates.commit_summary = 'description.first_line()'"#);
+    // Keep the descriptions when asked for them
+    test_env.set_up_fake_editor();
+    std::fs::write(repo_path.join("file1"), "1\n2\n3\n4\n5\n").unwrap();
+    test_env.jj_cmd_ok(&repo_path, &["commit", "-m", "base"]);
+    // Two hunks in file1, and one in file2
+    std::fs::write(repo_path.join("file1"), "1x\n2\n3\n4\n5x\n").unwrap();
+    std::fs::write(repo_path.join("file2"), "foo\n").unwrap();
+    test_env.jj_cmd_ok(&repo_path, &["describe", "-m", "change"]);
+    repo_path
+}
+
+#[test]
+fn test_split_hunks_from() {
+    let mut test_env = TestEnvironment::default();
+    let repo_path = init_hunks_repo(&mut test_env);
+    let selection_path = test_env.env_root().join("selection");
+
+    std::fs::write(&selection_path, "# The first hunk\nfile1:1\n").unwrap();
+    let (stdout, stderr) = test_env.jj_cmd_ok(
+        &repo_path,
+        &["split", "--hunks-from", selection_path.to_str().unwrap()],
+    );
+    insta::assert_snapshot!(stdout, @"");
+    insta::assert_snapshot!(stderr, @r###"
+    First part: change
+    Second part: change
+    Working copy now at: change
+    Parent commit      : change
+    "###);
+    let stdout = test_env.jj_cmd_success(&repo_path, &["cat", "-r", "@-", "file1"]);
+    insta::assert_snapshot!(stdout, @r###"
+    1x
+    2
+    3
+    4
+    5
+    "###);
+    let stdout = test_env.jj_cmd_success(&repo_path, &["diff", "--summary", "-r", "@"]);
+    insta::assert_snapshot!(stdout, @r###"
+    M file1
+    A file2
+    "###);
+
+    // In the second part, the remaining change to file1 is its first hunk
+    std::fs::write(&selection_path, "file1:1\nfile2\n").unwrap();
+    test_env.jj_cmd_ok(
+        &repo_path,
+        &["split", "--hunks-from", selection_path.to_str().unwrap()],
+    );
+    let stdout = test_env.jj_cmd_success(&repo_path, &["cat", "-r", "@-", "file1"]);
+    insta::assert_snapshot!(stdout, @r###"
+    1x
+    2
+    3
+    4
+    5x
+    "###);
+    let stdout = test_env.jj_cmd_success(&repo_path, &["diff", "--summary", "-r", "@-"]);
+    insta::assert_snapshot!(stdout, @r###"
+    M file1
+    A file2
+    "###);
+    let stdout = test_env.jj_cmd_success(&repo_path, &["diff", "--summary", "-r", "@"]);
+    insta::assert_snapshot!(stdout, @"");
+
+    // Paths and hunks that don't exist are errors
+    std::fs::write(&selection_path, "file3\n").unwrap();
+    let stderr = test_env.jj_cmd_failure(
+        &repo_path,
+        &[
+            "split",
+            "-r",
+            "@--",
+            "--hunks-from",
+            selection_path.to_str().unwrap(),
+        ],
+    );
+    insta::assert_snapshot!(stderr, @r###"
+    Error: Failed to select changes
+    Caused by: There are no changes to "file3"
+    "###);
+    std::fs::write(&selection_path, "file1:1,2\n").unwrap();
+    let stderr = test_env.jj_cmd_failure(
+        &repo_path,
+        &[
+            "split",
+            "-r",
+            "@--",
+            "--hunks-from",
+            selection_path.to_str().unwrap(),
+        ],
+    );
+    insta::assert_snapshot!(stderr, @r###"
+    Error: Failed to select changes
+    Caused by: There is no hunk 2 in "file1", which has 1 hunks
+    "###);
+}
+
+#[test]
+fn test_split_checklist() {
+    let mut test_env = TestEnvironment::default();
+    let repo_path = init_hunks_repo(&mut test_env);
+
+    // Without a configured diff editor, the checklist reads the piped input
+    let (stdout, stderr) =
+        test_env.jj_cmd_stdin_ok(&repo_path, &["split"], "2\n1.2 9\nlist\ndone\n");
+    insta::assert_snapshot!(stdout, @r###"
+    1. [ ] file1
+       1.1 [ ]
+           -1
+           +1x
+       1.2 [ ]
+           -5
+           +5x
+    2. [ ] file2
+       2.1 [ ]
+           +foo
+    Enter the number of a file (like `1`) or of a hunk (like `1.2`) to toggle it.
+    Only the selected changes are included in the result.
+    Other commands: `all`, `none`, `list`, `done`, `quit`.
+    Select: Selected file2
+    Select: Selected hunk 2 of file1
+    Select: 1. [~] file1
+       1.1 [ ]
+           -1
+           +1x
+       1.2 [x]
+           -5
+           +5x
+    2. [x] file2
+       2.1 [x]
+           +foo
+    Select: 
+    "###);
+    insta::assert_snapshot!(stderr, @r###"
+    Using default editor ':checklist'; you can change this by setting ui.diff-editor
+    Warning: Unrecognized response: 9
+    First part: change
+    Second part: change
+    Working copy now at: change
+    Parent commit      : change
+    "###);
+    let stdout = test_env.jj_cmd_success(&repo_path, &["cat", "-r", "@-", "file1"]);
+    insta::assert_snapshot!(stdout, @r###"
+    1
+    2
+    3
+    4
+    5x
+    "###);
+    let stdout = test_env.jj_cmd_success(&repo_path, &["diff", "--summary", "-r", "@-"]);
+    insta::assert_snapshot!(stdout, @r###"
+    M file1
+    A file2
+    "###);
+    let stdout = test_env.jj_cmd_success(&repo_path, &["diff", "--summary", "-r", "@"]);
+    insta::assert_snapshot!(stdout, @r###"
+    M file1
+    "###);
+
+    // Quitting leaves the commit alone
+    let assert = test_env
+        .jj_cmd_stdin(&repo_path, &["split"], "all\nquit\n")
+        .assert()
+        .code(1);
+    let stderr = test_env.normalize_output(&get_stderr_string(&assert));
+    insta::assert_snapshot!(stderr, @r###"
+    Using default editor ':checklist'; you can change this by setting ui.diff-editor
+    Error: Failed to edit diff
+    Caused by: The selection was cancelled
+    "###);
+}
+
 fn get_log_output(test_env: &TestEnvironment, cwd: &Path) -> String {
     let template = r#"separate(" ", change_id.short(), empty, description)"#;
     test_env.jj_cmd_success(cwd, &["log", "-T", template])
@@ -256,13 +256,60 @@ fn test_squash_partial() {
     test_env.jj_cmd_ok(&repo_path, &["undo"]);
     let (stdout, stderr) = test_env.jj_cmd_ok(&repo_path, &["squash", "b"]);
     insta::assert_snapshot!(stderr, @r###"
-    warning: The argument "b" is being interpreted as a path. To specify a revset, pass -r "b" instead.
+    Warning: The argument "b" is being interpreted as a path. To specify a revset, pass -r "b" instead.
     Working copy now at: mzvwutvl 1c4e5596 c | (no description set)
     Parent commit      : kkmpptxz 16cc94b4 b | (no description set)
     "###);
     insta::assert_snapshot!(stdout, @"");
 }
 
+#[test]
+fn test_squash_hunks_from() {
+    let test_env = TestEnvironment::default();
+    test_env.jj_cmd_ok(test_env.env_root(), &["init", "repo", "--git"]);
+    let repo_path = test_env.env_root().join("repo");
+    test_env.add_config(r#"templates.commit_summary = 'description.first_line()'"#);
+    std::fs::write(repo_path.join("file"), "1\n2\n3\n4\n5\n").unwrap();
+    test_env.jj_cmd_ok(&repo_path, &["commit", "-m", "parent"]);
+    std::fs::write(repo_path.join("file"), "1x\n2\n3\n4\n5x\n").unwrap();
+    test_env.jj_cmd_ok(&repo_path, &["describe", "-m", "child"]);
+    let selection_path = test_env.env_root().join("selection");
+
+    // Only the second hunk is moved into the parent
+    std::fs::write(&selection_path, "file:2\n").unwrap();
+    let (stdout, stderr) = test_env.jj_cmd_ok(
+        &repo_path,
+        &["squash", "--hunks-from", selection_path.to_str().unwrap()],
+    );
+    insta::assert_snapshot!(stdout, @"");
+    insta::assert_snapshot!(stderr, @r###"
+    Working copy now at: child
+    Parent commit      : parent
+    "###);
+    let stdout = test_env.jj_cmd_success(&repo_path, &["print", "file", "-r", "@-"]);
+    insta::assert_snapshot!(stdout, @r###"
+    1
+    2
+    3
+    4
+    5x
+    "###);
+    let stdout = test_env.jj_cmd_success(&repo_path, &["diff", "--summary"]);
+    insta::assert_snapshot!(stdout, @r###"
+    M file
+    "###);
+
+    // Selecting nothing is an error, as with --interactive
+    std::fs::write(&selection_path, "# nothing\n").unwrap();
+    let stderr = test_env.jj_cmd_failure(
+        &repo_path,
+        &["squash", "--hunks-from", selection_path.to_str().unwrap()],
+    );
+    insta::assert_snapshot!(stderr, @r###"
+    Error: No changes selected
+    "###);
+}
+
 fn get_log_output(test_env: &TestEnvironment, repo_path: &Path) -> String {
     let template = r#"commit_id.short() ++ " " ++ branches"#;
     test_env.jj_cmd_success(repo_path, &["log", "-T", template])
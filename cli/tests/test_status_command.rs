@@ -12,7 +12,19 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use crate::common::TestEnvironment;
+use crate::common::{get_stdout_string, TestEnvironment};
+
+/// Creates a named pipe, which is a special file that can't be snapshotted.
+/// Unlike a socket, its path isn't limited in length.
+#[cfg(unix)]
+fn make_fifo(path: &std::path::Path) {
+    use std::os::unix::ffi::OsStrExt as _;
+
+    let path = std::ffi::CString::new(path.as_os_str().as_bytes()).unwrap();
+    // SAFETY: `path` is a valid NUL-terminated string.
+    let result = unsafe { libc::mkfifo(path.as_ptr(), 0o600) };
+    assert_eq!(result, 0, "{}", std::io::Error::last_os_error());
+}
 
 #[test]
 fn test_status_merge() {
@@ -38,6 +50,52 @@ fn test_status_merge() {
     "###);
 }
 
+#[test]
+fn test_status_path_conflict() {
+    let test_env = TestEnvironment::default();
+    test_env.jj_cmd_ok(test_env.env_root(), &["init", "repo", "--git"]);
+    let repo_path = test_env.env_root().join("repo");
+
+    test_env.jj_cmd_ok(&repo_path, &["new", "-m=left"]);
+    std::fs::write(repo_path.join("foo"), "file").unwrap();
+    test_env.jj_cmd_ok(&repo_path, &["branch", "create", "left"]);
+    test_env.jj_cmd_ok(&repo_path, &["new", "@-", "-m=right"]);
+    std::fs::create_dir(repo_path.join("foo")).unwrap();
+    std::fs::write(repo_path.join("foo").join("bar"), "dir").unwrap();
+    test_env.jj_cmd_ok(&repo_path, &["new", "left", "@"]);
+
+    // The directory is checked out in place, and the file next to it
+    assert_eq!(
+        std::fs::read_to_string(repo_path.join("foo").join("bar")).unwrap(),
+        "dir"
+    );
+    assert_eq!(
+        std::fs::read_to_string(repo_path.join("foo.jjconflict-file")).unwrap(),
+        "file"
+    );
+    let stdout = test_env.jj_cmd_success(&repo_path, &["status"]);
+    let summary: String = stdout
+        .lines()
+        .take_while(|line| !line.starts_with("Working copy :"))
+        .map(|line| format!("{line}\n"))
+        .collect();
+    insta::assert_snapshot!(summary, @r###"
+    The working copy is clean
+    There are path conflicts between files and directories at these paths:
+      foo: the file is checked out as foo.jjconflict-file
+      Delete the file to keep the directory, or replace the directory with the file to keep the file.
+    "###);
+
+    // Deleting the file resolves the conflict in favor of the directory
+    std::fs::remove_file(repo_path.join("foo.jjconflict-file")).unwrap();
+    let stdout = test_env.jj_cmd_success(&repo_path, &["status"]);
+    assert!(!stdout.contains("conflict"), "{stdout}");
+    let stdout = test_env.jj_cmd_success(&repo_path, &["files"]);
+    insta::assert_snapshot!(stdout, @r###"
+    foo/bar
+    "###);
+}
+
 // See https://github.com/martinvonz/jj/issues/2051.
 #[test]
 fn test_status_ignored_gitignore() {
@@ -62,3 +120,130 @@ fn test_status_ignored_gitignore() {
     Parent commit: zzzzzzzz 00000000 (empty) (no description set)
     "###);
 }
+
+#[test]
+fn test_status_sorted_when_not_a_terminal() {
+    // Changes are only streamed in discovery order when stdout is a terminal
+    let test_env = TestEnvironment::default();
+    test_env.jj_cmd_ok(test_env.env_root(), &["init", "repo", "--git"]);
+    let repo_path = test_env.env_root().join("repo");
+
+    for name in ["b", "dir1/c", "dir2/a", "dir2/d"] {
+        let path = repo_path.join(name);
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(path, "initial").unwrap();
+    }
+    test_env.jj_cmd_ok(&repo_path, &["new"]);
+    std::fs::write(repo_path.join("dir2").join("a"), "modified").unwrap();
+    std::fs::remove_file(repo_path.join("dir1").join("c")).unwrap();
+    std::fs::write(repo_path.join("a"), "added").unwrap();
+    std::fs::write(repo_path.join("dir1").join("e"), "added").unwrap();
+
+    let stdout = test_env.jj_cmd_success(&repo_path, &["status"]);
+    let summary: String = stdout
+        .lines()
+        .take_while(|line| !line.starts_with("Working copy :"))
+        .map(|line| format!("{line}\n"))
+        .collect();
+    insta::assert_snapshot!(summary, @r###"
+    Working copy changes:
+    A a
+    D dir1/c
+    A dir1/e
+    M dir2/a
+    "###);
+}
+
+#[cfg(unix)]
+#[test]
+fn test_status_special_file() {
+    let test_env = TestEnvironment::default();
+    test_env.jj_cmd_ok(test_env.env_root(), &["init", "repo", "--git"]);
+    let repo_path = test_env.env_root().join("repo");
+
+    make_fifo(&repo_path.join("fifo"));
+
+    let stdout = test_env.jj_cmd_success(&repo_path, &["status"]);
+    insta::assert_snapshot!(stdout, @r###"
+    The working copy is clean
+    Untracked paths that weren't snapshotted:
+      fifo: special file
+    Working copy : qpvuntsm 230dd059 (empty) (no description set)
+    Parent commit: zzzzzzzz 00000000 (empty) (no description set)
+    "###);
+}
+
+#[cfg(unix)]
+#[test]
+fn test_status_null_terminated() {
+    let test_env = TestEnvironment::default();
+    test_env.jj_cmd_ok(test_env.env_root(), &["init", "repo", "--git"]);
+    let repo_path = test_env.env_root().join("repo");
+    std::fs::write(repo_path.join("file"), "1\n").unwrap();
+    test_env.jj_cmd_ok(&repo_path, &["new"]);
+    std::fs::write(repo_path.join("file"), "2\n").unwrap();
+    std::fs::write(repo_path.join("new\nfile"), "\n").unwrap();
+    make_fifo(&repo_path.join("fifo"));
+
+    let stdout = test_env.jj_cmd_success(&repo_path, &["status", "-z"]);
+    assert_eq!(stdout, "M\tfile\0A\tnew\nfile\0?\tfifo\0");
+}
+
+#[test]
+fn test_status_exit_codes() {
+    let test_env = TestEnvironment::default();
+    test_env.jj_cmd_ok(test_env.env_root(), &["init", "repo", "--git"]);
+    let repo_path = test_env.env_root().join("repo");
+    test_env.add_config("operation.allow-at-op-mutations = true");
+
+    // Clean working copy
+    test_env
+        .jj_cmd(&repo_path, &["status", "--quiet"])
+        .assert()
+        .success()
+        .stdout("");
+
+    // Working copy with changes. Without --quiet or --check, the exit code is
+    // always 0.
+    std::fs::write(repo_path.join("file"), "base").unwrap();
+    test_env
+        .jj_cmd(&repo_path, &["status", "--quiet"])
+        .assert()
+        .code(1)
+        .stdout("");
+    test_env.jj_cmd_ok(&repo_path, &["status"]);
+    let assert = test_env
+        .jj_cmd(&repo_path, &["status", "--check"])
+        .assert()
+        .code(1);
+    let stdout = get_stdout_string(&assert);
+    assert!(
+        stdout.starts_with("Working copy changes:\nA file\n"),
+        "{stdout}"
+    );
+
+    // Working copy with conflicts
+    test_env.jj_cmd_ok(&repo_path, &["new", "-m=left"]);
+    std::fs::write(repo_path.join("file"), "left").unwrap();
+    test_env.jj_cmd_ok(&repo_path, &["branch", "create", "left"]);
+    test_env.jj_cmd_ok(&repo_path, &["new", "@-", "-m=right"]);
+    std::fs::write(repo_path.join("file"), "right").unwrap();
+    test_env.jj_cmd_ok(&repo_path, &["new", "left", "@"]);
+    test_env
+        .jj_cmd(&repo_path, &["status", "--quiet"])
+        .assert()
+        .code(2)
+        .stdout("");
+
+    // Conflicted branches take precedence over conflicts in the working copy
+    test_env.jj_cmd_ok(&repo_path, &["branch", "create", "foo"]);
+    test_env.jj_cmd_ok(
+        &repo_path,
+        &["--at-op=@-", "branch", "create", "foo", "-r", "left"],
+    );
+    test_env
+        .jj_cmd(&repo_path, &["status", "--quiet"])
+        .assert()
+        .code(3)
+        .stdout("");
+}
@@ -77,3 +77,122 @@ fn test_tag_list() {
         test_tag2
          "###);
 }
+
+#[test]
+fn test_tag_create_delete() {
+    let test_env = TestEnvironment::default();
+    let workspace_root = test_env.env_root().join("repo");
+    let git_repo = git2::Repository::init(&workspace_root).unwrap();
+    set_up_tagged_git_repo(&git_repo);
+    test_env.jj_cmd_ok(&workspace_root, &["init", "--git-repo", "."]);
+
+    let (stdout, stderr) =
+        test_env.jj_cmd_ok(&workspace_root, &["tag", "create", "v1.0", "-r", "main"]);
+    insta::assert_snapshot!(stdout, @"");
+    insta::assert_snapshot!(stderr, @"");
+    // The tag is exported as a lightweight tag
+    let main_id = git_repo.revparse_single("main").unwrap().id();
+    let git_ref = git_repo.find_reference("refs/tags/v1.0").unwrap();
+    assert_eq!(git_ref.target(), Some(main_id));
+    let stdout = test_env.jj_cmd_success(
+        &workspace_root,
+        &["log", "--no-graph", "-r", "tags('v1.0')", "-T", "tags"],
+    );
+    insta::assert_snapshot!(stdout, @"test_tag test_tag2 v1.0");
+
+    let stderr = test_env.jj_cmd_failure(&workspace_root, &["tag", "create", "v1.0"]);
+    insta::assert_snapshot!(stderr, @r###"
+    Error: Tag already exists: v1.0
+    Hint: Use --force to move it.
+    "###);
+    let stderr = test_env.jj_cmd_failure(&workspace_root, &["tag", "create", "v1..0"]);
+    insta::assert_snapshot!(stderr, @r###"
+    Error: Invalid tag name: "v1..0"
+    Caused by: Name contains ".."
+    "###);
+
+    // Annotated tags from Git are protected
+    let stderr = test_env.jj_cmd_failure(&workspace_root, &["tag", "create", "test_tag"]);
+    insta::assert_snapshot!(stderr, @r###"
+    Error: Tag test_tag is an annotated tag imported from Git
+    Hint: Use --force to replace it with a lightweight tag.
+    "###);
+    let stderr = test_env.jj_cmd_failure(&workspace_root, &["tag", "delete", "test_tag"]);
+    insta::assert_snapshot!(stderr, @r###"
+    Error: Tag test_tag is an annotated tag imported from Git
+    Hint: Use --force to delete it.
+    "###);
+    let stderr = test_env.jj_cmd_failure(&workspace_root, &["tag", "delete", "unknown"]);
+    insta::assert_snapshot!(stderr, @r###"
+    Error: No such tag: unknown
+    "###);
+
+    let (stdout, stderr) = test_env.jj_cmd_ok(
+        &workspace_root,
+        &["tag", "delete", "--force", "test_tag", "v1.0"],
+    );
+    insta::assert_snapshot!(stdout, @"");
+    insta::assert_snapshot!(stderr, @"");
+    assert!(git_repo.find_reference("refs/tags/test_tag").is_err());
+    assert!(git_repo.find_reference("refs/tags/v1.0").is_err());
+    insta::assert_snapshot!(test_env.jj_cmd_success(&workspace_root, &["tag", "list"]), @r###"
+    test_tag2
+    "###);
+}
+
+#[test]
+fn test_tag_case_insensitive() {
+    let test_env = TestEnvironment::default();
+    test_env.jj_cmd_ok(test_env.env_root(), &["init", "repo", "--git"]);
+    let repo_path = test_env.env_root().join("repo");
+    test_env.add_config("ui.case-insensitive-refs = true");
+
+    test_env.jj_cmd_ok(&repo_path, &["describe", "-m", "first"]);
+    test_env.jj_cmd_ok(&repo_path, &["tag", "create", "Release"]);
+    test_env.jj_cmd_ok(&repo_path, &["new", "-m", "second"]);
+
+    // Falls back to the tag whose name differs only by case
+    let stdout = test_env.jj_cmd_success(
+        &repo_path,
+        &["log", "--no-graph", "-r", "release", "-T", "description"],
+    );
+    insta::assert_snapshot!(stdout, @r###"
+    first
+    "###);
+
+    // Creating a tag that differs only by case needs --force
+    let stderr = test_env.jj_cmd_failure(&repo_path, &["tag", "create", "release"]);
+    insta::assert_snapshot!(stderr, @r###"
+    Error: Tag name release differs only by case from tag Release
+    Hint: Use --force to create it anyway.
+    "###);
+    test_env.jj_cmd_ok(&repo_path, &["tag", "create", "--force", "release"]);
+
+    // Exact matches still resolve, but other spellings are now ambiguous
+    let stdout = test_env.jj_cmd_success(
+        &repo_path,
+        &["log", "--no-graph", "-r", "release", "-T", "description"],
+    );
+    insta::assert_snapshot!(stdout, @r###"
+    second
+    "###);
+    let stderr = test_env.jj_cmd_failure(&repo_path, &["log", "-r", "RELEASE"]);
+    insta::assert_snapshot!(stderr, @r###"
+    Error: Revision "RELEASE" matches more than one tag ignoring case
+    Hint: Did you mean "Release", "release"?
+    "###);
+
+    // Without the setting, only exact names resolve
+    let stderr = test_env.jj_cmd_failure(
+        &repo_path,
+        &[
+            "log",
+            "-r",
+            "RELEASE",
+            "--config-toml=ui.case-insensitive-refs=false",
+        ],
+    );
+    insta::assert_snapshot!(stderr, @r###"
+    Error: Revision "RELEASE" doesn't exist
+    "###);
+}
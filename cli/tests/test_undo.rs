@@ -340,7 +340,7 @@ fn test_branch_track_untrack_undo() {
     insta::assert_snapshot!(get_branch_output(&test_env, &repo_path), @r###"
     feature1: qpvuntsm 270721f5 (empty) commit
       @origin: qpvuntsm 270721f5 (empty) commit
-    feature2 (deleted)
+    feature2 (deleted, will be pushed)
       @origin: qpvuntsm 270721f5 (empty) commit
       (this branch will be *deleted permanently* on the remote on the next `jj git push`. Use `jj branch forget` to prevent this)
     "###);
@@ -360,7 +360,7 @@ fn test_branch_track_untrack_undo() {
     insta::assert_snapshot!(get_branch_output(&test_env, &repo_path), @r###"
     feature1: qpvuntsm 270721f5 (empty) commit
       @origin: qpvuntsm 270721f5 (empty) commit
-    feature2 (deleted)
+    feature2 (deleted, will be pushed)
       @origin: qpvuntsm 270721f5 (empty) commit
       (this branch will be *deleted permanently* on the remote on the next `jj git push`. Use `jj branch forget` to prevent this)
     "###);
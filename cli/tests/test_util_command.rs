@@ -84,6 +84,17 @@ fn test_gc_operation_log() {
 
     // Remove some operations.
     test_env.jj_cmd_ok(&repo_path, &["operation", "abandon", "..@-"]);
+
+    // A dry run only reports what would be pruned.
+    let (_stdout, stderr) =
+        test_env.jj_cmd_ok(&repo_path, &["util", "gc", "--expire=now", "--dry-run"]);
+    assert!(stderr.starts_with("Would prune "), "{stderr}");
+    assert!(
+        stderr.ends_with("Dry-run requested, no changes were made.\n"),
+        "{stderr}"
+    );
+    test_env.jj_cmd_ok(&repo_path, &["debug", "operation", &op_to_remove]);
+
     test_env.jj_cmd_ok(&repo_path, &["util", "gc", "--expire=now"]);
 
     // Now this doesn't work.
@@ -271,51 +271,78 @@ fn test_workspaces_conflicting_edits() {
     ◉  c0d4a99ef98ada7da8dc73a778bbb747c4178385
     ◉  0000000000000000000000000000000000000000
     "###);
-    let stderr = test_env.jj_cmd_failure(&secondary_path, &["st"]);
+    // Read-only commands show the repo without snapshotting the working copy
+    let (stdout, stderr) = test_env.jj_cmd_ok(&secondary_path, &["st"]);
+    insta::assert_snapshot!(stdout, @r###"
+    The working copy is clean
+    Working copy : pmmvwywv a1896a17 (empty) (no description set)
+    Parent commit: qpvuntsm c0d4a99e (no description set)
+    "###);
     insta::assert_snapshot!(stderr, @r###"
-    Error: The working copy is stale (not updated since operation a07b009d6eba).
+    Warning: The working copy is stale (not updated since operation a07b009d6eba), so it wasn't snapshotted.
     Hint: Run `jj workspace update-stale` to update it.
-    See https://github.com/martinvonz/jj/blob/main/docs/working-copy.md#stale-working-copy for more information.
     "###);
-    // Same error on second run, and from another command
-    let stderr = test_env.jj_cmd_failure(&secondary_path, &["log"]);
+    // Commands that would record an operation are refused
+    let stderr = test_env.jj_cmd_failure(&secondary_path, &["describe", "-m", "edited"]);
     insta::assert_snapshot!(stderr, @r###"
-    Error: The working copy is stale (not updated since operation a07b009d6eba).
+    Warning: The working copy is stale (not updated since operation a07b009d6eba), so it wasn't snapshotted.
     Hint: Run `jj workspace update-stale` to update it.
+    Error: The working copy is stale (not updated since operation a07b009d6eba).
+    Hint: The working copy is at commit 265af0cdbcc7, but the workspace's working-copy commit is now a1896a17282f.
+    Run `jj workspace update-stale` to update it.
     See https://github.com/martinvonz/jj/blob/main/docs/working-copy.md#stale-working-copy for more information.
     "###);
-    let (stdout, stderr) = test_env.jj_cmd_ok(&secondary_path, &["workspace", "update-stale"]);
-    // It was detected that the working copy is now stale.
-    // Since there was an uncommitted change in the working copy, it should
-    // have been committed first (causing divergence)
+    // Commands that ask for input fail before starting the editor, so the
+    // input isn't lost. The editor would fail otherwise.
+    for args in [&["describe"][..], &["commit"], &["split", "file"]] {
+        let stderr = test_env.jj_cmd_failure(
+            &secondary_path,
+            &[args, &["--config-toml=ui.editor='false'"]].concat(),
+        );
+        assert!(
+            stderr.contains("Error: The working copy is stale"),
+            "{args:?}: {stderr}"
+        );
+    }
+    // The changes on disk are kept
+    assert_eq!(
+        std::fs::read_to_string(secondary_path.join("file")).unwrap(),
+        "changed in second\n"
+    );
+
+    // The changes in the working copy are applied on top of the updated
+    // working-copy commit. They conflict with the changes squashed from main.
+    let (stdout, stderr) = test_env.jj_cmd_ok(
+        &secondary_path,
+        &[
+            "workspace",
+            "update-stale",
+            r#"--config-toml=templates.commit_summary='change_id.short(8) ++ if(conflict, " (conflict)")'"#,
+        ],
+    );
     insta::assert_snapshot!(stdout, @"");
     insta::assert_snapshot!(stderr, @r###"
-    Concurrent modification detected, resolving automatically.
-    Rebased 1 descendant commits onto commits rewritten by other operation
-    Working copy now at: pmmvwywv?? a1896a17 (empty) (no description set)
+    Working copy now at: pmmvwywv (conflict)
     Added 0 files, modified 1 files, removed 0 files
     "###);
-    insta::assert_snapshot!(get_log_output(&test_env, &secondary_path),
-    @r###"
-    ◉  a3c96849ef9f124cbfc2416dc13bf17309d5020a (divergent)
-    │ ◉  fe8f41ed01d693b2d4365cd89e42ad9c531a939b default@
-    ├─╯
-    │ @  a1896a17282f19089a5cec44358d6609910e0513 secondary@ (divergent)
+    let template = r#"separate(" ", change_id.short(8), working_copies, if(conflict, "conflict"))"#;
+    let stdout = test_env.jj_cmd_success(&secondary_path, &["log", "-T", template, "-r", "all()"]);
+    insta::assert_snapshot!(stdout, @r###"
+    @  pmmvwywv secondary@ conflict
+    │ ◉  mzvwutvl default@
     ├─╯
-    ◉  c0d4a99ef98ada7da8dc73a778bbb747c4178385
-    ◉  0000000000000000000000000000000000000000
+    ◉  qpvuntsm
+    ◉  zzzzzzzz
     "###);
-    // The stale working copy should have been resolved by the previous command
-    let stdout = get_log_output(&test_env, &secondary_path);
-    assert!(!stdout.starts_with("The working copy is stale"));
+    let stdout = test_env.jj_cmd_success(&secondary_path, &["cat", "file"]);
     insta::assert_snapshot!(stdout, @r###"
-    ◉  a3c96849ef9f124cbfc2416dc13bf17309d5020a (divergent)
-    │ ◉  fe8f41ed01d693b2d4365cd89e42ad9c531a939b default@
-    ├─╯
-    │ @  a1896a17282f19089a5cec44358d6609910e0513 secondary@ (divergent)
-    ├─╯
-    ◉  c0d4a99ef98ada7da8dc73a778bbb747c4178385
-    ◉  0000000000000000000000000000000000000000
+    <<<<<<<
+    %%%%%%%
+    -contents
+    +changed in main
+    +++++++
+    changed in second
+    >>>>>>>
     "###);
 }
 
@@ -358,10 +385,13 @@ fn test_workspaces_updated_by_other() {
     ◉  c0d4a99ef98ada7da8dc73a778bbb747c4178385
     ◉  0000000000000000000000000000000000000000
     "###);
-    let stderr = test_env.jj_cmd_failure(&secondary_path, &["st"]);
+    let stderr = test_env.jj_cmd_failure(&secondary_path, &["new"]);
     insta::assert_snapshot!(stderr, @r###"
-    Error: The working copy is stale (not updated since operation a07b009d6eba).
+    Warning: The working copy is stale (not updated since operation a07b009d6eba), so it wasn't snapshotted.
     Hint: Run `jj workspace update-stale` to update it.
+    Error: The working copy is stale (not updated since operation a07b009d6eba).
+    Hint: The working copy is at commit 265af0cdbcc7, but the workspace's working-copy commit is now a1896a17282f.
+    Run `jj workspace update-stale` to update it.
     See https://github.com/martinvonz/jj/blob/main/docs/working-copy.md#stale-working-copy for more information.
     "###);
     let (stdout, stderr) = test_env.jj_cmd_ok(&secondary_path, &["workspace", "update-stale"]);
@@ -524,7 +554,6 @@ fn test_workspaces_update_stale_snapshot() {
     let (stdout, stderr) = test_env.jj_cmd_ok(&secondary_path, &["workspace", "update-stale"]);
     insta::assert_snapshot!(stdout, @"");
     insta::assert_snapshot!(stderr, @r###"
-    Concurrent modification detected, resolving automatically.
     Nothing to do (the working copy is not stale).
     "###);
 
@@ -721,6 +750,122 @@ fn test_workspaces_root() {
     "###);
 }
 
+/// Test that workspaces keep working after being moved along with the repo
+#[test]
+fn test_workspaces_moved_with_repo() {
+    let test_env = TestEnvironment::default();
+    let parent_path = test_env.env_root().join("parent");
+    std::fs::create_dir(&parent_path).unwrap();
+    test_env.jj_cmd_ok(&parent_path, &["init", "--git", "main"]);
+    let main_path = parent_path.join("main");
+    test_env.jj_cmd_ok(&main_path, &["workspace", "add", "../secondary"]);
+    let repo_file_path = parent_path.join("secondary").join(".jj").join("repo");
+    let repo_file = std::fs::read_to_string(&repo_file_path).unwrap();
+    insta::assert_snapshot!(repo_file.replace('\\', "/"), @"../../main/.jj/repo");
+
+    let renamed_path = test_env.env_root().join("renamed");
+    std::fs::rename(&parent_path, &renamed_path).unwrap();
+    let stdout = test_env.jj_cmd_success(
+        &renamed_path.join("secondary"),
+        &["log", "--no-graph", "-r=@", "-T=working_copies"],
+    );
+    insta::assert_snapshot!(stdout, @"secondary@");
+}
+
+/// Test that an absolute path to the repo written by older versions is fixed
+/// up after the workspaces were moved
+#[test]
+fn test_workspaces_moved_with_legacy_absolute_repo_path() {
+    let test_env = TestEnvironment::default();
+    let parent_path = test_env.env_root().join("parent");
+    std::fs::create_dir(&parent_path).unwrap();
+    test_env.jj_cmd_ok(&parent_path, &["init", "--git", "main"]);
+    let main_path = parent_path.join("main");
+    test_env.jj_cmd_ok(&main_path, &["workspace", "add", "../secondary"]);
+    let repo_path = main_path.join(".jj").join("repo");
+    std::fs::write(
+        parent_path.join("secondary").join(".jj").join("repo"),
+        repo_path.to_str().unwrap(),
+    )
+    .unwrap();
+
+    let renamed_path = test_env.env_root().join("renamed");
+    std::fs::rename(&parent_path, &renamed_path).unwrap();
+    let secondary_path = renamed_path.join("secondary");
+    let (stdout, stderr) = test_env.jj_cmd_ok(
+        &secondary_path,
+        &["log", "--no-graph", "-r=@", "-T=working_copies"],
+    );
+    insta::assert_snapshot!(stdout, @"secondary@");
+    insta::assert_snapshot!(stderr.replace('\\', "/"), @r###"
+    Warning: Updated $TEST_ENV/renamed/secondary/.jj/repo to point to ../../main/.jj/repo instead of $TEST_ENV/parent/main/.jj/repo, which no longer exists
+    "###);
+    let repo_file = std::fs::read_to_string(secondary_path.join(".jj").join("repo")).unwrap();
+    insta::assert_snapshot!(repo_file.replace('\\', "/"), @"../../main/.jj/repo");
+
+    // The path is only fixed once
+    test_env.jj_cmd_success(&secondary_path, &["log"]);
+}
+
+/// Test that a secondary workspace moved on its own still finds the repo
+#[test]
+fn test_workspaces_secondary_moved_alone() {
+    let test_env = TestEnvironment::default();
+    test_env.jj_cmd_ok(test_env.env_root(), &["init", "--git", "main"]);
+    let main_path = test_env.env_root().join("main");
+    test_env.jj_cmd_ok(&main_path, &["workspace", "add", "../secondary"]);
+
+    let nested_path = test_env.env_root().join("nested");
+    std::fs::create_dir(&nested_path).unwrap();
+    let secondary_path = nested_path.join("secondary");
+    std::fs::rename(test_env.env_root().join("secondary"), &secondary_path).unwrap();
+    let (stdout, stderr) = test_env.jj_cmd_ok(
+        &secondary_path,
+        &["log", "--no-graph", "-r=@", "-T=working_copies"],
+    );
+    insta::assert_snapshot!(stdout, @"secondary@");
+    insta::assert_snapshot!(stderr.replace('\\', "/"), @r###"
+    Updated $TEST_ENV/nested/secondary/.jj/repo to point to ../../../main/.jj/repo instead of ../../main/.jj/repo, which no longer exists
+    "###);
+    let repo_file = std::fs::read_to_string(secondary_path.join(".jj").join("repo")).unwrap();
+    insta::assert_snapshot!(repo_file.replace('\\', "/"), @"../../../main/.jj/repo");
+
+    // The path is only fixed once
+    let (_stdout, stderr) = test_env.jj_cmd_ok(&secondary_path, &["log"]);
+    insta::assert_snapshot!(stderr, @"");
+}
+
+/// Test relinking a workspace whose repo was moved on its own
+#[test]
+fn test_workspaces_relink_moved_repo() {
+    let test_env = TestEnvironment::default();
+    test_env.jj_cmd_ok(test_env.env_root(), &["init", "--git", "main"]);
+    let main_path = test_env.env_root().join("main");
+    let secondary_path = test_env.env_root().join("secondary");
+    test_env.jj_cmd_ok(&main_path, &["workspace", "add", "../secondary"]);
+
+    std::fs::rename(&main_path, test_env.env_root().join("moved")).unwrap();
+    let stderr = test_env.jj_cmd_failure(&secondary_path, &["log"]);
+    insta::assert_snapshot!(stderr.replace('\\', "/"), @r###"
+    Error: $TEST_ENV/secondary/.jj/repo points to ../../main/.jj/repo, which is not a repo
+    Hint: If the repo was moved, run `jj debug relink --repo PATH` with its new path.
+    "###);
+
+    let (stdout, stderr) = test_env.jj_cmd_ok(
+        &secondary_path,
+        &["debug", "relink", "--repo", "../moved/.jj/repo"],
+    );
+    insta::assert_snapshot!(stdout, @"");
+    insta::assert_snapshot!(stderr, @r###"
+    Updated the path to the repo
+    "###);
+    let stdout = test_env.jj_cmd_success(
+        &secondary_path,
+        &["log", "--no-graph", "-r=@", "-T=working_copies"],
+    );
+    insta::assert_snapshot!(stdout, @"secondary@");
+}
+
 fn get_log_output(test_env: &TestEnvironment, cwd: &Path) -> String {
     let template = r#"
     separate(" ",
@@ -0,0 +1,54 @@
+use criterion::{criterion_group, criterion_main, Criterion};
+use jj_lib::matchers::{FilesMatcher, Matcher, PrefixMatcher, UnionMatcher};
+use jj_lib::repo_path::{RepoPath, RepoPathBuf};
+
+/// Paths like `dir12/sub3/file45.rs`, as if passed by `xargs`. Every 100th
+/// path is a directory, which covers some of the other paths.
+fn input_paths(count: usize) -> (Vec<RepoPathBuf>, Vec<RepoPathBuf>) {
+    let mut prefixes = vec![];
+    let mut files = vec![];
+    for i in 0..count {
+        if i % 100 == 0 {
+            prefixes.push(RepoPathBuf::from_internal_string(format!(
+                "dir{}/sub{}",
+                i % 50,
+                i % 7
+            )));
+        } else {
+            files.push(RepoPathBuf::from_internal_string(format!(
+                "dir{}/sub{}/file{i}.rs",
+                i % 50,
+                i % 7
+            )));
+        }
+    }
+    (prefixes, files)
+}
+
+fn bench_prefix_and_files_matcher(c: &mut Criterion) {
+    let mut group = c.benchmark_group("bench_prefix_and_files_matcher");
+    let (prefixes, files) = input_paths(10_000);
+    group.bench_function("new_10k", |b| {
+        b.iter(|| (PrefixMatcher::new(&prefixes), FilesMatcher::new(&files)));
+    });
+    let prefix_matcher = PrefixMatcher::new(&prefixes);
+    let files_matcher = FilesMatcher::new(&files);
+    let matcher = UnionMatcher::new(&prefix_matcher, &files_matcher);
+    group.bench_function("matches_10k", |b| {
+        b.iter(|| files.iter().filter(|file| matcher.matches(file)).count());
+    });
+    let dirs = (0..50)
+        .map(|i| RepoPathBuf::from_internal_string(format!("dir{i}")))
+        .collect::<Vec<_>>();
+    group.bench_function("visit_10k", |b| {
+        b.iter(|| {
+            matcher.visit(RepoPath::root());
+            for dir in &dirs {
+                matcher.visit(dir);
+            }
+        });
+    });
+}
+
+criterion_group!(benches, bench_prefix_and_files_matcher);
+criterion_main!(benches);
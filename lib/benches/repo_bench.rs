@@ -0,0 +1,226 @@
+// Copyright 2024 The Jujutsu Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Benchmarks of tree diff, tree merge, snapshot, checkout, and revset
+//! evaluation on synthetic repos of parameterized size.
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+use jj_lib::matchers::EverythingMatcher;
+use jj_lib::repo_path::RepoPathBuf;
+use jj_lib::revset::{RevsetExpression, RevsetFilterPredicate};
+use jj_lib::settings::UserSettings;
+use testutils::{
+    commit_with_tree, create_synthetic_tree, create_tree, write_working_copy_file,
+    CommitGraphBuilder, TestRepo, TestRepoBackend, TestWorkspace,
+};
+
+/// (number of directories, files per directory)
+const TREE_SIZES: &[(usize, usize)] = &[(10, 10), (100, 10), (10, 1000)];
+
+/// Number of commits in the linear history used for revset benchmarks.
+const HISTORY_LENGTHS: &[usize] = &[100, 1000];
+
+fn bench_tree_diff(c: &mut Criterion) {
+    let mut group = c.benchmark_group("tree_diff");
+    for &(num_dirs, files_per_dir) in TREE_SIZES {
+        let test_repo = TestRepo::init();
+        let tree1 = create_synthetic_tree(&test_repo.repo, num_dirs, files_per_dir, "left");
+        let tree2 = create_synthetic_tree(&test_repo.repo, num_dirs, files_per_dir, "right");
+        let label = format!("{num_dirs}x{files_per_dir}");
+        group.bench_function(BenchmarkId::new("identical", &label), |b| {
+            b.iter(|| tree1.diff(&tree1, &EverythingMatcher).count())
+        });
+        group.bench_function(BenchmarkId::new("all_modified", &label), |b| {
+            b.iter(|| tree1.diff(&tree2, &EverythingMatcher).count())
+        });
+        let empty_tree = test_repo
+            .repo
+            .store()
+            .get_root_tree(&test_repo.repo.store().empty_merged_tree_id())
+            .unwrap();
+        group.bench_function(BenchmarkId::new("all_added", &label), |b| {
+            b.iter(|| empty_tree.diff(&tree1, &EverythingMatcher).count())
+        });
+    }
+}
+
+fn bench_checkout(c: &mut Criterion) {
+    let mut group = c.benchmark_group("checkout");
+    group.sample_size(10);
+    for &(num_dirs, files_per_dir) in TREE_SIZES {
+        let settings = testutils::user_settings();
+        // The test backend reads trees concurrently, which goes through the
+        // diff stream instead of the iterator the other backends use
+        let mut test_workspace = TestWorkspace::init_with_backend(&settings, TestRepoBackend::Git);
+        let repo = test_workspace.repo.clone();
+        let tree = create_synthetic_tree(&repo, num_dirs, files_per_dir, "contents");
+        let commit = commit_with_tree(repo.store(), tree.id());
+        let empty_commit = commit_with_tree(repo.store(), repo.store().empty_merged_tree_id());
+        let workspace = &mut test_workspace.workspace;
+        let label = format!("{num_dirs}x{files_per_dir}");
+        group.bench_function(BenchmarkId::new("initial", &label), |b| {
+            b.iter(|| {
+                workspace
+                    .check_out(repo.op_id().clone(), None, &commit)
+                    .unwrap();
+                workspace
+                    .check_out(repo.op_id().clone(), None, &empty_commit)
+                    .unwrap();
+            })
+        });
+    }
+}
+
+fn bench_tree_merge(c: &mut Criterion) {
+    let mut group = c.benchmark_group("tree_merge");
+    for &(num_dirs, files_per_dir) in TREE_SIZES {
+        let test_repo = TestRepo::init();
+        let base = create_synthetic_tree(&test_repo.repo, num_dirs, files_per_dir, "base");
+        let side = create_synthetic_tree(&test_repo.repo, num_dirs, files_per_dir, "side");
+        let label = format!("{num_dirs}x{files_per_dir}");
+        group.bench_function(BenchmarkId::new("one_side_modified", &label), |b| {
+            b.iter(|| side.merge(&base, &base).unwrap())
+        });
+    }
+}
+
+fn bench_snapshot(c: &mut Criterion) {
+    let mut group = c.benchmark_group("snapshot");
+    group.sample_size(10);
+    for &(num_dirs, files_per_dir) in TREE_SIZES {
+        let settings = testutils::user_settings();
+        let mut test_workspace = TestWorkspace::init(&settings);
+        let workspace_root = test_workspace.workspace.workspace_root().clone();
+        for dir in 0..num_dirs {
+            for file in 0..files_per_dir {
+                let path = RepoPathBuf::from_internal_string(format!("dir{dir}/file{file}"));
+                write_working_copy_file(&workspace_root, &path, "contents");
+            }
+        }
+        test_workspace.snapshot().unwrap();
+        let label = format!("{num_dirs}x{files_per_dir}");
+        group.bench_function(BenchmarkId::new("unchanged", &label), |b| {
+            b.iter(|| test_workspace.snapshot().unwrap())
+        });
+        let mut round = 0;
+        group.bench_function(BenchmarkId::new("one_modified_per_dir", &label), |b| {
+            b.iter(|| {
+                round += 1;
+                for dir in 0..num_dirs {
+                    let path = RepoPathBuf::from_internal_string(format!("dir{dir}/file0"));
+                    write_working_copy_file(&workspace_root, &path, &format!("round {round}"));
+                }
+                test_workspace.snapshot().unwrap()
+            })
+        });
+    }
+}
+
+fn bench_revset(c: &mut Criterion) {
+    let mut group = c.benchmark_group("revset");
+    for &num_commits in HISTORY_LENGTHS {
+        let settings = testutils::user_settings();
+        let test_repo = TestRepo::init();
+        let mut tx = test_repo.repo.start_transaction(&settings);
+        let mut graph_builder = CommitGraphBuilder::new(&settings, tx.mut_repo());
+        let mut head = graph_builder.initial_commit();
+        for _ in 1..num_commits {
+            head = graph_builder.commit_with_parents(&[&head]);
+        }
+        let repo = tx.commit("test").unwrap();
+        let label = num_commits.to_string();
+        group.bench_function(BenchmarkId::new("all", &label), |b| {
+            b.iter(|| {
+                RevsetExpression::all()
+                    .evaluate_programmatic(repo.as_ref())
+                    .unwrap()
+                    .iter()
+                    .count()
+            })
+        });
+        group.bench_function(BenchmarkId::new("heads_of_ancestors", &label), |b| {
+            b.iter(|| {
+                RevsetExpression::commit(head.id().clone())
+                    .ancestors()
+                    .heads()
+                    .evaluate_programmatic(repo.as_ref())
+                    .unwrap()
+                    .iter()
+                    .count()
+            })
+        });
+    }
+}
+
+fn bench_file_revset(c: &mut Criterion) {
+    let mut group = c.benchmark_group("file_revset");
+    for &num_commits in HISTORY_LENGTHS {
+        for changed_path_filters in [false, true] {
+            let config = testutils::base_config()
+                .set_override("index.changed-path-filters", changed_path_filters)
+                .unwrap()
+                .build()
+                .unwrap();
+            let settings = UserSettings::from_config(config);
+            let test_repo = TestRepo::init_with_settings(&settings);
+            let repo = &test_repo.repo;
+            // Each commit modifies one file, the first one in every 10 commits
+            let mut tx = repo.start_transaction(&settings);
+            let mut parent_id = repo.store().root_commit_id().clone();
+            for i in 0..num_commits {
+                let path = RepoPathBuf::from_internal_string(format!("dir{}/file", i % 10));
+                let tree = create_tree(repo, &[(path.as_ref(), i.to_string().as_str())]);
+                let commit = tx
+                    .mut_repo()
+                    .new_commit(&settings, vec![parent_id], tree.id())
+                    .write()
+                    .unwrap();
+                parent_id = commit.id().clone();
+            }
+            let repo = tx.commit("test").unwrap();
+            let expression = RevsetExpression::filter(RevsetFilterPredicate::File(Some(vec![
+                RepoPathBuf::from_internal_string("dir0"),
+            ])));
+            let label = format!(
+                "{num_commits}/{}",
+                if changed_path_filters {
+                    "filters"
+                } else {
+                    "no_filters"
+                }
+            );
+            group.bench_function(BenchmarkId::new("one_dir", &label), |b| {
+                b.iter(|| {
+                    expression
+                        .clone()
+                        .evaluate_programmatic(repo.as_ref())
+                        .unwrap()
+                        .iter()
+                        .count()
+                })
+            });
+        }
+    }
+}
+
+criterion_group!(
+    benches,
+    bench_tree_diff,
+    bench_tree_merge,
+    bench_snapshot,
+    bench_checkout,
+    bench_revset,
+    bench_file_revset
+);
+criterion_main!(benches);
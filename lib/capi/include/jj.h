@@ -0,0 +1,154 @@
+#ifndef JJ_H
+#define JJ_H
+
+/* Generated by cbindgen from lib/capi/src/lib.rs. Do not edit. */
+
+#include <stdarg.h>
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+#include <stdlib.h>
+
+// How a file differs between two commits.
+typedef enum JjDiffKind {
+  // The file exists in both commits.
+  JJ_DIFF_KIND_MODIFIED = 0,
+  // The file only exists in the second commit.
+  JJ_DIFF_KIND_ADDED = 1,
+  // The file only exists in the first commit.
+  JJ_DIFF_KIND_REMOVED = 2,
+} JjDiffKind;
+
+// Result of a C API call.
+typedef enum JjStatus {
+  // The call succeeded.
+  JJ_STATUS_OK = 0,
+  // A pointer was null, or a string wasn't valid UTF-8 or a valid id.
+  JJ_STATUS_INVALID_ARGUMENT = 1,
+  // The workspace or repository couldn't be loaded.
+  JJ_STATUS_REPO_LOAD = 2,
+  // The revset couldn't be parsed, resolved, or evaluated.
+  JJ_STATUS_REVSET = 3,
+  // The commit or path doesn't exist.
+  JJ_STATUS_NOT_FOUND = 4,
+  // The caller-provided buffer is too small. The required size has been
+  // stored.
+  JJ_STATUS_BUFFER_TOO_SMALL = 5,
+  // The backend failed to read an object.
+  JJ_STATUS_BACKEND = 6,
+  // The path isn't a file (e.g. it's a directory, symlink, or submodule).
+  JJ_STATUS_NOT_A_FILE = 7,
+  // The library hit an internal error (a panic). The handle, if any, should
+  // not be used anymore except to free it.
+  JJ_STATUS_INTERNAL = 8,
+} JjStatus;
+
+// An open repository, loaded at its head operation.
+typedef struct JjRepo JjRepo;
+
+// A file that differs between two commits.
+typedef struct JjChangedFile {
+  // Repository-relative path using `/` as separator.
+  char *path;
+  // How the file changed.
+  enum JjDiffKind kind;
+} JjChangedFile;
+
+#ifdef __cplusplus
+extern "C" {
+#endif // __cplusplus
+
+// Returns a message describing the last error on the calling thread, or null
+// if no call has failed yet.
+//
+// The string is owned by the library and stays valid until the next failing
+// call on the same thread.
+const char *jj_last_error_message(void);
+
+// Opens the workspace at `workspace_path` and loads its repository at the
+// head operation. On success, stores a handle in `*out_repo` which must be
+// released with `jj_repo_free()`.
+//
+// The user's config is read from the same place as the `jj` CLI reads it
+// (including `$JJ_CONFIG`), followed by the repo's config.
+//
+// # Safety
+//
+// `workspace_path` must be a NUL-terminated string, and `out_repo` must be a
+// valid pointer.
+enum JjStatus jj_repo_open(const char *workspace_path, struct JjRepo **out_repo);
+
+// Releases a handle returned by `jj_repo_open()`. Null is ignored.
+//
+// # Safety
+//
+// `repo` must be null or a handle that hasn't been freed yet.
+void jj_repo_free(struct JjRepo *repo);
+
+// Evaluates `revset` and stores the hex ids of the resulting commits, in
+// index order (children before parents), in `*out_ids` and their number in
+// `*out_len`. The ids must be released with `jj_commit_ids_free()`.
+//
+// # Safety
+//
+// `repo` must be a valid handle, `revset` a NUL-terminated string, and
+// `out_ids` and `out_len` valid pointers.
+enum JjStatus jj_resolve_revset(const struct JjRepo *repo,
+                                const char *revset,
+                                char ***out_ids,
+                                size_t *out_len);
+
+// Releases ids returned by `jj_resolve_revset()`. Null is ignored.
+//
+// # Safety
+//
+// `ids` and `len` must be exactly as returned by `jj_resolve_revset()`.
+void jj_commit_ids_free(char **ids, size_t len);
+
+// Lists the files that differ between the commits `from_id` and `to_id`
+// (given as hex ids), sorted by path. The list is stored in `*out_files` and
+// its length in `*out_len`, and must be released with
+// `jj_changed_files_free()`.
+//
+// # Safety
+//
+// `repo` must be a valid handle, `from_id` and `to_id` NUL-terminated
+// strings, and `out_files` and `out_len` valid pointers.
+enum JjStatus jj_diff_files(const struct JjRepo *repo,
+                            const char *from_id,
+                            const char *to_id,
+                            struct JjChangedFile **out_files,
+                            size_t *out_len);
+
+// Releases a list returned by `jj_diff_files()`. Null is ignored.
+//
+// # Safety
+//
+// `files` and `len` must be exactly as returned by `jj_diff_files()`.
+void jj_changed_files_free(struct JjChangedFile *files, size_t len);
+
+// Reads the file at `path` in the commit `commit_id` (a hex id) into `buf`.
+// Conflicted files are read with conflict markers.
+//
+// The size of the contents is stored in `*out_len`. If it's larger than
+// `buf_len`, nothing is copied and `JJ_STATUS_BUFFER_TOO_SMALL` is returned,
+// so the call can be retried with a large enough buffer. `buf` may be null if
+// `buf_len` is 0.
+//
+// # Safety
+//
+// `repo` must be a valid handle, `commit_id` and `path` NUL-terminated
+// strings, `buf` valid for writing `buf_len` bytes, and `out_len` a valid
+// pointer.
+enum JjStatus jj_read_file(const struct JjRepo *repo,
+                           const char *commit_id,
+                           const char *path,
+                           uint8_t *buf,
+                           size_t buf_len,
+                           size_t *out_len);
+
+#ifdef __cplusplus
+} // extern "C"
+#endif // __cplusplus
+
+#endif /* JJ_H */
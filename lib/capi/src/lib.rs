@@ -0,0 +1,564 @@
+// Copyright 2024 The Jujutsu Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! A minimal C API for reading Jujutsu repositories.
+//!
+//! This is meant for tools like editor plugins, which want to compute diffs
+//! and read files (including conflicts) without spawning `jj` for every
+//! request. The API is read-only.
+//!
+//! Functions return a [`JjStatus`]. On failure, [`jj_last_error_message()`]
+//! describes the error. Every allocation returned by the API must be released
+//! with the matching `jj_*_free()` function.
+//!
+//! A [`JjRepo`] handle must not be used from several threads at the same
+//! time. It may be moved to another thread between calls.
+
+#![warn(missing_docs)]
+
+use std::cell::RefCell;
+use std::ffi::{c_char, CStr, CString};
+use std::io::Read as _;
+use std::panic::{self, AssertUnwindSafe};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::{env, ptr, slice};
+
+use itertools::Itertools as _;
+use jj_lib::backend::{BackendError, CommitId, TreeValue};
+use jj_lib::conflicts;
+use jj_lib::matchers::EverythingMatcher;
+use jj_lib::object_id::ObjectId as _;
+use jj_lib::repo::{ReadonlyRepo, Repo as _, StoreFactories};
+use jj_lib::repo_path::RepoPathBuf;
+use jj_lib::revset::{
+    self, DefaultSymbolResolver, RevsetAliasesMap, RevsetParseContext, RevsetWorkspaceContext,
+};
+use jj_lib::settings::UserSettings;
+use jj_lib::workspace::{default_working_copy_factories, Workspace};
+use pollster::FutureExt as _;
+
+/// Result of a C API call.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum JjStatus {
+    /// The call succeeded.
+    Ok = 0,
+    /// A pointer was null, or a string wasn't valid UTF-8 or a valid id.
+    InvalidArgument = 1,
+    /// The workspace or repository couldn't be loaded.
+    RepoLoad = 2,
+    /// The revset couldn't be parsed, resolved, or evaluated.
+    Revset = 3,
+    /// The commit or path doesn't exist.
+    NotFound = 4,
+    /// The caller-provided buffer is too small. The required size has been
+    /// stored.
+    BufferTooSmall = 5,
+    /// The backend failed to read an object.
+    Backend = 6,
+    /// The path isn't a file (e.g. it's a directory, symlink, or submodule).
+    NotAFile = 7,
+    /// The library hit an internal error (a panic). The handle, if any, should
+    /// not be used anymore except to free it.
+    Internal = 8,
+}
+
+/// How a file differs between two commits.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum JjDiffKind {
+    /// The file exists in both commits.
+    Modified = 0,
+    /// The file only exists in the second commit.
+    Added = 1,
+    /// The file only exists in the first commit.
+    Removed = 2,
+}
+
+/// A file that differs between two commits.
+#[repr(C)]
+#[derive(Debug)]
+pub struct JjChangedFile {
+    /// Repository-relative path using `/` as separator.
+    pub path: *mut c_char,
+    /// How the file changed.
+    pub kind: JjDiffKind,
+}
+
+/// An open repository, loaded at its head operation.
+pub struct JjRepo {
+    workspace: Workspace,
+    repo: Arc<ReadonlyRepo>,
+    settings: UserSettings,
+}
+
+thread_local! {
+    static LAST_ERROR: RefCell<Option<CString>> = const { RefCell::new(None) };
+}
+
+fn set_last_error(message: impl ToString) {
+    // Interior NUL bytes can't be represented, so drop them.
+    let message = message.to_string().replace('\0', "");
+    let message = CString::new(message).unwrap();
+    LAST_ERROR.with(|last| *last.borrow_mut() = Some(message));
+}
+
+fn fail(status: JjStatus, message: impl ToString) -> JjStatus {
+    set_last_error(message);
+    status
+}
+
+/// Runs the body of an API function. Unwinding across the FFI boundary is
+/// undefined behavior, so a panic is turned into `JjStatus::Internal`.
+fn guard(body: impl FnOnce() -> Result<(), JjStatus>) -> JjStatus {
+    match panic::catch_unwind(AssertUnwindSafe(body)) {
+        Ok(Ok(())) => JjStatus::Ok,
+        Ok(Err(status)) => status,
+        Err(payload) => {
+            let message = payload
+                .downcast_ref::<&str>()
+                .copied()
+                .or_else(|| payload.downcast_ref::<String>().map(String::as_str))
+                .unwrap_or("unknown panic");
+            fail(JjStatus::Internal, format!("Internal error: {message}"))
+        }
+    }
+}
+
+/// Returns the path to the user's config file or directory, the same way the
+/// `jj` CLI finds it.
+fn user_config_path() -> Option<PathBuf> {
+    if let Some(path) = env::var_os("JJ_CONFIG") {
+        return Some(PathBuf::from(path));
+    }
+    let platform_path = dirs::config_dir().map(|dir| dir.join("jj").join("config.toml"));
+    let home_path = dirs::home_dir().map(|dir| dir.join(".jjconfig.toml"));
+    [platform_path, home_path]
+        .into_iter()
+        .flatten()
+        .find(|path| path.exists())
+}
+
+fn add_config_files(
+    mut builder: config::ConfigBuilder<config::builder::DefaultState>,
+    path: &Path,
+) -> config::ConfigBuilder<config::builder::DefaultState> {
+    let mut files = vec![];
+    if path.is_dir() {
+        if let Ok(read_dir) = path.read_dir() {
+            files.extend(
+                read_dir
+                    .flatten()
+                    .map(|entry| entry.path())
+                    .filter(|path| path.is_file()),
+            );
+        }
+        files.sort();
+    } else {
+        files.push(path.to_owned());
+    }
+    for file in files {
+        builder = builder.add_source(
+            config::File::from(file)
+                .required(false)
+                .format(config::FileFormat::Toml),
+        );
+    }
+    builder
+}
+
+/// Loads the user's config, and the repo's config if `repo_path` is set. The
+/// `jj` CLI's built-in defaults and command-line overrides don't apply.
+fn load_settings(repo_path: Option<&Path>) -> Result<UserSettings, config::ConfigError> {
+    let mut builder = config::Config::builder();
+    if let Some(path) = user_config_path() {
+        builder = add_config_files(builder, &path);
+    }
+    if let Some(repo_path) = repo_path {
+        builder = add_config_files(builder, &repo_path.join("config.toml"));
+    }
+    Ok(UserSettings::from_config(builder.build()?))
+}
+
+/// Reads a NUL-terminated UTF-8 string argument.
+///
+/// # Safety
+///
+/// `ptr` must be null or point to a NUL-terminated string.
+unsafe fn str_arg<'a>(ptr: *const c_char, name: &str) -> Result<&'a str, JjStatus> {
+    if ptr.is_null() {
+        return Err(fail(JjStatus::InvalidArgument, format!("{name} is null")));
+    }
+    CStr::from_ptr(ptr).to_str().map_err(|_| {
+        fail(
+            JjStatus::InvalidArgument,
+            format!("{name} is not valid UTF-8"),
+        )
+    })
+}
+
+fn commit_id_arg(hex: &str, name: &str) -> Result<CommitId, JjStatus> {
+    CommitId::try_from_hex(hex).map_err(|_| {
+        fail(
+            JjStatus::InvalidArgument,
+            format!("{name} is not a commit id"),
+        )
+    })
+}
+
+fn into_c_string(s: String) -> *mut c_char {
+    // Paths and hex ids never contain NUL bytes.
+    CString::new(s).unwrap().into_raw()
+}
+
+/// Hands a vector over to the caller, who must free it by passing the
+/// pointer and length back to `free_boxed_slice()`.
+fn into_raw_parts<T>(items: Vec<T>) -> (*mut T, usize) {
+    let len = items.len();
+    let ptr = Box::into_raw(items.into_boxed_slice()) as *mut T;
+    (ptr, len)
+}
+
+/// # Safety
+///
+/// `ptr` and `len` must come from `into_raw_parts()`.
+unsafe fn free_boxed_slice<T>(ptr: *mut T, len: usize) -> Box<[T]> {
+    Box::from_raw(slice::from_raw_parts_mut(ptr, len))
+}
+
+impl JjRepo {
+    fn commit(&self, id: &CommitId) -> Result<jj_lib::commit::Commit, JjStatus> {
+        self.repo.store().get_commit(id).map_err(|err| match err {
+            BackendError::ObjectNotFound { .. } => {
+                fail(JjStatus::NotFound, format!("Commit {} not found", id.hex()))
+            }
+            err => fail(JjStatus::Backend, err),
+        })
+    }
+
+    fn resolve_revset(&self, revset_str: &str) -> Result<Vec<CommitId>, JjStatus> {
+        let workspace_root = self.workspace.workspace_root();
+        let context = RevsetParseContext {
+            aliases_map: &RevsetAliasesMap::new(),
+            user_email: self.settings.user_email(),
+            workspace: Some(RevsetWorkspaceContext {
+                cwd: workspace_root,
+                workspace_id: self.workspace.workspace_id(),
+                workspace_root,
+            }),
+        };
+        let expression =
+            revset::parse(revset_str, &context).map_err(|err| fail(JjStatus::Revset, err))?;
+        let repo = self.repo.as_ref();
+        let symbol_resolver = DefaultSymbolResolver::new(repo);
+        let revset = revset::optimize(expression)
+            .resolve_user_expression(repo, &symbol_resolver)
+            .map_err(|err| fail(JjStatus::Revset, err))?
+            .evaluate(repo)
+            .map_err(|err| fail(JjStatus::Revset, err))?;
+        Ok(revset.iter().collect())
+    }
+
+    fn read_file(&self, commit_id: &CommitId, path: &str) -> Result<Vec<u8>, JjStatus> {
+        let path = RepoPathBuf::from_relative_path(Path::new(path))
+            .filter(|path| !path.is_root())
+            .ok_or_else(|| fail(JjStatus::InvalidArgument, "path is not a valid file path"))?;
+        let commit = self.commit(commit_id)?;
+        let tree = commit.tree().map_err(|err| fail(JjStatus::Backend, err))?;
+        let value = tree.path_value(&path);
+        let store = self.repo.store();
+        match value.into_resolved() {
+            Ok(None) => Err(fail(
+                JjStatus::NotFound,
+                format!(
+                    "No file {} in commit {}",
+                    path.as_internal_file_string(),
+                    commit_id.hex()
+                ),
+            )),
+            Ok(Some(TreeValue::File { id, .. })) => {
+                let mut reader = store
+                    .read_file(&path, &id)
+                    .map_err(|err| fail(JjStatus::Backend, err))?;
+                let mut contents = vec![];
+                reader
+                    .read_to_end(&mut contents)
+                    .map_err(|err| fail(JjStatus::Backend, err))?;
+                Ok(contents)
+            }
+            Ok(Some(_)) => Err(fail(
+                JjStatus::NotAFile,
+                format!("{} is not a file", path.as_internal_file_string()),
+            )),
+            Err(conflict) => {
+                // Conflicts are returned with conflict markers, the same way
+                // they're written to the working copy.
+                let mut contents = vec![];
+                conflicts::materialize(&conflict, store, &path, &mut contents)
+                    .block_on()
+                    .map_err(|err| fail(JjStatus::Backend, err))?;
+                Ok(contents)
+            }
+        }
+    }
+}
+
+/// Returns a message describing the last error on the calling thread, or null
+/// if no call has failed yet.
+///
+/// The string is owned by the library and stays valid until the next failing
+/// call on the same thread.
+#[no_mangle]
+pub extern "C" fn jj_last_error_message() -> *const c_char {
+    panic::catch_unwind(|| {
+        LAST_ERROR.with(|last| {
+            last.borrow()
+                .as_ref()
+                .map_or(ptr::null(), |message| message.as_ptr())
+        })
+    })
+    .unwrap_or(ptr::null())
+}
+
+/// Opens the workspace at `workspace_path` and loads its repository at the
+/// head operation. On success, stores a handle in `*out_repo` which must be
+/// released with `jj_repo_free()`.
+///
+/// The user's config is read from the same place as the `jj` CLI reads it
+/// (including `$JJ_CONFIG`), followed by the repo's config.
+///
+/// # Safety
+///
+/// `workspace_path` must be a NUL-terminated string, and `out_repo` must be a
+/// valid pointer.
+#[no_mangle]
+pub unsafe extern "C" fn jj_repo_open(
+    workspace_path: *const c_char,
+    out_repo: *mut *mut JjRepo,
+) -> JjStatus {
+    guard(|| {
+        let workspace_path = str_arg(workspace_path, "workspace_path")?;
+        if out_repo.is_null() {
+            return Err(fail(JjStatus::InvalidArgument, "out_repo is null"));
+        }
+        let user_settings = load_settings(None).map_err(|err| fail(JjStatus::RepoLoad, err))?;
+        let workspace = Workspace::load(
+            &user_settings,
+            Path::new(workspace_path),
+            &StoreFactories::default(),
+            &default_working_copy_factories(),
+        )
+        .map_err(|err| fail(JjStatus::RepoLoad, err))?;
+        let settings = load_settings(Some(workspace.repo_path()))
+            .map_err(|err| fail(JjStatus::RepoLoad, err))?;
+        let repo = workspace
+            .repo_loader()
+            .load_at_head(&settings)
+            .map_err(|err| fail(JjStatus::RepoLoad, err))?;
+        let handle = Box::new(JjRepo {
+            workspace,
+            repo,
+            settings,
+        });
+        *out_repo = Box::into_raw(handle);
+        Ok(())
+    })
+}
+
+/// Releases a handle returned by `jj_repo_open()`. Null is ignored.
+///
+/// # Safety
+///
+/// `repo` must be null or a handle that hasn't been freed yet.
+#[no_mangle]
+pub unsafe extern "C" fn jj_repo_free(repo: *mut JjRepo) {
+    guard(|| {
+        if !repo.is_null() {
+            drop(Box::from_raw(repo));
+        }
+        Ok(())
+    });
+}
+
+/// Evaluates `revset` and stores the hex ids of the resulting commits, in
+/// index order (children before parents), in `*out_ids` and their number in
+/// `*out_len`. The ids must be released with `jj_commit_ids_free()`.
+///
+/// # Safety
+///
+/// `repo` must be a valid handle, `revset` a NUL-terminated string, and
+/// `out_ids` and `out_len` valid pointers.
+#[no_mangle]
+pub unsafe extern "C" fn jj_resolve_revset(
+    repo: *const JjRepo,
+    revset: *const c_char,
+    out_ids: *mut *mut *mut c_char,
+    out_len: *mut usize,
+) -> JjStatus {
+    guard(|| {
+        let repo = repo
+            .as_ref()
+            .ok_or_else(|| fail(JjStatus::InvalidArgument, "repo is null"))?;
+        let revset = str_arg(revset, "revset")?;
+        if out_ids.is_null() || out_len.is_null() {
+            return Err(fail(JjStatus::InvalidArgument, "output pointer is null"));
+        }
+        let commit_ids = repo.resolve_revset(revset)?;
+        let ids = commit_ids
+            .iter()
+            .map(|id| into_c_string(id.hex()))
+            .collect();
+        (*out_ids, *out_len) = into_raw_parts(ids);
+        Ok(())
+    })
+}
+
+/// Releases ids returned by `jj_resolve_revset()`. Null is ignored.
+///
+/// # Safety
+///
+/// `ids` and `len` must be exactly as returned by `jj_resolve_revset()`.
+#[no_mangle]
+pub unsafe extern "C" fn jj_commit_ids_free(ids: *mut *mut c_char, len: usize) {
+    guard(|| {
+        if !ids.is_null() {
+            for id in free_boxed_slice(ids, len).iter() {
+                drop(CString::from_raw(*id));
+            }
+        }
+        Ok(())
+    });
+}
+
+/// Lists the files that differ between the commits `from_id` and `to_id`
+/// (given as hex ids), sorted by path. The list is stored in `*out_files` and
+/// its length in `*out_len`, and must be released with
+/// `jj_changed_files_free()`.
+///
+/// # Safety
+///
+/// `repo` must be a valid handle, `from_id` and `to_id` NUL-terminated
+/// strings, and `out_files` and `out_len` valid pointers.
+#[no_mangle]
+pub unsafe extern "C" fn jj_diff_files(
+    repo: *const JjRepo,
+    from_id: *const c_char,
+    to_id: *const c_char,
+    out_files: *mut *mut JjChangedFile,
+    out_len: *mut usize,
+) -> JjStatus {
+    guard(|| {
+        let repo = repo
+            .as_ref()
+            .ok_or_else(|| fail(JjStatus::InvalidArgument, "repo is null"))?;
+        let from_id = str_arg(from_id, "from_id")?;
+        let to_id = str_arg(to_id, "to_id")?;
+        if out_files.is_null() || out_len.is_null() {
+            return Err(fail(JjStatus::InvalidArgument, "output pointer is null"));
+        }
+        let from_commit = repo.commit(&commit_id_arg(from_id, "from_id")?)?;
+        let to_commit = repo.commit(&commit_id_arg(to_id, "to_id")?)?;
+        let from_tree = from_commit
+            .tree()
+            .map_err(|err| fail(JjStatus::Backend, err))?;
+        let to_tree = to_commit
+            .tree()
+            .map_err(|err| fail(JjStatus::Backend, err))?;
+        let summary = from_tree
+            .diff_summary(&to_tree, &EverythingMatcher)
+            .map_err(|err| fail(JjStatus::Backend, err))?;
+        let mut changes = [
+            (summary.modified, JjDiffKind::Modified),
+            (summary.added, JjDiffKind::Added),
+            (summary.removed, JjDiffKind::Removed),
+        ]
+        .into_iter()
+        .flat_map(|(paths, kind)| paths.into_iter().map(move |path| (path, kind)))
+        .collect_vec();
+        changes.sort_by(|(path1, _), (path2, _)| path1.cmp(path2));
+        let files = changes
+            .into_iter()
+            .map(|(path, kind)| JjChangedFile {
+                path: into_c_string(path.into_internal_string()),
+                kind,
+            })
+            .collect();
+        (*out_files, *out_len) = into_raw_parts(files);
+        Ok(())
+    })
+}
+
+/// Releases a list returned by `jj_diff_files()`. Null is ignored.
+///
+/// # Safety
+///
+/// `files` and `len` must be exactly as returned by `jj_diff_files()`.
+#[no_mangle]
+pub unsafe extern "C" fn jj_changed_files_free(files: *mut JjChangedFile, len: usize) {
+    guard(|| {
+        if !files.is_null() {
+            for file in free_boxed_slice(files, len).iter() {
+                drop(CString::from_raw(file.path));
+            }
+        }
+        Ok(())
+    });
+}
+
+/// Reads the file at `path` in the commit `commit_id` (a hex id) into `buf`.
+/// Conflicted files are read with conflict markers.
+///
+/// The size of the contents is stored in `*out_len`. If it's larger than
+/// `buf_len`, nothing is copied and `JJ_STATUS_BUFFER_TOO_SMALL` is returned,
+/// so the call can be retried with a large enough buffer. `buf` may be null if
+/// `buf_len` is 0.
+///
+/// # Safety
+///
+/// `repo` must be a valid handle, `commit_id` and `path` NUL-terminated
+/// strings, `buf` valid for writing `buf_len` bytes, and `out_len` a valid
+/// pointer.
+#[no_mangle]
+pub unsafe extern "C" fn jj_read_file(
+    repo: *const JjRepo,
+    commit_id: *const c_char,
+    path: *const c_char,
+    buf: *mut u8,
+    buf_len: usize,
+    out_len: *mut usize,
+) -> JjStatus {
+    guard(|| {
+        let repo = repo
+            .as_ref()
+            .ok_or_else(|| fail(JjStatus::InvalidArgument, "repo is null"))?;
+        let commit_id = str_arg(commit_id, "commit_id")?;
+        let path = str_arg(path, "path")?;
+        if out_len.is_null() || (buf.is_null() && buf_len > 0) {
+            return Err(fail(JjStatus::InvalidArgument, "output pointer is null"));
+        }
+        let contents = repo.read_file(&commit_id_arg(commit_id, "commit_id")?, path)?;
+        *out_len = contents.len();
+        if contents.len() > buf_len {
+            return Err(fail(
+                JjStatus::BufferTooSmall,
+                format!("The file needs a buffer of {} bytes", contents.len()),
+            ));
+        }
+        if !contents.is_empty() {
+            ptr::copy_nonoverlapping(contents.as_ptr(), buf, contents.len());
+        }
+        Ok(())
+    })
+}
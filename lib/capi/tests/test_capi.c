@@ -0,0 +1,158 @@
+// Copyright 2024 The Jujutsu Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+// Exercises every entry point of the C API against the fixture repo created
+// by test_capi.rs.
+//
+// Usage: test_capi <workspace path> <parent id> <child id> <conflict id>
+
+#include <stdio.h>
+#include <string.h>
+
+#include "jj.h"
+
+#define CHECK(cond)                                                   \
+  do {                                                                \
+    if (!(cond)) {                                                    \
+      const char *message = jj_last_error_message();                  \
+      fprintf(stderr, "%s:%d: check failed: %s (last error: %s)\n",   \
+              __FILE__, __LINE__, #cond, message ? message : "none"); \
+      return 1;                                                       \
+    }                                                                 \
+  } while (0)
+
+static int test_open_errors(const char *workspace_path) {
+  JjRepo *repo = NULL;
+  CHECK(jj_repo_open(NULL, &repo) == JJ_STATUS_INVALID_ARGUMENT);
+  CHECK(jj_repo_open("/nonexistent/workspace", &repo) ==
+        JJ_STATUS_REPO_LOAD);
+  CHECK(jj_last_error_message() != NULL);
+  CHECK(repo == NULL);
+  CHECK(jj_repo_open(workspace_path, NULL) == JJ_STATUS_INVALID_ARGUMENT);
+  jj_repo_free(NULL);
+  return 0;
+}
+
+static int test_resolve_revset(const JjRepo *repo, const char *parent_id,
+                               const char *child_id) {
+  char **ids = NULL;
+  size_t len = 0;
+  CHECK(jj_resolve_revset(repo, child_id, &ids, &len) == JJ_STATUS_OK);
+  CHECK(len == 1);
+  CHECK(strcmp(ids[0], child_id) == 0);
+  jj_commit_ids_free(ids, len);
+
+  char revset[512];
+  snprintf(revset, sizeof(revset), "%s | %s", parent_id, child_id);
+  CHECK(jj_resolve_revset(repo, revset, &ids, &len) == JJ_STATUS_OK);
+  CHECK(len == 2);
+  CHECK(strcmp(ids[0], child_id) == 0);
+  CHECK(strcmp(ids[1], parent_id) == 0);
+  jj_commit_ids_free(ids, len);
+
+  CHECK(jj_resolve_revset(repo, "none()", &ids, &len) == JJ_STATUS_OK);
+  CHECK(len == 0);
+  jj_commit_ids_free(ids, len);
+
+  CHECK(jj_resolve_revset(repo, "(", &ids, &len) == JJ_STATUS_REVSET);
+  CHECK(jj_last_error_message() != NULL);
+  CHECK(jj_resolve_revset(repo, "no-such-branch", &ids, &len) ==
+        JJ_STATUS_REVSET);
+  jj_commit_ids_free(NULL, 0);
+  return 0;
+}
+
+static int test_diff_files(const JjRepo *repo, const char *parent_id,
+                           const char *child_id) {
+  JjChangedFile *files = NULL;
+  size_t len = 0;
+  CHECK(jj_diff_files(repo, parent_id, child_id, &files, &len) ==
+        JJ_STATUS_OK);
+  CHECK(len == 3);
+  CHECK(strcmp(files[0].path, "added") == 0);
+  CHECK(files[0].kind == JJ_DIFF_KIND_ADDED);
+  CHECK(strcmp(files[1].path, "dir/modified") == 0);
+  CHECK(files[1].kind == JJ_DIFF_KIND_MODIFIED);
+  CHECK(strcmp(files[2].path, "removed") == 0);
+  CHECK(files[2].kind == JJ_DIFF_KIND_REMOVED);
+  jj_changed_files_free(files, len);
+
+  CHECK(jj_diff_files(repo, child_id, child_id, &files, &len) ==
+        JJ_STATUS_OK);
+  CHECK(len == 0);
+  jj_changed_files_free(files, len);
+
+  CHECK(jj_diff_files(repo, "not hex", child_id, &files, &len) ==
+        JJ_STATUS_INVALID_ARGUMENT);
+  CHECK(jj_diff_files(repo, "0123456789abcdef", child_id, &files, &len) ==
+        JJ_STATUS_NOT_FOUND);
+  jj_changed_files_free(NULL, 0);
+  return 0;
+}
+
+static int test_read_file(const JjRepo *repo, const char *child_id,
+                          const char *conflict_id) {
+  char buf[256];
+  size_t len = 0;
+
+  // Query the size first, then read the contents
+  CHECK(jj_read_file(repo, child_id, "dir/modified", NULL, 0, &len) ==
+        JJ_STATUS_BUFFER_TOO_SMALL);
+  CHECK(len == 4);
+  CHECK(jj_read_file(repo, child_id, "dir/modified", (uint8_t *)buf,
+                     sizeof(buf), &len) == JJ_STATUS_OK);
+  CHECK(len == 4);
+  CHECK(memcmp(buf, "new\n", 4) == 0);
+
+  CHECK(jj_read_file(repo, child_id, "removed", (uint8_t *)buf, sizeof(buf),
+                     &len) == JJ_STATUS_NOT_FOUND);
+  CHECK(jj_read_file(repo, child_id, "dir", (uint8_t *)buf, sizeof(buf),
+                     &len) == JJ_STATUS_NOT_A_FILE);
+  CHECK(jj_read_file(repo, child_id, "../outside", (uint8_t *)buf,
+                     sizeof(buf), &len) == JJ_STATUS_INVALID_ARGUMENT);
+
+  // Conflicts are read with conflict markers
+  CHECK(jj_read_file(repo, conflict_id, "dir/modified", (uint8_t *)buf,
+                     sizeof(buf) - 1, &len) == JJ_STATUS_OK);
+  buf[len] = '\0';
+  CHECK(strstr(buf, "<<<<<<<") != NULL);
+  CHECK(strstr(buf, "left\n") != NULL);
+  CHECK(strstr(buf, "right\n") != NULL);
+  return 0;
+}
+
+int main(int argc, char **argv) {
+  if (argc != 5) {
+    fprintf(stderr,
+            "usage: %s <workspace path> <parent id> <child id> "
+            "<conflict id>\n",
+            argv[0]);
+    return 2;
+  }
+  const char *workspace_path = argv[1];
+  const char *parent_id = argv[2];
+  const char *child_id = argv[3];
+  const char *conflict_id = argv[4];
+
+  if (test_open_errors(workspace_path) != 0) {
+    return 1;
+  }
+  JjRepo *repo = NULL;
+  CHECK(jj_repo_open(workspace_path, &repo) == JJ_STATUS_OK);
+  int result = test_resolve_revset(repo, parent_id, child_id) ||
+               test_diff_files(repo, parent_id, child_id) ||
+               test_read_file(repo, child_id, conflict_id);
+  jj_repo_free(repo);
+  return result;
+}
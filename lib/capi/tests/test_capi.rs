@@ -0,0 +1,128 @@
+// Copyright 2024 The Jujutsu Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Compiles `test_capi.c` against the built library and runs it on a fixture
+//! repo.
+
+#![cfg(unix)]
+
+use std::env;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use jj_lib::backend::MergedTreeId;
+use jj_lib::merge::Merge;
+use jj_lib::object_id::ObjectId as _;
+use jj_lib::repo::Repo as _;
+use jj_lib::repo_path::RepoPath;
+use testutils::{create_single_tree, create_tree, TestRepoBackend, TestWorkspace};
+
+/// Finds the shared library Cargo built for this crate.
+fn find_library_dir() -> PathBuf {
+    let file_name = if cfg!(target_os = "macos") {
+        "libjj_capi.dylib"
+    } else {
+        "libjj_capi.so"
+    };
+    // The test binary lives in target/<profile>/deps/
+    let deps_dir = env::current_exe().unwrap().parent().unwrap().to_owned();
+    [deps_dir.parent().unwrap(), deps_dir.as_path()]
+        .into_iter()
+        .find(|dir| dir.join(file_name).is_file())
+        .unwrap_or_else(|| panic!("{file_name} not found near {}", deps_dir.display()))
+        .to_owned()
+}
+
+fn compile_test_program(out_dir: &Path) -> PathBuf {
+    let crate_dir = Path::new(env!("CARGO_MANIFEST_DIR"));
+    let library_dir = find_library_dir();
+    let program = out_dir.join("test_capi");
+    let compiler = env::var("CC").unwrap_or_else(|_| "cc".to_owned());
+    let status = Command::new(compiler)
+        .arg("-Wall")
+        .arg("-Werror")
+        .arg("-I")
+        .arg(crate_dir.join("include"))
+        .arg(crate_dir.join("tests").join("test_capi.c"))
+        .arg("-o")
+        .arg(&program)
+        .arg("-L")
+        .arg(&library_dir)
+        .arg(format!("-Wl,-rpath,{}", library_dir.display()))
+        .arg("-ljj_capi")
+        .status()
+        .expect("failed to run the C compiler");
+    assert!(status.success(), "failed to compile test_capi.c");
+    program
+}
+
+#[test]
+fn test_c_program() {
+    let settings = testutils::user_settings();
+    // The C API loads the repo from disk, so it can't use the in-memory backend
+    let test_workspace = TestWorkspace::init_with_backend(&settings, TestRepoBackend::Local);
+    let repo = &test_workspace.repo;
+
+    let modified_path = RepoPath::from_internal_string("dir/modified");
+    let removed_path = RepoPath::from_internal_string("removed");
+    let added_path = RepoPath::from_internal_string("added");
+    let parent_tree = create_tree(repo, &[(modified_path, "old\n"), (removed_path, "gone\n")]);
+    let child_tree = create_tree(repo, &[(modified_path, "new\n"), (added_path, "added\n")]);
+    let base_tree = create_single_tree(repo, &[(modified_path, "base\n")]);
+    let left_tree = create_single_tree(repo, &[(modified_path, "left\n")]);
+    let right_tree = create_single_tree(repo, &[(modified_path, "right\n")]);
+    let conflict_tree_id = MergedTreeId::Merge(Merge::from_removes_adds(
+        vec![base_tree.id().clone()],
+        vec![left_tree.id().clone(), right_tree.id().clone()],
+    ));
+
+    let mut tx = repo.start_transaction(&settings);
+    let root_commit_id = repo.store().root_commit_id().clone();
+    let parent = tx
+        .mut_repo()
+        .new_commit(&settings, vec![root_commit_id.clone()], parent_tree.id())
+        .write()
+        .unwrap();
+    let child = tx
+        .mut_repo()
+        .new_commit(&settings, vec![parent.id().clone()], child_tree.id())
+        .write()
+        .unwrap();
+    let conflict = tx
+        .mut_repo()
+        .new_commit(&settings, vec![root_commit_id], conflict_tree_id)
+        .write()
+        .unwrap();
+    tx.commit("test").unwrap();
+
+    let out_dir = testutils::new_temp_dir();
+    let program = compile_test_program(out_dir.path());
+    // Don't let the user's config affect the test
+    let config_path = out_dir.path().join("config.toml");
+    std::fs::write(&config_path, "user.email = \"test.user@example.com\"\n").unwrap();
+    let output = Command::new(program)
+        .env("JJ_CONFIG", &config_path)
+        .arg(test_workspace.workspace.workspace_root())
+        .arg(parent.id().hex())
+        .arg(child.id().hex())
+        .arg(conflict.id().hex())
+        .output()
+        .unwrap();
+    assert!(
+        output.status.success(),
+        "test_capi failed:\n{}{}",
+        String::from_utf8_lossy(&output.stdout),
+        String::from_utf8_lossy(&output.stderr)
+    );
+}
@@ -0,0 +1,39 @@
+// Copyright 2024 The Jujutsu Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Prints the id of each new operation in a repo until interrupted.
+//!
+//! Run it with `cargo run -p jj-lib --features notify --example watch-repo --
+//! <workspace>` and run `jj` commands in the workspace. Without the `notify`
+//! feature, the repo is polled instead.
+
+use std::error::Error;
+use std::path::PathBuf;
+
+use jj_lib::object_id::ObjectId as _;
+use jj_lib::repo_watcher::RepoWatcher;
+use jj_lib::workspace::WorkspaceLoader;
+
+fn main() -> Result<(), Box<dyn Error>> {
+    let workspace_root = std::env::args_os()
+        .nth(1)
+        .map_or_else(|| PathBuf::from("."), PathBuf::from);
+    let loader = WorkspaceLoader::init(&workspace_root)?;
+    let watcher = RepoWatcher::new(loader.repo_path())?;
+    eprintln!("Watching {}", loader.repo_path().display());
+    for event in watcher.events() {
+        println!("{}", event.new_op_head.hex());
+    }
+    Ok(())
+}
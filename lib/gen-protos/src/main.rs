@@ -29,6 +29,9 @@ fn main() -> Result<()> {
     prost_build::Config::new()
         .out_dir(&protos_dir)
         .include_file("mod.rs")
+        // Commit metadata is serialized in a stable order, so the encoded
+        // commit (which may be signed or compared) is deterministic.
+        .btree_map([".git_store.Commit.metadata", ".local_store.Commit.metadata"])
         // For old protoc versions. 3.12.4 needs this, but 3.21.12 doesn't.
         .protoc_arg("--experimental_allow_proto3_optional")
         .compile_protos(
@@ -0,0 +1,542 @@
+// Copyright 2024 The Jujutsu Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Moves the changes in a commit into the ancestors that last changed the
+//! same lines.
+
+use std::collections::{BTreeMap, HashMap};
+use std::ops::Range;
+use std::{iter, slice};
+
+use itertools::Itertools as _;
+use thiserror::Error;
+
+use crate::annotate::{
+    annotate_file, get_file_content, matching_lines, read_file_content, LineOrigin,
+};
+use crate::backend::{BackendError, BackendResult, CommitId, TreeValue};
+use crate::commit::Commit;
+use crate::diff::{diff_lines, find_line_ranges, DiffHunk, WhitespaceMode};
+use crate::gitattributes::GitAttributes;
+use crate::matchers::Matcher;
+use crate::merge::{Merge, MergedTreeValue};
+use crate::merged_tree::MergedTreeBuilder;
+use crate::repo::{MutableRepo, Repo};
+use crate::repo_path::{RepoPath, RepoPathBuf};
+use crate::revset::{RevsetEvaluationError, RevsetExpression};
+use crate::rewrite::merge_commit_trees;
+use crate::settings::UserSettings;
+use crate::store::Store;
+use crate::tree::{try_resolve_file_conflict, TreeMergeError};
+
+/// A hunk of the source commit's changes to a file, and where it goes in the
+/// destination commit's version of the file.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct AbsorbHunk {
+    /// The changed file.
+    pub path: RepoPathBuf,
+    /// The (0-based) range of lines the hunk replaces in the source's parent,
+    /// or in the merge of its parents.
+    pub parent_lines: Range<usize>,
+    destination_lines: Range<usize>,
+    new_content: Vec<u8>,
+}
+
+/// Why a change was left in the source commit.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum AbsorbSkipReason {
+    /// The changed lines were last changed in different commits, or lines
+    /// were added between lines from different commits.
+    Ambiguous,
+    /// The changed lines were last changed in an immutable commit.
+    Immutable,
+    /// The path isn't a regular file on both sides, e.g. because it was added,
+    /// deleted, or is conflicted.
+    UnsupportedPath,
+    /// Moving the hunk would create a conflict.
+    Conflict,
+}
+
+/// A change that was left in the source commit.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct SkippedHunk {
+    /// The changed file.
+    pub path: RepoPathBuf,
+    /// The (0-based) range of lines the hunk replaces in the source's parent
+    /// or parents, or `None` if the whole path was skipped.
+    pub parent_lines: Option<Range<usize>>,
+    /// Why the change was skipped.
+    pub reason: AbsorbSkipReason,
+}
+
+/// The hunks to move into each destination commit, and the changes to leave
+/// in the source commit.
+#[derive(Clone, Debug, Default)]
+pub struct AbsorbPlan {
+    /// The hunks to move, along with their destination commits, ordered by
+    /// path and line.
+    pub hunks: Vec<(CommitId, AbsorbHunk)>,
+    /// The changes to leave in the source commit.
+    pub skipped: Vec<SkippedHunk>,
+}
+
+/// Error from checking whether absorbed changes would create conflicts.
+#[derive(Debug, Error)]
+pub enum AbsorbError {
+    #[error(transparent)]
+    Backend(#[from] BackendError),
+    #[error(transparent)]
+    TreeMerge(#[from] TreeMergeError),
+    #[error(transparent)]
+    RevsetEvaluation(#[from] RevsetEvaluationError),
+}
+
+/// Finds the commit to move each hunk of `source`'s changes into.
+///
+/// Each hunk goes into the commit that last changed the lines it replaces,
+/// among the `destinations`, which must be ancestors of `source` ordered
+/// children first. The changes of a merge commit are relative to the merge of
+/// its parents, and each hunk goes into an ancestor of the parent whose lines
+/// it replaces.
+pub fn plan_absorb(
+    repo: &dyn Repo,
+    source: &Commit,
+    destinations: &[CommitId],
+    matcher: &dyn Matcher,
+) -> Result<AbsorbPlan, TreeMergeError> {
+    let store = repo.store();
+    let mut plan = AbsorbPlan::default();
+    let parents = source.parents();
+    if parents.is_empty() {
+        return Ok(plan);
+    }
+    let parent_tree = merge_commit_trees(repo, &parents)?;
+    let tree = source.tree()?;
+    for (path, diff) in parent_tree.diff(&tree, matcher) {
+        let (before, after) = diff?;
+        let (
+            Some(TreeValue::File { id: before_id, .. }),
+            Some(TreeValue::File { id: after_id, .. }),
+        ) = (before.as_normal(), after.as_normal())
+        else {
+            plan.skipped.push(SkippedHunk {
+                path,
+                parent_lines: None,
+                reason: AbsorbSkipReason::UnsupportedPath,
+            });
+            continue;
+        };
+        if before_id == after_id {
+            // Only the executable bit changed
+            continue;
+        }
+        let before_content = read_file_content(store, &path, before_id)?;
+        let after_content = read_file_content(store, &path, after_id)?;
+        let origins = annotate_parents(store, destinations, &parents, &before_content, &path)?;
+        let mut parent_line = 0;
+        for hunk in diff_lines(&before_content, &after_content, WhitespaceMode::Exact) {
+            match hunk {
+                DiffHunk::Matching(content) => {
+                    parent_line += find_line_ranges(content).len();
+                }
+                DiffHunk::Different(sides) => {
+                    let parent_lines = parent_line..parent_line + find_line_ranges(sides[0]).len();
+                    parent_line = parent_lines.end;
+                    match find_destination(&origins, parent_lines.clone()) {
+                        Ok((destination, destination_lines)) => plan.hunks.push((
+                            destination,
+                            AbsorbHunk {
+                                path: path.clone(),
+                                parent_lines,
+                                destination_lines,
+                                new_content: sides[1].to_vec(),
+                            },
+                        )),
+                        Err(reason) => plan.skipped.push(SkippedHunk {
+                            path: path.clone(),
+                            parent_lines: Some(parent_lines),
+                            reason,
+                        }),
+                    }
+                }
+            }
+        }
+    }
+    Ok(plan)
+}
+
+/// Annotates each line of `content`, the merged contents of the file at `path`
+/// in `parents`, with the commit that last changed it. Each line is attributed
+/// to the first parent that has it. Lines that aren't in any parent are
+/// annotated as `LineOrigin::Outside`.
+fn annotate_parents(
+    store: &Store,
+    domain: &[CommitId],
+    parents: &[Commit],
+    content: &[u8],
+    path: &RepoPath,
+) -> BackendResult<Vec<LineOrigin>> {
+    if let [parent] = parents {
+        return annotate_file(store, domain, parent, path);
+    }
+    let mut origins = vec![LineOrigin::Outside; find_line_ranges(content).len()];
+    let mut pending_lines = (0..origins.len()).collect_vec();
+    for parent in parents {
+        if pending_lines.is_empty() {
+            break;
+        }
+        let parent_content = get_file_content(store, parent, path)?;
+        let parent_origins = annotate_file(store, domain, parent, path)?;
+        let parent_lines: HashMap<usize, usize> = matching_lines(content, &parent_content)
+            .into_iter()
+            .collect();
+        pending_lines.retain(|line| {
+            let Some(&parent_line) = parent_lines.get(line) else {
+                return true;
+            };
+            origins[*line] = parent_origins[parent_line].clone();
+            false
+        });
+    }
+    Ok(origins)
+}
+
+/// Returns the commit that last changed the given lines, and the range of the
+/// lines in that commit's version of the file. If no lines are replaced, the
+/// lines around the insertion point must come from the same commit.
+fn find_destination(
+    origins: &[LineOrigin],
+    lines: Range<usize>,
+) -> Result<(CommitId, Range<usize>), AbsorbSkipReason> {
+    let neighbors;
+    let candidates = if lines.is_empty() {
+        let before = lines.start.checked_sub(1).map(|i| &origins[i]);
+        let after = origins.get(lines.start);
+        match (before, after) {
+            (
+                Some(LineOrigin::Commit {
+                    commit_id: before_id,
+                    line: before_line,
+                }),
+                Some(LineOrigin::Commit {
+                    commit_id: after_id,
+                    line: after_line,
+                }),
+            ) if before_id == after_id && before_line + 1 == *after_line => {
+                return Ok((after_id.clone(), *after_line..*after_line));
+            }
+            (Some(LineOrigin::Commit { commit_id, line }), None) => {
+                return Ok((commit_id.clone(), line + 1..line + 1));
+            }
+            (None, Some(LineOrigin::Commit { commit_id, line })) => {
+                return Ok((commit_id.clone(), *line..*line));
+            }
+            _ => {}
+        }
+        neighbors = [before, after]
+            .into_iter()
+            .flatten()
+            .cloned()
+            .collect::<Vec<_>>();
+        &neighbors[..]
+    } else {
+        &origins[lines.clone()]
+    };
+    if candidates
+        .iter()
+        .all(|origin| *origin == LineOrigin::Outside)
+    {
+        return Err(if candidates.is_empty() {
+            AbsorbSkipReason::Ambiguous
+        } else {
+            AbsorbSkipReason::Immutable
+        });
+    }
+    if lines.is_empty() {
+        return Err(AbsorbSkipReason::Ambiguous);
+    }
+    // The lines must be consecutive in the destination, so that no lines
+    // deleted by later commits are replaced
+    let LineOrigin::Commit {
+        commit_id,
+        line: first_line,
+    } = &candidates[0]
+    else {
+        return Err(AbsorbSkipReason::Ambiguous);
+    };
+    let consecutive = candidates.iter().enumerate().all(|(i, origin)| {
+        *origin
+            == LineOrigin::Commit {
+                commit_id: commit_id.clone(),
+                line: first_line + i,
+            }
+    });
+    if consecutive {
+        Ok((
+            commit_id.clone(),
+            *first_line..first_line + candidates.len(),
+        ))
+    } else {
+        Err(AbsorbSkipReason::Ambiguous)
+    }
+}
+
+/// Rewrites the destination commits with the hunks applied, and rebases their
+/// descendants, including the source commit. Returns the mapping from old to
+/// new commit ids of the rebased commits.
+pub fn apply_absorb(
+    mut_repo: &mut MutableRepo,
+    settings: &UserSettings,
+    source: &Commit,
+    hunks: &[(CommitId, AbsorbHunk)],
+) -> Result<HashMap<CommitId, CommitId>, TreeMergeError> {
+    let store = mut_repo.store().clone();
+    let mut hunks_by_destination: BTreeMap<&CommitId, BTreeMap<&RepoPathBuf, Vec<&AbsorbHunk>>> =
+        BTreeMap::new();
+    for (destination, hunk) in hunks {
+        hunks_by_destination
+            .entry(destination)
+            .or_default()
+            .entry(&hunk.path)
+            .or_default()
+            .push(hunk);
+    }
+    for (destination_id, hunks_by_path) in hunks_by_destination {
+        let destination = store.get_commit(destination_id)?;
+        let mut tree_builder = MergedTreeBuilder::new(destination.tree_id().clone());
+        for (path, hunks) in hunks_by_path {
+            let value = write_file_with_hunks(&store, &destination, path, hunks)?;
+            tree_builder.set_or_remove(path.clone(), Merge::normal(value));
+        }
+        let new_tree_id = tree_builder.write_tree(&store)?;
+        mut_repo
+            .rewrite_commit(settings, &destination)
+            .set_tree_id(new_tree_id)
+            .set_predecessors(vec![destination.id().clone(), source.id().clone()])
+            .write()?;
+    }
+    Ok(mut_repo.rebase_descendants_return_map(settings)?)
+}
+
+/// Writes the file at `path` in `destination` with the `hunks` applied.
+fn write_file_with_hunks<'a>(
+    store: &Store,
+    destination: &Commit,
+    path: &RepoPath,
+    hunks: impl IntoIterator<Item = &'a AbsorbHunk>,
+) -> BackendResult<TreeValue> {
+    let executable = match destination.tree()?.path_value(path).as_normal() {
+        Some(TreeValue::File { executable, .. }) => *executable,
+        _ => false,
+    };
+    let content = get_file_content(store, destination, path)?;
+    let line_ranges = find_line_ranges(&content);
+    let line_start = |line: usize| line_ranges.get(line).map_or(content.len(), |r| r.start);
+    let mut new_content = vec![];
+    let mut pos = 0;
+    for hunk in hunks
+        .into_iter()
+        .sorted_by_key(|hunk| hunk.destination_lines.start)
+    {
+        new_content.extend_from_slice(&content[pos..line_start(hunk.destination_lines.start)]);
+        new_content.extend_from_slice(&hunk.new_content);
+        pos = line_start(hunk.destination_lines.end);
+    }
+    new_content.extend_from_slice(&content[pos..]);
+    let id = store.write_file(path, &mut new_content.as_slice())?;
+    Ok(TreeValue::File { id, executable })
+}
+
+/// Moves the hunks that would create conflicts to the skipped changes. Each
+/// hunk is checked on top of the hunks accepted before it, so that a hunk that
+/// conflicts only with hunks in other commits doesn't prevent them from being
+/// moved.
+///
+/// Only the hunk's file is merged into the descendants of its destination, the
+/// same way they'll be rebased, so a hunk that would conflict is found without
+/// rewriting any commits.
+pub fn drop_conflicting_hunks(
+    repo: &dyn Repo,
+    source: &Commit,
+    plan: &mut AbsorbPlan,
+) -> Result<(), AbsorbError> {
+    let store = repo.store();
+    let mut descendants_by_destination: HashMap<CommitId, Vec<Commit>> = HashMap::new();
+    let mut rewrites_by_path: HashMap<RepoPathBuf, PathRewrites> = HashMap::new();
+    for (destination_id, hunk) in std::mem::take(&mut plan.hunks) {
+        let destination = store.get_commit(&destination_id)?;
+        if !descendants_by_destination.contains_key(&destination_id) {
+            // The revset is in reverse topological order
+            let descendants: Vec<Commit> = RevsetExpression::commit(destination_id.clone())
+                .descendants()
+                .evaluate_programmatic(repo)?
+                .iter()
+                .filter(|id| *id != destination_id)
+                .map(|id| store.get_commit(&id))
+                .try_collect()?;
+            let descendants = descendants.into_iter().rev().collect();
+            descendants_by_destination.insert(destination_id.clone(), descendants);
+        }
+        let accepted = rewrites_by_path
+            .entry(hunk.path.clone())
+            .or_default()
+            .try_add(
+                repo,
+                source,
+                &destination,
+                &descendants_by_destination[&destination_id],
+                &hunk,
+            )?;
+        if accepted {
+            plan.hunks.push((destination_id, hunk));
+        } else {
+            plan.skipped.push(SkippedHunk {
+                path: hunk.path,
+                parent_lines: Some(hunk.parent_lines),
+                reason: AbsorbSkipReason::Conflict,
+            });
+        }
+    }
+    Ok(())
+}
+
+/// The values of a path in the commits rewritten by the hunks accepted so far.
+#[derive(Default)]
+struct PathRewrites {
+    /// The accepted hunks by destination.
+    hunks: HashMap<CommitId, Vec<AbsorbHunk>>,
+    /// The values of the destinations with their hunks applied, before they're
+    /// rebased.
+    destination_values: HashMap<CommitId, MergedTreeValue>,
+    /// The values of the rewritten commits, after they're rebased.
+    values: HashMap<CommitId, MergedTreeValue>,
+}
+
+impl PathRewrites {
+    /// Adds `hunk` to the accepted hunks, unless moving it into `destination`
+    /// would create a conflict in a descendant of it, or change the contents
+    /// of `source`. The `descendants` must be ordered parents first.
+    fn try_add(
+        &mut self,
+        repo: &dyn Repo,
+        source: &Commit,
+        destination: &Commit,
+        descendants: &[Commit],
+        hunk: &AbsorbHunk,
+    ) -> Result<bool, TreeMergeError> {
+        let store = repo.store();
+        let path = &hunk.path;
+        let mut hunks = self
+            .hunks
+            .get(destination.id())
+            .cloned()
+            .unwrap_or_default();
+        hunks.push(hunk.clone());
+        let destination_value =
+            Merge::normal(write_file_with_hunks(store, destination, path, &hunks)?);
+        let mut new_values: HashMap<CommitId, MergedTreeValue> = HashMap::new();
+        for commit in iter::once(destination).chain(descendants) {
+            let is_destination = commit.id() == destination.id();
+            if !is_destination
+                && !commit
+                    .parent_ids()
+                    .iter()
+                    .any(|id| new_values.contains_key(id))
+            {
+                continue;
+            }
+            let old_value = commit.tree()?.path_value(path);
+            let value = if is_destination {
+                destination_value.clone()
+            } else {
+                self.destination_values
+                    .get(commit.id())
+                    .cloned()
+                    .unwrap_or_else(|| old_value.clone())
+            };
+            let current_value = |id: &CommitId| new_values.get(id).or(self.values.get(id)).cloned();
+            let new_value = rebase_path_value(repo, path, commit, value, &current_value)?;
+            if (!new_value.is_resolved() && old_value.is_resolved())
+                || (commit.id() == source.id() && new_value != old_value)
+            {
+                return Ok(false);
+            }
+            new_values.insert(commit.id().clone(), new_value);
+        }
+        self.values.extend(new_values);
+        self.destination_values
+            .insert(destination.id().clone(), destination_value);
+        self.hunks.insert(destination.id().clone(), hunks);
+        Ok(true)
+    }
+}
+
+/// Returns the value of `path` in `commit` rebased onto the new values of its
+/// parents, given `value`, its value before rebasing. `new_value` returns the
+/// new value of a rewritten commit.
+fn rebase_path_value(
+    repo: &dyn Repo,
+    path: &RepoPath,
+    commit: &Commit,
+    value: MergedTreeValue,
+    new_value: &dyn Fn(&CommitId) -> Option<MergedTreeValue>,
+) -> Result<MergedTreeValue, TreeMergeError> {
+    if commit.parent_ids().iter().all(|id| new_value(id).is_none()) {
+        return Ok(value);
+    }
+    let old_parents_value = merge_parent_values(repo, path, commit.parent_ids(), &|_| None)?;
+    let new_parents_value = merge_parent_values(repo, path, commit.parent_ids(), new_value)?;
+    let merged = Merge::from_vec(vec![value, old_parents_value, new_parents_value])
+        .flatten()
+        .simplify();
+    if let Some(resolved) = merged.resolve_trivial() {
+        return Ok(Merge::resolved(resolved.clone()));
+    }
+    let attributes = GitAttributes::new(commit.tree()?);
+    match try_resolve_file_conflict(repo.store(), path, &merged, &attributes)? {
+        Some(resolved) => Ok(Merge::normal(resolved)),
+        None => Ok(merged),
+    }
+}
+
+/// Merges the values of `path` in the given commits the way
+/// `merge_commit_trees()` merges their trees. `new_value` returns the new value
+/// of a rewritten commit.
+fn merge_parent_values(
+    repo: &dyn Repo,
+    path: &RepoPath,
+    commit_ids: &[CommitId],
+    new_value: &dyn Fn(&CommitId) -> Option<MergedTreeValue>,
+) -> Result<MergedTreeValue, TreeMergeError> {
+    let value = |id: &CommitId| -> BackendResult<MergedTreeValue> {
+        match new_value(id) {
+            Some(value) => Ok(value),
+            None => Ok(repo.store().get_commit(id)?.tree()?.path_value(path)),
+        }
+    };
+    let Some(first_id) = commit_ids.first() else {
+        return Ok(Merge::absent());
+    };
+    let mut merged = value(first_id)?;
+    for (i, other_id) in commit_ids.iter().enumerate().skip(1) {
+        let ancestor_ids = repo
+            .index()
+            .common_ancestors(&commit_ids[..i], slice::from_ref(other_id));
+        let ancestor_value = merge_parent_values(repo, path, &ancestor_ids, new_value)?;
+        merged = Merge::from_vec(vec![merged, ancestor_value, value(other_id)?])
+            .flatten()
+            .simplify();
+    }
+    Ok(merged)
+}
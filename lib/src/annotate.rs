@@ -0,0 +1,168 @@
+// Copyright 2024 The Jujutsu Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Finds the commit that last changed each line of a file.
+
+use std::collections::{HashMap, HashSet};
+use std::io::Read as _;
+
+use crate::backend::{BackendError, BackendResult, CommitId, FileId, TreeValue};
+use crate::commit::Commit;
+use crate::diff::{diff_lines, find_line_ranges, DiffHunk, WhitespaceMode};
+use crate::object_id::ObjectId as _;
+use crate::repo_path::RepoPath;
+use crate::store::Store;
+
+/// Where a line of a file was last changed.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum LineOrigin {
+    /// The line was last changed in this commit, and is at the given
+    /// (0-based) line index in the commit's version of the file.
+    Commit {
+        /// The commit that last changed the line.
+        commit_id: CommitId,
+        /// The index of the line in the commit's version of the file.
+        line: usize,
+    },
+    /// The line was last changed in a commit outside the searched commits.
+    Outside,
+}
+
+/// Annotates each line of the file at `path` in `start` with the commit that
+/// last changed it.
+///
+/// Only the commits in `domain` are searched. They must be ordered children
+/// first, as returned by evaluating a revset. Lines that come from a commit
+/// outside the domain, including all lines if `start` isn't in it, are
+/// annotated as `LineOrigin::Outside`. A path that isn't a regular file in a
+/// commit is treated as an empty file.
+pub fn annotate_file(
+    store: &Store,
+    domain: &[CommitId],
+    start: &Commit,
+    path: &RepoPath,
+) -> BackendResult<Vec<LineOrigin>> {
+    let start_content = get_file_content(store, start, path)?;
+    let num_lines = find_line_ranges(&start_content).len();
+    let mut origins = vec![LineOrigin::Outside; num_lines];
+    let domain_set: HashSet<&CommitId> = domain.iter().collect();
+    if !domain_set.contains(start.id()) {
+        return Ok(origins);
+    }
+
+    // For each commit still to visit, the lines of `start` whose origin isn't
+    // known yet, as pairs of (line in `start`, line in the commit).
+    let mut pending: HashMap<CommitId, Vec<(usize, usize)>> = HashMap::new();
+    pending.insert(start.id().clone(), (0..num_lines).map(|i| (i, i)).collect());
+    let mut contents = HashMap::from([(start.id().clone(), start_content)]);
+    for commit_id in domain {
+        let Some(mut lines) = pending.remove(commit_id) else {
+            continue;
+        };
+        let commit = store.get_commit(commit_id)?;
+        let content = match contents.remove(commit_id) {
+            Some(content) => content,
+            None => get_file_content(store, &commit, path)?,
+        };
+        for parent in commit.parents() {
+            if lines.is_empty() {
+                break;
+            }
+            if !contents.contains_key(parent.id()) {
+                let parent_content = get_file_content(store, &parent, path)?;
+                contents.insert(parent.id().clone(), parent_content);
+            }
+            let parent_content = &contents[parent.id()];
+            let parent_lines: HashMap<usize, usize> = matching_lines(&content, parent_content)
+                .into_iter()
+                .collect();
+            let in_domain = domain_set.contains(parent.id());
+            lines.retain(|&(start_line, line)| {
+                let Some(&parent_line) = parent_lines.get(&line) else {
+                    return true;
+                };
+                if in_domain {
+                    pending
+                        .entry(parent.id().clone())
+                        .or_default()
+                        .push((start_line, parent_line));
+                }
+                false
+            });
+        }
+        // The remaining lines aren't in any parent, so they were changed here
+        for (start_line, line) in lines {
+            origins[start_line] = LineOrigin::Commit {
+                commit_id: commit_id.clone(),
+                line,
+            };
+        }
+    }
+    Ok(origins)
+}
+
+/// Returns the pairs of (left line, right line) that are unchanged between
+/// `left` and `right`.
+pub(crate) fn matching_lines(left: &[u8], right: &[u8]) -> Vec<(usize, usize)> {
+    let mut pairs = vec![];
+    let mut left_line = 0;
+    let mut right_line = 0;
+    for hunk in diff_lines(left, right, WhitespaceMode::Exact) {
+        match hunk {
+            DiffHunk::Matching(content) => {
+                let num_lines = find_line_ranges(content).len();
+                pairs.extend((0..num_lines).map(|i| (left_line + i, right_line + i)));
+                left_line += num_lines;
+                right_line += num_lines;
+            }
+            DiffHunk::Different(sides) => {
+                left_line += find_line_ranges(sides[0]).len();
+                right_line += find_line_ranges(sides[1]).len();
+            }
+        }
+    }
+    pairs
+}
+
+/// Reads the file at `path` in `commit`, or returns an empty file if the path
+/// isn't a regular file there.
+pub(crate) fn get_file_content(
+    store: &Store,
+    commit: &Commit,
+    path: &RepoPath,
+) -> BackendResult<Vec<u8>> {
+    let value = commit.tree()?.path_value(path);
+    let Some(TreeValue::File { id, .. }) = value.as_normal() else {
+        return Ok(vec![]);
+    };
+    read_file_content(store, path, id)
+}
+
+/// Reads the file with the given id.
+pub(crate) fn read_file_content(
+    store: &Store,
+    path: &RepoPath,
+    id: &FileId,
+) -> BackendResult<Vec<u8>> {
+    let mut content = vec![];
+    store
+        .read_file(path, id)?
+        .read_to_end(&mut content)
+        .map_err(|err| BackendError::ReadObject {
+            object_type: "file".to_owned(),
+            hash: id.hex(),
+            source: err.into(),
+        })?;
+    Ok(content)
+}
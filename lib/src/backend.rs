@@ -16,9 +16,11 @@
 
 use std::any::Any;
 use std::collections::BTreeMap;
+use std::fmt;
 use std::fmt::Debug;
 use std::io::Read;
 use std::result::Result;
+use std::str;
 use std::time::SystemTime;
 use std::vec::Vec;
 
@@ -28,8 +30,10 @@ use thiserror::Error;
 use crate::content_hash::ContentHash;
 use crate::index::Index;
 use crate::merge::Merge;
-use crate::object_id::{id_type, ObjectId};
-use crate::repo_path::{RepoPath, RepoPathComponent, RepoPathComponentBuf};
+use crate::object_id::{id_type, HexPrefix, ObjectId};
+use crate::repo_path::{
+    is_valid_repo_path_str, RepoPath, RepoPathBuf, RepoPathComponent, RepoPathComponentBuf,
+};
 use crate::signing::SignResult;
 
 id_type!(pub CommitId);
@@ -140,17 +144,52 @@ impl MergedTreeId {
     }
 }
 
-content_hash! {
-    #[derive(Debug, PartialEq, Eq, Clone)]
-    pub struct Commit {
-        pub parents: Vec<CommitId>,
-        pub predecessors: Vec<CommitId>,
-        pub root_tree: MergedTreeId,
-        pub change_id: ChangeId,
-        pub description: String,
-        pub author: Signature,
-        pub committer: Signature,
-        pub secure_sig: Option<SecureSig>,
+/// Commit metadata key recording how a commit entered the repo if it wasn't
+/// created by jj. Set to [`COMMITTED_VIA_GIT`] for commits imported from Git.
+pub const COMMITTED_VIA_METADATA_KEY: &str = "committed-via";
+/// Value of [`COMMITTED_VIA_METADATA_KEY`] for commits imported from Git.
+pub const COMMITTED_VIA_GIT: &str = "git";
+/// Commit metadata key that is set (to "true") if the description was
+/// generated by jj rather than written by the user.
+pub const GENERATED_DESCRIPTION_METADATA_KEY: &str = "generated-description";
+/// Commit metadata key recording files that were explicitly copied or moved,
+/// as a JSON object mapping each destination path to its source path.
+pub const COPY_SOURCES_METADATA_KEY: &str = "copy-sources";
+/// Commit metadata key recording where each term of a conflicted commit's tree
+/// came from, as a JSON array with a label for each term of `root_tree`, in the
+/// order of `Merge::iter()`. An empty label means the origin is unknown.
+pub const CONFLICT_LABELS_METADATA_KEY: &str = "conflict-labels";
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Commit {
+    pub parents: Vec<CommitId>,
+    pub predecessors: Vec<CommitId>,
+    pub root_tree: MergedTreeId,
+    pub change_id: ChangeId,
+    pub description: String,
+    pub author: Signature,
+    pub committer: Signature,
+    pub secure_sig: Option<SecureSig>,
+    /// Extra key-value pairs. Backends must preserve keys they don't know
+    /// about, so future versions can add keys.
+    pub metadata: BTreeMap<String, String>,
+}
+
+impl ContentHash for Commit {
+    fn hash(&self, state: &mut impl digest::Update) {
+        self.parents.hash(state);
+        self.predecessors.hash(state);
+        self.root_tree.hash(state);
+        self.change_id.hash(state);
+        self.description.hash(state);
+        self.author.hash(state);
+        self.committer.hash(state);
+        self.secure_sig.hash(state);
+        // Commits without metadata hash as they did before the field was
+        // added, so their ids don't change.
+        if !self.metadata.is_empty() {
+            self.metadata.hash(state);
+        }
     }
 }
 
@@ -219,12 +258,46 @@ pub enum BackendError {
         object_type: &'static str,
         source: Box<dyn std::error::Error + Send + Sync>,
     },
+    #[error("Refusing to write {parent}: it references {missing_type} {missing_hash}, which doesn't exist")]
+    DanglingReference {
+        parent: String,
+        missing_type: String,
+        missing_hash: String,
+    },
     #[error(transparent)]
     Other(Box<dyn std::error::Error + Send + Sync>),
 }
 
 pub type BackendResult<T> = Result<T, BackendError>;
 
+/// The kinds of objects stored in a backend.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum ObjectKind {
+    Commit,
+    Tree,
+    File,
+    Symlink,
+    Conflict,
+}
+
+impl ObjectKind {
+    pub fn name(&self) -> &'static str {
+        match self {
+            ObjectKind::Commit => "commit",
+            ObjectKind::Tree => "tree",
+            ObjectKind::File => "file",
+            ObjectKind::Symlink => "symlink",
+            ObjectKind::Conflict => "conflict",
+        }
+    }
+}
+
+impl fmt::Display for ObjectKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
 #[derive(Debug, PartialEq, Eq, Clone, Hash)]
 pub enum TreeValue {
     File { id: FileId, executable: bool },
@@ -309,13 +382,29 @@ impl<'a> Iterator for TreeEntriesNonRecursiveIterator<'a> {
     }
 }
 
-content_hash! {
-    #[derive(Default, PartialEq, Eq, Debug, Clone)]
-    pub struct Tree {
-        entries: BTreeMap<RepoPathComponentBuf, TreeValue>,
+#[derive(Default, Debug, Clone)]
+pub struct Tree {
+    entries: BTreeMap<RepoPathComponentBuf, TreeValue>,
+    /// Whether this tree or any tree reachable from it has a conflict entry,
+    /// if known. This is derived from the entries, so it's not part of the
+    /// tree's content hash and is ignored when comparing trees.
+    has_conflict: Option<bool>,
+}
+
+impl ContentHash for Tree {
+    fn hash(&self, state: &mut impl digest::Update) {
+        self.entries.hash(state);
     }
 }
 
+impl PartialEq for Tree {
+    fn eq(&self, other: &Self) -> bool {
+        self.entries == other.entries
+    }
+}
+
+impl Eq for Tree {}
+
 impl Tree {
     pub fn is_empty(&self) -> bool {
         self.entries.is_empty()
@@ -333,10 +422,41 @@ impl Tree {
 
     pub fn set(&mut self, name: RepoPathComponentBuf, value: TreeValue) {
         self.entries.insert(name, value);
+        self.has_conflict = None;
     }
 
     pub fn remove(&mut self, name: &RepoPathComponent) {
         self.entries.remove(name);
+        self.has_conflict = None;
+    }
+
+    /// Whether this tree or any tree reachable from it has a conflict entry,
+    /// or `None` if that isn't known without walking the subtrees.
+    pub fn has_conflict_hint(&self) -> Option<bool> {
+        self.has_conflict
+    }
+
+    /// Records whether this tree has conflicts. The hint is reset whenever an
+    /// entry is changed.
+    pub fn set_has_conflict_hint(&mut self, has_conflict: Option<bool>) {
+        self.has_conflict = has_conflict;
+    }
+
+    /// Removes the entries pointing at the empty tree.
+    ///
+    /// Trees written to the store must not contain such entries. Otherwise two
+    /// trees with the same contents could have different ids.
+    pub fn normalize(&mut self, empty_tree_id: &TreeId) {
+        self.entries
+            .retain(|_, value| !matches!(value, TreeValue::Tree(id) if id == empty_tree_id));
+    }
+
+    /// Returns true if no entry points at the empty tree.
+    pub fn is_normalized(&self, empty_tree_id: &TreeId) -> bool {
+        !self
+            .entries
+            .values()
+            .any(|value| matches!(value, TreeValue::Tree(id) if id == empty_tree_id))
     }
 
     pub fn set_or_remove(&mut self, name: &RepoPathComponent, value: Option<TreeValue>) {
@@ -348,6 +468,7 @@ impl Tree {
                 self.entries.insert(name.to_owned(), value);
             }
         }
+        self.has_conflict = None;
     }
 
     pub fn entry(&self, name: &RepoPathComponent) -> Option<TreeEntry> {
@@ -380,6 +501,92 @@ pub fn make_root_commit(root_change_id: ChangeId, empty_tree_id: TreeId) -> Comm
         author: signature.clone(),
         committer: signature,
         secure_sig: None,
+        metadata: BTreeMap::new(),
+    }
+}
+
+/// Reads the copy sources recorded under [`COPY_SOURCES_METADATA_KEY`].
+/// Malformed values are ignored.
+pub(crate) fn parse_copy_sources(
+    metadata: &BTreeMap<String, String>,
+) -> BTreeMap<RepoPathBuf, RepoPathBuf> {
+    let Some(value) = metadata.get(COPY_SOURCES_METADATA_KEY) else {
+        return BTreeMap::new();
+    };
+    let Ok(sources) = serde_json::from_str::<BTreeMap<String, String>>(value) else {
+        return BTreeMap::new();
+    };
+    let is_valid = |path: &str| !path.is_empty() && is_valid_repo_path_str(path);
+    sources
+        .into_iter()
+        .filter(|(dest, source)| is_valid(dest) && is_valid(source))
+        .map(|(dest, source)| {
+            (
+                RepoPathBuf::from_internal_string(dest),
+                RepoPathBuf::from_internal_string(source),
+            )
+        })
+        .collect()
+}
+
+/// Records `sources` under [`COPY_SOURCES_METADATA_KEY`], removing the key if
+/// there are none.
+pub(crate) fn set_copy_sources(
+    metadata: &mut BTreeMap<String, String>,
+    sources: &BTreeMap<RepoPathBuf, RepoPathBuf>,
+) {
+    if sources.is_empty() {
+        metadata.remove(COPY_SOURCES_METADATA_KEY);
+        return;
+    }
+    // JSON strings can't represent paths that aren't valid UTF-8, so copies of
+    // such files aren't recorded
+    fn to_str(path: &RepoPath) -> Option<&str> {
+        str::from_utf8(path.as_internal_bytes()).ok()
+    }
+    let sources: BTreeMap<&str, &str> = sources
+        .iter()
+        .filter_map(|(dest, source)| Some((to_str(dest)?, to_str(source)?)))
+        .collect();
+    let value = serde_json::to_string(&sources).unwrap();
+    metadata.insert(COPY_SOURCES_METADATA_KEY.to_owned(), value);
+}
+
+/// Reads the conflict labels recorded under [`CONFLICT_LABELS_METADATA_KEY`].
+/// Returns `None` if there are none, if they're malformed, or if they don't
+/// have a label for each term of the conflicted `root_tree`.
+pub(crate) fn parse_conflict_labels(
+    metadata: &BTreeMap<String, String>,
+    root_tree: &MergedTreeId,
+) -> Option<Merge<String>> {
+    let value = metadata.get(CONFLICT_LABELS_METADATA_KEY)?;
+    let labels: Vec<String> = serde_json::from_str(value).ok()?;
+    conflict_labels_match(root_tree, labels.len()).then(|| Merge::from_vec(labels))
+}
+
+/// Records `labels` under [`CONFLICT_LABELS_METADATA_KEY`]. The key is
+/// removed instead if there are no labels or if they're all empty.
+pub(crate) fn set_conflict_labels(
+    metadata: &mut BTreeMap<String, String>,
+    labels: Option<&Merge<String>>,
+) {
+    match labels.filter(|labels| labels.iter().any(|label| !label.is_empty())) {
+        Some(labels) => {
+            let value = serde_json::to_string(&labels.iter().collect::<Vec<_>>()).unwrap();
+            metadata.insert(CONFLICT_LABELS_METADATA_KEY.to_owned(), value);
+        }
+        None => {
+            metadata.remove(CONFLICT_LABELS_METADATA_KEY);
+        }
+    }
+}
+
+fn conflict_labels_match(root_tree: &MergedTreeId, num_labels: usize) -> bool {
+    match root_tree {
+        MergedTreeId::Legacy(_) => false,
+        MergedTreeId::Merge(tree_ids) => {
+            !tree_ids.is_resolved() && tree_ids.iter().len() == num_labels
+        }
     }
 }
 
@@ -451,10 +658,60 @@ pub trait Backend: Send + Sync + Debug {
         sign_with: Option<&mut SigningFn>,
     ) -> BackendResult<(CommitId, Commit)>;
 
+    /// Returns whether an object of the given kind and id exists in the
+    /// backend. Used for validating references in newly written objects, so
+    /// it should be cheap.
+    fn contains(&self, kind: ObjectKind, id: &[u8]) -> BackendResult<bool>;
+
     /// Perform garbage collection.
     ///
     /// All commits found in the `index` won't be removed. In addition to that,
     /// objects created after `keep_newer` will be preserved. This mitigates a
     /// risk of deleting new commits created concurrently by another process.
     fn gc(&self, index: &dyn Index, keep_newer: SystemTime) -> BackendResult<()>;
+
+    /// Makes sure the given commits aren't removed by garbage collection run
+    /// outside of jj. Called with the commits newly referenced by an operation
+    /// before the operation is written. The default implementation does
+    /// nothing.
+    fn keep_commits(&self, ids: &[CommitId]) -> BackendResult<()> {
+        let _ = ids;
+        Ok(())
+    }
+
+    /// Finds the stored objects whose ids start with `prefix`, for debugging.
+    /// If `kind` is set, only objects that can be read as that kind are
+    /// returned. Otherwise, backends that store several kinds of objects in the
+    /// same way (e.g. files and symlinks) may report them as any of these
+    /// kinds. The default implementation returns an error.
+    fn find_objects_by_prefix(
+        &self,
+        prefix: &HexPrefix,
+        kind: Option<ObjectKind>,
+    ) -> BackendResult<Vec<(ObjectKind, Vec<u8>)>> {
+        let _ = (prefix, kind);
+        Err(BackendError::Other(
+            format!(
+                "The {} backend can't look up objects by prefix",
+                self.name()
+            )
+            .into(),
+        ))
+    }
+
+    /// Reads an object as it's stored by the backend, for debugging. The
+    /// default implementation returns an error.
+    fn read_raw_object(&self, kind: ObjectKind, id: &[u8]) -> BackendResult<Vec<u8>> {
+        let _ = (kind, id);
+        Err(BackendError::Other(
+            format!("The {} backend can't read raw objects", self.name()).into(),
+        ))
+    }
+
+    /// Returns the size of an object as it's stored by the backend, i.e. the
+    /// length of what `read_raw_object()` returns. The default implementation
+    /// reads the object.
+    fn object_size(&self, kind: ObjectKind, id: &[u8]) -> BackendResult<u64> {
+        Ok(self.read_raw_object(kind, id)?.len() as u64)
+    }
 }
@@ -0,0 +1,589 @@
+// Copyright 2024 The Jujutsu Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Bundles move commits between repos without a remote.
+//!
+//! A bundle is a single file with a set of commits and all the objects they
+//! reference. It starts with a header:
+//!
+//! * the magic bytes `JJBUNDLE`,
+//! * the format version,
+//! * flags for the capabilities needed to read the bundle,
+//! * the id of the root commit in the backend the bundle was written from,
+//! * the ids of the head commits,
+//! * the ids of the prerequisite commits, which are parents of commits in the
+//!   bundle that aren't in the bundle themselves.
+//!
+//! Then come the objects in dependency order: files, symlinks, and conflicts
+//! before the trees that contain them, trees before the commits that point to
+//! them, and parents before their children. Each object is a record of its
+//! kind, path, id in the source backend, the length of its contents, the
+//! contents, and a BLAKE2b-512 hash of the contents. Commits, trees, and
+//! conflicts are encoded like in the local backend. A zero byte ends the list.
+//!
+//! Integers are little-endian. Variable-length fields are prefixed with their
+//! length as a 32-bit integer, except for object contents, which use a 64-bit
+//! length.
+
+#![allow(missing_docs)]
+
+use std::collections::{HashMap, HashSet};
+use std::io::{self, Read, Write};
+use std::sync::Arc;
+
+use blake2::{Blake2b512, Digest};
+use itertools::Itertools;
+use prost::Message;
+use thiserror::Error;
+
+use crate::backend::{
+    self, BackendError, CommitId, ConflictId, FileId, MergedTreeId, ObjectKind, SymlinkId, TreeId,
+    TreeValue,
+};
+use crate::commit::Commit;
+use crate::dag_walk;
+use crate::local_backend::{
+    commit_from_proto, commit_to_proto, conflict_from_proto, conflict_to_proto, tree_from_proto,
+    tree_to_proto,
+};
+use crate::merge::Merge;
+use crate::object_id::ObjectId;
+use crate::repo::{MutableRepo, Repo};
+use crate::repo_path::{RepoPath, RepoPathBuf};
+use crate::store::Store;
+
+const MAGIC: &[u8] = b"JJBUNDLE";
+
+/// The version of the bundle format written by this version of jj.
+pub const BUNDLE_FORMAT_VERSION: u32 = 1;
+
+/// The bundle has path-level conflict objects.
+pub const CAPABILITY_CONFLICT_OBJECTS: u32 = 1 << 0;
+/// The bundle has trees with Git submodules. The submodule commits aren't
+/// included.
+pub const CAPABILITY_GIT_SUBMODULES: u32 = 1 << 1;
+
+const SUPPORTED_CAPABILITIES: u32 = CAPABILITY_CONFLICT_OBJECTS | CAPABILITY_GIT_SUBMODULES;
+
+const END_OF_OBJECTS: u8 = 0;
+const HASH_LENGTH: usize = 64;
+/// Upper bound on the length of ids and paths, and on the number of heads and
+/// prerequisites, so a corrupt bundle doesn't make us allocate huge buffers.
+const MAX_FIELD_LENGTH: u32 = 1 << 20;
+
+#[derive(Debug, Error)]
+pub enum BundleError {
+    #[error("Not a bundle file")]
+    NotABundle,
+    #[error("Unsupported bundle format version {0}")]
+    UnsupportedVersion(u32),
+    #[error("The bundle needs unsupported capabilities (flags {0:#x})")]
+    UnsupportedCapabilities(u32),
+    #[error("Corrupt bundle: {0}")]
+    Corrupt(String),
+    #[error("The bundle depends on commit {0}, which isn't in the repo")]
+    MissingPrerequisite(String),
+    #[error(transparent)]
+    Backend(#[from] BackendError),
+    #[error(transparent)]
+    Io(#[from] io::Error),
+}
+
+/// Summary of a bundle that was written or applied.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct BundleStats {
+    pub num_commits: usize,
+    /// Number of objects in the bundle, including the commits.
+    pub num_objects: usize,
+    /// Number of objects that the repo already had when the bundle was applied.
+    pub num_existing_objects: usize,
+}
+
+/// The result of applying a bundle.
+#[derive(Debug)]
+pub struct AppliedBundle {
+    /// The head commits of the bundle, with their ids in the target repo.
+    pub heads: Vec<Commit>,
+    pub stats: BundleStats,
+}
+
+/// Writes a bundle with the given commits and all the objects they reference.
+///
+/// Parents that aren't in `commits` become prerequisites, which the target
+/// repo must already have. Commit signatures aren't included, since the
+/// commits may get new ids when the bundle is applied to a repo with a
+/// different backend.
+pub fn write_bundle(
+    store: &Arc<Store>,
+    commits: &[Commit],
+    output: &mut dyn Write,
+) -> Result<BundleStats, BundleError> {
+    // Every repo has the root commit.
+    let commits = commits
+        .iter()
+        .filter(|commit| commit.id() != store.root_commit_id())
+        .collect_vec();
+    let commits_by_id: HashMap<&CommitId, &Commit> = commits
+        .iter()
+        .map(|&commit| (commit.id(), commit))
+        .collect();
+    // Parents before children
+    let mut sorted_commits = dag_walk::topo_order_reverse(
+        commits.iter().copied(),
+        |commit| commit.id().clone(),
+        |commit| {
+            commit
+                .parent_ids()
+                .iter()
+                .filter_map(|id| commits_by_id.get(id).copied())
+                .collect_vec()
+        },
+    );
+    sorted_commits.reverse();
+
+    let parent_ids: HashSet<&CommitId> = sorted_commits
+        .iter()
+        .flat_map(|commit| commit.parent_ids())
+        .collect();
+    let heads = sorted_commits
+        .iter()
+        .map(|commit| commit.id())
+        .filter(|id| !parent_ids.contains(id))
+        .collect_vec();
+    let prerequisites = parent_ids
+        .iter()
+        .copied()
+        .filter(|id| !commits_by_id.contains_key(id) && *id != store.root_commit_id())
+        .sorted()
+        .collect_vec();
+
+    let mut collector = ObjectCollector {
+        store,
+        seen: HashSet::new(),
+        objects: vec![],
+        capabilities: 0,
+    };
+    for commit in &sorted_commits {
+        for tree_id in commit.tree_id().to_merge().iter() {
+            collector.add_tree(RepoPath::root(), tree_id)?;
+        }
+        collector.objects.push((
+            ObjectKind::Commit,
+            RepoPathBuf::root(),
+            commit.id().to_bytes(),
+        ));
+    }
+
+    output.write_all(MAGIC)?;
+    write_u32(output, BUNDLE_FORMAT_VERSION)?;
+    write_u32(output, collector.capabilities)?;
+    write_field(output, store.root_commit_id().as_bytes())?;
+    write_u32(output, heads.len() as u32)?;
+    for id in heads {
+        write_field(output, id.as_bytes())?;
+    }
+    write_u32(output, prerequisites.len() as u32)?;
+    for id in prerequisites {
+        write_field(output, id.as_bytes())?;
+    }
+    for (kind, path, id) in &collector.objects {
+        let contents = read_object_contents(store, *kind, path, id)?;
+        output.write_all(&[kind_to_byte(*kind)])?;
+        write_field(output, path.as_internal_bytes())?;
+        write_field(output, id)?;
+        output.write_all(&(contents.len() as u64).to_le_bytes())?;
+        output.write_all(&contents)?;
+        output.write_all(&Blake2b512::digest(&contents))?;
+    }
+    output.write_all(&[END_OF_OBJECTS])?;
+    output.flush()?;
+
+    Ok(BundleStats {
+        num_commits: sorted_commits.len(),
+        num_objects: collector.objects.len(),
+        num_existing_objects: 0,
+    })
+}
+
+/// Imports the objects in a bundle into the repo's store and adds the head
+/// commits of the bundle as visible heads. Objects the store already has are
+/// skipped.
+///
+/// If the repo uses a different backend than the repo the bundle was written
+/// from, the objects get new ids as they're written. References between the
+/// objects are translated accordingly.
+pub fn apply_bundle(
+    mut_repo: &mut MutableRepo,
+    input: &mut dyn Read,
+) -> Result<AppliedBundle, BundleError> {
+    let store = mut_repo.store().clone();
+    let mut magic = [0; MAGIC.len()];
+    if input.read_exact(&mut magic).is_err() || magic != MAGIC {
+        return Err(BundleError::NotABundle);
+    }
+    let version = read_u32(input)?;
+    if version != BUNDLE_FORMAT_VERSION {
+        return Err(BundleError::UnsupportedVersion(version));
+    }
+    let capabilities = read_u32(input)?;
+    if capabilities & !SUPPORTED_CAPABILITIES != 0 {
+        return Err(BundleError::UnsupportedCapabilities(
+            capabilities & !SUPPORTED_CAPABILITIES,
+        ));
+    }
+    let root_commit_id = read_field(input)?;
+    let num_heads = read_count(input)?;
+    let head_ids: Vec<_> = (0..num_heads).map(|_| read_field(input)).try_collect()?;
+    let num_prerequisites = read_count(input)?;
+    let prerequisite_ids: Vec<_> = (0..num_prerequisites)
+        .map(|_| read_field(input))
+        .try_collect()?;
+
+    let mut translator = IdTranslator::default();
+    translator.insert(
+        ObjectKind::Commit,
+        root_commit_id,
+        store.root_commit_id().to_bytes(),
+    );
+    for id in prerequisite_ids {
+        if !store.contains(ObjectKind::Commit, &id)? {
+            return Err(BundleError::MissingPrerequisite(hex::encode(id)));
+        }
+        translator.insert(ObjectKind::Commit, id.clone(), id);
+    }
+
+    let mut stats = BundleStats::default();
+    while let Some((kind, path, id, contents)) = read_object(input)? {
+        stats.num_objects += 1;
+        if kind == ObjectKind::Commit {
+            stats.num_commits += 1;
+        }
+        if store.contains(kind, &id)? {
+            stats.num_existing_objects += 1;
+            translator.insert(kind, id.clone(), id);
+            continue;
+        }
+        let new_id = write_object(&store, &translator, kind, &path, contents)?;
+        translator.insert(kind, id, new_id);
+    }
+
+    let heads: Vec<_> = head_ids
+        .iter()
+        .map(|id| {
+            let id = CommitId::new(translator.get(ObjectKind::Commit, id)?);
+            Ok::<_, BundleError>(store.get_commit(&id)?)
+        })
+        .try_collect()?;
+    mut_repo.add_heads(&heads)?;
+    Ok(AppliedBundle { heads, stats })
+}
+
+/// Lists the objects reachable from commits in dependency order.
+struct ObjectCollector<'a> {
+    store: &'a Arc<Store>,
+    seen: HashSet<(ObjectKind, Vec<u8>)>,
+    objects: Vec<(ObjectKind, RepoPathBuf, Vec<u8>)>,
+    capabilities: u32,
+}
+
+impl ObjectCollector<'_> {
+    fn add_tree(&mut self, dir: &RepoPath, id: &TreeId) -> Result<(), BackendError> {
+        if !self.seen.insert((ObjectKind::Tree, id.to_bytes())) {
+            return Ok(());
+        }
+        let tree = self.store.get_tree(dir, id)?;
+        for entry in tree.entries_non_recursive() {
+            self.add_value(&dir.join(entry.name()), entry.value())?;
+        }
+        self.objects
+            .push((ObjectKind::Tree, dir.to_owned(), id.to_bytes()));
+        Ok(())
+    }
+
+    fn add_value(&mut self, path: &RepoPath, value: &TreeValue) -> Result<(), BackendError> {
+        match value {
+            TreeValue::File { id, .. } => self.add_leaf(ObjectKind::File, path, id.to_bytes()),
+            TreeValue::Symlink(id) => self.add_leaf(ObjectKind::Symlink, path, id.to_bytes()),
+            TreeValue::Tree(id) => self.add_tree(path, id)?,
+            TreeValue::GitSubmodule(_) => self.capabilities |= CAPABILITY_GIT_SUBMODULES,
+            TreeValue::Conflict(id) => {
+                self.capabilities |= CAPABILITY_CONFLICT_OBJECTS;
+                if self.seen.contains(&(ObjectKind::Conflict, id.to_bytes())) {
+                    return Ok(());
+                }
+                let conflict = self.store.read_conflict(path, id)?;
+                for value in conflict.iter().flatten() {
+                    self.add_value(path, value)?;
+                }
+                self.add_leaf(ObjectKind::Conflict, path, id.to_bytes());
+            }
+        }
+        Ok(())
+    }
+
+    fn add_leaf(&mut self, kind: ObjectKind, path: &RepoPath, id: Vec<u8>) {
+        if self.seen.insert((kind, id.clone())) {
+            self.objects.push((kind, path.to_owned(), id));
+        }
+    }
+}
+
+fn read_object_contents(
+    store: &Arc<Store>,
+    kind: ObjectKind,
+    path: &RepoPath,
+    id: &[u8],
+) -> Result<Vec<u8>, BundleError> {
+    let contents = match kind {
+        ObjectKind::File => {
+            let mut contents = vec![];
+            store
+                .read_file(path, &FileId::from_bytes(id))?
+                .read_to_end(&mut contents)?;
+            contents
+        }
+        ObjectKind::Symlink => store
+            .read_symlink(path, &SymlinkId::from_bytes(id))?
+            .into_bytes(),
+        ObjectKind::Conflict => {
+            let conflict = store.read_conflict(path, &ConflictId::from_bytes(id))?;
+            conflict_to_proto(&conflict.into_backend_conflict()).encode_to_vec()
+        }
+        ObjectKind::Tree => {
+            let tree = store.get_tree(path, &TreeId::from_bytes(id))?;
+            tree_to_proto(tree.data()).encode_to_vec()
+        }
+        ObjectKind::Commit => {
+            let commit = store.get_commit(&CommitId::from_bytes(id))?;
+            commit_to_proto(commit.store_commit()).encode_to_vec()
+        }
+    };
+    Ok(contents)
+}
+
+fn write_object(
+    store: &Arc<Store>,
+    translator: &IdTranslator,
+    kind: ObjectKind,
+    path: &RepoPath,
+    contents: Vec<u8>,
+) -> Result<Vec<u8>, BundleError> {
+    let decode_error = |err: prost::DecodeError| BundleError::Corrupt(format!("Bad {kind}: {err}"));
+    let id = match kind {
+        ObjectKind::File => store.write_file(path, &mut contents.as_slice())?.to_bytes(),
+        ObjectKind::Symlink => {
+            let target = String::from_utf8(contents)
+                .map_err(|_| BundleError::Corrupt("Symlink target isn't UTF-8".to_owned()))?;
+            store.write_symlink(path, &target)?.to_bytes()
+        }
+        ObjectKind::Conflict => {
+            let proto = crate::protos::local_store::Conflict::decode(contents.as_slice())
+                .map_err(decode_error)?;
+            let conflict = Merge::from_backend_conflict(conflict_from_proto(proto));
+            let conflict = conflict.try_map(|value| {
+                value
+                    .as_ref()
+                    .map(|value| translator.translate_value(value))
+                    .transpose()
+            })?;
+            store.write_conflict(path, &conflict)?.to_bytes()
+        }
+        ObjectKind::Tree => {
+            let proto = crate::protos::local_store::Tree::decode(contents.as_slice())
+                .map_err(decode_error)?;
+            let mut tree = backend::Tree::default();
+            for entry in tree_from_proto(proto).entries() {
+                let value = translator.translate_value(entry.value())?;
+                tree.set(entry.name().to_owned(), value);
+            }
+            store.write_tree(path, tree)?.id().to_bytes()
+        }
+        ObjectKind::Commit => {
+            let proto = crate::protos::local_store::Commit::decode(contents.as_slice())
+                .map_err(decode_error)?;
+            let mut commit = commit_from_proto(proto);
+            commit.parents = commit
+                .parents
+                .iter()
+                .map(|id| Ok(CommitId::new(translator.get(kind, id.as_bytes())?)))
+                .collect::<Result<_, BundleError>>()?;
+            if commit.parents.is_empty() {
+                return Err(BundleError::Corrupt("Commit has no parents".to_owned()));
+            }
+            // Predecessors outside the bundle are only history, so they're dropped.
+            commit.predecessors = commit
+                .predecessors
+                .iter()
+                .filter_map(|id| translator.get(kind, id.as_bytes()).ok())
+                .map(CommitId::new)
+                .collect();
+            commit.root_tree = match &commit.root_tree {
+                MergedTreeId::Legacy(id) => MergedTreeId::Legacy(TreeId::new(
+                    translator.get(ObjectKind::Tree, id.as_bytes())?,
+                )),
+                MergedTreeId::Merge(ids) => MergedTreeId::Merge(ids.try_map(|id| {
+                    Ok::<_, BundleError>(TreeId::new(
+                        translator.get(ObjectKind::Tree, id.as_bytes())?,
+                    ))
+                })?),
+            };
+            store.write_commit(commit, None)?.id().to_bytes()
+        }
+    };
+    Ok(id)
+}
+
+/// Maps ids of objects in the bundle to their ids in the target store.
+#[derive(Default)]
+struct IdTranslator {
+    ids: HashMap<(ObjectKind, Vec<u8>), Vec<u8>>,
+}
+
+impl IdTranslator {
+    fn insert(&mut self, kind: ObjectKind, source_id: Vec<u8>, target_id: Vec<u8>) {
+        self.ids.insert((kind, source_id), target_id);
+    }
+
+    fn get(&self, kind: ObjectKind, source_id: &[u8]) -> Result<Vec<u8>, BundleError> {
+        self.ids
+            .get(&(kind, source_id.to_vec()))
+            .cloned()
+            .ok_or_else(|| {
+                BundleError::Corrupt(format!(
+                    "Reference to {kind} {} that isn't in the bundle",
+                    hex::encode(source_id)
+                ))
+            })
+    }
+
+    fn translate_value(&self, value: &TreeValue) -> Result<TreeValue, BundleError> {
+        let value = match value {
+            TreeValue::File { id, executable } => TreeValue::File {
+                id: FileId::new(self.get(ObjectKind::File, id.as_bytes())?),
+                executable: *executable,
+            },
+            TreeValue::Symlink(id) => TreeValue::Symlink(SymlinkId::new(
+                self.get(ObjectKind::Symlink, id.as_bytes())?,
+            )),
+            TreeValue::Tree(id) => {
+                TreeValue::Tree(TreeId::new(self.get(ObjectKind::Tree, id.as_bytes())?))
+            }
+            TreeValue::Conflict(id) => TreeValue::Conflict(ConflictId::new(
+                self.get(ObjectKind::Conflict, id.as_bytes())?,
+            )),
+            TreeValue::GitSubmodule(id) => TreeValue::GitSubmodule(id.clone()),
+        };
+        Ok(value)
+    }
+}
+
+fn kind_to_byte(kind: ObjectKind) -> u8 {
+    match kind {
+        ObjectKind::File => 1,
+        ObjectKind::Symlink => 2,
+        ObjectKind::Conflict => 3,
+        ObjectKind::Tree => 4,
+        ObjectKind::Commit => 5,
+    }
+}
+
+fn kind_from_byte(byte: u8) -> Option<ObjectKind> {
+    match byte {
+        1 => Some(ObjectKind::File),
+        2 => Some(ObjectKind::Symlink),
+        3 => Some(ObjectKind::Conflict),
+        4 => Some(ObjectKind::Tree),
+        5 => Some(ObjectKind::Commit),
+        _ => None,
+    }
+}
+
+/// Reads the next object record. Returns `None` at the end of the objects.
+#[allow(clippy::type_complexity)]
+fn read_object(
+    input: &mut dyn Read,
+) -> Result<Option<(ObjectKind, RepoPathBuf, Vec<u8>, Vec<u8>)>, BundleError> {
+    let mut kind_byte = [0];
+    read_exact(input, &mut kind_byte)?;
+    if kind_byte[0] == END_OF_OBJECTS {
+        return Ok(None);
+    }
+    let kind = kind_from_byte(kind_byte[0])
+        .ok_or_else(|| BundleError::Corrupt(format!("Unknown object kind {}", kind_byte[0])))?;
+    let path = Some(read_field(input)?)
+        .filter(|path| path.is_empty() || !path.split(|&b| b == b'/').any(|name| name.is_empty()))
+        .ok_or_else(|| BundleError::Corrupt("Invalid path".to_owned()))?;
+    let path = RepoPathBuf::from_internal_bytes(path);
+    let id = read_field(input)?;
+    let mut len = [0; 8];
+    read_exact(input, &mut len)?;
+    let mut contents = vec![];
+    Read::take(&mut *input, u64::from_le_bytes(len)).read_to_end(&mut contents)?;
+    if contents.len() as u64 != u64::from_le_bytes(len) {
+        return Err(unexpected_end());
+    }
+    let mut hash = [0; HASH_LENGTH];
+    read_exact(input, &mut hash)?;
+    if Blake2b512::digest(&contents).as_slice() != hash.as_slice() {
+        return Err(BundleError::Corrupt(format!(
+            "Hash mismatch for {kind} {}",
+            hex::encode(&id)
+        )));
+    }
+    Ok(Some((kind, path, id, contents)))
+}
+
+fn write_u32(output: &mut dyn Write, value: u32) -> io::Result<()> {
+    output.write_all(&value.to_le_bytes())
+}
+
+fn write_field(output: &mut dyn Write, value: &[u8]) -> io::Result<()> {
+    write_u32(output, value.len() as u32)?;
+    output.write_all(value)
+}
+
+fn read_u32(input: &mut dyn Read) -> Result<u32, BundleError> {
+    let mut buf = [0; 4];
+    read_exact(input, &mut buf)?;
+    Ok(u32::from_le_bytes(buf))
+}
+
+fn read_count(input: &mut dyn Read) -> Result<u32, BundleError> {
+    let count = read_u32(input)?;
+    if count > MAX_FIELD_LENGTH {
+        return Err(BundleError::Corrupt(format!("Count {count} is too large")));
+    }
+    Ok(count)
+}
+
+fn read_field(input: &mut dyn Read) -> Result<Vec<u8>, BundleError> {
+    let len = read_count(input)?;
+    let mut value = vec![0; len as usize];
+    read_exact(input, &mut value)?;
+    Ok(value)
+}
+
+fn read_exact(input: &mut dyn Read, buf: &mut [u8]) -> Result<(), BundleError> {
+    input.read_exact(buf).map_err(|err| {
+        if err.kind() == io::ErrorKind::UnexpectedEof {
+            unexpected_end()
+        } else {
+            BundleError::Io(err)
+        }
+    })
+}
+
+fn unexpected_end() -> BundleError {
+    BundleError::Corrupt("Unexpected end of file".to_owned())
+}
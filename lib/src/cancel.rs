@@ -0,0 +1,139 @@
+// Copyright 2024 The Jujutsu Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Cooperative cancellation of long-running operations.
+//!
+//! A [`CancellationToken`] is handed to an operation such as a working-copy
+//! snapshot, and can be cancelled from any thread. The operation checks the
+//! token at points where it can stop without leaving the repo in an
+//! inconsistent state, and then returns [`Cancelled`] as an error. Nothing is
+//! rolled back: operations that would have been recorded by a transaction are
+//! just never committed.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+use thiserror::Error;
+
+/// Error returned by an operation that stopped because its
+/// [`CancellationToken`] was cancelled.
+#[derive(Clone, Copy, Debug, Error, PartialEq, Eq)]
+#[error("Operation was cancelled")]
+pub struct Cancelled;
+
+/// A shared flag that requests cancellation of the operations it was passed
+/// to. Clones share the same flag.
+#[derive(Clone, Debug, Default)]
+pub struct CancellationToken {
+    cancelled: Arc<AtomicBool>,
+}
+
+impl CancellationToken {
+    /// Creates a token that isn't cancelled yet.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Requests cancellation. Operations notice it at their next checkpoint.
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+    }
+
+    /// Returns true if `cancel()` has been called on this token or a clone of
+    /// it.
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Relaxed)
+    }
+
+    /// Returns `Err(Cancelled)` if the token has been cancelled.
+    pub fn checkpoint(&self) -> Result<(), Cancelled> {
+        if self.is_cancelled() {
+            Err(Cancelled)
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Wraps an iterator so that it yields `Err(Cancelled)` and then stops
+    /// once the token is cancelled. This is useful for interrupting renders
+    /// driven by e.g. a `TreeDiffIterator`.
+    pub fn wrap_iter<I: Iterator>(&self, iter: I) -> CancellableIter<I> {
+        CancellableIter {
+            iter,
+            token: self.clone(),
+            done: false,
+        }
+    }
+}
+
+// Tokens are compared by identity so they can be stored in option structs
+// that are compared for equality.
+impl PartialEq for CancellationToken {
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.cancelled, &other.cancelled)
+    }
+}
+
+impl Eq for CancellationToken {}
+
+/// Iterator returned by [`CancellationToken::wrap_iter()`].
+#[derive(Debug)]
+pub struct CancellableIter<I> {
+    iter: I,
+    token: CancellationToken,
+    done: bool,
+}
+
+impl<I: Iterator> Iterator for CancellableIter<I> {
+    type Item = Result<I::Item, Cancelled>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+        if let Err(err) = self.token.checkpoint() {
+            self.done = true;
+            return Some(Err(err));
+        }
+        self.iter.next().map(Ok)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cancellation_token() {
+        let token = CancellationToken::new();
+        let clone = token.clone();
+        assert_eq!(token, clone);
+        assert_ne!(token, CancellationToken::new());
+        assert_eq!(token.checkpoint(), Ok(()));
+        clone.cancel();
+        assert!(token.is_cancelled());
+        assert_eq!(token.checkpoint(), Err(Cancelled));
+    }
+
+    #[test]
+    fn test_cancellable_iter() {
+        let token = CancellationToken::new();
+        let mut iter = token.wrap_iter(0..10);
+        assert_eq!(iter.next(), Some(Ok(0)));
+        assert_eq!(iter.next(), Some(Ok(1)));
+        token.cancel();
+        assert_eq!(iter.next(), Some(Err(Cancelled)));
+        assert_eq!(iter.next(), None);
+    }
+}
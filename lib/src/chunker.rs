@@ -0,0 +1,276 @@
+// Copyright 2024 The Jujutsu Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Content-defined chunking of byte streams.
+//!
+//! Chunk boundaries are placed where a rolling "gear" hash of the preceding
+//! bytes matches a mask, so they depend only on the nearby content. Inserting
+//! or modifying bytes in one region of a file therefore only changes the
+//! chunks around that region, and the remaining chunks can be shared between
+//! versions of the file.
+
+#![allow(missing_docs)]
+
+use std::io::{self, Read};
+
+/// Table of pseudo-random values, one per byte value, mixed into the rolling
+/// hash. Generated at compile time so the boundaries are stable across
+/// versions.
+const GEAR: [u64; 256] = {
+    let mut table = [0; 256];
+    let mut state: u64 = 0x6a09_e667_f3bc_c908;
+    let mut i = 0;
+    while i < 256 {
+        // splitmix64
+        state = state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        table[i] = z ^ (z >> 31);
+        i += 1;
+    }
+    table
+};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ChunkerOptions {
+    /// No boundary is placed before a chunk has this many bytes.
+    pub min_size: usize,
+    /// The expected chunk size. Must be a power of two.
+    pub avg_size: usize,
+    /// A boundary is always placed when a chunk reaches this many bytes.
+    pub max_size: usize,
+}
+
+impl Default for ChunkerOptions {
+    fn default() -> Self {
+        ChunkerOptions {
+            min_size: 16 * 1024,
+            avg_size: 64 * 1024,
+            max_size: 256 * 1024,
+        }
+    }
+}
+
+/// Splits the contents of a reader into content-defined chunks.
+///
+/// Iterating yields the chunks in order. Concatenating them gives back the
+/// original contents.
+pub struct Chunker<R> {
+    reader: R,
+    options: ChunkerOptions,
+    mask: u64,
+    buf: Vec<u8>,
+    /// Position in `buf` of the next byte to feed into the hash.
+    pos: usize,
+    eof: bool,
+}
+
+impl<R: Read> Chunker<R> {
+    pub fn new(reader: R, options: ChunkerOptions) -> Self {
+        assert!(options.avg_size.is_power_of_two());
+        assert!(options.min_size <= options.max_size);
+        Chunker {
+            reader,
+            options,
+            mask: options.avg_size as u64 - 1,
+            buf: vec![],
+            pos: 0,
+            eof: false,
+        }
+    }
+
+    /// Reads more data into `buf`. Returns false at end of input.
+    fn fill_buf(&mut self) -> io::Result<bool> {
+        if self.eof {
+            return Ok(false);
+        }
+        let old_len = self.buf.len();
+        self.buf.resize(old_len + self.options.max_size, 0);
+        let bytes_read = loop {
+            match self.reader.read(&mut self.buf[old_len..]) {
+                Ok(bytes_read) => break bytes_read,
+                Err(err) if err.kind() == io::ErrorKind::Interrupted => continue,
+                Err(err) => return Err(err),
+            }
+        };
+        self.buf.truncate(old_len + bytes_read);
+        self.eof = bytes_read == 0;
+        Ok(!self.eof)
+    }
+
+    fn next_chunk(&mut self) -> io::Result<Option<Vec<u8>>> {
+        // The hash restarts at each chunk so the boundaries don't depend on
+        // what came before the previous boundary.
+        let mut hash: u64 = 0;
+        self.pos = 0;
+        loop {
+            while self.pos < self.buf.len() {
+                let len = self.pos + 1;
+                hash = (hash << 1).wrapping_add(GEAR[self.buf[self.pos] as usize]);
+                self.pos = len;
+                if len >= self.options.max_size
+                    || (len >= self.options.min_size && hash & self.mask == 0)
+                {
+                    let rest = self.buf.split_off(len);
+                    return Ok(Some(std::mem::replace(&mut self.buf, rest)));
+                }
+            }
+            if !self.fill_buf()? {
+                if self.buf.is_empty() {
+                    return Ok(None);
+                }
+                return Ok(Some(std::mem::take(&mut self.buf)));
+            }
+        }
+    }
+}
+
+impl<R: Read> Iterator for Chunker<R> {
+    type Item = io::Result<Vec<u8>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.next_chunk().transpose()
+    }
+}
+
+/// Reads the concatenation of a sequence of readers, opening each one only
+/// when the previous one is exhausted.
+pub struct ConcatReader<I, R> {
+    readers: I,
+    current: Option<R>,
+}
+
+impl<I, R> ConcatReader<I, R>
+where
+    I: Iterator<Item = io::Result<R>>,
+    R: Read,
+{
+    pub fn new(readers: impl IntoIterator<IntoIter = I>) -> Self {
+        ConcatReader {
+            readers: readers.into_iter(),
+            current: None,
+        }
+    }
+}
+
+impl<I, R> Read for ConcatReader<I, R>
+where
+    I: Iterator<Item = io::Result<R>>,
+    R: Read,
+{
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        loop {
+            if let Some(reader) = &mut self.current {
+                let bytes_read = reader.read(buf)?;
+                if bytes_read > 0 || buf.is_empty() {
+                    return Ok(bytes_read);
+                }
+            }
+            match self.readers.next() {
+                Some(reader) => self.current = Some(reader?),
+                None => return Ok(0),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use itertools::Itertools;
+
+    use super::*;
+
+    fn pseudo_random_bytes(len: usize, seed: u64) -> Vec<u8> {
+        let mut state = seed;
+        (0..len)
+            .map(|_| {
+                state = state
+                    .wrapping_mul(6364136223846793005)
+                    .wrapping_add(1442695040888963407);
+                (state >> 56) as u8
+            })
+            .collect()
+    }
+
+    fn chunk(data: &[u8], options: ChunkerOptions) -> Vec<Vec<u8>> {
+        Chunker::new(data, options).try_collect().unwrap()
+    }
+
+    #[test]
+    fn test_chunker_empty() {
+        assert!(chunk(b"", ChunkerOptions::default()).is_empty());
+    }
+
+    #[test]
+    fn test_chunker_sizes() {
+        let options = ChunkerOptions {
+            min_size: 64,
+            avg_size: 256,
+            max_size: 1024,
+        };
+        let data = pseudo_random_bytes(100_000, 1);
+        let chunks = chunk(&data, options);
+        assert_eq!(chunks.concat(), data);
+        let (last, rest) = chunks.split_last().unwrap();
+        assert!(rest
+            .iter()
+            .all(|chunk| (options.min_size..=options.max_size).contains(&chunk.len())));
+        assert!(last.len() <= options.max_size);
+
+        // Input without any boundaries is split at the max size
+        let data = vec![0; 3000];
+        let chunks = chunk(&data, options);
+        assert_eq!(
+            chunks.iter().map(|chunk| chunk.len()).collect_vec(),
+            vec![1024, 1024, 952]
+        );
+    }
+
+    #[test]
+    fn test_chunker_local_change() {
+        let options = ChunkerOptions {
+            min_size: 64,
+            avg_size: 256,
+            max_size: 1024,
+        };
+        let data1 = pseudo_random_bytes(100_000, 2);
+        let mut data2 = data1.clone();
+        data2.splice(50_000..50_010, *b"0123456789abcdef");
+        let chunks1 = chunk(&data1, options);
+        let chunks2 = chunk(&data2, options);
+        assert_eq!(chunks2.concat(), data2);
+        let shared = chunks2
+            .iter()
+            .filter(|chunk| chunks1.contains(chunk))
+            .count();
+        // Only the chunks around the change differ
+        assert!(shared + 3 >= chunks2.len(), "{shared} of {}", chunks2.len());
+    }
+
+    #[test]
+    fn test_concat_reader() {
+        let parts: Vec<io::Result<&[u8]>> = vec![Ok(b"abc"), Ok(b""), Ok(b"de")];
+        let mut reader = ConcatReader::new(parts);
+        let mut contents = vec![];
+        reader.read_to_end(&mut contents).unwrap();
+        assert_eq!(contents, b"abcde");
+
+        let parts: Vec<io::Result<&[u8]>> =
+            vec![Ok(b"abc"), Err(io::Error::other("failed to open"))];
+        let mut reader = ConcatReader::new(parts);
+        let mut contents = vec![];
+        assert!(reader.read_to_end(&mut contents).is_err());
+    }
+}
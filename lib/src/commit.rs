@@ -15,13 +15,16 @@
 #![allow(missing_docs)]
 
 use std::cmp::Ordering;
+use std::collections::BTreeMap;
 use std::fmt::{Debug, Error, Formatter};
 use std::hash::{Hash, Hasher};
 use std::sync::Arc;
 
 use crate::backend;
 use crate::backend::{BackendError, ChangeId, CommitId, MergedTreeId, Signature};
+use crate::merge::Merge;
 use crate::merged_tree::MergedTree;
+use crate::repo_path::{RepoPath, RepoPathBuf};
 use crate::signing::{SignResult, Verification};
 use crate::store::Store;
 
@@ -110,10 +113,11 @@ impl Commit {
     }
 
     pub fn has_conflict(&self) -> Result<bool, BackendError> {
-        if let MergedTreeId::Merge(tree_ids) = self.tree_id() {
-            Ok(!tree_ids.is_resolved())
-        } else {
-            Ok(self.tree()?.has_conflict())
+        match self.tree_id() {
+            MergedTreeId::Legacy(tree_id) => {
+                self.store.tree_has_conflict(RepoPath::root(), tree_id)
+            }
+            MergedTreeId::Merge(tree_ids) => Ok(!tree_ids.is_resolved()),
         }
     }
 
@@ -137,6 +141,25 @@ impl Commit {
         &self.data.committer
     }
 
+    /// Extra key-value pairs stored with the commit. See
+    /// [`backend::COMMITTED_VIA_METADATA_KEY`] for an example.
+    pub fn metadata(&self) -> &BTreeMap<String, String> {
+        &self.data.metadata
+    }
+
+    /// The files that were explicitly copied or moved in this commit, mapped
+    /// from their destination to their source path.
+    pub fn copy_sources(&self) -> BTreeMap<RepoPathBuf, RepoPathBuf> {
+        backend::parse_copy_sources(&self.data.metadata)
+    }
+
+    /// Labels describing where each term of the conflicted tree came from, in
+    /// the order of `Merge::iter()`, or `None` if none were recorded. Empty
+    /// labels are for terms of unknown origin.
+    pub fn conflict_labels(&self) -> Option<Merge<String>> {
+        backend::parse_conflict_labels(&self.data.metadata, &self.data.root_tree)
+    }
+
     /// A commit is discardable if it has one parent, no change from its
     /// parent, and an empty description.
     pub fn is_discardable(&self) -> bool {
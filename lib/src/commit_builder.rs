@@ -14,11 +14,19 @@
 
 #![allow(missing_docs)]
 
+use std::collections::BTreeMap;
 use std::sync::Arc;
 
-use crate::backend::{self, BackendResult, ChangeId, CommitId, MergedTreeId, Signature, SigningFn};
+use itertools::Itertools as _;
+
+use crate::backend::{
+    self, BackendResult, ChangeId, CommitId, MergedTreeId, Signature, SigningFn,
+    COMMITTED_VIA_METADATA_KEY, GENERATED_DESCRIPTION_METADATA_KEY,
+};
 use crate::commit::Commit;
+use crate::merge::Merge;
 use crate::repo::{MutableRepo, Repo};
+use crate::repo_path::RepoPathBuf;
 use crate::settings::{JJRng, SignSettings, UserSettings};
 use crate::signing::SignBehavior;
 
@@ -29,6 +37,8 @@ pub struct CommitBuilder<'repo> {
     commit: backend::Commit,
     rewrite_source: Option<Commit>,
     sign_settings: SignSettings,
+    normalize_description: bool,
+    clamp_committer_timestamp: bool,
 }
 
 impl CommitBuilder<'_> {
@@ -52,6 +62,7 @@ impl CommitBuilder<'_> {
             author: signature.clone(),
             committer: signature,
             secure_sig: None,
+            metadata: BTreeMap::new(),
         };
         CommitBuilder {
             mut_repo,
@@ -59,6 +70,8 @@ impl CommitBuilder<'_> {
             commit,
             rewrite_source: None,
             sign_settings: settings.sign_settings(),
+            normalize_description: settings.normalize_descriptions(),
+            clamp_committer_timestamp: settings.monotonic_commit_timestamps(),
         }
     }
 
@@ -71,6 +84,9 @@ impl CommitBuilder<'_> {
         let mut commit = predecessor.store_commit().clone();
         commit.predecessors = vec![predecessor.id().clone()];
         commit.committer = settings.signature();
+        // The rewritten commit is created by jj even if the predecessor was
+        // imported. Other metadata is carried over.
+        commit.metadata.remove(COMMITTED_VIA_METADATA_KEY);
         // If the user had not configured a name and email before but now they have,
         // update the author fields with the new information.
         if commit.author.name.is_empty()
@@ -89,6 +105,8 @@ impl CommitBuilder<'_> {
             rng: settings.get_rng(),
             rewrite_source: Some(predecessor.clone()),
             sign_settings: settings.sign_settings(),
+            normalize_description: settings.normalize_descriptions(),
+            clamp_committer_timestamp: settings.monotonic_commit_timestamps(),
         }
     }
 
@@ -140,8 +158,51 @@ impl CommitBuilder<'_> {
         &self.commit.description
     }
 
+    /// Sets the description. Unless disabled by the `ui.normalize-descriptions`
+    /// setting, the description is normalized by `normalize_description()`.
     pub fn set_description(mut self, description: impl Into<String>) -> Self {
-        self.commit.description = description.into();
+        let mut description = description.into();
+        if self.normalize_description {
+            description = normalize_description(&description);
+        }
+        if description != self.commit.description {
+            self.commit
+                .metadata
+                .remove(GENERATED_DESCRIPTION_METADATA_KEY);
+        }
+        self.commit.description = description;
+        self
+    }
+
+    pub fn metadata(&self) -> &BTreeMap<String, String> {
+        &self.commit.metadata
+    }
+
+    pub fn set_metadata(mut self, key: impl Into<String>, value: impl Into<String>) -> Self {
+        self.commit.metadata.insert(key.into(), value.into());
+        self
+    }
+
+    pub fn copy_sources(&self) -> BTreeMap<RepoPathBuf, RepoPathBuf> {
+        backend::parse_copy_sources(&self.commit.metadata)
+    }
+
+    /// Records that each destination file was copied or moved from its source.
+    /// Replaces the previously recorded sources.
+    pub fn set_copy_sources(mut self, sources: &BTreeMap<RepoPathBuf, RepoPathBuf>) -> Self {
+        backend::set_copy_sources(&mut self.commit.metadata, sources);
+        self
+    }
+
+    pub fn conflict_labels(&self) -> Option<Merge<String>> {
+        backend::parse_conflict_labels(&self.commit.metadata, &self.commit.root_tree)
+    }
+
+    /// Records where each term of the conflicted tree came from. The labels are
+    /// dropped when the commit is written if they don't have a label for each
+    /// term of its tree.
+    pub fn set_conflict_labels(mut self, labels: Option<&Merge<String>>) -> Self {
+        backend::set_conflict_labels(&mut self.commit.metadata, labels);
         self
     }
 
@@ -158,8 +219,11 @@ impl CommitBuilder<'_> {
         &self.commit.committer
     }
 
+    /// Sets the committer. Unlike the committer from the settings, its
+    /// timestamp is written as is even if it's earlier than the parents'.
     pub fn set_committer(mut self, committer: Signature) -> Self {
         self.commit.committer = committer;
+        self.clamp_committer_timestamp = false;
         self
     }
 
@@ -185,6 +249,40 @@ impl CommitBuilder<'_> {
             }
         }
 
+        // Conflict labels are dropped once the conflicts are resolved, or if the
+        // tree was replaced by one with a different number of terms
+        let conflict_labels =
+            backend::parse_conflict_labels(&self.commit.metadata, &self.commit.root_tree);
+        backend::set_conflict_labels(&mut self.commit.metadata, conflict_labels.as_ref());
+
+        // Copy sources are dropped once their destination is gone from the tree
+        let copy_sources = backend::parse_copy_sources(&self.commit.metadata);
+        if !copy_sources.is_empty() {
+            let tree = self
+                .mut_repo
+                .store()
+                .get_root_tree(&self.commit.root_tree)?;
+            let copy_sources = copy_sources
+                .into_iter()
+                .filter(|(dest, _)| tree.path_value(dest).is_present())
+                .collect();
+            backend::set_copy_sources(&mut self.commit.metadata, &copy_sources);
+        }
+
+        // A commit is committed no earlier than its parents even if the clock
+        // went backwards, unless disabled by the `timestamps.monotonic-commits`
+        // setting
+        if self.clamp_committer_timestamp {
+            let store = self.mut_repo.store();
+            for parent_id in &self.commit.parents {
+                let parent = store.get_commit(parent_id)?;
+                let parent_time = &parent.committer().timestamp.timestamp;
+                if self.commit.committer.timestamp.timestamp < *parent_time {
+                    self.commit.committer.timestamp.timestamp = parent_time.clone();
+                }
+            }
+        }
+
         let sign_settings = &self.sign_settings;
         let store = self.mut_repo.store();
 
@@ -209,3 +307,16 @@ impl CommitBuilder<'_> {
         Ok(commit)
     }
 }
+
+/// Removes trailing whitespace from each line and trailing blank lines, and
+/// makes a non-empty description end with exactly one newline. A description
+/// that consists only of whitespace becomes empty.
+pub fn normalize_description(description: &str) -> String {
+    let lines = description.lines().map(str::trim_end).join("\n");
+    let trimmed = lines.trim_end_matches('\n');
+    if trimmed.is_empty() {
+        String::new()
+    } else {
+        format!("{trimmed}\n")
+    }
+}
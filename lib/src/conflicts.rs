@@ -20,12 +20,13 @@ use std::iter::zip;
 use futures::StreamExt;
 use itertools::Itertools;
 
-use crate::backend::{BackendResult, CommitId, FileId, SymlinkId, TreeId, TreeValue};
+use crate::backend::{BackendError, BackendResult, CommitId, FileId, SymlinkId, TreeId, TreeValue};
 use crate::diff::{find_line_ranges, Diff, DiffHunk};
 use crate::files;
 use crate::files::{ContentHunk, MergeResult};
 use crate::merge::{Merge, MergeBuilder, MergedTreeValue};
-use crate::repo_path::RepoPath;
+use crate::object_id::ObjectId;
+use crate::repo_path::{RepoPath, RepoPathBuf, RepoPathComponentBuf};
 use crate::store::Store;
 
 const CONFLICT_START_LINE: &[u8] = b"<<<<<<<\n";
@@ -34,6 +35,20 @@ const CONFLICT_DIFF_LINE: &[u8] = b"%%%%%%%\n";
 const CONFLICT_MINUS_LINE: &[u8] = b"-------\n";
 const CONFLICT_PLUS_LINE: &[u8] = b"+++++++\n";
 
+/// Suffix of the name the non-directory side of a conflict between a file and
+/// a directory is checked out as. The directory side is checked out at the
+/// conflicted path itself.
+pub const PATH_CONFLICT_FILE_SUFFIX: &str = ".jjconflict-file";
+
+/// Returns the path that the file side of a conflict between a file and a
+/// directory at `path` is checked out at.
+pub fn path_conflict_file_path(path: &RepoPath) -> RepoPathBuf {
+    let (dir, name) = path.split().expect("the root can't be a path conflict");
+    let name = [name.as_bytes(), PATH_CONFLICT_FILE_SUFFIX.as_bytes()].concat();
+    let name = RepoPathComponentBuf::from_bytes(name);
+    dir.join(&name)
+}
+
 fn write_diff_hunks(hunks: &[DiffHunk], file: &mut dyn Write) -> std::io::Result<()> {
     for hunk in hunks {
         match hunk {
@@ -93,10 +108,23 @@ pub async fn materialize(
     store: &Store,
     path: &RepoPath,
     output: &mut dyn Write,
+) -> std::io::Result<()> {
+    materialize_with_labels(conflict, None, store, path, output).await
+}
+
+/// Like `materialize()`, but the conflict markers are followed by the labels
+/// of the terms they introduce. The labels are ignored unless they have as
+/// many terms as the conflict.
+pub async fn materialize_with_labels(
+    conflict: &MergedTreeValue,
+    labels: Option<&Merge<String>>,
+    store: &Store,
+    path: &RepoPath,
+    output: &mut dyn Write,
 ) -> std::io::Result<()> {
     if let Some(file_merge) = conflict.to_file_merge() {
         let content = extract_as_single_hunk(&file_merge, store, path).await;
-        materialize_merge_result(&content, output)
+        materialize_merge_result_with_labels(&content, labels, output)
     } else {
         // Unless all terms are regular files, we can't do much better than to try to
         // describe the merge.
@@ -141,6 +169,17 @@ pub async fn materialize_tree_value(
     store: &Store,
     path: &RepoPath,
     value: MergedTreeValue,
+) -> BackendResult<MaterializedTreeValue> {
+    materialize_tree_value_with_labels(store, path, value, None).await
+}
+
+/// Like `materialize_tree_value()`, but conflicts are materialized with the
+/// given labels. See `materialize_with_labels()`.
+pub async fn materialize_tree_value_with_labels(
+    store: &Store,
+    path: &RepoPath,
+    value: MergedTreeValue,
+    labels: Option<&Merge<String>>,
 ) -> BackendResult<MaterializedTreeValue> {
     match value.into_resolved() {
         Ok(None) => Ok(MaterializedTreeValue::Absent),
@@ -163,7 +202,7 @@ pub async fn materialize_tree_value(
         }
         Err(conflict) => {
             let mut contents = vec![];
-            materialize(&conflict, store, path, &mut contents)
+            materialize_with_labels(&conflict, labels, store, path, &mut contents)
                 .await
                 .expect("Failed to materialize conflict to in-memory buffer");
             Ok(MaterializedTreeValue::Conflict {
@@ -174,10 +213,130 @@ pub async fn materialize_tree_value(
     }
 }
 
+/// One part of a conflict, for tools that show each part separately (e.g. a
+/// 3-pane merge tool).
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct ConflictPartDescription {
+    /// Describes the part's position in the conflict, like "side 1" or
+    /// "base 1".
+    pub label: String,
+    /// Whether the part is one of the sides being merged (as opposed to a
+    /// base).
+    pub is_side: bool,
+    /// The part's value, or `None` if the path is absent in it.
+    pub value: Option<TreeValue>,
+}
+
+/// Describes the parts of a conflict, in the order they appear in the
+/// materialized conflict: the sides interleaved with the bases between them.
+/// Parts are described by their position unless `labels` has a non-empty
+/// label for them.
+pub fn describe_parts(
+    conflict: &MergedTreeValue,
+    labels: Option<&Merge<String>>,
+) -> Vec<ConflictPartDescription> {
+    let labels = labels.filter(|labels| labels.num_sides() == conflict.num_sides());
+    conflict
+        .iter()
+        .enumerate()
+        .map(|(i, value)| {
+            let is_side = i % 2 == 0;
+            let label = match labels.and_then(|labels| labels.iter().nth(i)) {
+                Some(label) if !label.is_empty() => label.clone(),
+                _ if is_side => format!("side {}", i / 2 + 1),
+                _ => format!("base {}", i / 2 + 1),
+            };
+            ConflictPartDescription {
+                label,
+                is_side,
+                value: value.clone(),
+            }
+        })
+        .collect()
+}
+
+/// Reads the content of a conflict part. Returns the file contents for files,
+/// the target for symlinks, and empty content if the path is absent. Returns
+/// `None` for trees and submodules, which have no content.
+pub async fn read_part_content(
+    store: &Store,
+    path: &RepoPath,
+    part: &ConflictPartDescription,
+) -> BackendResult<Option<Vec<u8>>> {
+    match &part.value {
+        None => Ok(Some(vec![])),
+        Some(TreeValue::File { id, .. }) => {
+            let mut content = vec![];
+            store
+                .read_file_async(path, id)
+                .await?
+                .read_to_end(&mut content)
+                .map_err(|err| BackendError::ReadObject {
+                    object_type: id.object_type(),
+                    hash: id.hex(),
+                    source: err.into(),
+                })?;
+            Ok(Some(content))
+        }
+        Some(TreeValue::Symlink(id)) => {
+            let target = store.read_symlink_async(path, id).await?;
+            Ok(Some(target.into_bytes()))
+        }
+        Some(TreeValue::Tree(_) | TreeValue::GitSubmodule(_) | TreeValue::Conflict(_)) => Ok(None),
+    }
+}
+
 pub fn materialize_merge_result(
     single_hunk: &Merge<ContentHunk>,
     output: &mut dyn Write,
 ) -> std::io::Result<()> {
+    materialize_merge_result_with_labels(single_hunk, None, output)
+}
+
+/// Writes a conflict marker line, followed by `label` if it's not empty.
+fn write_marker(output: &mut dyn Write, marker_line: &[u8], label: &str) -> std::io::Result<()> {
+    if label.is_empty() {
+        output.write_all(marker_line)
+    } else {
+        output.write_all(marker_line.strip_suffix(b"\n").unwrap())?;
+        writeln!(output, " {label}")
+    }
+}
+
+/// Writes a diff marker line, labeled with the labels of the diffed terms if
+/// either of them isn't empty.
+fn write_diff_marker(output: &mut dyn Write, from: &str, to: &str) -> std::io::Result<()> {
+    if from.is_empty() && to.is_empty() {
+        return output.write_all(CONFLICT_DIFF_LINE);
+    }
+    let from = if from.is_empty() { "base" } else { from };
+    let to = if to.is_empty() { "side" } else { to };
+    write_marker(
+        output,
+        CONFLICT_DIFF_LINE,
+        &format!("diff from {from} to {to}"),
+    )
+}
+
+/// Like `materialize_merge_result()`, but the conflict markers are followed
+/// by the labels of the terms they introduce. The labels are ignored unless
+/// they have as many terms as `single_hunk`.
+pub fn materialize_merge_result_with_labels(
+    single_hunk: &Merge<ContentHunk>,
+    labels: Option<&Merge<String>>,
+    output: &mut dyn Write,
+) -> std::io::Result<()> {
+    let labels = labels.filter(|labels| labels.num_sides() == single_hunk.num_sides());
+    let add_label = |index: usize| {
+        labels
+            .and_then(|labels| labels.get_add(index))
+            .map_or("", |label| label.as_str())
+    };
+    let remove_label = |index: usize| {
+        labels
+            .and_then(|labels| labels.get_remove(index))
+            .map_or("", |label| label.as_str())
+    };
     let slices = single_hunk.map(|content| content.0.as_slice());
     let merge_result = files::merge(&slices);
     match merge_result {
@@ -191,13 +350,13 @@ pub fn materialize_merge_result(
                 } else {
                     output.write_all(CONFLICT_START_LINE)?;
                     let mut add_index = 0;
-                    for left in hunk.removes() {
+                    for (remove_index, left) in hunk.removes().enumerate() {
                         let right1 = if let Some(right1) = hunk.get_add(add_index) {
                             right1
                         } else {
                             // If we have no more positive terms, emit the remaining negative
                             // terms as snapshots.
-                            output.write_all(CONFLICT_MINUS_LINE)?;
+                            write_marker(output, CONFLICT_MINUS_LINE, remove_label(remove_index))?;
                             output.write_all(&left.0)?;
                             continue;
                         };
@@ -216,23 +375,31 @@ pub fn materialize_merge_result(
                                 // If the next positive term is a better match, emit
                                 // the current positive term as a snapshot and the next
                                 // positive term as a diff.
-                                output.write_all(CONFLICT_PLUS_LINE)?;
+                                write_marker(output, CONFLICT_PLUS_LINE, add_label(add_index))?;
                                 output.write_all(&right1.0)?;
-                                output.write_all(CONFLICT_DIFF_LINE)?;
+                                write_diff_marker(
+                                    output,
+                                    remove_label(remove_index),
+                                    add_label(add_index + 1),
+                                )?;
                                 write_diff_hunks(&diff2, output)?;
                                 add_index += 2;
                                 continue;
                             }
                         }
 
-                        output.write_all(CONFLICT_DIFF_LINE)?;
+                        write_diff_marker(
+                            output,
+                            remove_label(remove_index),
+                            add_label(add_index),
+                        )?;
                         write_diff_hunks(&diff1, output)?;
                         add_index += 1;
                     }
 
                     //  Emit the remaining positive terms as snapshots.
-                    for slice in hunk.adds().skip(add_index) {
-                        output.write_all(CONFLICT_PLUS_LINE)?;
+                    for (index, slice) in hunk.adds().enumerate().skip(add_index) {
+                        write_marker(output, CONFLICT_PLUS_LINE, add_label(index))?;
                         output.write_all(&slice.0)?;
                     }
                     output.write_all(CONFLICT_END_LINE)?;
@@ -243,6 +410,43 @@ pub fn materialize_merge_result(
     Ok(())
 }
 
+fn conflicted_hunks(single_hunk: &Merge<ContentHunk>) -> Vec<Merge<ContentHunk>> {
+    let slices = single_hunk.map(|content| content.0.as_slice());
+    match files::merge(&slices) {
+        MergeResult::Resolved(_) => vec![],
+        MergeResult::Conflict(hunks) => hunks
+            .into_iter()
+            .filter(|hunk| !hunk.is_resolved())
+            .collect(),
+    }
+}
+
+/// Detects a partial resolution of a file conflict, i.e. `after` is `before`
+/// with some of its conflicted hunks resolved and the others left unchanged.
+/// Changes outside the conflicted hunks are allowed. Returns the number of
+/// resolved hunks and the number of conflicted hunks in `before`.
+pub async fn resolved_conflict_hunks(
+    store: &Store,
+    path: &RepoPath,
+    before: &MergedTreeValue,
+    after: &MergedTreeValue,
+) -> Option<(usize, usize)> {
+    let before = before.to_file_merge()?;
+    let after = after.to_file_merge()?;
+    if before.num_sides() != after.num_sides() {
+        return None;
+    }
+    let before_hunks = conflicted_hunks(&extract_as_single_hunk(&before, store, path).await);
+    let after_hunks = conflicted_hunks(&extract_as_single_hunk(&after, store, path).await);
+    // The remaining conflicts must appear unchanged and in the same order
+    let mut unresolved = before_hunks.iter();
+    let is_subsequence = after_hunks
+        .iter()
+        .all(|hunk| unresolved.any(|before_hunk| before_hunk == hunk));
+    (is_subsequence && after_hunks.len() < before_hunks.len())
+        .then(|| (before_hunks.len() - after_hunks.len(), before_hunks.len()))
+}
+
 fn diff_size(hunks: &[DiffHunk]) -> usize {
     hunks
         .iter()
@@ -298,6 +502,19 @@ pub fn parse_conflict(input: &[u8], num_sides: usize) -> Option<Vec<Merge<Conten
     }
 }
 
+/// Removes the label from a labeled marker line, e.g. "+++++++ label\n" becomes
+/// "+++++++\n". Other lines are returned as is.
+fn strip_marker_label(line: &[u8]) -> &[u8] {
+    let markers = [CONFLICT_DIFF_LINE, CONFLICT_MINUS_LINE, CONFLICT_PLUS_LINE];
+    for marker_line in markers {
+        let marker = marker_line.strip_suffix(b"\n").unwrap();
+        if line.starts_with(marker) && line.get(marker.len()) == Some(&b' ') {
+            return marker_line;
+        }
+    }
+    line
+}
+
 fn parse_conflict_hunk(input: &[u8]) -> Merge<ContentHunk> {
     enum State {
         Diff,
@@ -309,7 +526,7 @@ fn parse_conflict_hunk(input: &[u8]) -> Merge<ContentHunk> {
     let mut removes = vec![];
     let mut adds = vec![];
     for line in input.split_inclusive(|b| *b == b'\n') {
-        match line {
+        match strip_marker_label(line) {
             CONFLICT_DIFF_LINE => {
                 state = State::Diff;
                 removes.push(ContentHunk(vec![]));
@@ -0,0 +1,226 @@
+// Copyright 2024 The Jujutsu Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Bloom filters of the paths changed by each commit.
+//!
+//! The filter of a commit contains the files changed from its parent and all
+//! their ancestor directories, so `file(path)` can skip the commits which
+//! definitely don't touch `path` without diffing trees. Merge commits and
+//! commits changing too many paths don't have a filter.
+//!
+//! Filters are stored in a sidecar file per index segment. The file starts
+//! with a format version, and files of an unknown version are ignored until
+//! the index is rebuilt.
+
+use std::cmp::max;
+use std::collections::HashSet;
+
+use blake2::{Blake2b512, Digest};
+
+use crate::repo_path::RepoPath;
+
+const FORMAT_VERSION: u32 = 1;
+
+/// Commits changing more paths (counting parent directories) than this don't
+/// get a filter. This bounds the size of a filter.
+pub(super) const MAX_FILTER_PATHS: usize = 512;
+const BITS_PER_PATH: usize = 10;
+const MIN_FILTER_BYTES: usize = 8;
+const NUM_HASHES: u64 = 7;
+
+/// Hash of a path, which can be tested against any number of filters.
+#[derive(Clone, Copy, Debug)]
+pub(super) struct ChangedPathHash(u64, u64);
+
+impl ChangedPathHash {
+    pub fn new(path: &RepoPath) -> Self {
+        let digest = Blake2b512::digest(path.as_internal_bytes());
+        let h1 = u64::from_le_bytes(digest[0..8].try_into().unwrap());
+        let h2 = u64::from_le_bytes(digest[8..16].try_into().unwrap());
+        ChangedPathHash(h1, h2)
+    }
+
+    fn bit_positions(self, num_bits: u64) -> impl Iterator<Item = usize> {
+        let ChangedPathHash(h1, h2) = self;
+        (0..NUM_HASHES).map(move |i| (h1.wrapping_add(i.wrapping_mul(h2)) % num_bits) as usize)
+    }
+}
+
+/// Bloom filter of the paths changed by a commit.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub(super) struct ChangedPathFilter {
+    bits: Vec<u8>,
+}
+
+impl ChangedPathFilter {
+    /// Builds a filter from the changed file paths. Returns `None` if there
+    /// are too many paths.
+    pub fn from_changed_files<'a>(files: impl IntoIterator<Item = &'a RepoPath>) -> Option<Self> {
+        let mut paths = HashSet::new();
+        for file in files {
+            let mut path = Some(file);
+            while let Some(p) = path.filter(|p| !p.is_root()) {
+                if !paths.insert(p) {
+                    // The ancestors have been inserted too
+                    break;
+                }
+                path = p.parent();
+            }
+            if paths.len() > MAX_FILTER_PATHS {
+                return None;
+            }
+        }
+        let num_bytes = max(MIN_FILTER_BYTES, (paths.len() * BITS_PER_PATH + 7) / 8);
+        let mut filter = ChangedPathFilter {
+            bits: vec![0; num_bytes],
+        };
+        for path in paths {
+            for bit in ChangedPathHash::new(path).bit_positions(filter.num_bits()) {
+                filter.bits[bit / 8] |= 1 << (bit % 8);
+            }
+        }
+        Some(filter)
+    }
+
+    /// Returns false if the commit definitely doesn't change `path` or any
+    /// file under it.
+    pub fn may_contain(&self, hash: ChangedPathHash) -> bool {
+        hash.bit_positions(self.num_bits())
+            .all(|bit| self.bits[bit / 8] & (1 << (bit % 8)) != 0)
+    }
+
+    fn num_bits(&self) -> u64 {
+        u64::try_from(self.bits.len() * 8).unwrap()
+    }
+}
+
+/// Filters of the local commits of an index segment, in index order.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub(super) struct ChangedPathFilters {
+    filters: Vec<Option<ChangedPathFilter>>,
+}
+
+impl ChangedPathFilters {
+    pub fn new(filters: Vec<Option<ChangedPathFilter>>) -> Self {
+        ChangedPathFilters { filters }
+    }
+
+    pub fn get(&self, local_pos: u32) -> Option<&ChangedPathFilter> {
+        self.filters.get(local_pos as usize)?.as_ref()
+    }
+
+    pub fn serialize(&self) -> Vec<u8> {
+        let mut buf = vec![];
+        buf.extend(FORMAT_VERSION.to_le_bytes());
+        buf.extend(u32::try_from(self.filters.len()).unwrap().to_le_bytes());
+        for filter in &self.filters {
+            let bits = filter.as_ref().map_or(&[][..], |filter| &filter.bits);
+            buf.extend(u32::try_from(bits.len()).unwrap().to_le_bytes());
+            buf.extend(bits);
+        }
+        buf
+    }
+
+    /// Parses the sidecar `data` of a segment with `num_local_commits`.
+    /// Returns `None` if the data was written in another format version or
+    /// doesn't match the segment.
+    pub fn deserialize(mut data: &[u8], num_local_commits: u32) -> Option<Self> {
+        if read_u32(&mut data)? != FORMAT_VERSION || read_u32(&mut data)? != num_local_commits {
+            return None;
+        }
+        let mut filters = Vec::with_capacity(num_local_commits as usize);
+        for _ in 0..num_local_commits {
+            let len = read_u32(&mut data)? as usize;
+            if len == 0 {
+                filters.push(None);
+                continue;
+            }
+            if data.len() < len {
+                return None;
+            }
+            let (bits, rest) = data.split_at(len);
+            data = rest;
+            filters.push(Some(ChangedPathFilter {
+                bits: bits.to_vec(),
+            }));
+        }
+        data.is_empty().then_some(ChangedPathFilters { filters })
+    }
+}
+
+fn read_u32(data: &mut &[u8]) -> Option<u32> {
+    let bytes: &[u8] = data;
+    let value = u32::from_le_bytes(bytes.get(..4)?.try_into().unwrap());
+    *data = &bytes[4..];
+    Some(value)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::repo_path::RepoPathBuf;
+
+    fn repo_path(value: &str) -> &RepoPath {
+        RepoPath::from_internal_string(value)
+    }
+
+    #[test]
+    fn test_filter_contains_files_and_parent_dirs() {
+        let filter =
+            ChangedPathFilter::from_changed_files([repo_path("dir/sub/file"), repo_path("top")])
+                .unwrap();
+        for path in ["dir", "dir/sub", "dir/sub/file", "top"] {
+            assert!(filter.may_contain(ChangedPathHash::new(repo_path(path))));
+        }
+        // False positives are possible, but should be rare
+        let num_false_positives = (0..100)
+            .map(|i| RepoPathBuf::from_internal_string(format!("dir/other{i}")))
+            .filter(|path| filter.may_contain(ChangedPathHash::new(path)))
+            .count();
+        assert!(num_false_positives < 20, "{num_false_positives}");
+    }
+
+    #[test]
+    fn test_filter_too_many_paths() {
+        let files = (0..MAX_FILTER_PATHS)
+            .map(|i| RepoPathBuf::from_internal_string(format!("file{i}")))
+            .collect::<Vec<_>>();
+        assert!(ChangedPathFilter::from_changed_files(files.iter().map(|f| f.as_ref())).is_some());
+        let files = files
+            .iter()
+            .map(|f| f.as_ref())
+            .chain([repo_path("one/more")]);
+        assert!(ChangedPathFilter::from_changed_files(files).is_none());
+    }
+
+    #[test]
+    fn test_filters_serialization() {
+        let filters = ChangedPathFilters::new(vec![
+            None,
+            ChangedPathFilter::from_changed_files([repo_path("a")]),
+            None,
+        ]);
+        let data = filters.serialize();
+        assert_eq!(ChangedPathFilters::deserialize(&data, 3), Some(filters));
+        // Mismatched number of commits, truncated data, unknown version
+        assert_eq!(ChangedPathFilters::deserialize(&data, 2), None);
+        assert_eq!(
+            ChangedPathFilters::deserialize(&data[..data.len() - 1], 3),
+            None
+        );
+        let mut data = data;
+        data[0] = 0xff;
+        assert_eq!(ChangedPathFilters::deserialize(&data, 3), None);
+    }
+}
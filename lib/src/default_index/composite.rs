@@ -21,6 +21,7 @@ use std::sync::Arc;
 
 use itertools::Itertools;
 
+use super::changed_path::ChangedPathFilter;
 use super::entry::{
     IndexEntry, IndexPosition, IndexPositionByGeneration, LocalPosition, SmallIndexPositionsVec,
 };
@@ -44,6 +45,9 @@ pub(super) trait IndexSegment: Send + Sync {
 
     fn name(&self) -> Option<String>;
 
+    /// Size of the segment file in bytes, or 0 if it hasn't been written.
+    fn num_bytes(&self) -> usize;
+
     fn commit_id_to_pos(&self, commit_id: &CommitId) -> Option<IndexPosition>;
 
     /// Suppose the given `commit_id` exists, returns the previous and next
@@ -64,6 +68,8 @@ pub(super) trait IndexSegment: Send + Sync {
     fn num_parents(&self, local_pos: LocalPosition) -> u32;
 
     fn parent_positions(&self, local_pos: LocalPosition) -> SmallIndexPositionsVec;
+
+    fn changed_path_filter(&self, local_pos: LocalPosition) -> Option<&ChangedPathFilter>;
 }
 
 /// Abstraction over owned and borrowed types that can be cheaply converted to
@@ -133,7 +139,13 @@ impl<'a> CompositeIndex<'a> {
             .ancestor_index_segments()
             .map(|segment| IndexLevelStats {
                 num_commits: segment.num_local_commits(),
+                num_changed_path_filters: (0..segment.num_local_commits())
+                    .filter(|&pos| segment.changed_path_filter(LocalPosition(pos)).is_some())
+                    .count()
+                    .try_into()
+                    .unwrap(),
                 name: segment.name(),
+                num_bytes: segment.num_bytes(),
             })
             .collect_vec();
         levels.reverse();
@@ -466,7 +478,11 @@ impl IdIndexSourceEntry<ChangeId> for IndexEntry<'_> {
 
 pub struct IndexLevelStats {
     pub num_commits: u32,
+    /// Number of commits which have a changed-path filter.
+    pub num_changed_path_filters: u32,
     pub name: Option<String>,
+    /// Size of the segment file in bytes, or 0 if it hasn't been written.
+    pub num_bytes: usize,
 }
 
 pub struct IndexStats {
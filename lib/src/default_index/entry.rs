@@ -19,6 +19,7 @@ use std::hash::{Hash, Hasher};
 
 use smallvec::SmallVec;
 
+use super::changed_path::ChangedPathFilter;
 use super::composite::{CompositeIndex, IndexSegment};
 use crate::backend::{ChangeId, CommitId};
 use crate::object_id::ObjectId;
@@ -108,6 +109,11 @@ impl<'a> IndexEntry<'a> {
         self.source.parent_positions(self.local_pos)
     }
 
+    /// Returns the filter of the paths changed by this commit, if indexed.
+    pub(super) fn changed_path_filter(&self) -> Option<&'a ChangedPathFilter> {
+        self.source.changed_path_filter(self.local_pos)
+    }
+
     pub fn parents(&self) -> impl ExactSizeIterator<Item = IndexEntry<'a>> {
         let composite = CompositeIndex::new(self.source);
         self.parent_positions()
@@ -14,6 +14,7 @@
 
 #![allow(missing_docs)]
 
+mod changed_path;
 mod composite;
 mod entry;
 mod mutable;
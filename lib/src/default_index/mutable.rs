@@ -29,6 +29,7 @@ use itertools::Itertools;
 use smallvec::SmallVec;
 use tempfile::NamedTempFile;
 
+use super::changed_path::ChangedPathFilter;
 use super::composite::{AsCompositeIndex, ChangeIdIndexImpl, CompositeIndex, IndexSegment};
 use super::entry::{IndexPosition, LocalPosition, SmallIndexPositionsVec};
 use super::readonly::{DefaultReadonlyIndex, ReadonlyIndexSegment};
@@ -270,6 +271,25 @@ impl MutableIndexSegment {
     }
 
     pub(super) fn save_in(self, dir: &Path) -> io::Result<Arc<ReadonlyIndexSegment>> {
+        self.save_with(|index_file_id_hex, buf| {
+            let mut temp_file = NamedTempFile::new_in(dir)?;
+            let file = temp_file.as_file_mut();
+            file.write_all(buf)?;
+            persist_content_addressed_temp_file(temp_file, dir.join(index_file_id_hex))?;
+            Ok(())
+        })
+    }
+
+    /// Converts this segment to a readonly segment without writing it
+    /// anywhere.
+    pub(super) fn into_readonly(self) -> Arc<ReadonlyIndexSegment> {
+        self.save_with(|_, _| Ok(())).unwrap()
+    }
+
+    fn save_with(
+        self,
+        write: impl FnOnce(&str, &[u8]) -> io::Result<()>,
+    ) -> io::Result<Arc<ReadonlyIndexSegment>> {
         if self.num_local_commits() == 0 && self.parent_file.is_some() {
             return Ok(self.parent_file.unwrap());
         }
@@ -281,12 +301,7 @@ impl MutableIndexSegment {
         let mut hasher = Blake2b512::new();
         hasher.update(&buf);
         let index_file_id_hex = hex::encode(hasher.finalize());
-        let index_file_path = dir.join(&index_file_id_hex);
-
-        let mut temp_file = NamedTempFile::new_in(dir)?;
-        let file = temp_file.as_file_mut();
-        file.write_all(&buf)?;
-        persist_content_addressed_temp_file(temp_file, index_file_path)?;
+        write(&index_file_id_hex, &buf)?;
 
         Ok(ReadonlyIndexSegment::load_with_parent_file(
             &mut &buf[local_entries_offset..],
@@ -316,6 +331,10 @@ impl IndexSegment for MutableIndexSegment {
         None
     }
 
+    fn num_bytes(&self) -> usize {
+        0
+    }
+
     fn commit_id_to_pos(&self, commit_id: &CommitId) -> Option<IndexPosition> {
         self.commit_lookup.get(commit_id).cloned()
     }
@@ -375,6 +394,10 @@ impl IndexSegment for MutableIndexSegment {
     fn parent_positions(&self, local_pos: LocalPosition) -> SmallIndexPositionsVec {
         self.graph[local_pos.0 as usize].parent_positions.clone()
     }
+
+    fn changed_path_filter(&self, _local_pos: LocalPosition) -> Option<&ChangedPathFilter> {
+        None
+    }
 }
 
 /// In-memory mutable records for the on-disk commit index backend.
@@ -404,6 +427,10 @@ impl DefaultMutableIndex {
     pub(super) fn squash_and_save_in(self, dir: &Path) -> io::Result<Arc<ReadonlyIndexSegment>> {
         self.0.maybe_squash_with_ancestors().save_in(dir)
     }
+
+    pub(super) fn squash_into_readonly(self) -> Arc<ReadonlyIndexSegment> {
+        self.0.maybe_squash_with_ancestors().into_readonly()
+    }
 }
 
 impl AsCompositeIndex for DefaultMutableIndex {
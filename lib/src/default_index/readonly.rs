@@ -17,18 +17,18 @@
 use std::any::Any;
 use std::cmp::Ordering;
 use std::fmt::{Debug, Formatter};
-use std::fs::File;
-use std::io;
 use std::io::Read;
-use std::path::Path;
-use std::sync::Arc;
+use std::sync::{Arc, OnceLock};
+use std::{io, iter};
 
 use smallvec::SmallVec;
 use thiserror::Error;
 
+use super::changed_path::{ChangedPathFilter, ChangedPathFilters};
 use super::composite::{AsCompositeIndex, ChangeIdIndexImpl, CompositeIndex, IndexSegment};
 use super::entry::{IndexPosition, LocalPosition, SmallIndexPositionsVec};
 use super::mutable::DefaultMutableIndex;
+use super::store::DefaultIndexStore;
 use crate::backend::{ChangeId, CommitId};
 use crate::index::{AllHeadsForGcUnsupported, ChangeIdIndex, Index, MutableIndex, ReadonlyIndex};
 use crate::object_id::{HexPrefix, ObjectId, PrefixResolution};
@@ -54,7 +54,7 @@ impl ReadonlyIndexLoadError {
         Self::from_io_err(name, io::Error::new(io::ErrorKind::InvalidData, error))
     }
 
-    fn from_io_err(name: impl Into<String>, error: io::Error) -> Self {
+    pub(super) fn from_io_err(name: impl Into<String>, error: io::Error) -> Self {
         ReadonlyIndexLoadError {
             name: name.into(),
             error,
@@ -182,6 +182,8 @@ pub(super) struct ReadonlyIndexSegment {
     // Number of commits not counting the parent file
     num_local_commits: u32,
     data: Vec<u8>,
+    // Stored separately from the segment file, and may be missing
+    changed_path_filters: OnceLock<ChangedPathFilters>,
 }
 
 impl Debug for ReadonlyIndexSegment {
@@ -194,22 +196,12 @@ impl Debug for ReadonlyIndexSegment {
 }
 
 impl ReadonlyIndexSegment {
-    /// Loads both parent segments and local entries from the given file `name`.
-    pub(super) fn load(
-        dir: &Path,
-        name: String,
-        commit_id_length: usize,
-        change_id_length: usize,
-    ) -> Result<Arc<ReadonlyIndexSegment>, ReadonlyIndexLoadError> {
-        let mut file = File::open(dir.join(&name))
-            .map_err(|err| ReadonlyIndexLoadError::from_io_err(&name, err))?;
-        Self::load_from(&mut file, dir, name, commit_id_length, change_id_length)
-    }
-
     /// Loads both parent segments and local entries from the given `file`.
+    /// Parent segments are looked up in the `index_store`, which may have
+    /// loaded them already.
     pub(super) fn load_from(
         file: &mut dyn Read,
-        dir: &Path,
+        index_store: &DefaultIndexStore,
         name: String,
         commit_id_length: usize,
         change_id_length: usize,
@@ -228,12 +220,8 @@ impl ReadonlyIndexSegment {
             let parent_filename = String::from_utf8(parent_filename_bytes).map_err(|_| {
                 ReadonlyIndexLoadError::invalid_data(&name, "parent file name is not valid UTF-8")
             })?;
-            let parent_file = ReadonlyIndexSegment::load(
-                dir,
-                parent_filename,
-                commit_id_length,
-                change_id_length,
-            )?;
+            let parent_file =
+                index_store.load_segment(parent_filename, commit_id_length, change_id_length)?;
             Some(parent_file)
         } else {
             None
@@ -291,9 +279,20 @@ impl ReadonlyIndexSegment {
             commit_lookup_entry_size,
             num_local_commits,
             data,
+            changed_path_filters: OnceLock::new(),
         }))
     }
 
+    pub(super) fn changed_path_filters(&self) -> Option<&ChangedPathFilters> {
+        self.changed_path_filters.get()
+    }
+
+    /// Attaches the changed-path filters of the local commits. Does nothing if
+    /// the filters have been set already.
+    pub(super) fn set_changed_path_filters(&self, filters: ChangedPathFilters) {
+        let _ = self.changed_path_filters.set(filters);
+    }
+
     pub(super) fn as_composite(&self) -> CompositeIndex {
         CompositeIndex::new(self)
     }
@@ -379,6 +378,13 @@ impl IndexSegment for ReadonlyIndexSegment {
         Some(self.name.clone())
     }
 
+    fn num_bytes(&self) -> usize {
+        // The header has the parent file name with its length, and the numbers
+        // of local entries and overflow parent entries.
+        let parent_name_len = self.parent_file.as_ref().map_or(0, |file| file.name.len());
+        4 + parent_name_len + 8 + self.data.len()
+    }
+
     fn commit_id_to_pos(&self, commit_id: &CommitId) -> Option<IndexPosition> {
         let lookup_pos = self.commit_id_byte_prefix_to_lookup_pos(commit_id)?;
         let entry = self.commit_lookup_entry(lookup_pos);
@@ -457,6 +463,10 @@ impl IndexSegment for ReadonlyIndexSegment {
         }
         parent_entries
     }
+
+    fn changed_path_filter(&self, local_pos: LocalPosition) -> Option<&ChangedPathFilter> {
+        self.changed_path_filters()?.get(local_pos.0)
+    }
 }
 
 /// Commit index backend which stores data on local disk.
@@ -471,6 +481,14 @@ impl DefaultReadonlyIndex {
     pub(super) fn as_segment(&self) -> &Arc<ReadonlyIndexSegment> {
         &self.0
     }
+
+    /// Returns true if this index includes all segments of the `other` index,
+    /// sharing the same allocations. Indexes loaded through the same index
+    /// store share their common segments.
+    pub fn includes_segments_of(&self, other: &DefaultReadonlyIndex) -> bool {
+        iter::successors(Some(&self.0), |segment| segment.parent_file.as_ref())
+            .any(|segment| Arc::ptr_eq(segment, &other.0))
+    }
 }
 
 impl AsCompositeIndex for DefaultReadonlyIndex {
@@ -17,20 +17,23 @@
 use std::cmp::{Ordering, Reverse};
 use std::collections::{BTreeSet, BinaryHeap, HashSet};
 use std::fmt;
+use std::io::Read as _;
 use std::iter::Peekable;
 use std::ops::Range;
 use std::sync::Arc;
 
 use itertools::Itertools;
 
+use super::changed_path::ChangedPathHash;
 use super::revset_graph_iterator::RevsetGraphIterator;
-use crate::backend::{ChangeId, CommitId, MillisSinceEpoch};
+use crate::backend::{ChangeId, CommitId, FileId, MillisSinceEpoch, TreeValue};
 use crate::default_index::{AsCompositeIndex, CompositeIndex, IndexEntry, IndexPosition};
+use crate::diff::WhitespaceMode;
 use crate::matchers::{EverythingMatcher, Matcher, PrefixMatcher, Visit};
-use crate::repo_path::RepoPath;
+use crate::repo_path::{RepoPath, RepoPathBuf};
 use crate::revset::{
     ResolvedExpression, ResolvedPredicateExpression, Revset, RevsetEvaluationError,
-    RevsetFilterPredicate, GENERATION_RANGE_FULL,
+    RevsetFilterPredicate, SignatureTimestamp, GENERATION_RANGE_FULL,
 };
 use crate::revset_graph::RevsetGraphEdge;
 use crate::rewrite;
@@ -310,6 +313,44 @@ where
     }
 }
 
+/// Revset that takes the first `count` entries of the `candidates`.
+#[derive(Debug)]
+struct LimitRevset<S> {
+    candidates: S,
+    count: usize,
+}
+
+impl<S> InternalRevset for LimitRevset<S>
+where
+    S: InternalRevset,
+{
+    fn iter<'a, 'index: 'a>(
+        &'a self,
+        index: CompositeIndex<'index>,
+    ) -> Box<dyn Iterator<Item = IndexEntry<'index>> + 'a> {
+        Box::new(self.candidates.iter(index).take(self.count))
+    }
+
+    fn into_predicate<'a>(self: Box<Self>) -> Box<dyn ToPredicateFn + 'a>
+    where
+        Self: 'a,
+    {
+        self
+    }
+}
+
+impl<S> ToPredicateFn for LimitRevset<S>
+where
+    S: InternalRevset,
+{
+    fn to_predicate_fn<'a, 'index: 'a>(
+        &'a self,
+        index: CompositeIndex<'index>,
+    ) -> Box<dyn FnMut(&IndexEntry<'_>) -> bool + 'a> {
+        predicate_fn_from_positions(self.iter(index).map(|entry| entry.position()))
+    }
+}
+
 #[derive(Debug)]
 struct NotInPredicate<S>(S);
 
@@ -744,11 +785,24 @@ impl<'index> EvaluationContext<'index> {
                 }
                 Ok(Box::new(EagerRevset { positions }))
             }
-            ResolvedExpression::Latest { candidates, count } => {
+            ResolvedExpression::Latest {
+                candidates,
+                count,
+                by,
+            } => {
                 let candidate_set = self.evaluate(candidates)?;
-                Ok(Box::new(
-                    self.take_latest_revset(candidate_set.as_ref(), *count),
-                ))
+                Ok(Box::new(self.take_latest_revset(
+                    candidate_set.as_ref(),
+                    *count,
+                    *by,
+                )))
+            }
+            ResolvedExpression::Limit { candidates, count } => {
+                let candidates = self.evaluate(candidates)?;
+                Ok(Box::new(LimitRevset {
+                    candidates,
+                    count: *count,
+                }))
             }
             ResolvedExpression::Union(expression1, expression2) => {
                 let set1 = self.evaluate(expression1)?;
@@ -808,7 +862,12 @@ impl<'index> EvaluationContext<'index> {
         EagerRevset { positions }
     }
 
-    fn take_latest_revset(&self, candidate_set: &dyn InternalRevset, count: usize) -> EagerRevset {
+    fn take_latest_revset(
+        &self,
+        candidate_set: &dyn InternalRevset,
+        count: usize,
+        by: SignatureTimestamp,
+    ) -> EagerRevset {
         if count == 0 {
             return EagerRevset::empty();
         }
@@ -821,8 +880,12 @@ impl<'index> EvaluationContext<'index> {
 
         let make_rev_item = |entry: IndexEntry<'_>| {
             let commit = self.store.get_commit(&entry.commit_id()).unwrap();
+            let signature = match by {
+                SignatureTimestamp::Author => commit.author(),
+                SignatureTimestamp::Committer => commit.committer(),
+            };
             Reverse(Item {
-                timestamp: commit.committer().timestamp.timestamp.clone(),
+                timestamp: signature.timestamp.timestamp.clone(),
                 pos: entry.position(),
             })
         };
@@ -919,6 +982,20 @@ fn build_predicate_fn(
                     || pattern.matches(&commit.committer().email)
             })
         }
+        RevsetFilterPredicate::AuthorDate(pattern) => {
+            let pattern = pattern.clone();
+            box_pure_predicate_fn(move |_index, entry| {
+                let commit = store.get_commit(&entry.commit_id()).unwrap();
+                pattern.matches(&commit.author().timestamp)
+            })
+        }
+        RevsetFilterPredicate::CommitterDate(pattern) => {
+            let pattern = pattern.clone();
+            box_pure_predicate_fn(move |_index, entry| {
+                let commit = store.get_commit(&entry.commit_id()).unwrap();
+                pattern.matches(&commit.committer().timestamp)
+            })
+        }
         RevsetFilterPredicate::File(paths) => {
             // TODO: Add support for globs and other formats
             let matcher: Box<dyn Matcher> = if let Some(paths) = paths {
@@ -926,14 +1003,52 @@ fn build_predicate_fn(
             } else {
                 Box::new(EverythingMatcher)
             };
+            let path_hashes = paths.as_deref().and_then(changed_path_hashes);
+            box_pure_predicate_fn(move |index, entry| {
+                may_have_changed_paths(entry, path_hashes.as_deref())
+                    && has_diff_from_parent(&store, index, entry, matcher.as_ref())
+            })
+        }
+        RevsetFilterPredicate::FileIgnoringWhitespace(paths, whitespace) => {
+            let matcher = PrefixMatcher::new(paths);
+            let whitespace = *whitespace;
+            let path_hashes = changed_path_hashes(paths);
             box_pure_predicate_fn(move |index, entry| {
-                has_diff_from_parent(&store, index, entry, matcher.as_ref())
+                may_have_changed_paths(entry, path_hashes.as_deref())
+                    && has_non_whitespace_diff_from_parent(
+                        &store, index, entry, &matcher, whitespace,
+                    )
             })
         }
         RevsetFilterPredicate::HasConflict => box_pure_predicate_fn(move |_index, entry| {
             let commit = store.get_commit(&entry.commit_id()).unwrap();
             commit.has_conflict().unwrap()
         }),
+        RevsetFilterPredicate::HasMetadata(key) => {
+            let key = key.clone();
+            box_pure_predicate_fn(move |_index, entry| {
+                let commit = store.get_commit(&entry.commit_id()).unwrap();
+                commit.metadata().contains_key(&key)
+            })
+        }
+    }
+}
+
+/// Hashes the `paths` to look up in changed-path filters. Returns `None` if
+/// the filters can't be used because one of the paths is the root.
+fn changed_path_hashes(paths: &[RepoPathBuf]) -> Option<Vec<ChangedPathHash>> {
+    paths
+        .iter()
+        .map(|path| (!path.is_root()).then(|| ChangedPathHash::new(path)))
+        .collect()
+}
+
+/// Returns false if the changed-path filter of the commit tells that none of
+/// the paths can have been changed from its parent.
+fn may_have_changed_paths(entry: &IndexEntry<'_>, path_hashes: Option<&[ChangedPathHash]>) -> bool {
+    match (entry.changed_path_filter(), path_hashes) {
+        (Some(filter), Some(hashes)) => hashes.iter().any(|&hash| filter.may_contain(hash)),
+        _ => true,
     }
 }
 
@@ -959,6 +1074,55 @@ fn has_diff_from_parent(
     from_tree.diff(&to_tree, matcher).next().is_some()
 }
 
+/// Like `has_diff_from_parent()`, but ignores files whose contents only differ
+/// in whitespace ignored by `whitespace`.
+fn has_non_whitespace_diff_from_parent(
+    store: &Arc<Store>,
+    index: CompositeIndex<'_>,
+    entry: &IndexEntry<'_>,
+    matcher: &dyn Matcher,
+    whitespace: WhitespaceMode,
+) -> bool {
+    let commit = store.get_commit(&entry.commit_id()).unwrap();
+    let parents = commit.parents();
+    if let [parent] = parents.as_slice() {
+        if commit.tree_id() == parent.tree_id() {
+            return false;
+        }
+    }
+    let read_file = |path: &RepoPath, id: &FileId| {
+        let mut contents = vec![];
+        store
+            .read_file(path, id)
+            .unwrap()
+            .read_to_end(&mut contents)
+            .unwrap();
+        contents
+    };
+    let from_tree = rewrite::merge_commit_trees_without_repo(store, &index, &parents).unwrap();
+    let to_tree = commit.tree().unwrap();
+    from_tree.diff(&to_tree, matcher).any(|(path, diff)| {
+        let (before, after) = diff.unwrap();
+        match (before.as_resolved(), after.as_resolved()) {
+            (
+                Some(Some(TreeValue::File {
+                    id: before_id,
+                    executable: before_executable,
+                })),
+                Some(Some(TreeValue::File {
+                    id: after_id,
+                    executable: after_executable,
+                })),
+            ) if before_executable == after_executable => {
+                let before_contents = read_file(&path, before_id);
+                let after_contents = read_file(&path, after_id);
+                whitespace.normalize(&before_contents) != whitespace.normalize(&after_contents)
+            }
+            _ => true,
+        }
+    })
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -15,25 +15,30 @@
 #![allow(missing_docs)]
 
 use std::any::Any;
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
+use std::fs::File;
 use std::io::Write;
 use std::path::{Path, PathBuf};
-use std::sync::Arc;
+use std::sync::{Arc, RwLock, Weak};
 use std::{fs, io};
 
 use itertools::Itertools;
 use tempfile::NamedTempFile;
 use thiserror::Error;
 
+use super::changed_path::{ChangedPathFilter, ChangedPathFilters, MAX_FILTER_PATHS};
+use super::composite::{AsCompositeIndex as _, IndexSegment as _};
+use super::entry::IndexPosition;
 use super::mutable::DefaultMutableIndex;
 use super::readonly::{DefaultReadonlyIndex, ReadonlyIndexLoadError, ReadonlyIndexSegment};
-use crate::backend::{BackendError, BackendInitError, CommitId};
+use crate::backend::{BackendError, BackendInitError, BackendResult, CommitId};
 use crate::commit::CommitByCommitterTimestamp;
 use crate::dag_walk;
 use crate::file_util::{persist_content_addressed_temp_file, IoResultExt as _, PathError};
 use crate::index::{
     Index, IndexReadError, IndexStore, IndexWriteError, MutableIndex, ReadonlyIndex,
 };
+use crate::matchers::EverythingMatcher;
 use crate::object_id::ObjectId;
 use crate::op_store::{OpStoreError, OperationId};
 use crate::operation::Operation;
@@ -42,6 +47,9 @@ use crate::store::Store;
 // BLAKE2b-512 hash length in hex string
 const SEGMENT_FILE_NAME_LENGTH: usize = 64 * 2;
 
+/// Directory of the changed-path filter files, named after their segments.
+const CHANGED_PATHS_DIR: &str = "changed_paths";
+
 /// Error that may occur during `DefaultIndexStore` initialization.
 #[derive(Debug, Error)]
 #[error("Failed to initialize index store")]
@@ -78,9 +86,22 @@ pub enum DefaultIndexStoreError {
     OpStore(#[from] OpStoreError),
 }
 
+/// Index store which stores segment files in a directory.
+///
+/// Segment files are immutable, so loaded segments are shared by all indexes
+/// loaded through this store. Reloading a repo at a newer operation only needs
+/// to read the segments added since. The cache only holds weak references, so
+/// a segment is freed once no loaded index uses it anymore.
+///
+/// If changed-path filters are enabled, a Bloom filter of the paths changed by
+/// each new commit is written next to its segment, and used to speed up
+/// `file()` revsets. Commits indexed before the filters were enabled don't
+/// have filters until the index is rebuilt.
 #[derive(Debug)]
 pub struct DefaultIndexStore {
     dir: PathBuf,
+    cached_segments: RwLock<HashMap<String, Weak<ReadonlyIndexSegment>>>,
+    changed_path_filters: bool,
 }
 
 impl DefaultIndexStore {
@@ -93,20 +114,35 @@ impl DefaultIndexStore {
         std::fs::create_dir(&op_dir).context(&op_dir)?;
         Ok(DefaultIndexStore {
             dir: dir.to_owned(),
+            cached_segments: Default::default(),
+            changed_path_filters: false,
         })
     }
 
     pub fn load(dir: &Path) -> DefaultIndexStore {
         DefaultIndexStore {
             dir: dir.to_owned(),
+            cached_segments: Default::default(),
+            changed_path_filters: false,
         }
     }
 
+    /// Enables writing and reading changed-path filters.
+    pub fn with_changed_path_filters(mut self, enabled: bool) -> Self {
+        self.changed_path_filters = enabled;
+        self
+    }
+
     pub fn reinit(&self) -> Result<(), DefaultIndexStoreInitError> {
         // Remove all operation links to trigger rebuilding.
         let op_dir = self.dir.join("operations");
         std::fs::remove_dir_all(&op_dir).context(&op_dir)?;
         std::fs::create_dir(&op_dir).context(&op_dir)?;
+        self.cached_segments.write().unwrap().clear();
+        let changed_paths_dir = self.dir.join(CHANGED_PATHS_DIR);
+        if changed_paths_dir.is_dir() {
+            fs::remove_dir_all(&changed_paths_dir).context(&changed_paths_dir)?;
+        }
         // Remove index segments to save disk space. If raced, new segment file
         // will be created by the other process.
         for entry in self.dir.read_dir().context(&self.dir)? {
@@ -130,13 +166,64 @@ impl DefaultIndexStore {
         let op_id_file = self.dir.join("operations").join(op_id.hex());
         let index_file_id_hex =
             fs::read_to_string(op_id_file).map_err(DefaultIndexStoreError::LoadAssociation)?;
-        ReadonlyIndexSegment::load(
-            &self.dir,
-            index_file_id_hex,
+        self.load_segment(index_file_id_hex, commit_id_length, change_id_length)
+            .map_err(DefaultIndexStoreError::LoadIndex)
+    }
+
+    /// Loads the segment file `name` and its parent segments, reusing the
+    /// segments which have already been loaded.
+    pub(super) fn load_segment(
+        &self,
+        name: String,
+        commit_id_length: usize,
+        change_id_length: usize,
+    ) -> Result<Arc<ReadonlyIndexSegment>, ReadonlyIndexLoadError> {
+        {
+            let read_locked_cache = self.cached_segments.read().unwrap();
+            if let Some(segment) = read_locked_cache.get(&name).and_then(Weak::upgrade) {
+                return Ok(segment);
+            }
+        }
+        let mut file = File::open(self.dir.join(&name))
+            .map_err(|err| ReadonlyIndexLoadError::from_io_err(&name, err))?;
+        let segment = ReadonlyIndexSegment::load_from(
+            &mut file,
+            self,
+            name,
             commit_id_length,
             change_id_length,
-        )
-        .map_err(DefaultIndexStoreError::LoadIndex)
+        )?;
+        if self.changed_path_filters {
+            self.load_changed_path_filters(&segment);
+        }
+        self.cache_segment(&segment);
+        Ok(segment)
+    }
+
+    /// Attaches the changed-path filters of the `segment` if there's a valid
+    /// filter file. The filters are optional, so missing or unreadable files
+    /// are ignored.
+    fn load_changed_path_filters(&self, segment: &ReadonlyIndexSegment) {
+        let path = self.dir.join(CHANGED_PATHS_DIR).join(segment.name());
+        let data = match fs::read(&path) {
+            Ok(data) => data,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => return,
+            Err(err) => {
+                tracing::warn!(?path, ?err, "failed to read changed-path filters");
+                return;
+            }
+        };
+        match ChangedPathFilters::deserialize(&data, segment.num_local_commits()) {
+            Some(filters) => segment.set_changed_path_filters(filters),
+            None => tracing::warn!(?path, "ignoring incompatible changed-path filters"),
+        }
+    }
+
+    fn cache_segment(&self, segment: &Arc<ReadonlyIndexSegment>) {
+        let mut write_locked_cache = self.cached_segments.write().unwrap();
+        // Drop the entries of segments which have been freed
+        write_locked_cache.retain(|_, cached| cached.strong_count() > 0);
+        write_locked_cache.insert(segment.name().to_owned(), Arc::downgrade(segment));
     }
 
     /// Rebuilds index for the given `operation`.
@@ -158,6 +245,19 @@ impl DefaultIndexStore {
         operation: &Operation,
         store: &Arc<Store>,
     ) -> Result<Arc<ReadonlyIndexSegment>, DefaultIndexStoreError> {
+        let mutable_index = self.index_commits_at_operation(operation, store)?;
+        let index_file = self.save_mutable_index(mutable_index, operation.id(), store)?;
+        tracing::info!(?index_file, "saved new index file");
+        Ok(index_file)
+    }
+
+    /// Indexes the commits at the given `operation`, starting from the index
+    /// of the latest ancestor operation that has one.
+    fn index_commits_at_operation(
+        &self,
+        operation: &Operation,
+        store: &Arc<Store>,
+    ) -> Result<DefaultMutableIndex, DefaultIndexStoreError> {
         let view = operation.view()?;
         let operations_dir = self.dir.join("operations");
         let commit_id_length = store.commit_id_length();
@@ -245,25 +345,46 @@ impl DefaultIndexStore {
         for (CommitByCommitterTimestamp(commit), _) in commits.iter().rev() {
             mutable_index.add_commit(commit);
         }
+        tracing::info!(commits_count = commits.len(), "indexed commits");
 
-        let index_file = self.save_mutable_index(mutable_index, operation.id())?;
-        tracing::info!(
-            ?index_file,
-            commits_count = commits.len(),
-            "saved new index file"
-        );
-
-        Ok(index_file)
+        Ok(mutable_index)
     }
 
     fn save_mutable_index(
         &self,
         mutable_index: DefaultMutableIndex,
         op_id: &OperationId,
+        store: &Arc<Store>,
     ) -> Result<Arc<ReadonlyIndexSegment>, DefaultIndexStoreError> {
+        let parent_file = mutable_index
+            .as_composite()
+            .ancestor_files_without_local()
+            .next()
+            .cloned();
         let index_segment = mutable_index
             .squash_and_save_in(&self.dir)
             .map_err(DefaultIndexStoreError::SaveIndex)?;
+        let is_new_segment = parent_file.as_ref().map_or(true, |parent_file| {
+            !Arc::ptr_eq(parent_file, &index_segment)
+        });
+        if self.changed_path_filters
+            && is_new_segment
+            && index_segment.changed_path_filters().is_none()
+        {
+            // The filters are optional, so failing to build or save them
+            // shouldn't fail the operation. The segment is then queried
+            // without filters.
+            match build_changed_path_filters(&index_segment, parent_file.as_ref(), store) {
+                Ok(filters) => {
+                    if let Err(err) = self.save_changed_path_filters(&index_segment, &filters) {
+                        tracing::warn!(?err, "failed to save changed-path filters");
+                    }
+                    index_segment.set_changed_path_filters(filters);
+                }
+                Err(err) => tracing::warn!(?err, "failed to build changed-path filters"),
+            }
+        }
+        self.cache_segment(&index_segment);
         self.associate_file_with_operation(&index_segment, op_id)
             .map_err(|source| DefaultIndexStoreError::AssociateIndex {
                 op_id: op_id.to_owned(),
@@ -272,6 +393,19 @@ impl DefaultIndexStore {
         Ok(index_segment)
     }
 
+    fn save_changed_path_filters(
+        &self,
+        segment: &ReadonlyIndexSegment,
+        filters: &ChangedPathFilters,
+    ) -> io::Result<()> {
+        let dir = self.dir.join(CHANGED_PATHS_DIR);
+        fs::create_dir_all(&dir)?;
+        let mut temp_file = NamedTempFile::new_in(&dir)?;
+        temp_file.as_file_mut().write_all(&filters.serialize())?;
+        persist_content_addressed_temp_file(temp_file, dir.join(segment.name()))?;
+        Ok(())
+    }
+
     /// Records a link from the given operation to the this index version.
     fn associate_file_with_operation(
         &self,
@@ -327,18 +461,91 @@ impl IndexStore for DefaultIndexStore {
         Ok(Box::new(DefaultReadonlyIndex::from_segment(index_segment)))
     }
 
+    fn get_index_at_op_read_only(
+        &self,
+        op: &Operation,
+        store: &Arc<Store>,
+    ) -> Result<Box<dyn ReadonlyIndex>, IndexReadError> {
+        let index_segment = match self.load_index_segments_at_operation(
+            op.id(),
+            store.commit_id_length(),
+            store.change_id_length(),
+        ) {
+            Err(DefaultIndexStoreError::LoadAssociation(err))
+                if err.kind() == io::ErrorKind::NotFound =>
+            {
+                self.index_commits_at_operation(op, store)
+                    .map(DefaultMutableIndex::squash_into_readonly)
+            }
+            result => result,
+        }
+        .map_err(|err| IndexReadError(err.into()))?;
+        Ok(Box::new(DefaultReadonlyIndex::from_segment(index_segment)))
+    }
+
     fn write_index(
         &self,
         index: Box<dyn MutableIndex>,
         op_id: &OperationId,
+        store: &Arc<Store>,
     ) -> Result<Box<dyn ReadonlyIndex>, IndexWriteError> {
         let index = index
             .into_any()
             .downcast::<DefaultMutableIndex>()
             .expect("index to merge in must be a DefaultMutableIndex");
         let index_segment = self
-            .save_mutable_index(*index, op_id)
+            .save_mutable_index(*index, op_id, store)
             .map_err(|err| IndexWriteError(err.into()))?;
         Ok(Box::new(DefaultReadonlyIndex::from_segment(index_segment)))
     }
 }
+
+/// Computes the changed-path filters of the local commits of the `segment`.
+/// Filters which exist in the `previous` segments (which may have been squashed
+/// into the `segment`) are reused.
+fn build_changed_path_filters(
+    segment: &ReadonlyIndexSegment,
+    previous: Option<&Arc<ReadonlyIndexSegment>>,
+    store: &Arc<Store>,
+) -> BackendResult<ChangedPathFilters> {
+    let index = segment.as_composite();
+    let previous_index = previous.map(|segment| segment.as_composite());
+    let filters = (segment.num_parent_commits()..index.num_commits())
+        .map(|pos| {
+            let commit_id = index.entry_by_pos(IndexPosition(pos)).commit_id();
+            let previous_filter = previous_index.and_then(|previous_index| {
+                let previous_pos = previous_index.commit_id_to_pos(&commit_id)?;
+                let entry = previous_index.entry_by_pos(previous_pos);
+                entry.changed_path_filter().cloned()
+            });
+            match previous_filter {
+                Some(filter) => Ok(Some(filter)),
+                None => compute_changed_path_filter(store, &commit_id),
+            }
+        })
+        .try_collect()?;
+    Ok(ChangedPathFilters::new(filters))
+}
+
+/// Computes the filter of the paths changed from the parent of the commit.
+/// Root and merge commits don't get a filter.
+fn compute_changed_path_filter(
+    store: &Arc<Store>,
+    commit_id: &CommitId,
+) -> BackendResult<Option<ChangedPathFilter>> {
+    let commit = store.get_commit(commit_id)?;
+    let [parent_id] = commit.parent_ids() else {
+        return Ok(None);
+    };
+    let parent = store.get_commit(parent_id)?;
+    let changed_files: Vec<_> = parent
+        .tree()?
+        .diff(&commit.tree()?, &EverythingMatcher)
+        // One more file than the limit is enough to tell there are too many
+        .take(MAX_FILTER_PATHS + 1)
+        .map(|(path, diff)| diff.map(|_| path))
+        .try_collect()?;
+    Ok(ChangedPathFilter::from_changed_files(
+        changed_files.iter().map(|path| path.as_ref()),
+    ))
+}
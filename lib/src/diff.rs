@@ -14,6 +14,7 @@
 
 #![allow(missing_docs)]
 
+use std::borrow::Cow;
 use std::cmp::{max, min, Ordering};
 use std::collections::{BTreeMap, HashMap};
 use std::fmt::{Debug, Formatter};
@@ -42,6 +43,86 @@ pub fn find_line_ranges(text: &[u8]) -> Vec<Range<usize>> {
     ranges
 }
 
+/// How whitespace is treated when matching lines in a diff. Only the matching
+/// is affected; diff hunks still contain the original bytes.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum WhitespaceMode {
+    /// Lines must match exactly.
+    #[default]
+    Exact,
+    /// Ignore a carriage return at the end of a line.
+    IgnoreCrAtEol,
+    /// Ignore whitespace at the end of a line, and treat any other run of
+    /// whitespace as equal to any other.
+    IgnoreSpaceChange,
+    /// Ignore all whitespace within lines.
+    IgnoreAllSpace,
+}
+
+impl WhitespaceMode {
+    /// Normalizes `line`, excluding its newline, so that lines which should
+    /// match are equal.
+    fn normalize_line_content(self, line: &[u8], out: &mut Vec<u8>) {
+        match self {
+            WhitespaceMode::Exact => out.extend_from_slice(line),
+            WhitespaceMode::IgnoreCrAtEol => {
+                out.extend_from_slice(line.strip_suffix(b"\r").unwrap_or(line));
+            }
+            WhitespaceMode::IgnoreSpaceChange => {
+                let end = line
+                    .iter()
+                    .rposition(|b| !b.is_ascii_whitespace())
+                    .map_or(0, |i| i + 1);
+                let mut in_space = false;
+                for &b in &line[..end] {
+                    if b.is_ascii_whitespace() {
+                        in_space = true;
+                    } else {
+                        if in_space {
+                            out.push(b' ');
+                            in_space = false;
+                        }
+                        out.push(b);
+                    }
+                }
+            }
+            WhitespaceMode::IgnoreAllSpace => {
+                out.extend(line.iter().filter(|b| !b.is_ascii_whitespace()));
+            }
+        }
+    }
+
+    /// Normalizes each line of `text`, returning the normalized text and the
+    /// range of each line in it. Every line of the input has a range, even if
+    /// it becomes empty.
+    fn normalize_lines(self, text: &[u8]) -> (Vec<u8>, Vec<Range<usize>>) {
+        let mut normalized = Vec::with_capacity(text.len());
+        let mut ranges = vec![];
+        for line in text.split_inclusive(|b| *b == b'\n') {
+            let start = normalized.len();
+            match line.strip_suffix(b"\n") {
+                Some(content) => {
+                    self.normalize_line_content(content, &mut normalized);
+                    normalized.push(b'\n');
+                }
+                None => self.normalize_line_content(line, &mut normalized),
+            }
+            ranges.push(start..normalized.len());
+        }
+        (normalized, ranges)
+    }
+
+    /// Returns `text` with each line normalized, so that texts which only
+    /// differ in ignored whitespace are equal.
+    pub fn normalize<'a>(self, text: &'a [u8]) -> Cow<'a, [u8]> {
+        if self == WhitespaceMode::Exact {
+            Cow::Borrowed(text)
+        } else {
+            Cow::Owned(self.normalize_lines(text).0)
+        }
+    }
+}
+
 fn is_word_byte(b: u8) -> bool {
     // TODO: Make this configurable (probably higher up in the call stack)
     matches!(
@@ -635,6 +716,106 @@ pub fn diff<'a>(left: &'a [u8], right: &'a [u8]) -> Vec<DiffHunk<'a>> {
         .collect_vec()
 }
 
+/// Diffs two slices of bytes line by line, matching lines as normalized by
+/// `whitespace`. Unlike `diff()`, changed lines are not refined further.
+pub fn diff_lines<'a>(
+    left: &'a [u8],
+    right: &'a [u8],
+    whitespace: WhitespaceMode,
+) -> Vec<DiffHunk<'a>> {
+    if whitespace == WhitespaceMode::Exact {
+        return Diff::for_tokenizer(&[left, right], &find_line_ranges)
+            .hunks()
+            .collect_vec();
+    }
+    let (left_normalized, left_ranges) = whitespace.normalize_lines(left);
+    let (right_normalized, right_ranges) = whitespace.normalize_lines(right);
+    if left_normalized == right_normalized {
+        return if left.is_empty() && right.is_empty() {
+            vec![]
+        } else {
+            vec![DiffHunk::Matching(left)]
+        };
+    }
+    // Map the matching normalized lines back to line numbers. Only the last
+    // line can be empty, so the ranges identify the lines.
+    let left_line_numbers: HashMap<_, _> = left_ranges
+        .iter()
+        .enumerate()
+        .map(|(i, range)| ((range.start, range.end), i))
+        .collect();
+    let right_line_numbers: HashMap<_, _> = right_ranges
+        .iter()
+        .enumerate()
+        .map(|(i, range)| ((range.start, range.end), i))
+        .collect();
+    let matching_lines = unchanged_ranges(
+        &left_normalized,
+        &right_normalized,
+        &left_ranges,
+        &right_ranges,
+    )
+    .into_iter()
+    .map(|(left_range, right_range)| {
+        (
+            left_line_numbers[&(left_range.start, left_range.end)],
+            right_line_numbers[&(right_range.start, right_range.end)],
+        )
+    })
+    .chain([(left_ranges.len(), right_ranges.len())]);
+
+    let left_lines = find_line_ranges(left);
+    let right_lines = find_line_ranges(right);
+    let line_start = |lines: &[Range<usize>], text: &[u8], i: usize| {
+        lines.get(i).map_or(text.len(), |line| line.start)
+    };
+    let mut hunks = vec![];
+    let mut left_pos = 0;
+    let mut right_pos = 0;
+    let mut matching_start = None;
+    for (left_line, right_line) in matching_lines {
+        let left_start = line_start(&left_lines, left, left_line);
+        let right_start = line_start(&right_lines, right, right_line);
+        if left_start != left_pos || right_start != right_pos {
+            if let Some(start) = matching_start.take() {
+                hunks.push(DiffHunk::Matching(&left[start..left_pos]));
+            }
+            hunks.push(DiffHunk::Different(vec![
+                &left[left_pos..left_start],
+                &right[right_pos..right_start],
+            ]));
+        }
+        if left_line < left_lines.len() {
+            matching_start.get_or_insert(left_start);
+            left_pos = left_lines[left_line].end;
+            right_pos = right_lines[right_line].end;
+        }
+    }
+    if let Some(start) = matching_start {
+        hunks.push(DiffHunk::Matching(&left[start..left_pos]));
+    }
+    hunks
+}
+
+/// Like `diff()`, but matches lines as normalized by `whitespace`. Changed
+/// lines are refined at the word level without ignoring any whitespace.
+pub fn diff_with_whitespace<'a>(
+    left: &'a [u8],
+    right: &'a [u8],
+    whitespace: WhitespaceMode,
+) -> Vec<DiffHunk<'a>> {
+    if whitespace == WhitespaceMode::Exact {
+        return diff(left, right);
+    }
+    diff_lines(left, right, whitespace)
+        .into_iter()
+        .flat_map(|hunk| match hunk {
+            DiffHunk::Matching(_) => vec![hunk],
+            DiffHunk::Different(contents) => diff(contents[0], contents[1]),
+        })
+        .collect()
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -1188,4 +1369,96 @@ int main(int argc, char **argv)
             ]
         );
     }
+
+    #[test]
+    fn test_whitespace_mode_normalize() {
+        let text = b"a \t b  \r\n  c\r\n\n d";
+        assert_eq!(WhitespaceMode::Exact.normalize(text).as_ref(), text);
+        assert_eq!(
+            WhitespaceMode::IgnoreCrAtEol.normalize(text).as_ref(),
+            b"a \t b  \n  c\n\n d"
+        );
+        assert_eq!(
+            WhitespaceMode::IgnoreSpaceChange.normalize(text).as_ref(),
+            b"a b\n c\n\n d"
+        );
+        assert_eq!(
+            WhitespaceMode::IgnoreAllSpace.normalize(text).as_ref(),
+            b"ab\nc\n\nd"
+        );
+    }
+
+    #[test]
+    fn test_diff_lines_exact() {
+        assert_eq!(
+            diff_lines(b"a\nb \nc\n", b"a\nb\nc\n", WhitespaceMode::Exact),
+            vec![
+                DiffHunk::Matching(b"a\n"),
+                DiffHunk::Different(vec![b"b \n", b"b\n"]),
+                DiffHunk::Matching(b"c\n"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_diff_lines_whitespace_only() {
+        let left = b"fn f() {\n  x\n}\n";
+        let right = b"fn f()  {\r\n\tx \r\n}\r\n";
+        assert_eq!(
+            diff_lines(left, right, WhitespaceMode::IgnoreAllSpace),
+            vec![DiffHunk::Matching(left)]
+        );
+        assert_eq!(
+            diff_lines(left, right, WhitespaceMode::IgnoreSpaceChange),
+            vec![DiffHunk::Matching(left)]
+        );
+        assert_eq!(
+            diff_lines(b"a\r\nb \n", b"a\nb \r\n", WhitespaceMode::IgnoreCrAtEol),
+            vec![DiffHunk::Matching(b"a\r\nb \n")]
+        );
+        assert_eq!(
+            diff_lines(left, right, WhitespaceMode::IgnoreCrAtEol),
+            vec![
+                DiffHunk::Different(vec![b"fn f() {\n  x\n", b"fn f()  {\r\n\tx \r\n"]),
+                DiffHunk::Matching(b"}\n"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_diff_lines_mixed_changes() {
+        // Whitespace changes are matched, real changes are not
+        let left = b"a\nb\nc\nd\n";
+        let right = b"a \nB\n c\nd\ne";
+        assert_eq!(
+            diff_lines(left, right, WhitespaceMode::IgnoreAllSpace),
+            vec![
+                DiffHunk::Matching(b"a\n"),
+                DiffHunk::Different(vec![b"b\n", b"B\n"]),
+                DiffHunk::Matching(b"c\nd\n"),
+                DiffHunk::Different(vec![b"", b"e"]),
+            ]
+        );
+        assert_eq!(
+            diff_lines(left, right, WhitespaceMode::IgnoreSpaceChange),
+            vec![
+                DiffHunk::Matching(b"a\n"),
+                DiffHunk::Different(vec![b"b\nc\n", b"B\n c\n"]),
+                DiffHunk::Matching(b"d\n"),
+                DiffHunk::Different(vec![b"", b"e"]),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_diff_with_whitespace_refines_changed_lines() {
+        assert_eq!(
+            diff_with_whitespace(b"a b\nc\n", b"a  b\nd\n", WhitespaceMode::IgnoreAllSpace),
+            vec![
+                DiffHunk::Matching(b"a b\n"),
+                DiffHunk::Different(vec![b"c", b"d"]),
+                DiffHunk::Matching(b"\n"),
+            ]
+        );
+    }
 }
@@ -55,6 +55,39 @@ pub fn create_or_reuse_dir(dirname: &Path) -> io::Result<()> {
     }
 }
 
+/// Returns true if the error means that the file system or the file is
+/// read-only.
+pub fn is_read_only_error(err: &io::Error) -> bool {
+    if err.kind() == io::ErrorKind::PermissionDenied {
+        return true;
+    }
+    #[cfg(unix)]
+    {
+        err.raw_os_error() == Some(rustix::io::Errno::ROFS.raw_os_error())
+    }
+    #[cfg(not(unix))]
+    {
+        false
+    }
+}
+
+/// Returns true if files can't be created in the directory because it (or the
+/// file system it's on) is read-only. This only checks the permissions, so
+/// nothing is written to the directory.
+pub fn is_dir_read_only(dir: &Path) -> bool {
+    #[cfg(unix)]
+    {
+        match rustix::fs::access(dir, rustix::fs::Access::WRITE_OK) {
+            Ok(()) => false,
+            Err(err) => is_read_only_error(&err.into()),
+        }
+    }
+    #[cfg(not(unix))]
+    {
+        fs::metadata(dir).is_ok_and(|metadata| metadata.permissions().readonly())
+    }
+}
+
 /// Turns the given `to` path into relative path starting from the `from` path.
 ///
 /// Both `from` and `to` paths are supposed to be absolute and normalized in the
@@ -77,6 +110,16 @@ pub fn relative_path(from: &Path, to: &Path) -> PathBuf {
     to.to_owned()
 }
 
+/// Like `relative_path()`, but returns `None` if the paths have no common
+/// ancestor other than the filesystem root. Such paths are unlikely to be
+/// moved together, so the absolute path is more robust.
+pub fn relative_path_if_related(from: &Path, to: &Path) -> Option<PathBuf> {
+    let has_common_dir = iter::zip(from.components(), to.components())
+        .take_while(|(a, b)| a == b)
+        .any(|(component, _)| matches!(component, Component::Normal(_)));
+    has_common_dir.then(|| relative_path(from, to))
+}
+
 /// Consumes as much `..` and `.` as possible without considering symlinks.
 pub fn normalize_path(path: &Path) -> PathBuf {
     let mut result = PathBuf::new();
@@ -103,6 +146,28 @@ pub fn normalize_path(path: &Path) -> PathBuf {
     }
 }
 
+/// Tries to find where `old_path` is now, assuming that a directory containing
+/// it was moved or renamed along with `base`.
+///
+/// The trailing components of `old_path` are joined to each ancestor of
+/// `base`, and the first candidate accepted by `is_valid` is returned. Longer
+/// trailing parts are tried first, so the candidate which shares the most with
+/// the old path wins.
+pub fn find_moved_path(
+    old_path: &Path,
+    base: &Path,
+    is_valid: impl Fn(&Path) -> bool,
+) -> Option<PathBuf> {
+    let components: Vec<_> = old_path
+        .components()
+        .filter(|component| matches!(component, Component::Normal(_)))
+        .collect();
+    (0..components.len())
+        .map(|start| PathBuf::from_iter(&components[start..]))
+        .flat_map(|suffix| base.ancestors().map(move |ancestor| ancestor.join(&suffix)))
+        .find(|candidate| is_valid(candidate))
+}
+
 /// Like `NamedTempFile::persist()`, but doesn't try to overwrite the existing
 /// target on Windows.
 pub fn persist_content_addressed_temp_file<P: AsRef<Path>>(
@@ -156,6 +221,31 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_find_moved_path() {
+        let temp_dir = new_temp_dir();
+        let new_root = temp_dir.path().join("new");
+        fs::create_dir_all(new_root.join("main").join(".jj").join("repo")).unwrap();
+        fs::create_dir_all(new_root.join("secondary").join(".jj")).unwrap();
+        let is_repo = |path: &Path| path.ends_with(".jj/repo") && path.is_dir();
+
+        // The common parent directory was renamed from "old" to "new"
+        let old_path = temp_dir
+            .path()
+            .join("old")
+            .join("main")
+            .join(".jj")
+            .join("repo");
+        let base = new_root.join("secondary").join(".jj");
+        assert_eq!(
+            find_moved_path(&old_path, &base, is_repo),
+            Some(new_root.join("main").join(".jj").join("repo"))
+        );
+        // The directory containing the target itself was renamed
+        let old_path = new_root.join("renamed").join(".jj").join("repo");
+        assert_eq!(find_moved_path(&old_path, &base, is_repo), None);
+    }
+
     #[test]
     fn test_persist_no_existing_file() {
         let temp_dir = testutils::new_temp_dir();
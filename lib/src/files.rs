@@ -52,6 +52,16 @@ pub fn diff<'a>(left: &'a [u8], right: &'a [u8]) -> DiffLineIterator<'a> {
     DiffLineIterator::new(diff_hunks)
 }
 
+/// Like `diff()`, but matches lines as normalized by `whitespace`.
+pub fn diff_with_whitespace<'a>(
+    left: &'a [u8],
+    right: &'a [u8],
+    whitespace: diff::WhitespaceMode,
+) -> DiffLineIterator<'a> {
+    let diff_hunks = diff::diff_with_whitespace(left, right, whitespace);
+    DiffLineIterator::new(diff_hunks)
+}
+
 pub struct DiffLineIterator<'a> {
     diff_hunks: Vec<DiffHunk<'a>>,
     current_pos: usize,
@@ -357,6 +367,46 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_merge_multi_hunk_multi_way() {
+        // Three sides changed different lines
+        assert_eq!(
+            merge(
+                &[b"a\nx\nb\ny\nc\n", b"a\nx\nb\ny\nc\n"],
+                &[
+                    b"a1\nx\nb\ny\nc\n",
+                    b"a\nx\nb2\ny\nc\n",
+                    b"a\nx\nb\ny\nc3\n"
+                ]
+            ),
+            MergeResult::Resolved(hunk(b"a1\nx\nb2\ny\nc3\n"))
+        );
+        // Two sides made the same change, third side changed another line
+        assert_eq!(
+            merge(
+                &[b"a\nb\nc\n", b"a\nb\nc\n"],
+                &[b"a1\nb\nc\n", b"a1\nb\nc\n", b"a\nb\nc3\n"]
+            ),
+            MergeResult::Resolved(hunk(b"a1\nb\nc3\n"))
+        );
+        // Two sides made the same change, third side changed the same line in
+        // another way
+        assert_eq!(
+            merge(
+                &[b"a\nb\nc\n", b"a\nb\nc\n"],
+                &[b"a\nb1\nc\n", b"a\nb1\nc\n", b"a\nb2\nc\n"]
+            ),
+            MergeResult::Conflict(vec![
+                Merge::resolved(hunk(b"a\n")),
+                Merge::from_removes_adds(
+                    vec![hunk(b"b\n"), hunk(b"b\n")],
+                    vec![hunk(b"b1\n"), hunk(b"b1\n"), hunk(b"b2\n")]
+                ),
+                Merge::resolved(hunk(b"c\n"))
+            ])
+        );
+    }
+
     #[test]
     fn test_merge_multi_hunk() {
         // Two sides left one line unchanged, and added conflicting additional lines
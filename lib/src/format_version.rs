@@ -0,0 +1,446 @@
+// Copyright 2024 The Jujutsu Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Versions of the on-disk formats of the repo and the working copy, and
+//! migrations between them.
+//!
+//! Each component records its format version in a `format_version` file in its
+//! directory. A missing file means version 1, which is the format of repos
+//! created before versions were recorded. A binary refuses to load a component
+//! whose format is newer than it supports, unless the component records that
+//! older binaries can still read it, in which case the repo is loaded
+//! read-only. Components in an older format are brought up to date by
+//! `upgrade()`.
+
+use std::collections::BTreeMap;
+use std::fmt::{self, Display};
+use std::fs;
+use std::io::{self, Write as _};
+use std::path::{Path, PathBuf};
+
+use tempfile::NamedTempFile;
+use thiserror::Error;
+
+use crate::file_util::{IoResultExt as _, PathError};
+use crate::lock::{FileLock, LockKind};
+
+/// Name of the file recording the format version in a component's directory.
+pub const FORMAT_VERSION_FILE: &str = "format_version";
+
+/// A part of the repo or workspace with its own on-disk format.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum FormatComponent {
+    /// The layout of the repo directory itself.
+    Repo,
+    /// The operation store.
+    OpStore,
+    /// The commit index.
+    Index,
+    /// The commit store, such as the local backend's object files.
+    Store,
+    /// The working-copy state of a workspace.
+    WorkingCopy,
+}
+
+impl FormatComponent {
+    /// All components, in the order they are checked and upgraded.
+    pub const ALL: [FormatComponent; 5] = [
+        FormatComponent::Repo,
+        FormatComponent::OpStore,
+        FormatComponent::Index,
+        FormatComponent::Store,
+        FormatComponent::WorkingCopy,
+    ];
+
+    /// The components stored in the repo directory, as opposed to the
+    /// workspace.
+    pub const REPO: [FormatComponent; 4] = [
+        FormatComponent::Repo,
+        FormatComponent::OpStore,
+        FormatComponent::Index,
+        FormatComponent::Store,
+    ];
+
+    /// Name of the component for messages.
+    pub fn name(self) -> &'static str {
+        match self {
+            FormatComponent::Repo => "repo",
+            FormatComponent::OpStore => "operation store",
+            FormatComponent::Index => "index",
+            FormatComponent::Store => "commit store",
+            FormatComponent::WorkingCopy => "working copy",
+        }
+    }
+
+    /// The directory of the component, given the repo directory and the
+    /// working-copy state directory.
+    pub fn dir(self, repo_path: &Path, working_copy_path: &Path) -> PathBuf {
+        match self {
+            FormatComponent::Repo => repo_path.to_owned(),
+            FormatComponent::OpStore => repo_path.join("op_store"),
+            FormatComponent::Index => repo_path.join("index"),
+            FormatComponent::Store => repo_path.join("store"),
+            FormatComponent::WorkingCopy => working_copy_path.to_owned(),
+        }
+    }
+}
+
+impl Display for FormatComponent {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// The format version recorded for a component.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct FormatVersion {
+    /// The version the component's data is in.
+    pub version: u32,
+    /// The oldest version whose readers can still read the data. Binaries
+    /// supporting at least this version load the component read-only.
+    pub min_reader_version: u32,
+}
+
+impl FormatVersion {
+    /// The version of components without a `format_version` file.
+    pub const INITIAL: FormatVersion = FormatVersion {
+        version: 1,
+        min_reader_version: 1,
+    };
+
+    /// Reads the version recorded in `dir`. The file contains the version,
+    /// optionally followed by the minimum reader version.
+    pub fn read(dir: &Path) -> Result<Self, FormatVersionError> {
+        let path = dir.join(FORMAT_VERSION_FILE);
+        let content = match fs::read_to_string(&path) {
+            Ok(content) => content,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(Self::INITIAL),
+            Err(err) => return Err(PathError { path, error: err }.into()),
+        };
+        let invalid = || FormatVersionError::Invalid {
+            path: path.clone(),
+            content: content.clone(),
+        };
+        let numbers: Vec<u32> = content
+            .split_whitespace()
+            .map(|word| word.parse().map_err(|_| invalid()))
+            .collect::<Result<_, _>>()?;
+        match numbers[..] {
+            [version] if version > 0 => Ok(FormatVersion {
+                version,
+                min_reader_version: version,
+            }),
+            [version, min_reader_version]
+                if 0 < min_reader_version && min_reader_version <= version =>
+            {
+                Ok(FormatVersion {
+                    version,
+                    min_reader_version,
+                })
+            }
+            _ => Err(invalid()),
+        }
+    }
+
+    /// Atomically records the version in `dir`.
+    pub fn write(self, dir: &Path) -> Result<(), PathError> {
+        let path = dir.join(FORMAT_VERSION_FILE);
+        let mut temp_file = NamedTempFile::new_in(dir).context(dir)?;
+        if self.min_reader_version == self.version {
+            writeln!(temp_file, "{}", self.version)
+        } else {
+            writeln!(temp_file, "{} {}", self.version, self.min_reader_version)
+        }
+        .context(temp_file.path())?;
+        temp_file
+            .persist(&path)
+            .map_err(|err| err.error)
+            .context(&path)?;
+        Ok(())
+    }
+}
+
+/// The newest format version of each component that a binary supports.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SupportedVersions {
+    versions: BTreeMap<FormatComponent, u32>,
+}
+
+impl SupportedVersions {
+    /// The versions supported by this build of the library.
+    pub fn current() -> Self {
+        let versions = FormatComponent::ALL
+            .into_iter()
+            .map(|component| (component, FormatVersion::INITIAL.version))
+            .collect();
+        SupportedVersions { versions }
+    }
+
+    /// Sets the supported version of `component`.
+    pub fn with_version(mut self, component: FormatComponent, version: u32) -> Self {
+        self.versions.insert(component, version);
+        self
+    }
+
+    /// The newest supported version of `component`.
+    pub fn get(&self, component: FormatComponent) -> u32 {
+        self.versions[&component]
+    }
+}
+
+/// Error when a component's format can't be used by this binary.
+#[derive(Debug, Error)]
+pub enum FormatVersionError {
+    /// The component was written by a newer binary.
+    #[error("The {component} requires format {version}, this binary supports up to {supported}")]
+    TooNew {
+        /// The component.
+        component: FormatComponent,
+        /// The version the component is in.
+        version: u32,
+        /// The version this binary supports.
+        supported: u32,
+    },
+    /// The component needs to be upgraded before it can be used.
+    #[error("The {component} uses format {version}, this binary requires format {supported}")]
+    TooOld {
+        /// The component.
+        component: FormatComponent,
+        /// The version the component is in.
+        version: u32,
+        /// The version this binary supports.
+        supported: u32,
+    },
+    /// The version file couldn't be parsed.
+    #[error("Invalid format version {content:?} in {}", path.display())]
+    Invalid {
+        /// The version file.
+        path: PathBuf,
+        /// The contents of the file.
+        content: String,
+    },
+    /// The version file couldn't be read or written.
+    #[error(transparent)]
+    Path(#[from] PathError),
+}
+
+/// How a component whose format is supported can be used.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FormatCompatibility {
+    /// The component is in the current format.
+    ReadWrite,
+    /// The component is in a newer format which this binary can read but must
+    /// not write.
+    ReadOnly,
+}
+
+/// Checks the format version of `component`, which is stored in `dir`.
+pub fn check_component(
+    component: FormatComponent,
+    dir: &Path,
+    supported: &SupportedVersions,
+) -> Result<FormatCompatibility, FormatVersionError> {
+    let found = FormatVersion::read(dir)?;
+    let supported = supported.get(component);
+    if found.version == supported {
+        Ok(FormatCompatibility::ReadWrite)
+    } else if found.version < supported {
+        Err(FormatVersionError::TooOld {
+            component,
+            version: found.version,
+            supported,
+        })
+    } else if found.min_reader_version <= supported {
+        Ok(FormatCompatibility::ReadOnly)
+    } else {
+        Err(FormatVersionError::TooNew {
+            component,
+            version: found.version,
+            supported,
+        })
+    }
+}
+
+/// Checks the format versions of the components in the repo directory. The
+/// repo must be loaded read-only if any of them is `ReadOnly`.
+pub fn check_repo(
+    repo_path: &Path,
+    supported: &SupportedVersions,
+) -> Result<FormatCompatibility, FormatVersionError> {
+    let mut compatibility = FormatCompatibility::ReadWrite;
+    for component in FormatComponent::REPO {
+        // The working-copy path is unused for repo components
+        let dir = component.dir(repo_path, Path::new(""));
+        if check_component(component, &dir, supported)? == FormatCompatibility::ReadOnly {
+            compatibility = FormatCompatibility::ReadOnly;
+        }
+    }
+    Ok(compatibility)
+}
+
+/// A step upgrading a component from one format version to the next.
+#[derive(Clone, Debug)]
+pub struct Migration {
+    /// The component to upgrade.
+    pub component: FormatComponent,
+    /// The version to upgrade from. The migration upgrades to the next
+    /// version.
+    pub from_version: u32,
+    /// Whether binaries supporting `from_version` can still read the upgraded
+    /// data, in which case they load it read-only instead of refusing to.
+    pub readable_by_previous: bool,
+    /// What the migration does, for messages.
+    pub description: &'static str,
+    /// Rewrites the data in the component's directory.
+    pub run: fn(&Path) -> io::Result<()>,
+}
+
+/// The migrations of this build of the library, which upgrade components to
+/// `SupportedVersions::current()`.
+pub fn builtin_migrations() -> Vec<Migration> {
+    vec![]
+}
+
+/// A migration that was applied by `upgrade()`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct AppliedMigration {
+    /// The upgraded component.
+    pub component: FormatComponent,
+    /// The version the component was upgraded from.
+    pub from_version: u32,
+    /// The description of the migration.
+    pub description: &'static str,
+}
+
+/// Error when upgrading the formats of a repo or workspace.
+#[derive(Debug, Error)]
+pub enum UpgradeError {
+    /// A component is in a format that can't be upgraded.
+    #[error(transparent)]
+    FormatVersion(#[from] FormatVersionError),
+    /// There's no migration from a component's current format.
+    #[error("Don't know how to upgrade the {component} from format {version}")]
+    MissingMigration {
+        /// The component.
+        component: FormatComponent,
+        /// The version the component is in.
+        version: u32,
+    },
+    /// A migration failed. The component is still in the previous format.
+    #[error("Failed to upgrade the {component} from format {version}")]
+    Migration {
+        /// The component.
+        component: FormatComponent,
+        /// The version the component is still in.
+        version: u32,
+        /// The error from the migration.
+        #[source]
+        source: io::Error,
+    },
+    /// A new version couldn't be recorded.
+    #[error(transparent)]
+    Path(#[from] PathError),
+}
+
+/// Upgrades the components of the repo at `repo_path` and, if given, the
+/// working copy whose state is in `working_copy_path` to the `supported`
+/// versions by running `migrations` in order.
+///
+/// Repo components are upgraded while holding the operation heads lock, so no
+/// operation can be written at the same time, and the working copy while
+/// holding the working-copy lock. Each migration's new version is recorded
+/// atomically after it succeeds, so an interrupted upgrade can be resumed.
+pub fn upgrade(
+    repo_path: &Path,
+    working_copy_path: Option<&Path>,
+    supported: &SupportedVersions,
+    migrations: &[Migration],
+) -> Result<Vec<AppliedMigration>, UpgradeError> {
+    let mut applied = vec![];
+    {
+        let _lock = FileLock::lock(
+            repo_path.join("op_heads").join("heads").join("lock"),
+            LockKind::OpHeads,
+        );
+        for component in FormatComponent::REPO {
+            let dir = component.dir(repo_path, Path::new(""));
+            applied.extend(upgrade_component(component, &dir, supported, migrations)?);
+        }
+    }
+    if let Some(working_copy_path) = working_copy_path {
+        let _lock = FileLock::lock(
+            working_copy_path.join("working_copy.lock"),
+            LockKind::WorkingCopy,
+        );
+        let component = FormatComponent::WorkingCopy;
+        applied.extend(upgrade_component(
+            component,
+            working_copy_path,
+            supported,
+            migrations,
+        )?);
+    }
+    Ok(applied)
+}
+
+fn upgrade_component(
+    component: FormatComponent,
+    dir: &Path,
+    supported: &SupportedVersions,
+    migrations: &[Migration],
+) -> Result<Vec<AppliedMigration>, UpgradeError> {
+    let mut found = FormatVersion::read(dir)?;
+    let supported_version = supported.get(component);
+    if found.version > supported_version {
+        return Err(FormatVersionError::TooNew {
+            component,
+            version: found.version,
+            supported: supported_version,
+        }
+        .into());
+    }
+    let mut applied = vec![];
+    while found.version < supported_version {
+        let migration = migrations
+            .iter()
+            .find(|migration| {
+                migration.component == component && migration.from_version == found.version
+            })
+            .ok_or(UpgradeError::MissingMigration {
+                component,
+                version: found.version,
+            })?;
+        (migration.run)(dir).map_err(|source| UpgradeError::Migration {
+            component,
+            version: found.version,
+            source,
+        })?;
+        let new_version = found.version + 1;
+        found = FormatVersion {
+            version: new_version,
+            min_reader_version: if migration.readable_by_previous {
+                found.min_reader_version
+            } else {
+                new_version
+            },
+        };
+        found.write(dir)?;
+        applied.push(AppliedMigration {
+            component,
+            from_version: new_version - 1,
+            description: migration.description,
+        });
+    }
+    Ok(applied)
+}
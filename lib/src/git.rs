@@ -19,6 +19,8 @@ use std::collections::{BTreeMap, HashMap, HashSet};
 use std::default::Default;
 use std::io::Read;
 use std::path::PathBuf;
+use std::thread;
+use std::time::Duration;
 use std::{fmt, iter, str};
 
 use git2::Oid;
@@ -27,11 +29,12 @@ use tempfile::NamedTempFile;
 use thiserror::Error;
 
 use crate::backend::{BackendError, CommitId};
+use crate::cancel::{CancellationToken, Cancelled};
 use crate::commit::Commit;
 use crate::git_backend::GitBackend;
 use crate::object_id::ObjectId;
 use crate::op_store::{RefTarget, RefTargetOptionExt, RemoteRef, RemoteRefState};
-use crate::refs::BranchPushUpdate;
+use crate::refs::{self, BranchPushUpdate, RefNameError};
 use crate::repo::{MutableRepo, Repo};
 use crate::revset::RevsetExpression;
 use crate::settings::GitSettings;
@@ -81,14 +84,17 @@ fn parse_git_ref(ref_name: &str) -> Option<RefName> {
     }
 }
 
-fn to_git_ref_name(parsed_ref: &RefName) -> Option<String> {
+fn to_git_ref_name(parsed_ref: &RefName) -> Result<String, RefNameError> {
     match parsed_ref {
         RefName::LocalBranch(branch) => {
-            (!branch.is_empty() && branch != "HEAD").then(|| format!("refs/heads/{branch}"))
+            refs::validate_branch_name(branch)?;
+            Ok(format!("refs/heads/{branch}"))
         }
-        RefName::RemoteBranch { branch, remote } => (!branch.is_empty() && branch != "HEAD")
-            .then(|| format!("refs/remotes/{remote}/{branch}")),
-        RefName::Tag(tag) => Some(format!("refs/tags/{tag}")),
+        RefName::RemoteBranch { branch, remote } => {
+            refs::validate_branch_name(branch)?;
+            Ok(format!("refs/remotes/{remote}/{branch}"))
+        }
+        RefName::Tag(tag) => Ok(format!("refs/tags/{tag}")),
     }
 }
 
@@ -399,11 +405,14 @@ fn diff_refs_to_import(
                 let RemoteRef { target, state } = remote_ref;
                 (ref_name, (target, *state))
             }),
-        // TODO: compare to tags stored in the "git" remote view. Since tags should never
-        // be moved locally in jj, we can consider local tags as merge base.
-        view.tags().iter().map(|(name, target)| {
-            let ref_name = RefName::Tag(name.to_owned());
-            (ref_name, (target, RemoteRefState::Tracking))
+        // TODO: compare to tags stored in the "git" remote view. Until then, the
+        // last seen Git state is the merge base, so tags created or deleted in jj
+        // aren't reverted by an import before they've been exported.
+        view.git_refs().iter().filter_map(|(full_name, target)| {
+            let ref_name @ RefName::Tag(_) = parse_git_ref(full_name)? else {
+                return None;
+            };
+            Some((ref_name, (target, RemoteRefState::Tracking)))
         }),
     )
     .filter(|(ref_name, _)| git_ref_filter(ref_name))
@@ -572,9 +581,10 @@ pub struct FailedRefExport {
 /// The reason we failed to export a ref to Git.
 #[derive(Debug, Error)]
 pub enum FailedRefExportReason {
-    /// The name is not allowed in Git.
+    /// The name is not allowed in Git, or is reserved by jj. The branch is
+    /// kept in jj, but isn't exported.
     #[error("Name is not allowed in Git")]
-    InvalidGitName,
+    InvalidGitName(#[source] RefNameError),
     /// The ref was in a conflicted state from the last import. A re-import
     /// should fix it.
     #[error("Ref was in a conflicted state from the last import")]
@@ -606,17 +616,17 @@ struct RefsToExport {
     failed_branches: HashMap<RefName, FailedRefExportReason>,
 }
 
-/// Export changes to branches made in the Jujutsu repo compared to our last
-/// seen view of the Git repo in `mut_repo.view().git_refs()`. Returns a list of
-/// refs that failed to export.
+/// Export changes to branches and tags made in the Jujutsu repo compared to our
+/// last seen view of the Git repo in `mut_repo.view().git_refs()`. Returns a
+/// list of refs that failed to export.
 ///
 /// We ignore changed branches that are conflicted (were also changed in the Git
 /// repo compared to our last remembered view of the Git repo). These will be
 /// marked conflicted by the next `jj git import`.
 ///
-/// We do not export tags and other refs at the moment, since these aren't
-/// supposed to be modified by JJ. For them, the Git state is considered
-/// authoritative.
+/// Tags are exported as lightweight tags. An annotated tag in the Git repo is
+/// replaced if the tag was moved or deleted in jj, so callers should check
+/// `is_annotated_tag()` before changing a tag.
 pub fn export_refs(mut_repo: &mut MutableRepo) -> Result<Vec<FailedRefExport>, GitExportError> {
     export_some_refs(mut_repo, |_| true)
 }
@@ -667,10 +677,14 @@ pub fn export_some_refs(
         }
     }
     for (parsed_ref_name, old_oid) in branches_to_delete {
-        let Some(git_ref_name) = to_git_ref_name(&parsed_ref_name) else {
-            failed_branches.insert(parsed_ref_name, FailedRefExportReason::InvalidGitName);
-            continue;
+        let git_ref_name = match to_git_ref_name(&parsed_ref_name) {
+            Ok(git_ref_name) => git_ref_name,
+            Err(err) => {
+                failed_branches.insert(parsed_ref_name, FailedRefExportReason::InvalidGitName(err));
+                continue;
+            }
         };
+        let old_oid = raw_git_ref_oid(&git_repo, &parsed_ref_name, &git_ref_name, old_oid);
         if let Err(reason) = delete_git_ref(&git_repo, &git_ref_name, &old_oid) {
             failed_branches.insert(parsed_ref_name, reason);
         } else {
@@ -679,10 +693,15 @@ pub fn export_some_refs(
         }
     }
     for (parsed_ref_name, (old_oid, new_oid)) in branches_to_update {
-        let Some(git_ref_name) = to_git_ref_name(&parsed_ref_name) else {
-            failed_branches.insert(parsed_ref_name, FailedRefExportReason::InvalidGitName);
-            continue;
+        let git_ref_name = match to_git_ref_name(&parsed_ref_name) {
+            Ok(git_ref_name) => git_ref_name,
+            Err(err) => {
+                failed_branches.insert(parsed_ref_name, FailedRefExportReason::InvalidGitName(err));
+                continue;
+            }
         };
+        let old_oid =
+            old_oid.map(|oid| raw_git_ref_oid(&git_repo, &parsed_ref_name, &git_ref_name, oid));
         if let Err(reason) = update_git_ref(&git_repo, &git_ref_name, old_oid, new_oid) {
             failed_branches.insert(parsed_ref_name, reason);
         } else {
@@ -732,7 +751,50 @@ fn copy_exportable_local_branches_to_remote_view(
     }
 }
 
-/// Calculates diff of branches to be exported.
+/// Git tags can point to tag objects, but we record the commit they point to.
+/// If `git_ref_name` is a tag which points to a tag object for the commit
+/// `old_oid`, returns the id of the tag object so the ref can be updated under
+/// a lock.
+fn raw_git_ref_oid(
+    git_repo: &gix::Repository,
+    parsed_ref_name: &RefName,
+    git_ref_name: &str,
+    old_oid: gix::ObjectId,
+) -> gix::ObjectId {
+    if !matches!(parsed_ref_name, RefName::Tag(_)) {
+        return old_oid;
+    }
+    let Ok(git_ref) = git_repo.find_reference(git_ref_name) else {
+        return old_oid;
+    };
+    let Some(raw_oid) = git_ref.inner.target.try_id().map(ToOwned::to_owned) else {
+        return old_oid;
+    };
+    match git_ref.into_fully_peeled_id() {
+        Ok(peeled_id) if peeled_id.detach() == old_oid => raw_oid,
+        _ => old_oid,
+    }
+}
+
+/// Whether the tag `name` in the Git repo backing `store` is an annotated tag,
+/// i.e. points to a tag object rather than directly to a commit. Since jj only
+/// records the commit, the tag object would be lost if the tag were changed in
+/// jj and exported.
+pub fn is_annotated_tag(store: &Store, name: &str) -> bool {
+    let Some(git_repo) = get_git_repo(store) else {
+        return false;
+    };
+    let Ok(git_ref) = git_repo.find_reference(format!("refs/tags/{name}").as_str()) else {
+        return false;
+    };
+    let is_tag = git_ref
+        .try_id()
+        .and_then(|id| id.object().ok())
+        .is_some_and(|object| object.kind == gix::object::Kind::Tag);
+    is_tag
+}
+
+/// Calculates diff of branches and tags to be exported.
 fn diff_refs_to_export(
     view: &View,
     root_commit_id: &CommitId,
@@ -752,6 +814,9 @@ fn diff_refs_to_export(
                 };
                 (ref_name, &remote_ref.target)
             }),
+        view.tags()
+            .iter()
+            .map(|(name, target)| (RefName::Tag(name.to_owned()), target)),
     )
     .map(|(ref_name, new_target)| (ref_name, (RefTarget::absent_ref(), new_target)))
     .filter(|(ref_name, _)| git_ref_filter(ref_name))
@@ -769,7 +834,7 @@ fn diff_refs_to_export(
             // 2. `jj op undo`/`restore` in colocated repo
             matches!(
                 ref_name,
-                RefName::LocalBranch(..) | RefName::RemoteBranch { .. }
+                RefName::LocalBranch(..) | RefName::RemoteBranch { .. } | RefName::Tag(..)
             )
         })
         .filter(|(ref_name, _)| git_ref_filter(ref_name));
@@ -1086,6 +1151,8 @@ pub enum GitFetchError {
     // TODO: I'm sure there are other errors possible, such as transport-level errors.
     #[error("Unexpected git error when fetching")]
     InternalGitError(#[from] git2::Error),
+    #[error(transparent)]
+    Cancelled(#[from] Cancelled),
 }
 
 /// Describes successful `fetch()` result.
@@ -1103,7 +1170,7 @@ pub fn fetch(
     git_repo: &git2::Repository,
     remote_name: &str,
     branch_names: &[StringPattern],
-    callbacks: RemoteCallbacks<'_>,
+    mut callbacks: RemoteCallbacks<'_>,
     git_settings: &GitSettings,
 ) -> Result<GitFetchStats, GitFetchError> {
     // Perform a `git fetch` on the local git repo, updating the remote-tracking
@@ -1115,12 +1182,6 @@ pub fn fetch(
             GitFetchError::InternalGitError(err)
         }
     })?;
-    let mut fetch_options = git2::FetchOptions::new();
-    let mut proxy_options = git2::ProxyOptions::new();
-    proxy_options.auto();
-    fetch_options.proxy_options(proxy_options);
-    let callbacks = callbacks.into_git();
-    fetch_options.remote_callbacks(callbacks);
     // At this point, we are only updating Git's remote tracking branches, not the
     // local branches.
     let refspecs: Vec<_> = branch_names
@@ -1143,8 +1204,47 @@ pub fn fetch(
         };
         return Ok(stats);
     }
-    tracing::debug!("remote.download");
-    remote.download(&refspecs, Some(&mut fetch_options))?;
+    let mut retry_cb = callbacks.retry.take();
+    let cancel = callbacks.cancel;
+    let mut attempt = 1;
+    loop {
+        let mut fetch_options = git2::FetchOptions::new();
+        let mut proxy_options = git2::ProxyOptions::new();
+        proxy_options.auto();
+        fetch_options.proxy_options(proxy_options);
+        fetch_options.remote_callbacks(callbacks.to_git());
+        tracing::debug!(attempt, "remote.download");
+        match remote.download(&refspecs, Some(&mut fetch_options)) {
+            Ok(()) => break,
+            // The transfer was aborted by our progress callback. Nothing has
+            // been imported, so the repo is unchanged.
+            Err(_) if cancel.is_some_and(|cancel| cancel.is_cancelled()) => {
+                remote.disconnect().ok();
+                return Err(Cancelled.into());
+            }
+            Err(err) if attempt < git_settings.fetch_attempts && is_transient_fetch_error(&err) => {
+                // Objects are only imported after a successful transfer, so
+                // there's nothing to clean up.
+                let delay = fetch_retry_delay(git_settings.fetch_retry_delay, attempt);
+                tracing::info!(attempt, ?delay, err = %err, "retrying fetch");
+                if let Some(cb) = &mut retry_cb {
+                    cb(&FetchRetry {
+                        attempt,
+                        max_attempts: git_settings.fetch_attempts,
+                        delay,
+                        error: &err,
+                    });
+                }
+                remote.disconnect().ok();
+                thread::sleep(delay);
+                attempt += 1;
+            }
+            Err(err) => return Err(err.into()),
+        }
+    }
+    if let Some(cancel) = cancel {
+        cancel.checkpoint()?;
+    }
     tracing::debug!("remote.prune");
     remote.prune(None)?;
     tracing::debug!("remote.update_tips");
@@ -1181,6 +1281,20 @@ pub fn fetch(
     Ok(stats)
 }
 
+/// The delay before retrying a fetch after the given failed attempt. It
+/// doubles with each attempt, and saturates instead of overflowing.
+fn fetch_retry_delay(base: Duration, attempt: u32) -> Duration {
+    base.saturating_mul(2u32.saturating_pow(attempt.saturating_sub(1)))
+}
+
+/// Whether a failed transfer may succeed if it's tried again.
+fn is_transient_fetch_error(err: &git2::Error) -> bool {
+    matches!(
+        err.class(),
+        git2::ErrorClass::Net | git2::ErrorClass::Http | git2::ErrorClass::Ssh
+    ) && err.code() != git2::ErrorCode::Auth
+}
+
 #[derive(Error, Debug, PartialEq)]
 pub enum GitPushError {
     #[error("No git remote named '{0}'")]
@@ -1325,15 +1439,16 @@ fn push_refs(
     let mut proxy_options = git2::ProxyOptions::new();
     proxy_options.auto();
     push_options.proxy_options(proxy_options);
-    let mut callbacks = callbacks.into_git();
-    callbacks.push_update_reference(|refname, status| {
+    let mut callbacks = callbacks;
+    let mut git_callbacks = callbacks.to_git();
+    git_callbacks.push_update_reference(|refname, status| {
         // The status is Some if the ref update was rejected
         if status.is_none() {
             remaining_remote_refs.remove(refname);
         }
         Ok(())
     });
-    push_options.remote_callbacks(callbacks);
+    push_options.remote_callbacks(git_callbacks);
     remote
         .push(refspecs, Some(&mut push_options))
         .map_err(|err| match (err.class(), err.code()) {
@@ -1361,27 +1476,41 @@ fn push_refs(
 #[allow(clippy::type_complexity)]
 pub struct RemoteCallbacks<'a> {
     pub progress: Option<&'a mut dyn FnMut(&Progress)>,
+    /// Aborts the transfer once cancelled.
+    pub cancel: Option<&'a CancellationToken>,
+    /// Called before a failed transfer is retried.
+    pub retry: Option<&'a mut dyn FnMut(&FetchRetry)>,
     pub get_ssh_keys: Option<&'a mut dyn FnMut(&str) -> Vec<PathBuf>>,
     pub get_password: Option<&'a mut dyn FnMut(&str, &str) -> Option<String>>,
     pub get_username_password: Option<&'a mut dyn FnMut(&str) -> Option<(String, String)>>,
 }
 
-impl<'a> RemoteCallbacks<'a> {
-    fn into_git(mut self) -> git2::RemoteCallbacks<'a> {
+impl RemoteCallbacks<'_> {
+    /// Creates the callbacks for one transfer. Each transfer gets fresh
+    /// credential state, so retries try the same credentials again.
+    fn to_git(&mut self) -> git2::RemoteCallbacks<'_> {
         let mut callbacks = git2::RemoteCallbacks::new();
-        if let Some(progress_cb) = self.progress {
+        let cancel = self.cancel;
+        if self.progress.is_some() || cancel.is_some() {
+            let progress_cb = &mut self.progress;
             callbacks.transfer_progress(move |progress| {
-                progress_cb(&Progress {
-                    bytes_downloaded: (progress.received_objects() < progress.total_objects())
-                        .then(|| progress.received_bytes() as u64),
-                    overall: (progress.indexed_objects() + progress.indexed_deltas()) as f32
-                        / (progress.total_objects() + progress.total_deltas()) as f32,
-                });
-                true
+                if let Some(progress_cb) = progress_cb {
+                    progress_cb(&Progress {
+                        bytes_downloaded: (progress.received_objects() < progress.total_objects())
+                            .then(|| progress.received_bytes() as u64),
+                        overall: (progress.indexed_objects() + progress.indexed_deltas()) as f32
+                            / (progress.total_objects() + progress.total_deltas()) as f32,
+                    });
+                }
+                // Returning false makes libgit2 abort the transfer.
+                !cancel.is_some_and(|cancel| cancel.is_cancelled())
             });
         }
         // TODO: We should expose the callbacks to the caller instead -- the library
         // crate shouldn't read environment variables.
+        let get_ssh_keys = &mut self.get_ssh_keys;
+        let get_password = &mut self.get_password;
+        let get_username_password = &mut self.get_username_password;
         let mut tried_ssh_agent = false;
         let mut ssh_key_paths_to_try: Option<Vec<PathBuf>> = None;
         callbacks.credentials(move |url, username_from_url, allowed_types| {
@@ -1408,7 +1537,7 @@ impl<'a> RemoteCallbacks<'a> {
                     }
 
                     let paths = ssh_key_paths_to_try.get_or_insert_with(|| {
-                        if let Some(ref mut cb) = self.get_ssh_keys {
+                        if let Some(cb) = get_ssh_keys.as_mut() {
                             let mut paths = cb(username);
                             paths.reverse();
                             paths
@@ -1426,7 +1555,7 @@ impl<'a> RemoteCallbacks<'a> {
                     }
                 }
                 if allowed_types.contains(git2::CredentialType::USER_PASS_PLAINTEXT) {
-                    if let Some(ref mut cb) = self.get_password {
+                    if let Some(cb) = get_password.as_mut() {
                         if let Some(pw) = cb(url, username) {
                             tracing::info!(
                                 username,
@@ -1440,7 +1569,7 @@ impl<'a> RemoteCallbacks<'a> {
                     }
                 }
             } else if allowed_types.contains(git2::CredentialType::USER_PASS_PLAINTEXT) {
-                if let Some(ref mut cb) = self.get_username_password {
+                if let Some(cb) = get_username_password.as_mut() {
                     if let Some((username, pw)) = cb(url) {
                         tracing::info!(username, "using userpass_plaintext");
                         return git2::Cred::userpass_plaintext(&username, &pw).map_err(|err| {
@@ -1457,6 +1586,16 @@ impl<'a> RemoteCallbacks<'a> {
     }
 }
 
+/// Describes a failed transfer that's about to be retried.
+pub struct FetchRetry<'a> {
+    /// The attempt that failed, starting at 1.
+    pub attempt: u32,
+    pub max_attempts: u32,
+    /// How long to wait before the next attempt.
+    pub delay: Duration,
+    pub error: &'a git2::Error,
+}
+
 pub struct Progress {
     /// `Some` iff data transfer is currently in progress
     pub bytes_downloaded: Option<u64>,
@@ -1542,3 +1681,19 @@ pub fn parse_gitmodules(
         .collect();
     Ok(ret)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fetch_retry_delay() {
+        let base = Duration::from_secs(1);
+        assert_eq!(fetch_retry_delay(base, 1), Duration::from_secs(1));
+        assert_eq!(fetch_retry_delay(base, 3), Duration::from_secs(4));
+        // Large attempt counts saturate instead of overflowing
+        assert_eq!(fetch_retry_delay(base, 40), base * u32::MAX);
+        assert_eq!(fetch_retry_delay(base, u32::MAX), base * u32::MAX);
+        assert_eq!(fetch_retry_delay(Duration::MAX / 2, 3), Duration::MAX);
+    }
+}
@@ -15,7 +15,7 @@
 #![allow(missing_docs)]
 
 use std::any::Any;
-use std::collections::HashSet;
+use std::collections::{BTreeMap, HashSet};
 use std::fmt::{Debug, Error, Formatter};
 use std::io::{Cursor, Read};
 use std::path::{Path, PathBuf};
@@ -34,14 +34,14 @@ use thiserror::Error;
 use crate::backend::{
     make_root_commit, Backend, BackendError, BackendInitError, BackendLoadError, BackendResult,
     ChangeId, Commit, CommitId, Conflict, ConflictId, ConflictTerm, FileId, MergedTreeId,
-    MillisSinceEpoch, SecureSig, Signature, SigningFn, SymlinkId, Timestamp, Tree, TreeId,
-    TreeValue,
+    MillisSinceEpoch, ObjectKind, SecureSig, Signature, SigningFn, SymlinkId, Timestamp, Tree,
+    TreeId, TreeValue, COMMITTED_VIA_GIT, COMMITTED_VIA_METADATA_KEY,
 };
 use crate::file_util::{IoResultExt as _, PathError};
 use crate::index::Index;
 use crate::lock::FileLock;
 use crate::merge::{Merge, MergeBuilder};
-use crate::object_id::ObjectId;
+use crate::object_id::{HexPrefix, ObjectId};
 use crate::repo_path::{RepoPath, RepoPathComponentBuf};
 use crate::settings::UserSettings;
 use crate::stacked_table::{
@@ -91,6 +91,15 @@ pub enum GitBackendError {
     ReadMetadata(#[source] TableStoreError),
     #[error("Failed to write non-git metadata")]
     WriteMetadata(#[source] TableStoreError),
+    /// An object is missing from the backing Git repo, most likely because it
+    /// was pruned by `git gc` run outside of jj.
+    #[error("Git object {id} is missing from the backing Git repo")]
+    MissingGitObject {
+        id: String,
+        hint: String,
+        #[source]
+        source: gix::object::find::existing::Error,
+    },
 }
 
 impl From<GitBackendError> for BackendError {
@@ -208,6 +217,39 @@ impl GitBackend {
         Self::init_with_repo(settings, store_path, git_repo_path, git_repo)
     }
 
+    /// Initializes a backend that reads the objects of `self`, but writes new
+    /// objects to a new Git repo in the store directory. The objects of `self`
+    /// are found through the Git alternates mechanism, and the new objects get
+    /// the same ids as if they were written to `self`.
+    pub fn init_overlay(
+        &self,
+        settings: &UserSettings,
+        store_path: &Path,
+    ) -> Result<Self, Box<GitBackendInitError>> {
+        let git_repo_path = Path::new("git");
+        let git_repo = gix::ThreadSafeRepository::init_opts(
+            store_path.join(git_repo_path),
+            gix::create::Kind::Bare,
+            gix::create::Options::default(),
+            gix_open_opts_from_settings(settings),
+        )
+        .map_err(GitBackendInitError::InitRepository)?;
+        let alternates_path = git_repo.path().join("objects/info/alternates");
+        let base_objects_path = self.git_repo().common_dir().join("objects");
+        let mut alternates = gix::path::into_bstr(&base_objects_path).into_owned();
+        alternates.push(b'\n');
+        fs::write(&alternates_path, alternates)
+            .context(&alternates_path)
+            .map_err(GitBackendInitError::Path)?;
+        // The alternates are only read when the repo is opened
+        let git_repo = gix::ThreadSafeRepository::open_opts(
+            git_repo.path(),
+            gix_open_opts_from_settings(settings),
+        )
+        .map_err(GitBackendInitError::OpenRepository)?;
+        Self::init_with_repo(settings, store_path, git_repo_path, git_repo)
+    }
+
     fn init_with_repo(
         settings: &UserSettings,
         store_path: &Path,
@@ -491,6 +533,8 @@ fn commit_from_git_without_root_parent(
         author,
         committer,
         secure_sig,
+        // Set from the extra metadata, or by the caller when importing.
+        metadata: BTreeMap::new(),
     })
 }
 
@@ -558,6 +602,7 @@ fn serialize_extras(commit: &Commit) -> Vec<u8> {
     for predecessor in &commit.predecessors {
         proto.predecessors.push(predecessor.to_bytes());
     }
+    proto.metadata = commit.metadata.clone();
     proto.encode_to_vec()
 }
 
@@ -585,6 +630,7 @@ fn deserialize_extras(commit: &mut Commit, bytes: &[u8]) {
     for predecessor in &proto.predecessors {
         commit.predecessors.push(CommitId::from_bytes(predecessor));
     }
+    commit.metadata = proto.metadata;
 }
 
 /// Returns `RefEdit` that will create a ref in `refs/jj/keep` if not exist.
@@ -718,10 +764,19 @@ fn validate_git_object_id(id: &impl ObjectId) -> Result<gix::ObjectId, BackendEr
 
 fn map_not_found_err(err: gix::object::find::existing::Error, id: &impl ObjectId) -> BackendError {
     if matches!(err, gix::object::find::existing::Error::NotFound { .. }) {
+        // jj keeps the objects it references from being pruned, so a missing
+        // object most likely means that the refs keeping it were deleted too.
+        let missing = GitBackendError::MissingGitObject {
+            id: id.hex(),
+            hint: "The object may have been pruned by `git gc` run outside of jj. Run `jj debug \
+                   recover-heads` to remove the references to missing commits."
+                .to_owned(),
+            source: err,
+        };
         BackendError::ObjectNotFound {
             object_type: id.object_type(),
             hash: id.hex(),
-            source: Box::new(err),
+            source: Box::new(missing),
         }
     } else {
         to_read_object_err(err, id)
@@ -766,8 +821,12 @@ fn import_extra_metadata_entries_from_heads(
         // TODO(#1624): Should we read the root tree here and check if it has a
         // `.jjconflict-...` entries? That could happen if the user used `git` to e.g.
         // change the description of a commit with tree-level conflicts.
-        let commit =
+        let mut commit =
             commit_from_git_without_root_parent(&id, &git_object, uses_tree_conflict_format)?;
+        commit.metadata.insert(
+            COMMITTED_VIA_METADATA_KEY.to_owned(),
+            COMMITTED_VIA_GIT.to_owned(),
+        );
         mut_table.add_entry(id.to_bytes(), serialize_extras(&commit));
         work_ids.extend(
             commit
@@ -879,8 +938,7 @@ impl Backend for GitBackend {
         let mut tree = Tree::default();
         for entry in git_tree.iter() {
             let entry = entry.map_err(|err| to_read_object_err(err, id))?;
-            let name =
-                str::from_utf8(entry.filename()).map_err(|err| to_invalid_utf8_err(err, id))?;
+            let name: &[u8] = entry.filename();
             let (name, value) = match entry.mode().kind() {
                 gix::object::tree::EntryKind::Tree => {
                     let id = TreeId::from_bytes(entry.oid().as_bytes());
@@ -888,7 +946,7 @@ impl Backend for GitBackend {
                 }
                 gix::object::tree::EntryKind::Blob => {
                     let id = FileId::from_bytes(entry.oid().as_bytes());
-                    if let Some(basename) = name.strip_suffix(CONFLICT_SUFFIX) {
+                    if let Some(basename) = name.strip_suffix(CONFLICT_SUFFIX.as_bytes()) {
                         (
                             basename,
                             TreeValue::Conflict(ConflictId::from_bytes(entry.oid().as_bytes())),
@@ -922,7 +980,7 @@ impl Backend for GitBackend {
                     (name, TreeValue::GitSubmodule(id))
                 }
             };
-            tree.set(RepoPathComponentBuf::from(name), value);
+            tree.set(RepoPathComponentBuf::from_bytes(name), value);
         }
         Ok(tree)
     }
@@ -933,7 +991,7 @@ impl Backend for GitBackend {
         let entries = contents
             .entries()
             .map(|entry| {
-                let name = entry.name().as_str();
+                let name = entry.name().as_bytes();
                 match entry.value() {
                     TreeValue::File {
                         id,
@@ -968,7 +1026,7 @@ impl Backend for GitBackend {
                     },
                     TreeValue::Conflict(id) => gix::objs::tree::Entry {
                         mode: gix::object::tree::EntryKind::Blob.into(),
-                        filename: (name.to_owned() + CONFLICT_SUFFIX).into(),
+                        filename: [name, CONFLICT_SUFFIX.as_bytes()].concat().into(),
                         oid: id.as_bytes().try_into().unwrap(),
                     },
                 }
@@ -1156,8 +1214,9 @@ impl Backend for GitBackend {
             .edit_reference(to_no_gc_ref_update(&id))
             .map_err(|err| BackendError::Other(Box::new(err)))?;
 
-        // Update the signature to match the one that was actually written to the object
-        // store
+        // Update the signatures to match the ones that were actually written to the
+        // object store. Git timestamps have no sub-second precision.
+        contents.author.timestamp.timestamp = MillisSinceEpoch(author.time.seconds * 1000);
         contents.committer.timestamp.timestamp = MillisSinceEpoch(committer.time.seconds * 1000);
         let mut mut_table = table.start_mutation();
         mut_table.add_entry(id.to_bytes(), extras);
@@ -1166,6 +1225,19 @@ impl Backend for GitBackend {
     }
 
     #[tracing::instrument(skip(self, index))]
+    fn contains(&self, kind: ObjectKind, id: &[u8]) -> BackendResult<bool> {
+        // The root commit and the empty tree are synthesized on read.
+        if (kind == ObjectKind::Commit && id == self.root_commit_id.as_bytes())
+            || (kind == ObjectKind::Tree && id == self.empty_tree_id.as_bytes())
+        {
+            return Ok(true);
+        }
+        let Ok(oid) = gix::ObjectId::try_from(id) else {
+            return Ok(false);
+        };
+        Ok(self.lock_git_repo().has_object(oid))
+    }
+
     fn gc(&self, index: &dyn Index, keep_newer: SystemTime) -> BackendResult<()> {
         let git_repo = self.lock_git_repo();
         let new_heads = index
@@ -1180,6 +1252,134 @@ impl Backend for GitBackend {
         // TODO: pass in keep_newer to "git gc" command
         run_git_gc(self.git_repo_path()).map_err(|err| BackendError::Other(err.into()))
     }
+
+    fn keep_commits(&self, ids: &[CommitId]) -> BackendResult<()> {
+        let git_repo = self.lock_git_repo();
+        // Commits written or imported by jj usually have no-gc refs already,
+        // but the refs of hidden commits may have been removed by jj's GC
+        // since. Skip the existing refs, and create the others all at once
+        // since it's slow to write refs one by one.
+        let mut missing_ids = vec![];
+        for id in ids {
+            if *id == self.root_commit_id {
+                continue;
+            }
+            let name = format!("{NO_GC_REF_NAMESPACE}{}", id.hex());
+            let has_ref = git_repo
+                .try_find_reference(name.as_str())
+                .map_err(|err| BackendError::Other(err.into()))?
+                .is_some();
+            // A ref to a missing object would break git commands.
+            if !has_ref && git_repo.has_object(validate_git_object_id(id)?) {
+                missing_ids.push(id);
+            }
+        }
+        if missing_ids.is_empty() {
+            return Ok(());
+        }
+        tracing::debug!(count = missing_ids.len(), "recreate no-gc refs");
+        git_repo
+            .edit_references(missing_ids.into_iter().map(to_no_gc_ref_update))
+            .map_err(|err| BackendError::Other(err.into()))?;
+        Ok(())
+    }
+
+    fn find_objects_by_prefix(
+        &self,
+        prefix: &HexPrefix,
+        kind: Option<ObjectKind>,
+    ) -> BackendResult<Vec<(ObjectKind, Vec<u8>)>> {
+        let git_repo = self.open_git_repo().map_err(to_git2_other_err)?;
+        let odb = git_repo.odb().map_err(to_git2_other_err)?;
+        let prefix_hex = prefix.hex();
+        let mut oids = vec![];
+        odb.foreach(|oid| {
+            if oid.to_string().starts_with(&prefix_hex) {
+                oids.push(*oid);
+            }
+            true
+        })
+        .map_err(to_git2_other_err)?;
+        let mut found = vec![];
+        for oid in oids {
+            let (_, object_type) = odb.read_header(oid).map_err(to_git2_other_err)?;
+            let found_kind = match (kind, object_type) {
+                (Some(kind), _) if git_object_type(kind) == object_type => kind,
+                (Some(_), _) => continue,
+                (None, git2::ObjectType::Commit) => ObjectKind::Commit,
+                (None, git2::ObjectType::Tree) => ObjectKind::Tree,
+                (None, git2::ObjectType::Blob) => ObjectKind::File,
+                (None, _) => continue,
+            };
+            found.push((found_kind, oid.as_bytes().to_vec()));
+        }
+        found.sort();
+        found.dedup();
+        Ok(found)
+    }
+
+    fn read_raw_object(&self, kind: ObjectKind, id: &[u8]) -> BackendResult<Vec<u8>> {
+        let git_repo = self.open_git_repo().map_err(to_git2_other_err)?;
+        let odb = git_repo.odb().map_err(to_git2_other_err)?;
+        let oid = git2::Oid::from_bytes(id).map_err(to_git2_other_err)?;
+        let not_found =
+            |source: Box<dyn std::error::Error + Send + Sync>| BackendError::ObjectNotFound {
+                object_type: kind.to_string(),
+                hash: oid.to_string(),
+                source,
+            };
+        let object = match odb.read(oid) {
+            Ok(object) => object,
+            Err(err) if err.code() == git2::ErrorCode::NotFound => {
+                return Err(not_found(err.into()));
+            }
+            Err(err) => return Err(to_git2_other_err(err)),
+        };
+        if object.kind() != git_object_type(kind) {
+            return Err(not_found(
+                format!("The object is a {}", object.kind()).into(),
+            ));
+        }
+        Ok(object.data().to_vec())
+    }
+
+    fn object_size(&self, kind: ObjectKind, id: &[u8]) -> BackendResult<u64> {
+        let git_repo = self.open_git_repo().map_err(to_git2_other_err)?;
+        let odb = git_repo.odb().map_err(to_git2_other_err)?;
+        let oid = git2::Oid::from_bytes(id).map_err(to_git2_other_err)?;
+        let not_found =
+            |source: Box<dyn std::error::Error + Send + Sync>| BackendError::ObjectNotFound {
+                object_type: kind.to_string(),
+                hash: oid.to_string(),
+                source,
+            };
+        // Only the header is read, not the (possibly delta-compressed) data.
+        let (size, object_type) = match odb.read_header(oid) {
+            Ok(header) => header,
+            Err(err) if err.code() == git2::ErrorCode::NotFound => {
+                return Err(not_found(err.into()));
+            }
+            Err(err) => return Err(to_git2_other_err(err)),
+        };
+        if object_type != git_object_type(kind) {
+            return Err(not_found(format!("The object is a {object_type}").into()));
+        }
+        Ok(size as u64)
+    }
+}
+
+fn to_git2_other_err(err: git2::Error) -> BackendError {
+    BackendError::Other(err.into())
+}
+
+/// Returns the type of Git object that objects of the given kind are stored as.
+/// Files, symlinks, and legacy conflicts are all stored as blobs.
+fn git_object_type(kind: ObjectKind) -> git2::ObjectType {
+    match kind {
+        ObjectKind::Commit => git2::ObjectType::Commit,
+        ObjectKind::Tree => git2::ObjectType::Tree,
+        ObjectKind::File | ObjectKind::Symlink | ObjectKind::Conflict => git2::ObjectType::Blob,
+    }
 }
 
 /// Write a tree conflict as a special tree with `.jjconflict-base-N` and
@@ -1410,6 +1610,10 @@ mod tests {
             MillisSinceEpoch(2000 * 1000)
         );
         assert_eq!(commit.committer.timestamp.tz_offset, -480);
+        assert_eq!(
+            commit.metadata,
+            BTreeMap::from([("committed-via".to_owned(), "git".to_owned())])
+        );
 
         let root_tree = backend
             .read_tree(
@@ -1421,7 +1625,7 @@ mod tests {
         let mut root_entries = root_tree.entries();
         let dir = root_entries.next().unwrap();
         assert_eq!(root_entries.next(), None);
-        assert_eq!(dir.name().as_str(), "dir");
+        assert_eq!(dir.name().to_str(), Some("dir"));
         assert_eq!(
             dir.value(),
             &TreeValue::Tree(TreeId::from_bytes(dir_tree_id.as_bytes()))
@@ -1438,7 +1642,7 @@ mod tests {
         let file = entries.next().unwrap();
         let symlink = entries.next().unwrap();
         assert_eq!(entries.next(), None);
-        assert_eq!(file.name().as_str(), "normal");
+        assert_eq!(file.name().to_str(), Some("normal"));
         assert_eq!(
             file.value(),
             &TreeValue::File {
@@ -1446,7 +1650,7 @@ mod tests {
                 executable: false
             }
         );
-        assert_eq!(symlink.name().as_str(), "symlink");
+        assert_eq!(symlink.name().to_str(), Some("symlink"));
         assert_eq!(
             symlink.value(),
             &TreeValue::Symlink(SymlinkId::from_bytes(blob2.as_bytes()))
@@ -1616,6 +1820,7 @@ mod tests {
             author: create_signature(),
             committer: create_signature(),
             secure_sig: None,
+            metadata: BTreeMap::new(),
         };
 
         // No parents
@@ -1694,6 +1899,7 @@ mod tests {
             author: create_signature(),
             committer: create_signature(),
             secure_sig: None,
+            metadata: BTreeMap::new(),
         };
 
         // When writing a tree-level conflict, the root tree on the git side has the
@@ -1777,6 +1983,7 @@ mod tests {
             author: signature.clone(),
             committer: signature,
             secure_sig: None,
+            metadata: BTreeMap::new(),
         };
         let commit_id = backend.write_commit(commit, None).unwrap().0;
         let git_refs: Vec<_> = git_repo
@@ -1854,6 +2061,7 @@ mod tests {
             author: create_signature(),
             committer: create_signature(),
             secure_sig: None,
+            metadata: BTreeMap::new(),
         };
         // libgit2 doesn't seem to preserve negative timestamps, so set it to at least 1
         // second after the epoch, so the timestamp adjustment can remove 1
@@ -1881,6 +2089,97 @@ mod tests {
         assert_eq!(actual_commit2, commit2);
     }
 
+    #[test]
+    fn write_commit_subsecond_timestamps() {
+        let settings = user_settings();
+        let temp_dir = testutils::new_temp_dir();
+        let backend = GitBackend::init_internal(&settings, temp_dir.path()).unwrap();
+        let mut commit = Commit {
+            parents: vec![backend.root_commit_id().clone()],
+            predecessors: vec![],
+            root_tree: MergedTreeId::Legacy(backend.empty_tree_id().clone()),
+            change_id: ChangeId::new(vec![]),
+            description: "initial".to_string(),
+            author: create_signature(),
+            committer: create_signature(),
+            secure_sig: None,
+            metadata: BTreeMap::new(),
+        };
+        commit.author.timestamp = Timestamp {
+            timestamp: MillisSinceEpoch(1_700_000_000_123),
+            tz_offset: 60,
+        };
+        commit.committer.timestamp = Timestamp {
+            timestamp: MillisSinceEpoch(1_700_000_100_999),
+            tz_offset: -480,
+        };
+        let (commit_id, written_commit) = backend.write_commit(commit, None).unwrap();
+        // Git timestamps are truncated to seconds, and the returned commit should
+        // match the stored one
+        assert_eq!(
+            backend.read_commit(&commit_id).block_on().unwrap(),
+            written_commit
+        );
+        assert_eq!(
+            written_commit.author.timestamp,
+            Timestamp {
+                timestamp: MillisSinceEpoch(1_700_000_000_000),
+                tz_offset: 60,
+            }
+        );
+        assert_eq!(
+            written_commit.committer.timestamp,
+            Timestamp {
+                timestamp: MillisSinceEpoch(1_700_000_100_000),
+                tz_offset: -480,
+            }
+        );
+    }
+
+    #[test]
+    fn write_commit_metadata() {
+        let settings = user_settings();
+        let temp_dir = testutils::new_temp_dir();
+        let backend = GitBackend::init_internal(&settings, temp_dir.path()).unwrap();
+        let mut commit = Commit {
+            parents: vec![backend.root_commit_id().clone()],
+            predecessors: vec![],
+            root_tree: MergedTreeId::Legacy(backend.empty_tree_id().clone()),
+            change_id: ChangeId::from_hex("abc123"),
+            description: "".to_string(),
+            author: create_signature(),
+            committer: create_signature(),
+            secure_sig: None,
+            metadata: BTreeMap::new(),
+        };
+        let (plain_id, _) = backend.write_commit(commit.clone(), None).unwrap();
+        assert_eq!(
+            backend.read_commit(&plain_id).block_on().unwrap().metadata,
+            BTreeMap::new()
+        );
+
+        // Keys unknown to this version round-trip untouched. The metadata is
+        // stored outside the Git commit, so it doesn't affect the commit id
+        // unless it collides with an existing commit.
+        commit.description = "with metadata".to_string();
+        commit.metadata = BTreeMap::from([
+            ("generated-description".to_owned(), "true".to_owned()),
+            ("unknown-key".to_owned(), "value".to_owned()),
+        ]);
+        let (id, written_commit) = backend.write_commit(commit.clone(), None).unwrap();
+        assert_eq!(written_commit, commit);
+        assert_eq!(backend.read_commit(&id).block_on().unwrap(), commit);
+
+        // Extras written without the field have no metadata
+        let mut read_commit = commit.clone();
+        let extras = crate::protos::git_store::Commit {
+            change_id: commit.change_id.to_bytes(),
+            ..Default::default()
+        };
+        deserialize_extras(&mut read_commit, &extras.encode_to_vec());
+        assert_eq!(read_commit.metadata, BTreeMap::new());
+    }
+
     #[test]
     fn write_signed_commit() {
         let settings = user_settings();
@@ -1896,6 +2195,7 @@ mod tests {
             author: create_signature(),
             committer: create_signature(),
             secure_sig: None,
+            metadata: BTreeMap::new(),
         };
 
         let mut signer = |data: &_| {
@@ -0,0 +1,497 @@
+// Copyright 2024 The Jujutsu Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Support for a subset of Git's `.gitattributes` files.
+//!
+//! Only the `text`, `eol`, and `merge` attributes (and the `binary` macro,
+//! which unsets `text`, `diff`, and `merge`) affect jj's behavior. Other
+//! attributes are parsed but ignored. Patterns follow the `.gitattributes`
+//! rules: a pattern without a slash matches the file name at any depth below
+//! the directory of the `.gitattributes` file, and patterns never match the
+//! contents of a matching directory.
+
+#![allow(missing_docs)]
+
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::io::Read as _;
+use std::sync::{Arc, Mutex};
+
+use regex::Regex;
+
+use crate::backend::{BackendError, BackendResult, TreeValue};
+use crate::gitignore::push_glob_regex;
+use crate::merged_tree::MergedTree;
+use crate::object_id::ObjectId as _;
+use crate::repo_path::{RepoPath, RepoPathBuf, RepoPathComponent};
+
+/// Name of the per-directory attributes file.
+pub const GITATTRIBUTES_FILE_NAME: &str = ".gitattributes";
+
+/// State of a single attribute in a `.gitattributes` line.
+#[derive(Clone, Debug, Eq, PartialEq)]
+enum AttributeState {
+    /// `name`
+    Set,
+    /// `-name`
+    Unset,
+    /// `name=value`
+    Value(String),
+    /// `!name`
+    Unspecified,
+}
+
+#[derive(Debug)]
+struct GitAttributesLine {
+    regex: Regex,
+    attributes: Vec<(String, AttributeState)>,
+}
+
+impl GitAttributesLine {
+    fn parse(prefix: &str, input: &str) -> Option<GitAttributesLine> {
+        assert!(prefix.is_empty() || prefix.ends_with('/'));
+        let mut tokens = input.split_ascii_whitespace();
+        let pattern = tokens.next()?;
+        // Negative patterns are forbidden, and patterns matching directories
+        // never match files.
+        if pattern.starts_with('#') || pattern.starts_with('!') || pattern.ends_with('/') {
+            return None;
+        }
+
+        let mut attributes = vec![];
+        for token in tokens {
+            if token == "binary" {
+                attributes.extend(
+                    ["diff", "merge", "text"].map(|name| (name.to_owned(), AttributeState::Unset)),
+                );
+            } else if let Some(name) = token.strip_prefix('-') {
+                attributes.push((name.to_owned(), AttributeState::Unset));
+            } else if let Some(name) = token.strip_prefix('!') {
+                attributes.push((name.to_owned(), AttributeState::Unspecified));
+            } else if let Some((name, value)) = token.split_once('=') {
+                attributes.push((name.to_owned(), AttributeState::Value(value.to_owned())));
+            } else {
+                attributes.push((token.to_owned(), AttributeState::Set));
+            }
+        }
+
+        let (is_rooted, pattern) = match pattern.strip_prefix('/') {
+            None => (pattern.contains('/'), pattern),
+            Some(rest) => (true, rest),
+        };
+        let mut regex = String::new();
+        regex.push('^');
+        regex.push_str(&regex::escape(prefix));
+        if !is_rooted {
+            regex.push_str("(.*/)?");
+        }
+        push_glob_regex(&mut regex, pattern);
+        regex.push('$');
+        let regex = Regex::new(&regex).ok()?;
+
+        Some(GitAttributesLine { regex, attributes })
+    }
+
+    fn matches(&self, path: &str) -> bool {
+        self.regex.is_match(path)
+    }
+}
+
+/// Models the effective contents of multiple `.gitattributes` files. Lines in
+/// files chained later take precedence.
+#[derive(Debug)]
+pub struct GitAttributesFile {
+    parent: Option<Arc<GitAttributesFile>>,
+    lines: Vec<GitAttributesLine>,
+}
+
+impl GitAttributesFile {
+    pub fn empty() -> Arc<GitAttributesFile> {
+        Arc::new(GitAttributesFile {
+            parent: None,
+            lines: vec![],
+        })
+    }
+
+    /// Adds the lines of a `.gitattributes` file in the directory `prefix`,
+    /// which must be empty or end with a slash.
+    pub fn chain(self: &Arc<GitAttributesFile>, prefix: &str, input: &[u8]) -> Arc<Self> {
+        let mut lines = vec![];
+        for input_line in input.split(|b| *b == b'\n') {
+            // Skip non-utf8 lines
+            if let Ok(line_string) = std::str::from_utf8(input_line) {
+                if let Some(line) = GitAttributesLine::parse(prefix, line_string) {
+                    lines.push(line);
+                }
+            }
+        }
+
+        Arc::new(GitAttributesFile {
+            parent: Some(self.clone()),
+            lines,
+        })
+    }
+
+    fn all_lines_reversed<'a>(&'a self) -> Box<dyn Iterator<Item = &GitAttributesLine> + 'a> {
+        if let Some(parent) = &self.parent {
+            Box::new(self.lines.iter().rev().chain(parent.all_lines_reversed()))
+        } else {
+            Box::new(self.lines.iter().rev())
+        }
+    }
+
+    /// Returns the attributes of the file at the slash-separated `path`.
+    pub fn attributes_for(&self, path: &str) -> PathAttributes {
+        attributes_from_lines(self.all_lines_reversed(), path)
+    }
+}
+
+fn attributes_from_lines<'a>(
+    lines_reversed: impl Iterator<Item = &'a GitAttributesLine>,
+    path: &str,
+) -> PathAttributes {
+    // The last line specifying an attribute wins, so collect the first state
+    // found in the reversed lines.
+    let mut states: HashMap<&str, &AttributeState> = HashMap::new();
+    for line in lines_reversed {
+        if !line.matches(path) {
+            continue;
+        }
+        for (name, state) in line.attributes.iter().rev() {
+            states.entry(name.as_str()).or_insert(state);
+        }
+    }
+
+    let text = match states.get("text") {
+        Some(AttributeState::Set) => Some(true),
+        Some(AttributeState::Unset) => Some(false),
+        // `text=auto` leaves the decision to the content heuristics
+        Some(AttributeState::Value(_) | AttributeState::Unspecified) | None => None,
+    };
+    let eol = match states.get("eol") {
+        Some(AttributeState::Value(value)) if value == "lf" => Some(EolAttribute::Lf),
+        Some(AttributeState::Value(value)) if value == "crlf" => Some(EolAttribute::Crlf),
+        _ => None,
+    };
+    let merge_driver_name = match states.get("merge") {
+        Some(AttributeState::Set) => Some("text".to_owned()),
+        Some(AttributeState::Unset) => Some("binary".to_owned()),
+        Some(AttributeState::Value(value)) => Some(value.clone()),
+        Some(AttributeState::Unspecified) | None => None,
+    };
+    PathAttributes {
+        text,
+        eol,
+        merge_driver_name,
+    }
+}
+
+/// Line ending specified by the `eol` attribute.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum EolAttribute {
+    Lf,
+    Crlf,
+}
+
+/// The attributes of a file which jj understands. `None` means that the
+/// attribute isn't specified.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct PathAttributes {
+    /// `Some(true)` if the file is declared as text, `Some(false)` if it's
+    /// declared as binary (`-text` or `binary`).
+    pub text: Option<bool>,
+    pub eol: Option<EolAttribute>,
+    /// Name of the merge driver. `merge` and `-merge` are reported as the
+    /// built-in `text` and `binary` drivers respectively.
+    pub merge_driver_name: Option<String>,
+}
+
+impl PathAttributes {
+    /// Whether the file is declared as binary.
+    pub fn is_binary(&self) -> bool {
+        self.text == Some(false)
+    }
+
+    /// Whether the file must not be merged line by line. Conflicting changes to
+    /// such file are left as conflicts.
+    pub fn is_binary_merge(&self) -> bool {
+        match self.merge_driver_name.as_deref() {
+            Some("binary") => true,
+            Some(_) => false,
+            None => self.is_binary(),
+        }
+    }
+
+    /// Whether the line endings of the file are normalized to LF when it's
+    /// snapshotted. Like in Git, that's the case for files declared as text,
+    /// and for files with an `eol` attribute unless they're declared as
+    /// binary.
+    pub fn normalizes_line_endings(&self) -> bool {
+        self.text.unwrap_or(self.eol.is_some())
+    }
+
+    /// Whether LF line endings are converted to CRLF when the file is checked
+    /// out.
+    pub fn checks_out_crlf(&self) -> bool {
+        self.normalizes_line_endings() && self.eol == Some(EolAttribute::Crlf)
+    }
+}
+
+/// Replaces CRLF line endings by LF.
+pub fn convert_crlf_to_lf(contents: &[u8]) -> Cow<'_, [u8]> {
+    if !contents.windows(2).any(|pair| pair == b"\r\n") {
+        return Cow::Borrowed(contents);
+    }
+    let mut converted = Vec::with_capacity(contents.len());
+    let mut bytes = contents.iter().peekable();
+    while let Some(&byte) = bytes.next() {
+        if byte != b'\r' || bytes.peek() != Some(&&b'\n') {
+            converted.push(byte);
+        }
+    }
+    Cow::Owned(converted)
+}
+
+/// Replaces LF line endings by CRLF. Lines which already end with CRLF are
+/// left unchanged.
+pub fn convert_lf_to_crlf(contents: &[u8]) -> Cow<'_, [u8]> {
+    let has_bare_lf = contents
+        .iter()
+        .enumerate()
+        .any(|(i, &byte)| byte == b'\n' && (i == 0 || contents[i - 1] != b'\r'));
+    if !has_bare_lf {
+        return Cow::Borrowed(contents);
+    }
+    let mut converted = Vec::with_capacity(contents.len() + contents.len() / 16);
+    for (i, &byte) in contents.iter().enumerate() {
+        if byte == b'\n' && (i == 0 || contents[i - 1] != b'\r') {
+            converted.push(b'\r');
+        }
+        converted.push(byte);
+    }
+    Cow::Owned(converted)
+}
+
+/// Looks up the attributes of paths in a tree, taking into account the
+/// `.gitattributes` files of all parent directories and the repo-level
+/// attributes (typically `.git/info/attributes`), which take precedence over
+/// the files in the tree.
+#[derive(Debug)]
+pub struct GitAttributes {
+    /// Root tree to read the `.gitattributes` files from.
+    tree: Option<MergedTree>,
+    info_attributes: Arc<GitAttributesFile>,
+    /// Chained `.gitattributes` files by directory.
+    dir_files: Mutex<HashMap<RepoPathBuf, Arc<GitAttributesFile>>>,
+}
+
+impl GitAttributes {
+    /// Creates attributes backed by the `.gitattributes` files in `tree`. If
+    /// `tree` isn't a root tree, no files are read from it.
+    pub fn new(tree: MergedTree) -> Self {
+        GitAttributes {
+            tree: tree.dir().is_root().then_some(tree),
+            info_attributes: GitAttributesFile::empty(),
+            dir_files: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Creates attributes which don't specify anything for any path.
+    pub fn empty() -> Self {
+        GitAttributes {
+            tree: None,
+            info_attributes: GitAttributesFile::empty(),
+            dir_files: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Adds repo-level attributes, which take precedence over the
+    /// `.gitattributes` files in the tree.
+    pub fn with_info_attributes(mut self, input: &[u8]) -> Self {
+        self.info_attributes = self.info_attributes.chain("", input);
+        self
+    }
+
+    /// Returns the attributes of the file at `path`.
+    pub fn attributes_for(&self, path: &RepoPath) -> BackendResult<PathAttributes> {
+        let dir_file = match path.parent() {
+            Some(dir) => self.dir_file(dir)?,
+            None => GitAttributesFile::empty(),
+        };
+        let lines = self
+            .info_attributes
+            .all_lines_reversed()
+            .chain(dir_file.all_lines_reversed());
+        Ok(attributes_from_lines(
+            lines,
+            &path.as_internal_file_string(),
+        ))
+    }
+
+    fn dir_file(&self, dir: &RepoPath) -> BackendResult<Arc<GitAttributesFile>> {
+        if let Some(file) = self.dir_files.lock().unwrap().get(dir) {
+            return Ok(file.clone());
+        }
+        let parent_file = match dir.parent() {
+            Some(parent) => self.dir_file(parent)?,
+            None => GitAttributesFile::empty(),
+        };
+        let file = match self.read_attributes_file(dir)? {
+            Some(content) => {
+                let prefix = if dir.is_root() {
+                    String::new()
+                } else {
+                    format!("{}/", dir.as_internal_file_string())
+                };
+                parent_file.chain(&prefix, &content)
+            }
+            None => parent_file,
+        };
+        self.dir_files
+            .lock()
+            .unwrap()
+            .insert(dir.to_owned(), file.clone());
+        Ok(file)
+    }
+
+    fn read_attributes_file(&self, dir: &RepoPath) -> BackendResult<Option<Vec<u8>>> {
+        let Some(tree) = &self.tree else {
+            return Ok(None);
+        };
+        let path = dir.join(RepoPathComponent::new(GITATTRIBUTES_FILE_NAME));
+        // Conflicted attributes files are ignored
+        let Some(Some(TreeValue::File { id, .. })) = tree.path_value(&path).into_resolved().ok()
+        else {
+            return Ok(None);
+        };
+        let mut content = vec![];
+        tree.store()
+            .read_file(&path, &id)?
+            .read_to_end(&mut content)
+            .map_err(|err| BackendError::ReadObject {
+                object_type: "file".to_string(),
+                hash: id.hex(),
+                source: err.into(),
+            })?;
+        Ok(Some(content))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use assert_matches::assert_matches;
+
+    use super::*;
+
+    fn attributes(input: &[u8], path: &str) -> PathAttributes {
+        GitAttributesFile::empty()
+            .chain("", input)
+            .attributes_for(path)
+    }
+
+    #[test]
+    fn test_gitattributes_empty() {
+        assert_eq!(attributes(b"", "foo"), PathAttributes::default());
+        assert_eq!(
+            attributes(b"# comment text\n\n", "foo"),
+            PathAttributes::default()
+        );
+    }
+
+    #[test]
+    fn test_gitattributes_states() {
+        assert_eq!(attributes(b"foo text\n", "foo").text, Some(true));
+        assert_eq!(attributes(b"foo -text\n", "foo").text, Some(false));
+        assert_eq!(attributes(b"foo text=auto\n", "foo").text, None);
+        assert_eq!(attributes(b"foo text !text\n", "foo").text, None);
+        assert_eq!(
+            attributes(b"foo eol=crlf\n", "foo").eol,
+            Some(EolAttribute::Crlf)
+        );
+        assert_eq!(
+            attributes(b"foo eol=lf\n", "foo").eol,
+            Some(EolAttribute::Lf)
+        );
+        assert_eq!(attributes(b"foo eol=other\n", "foo").eol, None);
+        assert_eq!(
+            attributes(b"foo merge=union\n", "foo").merge_driver_name,
+            Some("union".to_owned())
+        );
+        assert!(!attributes(b"foo merge\n", "foo").is_binary_merge());
+        assert!(attributes(b"foo -merge\n", "foo").is_binary_merge());
+    }
+
+    #[test]
+    fn test_gitattributes_line_endings() {
+        assert!(!attributes(b"", "foo").normalizes_line_endings());
+        assert!(attributes(b"foo text\n", "foo").normalizes_line_endings());
+        assert!(!attributes(b"foo text\n", "foo").checks_out_crlf());
+        assert!(attributes(b"foo eol=lf\n", "foo").normalizes_line_endings());
+        assert!(attributes(b"foo eol=crlf\n", "foo").checks_out_crlf());
+        assert!(!attributes(b"foo eol=crlf -text\n", "foo").normalizes_line_endings());
+        assert!(!attributes(b"foo eol=crlf binary\n", "foo").checks_out_crlf());
+    }
+
+    #[test]
+    fn test_convert_line_endings() {
+        assert_eq!(convert_crlf_to_lf(b"a\r\nb\rc\n\r\n"), &b"a\nb\rc\n\n"[..]);
+        assert_matches!(convert_crlf_to_lf(b"a\nb\r"), Cow::Borrowed(_));
+        assert_eq!(
+            convert_lf_to_crlf(b"\na\r\nb\rc\n"),
+            &b"\r\na\r\nb\rc\r\n"[..]
+        );
+        assert_matches!(convert_lf_to_crlf(b"a\r\nb\r"), Cow::Borrowed(_));
+    }
+
+    #[test]
+    fn test_gitattributes_binary_macro() {
+        let attrs = attributes(b"*.png binary\n", "dir/image.png");
+        assert!(attrs.is_binary());
+        assert!(attrs.is_binary_merge());
+        // A later attribute overrides the macro
+        let attrs = attributes(b"*.png binary merge\n", "image.png");
+        assert!(attrs.is_binary());
+        assert!(!attrs.is_binary_merge());
+    }
+
+    #[test]
+    fn test_gitattributes_patterns() {
+        let input = b"*.txt text\n/rooted.bin -text\nsub/*.dat -text\ndir/ -text\n";
+        assert_eq!(attributes(input, "a.txt").text, Some(true));
+        assert_eq!(attributes(input, "x/y/a.txt").text, Some(true));
+        assert_eq!(attributes(input, "a.txt/b").text, None);
+        assert_eq!(attributes(input, "rooted.bin").text, Some(false));
+        assert_eq!(attributes(input, "x/rooted.bin").text, None);
+        assert_eq!(attributes(input, "sub/a.dat").text, Some(false));
+        assert_eq!(attributes(input, "x/sub/a.dat").text, None);
+        // Directory patterns don't apply to the files in the directory
+        assert_eq!(attributes(input, "dir/file").text, None);
+    }
+
+    #[test]
+    fn test_gitattributes_precedence() {
+        let file = GitAttributesFile::empty()
+            .chain("", b"*.txt text\n*.txt eol=lf\n")
+            .chain("sub/", b"*.txt -text\n");
+        assert_eq!(file.attributes_for("a.txt").text, Some(true));
+        assert_eq!(file.attributes_for("sub/a.txt").text, Some(false));
+        assert_eq!(file.attributes_for("sub/x/a.txt").text, Some(false));
+        // Attributes not overridden by the nested file are inherited
+        assert_eq!(file.attributes_for("sub/a.txt").eol, Some(EolAttribute::Lf));
+        // Later lines in the same file take precedence
+        let file = GitAttributesFile::empty().chain("", b"*.txt -text\na.txt text\n");
+        assert_eq!(file.attributes_for("a.txt").text, Some(true));
+        assert_eq!(file.attributes_for("b.txt").text, Some(false));
+    }
+}
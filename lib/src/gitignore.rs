@@ -81,56 +81,8 @@ impl GitIgnoreLine {
             regex.push_str("(.*/)?");
         }
 
-        let components = input.split('/').collect_vec();
-        for (i, component) in components.iter().enumerate() {
-            if *component == "**" {
-                if i == components.len() - 1 {
-                    regex.push_str(".*");
-                } else {
-                    regex.push_str("(.*/)?");
-                }
-            } else {
-                let mut in_escape = false;
-                let mut character_class: Option<String> = None;
-                for c in component.chars() {
-                    if in_escape {
-                        in_escape = false;
-                        if !matches!(c, ' ' | '#' | '!' | '?' | '\\' | '*') {
-                            regex.push_str(&regex_escape("\\"));
-                        }
-                        regex.push_str(&regex_escape(&c.to_string()));
-                    } else if c == '\\' {
-                        in_escape = true;
-                    } else if let Some(characters) = &mut character_class {
-                        if c == ']' {
-                            regex.push('[');
-                            regex.push_str(characters);
-                            regex.push(']');
-                            character_class = None;
-                        } else {
-                            characters.push(c);
-                        }
-                    } else {
-                        in_escape = false;
-                        if c == '?' {
-                            regex.push_str("[^/]");
-                        } else if c == '*' {
-                            regex.push_str("[^/]*");
-                        } else if c == '[' {
-                            character_class = Some(String::new());
-                        } else {
-                            regex.push_str(&regex_escape(&c.to_string()));
-                        }
-                    }
-                }
-                if in_escape {
-                    regex.push_str(&regex_escape("\\"));
-                }
-                if i < components.len() - 1 {
-                    regex.push('/');
-                }
-            }
-        }
+        push_glob_regex(&mut regex, input);
+
         if matches_only_directory {
             regex.push_str("/.*");
         } else {
@@ -146,6 +98,81 @@ impl GitIgnoreLine {
     }
 }
 
+/// Translates a path pattern to a regex matching slash-separated repo paths.
+/// The pattern is interpreted like in `.gitattributes` at the repo root: a
+/// pattern without a slash matches the file name in any directory. Returns
+/// `None` if the pattern isn't valid.
+pub(crate) fn glob_path_regex(pattern: &str) -> Option<Regex> {
+    let (is_rooted, pattern) = match pattern.strip_prefix('/') {
+        None => (pattern.contains('/'), pattern),
+        Some(rest) => (true, rest),
+    };
+    let mut regex = String::new();
+    regex.push('^');
+    if !is_rooted {
+        regex.push_str("(.*/)?");
+    }
+    push_glob_regex(&mut regex, pattern);
+    regex.push('$');
+    Regex::new(&regex).ok()
+}
+
+/// Appends the regex equivalent of the glob `input` to `regex`. The glob is
+/// matched against a slash-separated path, and `**` components match any
+/// number of directories.
+pub(crate) fn push_glob_regex(regex: &mut String, input: &str) {
+    let components = input.split('/').collect_vec();
+    for (i, component) in components.iter().enumerate() {
+        if *component == "**" {
+            if i == components.len() - 1 {
+                regex.push_str(".*");
+            } else {
+                regex.push_str("(.*/)?");
+            }
+        } else {
+            let mut in_escape = false;
+            let mut character_class: Option<String> = None;
+            for c in component.chars() {
+                if in_escape {
+                    in_escape = false;
+                    if !matches!(c, ' ' | '#' | '!' | '?' | '\\' | '*') {
+                        regex.push_str(&regex_escape("\\"));
+                    }
+                    regex.push_str(&regex_escape(&c.to_string()));
+                } else if c == '\\' {
+                    in_escape = true;
+                } else if let Some(characters) = &mut character_class {
+                    if c == ']' {
+                        regex.push('[');
+                        regex.push_str(characters);
+                        regex.push(']');
+                        character_class = None;
+                    } else {
+                        characters.push(c);
+                    }
+                } else {
+                    in_escape = false;
+                    if c == '?' {
+                        regex.push_str("[^/]");
+                    } else if c == '*' {
+                        regex.push_str("[^/]*");
+                    } else if c == '[' {
+                        character_class = Some(String::new());
+                    } else {
+                        regex.push_str(&regex_escape(&c.to_string()));
+                    }
+                }
+            }
+            if in_escape {
+                regex.push_str(&regex_escape("\\"));
+            }
+            if i < components.len() - 1 {
+                regex.push('/');
+            }
+        }
+    }
+}
+
 /// Models the effective contents of multiple .gitignore files.
 #[derive(Debug)]
 pub struct GitIgnoreFile {
@@ -0,0 +1,453 @@
+// Copyright 2024 The Jujutsu Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Signing backend that runs GnuPG (or a compatible program).
+
+#![allow(missing_docs)]
+
+use std::collections::HashMap;
+use std::ffi::OsString;
+use std::fmt::Debug;
+use std::io::Write;
+use std::process::{Command, ExitStatus, Stdio};
+use std::sync::Arc;
+use std::{fs, str};
+
+use itertools::Itertools as _;
+use thiserror::Error;
+
+use crate::settings::UserSettings;
+use crate::signing::{
+    SigStatus, SignError, SignInitError, SignResult, SigningBackend, Verification,
+};
+
+const PGP_SIGNATURE_PREFIX: &[u8] = b"-----BEGIN PGP SIGNATURE-----";
+
+#[derive(Debug, Error)]
+pub enum GpgError {
+    #[error("Failed to run {program:?}")]
+    Io {
+        program: OsString,
+        #[source]
+        err: std::io::Error,
+    },
+    #[error("{program:?} failed with {exit_status}:\n{stderr}")]
+    Command {
+        program: OsString,
+        exit_status: ExitStatus,
+        stderr: String,
+    },
+}
+
+impl From<GpgError> for SignError {
+    fn from(err: GpgError) -> Self {
+        SignError::Backend(Box::new(err))
+    }
+}
+
+/// Signs and verifies OpenPGP signatures by running `gpg`.
+///
+/// The program is configured by `signing.backends.gpg.program` (defaults to
+/// `gpg`), and its status output (`--status-fd`) is parsed to tell the
+/// verification results apart.
+#[derive(Debug)]
+pub struct GpgBackend {
+    program: OsString,
+    extra_args: Vec<OsString>,
+}
+
+impl GpgBackend {
+    pub fn new(program: OsString) -> Self {
+        GpgBackend {
+            program,
+            extra_args: vec![],
+        }
+    }
+
+    /// Arguments passed to every invocation, before the command-specific ones.
+    pub fn with_extra_args(mut self, args: &[OsString]) -> Self {
+        self.extra_args.extend_from_slice(args);
+        self
+    }
+
+    pub fn from_settings(settings: &UserSettings) -> Result<Self, SignInitError> {
+        let config = settings.config();
+        let program = config
+            .get_string("signing.backends.gpg.program")
+            .unwrap_or_else(|_| "gpg".to_owned());
+        let extra_args: Vec<String> = config
+            .get("signing.backends.gpg.extra-args")
+            .unwrap_or_default();
+        let extra_args = extra_args.into_iter().map(OsString::from).collect_vec();
+        Ok(GpgBackend::new(program.into()).with_extra_args(&extra_args))
+    }
+
+    fn command(&self) -> Command {
+        let mut command = Command::new(&self.program);
+        command.args(&self.extra_args);
+        command
+    }
+
+    /// Runs the command with `input` as its stdin. Returns its stdout, or an
+    /// error if it failed and `require_success` is set.
+    fn run(
+        &self,
+        mut command: Command,
+        input: &[u8],
+        require_success: bool,
+    ) -> Result<Vec<u8>, GpgError> {
+        let io_error = |err| GpgError::Io {
+            program: self.program.clone(),
+            err,
+        };
+        let mut child = command
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .map_err(io_error)?;
+        // The command may exit before reading all of its input, e.g. if the
+        // signature is malformed, so a broken pipe isn't an error by itself.
+        let write_result = child.stdin.take().unwrap().write_all(input);
+        let output = child.wait_with_output().map_err(io_error)?;
+        if require_success && !output.status.success() {
+            return Err(GpgError::Command {
+                program: self.program.clone(),
+                exit_status: output.status,
+                stderr: String::from_utf8_lossy(&output.stderr).into_owned(),
+            });
+        }
+        match write_result {
+            Err(err) if err.kind() != std::io::ErrorKind::BrokenPipe => Err(io_error(err)),
+            _ => Ok(output.stdout),
+        }
+    }
+
+    /// `gpg` verifies a single detached signature per process, but any number
+    /// of signed messages. So each signature is turned into a signed message
+    /// that's written to a temporary file, and the files are verified at once.
+    fn verify_signed_messages(
+        &self,
+        items: &[(&[u8], &[u8])],
+    ) -> Result<Vec<SignResult<Verification>>, GpgError> {
+        let io_error = |err| GpgError::Io {
+            program: self.program.clone(),
+            err,
+        };
+        let temp_dir = tempfile::Builder::new()
+            .prefix(".jj-gpg-")
+            .tempdir()
+            .map_err(io_error)?;
+        let mut results = Vec::with_capacity(items.len());
+        let mut message_paths = vec![];
+        for (i, (data, signature)) in items.iter().enumerate() {
+            let Some(message) = signed_message(data, signature) else {
+                results.push(Some(Err(SignError::InvalidSignatureFormat)));
+                continue;
+            };
+            // The file name is the index of the item, so the status output of
+            // each file can be matched to its item
+            let path = temp_dir.path().join(i.to_string());
+            fs::write(&path, message).map_err(io_error)?;
+            message_paths.push(path);
+            results.push(None);
+        }
+        if message_paths.is_empty() {
+            return Ok(results.into_iter().flatten().collect());
+        }
+
+        let mut command = self.command();
+        command
+            .args([
+                "--keyid-format=long",
+                "--status-fd=1",
+                "--batch",
+                "--verify-files",
+            ])
+            .args(&message_paths);
+        // gpg exits with an error if any signature is bad or can't be verified,
+        // which are told apart by the status output instead.
+        let output = self.run(command, b"", false)?;
+        let mut outputs = split_verify_files_output(&output);
+        Ok(results
+            .into_iter()
+            .enumerate()
+            .map(|(i, result)| {
+                result.unwrap_or_else(|| match outputs.remove(&i) {
+                    Some(output) => parse_gpg_verify_output(&output),
+                    None => Err(SignError::InvalidSignatureFormat),
+                })
+            })
+            .collect())
+    }
+}
+
+impl SigningBackend for GpgBackend {
+    fn name(&self) -> &str {
+        "gpg"
+    }
+
+    fn can_read(&self, signature: &[u8]) -> bool {
+        signature.starts_with(PGP_SIGNATURE_PREFIX)
+    }
+
+    fn sign(&self, data: &[u8], key: Option<&str>) -> SignResult<Vec<u8>> {
+        let mut command = self.command();
+        command.args(["--detach-sign", "--armor"]);
+        if let Some(key) = key {
+            command.arg("--local-user").arg(key);
+        }
+        Ok(self.run(command, data, true)?)
+    }
+
+    fn verify(&self, data: &[u8], signature: &[u8]) -> SignResult<Verification> {
+        self.verify_batch(&[(data, signature)]).pop().unwrap()
+    }
+
+    /// Verifies all the signatures in a single `gpg --verify-files` process.
+    fn verify_batch(&self, items: &[(&[u8], &[u8])]) -> Vec<SignResult<Verification>> {
+        match self.verify_signed_messages(items) {
+            Ok(results) => results,
+            Err(err) => {
+                // The error isn't about a particular signature, e.g. gpg
+                // couldn't be run, so it's reported for all of them
+                let err = Arc::new(err);
+                items
+                    .iter()
+                    .map(|_| Err(SignError::Backend(Box::new(err.clone()))))
+                    .collect()
+            }
+        }
+    }
+}
+
+/// Turns an ASCII-armored detached signature of `data` into a binary signed
+/// message: the signature packets followed by a literal data packet holding
+/// the data, as produced by old versions of PGP (RFC 4880, section 11.3).
+fn signed_message(data: &[u8], signature: &[u8]) -> Option<Vec<u8>> {
+    let mut message = dearmor(signature)?;
+    // The packet body is the data preceded by its format (binary), an empty
+    // file name, and a zero date
+    let body_len = u32::try_from(data.len() + 6).ok()?;
+    message.reserve(data.len() + 12);
+    // New format literal data packet tag, and a five-octet length
+    message.extend_from_slice(&[0xc0 | 11, 0xff]);
+    message.extend_from_slice(&body_len.to_be_bytes());
+    message.extend_from_slice(&[b'b', 0, 0, 0, 0, 0]);
+    message.extend_from_slice(data);
+    Some(message)
+}
+
+/// Decodes an ASCII-armored signature (RFC 4880, section 6.2). The armor
+/// checksum isn't checked since gpg checks the signature itself.
+fn dearmor(armored: &[u8]) -> Option<Vec<u8>> {
+    let text = str::from_utf8(armored).ok()?;
+    let mut lines = text
+        .lines()
+        .map(str::trim_end)
+        .skip_while(|line| line.is_empty());
+    if lines.next()? != str::from_utf8(PGP_SIGNATURE_PREFIX).unwrap() {
+        return None;
+    }
+    // The armor headers end with an empty line
+    lines.find(|line| line.is_empty())?;
+    let encoded: String = lines
+        .take_while(|line| !line.starts_with('=') && !line.starts_with("-----"))
+        .collect();
+    decode_base64(&encoded)
+}
+
+fn decode_base64(encoded: &str) -> Option<Vec<u8>> {
+    let mut decoded = Vec::with_capacity(encoded.len() / 4 * 3);
+    let mut buffer: u32 = 0;
+    let mut bits = 0;
+    for c in encoded.bytes().take_while(|&c| c != b'=') {
+        let value = match c {
+            b'A'..=b'Z' => c - b'A',
+            b'a'..=b'z' => c - b'a' + 26,
+            b'0'..=b'9' => c - b'0' + 52,
+            b'+' => 62,
+            b'/' => 63,
+            _ => return None,
+        };
+        buffer = (buffer << 6) | u32::from(value);
+        bits += 6;
+        if bits >= 8 {
+            bits -= 8;
+            decoded.push((buffer >> bits) as u8);
+        }
+    }
+    Some(decoded)
+}
+
+/// Splits the output of `gpg --status-fd=1 --verify-files` by file. The
+/// files are named by the index of their item.
+fn split_verify_files_output(output: &[u8]) -> HashMap<usize, Vec<u8>> {
+    let mut outputs: HashMap<usize, Vec<u8>> = HashMap::new();
+    let mut current = None;
+    for line in output.split(|&b| b == b'\n') {
+        if let Some(path) = line.strip_prefix(b"[GNUPG:] FILE_START 1 ") {
+            let file_name = path.rsplit(|&b| b == b'/' || b == b'\\').next().unwrap();
+            current = str::from_utf8(file_name)
+                .ok()
+                .and_then(|name| name.trim_end().parse().ok());
+        } else if line.starts_with(b"[GNUPG:] FILE_DONE") {
+            current = None;
+        } else if let Some(i) = current {
+            let output = outputs.entry(i).or_default();
+            output.extend_from_slice(line);
+            output.push(b'\n');
+        }
+    }
+    outputs
+}
+
+/// Parses the output of `gpg --status-fd=1 --verify`. See `doc/DETAILS` in
+/// the GnuPG sources for the format.
+pub fn parse_gpg_verify_output(output: &[u8]) -> SignResult<Verification> {
+    let mut no_public_key = None;
+    for line in output.split(|&b| b == b'\n') {
+        let Some(status) = line.strip_prefix(b"[GNUPG:] ") else {
+            continue;
+        };
+        let status = String::from_utf8_lossy(status);
+        let mut parts = status.splitn(3, ' ');
+        let keyword = parts.next().unwrap_or_default();
+        let key = parts.next().map(str::to_owned);
+        let display = parts.next().map(str::to_owned);
+        let status = match keyword {
+            "GOODSIG" => SigStatus::Good,
+            "EXPSIG" | "EXPKEYSIG" => SigStatus::Expired,
+            "BADSIG" | "REVKEYSIG" => SigStatus::Bad,
+            "NO_PUBKEY" => {
+                no_public_key = Some(key);
+                continue;
+            }
+            _ => continue,
+        };
+        return Ok(Verification {
+            status,
+            key,
+            display,
+        });
+    }
+    match no_public_key {
+        Some(key) => Ok(Verification {
+            status: SigStatus::Unknown,
+            key,
+            display: None,
+        }),
+        None => Err(SignError::InvalidSignatureFormat),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_gpg_verify_output() {
+        let output = b"[GNUPG:] NEWSIG\n\
+            [GNUPG:] KEY_CONSIDERED 0123456789ABCDEF0123456789ABCDEF01234567 0\n\
+            [GNUPG:] SIG_ID 7CrUvbrCWB6dqa04hxu+ZF2eCOU 2024-02-20 1708444800\n\
+            [GNUPG:] GOODSIG 89ABCDEF01234567 Test User <test.user@example.com>\n\
+            [GNUPG:] VALIDSIG 0123456789ABCDEF0123456789ABCDEF01234567\n";
+        assert_eq!(
+            parse_gpg_verify_output(output).unwrap(),
+            Verification {
+                status: SigStatus::Good,
+                key: Some("89ABCDEF01234567".to_owned()),
+                display: Some("Test User <test.user@example.com>".to_owned()),
+            }
+        );
+
+        let output = b"[GNUPG:] EXPKEYSIG 89ABCDEF01234567 Test User <test.user@example.com>\n";
+        assert_eq!(
+            parse_gpg_verify_output(output).unwrap().status,
+            SigStatus::Expired
+        );
+
+        let output = b"[GNUPG:] BADSIG 89ABCDEF01234567 Test User <test.user@example.com>\n";
+        assert_eq!(
+            parse_gpg_verify_output(output).unwrap().status,
+            SigStatus::Bad
+        );
+    }
+
+    #[test]
+    fn test_parse_gpg_verify_output_unknown_key() {
+        let output = b"[GNUPG:] NEWSIG\n\
+            [GNUPG:] ERRSIG 89ABCDEF01234567 1 8 00 1708444800 9 -\n\
+            [GNUPG:] NO_PUBKEY 89ABCDEF01234567\n";
+        assert_eq!(
+            parse_gpg_verify_output(output).unwrap(),
+            Verification {
+                status: SigStatus::Unknown,
+                key: Some("89ABCDEF01234567".to_owned()),
+                display: None,
+            }
+        );
+    }
+
+    #[test]
+    fn test_signed_message() {
+        let signature = b"-----BEGIN PGP SIGNATURE-----\n\
+            Comment: test\n\
+            \n\
+            c2ln\n\
+            bmF0dXJl\n\
+            =AAAA\n\
+            -----END PGP SIGNATURE-----\n";
+        assert_eq!(
+            signed_message(b"data", signature).unwrap(),
+            b"signature\xcb\xff\x00\x00\x00\x0ab\x00\x00\x00\x00\x00data"
+        );
+
+        // Invalid armor
+        assert_eq!(signed_message(b"data", b"signature"), None);
+        assert_eq!(
+            signed_message(b"data", b"-----BEGIN PGP SIGNATURE-----\n\n!!!!\n"),
+            None
+        );
+    }
+
+    #[test]
+    fn test_split_verify_files_output() {
+        let output = b"[GNUPG:] FILE_START 1 /tmp/.jj-gpg-abc/0\n\
+            [GNUPG:] GOODSIG 89ABCDEF01234567 Test User\n\
+            [GNUPG:] FILE_DONE\n\
+            [GNUPG:] FILE_START 1 /tmp/.jj-gpg-abc/2\n\
+            [GNUPG:] NO_PUBKEY 89ABCDEF01234567\n\
+            [GNUPG:] FILE_DONE\n";
+        let outputs = split_verify_files_output(output);
+        assert_eq!(outputs.len(), 2);
+        assert_eq!(
+            outputs[&0],
+            b"[GNUPG:] GOODSIG 89ABCDEF01234567 Test User\n"
+        );
+        assert_eq!(outputs[&2], b"[GNUPG:] NO_PUBKEY 89ABCDEF01234567\n");
+    }
+
+    #[test]
+    fn test_parse_gpg_verify_output_invalid() {
+        assert!(matches!(
+            parse_gpg_verify_output(b"[GNUPG:] NODATA 1\n"),
+            Err(SignError::InvalidSignatureFormat)
+        ));
+        assert!(matches!(
+            parse_gpg_verify_output(b""),
+            Err(SignError::InvalidSignatureFormat)
+        ));
+    }
+}
@@ -55,10 +55,22 @@ pub trait IndexStore: Send + Sync + Debug {
         store: &Arc<Store>,
     ) -> Result<Box<dyn ReadonlyIndex>, IndexReadError>;
 
+    /// Like `get_index_at_op()`, but for repos loaded in read-only mode. If
+    /// the index at the operation doesn't exist, it's built in memory instead
+    /// of being written to the index store.
+    fn get_index_at_op_read_only(
+        &self,
+        op: &Operation,
+        store: &Arc<Store>,
+    ) -> Result<Box<dyn ReadonlyIndex>, IndexReadError> {
+        self.get_index_at_op(op, store)
+    }
+
     fn write_index(
         &self,
         index: Box<dyn MutableIndex>,
         op_id: &OperationId,
+        store: &Arc<Store>,
     ) -> Result<Box<dyn ReadonlyIndex>, IndexWriteError>;
 }
 
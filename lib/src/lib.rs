@@ -21,7 +21,12 @@
 #[macro_use]
 pub mod content_hash;
 
+pub mod absorb;
+pub mod annotate;
 pub mod backend;
+pub mod bundle;
+pub mod cancel;
+pub mod chunker;
 pub mod commit;
 pub mod commit_builder;
 pub mod conflicts;
@@ -32,10 +37,14 @@ pub mod diff;
 pub mod file_util;
 pub mod files;
 pub mod fmt_util;
+pub mod format_version;
 pub mod fsmonitor;
 pub mod git;
 pub mod git_backend;
+pub mod gitattributes;
 pub mod gitignore;
+#[cfg(feature = "gpg")]
+pub mod gpg_signing;
 pub mod hex_util;
 pub mod id_prefix;
 pub mod index;
@@ -44,17 +53,22 @@ pub mod local_working_copy;
 pub mod lock;
 pub mod matchers;
 pub mod merge;
+pub mod merge_drivers;
 pub mod merged_tree;
 pub mod object_id;
 pub mod op_heads_store;
 pub mod op_store;
 pub mod op_walk;
 pub mod operation;
+pub mod patch;
 #[allow(missing_docs)]
 pub mod protos;
 pub mod refs;
 pub mod repo;
 pub mod repo_path;
+pub mod repo_scope;
+pub mod repo_stats;
+pub mod repo_watcher;
 pub mod revset;
 pub mod revset_graph;
 pub mod rewrite;
@@ -63,9 +77,11 @@ pub mod signing;
 pub mod simple_op_heads_store;
 pub mod simple_op_store;
 pub mod stacked_table;
+pub mod status;
 pub mod store;
 pub mod str_util;
 pub mod submodule_store;
+pub mod time_util;
 pub mod transaction;
 pub mod tree;
 pub mod tree_builder;
@@ -15,6 +15,7 @@
 #![allow(missing_docs)]
 
 use std::any::Any;
+use std::collections::HashSet;
 use std::fmt::Debug;
 use std::fs;
 use std::fs::File;
@@ -24,24 +25,41 @@ use std::time::SystemTime;
 
 use async_trait::async_trait;
 use blake2::{Blake2b512, Digest};
+use itertools::Itertools;
 use prost::Message;
 use tempfile::NamedTempFile;
 
 use crate::backend::{
     make_root_commit, Backend, BackendError, BackendResult, ChangeId, Commit, CommitId, Conflict,
-    ConflictId, ConflictTerm, FileId, MergedTreeId, MillisSinceEpoch, SecureSig, Signature,
-    SigningFn, SymlinkId, Timestamp, Tree, TreeId, TreeValue,
+    ConflictId, ConflictTerm, FileId, MergedTreeId, MillisSinceEpoch, ObjectKind, SecureSig,
+    Signature, SigningFn, SymlinkId, Timestamp, Tree, TreeId, TreeValue,
 };
+use crate::chunker::{Chunker, ChunkerOptions, ConcatReader};
 use crate::content_hash::blake2b_hash;
 use crate::file_util::persist_content_addressed_temp_file;
 use crate::index::Index;
 use crate::merge::MergeBuilder;
-use crate::object_id::ObjectId;
+use crate::object_id::{HexPrefix, ObjectId};
 use crate::repo_path::{RepoPath, RepoPathComponentBuf};
 
 const COMMIT_ID_LENGTH: usize = 64;
 const CHANGE_ID_LENGTH: usize = 16;
 
+/// Files at least this large are split into content-defined chunks, which are
+/// stored individually. Successive versions of a large file then share most
+/// of their chunks. The id of a file is the hash of its contents either way.
+const CHUNKED_FILE_THRESHOLD: usize = 1 << 20;
+
+/// The directories objects of each kind are stored in, relative to the store.
+const OBJECT_DIRS: &[(ObjectKind, &str)] = &[
+    (ObjectKind::Commit, "commits"),
+    (ObjectKind::Tree, "trees"),
+    (ObjectKind::File, "files"),
+    (ObjectKind::File, "chunked-files"),
+    (ObjectKind::Symlink, "symlinks"),
+    (ObjectKind::Conflict, "conflicts"),
+];
+
 fn map_not_found_err(err: std::io::Error, id: &impl ObjectId) -> BackendError {
     if err.kind() == std::io::ErrorKind::NotFound {
         BackendError::ObjectNotFound {
@@ -62,6 +80,21 @@ fn to_other_err(err: impl Into<Box<dyn std::error::Error + Send + Sync>>) -> Bac
     BackendError::Other(err.into())
 }
 
+/// Statistics about the files stored in chunks by a `LocalBackend`.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ChunkStats {
+    /// Number of files stored in chunks.
+    pub num_files: usize,
+    /// Total size of the contents of those files.
+    pub total_size: u64,
+    /// Number of chunks referenced by those files, counting duplicates.
+    pub num_chunk_refs: usize,
+    /// Number of distinct chunks.
+    pub num_unique_chunks: usize,
+    /// Total size of the distinct chunks (before compression).
+    pub unique_size: u64,
+}
+
 #[derive(Debug)]
 pub struct LocalBackend {
     path: PathBuf,
@@ -81,6 +114,8 @@ impl LocalBackend {
         fs::create_dir(store_path.join("files")).unwrap();
         fs::create_dir(store_path.join("symlinks")).unwrap();
         fs::create_dir(store_path.join("conflicts")).unwrap();
+        fs::create_dir(store_path.join("chunked-files")).unwrap();
+        fs::create_dir(store_path.join("chunks")).unwrap();
         let backend = Self::load(store_path);
         let empty_tree_id = backend
             .write_tree(RepoPath::root(), &Tree::default())
@@ -105,6 +140,14 @@ impl LocalBackend {
         self.path.join("files").join(id.hex())
     }
 
+    fn chunked_file_path(&self, id: &FileId) -> PathBuf {
+        self.path.join("chunked-files").join(id.hex())
+    }
+
+    fn chunk_path(&self, id: &FileId) -> PathBuf {
+        self.path.join("chunks").join(id.hex())
+    }
+
     fn symlink_path(&self, id: &SymlinkId) -> PathBuf {
         self.path.join("symlinks").join(id.hex())
     }
@@ -120,6 +163,135 @@ impl LocalBackend {
     fn conflict_path(&self, id: &ConflictId) -> PathBuf {
         self.path.join("conflicts").join(id.hex())
     }
+
+    fn read_tree_proto(&self, id: &TreeId) -> BackendResult<crate::protos::local_store::Tree> {
+        let path = self.tree_path(id);
+        let buf = fs::read(path).map_err(|err| map_not_found_err(err, id))?;
+        crate::protos::local_store::Tree::decode(&*buf).map_err(to_other_err)
+    }
+
+    /// Computes whether the tree has conflicts from the flags recorded in its
+    /// subtrees. Subtrees written before the flag was recorded are walked.
+    fn tree_has_conflict(&self, tree: &Tree) -> BackendResult<bool> {
+        for entry in tree.entries() {
+            let has_conflict = match entry.value() {
+                TreeValue::Conflict(_) => true,
+                TreeValue::Tree(id) => {
+                    let proto = self.read_tree_proto(id)?;
+                    match proto.has_conflict {
+                        Some(has_conflict) => has_conflict,
+                        None => self.tree_has_conflict(&tree_from_proto(proto))?,
+                    }
+                }
+                TreeValue::File { .. } | TreeValue::Symlink(_) | TreeValue::GitSubmodule(_) => {
+                    false
+                }
+            };
+            if has_conflict {
+                return Ok(true);
+            }
+        }
+        Ok(false)
+    }
+
+    fn write_whole_file(&self, contents: &[u8]) -> BackendResult<FileId> {
+        let temp_file = NamedTempFile::new_in(&self.path).map_err(to_other_err)?;
+        let mut encoder = zstd::Encoder::new(temp_file.as_file(), 0).map_err(to_other_err)?;
+        encoder.write_all(contents).map_err(to_other_err)?;
+        encoder.finish().map_err(to_other_err)?;
+        let id = FileId::new(Blake2b512::digest(contents).to_vec());
+
+        persist_content_addressed_temp_file(temp_file, self.file_path(&id))
+            .map_err(to_other_err)?;
+        Ok(id)
+    }
+
+    fn write_chunked_file(&self, contents: &mut dyn Read) -> BackendResult<FileId> {
+        // Repos created before chunked files were supported don't have these
+        // directories.
+        fs::create_dir_all(self.path.join("chunked-files")).map_err(to_other_err)?;
+        fs::create_dir_all(self.path.join("chunks")).map_err(to_other_err)?;
+        let mut hasher = Blake2b512::new();
+        let mut proto = crate::protos::local_store::ChunkedFile::default();
+        for chunk in Chunker::new(contents, ChunkerOptions::default()) {
+            let chunk = chunk.map_err(to_other_err)?;
+            hasher.update(&chunk);
+            let chunk_id = self.write_chunk(&chunk)?;
+            proto
+                .chunks
+                .push(crate::protos::local_store::chunked_file::Chunk {
+                    id: chunk_id.to_bytes(),
+                    size: chunk.len() as u64,
+                });
+        }
+        let id = FileId::new(hasher.finalize().to_vec());
+
+        let temp_file = NamedTempFile::new_in(&self.path).map_err(to_other_err)?;
+        temp_file
+            .as_file()
+            .write_all(&proto.encode_to_vec())
+            .map_err(to_other_err)?;
+        persist_content_addressed_temp_file(temp_file, self.chunked_file_path(&id))
+            .map_err(to_other_err)?;
+        Ok(id)
+    }
+
+    fn write_chunk(&self, chunk: &[u8]) -> BackendResult<FileId> {
+        let id = FileId::new(Blake2b512::digest(chunk).to_vec());
+        let path = self.chunk_path(&id);
+        // Chunks shared with other files are usually already stored, so avoid
+        // compressing them again.
+        if !path.exists() {
+            let temp_file = NamedTempFile::new_in(&self.path).map_err(to_other_err)?;
+            let mut encoder = zstd::Encoder::new(temp_file.as_file(), 0).map_err(to_other_err)?;
+            encoder.write_all(chunk).map_err(to_other_err)?;
+            encoder.finish().map_err(to_other_err)?;
+            persist_content_addressed_temp_file(temp_file, path).map_err(to_other_err)?;
+        }
+        Ok(id)
+    }
+
+    fn read_chunked_file(&self, id: &FileId) -> BackendResult<Box<dyn Read>> {
+        let path = self.chunked_file_path(id);
+        let buf = fs::read(path).map_err(|err| map_not_found_err(err, id))?;
+        let proto = crate::protos::local_store::ChunkedFile::decode(&*buf).map_err(to_other_err)?;
+        let chunk_paths = proto
+            .chunks
+            .into_iter()
+            .map(|chunk| self.chunk_path(&FileId::new(chunk.id)))
+            .collect_vec();
+        let readers = chunk_paths
+            .into_iter()
+            .map(|path| zstd::Decoder::new(File::open(path)?));
+        Ok(Box::new(ConcatReader::new(readers)))
+    }
+
+    /// Computes how much space is saved by sharing chunks between the files
+    /// stored in chunks.
+    pub fn chunk_stats(&self) -> BackendResult<ChunkStats> {
+        let mut stats = ChunkStats::default();
+        let entries = match fs::read_dir(self.path.join("chunked-files")) {
+            Ok(entries) => entries,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(stats),
+            Err(err) => return Err(to_other_err(err)),
+        };
+        let mut unique_chunk_ids = HashSet::new();
+        for entry in entries {
+            let buf = fs::read(entry.map_err(to_other_err)?.path()).map_err(to_other_err)?;
+            let proto =
+                crate::protos::local_store::ChunkedFile::decode(&*buf).map_err(to_other_err)?;
+            stats.num_files += 1;
+            for chunk in proto.chunks {
+                stats.total_size += chunk.size;
+                stats.num_chunk_refs += 1;
+                if unique_chunk_ids.insert(chunk.id) {
+                    stats.num_unique_chunks += 1;
+                    stats.unique_size += chunk.size;
+                }
+            }
+        }
+        Ok(stats)
+    }
 }
 
 #[async_trait]
@@ -158,30 +330,23 @@ impl Backend for LocalBackend {
 
     async fn read_file(&self, _path: &RepoPath, id: &FileId) -> BackendResult<Box<dyn Read>> {
         let path = self.file_path(id);
-        let file = File::open(path).map_err(|err| map_not_found_err(err, id))?;
-        Ok(Box::new(zstd::Decoder::new(file).map_err(to_other_err)?))
+        match File::open(path) {
+            Ok(file) => Ok(Box::new(zstd::Decoder::new(file).map_err(to_other_err)?)),
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => self.read_chunked_file(id),
+            Err(err) => Err(map_not_found_err(err, id)),
+        }
     }
 
     fn write_file(&self, _path: &RepoPath, contents: &mut dyn Read) -> BackendResult<FileId> {
-        let temp_file = NamedTempFile::new_in(&self.path).map_err(to_other_err)?;
-        let mut encoder = zstd::Encoder::new(temp_file.as_file(), 0).map_err(to_other_err)?;
-        let mut hasher = Blake2b512::new();
-        let mut buff: Vec<u8> = vec![0; 1 << 14];
-        loop {
-            let bytes_read = contents.read(&mut buff).map_err(to_other_err)?;
-            if bytes_read == 0 {
-                break;
-            }
-            let bytes = &buff[..bytes_read];
-            encoder.write_all(bytes).map_err(to_other_err)?;
-            hasher.update(bytes);
-        }
-        encoder.finish().map_err(to_other_err)?;
-        let id = FileId::new(hasher.finalize().to_vec());
-
-        persist_content_addressed_temp_file(temp_file, self.file_path(&id))
+        let mut head = vec![];
+        Read::take(&mut *contents, CHUNKED_FILE_THRESHOLD as u64)
+            .read_to_end(&mut head)
             .map_err(to_other_err)?;
-        Ok(id)
+        if head.len() < CHUNKED_FILE_THRESHOLD {
+            self.write_whole_file(&head)
+        } else {
+            self.write_chunked_file(&mut head.as_slice().chain(contents))
+        }
     }
 
     async fn read_symlink(&self, _path: &RepoPath, id: &SymlinkId) -> Result<String, BackendError> {
@@ -205,17 +370,16 @@ impl Backend for LocalBackend {
     }
 
     async fn read_tree(&self, _path: &RepoPath, id: &TreeId) -> BackendResult<Tree> {
-        let path = self.tree_path(id);
-        let buf = fs::read(path).map_err(|err| map_not_found_err(err, id))?;
-
-        let proto = crate::protos::local_store::Tree::decode(&*buf).map_err(to_other_err)?;
-        Ok(tree_from_proto(proto))
+        Ok(tree_from_proto(self.read_tree_proto(id)?))
     }
 
     fn write_tree(&self, _path: &RepoPath, tree: &Tree) -> BackendResult<TreeId> {
         let temp_file = NamedTempFile::new_in(&self.path).map_err(to_other_err)?;
 
-        let proto = tree_to_proto(tree);
+        let mut proto = tree_to_proto(tree);
+        if proto.has_conflict.is_none() {
+            proto.has_conflict = Some(self.tree_has_conflict(tree)?);
+        }
         temp_file
             .as_file()
             .write_all(&proto.encode_to_vec())
@@ -301,9 +465,94 @@ impl Backend for LocalBackend {
         Ok((id, commit))
     }
 
+    fn contains(&self, kind: ObjectKind, id: &[u8]) -> BackendResult<bool> {
+        // The root commit is synthesized on read.
+        if kind == ObjectKind::Commit && id == self.root_commit_id.as_bytes() {
+            return Ok(true);
+        }
+        let hex_id = hex::encode(id);
+        for (_, dir) in OBJECT_DIRS.iter().filter(|(k, _)| *k == kind) {
+            match fs::symlink_metadata(self.path.join(dir).join(&hex_id)) {
+                Ok(_) => return Ok(true),
+                Err(err) if err.kind() == std::io::ErrorKind::NotFound => {}
+                Err(err) => return Err(to_other_err(err)),
+            }
+        }
+        Ok(false)
+    }
+
     fn gc(&self, _index: &dyn Index, _keep_newer: SystemTime) -> BackendResult<()> {
         Ok(())
     }
+
+    fn find_objects_by_prefix(
+        &self,
+        prefix: &HexPrefix,
+        kind: Option<ObjectKind>,
+    ) -> BackendResult<Vec<(ObjectKind, Vec<u8>)>> {
+        let prefix_hex = prefix.hex();
+        let mut found = vec![];
+        let dirs = OBJECT_DIRS
+            .iter()
+            .filter(|(dir_kind, _)| kind.map_or(true, |kind| *dir_kind == kind));
+        for (dir_kind, dir) in dirs {
+            let entries = match fs::read_dir(self.path.join(dir)) {
+                Ok(entries) => entries,
+                // Stores created by older versions don't have all directories.
+                Err(err) if err.kind() == std::io::ErrorKind::NotFound => continue,
+                Err(err) => return Err(to_other_err(err)),
+            };
+            for entry in entries {
+                let entry = entry.map_err(to_other_err)?;
+                let file_name = entry.file_name();
+                let Some(name) = file_name.to_str() else {
+                    continue;
+                };
+                if !name.starts_with(&prefix_hex) {
+                    continue;
+                }
+                // Skip anything that isn't an object, such as leftover temporary files.
+                if let Ok(id) = hex::decode(name) {
+                    found.push((*dir_kind, id));
+                }
+            }
+        }
+        found.sort();
+        found.dedup();
+        Ok(found)
+    }
+
+    fn read_raw_object(&self, kind: ObjectKind, id: &[u8]) -> BackendResult<Vec<u8>> {
+        let hex_id = hex::encode(id);
+        for (_, dir) in OBJECT_DIRS.iter().filter(|(k, _)| *k == kind) {
+            match fs::read(self.path.join(dir).join(&hex_id)) {
+                Ok(data) => return Ok(data),
+                Err(err) if err.kind() == std::io::ErrorKind::NotFound => {}
+                Err(err) => return Err(to_other_err(err)),
+            }
+        }
+        Err(BackendError::ObjectNotFound {
+            object_type: kind.to_string(),
+            hash: hex_id,
+            source: format!("No {kind} object with this id in the store").into(),
+        })
+    }
+
+    fn object_size(&self, kind: ObjectKind, id: &[u8]) -> BackendResult<u64> {
+        let hex_id = hex::encode(id);
+        for (_, dir) in OBJECT_DIRS.iter().filter(|(k, _)| *k == kind) {
+            match fs::metadata(self.path.join(dir).join(&hex_id)) {
+                Ok(metadata) => return Ok(metadata.len()),
+                Err(err) if err.kind() == std::io::ErrorKind::NotFound => {}
+                Err(err) => return Err(to_other_err(err)),
+            }
+        }
+        Err(BackendError::ObjectNotFound {
+            object_type: kind.to_string(),
+            hash: hex_id,
+            source: format!("No {kind} object with this id in the store").into(),
+        })
+    }
 }
 
 pub fn commit_to_proto(commit: &Commit) -> crate::protos::local_store::Commit {
@@ -327,10 +576,11 @@ pub fn commit_to_proto(commit: &Commit) -> crate::protos::local_store::Commit {
     proto.description = commit.description.clone();
     proto.author = Some(signature_to_proto(&commit.author));
     proto.committer = Some(signature_to_proto(&commit.committer));
+    proto.metadata = commit.metadata.clone();
     proto
 }
 
-fn commit_from_proto(mut proto: crate::protos::local_store::Commit) -> Commit {
+pub(crate) fn commit_from_proto(mut proto: crate::protos::local_store::Commit) -> Commit {
     // Note how .take() sets the secure_sig field to None before we encode the data.
     // Needs to be done first since proto is partially moved a bunch below
     let secure_sig = proto.secure_sig.take().map(|sig| SecureSig {
@@ -357,26 +607,29 @@ fn commit_from_proto(mut proto: crate::protos::local_store::Commit) -> Commit {
         author: signature_from_proto(proto.author.unwrap_or_default()),
         committer: signature_from_proto(proto.committer.unwrap_or_default()),
         secure_sig,
+        metadata: proto.metadata,
     }
 }
 
-fn tree_to_proto(tree: &Tree) -> crate::protos::local_store::Tree {
+pub(crate) fn tree_to_proto(tree: &Tree) -> crate::protos::local_store::Tree {
     let mut proto = crate::protos::local_store::Tree::default();
     for entry in tree.entries() {
         proto.entries.push(crate::protos::local_store::tree::Entry {
-            name: entry.name().as_str().to_owned(),
+            name: entry.name().as_bytes().to_owned(),
             value: Some(tree_value_to_proto(entry.value())),
         });
     }
+    proto.has_conflict = tree.has_conflict_hint();
     proto
 }
 
-fn tree_from_proto(proto: crate::protos::local_store::Tree) -> Tree {
+pub(crate) fn tree_from_proto(proto: crate::protos::local_store::Tree) -> Tree {
     let mut tree = Tree::default();
     for proto_entry in proto.entries {
         let value = tree_value_from_proto(proto_entry.value.unwrap());
-        tree.set(RepoPathComponentBuf::from(proto_entry.name), value);
+        tree.set(RepoPathComponentBuf::from_bytes(proto_entry.name), value);
     }
+    tree.set_has_conflict_hint(proto.has_conflict);
     tree
 }
 
@@ -456,7 +709,7 @@ fn signature_from_proto(proto: crate::protos::local_store::commit::Signature) ->
     }
 }
 
-fn conflict_to_proto(conflict: &Conflict) -> crate::protos::local_store::Conflict {
+pub(crate) fn conflict_to_proto(conflict: &Conflict) -> crate::protos::local_store::Conflict {
     let mut proto = crate::protos::local_store::Conflict::default();
     for term in &conflict.removes {
         proto.removes.push(conflict_term_to_proto(term));
@@ -467,7 +720,7 @@ fn conflict_to_proto(conflict: &Conflict) -> crate::protos::local_store::Conflic
     proto
 }
 
-fn conflict_from_proto(proto: crate::protos::local_store::Conflict) -> Conflict {
+pub(crate) fn conflict_from_proto(proto: crate::protos::local_store::Conflict) -> Conflict {
     let mut conflict = Conflict::default();
     for term in proto.removes {
         conflict.removes.push(conflict_term_from_proto(term))
@@ -492,7 +745,10 @@ fn conflict_term_to_proto(part: &ConflictTerm) -> crate::protos::local_store::co
 
 #[cfg(test)]
 mod tests {
+    use std::collections::BTreeMap;
+
     use assert_matches::assert_matches;
+    use maplit::btreemap;
     use pollster::FutureExt;
 
     use super::*;
@@ -514,6 +770,7 @@ mod tests {
             author: create_signature(),
             committer: create_signature(),
             secure_sig: None,
+            metadata: BTreeMap::new(),
         };
 
         // No parents
@@ -548,6 +805,156 @@ mod tests {
         assert_eq!(root_merge_commit, commit);
     }
 
+    #[test]
+    fn write_commit_metadata() {
+        let temp_dir = testutils::new_temp_dir();
+        let backend = LocalBackend::init(temp_dir.path());
+        let mut commit = Commit {
+            parents: vec![backend.root_commit_id().clone()],
+            predecessors: vec![],
+            root_tree: MergedTreeId::resolved(backend.empty_tree_id().clone()),
+            change_id: ChangeId::from_hex("abc123"),
+            description: "".to_string(),
+            author: create_signature(),
+            committer: create_signature(),
+            secure_sig: None,
+            metadata: BTreeMap::new(),
+        };
+        let plain_id = backend.write_commit(commit.clone(), None).unwrap().0;
+
+        // Keys unknown to this version round-trip untouched
+        commit.metadata = btreemap! {
+            "committed-via".to_owned() => "git".to_owned(),
+            "unknown-key".to_owned() => "value".to_owned(),
+        };
+        let id = backend.write_commit(commit.clone(), None).unwrap().0;
+        assert_ne!(id, plain_id);
+        assert_eq!(backend.read_commit(&id).block_on().unwrap(), commit);
+
+        // A commit written without the field has no metadata
+        let mut proto = commit_to_proto(&commit);
+        proto.metadata.clear();
+        let read_commit = commit_from_proto(
+            crate::protos::local_store::Commit::decode(&*proto.encode_to_vec()).unwrap(),
+        );
+        assert_eq!(read_commit.metadata, BTreeMap::new());
+    }
+
+    #[test]
+    fn write_tree_records_conflicts() {
+        let temp_dir = testutils::new_temp_dir();
+        let backend = LocalBackend::init(temp_dir.path());
+        let read_tree = |id: &TreeId| backend.read_tree(RepoPath::root(), id).block_on().unwrap();
+
+        let mut conflicted = Tree::default();
+        conflicted.set(
+            RepoPathComponentBuf::from("file"),
+            TreeValue::Conflict(ConflictId::new(vec![0; 64])),
+        );
+        let conflicted_id = backend.write_tree(RepoPath::root(), &conflicted).unwrap();
+        assert_eq!(read_tree(&conflicted_id).has_conflict_hint(), Some(true));
+
+        // The flag of a parent tree is computed from its subtrees
+        let mut parent = Tree::default();
+        parent.set(
+            RepoPathComponentBuf::from("dir"),
+            TreeValue::Tree(conflicted_id.clone()),
+        );
+        let parent_id = backend.write_tree(RepoPath::root(), &parent).unwrap();
+        assert_eq!(read_tree(&parent_id).has_conflict_hint(), Some(true));
+        assert_eq!(
+            read_tree(backend.empty_tree_id()).has_conflict_hint(),
+            Some(false)
+        );
+
+        // The flag isn't part of the tree's id
+        parent.set_has_conflict_hint(Some(true));
+        assert_eq!(
+            backend.write_tree(RepoPath::root(), &parent).unwrap(),
+            parent_id
+        );
+    }
+
+    fn random_bytes(len: usize, seed: u64) -> Vec<u8> {
+        use rand::{RngCore as _, SeedableRng as _};
+        let mut rng = rand_chacha::ChaCha20Rng::seed_from_u64(seed);
+        let mut bytes = vec![0; len];
+        rng.fill_bytes(&mut bytes);
+        bytes
+    }
+
+    fn read_file(backend: &LocalBackend, id: &FileId) -> Vec<u8> {
+        let mut reader = backend.read_file(RepoPath::root(), id).block_on().unwrap();
+        let mut contents = vec![];
+        reader.read_to_end(&mut contents).unwrap();
+        contents
+    }
+
+    #[test]
+    fn write_file_round_trip() {
+        let temp_dir = testutils::new_temp_dir();
+        let backend = LocalBackend::init(temp_dir.path());
+
+        for (len, chunked) in [
+            (0, false),
+            (100, false),
+            (CHUNKED_FILE_THRESHOLD - 1, false),
+            (CHUNKED_FILE_THRESHOLD, true),
+            (3 * CHUNKED_FILE_THRESHOLD + 123, true),
+        ] {
+            let contents = random_bytes(len, len as u64);
+            let id = backend
+                .write_file(RepoPath::root(), &mut contents.as_slice())
+                .unwrap();
+            // The id doesn't depend on how the file is stored
+            assert_eq!(id.as_bytes(), Blake2b512::digest(&contents).as_slice());
+            assert_eq!(backend.chunked_file_path(&id).exists(), chunked);
+            assert_eq!(backend.file_path(&id).exists(), !chunked);
+            assert_eq!(read_file(&backend, &id), contents);
+        }
+
+        assert_matches!(
+            backend
+                .read_file(RepoPath::root(), &FileId::new(vec![0; 64]))
+                .block_on(),
+            Err(BackendError::ObjectNotFound { .. })
+        );
+    }
+
+    #[test]
+    fn write_chunked_file_shares_chunks() {
+        let temp_dir = testutils::new_temp_dir();
+        let backend = LocalBackend::init(temp_dir.path());
+
+        let contents1 = random_bytes(10 << 20, 1);
+        let mut contents2 = contents1.clone();
+        contents2[5 << 20..(5 << 20) + 1000].fill(b'x');
+        let id1 = backend
+            .write_file(RepoPath::root(), &mut contents1.as_slice())
+            .unwrap();
+        let id2 = backend
+            .write_file(RepoPath::root(), &mut contents2.as_slice())
+            .unwrap();
+        assert_ne!(id1, id2);
+        assert_eq!(read_file(&backend, &id1), contents1);
+        assert_eq!(read_file(&backend, &id2), contents2);
+
+        let stats = backend.chunk_stats().unwrap();
+        assert_eq!(stats.num_files, 2);
+        assert_eq!(stats.total_size, 20 << 20);
+        // Only the chunks around the modified region differ between the two
+        // versions
+        let chunks_per_file = stats.num_chunk_refs / 2;
+        assert!(stats.num_unique_chunks <= chunks_per_file + 3, "{stats:?}");
+        assert!(stats.unique_size < (11 << 20), "{stats:?}");
+
+        // Writing the same contents again doesn't add anything
+        backend
+            .write_file(RepoPath::root(), &mut contents2.as_slice())
+            .unwrap();
+        assert_eq!(backend.chunk_stats().unwrap(), stats);
+    }
+
     fn create_signature() -> Signature {
         Signature {
             name: "Someone".to_string(),
@@ -15,7 +15,7 @@
 #![allow(missing_docs)]
 
 use std::any::Any;
-use std::collections::HashSet;
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
 use std::error::Error;
 use std::fs::{File, Metadata, OpenOptions};
 use std::io::{Read, Write};
@@ -27,44 +27,52 @@ use std::os::unix::fs::PermissionsExt;
 use std::path::{Path, PathBuf};
 use std::sync::mpsc::{channel, Sender};
 use std::sync::Arc;
+#[cfg(unix)]
+use std::sync::Mutex;
 use std::time::UNIX_EPOCH;
-use std::{fs, iter, mem, slice};
+use std::{fs, iter, mem, slice, thread};
 
-use futures::StreamExt;
+use futures::{stream, StreamExt};
 use itertools::{EitherOrBoth, Itertools};
 use once_cell::unsync::OnceCell;
 use pollster::FutureExt;
 use prost::Message;
 use rayon::iter::IntoParallelIterator;
 use rayon::prelude::ParallelIterator;
-use tempfile::NamedTempFile;
+use tempfile::{NamedTempFile, TempDir};
 use thiserror::Error;
 use tracing::{instrument, trace_span};
 
 use crate::backend::{
-    BackendError, FileId, MergedTreeId, MillisSinceEpoch, SymlinkId, TreeId, TreeValue,
+    BackendError, BackendResult, FileId, MergedTreeId, MillisSinceEpoch, SymlinkId, TreeId,
+    TreeValue,
 };
+use crate::cancel::{CancellationToken, Cancelled};
 use crate::commit::Commit;
-use crate::conflicts::{self, materialize_tree_value, MaterializedTreeValue};
+use crate::conflicts::{self, materialize_tree_value_with_labels, MaterializedTreeValue};
 #[cfg(feature = "watchman")]
 use crate::fsmonitor::watchman;
 use crate::fsmonitor::FsmonitorKind;
+use crate::git_backend::GitBackend;
+use crate::gitattributes::{self, GitAttributes};
 use crate::gitignore::GitIgnoreFile;
-use crate::lock::FileLock;
+use crate::lock::{FileLock, LockKind};
 use crate::matchers::{
     DifferenceMatcher, EverythingMatcher, FilesMatcher, IntersectionMatcher, Matcher, PrefixMatcher,
 };
-use crate::merge::{Merge, MergeBuilder, MergedTreeValue};
+use crate::merge::{Merge, MergeBuilder, MergedTreeValue, MetadataConflict};
 use crate::merged_tree::{MergedTree, MergedTreeBuilder};
 use crate::object_id::ObjectId;
 use crate::op_store::{OperationId, WorkspaceId};
-use crate::repo_path::{RepoPath, RepoPathBuf, RepoPathComponent};
-use crate::settings::HumanByteSize;
+use crate::repo_path::{RepoPath, RepoPathBuf, RepoPathComponent, RepoPathComponentBuf};
+use crate::settings::{HumanByteSize, UserSettings};
+use crate::signing::Signer;
 use crate::store::Store;
-use crate::tree::Tree;
+use crate::tree::{Tree, TreeEntriesMode};
 use crate::working_copy::{
-    CheckoutError, CheckoutStats, LockedWorkingCopy, ResetError, SnapshotError, SnapshotOptions,
-    SnapshotProgress, WorkingCopy, WorkingCopyFactory, WorkingCopyStateError,
+    CheckoutError, CheckoutSkipReason, CheckoutStats, LockedWorkingCopy, ResetError,
+    SnapshotChangeCallback, SnapshotError, SnapshotOptions, SnapshotProgress, SnapshotSkipReason,
+    SnapshotStats, WorkingCopy, WorkingCopyFactory, WorkingCopyStateError,
 };
 
 #[cfg(unix)]
@@ -153,8 +161,8 @@ impl FileStatesMap {
     // TODO: skip sorting if the data is known to be sorted?
     fn from_proto_unsorted(mut data: Vec<crate::protos::working_copy::FileStateEntry>) -> Self {
         data.sort_unstable_by(|entry1, entry2| {
-            let path1 = RepoPath::from_internal_string(&entry1.path);
-            let path2 = RepoPath::from_internal_string(&entry2.path);
+            let path1 = RepoPath::from_internal_bytes(&entry1.path);
+            let path2 = RepoPath::from_internal_bytes(&entry2.path);
             path1.cmp(path2)
         });
         debug_assert!(is_file_state_entries_proto_unique_and_sorted(&data));
@@ -182,7 +190,7 @@ impl FileStatesMap {
             mem::take(&mut self.data),
             changed_file_states,
             |old_entry, (changed_path, _)| {
-                RepoPath::from_internal_string(&old_entry.path).cmp(changed_path)
+                RepoPath::from_internal_bytes(&old_entry.path).cmp(changed_path)
             },
         )
         .filter_map(|diff| match diff {
@@ -191,7 +199,7 @@ impl FileStatesMap {
                 Some(file_state_entry_to_proto(path, &state))
             }
             EitherOrBoth::Left(entry) => {
-                let present = !deleted_files.contains(RepoPath::from_internal_string(&entry.path));
+                let present = !deleted_files.contains(RepoPath::from_internal_bytes(&entry.path));
                 present.then_some(entry)
             }
         })
@@ -245,16 +253,16 @@ impl<'a> FileStates<'a> {
 
     fn exact_position(&self, path: &RepoPath) -> Option<usize> {
         self.data
-            .binary_search_by(|entry| RepoPath::from_internal_string(&entry.path).cmp(path))
+            .binary_search_by(|entry| RepoPath::from_internal_bytes(&entry.path).cmp(path))
             .ok()
     }
 
     fn prefixed_range(&self, base: &RepoPath) -> Range<usize> {
         let start = self
             .data
-            .partition_point(|entry| RepoPath::from_internal_string(&entry.path) < base);
+            .partition_point(|entry| RepoPath::from_internal_bytes(&entry.path) < base);
         let len = self.data[start..]
-            .partition_point(|entry| RepoPath::from_internal_string(&entry.path).starts_with(base));
+            .partition_point(|entry| RepoPath::from_internal_bytes(&entry.path).starts_with(base));
         start..(start + len)
     }
 
@@ -267,7 +275,7 @@ impl<'a> FileStates<'a> {
     pub fn paths(&self) -> impl ExactSizeIterator<Item = &'a RepoPath> {
         self.data
             .iter()
-            .map(|entry| RepoPath::from_internal_string(&entry.path))
+            .map(|entry| RepoPath::from_internal_bytes(&entry.path))
     }
 }
 
@@ -294,11 +302,30 @@ pub struct TreeState {
     // Currently only path prefixes
     sparse_patterns: Vec<RepoPathBuf>,
     own_mtime: MillisSinceEpoch,
+    /// Whether the filesystem preserves the executable bit. If it doesn't, the
+    /// executable bit is taken from the current tree when snapshotting, like
+    /// on Windows.
+    exec_bit_supported: bool,
+    /// Paths checked out with a metadata conflict (such as a conflict in the
+    /// executable bit) that the file on disk can't represent.
+    metadata_conflicts: BTreeMap<RepoPathBuf, MetadataConflict>,
 
     /// The most recent clock value returned by Watchman. Will only be set if
     /// the repo is configured to use the Watchman filesystem monitor and
     /// Watchman has been queried at least once.
     watchman_clock: Option<crate::protos::working_copy::WatchmanClock>,
+
+    /// Stats about the last snapshot. Not persisted.
+    snapshot_stats: SnapshotStats,
+
+    /// Attributes from the `.gitattributes` files of the tree being
+    /// snapshotted or checked out. They decide which files get their line
+    /// endings converted. Not persisted.
+    git_attributes: GitAttributes,
+
+    /// Labels of the terms of the conflicts in the tree being checked out,
+    /// written after the conflict markers. Not persisted.
+    conflict_labels: Option<Merge<String>>,
 }
 
 fn file_state_from_proto(proto: &crate::protos::working_copy::FileState) -> FileState {
@@ -345,7 +372,7 @@ fn file_state_to_proto(file_state: &FileState) -> crate::protos::working_copy::F
 fn file_state_entry_from_proto(
     proto: &crate::protos::working_copy::FileStateEntry,
 ) -> (&RepoPath, FileState) {
-    let path = RepoPath::from_internal_string(&proto.path);
+    let path = RepoPath::from_internal_bytes(&proto.path);
     (path, file_state_from_proto(proto.state.as_ref().unwrap()))
 }
 
@@ -354,7 +381,7 @@ fn file_state_entry_to_proto(
     state: &FileState,
 ) -> crate::protos::working_copy::FileStateEntry {
     crate::protos::working_copy::FileStateEntry {
-        path: path.into_internal_string(),
+        path: path.into_internal_bytes(),
         state: Some(file_state_to_proto(state)),
     }
 }
@@ -363,18 +390,56 @@ fn is_file_state_entries_proto_unique_and_sorted(
     data: &[crate::protos::working_copy::FileStateEntry],
 ) -> bool {
     data.iter()
-        .map(|entry| RepoPath::from_internal_string(&entry.path))
+        .map(|entry| RepoPath::from_internal_bytes(&entry.path))
         .tuple_windows()
         .all(|(path1, path2)| path1 < path2)
 }
 
+fn metadata_conflicts_from_proto(
+    proto: &[crate::protos::working_copy::MetadataConflictEntry],
+) -> BTreeMap<RepoPathBuf, MetadataConflict> {
+    use crate::protos::working_copy::MetadataConflictKind;
+    proto
+        .iter()
+        .map(|entry| {
+            let conflict = match entry.kind() {
+                MetadataConflictKind::ExecutableBit => MetadataConflict::Executable,
+                MetadataConflictKind::SymlinkVsFile => MetadataConflict::SymlinkVsFile,
+            };
+            (
+                RepoPathBuf::from_internal_bytes(entry.path.as_slice()),
+                conflict,
+            )
+        })
+        .collect()
+}
+
+fn metadata_conflicts_to_proto(
+    conflicts: &BTreeMap<RepoPathBuf, MetadataConflict>,
+) -> Vec<crate::protos::working_copy::MetadataConflictEntry> {
+    use crate::protos::working_copy::MetadataConflictKind;
+    conflicts
+        .iter()
+        .map(|(path, conflict)| {
+            let kind = match conflict {
+                MetadataConflict::Executable => MetadataConflictKind::ExecutableBit,
+                MetadataConflict::SymlinkVsFile => MetadataConflictKind::SymlinkVsFile,
+            };
+            crate::protos::working_copy::MetadataConflictEntry {
+                path: path.as_internal_bytes().to_owned(),
+                kind: kind as i32,
+            }
+        })
+        .collect()
+}
+
 fn sparse_patterns_from_proto(
     proto: Option<&crate::protos::working_copy::SparsePatterns>,
 ) -> Vec<RepoPathBuf> {
     let mut sparse_patterns = vec![];
     if let Some(proto_sparse_patterns) = proto {
         for prefix in &proto_sparse_patterns.prefixes {
-            sparse_patterns.push(RepoPathBuf::from_internal_string(prefix));
+            sparse_patterns.push(RepoPathBuf::from_internal_bytes(prefix.as_slice()));
         }
     } else {
         // For compatibility with old working copies.
@@ -384,45 +449,110 @@ fn sparse_patterns_from_proto(
     sparse_patterns
 }
 
-/// Creates intermediate directories from the `working_copy_path` to the
-/// `repo_path` parent.
+/// Directories known to be real directories (not symlinks) during a checkout.
+/// Each directory is only statted or created once, no matter how many paths in
+/// the checkout diff are below it.
+#[derive(Debug, Default)]
+struct CheckedDirs(HashSet<PathBuf>);
+
+/// Creates the directories `dirs` under the `working_copy_path`. Parents must
+/// come before their children, as returned by `collect_checkout_dirs()`.
 ///
-/// If an intermediate directory exists and if it is a symlink, this function
-/// will return an error. The `working_copy_path` directory may be a symlink.
+/// If a directory exists and if it is a symlink, it isn't followed, and it and
+/// the directories below it are skipped as `SymlinkedParent`. If it is a file,
+/// they are skipped as `UntrackedPath`. The skipped directories are returned.
+/// The `working_copy_path` directory may be a symlink.
 ///
 /// Note that this does not prevent TOCTOU bugs caused by concurrent checkouts.
 /// Another process may remove the directory created by this function and put a
 /// symlink there.
-fn create_parent_dirs(
+fn create_checkout_dirs(
+    working_copy_path: &Path,
+    dirs: &[RepoPathBuf],
+    checked_dirs: &mut CheckedDirs,
+) -> Result<HashMap<RepoPathBuf, CheckoutSkipReason>, CheckoutError> {
+    let mut skipped_dirs = HashMap::new();
+    for dir in dirs {
+        let parent_skip_reason = dir
+            .parent()
+            .and_then(|parent| skipped_dirs.get(parent).copied());
+        if let Some(reason) = parent_skip_reason {
+            skipped_dirs.insert(dir.clone(), reason);
+            continue;
+        }
+        if !cfg!(unix) && !dir.components().all(|c| c.to_str().is_some()) {
+            // The files below it are skipped for the same reason.
+            skipped_dirs.insert(dir.clone(), CheckoutSkipReason::InvalidFileName);
+            continue;
+        }
+        let dir_path = dir.to_fs_path(working_copy_path);
+        if checked_dirs.0.contains(&dir_path) {
+            continue;
+        }
+        match fs::create_dir(&dir_path) {
+            Ok(()) => {
+                checked_dirs.0.insert(dir_path);
+            }
+            Err(err) => match dir_path.symlink_metadata() {
+                Ok(metadata) if metadata.is_dir() => {
+                    checked_dirs.0.insert(dir_path);
+                }
+                Ok(metadata) if metadata.file_type().is_symlink() => {
+                    skipped_dirs.insert(dir.clone(), CheckoutSkipReason::SymlinkedParent);
+                }
+                Ok(_) => {
+                    skipped_dirs.insert(dir.clone(), CheckoutSkipReason::UntrackedPath);
+                }
+                Err(_) => {
+                    return Err(CheckoutError::Other {
+                        message: format!("Failed to create directory {}", dir_path.display()),
+                        err: err.into(),
+                    });
+                }
+            },
+        }
+    }
+    Ok(skipped_dirs)
+}
+
+/// Returns true if an existing intermediate directory from the
+/// `working_copy_path` to the `repo_path` parent is a symlink. Files must not
+/// be removed or written through such directories, which may point outside
+/// the working copy.
+fn has_symlinked_parent(
     working_copy_path: &Path,
     repo_path: &RepoPath,
-) -> Result<bool, CheckoutError> {
+    checked_dirs: &mut CheckedDirs,
+) -> bool {
     let parent_path = repo_path.parent().expect("repo path shouldn't be root");
     let mut dir_path = working_copy_path.to_owned();
     for c in parent_path.components() {
-        dir_path.push(c.as_str());
-        match fs::create_dir(&dir_path) {
-            Ok(()) => {}
-            Err(_)
-                if dir_path
-                    .symlink_metadata()
-                    .map(|m| m.is_dir())
-                    .unwrap_or(false) => {}
-            Err(err) => {
-                if dir_path.is_file() {
-                    return Ok(true);
-                }
-                return Err(CheckoutError::Other {
-                    message: format!(
-                        "Failed to create parent directories for {}",
-                        repo_path.to_fs_path(working_copy_path).display(),
-                    ),
-                    err: err.into(),
-                });
+        dir_path.push(c.to_fs_name());
+        if checked_dirs.0.contains(&dir_path) {
+            continue;
+        }
+        match dir_path.symlink_metadata() {
+            Ok(metadata) if metadata.file_type().is_symlink() => return true,
+            Ok(metadata) if metadata.is_dir() => {
+                checked_dirs.0.insert(dir_path.clone());
             }
+            Ok(_) => {}
+            Err(_) => return false,
         }
     }
-    Ok(false)
+    false
+}
+
+/// Removes the file, symlink, or conflict at `disk_path`. The type is checked
+/// with `lstat()`, so a symlink is removed itself and its target is never
+/// touched. Directories are left in place.
+fn remove_old_file(disk_path: &Path) {
+    match disk_path.symlink_metadata() {
+        Ok(metadata) if !metadata.is_dir() => {
+            fs::remove_file(disk_path).ok();
+        }
+        _ => {}
+    }
 }
 
 fn mtime_from_metadata(metadata: &Metadata) -> MillisSinceEpoch {
@@ -439,6 +569,30 @@ fn mtime_from_metadata(metadata: &Metadata) -> MillisSinceEpoch {
     )
 }
 
+/// Checks whether the filesystem at `dir` preserves the executable bit by
+/// flipping it on a temporary file. Filesystems like FAT don't.
+#[cfg(unix)]
+pub fn probe_exec_bit_supported(dir: &Path) -> bool {
+    let Ok(file) = NamedTempFile::new_in(dir) else {
+        // Assume that it does if we can't tell.
+        return true;
+    };
+    let has_mode = |mode: u32| {
+        fs::set_permissions(file.path(), fs::Permissions::from_mode(mode)).is_ok()
+            && file.path().metadata().map_or(false, |metadata| {
+                metadata.permissions().mode() & 0o111 == mode & 0o111
+            })
+    };
+    has_mode(0o755) && has_mode(0o644)
+}
+
+/// Checks whether the filesystem at `dir` preserves the executable bit. It
+/// never does on Windows.
+#[cfg(windows)]
+pub fn probe_exec_bit_supported(_dir: &Path) -> bool {
+    false
+}
+
 fn file_state(metadata: &Metadata) -> Option<FileState> {
     let metadata_file_type = metadata.file_type();
     let file_type = if metadata_file_type.is_dir() {
@@ -473,6 +627,14 @@ struct FsmonitorMatcher {
     watchman_clock: Option<crate::protos::working_copy::WatchmanClock>,
 }
 
+/// Whether the file is something other than a regular file, a symlink, or a
+/// directory, such as a FIFO or a socket. Such files can't be recorded in a
+/// tree, and opening them may block.
+fn is_special_file(metadata: &Metadata) -> bool {
+    let file_type = metadata.file_type();
+    !file_type.is_file() && !file_type.is_symlink() && !file_type.is_dir()
+}
+
 struct DirectoryToVisit<'a> {
     dir: RepoPathBuf,
     disk_dir: PathBuf,
@@ -480,6 +642,39 @@ struct DirectoryToVisit<'a> {
     file_states: FileStates<'a>,
 }
 
+/// The directories visited by a snapshot, identified by device and inode
+/// number. Symlinks are never followed, but bind mounts and the like can
+/// still make the traversal reach a directory again through one of its
+/// descendants.
+#[derive(Default)]
+struct VisitedDirs {
+    #[cfg(unix)]
+    dirs: Mutex<HashSet<(u64, u64)>>,
+}
+
+impl VisitedDirs {
+    /// Records the directory as visited. Returns false if it was visited
+    /// before.
+    #[cfg(unix)]
+    fn insert(&self, disk_dir: &Path) -> Result<bool, SnapshotError> {
+        use std::os::unix::fs::MetadataExt as _;
+
+        let metadata = disk_dir
+            .symlink_metadata()
+            .map_err(|err| SnapshotError::Other {
+                message: format!("Failed to stat directory {}", disk_dir.display()),
+                err: err.into(),
+            })?;
+        let key = (metadata.dev(), metadata.ino());
+        Ok(self.dirs.lock().unwrap().insert(key))
+    }
+
+    #[cfg(windows)]
+    fn insert(&self, _disk_dir: &Path) -> Result<bool, SnapshotError> {
+        Ok(true)
+    }
+}
+
 #[derive(Debug, Error)]
 pub enum TreeStateError {
     #[error("Reading tree state from {path}")]
@@ -523,16 +718,38 @@ impl TreeState {
         &self.sparse_patterns
     }
 
+    /// Stats about the last call to `snapshot()` on this tree state.
+    pub fn snapshot_stats(&self) -> &SnapshotStats {
+        &self.snapshot_stats
+    }
+
     fn sparse_matcher(&self) -> Box<dyn Matcher> {
         Box::new(PrefixMatcher::new(&self.sparse_patterns))
     }
 
+    pub fn exec_bit_supported(&self) -> bool {
+        self.exec_bit_supported
+    }
+
+    /// Paths checked out with a metadata conflict, which snapshots preserve
+    /// regardless of what the file on disk looks like.
+    pub fn metadata_conflicts(&self) -> &BTreeMap<RepoPathBuf, MetadataConflict> {
+        &self.metadata_conflicts
+    }
+
+    /// Overrides whether the filesystem is considered to preserve the
+    /// executable bit.
+    pub fn set_exec_bit_supported(&mut self, supported: bool) {
+        self.exec_bit_supported = supported;
+    }
+
     pub fn init(
         store: Arc<Store>,
         working_copy_path: PathBuf,
         state_path: PathBuf,
     ) -> Result<TreeState, TreeStateError> {
         let mut wc = TreeState::empty(store, working_copy_path, state_path);
+        wc.exec_bit_supported = probe_exec_bit_supported(&wc.working_copy_path);
         wc.save()?;
         Ok(wc)
     }
@@ -549,7 +766,12 @@ impl TreeState {
             file_states: FileStatesMap::new(),
             sparse_patterns: vec![RepoPathBuf::root()],
             own_mtime: MillisSinceEpoch(0),
+            exec_bit_supported: cfg!(unix),
+            metadata_conflicts: BTreeMap::new(),
             watchman_clock: None,
+            snapshot_stats: SnapshotStats::default(),
+            git_attributes: GitAttributes::empty(),
+            conflict_labels: None,
         }
     }
 
@@ -612,6 +834,8 @@ impl TreeState {
         self.file_states = FileStatesMap::from_proto_unsorted(proto.file_states);
         self.sparse_patterns = sparse_patterns_from_proto(proto.sparse_patterns.as_ref());
         self.watchman_clock = proto.watchman_clock;
+        self.exec_bit_supported = proto.exec_bit_supported.unwrap_or(cfg!(unix));
+        self.metadata_conflicts = metadata_conflicts_from_proto(&proto.metadata_conflicts);
         Ok(())
     }
 
@@ -631,10 +855,12 @@ impl TreeState {
         for path in &self.sparse_patterns {
             sparse_patterns
                 .prefixes
-                .push(path.as_internal_file_string().to_owned());
+                .push(path.as_internal_bytes().to_owned());
         }
         proto.sparse_patterns = Some(sparse_patterns);
         proto.watchman_clock = self.watchman_clock.clone();
+        proto.exec_bit_supported = Some(self.exec_bit_supported);
+        proto.metadata_conflicts = metadata_conflicts_to_proto(&self.metadata_conflicts);
 
         let mut temp_file = NamedTempFile::new_in(&self.state_path).unwrap();
         temp_file
@@ -674,7 +900,21 @@ impl TreeState {
             message: format!("Failed to open file {}", disk_path.display()),
             err: err.into(),
         })?;
-        Ok(self.store.write_file(path, &mut file)?)
+        if !self
+            .git_attributes
+            .attributes_for(path)?
+            .normalizes_line_endings()
+        {
+            return Ok(self.store.write_file(path, &mut file)?);
+        }
+        let mut contents = vec![];
+        file.read_to_end(&mut contents)
+            .map_err(|err| SnapshotError::Other {
+                message: format!("Failed to read file {}", disk_path.display()),
+                err: err.into(),
+            })?;
+        let contents = gitattributes::convert_crlf_to_lf(&contents);
+        Ok(self.store.write_file(path, &mut contents.as_ref())?)
     }
 
     fn write_symlink_to_store(
@@ -739,9 +979,13 @@ impl TreeState {
             base_ignores,
             fsmonitor_kind,
             progress,
+            on_change,
+            cancel,
             max_new_file_size,
         } = options;
 
+        self.snapshot_stats = SnapshotStats::default();
+        self.git_attributes = GitAttributes::new(self.current_tree()?);
         let sparse_matcher = self.sparse_matcher();
 
         let fsmonitor_clock_needs_save = fsmonitor_kind.is_some();
@@ -765,76 +1009,130 @@ impl TreeState {
         let (tree_entries_tx, tree_entries_rx) = channel();
         let (file_states_tx, file_states_rx) = channel();
         let (present_files_tx, present_files_rx) = channel();
+        let (skipped_paths_tx, skipped_paths_rx) = channel();
+        let visited_dirs = VisitedDirs::default();
 
-        trace_span!("traverse filesystem").in_scope(|| -> Result<(), SnapshotError> {
-            let current_tree = self.current_tree()?;
-            let directory_to_visit = DirectoryToVisit {
-                dir: RepoPathBuf::root(),
-                disk_dir: self.working_copy_path.clone(),
-                git_ignore: base_ignores,
-                file_states: self.file_states.all(),
-            };
-            self.visit_directory(
-                &matcher,
-                &current_tree,
-                tree_entries_tx,
-                file_states_tx,
-                present_files_tx,
-                directory_to_visit,
-                progress,
-                max_new_file_size,
-            )
-        })?;
-
-        let mut tree_builder = MergedTreeBuilder::new(self.tree_id.clone());
-        let mut deleted_files: HashSet<_> =
-            trace_span!("collecting existing files").in_scope(|| {
-                // Since file_states shouldn't contain files excluded by the sparse patterns,
-                // fsmonitor_matcher here is identical to the intersected matcher.
-                let file_states = self.file_states.all();
-                file_states
-                    .iter()
-                    .filter(|(path, state)| {
-                        fsmonitor_matcher.matches(path) && state.file_type != FileType::GitSubmodule
+        let deleted_files: HashSet<_> = trace_span!("collecting existing files").in_scope(|| {
+            // Since file_states shouldn't contain files excluded by the sparse patterns,
+            // fsmonitor_matcher here is identical to the intersected matcher.
+            let file_states = self.file_states.all();
+            file_states
+                .iter()
+                .filter(|(path, state)| {
+                    fsmonitor_matcher.matches(path) && state.file_type != FileType::GitSubmodule
+                })
+                .map(|(path, _state)| path.to_owned())
+                .collect()
+        });
+        // The bookkeeping of present files and file states happens on other
+        // threads while the filesystem is traversed, so it doesn't add to the
+        // latency of large snapshots. The threads finish when the traversal
+        // drops the senders.
+        let (deleted_files, changed_file_states) =
+            thread::scope(|scope| -> Result<_, SnapshotError> {
+                let present_files_handle = scope.spawn(move || {
+                    let mut deleted_files = deleted_files;
+                    trace_span!("process present files").in_scope(|| {
+                        for path in present_files_rx {
+                            deleted_files.remove(&path);
+                        }
+                    });
+                    deleted_files
+                });
+                let file_states_handle = scope.spawn(move || {
+                    trace_span!("process file states").in_scope(|| {
+                        file_states_rx
+                            .into_iter()
+                            .sorted_unstable_by(|(path1, _), (path2, _)| path1.cmp(path2))
+                            .collect_vec()
                     })
-                    .map(|(path, _state)| path.to_owned())
-                    .collect()
-            });
+                });
+                let traverse_result =
+                    trace_span!("traverse filesystem").in_scope(|| -> Result<(), SnapshotError> {
+                        let current_tree = self.current_tree()?;
+                        let directory_to_visit = DirectoryToVisit {
+                            dir: RepoPathBuf::root(),
+                            disk_dir: self.working_copy_path.clone(),
+                            git_ignore: base_ignores,
+                            file_states: self.file_states.all(),
+                        };
+                        self.visit_directory(
+                            &matcher,
+                            &current_tree,
+                            tree_entries_tx,
+                            file_states_tx,
+                            present_files_tx,
+                            skipped_paths_tx,
+                            &visited_dirs,
+                            directory_to_visit,
+                            progress,
+                            on_change,
+                            cancel,
+                            max_new_file_size,
+                        )
+                    });
+                let deleted_files = present_files_handle.join().unwrap();
+                let changed_file_states = file_states_handle.join().unwrap();
+                traverse_result?;
+                Ok((deleted_files, changed_file_states))
+            })?;
+        self.snapshot_stats.skipped_paths = skipped_paths_rx
+            .try_iter()
+            .sorted_unstable_by(|(path1, _), (path2, _)| path1.cmp(path2))
+            .collect();
+
+        let mut tree_overrides = BTreeMap::new();
         trace_span!("process tree entries").in_scope(|| -> Result<(), SnapshotError> {
             while let Ok((path, tree_values)) = tree_entries_rx.recv() {
-                tree_builder.set_or_remove(path, tree_values);
+                if path.has_reserved_component() && tree_values.is_present() {
+                    return Err(SnapshotError::ReservedPath {
+                        path: path.to_fs_path(&self.working_copy_path),
+                    });
+                }
+                tree_overrides.insert(path, tree_values);
             }
             Ok(())
         })?;
-        trace_span!("process present files").in_scope(|| {
-            while let Ok(path) = present_files_rx.recv() {
-                deleted_files.remove(&path);
-            }
-        });
         trace_span!("process deleted tree entries").in_scope(|| {
             is_dirty |= !deleted_files.is_empty();
             for file in &deleted_files {
-                tree_builder.set_or_remove(file.clone(), Merge::absent());
+                let value = Merge::absent();
+                if let Some(on_change) = on_change {
+                    on_change(file, &value);
+                }
+                tree_overrides.insert(file.clone(), value);
             }
         });
-        trace_span!("process file states").in_scope(|| {
-            let changed_file_states = file_states_rx
-                .iter()
-                .sorted_unstable_by(|(path1, _), (path2, _)| path1.cmp(path2))
-                .collect_vec();
+        trace_span!("process path conflicts")
+            .in_scope(|| self.resolve_path_conflicts(&mut tree_overrides))?;
+        trace_span!("merge file states").in_scope(|| {
             is_dirty |= !changed_file_states.is_empty();
             self.file_states
                 .merge_in(changed_file_states, &deleted_files);
+            for file in &deleted_files {
+                is_dirty |= self.metadata_conflicts.remove(file).is_some();
+            }
         });
-        trace_span!("write tree").in_scope(|| {
-            let new_tree_id = tree_builder.write_tree(&self.store).unwrap();
+        trace_span!("write tree").in_scope(|| -> Result<(), SnapshotError> {
+            // Only the changed paths are overridden, so only the directories
+            // containing them are written. The ids of the other subtrees are
+            // reused from the current tree.
+            let mut tree_builder = MergedTreeBuilder::new(self.tree_id.clone());
+            for (path, tree_values) in tree_overrides {
+                tree_builder.set_or_remove(path, tree_values);
+            }
+            let new_tree_id = tree_builder.write_tree(&self.store)?;
             is_dirty |= new_tree_id != self.tree_id;
             self.tree_id = new_tree_id;
-        });
+            Ok(())
+        })?;
         if cfg!(debug_assertions) {
             let tree = self.current_tree().unwrap();
             let tree_paths: HashSet<_> = tree
                 .entries_matching(sparse_matcher.as_ref())
+                .flat_map(|(path, value)| {
+                    expand_path_conflict(&self.store, path, value, sparse_matcher.as_ref()).unwrap()
+                })
                 .map(|(path, _)| path)
                 .collect();
             let file_states = self.file_states.all();
@@ -845,6 +1143,73 @@ impl TreeState {
         Ok(is_dirty)
     }
 
+    /// Translates the changes to the paths that conflicts between files and
+    /// directories are checked out as (see `expand_path_conflict()`) into
+    /// changes to the conflicted paths. Untouched conflicts are kept. If the
+    /// file was moved into place of the directory, the conflict is resolved
+    /// in favor of the file. Otherwise, the directory side is kept, including
+    /// any changes made to it, and the file side becomes a regular file if it
+    /// wasn't deleted.
+    fn resolve_path_conflicts(
+        &self,
+        tree_overrides: &mut BTreeMap<RepoPathBuf, MergedTreeValue>,
+    ) -> Result<(), SnapshotError> {
+        let tree = self.current_tree()?;
+        if tree_overrides.is_empty() || !tree.has_conflict() {
+            return Ok(());
+        }
+        let path_conflicts = tree
+            .conflicts()
+            .filter(|(_, value)| value.is_path_conflict())
+            .collect_vec();
+        for (path, value) in path_conflicts {
+            let file_path = conflicts::path_conflict_file_path(&path);
+            let changed_paths = tree_overrides
+                .range(path.clone()..)
+                .map(|(changed_path, _)| changed_path)
+                .take_while(|changed_path| changed_path.starts_with(&path))
+                .cloned()
+                .collect_vec();
+            let file_override = tree_overrides.remove(&file_path);
+            if file_override.is_none() && changed_paths.is_empty() {
+                continue;
+            }
+            let (dir_value, file_value) = value.split_path_conflict();
+            if let Some(path_override) = tree_overrides.remove(&path) {
+                // The directory was replaced by a file or symlink.
+                for changed_path in &changed_paths {
+                    tree_overrides.remove(changed_path);
+                }
+                tree_overrides.insert(path.clone(), path_override);
+            } else {
+                let trees = dir_value.to_tree_merge(&self.store, &path)?.unwrap();
+                for (sub_path, sub_value) in MergedTree::Merge(trees).entries() {
+                    tree_overrides.entry(sub_path).or_insert(sub_value);
+                }
+                let has_dir_entries = tree_overrides
+                    .range(path.clone()..)
+                    .take_while(|(sub_path, _)| sub_path.starts_with(&path))
+                    .any(|(_, sub_value)| sub_value.is_present());
+                if !has_dir_entries {
+                    // The file would be kept on the sides where it was present
+                    // if we only removed the directory's entries.
+                    let sub_paths = tree_overrides
+                        .range(path.clone()..)
+                        .map(|(sub_path, _)| sub_path)
+                        .take_while(|sub_path| sub_path.starts_with(&path))
+                        .cloned()
+                        .collect_vec();
+                    for sub_path in sub_paths {
+                        tree_overrides.remove(&sub_path);
+                    }
+                    tree_overrides.insert(path.clone(), Merge::absent());
+                }
+            }
+            tree_overrides.insert(file_path, file_override.unwrap_or(file_value));
+        }
+        Ok(())
+    }
+
     #[allow(clippy::too_many_arguments)]
     fn visit_directory(
         &self,
@@ -853,8 +1218,12 @@ impl TreeState {
         tree_entries_tx: Sender<(RepoPathBuf, MergedTreeValue)>,
         file_states_tx: Sender<(RepoPathBuf, FileState)>,
         present_files_tx: Sender<RepoPathBuf>,
+        skipped_paths_tx: Sender<(RepoPathBuf, SnapshotSkipReason)>,
+        visited_dirs: &VisitedDirs,
         directory_to_visit: DirectoryToVisit,
         progress: Option<&SnapshotProgress>,
+        on_change: Option<&SnapshotChangeCallback>,
+        cancel: Option<&CancellationToken>,
         max_new_file_size: u64,
     ) -> Result<(), SnapshotError> {
         let DirectoryToVisit {
@@ -867,6 +1236,12 @@ impl TreeState {
         if matcher.visit(&dir).is_nothing() {
             return Ok(());
         }
+        if let Some(cancel) = cancel {
+            cancel.checkpoint()?;
+        }
+        if !visited_dirs.insert(&disk_dir)? {
+            return Ok(());
+        }
 
         let git_ignore =
             git_ignore.chain_with_file(&dir.to_internal_dir_string(), disk_dir.join(".gitignore"));
@@ -880,22 +1255,25 @@ impl TreeState {
                 tree_entries_tx.clone(),
                 file_states_tx.clone(),
                 present_files_tx.clone(),
+                skipped_paths_tx.clone(),
             ),
-            |(tree_entries_tx, file_states_tx, present_files_tx),
+            |(tree_entries_tx, file_states_tx, present_files_tx, skipped_paths_tx),
              entry|
              -> Result<(), SnapshotError> {
+                // This doesn't follow symlinks, so symlinks to directories
+                // are recorded as symlinks instead of being traversed.
                 let file_type = entry.file_type().unwrap();
                 let file_name = entry.file_name();
-                let name = file_name
-                    .to_str()
-                    .ok_or_else(|| SnapshotError::InvalidUtf8Path {
+                let name = RepoPathComponentBuf::from_fs_name(&file_name).ok_or_else(|| {
+                    SnapshotError::InvalidUtf8Path {
                         path: file_name.clone(),
-                    })?;
+                    }
+                })?;
 
-                if name == ".jj" || name == ".git" {
+                if name.is_reserved() {
                     return Ok(());
                 }
-                let path = dir.join(RepoPathComponent::new(name));
+                let path = dir.join(&name);
                 let maybe_current_file_state = file_states.get(&path);
                 if let Some(file_state) = &maybe_current_file_state {
                     if file_state.file_type == FileType::GitSubmodule {
@@ -928,7 +1306,18 @@ impl TreeState {
                                     });
                                 }
                             };
-                            if let Some(new_file_state) = file_state(&metadata) {
+                            if is_special_file(&metadata) {
+                                skipped_paths_tx
+                                    .send((
+                                        tracked_path.to_owned(),
+                                        SnapshotSkipReason::SpecialFile,
+                                    ))
+                                    .ok();
+                                continue;
+                            }
+                            if let Some(new_file_state) =
+                                self.file_state(&metadata, Some(&current_file_state))
+                            {
                                 present_files_tx.send(tracked_path.to_owned()).ok();
                                 let update = self.get_updated_tree_value(
                                     tracked_path,
@@ -938,6 +1327,9 @@ impl TreeState {
                                     &new_file_state,
                                 )?;
                                 if let Some(tree_value) = update {
+                                    if let Some(on_change) = on_change {
+                                        on_change(tracked_path, &tree_value);
+                                    }
                                     tree_entries_tx
                                         .send((tracked_path.to_owned(), tree_value))
                                         .ok();
@@ -962,26 +1354,43 @@ impl TreeState {
                             tree_entries_tx.clone(),
                             file_states_tx.clone(),
                             present_files_tx.clone(),
+                            skipped_paths_tx.clone(),
+                            visited_dirs,
                             directory_to_visit,
                             progress,
+                            on_change,
+                            cancel,
                             max_new_file_size,
                         )?;
                     }
                 } else if matcher.matches(&path) {
+                    if let Some(cancel) = cancel {
+                        cancel.checkpoint()?;
+                    }
                     if let Some(progress) = progress {
                         progress(&path);
                     }
                     if maybe_current_file_state.is_none()
-                        && git_ignore.matches(path.as_internal_file_string())
+                        && git_ignore.matches(&path.as_internal_file_string())
                     {
                         // If it wasn't already tracked and it matches
                         // the ignored paths, then
                         // ignore it.
                     } else {
-                        let metadata = entry.metadata().map_err(|err| SnapshotError::Other {
-                            message: format!("Failed to stat file {}", entry.path().display()),
-                            err: err.into(),
+                        // Don't follow symlinks. They're recorded as symlinks.
+                        let metadata = entry.path().symlink_metadata().map_err(|err| {
+                            SnapshotError::Other {
+                                message: format!("Failed to stat file {}", entry.path().display()),
+                                err: err.into(),
+                            }
                         })?;
+                        if is_special_file(&metadata) {
+                            // Never open it, since opening e.g. a FIFO blocks.
+                            skipped_paths_tx
+                                .send((path, SnapshotSkipReason::SpecialFile))
+                                .ok();
+                            return Ok(());
+                        }
                         if maybe_current_file_state.is_none() && metadata.len() > max_new_file_size
                         {
                             return Err(SnapshotError::NewFileTooLarge {
@@ -990,7 +1399,9 @@ impl TreeState {
                                 max_size: HumanByteSize(max_new_file_size),
                             });
                         }
-                        if let Some(new_file_state) = file_state(&metadata) {
+                        if let Some(new_file_state) =
+                            self.file_state(&metadata, maybe_current_file_state.as_ref())
+                        {
                             present_files_tx.send(path.clone()).ok();
                             let update = self.get_updated_tree_value(
                                 &path,
@@ -1000,6 +1411,9 @@ impl TreeState {
                                 &new_file_state,
                             )?;
                             if let Some(tree_value) = update {
+                                if let Some(on_change) = on_change {
+                                    on_change(&path, &tree_value);
+                                }
                                 tree_entries_tx.send((path.clone(), tree_value)).ok();
                             }
                             if Some(&new_file_state) != maybe_current_file_state.as_ref() {
@@ -1059,6 +1473,31 @@ impl TreeState {
         })
     }
 
+    /// Returns the state of the file on disk. If the filesystem doesn't
+    /// preserve the executable bit, the bit is taken from the current state.
+    #[cfg_attr(windows, allow(unused_variables))]
+    fn file_state(
+        &self,
+        metadata: &Metadata,
+        current_file_state: Option<&FileState>,
+    ) -> Option<FileState> {
+        #[cfg_attr(windows, allow(unused_mut))]
+        let mut new_file_state = file_state(metadata)?;
+        #[cfg(unix)]
+        if !self.exec_bit_supported {
+            if let FileType::Normal { executable } = &mut new_file_state.file_type {
+                *executable = matches!(
+                    current_file_state,
+                    Some(FileState {
+                        file_type: FileType::Normal { executable: true },
+                        ..
+                    })
+                );
+            }
+        }
+        Some(new_file_state)
+    }
+
     fn get_updated_tree_value(
         &self,
         repo_path: &RepoPath,
@@ -1081,7 +1520,19 @@ impl TreeState {
         if clean {
             Ok(None)
         } else {
-            let current_tree_values = current_tree.path_value(repo_path);
+            let mut current_tree_values = current_tree.path_value(repo_path);
+            if current_tree_values.is_path_conflict() {
+                // The conflicted path itself is checked out as a directory, so a
+                // file there replaces the conflict.
+                current_tree_values = Merge::absent();
+            } else if let Some(conflict_value) =
+                path_conflict_for_file_path(current_tree, repo_path)
+            {
+                // Compare the file next to a path conflict with the file side
+                // of the conflict.
+                let (_, file_value) = conflict_value.split_path_conflict();
+                current_tree_values = file_value.simplify();
+            }
             let new_file_type = if cfg!(windows) {
                 let mut new_file_type = new_file_state.file_type.clone();
                 if matches!(new_file_type, FileType::Normal { .. })
@@ -1106,6 +1557,12 @@ impl TreeState {
                 }
                 FileType::GitSubmodule => panic!("git submodule cannot be written to store"),
             };
+            let new_tree_values = match self.metadata_conflicts.get(repo_path) {
+                Some(&conflict) if current_tree_values.metadata_conflict() == Some(conflict) => {
+                    preserve_metadata_conflict(conflict, &current_tree_values, new_tree_values)
+                }
+                _ => new_tree_values,
+            };
             if new_tree_values != current_tree_values {
                 Ok(Some(new_tree_values))
             } else {
@@ -1124,9 +1581,21 @@ impl TreeState {
         // If the file contained a conflict before and is now a normal file on disk, we
         // try to parse any conflict markers in the file into a conflict.
         if let Some(current_tree_value) = current_tree_values.as_resolved() {
-            #[cfg(unix)]
-            let _ = current_tree_value; // use the variable
             let id = self.write_file_to_store(repo_path, disk_path)?;
+            // If the filesystem doesn't preserve the executable bit, we preserve
+            // it from the current tree.
+            #[cfg(unix)]
+            let executable = if self.exec_bit_supported {
+                executable
+            } else {
+                matches!(
+                    current_tree_value,
+                    Some(TreeValue::File {
+                        executable: true,
+                        ..
+                    })
+                )
+            };
             // On Windows, we preserve the executable bit from the current tree.
             #[cfg(windows)]
             let executable = {
@@ -1258,7 +1727,7 @@ impl TreeState {
     #[cfg_attr(windows, allow(unused_variables))]
     fn set_executable(&self, disk_path: &Path, executable: bool) -> Result<(), CheckoutError> {
         #[cfg(unix)]
-        {
+        if self.exec_bit_supported {
             let mode = if executable { 0o755 } else { 0o644 };
             fs::set_permissions(disk_path, fs::Permissions::from_mode(mode))
                 .map_err(|err| checkout_error_for_stat_error(err, disk_path))?;
@@ -1266,7 +1735,26 @@ impl TreeState {
         Ok(())
     }
 
+    /// Sets the labels written after the conflict markers of the files
+    /// checked out from now on. See `Commit::conflict_labels()`.
+    pub fn set_conflict_labels(&mut self, labels: Option<Merge<String>>) {
+        self.conflict_labels = labels;
+    }
+
     pub fn check_out(&mut self, new_tree: &MergedTree) -> Result<CheckoutStats, CheckoutError> {
+        self.check_out_cancellable(new_tree, None)
+    }
+
+    /// Like `check_out()`, but stops updating files once `cancel` is
+    /// cancelled. The files written so far are recorded with placeholder
+    /// states, and the files removed so far are still recorded as present, so
+    /// the next snapshot records whatever ended up on disk as changes to the
+    /// old tree.
+    pub fn check_out_cancellable(
+        &mut self,
+        new_tree: &MergedTree,
+        cancel: Option<&CancellationToken>,
+    ) -> Result<CheckoutStats, CheckoutError> {
         let old_tree = self.current_tree().map_err(|err| match err {
             err @ BackendError::ObjectNotFound { .. } => CheckoutError::SourceNotFound {
                 source: Box::new(err),
@@ -1274,7 +1762,7 @@ impl TreeState {
             other => CheckoutError::InternalBackendError(other),
         })?;
         let stats = self
-            .update(&old_tree, new_tree, self.sparse_matcher().as_ref())
+            .update(&old_tree, new_tree, self.sparse_matcher().as_ref(), cancel)
             .block_on()?;
         self.tree_id = new_tree.id();
         Ok(stats)
@@ -1295,9 +1783,11 @@ impl TreeState {
         let added_matcher = DifferenceMatcher::new(&new_matcher, &old_matcher);
         let removed_matcher = DifferenceMatcher::new(&old_matcher, &new_matcher);
         let empty_tree = MergedTree::resolved(Tree::null(self.store.clone(), RepoPathBuf::root()));
-        let added_stats = self.update(&empty_tree, &tree, &added_matcher).block_on()?;
+        let added_stats = self
+            .update(&empty_tree, &tree, &added_matcher, None)
+            .block_on()?;
         let removed_stats = self
-            .update(&tree, &empty_tree, &removed_matcher)
+            .update(&tree, &empty_tree, &removed_matcher, None)
             .block_on()?;
         self.sparse_patterns = sparse_patterns;
         assert_eq!(added_stats.updated_files, 0);
@@ -1310,6 +1800,7 @@ impl TreeState {
             added_files: added_stats.added_files,
             removed_files: removed_stats.removed_files,
             skipped_files: added_stats.skipped_files,
+            skipped_paths: added_stats.skipped_paths,
         })
     }
 
@@ -1318,6 +1809,33 @@ impl TreeState {
         old_tree: &MergedTree,
         new_tree: &MergedTree,
         matcher: &dyn Matcher,
+        cancel: Option<&CancellationToken>,
+    ) -> Result<CheckoutStats, CheckoutError> {
+        let diffs = collect_checkout_diff(&self.store, old_tree, new_tree, matcher).await?;
+        // Writing such a path could overwrite the repo itself, so the whole
+        // checkout is refused before any file is touched.
+        if let Some((path, _, _)) = diffs
+            .iter()
+            .find(|(path, _, after)| after.is_present() && path.has_reserved_component())
+        {
+            return Err(CheckoutError::ReservedPath { path: path.clone() });
+        }
+        let dirs = collect_checkout_dirs(new_tree, &diffs)?;
+        self.git_attributes = GitAttributes::new(new_tree.clone());
+        self.apply_checkout_diff(diffs, dirs, cancel).await
+    }
+
+    /// Updates the files listed in `diffs` and their file states. Paths not
+    /// in `diffs` aren't statted, written, or re-recorded, so changes made to
+    /// them on disk are left for the next snapshot to pick up. The directories
+    /// `dirs` are created once the removals, which come first in `diffs`, are
+    /// done. Each parent directory of the listed paths is checked at most
+    /// once.
+    async fn apply_checkout_diff(
+        &mut self,
+        diffs: Vec<(RepoPathBuf, MergedTreeValue, MergedTreeValue)>,
+        dirs: Vec<RepoPathBuf>,
+        cancel: Option<&CancellationToken>,
     ) -> Result<CheckoutStats, CheckoutError> {
         // TODO: maybe it's better not include the skipped counts in the "intended"
         // counts
@@ -1326,25 +1844,71 @@ impl TreeState {
             added_files: 0,
             removed_files: 0,
             skipped_files: 0,
+            skipped_paths: vec![],
         };
         let mut changed_file_states = Vec::new();
         let mut deleted_files = HashSet::new();
+        let mut checked_dirs = CheckedDirs::default();
+        let mut dirs_to_create = Some(dirs);
+        let mut skipped_dirs = HashMap::new();
         let mut diff_stream = Box::pin(
-            old_tree
-                .diff_stream(new_tree, matcher)
-                .map(|(path, diff)| async {
-                    match diff {
-                        Ok((before, after)) => {
-                            let result = materialize_tree_value(&self.store, &path, after).await;
-                            (path, result.map(|value| (before.is_present(), value)))
-                        }
-                        Err(err) => (path, Err(err)),
-                    }
+            stream::iter(diffs)
+                .map(|(path, before, after)| async {
+                    let metadata_conflict = after.metadata_conflict();
+                    // The file can't be both a symlink and a regular file, so
+                    // the regular file side is checked out.
+                    let file_side = match metadata_conflict {
+                        Some(MetadataConflict::SymlinkVsFile) => after
+                            .adds()
+                            .flatten()
+                            .find(|value| matches!(value, TreeValue::File { .. }))
+                            .cloned(),
+                        _ => None,
+                    };
+                    let after = file_side.map_or(after, Merge::normal);
+                    let result = materialize_tree_value_with_labels(
+                        &self.store,
+                        &path,
+                        after,
+                        self.conflict_labels.as_ref(),
+                    )
+                    .await;
+                    let data = result.map(|value| (before.is_present(), metadata_conflict, value));
+                    (path, data)
                 })
                 .buffered(self.store.concurrency().max(1)),
         );
         while let Some((path, data)) = diff_stream.next().await {
-            let (present_before, after) = data?;
+            if cancel.is_some_and(|cancel| cancel.is_cancelled()) {
+                // Force the next snapshot to read back the files we've written.
+                // Deleted files are kept so the snapshot notices they're gone.
+                let changed_file_states = changed_file_states
+                    .into_iter()
+                    .map(|(path, _)| (path, FileState::placeholder()))
+                    .collect();
+                self.file_states
+                    .merge_in(changed_file_states, &HashSet::new());
+                return Err(Cancelled.into());
+            }
+            let (present_before, metadata_conflict, after) = data?;
+            self.metadata_conflicts.remove(&path);
+            if after.is_present() {
+                if let Some(dirs) = dirs_to_create.take() {
+                    skipped_dirs =
+                        create_checkout_dirs(&self.working_copy_path, &dirs, &mut checked_dirs)?;
+                }
+            }
+            if after.is_present() && !cfg!(unix) && !path.components().all(|c| c.to_str().is_some())
+            {
+                // The file name can't be represented on this platform. Don't
+                // record a file state for it, so it won't be considered deleted
+                // by the next snapshot.
+                stats.skipped_files += 1;
+                stats
+                    .skipped_paths
+                    .push((path, CheckoutSkipReason::InvalidFileName));
+                continue;
+            }
             if after.is_absent() {
                 stats.removed_files += 1;
             } else if !present_before {
@@ -1354,20 +1918,35 @@ impl TreeState {
             }
             let disk_path = path.to_fs_path(&self.working_copy_path);
 
-            if present_before {
-                fs::remove_file(&disk_path).ok();
-            } else if disk_path.exists() {
-                changed_file_states.push((path, FileState::placeholder()));
-                stats.skipped_files += 1;
-                continue;
-            }
-            if after.is_present() {
-                let skip = create_parent_dirs(&self.working_copy_path, &path)?;
-                if skip {
-                    changed_file_states.push((path, FileState::placeholder()));
-                    stats.skipped_files += 1;
+            let symlinked_parent =
+                has_symlinked_parent(&self.working_copy_path, &path, &mut checked_dirs);
+            let mut skip_reason = if symlinked_parent {
+                // Don't remove or write anything through the symlink. A path
+                // below a symlink isn't part of the working copy anyway, so
+                // there's nothing to remove.
+                if after.is_absent() {
+                    deleted_files.insert(path);
                     continue;
                 }
+                Some(CheckoutSkipReason::SymlinkedParent)
+            } else if present_before {
+                remove_old_file(&disk_path);
+                None
+            } else if disk_path.symlink_metadata().is_ok() {
+                Some(CheckoutSkipReason::UntrackedPath)
+            } else {
+                None
+            };
+            if skip_reason.is_none() && after.is_present() {
+                skip_reason = path
+                    .parent()
+                    .and_then(|parent| skipped_dirs.get(parent).copied());
+            }
+            if let Some(reason) = skip_reason {
+                changed_file_states.push((path.clone(), FileState::placeholder()));
+                stats.skipped_files += 1;
+                stats.skipped_paths.push((path, reason));
+                continue;
             }
             // TODO: Check that the file has not changed before overwriting/removing it.
             let file_state = match after {
@@ -1377,6 +1956,7 @@ impl TreeState {
                         if fs::remove_dir(parent_dir).is_err() {
                             break;
                         }
+                        checked_dirs.0.remove(parent_dir);
                         parent_dir = parent_dir.parent().unwrap();
                     }
                     deleted_files.insert(path);
@@ -1386,7 +1966,21 @@ impl TreeState {
                     executable,
                     mut reader,
                     ..
-                } => self.write_file(&disk_path, &mut reader, executable)?,
+                } => {
+                    if self.git_attributes.attributes_for(&path)?.checks_out_crlf() {
+                        let mut contents = vec![];
+                        reader
+                            .read_to_end(&mut contents)
+                            .map_err(|err| CheckoutError::Other {
+                                message: format!("Failed to read file {path:?}"),
+                                err: err.into(),
+                            })?;
+                        let contents = gitattributes::convert_lf_to_crlf(&contents);
+                        self.write_file(&disk_path, &mut contents.as_ref(), executable)?
+                    } else {
+                        self.write_file(&disk_path, &mut reader, executable)?
+                    }
+                }
                 MaterializedTreeValue::Symlink { id: _, target } => {
                     self.write_symlink(&disk_path, target)?
                 }
@@ -1401,6 +1995,9 @@ impl TreeState {
                     self.write_conflict(&disk_path, contents)?
                 }
             };
+            if let Some(conflict) = metadata_conflict {
+                self.metadata_conflicts.insert(path.clone(), conflict);
+            }
             changed_file_states.push((path, file_state));
         }
         self.file_states
@@ -1419,12 +2016,16 @@ impl TreeState {
         let matcher = self.sparse_matcher();
         let mut changed_file_states = Vec::new();
         let mut deleted_files = HashSet::new();
-        let mut diff_stream = old_tree.diff_stream(new_tree, matcher.as_ref());
-        while let Some((path, diff)) = diff_stream.next().await {
-            let (_before, after) = diff?;
+        let diffs =
+            collect_checkout_diff(&self.store, &old_tree, new_tree, matcher.as_ref()).await?;
+        for (path, _before, after) in diffs {
+            self.metadata_conflicts.remove(&path);
             if after.is_absent() {
                 deleted_files.insert(path);
             } else {
+                if let Some(conflict) = after.metadata_conflict() {
+                    self.metadata_conflicts.insert(path.clone(), conflict);
+                }
                 let file_type = match after.into_resolved() {
                     Ok(value) => match value.unwrap() {
                         #[cfg(unix)]
@@ -1466,10 +2067,177 @@ impl TreeState {
 
     pub fn reset_to_empty(&mut self) {
         self.file_states.clear();
+        self.metadata_conflicts.clear();
         self.tree_id = self.store.empty_merged_tree_id();
     }
 }
 
+/// Expands a conflict between a file and a directory into the paths it's
+/// checked out as: the entries of the directory side at their own paths, and
+/// the rest of the conflict at `conflicts::path_conflict_file_path()`. Other
+/// values are returned as is.
+fn expand_path_conflict(
+    store: &Arc<Store>,
+    path: RepoPathBuf,
+    value: MergedTreeValue,
+    matcher: &dyn Matcher,
+) -> BackendResult<Vec<(RepoPathBuf, MergedTreeValue)>> {
+    if !value.is_path_conflict() {
+        return Ok(vec![(path, value)]);
+    }
+    let (dir_value, file_value) = value.split_path_conflict();
+    let trees = dir_value.to_tree_merge(store, &path)?.unwrap();
+    let mut entries = vec![];
+    for (sub_path, sub_value) in MergedTree::Merge(trees).entries_matching(matcher) {
+        entries.extend(expand_path_conflict(store, sub_path, sub_value, matcher)?);
+    }
+    let file_path = conflicts::path_conflict_file_path(&path);
+    if matcher.matches(&file_path) {
+        entries.push((file_path, file_value.simplify()));
+    }
+    Ok(entries)
+}
+
+/// If `path` is where the file side of a conflict between a file and a
+/// directory is checked out, returns that conflict.
+fn path_conflict_for_file_path(tree: &MergedTree, path: &RepoPath) -> Option<MergedTreeValue> {
+    let (dir, name) = path.split()?;
+    let conflict_name = name
+        .as_bytes()
+        .strip_suffix(conflicts::PATH_CONFLICT_FILE_SUFFIX.as_bytes())?;
+    if conflict_name.is_empty() || tree.path_value(path).is_present() {
+        return None;
+    }
+    let value = tree.path_value(&dir.join(RepoPathComponent::from_bytes(conflict_name)));
+    value.is_path_conflict().then_some(value)
+}
+
+/// Returns the tree value to snapshot for a file checked out with a metadata
+/// conflict. The conflict is kept even if the file on disk looks like a
+/// resolution, since the file can't represent the conflict. Only edits to the
+/// contents of the checked-out file are taken.
+fn preserve_metadata_conflict(
+    conflict: MetadataConflict,
+    current_tree_values: &MergedTreeValue,
+    new_tree_values: MergedTreeValue,
+) -> MergedTreeValue {
+    match (conflict, new_tree_values.as_normal()) {
+        (MetadataConflict::Executable, Some(TreeValue::File { id, executable: _ })) => {
+            let file_ids = current_tree_values
+                .to_file_merge()
+                .unwrap()
+                .map(|file_id| file_id.as_ref().map(|_| id.clone()));
+            current_tree_values.with_new_file_ids(&file_ids)
+        }
+        (MetadataConflict::Executable, None) if new_tree_values.to_file_merge().is_some() => {
+            // The contents still conflict, but the executable bits came from
+            // the current tree anyway.
+            new_tree_values
+        }
+        (MetadataConflict::SymlinkVsFile, Some(new_file @ TreeValue::File { .. })) => {
+            // Only the side that was checked out is edited
+            let checked_out = current_tree_values
+                .adds()
+                .flatten()
+                .find(|value| matches!(value, TreeValue::File { .. }));
+            let adds = current_tree_values.adds().map(|term| {
+                if term.as_ref() == checked_out {
+                    Some(new_file.clone())
+                } else {
+                    term.clone()
+                }
+            });
+            Merge::from_removes_adds(current_tree_values.removes().cloned(), adds)
+        }
+        _ => current_tree_values.clone(),
+    }
+}
+
+/// Returns the changes to make to the working copy to go from `old_tree` to
+/// `new_tree`. Conflicts between files and directories are expanded by
+/// `expand_path_conflict()`. Removals are ordered first, so a directory is
+/// removed before a file replaces it and vice versa.
+async fn collect_checkout_diff(
+    store: &Arc<Store>,
+    old_tree: &MergedTree,
+    new_tree: &MergedTree,
+    matcher: &dyn Matcher,
+) -> BackendResult<Vec<(RepoPathBuf, MergedTreeValue, MergedTreeValue)>> {
+    let mut diffs = vec![];
+    let mut has_path_conflicts = false;
+    let mut diff_stream = old_tree.diff_stream(new_tree, matcher);
+    while let Some((path, diff)) = diff_stream.next().await {
+        let (before, after) = diff?;
+        has_path_conflicts |= before.is_path_conflict() || after.is_path_conflict();
+        diffs.push((path, before, after));
+    }
+    if !has_path_conflicts {
+        let (removals, others): (Vec<_>, Vec<_>) = diffs
+            .into_iter()
+            .partition(|(_, _, after)| after.is_absent());
+        return Ok(itertools::chain(removals, others).collect());
+    }
+
+    let mut expanded: BTreeMap<RepoPathBuf, (MergedTreeValue, MergedTreeValue)> = BTreeMap::new();
+    for (path, before, after) in diffs {
+        for (path, value) in expand_path_conflict(store, path.clone(), before, matcher)? {
+            let (expanded_before, _) = expanded
+                .entry(path)
+                .or_insert_with(|| (Merge::absent(), Merge::absent()));
+            if value.is_present() {
+                *expanded_before = value;
+            }
+        }
+        for (path, value) in expand_path_conflict(store, path, after, matcher)? {
+            let (_, expanded_after) = expanded
+                .entry(path)
+                .or_insert_with(|| (Merge::absent(), Merge::absent()));
+            if value.is_present() {
+                *expanded_after = value;
+            }
+        }
+    }
+    let (removals, others): (Vec<_>, Vec<_>) = expanded
+        .into_iter()
+        .filter(|(_, (before, after))| before != after)
+        .map(|(path, (before, after))| (path, before, after))
+        .partition(|(_, _, after)| after.is_absent());
+    Ok(itertools::chain(removals, others).collect())
+}
+
+/// Returns the directories the files written by `diffs` are checked out into,
+/// parents first. These are the directories of any side of `new_tree` on the
+/// way to the written files, so the directory sides of conflicts between files
+/// and directories are included.
+fn collect_checkout_dirs(
+    new_tree: &MergedTree,
+    diffs: &[(RepoPathBuf, MergedTreeValue, MergedTreeValue)],
+) -> BackendResult<Vec<RepoPathBuf>> {
+    let written_paths = diffs
+        .iter()
+        .filter(|(_, _, after)| after.is_present())
+        .map(|(path, _, _)| path);
+    let matcher = FilesMatcher::new(written_paths);
+    let trees = match new_tree {
+        MergedTree::Legacy(tree) => {
+            let MergedTree::Merge(trees) = MergedTree::from_legacy_tree(tree.clone())? else {
+                unreachable!();
+            };
+            trees
+        }
+        MergedTree::Merge(trees) => trees.clone(),
+    };
+    let mut dirs = BTreeSet::new();
+    for tree in trees.iter() {
+        for (path, value) in tree.entries_matching_with_mode(&matcher, TreeEntriesMode::WithDirs) {
+            if matches!(value, TreeValue::Tree(_)) {
+                dirs.insert(path);
+            }
+        }
+    }
+    Ok(dirs.into_iter().collect())
+}
+
 fn checkout_error_for_stat_error(err: std::io::Error, path: &Path) -> CheckoutError {
     CheckoutError::Other {
         message: format!("Failed to stat file {}", path.display()),
@@ -1477,6 +2245,68 @@ fn checkout_error_for_stat_error(err: std::io::Error, path: &Path) -> CheckoutEr
     }
 }
 
+/// A tree snapshotted from a directory by `snapshot_directory()`.
+pub struct DirectorySnapshot {
+    tree: MergedTree,
+    // Holds the temporary store the tree was written to, which is deleted when
+    // the snapshot is dropped
+    _temp_dir: TempDir,
+}
+
+impl DirectorySnapshot {
+    /// The snapshotted tree. Its store can also read the objects of the store
+    /// passed to `snapshot_directory()`, so trees of the repo can be loaded
+    /// from it to be compared with the snapshot.
+    pub fn tree(&self) -> &MergedTree {
+        &self.tree
+    }
+}
+
+/// Writes the files in `dir` to a temporary store layered on top of `store`
+/// and returns the resulting tree. Nothing is written to `store` itself.
+///
+/// The directory doesn't have to be a working copy, and no state is saved, so
+/// all files are read. `.gitignore` files in the directory are respected in
+/// addition to `options.base_ignores`, as when snapshotting a working copy.
+/// Only the Git backend supports temporary stores.
+pub fn snapshot_directory(
+    settings: &UserSettings,
+    store: &Store,
+    dir: &Path,
+    options: SnapshotOptions,
+) -> Result<DirectorySnapshot, SnapshotError> {
+    let temp_store_error = |err: Box<dyn Error + Send + Sync>| SnapshotError::Other {
+        message: "Failed to create a temporary store".to_owned(),
+        err,
+    };
+    let git_backend = store
+        .backend_impl()
+        .downcast_ref::<GitBackend>()
+        .ok_or_else(|| temp_store_error("The backend isn't a Git backend".into()))?;
+    let temp_dir = tempfile::Builder::new()
+        .prefix("jj-snapshot-")
+        .tempdir()
+        .map_err(|err| temp_store_error(err.into()))?;
+    let backend = git_backend
+        .init_overlay(settings, temp_dir.path())
+        .map_err(|err| temp_store_error(err))?;
+    let temp_store = Store::new(
+        Box::new(backend),
+        Signer::new(None, vec![]),
+        store.use_tree_conflict_format(),
+        store.strict_validation(),
+        store.merge_drivers().clone(),
+    );
+    // The state path is only used when saving the tree state, which we don't do.
+    let mut tree_state = TreeState::empty(temp_store.clone(), dir.to_owned(), dir.to_owned());
+    tree_state.snapshot(options)?;
+    let tree = temp_store.get_root_tree(&tree_state.tree_id)?;
+    Ok(DirectorySnapshot {
+        tree,
+        _temp_dir: temp_dir,
+    })
+}
+
 /// Working copy state stored in "checkout" file.
 #[derive(Clone, Debug)]
 struct CheckoutState {
@@ -1523,7 +2353,7 @@ impl WorkingCopy for LocalWorkingCopy {
 
     fn start_mutation(&self) -> Result<Box<dyn LockedWorkingCopy>, WorkingCopyStateError> {
         let lock_path = self.state_path.join("working_copy.lock");
-        let lock = FileLock::lock(lock_path);
+        let lock = FileLock::lock(lock_path, LockKind::WorkingCopy);
 
         let wc = LocalWorkingCopy {
             store: self.store.clone(),
@@ -1664,6 +2494,20 @@ impl LocalWorkingCopy {
         Ok(self.tree_state()?.file_states())
     }
 
+    /// Whether the filesystem was detected to preserve the executable bit when
+    /// the working copy was initialized.
+    pub fn exec_bit_supported(&self) -> Result<bool, WorkingCopyStateError> {
+        Ok(self.tree_state()?.exec_bit_supported())
+    }
+
+    /// Paths checked out with a conflict in metadata that the file on disk
+    /// can't represent.
+    pub fn metadata_conflicts(
+        &self,
+    ) -> Result<&BTreeMap<RepoPathBuf, MetadataConflict>, WorkingCopyStateError> {
+        Ok(self.tree_state()?.metadata_conflicts())
+    }
+
     #[instrument(skip_all)]
     fn save(&mut self) {
         self.write_proto(crate::protos::working_copy::Checkout {
@@ -1755,20 +2599,23 @@ impl LockedWorkingCopy for LockedLocalWorkingCopy {
         Ok(tree_state.current_tree_id().clone())
     }
 
+    fn snapshot_stats(&self) -> SnapshotStats {
+        self.wc
+            .tree_state()
+            .map(|tree_state| tree_state.snapshot_stats().clone())
+            .unwrap_or_default()
+    }
+
     fn check_out(&mut self, commit: &Commit) -> Result<CheckoutStats, CheckoutError> {
-        // TODO: Write a "pending_checkout" file with the new TreeId so we can
-        // continue an interrupted update if we find such a file.
-        let new_tree = commit.tree()?;
-        let stats = self
-            .wc
-            .tree_state_mut()
-            .map_err(|err| CheckoutError::Other {
-                message: "Failed to load the working copy state".to_string(),
-                err: err.into(),
-            })?
-            .check_out(&new_tree)?;
-        self.tree_state_dirty = true;
-        Ok(stats)
+        self.check_out_impl(commit, None)
+    }
+
+    fn check_out_cancellable(
+        &mut self,
+        commit: &Commit,
+        cancel: &CancellationToken,
+    ) -> Result<CheckoutStats, CheckoutError> {
+        self.check_out_impl(commit, Some(cancel))
     }
 
     fn reset(&mut self, commit: &Commit) -> Result<(), ResetError> {
@@ -1844,6 +2691,30 @@ impl LockedWorkingCopy for LockedLocalWorkingCopy {
 }
 
 impl LockedLocalWorkingCopy {
+    fn check_out_impl(
+        &mut self,
+        commit: &Commit,
+        cancel: Option<&CancellationToken>,
+    ) -> Result<CheckoutStats, CheckoutError> {
+        // TODO: Write a "pending_checkout" file with the new TreeId so we can
+        // continue an interrupted update if we find such a file.
+        let new_tree = commit.tree()?;
+        let tree_state = self
+            .wc
+            .tree_state_mut()
+            .map_err(|err| CheckoutError::Other {
+                message: "Failed to load the working copy state".to_string(),
+                err: err.into(),
+            })?;
+        tree_state.set_conflict_labels(commit.conflict_labels());
+        let result = tree_state.check_out_cancellable(&new_tree, cancel);
+        // A cancelled checkout still records the files it updated.
+        if matches!(result, Ok(_) | Err(CheckoutError::Cancelled(_))) {
+            self.tree_state_dirty = true;
+        }
+        result
+    }
+
     pub fn reset_watchman(&mut self) -> Result<(), SnapshotError> {
         self.wc
             .tree_state_mut()
@@ -1855,6 +2726,15 @@ impl LockedLocalWorkingCopy {
         self.tree_state_dirty = true;
         Ok(())
     }
+
+    /// Overrides whether the filesystem is considered to preserve the
+    /// executable bit, e.g. after moving the working copy to another
+    /// filesystem.
+    pub fn set_exec_bit_supported(&mut self, supported: bool) -> Result<(), WorkingCopyStateError> {
+        self.wc.tree_state_mut()?.set_exec_bit_supported(supported);
+        self.tree_state_dirty = true;
+        Ok(())
+    }
 }
 
 #[cfg(test)]
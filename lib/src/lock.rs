@@ -12,14 +12,93 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+//! File locks, and the order in which they must be taken.
+//!
+//! Most of the repo is lock-free. Readers never take a lock: they only need a
+//! consistent read of the op heads, which the op heads store provides by
+//! adding a new head before removing the heads it replaces. Writers take locks
+//! only around the few updates that need them, never for the whole duration
+//! of a transaction:
+//!
+//! 1. [`LockKind::WorkingCopy`]: a workspace's working-copy lock, held while
+//!    the working copy is snapshotted or updated. Each workspace has its own.
+//! 2. [`LockKind::OpHeads`]: the op heads lock, held while the op heads are
+//!    updated and while divergent operations are merged.
+//! 3. [`LockKind::Table`]: the lock of a stacked table, held while the table
+//!    is appended to.
+//! 4. [`LockKind::OpHeadsSequence`]: the lock of the op heads sequence file,
+//!    held while the sequence number is incremented. Stale op heads are
+//!    removed without the op heads lock, so the increment needs its own.
+//!
+//! A thread holding a lock may only take locks that come later in this list,
+//! so two processes can't deadlock waiting for each other. Debug builds panic
+//! when the order is violated.
+
 #![allow(missing_docs)]
 
+use std::cell::RefCell;
+use std::path::Path;
+
 #[cfg_attr(unix, path = "lock/unix.rs")]
 #[cfg_attr(not(unix), path = "lock/fallback.rs")]
 mod platform;
 
 pub use platform::FileLock;
 
+/// The kinds of file locks, in the order they must be taken.
+#[derive(Clone, Copy, Debug, Eq, Ord, PartialEq, PartialOrd)]
+pub enum LockKind {
+    WorkingCopy,
+    OpHeads,
+    Table,
+    OpHeadsSequence,
+}
+
+thread_local! {
+    static HELD_LOCKS: RefCell<Vec<LockKind>> = const { RefCell::new(Vec::new()) };
+}
+
+/// Tracks the kinds of locks held by the current thread to check the lock
+/// ordering in debug builds.
+struct LockOrderGuard {
+    kind: LockKind,
+}
+
+impl LockOrderGuard {
+    /// Must be created before waiting for the lock, so a violation panics
+    /// instead of deadlocking.
+    fn new(kind: LockKind, path: &Path) -> Self {
+        if cfg!(debug_assertions) {
+            HELD_LOCKS.with(|held| {
+                let mut held = held.borrow_mut();
+                if let Some(last) = held.iter().max() {
+                    assert!(
+                        kind > *last,
+                        "taking the {kind:?} lock {path:?} while holding the {last:?} lock \
+                         violates the lock ordering"
+                    );
+                }
+                held.push(kind);
+            });
+        }
+        LockOrderGuard { kind }
+    }
+}
+
+impl Drop for LockOrderGuard {
+    fn drop(&mut self) {
+        if cfg!(debug_assertions) {
+            HELD_LOCKS.with(|held| {
+                let mut held = held.borrow_mut();
+                // The lock may have been sent to another thread
+                if let Some(pos) = held.iter().rposition(|kind| *kind == self.kind) {
+                    held.remove(pos);
+                }
+            });
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use std::cmp::max;
@@ -34,7 +113,7 @@ mod tests {
         let lock_path = temp_dir.path().join("test.lock");
         assert!(!lock_path.exists());
         {
-            let _lock = FileLock::lock(lock_path.clone());
+            let _lock = FileLock::lock(lock_path.clone(), LockKind::OpHeads);
             assert!(lock_path.exists());
         }
         assert!(!lock_path.exists());
@@ -50,7 +129,7 @@ mod tests {
         thread::scope(|s| {
             for _ in 0..num_threads {
                 s.spawn(|| {
-                    let _lock = FileLock::lock(lock_path.clone());
+                    let _lock = FileLock::lock(lock_path.clone(), LockKind::OpHeads);
                     let data = fs::read(&data_path).unwrap();
                     let value = u32::from_le_bytes(data.try_into().unwrap());
                     thread::sleep(Duration::from_millis(1));
@@ -62,4 +141,30 @@ mod tests {
         let value = u32::from_le_bytes(data.try_into().unwrap());
         assert_eq!(value, num_threads as u32);
     }
+
+    #[test]
+    fn lock_order() {
+        let temp_dir = testutils::new_temp_dir();
+        let working_copy_lock_path = temp_dir.path().join("working_copy.lock");
+        let op_heads_lock_path = temp_dir.path().join("op_heads.lock");
+        let _working_copy_lock = FileLock::lock(working_copy_lock_path, LockKind::WorkingCopy);
+        {
+            let _op_heads_lock = FileLock::lock(op_heads_lock_path.clone(), LockKind::OpHeads);
+        }
+        // Can be taken again after it was released
+        let _op_heads_lock = FileLock::lock(op_heads_lock_path, LockKind::OpHeads);
+    }
+
+    #[test]
+    #[cfg(debug_assertions)]
+    #[should_panic(expected = "violates the lock ordering")]
+    fn lock_order_violated() {
+        let temp_dir = testutils::new_temp_dir();
+        let _op_heads_lock =
+            FileLock::lock(temp_dir.path().join("op_heads.lock"), LockKind::OpHeads);
+        let _working_copy_lock = FileLock::lock(
+            temp_dir.path().join("working_copy.lock"),
+            LockKind::WorkingCopy,
+        );
+    }
 }
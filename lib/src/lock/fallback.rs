@@ -19,13 +19,17 @@ use std::time::Duration;
 use backoff::{retry, ExponentialBackoff};
 use tracing::instrument;
 
+use super::{LockKind, LockOrderGuard};
+
 pub struct FileLock {
     path: PathBuf,
     _file: File,
+    _order: LockOrderGuard,
 }
 
 impl FileLock {
-    pub fn lock(path: PathBuf) -> FileLock {
+    pub fn lock(path: PathBuf, kind: LockKind) -> FileLock {
+        let mut order = Some(LockOrderGuard::new(kind, &path));
         let mut options = OpenOptions::new();
         options.create_new(true);
         options.write(true);
@@ -33,6 +37,7 @@ impl FileLock {
             Ok(file) => Ok(FileLock {
                 path: path.clone(),
                 _file: file,
+                _order: order.take().unwrap(),
             }),
             Err(err) if err.kind() == std::io::ErrorKind::AlreadyExists => {
                 Err(backoff::Error::Transient {
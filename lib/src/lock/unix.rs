@@ -20,13 +20,17 @@ use std::path::PathBuf;
 use rustix::fs::FlockOperation;
 use tracing::instrument;
 
+use super::{LockKind, LockOrderGuard};
+
 pub struct FileLock {
     path: PathBuf,
     file: File,
+    _order: LockOrderGuard,
 }
 
 impl FileLock {
-    pub fn lock(path: PathBuf) -> FileLock {
+    pub fn lock(path: PathBuf, kind: LockKind) -> FileLock {
+        let order = LockOrderGuard::new(kind, &path);
         loop {
             // Create lockfile, or open pre-existing one
             let file = File::create(&path).expect("failed to open lockfile");
@@ -43,7 +47,11 @@ impl FileLock {
                 continue;
             }
 
-            return Self { path, file };
+            return Self {
+                path,
+                file,
+                _order: order,
+            };
         }
     }
 }
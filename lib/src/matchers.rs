@@ -17,9 +17,12 @@
 use std::collections::{HashMap, HashSet};
 use std::iter;
 
+use regex::Regex;
+use thiserror::Error;
 use tracing::instrument;
 
-use crate::repo_path::{RepoPath, RepoPathComponentBuf};
+use crate::gitignore::glob_path_regex;
+use crate::repo_path::{RepoPath, RepoPathComponent, RepoPathComponentBuf};
 
 #[derive(PartialEq, Eq, Debug)]
 pub enum Visit {
@@ -53,6 +56,21 @@ impl Visit {
     pub fn is_nothing(&self) -> bool {
         *self == Visit::Nothing
     }
+
+    /// Returns whether the entry `name` of the directory this is the `Visit`
+    /// of may have to be visited (if it's a directory) or matched (if it's a
+    /// file). If so, it still has to be checked with the `Matcher`.
+    pub(crate) fn may_include(&self, name: &RepoPathComponent, is_dir: bool) -> bool {
+        match self {
+            Visit::AllRecursively => true,
+            Visit::Specific { dirs, files } => match (is_dir, dirs, files) {
+                (true, VisitDirs::All, _) | (false, _, VisitFiles::All) => true,
+                (true, VisitDirs::Set(dirs), _) => dirs.contains(name),
+                (false, _, VisitFiles::Set(files)) => files.contains(name),
+            },
+            Visit::Nothing => false,
+        }
+    }
 }
 
 #[derive(PartialEq, Eq, Debug)]
@@ -132,9 +150,16 @@ impl PrefixMatcher {
     pub fn new(prefixes: impl IntoIterator<Item = impl AsRef<RepoPath>>) -> Self {
         let mut tree = RepoPathTree::new();
         for prefix in prefixes {
-            let sub = tree.add(prefix.as_ref());
+            let prefix = prefix.as_ref();
+            // Paths under another prefix wouldn't match anything more, so they
+            // are dropped to keep the tree small when many paths are given.
+            if tree.walk_to(prefix).any(|(sub, _)| sub.is_file) {
+                continue;
+            }
+            let sub = tree.add(prefix);
             sub.is_dir = true;
             sub.is_file = true;
+            sub.entries.clear();
         }
         PrefixMatcher { tree }
     }
@@ -160,6 +185,103 @@ impl Matcher for PrefixMatcher {
     }
 }
 
+/// Matches files whose path matches one of the glob patterns. The patterns
+/// are interpreted like in `.gitattributes` at the repo root.
+#[derive(Debug)]
+pub struct GlobsMatcher {
+    regexes: Vec<Regex>,
+}
+
+#[derive(Debug, Error)]
+#[error("Invalid glob pattern: {0}")]
+pub struct InvalidGlobError(pub String);
+
+impl GlobsMatcher {
+    pub fn new(
+        patterns: impl IntoIterator<Item = impl AsRef<str>>,
+    ) -> Result<Self, InvalidGlobError> {
+        let regexes = patterns
+            .into_iter()
+            .map(|pattern| {
+                let pattern = pattern.as_ref();
+                glob_path_regex(pattern).ok_or_else(|| InvalidGlobError(pattern.to_owned()))
+            })
+            .collect::<Result<_, _>>()?;
+        Ok(GlobsMatcher { regexes })
+    }
+}
+
+impl Matcher for GlobsMatcher {
+    fn matches(&self, file: &RepoPath) -> bool {
+        let path = file.as_internal_file_string();
+        self.regexes.iter().any(|regex| regex.is_match(&path))
+    }
+
+    fn visit(&self, _dir: &RepoPath) -> Visit {
+        if self.regexes.is_empty() {
+            Visit::Nothing
+        } else {
+            Visit::Specific {
+                dirs: VisitDirs::All,
+                files: VisitFiles::All,
+            }
+        }
+    }
+}
+
+/// Matches paths that are matched by any of the input matchers.
+pub struct UnionMatcher<'input> {
+    input1: &'input dyn Matcher,
+    input2: &'input dyn Matcher,
+}
+
+impl<'input> UnionMatcher<'input> {
+    pub fn new(input1: &'input dyn Matcher, input2: &'input dyn Matcher) -> Self {
+        Self { input1, input2 }
+    }
+}
+
+impl Matcher for UnionMatcher<'_> {
+    fn matches(&self, file: &RepoPath) -> bool {
+        self.input1.matches(file) || self.input2.matches(file)
+    }
+
+    fn visit(&self, dir: &RepoPath) -> Visit {
+        match self.input1.visit(dir) {
+            Visit::AllRecursively => Visit::AllRecursively,
+            Visit::Nothing => self.input2.visit(dir),
+            Visit::Specific {
+                dirs: dirs1,
+                files: files1,
+            } => match self.input2.visit(dir) {
+                Visit::AllRecursively => Visit::AllRecursively,
+                Visit::Nothing => Visit::Specific {
+                    dirs: dirs1,
+                    files: files1,
+                },
+                Visit::Specific {
+                    dirs: dirs2,
+                    files: files2,
+                } => {
+                    let dirs = match (dirs1, dirs2) {
+                        (VisitDirs::All, _) | (_, VisitDirs::All) => VisitDirs::All,
+                        (VisitDirs::Set(dirs1), VisitDirs::Set(dirs2)) => {
+                            VisitDirs::Set(dirs1.union(&dirs2).cloned().collect())
+                        }
+                    };
+                    let files = match (files1, files2) {
+                        (VisitFiles::All, _) | (_, VisitFiles::All) => VisitFiles::All,
+                        (VisitFiles::Set(files1), VisitFiles::Set(files2)) => {
+                            VisitFiles::Set(files1.union(&files2).cloned().collect())
+                        }
+                    };
+                    Visit::Specific { dirs, files }
+                }
+            },
+        }
+    }
+}
+
 /// Matches paths that are matched by the first input matcher but not by the
 /// second.
 pub struct DifferenceMatcher<'input> {
@@ -339,9 +461,11 @@ impl RepoPathTree {
 
 #[cfg(test)]
 mod tests {
+    use itertools::Itertools as _;
     use maplit::hashset;
 
     use super::*;
+    use crate::repo_path::{RepoPathBuf, RepoPathComponent};
 
     fn repo_path(value: &str) -> &RepoPath {
         RepoPath::from_internal_string(value)
@@ -536,6 +660,166 @@ mod tests {
         assert_eq!(m.visit(repo_path("foo/bar/baz")), Visit::AllRecursively);
     }
 
+    #[test]
+    fn test_prefixmatcher_overlapping_prefixes() {
+        let m = PrefixMatcher::new([repo_path("src/lib"), repo_path("src"), repo_path("src")]);
+        // Prefixes under the "src" prefix are dropped
+        assert_eq!(m.tree, PrefixMatcher::new([repo_path("src")]).tree);
+        assert!(m.matches(repo_path("src/lib/tree.rs")));
+        assert_eq!(m.visit(repo_path("src/lib")), Visit::AllRecursively);
+    }
+
+    #[test]
+    fn test_unionmatcher_prefix_and_files() {
+        let m1 = PrefixMatcher::new([repo_path("src")]);
+        let m2 = FilesMatcher::new([repo_path("src/lib/tree.rs"), repo_path("docs/index.md")]);
+        let m = UnionMatcher::new(&m1, &m2);
+
+        assert!(m.matches(repo_path("src")));
+        assert!(m.matches(repo_path("src/main.rs")));
+        assert!(m.matches(repo_path("src/lib/tree.rs")));
+        assert!(m.matches(repo_path("docs/index.md")));
+        assert!(!m.matches(repo_path("docs")));
+        assert!(!m.matches(repo_path("docs/index.md/file")));
+        assert!(!m.matches(repo_path("docs/other.md")));
+
+        assert_eq!(
+            m.visit(RepoPath::root()),
+            Visit::sets(
+                hashset! {
+                    RepoPathComponentBuf::from("src"),
+                    RepoPathComponentBuf::from("docs"),
+                },
+                hashset! {RepoPathComponentBuf::from("src")},
+            )
+        );
+        assert_eq!(
+            m.visit(repo_path("docs")),
+            Visit::sets(
+                hashset! {},
+                hashset! {RepoPathComponentBuf::from("index.md")}
+            )
+        );
+        assert_eq!(m.visit(repo_path("src/lib")), Visit::AllRecursively);
+        assert_eq!(m.visit(repo_path("other")), Visit::Nothing);
+    }
+
+    #[test]
+    fn test_unionmatcher_root_prefix() {
+        let m1 = PrefixMatcher::new([RepoPath::root()]);
+        let m2 = FilesMatcher::new([repo_path("file")]);
+        let m = UnionMatcher::new(&m1, &m2);
+        assert!(m.matches(repo_path("file")));
+        assert!(m.matches(repo_path("dir/file")));
+        assert_eq!(m.visit(RepoPath::root()), Visit::AllRecursively);
+        let m = UnionMatcher::new(&m2, &m1);
+        assert_eq!(m.visit(RepoPath::root()), Visit::AllRecursively);
+    }
+
+    /// Compares the union of a `PrefixMatcher` and a `FilesMatcher` with
+    /// matching against each path in turn, using random sets of overlapping
+    /// paths.
+    #[test]
+    fn test_unionmatcher_prefix_and_files_random() {
+        use rand::{Rng as _, SeedableRng as _};
+
+        let names = ["a", "b", "c"];
+        // All paths up to 4 levels deep
+        let mut all_paths = vec![RepoPathBuf::root()];
+        for depth in 0..4 {
+            let parents = all_paths
+                .iter()
+                .filter(|path| path.components().count() == depth)
+                .cloned()
+                .collect_vec();
+            for parent in parents {
+                for name in names {
+                    all_paths.push(parent.join(RepoPathComponent::new(name)));
+                }
+            }
+        }
+
+        let mut rng = rand_chacha::ChaCha8Rng::seed_from_u64(0);
+        for _ in 0..200 {
+            let mut pick_paths = |max_count: usize| {
+                let count = rng.gen_range(0..=max_count);
+                (0..count)
+                    .map(|_| all_paths[rng.gen_range(1..all_paths.len())].clone())
+                    .collect_vec()
+            };
+            let prefixes = pick_paths(4);
+            let files = pick_paths(6);
+            let prefix_matcher = PrefixMatcher::new(&prefixes);
+            let files_matcher = FilesMatcher::new(&files);
+            let m = UnionMatcher::new(&prefix_matcher, &files_matcher);
+
+            for path in &all_paths {
+                let expected_matches =
+                    prefixes.iter().any(|prefix| path.starts_with(prefix)) || files.contains(path);
+                assert_eq!(
+                    m.matches(path),
+                    expected_matches,
+                    "matches({path:?}) with prefixes {prefixes:?} and files {files:?}"
+                );
+
+                let expected_visit = if prefixes.iter().any(|prefix| path.starts_with(prefix)) {
+                    Visit::AllRecursively
+                } else {
+                    let mut dirs = HashSet::new();
+                    let mut visit_files = HashSet::new();
+                    let candidates = itertools::chain(
+                        prefixes.iter().map(|prefix| (prefix, true)),
+                        files.iter().map(|file| (file, false)),
+                    );
+                    for (candidate, is_prefix) in candidates {
+                        let Some(tail) = candidate.strip_prefix(path) else {
+                            continue;
+                        };
+                        let mut components = tail.components();
+                        let Some(name) = components.next() else {
+                            continue;
+                        };
+                        if !components.as_path().is_root() || is_prefix {
+                            dirs.insert(name.to_owned());
+                        }
+                        if components.as_path().is_root() {
+                            visit_files.insert(name.to_owned());
+                        }
+                    }
+                    Visit::sets(dirs, visit_files)
+                };
+                assert_eq!(
+                    m.visit(path),
+                    expected_visit,
+                    "visit({path:?}) with prefixes {prefixes:?} and files {files:?}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_globsmatcher() {
+        let m = GlobsMatcher::new(["*.pb.rs", "/vendor/**", "gen/*.json"]).unwrap();
+        assert!(m.matches(repo_path("foo.pb.rs")));
+        assert!(m.matches(repo_path("src/foo.pb.rs")));
+        assert!(m.matches(repo_path("vendor/lib/foo.c")));
+        assert!(!m.matches(repo_path("src/vendor/foo.c")));
+        assert!(m.matches(repo_path("gen/lock.json")));
+        assert!(!m.matches(repo_path("src/gen/lock.json")));
+        assert!(!m.matches(repo_path("foo.rs")));
+        assert_eq!(
+            m.visit(RepoPath::root()),
+            Visit::Specific {
+                dirs: VisitDirs::All,
+                files: VisitFiles::All,
+            }
+        );
+
+        let m = GlobsMatcher::new(Vec::<String>::new()).unwrap();
+        assert!(!m.matches(repo_path("foo")));
+        assert_eq!(m.visit(RepoPath::root()), Visit::Nothing);
+    }
+
     #[test]
     fn test_differencematcher_remove_subdir() {
         let m1 = PrefixMatcher::new([repo_path("foo"), repo_path("bar")]);
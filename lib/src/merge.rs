@@ -16,7 +16,7 @@
 //! some common types of merged values.
 
 use std::borrow::Borrow;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet, VecDeque};
 use std::fmt::{Debug, Formatter};
 use std::hash::Hash;
 use std::io::Write;
@@ -252,24 +252,132 @@ impl<T> Merge<T> {
 
     /// Simplify the merge by joining diffs like A->B and B->C into A->C.
     /// Also drops trivial diffs like A->A.
-    pub fn simplify(mut self) -> Self
+    pub fn simplify(self) -> Self
     where
-        T: PartialEq,
+        T: Eq + Hash,
     {
-        let mut add_index = 0;
-        while add_index < self.values.len() {
-            let add = &self.values[add_index];
-            let mut removes = self.values.iter().enumerate().skip(1).step_by(2);
-            if let Some((remove_index, _)) = removes.find(|&(_, remove)| remove == add) {
-                // Align the current "add" value to the `remove_index/2`-th diff, then
-                // delete the diff pair.
-                self.values.swap(remove_index + 1, add_index);
-                self.values.drain(remove_index..remove_index + 2);
-            } else {
-                add_index += 2;
+        match self.simplified_indices() {
+            Some(indices) => self.select(&indices),
+            None => self,
+        }
+    }
+
+    /// Like `simplify()`, but also moves and drops the terms of `labels`, which
+    /// describe the terms of `self`, the same way. The labels are left as is
+    /// if they don't have as many terms as `self`.
+    pub fn simplify_with_labels<U>(self, labels: Merge<U>) -> (Self, Merge<U>)
+    where
+        T: Eq + Hash,
+    {
+        match self.simplified_indices() {
+            Some(indices) if labels.values.len() == self.values.len() => {
+                (self.select(&indices), labels.select(&indices))
+            }
+            Some(indices) => (self.select(&indices), labels),
+            None => (self, labels),
+        }
+    }
+
+    /// Returns the indices of the terms `simplify()` keeps, in their new
+    /// order, or `None` if it would keep all terms in place.
+    fn simplified_indices(&self) -> Option<Vec<usize>>
+    where
+        T: Eq + Hash,
+    {
+        if self.values.len() == 1 {
+            return None;
+        }
+        // Each "add" slot `k` holds the value at `slot_values[k]`, and slot `k > 0`
+        // forms the `k-1`-th diff pair with the remove at `k * 2 - 1`. Cancelling
+        // an add against the first matching remove moves the add of the remove's
+        // pair into the current slot and deletes the pair. This is equivalent to
+        // swapping and draining the values in place, but each remove is looked up
+        // in a map instead of scanning all of them, so this is linear in the
+        // number of terms.
+        let num_slots = self.num_sides();
+        let mut slot_values = (0..num_slots).map(|k| k * 2).collect_vec();
+        let mut deleted = vec![false; num_slots];
+        let mut removes_by_value: HashMap<&T, VecDeque<usize>> = HashMap::new();
+        for k in 1..num_slots {
+            removes_by_value
+                .entry(&self.values[k * 2 - 1])
+                .or_default()
+                .push_back(k);
+        }
+        let mut slot = 0;
+        while slot < num_slots {
+            if deleted[slot] {
+                slot += 1;
+                continue;
+            }
+            let add = &self.values[slot_values[slot]];
+            let pair = removes_by_value
+                .get_mut(add)
+                .and_then(|slots| slots.pop_front());
+            match pair {
+                Some(pair) if pair == slot => {
+                    deleted[slot] = true;
+                    slot += 1;
+                }
+                Some(pair) => {
+                    slot_values[slot] = slot_values[pair];
+                    deleted[pair] = true;
+                }
+                None => {
+                    slot += 1;
+                }
+            }
+        }
+        if !deleted.contains(&true) {
+            return None;
+        }
+
+        let mut indices = vec![];
+        for k in (0..num_slots).filter(|&k| !deleted[k]) {
+            if k > 0 {
+                indices.push(k * 2 - 1);
+            }
+            indices.push(slot_values[k]);
+        }
+        Some(indices)
+    }
+
+    /// Creates a merge of the terms at `indices`, each of which must appear at
+    /// most once.
+    fn select(self, indices: &[usize]) -> Self {
+        let mut old_values = self.values.into_iter().map(Some).collect_vec();
+        let values = indices
+            .iter()
+            .map(|&index| old_values[index].take().unwrap())
+            .collect();
+        Merge { values }
+    }
+
+    /// Drops diffs which are identical to an earlier diff, e.g. turns
+    /// A+(C-B)+(C-B) into A+(C-B). This matches the rule `resolve_trivial()`
+    /// applies when all sides made the same change, but unlike `simplify()`,
+    /// it changes the meaning of the merge.
+    pub fn collapse_duplicate_diffs(self) -> Self
+    where
+        T: Eq + Hash,
+    {
+        let keep = {
+            let mut seen = HashSet::new();
+            (1..self.num_sides())
+                .map(|k| seen.insert((&self.values[k * 2 - 1], &self.values[k * 2])))
+                .collect_vec()
+        };
+        if !keep.contains(&false) {
+            return self;
+        }
+        let mut values = self.values.into_iter();
+        let mut new_values = smallvec_inline![values.next().unwrap()];
+        for (keep, (remove, add)) in zip(keep, values.tuples()) {
+            if keep {
+                new_values.extend([remove, add]);
             }
         }
-        self
+        Merge { values: new_values }
     }
 
     /// If this merge can be trivially resolved, returns the value it resolves
@@ -468,6 +576,27 @@ impl<T: ContentHash> ContentHash for Merge<T> {
 /// shouldn't be.
 pub type MergedTreeValue = Merge<Option<TreeValue>>;
 
+/// A conflict in a file's metadata rather than (only) in its contents. These
+/// can't be represented by conflict markers in the working copy.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum MetadataConflict {
+    /// The sides disagree about whether the file is executable.
+    Executable,
+    /// Some sides have a symlink and others a regular file.
+    SymlinkVsFile,
+}
+
+/// How to resolve a `MetadataConflict`.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum MetadataResolution {
+    /// Set or clear the executable bit of all file terms.
+    Executable(bool),
+    /// Use the symlink side.
+    UseSymlink,
+    /// Use the regular file side.
+    UseFile,
+}
+
 impl MergedTreeValue {
     /// Create a `Merge` from a `backend::Conflict`, padding with `None` to
     /// make sure that there is exactly one more `adds()` than `removes()`.
@@ -501,6 +630,30 @@ impl MergedTreeValue {
                 .all(|value| matches!(value, Some(TreeValue::Tree(_)) | None))
     }
 
+    /// Whether this is a conflict between a directory and something else (a
+    /// file, a symlink, or a submodule) at the same path.
+    pub fn is_path_conflict(&self) -> bool {
+        let mut has_tree = false;
+        let mut has_other = false;
+        for value in self.iter().flatten() {
+            match value {
+                TreeValue::Tree(_) => has_tree = true,
+                _ => has_other = true,
+            }
+        }
+        has_tree && has_other
+    }
+
+    /// Splits a path conflict into the directory terms and the remaining
+    /// terms. Terms of the other kind are replaced by absent values, so both
+    /// merges have as many sides as `self`.
+    pub fn split_path_conflict(&self) -> (Self, Self) {
+        let is_tree = |value: &&TreeValue| matches!(value, TreeValue::Tree(_));
+        let dir_value = self.map(|term| term.as_ref().filter(is_tree).cloned());
+        let file_value = self.map(|term| term.as_ref().filter(|v| !is_tree(v)).cloned());
+        (dir_value, file_value)
+    }
+
     /// If this merge contains only files or absent entries, returns a merge of
     /// the `FileId`s`. The executable bits will be ignored. Use
     /// `Merge::with_new_file_ids()` to produce a new merge with the original
@@ -534,6 +687,81 @@ impl MergedTreeValue {
         Merge { values }
     }
 
+    /// Returns the kind of metadata conflict this is, if any. Only the sides
+    /// are classified. Sides which deleted the path don't conflict with the
+    /// others.
+    pub fn metadata_conflict(&self) -> Option<MetadataConflict> {
+        let mut has_file = false;
+        let mut has_symlink = false;
+        for value in self.adds().flatten() {
+            match value {
+                TreeValue::File { .. } => has_file = true,
+                TreeValue::Symlink(_) => has_symlink = true,
+                _ => return None,
+            }
+        }
+        if has_file && has_symlink {
+            return Some(MetadataConflict::SymlinkVsFile);
+        }
+        if !has_file {
+            return None;
+        }
+        // A base which isn't a file has no executable bit, like a missing one
+        let executable = self.map(|term| match term {
+            Some(TreeValue::File { id: _, executable }) => Some(*executable),
+            _ => None,
+        });
+        if executable.resolve_trivial().is_some() {
+            return None;
+        }
+        // A side that deleted the file doesn't disagree about the bit
+        let side_bits: HashSet<bool> = executable.adds().flatten().copied().collect();
+        (side_bits.len() > 1).then_some(MetadataConflict::Executable)
+    }
+
+    /// Resolves the metadata conflict as specified. Returns `None` if the
+    /// resolution doesn't apply to this value, or if more than one different
+    /// value of the chosen kind is on the sides.
+    ///
+    /// Setting the executable bit may leave a conflict in the contents.
+    pub fn resolve_metadata(&self, resolution: MetadataResolution) -> Option<Self> {
+        let conflict = self.metadata_conflict()?;
+        let unique_side = |is_kind: fn(&TreeValue) -> bool| {
+            let values: Vec<&TreeValue> = self
+                .adds()
+                .flatten()
+                .filter(|value| is_kind(value))
+                .unique()
+                .collect();
+            match values[..] {
+                [value] => Some(Merge::normal(value.clone())),
+                _ => None,
+            }
+        };
+        match (conflict, resolution) {
+            (MetadataConflict::Executable, MetadataResolution::Executable(executable)) => {
+                let value = self.map(|term| match term {
+                    Some(TreeValue::File { id, executable: _ }) => Some(TreeValue::File {
+                        id: id.clone(),
+                        executable,
+                    }),
+                    other => other.clone(),
+                });
+                match value.resolve_trivial() {
+                    Some(resolved) => Some(Merge::resolved(resolved.clone())),
+                    None => Some(value.simplify()),
+                }
+            }
+            (MetadataConflict::SymlinkVsFile, MetadataResolution::UseSymlink) => {
+                unique_side(|value| matches!(value, TreeValue::Symlink(_)))
+            }
+            (MetadataConflict::SymlinkVsFile, MetadataResolution::UseFile) => {
+                unique_side(|value| matches!(value, TreeValue::File { .. }))
+            }
+            _ => None,
+        }
+    }
+
     /// Give a summary description of the conflict's "removes" and "adds"
     pub fn describe(&self, file: &mut dyn Write) -> std::io::Result<()> {
         file.write_all(b"Conflict:\n")?;
@@ -616,6 +844,8 @@ fn describe_conflict_term(value: &TreeValue) -> String {
 
 #[cfg(test)]
 mod tests {
+    use std::cell::Cell;
+
     use super::*;
 
     fn c<T: Clone>(removes: &[T], adds: &[T]) -> Merge<T> {
@@ -807,6 +1037,95 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_simplify_many_sides() {
+        thread_local! {
+            static COMPARISONS: Cell<usize> = const { Cell::new(0) };
+        }
+
+        /// A value which counts how many times it's compared
+        #[derive(Clone, Debug, Hash)]
+        struct Counted(u32);
+
+        impl PartialEq for Counted {
+            fn eq(&self, other: &Self) -> bool {
+                COMPARISONS.with(|count| count.set(count.get() + 1));
+                self.0 == other.0
+            }
+        }
+
+        impl Eq for Counted {}
+
+        // A 51-part conflict in which every other diff is trivial
+        let removes = (1..26)
+            .map(|k| Counted(if k % 2 == 0 { k } else { 100 + k }))
+            .collect_vec();
+        let adds = (0..26).map(Counted).collect_vec();
+        let merge = Merge::from_removes_adds(removes, adds);
+        assert_eq!(merge.iter().len(), 51);
+
+        COMPARISONS.with(|count| count.set(0));
+        let simplified = merge.simplify();
+        let comparisons = COMPARISONS.with(|count| count.get());
+        assert_eq!(
+            simplified.map(|value| value.0),
+            Merge::from_removes_adds(
+                (1..26).step_by(2).map(|k| 100 + k),
+                [0].into_iter().chain((1..26).step_by(2))
+            )
+        );
+        // Comparing each add with each remove would take hundreds of comparisons
+        assert!(comparisons < 51 * 2, "{comparisons} comparisons");
+    }
+
+    #[test]
+    fn test_simplify_with_labels() {
+        let labels = |n: usize| Merge::from_vec((0..n).map(|i| format!("l{i}")).collect_vec());
+        assert_eq!(
+            c(&[0], &[1, 0]).simplify_with_labels(labels(3)),
+            (c(&[], &[1]), Merge::resolved("l0".to_owned()))
+        );
+        // The add matching the first remove is moved to the first side
+        assert_eq!(
+            c(&[0, 1, 2], &[3, 4, 5, 0]).simplify_with_labels(labels(7)),
+            (
+                c(&[1, 2], &[3, 5, 4]),
+                Merge::from_vec(["l0", "l3", "l4", "l5", "l2"].map(str::to_owned).to_vec())
+            )
+        );
+        // Labels of the wrong size are left as is
+        assert_eq!(
+            c(&[0], &[1, 0]).simplify_with_labels(labels(1)),
+            (c(&[], &[1]), labels(1))
+        );
+    }
+
+    #[test]
+    fn test_collapse_duplicate_diffs() {
+        assert_eq!(c(&[], &[0]).collapse_duplicate_diffs(), c(&[], &[0]));
+        assert_eq!(
+            c(&[0], &[1, 2]).collapse_duplicate_diffs(),
+            c(&[0], &[1, 2])
+        );
+        assert_eq!(
+            c(&[0, 0], &[1, 2, 2]).collapse_duplicate_diffs(),
+            c(&[0], &[1, 2])
+        );
+        assert_eq!(
+            c(&[0, 0], &[1, 2, 3]).collapse_duplicate_diffs(),
+            c(&[0, 0], &[1, 2, 3])
+        );
+        assert_eq!(
+            c(&[0, 1, 0, 1], &[2, 3, 4, 3, 4]).collapse_duplicate_diffs(),
+            c(&[0, 1], &[2, 3, 4])
+        );
+        // Diffs in opposite directions are different
+        assert_eq!(
+            c(&[0, 1], &[2, 1, 0]).collapse_duplicate_diffs(),
+            c(&[0, 1], &[2, 1, 0])
+        );
+    }
+
     #[test]
     fn test_merge_invariants() {
         fn check_invariants(removes: &[u32], adds: &[u32]) {
@@ -972,4 +1291,95 @@ mod tests {
             c(&[3, 2, 1, 6], &[4, 5, 0, 7, 8])
         );
     }
+
+    #[test]
+    fn test_metadata_conflict() {
+        use crate::backend::SymlinkId;
+
+        let file = |hex: &'static str, executable| {
+            Some(TreeValue::File {
+                id: FileId::from_hex(hex),
+                executable,
+            })
+        };
+        let symlink = |hex: &'static str| Some(TreeValue::Symlink(SymlinkId::from_hex(hex)));
+
+        // Both sides added the same file with different executable bits
+        let value = c(&[None], &[file("11", true), file("11", false)]);
+        assert_eq!(
+            value.metadata_conflict(),
+            Some(MetadataConflict::Executable)
+        );
+        assert_eq!(
+            value.resolve_metadata(MetadataResolution::Executable(true)),
+            Some(Merge::normal(file("11", true).unwrap()))
+        );
+        assert_eq!(value.resolve_metadata(MetadataResolution::UseFile), None);
+        // The contents may conflict too
+        let value = c(&[None], &[file("11", true), file("22", false)]);
+        assert_eq!(
+            value.metadata_conflict(),
+            Some(MetadataConflict::Executable)
+        );
+        assert_eq!(
+            value.resolve_metadata(MetadataResolution::Executable(false)),
+            Some(c(&[None], &[file("11", false), file("22", false)]))
+        );
+        // One side made the file executable
+        let value = c(&[file("00", false)], &[file("11", true), file("22", false)]);
+        assert_eq!(value.metadata_conflict(), None);
+        // One side deleted the file
+        let value = c(&[file("00", false)], &[file("11", true), None]);
+        assert_eq!(value.metadata_conflict(), None);
+
+        let value = c(&[None], &[symlink("11"), file("22", false)]);
+        assert_eq!(
+            value.metadata_conflict(),
+            Some(MetadataConflict::SymlinkVsFile)
+        );
+        assert_eq!(
+            value.resolve_metadata(MetadataResolution::UseSymlink),
+            Some(Merge::normal(symlink("11").unwrap()))
+        );
+        assert_eq!(
+            value.resolve_metadata(MetadataResolution::UseFile),
+            Some(Merge::normal(file("22", false).unwrap()))
+        );
+        assert_eq!(
+            value.resolve_metadata(MetadataResolution::Executable(true)),
+            None
+        );
+        // More than one different symlink
+        let value = c(
+            &[None, file("00", false)],
+            &[symlink("11"), file("22", false), symlink("33")],
+        );
+        assert_eq!(value.resolve_metadata(MetadataResolution::UseSymlink), None);
+        assert_eq!(
+            value.resolve_metadata(MetadataResolution::UseFile),
+            Some(Merge::normal(file("22", false).unwrap()))
+        );
+
+        // Only the sides are classified
+        let value = c(&[symlink("00")], &[file("11", false), file("22", false)]);
+        assert_eq!(value.metadata_conflict(), None);
+        let value = c(&[symlink("00")], &[file("11", true), file("22", false)]);
+        assert_eq!(
+            value.metadata_conflict(),
+            Some(MetadataConflict::Executable)
+        );
+        // A side deleted the file, and the other replaced it with a symlink
+        let value = c(&[file("00", false)], &[symlink("11"), None]);
+        assert_eq!(value.metadata_conflict(), None);
+
+        // Conflicts involving directories aren't metadata conflicts
+        let value = c(
+            &[None],
+            &[
+                file("11", false),
+                Some(TreeValue::Tree(TreeId::from_hex("22"))),
+            ],
+        );
+        assert_eq!(value.metadata_conflict(), None);
+    }
 }
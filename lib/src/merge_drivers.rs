@@ -0,0 +1,280 @@
+// Copyright 2024 The Jujutsu Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Merge drivers resolve conflicting changes to a file in a custom way.
+//!
+//! The driver for a path is looked up in the `merge.driver-paths` config
+//! first, then in the `merge` attribute from the `.gitattributes` files.
+//! External drivers are commands configured in `merge.drivers`, which receive
+//! the base and the two sides as temporary files.
+
+#![allow(missing_docs)]
+
+use std::collections::HashMap;
+use std::fs;
+use std::process::{Command, Stdio};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use regex::Regex;
+use thiserror::Error;
+
+use crate::files::{self, MergeResult};
+use crate::gitattributes::PathAttributes;
+use crate::gitignore::glob_path_regex;
+use crate::merge::Merge;
+use crate::repo_path::RepoPath;
+use crate::settings::ConfigResultExt as _;
+
+/// How long an external merge driver may run before it's killed and the file
+/// is merged as usual.
+pub const EXTERNAL_MERGE_DRIVER_TIMEOUT: Duration = Duration::from_secs(60);
+
+#[derive(Debug, Error)]
+pub enum MergeDriverConfigError {
+    #[error(transparent)]
+    Config(#[from] config::ConfigError),
+    #[error("Invalid path pattern '{0}' in merge.driver-paths")]
+    InvalidPattern(String),
+    #[error("Unknown merge driver '{driver}' for path pattern '{pattern}'")]
+    UnknownDriver { pattern: String, driver: String },
+    #[error("Merge driver '{0}' has an empty command")]
+    EmptyCommand(String),
+}
+
+/// A way of merging conflicting changes to a file.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum MergeDriver {
+    /// Merges line by line. This is the default.
+    Text,
+    /// Merges line by line, keeping the lines from all sides of conflicting
+    /// hunks.
+    Union,
+    /// Takes the content of the first side.
+    Ours,
+    /// Takes the content of the last side.
+    Theirs,
+    /// Leaves conflicting changes unresolved.
+    Binary,
+    /// Runs an external command. Falls back to `Text` if the command fails.
+    External(Arc<ExternalMergeDriver>),
+}
+
+impl MergeDriver {
+    fn builtin(name: &str) -> Option<Self> {
+        match name {
+            "text" => Some(MergeDriver::Text),
+            "union" => Some(MergeDriver::Union),
+            "ours" => Some(MergeDriver::Ours),
+            "theirs" => Some(MergeDriver::Theirs),
+            "binary" => Some(MergeDriver::Binary),
+            _ => None,
+        }
+    }
+
+    /// Merges the file contents. Returns `None` if the conflict can't be
+    /// resolved.
+    pub fn merge(&self, contents: &Merge<Vec<u8>>) -> Option<Vec<u8>> {
+        let slices = contents.map(|content| content.as_slice());
+        match self {
+            MergeDriver::Text => match files::merge(&slices) {
+                MergeResult::Resolved(content) => Some(content.0),
+                MergeResult::Conflict(_) => None,
+            },
+            MergeDriver::Union => match files::merge(&slices) {
+                MergeResult::Resolved(content) => Some(content.0),
+                MergeResult::Conflict(hunks) => {
+                    let mut merged = vec![];
+                    for hunk in &hunks {
+                        if let Some(content) = hunk.as_resolved() {
+                            merged.extend_from_slice(&content.0);
+                        } else {
+                            for content in hunk.adds() {
+                                merged.extend_from_slice(&content.0);
+                            }
+                        }
+                    }
+                    Some(merged)
+                }
+            },
+            MergeDriver::Ours => contents.adds().next().cloned(),
+            MergeDriver::Theirs => contents.adds().last().cloned(),
+            MergeDriver::Binary => None,
+            MergeDriver::External(driver) => driver
+                .run(contents)
+                .or_else(|| MergeDriver::Text.merge(contents)),
+        }
+    }
+}
+
+/// A merge command configured in `merge.drivers.<name>`.
+///
+/// The arguments `$base`, `$left`, and `$right` are replaced by the paths of
+/// temporary files with the contents of the base and the two sides, and
+/// `$output` by the path the command should write the result to. The command
+/// should exit with a non-zero status if it can't resolve the conflict.
+#[derive(Debug, Eq, PartialEq)]
+pub struct ExternalMergeDriver {
+    pub name: String,
+    pub command: Vec<String>,
+}
+
+impl ExternalMergeDriver {
+    /// Runs the command on a 2-sided conflict. Returns `None` if the conflict
+    /// has more sides, or if the command fails or times out.
+    fn run(&self, contents: &Merge<Vec<u8>>) -> Option<Vec<u8>> {
+        if contents.num_sides() != 2 {
+            return None;
+        }
+        let dir = tempfile::Builder::new()
+            .prefix("jj-merge-")
+            .tempdir()
+            .ok()?;
+        let mut paths = HashMap::new();
+        for (name, content) in [
+            ("base", contents.get_remove(0)?),
+            ("left", contents.get_add(0)?),
+            ("right", contents.get_add(1)?),
+        ] {
+            let path = dir.path().join(name);
+            fs::write(&path, content).ok()?;
+            paths.insert(name, path.to_str()?.to_owned());
+        }
+        let output_path = dir.path().join("output");
+        paths.insert("output", output_path.to_str()?.to_owned());
+        let mut args = self.command.iter().map(|arg| {
+            paths.iter().fold(arg.clone(), |arg, (name, path)| {
+                arg.replace(&format!("${name}"), path)
+            })
+        });
+        let program = args.next()?;
+
+        let mut child = Command::new(program)
+            .args(args)
+            .current_dir(dir.path())
+            .stdin(Stdio::null())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn()
+            .ok()?;
+        let deadline = Instant::now() + EXTERNAL_MERGE_DRIVER_TIMEOUT;
+        let status = loop {
+            match child.try_wait() {
+                Ok(Some(status)) => break status,
+                Ok(None) if Instant::now() < deadline => thread::sleep(Duration::from_millis(10)),
+                Ok(None) | Err(_) => {
+                    child.kill().ok();
+                    child.wait().ok();
+                    return None;
+                }
+            }
+        };
+        if !status.success() {
+            return None;
+        }
+        fs::read(output_path).ok()
+    }
+}
+
+/// The configured merge drivers.
+#[derive(Clone, Debug, Default)]
+pub struct MergeDrivers {
+    external: HashMap<String, Arc<ExternalMergeDriver>>,
+    /// Path patterns from `merge.driver-paths` and their drivers. Longer
+    /// patterns come first, so they take precedence.
+    path_rules: Vec<(Regex, MergeDriver)>,
+}
+
+impl MergeDrivers {
+    pub fn from_config(config: &config::Config) -> Result<Self, MergeDriverConfigError> {
+        let commands: HashMap<String, Vec<String>> =
+            config.get("merge.drivers").optional()?.unwrap_or_default();
+        let mut external = HashMap::new();
+        for (name, command) in commands {
+            if command.is_empty() {
+                return Err(MergeDriverConfigError::EmptyCommand(name));
+            }
+            let driver = ExternalMergeDriver {
+                name: name.clone(),
+                command,
+            };
+            external.insert(name, Arc::new(driver));
+        }
+        let mut drivers = MergeDrivers {
+            external,
+            path_rules: vec![],
+        };
+
+        let paths: HashMap<String, String> = config
+            .get("merge.driver-paths")
+            .optional()?
+            .unwrap_or_default();
+        let mut path_rules = vec![];
+        for (pattern, name) in paths {
+            let Some(driver) = drivers.named_driver(&name) else {
+                return Err(MergeDriverConfigError::UnknownDriver {
+                    pattern,
+                    driver: name,
+                });
+            };
+            let Some(regex) = glob_path_regex(&pattern) else {
+                return Err(MergeDriverConfigError::InvalidPattern(pattern));
+            };
+            path_rules.push((pattern, regex, driver));
+        }
+        path_rules.sort_by(|(pattern1, ..), (pattern2, ..)| {
+            pattern2
+                .len()
+                .cmp(&pattern1.len())
+                .then(pattern1.cmp(pattern2))
+        });
+        drivers.path_rules = path_rules
+            .into_iter()
+            .map(|(_, regex, driver)| (regex, driver))
+            .collect();
+        Ok(drivers)
+    }
+
+    /// Looks up a driver by name. `builtin:<name>` always refers to a built-in
+    /// driver. Other names refer to an external driver if one is configured,
+    /// or else to the built-in driver of that name.
+    pub fn named_driver(&self, name: &str) -> Option<MergeDriver> {
+        if let Some(name) = name.strip_prefix("builtin:") {
+            return MergeDriver::builtin(name);
+        }
+        match self.external.get(name) {
+            Some(driver) => Some(MergeDriver::External(driver.clone())),
+            None => MergeDriver::builtin(name),
+        }
+    }
+
+    /// Returns the driver for the file at `path`. Unknown driver names in the
+    /// attributes fall back to the text driver, like in Git.
+    pub fn driver_for(&self, path: &RepoPath, attributes: &PathAttributes) -> MergeDriver {
+        let path = path.as_internal_file_string();
+        if let Some((_, driver)) = self
+            .path_rules
+            .iter()
+            .find(|(regex, _)| regex.is_match(&path))
+        {
+            return driver.clone();
+        }
+        match attributes.merge_driver_name.as_deref() {
+            Some(name) => self.named_driver(name).unwrap_or(MergeDriver::Text),
+            None if attributes.is_binary() => MergeDriver::Binary,
+            None => MergeDriver::Text,
+        }
+    }
+}
@@ -25,15 +25,17 @@ use std::{iter, vec};
 use futures::stream::StreamExt;
 use futures::{Future, Stream, TryStreamExt};
 use itertools::Itertools;
+use pollster::FutureExt;
 
 use crate::backend::{BackendError, BackendResult, ConflictId, MergedTreeId, TreeId, TreeValue};
-use crate::matchers::{EverythingMatcher, Matcher};
+use crate::gitattributes::GitAttributes;
+use crate::matchers::{EverythingMatcher, Matcher, Visit};
 use crate::merge::{Merge, MergeBuilder, MergedTreeValue};
 use crate::repo_path::{RepoPath, RepoPathBuf, RepoPathComponent, RepoPathComponentsIter};
 use crate::store::Store;
 use crate::tree::{try_resolve_file_conflict, Tree, TreeMergeError};
 use crate::tree_builder::TreeBuilder;
-use crate::{backend, tree};
+use crate::{backend, conflicts, tree};
 
 /// Presents a view of a merged set of trees.
 #[derive(PartialEq, Eq, Clone, Debug)]
@@ -75,6 +77,16 @@ pub struct DiffSummary {
     pub removed: Vec<RepoPathBuf>,
 }
 
+impl DiffSummary {
+    /// Iterates over all changed paths in sorted order.
+    pub fn paths(&self) -> impl Iterator<Item = &RepoPath> {
+        [&self.modified, &self.added, &self.removed]
+            .into_iter()
+            .kmerge()
+            .map(|path| path.as_ref())
+    }
+}
+
 impl MergedTree {
     /// Creates a new `MergedTree` representing a single tree without conflicts.
     pub fn resolved(tree: Tree) -> Self {
@@ -131,7 +143,7 @@ impl MergedTree {
         let new_trees: Vec<_> = tree_builders
             .into_iter()
             .map(|builder| {
-                let tree_id = builder.write_tree();
+                let tree_id = builder.write_tree()?;
                 store.get_tree(RepoPath::root(), &tree_id)
             })
             .try_collect()?;
@@ -187,7 +199,7 @@ impl MergedTree {
     pub fn resolve(&self) -> Result<Merge<Tree>, TreeMergeError> {
         match self {
             MergedTree::Legacy(tree) => Ok(Merge::resolved(tree.clone())),
-            MergedTree::Merge(trees) => merge_trees(trees),
+            MergedTree::Merge(trees) => merge_trees(trees, &GitAttributes::new(self.clone())),
         }
     }
 
@@ -267,6 +279,76 @@ impl MergedTree {
         }
     }
 
+    /// Returns an equivalent tree without entries pointing at empty trees. See
+    /// `Tree::normalize()`.
+    pub fn normalize(&self) -> BackendResult<MergedTree> {
+        match self {
+            MergedTree::Legacy(tree) => Ok(MergedTree::Legacy(tree.normalize()?)),
+            MergedTree::Merge(trees) => {
+                Ok(MergedTree::Merge(trees.try_map(|tree| tree.normalize())?))
+            }
+        }
+    }
+
+    /// Returns a tree without the entries whose paths contain a reserved
+    /// component such as `.jj` or `.git`. Such entries can't be created by jj,
+    /// but may exist in trees written by older versions.
+    pub fn without_reserved_paths(&self) -> BackendResult<MergedTree> {
+        let reserved_paths = self
+            .entries()
+            .map(|(path, _)| path)
+            .filter(|path| path.has_reserved_component())
+            .collect_vec();
+        if reserved_paths.is_empty() {
+            return Ok(self.clone());
+        }
+        let mut tree_builder = MergedTreeBuilder::new(self.id());
+        for path in reserved_paths {
+            tree_builder.set_or_remove(path, Merge::absent());
+        }
+        let tree_id = tree_builder.write_tree(self.store())?;
+        self.store().get_root_tree(&tree_id)
+    }
+
+    /// Collapses the conflicts which have more than `max_sides` sides after
+    /// simplification, so they can still be materialized readably. Returns the
+    /// new tree and the paths of the collapsed conflicts.
+    ///
+    /// Identical diffs are dropped first. If that isn't enough, a conflict
+    /// `A+(B-C)+(D-E)+...` becomes the 2-sided conflict `A+(X-C)`, where `X`
+    /// is a file containing the rest of the conflict `B+(D-E)+...`,
+    /// materialized with conflict markers. Legacy trees are returned as is.
+    pub fn limit_conflict_sides(
+        &self,
+        max_sides: usize,
+    ) -> BackendResult<(MergedTree, Vec<RepoPathBuf>)> {
+        let max_sides = max(max_sides, 2);
+        match self {
+            MergedTree::Merge(trees) if trees.num_sides() > max_sides => {}
+            _ => return Ok((self.clone(), vec![])),
+        }
+        let store = self.store();
+        // The arity of the tree is the maximum arity of its entries, so all
+        // entries have to be written on top of an empty tree.
+        let mut tree_builder = MergedTreeBuilder::new(store.empty_merged_tree_id());
+        let mut collapsed_paths = vec![];
+        for (path, value) in self.entries() {
+            let value = value.simplify();
+            let value = if value.num_sides() > max_sides {
+                collapsed_paths.push(path.clone());
+                collapse_conflict(store, &path, value, max_sides)?
+            } else {
+                value
+            };
+            tree_builder.set_or_remove(path, value);
+        }
+        if collapsed_paths.is_empty() {
+            return Ok((self.clone(), vec![]));
+        }
+        let tree_id = tree_builder.write_tree(store)?;
+        Ok((store.get_root_tree(&tree_id)?, collapsed_paths))
+    }
+
     fn sub_tree_recursive(&self, mut components: RepoPathComponentsIter) -> Option<MergedTree> {
         if let Some(first) = components.next() {
             components.try_fold(self.sub_tree(first)?, |tree, name| tree.sub_tree(name))
@@ -367,11 +449,31 @@ impl MergedTree {
         base: &MergedTree,
         other: &MergedTree,
     ) -> Result<MergedTree, TreeMergeError> {
+        let no_labels = || Merge::resolved(String::new());
+        let (tree, _) =
+            self.merge_with_labels(base, other, [no_labels(), no_labels(), no_labels()])?;
+        Ok(tree)
+    }
+
+    /// Like `merge()`, but also returns labels describing where each term of
+    /// the result came from. `labels` has the labels of the terms of `self`,
+    /// `base`, and `other`. Labels that don't have a label for each term of
+    /// their tree are replaced by empty labels. Legacy trees only get empty
+    /// labels.
+    pub fn merge_with_labels(
+        &self,
+        base: &MergedTree,
+        other: &MergedTree,
+        labels: [Merge<String>; 3],
+    ) -> Result<(MergedTree, Merge<String>), TreeMergeError> {
         if let (MergedTree::Legacy(this), MergedTree::Legacy(base), MergedTree::Legacy(other)) =
             (self, base, other)
         {
             let merged_tree = tree::merge_trees(this, base, other)?;
-            Ok(MergedTree::legacy(merged_tree))
+            Ok((
+                MergedTree::legacy(merged_tree),
+                Merge::resolved(String::new()),
+            ))
         } else {
             // Convert legacy trees to merged trees and unwrap to `Merge<Tree>`
             let to_merge = |tree: &MergedTree| -> Result<Merge<Tree>, TreeMergeError> {
@@ -387,20 +489,39 @@ impl MergedTree {
                 }
             };
             let nested = Merge::from_vec(vec![to_merge(self)?, to_merge(base)?, to_merge(other)?]);
-            let tree = merge_trees(&nested.flatten().simplify())?;
+            let nested_labels = zip(nested.iter(), labels)
+                .map(|(trees, labels)| {
+                    if labels.iter().len() == trees.iter().len() {
+                        labels
+                    } else {
+                        trees.map(|_| String::new())
+                    }
+                })
+                .collect_vec();
+            let nested_labels = Merge::from_vec(nested_labels);
+            let attributes = GitAttributes::new(self.clone());
+            let (trees, labels) = nested
+                .flatten()
+                .simplify_with_labels(nested_labels.flatten());
+            let tree = merge_trees(&trees, &attributes)?;
+            let labels = if tree.is_resolved() {
+                Merge::resolved(String::new())
+            } else {
+                labels
+            };
             // If the result can be resolved, then `merge_trees()` above would have returned
             // a resolved merge. However, that function will always preserve the arity of
             // conflicts it cannot resolve. So we simplify the conflict again
             // here to possibly reduce a complex conflict to a simpler one.
-            let tree = tree.simplify();
+            let (tree, labels) = tree.simplify_with_labels(labels);
             // If debug assertions are enabled, check that the merge was idempotent. In
             // particular,  that this last simplification doesn't enable further automatic
             // resolutions
             if cfg!(debug_assertions) {
-                let re_merged = merge_trees(&tree).unwrap();
+                let re_merged = merge_trees(&tree, &attributes).unwrap();
                 debug_assert_eq!(re_merged, tree);
             }
-            Ok(MergedTree::Merge(tree))
+            Ok((MergedTree::Merge(tree), labels))
         }
     }
 }
@@ -475,7 +596,10 @@ fn trees_value<'a>(trees: &'a Merge<Tree>, basename: &RepoPathComponent) -> Merg
     MergedTreeVal::Conflict(value.map(|x| x.cloned()))
 }
 
-fn merge_trees(merge: &Merge<Tree>) -> Result<Merge<Tree>, TreeMergeError> {
+fn merge_trees(
+    merge: &Merge<Tree>,
+    attributes: &GitAttributes,
+) -> Result<Merge<Tree>, TreeMergeError> {
     if let Some(tree) = merge.resolve_trivial() {
         return Ok(Merge::resolved(tree.clone()));
     }
@@ -491,7 +615,7 @@ fn merge_trees(merge: &Merge<Tree>) -> Result<Merge<Tree>, TreeMergeError> {
     for basename in all_tree_basenames(merge) {
         let path_merge = merge.map(|tree| tree.value(basename).cloned());
         let path = dir.join(basename);
-        let path_merge = merge_tree_values(store, &path, path_merge)?;
+        let path_merge = merge_tree_values(store, &path, path_merge, attributes)?;
         match path_merge.into_resolved() {
             Ok(value) => {
                 new_tree.set_or_remove(basename, value);
@@ -501,6 +625,7 @@ fn merge_trees(merge: &Merge<Tree>) -> Result<Merge<Tree>, TreeMergeError> {
             }
         };
     }
+    new_tree.normalize(store.empty_tree_id());
     if conflicts.is_empty() {
         let new_tree_id = store.write_tree(dir, new_tree)?;
         Ok(Merge::resolved(new_tree_id))
@@ -514,7 +639,9 @@ fn merge_trees(merge: &Merge<Tree>) -> Result<Merge<Tree>, TreeMergeError> {
             for (basename, path_conflict) in &mut conflicts {
                 new_tree.set_or_remove(basename, path_conflict.next().unwrap());
             }
-            let tree = store.write_tree(dir, new_tree.clone())?;
+            let mut side_tree = new_tree.clone();
+            side_tree.normalize(store.empty_tree_id());
+            let tree = store.write_tree(dir, side_tree)?;
             new_trees.push(tree);
         }
         Ok(Merge::from_vec(new_trees))
@@ -529,26 +656,41 @@ fn merge_tree_values(
     store: &Arc<Store>,
     path: &RepoPath,
     values: MergedTreeValue,
+    attributes: &GitAttributes,
 ) -> Result<MergedTreeValue, TreeMergeError> {
     if let Some(resolved) = values.resolve_trivial() {
         return Ok(Merge::resolved(resolved.clone()));
     }
 
+    // Trees canceling each other don't need to be read. If the remaining trees
+    // can be merged cleanly, we're done. Otherwise, we merge all the trees below
+    // in order to preserve the arity of the conflict.
+    let simplified = values.clone().simplify();
+    if simplified.num_sides() < values.num_sides() {
+        if let Some(trees) = simplified.to_tree_merge(store, path)? {
+            let empty_tree_id = store.empty_tree_id();
+            if let Some(tree) = merge_trees(&trees, attributes)?.as_resolved() {
+                return Ok(Merge::resolved(
+                    (tree.id() != empty_tree_id).then(|| TreeValue::Tree(tree.id().clone())),
+                ));
+            }
+        }
+    }
+
     if let Some(trees) = values.to_tree_merge(store, path)? {
         // If all sides are trees or missing, merge the trees recursively, treating
         // missing trees as empty.
         let empty_tree_id = store.empty_tree_id();
-        let merged_tree = merge_trees(&trees)?;
+        let merged_tree = merge_trees(&trees, attributes)?;
         Ok(merged_tree
             .map(|tree| (tree.id() != empty_tree_id).then(|| TreeValue::Tree(tree.id().clone()))))
     } else {
         // Try to resolve file conflicts by merging the file contents. Treats missing
         // files as empty. The values may contain trees canceling each other (notably
-        // padded absent trees), so we need to simplify them first.
-        let simplified = values.clone().simplify();
+        // padded absent trees), so we need to use the simplified values.
         // No fast path for simplified.is_resolved(). If it could be resolved, it would
         // have been caught by values.resolve_trivial() above.
-        if let Some(resolved) = try_resolve_file_conflict(store, path, &simplified)? {
+        if let Some(resolved) = try_resolve_file_conflict(store, path, &simplified, attributes)? {
             Ok(Merge::normal(resolved))
         } else {
             // Failed to merge the files, or the paths are not files
@@ -569,18 +711,20 @@ struct TreeEntriesDirItem {
 }
 
 impl TreeEntriesDirItem {
-    fn new(tree: MergedTree, matcher: &dyn Matcher) -> Self {
+    /// Lists the entries of `tree` that `visit`, the matcher's `Visit` of the
+    /// tree's directory, includes. Subtrees are checked with the matcher when
+    /// they're visited.
+    fn new(tree: MergedTree, visit: &Visit, matcher: &dyn Matcher) -> Self {
         let mut entries = vec![];
         let dir = tree.dir();
         for name in tree.names() {
-            let path = dir.join(name);
             let value = tree.value(name).to_merge();
-            if value.is_tree() {
-                // TODO: Handle the other cases (specific files and trees)
-                if matcher.visit(&path).is_nothing() {
-                    continue;
-                }
-            } else if !matcher.matches(&path) {
+            let is_dir = value.is_tree();
+            if !visit.may_include(name, is_dir) {
+                continue;
+            }
+            let path = dir.join(name);
+            if !is_dir && !matcher.matches(&path) {
                 continue;
             }
             entries.push((path, value));
@@ -592,8 +736,9 @@ impl TreeEntriesDirItem {
 
 impl<'matcher> TreeEntriesIterator<'matcher> {
     fn new(tree: MergedTree, matcher: &'matcher dyn Matcher) -> Self {
+        let visit = matcher.visit(tree.dir());
         Self {
-            stack: vec![TreeEntriesDirItem::new(tree, matcher)],
+            stack: vec![TreeEntriesDirItem::new(tree, &visit, matcher)],
             matcher,
         }
     }
@@ -606,13 +751,17 @@ impl Iterator for TreeEntriesIterator<'_> {
         while let Some(top) = self.stack.last_mut() {
             if let Some((path, value)) = top.entries.pop() {
                 if value.is_tree() {
+                    let visit = self.matcher.visit(&path);
+                    if visit.is_nothing() {
+                        continue;
+                    }
                     let tree_merge = value
                         .to_tree_merge(top.tree.store(), &path)
                         .unwrap()
                         .unwrap();
                     let merged_tree = MergedTree::Merge(tree_merge);
                     self.stack
-                        .push(TreeEntriesDirItem::new(merged_tree, self.matcher));
+                        .push(TreeEntriesDirItem::new(merged_tree, &visit, self.matcher));
                 } else {
                     return Some((path, value));
                 }
@@ -721,6 +870,8 @@ impl Iterator for ConflictIterator {
 pub struct TreeDiffIterator<'matcher> {
     stack: Vec<TreeDiffItem>,
     matcher: &'matcher dyn Matcher,
+    /// Path of the last yielded item.
+    cursor: Option<RepoPathBuf>,
 }
 
 struct TreeDiffDirItem {
@@ -748,7 +899,78 @@ impl<'matcher> TreeDiffIterator<'matcher> {
                 root_dir, tree1, tree2, matcher,
             )));
         };
-        Self { stack, matcher }
+        Self {
+            stack,
+            matcher,
+            cursor: None,
+        }
+    }
+
+    /// Creates an iterator over the differences between two trees, skipping
+    /// all differences up to and including the one at `cursor` in the
+    /// iteration order. If there's no difference at `cursor`, the differences
+    /// that would be yielded before a file at `cursor` are skipped.
+    ///
+    /// Only the trees along the `cursor` path are read to find the position,
+    /// so this is cheap compared to iterating over the skipped differences.
+    /// The `cursor` is typically the path returned by `cursor()`.
+    pub fn new_after(
+        tree1: MergedTree,
+        tree2: MergedTree,
+        matcher: &'matcher dyn Matcher,
+        cursor: &RepoPath,
+    ) -> BackendResult<Self> {
+        let mut iter = Self::new(tree1, tree2, matcher);
+        let mut dir_path = RepoPathBuf::root();
+        let mut components = cursor.components().peekable();
+        while let Some(name) = components.next() {
+            let Some(TreeDiffItem::Dir(dir)) = iter.stack.last_mut() else {
+                break;
+            };
+            let target = dir_path.join(name);
+            while dir
+                .entries
+                .last()
+                .is_some_and(|(path, _, _)| *path < target)
+            {
+                dir.entries.pop();
+            }
+            if !dir
+                .entries
+                .last()
+                .is_some_and(|(path, _, _)| *path == target)
+            {
+                break;
+            }
+            let (path, before, after) = dir.entries.pop().unwrap();
+            let tree_before = before.is_tree();
+            let tree_after = after.is_tree();
+            let file_after_dir = tree_before && !tree_after && after.is_present();
+            // A file is skipped along with everything inside it. The file which
+            // replaces a directory is yielded after the directory contents, so
+            // it's skipped along with the contents if it's at the cursor.
+            if (!tree_before && !tree_after) || (file_after_dir && components.peek().is_none()) {
+                break;
+            }
+            // A file replaced by a directory is yielded before the directory
+            // contents, so it's skipped by not yielding it here.
+            let before_tree = Self::tree(&dir.tree1, &path, &before)?;
+            let after_tree = Self::tree(&dir.tree2, &path, &after)?;
+            let subdir = TreeDiffDirItem::from_trees(&path, before_tree, after_tree, matcher);
+            if file_after_dir {
+                iter.stack
+                    .push(TreeDiffItem::File(path.clone(), Merge::absent(), after));
+            }
+            iter.stack.push(TreeDiffItem::Dir(subdir));
+            dir_path = path;
+        }
+        Ok(iter)
+    }
+
+    /// Path of the last yielded difference, which can be passed to
+    /// `new_after()` to resume the iteration from there.
+    pub fn cursor(&self) -> Option<&RepoPath> {
+        self.cursor.as_deref()
     }
 
     fn single_tree(
@@ -789,8 +1011,32 @@ impl TreeDiffDirItem {
         tree2: MergedTree,
         matcher: &dyn Matcher,
     ) -> Self {
+        // When one side is empty, every entry on the other side is added or
+        // removed, so there's no need to merge the two lists of names. This
+        // is common when a directory is added or removed, and on the initial
+        // checkout.
+        let entries = if is_empty_tree(&tree1) {
+            Self::one_sided_entries(dir, &tree2, matcher, |value| (Merge::absent(), value))
+        } else if is_empty_tree(&tree2) {
+            Self::one_sided_entries(dir, &tree1, matcher, |value| (value, Merge::absent()))
+        } else {
+            Self::entries(dir, &tree1, &tree2, matcher)
+        };
+        Self {
+            tree1,
+            tree2,
+            entries,
+        }
+    }
+
+    fn entries(
+        dir: &RepoPath,
+        tree1: &MergedTree,
+        tree2: &MergedTree,
+        matcher: &dyn Matcher,
+    ) -> Vec<(RepoPathBuf, MergedTreeValue, MergedTreeValue)> {
         let mut entries = vec![];
-        for (name, before, after) in merged_tree_entry_diff(&tree1, &tree2) {
+        for (name, before, after) in merged_tree_entry_diff(tree1, tree2) {
             let path = dir.join(name);
             let before = before.to_merge();
             let after = after.to_merge();
@@ -818,11 +1064,46 @@ impl TreeDiffDirItem {
             entries.push((path, before, after));
         }
         entries.reverse();
-        Self {
-            tree1,
-            tree2,
-            entries,
+        entries
+    }
+
+    /// Lists the entries of `tree` which match `matcher`, paired with an absent
+    /// value on the other side by `to_diff`.
+    fn one_sided_entries(
+        dir: &RepoPath,
+        tree: &MergedTree,
+        matcher: &dyn Matcher,
+        to_diff: impl Fn(MergedTreeValue) -> (MergedTreeValue, MergedTreeValue),
+    ) -> Vec<(RepoPathBuf, MergedTreeValue, MergedTreeValue)> {
+        let mut entries = vec![];
+        for name in tree.names() {
+            let value = tree.value(name).to_merge();
+            if value.is_absent() {
+                continue;
+            }
+            let path = dir.join(name);
+            let matches = if value.is_tree() {
+                !matcher.visit(&path).is_nothing()
+            } else {
+                matcher.matches(&path)
+            };
+            if matches {
+                let (before, after) = to_diff(value);
+                entries.push((path, before, after));
+            }
         }
+        entries.reverse();
+        entries
+    }
+}
+
+/// Whether `tree` is resolved and has no entries.
+fn is_empty_tree(tree: &MergedTree) -> bool {
+    match tree {
+        MergedTree::Legacy(tree) => tree.data().is_empty(),
+        MergedTree::Merge(trees) => trees
+            .as_resolved()
+            .is_some_and(|tree| tree.data().is_empty()),
     }
 }
 
@@ -833,6 +1114,19 @@ impl Iterator for TreeDiffIterator<'_> {
     );
 
     fn next(&mut self) -> Option<Self::Item> {
+        let item = self.next_item()?;
+        self.cursor = Some(item.0.clone());
+        Some(item)
+    }
+}
+
+impl TreeDiffIterator<'_> {
+    fn next_item(
+        &mut self,
+    ) -> Option<(
+        RepoPathBuf,
+        BackendResult<(MergedTreeValue, MergedTreeValue)>,
+    )> {
         while let Some(top) = self.stack.last_mut() {
             let (dir, (path, before, after)) = match top {
                 TreeDiffItem::Dir(dir) => match dir.entries.pop() {
@@ -1151,6 +1445,38 @@ impl Stream for TreeDiffStreamImpl<'_> {
     }
 }
 
+/// Reduces a conflict with more than `max_sides` sides to at most
+/// `max_sides` sides. See `MergedTree::limit_conflict_sides()`.
+fn collapse_conflict(
+    store: &Arc<Store>,
+    path: &RepoPath,
+    value: MergedTreeValue,
+    max_sides: usize,
+) -> BackendResult<MergedTreeValue> {
+    let value = value.collapse_duplicate_diffs();
+    if value.num_sides() <= max_sides {
+        return Ok(value);
+    }
+    let mut terms = value.into_iter();
+    let first = terms.next().unwrap();
+    let base = terms.next().unwrap();
+    let rest = Merge::from_vec(terms.collect_vec());
+    let rest_value = if let Some(resolved) = rest.resolve_trivial() {
+        resolved.clone()
+    } else {
+        let mut contents = vec![];
+        conflicts::materialize(&rest, store, path, &mut contents)
+            .block_on()
+            .map_err(|err| BackendError::Other(err.into()))?;
+        let id = store.write_file(path, &mut contents.as_slice())?;
+        Some(TreeValue::File {
+            id,
+            executable: false,
+        })
+    };
+    Ok(Merge::from_vec(vec![first, base, rest_value]))
+}
+
 /// Helps with writing trees with conflicts. You start by creating an instance
 /// of this type with one or more base trees. You then add overrides on top. The
 /// overrides may be conflicts. Then you can write the result as a legacy tree
@@ -1210,7 +1536,7 @@ impl MergedTreeBuilder {
                         }
                     }
                 }
-                let legacy_id = tree_builder.write_tree();
+                let legacy_id = tree_builder.write_tree()?;
                 if store.use_tree_conflict_format() {
                     let legacy_tree = store.get_tree(RepoPath::root(), &legacy_id)?;
                     let merged_tree = MergedTree::from_legacy_tree(legacy_tree)?;
@@ -1266,7 +1592,7 @@ impl MergedTreeBuilder {
         let merge_builder: MergeBuilder<TreeId> = tree_builders
             .into_iter()
             .map(|builder| builder.write_tree())
-            .collect();
+            .try_collect()?;
         Ok(merge_builder.build())
     }
 }
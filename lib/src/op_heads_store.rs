@@ -17,6 +17,7 @@
 use std::any::Any;
 use std::collections::HashSet;
 use std::fmt::Debug;
+use std::path::Path;
 use std::sync::Arc;
 
 use itertools::Itertools;
@@ -25,6 +26,7 @@ use thiserror::Error;
 use crate::dag_walk;
 use crate::op_store::{OpStore, OpStoreError, OperationId};
 use crate::operation::Operation;
+use crate::repo::RepoReadOnly;
 
 #[derive(Debug, Error)]
 pub enum OpHeadResolutionError {
@@ -54,8 +56,51 @@ pub trait OpHeadsStore: Send + Sync + Debug {
     fn lock(&self) -> Box<dyn OpHeadsStoreLock + '_>;
 }
 
-// Given an OpHeadsStore, fetch and resolve its op heads down to one under a
-// lock.
+/// Resolves the op heads without taking the lock or writing anything, for
+/// repos loaded in read-only mode. Op heads that are ancestors of other op
+/// heads are ignored. Concurrent operations can't be merged without writing
+/// the merge operation, so `RepoReadOnly` is returned if there are several.
+pub fn resolve_op_heads_read_only<E>(
+    op_heads_store: &dyn OpHeadsStore,
+    op_store: &Arc<dyn OpStore>,
+    repo_path: &Path,
+) -> Result<Operation, E>
+where
+    E: From<OpHeadResolutionError> + From<OpStoreError> + From<RepoReadOnly>,
+{
+    let op_heads: Vec<_> = op_heads_store
+        .get_op_heads()
+        .into_iter()
+        .map(|op_id| -> Result<Operation, OpStoreError> {
+            let data = op_store.read_operation(&op_id)?;
+            Ok(Operation::new(op_store.clone(), op_id, data))
+        })
+        .try_collect()?;
+    let mut op_heads = dag_walk::heads_ok(
+        op_heads.into_iter().map(Ok),
+        |op: &Operation| op.id().clone(),
+        |op: &Operation| op.parents().collect_vec(),
+    )?
+    .into_iter()
+    .collect_vec();
+    match op_heads.len() {
+        0 => Err(OpHeadResolutionError::NoHeads.into()),
+        1 => Ok(op_heads.pop().unwrap()),
+        _ => Err(RepoReadOnly {
+            repo_path: repo_path.to_owned(),
+        }
+        .into()),
+    }
+}
+
+// Given an OpHeadsStore, fetch and resolve its op heads down to one.
+//
+// Loading the repo doesn't take the lock unless there are divergent operations
+// to merge. A writer publishes an operation by adding the new head before
+// removing the old ones, so a reader may see the old heads along with the new
+// one, but never a set of heads that misses the latest operation. Heads that
+// are ancestors of other heads are stale and can be ignored (and removed)
+// without coordinating with other processes.
 //
 // This routine is defined outside the trait because it must support generics.
 pub fn resolve_op_heads<E>(
@@ -79,10 +124,21 @@ where
         return Ok(Operation::new(op_store.clone(), operation_id, operation));
     }
 
-    // There are multiple heads. We take a lock, then check if there are still
+    // Several heads are usually a writer in the middle of replacing its
+    // parent head, or one that was interrupted doing so. That doesn't need the
+    // lock.
+    let (mut op_heads, ancestor_op_heads) = read_op_heads_without_ancestors(op_store, &op_heads)?;
+    if let [op_head] = &*op_heads {
+        if !ancestor_op_heads.is_empty() {
+            op_heads_store.update_op_heads(&ancestor_op_heads, op_head.id());
+        }
+        return Ok(op_heads.pop().unwrap());
+    }
+
+    // There are divergent heads. We take a lock, then check if there are still
     // multiple heads (it's likely that another process was in the process of
-    // deleting on of them). If there are still multiple heads, we attempt to
-    // merge all the views into one. We then write that view and a corresponding
+    // merging them). If there are still multiple heads, we attempt to merge
+    // all the views into one. We then write that view and a corresponding
     // operation to the op-store.
     // Note that the locking isn't necessary for correctness; we take the lock
     // only to prevent other concurrent processes from doing the same work (and
@@ -100,6 +156,29 @@ where
         return Ok(Operation::new(op_store.clone(), op_head_id, op_head));
     }
 
+    let (mut op_heads, ancestor_op_heads) =
+        read_op_heads_without_ancestors(op_store, &op_head_ids)?;
+
+    // Return without creating a merge operation
+    if let [op_head] = &*op_heads {
+        op_heads_store.update_op_heads(&ancestor_op_heads, op_head.id());
+        return Ok(op_head.clone());
+    }
+
+    op_heads.sort_by_key(|op| op.store_operation().metadata.end_time.timestamp.clone());
+    let new_op = resolver(op_heads)?;
+    let mut old_op_heads = ancestor_op_heads;
+    old_op_heads.extend_from_slice(new_op.parent_ids());
+    op_heads_store.update_op_heads(&old_op_heads, new_op.id());
+    Ok(new_op)
+}
+
+/// Reads the given op heads, and splits them into the ones that aren't
+/// ancestors of other op heads and the ids of the ones that are.
+fn read_op_heads_without_ancestors(
+    op_store: &Arc<dyn OpStore>,
+    op_head_ids: &[OperationId],
+) -> Result<(Vec<Operation>, Vec<OperationId>), OpStoreError> {
     let op_heads: Vec<_> = op_head_ids
         .iter()
         .map(|op_id: &OperationId| -> Result<Operation, OpStoreError> {
@@ -121,18 +200,8 @@ where
         .difference(&op_head_ids_after)
         .cloned()
         .collect_vec();
-    let mut op_heads = filtered_op_heads.into_iter().collect_vec();
-
-    // Return without creating a merge operation
-    if let [op_head] = &*op_heads {
-        op_heads_store.update_op_heads(&ancestor_op_heads, op_head.id());
-        return Ok(op_head.clone());
-    }
-
-    op_heads.sort_by_key(|op| op.store_operation().metadata.end_time.timestamp.clone());
-    let new_op = resolver(op_heads)?;
-    let mut old_op_heads = ancestor_op_heads;
-    old_op_heads.extend_from_slice(new_op.parent_ids());
-    op_heads_store.update_op_heads(&old_op_heads, new_op.id());
-    Ok(new_op)
+    Ok((
+        filtered_op_heads.into_iter().collect_vec(),
+        ancestor_op_heads,
+    ))
 }
@@ -353,24 +353,38 @@ pub(crate) fn flatten_remote_branches(
         .kmerge_by(|(full_name1, _), (full_name2, _)| full_name1 < full_name2)
 }
 
-content_hash! {
-    /// Represents an operation (transaction) on the repo view, just like how a
-    /// Commit object represents an operation on the tree.
-    ///
-    /// Operations and views are not meant to be exchanged between repos or users;
-    /// they represent local state and history.
-    ///
-    /// The operation history will almost always be linear. It will only have
-    /// forks when parallel operations occurred. The parent is determined when
-    /// the transaction starts. When the transaction commits, a lock will be
-    /// taken and it will be checked that the current head of the operation
-    /// graph is unchanged. If the current head has changed, there has been
-    /// concurrent operation.
-    #[derive(PartialEq, Eq, Clone, Debug)]
-    pub struct Operation {
-        pub view_id: ViewId,
-        pub parents: Vec<OperationId>,
-        pub metadata: OperationMetadata,
+/// Represents an operation (transaction) on the repo view, just like how a
+/// Commit object represents an operation on the tree.
+///
+/// Operations and views are not meant to be exchanged between repos or users;
+/// they represent local state and history.
+///
+/// The operation history will almost always be linear. It will only have
+/// forks when parallel operations occurred. The parent is determined when
+/// the transaction starts. When the transaction commits, a lock will be
+/// taken and it will be checked that the current head of the operation
+/// graph is unchanged. If the current head has changed, there has been
+/// concurrent operation.
+#[derive(PartialEq, Eq, Clone, Debug)]
+pub struct Operation {
+    pub view_id: ViewId,
+    pub parents: Vec<OperationId>,
+    pub metadata: OperationMetadata,
+    /// Operations this operation was recorded in place of (see
+    /// `Transaction::replace_base_operation()`). They aren't ancestors, but
+    /// are kept reachable so they can still be restored.
+    pub replaced: Vec<OperationId>,
+}
+
+impl ContentHash for Operation {
+    fn hash(&self, state: &mut impl digest::Update) {
+        self.view_id.hash(state);
+        self.parents.hash(state);
+        self.metadata.hash(state);
+        // Only hashed if present so the ids of existing operations don't change
+        if !self.replaced.is_empty() {
+            self.replaced.hash(state);
+        }
     }
 }
 
@@ -392,6 +406,7 @@ impl Operation {
             view_id: empty_view_id,
             parents: vec![],
             metadata,
+            replaced: vec![],
         }
     }
 }
@@ -460,9 +475,24 @@ pub trait OpStore: Send + Sync + Debug {
     /// All operations and views reachable from the `head_ids` won't be
     /// removed. In addition to that, objects created after `keep_newer` will be
     /// preserved. This mitigates a risk of deleting new heads created
-    /// concurrently by another process.
-    // TODO: return stats?
-    fn gc(&self, head_ids: &[OperationId], keep_newer: SystemTime) -> OpStoreResult<()>;
+    /// concurrently by another process. Returns the number of pruned objects.
+    fn gc(&self, head_ids: &[OperationId], keep_newer: SystemTime)
+        -> OpStoreResult<OpStoreGcStats>;
+
+    /// Counts the operations and views `gc()` would prune, without removing
+    /// anything.
+    fn gc_dry_run(
+        &self,
+        head_ids: &[OperationId],
+        keep_newer: SystemTime,
+    ) -> OpStoreResult<OpStoreGcStats>;
+}
+
+/// Number of objects pruned (or that would be pruned) by `OpStore::gc()`.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct OpStoreGcStats {
+    pub num_operations: usize,
+    pub num_views: usize,
 }
 
 #[cfg(test)]
@@ -22,6 +22,8 @@ use std::sync::Arc;
 use itertools::Itertools as _;
 use thiserror::Error;
 
+use crate::backend::CommitId;
+use crate::index::Index;
 use crate::object_id::{HexPrefix, PrefixResolution};
 use crate::op_heads_store::{OpHeadResolutionError, OpHeadsStore};
 use crate::op_store::{OpStore, OpStoreError, OpStoreResult, OperationId};
@@ -215,7 +217,9 @@ pub fn walk_ancestors(head_ops: &[Operation]) -> impl Iterator<Item = OpStoreRes
         .collect_vec();
     head_ops.sort_unstable_by(|op1, op2| op1.cmp(op2).reverse());
     // Lazily load operations based on timestamp-based heuristic. This works so long
-    // as the operation history is mostly linear.
+    // as the operation history is mostly linear. Operations don't end before their
+    // parents even if the clock went backwards, since the end times are clamped
+    // when the operations are written.
     dag_walk::topo_order_reverse_lazy_ok(
         head_ops.into_iter().map(Ok),
         |OperationByEndTime(op)| op.id().clone(),
@@ -224,6 +228,73 @@ pub fn walk_ancestors(head_ops: &[Operation]) -> impl Iterator<Item = OpStoreRes
     .map_ok(|OperationByEndTime(op)| op)
 }
 
+/// Walks at most `limit` operations from `head_ops`, newest first, and returns
+/// the first one in which any of the `commit_ids` was visible, along with the
+/// ones that were visible in it.
+///
+/// Visibility is decided by looking up the heads of each operation's view in
+/// the `index`, so no commits are read. Heads that aren't in the `index` are
+/// ignored.
+pub fn find_op_with_visible_commits(
+    head_ops: &[Operation],
+    index: &dyn Index,
+    commit_ids: &[CommitId],
+    limit: usize,
+) -> OpStoreResult<Option<(Operation, Vec<CommitId>)>> {
+    for op in walk_ancestors(head_ops).take(limit) {
+        let op = op?;
+        let view = op.view()?;
+        let heads = view
+            .heads()
+            .iter()
+            .filter(|id| index.has_id(id))
+            .collect_vec();
+        let visible_ids = commit_ids
+            .iter()
+            .filter(|id| heads.iter().any(|head| index.is_ancestor(id, head)))
+            .cloned()
+            .collect_vec();
+        if !visible_ids.is_empty() {
+            return Ok(Some((op, visible_ids)));
+        }
+    }
+    Ok(None)
+}
+
+/// Predicate over the tags in operation metadata. An operation matches if it
+/// has every tag in the filter with the same value. An empty filter matches
+/// all operations, including ones recorded without tags.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct OperationTagFilter {
+    tags: Vec<(String, String)>,
+}
+
+impl OperationTagFilter {
+    /// Creates a filter matching all operations.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Restricts the filter to operations with the tag `key` set to `value`.
+    pub fn with_tag(mut self, key: impl Into<String>, value: impl Into<String>) -> Self {
+        self.tags.push((key.into(), value.into()));
+        self
+    }
+
+    /// Whether the filter matches all operations.
+    pub fn is_empty(&self) -> bool {
+        self.tags.is_empty()
+    }
+
+    /// Whether `op` has all the tags of the filter.
+    pub fn matches(&self, op: &Operation) -> bool {
+        let op_tags = &op.store_operation().metadata.tags;
+        self.tags
+            .iter()
+            .all(|(key, value)| op_tags.get(key) == Some(value))
+    }
+}
+
 /// Stats about `reparent_range()`.
 #[derive(Clone, Debug, Eq, PartialEq)]
 pub struct ReparentStats {
@@ -0,0 +1,873 @@
+// Copyright 2024 The Jujutsu Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Parses patches in the unified diff format and applies them to trees.
+//!
+//! Patches can come from `git format-patch`, Mercurial (`hg export` and mq),
+//! quilt, or plain `diff -u`. The text before the first file's diff is the
+//! patch's description. Paths have their first component stripped, like with
+//! `patch -p1`, except in Git's rename headers, which have no prefix.
+
+#![allow(missing_docs)]
+
+use std::collections::BTreeMap;
+use std::io::Read;
+use std::sync::Arc;
+
+use thiserror::Error;
+
+use crate::backend::{BackendError, BackendResult, MergedTreeId, ObjectKind, TreeValue};
+use crate::diff::find_line_ranges;
+use crate::merge::Merge;
+use crate::merged_tree::{MergedTree, MergedTreeBuilder};
+use crate::object_id::ObjectId;
+use crate::repo_path::{RepoPath, RepoPathBuf};
+use crate::store::Store;
+
+/// A parsed patch: a description and changes to any number of files.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct Patch {
+    /// The text before the diffs, with mail and Mercurial headers removed.
+    /// Ends with a newline unless it's empty.
+    pub description: String,
+    pub files: Vec<FilePatch>,
+}
+
+/// The changes to one file.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct FilePatch {
+    /// The path of the file before the change, or `None` if the file is added.
+    pub old_path: Option<RepoPathBuf>,
+    /// The path of the file after the change, or `None` if the file is
+    /// deleted.
+    pub new_path: Option<RepoPathBuf>,
+    /// Whether the file is executable after the change, if the patch says.
+    pub new_executable: Option<bool>,
+    pub hunks: Vec<Hunk>,
+}
+
+/// A hunk of changes, starting with a `@@` line.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Hunk {
+    /// The 1-based line where the hunk starts in the old file, or the line
+    /// after which it inserts its lines if it has no lines from the old file.
+    pub old_start: usize,
+    /// Like `old_start`, but in the new file.
+    pub new_start: usize,
+    pub lines: Vec<HunkLine>,
+}
+
+/// A line of a hunk, including its newline, unless it's the last line of a
+/// file without a newline at the end.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum HunkLine {
+    Context(Vec<u8>),
+    Removed(Vec<u8>),
+    Added(Vec<u8>),
+}
+
+impl Hunk {
+    fn old_lines(&self) -> impl Iterator<Item = &[u8]> {
+        self.lines.iter().filter_map(|line| match line {
+            HunkLine::Context(text) | HunkLine::Removed(text) => Some(text.as_slice()),
+            HunkLine::Added(_) => None,
+        })
+    }
+
+    fn new_lines(&self) -> impl Iterator<Item = &[u8]> {
+        self.lines.iter().filter_map(|line| match line {
+            HunkLine::Context(text) | HunkLine::Added(text) => Some(text.as_slice()),
+            HunkLine::Removed(_) => None,
+        })
+    }
+
+    /// Writes the hunk in the unified diff format.
+    pub fn write_to(&self, out: &mut Vec<u8>) {
+        let old_count = self.old_lines().count();
+        let new_count = self.new_lines().count();
+        out.extend_from_slice(
+            format!(
+                "@@ -{} +{} @@\n",
+                format_range(self.old_start, old_count),
+                format_range(self.new_start, new_count)
+            )
+            .as_bytes(),
+        );
+        for line in &self.lines {
+            let (prefix, text) = match line {
+                HunkLine::Context(text) => (b' ', text),
+                HunkLine::Removed(text) => (b'-', text),
+                HunkLine::Added(text) => (b'+', text),
+            };
+            out.push(prefix);
+            out.extend_from_slice(text);
+            if !text.ends_with(b"\n") {
+                out.extend_from_slice(b"\n\\ No newline at end of file\n");
+            }
+        }
+    }
+}
+
+fn format_range(start: usize, count: usize) -> String {
+    if count == 1 {
+        start.to_string()
+    } else {
+        format!("{start},{count}")
+    }
+}
+
+#[derive(Debug, Error)]
+#[error("Invalid patch at line {line}: {message}")]
+pub struct PatchParseError {
+    /// The 1-based line number.
+    pub line: usize,
+    pub message: String,
+}
+
+/// Parses a patch. Text that isn't part of a file's diff, such as a
+/// diffstat, is ignored.
+pub fn parse_patch(text: &[u8]) -> Result<Patch, PatchParseError> {
+    let lines = find_line_ranges(text)
+        .into_iter()
+        .map(|range| &text[range])
+        .collect::<Vec<_>>();
+    let mut parser = PatchParser { lines, pos: 0 };
+    let header_end = parser.find_first_file();
+    let description = parse_description(&parser.lines[..header_end]);
+    parser.pos = header_end;
+    let mut files = vec![];
+    while let Some(file) = parser.parse_file()? {
+        files.push(file);
+    }
+    Ok(Patch { description, files })
+}
+
+/// Extracts the description from the header of a patch, removing mail and
+/// Mercurial headers, and the diffstat of `git format-patch` output.
+fn parse_description(header: &[&[u8]]) -> String {
+    let header = String::from_utf8_lossy(&header.concat()).into_owned();
+    let mut lines = header.lines().peekable();
+    let mut subject = None;
+    if lines.peek() == Some(&"# HG changeset patch") {
+        while lines.next_if(|line| line.starts_with('#')).is_some() {}
+    } else if lines.peek().is_some_and(|line| is_mail_header(line)) {
+        lines.next_if(|line| line.starts_with("From "));
+        let mut headers: Vec<String> = vec![];
+        while let Some(line) = lines.next() {
+            if line.is_empty() {
+                break;
+            } else if line.starts_with([' ', '\t']) {
+                if let Some(last) = headers.last_mut() {
+                    last.push_str(line);
+                }
+            } else {
+                headers.push(line.to_owned());
+            }
+        }
+        subject = headers
+            .iter()
+            .find_map(|header| header.strip_prefix("Subject:"))
+            .map(|subject| strip_patch_tag(subject.trim()).to_owned());
+    }
+    // The diffstat of `git format-patch` output starts with `---`, and the
+    // line before a file's `---` line may name the file
+    let body = lines
+        .take_while(|line| {
+            *line != "---" && !line.starts_with("diff ") && !line.starts_with("Index: ")
+        })
+        .collect::<Vec<_>>()
+        .join("\n");
+    let description = match subject {
+        Some(subject) => format!("{subject}\n\n{}", body.trim()),
+        None => body,
+    };
+    let description = description.trim();
+    if description.is_empty() {
+        String::new()
+    } else {
+        format!("{description}\n")
+    }
+}
+
+fn is_mail_header(line: &str) -> bool {
+    line.starts_with("From ") || line.starts_with("From: ") || line.starts_with("Subject: ")
+}
+
+/// Strips a tag like `[PATCH 2/3]` from the start of a mail subject.
+fn strip_patch_tag(subject: &str) -> &str {
+    if subject.starts_with('[') {
+        if let Some((_, rest)) = subject.split_once(']') {
+            return rest.trim_start();
+        }
+    }
+    subject
+}
+
+struct PatchParser<'a> {
+    lines: Vec<&'a [u8]>,
+    pos: usize,
+}
+
+impl<'a> PatchParser<'a> {
+    fn line(&self, pos: usize) -> Option<&'a str> {
+        let line = *self.lines.get(pos)?;
+        Some(std::str::from_utf8(line).unwrap_or_default())
+    }
+
+    fn error(&self, message: impl Into<String>) -> PatchParseError {
+        PatchParseError {
+            line: self.pos + 1,
+            message: message.into(),
+        }
+    }
+
+    fn is_file_start(&self, pos: usize) -> bool {
+        let Some(line) = self.line(pos) else {
+            return false;
+        };
+        line.starts_with("diff --git ")
+            || (line.starts_with("--- ")
+                && self
+                    .line(pos + 1)
+                    .is_some_and(|next| next.starts_with("+++ ")))
+    }
+
+    fn find_first_file(&self) -> usize {
+        (0..self.lines.len())
+            .find(|&pos| self.is_file_start(pos))
+            .unwrap_or(self.lines.len())
+    }
+
+    fn parse_file(&mut self) -> Result<Option<FilePatch>, PatchParseError> {
+        while self.pos < self.lines.len() && !self.is_file_start(self.pos) {
+            self.pos += 1;
+        }
+        let Some(line) = self.line(self.pos) else {
+            return Ok(None);
+        };
+        let mut file = FilePatch::default();
+        let mut is_git_diff = false;
+        if let Some(paths) = line.strip_prefix("diff --git ") {
+            is_git_diff = true;
+            let (old_path, new_path) = split_git_diff_paths(paths.trim_end())
+                .ok_or_else(|| self.error("Can't find the paths in the diff --git line"))?;
+            file.old_path = Some(self.parse_path(old_path, 1)?);
+            file.new_path = Some(self.parse_path(new_path, 1)?);
+            self.pos += 1;
+            while let Some(line) = self.line(self.pos) {
+                let line = line.trim_end();
+                if let Some(mode) = line
+                    .strip_prefix("new file mode ")
+                    .or_else(|| line.strip_prefix("new mode "))
+                {
+                    file.new_executable = Some(mode == "100755");
+                    if line.starts_with("new file mode ") {
+                        file.old_path = None;
+                    }
+                } else if line.starts_with("deleted file mode ") {
+                    file.new_path = None;
+                } else if let Some(path) = line.strip_prefix("rename from ") {
+                    file.old_path = Some(self.parse_path(path, 0)?);
+                } else if let Some(path) = line.strip_prefix("rename to ") {
+                    file.new_path = Some(self.parse_path(path, 0)?);
+                } else if line.starts_with("GIT binary patch") || line.starts_with("Binary files ")
+                {
+                    return Err(self.error("Binary patches are not supported"));
+                } else if line.starts_with("diff --git ") || line.starts_with("--- ") {
+                    break;
+                }
+                self.pos += 1;
+            }
+        }
+        if self.is_file_start(self.pos) && !self.line(self.pos).unwrap().starts_with("diff ") {
+            let old_path = self.parse_header_path("--- ")?;
+            let new_path = self.parse_header_path("+++ ")?;
+            // Git's extended headers are more precise, e.g. about renames
+            if !is_git_diff {
+                file.old_path = old_path;
+                file.new_path = new_path;
+            }
+            while self
+                .line(self.pos)
+                .is_some_and(|line| line.starts_with("@@ "))
+            {
+                file.hunks.push(self.parse_hunk()?);
+            }
+        }
+        if file.old_path.is_none() && file.new_path.is_none() {
+            return Err(self.error("The diff has neither an old nor a new file"));
+        }
+        Ok(Some(file))
+    }
+
+    /// Parses a `--- ` or `+++ ` line.
+    fn parse_header_path(&mut self, prefix: &str) -> Result<Option<RepoPathBuf>, PatchParseError> {
+        let line = self.line(self.pos).unwrap_or_default();
+        let path = line
+            .strip_prefix(prefix)
+            .ok_or_else(|| self.error(format!("Expected a line starting with {prefix:?}")))?;
+        // A tab separates the path from a timestamp
+        let path = path.split('\t').next().unwrap().trim_end();
+        let path = if path == "/dev/null" {
+            None
+        } else {
+            Some(self.parse_path(path, 1)?)
+        };
+        self.pos += 1;
+        Ok(path)
+    }
+
+    fn parse_path(&self, path: &str, strip: usize) -> Result<RepoPathBuf, PatchParseError> {
+        let path = path.trim_end();
+        let stripped = path.splitn(strip + 1, '/').nth(strip).unwrap_or_default();
+        RepoPathBuf::from_relative_path(stripped)
+            .filter(|path| !path.is_root())
+            .ok_or_else(|| self.error(format!("Invalid path {path:?}")))
+    }
+
+    fn parse_hunk(&mut self) -> Result<Hunk, PatchParseError> {
+        let line = self.line(self.pos).unwrap();
+        let (old_start, mut old_count, new_start, mut new_count) =
+            parse_hunk_header(line).ok_or_else(|| self.error("Invalid hunk header"))?;
+        self.pos += 1;
+        let mut lines = vec![];
+        while old_count > 0 || new_count > 0 {
+            let Some(&line) = self.lines.get(self.pos) else {
+                return Err(self.error("The hunk ends early"));
+            };
+            let hunk_line = match line.first() {
+                Some(b' ') => HunkLine::Context(line[1..].to_vec()),
+                // Some tools strip the space from empty context lines
+                Some(b'\n') => HunkLine::Context(line.to_vec()),
+                Some(b'-') => HunkLine::Removed(line[1..].to_vec()),
+                Some(b'+') => HunkLine::Added(line[1..].to_vec()),
+                Some(b'\\') => {
+                    self.pos += 1;
+                    strip_last_newline(&mut lines);
+                    continue;
+                }
+                _ => return Err(self.error("Invalid line in hunk")),
+            };
+            let (in_old, in_new) = match hunk_line {
+                HunkLine::Context(_) => (true, true),
+                HunkLine::Removed(_) => (true, false),
+                HunkLine::Added(_) => (false, true),
+            };
+            if (in_old && old_count == 0) || (in_new && new_count == 0) {
+                return Err(self.error("The hunk has more lines than its header says"));
+            }
+            old_count -= usize::from(in_old);
+            new_count -= usize::from(in_new);
+            lines.push(hunk_line);
+            self.pos += 1;
+        }
+        if self
+            .line(self.pos)
+            .is_some_and(|line| line.starts_with('\\'))
+        {
+            self.pos += 1;
+            strip_last_newline(&mut lines);
+        }
+        Ok(Hunk {
+            old_start,
+            new_start,
+            lines,
+        })
+    }
+}
+
+fn strip_last_newline(lines: &mut [HunkLine]) {
+    if let Some(HunkLine::Context(text) | HunkLine::Removed(text) | HunkLine::Added(text)) =
+        lines.last_mut()
+    {
+        if text.ends_with(b"\n") {
+            text.pop();
+        }
+    }
+}
+
+/// Splits the paths in a `diff --git a/foo b/foo` line, which are ambiguous
+/// if they contain spaces. Then the old and new paths are assumed to be the
+/// same.
+fn split_git_diff_paths(paths: &str) -> Option<(&str, &str)> {
+    let (old_path, new_path) = (paths.len() / 2, paths.len() / 2 + 1);
+    if paths.len() % 2 == 1
+        && paths.as_bytes()[old_path] == b' '
+        && paths[..old_path].get(2..) == paths[new_path..].get(2..)
+    {
+        return Some((&paths[..old_path], &paths[new_path..]));
+    }
+    paths.split_once(' ')
+}
+
+fn parse_hunk_header(line: &str) -> Option<(usize, usize, usize, usize)> {
+    let ranges = line.strip_prefix("@@ -")?;
+    let (ranges, _) = ranges.split_once(" @@")?;
+    let (old, new) = ranges.split_once(" +")?;
+    let parse_range = |range: &str| -> Option<(usize, usize)> {
+        match range.split_once(',') {
+            Some((start, count)) => Some((start.parse().ok()?, count.parse().ok()?)),
+            None => Some((range.parse().ok()?, 1)),
+        }
+    };
+    let (old_start, old_count) = parse_range(old)?;
+    let (new_start, new_count) = parse_range(new)?;
+    Some((old_start, old_count, new_start, new_count))
+}
+
+/// Applies hunks to the contents of a file. Returns the new contents, or the
+/// hunks that didn't apply.
+///
+/// A hunk applies where its context and removed lines match the file. The
+/// match closest to where the hunk says it starts is used, adjusted by the
+/// offset of the hunks before it. If there's no match, up to `fuzz` lines of
+/// context are ignored at the start and end of the hunk.
+pub fn apply_hunks(content: &[u8], hunks: &[Hunk], fuzz: usize) -> Result<Vec<u8>, Vec<Hunk>> {
+    let lines = find_line_ranges(content)
+        .into_iter()
+        .map(|range| &content[range])
+        .collect::<Vec<_>>();
+    let mut new_content = vec![];
+    let mut rejected = vec![];
+    // The next line to copy to the new content
+    let mut pos = 0;
+    // How far hunks were found from where they said they start
+    let mut offset: isize = 0;
+    for hunk in hunks {
+        let Some((start, end, trimmed)) = find_hunk(&lines, pos, offset, hunk, fuzz) else {
+            rejected.push(hunk.clone());
+            continue;
+        };
+        let expected_start = hunk_start(hunk) + trimmed.0;
+        offset = start as isize - expected_start as isize;
+        for line in &lines[pos..start] {
+            new_content.extend_from_slice(line);
+        }
+        let new_lines = hunk.new_lines().collect::<Vec<_>>();
+        for line in &new_lines[trimmed.0..new_lines.len() - trimmed.1] {
+            new_content.extend_from_slice(line);
+        }
+        pos = end;
+    }
+    if !rejected.is_empty() {
+        return Err(rejected);
+    }
+    for line in &lines[pos..] {
+        new_content.extend_from_slice(line);
+    }
+    Ok(new_content)
+}
+
+/// The 0-based line where the hunk starts in the old file.
+fn hunk_start(hunk: &Hunk) -> usize {
+    if hunk.old_lines().next().is_none() {
+        hunk.old_start
+    } else {
+        hunk.old_start.saturating_sub(1)
+    }
+}
+
+/// Finds where the hunk applies at or after line `min_start`. Returns the
+/// range of lines it replaces, and the number of context lines ignored at the
+/// start and end.
+fn find_hunk(
+    lines: &[&[u8]],
+    min_start: usize,
+    offset: isize,
+    hunk: &Hunk,
+    fuzz: usize,
+) -> Option<(usize, usize, (usize, usize))> {
+    let old_lines = hunk.old_lines().collect::<Vec<_>>();
+    let leading_context = hunk
+        .lines
+        .iter()
+        .take_while(|line| matches!(line, HunkLine::Context(_)))
+        .count();
+    let trailing_context = hunk
+        .lines
+        .iter()
+        .rev()
+        .take_while(|line| matches!(line, HunkLine::Context(_)))
+        .count();
+    for fuzz in 0..=fuzz {
+        let trimmed_start = fuzz.min(leading_context);
+        // A hunk with only context lines has the same ones at both ends
+        let trimmed_end = fuzz
+            .min(trailing_context)
+            .min(old_lines.len() - trimmed_start);
+        let trimmed = (trimmed_start, trimmed_end);
+        if fuzz > 0 && trimmed == (0, 0) {
+            break;
+        }
+        let pattern = &old_lines[trimmed_start..old_lines.len() - trimmed_end];
+        let expected = (hunk_start(hunk) + trimmed.0) as isize + offset;
+        let expected = expected.clamp(min_start as isize, lines.len() as isize) as usize;
+        let matches_at = |start: usize| {
+            start >= min_start
+                && start + pattern.len() <= lines.len()
+                && lines[start..start + pattern.len()] == *pattern
+        };
+        // Search outwards from the expected position
+        let max_distance = expected.max(lines.len() - expected);
+        for distance in 0..=max_distance {
+            let candidates = [expected.checked_sub(distance), Some(expected + distance)];
+            for start in candidates.into_iter().flatten() {
+                if matches_at(start) {
+                    return Some((start, start + pattern.len(), trimmed));
+                }
+            }
+        }
+    }
+    None
+}
+
+/// Hunks of a file that didn't apply.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct FileRejects {
+    pub old_path: Option<RepoPathBuf>,
+    pub new_path: Option<RepoPathBuf>,
+    pub hunks: Vec<Hunk>,
+}
+
+impl FileRejects {
+    /// The path to report the rejects under.
+    pub fn path(&self) -> &RepoPath {
+        self.new_path.as_ref().or(self.old_path.as_ref()).unwrap()
+    }
+
+    /// Formats the rejected hunks like a `.rej` file written by `patch`.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let format_path = |prefix: &str, path: &Option<RepoPathBuf>| match path {
+            Some(path) => format!("{prefix}{}", path.as_internal_file_string()),
+            None => "/dev/null".to_owned(),
+        };
+        let mut out = format!(
+            "--- {}\n+++ {}\n",
+            format_path("a/", &self.old_path),
+            format_path("b/", &self.new_path)
+        )
+        .into_bytes();
+        for hunk in &self.hunks {
+            hunk.write_to(&mut out);
+        }
+        out
+    }
+}
+
+#[derive(Debug, Error)]
+pub enum PatchApplyError {
+    #[error("File {} to patch doesn't exist or isn't a regular file", .0.as_internal_file_string())]
+    MissingFile(RepoPathBuf),
+    #[error("File {} to add already exists", .0.as_internal_file_string())]
+    FileExists(RepoPathBuf),
+    #[error("{} hunks failed to apply", .0.iter().map(|file| file.hunks.len()).sum::<usize>())]
+    Rejected(Vec<FileRejects>),
+    #[error(transparent)]
+    Backend(#[from] BackendError),
+}
+
+/// Applies a patch to a tree and writes the resulting tree. Nothing is
+/// written unless all hunks apply.
+pub fn apply_patch(
+    store: &Arc<Store>,
+    tree: &MergedTree,
+    patch: &Patch,
+    fuzz: usize,
+) -> Result<MergedTreeId, PatchApplyError> {
+    // The new contents and executable bits of the paths the patch touches,
+    // with `None` for removed paths
+    let mut changes: BTreeMap<RepoPathBuf, Option<(Vec<u8>, bool)>> = BTreeMap::new();
+    let mut rejects = vec![];
+    for file in &patch.files {
+        let old_file = match &file.old_path {
+            Some(path) => match changes.get(path) {
+                Some(Some(file)) => file.clone(),
+                Some(None) => return Err(PatchApplyError::MissingFile(path.clone())),
+                None => read_file(store, tree, path)?
+                    .ok_or_else(|| PatchApplyError::MissingFile(path.clone()))?,
+            },
+            None => (vec![], false),
+        };
+        if let (None, Some(path)) = (&file.old_path, &file.new_path) {
+            let exists = match changes.get(path) {
+                Some(change) => change.is_some(),
+                None => tree.path_value(path).is_present(),
+            };
+            if exists {
+                return Err(PatchApplyError::FileExists(path.clone()));
+            }
+        }
+        let new_content = match apply_hunks(&old_file.0, &file.hunks, fuzz) {
+            Ok(new_content) => new_content,
+            Err(hunks) => {
+                rejects.push(FileRejects {
+                    old_path: file.old_path.clone(),
+                    new_path: file.new_path.clone(),
+                    hunks,
+                });
+                continue;
+            }
+        };
+        if let Some(path) = &file.old_path {
+            changes.insert(path.clone(), None);
+        }
+        if let Some(path) = &file.new_path {
+            let executable = file.new_executable.unwrap_or(old_file.1);
+            changes.insert(path.clone(), Some((new_content, executable)));
+        }
+    }
+    if !rejects.is_empty() {
+        return Err(PatchApplyError::Rejected(rejects));
+    }
+    let mut tree_builder = MergedTreeBuilder::new(tree.id());
+    for (path, change) in changes {
+        let value = match change {
+            Some((content, executable)) => {
+                let id = store.write_file(&path, &mut content.as_slice())?;
+                Merge::normal(TreeValue::File { id, executable })
+            }
+            None => Merge::absent(),
+        };
+        tree_builder.set_or_remove(path, value);
+    }
+    Ok(tree_builder.write_tree(store)?)
+}
+
+/// Reads a regular file from the tree. Returns `None` if the path isn't a
+/// regular file.
+fn read_file(
+    store: &Store,
+    tree: &MergedTree,
+    path: &RepoPath,
+) -> BackendResult<Option<(Vec<u8>, bool)>> {
+    let value = tree.path_value(path);
+    let Some(TreeValue::File { id, executable }) = value.as_normal() else {
+        return Ok(None);
+    };
+    let mut content = vec![];
+    store
+        .read_file(path, id)?
+        .read_to_end(&mut content)
+        .map_err(|err| BackendError::ReadObject {
+            object_type: ObjectKind::File.to_string(),
+            hash: id.hex(),
+            source: err.into(),
+        })?;
+    Ok(Some((content, *executable)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn repo_path(value: &str) -> RepoPathBuf {
+        RepoPathBuf::from_internal_string(value)
+    }
+
+    #[test]
+    fn test_parse_git_format_patch() {
+        let patch = parse_patch(
+            b"\
+From 0123456789abcdef0123456789abcdef01234567 Mon Sep 17 00:00:00 2001
+From: Some One <some.one@example.com>
+Date: Mon, 1 Jan 2024 00:00:00 +0000
+Subject: [PATCH 1/2] Fix the frobnicator
+ when it's cold
+
+The frobnicator broke below freezing.
+---
+ file | 2 +-
+ 1 file changed, 1 insertion(+), 1 deletion(-)
+
+diff --git a/file b/file
+index 1234567..89abcde 100644
+--- a/file
++++ b/file
+@@ -1,2 +1,2 @@
+ a
+-b
++c
+diff --git a/old b/new
+similarity index 100%
+rename from old
+rename to new
+diff --git a/script b/script
+new file mode 100755
+--- /dev/null
++++ b/script
+@@ -0,0 +1 @@
++run
+\\ No newline at end of file
+-- 
+2.40.0
+",
+        )
+        .unwrap();
+        assert_eq!(
+            patch.description,
+            "Fix the frobnicator when it's cold\n\nThe frobnicator broke below freezing.\n"
+        );
+        assert_eq!(
+            patch.files,
+            vec![
+                FilePatch {
+                    old_path: Some(repo_path("file")),
+                    new_path: Some(repo_path("file")),
+                    new_executable: None,
+                    hunks: vec![Hunk {
+                        old_start: 1,
+                        new_start: 1,
+                        lines: vec![
+                            HunkLine::Context(b"a\n".to_vec()),
+                            HunkLine::Removed(b"b\n".to_vec()),
+                            HunkLine::Added(b"c\n".to_vec()),
+                        ],
+                    }],
+                },
+                FilePatch {
+                    old_path: Some(repo_path("old")),
+                    new_path: Some(repo_path("new")),
+                    new_executable: None,
+                    hunks: vec![],
+                },
+                FilePatch {
+                    old_path: None,
+                    new_path: Some(repo_path("script")),
+                    new_executable: Some(true),
+                    hunks: vec![Hunk {
+                        old_start: 0,
+                        new_start: 1,
+                        lines: vec![HunkLine::Added(b"run".to_vec())],
+                    }],
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_hg_and_quilt_patches() {
+        let hg_patch = parse_patch(
+            b"\
+# HG changeset patch
+# User Some One <some.one@example.com>
+# Parent  0123456789abcdef0123456789abcdef01234567
+Add a file
+
+diff -r 0123456789ab -r 89abcdef0123 dir/file
+--- /dev/null\tThu Jan 01 00:00:00 1970 +0000
++++ b/dir/file\tMon Jan 01 00:00:00 2024 +0000
+@@ -0,0 +1,1 @@
++a
+",
+        )
+        .unwrap();
+        assert_eq!(hg_patch.description, "Add a file\n");
+        assert_eq!(hg_patch.files[0].old_path, None);
+        assert_eq!(hg_patch.files[0].new_path, Some(repo_path("dir/file")));
+
+        let quilt_patch = parse_patch(
+            b"\
+Remove a file
+
+Index: project/file
+===================================================================
+--- project.orig/file
++++ /dev/null
+@@ -1 +0,0 @@
+-a
+",
+        )
+        .unwrap();
+        assert_eq!(quilt_patch.description, "Remove a file\n");
+        assert_eq!(quilt_patch.files[0].old_path, Some(repo_path("file")));
+        assert_eq!(quilt_patch.files[0].new_path, None);
+
+        let error = parse_patch(b"--- a/file\n+++ b/file\n@@ -1,2 +1,2 @@\n a\n").unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "Invalid patch at line 5: The hunk ends early"
+        );
+        let error = parse_patch(b"--- a/../file\n+++ b/file\n").unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            r#"Invalid patch at line 1: Invalid path "a/../file""#
+        );
+    }
+
+    #[test]
+    fn test_apply_hunks_with_offset_and_fuzz() {
+        let hunks = parse_patch(
+            b"\
+--- a/file
++++ b/file
+@@ -2,3 +2,3 @@
+ b
+-c
++C
+ d
+@@ -6,3 +6,3 @@
+ f
+-g
++G
+ h
+",
+        )
+        .unwrap()
+        .files
+        .remove(0)
+        .hunks;
+        assert_eq!(
+            apply_hunks(b"a\nb\nc\nd\ne\nf\ng\nh\n", &hunks, 0).unwrap(),
+            b"a\nb\nC\nd\ne\nf\nG\nh\n"
+        );
+        // Lines were added before both hunks
+        assert_eq!(
+            apply_hunks(b"0\n1\na\nb\nc\nd\ne\nf\ng\nh\n", &hunks, 0).unwrap(),
+            b"0\n1\na\nb\nC\nd\ne\nf\nG\nh\n"
+        );
+        // The context of the second hunk changed, which needs fuzz
+        let content = b"a\nb\nc\nd\ne\nF\ng\nh\n";
+        assert_eq!(apply_hunks(content, &hunks, 0).unwrap_err(), &hunks[1..]);
+        assert_eq!(
+            apply_hunks(content, &hunks, 1).unwrap(),
+            b"a\nb\nC\nd\ne\nF\nG\nh\n"
+        );
+        // A removed line changed, which no fuzz can ignore
+        assert_eq!(
+            apply_hunks(b"a\nb\nc\nd\ne\nf\nx\nh\n", &hunks, 2).unwrap_err(),
+            &hunks[1..]
+        );
+    }
+
+    #[test]
+    fn test_rejects_to_bytes() {
+        let patch_text = b"\
+--- a/file
++++ b/file
+@@ -1,2 +1,2 @@
+ a
+-b
+\\ No newline at end of file
++c
+\\ No newline at end of file
+";
+        let file = parse_patch(patch_text).unwrap().files.remove(0);
+        let rejects = FileRejects {
+            old_path: file.old_path,
+            new_path: file.new_path,
+            hunks: file.hunks,
+        };
+        assert_eq!(rejects.to_bytes(), patch_text);
+    }
+}
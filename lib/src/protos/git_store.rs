@@ -18,4 +18,10 @@ pub struct Commit {
     #[deprecated]
     #[prost(bool, tag = "9")]
     pub is_pruned: bool,
+    /// Extra key-value pairs. Unknown keys must be preserved.
+    #[prost(btree_map = "string, string", tag = "11")]
+    pub metadata: ::prost::alloc::collections::BTreeMap<
+        ::prost::alloc::string::String,
+        ::prost::alloc::string::String,
+    >,
 }
@@ -32,14 +32,19 @@ pub mod tree_value {
 pub struct Tree {
     #[prost(message, repeated, tag = "1")]
     pub entries: ::prost::alloc::vec::Vec<tree::Entry>,
+    /// Whether this tree or any of its subtrees has a conflict entry. Not set in
+    /// trees written before this was recorded.
+    #[prost(bool, optional, tag = "2")]
+    pub has_conflict: ::core::option::Option<bool>,
 }
 /// Nested message and enum types in `Tree`.
 pub mod tree {
     #[allow(clippy::derive_partial_eq_without_eq)]
     #[derive(Clone, PartialEq, ::prost::Message)]
     pub struct Entry {
-        #[prost(string, tag = "1")]
-        pub name: ::prost::alloc::string::String,
+        /// Raw file name, which may not be valid UTF-8.
+        #[prost(bytes = "vec", tag = "1")]
+        pub name: ::prost::alloc::vec::Vec<u8>,
         #[prost(message, optional, tag = "2")]
         pub value: ::core::option::Option<super::TreeValue>,
     }
@@ -67,6 +72,12 @@ pub struct Commit {
     pub committer: ::core::option::Option<commit::Signature>,
     #[prost(bytes = "vec", optional, tag = "9")]
     pub secure_sig: ::core::option::Option<::prost::alloc::vec::Vec<u8>>,
+    /// Extra key-value pairs. Unknown keys must be preserved.
+    #[prost(btree_map = "string, string", tag = "10")]
+    pub metadata: ::prost::alloc::collections::BTreeMap<
+        ::prost::alloc::string::String,
+        ::prost::alloc::string::String,
+    >,
 }
 /// Nested message and enum types in `Commit`.
 pub mod commit {
@@ -106,3 +117,21 @@ pub mod conflict {
         pub content: ::core::option::Option<super::TreeValue>,
     }
 }
+/// A large file stored as a sequence of content-defined chunks.
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct ChunkedFile {
+    #[prost(message, repeated, tag = "1")]
+    pub chunks: ::prost::alloc::vec::Vec<chunked_file::Chunk>,
+}
+/// Nested message and enum types in `ChunkedFile`.
+pub mod chunked_file {
+    #[allow(clippy::derive_partial_eq_without_eq)]
+    #[derive(Clone, PartialEq, ::prost::Message)]
+    pub struct Chunk {
+        #[prost(bytes = "vec", tag = "1")]
+        pub id: ::prost::alloc::vec::Vec<u8>,
+        #[prost(uint64, tag = "2")]
+        pub size: u64,
+    }
+}
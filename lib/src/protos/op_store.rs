@@ -134,6 +134,9 @@ pub struct Operation {
     pub parents: ::prost::alloc::vec::Vec<::prost::alloc::vec::Vec<u8>>,
     #[prost(message, optional, tag = "3")]
     pub metadata: ::core::option::Option<OperationMetadata>,
+    /// Operations this operation was recorded in place of.
+    #[prost(bytes = "vec", repeated, tag = "4")]
+    pub replaced: ::prost::alloc::vec::Vec<::prost::alloc::vec::Vec<u8>>,
 }
 /// TODO: Share with store.proto? Do we even need the timezone here?
 #[allow(clippy::derive_partial_eq_without_eq)]
@@ -15,16 +15,26 @@ pub struct FileState {
 #[allow(clippy::derive_partial_eq_without_eq)]
 #[derive(Clone, PartialEq, ::prost::Message)]
 pub struct FileStateEntry {
-    #[prost(string, tag = "1")]
-    pub path: ::prost::alloc::string::String,
+    /// Raw path, which may not be valid UTF-8.
+    #[prost(bytes = "vec", tag = "1")]
+    pub path: ::prost::alloc::vec::Vec<u8>,
     #[prost(message, optional, tag = "2")]
     pub state: ::core::option::Option<FileState>,
 }
+/// A path whose conflict couldn't be represented in the checked-out file.
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct MetadataConflictEntry {
+    #[prost(bytes = "vec", tag = "1")]
+    pub path: ::prost::alloc::vec::Vec<u8>,
+    #[prost(enumeration = "MetadataConflictKind", tag = "2")]
+    pub kind: i32,
+}
 #[allow(clippy::derive_partial_eq_without_eq)]
 #[derive(Clone, PartialEq, ::prost::Message)]
 pub struct SparsePatterns {
-    #[prost(string, repeated, tag = "1")]
-    pub prefixes: ::prost::alloc::vec::Vec<::prost::alloc::string::String>,
+    #[prost(bytes = "vec", repeated, tag = "1")]
+    pub prefixes: ::prost::alloc::vec::Vec<::prost::alloc::vec::Vec<u8>>,
 }
 #[allow(clippy::derive_partial_eq_without_eq)]
 #[derive(Clone, PartialEq, ::prost::Message)]
@@ -41,6 +51,13 @@ pub struct TreeState {
     pub sparse_patterns: ::core::option::Option<SparsePatterns>,
     #[prost(message, optional, tag = "4")]
     pub watchman_clock: ::core::option::Option<WatchmanClock>,
+    /// Whether the filesystem preserves the executable bit. Unset in states
+    /// written before this was detected, which means that it does.
+    #[prost(bool, optional, tag = "6")]
+    pub exec_bit_supported: ::core::option::Option<bool>,
+    /// Sorted by path.
+    #[prost(message, repeated, tag = "7")]
+    pub metadata_conflicts: ::prost::alloc::vec::Vec<MetadataConflictEntry>,
 }
 #[allow(clippy::derive_partial_eq_without_eq)]
 #[derive(Clone, PartialEq, ::prost::Message)]
@@ -107,3 +124,29 @@ impl FileType {
         }
     }
 }
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, ::prost::Enumeration)]
+#[repr(i32)]
+pub enum MetadataConflictKind {
+    ExecutableBit = 0,
+    SymlinkVsFile = 1,
+}
+impl MetadataConflictKind {
+    /// String value of the enum field names used in the ProtoBuf definition.
+    ///
+    /// The values are not transformed in any way and thus are considered stable
+    /// (if the ProtoBuf definition does not change) and safe for programmatic use.
+    pub fn as_str_name(&self) -> &'static str {
+        match self {
+            MetadataConflictKind::ExecutableBit => "ExecutableBit",
+            MetadataConflictKind::SymlinkVsFile => "SymlinkVsFile",
+        }
+    }
+    /// Creates an enum from field names used in the ProtoBuf definition.
+    pub fn from_str_name(value: &str) -> ::core::option::Option<Self> {
+        match value {
+            "ExecutableBit" => Some(Self::ExecutableBit),
+            "SymlinkVsFile" => Some(Self::SymlinkVsFile),
+            _ => None,
+        }
+    }
+}
@@ -14,12 +14,16 @@
 
 #![allow(missing_docs)]
 
-use itertools::EitherOrBoth;
+use itertools::{EitherOrBoth, Itertools as _};
+use thiserror::Error;
+use unicode_normalization::UnicodeNormalization as _;
 
 use crate::backend::CommitId;
 use crate::index::Index;
 use crate::merge::{trivial_merge, Merge};
 use crate::op_store::{RefTarget, RemoteRef};
+use crate::repo::Repo;
+use crate::revset::{self, RevsetEvaluationError};
 
 /// Compares `refs1` and `refs2` targets, yields entry if they differ.
 ///
@@ -202,6 +206,148 @@ pub fn classify_branch_push_action(targets: LocalAndRemoteRef) -> BranchPushActi
     }
 }
 
+/// Number of commits, counted up to a limit.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum CommitCount {
+    Exact(usize),
+    /// There are more commits than the limit, which is the contained value.
+    AtLeast(usize),
+}
+
+impl CommitCount {
+    pub fn is_zero(&self) -> bool {
+        *self == CommitCount::Exact(0)
+    }
+}
+
+/// How far a local ref is from its remote counterpart.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct AheadBehind {
+    /// Commits reachable from the local target but not from the remote target.
+    pub ahead: CommitCount,
+    /// Commits reachable from the remote target but not from the local target.
+    pub behind: CommitCount,
+}
+
+/// Counts the commits in `local_target % remote_target` and vice versa. At most
+/// `limit + 1` commits are visited on each side, so this is cheap even if the
+/// targets diverged long ago. The added commits of conflicted targets are
+/// used.
+pub fn count_ahead_behind(
+    repo: &dyn Repo,
+    local_target: &RefTarget,
+    remote_target: &RefTarget,
+    limit: usize,
+) -> Result<AheadBehind, RevsetEvaluationError> {
+    let local_ids = local_target.added_ids().cloned().collect_vec();
+    let remote_ids = remote_target.added_ids().cloned().collect_vec();
+    Ok(AheadBehind {
+        ahead: count_commits_up_to(repo, &local_ids, &remote_ids, limit)?,
+        behind: count_commits_up_to(repo, &remote_ids, &local_ids, limit)?,
+    })
+}
+
+fn count_commits_up_to(
+    repo: &dyn Repo,
+    wanted: &[CommitId],
+    unwanted: &[CommitId],
+    limit: usize,
+) -> Result<CommitCount, RevsetEvaluationError> {
+    let revset = revset::walk_revs(repo, wanted, unwanted)?;
+    let count = revset.iter().take(limit.saturating_add(1)).count();
+    if count > limit {
+        Ok(CommitCount::AtLeast(limit))
+    } else {
+        Ok(CommitCount::Exact(count))
+    }
+}
+
+/// Reason a branch name can't be used.
+#[derive(Clone, Debug, Error, PartialEq, Eq)]
+pub enum RefNameError {
+    #[error("Name is empty")]
+    Empty,
+    #[error("Name {0:?} is reserved")]
+    Reserved(String),
+    #[error("Name starts with '-'")]
+    LeadingDash,
+    #[error("Name contains {0:?}")]
+    InvalidCharacter(char),
+    #[error("Name contains {0:?}")]
+    InvalidSequence(&'static str),
+    #[error("Name has an empty path component")]
+    EmptyComponent,
+    #[error("Path component {0:?} starts with '.' or ends with \".lock\"")]
+    InvalidComponent(String),
+    #[error("Name ends with '.'")]
+    TrailingDot,
+}
+
+/// Checks that `name` can be used as a branch name.
+///
+/// This implements Git's rules for ref names (see `git help check-ref-format`),
+/// so the branch can be exported as `refs/heads/{name}`. In addition, names
+/// must not start with `-`, which would be confused with command-line options,
+/// and must not be `HEAD`.
+pub fn validate_branch_name(name: &str) -> Result<(), RefNameError> {
+    validate_ref_name(name)
+}
+
+/// Checks that `name` can be used as a tag name. The rules are the same as for
+/// branch names, so the tag can be exported as `refs/tags/{name}`.
+pub fn validate_tag_name(name: &str) -> Result<(), RefNameError> {
+    validate_ref_name(name)
+}
+
+fn validate_ref_name(name: &str) -> Result<(), RefNameError> {
+    if name.is_empty() {
+        return Err(RefNameError::Empty);
+    }
+    if name == "@" || name == "HEAD" {
+        return Err(RefNameError::Reserved(name.to_owned()));
+    }
+    if name.starts_with('-') {
+        return Err(RefNameError::LeadingDash);
+    }
+    if let Some(c) = name.chars().find(|&c| {
+        c.is_ascii_control() || matches!(c, ' ' | '~' | '^' | ':' | '?' | '*' | '[' | '\\')
+    }) {
+        return Err(RefNameError::InvalidCharacter(c));
+    }
+    if let Some(sequence) = ["..", "@{"].into_iter().find(|seq| name.contains(seq)) {
+        return Err(RefNameError::InvalidSequence(sequence));
+    }
+    for component in name.split('/') {
+        if component.is_empty() {
+            return Err(RefNameError::EmptyComponent);
+        }
+        if component.starts_with('.') || component.ends_with(".lock") {
+            return Err(RefNameError::InvalidComponent(component.to_owned()));
+        }
+    }
+    if name.ends_with('.') {
+        return Err(RefNameError::TrailingDot);
+    }
+    Ok(())
+}
+
+/// Finds an existing branch name that differs from `name` only by letter case
+/// or Unicode normalization. Such branches would be exported to the same Git
+/// ref on case-insensitive or normalizing file systems.
+pub fn find_colliding_branch_name<'a>(
+    name: &str,
+    existing_names: impl IntoIterator<Item = &'a str>,
+) -> Option<&'a str> {
+    let key = branch_name_collision_key(name);
+    existing_names
+        .into_iter()
+        .find(|&existing| existing != name && branch_name_collision_key(existing) == key)
+}
+
+fn branch_name_collision_key(name: &str) -> String {
+    name.nfc().flat_map(char::to_lowercase).collect()
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -342,4 +488,73 @@ mod tests {
             BranchPushAction::RemoteConflicted
         );
     }
+
+    #[test]
+    fn test_validate_branch_name() {
+        assert_eq!(validate_branch_name("main"), Ok(()));
+        assert_eq!(validate_branch_name("feature/foo-bar_1.2"), Ok(()));
+        assert_eq!(validate_branch_name("ünïcödé"), Ok(()));
+        assert_eq!(validate_branch_name(""), Err(RefNameError::Empty));
+        assert_eq!(
+            validate_branch_name("HEAD"),
+            Err(RefNameError::Reserved("HEAD".to_owned()))
+        );
+        assert_eq!(
+            validate_branch_name("@"),
+            Err(RefNameError::Reserved("@".to_owned()))
+        );
+        assert_eq!(validate_branch_name("-foo"), Err(RefNameError::LeadingDash));
+        for c in ['\0', '\n', '\x7f', ' ', '~', '^', ':', '?', '*', '[', '\\'] {
+            assert_eq!(
+                validate_branch_name(&format!("foo{c}bar")),
+                Err(RefNameError::InvalidCharacter(c))
+            );
+        }
+        assert_eq!(
+            validate_branch_name("foo..bar"),
+            Err(RefNameError::InvalidSequence(".."))
+        );
+        assert_eq!(
+            validate_branch_name("foo@{1}"),
+            Err(RefNameError::InvalidSequence("@{"))
+        );
+        assert_eq!(
+            validate_branch_name("/foo"),
+            Err(RefNameError::EmptyComponent)
+        );
+        assert_eq!(
+            validate_branch_name("foo/"),
+            Err(RefNameError::EmptyComponent)
+        );
+        assert_eq!(
+            validate_branch_name("foo//bar"),
+            Err(RefNameError::EmptyComponent)
+        );
+        assert_eq!(
+            validate_branch_name("foo/.bar"),
+            Err(RefNameError::InvalidComponent(".bar".to_owned()))
+        );
+        assert_eq!(
+            validate_branch_name("foo.lock/bar"),
+            Err(RefNameError::InvalidComponent("foo.lock".to_owned()))
+        );
+        assert_eq!(validate_branch_name("foo."), Err(RefNameError::TrailingDot));
+    }
+
+    #[test]
+    fn test_find_colliding_branch_name() {
+        let existing = ["main", "Feature", "caf\u{e9}"];
+        assert_eq!(find_colliding_branch_name("main", existing), None);
+        assert_eq!(find_colliding_branch_name("other", existing), None);
+        assert_eq!(find_colliding_branch_name("MAIN", existing), Some("main"));
+        assert_eq!(
+            find_colliding_branch_name("feature", existing),
+            Some("Feature")
+        );
+        // "e" followed by a combining acute accent
+        assert_eq!(
+            find_colliding_branch_name("cafe\u{301}", existing),
+            Some("caf\u{e9}")
+        );
+    }
 }
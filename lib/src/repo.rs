@@ -19,6 +19,7 @@ use std::fmt::{Debug, Formatter};
 use std::io::ErrorKind;
 use std::ops::Deref;
 use std::path::{Path, PathBuf};
+use std::str::FromStr;
 use std::sync::Arc;
 use std::{fs, slice};
 
@@ -30,35 +31,37 @@ use tracing::instrument;
 use self::dirty_cell::DirtyCell;
 use crate::backend::{
     Backend, BackendError, BackendInitError, BackendLoadError, BackendResult, ChangeId, CommitId,
-    MergedTreeId, SigningFn,
+    MergedTreeId, ObjectKind, SigningFn,
 };
 use crate::commit::{Commit, CommitByCommitterTimestamp};
 use crate::commit_builder::CommitBuilder;
 use crate::default_index::DefaultIndexStore;
 use crate::default_submodule_store::DefaultSubmoduleStore;
-use crate::file_util::{IoResultExt as _, PathError};
+use crate::file_util::{self, IoResultExt as _, PathError};
+use crate::format_version::{self, FormatCompatibility, FormatVersionError, SupportedVersions};
 use crate::git_backend::GitBackend;
 use crate::index::{ChangeIdIndex, Index, IndexStore, MutableIndex, ReadonlyIndex};
 use crate::local_backend::LocalBackend;
+use crate::merge_drivers::MergeDriverConfigError;
+use crate::merged_tree::{DiffSummary, MergedTree};
 use crate::object_id::{HexPrefix, ObjectId, PrefixResolution};
 use crate::op_heads_store::{self, OpHeadResolutionError, OpHeadsStore};
 use crate::op_store::{
     OpStore, OpStoreError, OperationId, RefTarget, RemoteRef, RemoteRefState, WorkspaceId,
 };
 use crate::operation::Operation;
-use crate::refs::{
-    diff_named_ref_targets, diff_named_remote_refs, merge_ref_targets, merge_remote_refs,
-};
-use crate::rewrite::{DescendantRebaser, RebaseOptions};
+use crate::refs::{self, merge_ref_targets, merge_remote_refs, RefNameError};
+use crate::repo_path::RepoPathBuf;
+use crate::rewrite::{DescendantRebaser, DiffSummaryCache, MergedParentTreeCache, RebaseOptions};
 use crate::settings::{RepoSettings, UserSettings};
 use crate::signing::{SignInitError, Signer};
 use crate::simple_op_heads_store::SimpleOpHeadsStore;
 use crate::simple_op_store::SimpleOpStore;
 use crate::store::Store;
 use crate::submodule_store::SubmoduleStore;
-use crate::transaction::Transaction;
+use crate::transaction::{Transaction, TransactionCommitError};
 use crate::tree::TreeMergeError;
-use crate::view::View;
+use crate::view::{View, ViewReference};
 use crate::{backend, dag_walk, op_store, revset};
 
 pub trait Repo {
@@ -85,8 +88,24 @@ pub trait Repo {
     fn resolve_change_id_prefix(&self, prefix: &HexPrefix) -> PrefixResolution<Vec<CommitId>>;
 
     fn shortest_unique_change_id_prefix_len(&self, target_id_bytes: &ChangeId) -> usize;
+
+    /// Returns the merge of the commit's parents' trees, i.e. the tree the
+    /// commit would have if it didn't change anything. Merges of several
+    /// parents are cached for the lifetime of the repo instance.
+    fn merged_parent_tree(&self, commit: &Commit) -> Result<MergedTree, TreeMergeError>;
+
+    /// Returns the files changed by the commit relative to its merged parent
+    /// tree. Summaries are cached for the lifetime of the repo instance.
+    fn diff_summary(&self, commit: &Commit) -> Result<Arc<DiffSummary>, TreeMergeError>;
 }
 
+/// A snapshot of the repo at a single operation.
+///
+/// The repo is pinned to the operation it was loaded at: the view, the index,
+/// and revset evaluation all reflect that operation no matter which operations
+/// are committed later, by this process or any other. Commits hidden by later
+/// operations can still be read since objects are only deleted by garbage
+/// collection. Use `reload_at_head()` to get a snapshot of a newer operation.
 pub struct ReadonlyRepo {
     repo_path: PathBuf,
     store: Arc<Store>,
@@ -100,6 +119,9 @@ pub struct ReadonlyRepo {
     change_id_index: OnceCell<Box<dyn ChangeIdIndex>>,
     // TODO: This should eventually become part of the index and not be stored fully in memory.
     view: View,
+    read_only: bool,
+    merged_parent_trees: MergedParentTreeCache,
+    diff_summaries: DiffSummaryCache,
 }
 
 impl Debug for ReadonlyRepo {
@@ -111,12 +133,22 @@ impl Debug for ReadonlyRepo {
     }
 }
 
+/// Error returned when trying to modify a repo that was loaded in read-only
+/// mode.
+#[derive(Debug, Error)]
+#[error("The repo at {} is read-only", .repo_path.display())]
+pub struct RepoReadOnly {
+    pub repo_path: PathBuf,
+}
+
 #[derive(Error, Debug)]
 pub enum RepoInitError {
     #[error(transparent)]
     Backend(#[from] BackendInitError),
     #[error(transparent)]
     Path(#[from] PathError),
+    #[error(transparent)]
+    MergeDrivers(#[from] MergeDriverConfigError),
 }
 
 impl ReadonlyRepo {
@@ -132,7 +164,11 @@ impl ReadonlyRepo {
     }
 
     pub fn default_index_store_initializer() -> &'static IndexStoreInitializer<'static> {
-        &|_settings, store_path| Ok(Box::new(DefaultIndexStore::init(store_path)?))
+        &|settings, store_path| {
+            let index_store = DefaultIndexStore::init(store_path)?
+                .with_changed_path_filters(settings.index_changed_path_filters());
+            Ok(Box::new(index_store))
+        }
     }
 
     pub fn default_submodule_store_initializer() -> &'static SubmoduleStoreInitializer<'static> {
@@ -157,7 +193,13 @@ impl ReadonlyRepo {
         let backend = backend_initializer(user_settings, &store_path)?;
         let backend_path = store_path.join("type");
         fs::write(&backend_path, backend.name()).context(&backend_path)?;
-        let store = Store::new(backend, signer, user_settings.use_tree_conflict_format());
+        let store = Store::new(
+            backend,
+            signer,
+            user_settings.use_tree_conflict_format(),
+            user_settings.strict_store_validation(),
+            user_settings.merge_drivers()?,
+        );
         let repo_settings = user_settings.with_repo(&repo_path).unwrap();
 
         let op_store_path = repo_path.join("op_store");
@@ -179,6 +221,7 @@ impl ReadonlyRepo {
             view_id: root_view_id,
             parents: vec![op_store.root_operation_id().clone()],
             metadata: operation_metadata,
+            replaced: vec![],
         };
         let init_operation_id = op_store.write_operation(&init_operation).unwrap();
         let init_operation = Operation::new(op_store.clone(), init_operation_id, init_operation);
@@ -216,6 +259,9 @@ impl ReadonlyRepo {
             change_id_index: OnceCell::new(),
             view,
             submodule_store,
+            read_only: false,
+            merged_parent_trees: Default::default(),
+            diff_summaries: Default::default(),
         }))
     }
 
@@ -228,6 +274,7 @@ impl ReadonlyRepo {
             op_heads_store: self.op_heads_store.clone(),
             index_store: self.index_store.clone(),
             submodule_store: self.submodule_store.clone(),
+            read_only: self.read_only,
         }
     }
 
@@ -235,6 +282,24 @@ impl ReadonlyRepo {
         &self.repo_path
     }
 
+    /// Whether the repo was loaded in read-only mode. See
+    /// `RepoLoader::set_read_only()`.
+    pub fn is_read_only(&self) -> bool {
+        self.read_only
+    }
+
+    /// Returns an error if the repo was loaded in read-only mode. Call this
+    /// before starting a transaction that will be committed.
+    pub fn check_writable(&self) -> Result<(), RepoReadOnly> {
+        if self.read_only {
+            Err(RepoReadOnly {
+                repo_path: self.repo_path.clone(),
+            })
+        } else {
+            Ok(())
+        }
+    }
+
     pub fn op_id(&self) -> &OperationId {
         self.operation.id()
     }
@@ -252,9 +317,15 @@ impl ReadonlyRepo {
             .get_or_init(|| {
                 // TODO: somehow propagate error, but it's weird if all callers
                 // had Result<T, IndexReadError> signature.
-                self.index_store
-                    .get_index_at_op(&self.operation, &self.store)
-                    .unwrap()
+                if self.read_only {
+                    self.index_store
+                        .get_index_at_op_read_only(&self.operation, &self.store)
+                        .unwrap()
+                } else {
+                    self.index_store
+                        .get_index_at_op(&self.operation, &self.store)
+                        .unwrap()
+                }
             })
             .deref()
     }
@@ -284,10 +355,18 @@ impl ReadonlyRepo {
         self: &Arc<ReadonlyRepo>,
         user_settings: &UserSettings,
     ) -> Transaction {
-        let mut_repo = MutableRepo::new(self.clone(), self.readonly_index(), &self.view);
+        let mut mut_repo = MutableRepo::new(self.clone(), self.readonly_index(), &self.view);
+        // Invalid values are reported by the UI when it loads the settings.
+        mut_repo.set_abandon_on_checkout(user_settings.abandon_on_checkout().unwrap_or_default());
         Transaction::new(mut_repo, user_settings)
     }
 
+    /// Loads a new snapshot of the repo at the current head operation. This
+    /// repo stays pinned to its own operation.
+    ///
+    /// The new repo shares the stores with this one, so commits and trees
+    /// which have already been read are cached, and index segments which have
+    /// already been loaded aren't read again.
     pub fn reload_at_head(
         &self,
         user_settings: &UserSettings,
@@ -295,6 +374,8 @@ impl ReadonlyRepo {
         self.loader().load_at_head(user_settings)
     }
 
+    /// Loads a new snapshot of the repo at the given `operation`, sharing the
+    /// stores like `reload_at_head()`.
     #[instrument]
     pub fn reload_at(&self, operation: &Operation) -> Result<Arc<ReadonlyRepo>, RepoLoaderError> {
         self.loader().load_at(operation)
@@ -329,6 +410,14 @@ impl Repo for ReadonlyRepo {
     fn shortest_unique_change_id_prefix_len(&self, target_id: &ChangeId) -> usize {
         self.change_id_index().shortest_unique_prefix_len(target_id)
     }
+
+    fn merged_parent_tree(&self, commit: &Commit) -> Result<MergedTree, TreeMergeError> {
+        self.merged_parent_trees.get_or_merge(self, commit)
+    }
+
+    fn diff_summary(&self, commit: &Commit) -> Result<Arc<DiffSummary>, TreeMergeError> {
+        self.diff_summaries.get_or_compute(self, commit)
+    }
 }
 
 pub type BackendInitializer<'a> =
@@ -385,7 +474,11 @@ impl Default for StoreFactories {
         // Index
         factories.add_index_store(
             DefaultIndexStore::name(),
-            Box::new(|_settings, store_path| Ok(Box::new(DefaultIndexStore::load(store_path)))),
+            Box::new(|settings, store_path| {
+                let index_store = DefaultIndexStore::load(store_path)
+                    .with_changed_path_filters(settings.index_changed_path_filters());
+                Ok(Box::new(index_store))
+            }),
         );
 
         // SubmoduleStores
@@ -414,6 +507,10 @@ pub enum StoreLoadError {
     Backend(#[from] BackendLoadError),
     #[error(transparent)]
     Signing(#[from] SignInitError),
+    #[error(transparent)]
+    MergeDrivers(#[from] MergeDriverConfigError),
+    #[error(transparent)]
+    FormatVersion(#[from] FormatVersionError),
 }
 
 impl StoreFactories {
@@ -585,6 +682,107 @@ pub enum RepoLoaderError {
     OpHeadResolution(#[from] OpHeadResolutionError),
     #[error(transparent)]
     OpStore(#[from] OpStoreError),
+    #[error(transparent)]
+    ReadOnly(#[from] RepoReadOnly),
+    #[error(transparent)]
+    TransactionCommit(#[from] TransactionCommitError),
+    #[error(
+        "Commit {} referenced by {referenced_by} is missing from the store",
+        commit_id.hex()
+    )]
+    MissingObjectForHead {
+        commit_id: CommitId,
+        referenced_by: ViewReference,
+    },
+    #[error(transparent)]
+    Backend(#[from] BackendError),
+}
+
+/// How thoroughly `find_missing_commits()` looks for commits that are missing
+/// from the store.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum ConsistencyCheck {
+    /// Checks the visible heads and the working-copy commits.
+    Quick,
+    /// Checks every commit referenced by the view, and every ancestor of the
+    /// visible heads.
+    Thorough,
+}
+
+/// Commits that the repo refers to but that are missing from the store.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct MissingCommits {
+    /// Commits referenced by the view, and where they're referenced from.
+    pub view_references: Vec<(CommitId, ViewReference)>,
+    /// Other ancestors of the visible heads. Only found by a thorough check.
+    pub ancestors: Vec<CommitId>,
+}
+
+impl MissingCommits {
+    pub fn is_empty(&self) -> bool {
+        self.view_references.is_empty() && self.ancestors.is_empty()
+    }
+}
+
+/// Looks for commits that the index or the view of `repo` refers to but that
+/// are missing from the store, e.g. because objects were deleted by hand.
+pub fn find_missing_commits(
+    repo: &dyn Repo,
+    check: ConsistencyCheck,
+) -> BackendResult<MissingCommits> {
+    let store = repo.store();
+    let mut exists: HashMap<CommitId, bool> = HashMap::new();
+    let mut commit_exists = |id: &CommitId| -> BackendResult<bool> {
+        if let Some(&found) = exists.get(id) {
+            return Ok(found);
+        }
+        let found = store.contains(ObjectKind::Commit, id.as_bytes())?;
+        exists.insert(id.clone(), found);
+        Ok(found)
+    };
+
+    let mut missing = MissingCommits::default();
+    for (id, reference) in repo.view().commit_references() {
+        let is_quick_reference = matches!(
+            reference,
+            ViewReference::Head | ViewReference::WorkingCopy(_)
+        );
+        if check == ConsistencyCheck::Quick && !is_quick_reference {
+            continue;
+        }
+        if !commit_exists(id)? {
+            missing.view_references.push((id.clone(), reference));
+        }
+    }
+    if check == ConsistencyCheck::Thorough {
+        let ancestors = revset::RevsetExpression::visible_heads()
+            .ancestors()
+            .evaluate_programmatic(repo)
+            .map_err(|err| BackendError::Other(err.into()))?;
+        for id in ancestors.iter() {
+            let is_view_reference = missing
+                .view_references
+                .iter()
+                .any(|(missing_id, _)| *missing_id == id);
+            if !is_view_reference && !commit_exists(&id)? {
+                missing.ancestors.push(id);
+            }
+        }
+    }
+    Ok(missing)
+}
+
+/// Returns an error naming the first visible head or working-copy commit of
+/// `repo` that's missing from the store.
+pub fn check_view_commits(repo: &dyn Repo) -> Result<(), RepoLoaderError> {
+    let missing = find_missing_commits(repo, ConsistencyCheck::Quick)?;
+    match missing.view_references.into_iter().next() {
+        Some((commit_id, referenced_by)) => Err(RepoLoaderError::MissingObjectForHead {
+            commit_id,
+            referenced_by,
+        }),
+        None => Ok(()),
+    }
 }
 
 #[derive(Clone)]
@@ -596,6 +794,7 @@ pub struct RepoLoader {
     op_heads_store: Arc<dyn OpHeadsStore>,
     index_store: Arc<dyn IndexStore>,
     submodule_store: Arc<dyn SubmoduleStore>,
+    read_only: bool,
 }
 
 impl RepoLoader {
@@ -604,10 +803,15 @@ impl RepoLoader {
         repo_path: &Path,
         store_factories: &StoreFactories,
     ) -> Result<Self, StoreLoadError> {
+        // Check the formats first, since the stores may fail to load data
+        // written by newer versions in confusing ways.
+        let compatibility = format_version::check_repo(repo_path, &SupportedVersions::current())?;
         let store = Store::new(
             store_factories.load_backend(user_settings, &repo_path.join("store"))?,
             Signer::from_settings(user_settings)?,
             user_settings.use_tree_conflict_format(),
+            user_settings.strict_store_validation(),
+            user_settings.merge_drivers()?,
         );
         let repo_settings = user_settings.with_repo(repo_path).unwrap();
         let op_store =
@@ -621,6 +825,12 @@ impl RepoLoader {
             store_factories
                 .load_submodule_store(user_settings, &repo_path.join("submodule_store"))?,
         );
+        // Creating the op heads lock file is the first write most commands
+        // would do, so the permissions of that directory tell whether the repo
+        // is writable. Formats which are newer than supported but still
+        // readable must not be written.
+        let read_only = file_util::is_dir_read_only(&repo_path.join("op_heads"))
+            || compatibility == FormatCompatibility::ReadOnly;
         Ok(Self {
             repo_path: repo_path.to_path_buf(),
             repo_settings,
@@ -629,6 +839,7 @@ impl RepoLoader {
             op_heads_store,
             index_store,
             submodule_store,
+            read_only,
         })
     }
 
@@ -636,6 +847,21 @@ impl RepoLoader {
         &self.repo_path
     }
 
+    /// Whether repos will be loaded in read-only mode.
+    pub fn is_read_only(&self) -> bool {
+        self.read_only
+    }
+
+    /// Sets whether repos will be loaded in read-only mode. The mode is
+    /// enabled automatically if the repo directory can't be written to.
+    ///
+    /// In read-only mode, nothing is written to the repo directory: a missing
+    /// index is built in memory, the op heads are read without taking the
+    /// lock, and attempts to modify the repo fail with `RepoReadOnly`.
+    pub fn set_read_only(&mut self, read_only: bool) {
+        self.read_only = read_only;
+    }
+
     pub fn store(&self) -> &Arc<Store> {
         &self.store
     }
@@ -656,13 +882,23 @@ impl RepoLoader {
         &self,
         user_settings: &UserSettings,
     ) -> Result<Arc<ReadonlyRepo>, RepoLoaderError> {
-        let op = op_heads_store::resolve_op_heads(
-            self.op_heads_store.as_ref(),
-            &self.op_store,
-            |op_heads| self._resolve_op_heads(op_heads, user_settings),
-        )?;
+        let op = if self.read_only {
+            op_heads_store::resolve_op_heads_read_only(
+                self.op_heads_store.as_ref(),
+                &self.op_store,
+                &self.repo_path,
+            )?
+        } else {
+            op_heads_store::resolve_op_heads(
+                self.op_heads_store.as_ref(),
+                &self.op_store,
+                |op_heads| self._resolve_op_heads(op_heads, user_settings),
+            )?
+        };
         let view = op.view()?;
-        Ok(self._finish_load(op, view))
+        let repo = self._finish_load(op, view);
+        check_view_commits(repo.as_ref())?;
+        Ok(repo)
     }
 
     #[instrument(skip(self))]
@@ -689,6 +925,9 @@ impl RepoLoader {
             index: OnceCell::with_value(index),
             change_id_index: OnceCell::new(),
             view,
+            read_only: self.read_only,
+            merged_parent_trees: Default::default(),
+            diff_summaries: Default::default(),
         };
         Arc::new(repo)
     }
@@ -705,7 +944,7 @@ impl RepoLoader {
             tx.mut_repo().rebase_descendants(user_settings)?;
         }
         let merged_repo = tx
-            .write("resolve concurrent operations")
+            .write("resolve concurrent operations")?
             .leave_unpublished();
         Ok(merged_repo.operation().clone())
     }
@@ -723,6 +962,9 @@ impl RepoLoader {
             index: OnceCell::new(),
             change_id_index: OnceCell::new(),
             view,
+            read_only: self.read_only,
+            merged_parent_trees: Default::default(),
+            diff_summaries: Default::default(),
         };
         Arc::new(repo)
     }
@@ -734,6 +976,49 @@ pub struct MutableRepo {
     view: DirtyCell<View>,
     rewritten_commits: HashMap<CommitId, HashSet<CommitId>>,
     abandoned_commits: HashSet<CommitId>,
+    collapsed_conflicts: Vec<CollapsedConflict>,
+    merged_parent_trees: MergedParentTreeCache,
+    diff_summaries: DiffSummaryCache,
+    abandon_on_checkout: AbandonOnCheckout,
+    abandoned_on_checkout: Vec<CommitId>,
+}
+
+/// Which working-copy commits are abandoned when the working copy moves to
+/// another commit. Commits pointed to by local branches, and commits with
+/// descendants, are never abandoned.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum AbandonOnCheckout {
+    /// Abandon the commit if it's empty and has no description.
+    #[default]
+    EmptyUndescribed,
+    /// Never abandon the commit.
+    Never,
+    /// Abandon the commit if it's empty, even if it has a description.
+    Empty,
+}
+
+impl FromStr for AbandonOnCheckout {
+    type Err = config::ConfigError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "empty-undescribed" => Ok(Self::EmptyUndescribed),
+            "never" => Ok(Self::Never),
+            "empty" => Ok(Self::Empty),
+            other => Err(config::ConfigError::Message(format!(
+                "unknown abandon-on-checkout policy: {other}"
+            ))),
+        }
+    }
+}
+
+/// A conflict which had too many sides, and was collapsed when a commit was
+/// rebased. See `MergedTree::limit_conflict_sides()`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CollapsedConflict {
+    /// The rebased commit.
+    pub commit_id: CommitId,
+    pub path: RepoPathBuf,
 }
 
 impl MutableRepo {
@@ -750,9 +1035,26 @@ impl MutableRepo {
             view: DirtyCell::with_clean(mut_view),
             rewritten_commits: Default::default(),
             abandoned_commits: Default::default(),
+            collapsed_conflicts: vec![],
+            merged_parent_trees: Default::default(),
+            diff_summaries: Default::default(),
+            abandon_on_checkout: AbandonOnCheckout::default(),
+            abandoned_on_checkout: vec![],
         }
     }
 
+    /// Sets which working-copy commits `edit()` and `check_out()` abandon when
+    /// moving away from them.
+    pub fn set_abandon_on_checkout(&mut self, policy: AbandonOnCheckout) {
+        self.abandon_on_checkout = policy;
+    }
+
+    /// The working-copy commits that were abandoned in this transaction
+    /// because the working copy moved away from them.
+    pub fn abandoned_on_checkout(&self) -> &[CommitId] {
+        &self.abandoned_on_checkout
+    }
+
     pub fn base_repo(&self) -> &Arc<ReadonlyRepo> {
         &self.base_repo
     }
@@ -864,6 +1166,20 @@ impl MutableRepo {
         self.abandoned_commits.insert(old_id);
     }
 
+    /// Record that the conflict at `path` was collapsed when writing the
+    /// commit.
+    pub fn record_collapsed_conflict(&mut self, commit_id: CommitId, path: RepoPathBuf) {
+        self.collapsed_conflicts
+            .push(CollapsedConflict { commit_id, path });
+    }
+
+    /// The conflicts collapsed by rebasing commits in this transaction. The
+    /// caller should warn about them, since the conflict sides are no longer
+    /// recorded separately.
+    pub fn collapsed_conflicts(&self) -> &[CollapsedConflict] {
+        &self.collapsed_conflicts
+    }
+
     fn clear_descendant_rebaser_plans(&mut self) {
         self.rewritten_commits.clear();
         self.abandoned_commits.clear();
@@ -1000,15 +1316,24 @@ impl MutableRepo {
                 .store()
                 .get_commit(&wc_commit_id)
                 .map_err(EditCommitError::WorkingCopyCommitNotFound)?;
-            if wc_commit.is_discardable()
+            let policy_allows = match self.abandon_on_checkout {
+                AbandonOnCheckout::EmptyUndescribed => wc_commit.is_discardable(),
+                AbandonOnCheckout::Never => false,
+                AbandonOnCheckout::Empty => matches!(
+                    &*wc_commit.parents(),
+                    [parent] if parent.tree_id() == wc_commit.tree_id()
+                ),
+            };
+            if policy_allows
                 && self
                     .view
                     .with_ref(|v| local_branch_target_ids(v).all(|id| id != wc_commit.id()))
                 && self.view().heads().contains(wc_commit.id())
             {
-                // Abandon the working-copy commit we're leaving if it's empty, not pointed by
-                // local branch, and a head commit.
-                self.record_abandoned_commit(wc_commit_id);
+                // Abandon the working-copy commit we're leaving if the policy allows it, and
+                // it's not pointed by local branch, and a head commit.
+                self.record_abandoned_commit(wc_commit_id.clone());
+                self.abandoned_on_checkout.push(wc_commit_id);
             }
         }
         self.set_wc_commit(workspace_id, commit.id().clone())
@@ -1099,6 +1424,88 @@ impl MutableRepo {
         self.view.mark_dirty();
     }
 
+    /// Removes the references to commits that are missing from the store, so
+    /// the repo can be used again after objects were lost. Returns the removed
+    /// references.
+    ///
+    /// A missing visible head is replaced by its nearest ancestors that exist,
+    /// as far as the index knows them. A workspace whose working-copy commit is
+    /// missing gets a new working-copy commit on top of the root commit, with
+    /// the tree from `wc_tree_ids` or else the empty tree.
+    pub fn remove_missing_view_commits(
+        &mut self,
+        settings: &UserSettings,
+        wc_tree_ids: &HashMap<WorkspaceId, MergedTreeId>,
+    ) -> BackendResult<Vec<(CommitId, ViewReference)>> {
+        let mut removed = vec![];
+        for (id, reference) in self.view().commit_references() {
+            if !self.store().contains(ObjectKind::Commit, id.as_bytes())? {
+                removed.push((id.clone(), reference));
+            }
+        }
+
+        for (id, reference) in &removed {
+            match reference {
+                ViewReference::Head => {
+                    self.remove_head(id);
+                    let mut to_visit = vec![id.clone()];
+                    while let Some(id) = to_visit.pop() {
+                        if !self.index().has_id(&id) {
+                            continue;
+                        }
+                        let parent_ids = revset::RevsetExpression::commit(id)
+                            .parents()
+                            .evaluate_programmatic(&*self)
+                            .map_err(|err| BackendError::Other(err.into()))?
+                            .iter()
+                            .collect_vec();
+                        for parent_id in parent_ids {
+                            if self
+                                .store()
+                                .contains(ObjectKind::Commit, parent_id.as_bytes())?
+                            {
+                                self.view_mut().add_head(&parent_id);
+                            } else {
+                                to_visit.push(parent_id);
+                            }
+                        }
+                    }
+                    self.view.mark_dirty();
+                }
+                ViewReference::WorkingCopy(workspace_id) => {
+                    let wc_commit = self
+                        .new_commit(
+                            settings,
+                            vec![self.store().root_commit_id().clone()],
+                            wc_tree_ids
+                                .get(workspace_id)
+                                .cloned()
+                                .unwrap_or_else(|| self.store().empty_merged_tree_id()),
+                        )
+                        .write()?;
+                    self.view_mut()
+                        .set_wc_commit(workspace_id.clone(), wc_commit.id().clone());
+                }
+                ViewReference::LocalBranch(name) => {
+                    self.set_local_branch_target(name, RefTarget::absent());
+                }
+                ViewReference::RemoteBranch { name, remote_name } => {
+                    self.set_remote_branch(name, remote_name, RemoteRef::absent());
+                }
+                ViewReference::Tag(name) => {
+                    self.set_tag_target(name, RefTarget::absent());
+                }
+                ViewReference::GitRef(name) => {
+                    self.set_git_ref_target(name, RefTarget::absent());
+                }
+                ViewReference::GitHead => {
+                    self.set_git_head_target(RefTarget::absent());
+                }
+            }
+        }
+        Ok(removed)
+    }
+
     /// Returns true if any local or remote branch of the given `name` exists.
     #[must_use]
     pub fn has_branch(&self, name: &str) -> bool {
@@ -1117,6 +1524,12 @@ impl MutableRepo {
         self.view_mut().set_local_branch_target(name, target);
     }
 
+    /// Resolves the conflicted local branch by pointing it to `commit_id`.
+    /// Returns false if the branch isn't conflicted.
+    pub fn resolve_branch_conflict(&mut self, name: &str, commit_id: CommitId) -> bool {
+        self.view_mut().resolve_branch_conflict(name, commit_id)
+    }
+
     pub fn merge_local_branch(
         &mut self,
         name: &str,
@@ -1195,6 +1608,31 @@ impl MutableRepo {
         view.set_tag_target(name, new_target);
     }
 
+    /// Points the tag `name` to `commit_id`. Fails if the name isn't valid, or
+    /// if the tag already exists and `allow_move` is false.
+    pub fn create_tag(
+        &mut self,
+        name: &str,
+        commit_id: &CommitId,
+        allow_move: bool,
+    ) -> Result<(), TagError> {
+        refs::validate_tag_name(name).map_err(|err| TagError::InvalidName(name.to_owned(), err))?;
+        if !allow_move && self.get_tag(name).is_present() {
+            return Err(TagError::AlreadyExists(name.to_owned()));
+        }
+        self.set_tag_target(name, RefTarget::normal(commit_id.clone()));
+        Ok(())
+    }
+
+    /// Deletes the tag `name`, which may be conflicted.
+    pub fn delete_tag(&mut self, name: &str) -> Result<(), TagError> {
+        if self.get_tag(name).is_absent() {
+            return Err(TagError::NotFound(name.to_owned()));
+        }
+        self.set_tag_target(name, RefTarget::absent());
+        Ok(())
+    }
+
     pub fn get_git_ref(&self, name: &str) -> RefTarget {
         self.view.with_ref(|v| v.get_git_ref(name).clone())
     }
@@ -1238,15 +1676,17 @@ impl MutableRepo {
     }
 
     fn merge_view(&mut self, base: &View, other: &View) {
+        let diff = base.diff(other);
+
         // Merge working-copy commits. If there's a conflict, we keep the self side.
-        for (workspace_id, base_wc_commit) in base.wc_commit_ids() {
+        for (workspace_id, (base_wc_commit, other_wc_commit)) in diff.wc_commits {
             let self_wc_commit = self.view().get_wc_commit_id(workspace_id);
-            let other_wc_commit = other.get_wc_commit_id(workspace_id);
-            if other_wc_commit == Some(base_wc_commit) || other_wc_commit == self_wc_commit {
-                // The other side didn't change or both sides changed in the
-                // same way.
+            if other_wc_commit == self_wc_commit {
+                // Both sides changed in the same way.
             } else if let Some(other_wc_commit) = other_wc_commit {
-                if self_wc_commit == Some(base_wc_commit) {
+                // The other side added the workspace or changed its working-copy
+                // commit. Apply that only if the self side didn't change it.
+                if self_wc_commit == base_wc_commit {
                     self.view_mut()
                         .set_wc_commit(workspace_id.clone(), other_wc_commit.clone());
                 }
@@ -1256,15 +1696,6 @@ impl MutableRepo {
                 self.view_mut().remove_wc_commit(workspace_id);
             }
         }
-        for (workspace_id, other_wc_commit) in other.wc_commit_ids() {
-            if self.view().get_wc_commit_id(workspace_id).is_none()
-                && base.get_wc_commit_id(workspace_id).is_none()
-            {
-                // The other side added the workspace.
-                self.view_mut()
-                    .set_wc_commit(workspace_id.clone(), other_wc_commit.clone());
-            }
-        }
 
         let base_heads = base.heads().iter().cloned().collect_vec();
         let own_heads = self.view().heads().iter().cloned().collect_vec();
@@ -1273,39 +1704,35 @@ impl MutableRepo {
         self.record_rewrites(&base_heads, &other_heads);
         // No need to remove heads removed by `other` because we already marked them
         // abandoned or rewritten.
-        for added_head in other.heads().difference(base.heads()) {
+        for added_head in diff.added_heads {
             self.view_mut().add_head(added_head);
         }
 
-        let changed_local_branches =
-            diff_named_ref_targets(base.local_branches(), other.local_branches());
-        for (name, (base_target, other_target)) in changed_local_branches {
+        for (name, (base_target, other_target)) in diff.local_branches {
             self.merge_local_branch(name, base_target, other_target);
         }
 
-        let changed_tags = diff_named_ref_targets(base.tags(), other.tags());
-        for (name, (base_target, other_target)) in changed_tags {
+        for (name, (base_target, other_target)) in diff.tags {
             self.merge_tag(name, base_target, other_target);
         }
 
-        let changed_git_refs = diff_named_ref_targets(base.git_refs(), other.git_refs());
-        for (name, (base_target, other_target)) in changed_git_refs {
+        for (name, (base_target, other_target)) in diff.git_refs {
             self.merge_git_ref(name, base_target, other_target);
         }
 
-        let changed_remote_branches =
-            diff_named_remote_refs(base.all_remote_branches(), other.all_remote_branches());
-        for ((name, remote_name), (base_ref, other_ref)) in changed_remote_branches {
+        for ((name, remote_name), (base_ref, other_ref)) in diff.remote_branches {
             self.merge_remote_branch(name, remote_name, base_ref, other_ref);
         }
 
-        let new_git_head_target = merge_ref_targets(
-            self.index(),
-            self.view().git_head(),
-            base.git_head(),
-            other.git_head(),
-        );
-        self.set_git_head_target(new_git_head_target);
+        if let Some((base_git_head, other_git_head)) = diff.git_head {
+            let new_git_head_target = merge_ref_targets(
+                self.index(),
+                self.view().git_head(),
+                base_git_head,
+                other_git_head,
+            );
+            self.set_git_head_target(new_git_head_target);
+        }
     }
 
     /// Finds and records commits that were rewritten or abandoned between
@@ -1388,6 +1815,14 @@ impl Repo for MutableRepo {
         let change_id_index = self.index.change_id_index(&mut self.view().heads().iter());
         change_id_index.shortest_unique_prefix_len(target_id)
     }
+
+    fn merged_parent_tree(&self, commit: &Commit) -> Result<MergedTree, TreeMergeError> {
+        self.merged_parent_trees.get_or_merge(self, commit)
+    }
+
+    fn diff_summary(&self, commit: &Commit) -> Result<Arc<DiffSummary>, TreeMergeError> {
+        self.diff_summaries.get_or_compute(self, commit)
+    }
 }
 
 /// Error from attempts to check out the root commit for editing
@@ -1404,6 +1839,17 @@ pub enum EditCommitError {
     RewriteRootCommit,
 }
 
+/// Error from attempts to create or delete a tag
+#[derive(Debug, Error)]
+pub enum TagError {
+    #[error("Invalid tag name: {0:?}")]
+    InvalidName(String, #[source] RefNameError),
+    #[error("Tag already exists: {0}")]
+    AlreadyExists(String),
+    #[error("No such tag: {0}")]
+    NotFound(String),
+}
+
 /// Error from attempts to check out a commit
 #[derive(Debug, Error)]
 pub enum CheckOutCommitError {
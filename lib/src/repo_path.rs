@@ -14,12 +14,14 @@
 
 #![allow(missing_docs)]
 
-use std::borrow::Borrow;
+use std::borrow::{Borrow, Cow};
 use std::cmp::Ordering;
+use std::ffi::OsStr;
 use std::fmt::{Debug, Error, Formatter};
 use std::iter::FusedIterator;
 use std::ops::Deref;
 use std::path::{Component, Path, PathBuf};
+use std::str;
 
 use ref_cast::{ref_cast_custom, RefCastCustom};
 use thiserror::Error;
@@ -28,19 +30,35 @@ use crate::file_util;
 
 content_hash! {
     /// Owned `RepoPath` component.
-    #[derive(Clone, PartialEq, Eq, PartialOrd, Ord, Debug, Hash)]
+    #[derive(Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
     pub struct RepoPathComponentBuf {
         // Don't add more fields. Eq, Hash, and Ord must be compatible with the
         // borrowed RepoPathComponent type.
-        value: String,
+        value: Vec<u8>,
     }
 }
 
 /// Borrowed `RepoPath` component.
-#[derive(PartialEq, Eq, PartialOrd, Ord, Debug, Hash, RefCastCustom)]
+///
+/// File names are stored as raw bytes, so names from Git trees that aren't
+/// valid UTF-8 round-trip, and components are ordered by bytes like in Git
+/// trees.
+#[derive(PartialEq, Eq, PartialOrd, Ord, Hash, RefCastCustom)]
 #[repr(transparent)]
 pub struct RepoPathComponent {
-    value: str,
+    value: [u8],
+}
+
+impl Debug for RepoPathComponent {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), Error> {
+        fmt_bytes_debug(&self.value, f)
+    }
+}
+
+impl Debug for RepoPathComponentBuf {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), Error> {
+        <RepoPathComponent as Debug>::fmt(self, f)
+    }
 }
 
 impl RepoPathComponent {
@@ -48,28 +66,104 @@ impl RepoPathComponent {
     ///
     /// The input `value` must not be empty and not contain path separator.
     pub fn new(value: &str) -> &Self {
-        assert!(is_valid_repo_path_component_str(value));
+        Self::from_bytes(value.as_bytes())
+    }
+
+    /// Wraps a file name that may not be valid UTF-8, e.g. one read from a Git
+    /// tree, as `RepoPathComponent`.
+    ///
+    /// The input `value` must not be empty and not contain path separator.
+    pub fn from_bytes(value: &[u8]) -> &Self {
+        assert!(is_valid_repo_path_component(value));
         Self::new_unchecked(value)
     }
 
     #[ref_cast_custom]
-    const fn new_unchecked(value: &str) -> &Self;
+    const fn new_unchecked(value: &[u8]) -> &Self;
 
-    pub fn as_str(&self) -> &str {
+    /// Returns the file name as raw bytes.
+    pub fn as_bytes(&self) -> &[u8] {
         &self.value
     }
+
+    /// Returns the file name if it's valid UTF-8.
+    pub fn to_str(&self) -> Option<&str> {
+        str::from_utf8(&self.value).ok()
+    }
+
+    /// Returns the file name for display, with bytes that aren't valid UTF-8
+    /// replaced by U+FFFD.
+    pub fn to_string_lossy(&self) -> Cow<'_, str> {
+        String::from_utf8_lossy(&self.value)
+    }
+
+    /// Returns true if this is the name of a directory where jj or Git keeps
+    /// its internal state (`.jj` or `.git`). Such names are compared
+    /// case-insensitively since they refer to the same directory on
+    /// case-insensitive file systems.
+    pub fn is_reserved(&self) -> bool {
+        RESERVED_COMPONENTS
+            .iter()
+            .any(|reserved| self.value.eq_ignore_ascii_case(reserved.as_bytes()))
+    }
+
+    /// Returns the file name to use in the working copy.
+    ///
+    /// On Unix, this is the raw file name. On other platforms, file names that
+    /// aren't valid UTF-8 can't be represented, and the lossy name is
+    /// returned. Callers should skip such files (see `to_str()`).
+    pub fn to_fs_name(&self) -> Cow<'_, OsStr> {
+        #[cfg(unix)]
+        {
+            use std::os::unix::ffi::OsStrExt as _;
+            Cow::Borrowed(OsStr::from_bytes(&self.value))
+        }
+        #[cfg(not(unix))]
+        {
+            match self.to_string_lossy() {
+                Cow::Borrowed(name) => Cow::Borrowed(OsStr::new(name)),
+                Cow::Owned(name) => Cow::Owned(name.into()),
+            }
+        }
+    }
+}
+
+impl RepoPathComponentBuf {
+    /// Creates a component from a file name that may not be valid UTF-8, e.g.
+    /// one read from a Git tree.
+    ///
+    /// The input must not be empty and not contain path separator.
+    pub fn from_bytes(value: impl Into<Vec<u8>>) -> Self {
+        let value = value.into();
+        assert!(is_valid_repo_path_component(&value));
+        RepoPathComponentBuf { value }
+    }
+
+    /// Creates a component from a file name in the working copy. Returns
+    /// `None` if the name can't be represented, which is only possible on
+    /// platforms other than Unix.
+    pub fn from_fs_name(name: &OsStr) -> Option<Self> {
+        #[cfg(unix)]
+        {
+            use std::os::unix::ffi::OsStrExt as _;
+            Some(RepoPathComponentBuf::from_bytes(name.as_bytes()))
+        }
+        #[cfg(not(unix))]
+        {
+            name.to_str().map(RepoPathComponentBuf::from)
+        }
+    }
 }
 
 impl From<&str> for RepoPathComponentBuf {
     fn from(value: &str) -> Self {
-        RepoPathComponentBuf::from(value.to_owned())
+        RepoPathComponentBuf::from_bytes(value)
     }
 }
 
 impl From<String> for RepoPathComponentBuf {
     fn from(value: String) -> Self {
-        assert!(is_valid_repo_path_component_str(&value));
-        RepoPathComponentBuf { value }
+        RepoPathComponentBuf::from_bytes(value)
     }
 }
 
@@ -115,13 +209,13 @@ impl ToOwned for RepoPathComponent {
 /// Iterator over `RepoPath` components.
 #[derive(Clone, Debug)]
 pub struct RepoPathComponentsIter<'a> {
-    value: &'a str,
+    value: &'a [u8],
 }
 
 impl<'a> RepoPathComponentsIter<'a> {
     /// Returns the remaining part as repository path.
     pub fn as_path(&self) -> &'a RepoPath {
-        RepoPath::from_internal_string_unchecked(self.value)
+        RepoPath::from_internal_bytes_unchecked(self.value)
     }
 }
 
@@ -132,10 +226,10 @@ impl<'a> Iterator for RepoPathComponentsIter<'a> {
         if self.value.is_empty() {
             return None;
         }
-        let (name, remainder) = self
-            .value
-            .split_once('/')
-            .unwrap_or_else(|| (&self.value, &self.value[self.value.len()..]));
+        let (name, remainder) = match self.value.iter().position(|&b| b == b'/') {
+            Some(pos) => (&self.value[..pos], &self.value[pos + 1..]),
+            None => (self.value, &self.value[self.value.len()..]),
+        };
         self.value = remainder;
         Some(RepoPathComponent::new_unchecked(name))
     }
@@ -146,10 +240,10 @@ impl DoubleEndedIterator for RepoPathComponentsIter<'_> {
         if self.value.is_empty() {
             return None;
         }
-        let (remainder, name) = self
-            .value
-            .rsplit_once('/')
-            .unwrap_or_else(|| (&self.value[..0], &self.value));
+        let (remainder, name) = match self.value.iter().rposition(|&b| b == b'/') {
+            Some(pos) => (&self.value[..pos], &self.value[pos + 1..]),
+            None => (&self.value[..0], self.value),
+        };
         self.value = remainder;
         Some(RepoPathComponent::new_unchecked(name))
     }
@@ -162,19 +256,21 @@ impl FusedIterator for RepoPathComponentsIter<'_> {}
 pub struct RepoPathBuf {
     // Don't add more fields. Eq, Hash, and Ord must be compatible with the
     // borrowed RepoPath type.
-    value: String,
+    value: Vec<u8>,
 }
 
 /// Borrowed repository path.
+///
+/// Like `RepoPathComponent`, paths are stored as raw bytes.
 #[derive(Eq, Hash, PartialEq, RefCastCustom)]
 #[repr(transparent)]
 pub struct RepoPath {
-    value: str,
+    value: [u8],
 }
 
 impl Debug for RepoPath {
     fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), Error> {
-        f.write_fmt(format_args!("{:?}", &self.value))
+        fmt_bytes_debug(&self.value, f)
     }
 }
 
@@ -184,12 +280,18 @@ impl Debug for RepoPathBuf {
     }
 }
 
+/// Formats `value` like a string, escaping the bytes that aren't valid UTF-8.
+fn fmt_bytes_debug(value: &[u8], f: &mut Formatter<'_>) -> Result<(), Error> {
+    match str::from_utf8(value) {
+        Ok(value) => Debug::fmt(value, f),
+        Err(_) => write!(f, "\"{}\"", value.escape_ascii()),
+    }
+}
+
 impl RepoPathBuf {
     /// Creates owned repository path pointing to the root.
     pub const fn root() -> Self {
-        RepoPathBuf {
-            value: String::new(),
-        }
+        RepoPathBuf { value: Vec::new() }
     }
 
     /// Creates `RepoPathBuf` from valid string representation.
@@ -197,31 +299,44 @@ impl RepoPathBuf {
     /// The input `value` must not contain empty path components. For example,
     /// `"/"`, `"/foo"`, `"foo/"`, `"foo//bar"` are all invalid.
     pub fn from_internal_string(value: impl Into<String>) -> Self {
+        Self::from_internal_bytes(value.into())
+    }
+
+    /// Creates `RepoPathBuf` from the raw bytes returned by
+    /// `RepoPath::as_internal_bytes()`.
+    ///
+    /// The input `value` must not contain empty path components.
+    pub fn from_internal_bytes(value: impl Into<Vec<u8>>) -> Self {
         let value = value.into();
-        assert!(is_valid_repo_path_str(&value));
+        assert!(is_valid_repo_path(&value));
         RepoPathBuf { value }
     }
 
     /// Converts repo-relative `Path` to `RepoPathBuf`.
     ///
-    /// The input path should not contain `.` or `..`.
+    /// Returns `None` if the path is absolute or contains a `..` component,
+    /// which could point outside the repo, or a leading `.`. Empty components
+    /// (`a//b`) and inner `.` components are normalized away by `Path`, so they
+    /// never end up in a `RepoPath`.
     pub fn from_relative_path(relative_path: impl AsRef<Path>) -> Option<Self> {
         let relative_path = relative_path.as_ref();
         let mut components = relative_path
             .components()
             .map(|c| match c {
-                Component::Normal(name) => Some(name.to_str().unwrap()),
-                // TODO: better to return Err instead of None?
-                _ => None,
+                Component::Normal(name) => RepoPathComponentBuf::from_fs_name(name),
+                Component::Prefix(_)
+                | Component::RootDir
+                | Component::CurDir
+                | Component::ParentDir => None,
             })
             .fuse();
-        let mut value = String::with_capacity(relative_path.as_os_str().len());
+        let mut value = Vec::with_capacity(relative_path.as_os_str().len());
         if let Some(name) = components.next() {
-            value.push_str(name?);
+            value.extend_from_slice(name?.as_bytes());
         }
         for name in components {
-            value.push('/');
-            value.push_str(name?);
+            value.push(b'/');
+            value.extend_from_slice(name?.as_bytes());
         }
         Some(RepoPathBuf { value })
     }
@@ -246,8 +361,8 @@ impl RepoPathBuf {
             .ok_or_else(|| FsPathParseError::InputNotInRepo(input.to_owned()))
     }
 
-    /// Consumes this and returns the underlying string representation.
-    pub fn into_internal_string(self) -> String {
+    /// Consumes this and returns the underlying byte representation.
+    pub fn into_internal_bytes(self) -> Vec<u8> {
         self.value
     }
 }
@@ -255,7 +370,7 @@ impl RepoPathBuf {
 impl RepoPath {
     /// Returns repository path pointing to the root.
     pub const fn root() -> &'static Self {
-        Self::from_internal_string_unchecked("")
+        Self::from_internal_bytes_unchecked(b"")
     }
 
     /// Wraps valid string representation as `RepoPath`.
@@ -263,35 +378,53 @@ impl RepoPath {
     /// The input `value` must not contain empty path components. For example,
     /// `"/"`, `"/foo"`, `"foo/"`, `"foo//bar"` are all invalid.
     pub fn from_internal_string(value: &str) -> &Self {
-        assert!(is_valid_repo_path_str(value));
-        Self::from_internal_string_unchecked(value)
+        Self::from_internal_bytes(value.as_bytes())
+    }
+
+    /// Wraps the raw bytes returned by `as_internal_bytes()` as `RepoPath`.
+    ///
+    /// The input `value` must not contain empty path components.
+    pub fn from_internal_bytes(value: &[u8]) -> &Self {
+        assert!(is_valid_repo_path(value));
+        Self::from_internal_bytes_unchecked(value)
     }
 
     #[ref_cast_custom]
-    const fn from_internal_string_unchecked(value: &str) -> &Self;
+    const fn from_internal_bytes_unchecked(value: &[u8]) -> &Self;
 
     /// The full string form used internally, not for presenting to users (where
     /// we may want to use the platform's separator). This format includes a
     /// trailing slash, unless this path represents the root directory. That
     /// way it can be concatenated with a basename and produce a valid path.
+    ///
+    /// Bytes that aren't valid UTF-8 are replaced by U+FFFD.
     pub fn to_internal_dir_string(&self) -> String {
         if self.value.is_empty() {
             String::new()
         } else {
-            [&self.value, "/"].concat()
+            format!("{}/", self.as_internal_file_string())
         }
     }
 
     /// The full string form used internally, not for presenting to users (where
     /// we may want to use the platform's separator).
-    pub fn as_internal_file_string(&self) -> &str {
+    ///
+    /// Bytes that aren't valid UTF-8 are replaced by U+FFFD, so the result
+    /// only parses back to the same path if it's valid UTF-8. Use
+    /// `as_internal_bytes()` to serialize paths.
+    pub fn as_internal_file_string(&self) -> Cow<'_, str> {
+        String::from_utf8_lossy(&self.value)
+    }
+
+    /// The full form used internally, as raw bytes.
+    pub fn as_internal_bytes(&self) -> &[u8] {
         &self.value
     }
 
     pub fn to_fs_path(&self, base: &Path) -> PathBuf {
         let mut result = PathBuf::with_capacity(base.as_os_str().len() + self.value.len() + 1);
         result.push(base);
-        result.extend(self.components().map(RepoPathComponent::as_str));
+        result.extend(self.components().map(RepoPathComponent::to_fs_name));
         result
     }
 
@@ -299,6 +432,12 @@ impl RepoPath {
         self.value.is_empty()
     }
 
+    /// Returns true if any component of this path is reserved for internal
+    /// state. See `RepoPathComponent::is_reserved()`.
+    pub fn has_reserved_component(&self) -> bool {
+        self.components().any(RepoPathComponent::is_reserved)
+    }
+
     /// Returns true if the `base` is a prefix of this path.
     pub fn starts_with(&self, base: &RepoPath) -> bool {
         self.strip_prefix(base).is_some()
@@ -311,10 +450,10 @@ impl RepoPath {
         } else {
             let tail = self.value.strip_prefix(&base.value)?;
             if tail.is_empty() {
-                Some(RepoPath::from_internal_string_unchecked(tail))
+                Some(RepoPath::from_internal_bytes_unchecked(tail))
             } else {
-                tail.strip_prefix('/')
-                    .map(RepoPath::from_internal_string_unchecked)
+                tail.strip_prefix(b"/")
+                    .map(RepoPath::from_internal_bytes_unchecked)
             }
         }
     }
@@ -337,9 +476,9 @@ impl RepoPath {
 
     pub fn join(&self, entry: &RepoPathComponent) -> RepoPathBuf {
         let value = if self.value.is_empty() {
-            entry.as_str().to_owned()
+            entry.as_bytes().to_owned()
         } else {
-            [&self.value, "/", entry.as_str()].concat()
+            [&self.value, b"/".as_slice(), entry.as_bytes()].concat()
         };
         RepoPathBuf { value }
     }
@@ -367,7 +506,7 @@ impl Deref for RepoPathBuf {
     type Target = RepoPath;
 
     fn deref(&self) -> &Self::Target {
-        RepoPath::from_internal_string_unchecked(&self.value)
+        RepoPath::from_internal_bytes_unchecked(&self.value)
     }
 }
 
@@ -387,8 +526,8 @@ impl ToOwned for RepoPath {
 impl Ord for RepoPath {
     fn cmp(&self, other: &Self) -> Ordering {
         // If there were leading/trailing slash, components-based Ord would
-        // disagree with str-based Eq.
-        debug_assert!(is_valid_repo_path_str(&self.value));
+        // disagree with bytes-based Eq.
+        debug_assert!(is_valid_repo_path(&self.value));
         self.components().cmp(other.components())
     }
 }
@@ -417,12 +556,65 @@ pub enum FsPathParseError {
     InputNotInRepo(PathBuf),
 }
 
-fn is_valid_repo_path_component_str(value: &str) -> bool {
-    !value.is_empty() && !value.contains('/')
+/// Converts between `RepoPath`s and the paths shown to or given by the user.
+///
+/// Paths are formatted relative to the cwd, so parsing a formatted path gives
+/// back the same `RepoPath`. Paths can also be formatted relative to the
+/// workspace root, which is more useful for scripts, but such paths only parse
+/// back to the same `RepoPath` if the cwd is the workspace root.
+#[derive(Clone, Debug)]
+pub struct RepoPathUiConverter {
+    cwd: PathBuf,
+    base: PathBuf,
+    relative_to_cwd: bool,
 }
 
-fn is_valid_repo_path_str(value: &str) -> bool {
-    !value.starts_with('/') && !value.ends_with('/') && !value.contains("//")
+impl RepoPathUiConverter {
+    /// Creates a converter for paths in the workspace at `base`.
+    ///
+    /// The `cwd` and `base` paths are supposed to be absolute and normalized
+    /// in the same manner.
+    pub fn new(cwd: PathBuf, base: PathBuf, relative_to_cwd: bool) -> Self {
+        RepoPathUiConverter {
+            cwd,
+            base,
+            relative_to_cwd,
+        }
+    }
+
+    /// Formats `file` as a path relative to the cwd, starting with `../` if
+    /// the file isn't under the cwd, or as a path relative to the workspace
+    /// root. The cwd or the root itself is formatted as `.`.
+    pub fn format_file_path(&self, file: &RepoPath) -> String {
+        let path = if self.relative_to_cwd {
+            file_util::relative_path(&self.cwd, &file.to_fs_path(&self.base))
+        } else if file.is_root() {
+            PathBuf::from(".")
+        } else {
+            file.to_fs_path(Path::new(""))
+        };
+        path.to_string_lossy().into_owned()
+    }
+
+    /// Parses a path relative to the cwd, or an absolute path.
+    pub fn parse_file_path(&self, input: &str) -> Result<RepoPathBuf, FsPathParseError> {
+        RepoPathBuf::parse_fs_path(&self.cwd, &self.base, input)
+    }
+}
+
+/// Names of directories that hold internal state and must never be tracked.
+const RESERVED_COMPONENTS: [&str; 2] = [".jj", ".git"];
+
+fn is_valid_repo_path_component(value: &[u8]) -> bool {
+    !value.is_empty() && !value.contains(&b'/')
+}
+
+pub(crate) fn is_valid_repo_path(value: &[u8]) -> bool {
+    !value.starts_with(b"/") && !value.ends_with(b"/") && !value.windows(2).any(|w| w == b"//")
+}
+
+pub(crate) fn is_valid_repo_path_str(value: &str) -> bool {
+    is_valid_repo_path(value.as_bytes())
 }
 
 #[cfg(test)]
@@ -460,6 +652,30 @@ mod tests {
         assert!(panic::catch_unwind(|| repo_path("x//y")).is_err());
     }
 
+    #[test]
+    fn test_from_relative_path() {
+        let from_relative_path = |value: &str| RepoPathBuf::from_relative_path(value);
+        assert_eq!(from_relative_path(""), Some(RepoPathBuf::root()));
+        assert_eq!(
+            from_relative_path("dir/file"),
+            Some(repo_path("dir/file").to_owned())
+        );
+        assert_eq!(
+            from_relative_path("dir//file"),
+            Some(repo_path("dir/file").to_owned())
+        );
+        assert_eq!(
+            from_relative_path("dir/./file"),
+            Some(repo_path("dir/file").to_owned())
+        );
+        assert_eq!(from_relative_path(".."), None);
+        assert_eq!(from_relative_path("../file"), None);
+        assert_eq!(from_relative_path("dir/../file"), None);
+        assert_eq!(from_relative_path("dir/.."), None);
+        assert_eq!(from_relative_path("./file"), None);
+        assert_eq!(from_relative_path("/file"), None);
+    }
+
     #[test]
     fn test_as_internal_file_string() {
         assert_eq!(RepoPath::root().as_internal_file_string(), "");
@@ -615,6 +831,19 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_has_reserved_component() {
+        assert!(!RepoPath::root().has_reserved_component());
+        assert!(!repo_path("dir/file").has_reserved_component());
+        assert!(!repo_path("dir/.jjconfig").has_reserved_component());
+        assert!(!repo_path(".gitignore").has_reserved_component());
+        assert!(repo_path(".jj").has_reserved_component());
+        assert!(repo_path(".jj/repo/store").has_reserved_component());
+        assert!(repo_path("dir/.git/config").has_reserved_component());
+        assert!(repo_path(".JJ/file").has_reserved_component());
+        assert!(repo_path("dir/.Git").has_reserved_component());
+    }
+
     #[test]
     fn test_to_fs_path() {
         assert_eq!(
@@ -636,6 +865,70 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_component_from_bytes() {
+        let name = RepoPathComponentBuf::from_bytes(b"file".as_slice());
+        assert_eq!(name.to_str(), Some("file"));
+        assert_eq!(name.as_bytes(), b"file");
+        assert_eq!(&*name, RepoPathComponent::new("file"));
+
+        // Invalid UTF-8 is kept as is
+        let name = RepoPathComponentBuf::from_bytes(b"caf\xe9".as_slice());
+        assert_eq!(name.to_str(), None);
+        assert_eq!(name.as_bytes(), b"caf\xe9");
+        assert_eq!(name.to_string_lossy(), "caf\u{FFFD}");
+        assert_eq!(format!("{name:?}"), r#""caf\xe9""#);
+        assert_ne!(name, RepoPathComponentBuf::from("caf\u{e9}"));
+    }
+
+    #[test]
+    fn test_non_utf8_path() {
+        let path = RepoPathBuf::from_internal_bytes(b"dir/caf\xe9".as_slice());
+        assert_eq!(path.as_internal_bytes(), b"dir/caf\xe9");
+        assert_eq!(path.as_internal_file_string(), "dir/caf\u{FFFD}");
+        assert_eq!(
+            path.components().map(|c| c.as_bytes()).collect_vec(),
+            vec![b"dir".as_slice(), b"caf\xe9"]
+        );
+        assert_eq!(path.parent(), Some(repo_path("dir")));
+        #[cfg(unix)]
+        {
+            use std::os::unix::ffi::OsStrExt as _;
+            assert_eq!(
+                path.to_fs_path(Path::new("base")).as_os_str().as_bytes(),
+                b"base/dir/caf\xe9"
+            );
+        }
+    }
+
+    #[test]
+    fn test_order_is_byte_order() {
+        // Like in Git trees, names are ordered by their bytes, so a name that
+        // isn't valid UTF-8 sorts after any ASCII name, and U+FFFD (which
+        // starts with 0xEF) sorts before 0xF0.
+        let name = |bytes: &[u8]| RepoPathComponentBuf::from_bytes(bytes);
+        let mut names = vec![
+            name(b"\xf0"),
+            name("\u{FFFD}".as_bytes()),
+            name(b"\xe9"),
+            name(b"z"),
+        ];
+        names.sort();
+        assert_eq!(
+            names,
+            vec![
+                name(b"z"),
+                name(b"\xe9"),
+                name("\u{FFFD}".as_bytes()),
+                name(b"\xf0"),
+            ]
+        );
+        assert!(
+            RepoPathBuf::from_internal_bytes(b"a/\xe9".as_slice())
+                < RepoPathBuf::from_internal_bytes(b"a/\xf0".as_slice())
+        );
+    }
+
     #[test]
     fn parse_fs_path_wc_in_cwd() {
         let temp_dir = testutils::new_temp_dir();
@@ -755,4 +1048,67 @@ mod tests {
             Ok(repo_path("dir/file"))
         );
     }
+
+    #[test]
+    fn test_ui_converter_round_trip() {
+        let temp_dir = testutils::new_temp_dir();
+        let wc_path = temp_dir.path().join("repo");
+        let paths = [
+            "",
+            "file",
+            "dir",
+            "dir/file",
+            "dir/subdir/file",
+            "other-dir/file",
+        ];
+        for cwd in ["", "dir", "dir/subdir", "other-dir", "missing/deep/dir"] {
+            let cwd_path = repo_path(cwd).to_fs_path(&wc_path);
+            let converter = RepoPathUiConverter::new(cwd_path, wc_path.clone(), true);
+            for path in paths {
+                let formatted = converter.format_file_path(repo_path(path));
+                assert_eq!(
+                    converter.parse_file_path(&formatted).as_deref(),
+                    Ok(repo_path(path)),
+                    "{path:?} formatted as {formatted:?} from {cwd:?}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_ui_converter_format_file_path() {
+        let temp_dir = testutils::new_temp_dir();
+        let wc_path = temp_dir.path().join("repo");
+        let sep = std::path::MAIN_SEPARATOR;
+        let converter = RepoPathUiConverter::new(wc_path.join("dir"), wc_path.clone(), true);
+        assert_eq!(converter.format_file_path(repo_path("dir")), ".");
+        assert_eq!(converter.format_file_path(repo_path("dir/file")), "file");
+        assert_eq!(converter.format_file_path(RepoPath::root()), "..");
+        assert_eq!(
+            converter.format_file_path(repo_path("other-dir/file")),
+            format!("..{sep}other-dir{sep}file")
+        );
+        // A directory whose name starts with the cwd's name isn't under the cwd
+        assert_eq!(
+            converter.format_file_path(repo_path("dir2/file")),
+            format!("..{sep}dir2{sep}file")
+        );
+
+        // Relative to the workspace root
+        let converter = RepoPathUiConverter::new(wc_path.join("dir"), wc_path.clone(), false);
+        assert_eq!(converter.format_file_path(RepoPath::root()), ".");
+        assert_eq!(
+            converter.format_file_path(repo_path("dir/file")),
+            format!("dir{sep}file")
+        );
+        assert_eq!(
+            converter.format_file_path(repo_path("other-dir/file")),
+            format!("other-dir{sep}file")
+        );
+        // Parsing is still relative to the cwd
+        assert_eq!(
+            converter.parse_file_path("file").as_deref(),
+            Ok(repo_path("dir/file"))
+        );
+    }
 }
@@ -0,0 +1,278 @@
+// Copyright 2024 The Jujutsu Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Path-scoped views of a repo.
+//!
+//! A workspace can be scoped to some path prefixes. Default revsets then only
+//! include commits touching those prefixes, and the working copy is sparse on
+//! them. Commits outside the scope remain in the repo and can be selected
+//! explicitly.
+//!
+//! The scope is stored per workspace in `.jj/scope`, along with the sparse
+//! patterns the working copy had before it was scoped, so they can be
+//! restored when the scope is cleared.
+
+#![allow(missing_docs)]
+
+use std::fs;
+use std::io::{self, Write as _};
+use std::path::Path;
+use std::rc::Rc;
+
+use tempfile::NamedTempFile;
+use thiserror::Error;
+
+use crate::file_util::{IoResultExt as _, PathError};
+use crate::repo_path::{is_valid_repo_path, RepoPath, RepoPathBuf};
+use crate::revset::{RevsetExpression, RevsetFilterPredicate};
+
+const SCOPE_FILE: &str = "scope";
+const SCOPE_RECORD: u8 = b's';
+const BASE_SPARSE_PATTERN_RECORD: u8 = b'b';
+
+#[derive(Debug, Error)]
+pub enum RepoScopeError {
+    #[error(transparent)]
+    Path(#[from] PathError),
+    #[error("Invalid scope file {0}")]
+    InvalidFile(String),
+}
+
+/// Path prefixes the repo view is restricted to. An empty list of prefixes
+/// means the whole repo.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct RepoScope {
+    prefixes: Vec<RepoPathBuf>,
+}
+
+impl RepoScope {
+    pub fn new(prefixes: impl IntoIterator<Item = RepoPathBuf>) -> Self {
+        RepoScope {
+            prefixes: normalize_prefixes(prefixes),
+        }
+    }
+
+    /// Whether the scope covers the whole repo.
+    pub fn is_everything(&self) -> bool {
+        self.prefixes.is_empty() || self.prefixes.iter().any(|prefix| prefix.is_root())
+    }
+
+    pub fn prefixes(&self) -> &[RepoPathBuf] {
+        &self.prefixes
+    }
+
+    /// Whether `path` is in the scope.
+    pub fn contains(&self, path: &RepoPath) -> bool {
+        self.is_everything() || self.prefixes.iter().any(|prefix| path.starts_with(prefix))
+    }
+
+    /// Expression matching the commits that touch the scope, or `None` if the
+    /// scope covers the whole repo. It's evaluated with the changed-path
+    /// filters of the index if they're enabled.
+    pub fn to_revset_expression(&self) -> Option<Rc<RevsetExpression>> {
+        (!self.is_everything()).then(|| {
+            RevsetExpression::filter(RevsetFilterPredicate::File(Some(self.prefixes.clone())))
+        })
+    }
+
+    /// Restricts sparse `patterns` to the scope, i.e. returns the paths that
+    /// are both in the scope and matched by the patterns.
+    pub fn restrict_sparse_patterns(&self, patterns: &[RepoPathBuf]) -> Vec<RepoPathBuf> {
+        if self.is_everything() {
+            return normalize_prefixes(patterns.iter().cloned());
+        }
+        let restricted = patterns.iter().flat_map(|pattern| {
+            self.prefixes.iter().filter_map(move |prefix| {
+                if pattern.starts_with(prefix) {
+                    Some(pattern.clone())
+                } else if prefix.starts_with(pattern) {
+                    Some(prefix.clone())
+                } else {
+                    None
+                }
+            })
+        });
+        normalize_prefixes(restricted)
+    }
+}
+
+/// The scope of a workspace, along with the sparse patterns its working copy
+/// had before it was scoped.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct WorkspaceScope {
+    pub scope: RepoScope,
+    pub base_sparse_patterns: Vec<RepoPathBuf>,
+}
+
+impl WorkspaceScope {
+    /// Loads the scope of the workspace at `workspace_root`. Returns `None` if
+    /// the workspace isn't scoped.
+    pub fn load(workspace_root: &Path) -> Result<Option<Self>, RepoScopeError> {
+        let path = workspace_root.join(".jj").join(SCOPE_FILE);
+        let data = match fs::read(&path) {
+            Ok(data) => data,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(error) => return Err(PathError { path, error }.into()),
+        };
+        let invalid = || RepoScopeError::InvalidFile(path.display().to_string());
+        let mut prefixes = vec![];
+        let mut base_sparse_patterns = vec![];
+        for record in data.split_terminator(|&b| b == 0) {
+            let (&kind, value) = record.split_first().ok_or_else(invalid)?;
+            if !is_valid_repo_path(value) {
+                return Err(invalid());
+            }
+            let value = RepoPathBuf::from_internal_bytes(value);
+            match kind {
+                SCOPE_RECORD => prefixes.push(value),
+                BASE_SPARSE_PATTERN_RECORD => base_sparse_patterns.push(value),
+                _ => return Err(invalid()),
+            }
+        }
+        Ok(Some(WorkspaceScope {
+            scope: RepoScope::new(prefixes),
+            base_sparse_patterns,
+        }))
+    }
+
+    /// Atomically records the scope in the workspace at `workspace_root`.
+    pub fn save(&self, workspace_root: &Path) -> Result<(), RepoScopeError> {
+        let dir = workspace_root.join(".jj");
+        let path = dir.join(SCOPE_FILE);
+        let mut data = vec![];
+        let records = itertools::chain(
+            self.scope.prefixes.iter().map(|p| (SCOPE_RECORD, p)),
+            self.base_sparse_patterns
+                .iter()
+                .map(|p| (BASE_SPARSE_PATTERN_RECORD, p)),
+        );
+        for (kind, value) in records {
+            data.push(kind);
+            data.extend_from_slice(value.as_internal_bytes());
+            data.push(0);
+        }
+        let mut temp_file = NamedTempFile::new_in(&dir).context(&dir)?;
+        temp_file.write_all(&data).context(temp_file.path())?;
+        temp_file
+            .persist(&path)
+            .map_err(|err| err.error)
+            .context(&path)?;
+        Ok(())
+    }
+
+    /// Removes the scope of the workspace at `workspace_root`.
+    pub fn remove(workspace_root: &Path) -> Result<(), RepoScopeError> {
+        let path = workspace_root.join(".jj").join(SCOPE_FILE);
+        match fs::remove_file(&path) {
+            Err(error) if error.kind() != io::ErrorKind::NotFound => {
+                Err(PathError { path, error }.into())
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// The sparse patterns the working copy would have without a scope, given
+    /// its `current` patterns. Patterns added inside the scope while it was
+    /// set are kept, and the base patterns outside the scope come back.
+    pub fn unscoped_sparse_patterns(&self, current: &[RepoPathBuf]) -> Vec<RepoPathBuf> {
+        let outside_scope = self
+            .base_sparse_patterns
+            .iter()
+            .filter(|pattern| !self.scope.contains(pattern))
+            .cloned();
+        normalize_prefixes(current.iter().cloned().chain(outside_scope))
+    }
+}
+
+/// Sorts the prefixes and removes the ones under another prefix.
+fn normalize_prefixes(prefixes: impl IntoIterator<Item = RepoPathBuf>) -> Vec<RepoPathBuf> {
+    let mut prefixes: Vec<_> = prefixes.into_iter().collect();
+    prefixes.sort();
+    prefixes.dedup();
+    let mut normalized: Vec<RepoPathBuf> = vec![];
+    for prefix in prefixes {
+        if !normalized.iter().any(|other| prefix.starts_with(other)) {
+            normalized.push(prefix);
+        }
+    }
+    normalized
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn repo_paths(values: &[&str]) -> Vec<RepoPathBuf> {
+        values
+            .iter()
+            .map(|value| RepoPathBuf::from_internal_string(*value))
+            .collect()
+    }
+
+    #[test]
+    fn test_restrict_sparse_patterns() {
+        let scope = RepoScope::new(repo_paths(&["a", "b/c"]));
+        assert_eq!(
+            scope.restrict_sparse_patterns(&repo_paths(&[""])),
+            repo_paths(&["a", "b/c"])
+        );
+        assert_eq!(
+            scope.restrict_sparse_patterns(&repo_paths(&["a/x", "b", "d"])),
+            repo_paths(&["a/x", "b/c"])
+        );
+        assert_eq!(scope.restrict_sparse_patterns(&repo_paths(&["d"])), vec![]);
+        assert_eq!(
+            RepoScope::default().restrict_sparse_patterns(&repo_paths(&["d", "d/e"])),
+            repo_paths(&["d"])
+        );
+    }
+
+    #[test]
+    fn test_unscoped_sparse_patterns() {
+        let workspace_scope = WorkspaceScope {
+            scope: RepoScope::new(repo_paths(&["a"])),
+            base_sparse_patterns: repo_paths(&["a/x", "b"]),
+        };
+        assert_eq!(
+            workspace_scope.unscoped_sparse_patterns(&repo_paths(&["a/x", "a/y"])),
+            repo_paths(&["a/x", "a/y", "b"])
+        );
+        let workspace_scope = WorkspaceScope {
+            scope: RepoScope::new(repo_paths(&["a"])),
+            base_sparse_patterns: repo_paths(&[""]),
+        };
+        assert_eq!(
+            workspace_scope.unscoped_sparse_patterns(&repo_paths(&["a"])),
+            repo_paths(&[""])
+        );
+    }
+
+    #[test]
+    fn test_save_and_load() {
+        let temp_dir = testutils::new_temp_dir();
+        fs::create_dir(temp_dir.path().join(".jj")).unwrap();
+        assert_eq!(WorkspaceScope::load(temp_dir.path()).unwrap(), None);
+        let workspace_scope = WorkspaceScope {
+            scope: RepoScope::new(repo_paths(&["a", "b/c"])),
+            base_sparse_patterns: repo_paths(&[""]),
+        };
+        workspace_scope.save(temp_dir.path()).unwrap();
+        assert_eq!(
+            WorkspaceScope::load(temp_dir.path()).unwrap(),
+            Some(workspace_scope)
+        );
+        WorkspaceScope::remove(temp_dir.path()).unwrap();
+        assert_eq!(WorkspaceScope::load(temp_dir.path()).unwrap(), None);
+    }
+}
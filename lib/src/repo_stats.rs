@@ -0,0 +1,272 @@
+// Copyright 2024 The Jujutsu Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Statistics about the objects in a repo, for finding out what makes it big.
+
+#![allow(missing_docs)]
+
+use std::collections::{HashMap, HashSet};
+use std::io;
+use std::slice;
+use std::sync::Arc;
+
+use itertools::Itertools as _;
+use thiserror::Error;
+
+use crate::backend::{
+    BackendError, CommitId, ConflictId, FileId, ObjectKind, SymlinkId, TreeId, TreeValue,
+};
+use crate::index::AllHeadsForGcUnsupported;
+use crate::object_id::ObjectId as _;
+use crate::op_store::OpStoreError;
+use crate::op_walk;
+use crate::repo::{ReadonlyRepo, Repo as _};
+use crate::repo_path::{RepoPath, RepoPathBuf};
+use crate::revset::RevsetExpression;
+use crate::store::Store;
+
+/// Number and total size of the objects of one kind.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct ObjectStats {
+    pub count: u64,
+    /// Total size in bytes, as the objects are stored by the backend. For
+    /// files, that's the size of their contents.
+    pub num_bytes: u64,
+}
+
+impl ObjectStats {
+    fn add(&mut self, num_bytes: u64) {
+        self.count += 1;
+        self.num_bytes += num_bytes;
+    }
+}
+
+/// A file and where it first appeared in the history.
+#[derive(Clone, Debug, Eq, Ord, PartialEq, PartialOrd)]
+pub struct LargeFile {
+    pub size: u64,
+    pub id: FileId,
+    /// The path of the file in `commit_id`.
+    pub path: RepoPathBuf,
+    /// The first commit, in topological order, whose tree has the file.
+    pub commit_id: CommitId,
+}
+
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct RepoStats {
+    pub commits: ObjectStats,
+    pub trees: ObjectStats,
+    pub files: ObjectStats,
+    pub symlinks: ObjectStats,
+    pub conflicts: ObjectStats,
+    /// The largest files, largest first.
+    pub largest_files: Vec<LargeFile>,
+    /// Number of operations in the history of the repo's operation.
+    pub num_operations: u64,
+}
+
+#[derive(Debug, Error)]
+pub enum RepoStatsError {
+    #[error(transparent)]
+    Backend(#[from] BackendError),
+    #[error(transparent)]
+    OpStore(#[from] OpStoreError),
+    #[error(transparent)]
+    AllHeadsUnsupported(#[from] AllHeadsForGcUnsupported),
+}
+
+/// Counts the objects reachable from the commits that garbage collection
+/// keeps, i.e. the ancestors of all heads in the index, and finds the
+/// `num_largest_files` largest files.
+///
+/// The commits are streamed from the index, children before parents, and
+/// every object is read once. Apart from the ids of the visited objects, only
+/// the largest files found so far and the trees that contain them are kept.
+/// Since a tree that has been visited isn't read again, those trees are what
+/// lets a large file be attributed to the first commit, in topological order,
+/// that has it.
+pub fn collect_repo_stats(
+    repo: &ReadonlyRepo,
+    num_largest_files: usize,
+) -> Result<RepoStats, RepoStatsError> {
+    let heads = repo.index().all_heads_for_gc()?.collect();
+    let commit_ids = RevsetExpression::commits(heads)
+        .ancestors()
+        .evaluate_programmatic(repo)
+        .map_err(|err| BackendError::Other(err.into()))?;
+    let mut collector = StatsCollector {
+        store: repo.store(),
+        num_largest_files,
+        stats: RepoStats::default(),
+        visited_trees: HashSet::new(),
+        visited_files: HashSet::new(),
+        visited_symlinks: HashSet::new(),
+        visited_conflicts: HashSet::new(),
+        large_files_in_trees: HashMap::new(),
+    };
+    for commit_id in commit_ids.iter() {
+        collector.visit_commit(&commit_id)?;
+    }
+    let mut stats = collector.stats;
+    stats.num_operations = op_walk::walk_ancestors(slice::from_ref(repo.operation()))
+        .process_results(|ops| ops.count())? as u64;
+    Ok(stats)
+}
+
+struct StatsCollector<'a> {
+    store: &'a Arc<Store>,
+    num_largest_files: usize,
+    stats: RepoStats,
+    visited_trees: HashSet<TreeId>,
+    visited_files: HashSet<FileId>,
+    visited_symlinks: HashSet<SymlinkId>,
+    visited_conflicts: HashSet<ConflictId>,
+    /// The files in `stats.largest_files` that each visited tree contains, by
+    /// their path relative to the tree. Trees without any are left out.
+    large_files_in_trees: HashMap<TreeId, Vec<(FileId, RepoPathBuf)>>,
+}
+
+impl StatsCollector<'_> {
+    fn visit_commit(&mut self, commit_id: &CommitId) -> Result<(), BackendError> {
+        // The root commit isn't stored
+        if commit_id == self.store.root_commit_id() {
+            return Ok(());
+        }
+        let size = self
+            .store
+            .object_size(ObjectKind::Commit, commit_id.as_bytes())?;
+        self.stats.commits.add(size);
+        let commit = self.store.read_commit_uncached(commit_id)?;
+        for tree_id in commit.root_tree.to_merge() {
+            self.visit_tree(commit_id, RepoPath::root(), &tree_id)?;
+        }
+        Ok(())
+    }
+
+    /// Visits the tree at `dir` unless it has been visited already, and
+    /// returns the large files it contains.
+    fn visit_tree(
+        &mut self,
+        commit_id: &CommitId,
+        dir: &RepoPath,
+        tree_id: &TreeId,
+    ) -> Result<Vec<(FileId, RepoPathBuf)>, BackendError> {
+        if !self.visited_trees.insert(tree_id.clone()) {
+            let large_files = self
+                .large_files_in_trees
+                .get(tree_id)
+                .cloned()
+                .unwrap_or_default();
+            for (id, path) in &large_files {
+                self.attribute_file(id, join_paths(dir, path), commit_id);
+            }
+            return Ok(large_files);
+        }
+        let size = self
+            .store
+            .object_size(ObjectKind::Tree, tree_id.as_bytes())?;
+        self.stats.trees.add(size);
+        let tree = self.store.read_tree_uncached(dir, tree_id)?;
+        let mut large_files = vec![];
+        for entry in tree.entries() {
+            let path = dir.join(entry.name());
+            match entry.value() {
+                TreeValue::Tree(id) => {
+                    let entry_path = RepoPath::root().join(entry.name());
+                    for (file_id, sub_path) in self.visit_tree(commit_id, &path, id)? {
+                        large_files.push((file_id, join_paths(&entry_path, &sub_path)));
+                    }
+                }
+                TreeValue::File { id, .. } => {
+                    if self.visited_files.insert(id.clone()) {
+                        let size = file_size(self.store.read_file(&path, id)?, id)?;
+                        self.stats.files.add(size);
+                        self.add_file(LargeFile {
+                            size,
+                            id: id.clone(),
+                            path,
+                            commit_id: commit_id.clone(),
+                        });
+                    } else {
+                        self.attribute_file(id, path, commit_id);
+                    }
+                    large_files.push((id.clone(), RepoPath::root().join(entry.name())));
+                }
+                TreeValue::Symlink(id) => {
+                    if self.visited_symlinks.insert(id.clone()) {
+                        let size = self.store.object_size(ObjectKind::Symlink, id.as_bytes())?;
+                        self.stats.symlinks.add(size);
+                    }
+                }
+                TreeValue::Conflict(id) => {
+                    if self.visited_conflicts.insert(id.clone()) {
+                        let size = self
+                            .store
+                            .object_size(ObjectKind::Conflict, id.as_bytes())?;
+                        self.stats.conflicts.add(size);
+                    }
+                }
+                TreeValue::GitSubmodule(_) => {}
+            }
+        }
+        // Files may have been pushed out of the largest files by the ones
+        // found after them.
+        large_files.retain(|(id, _)| self.stats.largest_files.iter().any(|file| file.id == *id));
+        if !large_files.is_empty() {
+            self.large_files_in_trees
+                .insert(tree_id.clone(), large_files.clone());
+        }
+        Ok(large_files)
+    }
+
+    /// Adds a newly found file to the largest files if it's large enough. Of
+    /// files of the same size, the one found first is kept.
+    fn add_file(&mut self, file: LargeFile) {
+        let largest_files = &mut self.stats.largest_files;
+        let index = largest_files.partition_point(|other| other.size >= file.size);
+        if index < self.num_largest_files {
+            largest_files.insert(index, file);
+            largest_files.truncate(self.num_largest_files);
+        }
+    }
+
+    /// Attributes a file that has been found before to `commit_id` if it's
+    /// one of the largest files. Commits are visited children first, so the
+    /// last commit it's attributed to is the first one in topological order.
+    fn attribute_file(&mut self, id: &FileId, path: RepoPathBuf, commit_id: &CommitId) {
+        let file = self
+            .stats
+            .largest_files
+            .iter_mut()
+            .find(|file| file.id == *id);
+        // Within a commit, keep the first path the file was found at.
+        if let Some(file) = file.filter(|file| file.commit_id != *commit_id) {
+            file.path = path;
+            file.commit_id = commit_id.clone();
+        }
+    }
+}
+
+fn join_paths(base: &RepoPath, path: &RepoPath) -> RepoPathBuf {
+    path.components()
+        .fold(base.to_owned(), |dir, name| dir.join(name))
+}
+
+fn file_size(mut contents: impl io::Read, id: &FileId) -> Result<u64, BackendError> {
+    io::copy(&mut contents, &mut io::sink()).map_err(|err| BackendError::ReadObject {
+        object_type: ObjectKind::File.to_string(),
+        hash: id.hex(),
+        source: err.into(),
+    })
+}
@@ -0,0 +1,287 @@
+// Copyright 2024 The Jujutsu Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Notifies long-running processes, such as GUIs, when operations are
+//! committed to a repo.
+//!
+//! The simple op heads store increments a sequence number in the
+//! `op_heads/sequence` file each time the op heads are updated. `RepoWatcher`
+//! watches that file with filesystem notifications if the `notify` feature is
+//! enabled, and polls it otherwise. Changes to the working copy that don't
+//! record an operation, such as updates of the file state cache, don't touch
+//! the file and aren't reported.
+
+#![allow(missing_docs)]
+
+use std::collections::HashSet;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, Sender};
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+use itertools::Itertools as _;
+use thiserror::Error;
+
+use crate::op_store::OperationId;
+use crate::simple_op_heads_store::SimpleOpHeadsStore;
+
+/// An operation became a head of the operation log.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct RepoChanged {
+    pub new_op_head: OperationId,
+}
+
+#[derive(Debug, Error)]
+pub enum RepoWatcherError {
+    #[error("Unsupported op heads store type: {0}")]
+    UnsupportedOpHeadsStore(String),
+    #[error("Failed to read the op heads in {path}")]
+    Io {
+        path: PathBuf,
+        #[source]
+        source: io::Error,
+    },
+    #[cfg(feature = "notify")]
+    #[error("Failed to watch the op heads")]
+    Notify(#[from] notify::Error),
+}
+
+#[derive(Clone, Debug)]
+pub struct RepoWatcherOptions {
+    /// How often to check the sequence file. With filesystem notifications,
+    /// this is only a fallback in case a notification is missed.
+    pub poll_interval: Duration,
+    /// How long the sequence file must stay unchanged before the new op heads
+    /// are reported, so a burst of operations results in one event per
+    /// resulting op head.
+    pub debounce: Duration,
+    /// Whether to poll even if filesystem notifications are available.
+    pub force_polling: bool,
+}
+
+impl Default for RepoWatcherOptions {
+    fn default() -> Self {
+        RepoWatcherOptions {
+            poll_interval: Duration::from_secs(1),
+            debounce: Duration::from_millis(50),
+            force_polling: false,
+        }
+    }
+}
+
+/// Watches a repo in a background thread and sends a `RepoChanged` event for
+/// each new op head.
+///
+/// The thread stops when the watcher is dropped.
+pub struct RepoWatcher {
+    events: Receiver<RepoChanged>,
+    wake_sender: Sender<()>,
+    stop: Arc<AtomicBool>,
+    thread: Option<JoinHandle<()>>,
+    #[cfg(feature = "notify")]
+    _notify_watcher: Option<notify::RecommendedWatcher>,
+}
+
+impl RepoWatcher {
+    /// Watches the repo at `repo_path`, i.e. the `.jj/repo` directory of a
+    /// workspace, with the default options.
+    pub fn new(repo_path: &Path) -> Result<Self, RepoWatcherError> {
+        Self::with_options(repo_path, RepoWatcherOptions::default())
+    }
+
+    pub fn with_options(
+        repo_path: &Path,
+        options: RepoWatcherOptions,
+    ) -> Result<Self, RepoWatcherError> {
+        let op_heads_dir = repo_path.join("op_heads");
+        let type_path = op_heads_dir.join("type");
+        // Repos created before the type file was introduced use the simple store
+        let store_type = match fs::read_to_string(&type_path) {
+            Ok(store_type) => store_type,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => {
+                SimpleOpHeadsStore::name().to_owned()
+            }
+            Err(source) => {
+                return Err(RepoWatcherError::Io {
+                    path: type_path,
+                    source,
+                })
+            }
+        };
+        if store_type != SimpleOpHeadsStore::name() {
+            return Err(RepoWatcherError::UnsupportedOpHeadsStore(store_type));
+        }
+        let store = SimpleOpHeadsStore::load(&op_heads_dir);
+        let io_error = |source| RepoWatcherError::Io {
+            path: op_heads_dir.clone(),
+            source,
+        };
+        let sequence_number = store.read_sequence_number().map_err(io_error)?;
+        let op_heads: HashSet<OperationId> = store
+            .try_get_op_heads()
+            .map_err(io_error)?
+            .into_iter()
+            .collect();
+
+        let (wake_sender, wake_receiver) = mpsc::channel();
+        #[cfg(feature = "notify")]
+        let notify_watcher = if options.force_polling {
+            None
+        } else {
+            Some(watch_sequence_file(&op_heads_dir, wake_sender.clone())?)
+        };
+        let (event_sender, events) = mpsc::channel();
+        let stop = Arc::new(AtomicBool::new(false));
+        let mut state = WatchState {
+            store,
+            options,
+            sequence_number,
+            op_heads,
+            wake_receiver,
+            event_sender,
+            stop: stop.clone(),
+        };
+        let thread = thread::spawn(move || state.run());
+        Ok(RepoWatcher {
+            events,
+            wake_sender,
+            stop,
+            thread: Some(thread),
+            #[cfg(feature = "notify")]
+            _notify_watcher: notify_watcher,
+        })
+    }
+
+    /// The channel on which the events are sent. It's disconnected if the
+    /// watcher fails to read the op heads, e.g. because the repo was deleted.
+    pub fn events(&self) -> &Receiver<RepoChanged> {
+        &self.events
+    }
+}
+
+impl Drop for RepoWatcher {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+        self.wake_sender.send(()).ok();
+        if let Some(thread) = self.thread.take() {
+            thread.join().ok();
+        }
+    }
+}
+
+#[cfg(feature = "notify")]
+fn watch_sequence_file(
+    op_heads_dir: &Path,
+    wake_sender: Sender<()>,
+) -> Result<notify::RecommendedWatcher, notify::Error> {
+    use notify::Watcher as _;
+
+    use crate::simple_op_heads_store::SEQUENCE_FILE_NAME;
+
+    // The file is replaced rather than written to, so the directory is watched
+    // instead of the file
+    let mut watcher = notify::recommended_watcher(move |event: notify::Result<notify::Event>| {
+        let is_sequence_file = |path: &PathBuf| {
+            path.file_name()
+                .is_some_and(|name| name == SEQUENCE_FILE_NAME)
+        };
+        match event {
+            Ok(event) if !event.paths.iter().any(is_sequence_file) => {}
+            // Errors wake up the thread too, so it checks the file right away
+            _ => {
+                wake_sender.send(()).ok();
+            }
+        }
+    })?;
+    watcher.watch(op_heads_dir, notify::RecursiveMode::NonRecursive)?;
+    Ok(watcher)
+}
+
+struct WatchState {
+    store: SimpleOpHeadsStore,
+    options: RepoWatcherOptions,
+    sequence_number: u64,
+    op_heads: HashSet<OperationId>,
+    wake_receiver: Receiver<()>,
+    event_sender: Sender<RepoChanged>,
+    stop: Arc<AtomicBool>,
+}
+
+impl WatchState {
+    fn run(&mut self) {
+        while self.wait_for_change() && self.send_new_op_heads() {}
+    }
+
+    /// Waits until the sequence number changes and then stays the same for
+    /// the debounce period. Returns false if the watcher was stopped or the
+    /// sequence file can't be read.
+    fn wait_for_change(&mut self) -> bool {
+        loop {
+            match self.wake_receiver.recv_timeout(self.options.poll_interval) {
+                Ok(()) | Err(RecvTimeoutError::Timeout) => {}
+                Err(RecvTimeoutError::Disconnected) => return false,
+            }
+            if self.stop.load(Ordering::Relaxed) {
+                return false;
+            }
+            let Ok(mut sequence_number) = self.store.read_sequence_number() else {
+                return false;
+            };
+            if sequence_number == self.sequence_number {
+                continue;
+            }
+            loop {
+                thread::sleep(self.options.debounce);
+                // Drain the notifications of the writes that were waited out
+                while self.wake_receiver.try_recv().is_ok() {}
+                if self.stop.load(Ordering::Relaxed) {
+                    return false;
+                }
+                let Ok(latest) = self.store.read_sequence_number() else {
+                    return false;
+                };
+                if latest == sequence_number {
+                    break;
+                }
+                sequence_number = latest;
+            }
+            self.sequence_number = sequence_number;
+            return true;
+        }
+    }
+
+    /// Sends an event for each op head that wasn't a head the last time.
+    /// Returns false if the op heads can't be read or the events are no longer
+    /// received.
+    fn send_new_op_heads(&mut self) -> bool {
+        let Ok(op_heads) = self.store.try_get_op_heads() else {
+            return false;
+        };
+        let op_heads: HashSet<OperationId> = op_heads.into_iter().collect();
+        for new_op_head in op_heads.difference(&self.op_heads).sorted() {
+            let event = RepoChanged {
+                new_op_head: new_op_head.clone(),
+            };
+            if self.event_sender.send(event).is_err() {
+                return false;
+            }
+        }
+        self.op_heads = op_heads;
+        true
+    }
+}
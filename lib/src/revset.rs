@@ -33,6 +33,7 @@ use thiserror::Error;
 
 use crate::backend::{BackendError, BackendResult, ChangeId, CommitId};
 use crate::commit::Commit;
+use crate::diff::WhitespaceMode;
 use crate::git;
 use crate::hex_util::to_forward_hex;
 use crate::object_id::{HexPrefix, PrefixResolution};
@@ -42,6 +43,7 @@ use crate::repo_path::{FsPathParseError, RepoPathBuf};
 use crate::revset_graph::RevsetGraphEdge;
 use crate::store::Store;
 use crate::str_util::StringPattern;
+use crate::time_util::DatePattern;
 
 /// Error occurred during symbol resolution.
 #[derive(Debug, Error)]
@@ -59,6 +61,17 @@ pub enum RevsetResolutionError {
     AmbiguousCommitIdPrefix(String),
     #[error("Change ID prefix \"{0}\" is ambiguous")]
     AmbiguousChangeIdPrefix(String),
+    #[error("Revision \"{name}\" is ambiguous")]
+    AmbiguousSymbol {
+        name: String,
+        kinds: Vec<RevsetSymbolKind>,
+    },
+    #[error("Revision \"{name}\" matches more than one {kind} ignoring case")]
+    AmbiguousRefNameCase {
+        name: String,
+        kind: RevsetSymbolKind,
+        candidates: Vec<String>,
+    },
     #[error("Unexpected error from store")]
     StoreError(#[source] BackendError),
 }
@@ -103,6 +116,7 @@ impl Rule {
             Rule::identifier => None,
             Rule::symbol => None,
             Rule::literal_string => None,
+            Rule::symbol_kind => None,
             Rule::whitespace => None,
             Rule::at_op => Some("@"),
             Rule::parents_op => Some("-"),
@@ -308,6 +322,11 @@ pub const GENERATION_RANGE_EMPTY: Range<u64> = 0..0;
 pub enum RevsetCommitRef {
     WorkingCopy(WorkspaceId),
     Symbol(String),
+    /// Symbol to be resolved only in the given namespace, e.g. `branch:main`.
+    QualifiedSymbol {
+        kind: RevsetSymbolKind,
+        name: String,
+    },
     RemoteSymbol {
         name: String,
         remote: String,
@@ -319,7 +338,7 @@ pub enum RevsetCommitRef {
         branch_pattern: StringPattern,
         remote_pattern: StringPattern,
     },
-    Tags,
+    Tags(StringPattern),
     GitRefs,
     GitHead,
 }
@@ -334,10 +353,26 @@ pub enum RevsetFilterPredicate {
     Author(StringPattern),
     /// Commits with committer's name or email containing the needle.
     Committer(StringPattern),
+    /// Commits with author date matching the pattern.
+    AuthorDate(DatePattern),
+    /// Commits with committer date matching the pattern.
+    CommitterDate(DatePattern),
     /// Commits modifying the paths specified by the pattern.
     File(Option<Vec<RepoPathBuf>>), // TODO: embed matcher expression?
+    /// Commits modifying the paths specified by the pattern in ways other than
+    /// the whitespace changes ignored by the mode.
+    FileIgnoringWhitespace(Vec<RepoPathBuf>, WhitespaceMode),
     /// Commits with conflicts
     HasConflict,
+    /// Commits with the given metadata key set.
+    HasMetadata(String),
+}
+
+/// Which of the commit signatures' timestamps to use.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum SignatureTimestamp {
+    Author,
+    Committer,
 }
 
 #[derive(Debug, PartialEq, Eq, Clone)]
@@ -374,6 +409,11 @@ pub enum RevsetExpression {
     Latest {
         candidates: Rc<RevsetExpression>,
         count: usize,
+        by: SignatureTimestamp,
+    },
+    Limit {
+        candidates: Rc<RevsetExpression>,
+        count: usize,
     },
     Filter(RevsetFilterPredicate),
     /// Marker for subtree that should be intersected as filter.
@@ -404,6 +444,11 @@ impl RevsetExpression {
         Rc::new(RevsetExpression::CommitRef(RevsetCommitRef::Symbol(value)))
     }
 
+    pub fn qualified_symbol(kind: RevsetSymbolKind, name: String) -> Rc<RevsetExpression> {
+        let commit_ref = RevsetCommitRef::QualifiedSymbol { kind, name };
+        Rc::new(RevsetExpression::CommitRef(commit_ref))
+    }
+
     pub fn remote_symbol(name: String, remote: String) -> Rc<RevsetExpression> {
         let commit_ref = RevsetCommitRef::RemoteSymbol { name, remote };
         Rc::new(RevsetExpression::CommitRef(commit_ref))
@@ -443,8 +488,8 @@ impl RevsetExpression {
         ))
     }
 
-    pub fn tags() -> Rc<RevsetExpression> {
-        Rc::new(RevsetExpression::CommitRef(RevsetCommitRef::Tags))
+    pub fn tags(pattern: StringPattern) -> Rc<RevsetExpression> {
+        Rc::new(RevsetExpression::CommitRef(RevsetCommitRef::Tags(pattern)))
     }
 
     pub fn git_refs() -> Rc<RevsetExpression> {
@@ -456,9 +501,27 @@ impl RevsetExpression {
     }
 
     pub fn latest(self: &Rc<RevsetExpression>, count: usize) -> Rc<RevsetExpression> {
+        self.latest_by(count, SignatureTimestamp::Committer)
+    }
+
+    /// Latest `count` commits in `self`, ordered by the `by` timestamp.
+    pub fn latest_by(
+        self: &Rc<RevsetExpression>,
+        count: usize,
+        by: SignatureTimestamp,
+    ) -> Rc<RevsetExpression> {
         Rc::new(RevsetExpression::Latest {
             candidates: self.clone(),
             count,
+            by,
+        })
+    }
+
+    /// Commits in `self`, up to the first `count` in iteration order.
+    pub fn limit(self: &Rc<RevsetExpression>, count: usize) -> Rc<RevsetExpression> {
+        Rc::new(RevsetExpression::Limit {
+            candidates: self.clone(),
+            count,
         })
     }
 
@@ -631,6 +694,58 @@ impl RevsetExpression {
         Rc::new(RevsetExpression::Difference(self.clone(), other.clone()))
     }
 
+    /// Returns true if the expression may refer to a working-copy commit,
+    /// either by the working-copy symbol or through sets that include it by
+    /// default, such as `all()` or `visible_heads()`.
+    ///
+    /// The analysis is syntactic and conservative. Symbols are assumed to
+    /// refer to the working copy since they may resolve to its change id.
+    /// Commits only reachable through refs (such as `branches()`) are not
+    /// considered references. Snapshotting the working copy can still move a
+    /// local ref pointing to the working-copy commit or its descendants, so
+    /// callers should check the current ref targets separately.
+    pub fn references_working_copy(&self) -> bool {
+        match self {
+            RevsetExpression::None => false,
+            RevsetExpression::All => true,
+            RevsetExpression::Commits(_) => false,
+            RevsetExpression::CommitRef(commit_ref) => match commit_ref {
+                RevsetCommitRef::WorkingCopy(_) => true,
+                RevsetCommitRef::Symbol(_) => true,
+                RevsetCommitRef::QualifiedSymbol { .. } => true,
+                RevsetCommitRef::RemoteSymbol { .. } => false,
+                RevsetCommitRef::VisibleHeads => true,
+                RevsetCommitRef::Root => false,
+                RevsetCommitRef::Branches(_) => false,
+                RevsetCommitRef::RemoteBranches { .. } => false,
+                RevsetCommitRef::Tags(_) => false,
+                RevsetCommitRef::GitRefs => false,
+                RevsetCommitRef::GitHead => false,
+            },
+            RevsetExpression::Ancestors { heads, .. } => heads.references_working_copy(),
+            // Descendants extend towards the visible heads.
+            RevsetExpression::Descendants { .. } => true,
+            RevsetExpression::Range { heads, .. } => heads.references_working_copy(),
+            RevsetExpression::DagRange { heads, .. } => heads.references_working_copy(),
+            RevsetExpression::Heads(candidates) => candidates.references_working_copy(),
+            RevsetExpression::Roots(candidates) => candidates.references_working_copy(),
+            RevsetExpression::Latest { candidates, .. } => candidates.references_working_copy(),
+            RevsetExpression::Limit { candidates, .. } => candidates.references_working_copy(),
+            // A bare filter selects from all commits.
+            RevsetExpression::Filter(_) => true,
+            RevsetExpression::AsFilter(candidates) => candidates.references_working_copy(),
+            RevsetExpression::Present(candidates) => candidates.references_working_copy(),
+            RevsetExpression::NotIn(_) => true,
+            RevsetExpression::Union(expression1, expression2) => {
+                expression1.references_working_copy() || expression2.references_working_copy()
+            }
+            RevsetExpression::Intersection(expression1, expression2)
+            | RevsetExpression::Difference(expression1, expression2) => {
+                expression1.references_working_copy() || expression2.references_working_copy()
+            }
+        }
+    }
+
     /// Resolve a programmatically created revset expression. In particular, the
     /// expression must not contain any symbols (branches, tags, change/commit
     /// prefixes). Callers must not include `RevsetExpression::symbol()` in
@@ -645,6 +760,21 @@ impl RevsetExpression {
             .unwrap()
     }
 
+    /// Returns the unqualified symbols in this expression, which are resolved
+    /// by precedence.
+    pub fn symbols(self: &Rc<Self>) -> Vec<String> {
+        let mut symbols = vec![];
+        transform_expression_bottom_up(self, |expression| {
+            if let RevsetExpression::CommitRef(RevsetCommitRef::Symbol(symbol)) =
+                expression.as_ref()
+            {
+                symbols.push(symbol.clone());
+            }
+            None
+        });
+        symbols
+    }
+
     /// Resolve a user-provided expression. Symbols will be resolved using the
     /// provided `SymbolResolver`.
     pub fn resolve_user_expression(
@@ -710,6 +840,12 @@ pub enum ResolvedExpression {
     Latest {
         candidates: Box<ResolvedExpression>,
         count: usize,
+        by: SignatureTimestamp,
+    },
+    /// First `count` commits of `candidates` in iteration order.
+    Limit {
+        candidates: Box<ResolvedExpression>,
+        count: usize,
     },
     Union(Box<ResolvedExpression>, Box<ResolvedExpression>),
     /// Intersects `candidates` with `predicate` by filtering.
@@ -988,6 +1124,16 @@ fn parse_primary_rule(
             let arguments_pair = pairs.next().unwrap();
             parse_function_expression(first, arguments_pair, state, span)
         }
+        Rule::symbol_kind => {
+            let kind = match first.as_str() {
+                "branch" => RevsetSymbolKind::Branch,
+                "change" => RevsetSymbolKind::ChangeId,
+                "commit" => RevsetSymbolKind::CommitId,
+                kind => panic!("unexpected symbol kind: {kind:?}"),
+            };
+            let name = parse_symbol_rule_as_literal(pairs.next().unwrap().into_inner())?;
+            Ok(RevsetExpression::qualified_symbol(kind, name))
+        }
         // Symbol without "@" may be substituted by aliases. Primary expression including "@"
         // is considered an indecomposable unit, and no alias substitution would be made.
         Rule::symbol if pairs.peek().is_none() => parse_symbol_rule(first.into_inner(), state),
@@ -1217,9 +1363,14 @@ static BUILTIN_FUNCTION_MAP: Lazy<HashMap<&'static str, RevsetFunction>> = Lazy:
             remote_pattern,
         ))
     });
-    map.insert("tags", |name, arguments_pair, _state| {
-        expect_no_arguments(name, arguments_pair)?;
-        Ok(RevsetExpression::tags())
+    map.insert("tags", |name, arguments_pair, state| {
+        let ([], [opt_arg]) = expect_arguments(name, arguments_pair)?;
+        let pattern = if let Some(arg) = opt_arg {
+            parse_function_argument_to_string_pattern(name, arg, state)?
+        } else {
+            StringPattern::everything()
+        };
+        Ok(RevsetExpression::tags(pattern))
     });
     map.insert("git_refs", |name, arguments_pair, _state| {
         expect_no_arguments(name, arguments_pair)?;
@@ -1230,14 +1381,39 @@ static BUILTIN_FUNCTION_MAP: Lazy<HashMap<&'static str, RevsetFunction>> = Lazy:
         Ok(RevsetExpression::git_head())
     });
     map.insert("latest", |name, arguments_pair, state| {
-        let ([candidates_arg], [count_opt_arg]) = expect_arguments(name, arguments_pair)?;
+        let ([candidates_arg], [count_opt_arg, by_opt_arg]) =
+            expect_named_arguments(name, &["", "", "by"], arguments_pair)?;
         let candidates = parse_expression_rule(candidates_arg.into_inner(), state)?;
         let count = if let Some(count_arg) = count_opt_arg {
             parse_function_argument_as_literal("integer", name, count_arg, state)?
         } else {
             1
         };
-        Ok(candidates.latest(count))
+        let by = if let Some(by_arg) = by_opt_arg {
+            let span = by_arg.as_span();
+            match parse_function_argument_to_string(name, by_arg, state)?.as_str() {
+                "author" => SignatureTimestamp::Author,
+                "committer" => SignatureTimestamp::Committer,
+                _ => {
+                    return Err(RevsetParseError::with_span(
+                        RevsetParseErrorKind::InvalidFunctionArguments {
+                            name: name.to_owned(),
+                            message: r#"Expected "author" or "committer""#.to_owned(),
+                        },
+                        span,
+                    ))
+                }
+            }
+        } else {
+            SignatureTimestamp::Committer
+        };
+        Ok(candidates.latest_by(count, by))
+    });
+    map.insert("limit", |name, arguments_pair, state| {
+        let ([candidates_arg, count_arg], []) = expect_arguments(name, arguments_pair)?;
+        let candidates = parse_expression_rule(candidates_arg.into_inner(), state)?;
+        let count = parse_function_argument_as_literal("integer", name, count_arg, state)?;
+        Ok(candidates.limit(count))
     });
     map.insert("merges", |name, arguments_pair, _state| {
         expect_no_arguments(name, arguments_pair)?;
@@ -1272,6 +1448,20 @@ static BUILTIN_FUNCTION_MAP: Lazy<HashMap<&'static str, RevsetFunction>> = Lazy:
             pattern,
         )))
     });
+    map.insert("author_date", |name, arguments_pair, state| {
+        let arg = expect_one_argument(name, arguments_pair)?;
+        let pattern = parse_function_argument_to_date_pattern(name, arg, state)?;
+        Ok(RevsetExpression::filter(RevsetFilterPredicate::AuthorDate(
+            pattern,
+        )))
+    });
+    map.insert("committer_date", |name, arguments_pair, state| {
+        let arg = expect_one_argument(name, arguments_pair)?;
+        let pattern = parse_function_argument_to_date_pattern(name, arg, state)?;
+        Ok(RevsetExpression::filter(
+            RevsetFilterPredicate::CommitterDate(pattern),
+        ))
+    });
     map.insert("empty", |name, arguments_pair, _state| {
         expect_no_arguments(name, arguments_pair)?;
         Ok(RevsetExpression::filter(RevsetFilterPredicate::File(None)).negated())
@@ -1279,33 +1469,80 @@ static BUILTIN_FUNCTION_MAP: Lazy<HashMap<&'static str, RevsetFunction>> = Lazy:
     map.insert("file", |name, arguments_pair, state| {
         if let Some(ctx) = state.workspace_ctx {
             let arguments_span = arguments_pair.as_span();
-            let paths: Vec<_> = arguments_pair
-                .into_inner()
-                .map(|arg| -> Result<_, RevsetParseError> {
-                    let span = arg.as_span();
-                    let needle = parse_function_argument_to_string(name, arg, state)?;
-                    let path = RepoPathBuf::parse_fs_path(ctx.cwd, ctx.workspace_root, needle)
-                        .map_err(|e| {
-                            RevsetParseError::with_span(
-                                RevsetParseErrorKind::FsPathParseError(e),
-                                span,
-                            )
-                        })?;
-                    Ok(path)
-                })
-                .try_collect()?;
-            if paths.is_empty() {
-                Err(RevsetParseError::with_span(
+            let make_error = |message: String, span| {
+                RevsetParseError::with_span(
                     RevsetParseErrorKind::InvalidFunctionArguments {
                         name: name.to_owned(),
-                        message: "Expected at least 1 argument".to_string(),
+                        message,
                     },
+                    span,
+                )
+            };
+            let mut paths = vec![];
+            let mut whitespace = None;
+            for arg in arguments_pair.into_inner() {
+                let span = arg.as_span();
+                if arg.as_rule() == Rule::keyword_argument {
+                    let mut pairs = arg.into_inner();
+                    let keyword = pairs.next().unwrap();
+                    let value = pairs.next().unwrap();
+                    if keyword.as_str() != "whitespace" {
+                        return Err(make_error(
+                            format!(r#"Unexpected keyword argument "{}""#, keyword.as_str()),
+                            span,
+                        ));
+                    }
+                    if whitespace.is_some() {
+                        return Err(make_error(
+                            r#"Got multiple values for keyword "whitespace""#.to_owned(),
+                            span,
+                        ));
+                    }
+                    let mode = match parse_function_argument_to_string(name, value, state)?.as_str()
+                    {
+                        "exact" => WhitespaceMode::Exact,
+                        "ignore-cr-at-eol" => WhitespaceMode::IgnoreCrAtEol,
+                        "ignore-space-change" => WhitespaceMode::IgnoreSpaceChange,
+                        "ignore-all-space" => WhitespaceMode::IgnoreAllSpace,
+                        _ => {
+                            return Err(make_error(
+                                "Expected whitespace mode \"exact\", \"ignore-cr-at-eol\", \
+                                 \"ignore-space-change\", or \"ignore-all-space\""
+                                    .to_owned(),
+                                span,
+                            ))
+                        }
+                    };
+                    whitespace = Some(mode);
+                    continue;
+                }
+                if whitespace.is_some() {
+                    return Err(make_error(
+                        "Positional argument follows keyword argument".to_owned(),
+                        span,
+                    ));
+                }
+                let needle = parse_function_argument_to_string(name, arg, state)?;
+                let path = RepoPathBuf::parse_fs_path(ctx.cwd, ctx.workspace_root, needle)
+                    .map_err(|e| {
+                        RevsetParseError::with_span(RevsetParseErrorKind::FsPathParseError(e), span)
+                    })?;
+                paths.push(path);
+            }
+            if paths.is_empty() {
+                Err(make_error(
+                    "Expected at least 1 argument".to_string(),
                     arguments_span,
                 ))
             } else {
-                Ok(RevsetExpression::filter(RevsetFilterPredicate::File(Some(
-                    paths,
-                ))))
+                match whitespace.unwrap_or_default() {
+                    WhitespaceMode::Exact => Ok(RevsetExpression::filter(
+                        RevsetFilterPredicate::File(Some(paths)),
+                    )),
+                    mode => Ok(RevsetExpression::filter(
+                        RevsetFilterPredicate::FileIgnoringWhitespace(paths, mode),
+                    )),
+                }
             }
         } else {
             Err(RevsetParseError::new(
@@ -1313,10 +1550,19 @@ static BUILTIN_FUNCTION_MAP: Lazy<HashMap<&'static str, RevsetFunction>> = Lazy:
             ))
         }
     });
-    map.insert("conflict", |name, arguments_pair, _state| {
+    map.insert("has_metadata", |name, arguments_pair, state| {
+        let arg = expect_one_argument(name, arguments_pair)?;
+        let key = parse_function_argument_to_string(name, arg, state)?;
+        Ok(RevsetExpression::filter(
+            RevsetFilterPredicate::HasMetadata(key),
+        ))
+    });
+    map.insert("conflicts", |name, arguments_pair, _state| {
         expect_no_arguments(name, arguments_pair)?;
         Ok(RevsetExpression::filter(RevsetFilterPredicate::HasConflict))
     });
+    // TODO: Remove the old name in a later release
+    map.insert("conflict", map["conflicts"]);
     map.insert("present", |name, arguments_pair, state| {
         let arg = expect_one_argument(name, arguments_pair)?;
         let expression = parse_expression_rule(arg.into_inner(), state)?;
@@ -1499,6 +1745,45 @@ fn parse_function_argument_to_string_pattern(
     Ok(pattern)
 }
 
+fn parse_function_argument_to_date_pattern(
+    name: &str,
+    pair: Pair<Rule>,
+    state: ParseState,
+) -> Result<DatePattern, RevsetParseError> {
+    let span = pair.as_span();
+    let make_error = |message| {
+        RevsetParseError::with_span(
+            RevsetParseErrorKind::InvalidFunctionArguments {
+                name: name.to_string(),
+                message,
+            },
+            span,
+        )
+    };
+    let make_type_error = || {
+        make_error(
+            r#"Expected function argument of date pattern, e.g. after:"2024-01-31""#.to_owned(),
+        )
+    };
+    let expression = parse_expression_rule(pair.into_inner(), state)?;
+    // TODO: Add proper parsed node if we drop support for legacy x:y range
+    let RevsetExpression::DagRange {
+        roots,
+        heads,
+        is_legacy: true,
+    } = expression.as_ref()
+    else {
+        return Err(make_type_error());
+    };
+    let RevsetExpression::CommitRef(RevsetCommitRef::Symbol(kind)) = roots.as_ref() else {
+        return Err(make_type_error());
+    };
+    let RevsetExpression::CommitRef(RevsetCommitRef::Symbol(date)) = heads.as_ref() else {
+        return Err(make_type_error());
+    };
+    DatePattern::from_str_kind(date, kind).map_err(|err| make_error(err.to_string()))
+}
+
 fn parse_function_argument_as_literal<T: FromStr>(
     type_name: &str,
     name: &str,
@@ -1617,8 +1902,17 @@ fn try_transform_expression<E>(
             RevsetExpression::Roots(candidates) => {
                 transform_rec(candidates, pre, post)?.map(RevsetExpression::Roots)
             }
-            RevsetExpression::Latest { candidates, count } => transform_rec(candidates, pre, post)?
-                .map(|candidates| RevsetExpression::Latest {
+            RevsetExpression::Latest {
+                candidates,
+                count,
+                by,
+            } => transform_rec(candidates, pre, post)?.map(|candidates| RevsetExpression::Latest {
+                candidates,
+                count: *count,
+                by: *by,
+            }),
+            RevsetExpression::Limit { candidates, count } => transform_rec(candidates, pre, post)?
+                .map(|candidates| RevsetExpression::Limit {
                     candidates,
                     count: *count,
                 }),
@@ -2011,8 +2305,59 @@ fn make_no_such_symbol_error(repo: &dyn Repo, name: impl Into<String>) -> Revset
     RevsetResolutionError::NoSuchRevision { name, candidates }
 }
 
+/// Namespace in which a symbol can be resolved.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum RevsetSymbolKind {
+    Tag,
+    Branch,
+    GitRef,
+    CommitId,
+    ChangeId,
+}
+
+impl RevsetSymbolKind {
+    /// All kinds in order of precedence when resolving an unqualified symbol.
+    pub const ALL: [RevsetSymbolKind; 5] = [
+        RevsetSymbolKind::Tag,
+        RevsetSymbolKind::Branch,
+        RevsetSymbolKind::GitRef,
+        RevsetSymbolKind::CommitId,
+        RevsetSymbolKind::ChangeId,
+    ];
+
+    /// The `<kind>:` prefix that selects this namespace explicitly, if any.
+    pub fn qualifier(self) -> Option<&'static str> {
+        match self {
+            RevsetSymbolKind::Tag | RevsetSymbolKind::GitRef => None,
+            RevsetSymbolKind::Branch => Some("branch"),
+            RevsetSymbolKind::CommitId => Some("commit"),
+            RevsetSymbolKind::ChangeId => Some("change"),
+        }
+    }
+}
+
+impl fmt::Display for RevsetSymbolKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            RevsetSymbolKind::Tag => "tag",
+            RevsetSymbolKind::Branch => "branch",
+            RevsetSymbolKind::GitRef => "git ref",
+            RevsetSymbolKind::CommitId => "commit id",
+            RevsetSymbolKind::ChangeId => "change id",
+        };
+        f.write_str(name)
+    }
+}
+
 pub trait SymbolResolver {
     fn resolve_symbol(&self, symbol: &str) -> Result<Vec<CommitId>, RevsetResolutionError>;
+
+    /// Resolves the symbol only in the namespace of the given `kind`.
+    fn resolve_qualified_symbol(
+        &self,
+        kind: RevsetSymbolKind,
+        symbol: &str,
+    ) -> Result<Vec<CommitId>, RevsetResolutionError>;
 }
 
 /// Fails on any attempt to resolve a symbol.
@@ -2028,6 +2373,14 @@ impl SymbolResolver for FailingSymbolResolver {
             candidates: Default::default(),
         })
     }
+
+    fn resolve_qualified_symbol(
+        &self,
+        _kind: RevsetSymbolKind,
+        symbol: &str,
+    ) -> Result<Vec<CommitId>, RevsetResolutionError> {
+        self.resolve_symbol(symbol)
+    }
 }
 
 pub type PrefixResolver<'a, T> = Box<dyn Fn(&dyn Repo, &HexPrefix) -> PrefixResolution<T> + 'a>;
@@ -2038,6 +2391,8 @@ pub struct DefaultSymbolResolver<'a> {
     repo: &'a dyn Repo,
     commit_id_resolver: PrefixResolver<'a, CommitId>,
     change_id_resolver: PrefixResolver<'a, Vec<CommitId>>,
+    strict: bool,
+    case_insensitive_refs: bool,
 }
 
 impl<'a> DefaultSymbolResolver<'a> {
@@ -2048,6 +2403,8 @@ impl<'a> DefaultSymbolResolver<'a> {
                 repo.index().resolve_commit_id_prefix(prefix)
             }),
             change_id_resolver: Box::new(|repo, prefix| repo.resolve_change_id_prefix(prefix)),
+            strict: false,
+            case_insensitive_refs: false,
         }
     }
 
@@ -2066,66 +2423,193 @@ impl<'a> DefaultSymbolResolver<'a> {
         self.change_id_resolver = change_id_resolver;
         self
     }
-}
 
-impl SymbolResolver for DefaultSymbolResolver<'_> {
-    fn resolve_symbol(&self, symbol: &str) -> Result<Vec<CommitId>, RevsetResolutionError> {
+    /// In strict mode, a symbol that matches in more than one namespace is an
+    /// error instead of being resolved by precedence.
+    pub fn with_strict(mut self, strict: bool) -> Self {
+        self.strict = strict;
+        self
+    }
+
+    /// If enabled, a symbol that doesn't match anything exactly resolves to
+    /// the tag or local branch whose name differs only by case. It's an error
+    /// if there is more than one such tag or branch.
+    pub fn with_case_insensitive_refs(mut self, case_insensitive_refs: bool) -> Self {
+        self.case_insensitive_refs = case_insensitive_refs;
+        self
+    }
+
+    /// Looks up the symbol in every namespace, in order of precedence.
+    ///
+    /// Ambiguous id prefixes are reported as errors only if the symbol doesn't
+    /// match anything else.
+    pub fn resolve_symbol_matches(
+        &self,
+        symbol: &str,
+    ) -> Result<Vec<(RevsetSymbolKind, Vec<CommitId>)>, RevsetResolutionError> {
         if symbol.is_empty() {
             return Err(RevsetResolutionError::EmptyString);
         }
-
-        // Try to resolve as a tag
-        let target = self.repo.view().get_tag(symbol);
-        if target.is_present() {
-            return Ok(target.added_ids().cloned().collect());
+        let mut matches = vec![];
+        let mut first_err = None;
+        for kind in RevsetSymbolKind::ALL {
+            match self.resolve_symbol_kind(kind, symbol) {
+                Ok(Some(ids)) => matches.push((kind, ids)),
+                Ok(None) => {}
+                Err(err) => {
+                    first_err.get_or_insert(err);
+                }
+            }
         }
-
-        // Try to resolve as a branch
-        if let Some(ids) = resolve_local_branch(self.repo, symbol) {
-            return Ok(ids);
+        if matches.is_empty() {
+            for kind in [RevsetSymbolKind::Tag, RevsetSymbolKind::Branch] {
+                if let Some(ids) = self.resolve_symbol_kind_ignoring_case(kind, symbol)? {
+                    matches.push((kind, ids));
+                    break;
+                }
+            }
         }
+        match first_err {
+            Some(err) if matches.is_empty() => Err(err),
+            _ => Ok(matches),
+        }
+    }
 
-        // Try to resolve as a git ref
-        if let Some(ids) = resolve_git_ref(self.repo, symbol) {
-            return Ok(ids);
+    /// Looks up the tag or local branch whose name is equal to `symbol`
+    /// ignoring case. Returns `None` if case-insensitive lookup is disabled.
+    fn resolve_symbol_kind_ignoring_case(
+        &self,
+        kind: RevsetSymbolKind,
+        symbol: &str,
+    ) -> Result<Option<Vec<CommitId>>, RevsetResolutionError> {
+        if !self.case_insensitive_refs {
+            return Ok(None);
         }
+        let view = self.repo.view();
+        let names = match kind {
+            RevsetSymbolKind::Tag => view.tag_names_ignoring_case(symbol),
+            RevsetSymbolKind::Branch => view.local_branch_names_ignoring_case(symbol),
+            _ => return Ok(None),
+        };
+        let name = match names {
+            [] => return Ok(None),
+            [name] => name,
+            _ => {
+                return Err(RevsetResolutionError::AmbiguousRefNameCase {
+                    name: symbol.to_owned(),
+                    kind,
+                    candidates: names.to_vec(),
+                })
+            }
+        };
+        let target = match kind {
+            RevsetSymbolKind::Tag => view.get_tag(name),
+            _ => view.get_local_branch(name),
+        };
+        Ok(target
+            .is_present()
+            .then(|| target.added_ids().cloned().collect()))
+    }
 
-        // Try to resolve as a commit id.
-        if let Some(prefix) = HexPrefix::new(symbol) {
-            match (self.commit_id_resolver)(self.repo, &prefix) {
-                PrefixResolution::AmbiguousMatch => {
-                    return Err(RevsetResolutionError::AmbiguousCommitIdPrefix(
-                        symbol.to_owned(),
-                    ));
-                }
-                PrefixResolution::SingleMatch(id) => {
-                    return Ok(vec![id]);
+    fn resolve_symbol_kind(
+        &self,
+        kind: RevsetSymbolKind,
+        symbol: &str,
+    ) -> Result<Option<Vec<CommitId>>, RevsetResolutionError> {
+        match kind {
+            RevsetSymbolKind::Tag => {
+                let target = self.repo.view().get_tag(symbol);
+                Ok(target
+                    .is_present()
+                    .then(|| target.added_ids().cloned().collect()))
+            }
+            RevsetSymbolKind::Branch => Ok(resolve_local_branch(self.repo, symbol)),
+            RevsetSymbolKind::GitRef => Ok(resolve_git_ref(self.repo, symbol)),
+            RevsetSymbolKind::CommitId => {
+                let Some(prefix) = HexPrefix::new(symbol) else {
+                    return Ok(None);
+                };
+                match (self.commit_id_resolver)(self.repo, &prefix) {
+                    PrefixResolution::AmbiguousMatch => Err(
+                        RevsetResolutionError::AmbiguousCommitIdPrefix(symbol.to_owned()),
+                    ),
+                    PrefixResolution::SingleMatch(id) => Ok(Some(vec![id])),
+                    PrefixResolution::NoMatch => Ok(None),
                 }
-                PrefixResolution::NoMatch => {
-                    // Fall through
+            }
+            RevsetSymbolKind::ChangeId => {
+                let Some(prefix) = to_forward_hex(symbol).as_deref().and_then(HexPrefix::new)
+                else {
+                    return Ok(None);
+                };
+                match (self.change_id_resolver)(self.repo, &prefix) {
+                    PrefixResolution::AmbiguousMatch => Err(
+                        RevsetResolutionError::AmbiguousChangeIdPrefix(symbol.to_owned()),
+                    ),
+                    PrefixResolution::SingleMatch(ids) => Ok(Some(ids)),
+                    PrefixResolution::NoMatch => Ok(None),
                 }
             }
         }
+    }
+}
 
-        // Try to resolve as a change id.
-        if let Some(prefix) = to_forward_hex(symbol).as_deref().and_then(HexPrefix::new) {
-            match (self.change_id_resolver)(self.repo, &prefix) {
-                PrefixResolution::AmbiguousMatch => {
-                    return Err(RevsetResolutionError::AmbiguousChangeIdPrefix(
-                        symbol.to_owned(),
-                    ));
-                }
-                PrefixResolution::SingleMatch(ids) => {
-                    return Ok(ids);
-                }
-                PrefixResolution::NoMatch => {
-                    // Fall through
-                }
+impl SymbolResolver for DefaultSymbolResolver<'_> {
+    fn resolve_symbol(&self, symbol: &str) -> Result<Vec<CommitId>, RevsetResolutionError> {
+        if symbol.is_empty() {
+            return Err(RevsetResolutionError::EmptyString);
+        }
+
+        if self.strict {
+            let mut matches = self.resolve_symbol_matches(symbol)?;
+            return match matches.len() {
+                0 => Err(make_no_such_symbol_error(self.repo, symbol)),
+                1 => Ok(matches.pop().unwrap().1),
+                _ => Err(RevsetResolutionError::AmbiguousSymbol {
+                    name: symbol.to_owned(),
+                    kinds: matches.iter().map(|(kind, _)| *kind).collect(),
+                }),
+            };
+        }
+
+        // Try to resolve as a tag, a branch, a git ref, a commit id, and a change
+        // id in that order.
+        for kind in RevsetSymbolKind::ALL {
+            if let Some(ids) = self.resolve_symbol_kind(kind, symbol)? {
+                return Ok(ids);
+            }
+        }
+        // Exact matches in any namespace take precedence over names differing
+        // only by case.
+        for kind in [RevsetSymbolKind::Tag, RevsetSymbolKind::Branch] {
+            if let Some(ids) = self.resolve_symbol_kind_ignoring_case(kind, symbol)? {
+                return Ok(ids);
             }
         }
 
         Err(make_no_such_symbol_error(self.repo, symbol))
     }
+
+    fn resolve_qualified_symbol(
+        &self,
+        kind: RevsetSymbolKind,
+        symbol: &str,
+    ) -> Result<Vec<CommitId>, RevsetResolutionError> {
+        if symbol.is_empty() {
+            return Err(RevsetResolutionError::EmptyString);
+        }
+        let ids = match self.resolve_symbol_kind(kind, symbol)? {
+            Some(ids) => Some(ids),
+            None => self.resolve_symbol_kind_ignoring_case(kind, symbol)?,
+        };
+        match ids {
+            Some(ids) => Ok(ids),
+            None => Err(RevsetResolutionError::NoSuchRevision {
+                name: format!("{}:{symbol}", kind.qualifier().unwrap_or_default()),
+                candidates: vec![],
+            }),
+        }
+    }
 }
 
 fn resolve_commit_ref(
@@ -2135,6 +2619,9 @@ fn resolve_commit_ref(
 ) -> Result<Vec<CommitId>, RevsetResolutionError> {
     match commit_ref {
         RevsetCommitRef::Symbol(symbol) => symbol_resolver.resolve_symbol(symbol),
+        RevsetCommitRef::QualifiedSymbol { kind, name } => {
+            symbol_resolver.resolve_qualified_symbol(*kind, name)
+        }
         RevsetCommitRef::RemoteSymbol { name, remote } => resolve_remote_branch(repo, name, remote)
             .ok_or_else(|| make_no_such_symbol_error(repo, format!("{name}@{remote}"))),
         RevsetCommitRef::WorkingCopy(workspace_id) => {
@@ -2171,11 +2658,13 @@ fn resolve_commit_ref(
                 .collect();
             Ok(commit_ids)
         }
-        RevsetCommitRef::Tags => {
-            let mut commit_ids = vec![];
-            for ref_target in repo.view().tags().values() {
-                commit_ids.extend(ref_target.added_ids().cloned());
-            }
+        RevsetCommitRef::Tags(pattern) => {
+            let commit_ids = repo
+                .view()
+                .tags_matching(pattern)
+                .flat_map(|(_, target)| target.added_ids())
+                .cloned()
+                .collect();
             Ok(commit_ids)
         }
         RevsetCommitRef::GitRefs => {
@@ -2208,6 +2697,8 @@ fn resolve_symbols(
                         | RevsetResolutionError::EmptyString
                         | RevsetResolutionError::AmbiguousCommitIdPrefix(_)
                         | RevsetResolutionError::AmbiguousChangeIdPrefix(_)
+                        | RevsetResolutionError::AmbiguousSymbol { .. }
+                        | RevsetResolutionError::AmbiguousRefNameCase { .. }
                         | RevsetResolutionError::StoreError(_) => Err(err),
                     })
                     .map(Some) // Always rewrite subtree
@@ -2295,7 +2786,16 @@ impl VisibilityResolutionContext<'_> {
             RevsetExpression::Roots(candidates) => {
                 ResolvedExpression::Roots(self.resolve(candidates).into())
             }
-            RevsetExpression::Latest { candidates, count } => ResolvedExpression::Latest {
+            RevsetExpression::Latest {
+                candidates,
+                count,
+                by,
+            } => ResolvedExpression::Latest {
+                candidates: self.resolve(candidates).into(),
+                count: *count,
+                by: *by,
+            },
+            RevsetExpression::Limit { candidates, count } => ResolvedExpression::Limit {
                 candidates: self.resolve(candidates).into(),
                 count: *count,
             },
@@ -2375,7 +2875,8 @@ impl VisibilityResolutionContext<'_> {
             | RevsetExpression::DagRange { .. }
             | RevsetExpression::Heads(_)
             | RevsetExpression::Roots(_)
-            | RevsetExpression::Latest { .. } => {
+            | RevsetExpression::Latest { .. }
+            | RevsetExpression::Limit { .. } => {
                 ResolvedPredicateExpression::Set(self.resolve(expression).into())
             }
             RevsetExpression::Filter(predicate) => {
@@ -2485,7 +2986,10 @@ pub struct RevsetWorkspaceContext<'a> {
 
 #[cfg(test)]
 mod tests {
+    use assert_matches::assert_matches;
+
     use super::*;
+    use crate::backend::MillisSinceEpoch;
 
     fn parse(revset_str: &str) -> Result<Rc<RevsetExpression>, RevsetParseErrorKind> {
         parse_with_aliases(revset_str, [] as [(&str, &str); 0])
@@ -2930,6 +3434,82 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_parse_date_pattern() {
+        assert_eq!(
+            parse(r#"author_date(after:"2024-01-31T00:00:00Z")"#),
+            Ok(RevsetExpression::filter(RevsetFilterPredicate::AuthorDate(
+                DatePattern::AtOrAfter(MillisSinceEpoch(1_706_659_200_000))
+            )))
+        );
+        assert_eq!(
+            parse(r#"committer_date(before:"2024-01-31T09:00:00+09:00")"#),
+            Ok(RevsetExpression::filter(
+                RevsetFilterPredicate::CommitterDate(DatePattern::Before(MillisSinceEpoch(
+                    1_706_659_200_000
+                )))
+            ))
+        );
+        assert_eq!(
+            parse(r#"author_date("2024-01-31")"#),
+            Err(RevsetParseErrorKind::InvalidFunctionArguments {
+                name: "author_date".to_owned(),
+                message: r#"Expected function argument of date pattern, e.g. after:"2024-01-31""#
+                    .to_owned()
+            })
+        );
+        assert_eq!(
+            parse("latest(all(), 2, by=author)"),
+            Ok(RevsetExpression::all().latest_by(2, SignatureTimestamp::Author))
+        );
+        assert_eq!(
+            parse("latest(all(), by=committer)"),
+            Ok(RevsetExpression::all().latest(1))
+        );
+    }
+
+    #[test]
+    fn test_parse_qualified_symbol() {
+        assert_eq!(
+            parse("branch:foo"),
+            Ok(RevsetExpression::qualified_symbol(
+                RevsetSymbolKind::Branch,
+                "foo".to_owned()
+            ))
+        );
+        assert_eq!(
+            parse(r#"change:"abc""#),
+            Ok(RevsetExpression::qualified_symbol(
+                RevsetSymbolKind::ChangeId,
+                "abc".to_owned()
+            ))
+        );
+        assert_eq!(
+            parse("commit:abc-"),
+            Ok(
+                RevsetExpression::qualified_symbol(RevsetSymbolKind::CommitId, "abc".to_owned())
+                    .parents()
+            )
+        );
+        // Other names are still parsed as the legacy range operator
+        assert_matches!(
+            parse("tag:foo").as_deref(),
+            Ok(RevsetExpression::DagRange {
+                is_legacy: true,
+                ..
+            })
+        );
+        assert_matches!(
+            parse("commit::foo").as_deref(),
+            Ok(RevsetExpression::DagRange {
+                is_legacy: false,
+                ..
+            })
+        );
+        assert_eq!(parse("branch:foo").unwrap().symbols(), Vec::<String>::new());
+        assert_eq!(parse("foo | bar-").unwrap().symbols(), vec!["foo", "bar"]);
+    }
+
     #[test]
     fn test_parse_revset_alias_symbol_decl() {
         let mut aliases_map = RevsetAliasesMap::new();
@@ -3120,6 +3700,36 @@ mod tests {
                 ]
             ))))
         );
+        assert_eq!(
+            parse_with_workspace(
+                r#"file(foo, whitespace="ignore-all-space")"#,
+                &WorkspaceId::default()
+            ),
+            Ok(RevsetExpression::filter(
+                RevsetFilterPredicate::FileIgnoringWhitespace(
+                    vec![RepoPathBuf::from_internal_string("foo")],
+                    WhitespaceMode::IgnoreAllSpace
+                )
+            ))
+        );
+        assert_eq!(
+            parse_with_workspace(r#"file(foo, whitespace="exact")"#, &WorkspaceId::default()),
+            parse_with_workspace("file(foo)", &WorkspaceId::default())
+        );
+        assert!(
+            parse_with_workspace(r#"file(whitespace="exact")"#, &WorkspaceId::default()).is_err()
+        );
+        assert!(
+            parse_with_workspace(r#"file(whitespace="exact", foo)"#, &WorkspaceId::default())
+                .is_err()
+        );
+        assert!(
+            parse_with_workspace(r#"file(foo, whitespace="all")"#, &WorkspaceId::default())
+                .is_err()
+        );
+        assert!(
+            parse_with_workspace(r#"file(foo, space="exact")"#, &WorkspaceId::default()).is_err()
+        );
     }
 
     #[test]
@@ -3351,6 +3961,49 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_references_working_copy() {
+        let workspace_id = WorkspaceId::default();
+        let references = |revset_str: &str| {
+            parse_with_aliases_and_workspace(
+                revset_str,
+                [("mine()", "branches()"), ("pushed()", "remote_branches()")],
+                &workspace_id,
+            )
+            .unwrap()
+            .references_working_copy()
+        };
+        assert!(references("@"));
+        assert!(references("all()"));
+        assert!(references("visible_heads()"));
+        assert!(references("@ | branches()"));
+        assert!(references("branches()::"));
+        assert!(references("~branches()"));
+        assert!(references("@ | remote_branches()"));
+        assert!(references("remote_branches()::"));
+        assert!(references("~remote_branches()"));
+        assert!(references("description(foo)"));
+        assert!(references("::@ & description(foo)"));
+        assert!(references("remote_branches() & @"));
+        assert!(references("heads(remote_branches()) ~ @"));
+        assert!(references("branches() & @"));
+        // Symbols may resolve to the working-copy commit
+        assert!(references("foo"));
+
+        assert!(!references("branches()"));
+        assert!(!references("mine()"));
+        assert!(!references("::branches()"));
+        assert!(!references("root()..tags()"));
+        assert!(!references("latest(git_refs() | git_head())"));
+        assert!(!references("remote_branches()"));
+        assert!(!references("pushed()"));
+        assert!(!references("::remote_branches()"));
+        assert!(!references("root()..remote_branches()"));
+        assert!(!references("remote_branches() & root()"));
+        assert!(!references("latest(remote_branches() | git_head())"));
+        assert!(!references("present(foo@origin) | none()"));
+    }
+
     #[test]
     fn test_optimize_subtree() {
         // Check that transform_expression_bottom_up() never rewrites enum variant
@@ -3376,12 +4029,12 @@ mod tests {
         assert_eq!(
             optimize(parse("(branches() & all())..(all() & tags())").unwrap()),
             RevsetExpression::branches(StringPattern::everything())
-                .range(&RevsetExpression::tags())
+                .range(&RevsetExpression::tags(StringPattern::everything()))
         );
         assert_eq!(
             optimize(parse("(branches() & all()):(all() & tags())").unwrap()),
             RevsetExpression::branches(StringPattern::everything())
-                .dag_range_to(&RevsetExpression::tags())
+                .dag_range_to(&RevsetExpression::tags(StringPattern::everything()))
         );
 
         assert_eq!(
@@ -3419,17 +4072,17 @@ mod tests {
         assert_eq!(
             optimize(parse("(branches() & all()) | (all() & tags())").unwrap()),
             RevsetExpression::branches(StringPattern::everything())
-                .union(&RevsetExpression::tags())
+                .union(&RevsetExpression::tags(StringPattern::everything()))
         );
         assert_eq!(
             optimize(parse("(branches() & all()) & (all() & tags())").unwrap()),
             RevsetExpression::branches(StringPattern::everything())
-                .intersection(&RevsetExpression::tags())
+                .intersection(&RevsetExpression::tags(StringPattern::everything()))
         );
         assert_eq!(
             optimize(parse("(branches() & all()) ~ (all() & tags())").unwrap()),
             RevsetExpression::branches(StringPattern::everything())
-                .minus(&RevsetExpression::tags())
+                .minus(&RevsetExpression::tags(StringPattern::everything()))
         );
     }
 
@@ -3478,7 +4131,7 @@ mod tests {
         ));
         assert_eq!(
             unwrap_union(&optimized).1.as_ref(),
-            &RevsetExpression::CommitRef(RevsetCommitRef::Tags),
+            &RevsetExpression::CommitRef(RevsetCommitRef::Tags(StringPattern::everything())),
         );
     }
 
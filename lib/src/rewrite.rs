@@ -14,25 +14,31 @@
 
 #![allow(missing_docs)]
 
-use std::collections::{HashMap, HashSet};
-use std::sync::Arc;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::slice;
+use std::sync::{Arc, Mutex};
 
 use futures::StreamExt;
 use itertools::Itertools;
 use pollster::FutureExt;
 use tracing::instrument;
 
-use crate::backend::{BackendError, BackendResult, CommitId, MergedTreeId};
+use crate::backend::{
+    BackendError, BackendResult, CommitId, MergedTreeId, GENERATED_DESCRIPTION_METADATA_KEY,
+};
+use crate::cancel::CancellationToken;
 use crate::commit::Commit;
+use crate::commit_builder::CommitBuilder;
 use crate::dag_walk;
 use crate::index::Index;
-use crate::matchers::{Matcher, Visit};
-use crate::merged_tree::{MergedTree, MergedTreeBuilder};
+use crate::matchers::{EverythingMatcher, Matcher, Visit};
+use crate::merge::Merge;
+use crate::merged_tree::{DiffSummary, MergedTree, MergedTreeBuilder};
 use crate::object_id::ObjectId;
 use crate::op_store::RefTarget;
 use crate::repo::{MutableRepo, Repo};
 use crate::repo_path::RepoPath;
-use crate::revset::{RevsetExpression, RevsetIteratorExt};
+use crate::revset::{RevsetEvaluationError, RevsetExpression, RevsetIteratorExt};
 use crate::settings::UserSettings;
 use crate::store::Store;
 use crate::tree::TreeMergeError;
@@ -73,6 +79,89 @@ pub fn merge_commit_trees_without_repo(
     }
 }
 
+/// Maximum number of merged parent trees kept by `MergedParentTreeCache`.
+const MERGED_PARENT_TREE_CACHE_CAPACITY: usize = 1000;
+
+/// Cache of the merged trees of merge commits' parents, i.e. the trees such
+/// commits would have if they didn't change anything.
+///
+/// Entries are keyed by the parent commit ids in order. The merge also depends
+/// on the parents' merge bases, so parents with the same trees aren't
+/// necessarily interchangeable. The cache holds a bounded number of entries,
+/// evicting the oldest first.
+#[derive(Debug, Default)]
+pub(crate) struct MergedParentTreeCache {
+    entries: Mutex<(HashMap<Vec<CommitId>, MergedTree>, VecDeque<Vec<CommitId>>)>,
+}
+
+impl MergedParentTreeCache {
+    pub fn get_or_merge(
+        &self,
+        repo: &dyn Repo,
+        commit: &Commit,
+    ) -> Result<MergedTree, TreeMergeError> {
+        let parent_ids = commit.parent_ids();
+        if let [parent_id] = parent_ids {
+            // Nothing to merge
+            let parent = repo.store().get_commit(parent_id)?;
+            return Ok(parent.tree()?);
+        }
+        if let Some(tree) = self.entries.lock().unwrap().0.get(parent_ids) {
+            return Ok(tree.clone());
+        }
+        let tree = merge_commit_trees(repo, &commit.parents())?;
+        let mut entries = self.entries.lock().unwrap();
+        let (trees, insertion_order) = &mut *entries;
+        if !trees.contains_key(parent_ids) {
+            if trees.len() >= MERGED_PARENT_TREE_CACHE_CAPACITY {
+                let oldest = insertion_order.pop_front().unwrap();
+                trees.remove(&oldest);
+            }
+            trees.insert(parent_ids.to_vec(), tree.clone());
+            insertion_order.push_back(parent_ids.to_vec());
+        }
+        Ok(tree)
+    }
+}
+
+/// Maximum number of commit diff summaries kept by `DiffSummaryCache`.
+const DIFF_SUMMARY_CACHE_CAPACITY: usize = 1000;
+
+/// Cache of the files changed by commits, relative to their merged parent
+/// trees.
+///
+/// Entries are keyed by commit id. The cache holds a bounded number of
+/// entries, evicting the oldest first.
+#[derive(Debug, Default)]
+pub(crate) struct DiffSummaryCache {
+    entries: Mutex<(HashMap<CommitId, Arc<DiffSummary>>, VecDeque<CommitId>)>,
+}
+
+impl DiffSummaryCache {
+    pub fn get_or_compute(
+        &self,
+        repo: &dyn Repo,
+        commit: &Commit,
+    ) -> Result<Arc<DiffSummary>, TreeMergeError> {
+        if let Some(summary) = self.entries.lock().unwrap().0.get(commit.id()) {
+            return Ok(summary.clone());
+        }
+        let parent_tree = repo.merged_parent_tree(commit)?;
+        let summary = Arc::new(parent_tree.diff_summary(&commit.tree()?, &EverythingMatcher)?);
+        let mut entries = self.entries.lock().unwrap();
+        let (summaries, insertion_order) = &mut *entries;
+        if !summaries.contains_key(commit.id()) {
+            if summaries.len() >= DIFF_SUMMARY_CACHE_CAPACITY {
+                let oldest = insertion_order.pop_front().unwrap();
+                summaries.remove(&oldest);
+            }
+            summaries.insert(commit.id().clone(), summary.clone());
+            insertion_order.push_back(commit.id().clone());
+        }
+        Ok(summary)
+    }
+}
+
 /// Restore matching paths from the source into the destination.
 pub fn restore_tree(
     source: &MergedTree,
@@ -131,23 +220,32 @@ pub fn rebase_commit_with_options(
         .map(|parent| parent.store_commit().root_tree.clone())
         .collect_vec();
 
-    let (old_base_tree_id, new_tree_id) = if new_parent_trees == old_parent_trees {
-        (
-            // Optimization: old_base_tree_id is only used for newly empty, but when the parents
-            // haven't changed it can't be newly empty.
-            None,
-            // Optimization: Skip merging.
-            old_commit.tree_id().clone(),
-        )
-    } else {
-        let old_base_tree = merge_commit_trees(mut_repo, &old_parents)?;
-        let new_base_tree = merge_commit_trees(mut_repo, new_parents)?;
-        let old_tree = old_commit.tree()?;
-        (
-            Some(old_base_tree.id()),
-            new_base_tree.merge(&old_base_tree, &old_tree)?.id(),
-        )
-    };
+    let (old_base_tree_id, new_tree_id, conflict_labels, collapsed_paths) =
+        if new_parent_trees == old_parent_trees {
+            (
+                // Optimization: old_base_tree_id is only used for newly empty, but when the
+                // parents haven't changed it can't be newly empty.
+                None,
+                // Optimization: Skip merging.
+                old_commit.tree_id().clone(),
+                old_commit.conflict_labels(),
+                vec![],
+            )
+        } else {
+            let (old_base_tree, new_tree, conflict_labels) =
+                rebase_tree(mut_repo, old_commit, new_parents)?;
+            // Conflicts can accumulate sides each time they're rebased. The terms of
+            // collapsed conflicts no longer match the labels.
+            let (new_tree, collapsed_paths) =
+                new_tree.limit_conflict_sides(settings.max_conflict_sides())?;
+            let conflict_labels = collapsed_paths.is_empty().then_some(conflict_labels);
+            (
+                Some(old_base_tree.id()),
+                new_tree.id(),
+                conflict_labels,
+                collapsed_paths,
+            )
+        };
     // Ensure we don't abandon commits with multiple parents (merge commits), even
     // if they're empty.
     if let [parent] = new_parents {
@@ -176,11 +274,79 @@ pub fn rebase_commit_with_options(
         .iter()
         .map(|commit| commit.id().clone())
         .collect();
-    Ok(mut_repo
+    let new_commit = mut_repo
         .rewrite_commit(settings, old_commit)
         .set_parents(new_parent_ids)
         .set_tree_id(new_tree_id)
-        .write()?)
+        .set_conflict_labels(conflict_labels.as_ref())
+        .write()?;
+    for path in collapsed_paths {
+        mut_repo.record_collapsed_conflict(new_commit.id().clone(), path);
+    }
+    Ok(new_commit)
+}
+
+/// Merges the changes `old_commit` made to its parents into the merged tree of
+/// `new_parents`. Returns the tree of the old parents, the new tree, and labels
+/// describing where each term of the new tree came from.
+fn rebase_tree(
+    repo: &dyn Repo,
+    old_commit: &Commit,
+    new_parents: &[Commit],
+) -> Result<(MergedTree, MergedTree, Merge<String>), TreeMergeError> {
+    let old_base_tree = repo.merged_parent_tree(old_commit)?;
+    let new_base_tree = merge_commit_trees(repo, new_parents)?;
+    let old_tree = old_commit.tree()?;
+    let old_parents = old_commit.parents();
+    let labels = [
+        tree_conflict_labels(
+            &new_base_tree,
+            single_commit(new_parents),
+            "rebase destination",
+        ),
+        tree_conflict_labels(
+            &old_base_tree,
+            single_commit(&old_parents),
+            "parents of rebased commit",
+        ),
+        tree_conflict_labels(&old_tree, Some(old_commit), "rebased commit"),
+    ];
+    let (new_tree, labels) = new_base_tree.merge_with_labels(&old_base_tree, &old_tree, labels)?;
+    Ok((old_base_tree, new_tree, labels))
+}
+
+fn single_commit(commits: &[Commit]) -> Option<&Commit> {
+    match commits {
+        [commit] => Some(commit),
+        _ => None,
+    }
+}
+
+/// Labels for the terms of `tree` when it's merged in the given `role`.
+/// `commit` is the commit `tree` belongs to, or `None` if it's the merged tree
+/// of several commits.
+///
+/// The terms of a resolved tree, or of a merged tree, are labeled with the
+/// role and the first line of the commit's description. A conflicted commit
+/// keeps the labels it has recorded, if any.
+fn tree_conflict_labels(tree: &MergedTree, commit: Option<&Commit>, role: &str) -> Merge<String> {
+    let first_line = commit
+        .and_then(|commit| commit.description().lines().next())
+        .unwrap_or("");
+    let label = if first_line.is_empty() {
+        role.to_owned()
+    } else {
+        format!("{role} \"{first_line}\"")
+    };
+    match (tree.id(), commit) {
+        (MergedTreeId::Merge(tree_ids), _) if tree_ids.is_resolved() => Merge::resolved(label),
+        (MergedTreeId::Merge(tree_ids), None) => tree_ids.map(|_| label.clone()),
+        (MergedTreeId::Legacy(_), _) => Merge::resolved(label),
+        // Terms of unknown origin are left unlabeled by `merge_with_labels()`
+        (MergedTreeId::Merge(_), Some(commit)) => commit
+            .conflict_labels()
+            .unwrap_or_else(|| Merge::resolved(String::new())),
+    }
 }
 
 pub fn rebase_to_dest_parent(
@@ -191,8 +357,8 @@ pub fn rebase_to_dest_parent(
     if source.parent_ids() == destination.parent_ids() {
         Ok(source.tree()?)
     } else {
-        let destination_parent_tree = merge_commit_trees(repo, &destination.parents())?;
-        let source_parent_tree = merge_commit_trees(repo, &source.parents())?;
+        let destination_parent_tree = repo.merged_parent_tree(destination)?;
+        let source_parent_tree = repo.merged_parent_tree(source)?;
         let source_tree = source.tree()?;
         let rebased_tree = destination_parent_tree.merge(&source_parent_tree, &source_tree)?;
         Ok(rebased_tree)
@@ -205,7 +371,7 @@ pub fn back_out_commit(
     old_commit: &Commit,
     new_parents: &[Commit],
 ) -> Result<Commit, TreeMergeError> {
-    let old_base_tree = merge_commit_trees(mut_repo, &old_commit.parents())?;
+    let old_base_tree = mut_repo.merged_parent_tree(old_commit)?;
     let new_base_tree = merge_commit_trees(mut_repo, new_parents)?;
     let old_tree = old_commit.tree()?;
     let new_tree = new_base_tree.merge(&old_tree, &old_base_tree)?;
@@ -217,9 +383,193 @@ pub fn back_out_commit(
     Ok(mut_repo
         .new_commit(settings, new_parent_ids, new_tree.id())
         .set_description(format!("backout of commit {}", &old_commit.id().hex()))
+        .set_metadata(GENERATED_DESCRIPTION_METADATA_KEY, "true")
         .write()?)
 }
 
+/// A commit visited by `transform_descendants()`, along with its new parents.
+pub struct CommitRewriteContext<'repo> {
+    settings: &'repo UserSettings,
+    mut_repo: &'repo mut MutableRepo,
+    old_commit: Commit,
+    new_parents: Vec<Commit>,
+    abandoned: &'repo mut bool,
+}
+
+impl<'repo> CommitRewriteContext<'repo> {
+    /// The commit as it was before the transformation.
+    pub fn old_commit(&self) -> &Commit {
+        &self.old_commit
+    }
+
+    /// The parents of the commit after the transformation, i.e. its old
+    /// parents replaced by what they were rewritten to.
+    pub fn new_parents(&self) -> &[Commit] {
+        &self.new_parents
+    }
+
+    /// Returns true if any of the parents was rewritten or abandoned.
+    pub fn parents_changed(&self) -> bool {
+        self.new_parents
+            .iter()
+            .map(|parent| parent.id())
+            .ne(self.old_commit.parent_ids())
+    }
+
+    pub fn repo(&self) -> &MutableRepo {
+        self.mut_repo
+    }
+
+    /// Returns a builder for the replacement of the commit, with its new
+    /// parents and its changes rebased onto them. The change id is preserved.
+    pub fn rebase(self) -> Result<CommitBuilder<'repo>, TreeMergeError> {
+        let (tree_id, conflict_labels) = if self.parents_changed() {
+            let (_, new_tree, conflict_labels) =
+                rebase_tree(self.mut_repo, &self.old_commit, &self.new_parents)?;
+            (new_tree.id(), Some(conflict_labels))
+        } else {
+            (
+                self.old_commit.tree_id().clone(),
+                self.old_commit.conflict_labels(),
+            )
+        };
+        let new_parent_ids = self
+            .new_parents
+            .iter()
+            .map(|commit| commit.id().clone())
+            .collect_vec();
+        let CommitRewriteContext {
+            settings,
+            mut_repo,
+            old_commit,
+            ..
+        } = self;
+        Ok(mut_repo
+            .rewrite_commit(settings, &old_commit)
+            .set_parents(new_parent_ids)
+            .set_tree_id(tree_id)
+            .set_conflict_labels(conflict_labels.as_ref()))
+    }
+
+    /// Abandons the commit. Its children are reparented onto its new parents.
+    /// The callback should return `None` after calling this.
+    pub fn abandon(self) {
+        *self.abandoned = true;
+    }
+}
+
+/// Visits the `roots` and all their descendants in topological order, and
+/// lets `callback` produce a replacement for each of them.
+///
+/// The callback returns a `CommitBuilder` (typically from
+/// `CommitRewriteContext::rebase()`) to rewrite the commit, or `None` to keep
+/// it as is apart from rebasing it onto its new parents. Branches and
+/// working-copy commits pointing to the old commits are moved to their
+/// replacements.
+///
+/// Returns a map from the ids of the rewritten (or rebased) commits to the ids
+/// of their replacements. Abandoned commits are not included.
+pub fn transform_descendants<E>(
+    settings: &UserSettings,
+    mut_repo: &mut MutableRepo,
+    roots: Vec<CommitId>,
+    mut callback: impl FnMut(CommitRewriteContext<'_>) -> Result<Option<CommitBuilder<'_>>, E>,
+) -> Result<HashMap<CommitId, CommitId>, E>
+where
+    E: From<TreeMergeError> + From<RevsetEvaluationError>,
+{
+    let store = mut_repo.store().clone();
+    let to_visit: Vec<Commit> = RevsetExpression::commits(roots)
+        .descendants()
+        .evaluate_programmatic(mut_repo)?
+        .iter()
+        .commits(&store)
+        .try_collect()
+        .map_err(|err| E::from(TreeMergeError::from(err)))?;
+    // Old commit id -> the ids it was replaced by. An abandoned commit is
+    // replaced by its new parents.
+    let mut replacements: HashMap<CommitId, Vec<CommitId>> = HashMap::new();
+    let mut rewritten = HashMap::new();
+    // The revset is in reverse topological order
+    for old_commit in to_visit.into_iter().rev() {
+        let mut new_parent_ids = old_commit
+            .parent_ids()
+            .iter()
+            .flat_map(|id| {
+                replacements
+                    .get(id)
+                    .map_or(slice::from_ref(id), Vec::as_slice)
+            })
+            .unique()
+            .cloned()
+            .collect_vec();
+        if new_parent_ids != old_commit.parent_ids() {
+            // Don't create a commit where one parent is an ancestor of another
+            let heads: HashSet<_> = mut_repo
+                .index()
+                .heads(&mut new_parent_ids.iter())
+                .into_iter()
+                .collect();
+            new_parent_ids.retain(|id| heads.contains(id));
+        }
+        let new_parents: Vec<_> = new_parent_ids
+            .iter()
+            .map(|id| store.get_commit(id))
+            .try_collect()
+            .map_err(|err| E::from(TreeMergeError::from(err)))?;
+
+        let mut abandoned = false;
+        let context = CommitRewriteContext {
+            settings,
+            mut_repo: &mut *mut_repo,
+            old_commit: old_commit.clone(),
+            new_parents,
+            abandoned: &mut abandoned,
+        };
+        let parents_changed = context.parents_changed();
+        let rewritten_commit = match callback(context)? {
+            Some(builder) => Some(builder.write().map_err(TreeMergeError::from)?),
+            None => None,
+        };
+        let new_commit = match rewritten_commit {
+            Some(commit) => Some(commit),
+            None if abandoned => None,
+            None if parents_changed => {
+                let new_parents: Vec<_> = new_parent_ids
+                    .iter()
+                    .map(|id| store.get_commit(id))
+                    .try_collect()
+                    .map_err(TreeMergeError::from)?;
+                Some(rebase_commit(
+                    settings,
+                    mut_repo,
+                    &old_commit,
+                    &new_parents,
+                )?)
+            }
+            None => continue,
+        };
+        let old_id = old_commit.id().clone();
+        match new_commit {
+            Some(new_commit) => {
+                // The callback may have assigned a new change id, in which case
+                // the rewrite isn't recorded by the builder.
+                mut_repo.set_rewritten_commit(old_id.clone(), [new_commit.id().clone()]);
+                replacements.insert(old_id.clone(), vec![new_commit.id().clone()]);
+                rewritten.insert(old_id, new_commit.id().clone());
+            }
+            None => {
+                mut_repo.record_abandoned_commit(old_id.clone());
+                replacements.insert(old_id, new_parent_ids);
+            }
+        }
+    }
+    // All descendants are already in place, so this only updates the branches,
+    // working-copy commits and heads.
+    mut_repo.rebase_descendants(settings)?;
+    Ok(rewritten)
+}
+
 #[derive(Clone, Default, PartialEq, Eq, Debug)]
 pub enum EmptyBehaviour {
     /// Always keep empty commits
@@ -244,6 +594,10 @@ pub enum EmptyBehaviour {
 #[derive(Clone, Default, PartialEq, Eq, Debug)]
 pub struct RebaseOptions {
     pub empty: EmptyBehaviour,
+    /// Stops the rebase between commits once cancelled. The commits rebased
+    /// so far are left in the `MutableRepo`, so the transaction should be
+    /// discarded.
+    pub cancel: Option<CancellationToken>,
 }
 
 /// Rebases descendants of a commit onto a new commit (or several).
@@ -589,6 +943,9 @@ impl<'settings, 'repo> DescendantRebaser<'settings, 'repo> {
 
     pub fn rebase_all(&mut self) -> Result<(), TreeMergeError> {
         while let Some(old_commit) = self.to_visit.pop() {
+            if let Some(cancel) = &self.options.cancel {
+                cancel.checkpoint()?;
+            }
             self.rebase_one(old_commit)?;
         }
         let mut view = self.mut_repo.view().store_view().clone();
@@ -16,6 +16,7 @@
 
 use std::path::Path;
 use std::sync::{Arc, Mutex};
+use std::time::Duration;
 
 use chrono::DateTime;
 use rand::prelude::*;
@@ -24,6 +25,8 @@ use rand_chacha::ChaCha20Rng;
 use crate::backend::{ChangeId, Commit, Signature, Timestamp};
 use crate::fmt_util::binary_prefix;
 use crate::fsmonitor::FsmonitorKind;
+use crate::merge_drivers::{MergeDriverConfigError, MergeDrivers};
+use crate::repo::AbandonOnCheckout;
 use crate::signing::SignBehavior;
 
 #[derive(Debug, Clone)]
@@ -42,6 +45,12 @@ pub struct RepoSettings {
 pub struct GitSettings {
     pub auto_local_branch: bool,
     pub abandon_unreachable_commits: bool,
+    /// How many times a fetch is attempted when the transfer fails because of
+    /// a network error.
+    pub fetch_attempts: u32,
+    /// How long to wait before the first retry of a fetch. The delay doubles
+    /// with each further retry.
+    pub fetch_retry_delay: Duration,
 }
 
 impl GitSettings {
@@ -51,6 +60,10 @@ impl GitSettings {
             abandon_unreachable_commits: config
                 .get_bool("git.abandon-unreachable-commits")
                 .unwrap_or(true),
+            fetch_attempts: config
+                .get::<u32>("git.fetch-attempts")
+                .map_or(1, |attempts| attempts.max(1)),
+            fetch_retry_delay: Duration::from_secs(1),
         }
     }
 }
@@ -60,6 +73,8 @@ impl Default for GitSettings {
         GitSettings {
             auto_local_branch: false,
             abandon_unreachable_commits: true,
+            fetch_attempts: 1,
+            fetch_retry_delay: Duration::from_secs(1),
         }
     }
 }
@@ -153,6 +168,27 @@ impl UserSettings {
             .unwrap_or(false)
     }
 
+    /// Whether the store should check that newly written trees and commits
+    /// only reference objects that exist. Enabled by default in debug builds.
+    pub fn strict_store_validation(&self) -> bool {
+        self.config
+            .get_bool("debug.strict-store-validation")
+            .unwrap_or(cfg!(debug_assertions))
+    }
+
+    /// Whether the index should store filters of the paths changed by each
+    /// commit, which speed up `file()` revsets.
+    pub fn index_changed_path_filters(&self) -> bool {
+        self.config
+            .get_bool("index.changed-path-filters")
+            .unwrap_or(false)
+    }
+
+    /// Merge drivers configured in `merge.drivers` and `merge.driver-paths`.
+    pub fn merge_drivers(&self) -> Result<MergeDrivers, MergeDriverConfigError> {
+        MergeDrivers::from_config(&self.config)
+    }
+
     pub fn user_name(&self) -> String {
         self.config.get_string("user.name").unwrap_or_default()
     }
@@ -204,6 +240,33 @@ impl UserSettings {
             .unwrap_or_default()
     }
 
+    /// Whether descriptions are normalized when set on a commit. See
+    /// `commit_builder::normalize_description()`.
+    pub fn normalize_descriptions(&self) -> bool {
+        self.config
+            .get_bool("ui.normalize-descriptions")
+            .unwrap_or(true)
+    }
+
+    /// Whether the committer timestamps of new and rewritten commits are moved
+    /// forward to the latest committer timestamp of their parents if the clock
+    /// says they're earlier.
+    pub fn monotonic_commit_timestamps(&self) -> bool {
+        self.config
+            .get_bool("timestamps.monotonic-commits")
+            .unwrap_or(true)
+    }
+
+    /// How far the end time of an operation may be moved forward to the end
+    /// time of its parent before the clock is considered wrong.
+    pub fn clock_skew_warning_threshold(&self) -> Result<Duration, config::ConfigError> {
+        match self.config.get::<u64>("timestamps.clock-skew-warning-secs") {
+            Ok(secs) => Ok(Duration::from_secs(secs)),
+            Err(config::ConfigError::NotFound(_)) => Ok(Duration::from_secs(3600)),
+            Err(err) => Err(err),
+        }
+    }
+
     pub fn default_revset(&self) -> String {
         self.config.get_string("revsets.log").unwrap_or_else(|_| {
             // For compatibility with old config files (<0.8.0)
@@ -234,6 +297,12 @@ impl UserSettings {
         self.config.get_bool("ui.diff-instructions").unwrap_or(true)
     }
 
+    /// Whether paths are shown relative to the current directory rather than
+    /// to the workspace root.
+    pub fn relative_paths(&self) -> bool {
+        self.config.get_bool("ui.relative-paths").unwrap_or(true)
+    }
+
     pub fn config(&self) -> &config::Config {
         &self.config
     }
@@ -261,6 +330,41 @@ impl UserSettings {
         }
     }
 
+    /// Consecutive working-copy snapshots recorded within this long of each
+    /// other are coalesced into one operation. Zero, the default, disables
+    /// coalescing.
+    pub fn snapshot_coalesce_window(&self) -> Result<Duration, config::ConfigError> {
+        match self.config.get::<u64>("snapshot.coalesce-window-secs") {
+            Ok(secs) => Ok(Duration::from_secs(secs)),
+            Err(config::ConfigError::NotFound(_)) => Ok(Duration::ZERO),
+            Err(err) => Err(err),
+        }
+    }
+
+    /// Conflicts with more sides than this after simplification are collapsed
+    /// when commits are rebased. See `MergedTree::limit_conflict_sides()`.
+    pub fn max_conflict_sides(&self) -> usize {
+        self.config
+            .get::<usize>("rebase.max-conflict-sides")
+            .unwrap_or(16)
+    }
+
+    /// Which working-copy commits are abandoned when the working copy moves
+    /// to another commit.
+    pub fn abandon_on_checkout(&self) -> Result<AbandonOnCheckout, config::ConfigError> {
+        match self.config.get_string("working-copy.abandon-on-checkout") {
+            Ok(policy) => policy.parse(),
+            Err(config::ConfigError::NotFound(_)) => Ok(AbandonOnCheckout::default()),
+            Err(err) => Err(err),
+        }
+    }
+
+    /// Whether the working copy should be snapshotted automatically before
+    /// running commands.
+    pub fn auto_snapshot(&self) -> bool {
+        self.config.get_bool("snapshot.auto").unwrap_or(true)
+    }
+
     // separate from sign_settings as those two are needed in pretty different
     // places
     pub fn signing_backend(&self) -> Option<String> {
@@ -19,9 +19,12 @@ use std::collections::HashMap;
 use std::fmt::Debug;
 use std::sync::RwLock;
 
+use itertools::Itertools as _;
 use thiserror::Error;
 
 use crate::backend::CommitId;
+#[cfg(feature = "gpg")]
+use crate::gpg_signing::GpgBackend;
 use crate::settings::UserSettings;
 
 /// A status of the signature, part of the [Verification] type.
@@ -33,6 +36,9 @@ pub enum SigStatus {
     Unknown,
     /// Valid signature that does not match the signed data.
     Bad,
+    /// Valid signature that matches the data, but was made by a key that has
+    /// expired (or the signature itself has expired).
+    Expired,
 }
 
 /// The result of a signature verification.
@@ -86,6 +92,19 @@ pub trait SigningBackend: Debug + Send + Sync {
     /// verify(data, sign(data)?)?.status == SigStatus::Good
     /// ```
     fn verify(&self, data: &[u8], signature: &[u8]) -> SignResult<Verification>;
+
+    /// Verify a batch of `(data, signature)` pairs. Returns one result per
+    /// pair, in the same order.
+    ///
+    /// Backends that have a high per-call cost (e.g. because they spawn a
+    /// process) should override this to verify the signatures concurrently or
+    /// in a single call.
+    fn verify_batch(&self, items: &[(&[u8], &[u8])]) -> Vec<SignResult<Verification>> {
+        items
+            .iter()
+            .map(|(data, signature)| self.verify(data, signature))
+            .collect()
+    }
 }
 
 /// An error type for the signing/verifying operations
@@ -150,11 +169,13 @@ impl Signer {
     /// Creates a signer based on user settings. Uses all known backends, and
     /// chooses one of them to be used for signing depending on the config.
     pub fn from_settings(settings: &UserSettings) -> Result<Self, SignInitError> {
+        #[allow(unused_mut)]
         let mut backends: Vec<Box<dyn SigningBackend>> = vec![
-            // Box::new(GpgBackend::from_settings(settings)?),
             // Box::new(SshBackend::from_settings(settings)?),
             // Box::new(X509Backend::from_settings(settings)?),
         ];
+        #[cfg(feature = "gpg")]
+        backends.push(Box::new(GpgBackend::from_settings(settings)?));
 
         let main_backend = settings
             .signing_backend()
@@ -204,46 +225,85 @@ impl Signer {
         data: &[u8],
         signature: &[u8],
     ) -> SignResult<Verification> {
-        let cached = self.cache.read().unwrap().get(commit_id).cloned();
-        if let Some(check) = cached {
-            return Ok(check);
-        }
+        self.verify_batch(&[(commit_id, data, signature)])
+            .pop()
+            .unwrap()
+    }
 
-        let verification = self
-            .main_backend
-            .iter()
-            .chain(self.backends.iter())
-            .filter(|b| b.can_read(signature))
-            // skip unknown and invalid sigs to allow other backends that can read to try
-            // for example, we might have gpg and sq, both of which could read a PGP signature
-            .find_map(|backend| match backend.verify(data, signature) {
-                Ok(check) if check.status == SigStatus::Unknown => None,
-                Err(SignError::InvalidSignatureFormat) => None,
-                e => Some(e),
-            })
-            .transpose()?;
+    /// Verifies the signatures of a batch of `(commit_id, data, signature)`
+    /// items. Returns one result per item, in the same order.
+    ///
+    /// Results are cached by commit id, and only the signatures that aren't
+    /// cached yet are passed to the backends, in one `verify_batch()` call per
+    /// backend.
+    pub fn verify_batch(
+        &self,
+        items: &[(&CommitId, &[u8], &[u8])],
+    ) -> Vec<SignResult<Verification>> {
+        let mut results: Vec<Option<SignResult<Verification>>> = {
+            let cache = self.cache.read().unwrap();
+            items
+                .iter()
+                .map(|(commit_id, _, _)| cache.get(*commit_id).cloned().map(Ok))
+                .collect()
+        };
+        // The first `Unknown` result of each item, which may still carry the
+        // key of the signature.
+        let mut unknowns: Vec<Option<Verification>> = vec![None; items.len()];
 
-        if let Some(verification) = verification {
-            // a key might get imported before next call?.
-            // realistically this is unlikely, but technically
-            // it's correct to not cache unknowns here
-            if verification.status != SigStatus::Unknown {
-                self.cache
-                    .write()
-                    .unwrap()
-                    .insert(commit_id.clone(), verification.clone());
+        for backend in self.main_backend.iter().chain(self.backends.iter()) {
+            let indices = (0..items.len())
+                .filter(|&i| results[i].is_none() && backend.can_read(items[i].2))
+                .collect_vec();
+            if indices.is_empty() {
+                continue;
+            }
+            let batch = indices
+                .iter()
+                .map(|&i| (items[i].1, items[i].2))
+                .collect_vec();
+            for (i, result) in indices.into_iter().zip(backend.verify_batch(&batch)) {
+                match result {
+                    // skip unknown and invalid sigs to allow other backends that can read to
+                    // try for example, we might have gpg and sq, both of which could read a
+                    // PGP signature
+                    Ok(check) if check.status == SigStatus::Unknown => {
+                        unknowns[i].get_or_insert(check);
+                    }
+                    Err(SignError::InvalidSignatureFormat) => {}
+                    Ok(check) => {
+                        // a key might get imported before next call?.
+                        // realistically this is unlikely, but technically
+                        // it's correct to not cache unknowns here
+                        self.cache
+                            .write()
+                            .unwrap()
+                            .insert(items[i].0.clone(), check.clone());
+                        results[i] = Some(Ok(check));
+                    }
+                    Err(err) => results[i] = Some(Err(err)),
+                }
             }
-            Ok(verification)
-        } else {
-            // now here it's correct to cache unknowns, as we don't
-            // have a backend that knows how to handle this signature
-            //
-            // not sure about how much of an optimization this is
-            self.cache
-                .write()
-                .unwrap()
-                .insert(commit_id.clone(), Verification::unknown());
-            Ok(Verification::unknown())
         }
+
+        results
+            .into_iter()
+            .zip(unknowns)
+            .zip(items)
+            .map(|((result, unknown), (commit_id, _, _))| {
+                result.unwrap_or_else(|| {
+                    let verification = unknown.unwrap_or_else(Verification::unknown);
+                    // now here it's correct to cache unknowns, as we don't
+                    // have a backend that knows how to handle this signature
+                    //
+                    // not sure about how much of an optimization this is
+                    self.cache
+                        .write()
+                        .unwrap()
+                        .insert((*commit_id).clone(), verification.clone());
+                    Ok(verification)
+                })
+            })
+            .collect()
     }
 }
@@ -17,15 +17,24 @@
 use std::any::Any;
 use std::fmt::{Debug, Formatter};
 use std::fs;
+use std::io::{self, ErrorKind, Write as _};
 use std::path::{Path, PathBuf};
 
-use crate::lock::FileLock;
+use tempfile::NamedTempFile;
+
+use crate::lock::{FileLock, LockKind};
 use crate::object_id::ObjectId;
 use crate::op_heads_store::{OpHeadsStore, OpHeadsStoreLock};
 use crate::op_store::OperationId;
 
+/// Name of the file in the op heads store's directory with the number of times
+/// the op heads have been updated. Watchers can watch or poll this file instead
+/// of listing the op heads.
+pub const SEQUENCE_FILE_NAME: &str = "sequence";
+
 pub struct SimpleOpHeadsStore {
     dir: PathBuf,
+    sequence_path: PathBuf,
 }
 
 impl Debug for SimpleOpHeadsStore {
@@ -44,12 +53,57 @@ impl SimpleOpHeadsStore {
     pub fn init(dir: &Path) -> Self {
         let op_heads_dir = dir.join("heads");
         fs::create_dir(&op_heads_dir).unwrap();
-        Self { dir: op_heads_dir }
+        Self {
+            dir: op_heads_dir,
+            sequence_path: dir.join(SEQUENCE_FILE_NAME),
+        }
     }
 
     pub fn load(dir: &Path) -> Self {
         let op_heads_dir = dir.join("heads");
-        Self { dir: op_heads_dir }
+        Self {
+            dir: op_heads_dir,
+            sequence_path: dir.join(SEQUENCE_FILE_NAME),
+        }
+    }
+
+    /// Lists the op heads, like `get_op_heads()` but returning the error if the
+    /// directory can't be read.
+    pub fn try_get_op_heads(&self) -> io::Result<Vec<OperationId>> {
+        let mut op_heads = vec![];
+        for op_head_entry in fs::read_dir(&self.dir)? {
+            let op_head_file_name = op_head_entry?.file_name();
+            let Some(op_head_file_name) = op_head_file_name.to_str() else {
+                continue;
+            };
+            if let Ok(op_head) = hex::decode(op_head_file_name) {
+                op_heads.push(OperationId::new(op_head));
+            }
+        }
+        Ok(op_heads)
+    }
+
+    /// Returns the number of times the op heads have been updated, or 0 if the
+    /// sequence file hasn't been written yet.
+    pub fn read_sequence_number(&self) -> io::Result<u64> {
+        match fs::read_to_string(&self.sequence_path) {
+            Ok(content) => Ok(content.trim().parse().unwrap_or(0)),
+            Err(err) if err.kind() == ErrorKind::NotFound => Ok(0),
+            Err(err) => Err(err),
+        }
+    }
+
+    fn increment_sequence_number(&self) -> io::Result<()> {
+        let _lock = FileLock::lock(
+            self.sequence_path.with_extension("lock"),
+            LockKind::OpHeadsSequence,
+        );
+        let next = self.read_sequence_number()? + 1;
+        // Replace the file atomically so readers never see a partial number
+        let mut temp_file = NamedTempFile::new_in(self.sequence_path.parent().unwrap())?;
+        temp_file.write_all(next.to_string().as_bytes())?;
+        temp_file.persist(&self.sequence_path)?;
+        Ok(())
     }
 
     fn add_op_head(&self, id: &OperationId) {
@@ -86,23 +140,22 @@ impl OpHeadsStore for SimpleOpHeadsStore {
         for old_id in old_ids {
             self.remove_op_head(old_id)
         }
+        // Only watchers depend on the sequence number, so failing to update it
+        // doesn't fail the operation.
+        self.increment_sequence_number().ok();
     }
 
     fn get_op_heads(&self) -> Vec<OperationId> {
-        let mut op_heads = vec![];
-        for op_head_entry in std::fs::read_dir(&self.dir).unwrap() {
-            let op_head_file_name = op_head_entry.unwrap().file_name();
-            let op_head_file_name = op_head_file_name.to_str().unwrap();
-            if let Ok(op_head) = hex::decode(op_head_file_name) {
-                op_heads.push(OperationId::new(op_head));
-            }
-        }
-        op_heads
+        // Callers report a repo without op heads as an error
+        self.try_get_op_heads().unwrap_or_else(|err| {
+            tracing::warn!(?err, dir = ?self.dir, "failed to list op heads");
+            vec![]
+        })
     }
 
     fn lock(&self) -> Box<dyn OpHeadsStoreLock + '_> {
         Box::new(SimpleOpHeadsStoreLock {
-            _lock: FileLock::lock(self.dir.join("lock")),
+            _lock: FileLock::lock(self.dir.join("lock"), LockKind::OpHeads),
         })
     }
 }
@@ -33,8 +33,8 @@ use crate::file_util::{persist_content_addressed_temp_file, IoResultExt as _, Pa
 use crate::merge::Merge;
 use crate::object_id::{HexPrefix, ObjectId, PrefixResolution};
 use crate::op_store::{
-    OpStore, OpStoreError, OpStoreResult, Operation, OperationId, OperationMetadata, RefTarget,
-    RemoteRef, RemoteRefState, RemoteView, View, ViewId, WorkspaceId,
+    OpStore, OpStoreError, OpStoreGcStats, OpStoreResult, Operation, OperationId,
+    OperationMetadata, RefTarget, RemoteRef, RemoteRefState, RemoteView, View, ViewId, WorkspaceId,
 };
 use crate::{dag_walk, git, op_store};
 
@@ -226,8 +226,31 @@ impl OpStore for SimpleOpStore {
             .map_err(|err| OpStoreError::Other(err.into()))
     }
 
+    fn gc(
+        &self,
+        head_ids: &[OperationId],
+        keep_newer: SystemTime,
+    ) -> OpStoreResult<OpStoreGcStats> {
+        self.prune(head_ids, keep_newer, false)
+    }
+
+    fn gc_dry_run(
+        &self,
+        head_ids: &[OperationId],
+        keep_newer: SystemTime,
+    ) -> OpStoreResult<OpStoreGcStats> {
+        self.prune(head_ids, keep_newer, true)
+    }
+}
+
+impl SimpleOpStore {
     #[tracing::instrument(skip(self))]
-    fn gc(&self, head_ids: &[OperationId], keep_newer: SystemTime) -> OpStoreResult<()> {
+    fn prune(
+        &self,
+        head_ids: &[OperationId],
+        keep_newer: SystemTime,
+        dry_run: bool,
+    ) -> OpStoreResult<OpStoreGcStats> {
         let to_op_id = |entry: &fs::DirEntry| -> Option<OperationId> {
             let name = entry.file_name().into_string().ok()?;
             OperationId::try_from_hex(&name).ok()
@@ -236,7 +259,8 @@ impl OpStore for SimpleOpStore {
             let name = entry.file_name().into_string().ok()?;
             ViewId::try_from_hex(&name).ok()
         };
-        let remove_file_if_not_new = |entry: &fs::DirEntry| -> Result<(), PathError> {
+        // Returns whether the file was (or would be) removed.
+        let remove_file_if_not_new = |entry: &fs::DirEntry| -> Result<bool, PathError> {
             let path = entry.path();
             // Check timestamp, but there's still TOCTOU problem if an existing
             // file is renewed.
@@ -244,21 +268,30 @@ impl OpStore for SimpleOpStore {
             let mtime = metadata.modified().expect("unsupported platform?");
             if mtime > keep_newer {
                 tracing::trace!(?path, "not removing");
-                Ok(())
+                Ok(false)
+            } else if dry_run {
+                tracing::trace!(?path, "would remove");
+                Ok(true)
             } else {
                 tracing::trace!(?path, "removing");
-                fs::remove_file(&path).context(&path)
+                fs::remove_file(&path).context(&path)?;
+                Ok(true)
             }
         };
 
         // Reachable objects are resolved without considering the keep_newer
         // parameter. We could collect ancestors of the "new" operations here,
-        // but more files can be added anyway after that.
+        // but more files can be added anyway after that. Replaced operations
+        // are kept along with the operations that replaced them.
         let read_op = |id: &OperationId| self.read_operation(id).map(|data| (id.clone(), data));
         let reachable_ops: HashMap<OperationId, Operation> = dag_walk::dfs_ok(
             head_ids.iter().map(read_op),
             |(id, _)| id.clone(),
-            |(_, data)| data.parents.iter().map(read_op).collect_vec(),
+            |(_, data)| {
+                itertools::chain(&data.parents, &data.replaced)
+                    .map(read_op)
+                    .collect_vec()
+            },
         )
         .try_collect()?;
         let reachable_views: HashSet<&ViewId> =
@@ -269,7 +302,8 @@ impl OpStore for SimpleOpStore {
             "collected reachable objects"
         );
 
-        let prune_ops = || -> Result<(), PathError> {
+        let mut stats = OpStoreGcStats::default();
+        let mut prune_ops = || -> Result<(), PathError> {
             let op_dir = self.path.join("operations");
             for entry in op_dir.read_dir().context(&op_dir)? {
                 let entry = entry.context(&op_dir)?;
@@ -283,13 +317,15 @@ impl OpStore for SimpleOpStore {
                 // If the operation was added after collecting reachable_views,
                 // its view mtime would also be renewed. So there's no need to
                 // update the reachable_views set to preserve the view.
-                remove_file_if_not_new(&entry)?;
+                if remove_file_if_not_new(&entry)? {
+                    stats.num_operations += 1;
+                }
             }
             Ok(())
         };
         prune_ops().map_err(|err| OpStoreError::Other(err.into()))?;
 
-        let prune_views = || -> Result<(), PathError> {
+        let mut prune_views = || -> Result<(), PathError> {
             let view_dir = self.path.join("views");
             for entry in view_dir.read_dir().context(&view_dir)? {
                 let entry = entry.context(&view_dir)?;
@@ -300,13 +336,15 @@ impl OpStore for SimpleOpStore {
                 if reachable_views.contains(&id) {
                     continue;
                 }
-                remove_file_if_not_new(&entry)?;
+                if remove_file_if_not_new(&entry)? {
+                    stats.num_views += 1;
+                }
             }
             Ok(())
         };
         prune_views().map_err(|err| OpStoreError::Other(err.into()))?;
 
-        Ok(())
+        Ok(stats)
     }
 }
 
@@ -384,6 +422,9 @@ fn operation_to_proto(operation: &Operation) -> crate::protos::op_store::Operati
     for parent in &operation.parents {
         proto.parents.push(parent.to_bytes());
     }
+    for replaced in &operation.replaced {
+        proto.replaced.push(replaced.to_bytes());
+    }
     proto
 }
 
@@ -391,10 +432,12 @@ fn operation_from_proto(proto: crate::protos::op_store::Operation) -> Operation
     let parents = proto.parents.into_iter().map(OperationId::new).collect();
     let view_id = ViewId::new(proto.view_id);
     let metadata = operation_metadata_from_proto(proto.metadata.unwrap_or_default());
+    let replaced = proto.replaced.into_iter().map(OperationId::new).collect();
     Operation {
         view_id,
         parents,
         metadata,
+        replaced,
     }
 }
 
@@ -762,6 +805,7 @@ mod tests {
                     "key2".to_string() => "value2".to_string(),
                 },
             },
+            replaced: vec![],
         }
     }
 
@@ -801,6 +845,15 @@ mod tests {
         let op_id = store.write_operation(&operation).unwrap();
         let read_operation = store.read_operation(&op_id).unwrap();
         assert_eq!(read_operation, operation);
+
+        let operation = Operation {
+            replaced: vec![OperationId::from_hex("ccc111")],
+            ..create_operation()
+        };
+        let replacing_op_id = store.write_operation(&operation).unwrap();
+        assert_ne!(replacing_op_id, op_id);
+        let read_operation = store.read_operation(&replacing_op_id).unwrap();
+        assert_eq!(read_operation, operation);
     }
 
     #[test]
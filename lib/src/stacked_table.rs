@@ -33,7 +33,7 @@ use tempfile::NamedTempFile;
 use thiserror::Error;
 
 use crate::file_util::persist_content_addressed_temp_file;
-use crate::lock::FileLock;
+use crate::lock::{FileLock, LockKind};
 
 pub trait TableSegment {
     fn segment_num_entries(&self) -> usize;
@@ -425,7 +425,7 @@ impl TableStore {
     }
 
     fn lock(&self) -> FileLock {
-        FileLock::lock(self.dir.join("lock"))
+        FileLock::lock(self.dir.join("lock"), LockKind::Table)
     }
 
     fn load_table(&self, name: String) -> TableStoreResult<Arc<ReadonlyTable>> {
@@ -0,0 +1,127 @@
+// Copyright 2024 The Jujutsu Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! High-level status of a working-copy commit and the repo it's in, as shown
+//! by `jj status`.
+
+#![allow(missing_docs)]
+
+use itertools::Itertools as _;
+
+use crate::commit::Commit;
+use crate::merge::MergedTreeValue;
+use crate::merged_tree::MergedTree;
+use crate::repo::Repo;
+use crate::repo_path::RepoPathBuf;
+use crate::tree::TreeMergeError;
+
+/// The most severe kind of problem found by `RepoStatus::gather()`. The
+/// variants are ordered by severity.
+#[derive(Clone, Copy, Debug, Eq, Ord, PartialEq, PartialOrd)]
+pub enum StatusLevel {
+    /// The working copy is clean.
+    Clean,
+    /// The working-copy commit has changes.
+    Changes,
+    /// The working-copy commit has conflicts.
+    Conflicts,
+    /// The working-copy commit is divergent, or there are conflicted branches.
+    RepoProblems,
+}
+
+/// Status of the working-copy commit and the repo.
+#[derive(Debug)]
+pub struct RepoStatus {
+    pub wc_commit: Option<WorkingCopyStatus>,
+    pub conflicted_local_branches: Vec<String>,
+    pub conflicted_remote_branches: Vec<(String, String)>,
+}
+
+#[derive(Debug)]
+pub struct WorkingCopyStatus {
+    pub commit: Commit,
+    pub parent_tree: MergedTree,
+    pub tree: MergedTree,
+    pub conflicts: Vec<(RepoPathBuf, MergedTreeValue)>,
+    pub is_divergent: bool,
+}
+
+impl WorkingCopyStatus {
+    /// Whether the commit's tree differs from its parents' tree.
+    pub fn has_changes(&self) -> bool {
+        self.tree.id() != self.parent_tree.id()
+    }
+}
+
+impl RepoStatus {
+    /// Gathers the status of `maybe_wc_commit`, if there's one, and of the
+    /// branches in `repo`.
+    pub fn gather(
+        repo: &dyn Repo,
+        maybe_wc_commit: Option<Commit>,
+    ) -> Result<Self, TreeMergeError> {
+        let wc_commit = maybe_wc_commit
+            .map(|commit| -> Result<_, TreeMergeError> {
+                let parent_tree = repo.merged_parent_tree(&commit)?;
+                let tree = commit.tree()?;
+                let conflicts = tree.conflicts().collect_vec();
+                let is_divergent = repo
+                    .resolve_change_id(commit.change_id())
+                    .map_or(false, |commit_ids| commit_ids.len() > 1);
+                Ok(WorkingCopyStatus {
+                    commit,
+                    parent_tree,
+                    tree,
+                    conflicts,
+                    is_divergent,
+                })
+            })
+            .transpose()?;
+        let conflicted_local_branches = repo
+            .view()
+            .local_branches()
+            .filter(|(_, target)| target.has_conflict())
+            .map(|(branch_name, _)| branch_name.to_owned())
+            .collect_vec();
+        let conflicted_remote_branches = repo
+            .view()
+            .all_remote_branches()
+            .filter(|(_, remote_ref)| remote_ref.target.has_conflict())
+            .map(|((branch_name, remote_name), _)| (branch_name.to_owned(), remote_name.to_owned()))
+            .collect_vec();
+        Ok(RepoStatus {
+            wc_commit,
+            conflicted_local_branches,
+            conflicted_remote_branches,
+        })
+    }
+
+    /// The most severe problem, for scripts that only need a summary. Repo
+    /// problems take precedence over conflicts, which take precedence over
+    /// changes.
+    pub fn level(&self) -> StatusLevel {
+        let wc_divergent = self.wc_commit.as_ref().map_or(false, |wc| wc.is_divergent);
+        if wc_divergent
+            || !self.conflicted_local_branches.is_empty()
+            || !self.conflicted_remote_branches.is_empty()
+        {
+            return StatusLevel::RepoProblems;
+        }
+        match &self.wc_commit {
+            Some(wc) if !wc.conflicts.is_empty() => StatusLevel::Conflicts,
+            Some(wc) if wc.has_changes() => StatusLevel::Changes,
+            _ => StatusLevel::Clean,
+        }
+    }
+}
@@ -15,27 +15,34 @@
 #![allow(missing_docs)]
 
 use std::any::Any;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::fmt::{Debug, Formatter};
 use std::io::Read;
-use std::sync::{Arc, RwLock};
+use std::num::NonZeroUsize;
+use std::sync::{Arc, Mutex, RwLock};
 use std::time::SystemTime;
 
+use clru::CLruCache;
 use pollster::FutureExt;
 
 use crate::backend::{
-    self, Backend, BackendResult, ChangeId, CommitId, ConflictId, FileId, MergedTreeId, SigningFn,
-    SymlinkId, TreeId,
+    self, Backend, BackendError, BackendResult, ChangeId, CommitId, ConflictId, FileId,
+    MergedTreeId, ObjectKind, SigningFn, SymlinkId, TreeId, TreeValue,
 };
 use crate::commit::Commit;
 use crate::index::Index;
 use crate::merge::{Merge, MergedTreeValue};
+use crate::merge_drivers::MergeDrivers;
 use crate::merged_tree::MergedTree;
+use crate::object_id::{HexPrefix, ObjectId};
 use crate::repo_path::{RepoPath, RepoPathBuf};
 use crate::signing::Signer;
 use crate::tree::Tree;
 use crate::tree_builder::TreeBuilder;
 
+// Each entry is only a tree id and a flag, so many trees can be remembered.
+const TREE_CONFLICT_CACHE_CAPACITY: usize = 100_000;
+
 /// Wraps the low-level backend and makes it return more convenient types. Also
 /// adds caching.
 pub struct Store {
@@ -43,7 +50,15 @@ pub struct Store {
     signer: Signer,
     commit_cache: RwLock<HashMap<CommitId, Arc<backend::Commit>>>,
     tree_cache: RwLock<HashMap<(RepoPathBuf, TreeId), Arc<backend::Tree>>>,
+    /// Whether trees (recursively) have conflicts, for the most recently used
+    /// trees whose backend didn't record it.
+    tree_conflict_cache: Mutex<CLruCache<TreeId, bool>>,
     use_tree_conflict_format: bool,
+    strict_validation: bool,
+    merge_drivers: MergeDrivers,
+    /// Objects known to exist in the backend. Only populated in strict
+    /// validation mode.
+    existing_objects: RwLock<HashSet<(ObjectKind, Vec<u8>)>>,
 }
 
 impl Debug for Store {
@@ -59,13 +74,21 @@ impl Store {
         backend: Box<dyn Backend>,
         signer: Signer,
         use_tree_conflict_format: bool,
+        strict_validation: bool,
+        merge_drivers: MergeDrivers,
     ) -> Arc<Self> {
         Arc::new(Store {
             backend,
             signer,
             commit_cache: Default::default(),
             tree_cache: Default::default(),
+            tree_conflict_cache: Mutex::new(CLruCache::new(
+                NonZeroUsize::new(TREE_CONFLICT_CACHE_CAPACITY).unwrap(),
+            )),
             use_tree_conflict_format,
+            strict_validation,
+            merge_drivers,
+            existing_objects: Default::default(),
         })
     }
 
@@ -82,6 +105,17 @@ impl Store {
         self.use_tree_conflict_format
     }
 
+    /// Whether trees and commits are checked for references to objects that
+    /// don't exist before they're written.
+    pub fn strict_validation(&self) -> bool {
+        self.strict_validation
+    }
+
+    /// Drivers used to merge conflicting changes to files.
+    pub fn merge_drivers(&self) -> &MergeDrivers {
+        &self.merge_drivers
+    }
+
     pub fn commit_id_length(&self) -> usize {
         self.backend.commit_id_length()
     }
@@ -143,6 +177,15 @@ impl Store {
         sign_with: Option<&mut SigningFn>,
     ) -> BackendResult<Commit> {
         assert!(!commit.parents.is_empty());
+        if self.strict_validation {
+            for tree_id in commit.root_tree.to_merge().iter() {
+                self.check_exists(
+                    || format!("commit for change {}", commit.change_id.hex()),
+                    ObjectKind::Tree,
+                    tree_id.as_bytes(),
+                )?;
+            }
+        }
 
         let (commit_id, commit) = self.backend.write_commit(commit, sign_with)?;
         let data = Arc::new(commit);
@@ -167,6 +210,18 @@ impl Store {
         Ok(Tree::new(self.clone(), dir.to_owned(), id.clone(), data))
     }
 
+    /// Reads a commit without adding it to the cache. For passes over many
+    /// objects that read each of them once.
+    pub fn read_commit_uncached(&self, id: &CommitId) -> BackendResult<backend::Commit> {
+        self.backend.read_commit(id).block_on()
+    }
+
+    /// Reads a tree without adding it to the cache. For passes over many
+    /// objects that read each of them once.
+    pub fn read_tree_uncached(&self, dir: &RepoPath, id: &TreeId) -> BackendResult<backend::Tree> {
+        self.backend.read_tree(dir, id).block_on()
+    }
+
     async fn get_backend_tree(
         &self,
         dir: &RepoPath,
@@ -186,6 +241,73 @@ impl Store {
         Ok(data)
     }
 
+    /// Returns whether the tree or any tree reachable from it has a conflict
+    /// entry, if that's known without reading any trees.
+    pub fn known_tree_has_conflict(&self, dir: &RepoPath, id: &TreeId) -> Option<bool> {
+        if let Some(&has_conflict) = self.tree_conflict_cache.lock().unwrap().get(id) {
+            return Some(has_conflict);
+        }
+        let key = (dir.to_owned(), id.clone());
+        let read_locked_cache = self.tree_cache.read().unwrap();
+        read_locked_cache.get(&key)?.has_conflict_hint()
+    }
+
+    /// Returns whether the tree or any tree reachable from it has a conflict
+    /// entry.
+    ///
+    /// Backends like the local backend record this in each tree. For other
+    /// backends, the tree is walked the first time this is called for it, and
+    /// the result is remembered for each tree id visited.
+    pub fn tree_has_conflict(&self, dir: &RepoPath, id: &TreeId) -> BackendResult<bool> {
+        if let Some(has_conflict) = self.known_tree_has_conflict(dir, id) {
+            return Ok(has_conflict);
+        }
+        let data = self.get_backend_tree(dir, id).block_on()?;
+        let has_conflict = match data.has_conflict_hint() {
+            Some(has_conflict) => has_conflict,
+            None => self.subtrees_have_conflict(dir, &data, true)?.unwrap(),
+        };
+        self.tree_conflict_cache
+            .lock()
+            .unwrap()
+            .put(id.clone(), has_conflict);
+        Ok(has_conflict)
+    }
+
+    /// Determines whether the tree has conflicts from its entries. Subtrees are
+    /// walked if `walk` is true. Otherwise, `None` is returned if it depends on
+    /// a subtree that isn't known to have conflicts or not.
+    fn subtrees_have_conflict(
+        &self,
+        dir: &RepoPath,
+        tree: &backend::Tree,
+        walk: bool,
+    ) -> BackendResult<Option<bool>> {
+        let mut known = true;
+        for entry in tree.entries() {
+            let has_conflict = match entry.value() {
+                TreeValue::Conflict(_) => Some(true),
+                TreeValue::Tree(id) => {
+                    let subdir = dir.join(entry.name());
+                    if walk {
+                        Some(self.tree_has_conflict(&subdir, id)?)
+                    } else {
+                        self.known_tree_has_conflict(&subdir, id)
+                    }
+                }
+                TreeValue::File { .. } | TreeValue::Symlink(_) | TreeValue::GitSubmodule(_) => {
+                    Some(false)
+                }
+            };
+            match has_conflict {
+                Some(true) => return Ok(Some(true)),
+                Some(false) => {}
+                None => known = false,
+            }
+        }
+        Ok(known.then_some(false))
+    }
+
     pub fn get_root_tree(self: &Arc<Self>, id: &MergedTreeId) -> BackendResult<MergedTree> {
         match &id {
             MergedTreeId::Legacy(id) => {
@@ -202,9 +324,34 @@ impl Store {
     pub fn write_tree(
         self: &Arc<Self>,
         path: &RepoPath,
-        tree: backend::Tree,
+        mut tree: backend::Tree,
     ) -> BackendResult<Tree> {
+        debug_assert!(
+            tree.is_normalized(self.empty_tree_id()),
+            "Tree at {path:?} has an entry pointing at the empty tree"
+        );
+        if self.strict_validation {
+            for entry in tree.entries() {
+                let (kind, id) = match entry.value() {
+                    TreeValue::File { id, .. } => (ObjectKind::File, id.as_bytes()),
+                    TreeValue::Symlink(id) => (ObjectKind::Symlink, id.as_bytes()),
+                    TreeValue::Tree(id) => (ObjectKind::Tree, id.as_bytes()),
+                    TreeValue::Conflict(id) => (ObjectKind::Conflict, id.as_bytes()),
+                    // Submodule commits live in another repo.
+                    TreeValue::GitSubmodule(_) => continue,
+                };
+                self.check_exists(|| format!("tree at {path:?}"), kind, id)?;
+            }
+        }
+        if tree.has_conflict_hint().is_none() {
+            // The subtrees were usually just written or read, so this doesn't
+            // read any trees. Backends that record the flag compute it
+            // themselves if it's still unknown.
+            let has_conflict = self.subtrees_have_conflict(path, &tree, false)?;
+            tree.set_has_conflict_hint(has_conflict);
+        }
         let tree_id = self.backend.write_tree(path, &tree)?;
+        self.mark_existing(ObjectKind::Tree, &tree_id);
         let data = Arc::new(tree);
         {
             let mut write_locked_cache = self.tree_cache.write().unwrap();
@@ -227,7 +374,9 @@ impl Store {
     }
 
     pub fn write_file(&self, path: &RepoPath, contents: &mut dyn Read) -> BackendResult<FileId> {
-        self.backend.write_file(path, contents)
+        let id = self.backend.write_file(path, contents)?;
+        self.mark_existing(ObjectKind::File, &id);
+        Ok(id)
     }
 
     pub fn read_symlink(&self, path: &RepoPath, id: &SymlinkId) -> BackendResult<String> {
@@ -243,7 +392,9 @@ impl Store {
     }
 
     pub fn write_symlink(&self, path: &RepoPath, contents: &str) -> BackendResult<SymlinkId> {
-        self.backend.write_symlink(path, contents)
+        let id = self.backend.write_symlink(path, contents)?;
+        self.mark_existing(ObjectKind::Symlink, &id);
+        Ok(id)
     }
 
     pub fn read_conflict(
@@ -260,8 +411,11 @@ impl Store {
         path: &RepoPath,
         contents: &MergedTreeValue,
     ) -> BackendResult<ConflictId> {
-        self.backend
-            .write_conflict(path, &contents.clone().into_backend_conflict())
+        let id = self
+            .backend
+            .write_conflict(path, &contents.clone().into_backend_conflict())?;
+        self.mark_existing(ObjectKind::Conflict, &id);
+        Ok(id)
     }
 
     pub fn tree_builder(self: &Arc<Self>, base_tree_id: TreeId) -> TreeBuilder {
@@ -271,4 +425,61 @@ impl Store {
     pub fn gc(&self, index: &dyn Index, keep_newer: SystemTime) -> BackendResult<()> {
         self.backend.gc(index, keep_newer)
     }
+
+    pub fn keep_commits(&self, ids: &[CommitId]) -> BackendResult<()> {
+        self.backend.keep_commits(ids)
+    }
+
+    pub fn find_objects_by_prefix(
+        &self,
+        prefix: &HexPrefix,
+        kind: Option<ObjectKind>,
+    ) -> BackendResult<Vec<(ObjectKind, Vec<u8>)>> {
+        self.backend.find_objects_by_prefix(prefix, kind)
+    }
+
+    pub fn read_raw_object(&self, kind: ObjectKind, id: &[u8]) -> BackendResult<Vec<u8>> {
+        self.backend.read_raw_object(kind, id)
+    }
+
+    pub fn object_size(&self, kind: ObjectKind, id: &[u8]) -> BackendResult<u64> {
+        self.backend.object_size(kind, id)
+    }
+
+    /// Returns whether an object of the given kind and id exists.
+    pub fn contains(&self, kind: ObjectKind, id: &[u8]) -> BackendResult<bool> {
+        self.backend.contains(kind, id)
+    }
+
+    /// Returns an error naming `parent` if the referenced object doesn't exist
+    /// in the backend.
+    fn check_exists(
+        &self,
+        parent: impl FnOnce() -> String,
+        kind: ObjectKind,
+        id: &[u8],
+    ) -> BackendResult<()> {
+        let key = (kind, id.to_vec());
+        if self.existing_objects.read().unwrap().contains(&key) {
+            return Ok(());
+        }
+        if !self.backend.contains(kind, id)? {
+            return Err(BackendError::DanglingReference {
+                parent: parent(),
+                missing_type: kind.to_string(),
+                missing_hash: hex::encode(id),
+            });
+        }
+        self.existing_objects.write().unwrap().insert(key);
+        Ok(())
+    }
+
+    fn mark_existing(&self, kind: ObjectKind, id: &impl ObjectId) {
+        if self.strict_validation {
+            self.existing_objects
+                .write()
+                .unwrap()
+                .insert((kind, id.to_bytes()));
+        }
+    }
 }
@@ -0,0 +1,298 @@
+// Copyright 2024 The Jujutsu Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Date helpers.
+
+use chrono::{DateTime, Duration, FixedOffset, Local, NaiveDate, NaiveDateTime, TimeZone};
+use thiserror::Error;
+
+use crate::backend::{MillisSinceEpoch, Timestamp};
+
+/// Error occurred during date pattern parsing.
+#[derive(Debug, Error)]
+pub enum DatePatternParseError {
+    /// Unknown pattern kind is specified.
+    #[error(r#"Invalid date pattern kind "{0}", expected "after" or "before""#)]
+    InvalidKind(String),
+    /// The date couldn't be parsed.
+    #[error(
+        r#"Invalid date "{0}", expected e.g. "2024-01-31", "2024-01-31 13:00", or "2 days ago""#
+    )]
+    InvalidDate(String),
+}
+
+/// Pattern to be tested against a commit timestamp.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum DatePattern {
+    /// Matches timestamps at or after the given time.
+    AtOrAfter(MillisSinceEpoch),
+    /// Matches timestamps before the given time.
+    Before(MillisSinceEpoch),
+}
+
+impl DatePattern {
+    /// Parses the given date as pattern of the specified `kind`. Relative
+    /// dates are relative to the current time, and dates without a time zone
+    /// are in the local time zone.
+    pub fn from_str_kind(src: &str, kind: &str) -> Result<Self, DatePatternParseError> {
+        DatePattern::from_str_kind_at(src, kind, Local::now())
+    }
+
+    /// Like `from_str_kind()`, but relative to `now`. Dates without a time
+    /// zone are in the time zone of `now`, using the offset in effect at that
+    /// date (which differs from the current offset across daylight saving time
+    /// changes).
+    pub fn from_str_kind_at<Tz: TimeZone>(
+        src: &str,
+        kind: &str,
+        now: DateTime<Tz>,
+    ) -> Result<Self, DatePatternParseError> {
+        let make_pattern = match kind {
+            "after" => DatePattern::AtOrAfter,
+            "before" => DatePattern::Before,
+            _ => return Err(DatePatternParseError::InvalidKind(kind.to_owned())),
+        };
+        let datetime = parse_date(src, now)
+            .ok_or_else(|| DatePatternParseError::InvalidDate(src.to_owned()))?;
+        Ok(make_pattern(MillisSinceEpoch(datetime.timestamp_millis())))
+    }
+
+    /// Returns true if the timestamp matches this pattern.
+    pub fn matches(&self, timestamp: &Timestamp) -> bool {
+        match self {
+            DatePattern::AtOrAfter(bound) => timestamp.timestamp >= *bound,
+            DatePattern::Before(bound) => timestamp.timestamp < *bound,
+        }
+    }
+}
+
+/// Parses an absolute date like "2024-01-31", "2024-01-31 13:00", or an RFC
+/// 3339 timestamp, or a relative date like "2 days ago" or "now".
+fn parse_date<Tz: TimeZone>(src: &str, now: DateTime<Tz>) -> Option<DateTime<FixedOffset>> {
+    let src = src.trim();
+    if src == "now" {
+        return Some(now.fixed_offset());
+    }
+    if let Some(offset) = src.strip_suffix(" ago") {
+        let datetime = now.checked_sub_signed(parse_duration(offset)?)?;
+        return Some(datetime.fixed_offset());
+    }
+    if let Ok(datetime) = DateTime::parse_from_rfc3339(src) {
+        return Some(datetime);
+    }
+    let naive = [
+        "%Y-%m-%d %H:%M:%S",
+        "%Y-%m-%dT%H:%M:%S",
+        "%Y-%m-%d %H:%M",
+        "%Y-%m-%dT%H:%M",
+    ]
+    .iter()
+    .find_map(|format| NaiveDateTime::parse_from_str(src, format).ok())
+    .or_else(|| {
+        let date = NaiveDate::parse_from_str(src, "%Y-%m-%d").ok()?;
+        date.and_hms_opt(0, 0, 0)
+    })?;
+    // An ambiguous local time (when the clock is turned back) resolves to the
+    // earlier instant. A skipped local time (when the clock is turned forward)
+    // is invalid.
+    let datetime = now.timezone().from_local_datetime(&naive).earliest()?;
+    Some(datetime.fixed_offset())
+}
+
+/// Parses a duration like "3 days" or "1 hour".
+fn parse_duration(src: &str) -> Option<Duration> {
+    let (count, unit) = src.trim().split_once(' ')?;
+    let count: i64 = count.parse().ok()?;
+    let unit_seconds = match unit.trim().strip_suffix('s').unwrap_or(unit.trim()) {
+        "second" => 1,
+        "minute" => 60,
+        "hour" => 60 * 60,
+        "day" => 24 * 60 * 60,
+        "week" => 7 * 24 * 60 * 60,
+        _ => return None,
+    };
+    let seconds = count.checked_mul(unit_seconds)?;
+    // Duration::seconds() panics if the value doesn't fit in milliseconds
+    (seconds.checked_abs()? <= i64::MAX / 1000).then(|| Duration::seconds(seconds))
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::LocalResult;
+
+    use super::*;
+
+    fn now() -> DateTime<FixedOffset> {
+        DateTime::parse_from_rfc3339("2024-03-10T12:30:00+09:00").unwrap()
+    }
+
+    fn parse(src: &str) -> Option<String> {
+        parse_date(src, now()).map(|datetime| datetime.to_rfc3339())
+    }
+
+    #[test]
+    fn test_parse_absolute_date() {
+        assert_eq!(
+            parse("2024-01-31"),
+            Some("2024-01-31T00:00:00+09:00".to_owned())
+        );
+        assert_eq!(
+            parse("2024-01-31 13:05"),
+            Some("2024-01-31T13:05:00+09:00".to_owned())
+        );
+        assert_eq!(
+            parse("2024-01-31T13:05:06"),
+            Some("2024-01-31T13:05:06+09:00".to_owned())
+        );
+        assert_eq!(
+            parse("2024-01-31T13:05:06-05:00"),
+            Some("2024-01-31T13:05:06-05:00".to_owned())
+        );
+        assert_eq!(parse("2024-02-30"), None);
+        assert_eq!(parse("yesterday"), None);
+        assert_eq!(parse(""), None);
+    }
+
+    #[test]
+    fn test_parse_relative_date() {
+        assert_eq!(parse("now"), Some("2024-03-10T12:30:00+09:00".to_owned()));
+        assert_eq!(
+            parse("90 seconds ago"),
+            Some("2024-03-10T12:28:30+09:00".to_owned())
+        );
+        assert_eq!(
+            parse("1 hour ago"),
+            Some("2024-03-10T11:30:00+09:00".to_owned())
+        );
+        assert_eq!(
+            parse("2 weeks ago"),
+            Some("2024-02-25T12:30:00+09:00".to_owned())
+        );
+        assert_eq!(parse("2 fortnights ago"), None);
+        assert_eq!(parse("two days ago"), None);
+        assert_eq!(parse("2 days"), None);
+    }
+
+    /// Time zone which is UTC+1 in winter and UTC+2 in summer, switching at
+    /// 01:00 UTC on 2024-03-31 and 2024-10-27 like Central European Time.
+    #[derive(Clone, Copy, Debug)]
+    struct DstZone;
+
+    impl DstZone {
+        fn offset_at_utc(utc: &NaiveDateTime) -> FixedOffset {
+            let switch_at = |month, day| {
+                NaiveDate::from_ymd_opt(2024, month, day)
+                    .and_then(|date| date.and_hms_opt(1, 0, 0))
+                    .unwrap()
+            };
+            let hours = if (switch_at(3, 31)..switch_at(10, 27)).contains(utc) {
+                2
+            } else {
+                1
+            };
+            FixedOffset::east_opt(hours * 3600).unwrap()
+        }
+    }
+
+    impl TimeZone for DstZone {
+        type Offset = FixedOffset;
+
+        fn from_offset(_offset: &FixedOffset) -> Self {
+            DstZone
+        }
+
+        fn offset_from_local_date(&self, local: &NaiveDate) -> LocalResult<FixedOffset> {
+            self.offset_from_local_datetime(&local.and_hms_opt(0, 0, 0).unwrap())
+        }
+
+        fn offset_from_local_datetime(&self, local: &NaiveDateTime) -> LocalResult<FixedOffset> {
+            // The earlier instant (with the larger offset) comes first
+            let mut offsets = [2, 1]
+                .map(|hours| FixedOffset::east_opt(hours * 3600).unwrap())
+                .into_iter()
+                .filter(|offset| Self::offset_at_utc(&(*local - *offset)) == *offset);
+            match (offsets.next(), offsets.next()) {
+                (Some(first), Some(second)) => LocalResult::Ambiguous(first, second),
+                (Some(offset), None) => LocalResult::Single(offset),
+                (None, _) => LocalResult::None,
+            }
+        }
+
+        fn offset_from_utc_date(&self, utc: &NaiveDate) -> FixedOffset {
+            Self::offset_at_utc(&utc.and_hms_opt(0, 0, 0).unwrap())
+        }
+
+        fn offset_from_utc_datetime(&self, utc: &NaiveDateTime) -> FixedOffset {
+            Self::offset_at_utc(utc)
+        }
+    }
+
+    #[test]
+    fn test_parse_date_across_dst_change() {
+        let parse_at = |src: &str, now: &str| {
+            let now = DateTime::parse_from_rfc3339(now)
+                .unwrap()
+                .with_timezone(&DstZone);
+            parse_date(src, now).map(|datetime| datetime.to_rfc3339())
+        };
+        // Summer date parsed in winter, and vice versa
+        assert_eq!(
+            parse_at("2024-07-01", "2024-01-15T12:00:00+01:00"),
+            Some("2024-07-01T00:00:00+02:00".to_owned())
+        );
+        assert_eq!(
+            parse_at("2024-01-01", "2024-07-15T12:00:00+02:00"),
+            Some("2024-01-01T00:00:00+01:00".to_owned())
+        );
+        // The hour skipped when the clock is turned forward doesn't exist
+        assert_eq!(
+            parse_at("2024-03-31 02:30", "2024-07-15T12:00:00+02:00"),
+            None
+        );
+        // The hour repeated when the clock is turned back is the earlier one
+        assert_eq!(
+            parse_at("2024-10-27 02:30", "2024-07-15T12:00:00+02:00"),
+            Some("2024-10-27T02:30:00+02:00".to_owned())
+        );
+        // Relative dates are exact durations, in the offset of the result
+        assert_eq!(
+            parse_at("1 day ago", "2024-03-31T12:00:00+02:00"),
+            Some("2024-03-30T11:00:00+01:00".to_owned())
+        );
+    }
+
+    #[test]
+    fn test_date_pattern_boundaries() {
+        let pattern = |kind| DatePattern::from_str_kind_at("2024-01-31", kind, now()).unwrap();
+        let timestamp = |millis| Timestamp {
+            timestamp: MillisSinceEpoch(millis),
+            tz_offset: 0,
+        };
+        // 2024-01-31T00:00:00+09:00
+        let bound = 1_706_626_800_000;
+        assert!(pattern("after").matches(&timestamp(bound)));
+        assert!(!pattern("after").matches(&timestamp(bound - 1)));
+        assert!(!pattern("before").matches(&timestamp(bound)));
+        assert!(pattern("before").matches(&timestamp(bound - 1)));
+
+        assert!(matches!(
+            DatePattern::from_str_kind_at("2024-01-31", "on", now()),
+            Err(DatePatternParseError::InvalidKind(_))
+        ));
+        assert!(matches!(
+            DatePattern::from_str_kind_at("soon", "after", now()),
+            Err(DatePatternParseError::InvalidDate(_))
+        ));
+    }
+}
@@ -14,24 +14,378 @@
 
 #![allow(missing_docs)]
 
+use std::collections::{HashMap, HashSet};
 use std::sync::Arc;
 
+use chrono::{DateTime, FixedOffset, LocalResult, TimeZone as _};
 use itertools::Itertools as _;
+use thiserror::Error;
 
-use crate::backend::Timestamp;
-use crate::index::ReadonlyIndex;
-use crate::op_store::OperationMetadata;
+use crate::backend::{BackendError, ChangeId, CommitId, Timestamp};
+use crate::commit::Commit;
+use crate::index::{IndexWriteError, ReadonlyIndex};
+use crate::object_id::ObjectId as _;
+use crate::op_store::{OpStoreError, OperationId, OperationMetadata, RefTarget};
 use crate::operation::Operation;
-use crate::repo::{MutableRepo, ReadonlyRepo, Repo, RepoLoader, RepoLoaderError};
-use crate::settings::UserSettings;
+use crate::refs::diff_named_ref_targets;
+use crate::repo::{MutableRepo, ReadonlyRepo, Repo, RepoLoader, RepoLoaderError, RepoReadOnly};
+use crate::repo_path::RepoPathBuf;
+use crate::revset::{RevsetEvaluationError, RevsetExpression};
+use crate::settings::{ConfigResultExt as _, UserSettings};
+use crate::tree::TreeMergeError;
 use crate::view::View;
 use crate::{dag_walk, op_store};
 
+/// Error returned when the changes in a transaction are rejected or can't be
+/// validated.
+#[derive(Debug, Error)]
+pub enum TransactionValidationError {
+    /// The changes were rejected. Contains one message per violation.
+    #[error("{}", .0.join("\n"))]
+    Rejected(Vec<String>),
+    #[error(transparent)]
+    Backend(#[from] BackendError),
+    #[error(transparent)]
+    RevsetEvaluation(#[from] RevsetEvaluationError),
+    #[error(transparent)]
+    TreeMerge(#[from] TreeMergeError),
+}
+
+/// Error returned when a transaction can't be written.
+#[derive(Debug, Error)]
+pub enum TransactionCommitError {
+    #[error(transparent)]
+    ReadOnly(#[from] RepoReadOnly),
+    #[error(transparent)]
+    Validation(#[from] TransactionValidationError),
+    #[error("Failed to protect new commits from garbage collection")]
+    Backend(#[source] BackendError),
+    #[error("Failed to write the index")]
+    IndexWrite(#[source] IndexWriteError),
+}
+
+/// Returned by `Transaction::validate()` when the validators accepted the
+/// changes, to commit them without running the validators again.
+#[derive(Debug)]
+pub struct Validated(());
+
+/// Checks the changes in a transaction before it's written.
+///
+/// All registered validators are run, and the violations reported by each of
+/// them are combined into a single `TransactionValidationError::Rejected`.
+pub trait TransactionValidator: Send + Sync {
+    fn validate(&self, changes: &PendingChanges) -> Result<(), TransactionValidationError>;
+}
+
+/// Read-only view of the changes made in a transaction compared to its base
+/// repo.
+pub struct PendingChanges<'a> {
+    mut_repo: &'a MutableRepo,
+}
+
+impl<'a> PendingChanges<'a> {
+    pub fn base_repo(&self) -> &'a Arc<ReadonlyRepo> {
+        self.mut_repo.base_repo()
+    }
+
+    pub fn repo(&self) -> &'a MutableRepo {
+        self.mut_repo
+    }
+
+    /// Commits that became visible in the transaction, including new versions
+    /// of rewritten commits.
+    pub fn added_commits(&self) -> Result<Vec<Commit>, TransactionValidationError> {
+        self.visible_commits_diff(self.base_repo().as_ref(), self.mut_repo)
+    }
+
+    /// Commits that are no longer visible, such as rewritten or abandoned
+    /// commits.
+    pub fn removed_commits(&self) -> Result<Vec<Commit>, TransactionValidationError> {
+        self.visible_commits_diff(self.mut_repo, self.base_repo().as_ref())
+    }
+
+    /// Commits created, rewritten, and abandoned in the transaction.
+    ///
+    /// A removed commit is considered rewritten into each added commit with
+    /// the same change id, and abandoned if there is no such commit.
+    pub fn commit_changes(&self) -> Result<CommitChanges, TransactionValidationError> {
+        let added_commits = self.added_commits()?;
+        let removed_commits = self.removed_commits()?;
+        let mut added_by_change_id: HashMap<&ChangeId, Vec<&Commit>> = HashMap::new();
+        for commit in &added_commits {
+            added_by_change_id
+                .entry(commit.change_id())
+                .or_default()
+                .push(commit);
+        }
+        let removed_change_ids: HashSet<&ChangeId> = removed_commits
+            .iter()
+            .map(|commit| commit.change_id())
+            .collect();
+        let mut changes = CommitChanges::default();
+        for commit in &removed_commits {
+            match added_by_change_id.get(commit.change_id()) {
+                Some(new_commits) => changes.rewritten.extend(
+                    new_commits
+                        .iter()
+                        .map(|&new_commit| (commit.clone(), new_commit.clone())),
+                ),
+                None => changes.abandoned.push(commit.clone()),
+            }
+        }
+        changes.created = added_commits
+            .iter()
+            .filter(|commit| !removed_change_ids.contains(commit.change_id()))
+            .cloned()
+            .collect();
+        Ok(changes)
+    }
+
+    /// Local branches whose target changed, along with the old and new
+    /// targets.
+    pub fn changed_local_branches(
+        &self,
+    ) -> impl Iterator<Item = (&'a str, &'a RefTarget, &'a RefTarget)> {
+        diff_named_ref_targets(
+            self.base_repo().view().local_branches(),
+            self.mut_repo.view().local_branches(),
+        )
+        .map(|(name, (old_target, new_target))| (name, old_target, new_target))
+    }
+
+    /// Commits visible in `to` but not in `from`. The mutable index contains
+    /// the commits of both repos.
+    fn visible_commits_diff(
+        &self,
+        from: &dyn Repo,
+        to: &dyn Repo,
+    ) -> Result<Vec<Commit>, TransactionValidationError> {
+        let expression = RevsetExpression::commits(from.view().heads().iter().cloned().collect())
+            .range(&RevsetExpression::commits(
+                to.view().heads().iter().cloned().collect(),
+            ));
+        let commits = expression
+            .evaluate_programmatic(self.mut_repo)?
+            .iter()
+            .map(|id| self.mut_repo.store().get_commit(&id))
+            .try_collect()?;
+        Ok(commits)
+    }
+}
+
+/// Commits changed by a transaction, as computed by
+/// `PendingChanges::commit_changes()`.
+#[derive(Clone, Debug, Default)]
+pub struct CommitChanges {
+    /// Commits with a new change id.
+    pub created: Vec<Commit>,
+    /// Old and new versions of rewritten commits.
+    pub rewritten: Vec<(Commit, Commit)>,
+    /// Commits removed without a new version.
+    pub abandoned: Vec<Commit>,
+}
+
+/// Rejects transactions that rewrite immutable commits, i.e. the ancestors of
+/// `immutable_heads` and the root commit. Abandoned commits aren't checked,
+/// since importing refs abandons the commits that were dropped by the remote.
+#[derive(Clone, Debug)]
+pub struct ImmutableCommitsValidator {
+    immutable_heads: Vec<CommitId>,
+}
+
+impl ImmutableCommitsValidator {
+    pub fn new(immutable_heads: Vec<CommitId>) -> Self {
+        ImmutableCommitsValidator { immutable_heads }
+    }
+}
+
+impl TransactionValidator for ImmutableCommitsValidator {
+    fn validate(&self, changes: &PendingChanges) -> Result<(), TransactionValidationError> {
+        let rewritten_ids = changes
+            .commit_changes()?
+            .rewritten
+            .iter()
+            .map(|(old_commit, _)| old_commit.id().clone())
+            .unique()
+            .collect_vec();
+        if rewritten_ids.is_empty() {
+            return Ok(());
+        }
+        let immutable_expression = RevsetExpression::commits(self.immutable_heads.clone())
+            .ancestors()
+            .union(&RevsetExpression::root());
+        let violations = RevsetExpression::commits(rewritten_ids)
+            .intersection(&immutable_expression)
+            .evaluate_programmatic(changes.repo())?
+            .iter()
+            .map(|id| format!("Commit {} is immutable", short_commit_hex(&id)))
+            .collect_vec();
+        reject_violations(violations)
+    }
+}
+
+/// Rejects transactions that add commits changing files under any of the
+/// `prefixes`, as configured by `validation.forbidden-paths`.
+#[derive(Clone, Debug)]
+pub struct ForbiddenPathsValidator {
+    prefixes: Vec<RepoPathBuf>,
+}
+
+impl ForbiddenPathsValidator {
+    pub fn new(prefixes: Vec<RepoPathBuf>) -> Self {
+        ForbiddenPathsValidator { prefixes }
+    }
+}
+
+impl TransactionValidator for ForbiddenPathsValidator {
+    fn validate(&self, changes: &PendingChanges) -> Result<(), TransactionValidationError> {
+        let mut violations = vec![];
+        for commit in changes.added_commits()? {
+            let summary = changes.repo().diff_summary(&commit)?;
+            let forbidden_path = summary
+                .paths()
+                .find(|path| self.prefixes.iter().any(|prefix| path.starts_with(prefix)));
+            if let Some(path) = forbidden_path {
+                violations.push(format!(
+                    "Commit {} changes the forbidden path {}",
+                    short_commit_hex(commit.id()),
+                    path.as_internal_file_string()
+                ));
+            }
+        }
+        reject_violations(violations)
+    }
+}
+
+/// Rejects transactions that add commits without a description, as
+/// configured by `validation.require-description`. Working-copy commits are
+/// exempt, since they're usually described later.
+#[derive(Clone, Debug, Default)]
+pub struct RequireDescriptionValidator;
+
+impl TransactionValidator for RequireDescriptionValidator {
+    fn validate(&self, changes: &PendingChanges) -> Result<(), TransactionValidationError> {
+        let wc_commit_ids: HashSet<&CommitId> =
+            changes.repo().view().wc_commit_ids().values().collect();
+        let violations = changes
+            .added_commits()?
+            .iter()
+            .filter(|commit| {
+                commit.description().trim().is_empty() && !wc_commit_ids.contains(commit.id())
+            })
+            .map(|commit| {
+                format!(
+                    "Commit {} has no description",
+                    short_commit_hex(commit.id())
+                )
+            })
+            .collect_vec();
+        reject_violations(violations)
+    }
+}
+
+/// Validators configured by the `validation.*` settings.
+pub fn builtin_validators(
+    settings: &UserSettings,
+) -> Result<Vec<Arc<dyn TransactionValidator>>, config::ConfigError> {
+    let config = settings.config();
+    let mut validators: Vec<Arc<dyn TransactionValidator>> = vec![];
+    let forbidden_paths: Vec<String> = config
+        .get("validation.forbidden-paths")
+        .optional()?
+        .unwrap_or_default();
+    if !forbidden_paths.is_empty() {
+        let prefixes = forbidden_paths
+            .iter()
+            .map(|path| {
+                RepoPathBuf::from_relative_path(path).ok_or_else(|| {
+                    config::ConfigError::Message(format!(
+                        "validation.forbidden-paths: invalid path {path:?}"
+                    ))
+                })
+            })
+            .try_collect()?;
+        validators.push(Arc::new(ForbiddenPathsValidator::new(prefixes)));
+    }
+    if config
+        .get_bool("validation.require-description")
+        .optional()?
+        .unwrap_or(false)
+    {
+        validators.push(Arc::new(RequireDescriptionValidator));
+    }
+    Ok(validators)
+}
+
+fn short_commit_hex(id: &CommitId) -> String {
+    id.hex()[..12].to_owned()
+}
+
+fn reject_violations(violations: Vec<String>) -> Result<(), TransactionValidationError> {
+    if violations.is_empty() {
+        Ok(())
+    } else {
+        Err(TransactionValidationError::Rejected(violations))
+    }
+}
+
+/// The description of an operation: a message for humans, plus key-value tags
+/// for tools to filter the operation log by, such as `triggered-by=ci`.
+///
+/// Plain strings convert into descriptions without tags, so they can be passed
+/// wherever a description is expected.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct OperationDescription {
+    pub description: String,
+    pub tags: HashMap<String, String>,
+}
+
+impl OperationDescription {
+    pub fn new(description: impl Into<String>) -> Self {
+        OperationDescription {
+            description: description.into(),
+            tags: HashMap::new(),
+        }
+    }
+
+    /// Adds a tag, replacing any previous value for the same key.
+    pub fn with_tag(mut self, key: impl Into<String>, value: impl Into<String>) -> Self {
+        self.tags.insert(key.into(), value.into());
+        self
+    }
+}
+
+impl From<String> for OperationDescription {
+    fn from(description: String) -> Self {
+        OperationDescription::new(description)
+    }
+}
+
+impl From<&String> for OperationDescription {
+    fn from(description: &String) -> Self {
+        OperationDescription::new(description.clone())
+    }
+}
+
+impl From<&str> for OperationDescription {
+    fn from(description: &str) -> Self {
+        OperationDescription::new(description)
+    }
+}
+
+/// A set of changes to the repo that will be recorded as a single operation.
+///
+/// Library functions that change the repo, such as `git::fetch()`,
+/// `rewrite::rebase_commit()`, or `MutableRepo::set_local_branch_target()`,
+/// take the transaction's `MutableRepo`, so any number of them can be composed
+/// into one operation. Nothing is locked until the transaction is committed.
 pub struct Transaction {
     mut_repo: MutableRepo,
     parent_ops: Vec<Operation>,
+    replaced_op_ids: Vec<OperationId>,
     op_metadata: OperationMetadata,
     end_time: Option<Timestamp>,
+    validators: Vec<Arc<dyn TransactionValidator>>,
 }
 
 impl Transaction {
@@ -42,8 +396,10 @@ impl Transaction {
         Transaction {
             mut_repo,
             parent_ops,
+            replaced_op_ids: vec![],
             op_metadata,
             end_time,
+            validators: vec![],
         }
     }
 
@@ -80,15 +436,97 @@ impl Transaction {
         Ok(())
     }
 
+    /// Records the transaction in place of its base operation instead of on
+    /// top of it. The new operation gets the base operation's parents, and
+    /// replaces it in the operation heads when published. Its view still
+    /// includes the base operation's changes.
+    ///
+    /// The replaced operation is recorded in the new operation, which keeps it
+    /// reachable so it can still be restored. This is used to coalesce
+    /// consecutive operations, such as working-copy snapshots.
+    ///
+    /// Panics if the transaction already has more than one parent.
+    pub fn replace_base_operation(&mut self) -> Result<(), OpStoreError> {
+        assert_eq!(self.parent_ops.len(), 1, "can't replace a merge operation");
+        let base_op = self.parent_ops.pop().unwrap();
+        self.parent_ops = base_op.parents().try_collect()?;
+        self.replaced_op_ids.push(base_op.id().clone());
+        Ok(())
+    }
+
+    /// Registers a validator to run before the transaction is written.
+    pub fn add_validator(&mut self, validator: Arc<dyn TransactionValidator>) {
+        self.validators.push(validator);
+    }
+
+    /// Read-only view of the changes made so far, e.g. to report what the
+    /// transaction would do without committing it.
+    pub fn pending_changes(&self) -> PendingChanges<'_> {
+        PendingChanges {
+            mut_repo: &self.mut_repo,
+        }
+    }
+
+    /// Runs all registered validators against the pending changes.
+    pub fn validate(&self) -> Result<Validated, TransactionValidationError> {
+        let changes = self.pending_changes();
+        let mut violations = vec![];
+        for validator in &self.validators {
+            match validator.validate(&changes) {
+                Ok(()) => {}
+                Err(TransactionValidationError::Rejected(messages)) => violations.extend(messages),
+                Err(err) => return Err(err),
+            }
+        }
+        reject_violations(violations)?;
+        Ok(Validated(()))
+    }
+
     /// Writes the transaction to the operation store and publishes it.
-    pub fn commit(self, description: impl Into<String>) -> Arc<ReadonlyRepo> {
-        self.write(description).publish()
+    ///
+    /// The description may be a plain string or an `OperationDescription`
+    /// with tags. Its tags are added to any set by `set_tag()`.
+    ///
+    /// Fails without writing anything if the repo was loaded in read-only mode
+    /// or if a validator rejects the transaction.
+    pub fn commit(
+        self,
+        description: impl Into<OperationDescription>,
+    ) -> Result<Arc<ReadonlyRepo>, TransactionCommitError> {
+        Ok(self.write(description)?.publish())
+    }
+
+    /// Like `commit()`, but doesn't run the validators again after
+    /// `validate()` accepted the changes. Changes made in between, such as the
+    /// Git refs recorded when exporting to Git, aren't validated.
+    pub fn commit_validated(
+        self,
+        description: impl Into<OperationDescription>,
+        _validated: Validated,
+    ) -> Result<Arc<ReadonlyRepo>, TransactionCommitError> {
+        self.mut_repo.base_repo().check_writable()?;
+        Ok(self.write_unchecked(description)?.publish())
     }
 
     /// Writes the transaction to the operation store, but does not publish it.
     /// That means that a repo can be loaded at the operation, but the
     /// operation will not be seen when loading the repo at head.
-    pub fn write(mut self, description: impl Into<String>) -> UnpublishedOperation {
+    ///
+    /// Fails without writing anything if the repo was loaded in read-only mode
+    /// or if a validator rejects the transaction.
+    pub fn write(
+        self,
+        description: impl Into<OperationDescription>,
+    ) -> Result<UnpublishedOperation, TransactionCommitError> {
+        self.mut_repo.base_repo().check_writable()?;
+        self.validate()?;
+        self.write_unchecked(description)
+    }
+
+    fn write_unchecked(
+        mut self,
+        description: impl Into<OperationDescription>,
+    ) -> Result<UnpublishedOperation, TransactionCommitError> {
         let mut_repo = self.mut_repo;
         // TODO: Should we instead just do the rebasing here if necessary?
         assert!(
@@ -98,14 +536,33 @@ impl Transaction {
         let base_repo = mut_repo.base_repo().clone();
         let (mut_index, view) = mut_repo.consume();
 
+        // Protect the newly referenced commits from external GC before any
+        // operation refers to them.
+        let base_commit_ids: HashSet<&CommitId> =
+            base_repo.view().all_referenced_commit_ids().collect();
+        let new_commit_ids = view
+            .all_referenced_commit_ids()
+            .filter(|id| !base_commit_ids.contains(id))
+            .unique()
+            .cloned()
+            .collect_vec();
+        base_repo
+            .store()
+            .keep_commits(&new_commit_ids)
+            .map_err(TransactionCommitError::Backend)?;
+
         let view_id = base_repo.op_store().write_view(view.store_view()).unwrap();
-        self.op_metadata.description = description.into();
+        let OperationDescription { description, tags } = description.into();
+        self.op_metadata.description = description;
+        self.op_metadata.tags.extend(tags);
         self.op_metadata.end_time = self.end_time.unwrap_or_else(Timestamp::now);
+        clamp_op_times(&mut self.op_metadata, &self.parent_ops);
         let parents = self.parent_ops.iter().map(|op| op.id().clone()).collect();
         let store_operation = op_store::Operation {
             view_id,
             parents,
             metadata: self.op_metadata,
+            replaced: self.replaced_op_ids,
         };
         let new_op_id = base_repo
             .op_store()
@@ -115,12 +572,64 @@ impl Transaction {
 
         let index = base_repo
             .index_store()
-            .write_index(mut_index, operation.id())
-            .unwrap();
-        UnpublishedOperation::new(base_repo.loader(), operation, view, index)
+            .write_index(mut_index, operation.id(), base_repo.store())
+            .map_err(TransactionCommitError::IndexWrite)?;
+        Ok(UnpublishedOperation::new(
+            base_repo.loader(),
+            operation,
+            view,
+            index,
+        ))
+    }
+}
+
+/// Tag recording the end time of an operation as read from the clock, if its
+/// `end_time` was moved forward to the end time of a parent operation.
+pub const WALL_CLOCK_TIME_TAG: &str = "wall-clock-time";
+
+/// Moves the start and end times of an operation forward to the latest end
+/// time of its parents if the clock says they're earlier, so the times never
+/// go backwards along the operation graph. The end time from the clock is
+/// recorded in the `WALL_CLOCK_TIME_TAG` tag.
+fn clamp_op_times(op_metadata: &mut OperationMetadata, parent_ops: &[Operation]) {
+    let Some(min_time) = parent_ops
+        .iter()
+        .map(|op| &op.store_operation().metadata.end_time.timestamp)
+        .max()
+    else {
+        return;
+    };
+    if op_metadata.start_time.timestamp < *min_time {
+        op_metadata.start_time.timestamp = min_time.clone();
+    }
+    if op_metadata.end_time.timestamp < *min_time {
+        op_metadata.tags.insert(
+            WALL_CLOCK_TIME_TAG.to_string(),
+            format_timestamp(&op_metadata.end_time),
+        );
+        op_metadata.end_time.timestamp = min_time.clone();
     }
 }
 
+fn format_timestamp(timestamp: &Timestamp) -> String {
+    let tz = FixedOffset::east_opt(timestamp.tz_offset * 60)
+        .unwrap_or_else(|| FixedOffset::east_opt(0).unwrap());
+    match tz.timestamp_millis_opt(timestamp.timestamp.0) {
+        LocalResult::Single(datetime) | LocalResult::Ambiguous(datetime, _) => {
+            datetime.to_rfc3339()
+        }
+        LocalResult::None => timestamp.timestamp.0.to_string(),
+    }
+}
+
+/// Returns the end time of the operation as read from the clock, if it was
+/// earlier than the end time of a parent operation.
+pub fn wall_clock_end_time(op_metadata: &OperationMetadata) -> Option<Timestamp> {
+    let value = op_metadata.tags.get(WALL_CLOCK_TIME_TAG)?;
+    let datetime = DateTime::parse_from_rfc3339(value).ok()?;
+    Some(Timestamp::from_datetime(datetime))
+}
+
 pub fn create_op_metadata(user_settings: &UserSettings, description: String) -> OperationMetadata {
     let start_time = user_settings
         .operation_timestamp()
@@ -176,10 +685,16 @@ impl UnpublishedOperation {
     pub fn publish(mut self) -> Arc<ReadonlyRepo> {
         let data = self.data.take().unwrap();
         {
+            let old_ids = itertools::chain(
+                data.operation.parent_ids(),
+                &data.operation.store_operation().replaced,
+            )
+            .cloned()
+            .collect_vec();
             let _lock = self.repo_loader.op_heads_store().lock();
             self.repo_loader
                 .op_heads_store()
-                .update_op_heads(data.operation.parent_ids(), data.operation.id());
+                .update_op_heads(&old_ids, data.operation.id());
         }
         let repo = self
             .repo_loader
@@ -23,16 +23,20 @@ use itertools::Itertools;
 use thiserror::Error;
 use tracing::instrument;
 
+use crate::backend;
 use crate::backend::{
-    BackendError, ConflictId, FileId, TreeEntriesNonRecursiveIterator, TreeEntry, TreeId, TreeValue,
+    BackendError, BackendResult, ConflictId, FileId, TreeEntriesNonRecursiveIterator, TreeEntry,
+    TreeId, TreeValue,
 };
-use crate::files::MergeResult;
-use crate::matchers::{EverythingMatcher, Matcher};
+use crate::cancel::Cancelled;
+use crate::gitattributes::GitAttributes;
+use crate::matchers::{EverythingMatcher, Matcher, Visit};
 use crate::merge::{trivial_merge, Merge, MergedTreeValue};
+use crate::merge_drivers::MergeDriver;
+use crate::merged_tree::MergedTree;
 use crate::object_id::ObjectId;
 use crate::repo_path::{RepoPath, RepoPathBuf, RepoPathComponent, RepoPathComponentsIter};
 use crate::store::Store;
-use crate::{backend, files};
 
 #[derive(Debug, Error)]
 pub enum TreeMergeError {
@@ -43,6 +47,8 @@ pub enum TreeMergeError {
     },
     #[error("Backend error")]
     BackendError(#[from] BackendError),
+    #[error(transparent)]
+    Cancelled(#[from] Cancelled),
 }
 
 #[derive(Clone)]
@@ -88,11 +94,13 @@ impl Tree {
     }
 
     pub fn null(store: Arc<Store>, dir: RepoPathBuf) -> Self {
+        let mut data = backend::Tree::default();
+        data.set_has_conflict_hint(Some(false));
         Tree {
             store,
             dir,
             id: TreeId::new(vec![]),
-            data: Arc::new(backend::Tree::default()),
+            data: Arc::new(data),
         }
     }
 
@@ -117,14 +125,28 @@ impl Tree {
     }
 
     pub fn entries(&self) -> TreeEntriesIterator<'static> {
-        TreeEntriesIterator::new(self.clone(), &EverythingMatcher)
+        TreeEntriesIterator::new(self.clone(), &EverythingMatcher, TreeEntriesMode::FilesOnly)
     }
 
     pub fn entries_matching<'matcher>(
         &self,
         matcher: &'matcher dyn Matcher,
     ) -> TreeEntriesIterator<'matcher> {
-        TreeEntriesIterator::new(self.clone(), matcher)
+        TreeEntriesIterator::new(self.clone(), matcher, TreeEntriesMode::FilesOnly)
+    }
+
+    /// Like `entries()`, but also yields each directory (as a
+    /// `TreeValue::Tree`) before its contents.
+    pub fn entries_recursive_with_dirs(&self) -> TreeEntriesIterator<'static> {
+        TreeEntriesIterator::new(self.clone(), &EverythingMatcher, TreeEntriesMode::WithDirs)
+    }
+
+    pub fn entries_matching_with_mode<'matcher>(
+        &self,
+        matcher: &'matcher dyn Matcher,
+        mode: TreeEntriesMode,
+    ) -> TreeEntriesIterator<'matcher> {
+        TreeEntriesIterator::new(self.clone(), matcher, mode)
     }
 
     pub fn entry(&self, basename: &RepoPathComponent) -> Option<TreeEntry> {
@@ -170,8 +192,33 @@ impl Tree {
         }
     }
 
+    /// Returns an equivalent tree where no tree (recursively) has an entry
+    /// pointing at the empty tree. Such entries can only exist in trees that
+    /// were not written by jj, e.g. trees imported from Git.
+    pub fn normalize(&self) -> BackendResult<Tree> {
+        let mut new_data = self.data.as_ref().clone();
+        for entry in self.entries_non_recursive() {
+            if let TreeValue::Tree(id) = entry.value() {
+                let subdir = self.dir.join(entry.name());
+                let sub_tree = self.store.get_tree(&subdir, id)?.normalize()?;
+                new_data.set(
+                    entry.name().to_owned(),
+                    TreeValue::Tree(sub_tree.id().clone()),
+                );
+            }
+        }
+        new_data.normalize(self.store.empty_tree_id());
+        if new_data == *self.data {
+            return Ok(self.clone());
+        }
+        self.store.write_tree(&self.dir, new_data)
+    }
+
     pub fn conflicts_matching(&self, matcher: &dyn Matcher) -> Vec<(RepoPathBuf, ConflictId)> {
         let mut conflicts = vec![];
+        if self.maybe_has_conflict() == Some(false) {
+            return conflicts;
+        }
         for (name, value) in self.entries_matching(matcher) {
             if let TreeValue::Conflict(id) = value {
                 conflicts.push((name.clone(), id.clone()));
@@ -185,14 +232,35 @@ impl Tree {
         self.conflicts_matching(&EverythingMatcher)
     }
 
+    /// Whether this tree or any of its subtrees has a conflict, if that's
+    /// known without reading any trees. It's known for trees written by the
+    /// local backend, and for trees `has_conflict()` was called on before.
+    pub fn maybe_has_conflict(&self) -> Option<bool> {
+        self.data
+            .has_conflict_hint()
+            .or_else(|| self.store.known_tree_has_conflict(&self.dir, &self.id))
+    }
+
     pub fn has_conflict(&self) -> bool {
-        !self.conflicts().is_empty()
+        self.maybe_has_conflict()
+            .unwrap_or_else(|| self.store.tree_has_conflict(&self.dir, &self.id).unwrap())
     }
 }
 
+/// Which entries a `TreeEntriesIterator` yields.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum TreeEntriesMode {
+    /// Only yield non-tree entries. Directories are walked but not yielded.
+    FilesOnly,
+    /// Also yield the directories the matcher visits, each one before its
+    /// contents (pre-order).
+    WithDirs,
+}
+
 pub struct TreeEntriesIterator<'matcher> {
     stack: Vec<TreeEntriesDirItem>,
     matcher: &'matcher dyn Matcher,
+    mode: TreeEntriesMode,
 }
 
 struct TreeEntriesDirItem {
@@ -200,10 +268,16 @@ struct TreeEntriesDirItem {
     entries: Vec<(RepoPathBuf, TreeValue)>,
 }
 
-impl From<Tree> for TreeEntriesDirItem {
-    fn from(tree: Tree) -> Self {
+impl TreeEntriesDirItem {
+    /// Lists the entries of `tree` that `visit`, the matcher's `Visit` of the
+    /// tree's directory, may include.
+    fn new(tree: Tree, visit: &Visit) -> Self {
         let mut entries = tree
             .entries_non_recursive()
+            .filter(|entry| {
+                let is_dir = matches!(entry.value(), TreeValue::Tree(_));
+                visit.may_include(entry.name(), is_dir)
+            })
             .map(|entry| (tree.dir().join(entry.name()), entry.value().clone()))
             .collect_vec();
         entries.reverse();
@@ -212,11 +286,12 @@ impl From<Tree> for TreeEntriesDirItem {
 }
 
 impl<'matcher> TreeEntriesIterator<'matcher> {
-    fn new(tree: Tree, matcher: &'matcher dyn Matcher) -> Self {
-        // TODO: Restrict walk according to Matcher::visit()
+    fn new(tree: Tree, matcher: &'matcher dyn Matcher, mode: TreeEntriesMode) -> Self {
+        let visit = matcher.visit(tree.dir());
         Self {
-            stack: vec![TreeEntriesDirItem::from(tree)],
+            stack: vec![TreeEntriesDirItem::new(tree, &visit)],
             matcher,
+            mode,
         }
     }
 }
@@ -229,12 +304,15 @@ impl Iterator for TreeEntriesIterator<'_> {
             if let Some((path, value)) = top.entries.pop() {
                 match value {
                     TreeValue::Tree(id) => {
-                        // TODO: Handle the other cases (specific files and trees)
-                        if self.matcher.visit(&path).is_nothing() {
+                        let visit = self.matcher.visit(&path);
+                        if visit.is_nothing() {
                             continue;
                         }
                         let subtree = top.tree.known_sub_tree(&path, &id);
-                        self.stack.push(TreeEntriesDirItem::from(subtree));
+                        self.stack.push(TreeEntriesDirItem::new(subtree, &visit));
+                        if self.mode == TreeEntriesMode::WithDirs {
+                            return Some((path, TreeValue::Tree(id)));
+                        }
                     }
                     value => {
                         if self.matcher.matches(&path) {
@@ -290,6 +368,16 @@ pub fn merge_trees(
     side1_tree: &Tree,
     base_tree: &Tree,
     side2_tree: &Tree,
+) -> Result<Tree, TreeMergeError> {
+    let attributes = GitAttributes::new(MergedTree::legacy(side1_tree.clone()));
+    merge_trees_with_attributes(side1_tree, base_tree, side2_tree, &attributes)
+}
+
+fn merge_trees_with_attributes(
+    side1_tree: &Tree,
+    base_tree: &Tree,
+    side2_tree: &Tree,
+    attributes: &GitAttributes,
 ) -> Result<Tree, TreeMergeError> {
     let store = base_tree.store();
     let dir = base_tree.dir();
@@ -313,11 +401,19 @@ pub fn merge_trees(
             // value
         } else {
             // The two sides changed in different ways
-            let new_value =
-                merge_tree_value(store, dir, basename, maybe_base, maybe_side1, maybe_side2)?;
+            let new_value = merge_tree_value(
+                store,
+                dir,
+                basename,
+                maybe_base,
+                maybe_side1,
+                maybe_side2,
+                attributes,
+            )?;
             new_tree.set_or_remove(basename, new_value);
         }
     }
+    new_tree.normalize(store.empty_tree_id());
     Ok(store.write_tree(dir, new_tree)?)
 }
 
@@ -341,6 +437,7 @@ fn merge_tree_value(
     maybe_base: Option<&TreeValue>,
     maybe_side1: Option<&TreeValue>,
     maybe_side2: Option<&TreeValue>,
+    attributes: &GitAttributes,
 ) -> Result<Option<TreeValue>, TreeMergeError> {
     // Resolve non-trivial conflicts:
     //   * resolve tree conflicts by recursing
@@ -354,11 +451,17 @@ fn merge_tree_value(
     let side2_tree_id = maybe_tree_id(maybe_side2, empty_tree_id);
     Ok(match (base_tree_id, side1_tree_id, side2_tree_id) {
         (Some(base_id), Some(side1_id), Some(side2_id)) => {
+            // If two of the trees are the same, the merge can be resolved without
+            // reading any of them.
+            if let Some(&merged_id) = trivial_merge(&[base_id], &[side1_id, side2_id]) {
+                return Ok((merged_id != empty_tree_id).then(|| TreeValue::Tree(merged_id.clone())));
+            }
             let subdir = dir.join(basename);
             let base_tree = store.get_tree(&subdir, base_id)?;
             let side1_tree = store.get_tree(&subdir, side1_id)?;
             let side2_tree = store.get_tree(&subdir, side2_id)?;
-            let merged_tree = merge_trees(&side1_tree, &base_tree, &side2_tree)?;
+            let merged_tree =
+                merge_trees_with_attributes(&side1_tree, &base_tree, &side2_tree, attributes)?;
             if merged_tree.id() == empty_tree_id {
                 None
             } else {
@@ -383,7 +486,7 @@ fn merge_tree_value(
                 Ok(value) => value,
                 Err(conflict) => {
                     if let Some(tree_value) =
-                        try_resolve_file_conflict(store, &filename, &conflict)?
+                        try_resolve_file_conflict(store, &filename, &conflict, attributes)?
                     {
                         Some(tree_value)
                     } else {
@@ -399,11 +502,14 @@ fn merge_tree_value(
 /// Resolves file-level conflict by merging content hunks.
 ///
 /// The input `conflict` is supposed to be simplified. It shouldn't contain
-/// non-file values that cancel each other.
+/// non-file values that cancel each other. The contents are merged by the
+/// merge driver configured in the store or selected by `attributes`. Files
+/// which `attributes` declare as binary are never merged by content.
 pub fn try_resolve_file_conflict(
     store: &Store,
     filename: &RepoPath,
     conflict: &MergedTreeValue,
+    attributes: &GitAttributes,
 ) -> Result<Option<TreeValue>, TreeMergeError> {
     // If there are any non-file or any missing parts in the conflict, we can't
     // merge it. We check early so we don't waste time reading file contents if
@@ -433,6 +539,12 @@ pub fn try_resolve_file_conflict(
             executable,
         }));
     }
+    let driver = store
+        .merge_drivers()
+        .driver_for(filename, &attributes.attributes_for(filename)?);
+    if driver == MergeDriver::Binary {
+        return Ok(None);
+    }
 
     // While the input conflict should be simplified by caller, it might contain
     // terms which only differ in executable bits. Simplify the conflict further
@@ -454,13 +566,11 @@ pub fn try_resolve_file_conflict(
                 })?;
             Ok(content)
         })?;
-    let slices = contents.map(|content| content.as_slice());
-    let merge_result = files::merge(&slices);
-    match merge_result {
-        MergeResult::Resolved(merged_content) => {
-            let id = store.write_file(filename, &mut merged_content.0.as_slice())?;
+    match driver.merge(&contents) {
+        Some(merged_content) => {
+            let id = store.write_file(filename, &mut merged_content.as_slice())?;
             Ok(Some(TreeValue::File { id, executable }))
         }
-        MergeResult::Conflict(_) => Ok(None),
+        None => Ok(None),
     }
 }
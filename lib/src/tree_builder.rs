@@ -14,15 +14,25 @@
 
 #![allow(missing_docs)]
 
-use std::collections::BTreeMap;
+use std::collections::{BTreeMap, HashMap};
 use std::sync::Arc;
 
+use thiserror::Error;
+
 use crate::backend;
-use crate::backend::{TreeId, TreeValue};
+use crate::backend::{BackendError, BackendResult, TreeId, TreeValue};
 use crate::repo_path::{RepoPath, RepoPathBuf};
 use crate::store::Store;
 use crate::tree::Tree;
 
+/// Error for a tree entry whose path contains a reserved component, such as
+/// `.jj` or `.git`. It's wrapped in `BackendError::Other`.
+#[derive(Debug, Error)]
+#[error(r#"Path "{}" contains a reserved component"#, path.as_internal_file_string())]
+pub struct ReservedPathError {
+    pub path: RepoPathBuf,
+}
+
 #[derive(Debug)]
 enum Override {
     Tombstone,
@@ -69,19 +79,50 @@ impl TreeBuilder {
         }
     }
 
-    pub fn write_tree(self) -> TreeId {
+    /// Writes the modified trees. Fails if a path with a reserved component is
+    /// added. Such paths can still be removed, and trees that already contain
+    /// them (e.g. imported from Git) can still be merged.
+    pub fn write_tree(self) -> BackendResult<TreeId> {
         if self.overrides.is_empty() {
-            return self.base_tree_id;
+            return Ok(self.base_tree_id);
+        }
+        if let Some((path, _)) = self.overrides.iter().find(|(path, file_override)| {
+            matches!(file_override, Override::Replace(_)) && path.has_reserved_component()
+        }) {
+            return Err(BackendError::Other(Box::new(ReservedPathError {
+                path: path.clone(),
+            })));
         }
 
+        let store = &self.store;
         let mut trees_to_write = self.get_base_trees();
+        // If a base tree is known to have no conflicts, neither do its unchanged
+        // entries, so whether the new tree has conflicts follows from the
+        // changed entries. The unchanged subtrees then don't have to be read.
+        let mut has_conflict_hints: HashMap<RepoPathBuf, Option<bool>> = trees_to_write
+            .iter()
+            .map(|(dir, tree)| {
+                let conflict_free = tree.is_empty() || tree.has_conflict_hint() == Some(false);
+                (dir.clone(), conflict_free.then_some(false))
+            })
+            .collect();
 
         // Update entries in parent trees for file overrides
         for (path, file_override) in self.overrides {
             let (dir, basename) = path.split().unwrap();
+            // The entry is replaced again when the tree at the path is written
+            let is_written_dir = trees_to_write.contains_key(&path);
             let tree = trees_to_write.get_mut(dir).unwrap();
+            let hint = has_conflict_hints.get_mut(dir).unwrap();
             match file_override {
                 Override::Replace(value) => {
+                    let entry_has_conflict = match &value {
+                        _ if is_written_dir => Some(false),
+                        TreeValue::Conflict(_) => Some(true),
+                        TreeValue::Tree(id) => store.known_tree_has_conflict(&path, id),
+                        _ => Some(false),
+                    };
+                    add_changed_entry_hint(hint, entry_has_conflict);
                     tree.set(basename.to_owned(), value);
                 }
                 Override::Tombstone => {
@@ -92,8 +133,10 @@ impl TreeBuilder {
 
         // Write trees in reverse lexicographical order, starting with trees without
         // children.
-        let store = &self.store;
-        while let Some((dir, tree)) = trees_to_write.pop_last() {
+        while let Some((dir, mut tree)) = trees_to_write.pop_last() {
+            // Base trees that weren't written by jj may have empty subtrees
+            tree.normalize(store.empty_tree_id());
+            tree.set_has_conflict_hint(has_conflict_hints[&dir]);
             if let Some((parent, basename)) = dir.split() {
                 let parent_tree = trees_to_write.get_mut(parent).unwrap();
                 if tree.is_empty() {
@@ -103,13 +146,15 @@ impl TreeBuilder {
                         // Entry would have been replaced with file (see above)
                     }
                 } else {
-                    let tree = store.write_tree(&dir, tree).unwrap();
+                    let tree = store.write_tree(&dir, tree)?;
+                    let hint = has_conflict_hints.get_mut(parent).unwrap();
+                    add_changed_entry_hint(hint, tree.data().has_conflict_hint());
                     parent_tree.set(basename.to_owned(), TreeValue::Tree(tree.id().clone()));
                 }
             } else {
                 // We're writing the root tree. Write it even if empty. Return its id.
                 assert!(trees_to_write.is_empty());
-                return store.write_tree(&dir, tree).unwrap().id().clone();
+                return Ok(store.write_tree(&dir, tree)?.id().clone());
             }
         }
 
@@ -151,3 +196,13 @@ impl TreeBuilder {
             .collect()
     }
 }
+
+/// Updates the conflict hint of a tree after an entry was set. The hint is
+/// `Some(false)` as long as the tree is known to have no conflicts.
+fn add_changed_entry_hint(hint: &mut Option<bool>, entry_has_conflict: Option<bool>) {
+    *hint = match (*hint, entry_has_conflict) {
+        (Some(true), _) | (_, Some(true)) => Some(true),
+        (Some(false), Some(false)) => Some(false),
+        _ => None,
+    };
+}
@@ -14,28 +14,138 @@
 
 #![allow(missing_docs)]
 
-use std::collections::{BTreeMap, HashMap, HashSet};
+use std::collections::{btree_map, BTreeMap, HashMap, HashSet};
+use std::fmt;
+use std::sync::OnceLock;
 
 use itertools::Itertools;
 
 use crate::backend::CommitId;
+use crate::index::Index;
 use crate::op_store::{BranchTarget, RefTarget, RefTargetOptionExt as _, RemoteRef, WorkspaceId};
 use crate::refs::LocalAndRemoteRef;
 use crate::str_util::StringPattern;
 use crate::{op_store, refs};
 
-#[derive(PartialEq, Eq, Debug, Clone)]
+/// A place in the view that refers to a commit.
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub enum ViewReference {
+    Head,
+    WorkingCopy(WorkspaceId),
+    LocalBranch(String),
+    RemoteBranch { name: String, remote_name: String },
+    Tag(String),
+    GitRef(String),
+    GitHead,
+}
+
+impl fmt::Display for ViewReference {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ViewReference::Head => write!(f, "a visible head"),
+            ViewReference::WorkingCopy(workspace_id) => write!(
+                f,
+                "the working-copy commit of workspace {}",
+                workspace_id.as_str()
+            ),
+            ViewReference::LocalBranch(name) => write!(f, "branch {name}"),
+            ViewReference::RemoteBranch { name, remote_name } => {
+                write!(f, "branch {name}@{remote_name}")
+            }
+            ViewReference::Tag(name) => write!(f, "tag {name}"),
+            ViewReference::GitRef(name) => write!(f, "git ref {name}"),
+            ViewReference::GitHead => write!(f, "git HEAD"),
+        }
+    }
+}
+
+/// Commits a conflicted branch could be resolved to.
+///
+/// The commit ids are deduplicated and sorted topologically (parents first) so
+/// that the order doesn't depend on the order in which the conflicting
+/// operations were merged.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct BranchConflictCandidates {
+    /// Targets the branch was moved away from.
+    pub removed_ids: Vec<CommitId>,
+    /// Targets the branch was moved to.
+    pub added_ids: Vec<CommitId>,
+}
+
+impl BranchConflictCandidates {
+    fn new(target: &RefTarget, index: &dyn Index) -> Self {
+        BranchConflictCandidates {
+            removed_ids: index.topo_order(&mut target.removed_ids().unique()),
+            added_ids: index.topo_order(&mut target.added_ids().unique()),
+        }
+    }
+
+    /// Iterates over all candidate commit ids, removed ones first.
+    pub fn iter(&self) -> impl Iterator<Item = &CommitId> {
+        self.removed_ids.iter().chain(&self.added_ids)
+    }
+}
+
+/// Local branch and tag names keyed by their case-folded form. Each list of
+/// names is sorted.
+#[derive(Clone, Debug, Default)]
+struct CaseFoldedRefNames {
+    local_branches: HashMap<String, Vec<String>>,
+    tags: HashMap<String, Vec<String>>,
+}
+
+impl CaseFoldedRefNames {
+    fn new(data: &op_store::View) -> Self {
+        let fold = |names: btree_map::Keys<'_, String, RefTarget>| {
+            let mut folded: HashMap<String, Vec<String>> = HashMap::new();
+            for name in names {
+                folded
+                    .entry(name.to_lowercase())
+                    .or_default()
+                    .push(name.clone());
+            }
+            folded
+        };
+        CaseFoldedRefNames {
+            local_branches: fold(data.local_branches.keys()),
+            tags: fold(data.tags.keys()),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
 pub struct View {
     data: op_store::View,
+    /// Built on first case-insensitive lookup, and cleared whenever branches
+    /// or tags may have been added or removed.
+    case_folded_names: OnceLock<CaseFoldedRefNames>,
 }
 
+impl PartialEq for View {
+    fn eq(&self, other: &Self) -> bool {
+        self.data == other.data
+    }
+}
+
+impl Eq for View {}
+
 impl View {
     pub fn new(op_store_view: op_store::View) -> Self {
         View {
             data: op_store_view,
+            case_folded_names: OnceLock::new(),
         }
     }
 
+    fn case_folded_names(&self) -> &CaseFoldedRefNames {
+        self.case_folded_names
+            .get_or_init(|| CaseFoldedRefNames::new(&self.data))
+    }
+
+    fn invalidate_case_folded_names(&mut self) {
+        self.case_folded_names.take();
+    }
+
     pub fn wc_commit_ids(&self) -> &HashMap<WorkspaceId, CommitId> {
         &self.data.wc_commit_ids
     }
@@ -71,6 +181,17 @@ impl View {
         &self.data.tags
     }
 
+    /// Iterates tag `(name, target)`s matching the given pattern. Entries are
+    /// sorted by `name`.
+    pub fn tags_matching<'a: 'b, 'b>(
+        &'a self,
+        pattern: &'b StringPattern,
+    ) -> impl Iterator<Item = (&'a str, &'a RefTarget)> + 'b {
+        pattern
+            .filter_btree_map(&self.data.tags)
+            .map(|(name, target)| (name.as_ref(), target))
+    }
+
     pub fn git_refs(&self) -> &BTreeMap<String, RefTarget> {
         &self.data.git_refs
     }
@@ -108,6 +229,7 @@ impl View {
 
     // TODO: maybe rename to forget_branch() because this seems unusual operation?
     pub fn remove_branch(&mut self, name: &str) {
+        self.invalidate_case_folded_names();
         self.data.local_branches.remove(name);
         for remote_view in self.data.remote_views.values_mut() {
             remote_view.branches.remove(name);
@@ -137,9 +259,19 @@ impl View {
         self.data.local_branches.get(name).flatten()
     }
 
+    /// Returns the names of the local branches which are equal to `name`
+    /// ignoring case, in lexicographical order.
+    pub fn local_branch_names_ignoring_case(&self, name: &str) -> &[String] {
+        self.case_folded_names()
+            .local_branches
+            .get(&name.to_lowercase())
+            .map_or(&[], |names| names)
+    }
+
     /// Sets local branch to point to the given target. If the target is absent,
     /// and if no associated remote branches exist, the branch will be removed.
     pub fn set_local_branch_target(&mut self, name: &str, target: RefTarget) {
+        self.invalidate_case_folded_names();
         if target.is_present() {
             self.data.local_branches.insert(name.to_owned(), target);
         } else {
@@ -147,6 +279,36 @@ impl View {
         }
     }
 
+    /// Iterates local branch `(name, target)`s whose targets are conflicted, in
+    /// lexicographical order.
+    pub fn conflicted_local_branches(&self) -> impl Iterator<Item = (&str, &RefTarget)> {
+        self.local_branches()
+            .filter(|(_, target)| target.has_conflict())
+    }
+
+    /// Returns the candidate targets of the conflicted local branch. Returns
+    /// `None` if the branch isn't conflicted.
+    pub fn local_branch_conflict_candidates(
+        &self,
+        name: &str,
+        index: &dyn Index,
+    ) -> Option<BranchConflictCandidates> {
+        let target = self.get_local_branch(name);
+        target
+            .has_conflict()
+            .then(|| BranchConflictCandidates::new(target, index))
+    }
+
+    /// Resolves the conflicted local branch by pointing it to `commit_id`.
+    /// Returns false without changing anything if the branch isn't conflicted.
+    pub fn resolve_branch_conflict(&mut self, name: &str, commit_id: CommitId) -> bool {
+        if !self.get_local_branch(name).has_conflict() {
+            return false;
+        }
+        self.set_local_branch_target(name, RefTarget::normal(commit_id));
+        true
+    }
+
     /// Iterates over `((name, remote_name), remote_ref)` for all remote
     /// branches in lexicographical order.
     pub fn all_remote_branches(&self) -> impl Iterator<Item = ((&str, &str), &RemoteRef)> {
@@ -282,9 +444,19 @@ impl View {
         self.data.tags.get(name).flatten()
     }
 
+    /// Returns the names of the tags which are equal to `name` ignoring case,
+    /// in lexicographical order.
+    pub fn tag_names_ignoring_case(&self, name: &str) -> &[String] {
+        self.case_folded_names()
+            .tags
+            .get(&name.to_lowercase())
+            .map_or(&[], |names| names)
+    }
+
     /// Sets tag to point to the given target. If the target is absent, the tag
     /// will be removed.
     pub fn set_tag_target(&mut self, name: &str, target: RefTarget) {
+        self.invalidate_case_folded_names();
         if target.is_present() {
             self.data.tags.insert(name.to_owned(), target);
         } else {
@@ -354,7 +526,63 @@ impl View {
         )
     }
 
+    /// Lists the commits referenced by this view along with where they're
+    /// referenced from. Commits referenced from several places are listed once
+    /// for each place.
+    ///
+    /// Unlike `all_referenced_commit_ids()`, this only includes the current
+    /// targets of the references.
+    pub fn commit_references(&self) -> Vec<(&CommitId, ViewReference)> {
+        let op_store::View {
+            head_ids,
+            local_branches,
+            tags,
+            remote_views,
+            git_refs,
+            git_head,
+            wc_commit_ids,
+        } = &self.data;
+        let mut references = vec![];
+        for id in head_ids {
+            references.push((id, ViewReference::Head));
+        }
+        for (workspace_id, id) in wc_commit_ids {
+            references.push((id, ViewReference::WorkingCopy(workspace_id.clone())));
+        }
+        for (name, target) in local_branches {
+            for id in target.added_ids() {
+                references.push((id, ViewReference::LocalBranch(name.clone())));
+            }
+        }
+        for (remote_name, remote_view) in remote_views {
+            for (name, remote_ref) in &remote_view.branches {
+                for id in remote_ref.target.added_ids() {
+                    let reference = ViewReference::RemoteBranch {
+                        name: name.clone(),
+                        remote_name: remote_name.clone(),
+                    };
+                    references.push((id, reference));
+                }
+            }
+        }
+        for (name, target) in tags {
+            for id in target.added_ids() {
+                references.push((id, ViewReference::Tag(name.clone())));
+            }
+        }
+        for (name, target) in git_refs {
+            for id in target.added_ids() {
+                references.push((id, ViewReference::GitRef(name.clone())));
+            }
+        }
+        for id in git_head.added_ids() {
+            references.push((id, ViewReference::GitHead));
+        }
+        references
+    }
+
     pub fn set_view(&mut self, data: op_store::View) {
+        self.invalidate_case_folded_names();
         self.data = data;
     }
 
@@ -363,6 +591,89 @@ impl View {
     }
 
     pub fn store_view_mut(&mut self) -> &mut op_store::View {
+        self.invalidate_case_folded_names();
         &mut self.data
     }
+
+    /// Computes the changes from this view to the `other` view.
+    pub fn diff<'a>(&'a self, other: &'a View) -> ViewDiff<'a> {
+        let wc_commits = itertools::chain(
+            self.data.wc_commit_ids.keys(),
+            other.data.wc_commit_ids.keys(),
+        )
+        .map(|workspace_id| {
+            let old_commit_id = self.get_wc_commit_id(workspace_id);
+            let new_commit_id = other.get_wc_commit_id(workspace_id);
+            (workspace_id, (old_commit_id, new_commit_id))
+        })
+        .filter(|(_, (old_commit_id, new_commit_id))| old_commit_id != new_commit_id)
+        .collect();
+        let added_heads = other
+            .heads()
+            .difference(self.heads())
+            .sorted_unstable()
+            .collect();
+        let removed_heads = self
+            .heads()
+            .difference(other.heads())
+            .sorted_unstable()
+            .collect();
+        let local_branches =
+            refs::diff_named_ref_targets(self.local_branches(), other.local_branches()).collect();
+        let remote_branches =
+            refs::diff_named_remote_refs(self.all_remote_branches(), other.all_remote_branches())
+                .collect();
+        let str_keyed = |refs: &'a BTreeMap<String, RefTarget>| {
+            refs.iter().map(|(name, target)| (name.as_str(), target))
+        };
+        let tags =
+            refs::diff_named_ref_targets(str_keyed(self.tags()), str_keyed(other.tags())).collect();
+        let git_refs =
+            refs::diff_named_ref_targets(str_keyed(self.git_refs()), str_keyed(other.git_refs()))
+                .collect();
+        let git_head =
+            (self.git_head() != other.git_head()).then_some((self.git_head(), other.git_head()));
+        ViewDiff {
+            wc_commits,
+            added_heads,
+            removed_heads,
+            local_branches,
+            remote_branches,
+            tags,
+            git_refs,
+            git_head,
+        }
+    }
+}
+
+/// Changes between two views, as computed by `View::diff()`.
+///
+/// Merging concurrent operations applies the changes between the base view and
+/// one side to the other side, so this is also what an operation "did".
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ViewDiff<'a> {
+    /// Changed working-copy commits by workspace. `None` means that the
+    /// workspace doesn't exist in that view.
+    pub wc_commits: BTreeMap<&'a WorkspaceId, (Option<&'a CommitId>, Option<&'a CommitId>)>,
+    /// Heads only in the new view, sorted by commit id.
+    pub added_heads: Vec<&'a CommitId>,
+    /// Heads only in the old view, sorted by commit id.
+    pub removed_heads: Vec<&'a CommitId>,
+    /// Changed local branches as `(name, (old_target, new_target))`, sorted by
+    /// name. Added or removed branches have an absent target on one side.
+    pub local_branches: Vec<(&'a str, (&'a RefTarget, &'a RefTarget))>,
+    /// Changed remote branches as `((name, remote_name), (old_ref, new_ref))`.
+    pub remote_branches: Vec<((&'a str, &'a str), (&'a RemoteRef, &'a RemoteRef))>,
+    /// Changed tags as `(name, (old_target, new_target))`.
+    pub tags: Vec<(&'a str, (&'a RefTarget, &'a RefTarget))>,
+    /// Changed Git refs as `(name, (old_target, new_target))`.
+    pub git_refs: Vec<(&'a str, (&'a RefTarget, &'a RefTarget))>,
+    /// Old and new Git HEAD if it changed.
+    pub git_head: Option<(&'a RefTarget, &'a RefTarget)>,
+}
+
+impl ViewDiff<'_> {
+    pub fn is_empty(&self) -> bool {
+        *self == ViewDiff::default()
+    }
 }
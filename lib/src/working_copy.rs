@@ -23,9 +23,11 @@ use std::sync::Arc;
 use thiserror::Error;
 
 use crate::backend::{BackendError, MergedTreeId};
+use crate::cancel::{CancellationToken, Cancelled};
 use crate::commit::Commit;
 use crate::fsmonitor::FsmonitorKind;
 use crate::gitignore::GitIgnoreFile;
+use crate::merge::MergedTreeValue;
 use crate::op_store::{OperationId, WorkspaceId};
 use crate::repo_path::{RepoPath, RepoPathBuf};
 use crate::settings::HumanByteSize;
@@ -101,9 +103,27 @@ pub trait LockedWorkingCopy {
     /// Snapshot the working copy and return the tree id.
     fn snapshot(&mut self, options: SnapshotOptions) -> Result<MergedTreeId, SnapshotError>;
 
+    /// Stats about the last call to `snapshot()`. Implementations that don't
+    /// collect stats return empty stats.
+    fn snapshot_stats(&self) -> SnapshotStats {
+        SnapshotStats::default()
+    }
+
     /// Check out the specified commit in the working copy.
     fn check_out(&mut self, commit: &Commit) -> Result<CheckoutStats, CheckoutError>;
 
+    /// Like `check_out()`, but stops early with `CheckoutError::Cancelled` if
+    /// `cancel` is cancelled. Implementations that can't be interrupted only
+    /// check the token before starting.
+    fn check_out_cancellable(
+        &mut self,
+        commit: &Commit,
+        cancel: &CancellationToken,
+    ) -> Result<CheckoutStats, CheckoutError> {
+        cancel.checkpoint()?;
+        self.check_out(commit)
+    }
+
     /// Update to another commit without touching the files in the working copy.
     fn reset(&mut self, commit: &Commit) -> Result<(), ResetError>;
 
@@ -162,6 +182,16 @@ pub enum SnapshotError {
         /// The maximum allowed size.
         max_size: HumanByteSize,
     },
+    /// A path in the working copy contained a component reserved for internal
+    /// state, such as `.jj` or `.git`.
+    #[error("Working copy path {} contains a reserved component", path.display())]
+    ReservedPath {
+        /// The reserved path.
+        path: PathBuf,
+    },
+    /// The snapshot was cancelled through `SnapshotOptions::cancel`.
+    #[error(transparent)]
+    Cancelled(#[from] Cancelled),
     /// Some other error happened while snapshotting the working copy.
     #[error("{message}")]
     Other {
@@ -188,6 +218,13 @@ pub struct SnapshotOptions<'a> {
     pub fsmonitor_kind: Option<FsmonitorKind>,
     /// A callback for the UI to display progress.
     pub progress: Option<&'a SnapshotProgress<'a>>,
+    /// A callback for the UI to display changes as soon as they're found,
+    /// before the snapshot is complete.
+    pub on_change: Option<&'a SnapshotChangeCallback<'a>>,
+    /// A token that can be used to stop the snapshot early, in which case
+    /// `LockedWorkingCopy::snapshot()` returns `SnapshotError::Cancelled` and
+    /// the recorded working-copy state is left as it was.
+    pub cancel: Option<&'a CancellationToken>,
     /// The size of the largest file that should be allowed to become tracked
     /// (already tracked files are always snapshotted). If there are larger
     /// files in the working copy, then `LockedWorkingCopy::snapshot()` may
@@ -203,6 +240,8 @@ impl SnapshotOptions<'_> {
             base_ignores: GitIgnoreFile::empty(),
             fsmonitor_kind: None,
             progress: None,
+            on_change: None,
+            cancel: None,
             max_new_file_size: u64::MAX,
         }
     }
@@ -211,6 +250,28 @@ impl SnapshotOptions<'_> {
 /// A callback for getting progress updates.
 pub type SnapshotProgress<'a> = dyn Fn(&RepoPath) + 'a + Sync;
 
+/// A callback for paths whose contents changed since the last snapshot. It's
+/// called with the new value, which is absent if the path was deleted. Calls
+/// may come from multiple threads, in no particular order, and before the new
+/// tree is written.
+pub type SnapshotChangeCallback<'a> = dyn Fn(&RepoPath, &MergedTreeValue) + 'a + Sync;
+
+/// Stats about a snapshot of a working copy.
+#[derive(Debug, Default, PartialEq, Eq, Clone)]
+pub struct SnapshotStats {
+    /// The paths in the working copy that weren't recorded, and why. Ignored
+    /// paths aren't included.
+    pub skipped_paths: Vec<(RepoPathBuf, SnapshotSkipReason)>,
+}
+
+/// Why a snapshot didn't record a path in the working copy.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum SnapshotSkipReason {
+    /// The path is a FIFO, socket, device node, or another kind of file that
+    /// can't be recorded in a tree. Such files are never opened.
+    SpecialFile,
+}
+
 /// Stats about a checkout operation on a working copy. All "files" mentioned
 /// below may also be symlinks or materialized conflicts.
 #[derive(Debug, PartialEq, Eq, Clone)]
@@ -223,9 +284,23 @@ pub struct CheckoutStats {
     /// The number of files removed in the working copy.
     pub removed_files: u32,
     /// The number of files that were supposed to be updated or added in the
-    /// working copy but were skipped because there was an untracked (probably
-    /// ignored) file in its place.
+    /// working copy but were skipped, usually because there was an untracked
+    /// (probably ignored) file in its place.
     pub skipped_files: u32,
+    /// The paths that were skipped, and why.
+    pub skipped_paths: Vec<(RepoPathBuf, CheckoutSkipReason)>,
+}
+
+/// Why a checkout didn't update a path in the working copy.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum CheckoutSkipReason {
+    /// There was an untracked file or directory in the path's place.
+    UntrackedPath,
+    /// A parent directory of the path is a symlink. The checkout doesn't
+    /// follow symlinks, so it can't write to places outside the working copy.
+    SymlinkedParent,
+    /// The path can't be represented on this platform.
+    InvalidFileName,
 }
 
 /// The working-copy checkout failed.
@@ -245,6 +320,17 @@ pub enum CheckoutError {
     /// Reading or writing from the commit backend failed.
     #[error("Internal backend error")]
     InternalBackendError(#[from] BackendError),
+    /// The checkout was cancelled. Files that were already updated are recorded
+    /// such that the next snapshot picks up whatever is on disk.
+    #[error(transparent)]
+    Cancelled(#[from] Cancelled),
+    /// The tree to check out contained a path with a component reserved for
+    /// internal state, such as `.jj` or `.git`. Nothing was written.
+    #[error(r#"Path "{}" contains a reserved component"#, path.as_internal_file_string())]
+    ReservedPath {
+        /// The reserved path.
+        path: RepoPathBuf,
+    },
     /// Some other error happened while checking out the working copy.
     #[error("{message}")]
     Other {
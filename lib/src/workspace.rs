@@ -26,18 +26,21 @@ use thiserror::Error;
 use crate::backend::{Backend, BackendInitError, MergedTreeId};
 use crate::commit::Commit;
 use crate::file_util::{self, IoResultExt as _, PathError};
+use crate::format_version::{self, FormatCompatibility, FormatComponent, SupportedVersions};
 use crate::git_backend::{canonicalize_git_repo_path, GitBackend};
 use crate::local_backend::LocalBackend;
 use crate::local_working_copy::{LocalWorkingCopy, LocalWorkingCopyFactory};
+use crate::merge_drivers::MergeDriverConfigError;
 use crate::op_store::{OperationId, WorkspaceId};
 use crate::repo::{
     read_store_type_compat, BackendInitializer, CheckOutCommitError, IndexStoreInitializer,
     OpHeadsStoreInitializer, OpStoreInitializer, ReadonlyRepo, Repo, RepoInitError, RepoLoader,
-    StoreFactories, StoreLoadError, SubmoduleStoreInitializer,
+    RepoReadOnly, StoreFactories, StoreLoadError, SubmoduleStoreInitializer,
 };
 use crate::settings::UserSettings;
 use crate::signing::{SignInitError, Signer};
 use crate::store::Store;
+use crate::transaction::TransactionCommitError;
 use crate::working_copy::{
     CheckoutError, CheckoutStats, LockedWorkingCopy, WorkingCopy, WorkingCopyFactory,
     WorkingCopyStateError,
@@ -61,12 +64,18 @@ pub enum WorkspaceInitError {
     Backend(#[from] BackendInitError),
     #[error(transparent)]
     SignInit(#[from] SignInitError),
+    #[error(transparent)]
+    MergeDrivers(#[from] MergeDriverConfigError),
+    #[error(transparent)]
+    TransactionCommit(#[from] TransactionCommitError),
 }
 
 #[derive(Error, Debug)]
 pub enum WorkspaceLoadError {
-    #[error("The repo appears to no longer be at {0}")]
-    RepoDoesNotExist(PathBuf),
+    #[error("{} points to {}, which is not a repo", file.display(), target.display())]
+    RepoDoesNotExist { file: PathBuf, target: PathBuf },
+    #[error("{} points to {}, which is not a Git repo", file.display(), target.display())]
+    GitRepoDoesNotExist { file: PathBuf, target: PathBuf },
     #[error("There is no Jujutsu repo in {0}")]
     NoWorkspaceHere(PathBuf),
     #[error("Cannot read the repo")]
@@ -87,6 +96,147 @@ pub struct Workspace {
     working_copy: Box<dyn WorkingCopy>,
 }
 
+/// Finds the repo directory of the workspace, following `.jj/repo` if it's a
+/// file pointing to the repo of another workspace.
+fn resolve_repo_dir(
+    workspace_root: &Path,
+    repaired_paths: &mut Vec<RepairedPath>,
+) -> Result<PathBuf, WorkspaceLoadError> {
+    let jj_dir = workspace_root.join(".jj");
+    if !jj_dir.is_dir() {
+        return Err(WorkspaceLoadError::NoWorkspaceHere(
+            workspace_root.to_owned(),
+        ));
+    }
+    let repo_dir = jj_dir.join("repo");
+    if !repo_dir.is_file() {
+        return Ok(repo_dir);
+    }
+    // If .jj/repo is a file, then we interpret its contents as a path to the
+    // actual repo directory (typically in another workspace), relative to .jj/.
+    let jj_dir = jj_dir.canonicalize().context(&jj_dir)?;
+    let repo_dir = resolve_path_file(
+        &jj_dir.join("repo"),
+        &jj_dir,
+        |path| path.join("store").is_dir(),
+        |file, target| WorkspaceLoadError::RepoDoesNotExist { file, target },
+        repaired_paths,
+    )?;
+    if !repo_dir.is_dir() {
+        return Err(WorkspaceLoadError::RepoDoesNotExist {
+            file: jj_dir.join("repo"),
+            target: repo_dir,
+        });
+    }
+    Ok(repo_dir)
+}
+
+/// Resolves the path stored in `file` relative to `base`.
+///
+/// If the path no longer exists, the target is looked up by its trailing
+/// components among the ancestors of `base`. That finds a target which was
+/// moved along with `base` if the path is a legacy absolute path, and a
+/// target which stayed in place if `base` was moved on its own, e.g. a
+/// secondary workspace moved to a subdirectory. The `file` is then rewritten
+/// with a relative path to the new location.
+fn resolve_path_file(
+    file: &Path,
+    base: &Path,
+    is_valid_target: impl Fn(&Path) -> bool,
+    not_found: impl FnOnce(PathBuf, PathBuf) -> WorkspaceLoadError,
+    repaired_paths: &mut Vec<RepairedPath>,
+) -> Result<PathBuf, WorkspaceLoadError> {
+    let buf = fs::read(file).context(file)?;
+    let target =
+        PathBuf::from(String::from_utf8(buf).map_err(|_| WorkspaceLoadError::NonUnicodePath)?);
+    let path = base.join(&target);
+    if let Ok(path) = path.canonicalize() {
+        return Ok(path);
+    }
+    let new_path = file_util::find_moved_path(&target, base, is_valid_target);
+    let Some(new_path) = new_path else {
+        return Err(not_found(file.to_owned(), target));
+    };
+    let new_path = new_path.canonicalize().context(&new_path)?;
+    let new_target =
+        file_util::relative_path_if_related(base, &new_path).unwrap_or_else(|| new_path.clone());
+    write_path_file(file, &new_target)?;
+    repaired_paths.push(RepairedPath {
+        file: file.to_owned(),
+        old_target: target,
+        new_target,
+    });
+    Ok(new_path)
+}
+
+/// Writes `path` to `file`. Relative paths are written with forward slashes
+/// so that they can be read on any platform.
+fn write_path_file(file: &Path, path: &Path) -> Result<(), WorkspaceLoadError> {
+    let path_str = if path.is_relative() {
+        path.components()
+            .map(|component| component.as_os_str().to_str())
+            .collect::<Option<Vec<_>>>()
+            .map(|components| components.join("/"))
+    } else {
+        path.to_str().map(str::to_owned)
+    };
+    let path_str = path_str.ok_or(WorkspaceLoadError::NonUnicodePath)?;
+    fs::write(file, path_str).context(file)?;
+    Ok(())
+}
+
+/// Points the workspace at the repo at `repo_path`, e.g. after the repo was
+/// moved.
+pub fn relink_repo(workspace_root: &Path, repo_path: &Path) -> Result<(), WorkspaceLoadError> {
+    let jj_dir = workspace_root.join(".jj");
+    if !jj_dir.is_dir() {
+        return Err(WorkspaceLoadError::NoWorkspaceHere(
+            workspace_root.to_owned(),
+        ));
+    }
+    let jj_dir = jj_dir.canonicalize().context(&jj_dir)?;
+    let file = jj_dir.join("repo");
+    let repo_dir = repo_path
+        .canonicalize()
+        .ok()
+        .filter(|path| path.join("store").is_dir())
+        .ok_or_else(|| WorkspaceLoadError::RepoDoesNotExist {
+            file: file.clone(),
+            target: repo_path.to_owned(),
+        })?;
+    write_path_file(
+        &file,
+        &file_util::relative_path_if_related(&jj_dir, &repo_dir).unwrap_or(repo_dir),
+    )
+}
+
+/// Points the repo of the workspace at the Git repo at `git_repo_path`, e.g.
+/// after the Git repo was moved.
+pub fn relink_git_repo(
+    workspace_root: &Path,
+    git_repo_path: &Path,
+) -> Result<(), WorkspaceLoadError> {
+    let store_path = resolve_repo_dir(workspace_root, &mut vec![])?.join("store");
+    let file = store_path.join("git_target");
+    // Only repos backed by Git have a Git repo to relink.
+    fs::metadata(&file).context(&file)?;
+    let mut git_repo_path = git_repo_path.to_owned();
+    if git_repo_path.join(".git").is_dir() {
+        git_repo_path.push(".git");
+    }
+    let git_repo_path = canonicalize_git_repo_path(&git_repo_path)
+        .ok()
+        .filter(|path| path.join("HEAD").is_file())
+        .ok_or_else(|| WorkspaceLoadError::GitRepoDoesNotExist {
+            file: file.clone(),
+            target: git_repo_path.clone(),
+        })?;
+    write_path_file(
+        &file,
+        &file_util::relative_path_if_related(&store_path, &git_repo_path).unwrap_or(git_repo_path),
+    )
+}
+
 fn create_jj_dir(workspace_root: &Path) -> Result<PathBuf, WorkspaceInitError> {
     let jj_dir = workspace_root.join(".jj");
     match std::fs::create_dir(&jj_dir).context(&jj_dir) {
@@ -98,6 +248,33 @@ fn create_jj_dir(workspace_root: &Path) -> Result<PathBuf, WorkspaceInitError> {
     }
 }
 
+/// If `git_dir` is the Git directory of a linked worktree, or a `.git` file
+/// pointing to one, returns the directory of the main repo, which holds the
+/// objects and refs shared by all worktrees.
+///
+/// The directory of a linked worktree has a `commondir` file with the path to
+/// the main repo. This is also the case for worktrees created from other linked
+/// worktrees.
+fn linked_worktree_common_dir(git_dir: &Path) -> Result<Option<PathBuf>, PathError> {
+    let git_dir = if git_dir.is_file() {
+        let contents = fs::read_to_string(git_dir).context(git_dir)?;
+        let Some(target) = contents.trim_end().strip_prefix("gitdir:") else {
+            return Ok(None);
+        };
+        git_dir.parent().unwrap().join(target.trim_start())
+    } else {
+        git_dir.to_owned()
+    };
+    let common_dir_file = git_dir.join("commondir");
+    if !common_dir_file.is_file() {
+        return Ok(None);
+    }
+    let common_dir = fs::read_to_string(&common_dir_file).context(&common_dir_file)?;
+    let common_dir = git_dir.join(common_dir.trim_end());
+    let common_dir = common_dir.canonicalize().context(&common_dir)?;
+    Ok(Some(common_dir))
+}
+
 fn init_working_copy(
     user_settings: &UserSettings,
     repo: &Arc<ReadonlyRepo>,
@@ -115,7 +292,7 @@ fn init_working_copy(
         user_settings,
         &repo.store().root_commit(),
     )?;
-    let repo = tx.commit(format!("add workspace '{}'", workspace_id.as_str()));
+    let repo = tx.commit(format!("add workspace '{}'", workspace_id.as_str()))?;
 
     let working_copy = working_copy_factory.init_working_copy(
         repo.store().clone(),
@@ -192,6 +369,9 @@ impl Workspace {
     }
 
     /// Initializes a workspace with an existing Git repo at the specified path.
+    ///
+    /// The path can be a Git repo, its working tree, or a linked worktree, in
+    /// which case the main repo is used.
     pub fn init_external_git(
         user_settings: &UserSettings,
         workspace_root: &Path,
@@ -207,25 +387,23 @@ impl Workspace {
                 git_repo_path.pop();
             }
         }
+        if let Some(common_dir) = linked_worktree_common_dir(&git_repo_path)? {
+            git_repo_path = common_dir;
+        }
 
         let backend_initializer =
             |settings: &UserSettings, store_path: &Path| -> Result<Box<dyn Backend>, _> {
-                // If the git repo is inside the workspace, use a relative path to it so the
-                // whole workspace can be moved without breaking.
+                // Use a relative path to the git repo so the workspace and the git repo
+                // can be moved together without breaking, unless they have nothing in
+                // common but the filesystem root.
                 // TODO: Clean up path normalization. store_path is canonicalized by
-                // ReadonlyRepo::init(). workspace_root will be canonicalized by
-                // Workspace::new(), but it's not yet here.
-                let store_relative_git_repo_path = match (
-                    workspace_root.canonicalize(),
-                    canonicalize_git_repo_path(&git_repo_path),
-                ) {
-                    (Ok(workspace_root), Ok(git_repo_path))
-                        if git_repo_path.starts_with(&workspace_root) =>
-                    {
-                        file_util::relative_path(store_path, &git_repo_path)
-                    }
-                    _ => git_repo_path.to_owned(),
-                };
+                // ReadonlyRepo::init().
+                let store_relative_git_repo_path = canonicalize_git_repo_path(&git_repo_path)
+                    .ok()
+                    .and_then(|git_repo_path| {
+                        file_util::relative_path_if_related(store_path, &git_repo_path)
+                    })
+                    .unwrap_or_else(|| git_repo_path.to_owned());
                 let backend =
                     GitBackend::init_external(settings, store_path, &store_relative_git_repo_path)?;
                 Ok(Box::new(backend))
@@ -264,6 +442,7 @@ impl Workspace {
             .map_err(|repo_init_err| match repo_init_err {
                 RepoInitError::Backend(err) => WorkspaceInitError::Backend(err),
                 RepoInitError::Path(err) => WorkspaceInitError::Path(err),
+                RepoInitError::MergeDrivers(err) => WorkspaceInitError::MergeDrivers(err),
             })?;
             let (working_copy, repo) = init_working_copy(
                 user_settings,
@@ -313,6 +492,11 @@ impl Workspace {
         let jj_dir = create_jj_dir(workspace_root)?;
 
         let repo_dir = repo.repo_path().canonicalize().context(repo.repo_path())?;
+        // Store the path relative to the workspace so that they can be moved
+        // together.
+        let canonical_jj_dir = jj_dir.canonicalize().context(&jj_dir)?;
+        let repo_dir =
+            file_util::relative_path_if_related(&canonical_jj_dir, &repo_dir).unwrap_or(repo_dir);
         let repo_file_path = jj_dir.join("repo");
         let mut repo_file = File::create(&repo_file_path).context(&repo_file_path)?;
         repo_file
@@ -370,6 +554,15 @@ impl Workspace {
     pub fn start_working_copy_mutation(
         &mut self,
     ) -> Result<LockedWorkspace, WorkingCopyStateError> {
+        if self.repo_loader.is_read_only() {
+            return Err(WorkingCopyStateError {
+                message: "Cannot update the working copy of a read-only repo".to_string(),
+                err: RepoReadOnly {
+                    repo_path: self.repo_path().to_owned(),
+                }
+                .into(),
+            });
+        }
         let locked_wc = self.working_copy.start_mutation()?;
         Ok(LockedWorkspace {
             base: self,
@@ -426,41 +619,61 @@ impl<'a> LockedWorkspace<'a> {
     }
 }
 
+/// A file pointing to a moved repo which was updated while loading the
+/// workspace.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RepairedPath {
+    /// The file that was rewritten.
+    pub file: PathBuf,
+    /// The (absolute) path the file used to point to.
+    pub old_target: PathBuf,
+    /// The path the file points to now.
+    pub new_target: PathBuf,
+}
+
 #[derive(Clone, Debug)]
 pub struct WorkspaceLoader {
     workspace_root: PathBuf,
     repo_dir: PathBuf,
     working_copy_state_path: PathBuf,
+    repaired_paths: Vec<RepairedPath>,
 }
 
 impl WorkspaceLoader {
+    /// Creates a loader for the workspace at `workspace_root`, which may also
+    /// point to the workspace's `.jj` directory.
     pub fn init(workspace_root: &Path) -> Result<Self, WorkspaceLoadError> {
-        let jj_dir = workspace_root.join(".jj");
-        if !jj_dir.is_dir() {
-            return Err(WorkspaceLoadError::NoWorkspaceHere(
-                workspace_root.to_owned(),
-            ));
-        }
-        let mut repo_dir = jj_dir.join("repo");
-        // If .jj/repo is a file, then we interpret its contents as a relative path to
-        // the actual repo directory (typically in another workspace).
-        if repo_dir.is_file() {
-            let buf = fs::read(&repo_dir).context(&repo_dir)?;
-            let repo_path_str =
-                String::from_utf8(buf).map_err(|_| WorkspaceLoadError::NonUnicodePath)?;
-            repo_dir = jj_dir
-                .join(&repo_path_str)
-                .canonicalize()
-                .context(&repo_path_str)?;
-            if !repo_dir.is_dir() {
-                return Err(WorkspaceLoadError::RepoDoesNotExist(repo_dir));
+        let workspace_root = match (workspace_root.file_name(), workspace_root.parent()) {
+            (Some(name), Some(parent))
+                if name == ".jj"
+                    && workspace_root.join("repo").exists()
+                    && !workspace_root.join(".jj").is_dir() =>
+            {
+                parent
             }
+            _ => workspace_root,
+        };
+        let mut repaired_paths = vec![];
+        let repo_dir = resolve_repo_dir(workspace_root, &mut repaired_paths)?;
+        // The store may point to a Git repo, which may have moved along with
+        // the workspace.
+        let store_path = repo_dir.join("store");
+        let git_target_path = store_path.join("git_target");
+        if git_target_path.is_file() {
+            resolve_path_file(
+                &git_target_path,
+                &store_path,
+                |path| path.join("HEAD").is_file(),
+                |file, target| WorkspaceLoadError::GitRepoDoesNotExist { file, target },
+                &mut repaired_paths,
+            )?;
         }
-        let working_copy_state_path = jj_dir.join("working_copy");
+        let working_copy_state_path = workspace_root.join(".jj").join("working_copy");
         Ok(WorkspaceLoader {
             workspace_root: workspace_root.to_owned(),
             repo_dir,
             working_copy_state_path,
+            repaired_paths,
         })
     }
 
@@ -472,13 +685,34 @@ impl WorkspaceLoader {
         &self.repo_dir
     }
 
+    /// The directory containing the working-copy state, such as `.jj/working_copy`.
+    pub fn working_copy_state_path(&self) -> &Path {
+        &self.working_copy_state_path
+    }
+
+    /// Files pointing to moved repos which were fixed up by `init()`.
+    pub fn repaired_paths(&self) -> &[RepairedPath] {
+        &self.repaired_paths
+    }
+
     pub fn load(
         &self,
         user_settings: &UserSettings,
         store_factories: &StoreFactories,
         working_copy_factories: &HashMap<String, Box<dyn WorkingCopyFactory>>,
     ) -> Result<Workspace, WorkspaceLoadError> {
-        let repo_loader = RepoLoader::init(user_settings, &self.repo_dir, store_factories)?;
+        let mut repo_loader = RepoLoader::init(user_settings, &self.repo_dir, store_factories)?;
+        let compatibility = format_version::check_component(
+            FormatComponent::WorkingCopy,
+            &self.working_copy_state_path,
+            &SupportedVersions::current(),
+        )
+        .map_err(StoreLoadError::from)?;
+        if compatibility == FormatCompatibility::ReadOnly {
+            // The working copy is updated along with the repo, so neither may
+            // be written to.
+            repo_loader.set_read_only(true);
+        }
         let working_copy = self.load_working_copy(repo_loader.store(), working_copy_factories)?;
         let workspace = Workspace::new(&self.workspace_root, working_copy, repo_loader)?;
         Ok(workspace)
@@ -6,12 +6,16 @@ fn test_no_forgotten_test_files() {
     testutils::assert_no_forgotten_test_files(&test_dir);
 }
 
+mod test_absorb;
+mod test_backend_call_counts;
 mod test_bad_locking;
+mod test_bundle;
 mod test_commit_builder;
 mod test_commit_concurrent;
 mod test_conflicts;
 mod test_default_revset_graph_iterator;
 mod test_diff_summary;
+mod test_format_version;
 mod test_git;
 mod test_git_backend;
 mod test_id_prefix;
@@ -26,8 +30,12 @@ mod test_merged_tree;
 mod test_mut_repo;
 mod test_operations;
 mod test_refs;
+mod test_repo_stats;
+mod test_repo_watcher;
 mod test_revset;
 mod test_rewrite;
 mod test_signing;
+mod test_status;
+mod test_tree;
 mod test_view;
 mod test_workspace;
@@ -0,0 +1,269 @@
+// Copyright 2024 The Jujutsu Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use jj_lib::absorb::{
+    apply_absorb, drop_conflicting_hunks, plan_absorb, AbsorbSkipReason, SkippedHunk,
+};
+use jj_lib::annotate::{annotate_file, LineOrigin};
+use jj_lib::backend::{CommitId, TreeValue};
+use jj_lib::commit::Commit;
+use jj_lib::matchers::EverythingMatcher;
+use jj_lib::repo::Repo;
+use jj_lib::repo_path::RepoPath;
+use jj_lib::store::Store;
+use testutils::read_file;
+use testutils::test_repo_builder::TestRepoBuilder;
+
+fn file_content(store: &Store, commit: &Commit, path: &RepoPath) -> String {
+    let value = commit.tree().unwrap().path_value(path);
+    let Some(TreeValue::File { id, .. }) = value.as_normal() else {
+        panic!("{path:?} isn't a file in {:?}", commit.id());
+    };
+    String::from_utf8(read_file(store, path, id)).unwrap()
+}
+
+fn ids(commits: &[&Commit]) -> Vec<CommitId> {
+    commits.iter().map(|commit| commit.id().clone()).collect()
+}
+
+#[test]
+fn test_annotate_file() {
+    let fixture = TestRepoBuilder::new()
+        .commit("a")
+        .file("file", "1\n2\n3\n")
+        .commit_on("a", "b")
+        .file("file", "1\n2b\n3\n4\n")
+        .commit_on("b", "c")
+        .file("file", "0\n1\n2b\n3\n4\n")
+        .build();
+    let store = fixture.repo().store();
+    let path = RepoPath::from_internal_string("file");
+    let (a, b, c) = (
+        fixture.commit("a"),
+        fixture.commit("b"),
+        fixture.commit("c"),
+    );
+    let origin = |commit: &Commit, line| LineOrigin::Commit {
+        commit_id: commit.id().clone(),
+        line,
+    };
+
+    let origins = annotate_file(store, &ids(&[c, b, a]), c, path).unwrap();
+    assert_eq!(
+        origins,
+        vec![
+            origin(c, 0),
+            origin(a, 0),
+            origin(b, 1),
+            origin(a, 2),
+            origin(b, 3),
+        ]
+    );
+
+    // Lines from commits outside the domain aren't attributed
+    let origins = annotate_file(store, &ids(&[c, b]), c, path).unwrap();
+    assert_eq!(
+        origins,
+        vec![
+            origin(c, 0),
+            LineOrigin::Outside,
+            origin(b, 1),
+            LineOrigin::Outside,
+            origin(b, 3),
+        ]
+    );
+}
+
+#[test]
+fn test_absorb_into_stack() {
+    let fixture = TestRepoBuilder::new()
+        .commit("a")
+        .file("file", "1\n2\n3\n4\n5\n")
+        .commit_on("a", "b")
+        .file("file", "1\n2\n3b\n4\n5\n")
+        .commit_on("b", "c")
+        .file("file", "1\n2\n3b\n4\n5c\n")
+        .commit_on("c", "source")
+        .file("file", "1a\n2\n3bb\n4\n5cc\n")
+        .build();
+    let repo = fixture.repo();
+    let store = repo.store();
+    let path = RepoPath::from_internal_string("file");
+    let (a, b, c) = (
+        fixture.commit("a"),
+        fixture.commit("b"),
+        fixture.commit("c"),
+    );
+    let source = fixture.commit("source");
+
+    let plan = plan_absorb(repo.as_ref(), source, &ids(&[c, b, a]), &EverythingMatcher).unwrap();
+    let destinations = plan
+        .hunks
+        .iter()
+        .map(|(id, hunk)| (id.clone(), hunk.parent_lines.clone()))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        destinations,
+        vec![
+            (a.id().clone(), 0..1),
+            (b.id().clone(), 2..3),
+            (c.id().clone(), 4..5),
+        ]
+    );
+    assert_eq!(plan.skipped, vec![]);
+
+    let mut tx = repo.start_transaction(&fixture.settings);
+    let rebased = apply_absorb(tx.mut_repo(), &fixture.settings, source, &plan.hunks).unwrap();
+    let new_source = store.get_commit(&rebased[source.id()]).unwrap();
+    assert_eq!(new_source.tree_id(), source.tree_id());
+    let new_c = new_source.parents().remove(0);
+    let new_b = new_c.parents().remove(0);
+    let new_a = new_b.parents().remove(0);
+    assert_eq!(new_a.change_id(), a.change_id());
+    assert_eq!(file_content(store, &new_a, path), "1a\n2\n3\n4\n5\n");
+    assert_eq!(file_content(store, &new_b, path), "1a\n2\n3bb\n4\n5\n");
+    assert_eq!(file_content(store, &new_c, path), "1a\n2\n3bb\n4\n5cc\n");
+    assert_eq!(new_c.tree_id(), source.tree_id());
+}
+
+#[test]
+fn test_absorb_skipped_hunks() {
+    let fixture = TestRepoBuilder::new()
+        .commit("a")
+        .file("file", "1\n2\n3\n4\n5\n")
+        .file("other", "other\n")
+        .commit_on("a", "b")
+        .file("file", "1\n2b\n3\n4\n5\n")
+        .commit_on("b", "source")
+        .file("file", "1\n2bb\n3x\n4\n5x\n")
+        .file("added", "added\n")
+        .build();
+    let repo = fixture.repo();
+    let (a, b) = (fixture.commit("a"), fixture.commit("b"));
+    let source = fixture.commit("source");
+
+    // Only `b` is mutable, so the change to line 5 can't be absorbed, and
+    // lines 2 and 3 were last changed in different commits
+    let plan = plan_absorb(repo.as_ref(), source, &ids(&[b]), &EverythingMatcher).unwrap();
+    assert_eq!(plan.hunks, vec![]);
+    assert_eq!(
+        plan.skipped,
+        vec![
+            SkippedHunk {
+                path: RepoPath::from_internal_string("added").to_owned(),
+                parent_lines: None,
+                reason: AbsorbSkipReason::UnsupportedPath,
+            },
+            SkippedHunk {
+                path: RepoPath::from_internal_string("file").to_owned(),
+                parent_lines: Some(1..3),
+                reason: AbsorbSkipReason::Ambiguous,
+            },
+            SkippedHunk {
+                path: RepoPath::from_internal_string("file").to_owned(),
+                parent_lines: Some(4..5),
+                reason: AbsorbSkipReason::Immutable,
+            },
+        ]
+    );
+
+    // With `a` mutable too, the change to line 5 goes into `a`
+    let mut plan = plan_absorb(repo.as_ref(), source, &ids(&[b, a]), &EverythingMatcher).unwrap();
+    drop_conflicting_hunks(repo.as_ref(), source, &mut plan).unwrap();
+    let destinations = plan
+        .hunks
+        .iter()
+        .map(|(id, hunk)| (id.clone(), hunk.parent_lines.clone()))
+        .collect::<Vec<_>>();
+    assert_eq!(destinations, vec![(a.id().clone(), 4..5)]);
+}
+
+#[test]
+fn test_absorb_conflicting_hunk() {
+    // Moving the change to line 2 into `a` would conflict with the change to
+    // the adjacent line 3 in `b`
+    let fixture = TestRepoBuilder::new()
+        .commit("a")
+        .file("file", "1\n2\n3\n")
+        .commit_on("a", "b")
+        .file("file", "1\n2\n3b\n")
+        .commit_on("b", "source")
+        .file("file", "1\n2x\n3b\n")
+        .build();
+    let repo = fixture.repo();
+    let (a, b) = (fixture.commit("a"), fixture.commit("b"));
+    let source = fixture.commit("source");
+
+    let mut plan = plan_absorb(repo.as_ref(), source, &ids(&[b, a]), &EverythingMatcher).unwrap();
+    assert_eq!(plan.hunks.len(), 1);
+    drop_conflicting_hunks(repo.as_ref(), source, &mut plan).unwrap();
+    assert_eq!(plan.hunks, vec![]);
+    assert_eq!(
+        plan.skipped,
+        vec![SkippedHunk {
+            path: RepoPath::from_internal_string("file").to_owned(),
+            parent_lines: Some(1..2),
+            reason: AbsorbSkipReason::Conflict,
+        }]
+    );
+}
+
+#[test]
+fn test_absorb_from_merge() {
+    let fixture = TestRepoBuilder::new()
+        .commit("a")
+        .file("file", "1\n2\n3\n")
+        .commit("other")
+        .file("other", "1\n2\n")
+        .commit_with_parents(&["a", "other"], "source")
+        .file("file", "1\n2x\n3\n")
+        .file("other", "1x\n2\n")
+        .build();
+    let repo = fixture.repo();
+    let store = repo.store();
+    let (a, other) = (fixture.commit("a"), fixture.commit("other"));
+    let source = fixture.commit("source");
+
+    // The changes are relative to the merge of the parents, and each hunk goes
+    // into the parent that has the lines
+    let mut plan =
+        plan_absorb(repo.as_ref(), source, &ids(&[a, other]), &EverythingMatcher).unwrap();
+    drop_conflicting_hunks(repo.as_ref(), source, &mut plan).unwrap();
+    let destinations = plan
+        .hunks
+        .iter()
+        .map(|(id, hunk)| (id.clone(), hunk.path.clone(), hunk.parent_lines.clone()))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        destinations,
+        vec![
+            (
+                a.id().clone(),
+                RepoPath::from_internal_string("file").to_owned(),
+                1..2
+            ),
+            (
+                other.id().clone(),
+                RepoPath::from_internal_string("other").to_owned(),
+                0..1
+            ),
+        ]
+    );
+    assert_eq!(plan.skipped, vec![]);
+
+    let mut tx = repo.start_transaction(&fixture.settings);
+    let rebased = apply_absorb(tx.mut_repo(), &fixture.settings, source, &plan.hunks).unwrap();
+    let new_source = store.get_commit(&rebased[source.id()]).unwrap();
+    assert_eq!(new_source.tree_id(), source.tree_id());
+}
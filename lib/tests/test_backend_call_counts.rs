@@ -0,0 +1,527 @@
+// Copyright 2024 The Jujutsu Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Tests asserting how many backend calls common operations make. These guard
+//! against performance regressions that would not show up as test failures
+//! otherwise.
+
+use std::fs::File;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+
+use itertools::Itertools;
+use jj_lib::backend::{FileId, TreeValue};
+use jj_lib::git_backend::GitBackend;
+use jj_lib::matchers::{EverythingMatcher, FilesMatcher, Matcher, PrefixMatcher, Visit};
+use jj_lib::merge::Merge;
+use jj_lib::merged_tree::{MergedTree, MergedTreeBuilder};
+use jj_lib::repo::{ReadonlyRepo, Repo};
+use jj_lib::repo_path::{RepoPath, RepoPathBuf, RepoPathComponent};
+use jj_lib::tree::merge_trees;
+use testutils::instrumented_backend::{BackendCall, InstrumentedBackend};
+use testutils::{
+    create_synthetic_tree, create_tree, write_file, write_working_copy_file, TestRepo,
+    TestRepoBackend, TestWorkspace,
+};
+
+fn modify_file(
+    repo: &Arc<ReadonlyRepo>,
+    tree: &MergedTree,
+    path: &RepoPath,
+    contents: &str,
+) -> MergedTree {
+    let id = write_file(repo.store(), path, contents);
+    let mut tree_builder = MergedTreeBuilder::new(tree.id());
+    tree_builder.set_or_remove(
+        path.to_owned(),
+        Merge::normal(TreeValue::File {
+            id,
+            executable: false,
+        }),
+    );
+    let tree_id = tree_builder.write_tree(repo.store()).unwrap();
+    repo.store().get_root_tree(&tree_id).unwrap()
+}
+
+/// Counts the paths a matcher is asked about.
+struct CountingMatcher<'a> {
+    inner: &'a dyn Matcher,
+    num_calls: AtomicUsize,
+}
+
+impl<'a> CountingMatcher<'a> {
+    fn new(inner: &'a dyn Matcher) -> Self {
+        CountingMatcher {
+            inner,
+            num_calls: AtomicUsize::new(0),
+        }
+    }
+
+    fn take_num_calls(&self) -> usize {
+        self.num_calls.swap(0, Ordering::Relaxed)
+    }
+}
+
+impl Matcher for CountingMatcher<'_> {
+    fn matches(&self, file: &RepoPath) -> bool {
+        self.num_calls.fetch_add(1, Ordering::Relaxed);
+        self.inner.matches(file)
+    }
+
+    fn visit(&self, dir: &RepoPath) -> Visit {
+        self.num_calls.fetch_add(1, Ordering::Relaxed);
+        self.inner.visit(dir)
+    }
+}
+
+fn file_value(file_id: &FileId) -> TreeValue {
+    TreeValue::File {
+        id: file_id.clone(),
+        executable: false,
+    }
+}
+
+#[test]
+fn test_diff_identical_trees_reads_nothing() {
+    let test_repo = TestRepo::init();
+    let repo = &test_repo.repo;
+    let tree = create_synthetic_tree(repo, 10, 10, "a");
+
+    let (store, counts) = test_repo.instrumented_store();
+    let tree1 = store.get_root_tree(&tree.id()).unwrap();
+    let tree2 = store.get_root_tree(&tree.id()).unwrap();
+    counts.reset();
+    assert_eq!(tree1.diff(&tree2, &EverythingMatcher).count(), 0);
+    assert_eq!(counts.total(), 0);
+}
+
+#[test]
+fn test_diff_skips_identical_subtrees() {
+    let test_repo = TestRepo::init();
+    let repo = &test_repo.repo;
+    let tree = create_synthetic_tree(repo, 10, 10, "a");
+    let path = RepoPath::from_internal_string("dir3/file5");
+    let modified_tree = modify_file(repo, &tree, path, "modified\n");
+
+    let (store, counts) = test_repo.instrumented_store();
+    let tree1 = store.get_root_tree(&tree.id()).unwrap();
+    let tree2 = store.get_root_tree(&modified_tree.id()).unwrap();
+    counts.reset();
+    let diff = tree1
+        .diff(&tree2, &EverythingMatcher)
+        .map(|(path, _diff)| path)
+        .collect_vec();
+    assert_eq!(diff, vec![path.to_owned()]);
+    // Only "dir3" should be read from each side. The other 9 directories are
+    // identical and must be skipped without reading them.
+    assert_eq!(counts.get(BackendCall::ReadTree), 2);
+    assert_eq!(counts.get(BackendCall::ReadFile), 0);
+}
+
+#[test]
+fn test_entries_matching_reads_only_visited_trees() {
+    let test_repo = TestRepo::init();
+    let repo = &test_repo.repo;
+    let tree = create_synthetic_tree(repo, 10, 10, "a");
+
+    let (store, counts) = test_repo.instrumented_store();
+    let tree = store.get_root_tree(&tree.id()).unwrap();
+    counts.reset();
+    let matcher = PrefixMatcher::new([RepoPath::from_internal_string("dir3")]);
+    assert_eq!(tree.entries_matching(&matcher).count(), 10);
+    // Directories the matcher can't match anything in aren't read
+    assert_eq!(counts.get(BackendCall::ReadTree), 1);
+    assert_eq!(counts.get(BackendCall::ReadFile), 0);
+}
+
+#[test]
+fn test_entries_matching_files_visits_only_their_paths() {
+    let test_repo = TestRepo::init();
+    let repo = &test_repo.repo;
+    let tree = create_synthetic_tree(repo, 10, 10, "a");
+    let root_tree_id = tree.id().to_merge().into_resolved().unwrap();
+    let paths = ["dir3/file5", "dir7/file1"].map(RepoPathBuf::from_internal_string);
+    let files_matcher = FilesMatcher::new(&paths);
+    let matcher = CountingMatcher::new(&files_matcher);
+
+    // Only the directories of the files are read, and the matcher is only
+    // asked about the root, the two directories, and the two files. The other
+    // entries are skipped by the `Visit` of their parent directory.
+    let (store, counts) = test_repo.instrumented_store();
+    let merged_tree = store.get_root_tree(&tree.id()).unwrap();
+    counts.reset();
+    let entries = merged_tree
+        .entries_matching(&matcher)
+        .map(|(path, _value)| path)
+        .collect_vec();
+    assert_eq!(entries, paths);
+    assert_eq!(counts.get(BackendCall::ReadTree), 2);
+    assert_eq!(matcher.take_num_calls(), 5);
+
+    // Same for a single tree
+    let (store, counts) = test_repo.instrumented_store();
+    let tree = store.get_tree(RepoPath::root(), &root_tree_id).unwrap();
+    counts.reset();
+    let entries = tree
+        .entries_matching(&matcher)
+        .map(|(path, _value)| path)
+        .collect_vec();
+    assert_eq!(entries, paths);
+    assert_eq!(counts.get(BackendCall::ReadTree), 2);
+    assert_eq!(matcher.take_num_calls(), 5);
+}
+
+#[test]
+fn test_merge_disjoint_changes_reads_no_files() {
+    let test_repo = TestRepo::init();
+    let repo = &test_repo.repo;
+    let base = create_synthetic_tree(repo, 10, 10, "a");
+    let side1 = modify_file(
+        repo,
+        &base,
+        RepoPath::from_internal_string("dir1/file1"),
+        "side1\n",
+    );
+    let side2 = modify_file(
+        repo,
+        &base,
+        RepoPath::from_internal_string("dir2/file2"),
+        "side2\n",
+    );
+
+    let (store, counts) = test_repo.instrumented_store();
+    let base = store.get_root_tree(&base.id()).unwrap();
+    let side1 = store.get_root_tree(&side1.id()).unwrap();
+    let side2 = store.get_root_tree(&side2.id()).unwrap();
+    counts.reset();
+    let merged = side1.merge(&base, &side2).unwrap();
+    assert!(merged.id().to_merge().is_resolved());
+    // Changes to different directories can be merged at the directory level
+    // without looking at any file contents.
+    assert_eq!(counts.get(BackendCall::ReadFile), 0);
+}
+
+#[test]
+fn test_merge_reads_only_trees_on_touched_paths() {
+    let test_repo = TestRepo::init();
+    let repo = &test_repo.repo;
+    let paths = (0..10)
+        .flat_map(|i| {
+            (0..10).map(move |j| RepoPathBuf::from_internal_string(format!("dir{i}/sub{j}/file")))
+        })
+        .collect_vec();
+    let path_contents = paths
+        .iter()
+        .map(|path| (path.as_ref(), "base\n"))
+        .collect_vec();
+    let base = create_tree(repo, &path_contents);
+    let side1 = modify_file(
+        repo,
+        &base,
+        RepoPath::from_internal_string("dir1/sub1/file"),
+        "side1\n",
+    );
+    let side2 = modify_file(
+        repo,
+        &base,
+        RepoPath::from_internal_string("dir1/sub2/file"),
+        "side2\n",
+    );
+
+    let (store, counts) = test_repo.instrumented_store();
+    let base = store.get_root_tree(&base.id()).unwrap();
+    let side1 = store.get_root_tree(&side1.id()).unwrap();
+    let side2 = store.get_root_tree(&side2.id()).unwrap();
+    counts.reset();
+    let merged = side1.merge(&base, &side2).unwrap();
+    assert!(merged.id().to_merge().is_resolved());
+    // Only "dir1" needs to be read from each side. Its subdirectories changed on
+    // at most one side, so they can be resolved by comparing their ids.
+    assert_eq!(counts.get(BackendCall::ReadTree), 3);
+    assert_eq!(counts.get(BackendCall::ReadFile), 0);
+}
+
+#[test]
+fn test_tree_conflict_flag_through_nested_writes() {
+    let test_repo = TestRepo::init();
+    let store = test_repo.repo.store();
+    let conflict_path = RepoPath::from_internal_string("dir1/dir2/file");
+    let base_id = write_file(store, conflict_path, "base\n");
+    let left_id = write_file(store, conflict_path, "left\n");
+    let right_id = write_file(store, conflict_path, "right\n");
+    let conflict = Merge::from_removes_adds(
+        vec![Some(file_value(&base_id))],
+        vec![Some(file_value(&left_id)), Some(file_value(&right_id))],
+    );
+    let conflict_id = store.write_conflict(conflict_path, &conflict).unwrap();
+    let other_path = RepoPath::from_internal_string("dir3/file");
+    let other_id = write_file(store, other_path, "other\n");
+    let mut tree_builder = store.tree_builder(store.empty_tree_id().clone());
+    tree_builder.set(conflict_path.to_owned(), TreeValue::Conflict(conflict_id));
+    tree_builder.set(other_path.to_owned(), file_value(&other_id));
+    let tree_id = tree_builder.write_tree().unwrap();
+
+    // The flag is recorded in each tree, so nothing needs to be walked
+    let (store, counts) = test_repo.instrumented_store();
+    let tree = store.get_tree(RepoPath::root(), &tree_id).unwrap();
+    let dir1 = tree.sub_tree(RepoPathComponent::new("dir1")).unwrap();
+    let dir3 = tree.sub_tree(RepoPathComponent::new("dir3")).unwrap();
+    counts.reset();
+    assert_eq!(tree.maybe_has_conflict(), Some(true));
+    assert_eq!(dir1.maybe_has_conflict(), Some(true));
+    assert_eq!(dir3.maybe_has_conflict(), Some(false));
+    assert!(tree.has_conflict());
+    assert!(dir3.conflicts().is_empty());
+    assert_eq!(counts.get(BackendCall::ReadTree), 0);
+
+    // Resolving the conflict clears the flag of all the parent trees
+    let mut tree_builder = store.tree_builder(tree_id);
+    tree_builder.set(conflict_path.to_owned(), file_value(&left_id));
+    let resolved_id = tree_builder.write_tree().unwrap();
+    let resolved = store.get_tree(RepoPath::root(), &resolved_id).unwrap();
+    assert_eq!(resolved.maybe_has_conflict(), Some(false));
+    assert!(resolved.conflicts().is_empty());
+
+    // Merging trees sets it again
+    let mut tree_builder = store.tree_builder(resolved_id.clone());
+    tree_builder.set(conflict_path.to_owned(), file_value(&base_id));
+    let side1_id = tree_builder.write_tree().unwrap();
+    let mut tree_builder = store.tree_builder(resolved_id);
+    tree_builder.set(conflict_path.to_owned(), file_value(&right_id));
+    let side2_id = tree_builder.write_tree().unwrap();
+    let side1 = store.get_tree(RepoPath::root(), &side1_id).unwrap();
+    let side2 = store.get_tree(RepoPath::root(), &side2_id).unwrap();
+    let merged = merge_trees(&side1, &resolved, &side2).unwrap();
+    assert_eq!(merged.maybe_has_conflict(), Some(true));
+    assert_eq!(merged.conflicts().len(), 1);
+}
+
+#[test]
+fn test_tree_builder_conflict_flag_reads_only_touched_trees() {
+    let test_repo = TestRepo::init();
+    let repo = &test_repo.repo;
+    let tree = create_synthetic_tree(repo, 10, 10, "a");
+    let tree_id = tree.id().to_merge().as_resolved().unwrap().clone();
+    let path = RepoPath::from_internal_string("dir3/file5");
+    let base_id = write_file(repo.store(), path, "base\n");
+    let left_id = write_file(repo.store(), path, "left\n");
+    let right_id = write_file(repo.store(), path, "right\n");
+
+    // The base trees have no conflicts, so neither have the unchanged sibling
+    // trees, which aren't read
+    let (store, counts) = test_repo.instrumented_store();
+    let mut tree_builder = store.tree_builder(tree_id.clone());
+    tree_builder.set(path.to_owned(), file_value(&left_id));
+    let resolved_id = tree_builder.write_tree().unwrap();
+    assert_eq!(counts.get(BackendCall::ReadTree), 2);
+    let resolved = store.get_tree(RepoPath::root(), &resolved_id).unwrap();
+    assert_eq!(resolved.maybe_has_conflict(), Some(false));
+
+    // Adding a conflict sets the flag of the parent trees
+    let (store, counts) = test_repo.instrumented_store();
+    let conflict = Merge::from_removes_adds(
+        vec![Some(file_value(&base_id))],
+        vec![Some(file_value(&left_id)), Some(file_value(&right_id))],
+    );
+    let conflict_id = store.write_conflict(path, &conflict).unwrap();
+    let mut tree_builder = store.tree_builder(tree_id);
+    tree_builder.set(path.to_owned(), TreeValue::Conflict(conflict_id));
+    let conflicted_id = tree_builder.write_tree().unwrap();
+    assert_eq!(counts.get(BackendCall::ReadTree), 2);
+    let conflicted = store.get_tree(RepoPath::root(), &conflicted_id).unwrap();
+    assert_eq!(conflicted.maybe_has_conflict(), Some(true));
+}
+
+#[test]
+fn test_git_tree_conflict_flag_is_memoized() {
+    let settings = testutils::user_settings();
+    let test_repo = TestRepo::init_with_backend(TestRepoBackend::Git);
+    let repo = &test_repo.repo;
+    let tree = create_synthetic_tree(repo, 10, 10, "a");
+    let path = RepoPath::from_internal_string("dir3/file5");
+    let modified_tree = modify_file(repo, &tree, path, "modified\n");
+    let root_tree_id = |tree: &MergedTree| tree.id().to_merge().as_resolved().unwrap().clone();
+
+    let store_path = repo.repo_path().join("store");
+    let backend = GitBackend::load(&settings, &store_path).unwrap();
+    let (store, counts) = InstrumentedBackend::new(Box::new(backend))
+        .into_store(repo.store().use_tree_conflict_format());
+    let tree = store
+        .get_tree(RepoPath::root(), &root_tree_id(&tree))
+        .unwrap();
+    let modified_tree = store
+        .get_tree(RepoPath::root(), &root_tree_id(&modified_tree))
+        .unwrap();
+    // Git trees don't record the flag, so the first check walks the tree
+    assert_eq!(tree.maybe_has_conflict(), None);
+    counts.reset();
+    assert!(!tree.has_conflict());
+    assert_eq!(counts.get(BackendCall::ReadTree), 10);
+    assert_eq!(tree.maybe_has_conflict(), Some(false));
+
+    // Later checks reuse the result for each tree id
+    counts.reset();
+    assert!(!tree.has_conflict());
+    assert!(tree.conflicts().is_empty());
+    assert_eq!(counts.get(BackendCall::ReadTree), 0);
+    // Only the modified subtree needs to be read
+    assert!(!modified_tree.has_conflict());
+    assert_eq!(counts.get(BackendCall::ReadTree), 1);
+}
+
+#[test]
+fn test_snapshot_after_touching_one_file_hashes_one_file() {
+    let settings = testutils::user_settings();
+    let (mut test_workspace, counts) = TestWorkspace::init_instrumented(&settings);
+    let workspace_root = test_workspace.workspace.workspace_root().clone();
+
+    let paths = (0..20)
+        .map(|i| RepoPathBuf::from_internal_string(format!("dir{}/file{i}", i % 4)))
+        .collect_vec();
+    for path in &paths {
+        write_working_copy_file(&workspace_root, path, "initial");
+    }
+    // Make sure the files' mtimes are older than the working-copy state file's,
+    // or they would be considered possibly modified and be hashed again.
+    let old_mtime = SystemTime::UNIX_EPOCH + Duration::from_secs(1_000_000_000);
+    for path in &paths {
+        let file = File::options()
+            .write(true)
+            .open(path.to_fs_path(&workspace_root))
+            .unwrap();
+        file.set_modified(old_mtime).unwrap();
+    }
+    test_workspace.snapshot().unwrap();
+    assert_eq!(counts.get(BackendCall::WriteFile), paths.len());
+
+    counts.reset();
+    write_working_copy_file(&workspace_root, &paths[7], "modified contents");
+    test_workspace.snapshot().unwrap();
+    assert_eq!(counts.get(BackendCall::WriteFile), 1);
+    assert_eq!(counts.get(BackendCall::ReadFile), 0);
+    // Only the modified file's directory and the root tree are written. The
+    // other directories keep their subtree ids.
+    assert_eq!(counts.get(BackendCall::WriteTree), 2);
+}
+
+#[test]
+fn test_merged_parent_tree_merges_each_parent_set_once() {
+    let settings = testutils::user_settings();
+    let (test_workspace, counts) = TestWorkspace::init_instrumented(&settings);
+    let repo = &test_workspace.repo;
+    let path_a = RepoPath::from_internal_string("a");
+    let path_b = RepoPath::from_internal_string("b");
+    let base_tree = create_tree(repo, &[(path_a, "base\n"), (path_b, "base\n")]);
+    let tree_a = modify_file(repo, &base_tree, path_a, "a\n");
+    let tree_b = modify_file(repo, &base_tree, path_b, "b\n");
+
+    // Several merge commits of the same parents, as in a history where the same
+    // branches were merged repeatedly
+    let mut tx = repo.start_transaction(&settings);
+    let mut_repo = tx.mut_repo();
+    let root_id = repo.store().root_commit_id().clone();
+    let mut new_commit = |parent_ids: Vec<_>, tree: &MergedTree| {
+        mut_repo
+            .new_commit(&settings, parent_ids, tree.id())
+            .write()
+            .unwrap()
+    };
+    let base = new_commit(vec![root_id], &base_tree);
+    let commit_a = new_commit(vec![base.id().clone()], &tree_a);
+    let commit_b = new_commit(vec![base.id().clone()], &tree_b);
+    let merge_ab = vec![commit_a.id().clone(), commit_b.id().clone()];
+    let merged_tree = tree_a.merge(&base_tree, &tree_b).unwrap();
+    let merges = (0..3)
+        .map(|_| new_commit(merge_ab.clone(), &merged_tree))
+        .collect_vec();
+    let repo = tx.commit("test").unwrap();
+
+    // Render the diffs of all the merge commits twice, like `jj log -p` would
+    // for a long history.
+    counts.reset();
+    let first_tree = repo.merged_parent_tree(&merges[0]).unwrap();
+    assert_eq!(first_tree.id(), merged_tree.id());
+    let merge_write_count = counts.get(BackendCall::WriteTree);
+    assert!(merge_write_count > 0);
+    for _ in 0..2 {
+        for commit in &merges {
+            let parent_tree = repo.merged_parent_tree(commit).unwrap();
+            assert_eq!(
+                parent_tree
+                    .diff(&commit.tree().unwrap(), &EverythingMatcher)
+                    .count(),
+                0
+            );
+        }
+    }
+    assert_eq!(counts.get(BackendCall::WriteTree), merge_write_count);
+
+    // A new repo instance doesn't reuse the cached trees
+    let repo = repo.reload_at(repo.operation()).unwrap();
+    counts.reset();
+    repo.merged_parent_tree(&merges[1]).unwrap();
+    assert_eq!(counts.get(BackendCall::WriteTree), merge_write_count);
+}
+
+#[test]
+fn test_diff_summary_diffs_each_commit_once() {
+    let settings = testutils::user_settings();
+    let (test_workspace, counts) = TestWorkspace::init_instrumented(&settings);
+    let repo = &test_workspace.repo;
+    let path_a = RepoPath::from_internal_string("dir/a");
+    let path_b = RepoPath::from_internal_string("dir/b");
+    let tree1 = create_tree(repo, &[(path_a, "1\n")]);
+    let tree2 = modify_file(repo, &tree1, path_b, "2\n");
+    let tree3 = modify_file(repo, &tree2, path_a, "3\n");
+
+    let mut tx = repo.start_transaction(&settings);
+    let mut_repo = tx.mut_repo();
+    let mut parent_id = repo.store().root_commit_id().clone();
+    let mut commits = vec![];
+    for tree in [&tree1, &tree2, &tree3] {
+        let commit = mut_repo
+            .new_commit(&settings, vec![parent_id], tree.id())
+            .write()
+            .unwrap();
+        parent_id = commit.id().clone();
+        commits.push(commit);
+    }
+    let repo = tx.commit("test").unwrap();
+
+    let summaries = commits
+        .iter()
+        .map(|commit| repo.diff_summary(commit).unwrap())
+        .collect_vec();
+    let files = summaries
+        .iter()
+        .map(|summary| summary.paths().map(|path| path.to_owned()).collect_vec())
+        .collect_vec();
+    assert_eq!(
+        files,
+        vec![
+            vec![path_a.to_owned()],
+            vec![path_b.to_owned()],
+            vec![path_a.to_owned()],
+        ]
+    );
+
+    // Listing the files again, as a log template would for every commit it
+    // renders, reuses the summaries without diffing the trees.
+    counts.reset();
+    for (commit, summary) in commits.iter().zip(&summaries) {
+        assert!(Arc::ptr_eq(&repo.diff_summary(commit).unwrap(), summary));
+    }
+    assert_eq!(counts.total(), 0);
+}
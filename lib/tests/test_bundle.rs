@@ -0,0 +1,160 @@
+// Copyright 2024 The Jujutsu Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::sync::Arc;
+
+use assert_matches::assert_matches;
+use jj_lib::backend::TreeValue;
+use jj_lib::bundle::{apply_bundle, write_bundle, AppliedBundle, BundleError};
+use jj_lib::commit::Commit;
+use jj_lib::repo::{ReadonlyRepo, Repo};
+use jj_lib::repo_path::RepoPath;
+use test_case::test_case;
+use testutils::{create_tree, TestRepo, TestRepoBackend};
+
+fn create_bundle(repo: &Arc<ReadonlyRepo>, commits: &[Commit]) -> Vec<u8> {
+    let mut bundle = vec![];
+    write_bundle(repo.store(), commits, &mut bundle).unwrap();
+    bundle
+}
+
+fn apply(repo: &Arc<ReadonlyRepo>, bundle: &[u8]) -> (Arc<ReadonlyRepo>, AppliedBundle) {
+    let settings = testutils::user_settings();
+    let mut tx = repo.start_transaction(&settings);
+    let applied = apply_bundle(tx.mut_repo(), &mut &bundle[..]).unwrap();
+    (tx.commit("apply bundle").unwrap(), applied)
+}
+
+#[test_case(TestRepoBackend::Local, TestRepoBackend::Local ; "local to local")]
+#[test_case(TestRepoBackend::Git, TestRepoBackend::Git ; "git to git")]
+#[test_case(TestRepoBackend::Git, TestRepoBackend::Local ; "git to local")]
+#[test_case(TestRepoBackend::Local, TestRepoBackend::Git ; "local to git")]
+fn test_bundle_round_trip(source_backend: TestRepoBackend, dest_backend: TestRepoBackend) {
+    let settings = testutils::user_settings();
+    let source_repo = TestRepo::init_with_backend(source_backend);
+    let repo = &source_repo.repo;
+    let file_path = RepoPath::from_internal_string("dir/file");
+    let tree1 = create_tree(repo, &[(file_path, "1")]);
+    let tree2 = create_tree(repo, &[(file_path, "2")]);
+
+    let mut tx = repo.start_transaction(&settings);
+    let commit1 = tx
+        .mut_repo()
+        .new_commit(
+            &settings,
+            vec![repo.store().root_commit_id().clone()],
+            tree1.id(),
+        )
+        .set_description("commit 1")
+        .write()
+        .unwrap();
+    let commit2 = tx
+        .mut_repo()
+        .new_commit(&settings, vec![commit1.id().clone()], tree2.id())
+        .set_description("commit 2")
+        .write()
+        .unwrap();
+    tx.commit("test").unwrap();
+    let bundle = create_bundle(repo, &[commit1.clone(), commit2.clone()]);
+
+    let dest_repo = TestRepo::init_with_backend(dest_backend);
+    let (repo, applied) = apply(&dest_repo.repo, &bundle);
+    assert_eq!(applied.stats.num_commits, 2);
+    assert_eq!(applied.stats.num_existing_objects, 0);
+    assert_eq!(applied.heads.len(), 1);
+    let new_commit2 = &applied.heads[0];
+    assert!(repo.view().heads().contains(new_commit2.id()));
+    assert_eq!(new_commit2.change_id(), commit2.change_id());
+    assert_eq!(new_commit2.description(), "commit 2\n");
+    assert_eq!(new_commit2.author(), commit2.author());
+    assert_matches!(
+        new_commit2.tree().unwrap().path_value(file_path).into_resolved(),
+        Ok(Some(TreeValue::File { id, .. }))
+            if testutils::read_file(repo.store(), file_path, &id) == b"2"
+    );
+    let new_commit1 = &new_commit2.parents()[0];
+    assert_eq!(new_commit1.change_id(), commit1.change_id());
+    assert_eq!(new_commit1.description(), "commit 1\n");
+    assert_eq!(
+        new_commit1.parent_ids(),
+        &[repo.store().root_commit_id().clone()]
+    );
+    if source_backend == dest_backend {
+        assert_eq!(new_commit2.id(), commit2.id());
+        assert_eq!(new_commit1.id(), commit1.id());
+    }
+}
+
+#[test]
+fn test_bundle_existing_objects() {
+    let settings = testutils::user_settings();
+    let source_repo = TestRepo::init_with_backend(TestRepoBackend::Local);
+    let repo = &source_repo.repo;
+    let file_path = RepoPath::from_internal_string("file");
+    let tree1 = create_tree(repo, &[(file_path, "1")]);
+    let tree2 = create_tree(repo, &[(file_path, "2")]);
+
+    let mut tx = repo.start_transaction(&settings);
+    let commit1 = tx
+        .mut_repo()
+        .new_commit(
+            &settings,
+            vec![repo.store().root_commit_id().clone()],
+            tree1.id(),
+        )
+        .write()
+        .unwrap();
+    let commit2 = tx
+        .mut_repo()
+        .new_commit(&settings, vec![commit1.id().clone()], tree2.id())
+        .write()
+        .unwrap();
+    tx.commit("test").unwrap();
+
+    // The destination gets the first commit on its own first
+    let dest_repo = TestRepo::init_with_backend(TestRepoBackend::Local);
+    let (dest, _) = apply(&dest_repo.repo, &create_bundle(repo, &[commit1.clone()]));
+
+    let (dest, applied) = apply(
+        &dest,
+        &create_bundle(repo, &[commit1.clone(), commit2.clone()]),
+    );
+    // commit1, its tree, and its file were already there
+    assert_eq!(applied.stats.num_commits, 2);
+    assert_eq!(applied.stats.num_objects, 6);
+    assert_eq!(applied.stats.num_existing_objects, 3);
+    assert_eq!(applied.heads.len(), 1);
+    assert_eq!(applied.heads[0].id(), commit2.id());
+    assert!(dest.view().heads().contains(commit2.id()));
+
+    // A bundle whose parent isn't in the repo can't be applied
+    let other_repo = TestRepo::init_with_backend(TestRepoBackend::Local);
+    let bundle = create_bundle(repo, &[commit2.clone()]);
+    let mut tx = other_repo.repo.start_transaction(&settings);
+    assert_matches!(
+        apply_bundle(tx.mut_repo(), &mut &bundle[..]),
+        Err(BundleError::MissingPrerequisite(id)) if id == commit1.id().hex()
+    );
+}
+
+#[test]
+fn test_bundle_not_a_bundle() {
+    let settings = testutils::user_settings();
+    let test_repo = TestRepo::init_with_backend(TestRepoBackend::Local);
+    let mut tx = test_repo.repo.start_transaction(&settings);
+    assert_matches!(
+        apply_bundle(tx.mut_repo(), &mut &b"not a bundle"[..]),
+        Err(BundleError::NotABundle)
+    );
+}
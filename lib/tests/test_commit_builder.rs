@@ -12,12 +12,15 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use jj_lib::backend::{ChangeId, MillisSinceEpoch, Signature, Timestamp};
+use jj_lib::backend::{
+    ChangeId, MillisSinceEpoch, Signature, Timestamp, COPY_SOURCES_METADATA_KEY,
+};
 use jj_lib::matchers::EverythingMatcher;
 use jj_lib::merged_tree::DiffSummary;
 use jj_lib::repo::Repo;
 use jj_lib::repo_path::{RepoPath, RepoPathBuf};
 use jj_lib::settings::UserSettings;
+use maplit::btreemap;
 use test_case::test_case;
 use testutils::{assert_rebased_onto, create_tree, CommitGraphBuilder, TestRepo, TestRepoBackend};
 
@@ -75,11 +78,11 @@ fn test_initial(backend: TestRepoBackend) {
     assert_eq!(builder.author(), &author_signature);
     assert_eq!(builder.committer(), &committer_signature);
     let commit = builder.write().unwrap();
-    tx.commit("test");
+    tx.commit("test").unwrap();
 
     assert_eq!(commit.parents(), vec![store.root_commit()]);
     assert_eq!(commit.predecessors(), vec![]);
-    assert_eq!(commit.description(), "description");
+    assert_eq!(commit.description(), "description\n");
     assert_eq!(commit.author(), &author_signature);
     assert_eq!(commit.committer(), &committer_signature);
     assert_eq!(
@@ -125,7 +128,7 @@ fn test_rewrite(backend: TestRepoBackend) {
         )
         .write()
         .unwrap();
-    let repo = tx.commit("test");
+    let repo = tx.commit("test").unwrap();
 
     let rewritten_tree = create_tree(
         &repo,
@@ -151,7 +154,7 @@ fn test_rewrite(backend: TestRepoBackend) {
         .write()
         .unwrap();
     tx.mut_repo().rebase_descendants(&settings).unwrap();
-    tx.commit("test");
+    tx.commit("test").unwrap();
     assert_eq!(rewritten_commit.parents(), vec![store.root_commit()]);
     assert_eq!(
         rewritten_commit.predecessors(),
@@ -244,6 +247,271 @@ fn test_rewrite_update_missing_user(backend: TestRepoBackend) {
     );
 }
 
+// Author and committer timestamps are distinct, and only the latter should be
+// updated on rewrite
+#[test_case(TestRepoBackend::Local ; "local backend")]
+#[test_case(TestRepoBackend::Git ; "git backend")]
+fn test_rewrite_distinct_timestamps(backend: TestRepoBackend) {
+    let settings = testutils::user_settings();
+    let test_repo = TestRepo::init_with_backend(backend);
+    let repo = &test_repo.repo;
+
+    let author = Signature {
+        name: "Author".to_string(),
+        email: "author@example.com".to_string(),
+        timestamp: Timestamp {
+            timestamp: MillisSinceEpoch(1_700_000_000_000),
+            tz_offset: 60,
+        },
+    };
+    let committer = Signature {
+        name: "Committer".to_string(),
+        email: "committer@example.com".to_string(),
+        timestamp: Timestamp {
+            timestamp: MillisSinceEpoch(1_700_000_100_000),
+            tz_offset: -480,
+        },
+    };
+    let mut tx = repo.start_transaction(&settings);
+    let initial_commit = tx
+        .mut_repo()
+        .new_commit(
+            &settings,
+            vec![repo.store().root_commit_id().clone()],
+            repo.store().empty_merged_tree_id(),
+        )
+        .set_author(author.clone())
+        .set_committer(committer.clone())
+        .write()
+        .unwrap();
+    assert_eq!(initial_commit.author(), &author);
+    assert_eq!(initial_commit.committer(), &committer);
+
+    let config = config::Config::builder()
+        .set_override("user.name", "Rewrite User")
+        .unwrap()
+        .set_override("user.email", "rewrite.user@example.com")
+        .unwrap()
+        .set_override("debug.commit-timestamp", "2024-02-01T10:00:00+05:30")
+        .unwrap()
+        .build()
+        .unwrap();
+    let rewrite_settings = UserSettings::from_config(config);
+    let rewritten_commit = tx
+        .mut_repo()
+        .rewrite_commit(&rewrite_settings, &initial_commit)
+        .set_description("rewritten")
+        .write()
+        .unwrap();
+    let repo = tx.commit("test").unwrap();
+
+    let rewritten_commit = repo.store().get_commit(rewritten_commit.id()).unwrap();
+    assert_eq!(rewritten_commit.author(), &author);
+    assert_eq!(
+        rewritten_commit.committer().timestamp,
+        Timestamp {
+            timestamp: MillisSinceEpoch(1_706_761_800_000),
+            tz_offset: 330,
+        }
+    );
+    assert_ne!(
+        rewritten_commit.committer().timestamp,
+        rewritten_commit.author().timestamp
+    );
+}
+
+#[test_case(TestRepoBackend::Local ; "local backend")]
+#[test_case(TestRepoBackend::Git ; "git backend")]
+fn test_rewrite_copy_sources(backend: TestRepoBackend) {
+    let settings = testutils::user_settings();
+    let test_repo = TestRepo::init_with_backend(backend);
+    let repo = &test_repo.repo;
+
+    let source = RepoPath::from_internal_string("source");
+    let copy1 = RepoPath::from_internal_string("copy1");
+    let copy2 = RepoPath::from_internal_string("dir/copy2");
+    let tree = create_tree(repo, &[(source, "a"), (copy1, "a"), (copy2, "a")]);
+    let copy_sources = btreemap! {
+        copy1.to_owned() => source.to_owned(),
+        copy2.to_owned() => source.to_owned(),
+    };
+    let mut tx = repo.start_transaction(&settings);
+    let initial_commit = tx
+        .mut_repo()
+        .new_commit(
+            &settings,
+            vec![repo.store().root_commit_id().clone()],
+            tree.id(),
+        )
+        .set_copy_sources(&copy_sources)
+        .write()
+        .unwrap();
+    tx.commit("test").unwrap();
+    let initial_commit = repo.store().get_commit(initial_commit.id()).unwrap();
+    assert_eq!(initial_commit.copy_sources(), copy_sources);
+
+    // The sources are kept for copies that still exist after a rewrite
+    let repo = test_repo.repo.reload_at_head(&settings).unwrap();
+    let mut tx = repo.start_transaction(&settings);
+    let rewritten_commit = tx
+        .mut_repo()
+        .rewrite_commit(&settings, &initial_commit)
+        .set_description("rewritten")
+        .write()
+        .unwrap();
+    assert_eq!(rewritten_commit.copy_sources(), copy_sources);
+
+    let tree = create_tree(&repo, &[(source, "a"), (copy2, "b")]);
+    let rewritten_commit = tx
+        .mut_repo()
+        .rewrite_commit(&settings, &rewritten_commit)
+        .set_tree_id(tree.id())
+        .write()
+        .unwrap();
+    assert_eq!(
+        rewritten_commit.copy_sources(),
+        btreemap! { copy2.to_owned() => source.to_owned() }
+    );
+
+    let tree = create_tree(&repo, &[(source, "a")]);
+    let rewritten_commit = tx
+        .mut_repo()
+        .rewrite_commit(&settings, &rewritten_commit)
+        .set_tree_id(tree.id())
+        .write()
+        .unwrap();
+    assert!(rewritten_commit.copy_sources().is_empty());
+    assert!(!rewritten_commit
+        .metadata()
+        .contains_key(COPY_SOURCES_METADATA_KEY));
+}
+
+#[test_case(None ; "default")]
+#[test_case(Some(true) ; "enabled")]
+#[test_case(Some(false) ; "disabled")]
+fn test_description_normalization(enabled: Option<bool>) {
+    let settings = match enabled {
+        None => testutils::user_settings(),
+        Some(enabled) => {
+            let config = testutils::base_config()
+                .add_source(config::File::from_str(
+                    &format!("ui.normalize-descriptions = {enabled}"),
+                    config::FileFormat::Toml,
+                ))
+                .build()
+                .unwrap();
+            UserSettings::from_config(config)
+        }
+    };
+    let test_repo = TestRepo::init();
+    let repo = &test_repo.repo;
+
+    let mut tx = repo.start_transaction(&settings);
+    let mut write_commit = |description: &str| {
+        tx.mut_repo()
+            .new_commit(
+                &settings,
+                vec![repo.store().root_commit_id().clone()],
+                repo.store().empty_merged_tree_id(),
+            )
+            .set_description(description)
+            .write()
+            .unwrap()
+    };
+    let descriptions = [
+        "",
+        " \n\n",
+        "subject",
+        "subject \n\nbody\t\n  \n\n",
+        "subject\r\nbody\r\n",
+    ];
+    let stored = descriptions.map(|description| write_commit(description).description().to_owned());
+    // Descriptions are normalized by default
+    if enabled != Some(false) {
+        assert_eq!(
+            stored,
+            ["", "", "subject\n", "subject\n\nbody\n", "subject\nbody\n"]
+        );
+    } else {
+        assert_eq!(stored, descriptions);
+    }
+}
+
+#[test_case(true ; "enabled")]
+#[test_case(false ; "disabled")]
+fn test_monotonic_commit_timestamps(enabled: bool) {
+    let settings_at = |timestamp: &str| {
+        let config = testutils::base_config()
+            .set_override("debug.commit-timestamp", timestamp)
+            .unwrap()
+            .set_override("timestamps.monotonic-commits", enabled)
+            .unwrap()
+            .build()
+            .unwrap();
+        UserSettings::from_config(config)
+    };
+    let parent_settings = settings_at("2024-02-01T10:00:00+05:30");
+    // The clock went back by a day
+    let child_settings = settings_at("2024-01-31T10:00:00+07:00");
+    let test_repo = TestRepo::init();
+    let repo = &test_repo.repo;
+
+    let mut tx = repo.start_transaction(&parent_settings);
+    let parent = tx
+        .mut_repo()
+        .new_commit(
+            &parent_settings,
+            vec![repo.store().root_commit_id().clone()],
+            repo.store().empty_merged_tree_id(),
+        )
+        .write()
+        .unwrap();
+    let child = tx
+        .mut_repo()
+        .new_commit(
+            &child_settings,
+            vec![parent.id().clone()],
+            repo.store().empty_merged_tree_id(),
+        )
+        .write()
+        .unwrap();
+    let rewritten_parent = tx
+        .mut_repo()
+        .rewrite_commit(&child_settings, &parent)
+        .set_description("rewritten")
+        .write()
+        .unwrap();
+
+    let child_time = MillisSinceEpoch(1_706_670_000_000);
+    let parent_time = MillisSinceEpoch(1_706_761_800_000);
+    // The author timestamp is kept, and so is the time zone of the committer
+    assert_eq!(child.author().timestamp.timestamp, child_time);
+    assert_eq!(child.committer().timestamp.tz_offset, 420);
+    // Descriptions are normalized by default
+    if enabled != Some(false) {
+        assert_eq!(child.committer().timestamp.timestamp, parent_time);
+    } else {
+        assert_eq!(child.committer().timestamp.timestamp, child_time);
+    }
+    // Rewriting a commit doesn't clamp it to its old timestamp, only to its
+    // parents'
+    assert_eq!(rewritten_parent.committer().timestamp.timestamp, child_time);
+
+    // An explicitly set committer is kept as is
+    let committer = child_settings.signature();
+    let commit = tx
+        .mut_repo()
+        .new_commit(
+            &parent_settings,
+            vec![parent.id().clone()],
+            repo.store().empty_merged_tree_id(),
+        )
+        .set_committer(committer.clone())
+        .write()
+        .unwrap();
+    assert_eq!(commit.committer(), &committer);
+}
+
 #[test_case(TestRepoBackend::Local ; "local backend")]
 // #[test_case(TestRepoBackend::Git ; "git backend")]
 fn test_commit_builder_descendants(backend: TestRepoBackend) {
@@ -257,7 +525,7 @@ fn test_commit_builder_descendants(backend: TestRepoBackend) {
     let commit1 = graph_builder.initial_commit();
     let commit2 = graph_builder.commit_with_parents(&[&commit1]);
     let commit3 = graph_builder.commit_with_parents(&[&commit2]);
-    let repo = tx.commit("test");
+    let repo = tx.commit("test").unwrap();
 
     // Test with for_new_commit()
     let mut tx = repo.start_transaction(&settings);
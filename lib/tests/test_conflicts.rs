@@ -12,9 +12,10 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use jj_lib::backend::FileId;
+use jj_lib::backend::{FileId, TreeValue};
 use jj_lib::conflicts::{
-    extract_as_single_hunk, materialize_merge_result, parse_conflict, update_from_content,
+    describe_parts, extract_as_single_hunk, materialize_merge_result,
+    materialize_merge_result_with_labels, parse_conflict, read_part_content, update_from_content,
 };
 use jj_lib::merge::Merge;
 use jj_lib::repo::Repo;
@@ -740,6 +741,114 @@ fn test_update_conflict_from_content_modify_delete() {
     );
 }
 
+#[test]
+fn test_describe_conflict_parts() {
+    let test_repo = TestRepo::init();
+    let store = test_repo.repo.store();
+
+    let path = RepoPath::from_internal_string("file");
+    let base_id = testutils::write_file(store, path, "base\n");
+    let left_id = testutils::write_file(store, path, "left\n");
+    let file_value = |id: &FileId| {
+        Some(TreeValue::File {
+            id: id.clone(),
+            executable: false,
+        })
+    };
+    // A modify/delete conflict
+    let conflict =
+        Merge::from_removes_adds(vec![file_value(&base_id)], vec![file_value(&left_id), None]);
+    let parts = describe_parts(&conflict, None);
+    assert_eq!(
+        parts
+            .iter()
+            .map(|part| (part.label.as_str(), part.is_side, part.value.clone()))
+            .collect::<Vec<_>>(),
+        vec![
+            ("side 1", true, file_value(&left_id)),
+            ("base 1", false, file_value(&base_id)),
+            ("side 2", true, None),
+        ]
+    );
+    let contents = parts
+        .iter()
+        .map(|part| read_part_content(store, path, part).block_on().unwrap())
+        .collect::<Vec<_>>();
+    assert_eq!(
+        contents,
+        vec![
+            Some(b"left\n".to_vec()),
+            Some(b"base\n".to_vec()),
+            Some(vec![]),
+        ]
+    );
+
+    // Non-empty labels replace the positions
+    let labels = Merge::from_removes_adds(
+        vec!["base".to_owned()],
+        vec!["left".to_owned(), "".to_owned()],
+    );
+    let parts = describe_parts(&conflict, Some(&labels));
+    assert_eq!(
+        parts
+            .iter()
+            .map(|part| part.label.as_str())
+            .collect::<Vec<_>>(),
+        vec!["left", "base", "side 2"]
+    );
+}
+
+#[test]
+fn test_materialize_conflict_with_labels() {
+    let test_repo = TestRepo::init();
+    let store = test_repo.repo.store();
+
+    let path = RepoPath::from_internal_string("file");
+    let base_id = testutils::write_file(store, path, "line 1\nline 2\nline 3\n");
+    let left_id = testutils::write_file(store, path, "line 1\nleft\nline 3\n");
+    let right_id = testutils::write_file(store, path, "line 1\nright\nline 3\n");
+    let conflict = Merge::from_removes_adds(
+        vec![Some(base_id.clone())],
+        vec![Some(left_id.clone()), Some(right_id.clone())],
+    );
+    let labels = Merge::from_removes_adds(
+        vec!["parents of rebased commit".to_owned()],
+        vec!["rebase destination".to_owned(), "rebased commit".to_owned()],
+    );
+    let contents = extract_as_single_hunk(&conflict, store, path).block_on();
+    let mut materialized = vec![];
+    materialize_merge_result_with_labels(&contents, Some(&labels), &mut materialized).unwrap();
+    insta::assert_snapshot!(String::from_utf8(materialized.clone()).unwrap(), @r###"
+    line 1
+    <<<<<<<
+    %%%%%%% diff from parents of rebased commit to rebase destination
+    -line 2
+    +left
+    +++++++ rebased commit
+    right
+    >>>>>>>
+    line 3
+    "###);
+    // The labels don't prevent the markers from being parsed
+    assert_eq!(
+        parse_conflict(&materialized, 2),
+        parse_conflict(
+            materialize_conflict_string(store, path, &conflict).as_bytes(),
+            2
+        )
+    );
+    assert!(parse_conflict(&materialized, 2).is_some());
+
+    // Labels that don't match the conflict are ignored
+    let mut materialized = vec![];
+    let labels = Merge::resolved("label".to_owned());
+    materialize_merge_result_with_labels(&contents, Some(&labels), &mut materialized).unwrap();
+    assert_eq!(
+        String::from_utf8(materialized).unwrap(),
+        materialize_conflict_string(store, path, &conflict)
+    );
+}
+
 fn materialize_conflict_string(
     store: &Store,
     path: &RepoPath,
@@ -15,7 +15,7 @@
 use jj_lib::matchers::{EverythingMatcher, FilesMatcher};
 use jj_lib::merged_tree::DiffSummary;
 use jj_lib::repo_path::{RepoPath, RepoPathBuf};
-use testutils::{create_tree, TestRepo};
+use testutils::test_repo_builder::TestRepoBuilder;
 
 fn to_owned_path_vec(paths: &[&RepoPath]) -> Vec<RepoPathBuf> {
     paths.iter().map(|&path| path.to_owned()).collect()
@@ -23,31 +23,23 @@ fn to_owned_path_vec(paths: &[&RepoPath]) -> Vec<RepoPathBuf> {
 
 #[test]
 fn test_types() {
-    let test_repo = TestRepo::init();
-    let repo = &test_repo.repo;
+    let fixture = TestRepoBuilder::new()
+        .commit("before")
+        .file("clean", "clean")
+        .file("modified", "contents before")
+        .file("removed", "removed contents")
+        .commit_on("before", "after")
+        .file("modified", "contents after")
+        .file("added", "added contents")
+        .remove_file("removed")
+        .build();
 
-    let clean_path = RepoPath::from_internal_string("clean");
     let modified_path = RepoPath::from_internal_string("modified");
     let added_path = RepoPath::from_internal_string("added");
     let removed_path = RepoPath::from_internal_string("removed");
 
-    let tree1 = create_tree(
-        repo,
-        &[
-            (clean_path, "clean"),
-            (modified_path, "contents before"),
-            (removed_path, "removed contents"),
-        ],
-    );
-
-    let tree2 = create_tree(
-        repo,
-        &[
-            (clean_path, "clean"),
-            (modified_path, "contents after"),
-            (added_path, "added contents"),
-        ],
-    );
+    let tree1 = fixture.commit("before").tree().unwrap();
+    let tree2 = fixture.commit("after").tree().unwrap();
 
     assert_eq!(
         tree1.diff_summary(&tree2, &EverythingMatcher).unwrap(),
@@ -61,14 +53,19 @@ fn test_types() {
 
 #[test]
 fn test_tree_file_transition() {
-    let test_repo = TestRepo::init();
-    let repo = &test_repo.repo;
+    let fixture = TestRepoBuilder::new()
+        .commit("dir")
+        .file("dir/file", "contents")
+        .commit_on("dir", "file")
+        .remove_file("dir/file")
+        .file("dir", "contents")
+        .build();
 
     let dir_file_path = RepoPath::from_internal_string("dir/file");
     let dir_path = RepoPath::from_internal_string("dir");
 
-    let tree1 = create_tree(repo, &[(dir_file_path, "contents")]);
-    let tree2 = create_tree(repo, &[(dir_path, "contents")]);
+    let tree1 = fixture.commit("dir").tree().unwrap();
+    let tree2 = fixture.commit("file").tree().unwrap();
 
     assert_eq!(
         tree1.diff_summary(&tree2, &EverythingMatcher).unwrap(),
@@ -90,8 +87,22 @@ fn test_tree_file_transition() {
 
 #[test]
 fn test_sorting() {
-    let test_repo = TestRepo::init();
-    let repo = &test_repo.repo;
+    let fixture = TestRepoBuilder::new()
+        .commit("before")
+        .file("a", "before")
+        .file("f/a", "before")
+        .file("f/f/a", "before")
+        .commit_on("before", "after")
+        .file("a", "after")
+        .file("b", "after")
+        .file("f/a", "after")
+        .file("f/b", "after")
+        .file("f/f/a", "after")
+        .file("f/f/b", "after")
+        .file("n", "after")
+        .file("s/b", "after")
+        .file("z", "after")
+        .build();
 
     let a_path = RepoPath::from_internal_string("a");
     let b_path = RepoPath::from_internal_string("b");
@@ -103,29 +114,8 @@ fn test_sorting() {
     let s_b_path = RepoPath::from_internal_string("s/b");
     let z_path = RepoPath::from_internal_string("z");
 
-    let tree1 = create_tree(
-        repo,
-        &[
-            (a_path, "before"),
-            (f_a_path, "before"),
-            (f_f_a_path, "before"),
-        ],
-    );
-
-    let tree2 = create_tree(
-        repo,
-        &[
-            (a_path, "after"),
-            (b_path, "after"),
-            (f_a_path, "after"),
-            (f_b_path, "after"),
-            (f_f_a_path, "after"),
-            (f_f_b_path, "after"),
-            (n_path, "after"),
-            (s_b_path, "after"),
-            (z_path, "after"),
-        ],
-    );
+    let tree1 = fixture.commit("before").tree().unwrap();
+    let tree2 = fixture.commit("after").tree().unwrap();
 
     assert_eq!(
         tree1.diff_summary(&tree2, &EverythingMatcher).unwrap(),
@@ -147,14 +137,19 @@ fn test_sorting() {
 
 #[test]
 fn test_matcher_dir_file_transition() {
-    let test_repo = TestRepo::init();
-    let repo = &test_repo.repo;
+    let fixture = TestRepoBuilder::new()
+        .commit("file")
+        .file("a", "before")
+        .commit_on("file", "dir")
+        .remove_file("a")
+        .file("a/a", "after")
+        .build();
 
     let a_path = RepoPath::from_internal_string("a");
     let a_a_path = RepoPath::from_internal_string("a/a");
 
-    let tree1 = create_tree(repo, &[(a_path, "before")]);
-    let tree2 = create_tree(repo, &[(a_a_path, "after")]);
+    let tree1 = fixture.commit("file").tree().unwrap();
+    let tree2 = fixture.commit("dir").tree().unwrap();
 
     let matcher = FilesMatcher::new([&a_path]);
     assert_eq!(
@@ -213,28 +208,28 @@ fn test_matcher_dir_file_transition() {
 
 #[test]
 fn test_matcher_normal_cases() {
-    let test_repo = TestRepo::init();
-    let repo = &test_repo.repo;
+    let fixture = TestRepoBuilder::new()
+        .commit("before")
+        .file("a", "before")
+        .file("dir1/a", "before")
+        .commit_on("before", "after")
+        // File "a" gets modified
+        .file("a", "after")
+        // File "dir1/a" gets modified
+        .file("dir1/a", "after")
+        // File "dir2/b" gets created
+        .file("dir2/b", "after")
+        // File "z" gets created
+        .file("z", "after")
+        .build();
 
     let a_path = RepoPath::from_internal_string("a");
     let dir1_a_path = RepoPath::from_internal_string("dir1/a");
     let dir2_b_path = RepoPath::from_internal_string("dir2/b");
     let z_path = RepoPath::from_internal_string("z");
 
-    let tree1 = create_tree(repo, &[(a_path, "before"), (dir1_a_path, "before")]);
-    // File "a" gets modified
-    // File "dir1/a" gets modified
-    // File "dir2/b" gets created
-    // File "z" gets created
-    let tree2 = create_tree(
-        repo,
-        &[
-            (a_path, "after"),
-            (dir1_a_path, "after"),
-            (dir2_b_path, "after"),
-            (z_path, "after"),
-        ],
-    );
+    let tree1 = fixture.commit("before").tree().unwrap();
+    let tree2 = fixture.commit("after").tree().unwrap();
 
     let matcher = FilesMatcher::new([a_path, z_path]);
     assert_eq!(
@@ -0,0 +1,237 @@
+// Copyright 2024 The Jujutsu Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::path::Path;
+
+use assert_matches::assert_matches;
+use jj_lib::format_version::{
+    self, AppliedMigration, FormatCompatibility, FormatComponent, FormatVersion,
+    FormatVersionError, Migration, SupportedVersions, UpgradeError, FORMAT_VERSION_FILE,
+};
+use jj_lib::repo::{RepoLoader, StoreLoadError};
+use jj_lib::workspace::{default_working_copy_factories, Workspace, WorkspaceLoadError};
+use testutils::{TestRepo, TestWorkspace};
+
+fn write_format_version(dir: &Path, content: &str) {
+    std::fs::write(dir.join(FORMAT_VERSION_FILE), content).unwrap();
+}
+
+fn load_repo(repo_path: &Path) -> Result<RepoLoader, StoreLoadError> {
+    let settings = testutils::user_settings();
+    RepoLoader::init(&settings, repo_path, &TestRepo::default_store_factories())
+}
+
+fn add_marker_file(dir: &Path) -> std::io::Result<()> {
+    std::fs::write(dir.join("migrated"), "")
+}
+
+fn sample_migration(readable_by_previous: bool) -> Migration {
+    Migration {
+        component: FormatComponent::Index,
+        from_version: 1,
+        readable_by_previous,
+        description: "add marker file",
+        run: add_marker_file,
+    }
+}
+
+#[test]
+fn test_load_repo_without_versions() {
+    // Repos created before versions were recorded are in the initial formats
+    let test_repo = TestRepo::init();
+    let repo_path = test_repo.repo.repo_path();
+    assert_eq!(
+        FormatVersion::read(&repo_path.join("index")).unwrap(),
+        FormatVersion::INITIAL
+    );
+    let loader = load_repo(repo_path).unwrap();
+    assert!(!loader.is_read_only());
+}
+
+#[test]
+fn test_load_repo_too_new() {
+    let test_repo = TestRepo::init();
+    let repo_path = test_repo.repo.repo_path();
+    write_format_version(&repo_path.join("index"), "3\n");
+
+    let err = load_repo(repo_path).err().unwrap();
+    assert_matches!(
+        err,
+        StoreLoadError::FormatVersion(FormatVersionError::TooNew {
+            component: FormatComponent::Index,
+            version: 3,
+            supported: 1,
+        })
+    );
+    assert_eq!(
+        err.to_string(),
+        "The index requires format 3, this binary supports up to 1"
+    );
+}
+
+#[test]
+fn test_load_repo_newer_but_readable() {
+    // A newer format which older binaries can still read makes the repo
+    // read-only
+    let settings = testutils::user_settings();
+    let test_repo = TestRepo::init();
+    let repo_path = test_repo.repo.repo_path();
+    write_format_version(&repo_path.join("op_store"), "2 1\n");
+
+    let loader = load_repo(repo_path).unwrap();
+    assert!(loader.is_read_only());
+    let repo = loader.load_at_head(&settings).unwrap();
+    assert!(repo.check_writable().is_err());
+}
+
+#[test]
+fn test_load_workspace_too_new() {
+    let settings = testutils::user_settings();
+    let test_workspace = TestWorkspace::init(&settings);
+    let workspace_root = test_workspace.workspace.workspace_root().clone();
+    write_format_version(&workspace_root.join(".jj").join("working_copy"), "2\n");
+
+    let result = Workspace::load(
+        &settings,
+        &workspace_root,
+        &TestRepo::default_store_factories(),
+        &default_working_copy_factories(),
+    );
+    assert_matches!(
+        result.err(),
+        Some(WorkspaceLoadError::StoreLoadError(
+            StoreLoadError::FormatVersion(FormatVersionError::TooNew {
+                component: FormatComponent::WorkingCopy,
+                version: 2,
+                supported: 1,
+            })
+        ))
+    );
+}
+
+#[test]
+fn test_invalid_version_file() {
+    let test_repo = TestRepo::init();
+    let repo_path = test_repo.repo.repo_path();
+    write_format_version(repo_path, "1 2\n");
+    assert_matches!(
+        load_repo(repo_path).err(),
+        Some(StoreLoadError::FormatVersion(FormatVersionError::Invalid { content, .. }))
+            if content == "1 2\n"
+    );
+}
+
+#[test]
+fn test_check_repo_too_old() {
+    let test_repo = TestRepo::init();
+    let repo_path = test_repo.repo.repo_path();
+    let supported = SupportedVersions::current().with_version(FormatComponent::Index, 2);
+
+    let err = format_version::check_repo(repo_path, &supported)
+        .err()
+        .unwrap();
+    assert_matches!(
+        err,
+        FormatVersionError::TooOld {
+            component: FormatComponent::Index,
+            version: 1,
+            supported: 2,
+        }
+    );
+    assert_eq!(
+        err.to_string(),
+        "The index uses format 1, this binary requires format 2"
+    );
+}
+
+#[test]
+fn test_upgrade() {
+    let test_repo = TestRepo::init();
+    let repo_path = test_repo.repo.repo_path();
+    let index_dir = repo_path.join("index");
+    let supported = SupportedVersions::current().with_version(FormatComponent::Index, 2);
+    let migrations = [sample_migration(false)];
+
+    let applied = format_version::upgrade(repo_path, None, &supported, &migrations).unwrap();
+    assert_eq!(
+        applied,
+        vec![AppliedMigration {
+            component: FormatComponent::Index,
+            from_version: 1,
+            description: "add marker file",
+        }]
+    );
+    assert!(index_dir.join("migrated").is_file());
+    assert_eq!(
+        FormatVersion::read(&index_dir).unwrap(),
+        FormatVersion {
+            version: 2,
+            min_reader_version: 2,
+        }
+    );
+    assert_eq!(
+        format_version::check_repo(repo_path, &supported).unwrap(),
+        FormatCompatibility::ReadWrite
+    );
+
+    // Nothing is left to upgrade
+    let applied = format_version::upgrade(repo_path, None, &supported, &migrations).unwrap();
+    assert_eq!(applied, vec![]);
+
+    // Binaries supporting only the old format refuse to load the repo
+    assert_matches!(
+        load_repo(repo_path).err(),
+        Some(StoreLoadError::FormatVersion(
+            FormatVersionError::TooNew { .. }
+        ))
+    );
+}
+
+#[test]
+fn test_upgrade_readable_by_previous() {
+    let test_repo = TestRepo::init();
+    let repo_path = test_repo.repo.repo_path();
+    let supported = SupportedVersions::current().with_version(FormatComponent::Index, 2);
+
+    format_version::upgrade(repo_path, None, &supported, &[sample_migration(true)]).unwrap();
+    assert_eq!(
+        FormatVersion::read(&repo_path.join("index")).unwrap(),
+        FormatVersion {
+            version: 2,
+            min_reader_version: 1,
+        }
+    );
+    // Binaries supporting only the old format load the repo read-only
+    assert!(load_repo(repo_path).unwrap().is_read_only());
+}
+
+#[test]
+fn test_upgrade_missing_migration() {
+    let test_repo = TestRepo::init();
+    let repo_path = test_repo.repo.repo_path();
+    let supported = SupportedVersions::current().with_version(FormatComponent::OpStore, 2);
+
+    assert_matches!(
+        format_version::upgrade(repo_path, None, &supported, &[sample_migration(false)]),
+        Err(UpgradeError::MissingMigration {
+            component: FormatComponent::OpStore,
+            version: 1,
+        })
+    );
+    // The version is unchanged
+    assert_eq!(
+        FormatVersion::read(&repo_path.join("op_store")).unwrap(),
+        FormatVersion::INITIAL
+    );
+}
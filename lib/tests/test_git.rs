@@ -13,8 +13,11 @@
 // limitations under the License.
 
 use std::collections::{BTreeMap, HashSet};
+use std::net::TcpListener;
 use std::path::PathBuf;
+use std::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::{mpsc, Arc, Barrier};
+use std::time::Duration;
 use std::{fs, thread};
 
 use assert_matches::assert_matches;
@@ -31,8 +34,9 @@ use jj_lib::git::{
 use jj_lib::git_backend::GitBackend;
 use jj_lib::object_id::ObjectId;
 use jj_lib::op_store::{BranchTarget, RefTarget, RemoteRef, RemoteRefState};
-use jj_lib::refs::BranchPushUpdate;
-use jj_lib::repo::{MutableRepo, ReadonlyRepo, Repo};
+use jj_lib::refs::{BranchPushUpdate, RefNameError};
+use jj_lib::repo::{MutableRepo, ReadonlyRepo, Repo, TagError};
+use jj_lib::rewrite::rebase_commit;
 use jj_lib::settings::{GitSettings, UserSettings};
 use jj_lib::signing::Signer;
 use jj_lib::str_util::StringPattern;
@@ -57,7 +61,7 @@ fn empty_git_commit<'r>(
             Some(ref_name),
             &signature,
             &signature,
-            &format!("random commit {}", rand::random::<u32>()),
+            &format!("random commit {}\n", rand::random::<u32>()),
             &empty_tree,
             parents,
         )
@@ -112,7 +116,7 @@ fn test_import_refs() {
     git::import_head(tx.mut_repo()).unwrap();
     let stats = git::import_refs(tx.mut_repo(), &git_settings).unwrap();
     tx.mut_repo().rebase_descendants(&settings).unwrap();
-    let repo = tx.commit("test");
+    let repo = tx.commit("test").unwrap();
     let view = repo.view();
 
     assert!(stats.abandoned_commits.is_empty());
@@ -234,7 +238,7 @@ fn test_import_refs_reimport() {
     let mut tx = repo.start_transaction(&settings);
     let stats = git::import_refs(tx.mut_repo(), &git_settings).unwrap();
     tx.mut_repo().rebase_descendants(&settings).unwrap();
-    let repo = tx.commit("test");
+    let repo = tx.commit("test").unwrap();
 
     assert!(stats.abandoned_commits.is_empty());
     let expected_heads = hashset! {
@@ -257,12 +261,12 @@ fn test_import_refs_reimport() {
         .unwrap();
     tx.mut_repo()
         .set_local_branch_target("feature2", RefTarget::normal(commit6.id().clone()));
-    let repo = tx.commit("test");
+    let repo = tx.commit("test").unwrap();
 
     let mut tx = repo.start_transaction(&settings);
     let stats = git::import_refs(tx.mut_repo(), &git_settings).unwrap();
     tx.mut_repo().rebase_descendants(&settings).unwrap();
-    let repo = tx.commit("test");
+    let repo = tx.commit("test").unwrap();
 
     assert_eq!(
         // The order is unstable just because we import heads from Git repo.
@@ -487,7 +491,7 @@ fn test_import_refs_reimport_with_deleted_remote_ref() {
     let mut tx = repo.start_transaction(&settings);
     git::import_refs(tx.mut_repo(), &git_settings).unwrap();
     tx.mut_repo().rebase_descendants(&settings).unwrap();
-    let repo = tx.commit("test");
+    let repo = tx.commit("test").unwrap();
 
     let expected_heads = hashset! {
             jj_id(&commit_main),
@@ -543,7 +547,7 @@ fn test_import_refs_reimport_with_deleted_remote_ref() {
     let mut tx = repo.start_transaction(&settings);
     git::import_refs(tx.mut_repo(), &git_settings).unwrap();
     tx.mut_repo().rebase_descendants(&settings).unwrap();
-    let repo = tx.commit("test");
+    let repo = tx.commit("test").unwrap();
 
     let view = repo.view();
     // The local branches were indeed deleted
@@ -606,7 +610,7 @@ fn test_import_refs_reimport_with_moved_remote_ref() {
     let mut tx = repo.start_transaction(&settings);
     git::import_refs(tx.mut_repo(), &git_settings).unwrap();
     tx.mut_repo().rebase_descendants(&settings).unwrap();
-    let repo = tx.commit("test");
+    let repo = tx.commit("test").unwrap();
 
     let expected_heads = hashset! {
             jj_id(&commit_main),
@@ -672,7 +676,7 @@ fn test_import_refs_reimport_with_moved_remote_ref() {
     let mut tx = repo.start_transaction(&settings);
     git::import_refs(tx.mut_repo(), &git_settings).unwrap();
     tx.mut_repo().rebase_descendants(&settings).unwrap();
-    let repo = tx.commit("test");
+    let repo = tx.commit("test").unwrap();
 
     let view = repo.view();
     assert_eq!(view.branches().count(), 3);
@@ -739,7 +743,7 @@ fn test_import_refs_reimport_with_moved_untracked_remote_ref() {
     let mut tx = repo.start_transaction(&settings);
     git::import_refs(tx.mut_repo(), &git_settings).unwrap();
     tx.mut_repo().rebase_descendants(&settings).unwrap();
-    let repo = tx.commit("test");
+    let repo = tx.commit("test").unwrap();
     let view = repo.view();
 
     assert_eq!(*view.heads(), hashset! { jj_id(&commit_remote_t0) });
@@ -759,7 +763,7 @@ fn test_import_refs_reimport_with_moved_untracked_remote_ref() {
     let mut tx = repo.start_transaction(&settings);
     git::import_refs(tx.mut_repo(), &git_settings).unwrap();
     tx.mut_repo().rebase_descendants(&settings).unwrap();
-    let repo = tx.commit("test");
+    let repo = tx.commit("test").unwrap();
     let view = repo.view();
 
     // commit_remote_t0 should be abandoned, but commit_base shouldn't because
@@ -909,7 +913,7 @@ fn test_import_refs_reimport_conflicted_remote_branch() {
     // The conflict can be resolved by importing the current Git state
     let mut tx = repo.start_transaction(&settings);
     git::import_refs(tx.mut_repo(), &git_settings).unwrap();
-    let repo = tx.commit("test");
+    let repo = tx.commit("test").unwrap();
     assert_eq!(
         repo.view().get_git_ref("refs/remotes/origin/main"),
         &RefTarget::normal(jj_id(&commit2)),
@@ -977,7 +981,7 @@ fn test_import_some_refs() {
     })
     .unwrap();
     tx.mut_repo().rebase_descendants(&settings).unwrap();
-    let repo = tx.commit("test");
+    let repo = tx.commit("test").unwrap();
 
     // There are two heads, feature2 and feature4.
     let view = repo.view();
@@ -1054,7 +1058,7 @@ fn test_import_some_refs() {
     })
     .unwrap();
     tx.mut_repo().rebase_descendants(&settings).unwrap();
-    let repo = tx.commit("test");
+    let repo = tx.commit("test").unwrap();
 
     // feature2 and feature4 will still be heads, and all four branches should be
     // present.
@@ -1071,7 +1075,7 @@ fn test_import_some_refs() {
     .unwrap();
     // No descendant should be rewritten.
     assert_eq!(tx.mut_repo().rebase_descendants(&settings).unwrap(), 0);
-    let repo = tx.commit("test");
+    let repo = tx.commit("test").unwrap();
 
     // feature2 and feature4 should still be the heads, and all three branches
     // feature2, feature3, and feature3 should exist.
@@ -1088,7 +1092,7 @@ fn test_import_some_refs() {
     .unwrap();
     // No descendant should be rewritten
     assert_eq!(tx.mut_repo().rebase_descendants(&settings).unwrap(), 0);
-    let repo = tx.commit("test");
+    let repo = tx.commit("test").unwrap();
 
     // feature2 and feature4 should still be the heads, and both branches
     // should exist.
@@ -1104,7 +1108,7 @@ fn test_import_some_refs() {
     .unwrap();
     // No descendant should be rewritten
     assert_eq!(tx.mut_repo().rebase_descendants(&settings).unwrap(), 0);
-    let repo = tx.commit("test");
+    let repo = tx.commit("test").unwrap();
 
     // feature2 should now be the only head and only branch.
     let view = repo.view();
@@ -1179,7 +1183,7 @@ fn test_import_refs_empty_git_repo() {
     tx.mut_repo()
         .rebase_descendants(&test_data.settings)
         .unwrap();
-    let repo = tx.commit("test");
+    let repo = tx.commit("test").unwrap();
     assert_eq!(*repo.view().heads(), heads_before);
     assert_eq!(repo.view().branches().count(), 0);
     assert_eq!(repo.view().tags().len(), 0);
@@ -1282,7 +1286,7 @@ fn test_import_refs_detached_head() {
     tx.mut_repo()
         .rebase_descendants(&test_data.settings)
         .unwrap();
-    let repo = tx.commit("test");
+    let repo = tx.commit("test").unwrap();
 
     let expected_heads = hashset! { jj_id(&commit1) };
     assert_eq!(*repo.view().heads(), expected_heads);
@@ -1433,6 +1437,96 @@ fn test_export_refs_unborn_git_branch() {
     assert!(!git_repo.head_detached().unwrap());
 }
 
+#[test]
+fn test_export_refs_tag() {
+    // Tags created and deleted in jj are exported as lightweight tags
+    let test_data = GitRepoData::create();
+    let git_settings = GitSettings::default();
+    let git_repo = test_data.git_repo;
+    let commit = empty_git_commit(&git_repo, "refs/heads/main", &[]);
+    let mut tx = test_data.repo.start_transaction(&test_data.settings);
+    let mut_repo = tx.mut_repo();
+    git::import_refs(mut_repo, &git_settings).unwrap();
+
+    mut_repo.create_tag("v1.0", &jj_id(&commit), false).unwrap();
+    assert_matches!(
+        mut_repo.create_tag("v1.0", &jj_id(&commit), false),
+        Err(TagError::AlreadyExists(_))
+    );
+    assert_matches!(
+        mut_repo.create_tag("v1.0..", &jj_id(&commit), false),
+        Err(TagError::InvalidName(
+            _,
+            RefNameError::InvalidSequence("..")
+        ))
+    );
+    // A tag that hasn't been exported yet isn't removed by an import
+    git::import_refs(mut_repo, &git_settings).unwrap();
+    assert_eq!(mut_repo.get_tag("v1.0"), RefTarget::normal(jj_id(&commit)));
+
+    assert!(git::export_refs(mut_repo).unwrap().is_empty());
+    assert_eq!(
+        mut_repo.get_git_ref("refs/tags/v1.0"),
+        RefTarget::normal(jj_id(&commit))
+    );
+    let git_ref = git_repo.find_reference("refs/tags/v1.0").unwrap();
+    assert_eq!(git_ref.target(), Some(commit.id()));
+    assert!(!git::is_annotated_tag(mut_repo.store(), "v1.0"));
+
+    // Importing again doesn't change anything
+    let view_before = mut_repo.view().store_view().clone();
+    git::import_refs(mut_repo, &git_settings).unwrap();
+    assert_eq!(*mut_repo.view().store_view(), view_before);
+
+    mut_repo.delete_tag("v1.0").unwrap();
+    assert_matches!(mut_repo.delete_tag("v1.0"), Err(TagError::NotFound(_)));
+    assert!(git::export_refs(mut_repo).unwrap().is_empty());
+    assert!(mut_repo.get_git_ref("refs/tags/v1.0").is_absent());
+    assert!(git_repo.find_reference("refs/tags/v1.0").is_err());
+}
+
+#[test]
+fn test_export_refs_annotated_tag() {
+    // An annotated tag imported from Git is left alone until it's changed in jj
+    let test_data = GitRepoData::create();
+    let git_settings = GitSettings::default();
+    let git_repo = test_data.git_repo;
+    let commit1 = empty_git_commit(&git_repo, "refs/heads/main", &[]);
+    let commit2 = empty_git_commit(&git_repo, "refs/heads/main", &[&commit1]);
+    let signature = git2::Signature::now("Someone", "someone@example.com").unwrap();
+    let tag_oid = git_repo
+        .tag("v1.0", commit1.as_object(), &signature, "release", false)
+        .unwrap();
+    let mut tx = test_data.repo.start_transaction(&test_data.settings);
+    let mut_repo = tx.mut_repo();
+    git::import_refs(mut_repo, &git_settings).unwrap();
+    assert_eq!(mut_repo.get_tag("v1.0"), RefTarget::normal(jj_id(&commit1)));
+    assert!(git::is_annotated_tag(mut_repo.store(), "v1.0"));
+    assert!(git::export_refs(mut_repo).unwrap().is_empty());
+    assert_eq!(
+        git_repo.find_reference("refs/tags/v1.0").unwrap().target(),
+        Some(tag_oid)
+    );
+
+    // Moving the tag replaces the tag object by a lightweight tag
+    mut_repo.create_tag("v1.0", &jj_id(&commit2), true).unwrap();
+    assert!(git::export_refs(mut_repo).unwrap().is_empty());
+    assert_eq!(
+        git_repo.find_reference("refs/tags/v1.0").unwrap().target(),
+        Some(commit2.id())
+    );
+    assert!(!git::is_annotated_tag(mut_repo.store(), "v1.0"));
+
+    // An annotated tag can be deleted too
+    git_repo
+        .tag("v2.0", commit1.as_object(), &signature, "release", false)
+        .unwrap();
+    git::import_refs(mut_repo, &git_settings).unwrap();
+    mut_repo.delete_tag("v2.0").unwrap();
+    assert!(git::export_refs(mut_repo).unwrap().is_empty());
+    assert!(git_repo.find_reference("refs/tags/v2.0").is_err());
+}
+
 #[test]
 fn test_export_import_sequence() {
     // Import a branch pointing to A, modify it in jj to point to B, export it,
@@ -1668,9 +1762,15 @@ fn test_export_partial_failure() {
     let failed = git::export_refs(mut_repo).unwrap();
     assert_eq!(failed.len(), 3);
     assert_eq!(failed[0].name, RefName::LocalBranch("".to_string()));
-    assert_matches!(failed[0].reason, FailedRefExportReason::InvalidGitName);
+    assert_matches!(
+        failed[0].reason,
+        FailedRefExportReason::InvalidGitName(RefNameError::Empty)
+    );
     assert_eq!(failed[1].name, RefName::LocalBranch("HEAD".to_string()));
-    assert_matches!(failed[1].reason, FailedRefExportReason::InvalidGitName);
+    assert_matches!(
+        failed[1].reason,
+        FailedRefExportReason::InvalidGitName(RefNameError::Reserved(_))
+    );
     assert_eq!(failed[2].name, RefName::LocalBranch("main/sub".to_string()));
     assert_matches!(failed[2].reason, FailedRefExportReason::FailedToSet(_));
 
@@ -1705,9 +1805,15 @@ fn test_export_partial_failure() {
     let failed = git::export_refs(mut_repo).unwrap();
     assert_eq!(failed.len(), 2);
     assert_eq!(failed[0].name, RefName::LocalBranch("".to_string()));
-    assert_matches!(failed[0].reason, FailedRefExportReason::InvalidGitName);
+    assert_matches!(
+        failed[0].reason,
+        FailedRefExportReason::InvalidGitName(RefNameError::Empty)
+    );
     assert_eq!(failed[1].name, RefName::LocalBranch("HEAD".to_string()));
-    assert_matches!(failed[1].reason, FailedRefExportReason::InvalidGitName);
+    assert_matches!(
+        failed[1].reason,
+        FailedRefExportReason::InvalidGitName(RefNameError::Reserved(_))
+    );
     assert!(git_repo.find_reference("refs/heads/").is_err());
     assert!(git_repo.find_reference("refs/heads/HEAD").is_err());
     assert!(git_repo.find_reference("refs/heads/main").is_err());
@@ -1733,6 +1839,46 @@ fn test_export_partial_failure() {
     );
 }
 
+#[test]
+fn test_export_skips_imported_invalid_name() {
+    // Git allows branch names that jj doesn't. They're imported as is, but
+    // changes to them aren't exported.
+    let test_data = GitRepoData::create();
+    let git_settings = GitSettings::default();
+    let git_repo = test_data.git_repo;
+    let commit1 = empty_git_commit(&git_repo, "refs/heads/-dash", &[]);
+    let mut tx = test_data.repo.start_transaction(&test_data.settings);
+    let mut_repo = tx.mut_repo();
+    git::import_refs(mut_repo, &git_settings).unwrap();
+    assert_eq!(
+        mut_repo.get_local_branch("-dash"),
+        RefTarget::normal(jj_id(&commit1))
+    );
+
+    let commit2 = write_random_commit(mut_repo, &test_data.settings);
+    mut_repo.set_local_branch_target("-dash", RefTarget::normal(commit2.id().clone()));
+    let failed = git::export_refs(mut_repo).unwrap();
+    assert_eq!(failed.len(), 1);
+    assert_eq!(failed[0].name, RefName::LocalBranch("-dash".to_string()));
+    assert_matches!(
+        failed[0].reason,
+        FailedRefExportReason::InvalidGitName(RefNameError::LeadingDash)
+    );
+    // The Git ref and the jj branch are left as they were
+    assert_eq!(
+        git_repo
+            .find_reference("refs/heads/-dash")
+            .unwrap()
+            .target()
+            .unwrap(),
+        commit1.id()
+    );
+    assert_eq!(
+        mut_repo.get_local_branch("-dash"),
+        RefTarget::normal(commit2.id().clone())
+    );
+}
+
 #[test]
 fn test_export_reexport_transitions() {
     // Test exporting after making changes on the jj side, or the git side, or both
@@ -2049,7 +2195,7 @@ fn test_fetch_initial_commit() {
     // No default branch because the origin repo's HEAD wasn't set
     assert_eq!(stats.default_branch, None);
     assert!(stats.import_stats.abandoned_commits.is_empty());
-    let repo = tx.commit("test");
+    let repo = tx.commit("test").unwrap();
     // The initial commit is visible after git::fetch().
     let view = repo.view();
     assert!(view.heads().contains(&jj_id(&initial_git_commit)));
@@ -2096,7 +2242,7 @@ fn test_fetch_success() {
         &git_settings,
     )
     .unwrap();
-    test_data.repo = tx.commit("test");
+    test_data.repo = tx.commit("test").unwrap();
 
     test_data.origin_repo.set_head("refs/heads/main").unwrap();
     let new_git_commit = empty_git_commit(
@@ -2122,7 +2268,7 @@ fn test_fetch_success() {
     // The default branch is "main"
     assert_eq!(stats.default_branch, Some("main".to_string()));
     assert!(stats.import_stats.abandoned_commits.is_empty());
-    let repo = tx.commit("test");
+    let repo = tx.commit("test").unwrap();
     // The new commit is visible after we fetch again
     let view = repo.view();
     assert!(view.heads().contains(&jj_id(&new_git_commit)));
@@ -2245,6 +2391,97 @@ fn test_fetch_no_default_branch() {
     assert_eq!(stats.default_branch, None);
 }
 
+#[test]
+fn test_fetch_rebase_and_move_branch_in_one_operation() {
+    let test_data = GitRepoData::create();
+    let git_settings = GitSettings::default();
+    let initial_git_commit = empty_git_commit(&test_data.origin_repo, "refs/heads/main", &[]);
+
+    let mut tx = test_data.repo.start_transaction(&test_data.settings);
+    git::fetch(
+        tx.mut_repo(),
+        &test_data.git_repo,
+        "origin",
+        &[StringPattern::everything()],
+        git::RemoteCallbacks::default(),
+        &git_settings,
+    )
+    .unwrap();
+    let initial_commit = tx
+        .mut_repo()
+        .store()
+        .get_commit(&jj_id(&initial_git_commit))
+        .unwrap();
+    let feature_commit = create_random_commit(tx.mut_repo(), &test_data.settings)
+        .set_parents(vec![initial_commit.id().clone()])
+        .write()
+        .unwrap();
+    tx.mut_repo()
+        .set_local_branch_target("feature", RefTarget::normal(feature_commit.id().clone()));
+    let repo = tx.commit("set up").unwrap();
+
+    // Fetch a new main, rebase the feature branch onto it, and move the branch
+    // to the rebased commit, all in a single transaction.
+    let new_git_commit = empty_git_commit(
+        &test_data.origin_repo,
+        "refs/heads/main",
+        &[&initial_git_commit],
+    );
+    let mut tx = repo.start_transaction(&test_data.settings);
+    git::fetch(
+        tx.mut_repo(),
+        &test_data.git_repo,
+        "origin",
+        &[StringPattern::everything()],
+        git::RemoteCallbacks::default(),
+        &git_settings,
+    )
+    .unwrap();
+    let new_main_commit = tx
+        .mut_repo()
+        .store()
+        .get_commit(&jj_id(&new_git_commit))
+        .unwrap();
+    let rebased_commit = rebase_commit(
+        &test_data.settings,
+        tx.mut_repo(),
+        &feature_commit,
+        &[new_main_commit.clone()],
+    )
+    .unwrap();
+    tx.mut_repo()
+        .rebase_descendants(&test_data.settings)
+        .unwrap();
+    tx.mut_repo()
+        .set_local_branch_target("feature", RefTarget::normal(rebased_commit.id().clone()));
+    let new_repo = tx.commit("sync feature with main").unwrap();
+
+    // Exactly one operation was added, with the given description.
+    let operation = new_repo.operation();
+    assert_eq!(operation.parent_ids(), &[repo.op_id().clone()]);
+    assert_eq!(
+        operation.store_operation().metadata.description,
+        "sync feature with main"
+    );
+    let reloaded_repo = load_repo_at_head(&test_data.settings, new_repo.repo_path());
+    assert_eq!(reloaded_repo.op_id(), new_repo.op_id());
+
+    let view = new_repo.view();
+    assert_eq!(rebased_commit.parent_ids(), &[new_main_commit.id().clone()]);
+    assert_eq!(
+        *view.get_remote_branch("main", "origin"),
+        RemoteRef {
+            target: RefTarget::normal(new_main_commit.id().clone()),
+            state: RemoteRefState::New,
+        }
+    );
+    assert_eq!(
+        *view.get_local_branch("feature"),
+        RefTarget::normal(rebased_commit.id().clone())
+    );
+    assert!(!view.heads().contains(feature_commit.id()));
+}
+
 #[test]
 fn test_fetch_empty_refspecs() {
     let test_data = GitRepoData::create();
@@ -2290,6 +2527,84 @@ fn test_fetch_no_such_remote() {
     assert!(matches!(result, Err(GitFetchError::NoSuchRemote(_))));
 }
 
+#[test]
+fn test_fetch_retries_network_errors() {
+    let test_data = GitRepoData::create();
+    let git_settings = GitSettings {
+        fetch_attempts: 3,
+        fetch_retry_delay: Duration::ZERO,
+        ..Default::default()
+    };
+    // A server that hangs up on every connection
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let port = listener.local_addr().unwrap().port();
+    let num_connections = Arc::new(AtomicUsize::new(0));
+    thread::spawn({
+        let num_connections = num_connections.clone();
+        move || {
+            for stream in listener.incoming() {
+                num_connections.fetch_add(1, Ordering::SeqCst);
+                drop(stream);
+            }
+        }
+    });
+    test_data
+        .git_repo
+        .remote("flaky", &format!("git://127.0.0.1:{port}/repo"))
+        .unwrap();
+
+    let mut retries = vec![];
+    let mut on_retry = |retry: &git::FetchRetry| {
+        retries.push((retry.attempt, retry.max_attempts));
+    };
+    let mut callbacks = git::RemoteCallbacks::default();
+    callbacks.retry = Some(&mut on_retry);
+    let mut tx = test_data.repo.start_transaction(&test_data.settings);
+    let result = git::fetch(
+        tx.mut_repo(),
+        &test_data.git_repo,
+        "flaky",
+        &[StringPattern::everything()],
+        callbacks,
+        &git_settings,
+    );
+    assert_matches!(result, Err(GitFetchError::InternalGitError(_)));
+    assert_eq!(retries, vec![(1, 3), (2, 3)]);
+    assert_eq!(num_connections.load(Ordering::SeqCst), 3);
+    assert_eq!(tx.mut_repo().view().branches().count(), 0);
+}
+
+#[test]
+fn test_fetch_does_not_retry_other_errors() {
+    let test_data = GitRepoData::create();
+    let git_settings = GitSettings {
+        fetch_attempts: 3,
+        fetch_retry_delay: Duration::ZERO,
+        ..Default::default()
+    };
+    let missing_dir = test_data._temp_dir.path().join("missing");
+    test_data
+        .git_repo
+        .remote("missing", missing_dir.to_str().unwrap())
+        .unwrap();
+
+    let mut num_retries = 0;
+    let mut on_retry = |_: &git::FetchRetry| num_retries += 1;
+    let mut callbacks = git::RemoteCallbacks::default();
+    callbacks.retry = Some(&mut on_retry);
+    let mut tx = test_data.repo.start_transaction(&test_data.settings);
+    let result = git::fetch(
+        tx.mut_repo(),
+        &test_data.git_repo,
+        "missing",
+        &[StringPattern::everything()],
+        callbacks,
+        &git_settings,
+    );
+    assert_matches!(result, Err(GitFetchError::InternalGitError(_)));
+    assert_eq!(num_retries, 0);
+}
+
 struct PushTestSetup {
     source_repo_dir: PathBuf,
     jj_repo: Arc<ReadonlyRepo>,
@@ -2349,7 +2664,7 @@ fn set_up_push_repos(settings: &UserSettings, temp_dir: &TempDir) -> PushTestSet
             state: RemoteRefState::Tracking,
         },
     );
-    let jj_repo = tx.commit("test");
+    let jj_repo = tx.commit("test").unwrap();
     PushTestSetup {
         source_repo_dir,
         jj_repo,
@@ -2418,7 +2733,7 @@ fn test_push_branches_success() {
     );
 
     // Check that the repo view reflects the changes in the Git repo
-    setup.jj_repo = tx.commit("test");
+    setup.jj_repo = tx.commit("test").unwrap();
     let mut tx = setup.jj_repo.start_transaction(&settings);
     git::import_refs(tx.mut_repo(), &GitSettings::default()).unwrap();
     assert!(!tx.mut_repo().has_changes());
@@ -2471,7 +2786,7 @@ fn test_push_branches_deletion() {
     assert!(view.get_remote_branch("main", "origin").is_absent());
 
     // Check that the repo view reflects the changes in the Git repo
-    setup.jj_repo = tx.commit("test");
+    setup.jj_repo = tx.commit("test").unwrap();
     let mut tx = setup.jj_repo.start_transaction(&settings);
     git::import_refs(tx.mut_repo(), &GitSettings::default()).unwrap();
     assert!(!tx.mut_repo().has_changes());
@@ -2541,7 +2856,7 @@ fn test_push_branches_mixed_deletion_and_addition() {
     );
 
     // Check that the repo view reflects the changes in the Git repo
-    setup.jj_repo = tx.commit("test");
+    setup.jj_repo = tx.commit("test").unwrap();
     let mut tx = setup.jj_repo.start_transaction(&settings);
     git::import_refs(tx.mut_repo(), &GitSettings::default()).unwrap();
     assert!(!tx.mut_repo().has_changes());
@@ -2554,7 +2869,7 @@ fn test_push_branches_not_fast_forward() {
     let mut setup = set_up_push_repos(&settings, &temp_dir);
     let mut tx = setup.jj_repo.start_transaction(&settings);
     let new_commit = write_random_commit(tx.mut_repo(), &settings);
-    setup.jj_repo = tx.commit("test");
+    setup.jj_repo = tx.commit("test").unwrap();
     let mut tx = setup.jj_repo.start_transaction(&settings);
 
     let targets = GitBranchPushTargets {
@@ -2584,7 +2899,7 @@ fn test_push_branches_not_fast_forward_with_force() {
     let mut setup = set_up_push_repos(&settings, &temp_dir);
     let mut tx = setup.jj_repo.start_transaction(&settings);
     let new_commit = write_random_commit(tx.mut_repo(), &settings);
-    setup.jj_repo = tx.commit("test");
+    setup.jj_repo = tx.commit("test").unwrap();
     let mut tx = setup.jj_repo.start_transaction(&settings);
 
     let targets = GitBranchPushTargets {
@@ -2710,7 +3025,7 @@ fn test_bulk_update_extra_on_import_refs() {
         let mut tx = repo.start_transaction(&settings);
         git::import_refs(tx.mut_repo(), &git_settings).unwrap();
         tx.mut_repo().rebase_descendants(&settings).unwrap();
-        tx.commit("test")
+        tx.commit("test").unwrap()
     };
 
     // Extra metadata table shouldn't be created per read_commit() call. The number
@@ -2749,7 +3064,7 @@ fn test_rewrite_imported_commit() {
     let mut tx = repo.start_transaction(&settings);
     git::import_refs(tx.mut_repo(), &git_settings).unwrap();
     tx.mut_repo().rebase_descendants(&settings).unwrap();
-    let repo = tx.commit("test");
+    let repo = tx.commit("test").unwrap();
     let imported_commit = repo.store().get_commit(&jj_id(&git_commit)).unwrap();
 
     // Try to create identical commit with different change id.
@@ -2766,7 +3081,7 @@ fn test_rewrite_imported_commit() {
         .set_description(imported_commit.description())
         .write()
         .unwrap();
-    let repo = tx.commit("test");
+    let repo = tx.commit("test").unwrap();
 
     // Imported commit shouldn't be reused, and the timestamp of the authored
     // commit should be adjusted to create new commit.
@@ -2810,7 +3125,7 @@ fn test_concurrent_write_commit() {
                     .set_description("racy commit")
                     .write()
                     .unwrap();
-                tx.commit(format!("writer {i}"));
+                tx.commit(format!("writer {i}")).unwrap();
                 sender
                     .send((commit.id().clone(), commit.change_id().clone()))
                     .unwrap();
@@ -2895,7 +3210,7 @@ fn test_concurrent_read_write_commit() {
                     .set_description(format!("commit {i}"))
                     .write()
                     .unwrap();
-                tx.commit(format!("writer {i}"));
+                tx.commit(format!("writer {i}")).unwrap();
                 assert_eq!(commit.id(), commit_id);
             });
         }
@@ -2933,7 +3248,7 @@ fn test_concurrent_read_write_commit() {
                         })
                         .collect_vec();
                     if tx.mut_repo().has_changes() {
-                        tx.commit(format!("reader {i}"));
+                        tx.commit(format!("reader {i}")).unwrap();
                     }
                     thread::yield_now();
                 }
@@ -17,11 +17,16 @@ use std::process::Command;
 use std::sync::Arc;
 use std::time::SystemTime;
 
-use jj_lib::backend::CommitId;
-use jj_lib::git_backend::GitBackend;
+use assert_matches::assert_matches;
+use jj_lib::backend::{BackendError, CommitId};
+use jj_lib::git_backend::{GitBackend, GitBackendError};
+use jj_lib::object_id::ObjectId as _;
 use jj_lib::repo::{ReadonlyRepo, Repo};
 use maplit::hashset;
-use testutils::{create_random_commit, CommitGraphBuilder, TestRepo, TestRepoBackend};
+use testutils::{
+    create_random_commit, load_repo_at_head, write_random_commit, CommitGraphBuilder, TestRepo,
+    TestRepoBackend,
+};
 
 fn get_git_backend(repo: &Arc<ReadonlyRepo>) -> &GitBackend {
     repo.store()
@@ -82,7 +87,7 @@ fn test_gc() {
         .set_predecessors(vec![commit_d.id().clone()])
         .write()
         .unwrap();
-    let repo = tx.commit("test");
+    let repo = tx.commit("test").unwrap();
     assert_eq!(
         *repo.view().heads(),
         hashset! {
@@ -187,3 +192,82 @@ fn test_gc() {
     repo.store().gc(base_index.as_index(), now).unwrap();
     assert_eq!(collect_no_gc_refs(&git_repo), hashset! {});
 }
+
+#[test]
+fn test_keep_refs_recreated_on_operation() {
+    let settings = testutils::user_settings();
+    let test_repo = TestRepo::init_with_backend(TestRepoBackend::Git);
+    let repo = test_repo.repo;
+    let git_repo = get_git_repo(&repo);
+
+    let mut tx = repo.start_transaction(&settings);
+    let commit = write_random_commit(tx.mut_repo(), &settings);
+    let repo = tx.commit("add commit").unwrap();
+    let mut tx = repo.start_transaction(&settings);
+    tx.mut_repo().remove_head(commit.id());
+    let repo = tx.commit("hide commit").unwrap();
+    assert_eq!(
+        collect_no_gc_refs(&git_repo),
+        hashset! {commit.id().clone()}
+    );
+
+    // Simulate jj's GC removing the no-gc ref of the hidden commit
+    std::fs::remove_file(
+        git_repo
+            .path()
+            .join("refs")
+            .join("jj")
+            .join("keep")
+            .join(commit.id().hex()),
+    )
+    .unwrap();
+    assert_eq!(collect_no_gc_refs(&git_repo), hashset! {});
+
+    // Making the commit visible again recreates the ref
+    let mut tx = repo.start_transaction(&settings);
+    tx.mut_repo().add_head(&commit).unwrap();
+    tx.commit("unhide commit").unwrap();
+    assert_eq!(
+        collect_no_gc_refs(&git_repo),
+        hashset! {commit.id().clone()}
+    );
+}
+
+#[test]
+fn test_missing_git_object() {
+    let settings = testutils::user_settings();
+    let test_repo = TestRepo::init_with_backend(TestRepoBackend::Git);
+    let repo = test_repo.repo;
+    let git_repo = get_git_repo(&repo);
+
+    let mut tx = repo.start_transaction(&settings);
+    let mut graph_builder = CommitGraphBuilder::new(&settings, tx.mut_repo());
+    let commit_a = graph_builder.initial_commit();
+    let commit_b = graph_builder.commit_with_parents(&[&commit_a]);
+    let repo = tx.commit("add commits").unwrap();
+    assert_eq!(*repo.view().heads(), hashset! {commit_b.id().clone()});
+
+    // Delete the loose object of the non-head commit as if it were pruned
+    // by an external `git gc`
+    let hex = commit_a.id().hex();
+    std::fs::remove_file(
+        git_repo
+            .path()
+            .join("objects")
+            .join(&hex[..2])
+            .join(&hex[2..]),
+    )
+    .unwrap();
+
+    let repo = load_repo_at_head(&settings, repo.repo_path());
+    assert!(repo.store().get_commit(commit_b.id()).is_ok());
+    let err = repo.store().get_commit(commit_a.id()).unwrap_err();
+    assert_matches!(
+        &err,
+        BackendError::ObjectNotFound { source, .. }
+            if matches!(
+                source.downcast_ref::<GitBackendError>(),
+                Some(GitBackendError::MissingGitObject { id, .. }) if *id == hex
+            )
+    );
+}
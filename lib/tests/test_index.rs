@@ -16,6 +16,7 @@ use std::fs;
 use std::sync::Arc;
 
 use assert_matches::assert_matches;
+use itertools::Itertools as _;
 use jj_lib::backend::{ChangeId, CommitId};
 use jj_lib::commit::Commit;
 use jj_lib::commit_builder::CommitBuilder;
@@ -27,10 +28,12 @@ use jj_lib::index::Index as _;
 use jj_lib::object_id::{HexPrefix, ObjectId as _, PrefixResolution};
 use jj_lib::op_store::{RefTarget, RemoteRef};
 use jj_lib::repo::{MutableRepo, ReadonlyRepo, Repo};
+use jj_lib::repo_path::{RepoPath, RepoPathBuf};
+use jj_lib::revset::{RevsetExpression, RevsetFilterPredicate};
 use jj_lib::settings::UserSettings;
 use testutils::test_backend::TestBackend;
 use testutils::{
-    commit_transactions, create_random_commit, load_repo_at_head, write_random_commit,
+    commit_transactions, create_random_commit, create_tree, load_repo_at_head, write_random_commit,
     CommitGraphBuilder, TestRepo,
 };
 
@@ -98,7 +101,7 @@ fn test_index_commits_standard_cases() {
     let commit_f = graph_builder.commit_with_parents(&[&commit_b, &commit_e]);
     let commit_g = graph_builder.commit_with_parents(&[&commit_f]);
     let commit_h = graph_builder.commit_with_parents(&[&commit_e]);
-    let repo = tx.commit("test");
+    let repo = tx.commit("test").unwrap();
 
     let index = as_readonly_composite(&repo);
     // There should be the root commit, plus 8 more
@@ -157,7 +160,7 @@ fn test_index_commits_criss_cross() {
         left_commits.push(new_left);
         right_commits.push(new_right);
     }
-    let repo = tx.commit("test");
+    let repo = tx.commit("test").unwrap();
 
     let index = as_readonly_composite(&repo);
     // There should the root commit, plus 2 for each generation
@@ -287,11 +290,11 @@ fn test_index_commits_previous_operations() {
     let commit_a = graph_builder.initial_commit();
     let commit_b = graph_builder.commit_with_parents(&[&commit_a]);
     let commit_c = graph_builder.commit_with_parents(&[&commit_b]);
-    let repo = tx.commit("test");
+    let repo = tx.commit("test").unwrap();
 
     let mut tx = repo.start_transaction(&settings);
     tx.mut_repo().remove_head(commit_c.id());
-    let repo = tx.commit("test");
+    let repo = tx.commit("test").unwrap();
 
     // Delete index from disk
     let index_operations_dir = repo.repo_path().join("index").join("operations");
@@ -343,7 +346,7 @@ fn test_index_commits_hidden_but_referenced() {
             state: jj_lib::op_store::RemoteRefState::New,
         },
     );
-    let repo = tx.commit("test");
+    let repo = tx.commit("test").unwrap();
 
     // All commits should be indexed
     assert!(repo.index().has_id(commit_a.id()));
@@ -382,7 +385,7 @@ fn test_index_commits_incremental() {
     let commit_a = child_commit(tx.mut_repo(), &settings, &root_commit)
         .write()
         .unwrap();
-    let repo = tx.commit("test");
+    let repo = tx.commit("test").unwrap();
 
     let index = as_readonly_composite(&repo);
     // There should be the root commit, plus 1 more
@@ -395,7 +398,7 @@ fn test_index_commits_incremental() {
     let commit_c = child_commit(tx.mut_repo(), &settings, &commit_b)
         .write()
         .unwrap();
-    tx.commit("test");
+    tx.commit("test").unwrap();
 
     let repo = load_repo_at_head(&settings, repo.repo_path());
     let index = as_readonly_composite(&repo);
@@ -433,13 +436,13 @@ fn test_index_commits_incremental_empty_transaction() {
     let commit_a = child_commit(tx.mut_repo(), &settings, &root_commit)
         .write()
         .unwrap();
-    let repo = tx.commit("test");
+    let repo = tx.commit("test").unwrap();
 
     let index = as_readonly_composite(&repo);
     // There should be the root commit, plus 1 more
     assert_eq!(index.num_commits(), 1 + 1);
 
-    repo.start_transaction(&settings).commit("test");
+    repo.start_transaction(&settings).commit("test").unwrap();
 
     let repo = load_repo_at_head(&settings, repo.repo_path());
     let index = as_readonly_composite(&repo);
@@ -475,7 +478,7 @@ fn test_index_commits_incremental_already_indexed() {
     let commit_a = child_commit(tx.mut_repo(), &settings, &root_commit)
         .write()
         .unwrap();
-    let repo = tx.commit("test");
+    let repo = tx.commit("test").unwrap();
 
     assert!(repo.index().has_id(commit_a.id()));
     assert_eq!(as_readonly_composite(&repo).num_commits(), 1 + 1);
@@ -495,7 +498,7 @@ fn create_n_commits(
     for _ in 0..num_commits {
         write_random_commit(tx.mut_repo(), settings);
     }
-    tx.commit("test")
+    tx.commit("test").unwrap()
 }
 
 fn as_readonly_composite(repo: &Arc<ReadonlyRepo>) -> CompositeIndex<'_> {
@@ -604,7 +607,7 @@ fn test_reindex_from_merged_operation() {
     for _ in 0..2 {
         let mut tx = repo.start_transaction(&settings);
         let commit = write_random_commit(tx.mut_repo(), &settings);
-        let repo = tx.commit("test");
+        let repo = tx.commit("test").unwrap();
         let mut tx = repo.start_transaction(&settings);
         tx.mut_repo().remove_head(commit.id());
         txs.push(tx);
@@ -614,7 +617,7 @@ fn test_reindex_from_merged_operation() {
     op_ids_to_delete.push(repo.op_id());
     let mut tx = repo.start_transaction(&settings);
     write_random_commit(tx.mut_repo(), &settings);
-    let repo = tx.commit("test");
+    let repo = tx.commit("test").unwrap();
     op_ids_to_delete.push(repo.op_id());
     let operation_to_reload = repo.operation();
 
@@ -643,12 +646,12 @@ fn test_reindex_missing_commit() {
 
     let mut tx = repo.start_transaction(&settings);
     let missing_commit = write_random_commit(tx.mut_repo(), &settings);
-    let repo = tx.commit("test");
+    let repo = tx.commit("test").unwrap();
     let bad_op_id = repo.op_id();
 
     let mut tx = repo.start_transaction(&settings);
     tx.mut_repo().remove_head(missing_commit.id());
-    let repo = tx.commit("test");
+    let repo = tx.commit("test").unwrap();
 
     // Remove historical head commit to simulate bad GC.
     let test_backend: &TestBackend = repo.store().backend_impl().downcast_ref().unwrap();
@@ -794,3 +797,97 @@ fn test_change_id_index() {
     assert_eq!(resolve_prefix("a"), PrefixResolution::AmbiguousMatch);
     assert_eq!(resolve_prefix("b"), PrefixResolution::NoMatch);
 }
+
+#[test]
+fn test_changed_path_filters_random_history() {
+    use rand::{Rng as _, SeedableRng as _};
+
+    let config = testutils::base_config()
+        .set_override("index.changed-path-filters", true)
+        .unwrap()
+        .build()
+        .unwrap();
+    let settings = UserSettings::from_config(config);
+    let test_repo = TestRepo::init_with_settings(&settings);
+    let mut repo = test_repo.repo.clone();
+
+    let file_paths =
+        ["a", "b", "dir/a", "dir/b", "dir/sub/a", "other/a"].map(RepoPath::from_internal_string);
+    let mut rng = rand_chacha::ChaCha8Rng::seed_from_u64(0);
+    let mut commits = vec![repo.store().root_commit()];
+    // Commit in several transactions so the filters are built with new
+    // segments, some of which squash the previous ones
+    for _ in 0..5 {
+        let mut tx = repo.start_transaction(&settings);
+        for _ in 0..10 {
+            let parent_ids = if commits.len() > 2 && rng.gen_bool(0.2) {
+                (0..2)
+                    .map(|_| commits[rng.gen_range(1..commits.len())].id().clone())
+                    .unique()
+                    .collect_vec()
+            } else {
+                vec![commits[rng.gen_range(0..commits.len())].id().clone()]
+            };
+            let mut path_contents = vec![];
+            for &path in &file_paths {
+                if rng.gen_bool(0.3) {
+                    path_contents.push((path, ["1", "2"][rng.gen_range(0..2)]));
+                }
+            }
+            let tree = create_tree(&repo, &path_contents);
+            let commit = tx
+                .mut_repo()
+                .new_commit(&settings, parent_ids, tree.id())
+                .write()
+                .unwrap();
+            commits.push(commit);
+        }
+        repo = tx.commit("test").unwrap();
+    }
+
+    // The same index without loading the filters
+    let repo_without_filters = load_repo_at_head(&testutils::user_settings(), repo.repo_path());
+    let num_filters = |repo: &Arc<ReadonlyRepo>| {
+        let stats = as_readonly_composite(repo).stats();
+        stats
+            .levels
+            .iter()
+            .map(|level| level.num_changed_path_filters)
+            .sum::<u32>()
+    };
+    assert!(num_filters(&repo) > 0);
+    assert_eq!(num_filters(&repo_without_filters), 0);
+
+    let queries: &[&[&str]] = &[
+        &["a"],
+        &["dir"],
+        &["dir/a"],
+        &["dir/sub"],
+        &["dir/sub/a"],
+        &["other"],
+        &["missing"],
+        &["dir/missing"],
+        &["a", "other"],
+        &[""],
+    ];
+    for paths in queries {
+        let paths = paths
+            .iter()
+            .map(|path| RepoPathBuf::from_internal_string(*path))
+            .collect_vec();
+        let expression = RevsetExpression::filter(RevsetFilterPredicate::File(Some(paths)));
+        let evaluate = |repo: &Arc<ReadonlyRepo>| {
+            expression
+                .clone()
+                .evaluate_programmatic(repo.as_ref())
+                .unwrap()
+                .iter()
+                .collect_vec()
+        };
+        assert_eq!(
+            evaluate(&repo),
+            evaluate(&repo_without_filters),
+            "{expression:?}"
+        );
+    }
+}
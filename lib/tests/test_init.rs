@@ -138,6 +138,42 @@ fn test_init_external_git() {
     write_random_commit(tx.mut_repo(), &settings);
 }
 
+#[test]
+fn test_init_external_git_linked_worktree() {
+    let settings = testutils::user_settings();
+    let temp_dir = testutils::new_temp_dir();
+    let (canonical, uncanonical) = canonicalize(temp_dir.path());
+    let git_repo_path = uncanonical.join("git");
+    let git_repo = git2::Repository::init(&git_repo_path).unwrap();
+    // A linked worktree needs a commit to check out
+    let signature = git2::Signature::now("Some One", "someone@example.com").unwrap();
+    let tree_id = git_repo.treebuilder(None).unwrap().write().unwrap();
+    let tree = git_repo.find_tree(tree_id).unwrap();
+    git_repo
+        .commit(Some("HEAD"), &signature, &signature, "initial", &tree, &[])
+        .unwrap();
+    git_repo
+        .worktree("worktree", &uncanonical.join("worktree"), None)
+        .unwrap();
+    std::fs::create_dir(uncanonical.join("jj")).unwrap();
+    let (_workspace, repo) = Workspace::init_external_git(
+        &settings,
+        &uncanonical.join("jj"),
+        &uncanonical.join("worktree"),
+    )
+    .unwrap();
+    let git_backend = repo
+        .store()
+        .backend_impl()
+        .downcast_ref::<GitBackend>()
+        .unwrap();
+    // The main repo is used, not the worktree's private directory
+    assert_eq!(
+        git_backend.git_repo_path(),
+        canonical.join("git").join(".git")
+    );
+}
+
 #[test_case(TestRepoBackend::Local ; "local backend")]
 #[test_case(TestRepoBackend::Git ; "git backend")]
 fn test_init_no_config_set(backend: TestRepoBackend) {
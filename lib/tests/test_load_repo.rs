@@ -12,8 +12,44 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use jj_lib::repo::RepoLoader;
-use testutils::{write_random_commit, TestRepo};
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc;
+use std::thread;
+use std::time::{Duration, Instant, SystemTime};
+
+use assert_matches::assert_matches;
+use itertools::Itertools;
+use jj_lib::backend::CommitId;
+use jj_lib::default_index::DefaultReadonlyIndex;
+use jj_lib::object_id::ObjectId as _;
+use jj_lib::op_store::RefTarget;
+use jj_lib::repo::{
+    find_missing_commits, ConsistencyCheck, ReadonlyRepo, Repo as _, RepoLoader, RepoLoaderError,
+};
+use jj_lib::revset::RevsetExpression;
+use jj_lib::transaction::TransactionCommitError;
+use jj_lib::view::ViewReference;
+use testutils::{write_random_commit, TestRepo, TestRepoBackend};
+
+/// Lists every file and directory under `dir` with its size and mtime.
+fn dir_state(dir: &Path) -> Vec<(PathBuf, u64, SystemTime)> {
+    let mut state = vec![];
+    let mut pending = vec![dir.to_owned()];
+    while let Some(dir) = pending.pop() {
+        for entry in fs::read_dir(&dir).unwrap() {
+            let entry = entry.unwrap();
+            let metadata = entry.metadata().unwrap();
+            if metadata.is_dir() {
+                pending.push(entry.path());
+            }
+            state.push((entry.path(), metadata.len(), metadata.modified().unwrap()));
+        }
+    }
+    state.sort();
+    state
+}
 
 #[test]
 fn test_load_at_operation() {
@@ -23,11 +59,11 @@ fn test_load_at_operation() {
 
     let mut tx = repo.start_transaction(&settings);
     let commit = write_random_commit(tx.mut_repo(), &settings);
-    let repo = tx.commit("add commit");
+    let repo = tx.commit("add commit").unwrap();
 
     let mut tx = repo.start_transaction(&settings);
     tx.mut_repo().remove_head(commit.id());
-    tx.commit("remove commit");
+    tx.commit("remove commit").unwrap();
 
     // If we load the repo at head, we should not see the commit since it was
     // removed
@@ -51,3 +87,311 @@ fn test_load_at_operation() {
     let old_repo = loader.load_at(repo.operation()).unwrap();
     assert!(old_repo.view().heads().contains(commit.id()));
 }
+
+#[test]
+fn test_reload_pinned_snapshot() {
+    let settings = testutils::user_settings();
+    let test_repo = TestRepo::init();
+    let repo = &test_repo.repo;
+
+    // Add enough commits that the index segment isn't squashed with the next
+    // one
+    let mut tx = repo.start_transaction(&settings);
+    let commits = (0..10)
+        .map(|_| write_random_commit(tx.mut_repo(), &settings))
+        .collect_vec();
+    let old_repo = tx.commit("add commits").unwrap();
+
+    // Hide a commit and add another through a separate handle
+    let loader = RepoLoader::init(
+        &settings,
+        old_repo.repo_path(),
+        &TestRepo::default_store_factories(),
+    )
+    .unwrap();
+    let writer_repo = loader.load_at_head(&settings).unwrap();
+    let mut tx = writer_repo.start_transaction(&settings);
+    tx.mut_repo().remove_head(commits[0].id());
+    let new_commit = write_random_commit(tx.mut_repo(), &settings);
+    tx.commit("hide and add commits").unwrap();
+
+    let new_repo = old_repo.reload_at_head(&settings).unwrap();
+    assert_ne!(new_repo.op_id(), old_repo.op_id());
+    let visible_heads = |repo: &ReadonlyRepo| -> Vec<CommitId> {
+        RevsetExpression::visible_heads()
+            .evaluate_programmatic(repo)
+            .unwrap()
+            .iter()
+            .collect()
+    };
+
+    // The old repo still answers queries at its own operation
+    assert!(visible_heads(&old_repo).contains(commits[0].id()));
+    assert!(!visible_heads(&old_repo).contains(new_commit.id()));
+    assert!(!old_repo.index().has_id(new_commit.id()));
+    let hidden_commit = old_repo.store().get_commit(commits[0].id()).unwrap();
+    assert!(hidden_commit.tree().is_ok());
+
+    assert!(!visible_heads(&new_repo).contains(commits[0].id()));
+    assert!(visible_heads(&new_repo).contains(new_commit.id()));
+    assert!(new_repo.index().has_id(commits[0].id()));
+
+    // The index segments loaded by the old repo are shared
+    fn default_index(repo: &ReadonlyRepo) -> &DefaultReadonlyIndex {
+        repo.readonly_index()
+            .as_any()
+            .downcast_ref::<DefaultReadonlyIndex>()
+            .unwrap()
+    }
+    assert!(default_index(&new_repo).includes_segments_of(default_index(&old_repo)));
+    assert!(!default_index(&old_repo).includes_segments_of(default_index(&new_repo)));
+}
+
+#[test]
+fn test_load_read_only() {
+    let settings = testutils::user_settings();
+    let test_repo = TestRepo::init();
+    let repo = &test_repo.repo;
+
+    let mut tx = repo.start_transaction(&settings);
+    let commit = write_random_commit(tx.mut_repo(), &settings);
+    let repo = tx.commit("add commit").unwrap();
+
+    // Remove the index so that loading the repo has to rebuild it
+    let index_ops_dir = repo.repo_path().join("index").join("operations");
+    for entry in fs::read_dir(&index_ops_dir).unwrap() {
+        fs::remove_file(entry.unwrap().path()).unwrap();
+    }
+    let state_before = dir_state(repo.repo_path());
+
+    let mut loader = RepoLoader::init(
+        &settings,
+        repo.repo_path(),
+        &TestRepo::default_store_factories(),
+    )
+    .unwrap();
+    assert!(!loader.is_read_only());
+    loader.set_read_only(true);
+    let head_repo = loader.load_at_head(&settings).unwrap();
+    assert!(head_repo.is_read_only());
+    assert!(head_repo.index().has_id(commit.id()));
+    assert!(head_repo.check_writable().is_err());
+
+    // Transactions can be started, but not committed
+    let mut tx = head_repo.start_transaction(&settings);
+    write_random_commit(tx.mut_repo(), &settings);
+    assert_matches!(
+        tx.commit("rejected"),
+        Err(TransactionCommitError::ReadOnly(_))
+    );
+
+    // Nothing was written to the repo, not even the rebuilt index
+    assert_eq!(dir_state(repo.repo_path()), state_before);
+}
+
+#[test]
+fn test_load_read_only_concurrent_operations() {
+    let settings = testutils::user_settings();
+    let test_repo = TestRepo::init();
+    let repo = &test_repo.repo;
+
+    let mut tx1 = repo.start_transaction(&settings);
+    write_random_commit(tx1.mut_repo(), &settings);
+    tx1.commit("transaction 1").unwrap();
+    let mut tx2 = repo.start_transaction(&settings);
+    write_random_commit(tx2.mut_repo(), &settings);
+    tx2.commit("transaction 2").unwrap();
+
+    // Divergent operations can't be merged without writing to the repo
+    let mut loader = RepoLoader::init(
+        &settings,
+        repo.repo_path(),
+        &TestRepo::default_store_factories(),
+    )
+    .unwrap();
+    loader.set_read_only(true);
+    assert!(matches!(
+        loader.load_at_head(&settings),
+        Err(RepoLoaderError::ReadOnly(_))
+    ));
+}
+
+#[test]
+fn test_load_while_op_heads_locked() {
+    let settings = testutils::user_settings();
+    let test_repo = TestRepo::init();
+    let repo = &test_repo.repo;
+    let old_op_id = repo.op_id().clone();
+
+    let mut tx = repo.start_transaction(&settings);
+    let commit = write_random_commit(tx.mut_repo(), &settings);
+    let repo = tx.commit("add commit").unwrap();
+    // Leave the old head behind, as a writer does between adding the new head
+    // and removing the old one
+    repo.op_heads_store().update_op_heads(&[], &old_op_id);
+
+    let loader = RepoLoader::init(
+        &settings,
+        repo.repo_path(),
+        &TestRepo::default_store_factories(),
+    )
+    .unwrap();
+    let mut read_only_loader = loader.clone();
+    read_only_loader.set_read_only(true);
+    thread::scope(|s| {
+        let (locked_sender, locked_receiver) = mpsc::channel();
+        let (done_sender, done_receiver) = mpsc::channel::<()>();
+        s.spawn({
+            let settings = settings.clone();
+            let repo = repo.clone();
+            move || {
+                // Another writer with a transaction open, currently holding
+                // the op heads lock. It gives up after a while so a failing
+                // test doesn't hang.
+                let mut tx = repo.start_transaction(&settings);
+                write_random_commit(tx.mut_repo(), &settings);
+                let lock = repo.op_heads_store().lock();
+                locked_sender.send(()).unwrap();
+                done_receiver.recv_timeout(Duration::from_secs(10)).ok();
+                drop(lock);
+                tx.commit("concurrent transaction").unwrap();
+            }
+        });
+        locked_receiver.recv().unwrap();
+
+        let start = Instant::now();
+        for loader in [&loader, &read_only_loader] {
+            let head_repo = loader.load_at_head(&settings).unwrap();
+            assert_eq!(head_repo.op_id(), repo.op_id());
+            // Like `jj log`
+            let commit_ids = RevsetExpression::all()
+                .evaluate_programmatic(head_repo.as_ref())
+                .unwrap()
+                .iter()
+                .collect_vec();
+            assert!(commit_ids.contains(commit.id()));
+        }
+        assert!(start.elapsed() < Duration::from_secs(5));
+        done_sender.send(()).unwrap();
+    });
+
+    // The stale head was removed without waiting for the lock
+    assert!(!repo.op_heads_store().get_op_heads().contains(&old_op_id));
+}
+
+#[cfg(unix)]
+#[test]
+fn test_load_read_only_detected() {
+    use std::os::unix::fs::PermissionsExt as _;
+
+    fn set_mode_recursively(dir: &Path, mode: u32) {
+        for (path, _, _) in dir_state(dir) {
+            if path.is_dir() {
+                fs::set_permissions(&path, fs::Permissions::from_mode(mode)).unwrap();
+            }
+        }
+        fs::set_permissions(dir, fs::Permissions::from_mode(mode)).unwrap();
+    }
+
+    let settings = testutils::user_settings();
+    let test_repo = TestRepo::init();
+    let repo = &test_repo.repo;
+
+    let mut tx = repo.start_transaction(&settings);
+    let commit = write_random_commit(tx.mut_repo(), &settings);
+    let repo = tx.commit("add commit").unwrap();
+
+    set_mode_recursively(repo.repo_path(), 0o555);
+    // Permissions don't apply to privileged users
+    if fs::File::create(repo.repo_path().join("op_heads").join("probe")).is_ok() {
+        set_mode_recursively(repo.repo_path(), 0o755);
+        return;
+    }
+
+    let loader = RepoLoader::init(
+        &settings,
+        repo.repo_path(),
+        &TestRepo::default_store_factories(),
+    )
+    .unwrap();
+    assert!(loader.is_read_only());
+    let head_repo = loader.load_at_head(&settings).unwrap();
+    assert!(head_repo.index().has_id(commit.id()));
+    set_mode_recursively(repo.repo_path(), 0o755);
+}
+
+#[test]
+fn test_load_missing_head() {
+    let settings = testutils::user_settings();
+    let test_repo = TestRepo::init_with_backend(TestRepoBackend::Local);
+    let repo = &test_repo.repo;
+
+    let mut tx = repo.start_transaction(&settings);
+    let commit1 = write_random_commit(tx.mut_repo(), &settings);
+    let commit2 = tx
+        .mut_repo()
+        .new_commit(
+            &settings,
+            vec![commit1.id().clone()],
+            commit1.tree_id().clone(),
+        )
+        .write()
+        .unwrap();
+    tx.mut_repo()
+        .set_local_branch_target("main", RefTarget::normal(commit2.id().clone()));
+    let repo = tx.commit("add commits").unwrap();
+
+    // Lose the head commit
+    fs::remove_file(
+        repo.repo_path()
+            .join("store")
+            .join("commits")
+            .join(commit2.id().hex()),
+    )
+    .unwrap();
+
+    let loader = RepoLoader::init(
+        &settings,
+        repo.repo_path(),
+        &TestRepo::default_store_factories(),
+    )
+    .unwrap();
+    assert_matches!(
+        loader.load_at_head(&settings),
+        Err(RepoLoaderError::MissingObjectForHead { commit_id, referenced_by: ViewReference::Head })
+            if commit_id == *commit2.id()
+    );
+
+    // The repo can still be loaded without the check
+    let repo = loader.load_at(repo.operation()).unwrap();
+    let missing = find_missing_commits(repo.as_ref(), ConsistencyCheck::Quick).unwrap();
+    assert_eq!(
+        missing.view_references,
+        vec![(commit2.id().clone(), ViewReference::Head)]
+    );
+    let missing = find_missing_commits(repo.as_ref(), ConsistencyCheck::Thorough).unwrap();
+    assert_eq!(
+        missing.view_references,
+        vec![
+            (commit2.id().clone(), ViewReference::Head),
+            (
+                commit2.id().clone(),
+                ViewReference::LocalBranch("main".to_owned())
+            ),
+        ]
+    );
+    assert_eq!(missing.ancestors, vec![]);
+
+    // Recovering drops the references and makes the parent a head again
+    let mut tx = repo.start_transaction(&settings);
+    let removed = tx
+        .mut_repo()
+        .remove_missing_view_commits(&settings, &HashMap::new())
+        .unwrap();
+    assert_eq!(removed, missing.view_references);
+    tx.commit("recover missing heads").unwrap();
+    let repo = loader.load_at_head(&settings).unwrap();
+    assert!(repo.view().heads().contains(commit1.id()));
+    assert!(!repo.view().heads().contains(commit2.id()));
+    assert!(repo.view().get_local_branch("main").is_absent());
+}
@@ -12,30 +12,40 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+use std::collections::BTreeMap;
 use std::fs::OpenOptions;
 use std::io::Write;
 #[cfg(unix)]
 use std::os::unix::fs::PermissionsExt;
 #[cfg(unix)]
 use std::os::unix::net::UnixListener;
-use std::path::Path;
-use std::sync::Arc;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::SystemTime;
 
+use assert_matches::assert_matches;
 use itertools::Itertools;
 use jj_lib::backend::{MergedTreeId, TreeId, TreeValue};
+use jj_lib::cancel::CancellationToken;
 use jj_lib::fsmonitor::FsmonitorKind;
-use jj_lib::local_working_copy::LocalWorkingCopy;
-use jj_lib::merge::Merge;
+use jj_lib::local_working_copy::{LocalWorkingCopy, LockedLocalWorkingCopy};
+use jj_lib::merge::{Merge, MergedTreeValue, MetadataConflict};
 use jj_lib::merged_tree::{MergedTree, MergedTreeBuilder};
 use jj_lib::op_store::{OperationId, WorkspaceId};
 use jj_lib::repo::{ReadonlyRepo, Repo};
-use jj_lib::repo_path::{RepoPath, RepoPathBuf, RepoPathComponent};
+use jj_lib::repo_path::{RepoPath, RepoPathBuf, RepoPathComponent, RepoPathComponentBuf};
 use jj_lib::settings::UserSettings;
-use jj_lib::working_copy::{CheckoutStats, SnapshotError, SnapshotOptions};
+use jj_lib::working_copy::{
+    CheckoutError, CheckoutSkipReason, CheckoutStats, SnapshotError, SnapshotOptions,
+    SnapshotSkipReason,
+};
 use jj_lib::workspace::LockedWorkspace;
 use test_case::test_case;
+use testutils::instrumented_backend::BackendCall;
 use testutils::{
-    commit_with_tree, create_tree, write_random_commit, TestRepoBackend, TestWorkspace,
+    commit_with_tree, create_single_tree, create_tree, write_random_commit, TestRepoBackend,
+    TestWorkspace,
 };
 
 fn to_owned_path_vec(paths: &[&RepoPath]) -> Vec<RepoPathBuf> {
@@ -157,7 +167,7 @@ fn test_checkout_file_transitions(backend: TestRepoBackend) {
             Kind::GitSubmodule => {
                 let mut tx = repo.start_transaction(settings);
                 let id = write_random_commit(tx.mut_repo(), settings).id().clone();
-                tx.commit("test");
+                tx.commit("test").unwrap();
                 Merge::normal(TreeValue::GitSubmodule(id))
             }
         };
@@ -204,7 +214,7 @@ fn test_checkout_file_transitions(backend: TestRepoBackend) {
     assert_eq!(new_tree.id(), right_tree_id);
 
     for (_left_kind, right_kind, path) in &files {
-        let wc_path = workspace_root.join(path.as_internal_file_string());
+        let wc_path = path.to_fs_path(&workspace_root);
         let maybe_metadata = wc_path.symlink_metadata();
         match right_kind {
             Kind::Missing => {
@@ -285,6 +295,207 @@ fn test_conflict_subdirectory() {
         .unwrap();
 }
 
+/// Creates a tree where "foo" is a file on one side and a directory
+/// containing "foo/bar" on the other.
+fn create_path_conflict_tree(repo: &Arc<ReadonlyRepo>) -> MergedTree {
+    let file_path = RepoPath::from_internal_string("foo");
+    let dir_file_path = RepoPath::from_internal_string("foo/bar");
+    let base_tree = create_single_tree(repo, &[]);
+    let file_tree = create_single_tree(repo, &[(file_path, "file contents")]);
+    let dir_tree = create_single_tree(repo, &[(dir_file_path, "dir contents")]);
+    MergedTree::new(Merge::from_removes_adds(
+        vec![base_tree],
+        vec![file_tree, dir_tree],
+    ))
+}
+
+#[test]
+fn test_path_conflict_checkout() {
+    let settings = testutils::user_settings();
+    let mut test_workspace = TestWorkspace::init(&settings);
+    let repo = &test_workspace.repo;
+    let workspace_root = test_workspace.workspace.workspace_root().clone();
+
+    let file_path = RepoPath::from_internal_string("foo");
+    let dir_file_path = RepoPath::from_internal_string("foo/bar");
+    let conflict_file_path = RepoPath::from_internal_string("foo.jjconflict-file");
+    let tree = create_path_conflict_tree(repo);
+    let commit = commit_with_tree(repo.store(), tree.id());
+    let ws = &mut test_workspace.workspace;
+    ws.check_out(repo.op_id().clone(), None, &commit).unwrap();
+
+    // The directory is checked out in place, and the file next to it
+    assert!(file_path.to_fs_path(&workspace_root).is_dir());
+    assert_eq!(
+        std::fs::read_to_string(dir_file_path.to_fs_path(&workspace_root)).unwrap(),
+        "dir contents"
+    );
+    assert_eq!(
+        std::fs::read_to_string(conflict_file_path.to_fs_path(&workspace_root)).unwrap(),
+        "file contents"
+    );
+
+    // Snapshotting the untouched working copy preserves the conflict
+    let new_tree = test_workspace.snapshot().unwrap();
+    assert_eq!(new_tree.id(), tree.id());
+
+    // Checking out another tree removes both paths
+    let repo = &test_workspace.repo;
+    let ws = &mut test_workspace.workspace;
+    let empty_commit = commit_with_tree(repo.store(), repo.store().empty_merged_tree_id());
+    ws.check_out(repo.op_id().clone(), None, &empty_commit)
+        .unwrap();
+    assert!(!file_path.to_fs_path(&workspace_root).exists());
+    assert!(!conflict_file_path.to_fs_path(&workspace_root).exists());
+}
+
+#[test]
+fn test_path_conflict_resolve_to_directory() {
+    let settings = testutils::user_settings();
+    let mut test_workspace = TestWorkspace::init(&settings);
+    let repo = &test_workspace.repo;
+    let workspace_root = test_workspace.workspace.workspace_root().clone();
+
+    let dir_file_path = RepoPath::from_internal_string("foo/bar");
+    let conflict_file_path = RepoPath::from_internal_string("foo.jjconflict-file");
+    let tree = create_path_conflict_tree(repo);
+    let commit = commit_with_tree(repo.store(), tree.id());
+    let ws = &mut test_workspace.workspace;
+    ws.check_out(repo.op_id().clone(), None, &commit).unwrap();
+
+    // Deleting the file resolves the conflict in favor of the directory, and
+    // changes to the directory are kept
+    std::fs::remove_file(conflict_file_path.to_fs_path(&workspace_root)).unwrap();
+    std::fs::write(dir_file_path.to_fs_path(&workspace_root), "new contents").unwrap();
+    let new_tree = test_workspace.snapshot().unwrap();
+    let expected_tree =
+        create_single_tree(&test_workspace.repo, &[(dir_file_path, "new contents")]);
+    assert_eq!(new_tree.id(), MergedTree::resolved(expected_tree).id());
+}
+
+#[test]
+fn test_path_conflict_resolve_to_file() {
+    let settings = testutils::user_settings();
+    let mut test_workspace = TestWorkspace::init(&settings);
+    let repo = &test_workspace.repo;
+    let workspace_root = test_workspace.workspace.workspace_root().clone();
+
+    let file_path = RepoPath::from_internal_string("foo");
+    let conflict_file_path = RepoPath::from_internal_string("foo.jjconflict-file");
+    let tree = create_path_conflict_tree(repo);
+    let commit = commit_with_tree(repo.store(), tree.id());
+    let ws = &mut test_workspace.workspace;
+    ws.check_out(repo.op_id().clone(), None, &commit).unwrap();
+
+    // Replacing the directory by the file resolves the conflict in favor of the
+    // file
+    std::fs::remove_dir_all(file_path.to_fs_path(&workspace_root)).unwrap();
+    std::fs::rename(
+        conflict_file_path.to_fs_path(&workspace_root),
+        file_path.to_fs_path(&workspace_root),
+    )
+    .unwrap();
+    let new_tree = test_workspace.snapshot().unwrap();
+    let expected_tree = create_single_tree(&test_workspace.repo, &[(file_path, "file contents")]);
+    assert_eq!(new_tree.id(), MergedTree::resolved(expected_tree).id());
+}
+
+#[cfg(unix)]
+#[test]
+fn test_metadata_conflict_checkout() {
+    let settings = testutils::user_settings();
+    let mut test_workspace = TestWorkspace::init(&settings);
+    let repo = test_workspace.repo.clone();
+    let store = repo.store();
+    let workspace_root = test_workspace.workspace.workspace_root().clone();
+
+    let exec_path = RepoPath::from_internal_string("exec");
+    let link_path = RepoPath::from_internal_string("link");
+    let file = |path, contents, executable| {
+        Some(TreeValue::File {
+            id: testutils::write_file(store, path, contents),
+            executable,
+        })
+    };
+    let symlink = Some(TreeValue::Symlink(
+        store.write_symlink(link_path, "target").unwrap(),
+    ));
+    let exec_value = Merge::from_removes_adds(
+        vec![None],
+        vec![
+            file(exec_path, "contents\n", true),
+            file(exec_path, "contents\n", false),
+        ],
+    );
+    let link_value = Merge::from_removes_adds(
+        vec![None],
+        vec![symlink.clone(), file(link_path, "file contents\n", false)],
+    );
+    let mut tree_builder = MergedTreeBuilder::new(store.empty_merged_tree_id());
+    tree_builder.set_or_remove(exec_path.to_owned(), exec_value.clone());
+    tree_builder.set_or_remove(link_path.to_owned(), link_value.clone());
+    let tree_id = tree_builder.write_tree(store).unwrap();
+    let commit = commit_with_tree(store, tree_id.clone());
+    let ws = &mut test_workspace.workspace;
+    ws.check_out(repo.op_id().clone(), None, &commit).unwrap();
+
+    // Both are checked out as non-executable regular files, and the conflicts
+    // are recorded in the working-copy state
+    let exec_disk_path = exec_path.to_fs_path(&workspace_root);
+    let link_disk_path = link_path.to_fs_path(&workspace_root);
+    let mode = |path: &Path| path.metadata().unwrap().permissions().mode();
+    assert_eq!(mode(&exec_disk_path) & 0o111, 0);
+    assert!(link_disk_path.symlink_metadata().unwrap().is_file());
+    assert_eq!(
+        std::fs::read_to_string(&link_disk_path).unwrap(),
+        "file contents\n"
+    );
+    let local_wc: &LocalWorkingCopy = ws.working_copy().as_any().downcast_ref().unwrap();
+    assert_eq!(
+        local_wc.metadata_conflicts().unwrap(),
+        &BTreeMap::from([
+            (exec_path.to_owned(), MetadataConflict::Executable),
+            (link_path.to_owned(), MetadataConflict::SymlinkVsFile),
+        ])
+    );
+
+    // Changing the executable bit on disk doesn't resolve the conflict
+    std::fs::set_permissions(&exec_disk_path, std::fs::Permissions::from_mode(0o755)).unwrap();
+    assert_eq!(*test_workspace.snapshot().unwrap().id(), tree_id);
+
+    // Edits to the contents are kept on the conflicting sides
+    std::fs::write(&exec_disk_path, "modified\n").unwrap();
+    std::fs::write(&link_disk_path, "modified\n").unwrap();
+    let new_tree = test_workspace.snapshot().unwrap();
+    assert_eq!(
+        new_tree.path_value(exec_path),
+        Merge::from_removes_adds(
+            vec![None],
+            vec![
+                file(exec_path, "modified\n", true),
+                file(exec_path, "modified\n", false),
+            ],
+        )
+    );
+    assert_eq!(
+        new_tree.path_value(link_path),
+        Merge::from_removes_adds(
+            vec![None],
+            vec![symlink, file(link_path, "modified\n", false)],
+        )
+    );
+
+    // Checking out a resolution clears the recorded conflicts
+    let resolved_tree = create_tree(&test_workspace.repo, &[(exec_path, "resolved\n")]);
+    let repo = &test_workspace.repo;
+    let resolved_commit = commit_with_tree(repo.store(), resolved_tree.id());
+    let ws = &mut test_workspace.workspace;
+    ws.check_out(repo.op_id().clone(), None, &resolved_commit)
+        .unwrap();
+    let local_wc: &LocalWorkingCopy = ws.working_copy().as_any().downcast_ref().unwrap();
+    assert!(local_wc.metadata_conflicts().unwrap().is_empty());
+}
+
 #[test]
 fn test_tree_builder_file_directory_transition() {
     let settings = testutils::user_settings();
@@ -305,7 +516,7 @@ fn test_tree_builder_file_directory_transition() {
     // Add file at parent_path
     let mut tree_builder = store.tree_builder(store.empty_tree_id().clone());
     testutils::write_normal_file(&mut tree_builder, parent_path, "");
-    let tree_id = tree_builder.write_tree();
+    let tree_id = tree_builder.write_tree().unwrap();
     check_out_tree(&tree_id);
     assert!(parent_path.to_fs_path(&workspace_root).is_file());
     assert!(!child_path.to_fs_path(&workspace_root).exists());
@@ -314,7 +525,7 @@ fn test_tree_builder_file_directory_transition() {
     let mut tree_builder = store.tree_builder(tree_id);
     tree_builder.remove(parent_path.to_owned());
     testutils::write_normal_file(&mut tree_builder, child_path, "");
-    let tree_id = tree_builder.write_tree();
+    let tree_id = tree_builder.write_tree().unwrap();
     check_out_tree(&tree_id);
     assert!(parent_path.to_fs_path(&workspace_root).is_dir());
     assert!(child_path.to_fs_path(&workspace_root).is_file());
@@ -323,7 +534,7 @@ fn test_tree_builder_file_directory_transition() {
     let mut tree_builder = store.tree_builder(tree_id);
     tree_builder.remove(child_path.to_owned());
     testutils::write_normal_file(&mut tree_builder, parent_path, "");
-    let tree_id = tree_builder.write_tree();
+    let tree_id = tree_builder.write_tree().unwrap();
     check_out_tree(&tree_id);
     assert!(parent_path.to_fs_path(&workspace_root).is_file());
     assert!(!child_path.to_fs_path(&workspace_root).exists());
@@ -381,6 +592,14 @@ fn test_conflicting_changes_on_disk() {
             added_files: 3,
             removed_files: 0,
             skipped_files: 3,
+            skipped_paths: vec![
+                (dir_file_path.to_owned(), CheckoutSkipReason::UntrackedPath),
+                (
+                    file_dir_path.join(RepoPathComponent::new("file")),
+                    CheckoutSkipReason::UntrackedPath,
+                ),
+                (file_file_path.to_owned(), CheckoutSkipReason::UntrackedPath),
+            ],
         }
     );
 
@@ -503,6 +722,153 @@ fn test_checkout_discard() {
     assert!(!reloaded_wc.file_states().unwrap().contains_path(file2_path));
 }
 
+/// Records the modification time of every file and directory in the working
+/// copy (except `.jj`). It changes if a file is rewritten, or if an entry is
+/// added to or removed from a directory.
+fn collect_disk_mtimes(dir: &Path, mtimes: &mut BTreeMap<PathBuf, SystemTime>) {
+    for entry in std::fs::read_dir(dir).unwrap() {
+        let entry = entry.unwrap();
+        if entry.file_name() == ".jj" {
+            continue;
+        }
+        let metadata = entry.metadata().unwrap();
+        mtimes.insert(entry.path(), metadata.modified().unwrap());
+        if metadata.is_dir() {
+            collect_disk_mtimes(&entry.path(), mtimes);
+        }
+    }
+}
+
+#[test]
+fn test_checkout_only_touches_diff() {
+    // Switching between commits that differ in a few files out of many should
+    // only touch the changed files on disk, and only record their states.
+    let settings = testutils::user_settings();
+    let mut test_workspace = TestWorkspace::init(&settings);
+    let repo = test_workspace.repo.clone();
+    let workspace_root = test_workspace.workspace.workspace_root().clone();
+
+    let paths = (0..100)
+        .cartesian_product(0..100)
+        .map(|(i, j)| RepoPathBuf::from_internal_string(format!("dir{i:02}/file{j:02}")))
+        .collect_vec();
+    let modified_path = RepoPath::from_internal_string("dir00/file00");
+    let added_path = RepoPath::from_internal_string("dir99/added");
+    let untouched_path = RepoPath::from_internal_string("dir50/file50");
+    let contents1 = paths
+        .iter()
+        .map(|path| (path.as_ref(), "initial"))
+        .collect_vec();
+    let contents2 = contents1
+        .iter()
+        .map(|&(path, contents)| {
+            if path == modified_path {
+                (path, "modified")
+            } else {
+                (path, contents)
+            }
+        })
+        .chain([(added_path, "added")])
+        .collect_vec();
+    let commit1 = commit_with_tree(repo.store(), create_tree(&repo, &contents1).id());
+    let commit2 = commit_with_tree(repo.store(), create_tree(&repo, &contents2).id());
+
+    let ws = &mut test_workspace.workspace;
+    ws.check_out(repo.op_id().clone(), None, &commit1).unwrap();
+    let wc: &LocalWorkingCopy = ws.working_copy().as_any().downcast_ref().unwrap();
+    let old_file_states = wc
+        .file_states()
+        .unwrap()
+        .iter()
+        .map(|(path, state)| (path.to_owned(), state))
+        .collect_vec();
+
+    // A file outside the diff is modified on disk
+    testutils::write_working_copy_file(&workspace_root, untouched_path, "modified on disk");
+    let mut old_mtimes = BTreeMap::new();
+    collect_disk_mtimes(&workspace_root, &mut old_mtimes);
+    // The checkout doesn't need to look into directories outside the diff.
+    // (This isn't enforced if the test runs as root.)
+    #[cfg(unix)]
+    let untouched_dir = untouched_path.parent().unwrap().to_fs_path(&workspace_root);
+    #[cfg(unix)]
+    std::fs::set_permissions(&untouched_dir, std::fs::Permissions::from_mode(0o000)).unwrap();
+
+    let stats = ws.check_out(repo.op_id().clone(), None, &commit2);
+    #[cfg(unix)]
+    std::fs::set_permissions(&untouched_dir, std::fs::Permissions::from_mode(0o755)).unwrap();
+    let stats = stats.unwrap();
+    assert_eq!(
+        stats,
+        CheckoutStats {
+            updated_files: 1,
+            added_files: 1,
+            removed_files: 0,
+            skipped_files: 0,
+            skipped_paths: vec![],
+        }
+    );
+    // Only the changed files and their directories were modified on disk
+    let mut new_mtimes = BTreeMap::new();
+    collect_disk_mtimes(&workspace_root, &mut new_mtimes);
+    let changed_disk_paths = [
+        modified_path.to_fs_path(&workspace_root),
+        added_path.to_fs_path(&workspace_root),
+        workspace_root.join("dir00"),
+        workspace_root.join("dir99"),
+    ];
+    assert_eq!(new_mtimes.len(), old_mtimes.len() + 1);
+    for (path, old_mtime) in &old_mtimes {
+        if !changed_disk_paths.contains(path) {
+            assert_eq!(new_mtimes.get(path), Some(old_mtime), "{path:?}");
+        }
+    }
+
+    // The file states of the other paths weren't re-recorded, and the file
+    // modified on disk wasn't overwritten
+    let wc: &LocalWorkingCopy = ws.working_copy().as_any().downcast_ref().unwrap();
+    let new_file_states = wc.file_states().unwrap();
+    for (path, old_state) in &old_file_states {
+        if **path != *modified_path {
+            assert_eq!(
+                new_file_states.get(path).as_ref(),
+                Some(old_state),
+                "{path:?}"
+            );
+        }
+    }
+    assert_eq!(new_file_states.paths().len(), paths.len() + 1);
+    assert_eq!(
+        std::fs::read_to_string(modified_path.to_fs_path(&workspace_root)).unwrap(),
+        "modified"
+    );
+    assert_eq!(
+        std::fs::read_to_string(added_path.to_fs_path(&workspace_root)).unwrap(),
+        "added"
+    );
+    assert_eq!(
+        std::fs::read_to_string(untouched_path.to_fs_path(&workspace_root)).unwrap(),
+        "modified on disk"
+    );
+
+    // The next snapshot picks up the change made on disk
+    let tree = test_workspace.snapshot().unwrap();
+    let expected_contents = contents2
+        .iter()
+        .map(|&(path, contents)| {
+            if path == untouched_path {
+                (path, "modified on disk")
+            } else {
+                (path, contents)
+            }
+        })
+        .collect_vec();
+    assert_eq!(
+        tree.id(),
+        MergedTreeId::resolved(create_single_tree(&repo, &expected_contents).id().clone())
+    );
+}
+
 #[test]
 fn test_snapshot_racy_timestamps() {
     // Tests that file modifications are detected even if they happen the same
@@ -538,6 +904,52 @@ fn test_snapshot_racy_timestamps() {
     }
 }
 
+#[test]
+fn test_snapshot_matches_tree_built_from_scratch() {
+    use rand::{Rng as _, SeedableRng as _};
+
+    let settings = testutils::user_settings();
+    let mut test_workspace = TestWorkspace::init(&settings);
+    let repo = test_workspace.repo.clone();
+    let workspace_root = test_workspace.workspace.workspace_root().clone();
+
+    let mut rng = rand_chacha::ChaCha8Rng::seed_from_u64(0);
+    let mut files: BTreeMap<RepoPathBuf, String> = BTreeMap::new();
+    for round in 0..20 {
+        // Add, modify, and delete random files, sometimes emptying or adding
+        // whole directories.
+        for _ in 0..rng.gen_range(1..30) {
+            let path = RepoPathBuf::from_internal_string(format!(
+                "dir{}/sub{}/file{}",
+                rng.gen_range(0..4),
+                rng.gen_range(0..3),
+                rng.gen_range(0..5)
+            ));
+            if rng.gen_bool(0.3) {
+                if files.remove(&path).is_some() {
+                    std::fs::remove_file(path.to_fs_path(&workspace_root)).unwrap();
+                }
+            } else {
+                let contents = format!("round {round}: {}\n", rng.gen::<u32>());
+                testutils::write_working_copy_file(&workspace_root, &path, &contents);
+                files.insert(path, contents);
+            }
+        }
+
+        let snapshot_tree = test_workspace.snapshot().unwrap();
+        let path_contents = files
+            .iter()
+            .map(|(path, contents)| (path.as_ref(), contents.as_str()))
+            .collect_vec();
+        let expected_tree = create_single_tree(&repo, &path_contents);
+        assert_eq!(
+            *snapshot_tree.id(),
+            MergedTreeId::resolved(expected_tree.id().clone()),
+            "snapshot differs in round {round}"
+        );
+    }
+}
+
 #[cfg(unix)]
 #[test]
 fn test_snapshot_special_file() {
@@ -567,6 +979,14 @@ fn test_snapshot_special_file() {
         .locked_wc()
         .snapshot(SnapshotOptions::empty_for_test())
         .unwrap();
+    // The socket should be reported as skipped
+    assert_eq!(
+        locked_ws.locked_wc().snapshot_stats().skipped_paths,
+        vec![(
+            RepoPathBuf::from_internal_string("socket"),
+            SnapshotSkipReason::SpecialFile
+        )]
+    );
     locked_ws.finish(OperationId::from_hex("abc123")).unwrap();
     let tree = store.get_root_tree(&tree_id).unwrap();
     // Only the regular files should be in the tree
@@ -597,6 +1017,121 @@ fn test_snapshot_special_file() {
     );
 }
 
+#[test]
+fn test_snapshot_reports_changes_before_writing_tree() {
+    let settings = testutils::user_settings();
+    let (mut test_workspace, counts) = TestWorkspace::init_instrumented(&settings);
+    let workspace_root = test_workspace.workspace.workspace_root().clone();
+
+    let unchanged_path = RepoPath::from_internal_string("unchanged");
+    let modified_path = RepoPath::from_internal_string("modified");
+    let deleted_path = RepoPath::from_internal_string("deleted");
+    let added_path = RepoPath::from_internal_string("dir/added");
+    for path in [unchanged_path, modified_path, deleted_path] {
+        testutils::write_working_copy_file(&workspace_root, path, "initial");
+    }
+    test_workspace.snapshot().unwrap();
+
+    testutils::write_working_copy_file(&workspace_root, modified_path, "modified contents");
+    std::fs::remove_file(deleted_path.to_fs_path(&workspace_root)).unwrap();
+    testutils::write_working_copy_file(&workspace_root, added_path, "added");
+
+    // Record how many trees had been written when each change was reported
+    let changes = Mutex::new(vec![]);
+    let on_change = |path: &RepoPath, value: &MergedTreeValue| {
+        changes.lock().unwrap().push((
+            path.to_owned(),
+            value.is_present(),
+            counts.get(BackendCall::WriteTree),
+        ));
+    };
+    counts.reset();
+    let mut locked_ws = test_workspace
+        .workspace
+        .start_working_copy_mutation()
+        .unwrap();
+    locked_ws
+        .locked_wc()
+        .snapshot(SnapshotOptions {
+            on_change: Some(&on_change),
+            ..SnapshotOptions::empty_for_test()
+        })
+        .unwrap();
+    locked_ws.finish(OperationId::from_hex("abc123")).unwrap();
+    assert_ne!(counts.get(BackendCall::WriteTree), 0);
+    assert_eq!(
+        changes
+            .into_inner()
+            .unwrap()
+            .into_iter()
+            .sorted()
+            .collect_vec(),
+        vec![
+            (deleted_path.to_owned(), false, 0),
+            (added_path.to_owned(), true, 0),
+            (modified_path.to_owned(), true, 0),
+        ]
+    );
+}
+
+#[cfg(unix)]
+#[test]
+fn test_snapshot_looping_symlinks() {
+    // Tests that symlinks that point to themselves or to a parent directory are
+    // recorded as symlinks without being followed.
+    let settings = testutils::user_settings();
+    let mut test_workspace = TestWorkspace::init(&settings);
+    let workspace_root = test_workspace.workspace.workspace_root().clone();
+
+    let self_link_path = RepoPath::from_internal_string("self");
+    std::os::unix::fs::symlink("self", self_link_path.to_fs_path(&workspace_root)).unwrap();
+    let dir_path = RepoPath::from_internal_string("dir");
+    std::fs::create_dir(dir_path.to_fs_path(&workspace_root)).unwrap();
+    let parent_link_path = RepoPath::from_internal_string("dir/parent");
+    std::os::unix::fs::symlink("..", parent_link_path.to_fs_path(&workspace_root)).unwrap();
+
+    let tree = test_workspace.snapshot().unwrap();
+    assert_eq!(
+        tree.entries().map(|(path, _value)| path).collect_vec(),
+        to_owned_path_vec(&[parent_link_path, self_link_path])
+    );
+    for path in [parent_link_path, self_link_path] {
+        assert_matches!(
+            tree.path_value(path).into_resolved(),
+            Ok(Some(TreeValue::Symlink(_)))
+        );
+    }
+}
+
+#[cfg(unix)]
+#[test]
+fn test_checkout_non_utf8_file_name() {
+    // Tests that a file whose name isn't valid UTF-8 (as can happen in Git repos)
+    // is checked out with its raw name, and that it's snapshotted unchanged.
+    use std::ffi::OsStr;
+    use std::os::unix::ffi::OsStrExt as _;
+
+    let settings = testutils::user_settings();
+    let mut test_workspace = TestWorkspace::init_with_backend(&settings, TestRepoBackend::Git);
+    let repo = test_workspace.repo.clone();
+    let workspace_root = test_workspace.workspace.workspace_root().clone();
+
+    let name = RepoPathComponentBuf::from_bytes(b"caf\xe9");
+    let file_path = RepoPathBuf::root().join(&name);
+    let tree = create_tree(&repo, &[(&file_path, "contents")]);
+    let commit = commit_with_tree(repo.store(), tree.id());
+
+    let ws = &mut test_workspace.workspace;
+    ws.check_out(repo.op_id().clone(), None, &commit).unwrap();
+    let disk_path = workspace_root.join(OsStr::from_bytes(b"caf\xe9"));
+    assert_eq!(file_path.to_fs_path(&workspace_root), disk_path);
+    assert_eq!(std::fs::read(&disk_path).unwrap(), b"contents");
+
+    // Snapshotting the unmodified working copy should result in the same tree
+    let new_tree = test_workspace.snapshot().unwrap();
+    assert_eq!(new_tree.id(), tree.id());
+}
+
 #[test]
 fn test_gitignores() {
     // Tests that .gitignore files are respected.
@@ -707,6 +1242,60 @@ fn test_gitignores_in_ignored_dir() {
     );
 }
 
+#[test]
+fn test_gitattributes_line_endings() {
+    // Tests that line endings are converted according to the .gitattributes
+    // file in the checked-out tree.
+    let settings = testutils::user_settings();
+    let mut test_workspace = TestWorkspace::init(&settings);
+    let repo = test_workspace.repo.clone();
+    let workspace_root = test_workspace.workspace.workspace_root().clone();
+
+    let gitattributes_path = RepoPath::from_internal_string(".gitattributes");
+    let crlf_path = RepoPath::from_internal_string("dir/crlf.txt");
+    let text_path = RepoPath::from_internal_string("text.md");
+    let binary_path = RepoPath::from_internal_string("binary.txt");
+    let new_path = RepoPath::from_internal_string("new.txt");
+    let tree = create_tree(
+        &repo,
+        &[
+            (
+                gitattributes_path,
+                "*.txt eol=crlf\n*.md text\nbinary.txt -text\n",
+            ),
+            (crlf_path, "1\n2\n"),
+            (text_path, "1\n"),
+            (binary_path, "1\n"),
+        ],
+    );
+    let commit = commit_with_tree(repo.store(), tree.id());
+    let ws = &mut test_workspace.workspace;
+    ws.check_out(repo.op_id().clone(), None, &commit).unwrap();
+
+    let read_disk = |path: &RepoPath| std::fs::read(path.to_fs_path(&workspace_root)).unwrap();
+    assert_eq!(read_disk(crlf_path), b"1\r\n2\r\n");
+    assert_eq!(read_disk(text_path), b"1\n");
+    assert_eq!(read_disk(binary_path), b"1\n");
+
+    // Files checked out with CRLF aren't considered modified
+    let new_tree = test_workspace.snapshot().unwrap();
+    assert_eq!(new_tree.id(), tree.id());
+
+    testutils::write_working_copy_file(&workspace_root, crlf_path, "3\r\n");
+    testutils::write_working_copy_file(&workspace_root, text_path, "3\r\n");
+    testutils::write_working_copy_file(&workspace_root, binary_path, "3\r\n");
+    testutils::write_working_copy_file(&workspace_root, new_path, "3\r\n");
+    let new_tree = test_workspace.snapshot().unwrap();
+    let read_tree = |path: &RepoPath| match new_tree.path_value(path).into_resolved() {
+        Ok(Some(TreeValue::File { id, .. })) => testutils::read_file(repo.store(), path, &id),
+        value => panic!("unexpected value at {path:?}: {value:?}"),
+    };
+    assert_eq!(read_tree(crlf_path), b"3\n");
+    assert_eq!(read_tree(text_path), b"3\n");
+    assert_eq!(read_tree(binary_path), b"3\r\n");
+    assert_eq!(read_tree(new_path), b"3\n");
+}
+
 #[test]
 fn test_gitignores_checkout_never_overwrites_ignored() {
     // Tests that a .gitignore'd file doesn't get overwritten if check out a commit
@@ -771,7 +1360,7 @@ fn test_gitignores_ignored_directory_already_tracked() {
                 }
             }
         }
-        let id = tree_builder.write_tree();
+        let id = tree_builder.write_tree().unwrap();
         MergedTree::legacy(store.get_tree(RepoPath::root(), &id).unwrap())
     };
 
@@ -874,6 +1463,61 @@ fn test_dotgit_ignored() {
     assert_eq!(new_tree.id(), empty_tree_id);
 }
 
+#[test]
+fn test_dotjj_ignored() {
+    // Tests that files planted under .jj directories are never snapshotted, at
+    // the workspace root or below.
+
+    let settings = testutils::user_settings();
+    let mut test_workspace = TestWorkspace::init(&settings);
+    let store = test_workspace.repo.store().clone();
+    let workspace_root = test_workspace.workspace.workspace_root().clone();
+
+    for path in [".jj/planted", ".jj/repo/store/planted", "dir/.jj/file"] {
+        testutils::write_working_copy_file(
+            &workspace_root,
+            RepoPath::from_internal_string(path),
+            "contents",
+        );
+    }
+    let new_tree = test_workspace.snapshot().unwrap();
+    assert_eq!(new_tree.id(), store.empty_merged_tree_id());
+}
+
+#[test]
+fn test_check_out_reserved_path() {
+    // Tests that trees with paths under .jj or .git directories, which may have
+    // been written by other tools, are never checked out.
+
+    let settings = testutils::user_settings();
+    let mut test_workspace = TestWorkspace::init(&settings);
+    let repo = &test_workspace.repo;
+    let workspace_root = test_workspace.workspace.workspace_root().clone();
+
+    let file_path = RepoPath::from_internal_string("file");
+    let reserved_path = RepoPath::from_internal_string(".jj/repo/store/type");
+    let tree = MergedTree::resolved(testutils::create_single_tree_unchecked(
+        repo,
+        &[(file_path, "contents"), (reserved_path, "planted")],
+    ));
+    let commit = commit_with_tree(repo.store(), tree.id());
+    let ws = &mut test_workspace.workspace;
+    let err = ws
+        .check_out(repo.op_id().clone(), None, &commit)
+        .unwrap_err();
+    assert_matches!(
+        err,
+        CheckoutError::ReservedPath { path } if path == reserved_path.to_owned()
+    );
+
+    // Nothing was written, not even the other files
+    assert!(!file_path.to_fs_path(&workspace_root).exists());
+    assert_ne!(
+        std::fs::read_to_string(reserved_path.to_fs_path(&workspace_root)).unwrap(),
+        "planted"
+    );
+}
+
 #[test]
 fn test_gitsubmodule() {
     // Tests that git submodules are ignored.
@@ -900,14 +1544,14 @@ fn test_gitsubmodule() {
 
     let mut tx = repo.start_transaction(&settings);
     let submodule_id = write_random_commit(tx.mut_repo(), &settings).id().clone();
-    tx.commit("create submodule commit");
+    tx.commit("create submodule commit").unwrap();
 
     tree_builder.set(
         submodule_path.to_owned(),
         TreeValue::GitSubmodule(submodule_id),
     );
 
-    let tree_id = MergedTreeId::Legacy(tree_builder.write_tree());
+    let tree_id = MergedTreeId::Legacy(tree_builder.write_tree().unwrap());
     let tree = store.get_root_tree(&tree_id).unwrap();
     let commit = commit_with_tree(repo.store(), tree.id());
     let ws = &mut test_workspace.workspace;
@@ -949,14 +1593,119 @@ fn test_existing_directory_symlink() {
     let tree = create_tree(repo, &[(file_path, "contents")]);
     let commit = commit_with_tree(repo.store(), tree.id());
 
-    // Checkout should fail because "parent" already exists and is a symlink.
+    // The file should be skipped because "parent" already exists and is a
+    // symlink.
     let ws = &mut test_workspace.workspace;
-    assert!(ws.check_out(repo.op_id().clone(), None, &commit).is_err());
+    let stats = ws.check_out(repo.op_id().clone(), None, &commit).unwrap();
+    assert_eq!(
+        stats.skipped_paths,
+        vec![(file_path.to_owned(), CheckoutSkipReason::SymlinkedParent)]
+    );
 
     // Therefore, "../escaped" shouldn't be created.
     assert!(!workspace_root.parent().unwrap().join("escaped").exists());
 }
 
+#[cfg(unix)]
+#[test]
+fn test_checkout_replaces_symlink_without_touching_target() {
+    let settings = testutils::user_settings();
+    let mut test_workspace = TestWorkspace::init(&settings);
+    let repo = &test_workspace.repo;
+    let workspace_root = test_workspace.workspace.workspace_root().clone();
+    let outside_dir = testutils::new_temp_dir();
+    let target_path = outside_dir.path().join("target");
+    std::fs::write(&target_path, "outside contents").unwrap();
+
+    // A tracked symlink pointing outside the workspace is replaced by a regular
+    // file.
+    let path = RepoPath::from_internal_string("link");
+    let store = repo.store();
+    let mut tree_builder = store.tree_builder(store.empty_tree_id().clone());
+    testutils::write_symlink(&mut tree_builder, path, target_path.to_str().unwrap());
+    let id = tree_builder.write_tree().unwrap();
+    let tree1 = MergedTree::legacy(store.get_tree(RepoPath::root(), &id).unwrap());
+    let tree2 = create_tree(repo, &[(path, "new contents")]);
+    let commit1 = commit_with_tree(repo.store(), tree1.id());
+    let commit2 = commit_with_tree(repo.store(), tree2.id());
+
+    let ws = &mut test_workspace.workspace;
+    ws.check_out(repo.op_id().clone(), None, &commit1).unwrap();
+    let disk_path = path.to_fs_path(&workspace_root);
+    assert!(disk_path
+        .symlink_metadata()
+        .unwrap()
+        .file_type()
+        .is_symlink());
+
+    let stats = ws.check_out(repo.op_id().clone(), None, &commit2).unwrap();
+    assert_eq!(stats.updated_files, 1);
+    assert_eq!(stats.skipped_paths, vec![]);
+    assert!(disk_path.symlink_metadata().unwrap().is_file());
+    assert_eq!(std::fs::read_to_string(&disk_path).unwrap(), "new contents");
+    assert_eq!(
+        std::fs::read_to_string(&target_path).unwrap(),
+        "outside contents"
+    );
+}
+
+#[cfg(unix)]
+#[test]
+fn test_checkout_through_symlinked_parent() {
+    let settings = testutils::user_settings();
+    let mut test_workspace = TestWorkspace::init(&settings);
+    let repo = test_workspace.repo.clone();
+    let workspace_root = test_workspace.workspace.workspace_root().clone();
+    let outside_dir = testutils::new_temp_dir();
+    std::fs::create_dir(outside_dir.path().join("sub")).unwrap();
+    let outside_file = outside_dir.path().join("sub").join("file");
+    std::fs::write(&outside_file, "outside contents").unwrap();
+
+    let tracked_path = RepoPath::from_internal_string("dir/sub/file");
+    let new_path = RepoPath::from_internal_string("dir/sub/new");
+    let tree1 = create_tree(&repo, &[(tracked_path, "contents")]);
+    let tree2 = create_tree(&repo, &[(tracked_path, "modified"), (new_path, "new")]);
+    let commit1 = commit_with_tree(repo.store(), tree1.id());
+    let commit2 = commit_with_tree(repo.store(), tree2.id());
+    let commit3 = commit_with_tree(repo.store(), repo.store().empty_merged_tree_id());
+
+    let ws = &mut test_workspace.workspace;
+    ws.check_out(repo.op_id().clone(), None, &commit1).unwrap();
+
+    // Replace the directory in the middle of the tracked path with a symlink
+    // pointing outside the workspace.
+    std::fs::remove_dir_all(workspace_root.join("dir")).unwrap();
+    std::os::unix::fs::symlink(outside_dir.path(), workspace_root.join("dir")).unwrap();
+
+    // Neither the modified nor the added file is written through the symlink.
+    let stats = ws.check_out(repo.op_id().clone(), None, &commit2).unwrap();
+    assert_eq!(
+        stats.skipped_paths,
+        vec![
+            (tracked_path.to_owned(), CheckoutSkipReason::SymlinkedParent),
+            (new_path.to_owned(), CheckoutSkipReason::SymlinkedParent),
+        ]
+    );
+    assert_eq!(
+        std::fs::read_to_string(&outside_file).unwrap(),
+        "outside contents"
+    );
+    assert!(!outside_dir.path().join("sub").join("new").exists());
+
+    // Removing the files doesn't remove anything through the symlink either.
+    ws.check_out(repo.op_id().clone(), None, &commit3).unwrap();
+    assert_eq!(
+        std::fs::read_to_string(&outside_file).unwrap(),
+        "outside contents"
+    );
+    assert!(workspace_root
+        .join("dir")
+        .symlink_metadata()
+        .unwrap()
+        .file_type()
+        .is_symlink());
+}
+
 #[test]
 fn test_fsmonitor() {
     let settings = testutils::user_settings();
@@ -1051,6 +1800,70 @@ fn test_fsmonitor() {
     }
 }
 
+#[cfg(unix)]
+#[test]
+fn test_exec_bit_unsupported() {
+    let settings = testutils::user_settings();
+    let mut test_workspace = TestWorkspace::init(&settings);
+    let repo = test_workspace.repo.clone();
+    let store = repo.store();
+    let workspace_root = test_workspace.workspace.workspace_root().clone();
+
+    // The temporary directory preserves the executable bit
+    let ws = &mut test_workspace.workspace;
+    let local_wc: &LocalWorkingCopy = ws.working_copy().as_any().downcast_ref().unwrap();
+    assert!(local_wc.exec_bit_supported().unwrap());
+
+    // Simulate a filesystem that doesn't
+    let mut locked_ws = ws.start_working_copy_mutation().unwrap();
+    let locked_local_wc: &mut LockedLocalWorkingCopy =
+        locked_ws.locked_wc().as_any_mut().downcast_mut().unwrap();
+    locked_local_wc.set_exec_bit_supported(false).unwrap();
+    locked_ws.finish(repo.op_id().clone()).unwrap();
+
+    let executable_path = RepoPath::from_internal_string("executable");
+    let normal_path = RepoPath::from_internal_string("normal");
+    let mut tree_builder = MergedTreeBuilder::new(store.empty_merged_tree_id());
+    for (path, executable) in [(executable_path, true), (normal_path, false)] {
+        let id = testutils::write_file(store, path, "contents\n");
+        tree_builder.set_or_remove(
+            path.to_owned(),
+            Merge::normal(TreeValue::File { id, executable }),
+        );
+    }
+    let tree_id = tree_builder.write_tree(store).unwrap();
+    let commit = commit_with_tree(store, tree_id.clone());
+    ws.check_out(repo.op_id().clone(), None, &commit).unwrap();
+
+    // The executable bit isn't set on disk, but that's not a change
+    let executable_disk_path = executable_path.to_fs_path(&workspace_root);
+    let normal_disk_path = normal_path.to_fs_path(&workspace_root);
+    let mode = |path: &Path| path.metadata().unwrap().permissions().mode();
+    assert_eq!(mode(&executable_disk_path) & 0o111, 0);
+    assert_eq!(*test_workspace.snapshot().unwrap().id(), tree_id);
+
+    // Changes to the executable bit on disk are ignored, but changes to the
+    // contents aren't
+    std::fs::set_permissions(&normal_disk_path, std::fs::Permissions::from_mode(0o755)).unwrap();
+    assert_eq!(*test_workspace.snapshot().unwrap().id(), tree_id);
+    std::fs::write(&executable_disk_path, "modified\n").unwrap();
+    let new_tree = test_workspace.snapshot().unwrap();
+    assert_eq!(
+        new_tree.path_value(executable_path),
+        Merge::normal(TreeValue::File {
+            id: testutils::write_file(store, executable_path, "modified\n"),
+            executable: true,
+        })
+    );
+    assert_eq!(
+        new_tree.path_value(normal_path),
+        Merge::normal(TreeValue::File {
+            id: testutils::write_file(store, normal_path, "contents\n"),
+            executable: false,
+        })
+    );
+}
+
 #[test]
 fn test_snapshot_max_new_file_size() {
     let settings = UserSettings::from_config(
@@ -1084,3 +1897,55 @@ fn test_snapshot_max_new_file_size() {
         "the failure should be attributed to new file size"
     );
 }
+
+#[test]
+fn test_snapshot_cancelled() {
+    let settings = testutils::user_settings();
+    let mut test_workspace = TestWorkspace::init(&settings);
+    let repo = test_workspace.repo.clone();
+    let workspace_root = test_workspace.workspace.workspace_root().clone();
+
+    let path_contents = (0..200)
+        .map(|i| {
+            let path = RepoPathBuf::from_internal_string(format!("dir{}/file{i}", i % 10));
+            (path, format!("contents {i}\n"))
+        })
+        .collect_vec();
+    for (path, contents) in &path_contents {
+        testutils::write_working_copy_file(&workspace_root, path, contents);
+    }
+
+    // Cancel the snapshot after some files have been visited. The progress
+    // callback is called from the snapshot's worker threads.
+    let cancel = CancellationToken::new();
+    let num_visited = AtomicUsize::new(0);
+    let progress = |_path: &RepoPath| {
+        if num_visited.fetch_add(1, Ordering::Relaxed) + 1 == 10 {
+            cancel.cancel();
+        }
+    };
+    let mut locked_ws = test_workspace
+        .workspace
+        .start_working_copy_mutation()
+        .unwrap();
+    let result = locked_ws.locked_wc().snapshot(SnapshotOptions {
+        progress: Some(&progress),
+        on_change: None,
+        cancel: Some(&cancel),
+        ..SnapshotOptions::empty_for_test()
+    });
+    assert_matches!(result, Err(SnapshotError::Cancelled(_)));
+    assert!(num_visited.load(Ordering::Relaxed) < path_contents.len());
+    locked_ws.finish(repo.op_id().clone()).unwrap();
+
+    // A subsequent snapshot records all files
+    let new_tree = test_workspace.snapshot().unwrap();
+    let expected_tree = create_tree(
+        &repo,
+        &path_contents
+            .iter()
+            .map(|(path, contents)| (path.as_ref(), contents.as_str()))
+            .collect_vec(),
+    );
+    assert_eq!(new_tree.id(), expected_tree.id());
+}
@@ -74,6 +74,7 @@ fn test_sparse_checkout() {
             added_files: 0,
             removed_files: 3,
             skipped_files: 0,
+            skipped_paths: vec![],
         }
     );
     assert_eq!(
@@ -123,6 +124,7 @@ fn test_sparse_checkout() {
             added_files: 2,
             removed_files: 2,
             skipped_files: 0,
+            skipped_paths: vec![],
         }
     );
     assert_eq!(locked_wc.sparse_patterns().unwrap(), sparse_patterns);
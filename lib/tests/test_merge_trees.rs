@@ -14,10 +14,12 @@
 
 use itertools::Itertools;
 use jj_lib::backend::TreeValue;
+use jj_lib::op_store::RefTarget;
 use jj_lib::repo::Repo;
 use jj_lib::repo_path::{RepoPath, RepoPathComponent};
 use jj_lib::rewrite::rebase_commit;
 use jj_lib::tree::{merge_trees, Tree};
+use testutils::test_repo_builder::TestRepoBuilder;
 use testutils::{create_single_tree, create_tree, TestRepo};
 
 #[test]
@@ -60,7 +62,7 @@ fn test_same_type() {
                 );
             }
         }
-        let tree_id = tree_builder.write_tree();
+        let tree_id = tree_builder.write_tree().unwrap();
         store.get_tree(RepoPath::root(), &tree_id).unwrap()
     };
 
@@ -74,7 +76,7 @@ fn test_same_type() {
     // Check that we have exactly the paths we expect in the merged tree
     let names = merged_tree
         .entries_non_recursive()
-        .map(|entry| entry.name().as_str())
+        .map(|entry| entry.name().to_str().unwrap())
         .collect_vec();
     assert_eq!(
         names,
@@ -202,7 +204,7 @@ fn test_executable() {
                 testutils::write_normal_file(&mut tree_builder, repo_path, "contents");
             }
         }
-        let tree_id = tree_builder.write_tree();
+        let tree_id = tree_builder.write_tree().unwrap();
         store.get_tree(RepoPath::root(), &tree_id).unwrap()
     };
 
@@ -236,128 +238,115 @@ fn test_executable() {
 #[test]
 fn test_subtrees() {
     // Tests that subtrees are merged.
+    let fixture = TestRepoBuilder::new()
+        .commit("base")
+        .file("f1", "contents")
+        .file("d1/f1", "contents")
+        .file("d1/d1/f1", "contents")
+        .file("d1/d1/d1/f1", "contents")
+        .commit_on("base", "side1")
+        .file("f2", "contents")
+        .file("d1/f2", "contents")
+        .commit_on("base", "side2")
+        .file("d1/d1/d1/f2", "contents")
+        .commit_with_parents(&["side1", "side2"], "merge")
+        .commit("expected")
+        .file("f1", "contents")
+        .file("f2", "contents")
+        .file("d1/f1", "contents")
+        .file("d1/f2", "contents")
+        .file("d1/d1/f1", "contents")
+        .file("d1/d1/d1/f1", "contents")
+        .file("d1/d1/d1/f2", "contents")
+        .build();
 
-    let test_repo = TestRepo::init();
-    let repo = &test_repo.repo;
-    let store = repo.store();
-
-    let write_tree = |paths: Vec<&str>| -> Tree {
-        let mut tree_builder = store.tree_builder(store.empty_tree_id().clone());
-        for path in paths {
-            testutils::write_normal_file(
-                &mut tree_builder,
-                RepoPath::from_internal_string(path),
-                &format!("contents of {path:?}"),
-            );
-        }
-        let tree_id = tree_builder.write_tree();
-        store.get_tree(RepoPath::root(), &tree_id).unwrap()
-    };
-
-    let base_tree = write_tree(vec!["f1", "d1/f1", "d1/d1/f1", "d1/d1/d1/f1"]);
-    let side1_tree = write_tree(vec![
-        "f1",
-        "f2",
-        "d1/f1",
-        "d1/f2",
-        "d1/d1/f1",
-        "d1/d1/d1/f1",
-    ]);
-    let side2_tree = write_tree(vec![
-        "f1",
-        "d1/f1",
-        "d1/d1/f1",
-        "d1/d1/d1/f1",
-        "d1/d1/d1/f2",
-    ]);
-
-    let merged_tree = merge_trees(&side1_tree, &base_tree, &side2_tree).unwrap();
-    let entries = merged_tree.entries().collect_vec();
-
-    let expected_tree = write_tree(vec![
-        "f1",
-        "f2",
-        "d1/f1",
-        "d1/f2",
-        "d1/d1/f1",
-        "d1/d1/d1/f1",
-        "d1/d1/d1/f2",
-    ]);
-    let expected_entries = expected_tree.entries().collect_vec();
-    assert_eq!(entries, expected_entries);
+    assert_eq!(
+        fixture.commit("merge").tree_id(),
+        fixture.commit("expected").tree_id()
+    );
 }
 
 #[test]
 fn test_subtree_becomes_empty() {
     // Tests that subtrees that become empty are removed from the parent tree.
+    let fixture = TestRepoBuilder::new()
+        .commit("base")
+        .file("f1", "contents")
+        .file("d1/f1", "contents")
+        .file("d1/d1/d1/f1", "contents")
+        .file("d1/d1/d1/f2", "contents")
+        .commit_on("base", "side1")
+        .remove_file("d1/d1/d1/f2")
+        .commit_on("base", "side2")
+        .remove_file("f1")
+        .remove_file("d1/f1")
+        .remove_file("d1/d1/d1/f1")
+        .commit_with_parents(&["side1", "side2"], "merge")
+        .build();
 
-    let test_repo = TestRepo::init();
-    let repo = &test_repo.repo;
-    let store = repo.store();
-
-    let write_tree = |paths: Vec<&str>| -> Tree {
-        let mut tree_builder = store.tree_builder(store.empty_tree_id().clone());
-        for path in paths {
-            testutils::write_normal_file(
-                &mut tree_builder,
-                RepoPath::from_internal_string(path),
-                &format!("contents of {path:?}"),
-            );
-        }
-        let tree_id = tree_builder.write_tree();
-        store.get_tree(RepoPath::root(), &tree_id).unwrap()
-    };
-
-    let base_tree = write_tree(vec!["f1", "d1/f1", "d1/d1/d1/f1", "d1/d1/d1/f2"]);
-    let side1_tree = write_tree(vec!["f1", "d1/f1", "d1/d1/d1/f1"]);
-    let side2_tree = write_tree(vec!["d1/d1/d1/f2"]);
-
-    let merged_tree = merge_trees(&side1_tree, &base_tree, &side2_tree).unwrap();
-    assert_eq!(merged_tree.id(), store.empty_tree_id());
+    assert_eq!(
+        *fixture.commit("merge").tree_id(),
+        fixture.repo().store().empty_merged_tree_id()
+    );
 }
 
 #[test]
 fn test_subtree_one_missing() {
     // Tests that merging trees where one side is missing is resolved as if the
     // missing side was empty.
-    let test_repo = TestRepo::init();
-    let repo = &test_repo.repo;
-    let store = repo.store();
-
-    let write_tree = |paths: Vec<&str>| -> Tree {
-        let mut tree_builder = store.tree_builder(store.empty_tree_id().clone());
-        for path in paths {
-            testutils::write_normal_file(
-                &mut tree_builder,
-                RepoPath::from_internal_string(path),
-                &format!("contents of {path:?}"),
-            );
-        }
-        let tree_id = tree_builder.write_tree();
-        store.get_tree(RepoPath::root(), &tree_id).unwrap()
-    };
-
-    let tree1 = write_tree(vec![]);
-    let tree2 = write_tree(vec!["d1/f1"]);
-    let tree3 = write_tree(vec!["d1/f1", "d1/f2"]);
+    let fixture = TestRepoBuilder::new()
+        .commit("empty")
+        .commit("one")
+        .file("d1/f1", "contents")
+        .commit_on("one", "two")
+        .file("d1/f2", "contents")
+        .commit("only_f2")
+        .file("d1/f2", "contents")
+        .build();
+    let tree1 = fixture.commit("empty").tree().unwrap();
+    let tree2 = fixture.commit("one").tree().unwrap();
+    let tree3 = fixture.commit("two").tree().unwrap();
 
     // The two sides add different trees
-    let merged_tree = merge_trees(&tree2, &tree1, &tree3).unwrap();
-    let expected_entries = write_tree(vec!["d1/f1", "d1/f2"]).entries().collect_vec();
-    assert_eq!(merged_tree.entries().collect_vec(), expected_entries);
+    let merged_tree = tree2.merge(&tree1, &tree3).unwrap();
+    assert_eq!(merged_tree.id(), tree3.id());
     // Same tree other way
-    let reverse_merged_tree = merge_trees(&tree3, &tree1, &tree2).unwrap();
+    let reverse_merged_tree = tree3.merge(&tree1, &tree2).unwrap();
     assert_eq!(reverse_merged_tree.id(), merged_tree.id());
 
     // One side removes, the other side modifies
-    let merged_tree = merge_trees(&tree1, &tree2, &tree3).unwrap();
-    let expected_entries = write_tree(vec!["d1/f2"]).entries().collect_vec();
-    assert_eq!(merged_tree.entries().collect_vec(), expected_entries);
+    let merged_tree = tree1.merge(&tree2, &tree3).unwrap();
+    assert_eq!(merged_tree.id(), *fixture.commit("only_f2").tree_id());
     // Same tree other way
-    let reverse_merged_tree = merge_trees(&tree3, &tree2, &tree1).unwrap();
+    let reverse_merged_tree = tree3.merge(&tree2, &tree1).unwrap();
     assert_eq!(reverse_merged_tree.id(), merged_tree.id());
 }
 
+#[test]
+fn test_conflicting_merge_commit() {
+    // Merging parents that changed the same file differently results in a
+    // conflicted merge commit, which a child can then resolve.
+    let fixture = TestRepoBuilder::new()
+        .commit("base")
+        .file("file", "base\n")
+        .commit_on("base", "side1")
+        .file("file", "side1\n")
+        .commit_on("base", "side2")
+        .file("file", "side2\n")
+        .commit_with_parents(&["side1", "side2"], "merge")
+        .commit_on("merge", "resolved")
+        .file("file", "resolved\n")
+        .branch("main")
+        .build();
+
+    assert!(fixture.commit("merge").has_conflict().unwrap());
+    assert!(!fixture.commit("resolved").has_conflict().unwrap());
+    assert_eq!(
+        fixture.repo().view().get_local_branch("main"),
+        &RefTarget::normal(fixture.commit("resolved").id().clone())
+    );
+}
+
 #[test]
 fn test_types() {
     // Tests conflicts between different types. This is mostly to test that the
@@ -400,11 +389,11 @@ fn test_types() {
         RepoPath::from_internal_string("tree_normal_symlink"),
         "contents",
     );
-    let base_tree_id = base_tree_builder.write_tree();
+    let base_tree_id = base_tree_builder.write_tree().unwrap();
     let base_tree = store.get_tree(RepoPath::root(), &base_tree_id).unwrap();
-    let side1_tree_id = side1_tree_builder.write_tree();
+    let side1_tree_id = side1_tree_builder.write_tree().unwrap();
     let side1_tree = store.get_tree(RepoPath::root(), &side1_tree_id).unwrap();
-    let side2_tree_id = side2_tree_builder.write_tree();
+    let side2_tree_id = side2_tree_builder.write_tree().unwrap();
     let side2_tree = store.get_tree(RepoPath::root(), &side2_tree_id).unwrap();
 
     // Created the merged tree
@@ -495,7 +484,7 @@ fn test_simplify_conflict() {
     match further_rebased_tree.value(component).unwrap() {
         TreeValue::Conflict(id) => {
             let conflict = store
-                .read_conflict(RepoPath::from_internal_string(component.as_str()), id)
+                .read_conflict(RepoPath::from_internal_bytes(component.as_bytes()), id)
                 .unwrap();
             assert_eq!(
                 conflict.removes().map(|v| v.as_ref()).collect_vec(),
@@ -534,10 +523,6 @@ fn test_simplify_conflict() {
 
 #[test]
 fn test_simplify_conflict_after_resolving_parent() {
-    let settings = testutils::user_settings();
-    let test_repo = TestRepo::init();
-    let repo = &test_repo.repo;
-
     // Set up a repo like this:
     // D
     // | C
@@ -550,40 +535,35 @@ fn test_simplify_conflict_after_resolving_parent() {
     // which creates a conflict. We resolve the conflict in the first line and
     // rebase C2 (the rebased C) onto the resolved conflict. C3 should not have
     // a conflict since it changed an unrelated line.
+    let fixture = TestRepoBuilder::new()
+        .commit("A")
+        .file("dir/file", "abc\ndef\nghi\n")
+        .commit_on("A", "B")
+        .file("dir/file", "Abc\ndef\nghi\n")
+        .commit_on("B", "C")
+        .file("dir/file", "Abc\ndef\nGhi\n")
+        .commit_on("A", "D")
+        .file("dir/file", "abC\ndef\nghi\n")
+        .build();
+    let settings = &fixture.settings;
+    let repo = fixture.repo();
     let path = RepoPath::from_internal_string("dir/file");
-    let mut tx = repo.start_transaction(&settings);
-    let tree_a = create_tree(repo, &[(path, "abc\ndef\nghi\n")]);
-    let commit_a = tx
-        .mut_repo()
-        .new_commit(
-            &settings,
-            vec![repo.store().root_commit_id().clone()],
-            tree_a.id(),
-        )
-        .write()
-        .unwrap();
-    let tree_b = create_tree(repo, &[(path, "Abc\ndef\nghi\n")]);
-    let commit_b = tx
-        .mut_repo()
-        .new_commit(&settings, vec![commit_a.id().clone()], tree_b.id())
-        .write()
-        .unwrap();
-    let tree_c = create_tree(repo, &[(path, "Abc\ndef\nGhi\n")]);
-    let commit_c = tx
-        .mut_repo()
-        .new_commit(&settings, vec![commit_b.id().clone()], tree_c.id())
-        .write()
-        .unwrap();
-    let tree_d = create_tree(repo, &[(path, "abC\ndef\nghi\n")]);
-    let commit_d = tx
-        .mut_repo()
-        .new_commit(&settings, vec![commit_a.id().clone()], tree_d.id())
-        .write()
-        .unwrap();
-
-    let commit_b2 = rebase_commit(&settings, tx.mut_repo(), &commit_b, &[commit_d]).unwrap();
-    let commit_c2 =
-        rebase_commit(&settings, tx.mut_repo(), &commit_c, &[commit_b2.clone()]).unwrap();
+    let mut tx = repo.start_transaction(settings);
+
+    let commit_b2 = rebase_commit(
+        settings,
+        tx.mut_repo(),
+        fixture.commit("B"),
+        &[fixture.commit("D").clone()],
+    )
+    .unwrap();
+    let commit_c2 = rebase_commit(
+        settings,
+        tx.mut_repo(),
+        fixture.commit("C"),
+        &[commit_b2.clone()],
+    )
+    .unwrap();
 
     // Test the setup: Both B and C should have conflicts.
     let tree_b2 = commit_b2.tree().unwrap();
@@ -595,13 +575,13 @@ fn test_simplify_conflict_after_resolving_parent() {
     let tree_b3 = create_tree(repo, &[(path, "AbC\ndef\nghi\n")]);
     let commit_b3 = tx
         .mut_repo()
-        .rewrite_commit(&settings, &commit_b2)
+        .rewrite_commit(settings, &commit_b2)
         .set_tree_id(tree_b3.id())
         .write()
         .unwrap();
-    let commit_c3 = rebase_commit(&settings, tx.mut_repo(), &commit_c2, &[commit_b3]).unwrap();
-    tx.mut_repo().rebase_descendants(&settings).unwrap();
-    let repo = tx.commit("test");
+    let commit_c3 = rebase_commit(settings, tx.mut_repo(), &commit_c2, &[commit_b3]).unwrap();
+    tx.mut_repo().rebase_descendants(settings).unwrap();
+    let repo = tx.commit("test").unwrap();
 
     // The conflict should now be resolved.
     let tree_c2 = commit_c3.tree().unwrap();
@@ -622,5 +602,107 @@ fn test_simplify_conflict_after_resolving_parent() {
     }
 }
 
+#[test]
+fn test_simplify_conflict_resolved_in_descendant() {
+    // Set up a repo like this:
+    // F
+    // E (resolves the conflict)
+    // C2 (conflicted)
+    // B2 (conflicted)
+    // D
+    // A
+    //
+    // Same as test_simplify_conflict_after_resolving_parent(), but the conflict
+    // introduced by rebasing B onto D is resolved two commits above B2. We then
+    // rewrite B2 without touching the conflict. The rebased C should still
+    // have the conflict, but the rebased E and F should not get it back.
+    let fixture = TestRepoBuilder::new()
+        .commit("A")
+        .file("dir/file", "abc\ndef\nghi\njkl\n")
+        .commit_on("A", "B")
+        .file("dir/file", "Abc\ndef\nghi\njkl\n")
+        .commit_on("B", "C")
+        .file("dir/file", "Abc\ndef\nGhi\njkl\n")
+        .commit_on("A", "D")
+        .file("dir/file", "abC\ndef\nghi\njkl\n")
+        .build();
+    let settings = &fixture.settings;
+    let repo = fixture.repo();
+    let path = RepoPath::from_internal_string("dir/file");
+    let other_path = RepoPath::from_internal_string("other");
+    let tree_a = fixture.commit("A").tree().unwrap();
+    let mut tx = repo.start_transaction(settings);
+
+    let commit_b2 = rebase_commit(
+        settings,
+        tx.mut_repo(),
+        fixture.commit("B"),
+        &[fixture.commit("D").clone()],
+    )
+    .unwrap();
+    let commit_c2 = rebase_commit(
+        settings,
+        tx.mut_repo(),
+        fixture.commit("C"),
+        &[commit_b2.clone()],
+    )
+    .unwrap();
+    let tree_e = create_tree(repo, &[(path, "AbC\ndef\nGhi\njkl\n")]);
+    let commit_e = tx
+        .mut_repo()
+        .new_commit(settings, vec![commit_c2.id().clone()], tree_e.id())
+        .write()
+        .unwrap();
+    let tree_f = create_tree(repo, &[(path, "AbC\ndef\nGhi\nJkl\n")]);
+    let commit_f = tx
+        .mut_repo()
+        .new_commit(settings, vec![commit_e.id().clone()], tree_f.id())
+        .write()
+        .unwrap();
+
+    // Test the setup: B2 and C2 have conflicts, E and F don't.
+    assert!(!commit_b2.tree().unwrap().path_value(path).is_resolved());
+    assert!(!commit_c2.tree().unwrap().path_value(path).is_resolved());
+    assert!(!commit_e.has_conflict().unwrap());
+
+    // Add an unrelated file in B2 and rebase the descendants.
+    let tree_a_with_other = create_tree(
+        repo,
+        &[(path, "abc\ndef\nghi\njkl\n"), (other_path, "other\n")],
+    );
+    let tree_b3 = commit_b2
+        .tree()
+        .unwrap()
+        .merge(&tree_a, &tree_a_with_other)
+        .unwrap();
+    tx.mut_repo()
+        .rewrite_commit(settings, &commit_b2)
+        .set_tree_id(tree_b3.id())
+        .write()
+        .unwrap();
+    let rebase_map = tx
+        .mut_repo()
+        .rebase_descendants_return_map(settings)
+        .unwrap();
+    let store = repo.store();
+    let commit_c3 = store.get_commit(&rebase_map[commit_c2.id()]).unwrap();
+    let commit_e2 = store.get_commit(&rebase_map[commit_e.id()]).unwrap();
+    let commit_f2 = store.get_commit(&rebase_map[commit_f.id()]).unwrap();
+
+    // C3 still has the conflict, but E2 and F2 have only their own changes on
+    // top of the resolution.
+    assert!(!commit_c3.tree().unwrap().path_value(path).is_resolved());
+    let expected_tree_e2 = create_tree(
+        repo,
+        &[(path, "AbC\ndef\nGhi\njkl\n"), (other_path, "other\n")],
+    );
+    assert_eq!(*commit_e2.tree_id(), expected_tree_e2.id());
+    let expected_tree_f2 = create_tree(
+        repo,
+        &[(path, "AbC\ndef\nGhi\nJkl\n"), (other_path, "other\n")],
+    );
+    assert_eq!(*commit_f2.tree_id(), expected_tree_f2.id());
+}
+
 // TODO: Add tests for simplification of multi-way conflicts. Both the content
 // and the executable bit need testing.
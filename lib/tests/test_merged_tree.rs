@@ -12,21 +12,30 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+use std::io::Read as _;
+
+use assert_matches::assert_matches;
 use futures::executor::block_on;
 use futures::StreamExt;
 use itertools::Itertools;
-use jj_lib::backend::{FileId, MergedTreeId, TreeValue};
+use jj_lib::backend::{BackendError, FileId, MergedTreeId, TreeValue};
+use jj_lib::conflicts;
 use jj_lib::files::MergeResult;
 use jj_lib::matchers::{EverythingMatcher, FilesMatcher, Matcher, PrefixMatcher};
 use jj_lib::merge::{Merge, MergeBuilder};
+use jj_lib::merge_drivers::MergeDriverConfigError;
 use jj_lib::merged_tree::{
     MergedTree, MergedTreeBuilder, MergedTreeVal, TreeDiffIterator, TreeDiffStreamImpl,
 };
 use jj_lib::repo::Repo;
-use jj_lib::repo_path::{RepoPath, RepoPathComponent};
+use jj_lib::repo_path::{RepoPath, RepoPathBuf, RepoPathComponent};
+use jj_lib::settings::UserSettings;
 use jj_lib::tree::merge_trees;
+use jj_lib::tree_builder::ReservedPathError;
 use pretty_assertions::assert_eq;
-use testutils::{create_single_tree, write_file, TestRepo};
+use testutils::{
+    create_single_tree, create_single_tree_unchecked, create_tree, write_file, TestRepo,
+};
 
 fn file_value(file_id: &FileId) -> TreeValue {
     TreeValue::File {
@@ -142,7 +151,7 @@ fn test_from_legacy_tree() {
     let dir1_filename_id = write_file(store.as_ref(), dir1_filename, "file5_v2");
     tree_builder.set(dir1_filename.to_owned(), file_value(&dir1_filename_id));
 
-    let tree_id = tree_builder.write_tree();
+    let tree_id = tree_builder.write_tree().unwrap();
     let tree = store.get_tree(RepoPath::root(), &tree_id).unwrap();
 
     let merged_tree = MergedTree::from_legacy_tree(tree.clone()).unwrap();
@@ -254,6 +263,68 @@ fn test_from_legacy_tree() {
     assert_eq!(recreated_merged_id, merged_tree.id());
 }
 
+#[test]
+fn test_write_tree_with_reserved_path() {
+    let test_repo = TestRepo::init();
+    let repo = &test_repo.repo;
+    let store = repo.store();
+
+    let path = RepoPath::from_internal_string(".git/config");
+    let file_id = write_file(store, path, "contents");
+    let mut tree_builder = store.tree_builder(store.empty_tree_id().clone());
+    tree_builder.set(path.to_owned(), file_value(&file_id));
+    let err = tree_builder.write_tree().unwrap_err();
+    let BackendError::Other(err) = err else {
+        panic!("unexpected error: {err:?}");
+    };
+    let err = err.downcast::<ReservedPathError>().unwrap();
+    assert_eq!(err.path, path.to_owned());
+
+    // Also below the root, and through a merged tree builder
+    let path = RepoPath::from_internal_string("dir/.jj/file");
+    let mut tree_builder = MergedTreeBuilder::new(store.empty_merged_tree_id());
+    tree_builder.set_or_remove(path.to_owned(), Merge::normal(file_value(&file_id)));
+    let err = tree_builder.write_tree(store).unwrap_err();
+    assert_matches!(err, BackendError::Other(err) if err.is::<ReservedPathError>());
+
+    // Reserved paths can be removed
+    let tree = create_single_tree_unchecked(repo, &[(path, "contents")]);
+    let mut tree_builder = store.tree_builder(tree.id().clone());
+    tree_builder.remove(path.to_owned());
+    assert_eq!(tree_builder.write_tree().unwrap(), *store.empty_tree_id());
+}
+
+#[test]
+fn test_without_reserved_paths() {
+    let test_repo = TestRepo::init();
+    let repo = &test_repo.repo;
+
+    // Trees written by other tools may contain reserved paths, which the tree
+    // builder refuses to add
+    let keep_path = RepoPath::from_internal_string("keep");
+    let reserved_path = RepoPath::from_internal_string(".jj/file");
+    let tree = MergedTree::resolved(create_single_tree_unchecked(
+        repo,
+        &[(keep_path, "contents"), (reserved_path, "contents")],
+    ));
+    assert!(tree.path_value(reserved_path).is_present());
+
+    let stripped_tree = tree.without_reserved_paths().unwrap();
+    assert_eq!(
+        stripped_tree.id(),
+        MergedTreeId::resolved(
+            create_single_tree(repo, &[(keep_path, "contents")])
+                .id()
+                .clone()
+        )
+    );
+    // Already clean trees are returned as is
+    assert_eq!(
+        stripped_tree.without_reserved_paths().unwrap().id(),
+        stripped_tree.id()
+    );
+}
+
 #[test]
 fn test_path_value_and_entries() {
     let test_repo = TestRepo::init();
@@ -490,6 +561,97 @@ fn test_resolve_with_conflict() {
     )
 }
 
+#[test]
+fn test_resolve_with_multi_way_conflicts() {
+    let test_repo = TestRepo::init();
+    let repo = &test_repo.repo;
+    let store = repo.store();
+
+    // Three sides, as in an octopus merge. Hunks are resolved if the sides
+    // agree or if only one of them changed, and conflicts keep all three sides.
+    let agree_path = RepoPath::from_internal_string("agree");
+    let partial_path = RepoPath::from_internal_string("partial");
+    let conflict_path = RepoPath::from_internal_string("conflict");
+    let base = create_single_tree(
+        repo,
+        &[
+            (agree_path, "a\nb\nc\n"),
+            (partial_path, "a\nb\nc\n"),
+            (conflict_path, "a\nb\nc\n"),
+        ],
+    );
+    let side1 = create_single_tree(
+        repo,
+        &[
+            (agree_path, "a1\nb\nc\n"),
+            (partial_path, "a1\nb\nc\n"),
+            (conflict_path, "a\nb1\nc\n"),
+        ],
+    );
+    let side2 = create_single_tree(
+        repo,
+        &[
+            (agree_path, "a1\nb\nc\n"),
+            (partial_path, "a1\nb\nc\n"),
+            (conflict_path, "a\nb2\nc\n"),
+        ],
+    );
+    let side3 = create_single_tree(
+        repo,
+        &[
+            (agree_path, "a1\nb\nc\n"),
+            (partial_path, "a\nb\nc3\n"),
+            (conflict_path, "a\nb3\nc\n"),
+        ],
+    );
+
+    let tree = MergedTree::new(Merge::from_removes_adds(
+        vec![base.clone(), base],
+        vec![side1.clone(), side2, side3],
+    ));
+    let resolved = MergedTree::new(tree.resolve().unwrap());
+    assert_eq!(
+        resolved.path_value(agree_path),
+        Merge::resolved(side1.path_value(agree_path))
+    );
+    let partial_value = resolved.path_value(partial_path);
+    let Some(Some(TreeValue::File { id, .. })) = partial_value.as_resolved() else {
+        panic!("unexpected value: {partial_value:?}");
+    };
+    let mut content = String::new();
+    store
+        .read_file(partial_path, id)
+        .unwrap()
+        .read_to_string(&mut content)
+        .unwrap();
+    assert_eq!(content, "a1\nb\nc3\n");
+
+    let conflict = resolved.path_value(conflict_path);
+    assert_eq!(conflict.adds().len(), 3);
+    let mut materialized = vec![];
+    block_on(conflicts::materialize(
+        &conflict,
+        store,
+        conflict_path,
+        &mut materialized,
+    ))
+    .unwrap();
+    insta::assert_snapshot!(String::from_utf8(materialized).unwrap(), @r###"
+    a
+    <<<<<<<
+    %%%%%%%
+    -b
+    +b1
+    %%%%%%%
+    -b
+    +b2
+    +++++++
+    b3
+    >>>>>>>
+    c
+    "###);
+}
+
 #[test]
 fn test_resolve_with_conflict_containing_empty_subtree() {
     let test_repo = TestRepo::init();
@@ -1189,6 +1351,176 @@ fn test_diff_dir_file() {
 }
 
 /// Merge 3 resolved trees that can be resolved
+#[test]
+fn test_diff_resume_after_cursor() {
+    use rand::{Rng as _, SeedableRng as _};
+
+    let test_repo = TestRepo::init();
+    let repo = &test_repo.repo;
+
+    // Random paths from a small namespace, so the two trees replace files by
+    // directories and vice versa at various depths.
+    let mut rng = rand_chacha::ChaCha8Rng::seed_from_u64(0);
+    let mut random_tree = || {
+        let mut paths: Vec<RepoPathBuf> = vec![];
+        for _ in 0..rng.gen_range(0..12) {
+            let depth = rng.gen_range(1..=3);
+            let path = RepoPathBuf::from_internal_string(
+                (0..depth)
+                    .map(|_| ["a", "b", "c"][rng.gen_range(0..3)])
+                    .join("/"),
+            );
+            if paths
+                .iter()
+                .all(|other| !other.starts_with(&path) && !path.starts_with(other))
+            {
+                paths.push(path);
+            }
+        }
+        let path_contents: Vec<(&RepoPath, &str)> = paths
+            .iter()
+            .map(|path| (path.as_ref(), ["1", "2"][rng.gen_range(0..2)]))
+            .collect();
+        let tree = create_tree(repo, &path_contents);
+        (paths, tree)
+    };
+
+    for _ in 0..50 {
+        let (paths1, tree1) = random_tree();
+        let (paths2, tree2) = random_tree();
+        let full_diff = tree1
+            .diff(&tree2, &EverythingMatcher)
+            .map(|(path, diff)| (path, diff.unwrap()))
+            .collect_vec();
+
+        // Cursors at every file and directory of both trees, and their
+        // nonexistent children
+        let mut cursors = vec![RepoPathBuf::root()];
+        for path in paths1.iter().chain(&paths2) {
+            let mut dir = Some(path.as_ref());
+            while let Some(ancestor) = dir {
+                cursors.push(ancestor.to_owned());
+                dir = ancestor.parent();
+            }
+            cursors.push(path.join(RepoPathComponent::new("a")));
+        }
+        for cursor in cursors.iter().sorted().dedup() {
+            let expected = if let Some(pos) = full_diff.iter().position(|(path, _)| path == cursor)
+            {
+                full_diff[pos + 1..].to_vec()
+            } else {
+                // The file replacing a directory containing the cursor comes
+                // after the cursor
+                full_diff
+                    .iter()
+                    .filter(|(path, (before, _))| {
+                        path > cursor
+                            || (cursor.starts_with(path)
+                                && before.is_absent()
+                                && tree1.path_value(path).is_tree())
+                    })
+                    .cloned()
+                    .collect_vec()
+            };
+            let actual = TreeDiffIterator::new_after(
+                tree1.clone(),
+                tree2.clone(),
+                &EverythingMatcher,
+                cursor,
+            )
+            .unwrap()
+            .map(|(path, diff)| (path, diff.unwrap()))
+            .collect_vec();
+            assert_eq!(actual, expected, "cursor: {cursor:?}");
+        }
+
+        // Resuming from the cursor after each item gives the remaining items
+        assert_eq!(tree1.diff(&tree2, &EverythingMatcher).cursor(), None);
+        for i in 0..full_diff.len() {
+            let mut iter = tree1.diff(&tree2, &EverythingMatcher);
+            iter.nth(i).unwrap();
+            let cursor = iter.cursor().unwrap().to_owned();
+            assert_eq!(cursor, full_diff[i].0);
+            let resumed = TreeDiffIterator::new_after(
+                tree1.clone(),
+                tree2.clone(),
+                &EverythingMatcher,
+                &cursor,
+            )
+            .unwrap()
+            .map(|(path, diff)| (path, diff.unwrap()))
+            .collect_vec();
+            assert_eq!(resumed, full_diff[i + 1..]);
+        }
+    }
+}
+
+#[test]
+fn test_diff_empty_side() {
+    use rand::{Rng as _, SeedableRng as _};
+
+    let test_repo = TestRepo::init();
+    let repo = &test_repo.repo;
+    let empty_tree = repo
+        .store()
+        .get_root_tree(&repo.store().empty_merged_tree_id())
+        .unwrap();
+
+    let mut rng = rand_chacha::ChaCha8Rng::seed_from_u64(0);
+    let mut random_tree = || {
+        let mut paths: Vec<RepoPathBuf> = vec![];
+        for _ in 0..rng.gen_range(0..12) {
+            let depth = rng.gen_range(1..=3);
+            let path = RepoPathBuf::from_internal_string(
+                (0..depth)
+                    .map(|_| ["a", "b", "c"][rng.gen_range(0..3)])
+                    .join("/"),
+            );
+            if paths
+                .iter()
+                .all(|other| !other.starts_with(&path) && !path.starts_with(other))
+            {
+                paths.push(path);
+            }
+        }
+        let path_contents: Vec<(&RepoPath, &str)> = paths
+            .iter()
+            .map(|path| (path.as_ref(), ["1", "2"][rng.gen_range(0..2)]))
+            .collect();
+        create_tree(repo, &path_contents)
+    };
+
+    let prefix_matcher = PrefixMatcher::new([RepoPath::from_internal_string("a/b")]);
+    let files_matcher = FilesMatcher::new([
+        RepoPath::from_internal_string("b"),
+        RepoPath::from_internal_string("c/a"),
+    ]);
+    let matchers: [&dyn Matcher; 3] = [&EverythingMatcher, &prefix_matcher, &files_matcher];
+    for _ in 0..50 {
+        let tree1 = random_tree();
+        let tree2 = random_tree();
+        for matcher in matchers {
+            // The stream doesn't special-case empty trees, so it gives the
+            // generic result
+            diff_stream_equals_iter(&empty_tree, &tree1, matcher);
+            diff_stream_equals_iter(&tree1, &empty_tree, matcher);
+            // Directories added or removed between the trees are diffed
+            // against empty trees too
+            diff_stream_equals_iter(&tree1, &tree2, matcher);
+
+            let added = empty_tree
+                .diff(&tree1, matcher)
+                .map(|(path, diff)| (path, diff.unwrap()))
+                .collect_vec();
+            let expected = tree1
+                .entries_matching(matcher)
+                .map(|(path, value)| (path, (Merge::absent(), value)))
+                .collect_vec();
+            assert_eq!(added, expected);
+        }
+    }
+}
+
 #[test]
 fn test_merge_simple() {
     let test_repo = TestRepo::init();
@@ -1210,6 +1542,223 @@ fn test_merge_simple() {
 }
 
 /// Merge 3 resolved trees that can be partially resolved
+#[test]
+fn test_merge_binary_attribute() {
+    let test_repo = TestRepo::init();
+    let repo = &test_repo.repo;
+
+    let attributes_path = RepoPath::from_internal_string(".gitattributes");
+    let nested_attributes_path = RepoPath::from_internal_string("dir/.gitattributes");
+    let text_path = RepoPath::from_internal_string("dir/file.txt");
+    let binary_path = RepoPath::from_internal_string("dir/file.bin");
+    let text_override_path = RepoPath::from_internal_string("dir/text.bin");
+    let attributes = "*.bin binary\n";
+    let nested_attributes = "text.bin merge\n";
+    let create_tree = |contents: &str| {
+        create_single_tree(
+            repo,
+            &[
+                (attributes_path, attributes),
+                (nested_attributes_path, nested_attributes),
+                (text_path, contents),
+                (binary_path, contents),
+                (text_override_path, contents),
+            ],
+        )
+    };
+    let base = create_tree("a\nb\nc\n");
+    let side1 = create_tree("a1\nb\nc\n");
+    let side2 = create_tree("a\nb\nc2\n");
+
+    // Files declared as binary aren't merged line by line, unless a nested
+    // .gitattributes file overrides the merge driver.
+    let check_merged = |merged: MergedTree| {
+        let merged_content = |path: &RepoPath| match merged.path_value(path).into_resolved() {
+            Ok(Some(TreeValue::File { id, .. })) => {
+                let mut content = String::new();
+                repo.store()
+                    .read_file(path, &id)
+                    .unwrap()
+                    .read_to_string(&mut content)
+                    .unwrap();
+                Some(content)
+            }
+            _ => None,
+        };
+        assert_eq!(merged_content(text_path).as_deref(), Some("a1\nb\nc2\n"));
+        assert_eq!(merged_content(binary_path), None);
+        assert_eq!(
+            merged_content(text_override_path).as_deref(),
+            Some("a1\nb\nc2\n")
+        );
+    };
+    check_merged(
+        MergedTree::new(Merge::resolved(side1.clone()))
+            .merge(
+                &MergedTree::new(Merge::resolved(base.clone())),
+                &MergedTree::new(Merge::resolved(side2.clone())),
+            )
+            .unwrap(),
+    );
+    check_merged(
+        MergedTree::legacy(side1)
+            .merge(&MergedTree::legacy(base), &MergedTree::legacy(side2))
+            .unwrap(),
+    );
+}
+
+/// Merges trees with the given files, where each file is changed from
+/// `base` to `side1` and `side2`, and returns the merged contents by path.
+fn merge_files_with_settings(
+    settings: &UserSettings,
+    files: &[(&RepoPath, &str, &str, &str)],
+) -> Vec<Option<String>> {
+    let test_repo = TestRepo::init_with_settings(settings);
+    let repo = &test_repo.repo;
+    let create_tree = |index: usize| {
+        let path_contents = files
+            .iter()
+            .map(|&(path, base, side1, side2)| (path, [base, side1, side2][index]))
+            .collect_vec();
+        MergedTree::new(Merge::resolved(create_single_tree(repo, &path_contents)))
+    };
+    let merged = create_tree(1)
+        .merge(&create_tree(0), &create_tree(2))
+        .unwrap();
+    files
+        .iter()
+        .map(
+            |&(path, ..)| match merged.path_value(path).into_resolved() {
+                Ok(Some(TreeValue::File { id, .. })) => {
+                    let mut content = String::new();
+                    repo.store()
+                        .read_file(path, &id)
+                        .unwrap()
+                        .read_to_string(&mut content)
+                        .unwrap();
+                    Some(content)
+                }
+                _ => None,
+            },
+        )
+        .collect()
+}
+
+fn settings_with_config(toml: &str) -> UserSettings {
+    let config = testutils::base_config()
+        .add_source(config::File::from_str(toml, config::FileFormat::Toml))
+        .build()
+        .unwrap();
+    UserSettings::from_config(config)
+}
+
+#[test]
+fn test_merge_builtin_drivers() {
+    let settings = settings_with_config(
+        r#"
+        [merge.driver-paths]
+        "*.union" = "builtin:union"
+        "*.ours" = "builtin:ours"
+        "*.theirs" = "builtin:theirs"
+        "*.bin" = "builtin:binary"
+        "/dir/*.bin" = "builtin:text"
+        "#,
+    );
+    let files = [
+        (
+            ".gitattributes",
+            "*.log merge=union\n",
+            "*.log merge=union\n",
+            "*.log merge=union\n",
+        ),
+        ("file.txt", "a\n", "b\n", "c\n"),
+        ("file.union", "a\nx\n", "b\nx\n", "c\nx\n"),
+        ("file.log", "a\n", "b\n", "c\n"),
+        ("file.ours", "a\n", "b\n", "c\n"),
+        ("file.theirs", "a\n", "b\n", "c\n"),
+        ("file.bin", "a\nb\nc\n", "a1\nb\nc\n", "a\nb\nc2\n"),
+        ("dir/file.bin", "a\nb\nc\n", "a1\nb\nc\n", "a\nb\nc2\n"),
+    ]
+    .map(|(path, base, side1, side2)| (RepoPath::from_internal_string(path), base, side1, side2));
+    let merged = merge_files_with_settings(&settings, &files);
+    assert_eq!(
+        merged[1..],
+        [
+            // Conflicting changes aren't resolved by the default driver
+            None,
+            Some("b\nc\nx\n".to_owned()),
+            // Drivers can be selected by .gitattributes
+            Some("b\nc\n".to_owned()),
+            Some("b\n".to_owned()),
+            Some("c\n".to_owned()),
+            // Non-conflicting changes aren't merged by the binary driver
+            None,
+            // The longest matching pattern wins
+            Some("a1\nb\nc2\n".to_owned()),
+        ]
+    );
+}
+
+#[cfg(unix)]
+#[test]
+fn test_merge_external_driver() {
+    let settings = settings_with_config(
+        r#"
+        [merge.drivers]
+        concat = ["sh", "-c", 'cat "$0" "$1" > "$2"', "$left", "$right", "$output"]
+        fail = ["sh", "-c", "exit 1"]
+        missing = ["this-command-does-not-exist"]
+
+        [merge.driver-paths]
+        "*.concat" = "concat"
+        "*.fail" = "fail"
+        "*.missing" = "missing"
+        "#,
+    );
+    let files = [
+        ("file.concat", "a\n", "b\n", "c\n"),
+        // Falls back to the default driver if the command fails
+        ("clean.fail", "a\nb\nc\n", "a1\nb\nc\n", "a\nb\nc2\n"),
+        ("conflict.fail", "a\n", "b\n", "c\n"),
+        ("clean.missing", "a\nb\nc\n", "a1\nb\nc\n", "a\nb\nc2\n"),
+    ]
+    .map(|(path, base, side1, side2)| (RepoPath::from_internal_string(path), base, side1, side2));
+    let merged = merge_files_with_settings(&settings, &files);
+    assert_eq!(
+        merged,
+        [
+            Some("b\nc\n".to_owned()),
+            Some("a1\nb\nc2\n".to_owned()),
+            None,
+            Some("a1\nb\nc2\n".to_owned()),
+        ]
+    );
+}
+
+#[test]
+fn test_merge_driver_config_errors() {
+    let settings = settings_with_config(
+        r#"
+        [merge.driver-paths]
+        "*.foo" = "no-such-driver"
+        "#,
+    );
+    assert_matches!(
+        settings.merge_drivers(),
+        Err(MergeDriverConfigError::UnknownDriver { .. })
+    );
+    let settings = settings_with_config(
+        r#"
+        [merge.drivers]
+        empty = []
+        "#,
+    );
+    assert_matches!(
+        settings.merge_drivers(),
+        Err(MergeDriverConfigError::EmptyCommand(_))
+    );
+}
+
 #[test]
 fn test_merge_partial_resolution() {
     let test_repo = TestRepo::init();
@@ -1486,3 +2035,95 @@ fn test_merge_simplify_file_conflict_with_absent() {
     let merged = child1_merged.merge(&parent_merged, &child2_merged).unwrap();
     assert_eq!(merged, expected_merged);
 }
+
+#[test]
+fn test_limit_conflict_sides() {
+    let test_repo = TestRepo::init();
+    let repo = &test_repo.repo;
+    let store = repo.store();
+
+    let file_path = RepoPath::from_internal_string("file");
+    let dup_path = RepoPath::from_internal_string("dup");
+    let same_path = RepoPath::from_internal_string("same");
+    let create_tree = |file: &str, dup: &str| {
+        create_single_tree(
+            repo,
+            &[(file_path, file), (dup_path, dup), (same_path, "same\n")],
+        )
+    };
+    let base = create_tree("base\n", "base\n");
+    let tree = MergedTree::new(Merge::from_removes_adds(
+        vec![base.clone(), base.clone(), base],
+        vec![
+            create_tree("0\n", "x\n"),
+            create_tree("1\n", "y\n"),
+            create_tree("2\n", "y\n"),
+            create_tree("3\n", "y\n"),
+        ],
+    ));
+    let value =
+        |path: &RepoPath, contents: &str| Some(file_value(&write_file(store, path, contents)));
+
+    // Conflicts with few enough sides are left alone
+    let (limited_tree, collapsed_paths) = tree.limit_conflict_sides(4).unwrap();
+    assert_eq!(limited_tree.id(), tree.id());
+    assert!(collapsed_paths.is_empty());
+
+    let (limited_tree, collapsed_paths) = tree.limit_conflict_sides(2).unwrap();
+    assert_eq!(
+        collapsed_paths,
+        vec![dup_path.to_owned(), file_path.to_owned()]
+    );
+    let MergedTree::Merge(trees) = &limited_tree else {
+        panic!("expected a merged tree");
+    };
+    assert_eq!(trees.num_sides(), 2);
+    // Identical diffs are dropped
+    assert_eq!(
+        limited_tree.path_value(dup_path),
+        Merge::from_removes_adds(
+            vec![value(dup_path, "base\n")],
+            vec![value(dup_path, "x\n"), value(dup_path, "y\n")]
+        )
+    );
+    // Otherwise, the rest of the conflict is materialized into a file
+    let file_conflict = limited_tree.path_value(file_path);
+    assert_eq!(file_conflict.num_sides(), 2);
+    assert_eq!(file_conflict.first(), &value(file_path, "0\n"));
+    assert_eq!(
+        file_conflict.get_remove(0),
+        Some(&value(file_path, "base\n"))
+    );
+    let Some(Some(TreeValue::File { id, .. })) = file_conflict.get_add(1) else {
+        panic!("expected a file, got {file_conflict:?}");
+    };
+    let mut contents = vec![];
+    store
+        .read_file(file_path, id)
+        .unwrap()
+        .read_to_end(&mut contents)
+        .unwrap();
+    let rest = Merge::from_removes_adds(
+        vec![value(file_path, "base\n"), value(file_path, "base\n")],
+        vec![
+            value(file_path, "1\n"),
+            value(file_path, "2\n"),
+            value(file_path, "3\n"),
+        ],
+    );
+    let mut expected_contents = vec![];
+    block_on(conflicts::materialize(
+        &rest,
+        store,
+        file_path,
+        &mut expected_contents,
+    ))
+    .unwrap();
+    assert_eq!(contents, expected_contents);
+    assert!(contents.starts_with(b"<<<<<<<"));
+    // Resolved paths are kept
+    assert_eq!(
+        limited_tree.path_value(same_path),
+        Merge::normal(file_value(&write_file(store, same_path, "same\n")))
+    );
+}
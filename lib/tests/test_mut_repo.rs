@@ -12,9 +12,12 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+use std::slice;
+
 use jj_lib::backend::CommitId;
 use jj_lib::op_store::{RefTarget, RemoteRef, RemoteRefState, WorkspaceId};
-use jj_lib::repo::Repo;
+use jj_lib::repo::{AbandonOnCheckout, Repo};
+use jj_lib::settings::UserSettings;
 use maplit::hashset;
 use testutils::{
     assert_rebased_onto, create_random_commit, write_random_commit, CommitGraphBuilder, TestRepo,
@@ -29,12 +32,12 @@ fn test_edit() {
 
     let mut tx = repo.start_transaction(&settings);
     let wc_commit = write_random_commit(tx.mut_repo(), &settings);
-    let repo = tx.commit("test");
+    let repo = tx.commit("test").unwrap();
 
     let mut tx = repo.start_transaction(&settings);
     let ws_id = WorkspaceId::default();
     tx.mut_repo().edit(ws_id.clone(), &wc_commit).unwrap();
-    let repo = tx.commit("test");
+    let repo = tx.commit("test").unwrap();
     assert_eq!(repo.view().get_wc_commit_id(&ws_id), Some(wc_commit.id()));
 }
 
@@ -47,7 +50,7 @@ fn test_checkout() {
 
     let mut tx = repo.start_transaction(&settings);
     let wc_commit_parent = write_random_commit(tx.mut_repo(), &settings);
-    let repo = tx.commit("test");
+    let repo = tx.commit("test").unwrap();
 
     let mut tx = repo.start_transaction(&settings);
     let ws_id = WorkspaceId::default();
@@ -58,7 +61,7 @@ fn test_checkout() {
     assert_eq!(wc_commit.tree_id(), wc_commit_parent.tree_id());
     assert_eq!(wc_commit.parents().len(), 1);
     assert_eq!(wc_commit.parents()[0].id(), wc_commit_parent.id());
-    let repo = tx.commit("test");
+    let repo = tx.commit("test").unwrap();
     assert_eq!(repo.view().get_wc_commit_id(&ws_id), Some(wc_commit.id()));
 }
 
@@ -75,7 +78,7 @@ fn test_checkout_previous_not_empty() {
     let old_wc_commit = write_random_commit(mut_repo, &settings);
     let ws_id = WorkspaceId::default();
     mut_repo.edit(ws_id.clone(), &old_wc_commit).unwrap();
-    let repo = tx.commit("test");
+    let repo = tx.commit("test").unwrap();
 
     let mut tx = repo.start_transaction(&settings);
     let mut_repo = tx.mut_repo();
@@ -105,7 +108,7 @@ fn test_checkout_previous_empty() {
         .unwrap();
     let ws_id = WorkspaceId::default();
     mut_repo.edit(ws_id.clone(), &old_wc_commit).unwrap();
-    let repo = tx.commit("test");
+    let repo = tx.commit("test").unwrap();
 
     let mut tx = repo.start_transaction(&settings);
     let mut_repo = tx.mut_repo();
@@ -136,7 +139,7 @@ fn test_checkout_previous_empty_with_description() {
         .unwrap();
     let ws_id = WorkspaceId::default();
     mut_repo.edit(ws_id.clone(), &old_wc_commit).unwrap();
-    let repo = tx.commit("test");
+    let repo = tx.commit("test").unwrap();
 
     let mut tx = repo.start_transaction(&settings);
     let mut_repo = tx.mut_repo();
@@ -167,7 +170,7 @@ fn test_checkout_previous_empty_with_local_branch() {
     mut_repo.set_local_branch_target("b", RefTarget::normal(old_wc_commit.id().clone()));
     let ws_id = WorkspaceId::default();
     mut_repo.edit(ws_id.clone(), &old_wc_commit).unwrap();
-    let repo = tx.commit("test");
+    let repo = tx.commit("test").unwrap();
 
     let mut tx = repo.start_transaction(&settings);
     let mut_repo = tx.mut_repo();
@@ -205,7 +208,7 @@ fn test_checkout_previous_empty_non_head() {
         .unwrap();
     let ws_id = WorkspaceId::default();
     mut_repo.edit(ws_id.clone(), &old_wc_commit).unwrap();
-    let repo = tx.commit("test");
+    let repo = tx.commit("test").unwrap();
 
     let mut tx = repo.start_transaction(&settings);
     let mut_repo = tx.mut_repo();
@@ -218,6 +221,107 @@ fn test_checkout_previous_empty_non_head() {
     );
 }
 
+/// Checks out a new commit on top of an empty working-copy commit with the
+/// given description, and returns whether the old commit was abandoned.
+fn checkout_away_from_empty_commit(policy: &str, description: &str, with_branch: bool) -> bool {
+    let config = testutils::base_config()
+        .set_override("working-copy.abandon-on-checkout", policy)
+        .unwrap()
+        .build()
+        .unwrap();
+    let settings = UserSettings::from_config(config);
+    let test_repo = TestRepo::init();
+    let repo = &test_repo.repo;
+
+    let mut tx = repo.start_transaction(&settings);
+    let mut_repo = tx.mut_repo();
+    let old_wc_commit = mut_repo
+        .new_commit(
+            &settings,
+            vec![repo.store().root_commit_id().clone()],
+            repo.store().empty_merged_tree_id(),
+        )
+        .set_description(description)
+        .write()
+        .unwrap();
+    if with_branch {
+        mut_repo.set_local_branch_target("b", RefTarget::normal(old_wc_commit.id().clone()));
+    }
+    let ws_id = WorkspaceId::default();
+    mut_repo.edit(ws_id.clone(), &old_wc_commit).unwrap();
+    let repo = tx.commit("test").unwrap();
+
+    let mut tx = repo.start_transaction(&settings);
+    let mut_repo = tx.mut_repo();
+    let new_wc_commit = write_random_commit(mut_repo, &settings);
+    mut_repo.edit(ws_id, &new_wc_commit).unwrap();
+    mut_repo.rebase_descendants(&settings).unwrap();
+    let abandoned = !mut_repo.view().heads().contains(old_wc_commit.id());
+    assert_eq!(
+        mut_repo.abandoned_on_checkout(),
+        if abandoned {
+            slice::from_ref(old_wc_commit.id())
+        } else {
+            &[]
+        }
+    );
+    abandoned
+}
+
+#[test]
+fn test_checkout_previous_abandon_policy_empty_undescribed() {
+    assert!(checkout_away_from_empty_commit(
+        "empty-undescribed",
+        "",
+        false
+    ));
+    assert!(!checkout_away_from_empty_commit(
+        "empty-undescribed",
+        "desc",
+        false
+    ));
+    assert!(!checkout_away_from_empty_commit(
+        "empty-undescribed",
+        "",
+        true
+    ));
+}
+
+#[test]
+fn test_checkout_previous_abandon_policy_never() {
+    assert!(!checkout_away_from_empty_commit("never", "", false));
+    assert!(!checkout_away_from_empty_commit("never", "desc", false));
+    assert!(!checkout_away_from_empty_commit("never", "", true));
+}
+
+#[test]
+fn test_checkout_previous_abandon_policy_empty() {
+    assert!(checkout_away_from_empty_commit("empty", "", false));
+    assert!(checkout_away_from_empty_commit("empty", "desc", false));
+    // Local branches protect the commit regardless of the policy
+    assert!(!checkout_away_from_empty_commit("empty", "", true));
+    assert!(!checkout_away_from_empty_commit("empty", "desc", true));
+}
+
+#[test]
+fn test_abandon_on_checkout_setting() {
+    let parse = |policy: &str| {
+        let config = testutils::base_config()
+            .set_override("working-copy.abandon-on-checkout", policy)
+            .unwrap()
+            .build()
+            .unwrap();
+        UserSettings::from_config(config).abandon_on_checkout()
+    };
+    assert_eq!(
+        testutils::user_settings().abandon_on_checkout().unwrap(),
+        AbandonOnCheckout::EmptyUndescribed
+    );
+    assert_eq!(parse("never").unwrap(), AbandonOnCheckout::Never);
+    assert_eq!(parse("empty").unwrap(), AbandonOnCheckout::Empty);
+    assert!(parse("always").is_err());
+}
+
 #[test]
 fn test_edit_initial() {
     // Test that MutableRepo::edit() can be used on the initial working-copy commit
@@ -228,14 +332,14 @@ fn test_edit_initial() {
 
     let mut tx = repo.start_transaction(&settings);
     let wc_commit = write_random_commit(tx.mut_repo(), &settings);
-    let repo = tx.commit("test");
+    let repo = tx.commit("test").unwrap();
 
     let mut tx = repo.start_transaction(&settings);
     let workspace_id = WorkspaceId::new("new-workspace".to_string());
     tx.mut_repo()
         .edit(workspace_id.clone(), &wc_commit)
         .unwrap();
-    let repo = tx.commit("test");
+    let repo = tx.commit("test").unwrap();
     assert_eq!(
         repo.view().get_wc_commit_id(&workspace_id),
         Some(wc_commit.id())
@@ -263,7 +367,7 @@ fn test_add_head_success() {
     mut_repo.add_head(&new_commit).unwrap();
     assert!(mut_repo.view().heads().contains(new_commit.id()));
     assert!(mut_repo.index().has_id(new_commit.id()));
-    let repo = tx.commit("test");
+    let repo = tx.commit("test").unwrap();
     assert!(repo.view().heads().contains(new_commit.id()));
     assert!(repo.index().has_id(new_commit.id()));
 }
@@ -281,7 +385,7 @@ fn test_add_head_ancestor() {
     let commit1 = graph_builder.initial_commit();
     let commit2 = graph_builder.commit_with_parents(&[&commit1]);
     let commit3 = graph_builder.commit_with_parents(&[&commit2]);
-    let repo = tx.commit("test");
+    let repo = tx.commit("test").unwrap();
 
     assert_eq!(repo.view().heads(), &hashset! {commit3.id().clone()});
     let mut tx = repo.start_transaction(&settings);
@@ -300,7 +404,7 @@ fn test_add_head_not_immediate_child() {
 
     let mut tx = repo.start_transaction(&settings);
     let initial = write_random_commit(tx.mut_repo(), &settings);
-    let repo = tx.commit("test");
+    let repo = tx.commit("test").unwrap();
 
     // Create some commits outside of the repo by using a temporary transaction.
     // Then add one of them as a head.
@@ -343,7 +447,7 @@ fn test_remove_head() {
     let commit1 = graph_builder.initial_commit();
     let commit2 = graph_builder.commit_with_parents(&[&commit1]);
     let commit3 = graph_builder.commit_with_parents(&[&commit2]);
-    let repo = tx.commit("test");
+    let repo = tx.commit("test").unwrap();
 
     let mut tx = repo.start_transaction(&settings);
     let mut_repo = tx.mut_repo();
@@ -356,7 +460,7 @@ fn test_remove_head() {
     assert!(mut_repo.index().has_id(commit1.id()));
     assert!(mut_repo.index().has_id(commit2.id()));
     assert!(mut_repo.index().has_id(commit3.id()));
-    let repo = tx.commit("test");
+    let repo = tx.commit("test").unwrap();
     let heads = repo.view().heads().clone();
     assert!(!heads.contains(commit3.id()));
     assert!(!heads.contains(commit2.id()));
@@ -390,7 +494,7 @@ fn test_has_changed() {
         .unwrap();
     mut_repo.set_local_branch_target("main", RefTarget::normal(commit1.id().clone()));
     mut_repo.set_remote_branch("main", "origin", normal_remote_ref(commit1.id()));
-    let repo = tx.commit("test");
+    let repo = tx.commit("test").unwrap();
     // Test the setup
     assert_eq!(repo.view().heads(), &hashset! {commit1.id().clone()});
 
@@ -447,7 +551,7 @@ fn test_rebase_descendants_simple() {
     let commit3 = graph_builder.commit_with_parents(&[&commit2]);
     let commit4 = graph_builder.commit_with_parents(&[&commit1]);
     let commit5 = graph_builder.commit_with_parents(&[&commit4]);
-    let repo = tx.commit("test");
+    let repo = tx.commit("test").unwrap();
 
     let mut tx = repo.start_transaction(&settings);
     let mut_repo = tx.mut_repo();
@@ -487,7 +591,7 @@ fn test_rebase_descendants_conflicting_rewrite() {
     let commit1 = graph_builder.initial_commit();
     let commit2 = graph_builder.commit_with_parents(&[&commit1]);
     let _commit3 = graph_builder.commit_with_parents(&[&commit2]);
-    let repo = tx.commit("test");
+    let repo = tx.commit("test").unwrap();
 
     let mut tx = repo.start_transaction(&settings);
     let mut_repo = tx.mut_repo();
@@ -12,20 +12,31 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use std::path::Path;
+use std::collections::{BTreeMap, HashMap};
+use std::path::{Path, PathBuf};
 use std::slice;
+use std::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::Arc;
 use std::time::SystemTime;
 
 use assert_matches::assert_matches;
 use itertools::Itertools as _;
-use jj_lib::backend::CommitId;
+use jj_lib::backend::{CommitId, MillisSinceEpoch, Timestamp};
 use jj_lib::object_id::ObjectId;
-use jj_lib::op_walk::{self, OpsetEvaluationError, OpsetResolutionError};
+use jj_lib::op_store::{OpStoreGcStats, RefTarget};
+use jj_lib::op_walk::{self, OperationTagFilter, OpsetEvaluationError, OpsetResolutionError};
 use jj_lib::operation::Operation;
 use jj_lib::repo::{ReadonlyRepo, Repo};
+use jj_lib::repo_path::{RepoPath, RepoPathBuf};
 use jj_lib::settings::UserSettings;
-use testutils::{create_random_commit, write_random_commit, TestRepo};
+use jj_lib::transaction::{
+    self, ImmutableCommitsValidator, OperationDescription, PendingChanges, TransactionCommitError,
+    TransactionValidationError, TransactionValidator, WALL_CLOCK_TIME_TAG,
+};
+use testutils::test_repo_builder::TestRepoBuilder;
+use testutils::{
+    create_random_commit, create_tree, write_random_commit, TestRepo, TestRepoBackend,
+};
 
 fn list_dir(dir: &Path) -> Vec<String> {
     std::fs::read_dir(dir)
@@ -48,7 +59,7 @@ fn test_unpublished_operation() {
 
     let mut tx1 = repo.start_transaction(&settings);
     write_random_commit(tx1.mut_repo(), &settings);
-    let unpublished_op = tx1.write("transaction 1");
+    let unpublished_op = tx1.write("transaction 1").unwrap();
     let op_id1 = unpublished_op.operation().id().clone();
     assert_ne!(op_id1, op_id0);
     assert_eq!(list_dir(&op_heads_dir), vec![op_id0.hex()]);
@@ -70,14 +81,24 @@ fn test_consecutive_operations() {
 
     let mut tx1 = repo.start_transaction(&settings);
     write_random_commit(tx1.mut_repo(), &settings);
-    let op_id1 = tx1.commit("transaction 1").operation().id().clone();
+    let op_id1 = tx1
+        .commit("transaction 1")
+        .unwrap()
+        .operation()
+        .id()
+        .clone();
     assert_ne!(op_id1, op_id0);
     assert_eq!(list_dir(&op_heads_dir), vec![op_id1.hex()]);
 
     let repo = repo.reload_at_head(&settings).unwrap();
     let mut tx2 = repo.start_transaction(&settings);
     write_random_commit(tx2.mut_repo(), &settings);
-    let op_id2 = tx2.commit("transaction 2").operation().id().clone();
+    let op_id2 = tx2
+        .commit("transaction 2")
+        .unwrap()
+        .operation()
+        .id()
+        .clone();
     assert_ne!(op_id2, op_id0);
     assert_ne!(op_id2, op_id1);
     assert_eq!(list_dir(&op_heads_dir), vec![op_id2.hex()]);
@@ -102,7 +123,12 @@ fn test_concurrent_operations() {
 
     let mut tx1 = repo.start_transaction(&settings);
     write_random_commit(tx1.mut_repo(), &settings);
-    let op_id1 = tx1.commit("transaction 1").operation().id().clone();
+    let op_id1 = tx1
+        .commit("transaction 1")
+        .unwrap()
+        .operation()
+        .id()
+        .clone();
     assert_ne!(op_id1, op_id0);
     assert_eq!(list_dir(&op_heads_dir), vec![op_id1.hex()]);
 
@@ -110,7 +136,12 @@ fn test_concurrent_operations() {
     // since they were run in parallel.
     let mut tx2 = repo.start_transaction(&settings);
     write_random_commit(tx2.mut_repo(), &settings);
-    let op_id2 = tx2.commit("transaction 2").operation().id().clone();
+    let op_id2 = tx2
+        .commit("transaction 2")
+        .unwrap()
+        .operation()
+        .id()
+        .clone();
     assert_ne!(op_id2, op_id0);
     assert_ne!(op_id2, op_id1);
     let mut actual_heads_on_disk = list_dir(&op_heads_dir);
@@ -128,6 +159,50 @@ fn test_concurrent_operations() {
     assert_eq!(list_dir(&op_heads_dir), vec![merged_op_id.hex()]);
 }
 
+#[test]
+fn test_replace_base_operation() {
+    let settings = testutils::user_settings();
+    let test_repo = TestRepo::init();
+    let repo = &test_repo.repo;
+
+    let op_heads_dir = repo.repo_path().join("op_heads").join("heads");
+    let op_id0 = repo.op_id().clone();
+
+    let mut tx1 = repo.start_transaction(&settings);
+    let commit1 = write_random_commit(tx1.mut_repo(), &settings);
+    let repo1 = tx1.commit("transaction 1").unwrap();
+    let op_id1 = repo1.op_id().clone();
+
+    // The new operation is recorded on top of the base operation's parent, and
+    // replaces the base operation in the op heads
+    let mut tx2 = repo1.start_transaction(&settings);
+    tx2.replace_base_operation().unwrap();
+    let commit2 = write_random_commit(tx2.mut_repo(), &settings);
+    let repo2 = tx2.commit("transaction 2").unwrap();
+    let op_id2 = repo2.op_id().clone();
+    assert_eq!(repo2.operation().parent_ids(), slice::from_ref(&op_id0));
+    assert_eq!(list_dir(&op_heads_dir), vec![op_id2.hex()]);
+
+    // The view still includes the changes from the replaced operation
+    let repo = repo.reload_at_head(&settings).unwrap();
+    assert_eq!(repo.op_id(), &op_id2);
+    assert!(repo.view().heads().contains(commit1.id()));
+    assert!(repo.view().heads().contains(commit2.id()));
+
+    // The replaced operation is recorded in the new operation, and is kept
+    // reachable by it
+    assert_eq!(
+        repo.operation().store_operation().replaced,
+        vec![op_id1.clone()]
+    );
+    repo.op_store()
+        .gc(slice::from_ref(&op_id2), SystemTime::now())
+        .unwrap();
+    let op1 = repo.op_store().read_operation(&op_id1).unwrap();
+    assert_eq!(op1.parents, vec![op_id0]);
+    repo.op_store().read_view(&op1.view_id).unwrap();
+}
+
 fn assert_heads(repo: &dyn Repo, expected: Vec<&CommitId>) {
     let expected = expected.iter().cloned().cloned().collect();
     assert_eq!(*repo.view().heads(), expected);
@@ -145,7 +220,7 @@ fn test_isolation() {
         .set_parents(vec![repo.store().root_commit_id().clone()])
         .write()
         .unwrap();
-    let repo = tx.commit("test");
+    let repo = tx.commit("test").unwrap();
 
     let mut tx1 = repo.start_transaction(&settings);
     let mut_repo1 = tx1.mut_repo();
@@ -176,12 +251,12 @@ fn test_isolation() {
     assert_heads(mut_repo2, vec![rewrite2.id()]);
 
     // The base repo and tx2 don't see the commits from tx1.
-    tx1.commit("transaction 1");
+    tx1.commit("transaction 1").unwrap();
     assert_heads(repo.as_ref(), vec![initial.id()]);
     assert_heads(mut_repo2, vec![rewrite2.id()]);
 
     // The base repo still doesn't see the commits after both transactions commit.
-    tx2.commit("transaction 2");
+    tx2.commit("transaction 2").unwrap();
     assert_heads(repo.as_ref(), vec![initial.id()]);
     // After reload, the base repo sees both rewrites.
     let repo = repo.reload_at_head(&settings).unwrap();
@@ -216,10 +291,10 @@ fn test_reparent_range_linear() {
         write_random_commit(tx.mut_repo(), &settings);
         tx
     };
-    let repo_a = random_tx(&repo_0).commit("op A");
-    let repo_b = random_tx(&repo_a).commit("op B");
-    let repo_c = random_tx(&repo_b).commit("op C");
-    let repo_d = random_tx(&repo_c).commit("op D");
+    let repo_a = random_tx(&repo_0).commit("op A").unwrap();
+    let repo_b = random_tx(&repo_a).commit("op B").unwrap();
+    let repo_c = random_tx(&repo_b).commit("op C").unwrap();
+    let repo_d = random_tx(&repo_c).commit("op D").unwrap();
 
     // Reparent B..D (=C|D) onto A:
     // D'
@@ -296,10 +371,10 @@ fn test_reparent_range_branchy() {
         write_random_commit(tx.mut_repo(), &settings);
         tx
     };
-    let repo_a = random_tx(&repo_0).commit("op A");
-    let repo_b = random_tx(&repo_a).commit("op B");
-    let repo_c = random_tx(&repo_b).commit("op C");
-    let repo_d = random_tx(&repo_c).commit("op D");
+    let repo_a = random_tx(&repo_0).commit("op A").unwrap();
+    let repo_b = random_tx(&repo_a).commit("op B").unwrap();
+    let repo_c = random_tx(&repo_b).commit("op C").unwrap();
+    let repo_d = random_tx(&repo_c).commit("op D").unwrap();
     let tx_e = random_tx(&repo_d);
     let tx_f = random_tx(&repo_c);
     let repo_g = testutils::commit_transactions(&settings, vec![tx_e, tx_f]);
@@ -417,6 +492,63 @@ fn test_reparent_range_branchy() {
     assert_eq!(new_op_f.parent_ids(), slice::from_ref(repo_d.op_id()));
 }
 
+/// Reads all files below `dir`, keyed by their path relative to `dir`.
+fn read_dir_contents(dir: &Path) -> BTreeMap<PathBuf, Vec<u8>> {
+    let mut contents = BTreeMap::new();
+    let mut pending = vec![dir.to_owned()];
+    while let Some(path) = pending.pop() {
+        for entry in std::fs::read_dir(&path).unwrap() {
+            let entry = entry.unwrap();
+            if entry.file_type().unwrap().is_dir() {
+                pending.push(entry.path());
+            } else {
+                let relative_path = entry.path().strip_prefix(dir).unwrap().to_owned();
+                contents.insert(relative_path, std::fs::read(entry.path()).unwrap());
+            }
+        }
+    }
+    contents
+}
+
+#[test]
+fn test_reproducible_repo_state() {
+    // Running the same sequence of operations with the same seed and timestamps
+    // produces byte-for-byte identical commit and operation stores.
+    let run = || {
+        let fixture = TestRepoBuilder::new()
+            .backend(TestRepoBackend::Local)
+            .commit("A")
+            .file("file", "a\n")
+            .branch("main")
+            .commit_on("A", "B")
+            .file("file", "b\n")
+            .build();
+        let settings = &fixture.settings;
+        let mut tx = fixture.repo().start_transaction(settings);
+        tx.mut_repo()
+            .rewrite_commit(settings, fixture.commit("B"))
+            .set_description("rewritten")
+            .write()
+            .unwrap();
+        tx.mut_repo().rebase_descendants(settings).unwrap();
+        let repo = tx.commit("rewrite B").unwrap();
+        let repo_path = repo.repo_path();
+        (
+            repo.op_id().clone(),
+            read_dir_contents(&repo_path.join("store")),
+            read_dir_contents(&repo_path.join("op_store")),
+            fixture,
+        )
+    };
+    let (op_id1, store1, op_store1, _fixture1) = run();
+    let (op_id2, store2, op_store2, _fixture2) = run();
+    assert_eq!(op_id1, op_id2);
+    assert!(!store1.is_empty());
+    assert!(store1 == store2, "commit stores differ");
+    assert!(!op_store1.is_empty());
+    assert!(op_store1 == op_store2, "operation stores differ");
+}
+
 fn stable_op_id_settings() -> UserSettings {
     UserSettings::from_config(
         testutils::base_config()
@@ -439,7 +571,7 @@ fn test_resolve_op_id() {
     let mut operations = Vec::new();
     for i in (0..6).chain([16]) {
         let tx = repo.start_transaction(&settings);
-        let repo = tx.commit(format!("transaction {i}"));
+        let repo = tx.commit(format!("transaction {i}")).unwrap();
         operations.push(repo.operation().clone());
     }
     // "c" and "0" are ambiguous
@@ -530,7 +662,7 @@ fn test_resolve_op_parents_children() {
     let mut repos = Vec::new();
     for _ in 0..3 {
         let tx = repo.start_transaction(&settings);
-        repos.push(tx.commit("test"));
+        repos.push(tx.commit("test").unwrap());
         repo = repos.last().unwrap();
     }
     let operations = repos.iter().map(|repo| repo.operation()).collect_vec();
@@ -633,12 +765,12 @@ fn test_gc() {
         write_random_commit(tx.mut_repo(), &settings);
         tx
     };
-    let repo_a = random_tx(&repo_0).commit("op A");
-    let repo_b = random_tx(&repo_a).commit("op B");
-    let repo_c = random_tx(&repo_b).commit("op C");
-    let repo_d = random_tx(&repo_c).commit("op D");
-    let repo_e = empty_tx(&repo_b).commit("op E");
-    let repo_f = random_tx(&repo_e).commit("op F");
+    let repo_a = random_tx(&repo_0).commit("op A").unwrap();
+    let repo_b = random_tx(&repo_a).commit("op B").unwrap();
+    let repo_c = random_tx(&repo_b).commit("op C").unwrap();
+    let repo_d = random_tx(&repo_c).commit("op D").unwrap();
+    let repo_e = empty_tx(&repo_b).commit("op E").unwrap();
+    let repo_f = random_tx(&repo_e).commit("op F").unwrap();
 
     // Sanity check for the original state
     let mut expected_op_entries = list_dir(&op_dir);
@@ -658,8 +790,24 @@ fn test_gc() {
     assert_eq!(list_dir(&op_dir), expected_op_entries);
     assert_eq!(list_dir(&view_dir), expected_view_entries);
 
+    // A dry run counts the unreachable objects without removing them
+    let stats = op_store
+        .gc_dry_run(slice::from_ref(repo_d.op_id()), now)
+        .unwrap();
+    assert_eq!(
+        stats,
+        OpStoreGcStats {
+            num_operations: 2,
+            num_views: 1,
+        }
+    );
+    assert_eq!(list_dir(&op_dir), expected_op_entries);
+    assert_eq!(list_dir(&view_dir), expected_view_entries);
+
     // E|F are no longer reachable, but E's view is still reachable
-    op_store.gc(slice::from_ref(repo_d.op_id()), now).unwrap();
+    let stats = op_store.gc(slice::from_ref(repo_d.op_id()), now).unwrap();
+    assert_eq!(stats.num_operations, 2);
+    assert_eq!(stats.num_views, 1);
     expected_op_entries
         .retain(|name| *name != repo_e.op_id().hex() && *name != repo_f.op_id().hex());
     expected_view_entries.retain(|name| *name != repo_f.operation().view_id().hex());
@@ -685,3 +833,483 @@ fn test_gc() {
     assert_eq!(expected_op_entries.len(), 2);
     assert_eq!(expected_view_entries.len(), 2);
 }
+
+/// Rejects commits that change the given path compared to their first parent.
+struct ForbiddenPathValidator {
+    path: RepoPathBuf,
+}
+
+impl TransactionValidator for ForbiddenPathValidator {
+    fn validate(&self, changes: &PendingChanges) -> Result<(), TransactionValidationError> {
+        let mut violations = vec![];
+        for commit in changes.added_commits()? {
+            let parent_tree = commit.parents()[0].tree()?;
+            if parent_tree.path_value(&self.path) != commit.tree()?.path_value(&self.path) {
+                violations.push(format!(
+                    "Commit {} touches {}",
+                    commit.id().hex(),
+                    self.path.as_internal_file_string()
+                ));
+            }
+        }
+        if violations.is_empty() {
+            Ok(())
+        } else {
+            Err(TransactionValidationError::Rejected(violations))
+        }
+    }
+}
+
+/// Rejects any change to the given local branch.
+struct ProtectedBranchValidator {
+    name: String,
+}
+
+impl TransactionValidator for ProtectedBranchValidator {
+    fn validate(&self, changes: &PendingChanges) -> Result<(), TransactionValidationError> {
+        if changes
+            .changed_local_branches()
+            .any(|(name, _, _)| name == self.name)
+        {
+            Err(TransactionValidationError::Rejected(vec![format!(
+                "Branch {} is protected",
+                self.name
+            )]))
+        } else {
+            Ok(())
+        }
+    }
+}
+
+#[test]
+fn test_operation_description_tags() {
+    let settings = testutils::user_settings();
+    let test_repo = TestRepo::init();
+    let repo = &test_repo.repo;
+
+    // Plain descriptions are recorded without tags
+    let mut tx = repo.start_transaction(&settings);
+    write_random_commit(tx.mut_repo(), &settings);
+    let repo = tx.commit("untagged").unwrap();
+    assert!(repo.operation().store_operation().metadata.tags.is_empty());
+
+    // Tags of the description are added to the ones set on the transaction
+    let mut tx = repo.start_transaction(&settings);
+    tx.set_tag("args".to_string(), "bot --run".to_string());
+    write_random_commit(tx.mut_repo(), &settings);
+    let repo = tx
+        .commit(
+            OperationDescription::new("tagged by ci")
+                .with_tag("triggered-by", "ci")
+                .with_tag("pr", "1234"),
+        )
+        .unwrap();
+    let mut tx = repo.start_transaction(&settings);
+    write_random_commit(tx.mut_repo(), &settings);
+    let repo = tx
+        .commit(OperationDescription::new("tagged by hand").with_tag("pr", "1234"))
+        .unwrap();
+
+    // The tags are persisted
+    let repo = testutils::load_repo_at_head(&settings, repo.repo_path());
+    let ops: Vec<Operation> = op_walk::walk_ancestors(slice::from_ref(repo.operation()))
+        .try_collect()
+        .unwrap();
+    let metadata = &ops[1].store_operation().metadata;
+    assert_eq!(metadata.description, "tagged by ci");
+    assert_eq!(
+        metadata.tags,
+        HashMap::from([
+            ("args".to_string(), "bot --run".to_string()),
+            ("triggered-by".to_string(), "ci".to_string()),
+            ("pr".to_string(), "1234".to_string()),
+        ])
+    );
+
+    let filter_descriptions = |filter: &OperationTagFilter| {
+        ops.iter()
+            .filter(|op| filter.matches(op))
+            .map(|op| op.store_operation().metadata.description.as_str())
+            .collect_vec()
+    };
+    assert_eq!(
+        filter_descriptions(&OperationTagFilter::new().with_tag("pr", "1234")),
+        vec!["tagged by hand", "tagged by ci"]
+    );
+    assert_eq!(
+        filter_descriptions(
+            &OperationTagFilter::new()
+                .with_tag("pr", "1234")
+                .with_tag("triggered-by", "ci")
+        ),
+        vec!["tagged by ci"]
+    );
+    assert_eq!(
+        filter_descriptions(&OperationTagFilter::new().with_tag("pr", "1")),
+        Vec::<&str>::new()
+    );
+    // Operations without tags only match the empty filter
+    assert_eq!(
+        filter_descriptions(&OperationTagFilter::new()).len(),
+        ops.len()
+    );
+}
+
+#[test]
+fn test_operation_times_with_backwards_clock() {
+    let settings_at = |timestamp: &str| {
+        let config = testutils::base_config()
+            .set_override("debug.operation-timestamp", timestamp)
+            .unwrap()
+            .build()
+            .unwrap();
+        UserSettings::from_config(config)
+    };
+    let test_repo = TestRepo::init_with_settings(&settings_at("2001-02-03T04:05:06+07:00"));
+    let repo_0 = test_repo.repo;
+    let time_0 = repo_0
+        .operation()
+        .store_operation()
+        .metadata
+        .end_time
+        .clone();
+
+    // The clock went back by an hour, so the operation is moved forward to the
+    // end of its parent, and the time from the clock is recorded in a tag
+    let repo_1 = repo_0
+        .start_transaction(&settings_at("2001-02-03T03:05:06+07:00"))
+        .commit("op 1")
+        .unwrap();
+    let metadata = &repo_1.operation().store_operation().metadata;
+    assert_eq!(metadata.start_time, time_0);
+    assert_eq!(metadata.end_time, time_0);
+    assert_eq!(
+        metadata.tags,
+        HashMap::from([(
+            WALL_CLOCK_TIME_TAG.to_string(),
+            "2001-02-03T03:05:06+07:00".to_string()
+        )])
+    );
+    assert_eq!(
+        transaction::wall_clock_end_time(metadata),
+        Some(Timestamp {
+            timestamp: MillisSinceEpoch(time_0.timestamp.0 - 3_600_000),
+            tz_offset: 420,
+        })
+    );
+
+    // Once the clock has caught up, the times are kept as is
+    let repo_2 = repo_1
+        .start_transaction(&settings_at("2001-02-03T05:05:06+07:00"))
+        .commit("op 2")
+        .unwrap();
+    let metadata = &repo_2.operation().store_operation().metadata;
+    assert_eq!(
+        metadata.end_time.timestamp,
+        MillisSinceEpoch(time_0.timestamp.0 + 3_600_000)
+    );
+    assert!(metadata.tags.is_empty());
+    assert_eq!(transaction::wall_clock_end_time(metadata), None);
+
+    // Concurrent operations are walked in the order of the clamped times
+    let repo_3 = repo_1
+        .start_transaction(&settings_at("2001-02-03T01:05:06+07:00"))
+        .commit("op 3")
+        .unwrap();
+    let descriptions =
+        op_walk::walk_ancestors(&[repo_3.operation().clone(), repo_2.operation().clone()])
+            .map_ok(|op| op.store_operation().metadata.description.clone())
+            .try_collect::<_, Vec<_>, _>()
+            .unwrap();
+    assert_eq!(
+        descriptions,
+        ["op 2", "op 3", "op 1", "initialize repo", ""]
+    );
+}
+
+#[test]
+fn test_find_op_with_visible_commits() {
+    let settings = testutils::user_settings();
+    let test_repo = TestRepo::init();
+    let repo = &test_repo.repo;
+
+    let mut tx = repo.start_transaction(&settings);
+    let commit_a = write_random_commit(tx.mut_repo(), &settings);
+    let repo_with_a = tx.commit("add a").unwrap();
+    let mut tx = repo_with_a.start_transaction(&settings);
+    tx.mut_repo().record_abandoned_commit(commit_a.id().clone());
+    tx.mut_repo().rebase_descendants(&settings).unwrap();
+    let repo = tx.commit("abandon a").unwrap();
+    let mut tx = repo.start_transaction(&settings);
+    let commit_b = write_random_commit(tx.mut_repo(), &settings);
+    let repo = tx.commit("add b").unwrap();
+
+    let find = |commit_ids: &[CommitId], limit| {
+        op_walk::find_op_with_visible_commits(
+            slice::from_ref(repo.operation()),
+            repo.index(),
+            commit_ids,
+            limit,
+        )
+        .unwrap()
+        .map(|(op, ids)| (op.id().clone(), ids))
+    };
+    // The abandoned commit was last visible two operations ago
+    let ids = [commit_a.id().clone(), commit_b.id().clone()];
+    assert_eq!(
+        find(&ids, 10),
+        Some((repo.op_id().clone(), vec![commit_b.id().clone()]))
+    );
+    assert_eq!(
+        find(&ids[..1], 10),
+        Some((repo_with_a.op_id().clone(), vec![commit_a.id().clone()]))
+    );
+    // Only the given number of operations are searched
+    assert_eq!(find(&ids[..1], 2), None);
+}
+
+#[test]
+fn test_pending_commit_changes() {
+    let settings = testutils::user_settings();
+    let test_repo = TestRepo::init();
+    let repo = &test_repo.repo;
+
+    let mut tx = repo.start_transaction(&settings);
+    let commit_a = write_random_commit(tx.mut_repo(), &settings);
+    let commit_b = create_random_commit(tx.mut_repo(), &settings)
+        .set_parents(vec![commit_a.id().clone()])
+        .write()
+        .unwrap();
+    let commit_c = write_random_commit(tx.mut_repo(), &settings);
+    let repo = tx.commit("test").unwrap();
+
+    // Rewrite A (and thereby B), abandon C, and create D
+    let mut tx = repo.start_transaction(&settings);
+    let new_commit_a = tx
+        .mut_repo()
+        .rewrite_commit(&settings, &commit_a)
+        .set_description("rewritten")
+        .write()
+        .unwrap();
+    tx.mut_repo().record_abandoned_commit(commit_c.id().clone());
+    tx.mut_repo().rebase_descendants(&settings).unwrap();
+    let commit_d = write_random_commit(tx.mut_repo(), &settings);
+    let new_commit_b = tx
+        .repo()
+        .store()
+        .get_commit(
+            tx.repo()
+                .view()
+                .heads()
+                .iter()
+                .find(|id| *id != commit_d.id())
+                .unwrap(),
+        )
+        .unwrap();
+    assert_eq!(new_commit_b.change_id(), commit_b.change_id());
+
+    let changes = tx.pending_changes().commit_changes().unwrap();
+    assert_eq!(changes.created, vec![commit_d]);
+    assert_eq!(changes.abandoned, vec![commit_c]);
+    assert_eq!(
+        changes.rewritten.into_iter().sorted().collect_vec(),
+        [(commit_a, new_commit_a), (commit_b, new_commit_b)]
+            .into_iter()
+            .sorted()
+            .collect_vec()
+    );
+}
+
+#[test]
+fn test_transaction_validation() {
+    let settings = testutils::user_settings();
+    let test_repo = TestRepo::init();
+    let repo = &test_repo.repo;
+    let secret_path = RepoPath::from_internal_string("secret");
+    let validators: [Arc<dyn TransactionValidator>; 2] = [
+        Arc::new(ForbiddenPathValidator {
+            path: secret_path.to_owned(),
+        }),
+        Arc::new(ProtectedBranchValidator {
+            name: "main".to_owned(),
+        }),
+    ];
+
+    // Changes that don't violate any rule can be committed
+    let mut tx = repo.start_transaction(&settings);
+    for validator in &validators {
+        tx.add_validator(validator.clone());
+    }
+    write_random_commit(tx.mut_repo(), &settings);
+    let repo = tx.commit("allowed").unwrap();
+
+    let op_heads_dir = repo.repo_path().join("op_heads").join("heads");
+    let operations_dir = repo.repo_path().join("op_store").join("operations");
+    let views_dir = repo.repo_path().join("op_store").join("views");
+    let op_heads_before = list_dir(&op_heads_dir);
+    let operations_before = list_dir(&operations_dir);
+    let views_before = list_dir(&views_dir);
+
+    // All violations are reported at once, and nothing is written
+    let mut tx = repo.start_transaction(&settings);
+    for validator in &validators {
+        tx.add_validator(validator.clone());
+    }
+    let tree = create_tree(&repo, &[(secret_path, "password")]);
+    let commit = tx
+        .mut_repo()
+        .new_commit(
+            &settings,
+            vec![repo.store().root_commit_id().clone()],
+            tree.id(),
+        )
+        .write()
+        .unwrap();
+    tx.mut_repo()
+        .set_local_branch_target("main", RefTarget::normal(commit.id().clone()));
+    assert_matches!(
+        tx.validate(),
+        Err(TransactionValidationError::Rejected(violations)) if violations == vec![
+            format!("Commit {} touches secret", commit.id().hex()),
+            "Branch main is protected".to_owned(),
+        ]
+    );
+    assert_matches!(
+        tx.commit("rejected"),
+        Err(TransactionCommitError::Validation(
+            TransactionValidationError::Rejected(_)
+        ))
+    );
+    assert_eq!(list_dir(&op_heads_dir), op_heads_before);
+    assert_eq!(list_dir(&operations_dir), operations_before);
+    assert_eq!(list_dir(&views_dir), views_before);
+    let reloaded_repo = repo.loader().load_at_head(&settings).unwrap();
+    assert_eq!(reloaded_repo.op_id(), repo.op_id());
+    assert!(reloaded_repo.view().get_local_branch("main").is_absent());
+    assert!(!reloaded_repo.view().heads().contains(commit.id()));
+}
+
+/// Accepts everything, but counts how often it's run.
+#[derive(Default)]
+struct CountingValidator {
+    num_calls: AtomicUsize,
+}
+
+impl TransactionValidator for CountingValidator {
+    fn validate(&self, _changes: &PendingChanges) -> Result<(), TransactionValidationError> {
+        self.num_calls.fetch_add(1, Ordering::Relaxed);
+        Ok(())
+    }
+}
+
+#[test]
+fn test_commit_validated_runs_validators_once() {
+    let settings = testutils::user_settings();
+    let test_repo = TestRepo::init();
+    let repo = &test_repo.repo;
+    let validator = Arc::new(CountingValidator::default());
+
+    let mut tx = repo.start_transaction(&settings);
+    tx.add_validator(validator.clone());
+    write_random_commit(tx.mut_repo(), &settings);
+    let validated = tx.validate().unwrap();
+    assert_eq!(validator.num_calls.load(Ordering::Relaxed), 1);
+    let new_repo = tx.commit_validated("test", validated).unwrap();
+    assert_eq!(validator.num_calls.load(Ordering::Relaxed), 1);
+    assert_ne!(new_repo.op_id(), repo.op_id());
+
+    // A plain commit runs them itself
+    let mut tx = new_repo.start_transaction(&settings);
+    tx.add_validator(validator.clone());
+    write_random_commit(tx.mut_repo(), &settings);
+    tx.commit("test").unwrap();
+    assert_eq!(validator.num_calls.load(Ordering::Relaxed), 2);
+}
+
+#[test]
+fn test_builtin_validators() {
+    let config = testutils::base_config()
+        .add_source(config::File::from_str(
+            r#"
+            validation.forbidden-paths = ["secret"]
+            validation.require-description = true
+            "#,
+            config::FileFormat::Toml,
+        ))
+        .build()
+        .unwrap();
+    let settings = UserSettings::from_config(config);
+    let test_repo = TestRepo::init();
+    let repo = &test_repo.repo;
+    let validators = transaction::builtin_validators(&settings).unwrap();
+    assert_eq!(validators.len(), 2);
+
+    let mut tx = repo.start_transaction(&settings);
+    for validator in &validators {
+        tx.add_validator(validator.clone());
+    }
+    let tree = create_tree(
+        repo,
+        &[(RepoPath::from_internal_string("secret/key"), "password")],
+    );
+    let commit = tx
+        .mut_repo()
+        .new_commit(
+            &settings,
+            vec![repo.store().root_commit_id().clone()],
+            tree.id(),
+        )
+        .write()
+        .unwrap();
+    let short_id = &commit.id().hex()[..12];
+    assert_matches!(
+        tx.validate(),
+        Err(TransactionValidationError::Rejected(violations)) if violations == vec![
+            format!("Commit {short_id} changes the forbidden path secret/key"),
+            format!("Commit {short_id} has no description"),
+        ]
+    );
+}
+
+#[test]
+fn test_immutable_commits_validator() {
+    let settings = testutils::user_settings();
+    let test_repo = TestRepo::init();
+    let repo = &test_repo.repo;
+    let mut tx = repo.start_transaction(&settings);
+    let commit_a = write_random_commit(tx.mut_repo(), &settings);
+    let commit_b = create_random_commit(tx.mut_repo(), &settings)
+        .set_parents(vec![commit_a.id().clone()])
+        .write()
+        .unwrap();
+    let repo = tx.commit("test").unwrap();
+    let validator: Arc<dyn TransactionValidator> =
+        Arc::new(ImmutableCommitsValidator::new(vec![commit_a.id().clone()]));
+
+    // Mutable commits can be rewritten
+    let mut tx = repo.start_transaction(&settings);
+    tx.add_validator(validator.clone());
+    tx.mut_repo()
+        .rewrite_commit(&settings, &commit_b)
+        .set_description("b")
+        .write()
+        .unwrap();
+    assert_matches!(tx.validate(), Ok(()));
+
+    // Rewriting an immutable commit is rejected, but not rebasing its mutable
+    // descendants
+    let mut tx = repo.start_transaction(&settings);
+    tx.add_validator(validator);
+    tx.mut_repo()
+        .rewrite_commit(&settings, &commit_a)
+        .set_description("a")
+        .write()
+        .unwrap();
+    tx.mut_repo().rebase_descendants(&settings).unwrap();
+    assert_matches!(
+        tx.validate(),
+        Err(TransactionValidationError::Rejected(violations)) if violations == vec![
+            format!("Commit {} is immutable", &commit_a.id().hex()[..12]),
+        ]
+    );
+}
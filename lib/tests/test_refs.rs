@@ -14,7 +14,7 @@
 
 use jj_lib::merge::Merge;
 use jj_lib::op_store::RefTarget;
-use jj_lib::refs::merge_ref_targets;
+use jj_lib::refs::{count_ahead_behind, merge_ref_targets, CommitCount};
 use jj_lib::repo::Repo;
 use testutils::{CommitGraphBuilder, TestWorkspace};
 
@@ -41,7 +41,7 @@ fn test_merge_ref_targets() {
     let commit5 = graph_builder.commit_with_parents(&[&commit1]);
     let commit6 = graph_builder.commit_with_parents(&[&commit5]);
     let commit7 = graph_builder.commit_with_parents(&[&commit5]);
-    let repo = tx.commit("test");
+    let repo = tx.commit("test").unwrap();
 
     let target1 = RefTarget::normal(commit1.id().clone());
     let target2 = RefTarget::normal(commit2.id().clone());
@@ -387,3 +387,71 @@ fn test_merge_ref_targets() {
         )
     );
 }
+
+#[test]
+fn test_count_ahead_behind() {
+    let settings = testutils::user_settings();
+    let test_workspace = TestWorkspace::init(&settings);
+    let repo = &test_workspace.repo;
+
+    // 4 6
+    // 3 5
+    // |/
+    // 2
+    // 1
+    let mut tx = repo.start_transaction(&settings);
+    let mut graph_builder = CommitGraphBuilder::new(&settings, tx.mut_repo());
+    let commit1 = graph_builder.initial_commit();
+    let commit2 = graph_builder.commit_with_parents(&[&commit1]);
+    let commit3 = graph_builder.commit_with_parents(&[&commit2]);
+    let commit4 = graph_builder.commit_with_parents(&[&commit3]);
+    let commit5 = graph_builder.commit_with_parents(&[&commit2]);
+    let commit6 = graph_builder.commit_with_parents(&[&commit5]);
+    let repo = tx.commit("test").unwrap();
+
+    let target2 = RefTarget::normal(commit2.id().clone());
+    let target4 = RefTarget::normal(commit4.id().clone());
+    let target6 = RefTarget::normal(commit6.id().clone());
+    let count = |local: &RefTarget, remote: &RefTarget, limit: usize| {
+        let counts = count_ahead_behind(repo.as_ref(), local, remote, limit).unwrap();
+        (counts.ahead, counts.behind)
+    };
+
+    // Up to date
+    assert_eq!(
+        count(&target4, &target4, 1000),
+        (CommitCount::Exact(0), CommitCount::Exact(0))
+    );
+    // Ahead only
+    assert_eq!(
+        count(&target4, &target2, 1000),
+        (CommitCount::Exact(2), CommitCount::Exact(0))
+    );
+    // Diverged
+    assert_eq!(
+        count(&target4, &target6, 1000),
+        (CommitCount::Exact(2), CommitCount::Exact(2))
+    );
+    // The counts are capped
+    assert_eq!(
+        count(&target4, &target6, 2),
+        (CommitCount::Exact(2), CommitCount::Exact(2))
+    );
+    assert_eq!(
+        count(&target4, &target6, 1),
+        (CommitCount::AtLeast(1), CommitCount::AtLeast(1))
+    );
+    assert_eq!(
+        count(&target6, &RefTarget::absent(), 1),
+        (CommitCount::AtLeast(1), CommitCount::Exact(0))
+    );
+    // Conflicted targets are compared using their added commits
+    let conflicted = RefTarget::from_legacy_form(
+        [commit2.id().clone()],
+        [commit4.id().clone(), commit6.id().clone()],
+    );
+    assert_eq!(
+        count(&conflicted, &target4, 1000),
+        (CommitCount::Exact(2), CommitCount::Exact(0))
+    );
+}
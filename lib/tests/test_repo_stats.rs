@@ -0,0 +1,118 @@
+// Copyright 2024 The Jujutsu Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use jj_lib::repo_path::RepoPathBuf;
+use jj_lib::repo_stats::{collect_repo_stats, ObjectStats};
+use test_case::test_case;
+use testutils::test_repo_builder::TestRepoBuilder;
+use testutils::TestRepoBackend;
+
+#[test_case(TestRepoBackend::Local ; "local backend")]
+#[test_case(TestRepoBackend::Git ; "git backend")]
+fn test_collect_repo_stats(backend: TestRepoBackend) {
+    let big = "b".repeat(100);
+    let medium = "m".repeat(50);
+    let huge = "h".repeat(200);
+    let fixture = TestRepoBuilder::new()
+        .backend(backend)
+        .commit("a")
+        .file("big", &big)
+        .file("small", "1\n")
+        .commit_on("a", "b")
+        .file("small", "22\n")
+        .file("dir/medium", &medium)
+        .commit_on("b", "c")
+        .file("dir/copy", &big)
+        .file("huge", &huge)
+        .build();
+    let repo = fixture.repo();
+
+    let stats = collect_repo_stats(repo, 2).unwrap();
+    assert_eq!(stats.commits.count, 3);
+    assert!(stats.commits.num_bytes > 0);
+    // The root trees of all commits, and the `dir` trees of `b` and `c`
+    assert_eq!(stats.trees.count, 5);
+    assert!(stats.trees.num_bytes > 0);
+    // `dir/copy` has the same contents as `big`, so it's counted once
+    assert_eq!(
+        stats.files,
+        ObjectStats {
+            count: 5,
+            num_bytes: 100 + 2 + 3 + 50 + 200,
+        }
+    );
+    assert_eq!(stats.symlinks, ObjectStats::default());
+    assert_eq!(stats.conflicts, ObjectStats::default());
+    // The root operation, the one that initialized the repo, and the one that
+    // added the commits
+    assert_eq!(stats.num_operations, 3);
+
+    let largest_files = stats
+        .largest_files
+        .iter()
+        .map(|file| (file.size, file.path.clone(), file.commit_id.clone()))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        largest_files,
+        vec![
+            (
+                200,
+                RepoPathBuf::from_internal_string("huge"),
+                fixture.commit("c").id().clone()
+            ),
+            (
+                100,
+                RepoPathBuf::from_internal_string("big"),
+                fixture.commit("a").id().clone()
+            ),
+        ]
+    );
+}
+
+#[test_case(TestRepoBackend::Local ; "local backend")]
+#[test_case(TestRepoBackend::Git ; "git backend")]
+fn test_collect_repo_stats_file_in_visited_tree(backend: TestRepoBackend) {
+    let big = "b".repeat(100);
+    // `c` restores the `dir` tree of `a`. Commits are visited children first,
+    // so `big` is found in `c` before `a`.
+    let fixture = TestRepoBuilder::new()
+        .backend(backend)
+        .commit("a")
+        .file("dir/big", &big)
+        .commit_on("a", "b")
+        .remove_file("dir/big")
+        .file("small", "1\n")
+        .commit_on("b", "c")
+        .file("dir/big", &big)
+        .build();
+    let repo = fixture.repo();
+
+    let stats = collect_repo_stats(repo, 1).unwrap();
+    assert_eq!(stats.commits.count, 3);
+    // The root trees of all commits, and the `dir` tree shared by `a` and `c`
+    assert_eq!(stats.trees.count, 4);
+    let largest_files = stats
+        .largest_files
+        .iter()
+        .map(|file| (file.size, file.path.clone(), file.commit_id.clone()))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        largest_files,
+        vec![(
+            100,
+            RepoPathBuf::from_internal_string("dir/big"),
+            fixture.commit("a").id().clone()
+        )]
+    );
+}
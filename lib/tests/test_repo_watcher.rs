@@ -0,0 +1,166 @@
+// Copyright 2024 The Jujutsu Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::collections::HashSet;
+use std::path::Path;
+use std::sync::mpsc::RecvTimeoutError;
+use std::thread;
+use std::time::Duration;
+
+use jj_lib::op_heads_store::OpHeadsStore as _;
+use jj_lib::op_store::OperationId;
+use jj_lib::repo_watcher::{RepoChanged, RepoWatcher, RepoWatcherOptions};
+use jj_lib::simple_op_heads_store::SimpleOpHeadsStore;
+use testutils::{write_random_commit, TestRepo, TestWorkspace};
+
+const TIMEOUT: Duration = Duration::from_secs(10);
+
+fn polling_watcher(repo_path: &Path, debounce: Duration) -> RepoWatcher {
+    let options = RepoWatcherOptions {
+        poll_interval: Duration::from_millis(10),
+        debounce,
+        force_polling: true,
+    };
+    RepoWatcher::with_options(repo_path, options).unwrap()
+}
+
+fn recv_op_head(watcher: &RepoWatcher) -> OperationId {
+    let RepoChanged { new_op_head } = watcher.events().recv_timeout(TIMEOUT).unwrap();
+    new_op_head
+}
+
+#[test]
+fn test_repo_watcher_reports_operations() {
+    let settings = testutils::user_settings();
+    let test_repo = TestRepo::init();
+    let mut repo = test_repo.repo.clone();
+    let op_heads_store = SimpleOpHeadsStore::load(&repo.repo_path().join("op_heads"));
+    let watcher = polling_watcher(repo.repo_path(), Duration::from_millis(20));
+
+    // Operations committed by another thread are reported one by one, and each
+    // of them increments the sequence number
+    for _ in 0..3 {
+        let sequence_number = op_heads_store.read_sequence_number().unwrap();
+        let base_repo = repo.clone();
+        let thread_settings = settings.clone();
+        repo = thread::spawn(move || {
+            let mut tx = base_repo.start_transaction(&thread_settings);
+            write_random_commit(tx.mut_repo(), &thread_settings);
+            tx.commit("test").unwrap()
+        })
+        .join()
+        .unwrap();
+        assert_eq!(recv_op_head(&watcher), *repo.op_id());
+        assert_eq!(
+            op_heads_store.read_sequence_number().unwrap(),
+            sequence_number + 1
+        );
+    }
+}
+
+#[test]
+fn test_sequence_number_concurrent_updates() {
+    let temp_dir = testutils::new_temp_dir();
+    let op_heads_store = SimpleOpHeadsStore::init(temp_dir.path());
+
+    // Stale op heads are removed without the op heads lock, and such updates
+    // don't lose each other's increments either
+    thread::scope(|scope| {
+        for i in 0..8u8 {
+            let op_heads_store = &op_heads_store;
+            scope.spawn(move || {
+                for j in 0..10u8 {
+                    op_heads_store.update_op_heads(&[], &OperationId::new(vec![i, j]));
+                }
+            });
+        }
+    });
+    assert_eq!(op_heads_store.read_sequence_number().unwrap(), 80);
+    assert_eq!(op_heads_store.get_op_heads().len(), 80);
+}
+
+#[test]
+fn test_repo_watcher_coalesces_bursts() {
+    let settings = testutils::user_settings();
+    let test_repo = TestRepo::init();
+    let repo = test_repo.repo.clone();
+    let watcher = polling_watcher(repo.repo_path(), Duration::from_millis(500));
+
+    // A burst of operations is reported as its resulting op head
+    let last_repo = thread::spawn(move || {
+        let mut repo = repo;
+        for _ in 0..3 {
+            let mut tx = repo.start_transaction(&settings);
+            write_random_commit(tx.mut_repo(), &settings);
+            repo = tx.commit("test").unwrap();
+        }
+        repo
+    })
+    .join()
+    .unwrap();
+    assert_eq!(recv_op_head(&watcher), *last_repo.op_id());
+    assert_eq!(
+        watcher
+            .events()
+            .recv_timeout(Duration::from_millis(700))
+            .unwrap_err(),
+        RecvTimeoutError::Timeout
+    );
+}
+
+#[test]
+fn test_repo_watcher_concurrent_operations() {
+    let settings = testutils::user_settings();
+    let test_repo = TestRepo::init();
+    let repo = test_repo.repo.clone();
+    let watcher = polling_watcher(repo.repo_path(), Duration::from_millis(100));
+
+    // Both heads of concurrent operations are reported
+    let op_ids = thread::spawn(move || {
+        let mut tx1 = repo.start_transaction(&settings);
+        write_random_commit(tx1.mut_repo(), &settings);
+        let mut tx2 = repo.start_transaction(&settings);
+        write_random_commit(tx2.mut_repo(), &settings);
+        [tx1.commit("tx1").unwrap(), tx2.commit("tx2").unwrap()].map(|repo| repo.op_id().clone())
+    })
+    .join()
+    .unwrap();
+    let reported = HashSet::from([recv_op_head(&watcher), recv_op_head(&watcher)]);
+    assert_eq!(reported, HashSet::from(op_ids));
+}
+
+#[test]
+fn test_repo_watcher_ignores_working_copy_changes() {
+    let settings = testutils::user_settings();
+    let mut test_workspace = TestWorkspace::init(&settings);
+    let repo = test_workspace.repo.clone();
+    let watcher = polling_watcher(repo.repo_path(), Duration::from_millis(20));
+
+    // Updating the working-copy state without an operation isn't reported
+    let workspace_root = test_workspace.workspace.workspace_root().clone();
+    std::fs::write(workspace_root.join("file"), "contents").unwrap();
+    test_workspace.snapshot().unwrap();
+    assert_eq!(
+        watcher
+            .events()
+            .recv_timeout(Duration::from_millis(200))
+            .unwrap_err(),
+        RecvTimeoutError::Timeout
+    );
+
+    let mut tx = repo.start_transaction(&settings);
+    write_random_commit(tx.mut_repo(), &settings);
+    let repo = tx.commit("test").unwrap();
+    assert_eq!(recv_op_head(&watcher), *repo.op_id());
+}
@@ -20,6 +20,7 @@ use jj_lib::backend::{CommitId, MillisSinceEpoch, Signature, Timestamp};
 use jj_lib::commit::Commit;
 use jj_lib::git;
 use jj_lib::git_backend::GitBackend;
+use jj_lib::hex_util::to_reverse_hex;
 use jj_lib::object_id::ObjectId;
 use jj_lib::op_store::{RefTarget, RemoteRef, RemoteRefState, WorkspaceId};
 use jj_lib::repo::Repo;
@@ -27,7 +28,7 @@ use jj_lib::repo_path::RepoPath;
 use jj_lib::revset::{
     optimize, parse, DefaultSymbolResolver, FailingSymbolResolver, ResolvedExpression, Revset,
     RevsetAliasesMap, RevsetExpression, RevsetFilterPredicate, RevsetParseContext,
-    RevsetResolutionError, RevsetWorkspaceContext,
+    RevsetResolutionError, RevsetSymbolKind, RevsetWorkspaceContext,
 };
 use jj_lib::revset_graph::{ReverseRevsetGraphIterator, RevsetGraphEdge};
 use jj_lib::settings::GitSettings;
@@ -109,7 +110,7 @@ fn test_resolve_symbol_commit_id() {
             .unwrap();
         commits.push(commit);
     }
-    let repo = tx.commit("test");
+    let repo = tx.commit("test").unwrap();
 
     // Test the test setup
     assert_eq!(
@@ -255,7 +256,7 @@ fn test_resolve_symbol_change_id(readonly: bool) {
 
     let _readonly_repo;
     let repo: &dyn Repo = if readonly {
-        _readonly_repo = tx.commit("test");
+        _readonly_repo = tx.commit("test").unwrap();
         _readonly_repo.as_ref()
     } else {
         tx.mut_repo()
@@ -652,6 +653,164 @@ fn test_resolve_symbol_tags() {
     );
 }
 
+#[test]
+fn test_resolve_symbol_ambiguous() {
+    let settings = testutils::user_settings();
+    let test_repo = TestRepo::init();
+    let repo = &test_repo.repo;
+
+    let mut tx = repo.start_transaction(&settings);
+    let mut_repo = tx.mut_repo();
+
+    let commit1 = write_random_commit(mut_repo, &settings);
+    let commit2 = write_random_commit(mut_repo, &settings);
+    let commit3 = write_random_commit(mut_repo, &settings);
+
+    // Branches named like a commit id prefix and a change id prefix
+    let commit_prefix = commit2.id().hex()[..12].to_owned();
+    let change_prefix = to_reverse_hex(&commit3.change_id().hex()[..12]).unwrap();
+    mut_repo.set_local_branch_target(&commit_prefix, RefTarget::normal(commit1.id().clone()));
+    mut_repo.set_local_branch_target(&change_prefix, RefTarget::normal(commit1.id().clone()));
+
+    // The branch takes precedence by default
+    assert_eq!(
+        resolve_symbol(mut_repo, &commit_prefix).unwrap(),
+        vec![commit1.id().clone()],
+    );
+    assert_eq!(
+        resolve_symbol(mut_repo, &change_prefix).unwrap(),
+        vec![commit1.id().clone()],
+    );
+    let symbol_resolver = DefaultSymbolResolver::new(mut_repo);
+    assert_eq!(
+        symbol_resolver
+            .resolve_symbol_matches(&commit_prefix)
+            .unwrap(),
+        vec![
+            (RevsetSymbolKind::Branch, vec![commit1.id().clone()]),
+            (RevsetSymbolKind::CommitId, vec![commit2.id().clone()]),
+        ],
+    );
+
+    // Strict mode turns the ambiguity into an error
+    let symbol_resolver = DefaultSymbolResolver::new(mut_repo).with_strict(true);
+    assert_matches!(
+        symbol_resolver.resolve_symbol(&commit_prefix),
+        Err(RevsetResolutionError::AmbiguousSymbol { name, kinds })
+            if name == commit_prefix
+                && kinds == [RevsetSymbolKind::Branch, RevsetSymbolKind::CommitId]
+    );
+    assert_matches!(
+        symbol_resolver.resolve_symbol(&change_prefix),
+        Err(RevsetResolutionError::AmbiguousSymbol { kinds, .. })
+            if kinds == [RevsetSymbolKind::Branch, RevsetSymbolKind::ChangeId]
+    );
+    // Unambiguous symbols still resolve in strict mode
+    assert_eq!(
+        symbol_resolver.resolve_symbol(&commit1.id().hex()).unwrap(),
+        vec![commit1.id().clone()],
+    );
+
+    // Explicit namespaces
+    assert_eq!(
+        resolve_symbol(mut_repo, &format!("branch:{commit_prefix}")).unwrap(),
+        vec![commit1.id().clone()],
+    );
+    assert_eq!(
+        resolve_symbol(mut_repo, &format!("commit:{commit_prefix}")).unwrap(),
+        vec![commit2.id().clone()],
+    );
+    assert_eq!(
+        resolve_symbol(mut_repo, &format!("change:{change_prefix}")).unwrap(),
+        vec![commit3.id().clone()],
+    );
+    assert_matches!(
+        resolve_symbol(mut_repo, &format!("change:{commit_prefix}")),
+        Err(RevsetResolutionError::NoSuchRevision { .. })
+    );
+    assert_matches!(
+        resolve_symbol(mut_repo, &format!("branch:{}", commit2.id().hex())),
+        Err(RevsetResolutionError::NoSuchRevision { .. })
+    );
+}
+
+#[test]
+fn test_resolve_symbol_ignoring_case() {
+    let settings = testutils::user_settings();
+    let test_repo = TestRepo::init();
+    let repo = &test_repo.repo;
+
+    let mut tx = repo.start_transaction(&settings);
+    let mut_repo = tx.mut_repo();
+
+    let commit1 = write_random_commit(mut_repo, &settings);
+    let commit2 = write_random_commit(mut_repo, &settings);
+    let commit3 = write_random_commit(mut_repo, &settings);
+    mut_repo.set_local_branch_target("Feature/Login", RefTarget::normal(commit1.id().clone()));
+    mut_repo.set_tag_target("V1.0", RefTarget::normal(commit2.id().clone()));
+    mut_repo.set_local_branch_target("main", RefTarget::normal(commit2.id().clone()));
+    mut_repo.set_tag_target("Main", RefTarget::normal(commit3.id().clone()));
+
+    // Case-insensitive lookup is disabled by default
+    assert_matches!(
+        resolve_symbol(mut_repo, "feature/login"),
+        Err(RevsetResolutionError::NoSuchRevision { .. })
+    );
+
+    let symbol_resolver = DefaultSymbolResolver::new(mut_repo).with_case_insensitive_refs(true);
+    assert_eq!(
+        symbol_resolver.resolve_symbol("feature/login").unwrap(),
+        vec![commit1.id().clone()],
+    );
+    assert_eq!(
+        symbol_resolver.resolve_symbol("v1.0").unwrap(),
+        vec![commit2.id().clone()],
+    );
+    assert_eq!(
+        symbol_resolver
+            .resolve_qualified_symbol(RevsetSymbolKind::Branch, "FEATURE/LOGIN")
+            .unwrap(),
+        vec![commit1.id().clone()],
+    );
+    // Exact matches take precedence, even over a tag that would otherwise win
+    assert_eq!(
+        symbol_resolver.resolve_symbol("main").unwrap(),
+        vec![commit2.id().clone()],
+    );
+    assert_eq!(
+        symbol_resolver.resolve_symbol("Main").unwrap(),
+        vec![commit3.id().clone()],
+    );
+    assert_matches!(
+        symbol_resolver.resolve_symbol("nonexistent"),
+        Err(RevsetResolutionError::NoSuchRevision { .. })
+    );
+
+    // Names differing only by case make the lookup ambiguous
+    mut_repo.set_local_branch_target("feature/LOGIN", RefTarget::normal(commit3.id().clone()));
+    let symbol_resolver = DefaultSymbolResolver::new(mut_repo).with_case_insensitive_refs(true);
+    assert_matches!(
+        symbol_resolver.resolve_symbol("feature/login"),
+        Err(RevsetResolutionError::AmbiguousRefNameCase { name, kind, candidates })
+            if name == "feature/login"
+                && kind == RevsetSymbolKind::Branch
+                && candidates == ["Feature/Login", "feature/LOGIN"]
+    );
+    // An exact match is still fine
+    assert_eq!(
+        symbol_resolver.resolve_symbol("feature/LOGIN").unwrap(),
+        vec![commit3.id().clone()],
+    );
+
+    // Removed branches are no longer candidates
+    mut_repo.set_local_branch_target("feature/LOGIN", RefTarget::absent());
+    let symbol_resolver = DefaultSymbolResolver::new(mut_repo).with_case_insensitive_refs(true);
+    assert_eq!(
+        symbol_resolver.resolve_symbol("feature/login").unwrap(),
+        vec![commit1.id().clone()],
+    );
+}
+
 #[test]
 fn test_resolve_symbol_git_head() {
     let settings = testutils::user_settings();
@@ -2030,6 +2189,72 @@ fn test_evaluate_expression_latest() {
     );
 }
 
+#[test]
+fn test_evaluate_expression_limit() {
+    let settings = testutils::user_settings();
+    let test_repo = TestRepo::init();
+    let repo = &test_repo.repo;
+
+    let mut tx = repo.start_transaction(&settings);
+    let mut_repo = tx.mut_repo();
+
+    let root_commit = repo.store().root_commit();
+    let commit1 = write_random_commit(mut_repo, &settings);
+    let commit2 = create_random_commit(mut_repo, &settings)
+        .set_parents(vec![commit1.id().clone()])
+        .write()
+        .unwrap();
+    let commit3 = create_random_commit(mut_repo, &settings)
+        .set_parents(vec![commit2.id().clone()])
+        .write()
+        .unwrap();
+
+    // Takes the first entries in iteration order
+    assert_eq!(
+        resolve_commit_ids(mut_repo, "limit(all(), 2)"),
+        vec![commit3.id().clone(), commit2.id().clone()],
+    );
+
+    // Should not panic with count = 0 or empty set
+    assert_eq!(resolve_commit_ids(mut_repo, "limit(all(), 0)"), vec![]);
+    assert_eq!(resolve_commit_ids(mut_repo, "limit(none(), 2)"), vec![]);
+
+    // Should not panic if count is larger than the candidates size
+    assert_eq!(
+        resolve_commit_ids(mut_repo, "limit(~root(), 10)"),
+        vec![
+            commit3.id().clone(),
+            commit2.id().clone(),
+            commit1.id().clone(),
+        ],
+    );
+
+    // Composed with other expressions, both as set and as filter
+    assert_eq!(
+        resolve_commit_ids(mut_repo, "limit(all(), 3) ~ limit(all(), 1)"),
+        vec![commit2.id().clone(), commit1.id().clone()],
+    );
+    assert_eq!(
+        resolve_commit_ids(
+            mut_repo,
+            &format!("::{} & limit(all(), 2)", commit2.id().hex())
+        ),
+        vec![commit2.id().clone()],
+    );
+    assert_eq!(
+        resolve_commit_ids(mut_repo, "limit(::root() | limit(all(), 1), 5)"),
+        vec![commit3.id().clone(), root_commit.id().clone()],
+    );
+    assert_eq!(
+        resolve_commit_ids(mut_repo, "roots(limit(all(), 2))"),
+        vec![commit2.id().clone()],
+    );
+    assert_eq!(
+        resolve_commit_ids(mut_repo, "limit(description(x) | all(), 1)"),
+        vec![commit3.id().clone()],
+    );
+}
+
 #[test]
 fn test_evaluate_expression_merges() {
     let settings = testutils::user_settings();
@@ -2308,6 +2533,87 @@ fn test_evaluate_expression_committer() {
     );
 }
 
+#[test]
+fn test_evaluate_expression_signature_date() {
+    let settings = testutils::user_settings();
+    let test_repo = TestRepo::init();
+    let repo = &test_repo.repo;
+
+    let mut tx = repo.start_transaction(&settings);
+    let mut_repo = tx.mut_repo();
+
+    // 2024-01-31T00:00:00Z
+    let bound = 1_706_659_200_000;
+    let mut write_commit_with_timestamps = |author_millis: i64, committer_millis: i64| {
+        let builder = create_random_commit(mut_repo, &settings);
+        let mut author = builder.author().clone();
+        author.timestamp = Timestamp {
+            timestamp: MillisSinceEpoch(author_millis),
+            tz_offset: 60,
+        };
+        let mut committer = builder.committer().clone();
+        committer.timestamp = Timestamp {
+            timestamp: MillisSinceEpoch(committer_millis),
+            tz_offset: -480,
+        };
+        builder
+            .set_author(author)
+            .set_committer(committer)
+            .write()
+            .unwrap()
+    };
+    let commit1 = write_commit_with_timestamps(bound - 1, bound);
+    let commit2 = write_commit_with_timestamps(bound, bound - 1);
+
+    // The bound is inclusive for "after", and exclusive for "before"
+    assert_eq!(
+        resolve_commit_ids(mut_repo, r#"author_date(after:"2024-01-31T00:00:00Z")"#),
+        vec![commit2.id().clone()]
+    );
+    assert_eq!(
+        resolve_commit_ids(
+            mut_repo,
+            r#"~root() & author_date(before:"2024-01-31T00:00:00Z")"#
+        ),
+        vec![commit1.id().clone()]
+    );
+    assert_eq!(
+        resolve_commit_ids(
+            mut_repo,
+            r#"committer_date(after:"2024-01-31T01:00:00+01:00")"#
+        ),
+        vec![commit1.id().clone()]
+    );
+    assert_eq!(
+        resolve_commit_ids(
+            mut_repo,
+            r#"~root() & committer_date(before:"2024-01-31T00:00:00Z")"#
+        ),
+        vec![commit2.id().clone()]
+    );
+    assert_eq!(
+        resolve_commit_ids(
+            mut_repo,
+            r#"author_date(after:"2024-01-30") & committer_date(before:"now")"#
+        ),
+        vec![commit2.id().clone(), commit1.id().clone()]
+    );
+
+    // latest() can order by either timestamp
+    assert_eq!(
+        resolve_commit_ids(mut_repo, "latest(all())"),
+        vec![commit1.id().clone()]
+    );
+    assert_eq!(
+        resolve_commit_ids(mut_repo, "latest(all(), by=committer)"),
+        vec![commit1.id().clone()]
+    );
+    assert_eq!(
+        resolve_commit_ids(mut_repo, "latest(all(), by=author)"),
+        vec![commit2.id().clone()]
+    );
+}
+
 #[test]
 fn test_evaluate_expression_union() {
     let settings = testutils::user_settings();
@@ -2676,6 +2982,72 @@ fn test_evaluate_expression_file() {
     );
 }
 
+#[test]
+fn test_evaluate_expression_file_ignoring_whitespace() {
+    let settings = testutils::user_settings();
+    let test_workspace = TestWorkspace::init(&settings);
+    let repo = &test_workspace.repo;
+
+    let mut tx = repo.start_transaction(&settings);
+    let mut_repo = tx.mut_repo();
+
+    let file_path = RepoPath::from_internal_string("file");
+    let tree1 = create_tree(repo, &[(file_path, "a b\nc\n")]);
+    // Only whitespace changes, including line endings
+    let tree2 = create_tree(repo, &[(file_path, "a  b \r\nc\r\n")]);
+    // A real change
+    let tree3 = create_tree(repo, &[(file_path, "a  b \r\nd\r\n")]);
+    let commit1 = mut_repo
+        .new_commit(
+            &settings,
+            vec![repo.store().root_commit_id().clone()],
+            tree1.id(),
+        )
+        .write()
+        .unwrap();
+    let commit2 = mut_repo
+        .new_commit(&settings, vec![commit1.id().clone()], tree2.id())
+        .write()
+        .unwrap();
+    let commit3 = mut_repo
+        .new_commit(&settings, vec![commit2.id().clone()], tree3.id())
+        .write()
+        .unwrap();
+
+    let resolve = |whitespace: &str| {
+        resolve_commit_ids_in_workspace(
+            mut_repo,
+            &format!(r#"file("file", whitespace="{whitespace}")"#),
+            &test_workspace.workspace,
+            None,
+        )
+    };
+    assert_eq!(
+        resolve("exact"),
+        vec![
+            commit3.id().clone(),
+            commit2.id().clone(),
+            commit1.id().clone()
+        ]
+    );
+    assert_eq!(
+        resolve("ignore-cr-at-eol"),
+        vec![
+            commit3.id().clone(),
+            commit2.id().clone(),
+            commit1.id().clone()
+        ]
+    );
+    assert_eq!(
+        resolve("ignore-space-change"),
+        vec![commit3.id().clone(), commit1.id().clone()]
+    );
+    assert_eq!(
+        resolve("ignore-all-space"),
+        vec![commit3.id().clone(), commit1.id().clone()]
+    );
+}
+
 #[test]
 fn test_evaluate_expression_conflict() {
     let settings = testutils::user_settings();
@@ -2705,10 +3077,19 @@ fn test_evaluate_expression_conflict() {
     let commit4 = create_commit(vec![commit3.id().clone()], tree4.id());
 
     // Only commit4 has a conflict
+    assert_eq!(
+        resolve_commit_ids(mut_repo, "conflicts()"),
+        vec![commit4.id().clone()]
+    );
+    // The old name is still supported
     assert_eq!(
         resolve_commit_ids(mut_repo, "conflict()"),
         vec![commit4.id().clone()]
     );
+    assert_eq!(
+        resolve_commit_ids(mut_repo, &format!("conflicts() & ::{}", commit3.id().hex())),
+        vec![]
+    );
 }
 
 #[test]
@@ -2741,7 +3122,7 @@ fn test_reverse_graph_iterator() {
     let commit_d = graph_builder.commit_with_parents(&[&commit_c]);
     let commit_e = graph_builder.commit_with_parents(&[&commit_c]);
     let commit_f = graph_builder.commit_with_parents(&[&commit_d, &commit_e]);
-    let repo = tx.commit("test");
+    let repo = tx.commit("test").unwrap();
 
     let revset = revset_for_commits(
         repo.as_ref(),
@@ -13,18 +13,23 @@
 // limitations under the License.
 
 use itertools::Itertools as _;
+use jj_lib::backend::{CommitId, Signature};
 use jj_lib::commit::Commit;
 use jj_lib::matchers::{EverythingMatcher, FilesMatcher};
+use jj_lib::merge::Merge;
 use jj_lib::merged_tree::MergedTree;
 use jj_lib::op_store::{RefTarget, RemoteRef, RemoteRefState, WorkspaceId};
 use jj_lib::repo::Repo;
 use jj_lib::repo_path::RepoPath;
-use jj_lib::rewrite::{rebase_commit_with_options, restore_tree, EmptyBehaviour, RebaseOptions};
+use jj_lib::rewrite::{
+    rebase_commit, rebase_commit_with_options, restore_tree, transform_descendants, EmptyBehaviour,
+    RebaseOptions,
+};
 use maplit::{hashmap, hashset};
 use test_case::test_case;
 use testutils::{
-    assert_abandoned_with_parent, assert_rebased_onto, create_random_commit, create_tree,
-    write_random_commit, CommitGraphBuilder, TestRepo,
+    assert_abandoned_with_parent, assert_rebased_onto, create_random_commit, create_single_tree,
+    create_tree, write_random_commit, CommitGraphBuilder, TestRepo,
 };
 
 #[test]
@@ -939,7 +944,7 @@ fn test_rebase_descendants_basic_branch_update() {
     let commit_b = graph_builder.commit_with_parents(&[&commit_a]);
     tx.mut_repo()
         .set_local_branch_target("main", RefTarget::normal(commit_b.id().clone()));
-    let repo = tx.commit("test");
+    let repo = tx.commit("test").unwrap();
 
     let mut tx = repo.start_transaction(&settings);
     let commit_b2 = tx
@@ -982,7 +987,7 @@ fn test_rebase_descendants_branch_move_two_steps() {
     let commit_c = graph_builder.commit_with_parents(&[&commit_b]);
     tx.mut_repo()
         .set_local_branch_target("main", RefTarget::normal(commit_c.id().clone()));
-    let repo = tx.commit("test");
+    let repo = tx.commit("test").unwrap();
 
     let mut tx = repo.start_transaction(&settings);
     let commit_b2 = tx
@@ -1036,7 +1041,7 @@ fn test_rebase_descendants_basic_branch_update_with_non_local_branch() {
         .set_remote_branch("main", "origin", commit_b_remote_ref.clone());
     tx.mut_repo()
         .set_tag_target("v1", RefTarget::normal(commit_b.id().clone()));
-    let repo = tx.commit("test");
+    let repo = tx.commit("test").unwrap();
 
     let mut tx = repo.start_transaction(&settings);
     let commit_b2 = tx
@@ -1085,7 +1090,7 @@ fn test_rebase_descendants_update_branch_after_abandon() {
     let commit_b = graph_builder.commit_with_parents(&[&commit_a]);
     tx.mut_repo()
         .set_local_branch_target("main", RefTarget::normal(commit_b.id().clone()));
-    let repo = tx.commit("test");
+    let repo = tx.commit("test").unwrap();
 
     let mut tx = repo.start_transaction(&settings);
     tx.mut_repo().record_abandoned_commit(commit_b.id().clone());
@@ -1125,7 +1130,7 @@ fn test_rebase_descendants_update_branches_after_divergent_rewrite() {
         .set_local_branch_target("main", RefTarget::normal(commit_b.id().clone()));
     tx.mut_repo()
         .set_local_branch_target("other", RefTarget::normal(commit_c.id().clone()));
-    let repo = tx.commit("test");
+    let repo = tx.commit("test").unwrap();
 
     let mut tx = repo.start_transaction(&settings);
     let commit_b2 = tx
@@ -1199,7 +1204,7 @@ fn test_rebase_descendants_rewrite_updates_branch_conflict() {
             [commit_b.id().clone(), commit_c.id().clone()],
         ),
     );
-    let repo = tx.commit("test");
+    let repo = tx.commit("test").unwrap();
 
     let mut tx = repo.start_transaction(&settings);
     let commit_a2 = tx
@@ -1280,7 +1285,7 @@ fn test_rebase_descendants_rewrite_resolves_branch_conflict() {
             [commit_b.id().clone(), commit_c.id().clone()],
         ),
     );
-    let repo = tx.commit("test");
+    let repo = tx.commit("test").unwrap();
 
     let mut tx = repo.start_transaction(&settings);
     let commit_b2 = tx
@@ -1320,7 +1325,7 @@ fn test_rebase_descendants_branch_delete_modify_abandon() {
         "main",
         RefTarget::from_legacy_form([commit_a.id().clone()], [commit_b.id().clone()]),
     );
-    let repo = tx.commit("test");
+    let repo = tx.commit("test").unwrap();
 
     let mut tx = repo.start_transaction(&settings);
     tx.mut_repo().record_abandoned_commit(commit_b.id().clone());
@@ -1358,7 +1363,7 @@ fn test_rebase_descendants_update_checkout() {
     tx.mut_repo()
         .set_wc_commit(ws3_id.clone(), commit_a.id().clone())
         .unwrap();
-    let repo = tx.commit("test");
+    let repo = tx.commit("test").unwrap();
 
     let mut tx = repo.start_transaction(&settings);
     let commit_c = tx
@@ -1368,7 +1373,7 @@ fn test_rebase_descendants_update_checkout() {
         .write()
         .unwrap();
     tx.mut_repo().rebase_descendants(&settings).unwrap();
-    let repo = tx.commit("test");
+    let repo = tx.commit("test").unwrap();
 
     // Workspaces 1 and 2 had B checked out, so they get updated to C. Workspace 3
     // had A checked out, so it doesn't get updated.
@@ -1407,12 +1412,12 @@ fn test_rebase_descendants_update_checkout_abandoned() {
     tx.mut_repo()
         .set_wc_commit(ws3_id.clone(), commit_a.id().clone())
         .unwrap();
-    let repo = tx.commit("test");
+    let repo = tx.commit("test").unwrap();
 
     let mut tx = repo.start_transaction(&settings);
     tx.mut_repo().record_abandoned_commit(commit_b.id().clone());
     tx.mut_repo().rebase_descendants(&settings).unwrap();
-    let repo = tx.commit("test");
+    let repo = tx.commit("test").unwrap();
 
     // Workspaces 1 and 2 had B checked out, so they get updated to the same new
     // commit on top of C. Workspace 3 had A checked out, so it doesn't get updated.
@@ -1460,12 +1465,12 @@ fn test_rebase_descendants_update_checkout_abandoned_merge() {
     tx.mut_repo()
         .set_wc_commit(workspace_id.clone(), commit_d.id().clone())
         .unwrap();
-    let repo = tx.commit("test");
+    let repo = tx.commit("test").unwrap();
 
     let mut tx = repo.start_transaction(&settings);
     tx.mut_repo().record_abandoned_commit(commit_d.id().clone());
     tx.mut_repo().rebase_descendants(&settings).unwrap();
-    let repo = tx.commit("test");
+    let repo = tx.commit("test").unwrap();
 
     let new_checkout_id = repo.view().get_wc_commit_id(&workspace_id).unwrap();
     let checkout = repo.store().get_commit(new_checkout_id).unwrap();
@@ -1543,6 +1548,7 @@ fn test_empty_commit_option(empty_behavior: EmptyBehaviour) {
             &settings,
             RebaseOptions {
                 empty: empty_behavior.clone(),
+                cancel: None,
             },
         )
         .unwrap();
@@ -1683,6 +1689,7 @@ fn test_rebase_abandoning_empty() {
 
     let rebase_options = RebaseOptions {
         empty: EmptyBehaviour::AbandonAllEmpty,
+        cancel: None,
     };
     rebase_commit_with_options(
         &settings,
@@ -1716,3 +1723,210 @@ fn test_rebase_abandoning_empty() {
         Some(new_commit_e.id())
     );
 }
+
+#[test]
+fn test_transform_descendants_reauthor() {
+    let settings = testutils::user_settings();
+    let test_repo = TestRepo::init();
+    let repo = &test_repo.repo;
+
+    // All commits get a new author. The merge commit D should be rewritten
+    // onto the new B and C, and the branch should follow commit E.
+    //
+    // E
+    // D
+    // |\
+    // B C
+    // |/
+    // A
+    let mut tx = repo.start_transaction(&settings);
+    let mut graph_builder = CommitGraphBuilder::new(&settings, tx.mut_repo());
+    let commit_a = graph_builder.initial_commit();
+    let commit_b = graph_builder.commit_with_parents(&[&commit_a]);
+    let commit_c = graph_builder.commit_with_parents(&[&commit_a]);
+    let commit_d = graph_builder.commit_with_parents(&[&commit_b, &commit_c]);
+    let commit_e = graph_builder.commit_with_parents(&[&commit_d]);
+    tx.mut_repo()
+        .set_local_branch_target("main", RefTarget::normal(commit_e.id().clone()));
+
+    let new_author = Signature {
+        name: "New Author".to_owned(),
+        email: "new.author@example.com".to_owned(),
+        timestamp: commit_a.author().timestamp.clone(),
+    };
+    let rewrite_map = transform_descendants(
+        &settings,
+        tx.mut_repo(),
+        vec![commit_a.id().clone()],
+        |context| {
+            let builder = context.rebase()?.set_author(new_author.clone());
+            Ok::<_, Box<dyn std::error::Error>>(Some(builder))
+        },
+    )
+    .unwrap();
+    assert_eq!(rewrite_map.len(), 5);
+    let new_commit_a = assert_rebased_onto(
+        tx.mut_repo(),
+        &rewrite_map,
+        &commit_a,
+        &[repo.store().root_commit_id()],
+    );
+    let new_commit_b =
+        assert_rebased_onto(tx.mut_repo(), &rewrite_map, &commit_b, &[new_commit_a.id()]);
+    let new_commit_c =
+        assert_rebased_onto(tx.mut_repo(), &rewrite_map, &commit_c, &[new_commit_a.id()]);
+    let new_commit_d = assert_rebased_onto(
+        tx.mut_repo(),
+        &rewrite_map,
+        &commit_d,
+        &[new_commit_b.id(), new_commit_c.id()],
+    );
+    let new_commit_e =
+        assert_rebased_onto(tx.mut_repo(), &rewrite_map, &commit_e, &[new_commit_d.id()]);
+    for commit in [
+        &new_commit_a,
+        &new_commit_b,
+        &new_commit_c,
+        &new_commit_d,
+        &new_commit_e,
+    ] {
+        assert_eq!(commit.author(), &new_author);
+    }
+    assert_eq!(new_commit_d.tree_id(), commit_d.tree_id());
+
+    assert_eq!(
+        *tx.mut_repo().view().heads(),
+        hashset! {new_commit_e.id().clone()}
+    );
+    assert_eq!(
+        tx.mut_repo().get_local_branch("main"),
+        RefTarget::normal(new_commit_e.id().clone())
+    );
+}
+
+#[test]
+fn test_transform_descendants_abandon() {
+    let settings = testutils::user_settings();
+    let test_repo = TestRepo::init();
+    let repo = &test_repo.repo;
+
+    // Commit B is abandoned and the other commits are kept as is. The merge
+    // commit D should be rebased onto C only, since A is an ancestor of C.
+    // The branch on B should move to A.
+    //
+    // E
+    // D
+    // |\
+    // B C
+    // |/
+    // A
+    let mut tx = repo.start_transaction(&settings);
+    let mut graph_builder = CommitGraphBuilder::new(&settings, tx.mut_repo());
+    let commit_a = graph_builder.initial_commit();
+    let commit_b = graph_builder.commit_with_parents(&[&commit_a]);
+    let commit_c = graph_builder.commit_with_parents(&[&commit_a]);
+    let commit_d = graph_builder.commit_with_parents(&[&commit_b, &commit_c]);
+    let commit_e = graph_builder.commit_with_parents(&[&commit_d]);
+    tx.mut_repo()
+        .set_local_branch_target("b", RefTarget::normal(commit_b.id().clone()));
+
+    let mut visited: Vec<CommitId> = vec![];
+    let rewrite_map = transform_descendants(
+        &settings,
+        tx.mut_repo(),
+        vec![commit_a.id().clone()],
+        |context| {
+            visited.push(context.old_commit().id().clone());
+            if context.old_commit().id() == commit_b.id() {
+                context.abandon();
+            }
+            Ok::<_, Box<dyn std::error::Error>>(None)
+        },
+    )
+    .unwrap();
+    // Parents are visited before their children
+    assert_eq!(visited.len(), 5);
+    assert_eq!(visited[0], *commit_a.id());
+    assert_eq!(visited[3], *commit_d.id());
+    assert_eq!(visited[4], *commit_e.id());
+
+    assert_eq!(rewrite_map.len(), 2);
+    let new_commit_d =
+        assert_rebased_onto(tx.mut_repo(), &rewrite_map, &commit_d, &[commit_c.id()]);
+    let new_commit_e =
+        assert_rebased_onto(tx.mut_repo(), &rewrite_map, &commit_e, &[new_commit_d.id()]);
+
+    assert_eq!(
+        *tx.mut_repo().view().heads(),
+        hashset! {new_commit_e.id().clone()}
+    );
+    assert_eq!(
+        tx.mut_repo().get_local_branch("b"),
+        RefTarget::normal(commit_a.id().clone())
+    );
+}
+
+#[test]
+fn test_rebase_conflict_labels() {
+    let settings = testutils::user_settings();
+    let test_repo = TestRepo::init();
+    let repo = &test_repo.repo;
+
+    // Commit C is rebased onto B, which conflicts. The conflicted commit is
+    // then rebased onto D, which only changes another file.
+    //
+    // D
+    // B C
+    // |/
+    // A
+    let file_path = RepoPath::from_internal_string("file");
+    let other_path = RepoPath::from_internal_string("other");
+    let mut tx = repo.start_transaction(&settings);
+    let mut write_commit = |parents: &[&Commit], contents: &[(&RepoPath, &str)], desc: &str| {
+        let tree = MergedTree::resolved(create_single_tree(repo, contents));
+        let parent_ids = parents.iter().map(|commit| commit.id().clone()).collect();
+        tx.mut_repo()
+            .new_commit(&settings, parent_ids, tree.id())
+            .set_description(desc)
+            .write()
+            .unwrap()
+    };
+    let root_commit = repo.store().root_commit();
+    let commit_a = write_commit(&[&root_commit], &[(file_path, "a\n")], "a");
+    let commit_b = write_commit(&[&commit_a], &[(file_path, "b\n")], "b");
+    let commit_c = write_commit(&[&commit_a], &[(file_path, "c\n")], "c\n\nbody");
+    let commit_d = write_commit(&[&commit_b], &[(file_path, "b\n"), (other_path, "d\n")], "");
+    assert_eq!(commit_c.conflict_labels(), None);
+
+    let rebased_c = rebase_commit(&settings, tx.mut_repo(), &commit_c, &[commit_b]).unwrap();
+    assert!(rebased_c.has_conflict().unwrap());
+    assert_eq!(
+        rebased_c.conflict_labels(),
+        Some(Merge::from_removes_adds(
+            vec![r#"parents of rebased commit "a""#.to_owned()],
+            vec![
+                r#"rebase destination "b""#.to_owned(),
+                r#"rebased commit "c""#.to_owned(),
+            ],
+        ))
+    );
+
+    // The labels survive the simplification of the conflict
+    let rebased_c = rebase_commit(&settings, tx.mut_repo(), &rebased_c, &[commit_d]).unwrap();
+    assert!(rebased_c.has_conflict().unwrap());
+    assert_eq!(
+        rebased_c.conflict_labels(),
+        Some(Merge::from_removes_adds(
+            vec![r#"parents of rebased commit "a""#.to_owned()],
+            vec![
+                "rebase destination".to_owned(),
+                r#"rebased commit "c""#.to_owned(),
+            ],
+        ))
+    );
+
+    // A resolved commit has no labels
+    let resolved_c = rebase_commit(&settings, tx.mut_repo(), &rebased_c, &[commit_a]).unwrap();
+    assert!(!resolved_c.has_conflict().unwrap());
+    assert_eq!(resolved_c.conflict_labels(), None);
+}
@@ -0,0 +1,75 @@
+// Copyright 2024 The Jujutsu Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use jj_lib::op_store::RefTarget;
+use jj_lib::repo::Repo;
+use jj_lib::repo_path::RepoPath;
+use jj_lib::status::{RepoStatus, StatusLevel};
+use testutils::{create_random_commit, create_tree, write_random_commit, TestRepo};
+
+#[test]
+fn test_repo_status_level() {
+    let settings = testutils::user_settings();
+    let test_repo = TestRepo::init();
+    let repo = &test_repo.repo;
+    let root_commit_id = repo.store().root_commit_id().clone();
+    let path = RepoPath::from_internal_string("file");
+    let base_tree = create_tree(repo, &[(path, "base\n")]);
+    let left_tree = create_tree(repo, &[(path, "left\n")]);
+    let right_tree = create_tree(repo, &[(path, "right\n")]);
+    let conflict_tree = left_tree.merge(&base_tree, &right_tree).unwrap();
+
+    let mut tx = repo.start_transaction(&settings);
+    let mut_repo = tx.mut_repo();
+    let mut new_commit = |parent_id, tree_id| {
+        mut_repo
+            .new_commit(&settings, vec![parent_id], tree_id)
+            .write()
+            .unwrap()
+    };
+    let base = new_commit(root_commit_id, base_tree.id());
+    let clean = new_commit(base.id().clone(), base_tree.id());
+    let changed = new_commit(base.id().clone(), left_tree.id());
+    let conflicted = new_commit(base.id().clone(), conflict_tree.id());
+    let level = |repo: &dyn Repo, commit| RepoStatus::gather(repo, commit).unwrap().level();
+    assert_eq!(level(tx.repo(), None), StatusLevel::Clean);
+    assert_eq!(level(tx.repo(), Some(clean.clone())), StatusLevel::Clean);
+    assert_eq!(
+        level(tx.repo(), Some(changed.clone())),
+        StatusLevel::Changes
+    );
+    assert_eq!(
+        level(tx.repo(), Some(conflicted.clone())),
+        StatusLevel::Conflicts
+    );
+
+    // Divergence takes precedence over conflicts
+    let divergent = create_random_commit(tx.mut_repo(), &settings)
+        .set_change_id(conflicted.change_id().clone())
+        .write()
+        .unwrap();
+    let status = RepoStatus::gather(tx.repo(), Some(divergent)).unwrap();
+    assert!(status.wc_commit.as_ref().unwrap().is_divergent);
+    assert_eq!(status.level(), StatusLevel::RepoProblems);
+
+    // Conflicted branches are a repo problem even if the working copy is clean
+    let other = write_random_commit(tx.mut_repo(), &settings);
+    tx.mut_repo().set_local_branch_target(
+        "main",
+        RefTarget::from_legacy_form([], [clean.id().clone(), other.id().clone()]),
+    );
+    let status = RepoStatus::gather(tx.repo(), Some(clean)).unwrap();
+    assert_eq!(status.conflicted_local_branches, vec!["main".to_owned()]);
+    assert_eq!(status.level(), StatusLevel::RepoProblems);
+}
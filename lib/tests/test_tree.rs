@@ -0,0 +1,269 @@
+// Copyright 2024 The Jujutsu Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::sync::Arc;
+
+use assert_matches::assert_matches;
+use jj_lib::backend::{self, Backend, BackendError, FileId, MergedTreeId, TreeId, TreeValue};
+use jj_lib::matchers::{EverythingMatcher, PrefixMatcher};
+use jj_lib::merged_tree::MergedTree;
+use jj_lib::object_id::ObjectId;
+use jj_lib::repo::{ReadonlyRepo, Repo};
+use jj_lib::repo_path::{RepoPath, RepoPathBuf, RepoPathComponent};
+use jj_lib::settings::UserSettings;
+use jj_lib::tree::{Tree, TreeEntriesMode};
+use testutils::test_backend::TestBackend;
+use testutils::{create_single_tree, write_file, TestRepo};
+
+/// Writes a tree bypassing the store, which would reject unnormalized trees.
+fn write_unnormalized_tree(repo: &Arc<ReadonlyRepo>, dir: &RepoPath, data: backend::Tree) -> Tree {
+    let store = repo.store();
+    let backend = store.backend_impl().downcast_ref::<TestBackend>().unwrap();
+    let id = backend.write_tree(dir, &data).unwrap();
+    store.get_tree(dir, &id).unwrap()
+}
+
+#[test]
+#[cfg(debug_assertions)]
+#[should_panic(expected = "has an entry pointing at the empty tree")]
+fn test_write_unnormalized_tree_panics() {
+    let test_repo = TestRepo::init();
+    let store = test_repo.repo.store();
+
+    let mut tree_data = backend::Tree::default();
+    tree_data.set(
+        "empty".into(),
+        TreeValue::Tree(store.empty_tree_id().clone()),
+    );
+    let _ = store.write_tree(RepoPath::root(), tree_data);
+}
+
+#[test]
+fn test_normalize_tree() {
+    let test_repo = TestRepo::init();
+    let repo = &test_repo.repo;
+    let store = repo.store();
+
+    // "dir" contains only an empty directory, so it should disappear too
+    let file_path = RepoPath::from_internal_string("file");
+    let file_id = write_file(store, file_path, "contents");
+    let mut dir_data = backend::Tree::default();
+    dir_data.set(
+        "empty".into(),
+        TreeValue::Tree(store.empty_tree_id().clone()),
+    );
+    let dir_tree = write_unnormalized_tree(repo, RepoPath::from_internal_string("dir"), dir_data);
+    let mut root_data = backend::Tree::default();
+    root_data.set(
+        "file".into(),
+        TreeValue::File {
+            id: file_id,
+            executable: false,
+        },
+    );
+    root_data.set("dir".into(), TreeValue::Tree(dir_tree.id().clone()));
+    let unnormalized_tree = write_unnormalized_tree(repo, RepoPath::root(), root_data);
+
+    let expected_tree = create_single_tree(repo, &[(file_path, "contents")]);
+    let normalized_tree = unnormalized_tree.normalize().unwrap();
+    assert_eq!(normalized_tree.id(), expected_tree.id());
+    assert_ne!(unnormalized_tree.id(), expected_tree.id());
+    // Normalizing a normalized tree doesn't change it
+    assert_eq!(normalized_tree.normalize().unwrap(), normalized_tree);
+
+    // The trees differ only in their ids, not in their contents
+    let unnormalized_merged_tree = MergedTree::resolved(unnormalized_tree);
+    let normalized_merged_tree = unnormalized_merged_tree.normalize().unwrap();
+    assert_eq!(
+        normalized_merged_tree,
+        MergedTree::resolved(expected_tree.clone())
+    );
+    assert_eq!(
+        unnormalized_merged_tree
+            .diff(&normalized_merged_tree, &EverythingMatcher)
+            .count(),
+        0
+    );
+}
+
+fn entry_paths_and_kinds(
+    entries: impl Iterator<Item = (RepoPathBuf, TreeValue)>,
+) -> Vec<(String, &'static str)> {
+    entries
+        .map(|(path, value)| {
+            let kind = match value {
+                TreeValue::Tree(_) => "dir",
+                _ => "file",
+            };
+            (path.as_internal_file_string().to_owned(), kind)
+        })
+        .collect()
+}
+
+#[test]
+fn test_entries_recursive_with_dirs() {
+    let test_repo = TestRepo::init();
+    let repo = &test_repo.repo;
+
+    let tree = create_single_tree(
+        repo,
+        &[
+            (RepoPath::from_internal_string("a"), "a"),
+            (RepoPath::from_internal_string("dir1/b"), "b"),
+            (RepoPath::from_internal_string("dir1/sub/c"), "c"),
+            (RepoPath::from_internal_string("dir2/d"), "d"),
+        ],
+    );
+
+    // Directories come before their contents
+    assert_eq!(
+        entry_paths_and_kinds(tree.entries_recursive_with_dirs()),
+        vec![
+            ("a".to_owned(), "file"),
+            ("dir1".to_owned(), "dir"),
+            ("dir1/b".to_owned(), "file"),
+            ("dir1/sub".to_owned(), "dir"),
+            ("dir1/sub/c".to_owned(), "file"),
+            ("dir2".to_owned(), "dir"),
+            ("dir2/d".to_owned(), "file"),
+        ]
+    );
+    // The yielded tree ids are the ids of the subtrees
+    let (_, sub_value) = tree
+        .entries_recursive_with_dirs()
+        .find(|(path, _)| path.as_internal_file_string() == "dir1/sub")
+        .unwrap();
+    let sub_tree = tree
+        .sub_tree(RepoPathComponent::new("dir1"))
+        .unwrap()
+        .sub_tree(RepoPathComponent::new("sub"))
+        .unwrap();
+    assert_eq!(sub_value, TreeValue::Tree(sub_tree.id().clone()));
+
+    // The file-only mode is unchanged
+    assert_eq!(
+        entry_paths_and_kinds(
+            tree.entries_matching_with_mode(&EverythingMatcher, TreeEntriesMode::FilesOnly)
+        ),
+        entry_paths_and_kinds(tree.entries()),
+    );
+
+    // Directories outside the matcher are neither yielded nor walked
+    let matcher = PrefixMatcher::new([RepoPath::from_internal_string("dir1")]);
+    assert_eq!(
+        entry_paths_and_kinds(tree.entries_matching_with_mode(&matcher, TreeEntriesMode::WithDirs)),
+        vec![
+            ("dir1".to_owned(), "dir"),
+            ("dir1/b".to_owned(), "file"),
+            ("dir1/sub".to_owned(), "dir"),
+            ("dir1/sub/c".to_owned(), "file"),
+        ]
+    );
+}
+
+#[test]
+fn test_entries_recursive_with_dirs_empty_tree() {
+    let test_repo = TestRepo::init();
+    let store = test_repo.repo.store();
+
+    let tree = store
+        .get_tree(RepoPath::root(), store.empty_tree_id())
+        .unwrap();
+    assert_eq!(tree.entries_recursive_with_dirs().count(), 0);
+    assert_eq!(tree.entries().count(), 0);
+}
+
+#[test]
+fn test_write_dangling_tree() {
+    let dangling_id = FileId::new(vec![0xab; 10]);
+    let mut dangling_data = backend::Tree::default();
+    dangling_data.set(
+        "file".into(),
+        TreeValue::File {
+            id: dangling_id.clone(),
+            executable: false,
+        },
+    );
+
+    // In strict mode, the store refuses to write a tree referencing a file that
+    // was never written
+    let settings = store_validation_settings(true);
+    let test_repo = TestRepo::init_with_settings(&settings);
+    let store = test_repo.repo.store();
+    assert!(store.strict_validation());
+    let err = store
+        .write_tree(RepoPath::root(), dangling_data.clone())
+        .unwrap_err();
+    assert_matches!(
+        &err,
+        BackendError::DanglingReference { missing_hash, .. } if *missing_hash == dangling_id.hex()
+    );
+    assert_eq!(
+        err.to_string(),
+        format!(
+            r#"Refusing to write tree at "": it references file {}, which doesn't exist"#,
+            dangling_id.hex()
+        )
+    );
+    // Nor a commit referencing a tree that was never written
+    let mut tx = test_repo.repo.start_transaction(&settings);
+    let err = tx
+        .mut_repo()
+        .new_commit(
+            &settings,
+            vec![store.root_commit_id().clone()],
+            MergedTreeId::resolved(TreeId::new(vec![0xcd; 10])),
+        )
+        .write()
+        .unwrap_err();
+    assert_matches!(err, BackendError::DanglingReference { .. });
+    // Once the file exists, the tree can be written
+    let file_path = RepoPath::from_internal_string("file");
+    let file_id = write_file(store, file_path, "contents");
+    let mut data = backend::Tree::default();
+    data.set(
+        "file".into(),
+        TreeValue::File {
+            id: file_id,
+            executable: false,
+        },
+    );
+    assert!(store.write_tree(RepoPath::root(), data).is_ok());
+
+    // Without strict mode, the dangling tree is written and the error only shows
+    // up when reading the file
+    let settings = store_validation_settings(false);
+    let test_repo = TestRepo::init_with_settings(&settings);
+    let store = test_repo.repo.store();
+    assert!(!store.strict_validation());
+    let tree = store.write_tree(RepoPath::root(), dangling_data).unwrap();
+    let TreeValue::File { id, .. } = tree.value(RepoPathComponent::new("file")).unwrap() else {
+        panic!("expected a file entry");
+    };
+    assert_matches!(
+        store.read_file(file_path, id).err(),
+        Some(BackendError::ObjectNotFound { .. })
+    );
+}
+
+fn store_validation_settings(strict: bool) -> UserSettings {
+    let config = testutils::base_config()
+        .add_source(config::File::from_str(
+            &format!("debug.strict-store-validation = {strict}"),
+            config::FileFormat::Toml,
+        ))
+        .build()
+        .unwrap();
+    UserSettings::from_config(config)
+}
@@ -14,8 +14,10 @@
 
 use std::collections::BTreeMap;
 
+use itertools::Itertools;
 use jj_lib::op_store::{BranchTarget, RefTarget, RemoteRef, RemoteRefState, WorkspaceId};
 use jj_lib::repo::Repo;
+use jj_lib::view::BranchConflictCandidates;
 use maplit::{btreemap, hashset};
 use test_case::test_case;
 use testutils::{
@@ -44,7 +46,7 @@ fn test_heads_fork() {
     let initial = graph_builder.initial_commit();
     let child1 = graph_builder.commit_with_parents(&[&initial]);
     let child2 = graph_builder.commit_with_parents(&[&initial]);
-    let repo = tx.commit("test");
+    let repo = tx.commit("test").unwrap();
 
     assert_eq!(
         *repo.view().heads(),
@@ -67,11 +69,85 @@ fn test_heads_merge() {
     let child1 = graph_builder.commit_with_parents(&[&initial]);
     let child2 = graph_builder.commit_with_parents(&[&initial]);
     let merge = graph_builder.commit_with_parents(&[&child1, &child2]);
-    let repo = tx.commit("test");
+    let repo = tx.commit("test").unwrap();
 
     assert_eq!(*repo.view().heads(), hashset! {merge.id().clone()});
 }
 
+#[test]
+fn test_view_diff() {
+    let settings = testutils::user_settings();
+    let test_repo = TestRepo::init();
+    let repo = &test_repo.repo;
+
+    let mut tx = repo.start_transaction(&settings);
+    let mut_repo = tx.mut_repo();
+    let commit1 = write_random_commit(mut_repo, &settings);
+    let commit2 = write_random_commit(mut_repo, &settings);
+    mut_repo.set_local_branch_target("moved", RefTarget::normal(commit1.id().clone()));
+    mut_repo.set_local_branch_target("removed", RefTarget::normal(commit1.id().clone()));
+    mut_repo.set_local_branch_target("unchanged", RefTarget::normal(commit1.id().clone()));
+    mut_repo.set_tag_target("tag", RefTarget::normal(commit1.id().clone()));
+    let ws_id = WorkspaceId::default();
+    mut_repo
+        .set_wc_commit(ws_id.clone(), commit1.id().clone())
+        .unwrap();
+    let repo1 = tx.commit("test").unwrap();
+
+    let mut tx = repo1.start_transaction(&settings);
+    let mut_repo = tx.mut_repo();
+    let commit3 = create_random_commit(mut_repo, &settings)
+        .set_parents(vec![commit2.id().clone()])
+        .write()
+        .unwrap();
+    mut_repo.set_local_branch_target("added", RefTarget::normal(commit3.id().clone()));
+    mut_repo.set_local_branch_target("moved", RefTarget::normal(commit3.id().clone()));
+    mut_repo.set_local_branch_target("removed", RefTarget::absent());
+    let remote_ref = RemoteRef {
+        target: RefTarget::normal(commit2.id().clone()),
+        state: RemoteRefState::Tracking,
+    };
+    mut_repo.set_remote_branch("moved", "origin", remote_ref.clone());
+    mut_repo.set_tag_target("tag", RefTarget::absent());
+    mut_repo.set_git_ref_target("refs/heads/moved", RefTarget::normal(commit3.id().clone()));
+    mut_repo
+        .set_wc_commit(ws_id.clone(), commit3.id().clone())
+        .unwrap();
+    let repo2 = tx.commit("test").unwrap();
+
+    let diff = repo1.view().diff(repo2.view());
+    assert_eq!(
+        diff.wc_commits,
+        btreemap! { &ws_id => (Some(commit1.id()), Some(commit3.id())) }
+    );
+    assert_eq!(diff.added_heads, vec![commit3.id()]);
+    assert_eq!(diff.removed_heads, vec![commit2.id()]);
+    let absent = RefTarget::absent();
+    let target1 = RefTarget::normal(commit1.id().clone());
+    let target3 = RefTarget::normal(commit3.id().clone());
+    assert_eq!(
+        diff.local_branches,
+        vec![
+            ("added", (&absent, &target3)),
+            ("moved", (&target1, &target3)),
+            ("removed", (&target1, &absent)),
+        ]
+    );
+    assert_eq!(
+        diff.remote_branches,
+        vec![(("moved", "origin"), (RemoteRef::absent_ref(), &remote_ref))]
+    );
+    assert_eq!(diff.tags, vec![("tag", (&target1, &absent))]);
+    assert_eq!(
+        diff.git_refs,
+        vec![("refs/heads/moved", (&absent, &target3))]
+    );
+    assert_eq!(diff.git_head, None);
+    assert!(!diff.is_empty());
+
+    assert!(repo2.view().diff(repo2.view()).is_empty());
+}
+
 #[test]
 fn test_merge_views_heads() {
     // Tests merging of the view's heads (by performing concurrent operations).
@@ -84,7 +160,7 @@ fn test_merge_views_heads() {
     let head_unchanged = write_random_commit(mut_repo, &settings);
     let head_remove_tx1 = write_random_commit(mut_repo, &settings);
     let head_remove_tx2 = write_random_commit(mut_repo, &settings);
-    let repo = tx.commit("test");
+    let repo = tx.commit("test").unwrap();
 
     let mut tx1 = repo.start_transaction(&settings);
     tx1.mut_repo().remove_head(head_remove_tx1.id());
@@ -149,7 +225,7 @@ fn test_merge_views_checkout() {
         .mut_repo()
         .set_wc_commit(ws5_id.clone(), commit1.id().clone())
         .unwrap();
-    let repo = initial_tx.commit("test");
+    let repo = initial_tx.commit("test").unwrap();
 
     let mut tx1 = repo.start_transaction(&settings);
     tx1.mut_repo()
@@ -230,7 +306,7 @@ fn test_merge_views_branches() {
         "feature",
         RefTarget::normal(feature_branch_local_tx0.id().clone()),
     );
-    let repo = tx.commit("test");
+    let repo = tx.commit("test").unwrap();
 
     let mut tx1 = repo.start_transaction(&settings);
     let main_branch_local_tx1 = write_random_commit(tx1.mut_repo(), &settings);
@@ -286,6 +362,64 @@ fn test_merge_views_branches() {
     );
 }
 
+#[test]
+fn test_branch_conflict_candidates() {
+    let settings = testutils::user_settings();
+    let test_repo = TestRepo::init();
+    let repo = &test_repo.repo;
+
+    let mut tx = repo.start_transaction(&settings);
+    let mut_repo = tx.mut_repo();
+    let commit1 = write_random_commit(mut_repo, &settings);
+    let commit2 = write_random_commit(mut_repo, &settings);
+    let commit3 = write_random_commit(mut_repo, &settings);
+    mut_repo.set_local_branch_target("normal", RefTarget::normal(commit1.id().clone()));
+    // The candidates are sorted regardless of the order in the target
+    mut_repo.set_local_branch_target(
+        "conflicted",
+        RefTarget::from_legacy_form(
+            [commit1.id().clone()],
+            [commit3.id().clone(), commit2.id().clone()],
+        ),
+    );
+
+    assert_eq!(
+        mut_repo
+            .view()
+            .conflicted_local_branches()
+            .map(|(name, _)| name)
+            .collect_vec(),
+        vec!["conflicted"]
+    );
+    assert_eq!(
+        mut_repo
+            .view()
+            .local_branch_conflict_candidates("normal", mut_repo.index()),
+        None
+    );
+    assert_eq!(
+        mut_repo
+            .view()
+            .local_branch_conflict_candidates("conflicted", mut_repo.index()),
+        Some(BranchConflictCandidates {
+            removed_ids: vec![commit1.id().clone()],
+            added_ids: vec![commit2.id().clone(), commit3.id().clone()],
+        })
+    );
+
+    assert!(!mut_repo.resolve_branch_conflict("normal", commit2.id().clone()));
+    assert_eq!(
+        mut_repo.get_local_branch("normal"),
+        RefTarget::normal(commit1.id().clone())
+    );
+    assert!(mut_repo.resolve_branch_conflict("conflicted", commit3.id().clone()));
+    assert_eq!(
+        mut_repo.get_local_branch("conflicted"),
+        RefTarget::normal(commit3.id().clone())
+    );
+    assert_eq!(mut_repo.view().conflicted_local_branches().count(), 0);
+}
+
 #[test]
 fn test_merge_views_tags() {
     // Tests merging of tags (by performing concurrent operations). See
@@ -300,7 +434,7 @@ fn test_merge_views_tags() {
     mut_repo.set_tag_target("v1.0", RefTarget::normal(v1_tx0.id().clone()));
     let v2_tx0 = write_random_commit(mut_repo, &settings);
     mut_repo.set_tag_target("v2.0", RefTarget::normal(v2_tx0.id().clone()));
-    let repo = tx.commit("test");
+    let repo = tx.commit("test").unwrap();
 
     let mut tx1 = repo.start_transaction(&settings);
     let v1_tx1 = write_random_commit(tx1.mut_repo(), &settings);
@@ -350,7 +484,7 @@ fn test_merge_views_git_refs() {
         "refs/heads/feature",
         RefTarget::normal(feature_branch_tx0.id().clone()),
     );
-    let repo = tx.commit("test");
+    let repo = tx.commit("test").unwrap();
 
     let mut tx1 = repo.start_transaction(&settings);
     let main_branch_tx1 = write_random_commit(tx1.mut_repo(), &settings);
@@ -398,7 +532,7 @@ fn test_merge_views_git_heads() {
     let tx0_head = write_random_commit(tx0.mut_repo(), &settings);
     tx0.mut_repo()
         .set_git_head_target(RefTarget::normal(tx0_head.id().clone()));
-    let repo = tx0.commit("test");
+    let repo = tx0.commit("test").unwrap();
 
     let mut tx1 = repo.start_transaction(&settings);
     let tx1_head = write_random_commit(tx1.mut_repo(), &settings);
@@ -427,7 +561,7 @@ fn test_merge_views_divergent() {
 
     let mut tx = test_repo.repo.start_transaction(&settings);
     let commit_a = write_random_commit(tx.mut_repo(), &settings);
-    let repo = tx.commit("test");
+    let repo = tx.commit("test").unwrap();
 
     let mut tx1 = repo.start_transaction(&settings);
     let commit_a2 = tx1
@@ -466,7 +600,7 @@ fn test_merge_views_child_on_rewritten(child_first: bool) {
 
     let mut tx = test_repo.repo.start_transaction(&settings);
     let commit_a = write_random_commit(tx.mut_repo(), &settings);
-    let repo = tx.commit("test");
+    let repo = tx.commit("test").unwrap();
 
     let mut tx1 = repo.start_transaction(&settings);
     let commit_b = create_random_commit(tx1.mut_repo(), &settings)
@@ -516,7 +650,7 @@ fn test_merge_views_child_on_rewritten_divergent(on_rewritten: bool, child_first
         .set_change_id(commit_a2.change_id().clone())
         .write()
         .unwrap();
-    let repo = tx.commit("test");
+    let repo = tx.commit("test").unwrap();
 
     let mut tx1 = repo.start_transaction(&settings);
     let parent = if on_rewritten { &commit_a2 } else { &commit_a3 };
@@ -572,7 +706,7 @@ fn test_merge_views_child_on_abandoned(child_first: bool) {
         .set_parents(vec![commit_a.id().clone()])
         .write()
         .unwrap();
-    let repo = tx.commit("test");
+    let repo = tx.commit("test").unwrap();
 
     let mut tx1 = repo.start_transaction(&settings);
     let commit_c = create_random_commit(tx1.mut_repo(), &settings)
@@ -0,0 +1,236 @@
+// Copyright 2024 The Jujutsu Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::any::Any;
+use std::collections::HashMap;
+use std::fmt::{Debug, Error, Formatter};
+use std::io::Read;
+use std::sync::{Arc, Mutex};
+use std::time::SystemTime;
+
+use async_trait::async_trait;
+use jj_lib::backend::{
+    Backend, BackendResult, ChangeId, Commit, CommitId, Conflict, ConflictId, FileId, ObjectKind,
+    SigningFn, SymlinkId, Tree, TreeId,
+};
+use jj_lib::index::Index;
+use jj_lib::merge_drivers::MergeDrivers;
+use jj_lib::object_id::HexPrefix;
+use jj_lib::repo_path::RepoPath;
+use jj_lib::signing::Signer;
+use jj_lib::store::Store;
+
+/// The kind of a call made to a `Backend`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum BackendCall {
+    ReadFile,
+    WriteFile,
+    ReadSymlink,
+    WriteSymlink,
+    ReadTree,
+    WriteTree,
+    ReadConflict,
+    WriteConflict,
+    ReadCommit,
+    WriteCommit,
+}
+
+/// Counts of calls made to an `InstrumentedBackend`, shared between the
+/// backend and the test or benchmark observing it.
+#[derive(Debug, Default)]
+pub struct BackendCallCounts {
+    counts: Mutex<HashMap<BackendCall, usize>>,
+}
+
+impl BackendCallCounts {
+    /// Number of calls of the given kind since creation or the last `reset()`.
+    pub fn get(&self, call: BackendCall) -> usize {
+        self.counts.lock().unwrap().get(&call).copied().unwrap_or(0)
+    }
+
+    /// Total number of calls of any kind.
+    pub fn total(&self) -> usize {
+        self.counts.lock().unwrap().values().sum()
+    }
+
+    pub fn reset(&self) {
+        self.counts.lock().unwrap().clear();
+    }
+
+    fn record(&self, call: BackendCall) {
+        *self.counts.lock().unwrap().entry(call).or_default() += 1;
+    }
+}
+
+/// A backend that forwards all calls to another backend and counts them by
+/// kind. Useful for asserting algorithmic properties such as "diffing two
+/// identical trees reads no files".
+pub struct InstrumentedBackend {
+    inner: Box<dyn Backend>,
+    counts: Arc<BackendCallCounts>,
+}
+
+impl InstrumentedBackend {
+    pub fn new(inner: Box<dyn Backend>) -> Self {
+        Self::with_counts(inner, Arc::new(BackendCallCounts::default()))
+    }
+
+    /// Creates a backend recording its calls into the given (possibly shared)
+    /// counts.
+    pub fn with_counts(inner: Box<dyn Backend>, counts: Arc<BackendCallCounts>) -> Self {
+        InstrumentedBackend { inner, counts }
+    }
+
+    pub fn counts(&self) -> &Arc<BackendCallCounts> {
+        &self.counts
+    }
+
+    /// Wraps this backend in a new `Store` (with an empty cache) and returns
+    /// it along with the call counts.
+    pub fn into_store(
+        self,
+        use_tree_conflict_format: bool,
+    ) -> (Arc<Store>, Arc<BackendCallCounts>) {
+        let counts = self.counts.clone();
+        let store = Store::new(
+            Box::new(self),
+            Signer::new(None, vec![]),
+            use_tree_conflict_format,
+            false,
+            MergeDrivers::default(),
+        );
+        (store, counts)
+    }
+}
+
+impl Debug for InstrumentedBackend {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), Error> {
+        f.debug_struct("InstrumentedBackend")
+            .field("inner", &self.inner)
+            .finish_non_exhaustive()
+    }
+}
+
+#[async_trait]
+impl Backend for InstrumentedBackend {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn name(&self) -> &str {
+        self.inner.name()
+    }
+
+    fn commit_id_length(&self) -> usize {
+        self.inner.commit_id_length()
+    }
+
+    fn change_id_length(&self) -> usize {
+        self.inner.change_id_length()
+    }
+
+    fn root_commit_id(&self) -> &CommitId {
+        self.inner.root_commit_id()
+    }
+
+    fn root_change_id(&self) -> &ChangeId {
+        self.inner.root_change_id()
+    }
+
+    fn empty_tree_id(&self) -> &TreeId {
+        self.inner.empty_tree_id()
+    }
+
+    fn concurrency(&self) -> usize {
+        self.inner.concurrency()
+    }
+
+    async fn read_file(&self, path: &RepoPath, id: &FileId) -> BackendResult<Box<dyn Read>> {
+        self.counts.record(BackendCall::ReadFile);
+        self.inner.read_file(path, id).await
+    }
+
+    fn write_file(&self, path: &RepoPath, contents: &mut dyn Read) -> BackendResult<FileId> {
+        self.counts.record(BackendCall::WriteFile);
+        self.inner.write_file(path, contents)
+    }
+
+    async fn read_symlink(&self, path: &RepoPath, id: &SymlinkId) -> BackendResult<String> {
+        self.counts.record(BackendCall::ReadSymlink);
+        self.inner.read_symlink(path, id).await
+    }
+
+    fn write_symlink(&self, path: &RepoPath, target: &str) -> BackendResult<SymlinkId> {
+        self.counts.record(BackendCall::WriteSymlink);
+        self.inner.write_symlink(path, target)
+    }
+
+    async fn read_tree(&self, path: &RepoPath, id: &TreeId) -> BackendResult<Tree> {
+        self.counts.record(BackendCall::ReadTree);
+        self.inner.read_tree(path, id).await
+    }
+
+    fn write_tree(&self, path: &RepoPath, contents: &Tree) -> BackendResult<TreeId> {
+        self.counts.record(BackendCall::WriteTree);
+        self.inner.write_tree(path, contents)
+    }
+
+    fn read_conflict(&self, path: &RepoPath, id: &ConflictId) -> BackendResult<Conflict> {
+        self.counts.record(BackendCall::ReadConflict);
+        self.inner.read_conflict(path, id)
+    }
+
+    fn write_conflict(&self, path: &RepoPath, contents: &Conflict) -> BackendResult<ConflictId> {
+        self.counts.record(BackendCall::WriteConflict);
+        self.inner.write_conflict(path, contents)
+    }
+
+    async fn read_commit(&self, id: &CommitId) -> BackendResult<Commit> {
+        self.counts.record(BackendCall::ReadCommit);
+        self.inner.read_commit(id).await
+    }
+
+    fn write_commit(
+        &self,
+        contents: Commit,
+        sign_with: Option<&mut SigningFn>,
+    ) -> BackendResult<(CommitId, Commit)> {
+        self.counts.record(BackendCall::WriteCommit);
+        self.inner.write_commit(contents, sign_with)
+    }
+
+    fn contains(&self, kind: ObjectKind, id: &[u8]) -> BackendResult<bool> {
+        self.inner.contains(kind, id)
+    }
+
+    fn gc(&self, index: &dyn Index, keep_newer: SystemTime) -> BackendResult<()> {
+        self.inner.gc(index, keep_newer)
+    }
+
+    fn keep_commits(&self, ids: &[CommitId]) -> BackendResult<()> {
+        self.inner.keep_commits(ids)
+    }
+
+    fn find_objects_by_prefix(
+        &self,
+        prefix: &HexPrefix,
+        kind: Option<ObjectKind>,
+    ) -> BackendResult<Vec<(ObjectKind, Vec<u8>)>> {
+        self.inner.find_objects_by_prefix(prefix, kind)
+    }
+
+    fn read_raw_object(&self, kind: ObjectKind, id: &[u8]) -> BackendResult<Vec<u8>> {
+        self.inner.read_raw_object(kind, id)
+    }
+}
@@ -12,10 +12,11 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 use std::env;
 use std::fs::{self, OpenOptions};
 use std::io::{Read, Write};
+use std::iter;
 use std::path::{Path, PathBuf};
 use std::sync::{Arc, Once};
 
@@ -30,7 +31,9 @@ use jj_lib::git_backend::GitBackend;
 use jj_lib::local_backend::LocalBackend;
 use jj_lib::merged_tree::MergedTree;
 use jj_lib::object_id::ObjectId;
-use jj_lib::repo::{MutableRepo, ReadonlyRepo, Repo, RepoLoader, StoreFactories};
+use jj_lib::repo::{
+    BackendInitializer, MutableRepo, ReadonlyRepo, Repo, RepoLoader, StoreFactories,
+};
 use jj_lib::repo_path::{RepoPath, RepoPathBuf};
 use jj_lib::settings::UserSettings;
 use jj_lib::signing::Signer;
@@ -42,9 +45,12 @@ use jj_lib::working_copy::{SnapshotError, SnapshotOptions};
 use jj_lib::workspace::Workspace;
 use tempfile::TempDir;
 
+use crate::instrumented_backend::{BackendCallCounts, InstrumentedBackend};
 use crate::test_backend::TestBackend;
 
+pub mod instrumented_backend;
 pub mod test_backend;
+pub mod test_repo_builder;
 pub mod test_signing_backend;
 
 pub fn hermetic_libgit2() {
@@ -166,6 +172,15 @@ impl TestRepo {
         }
     }
 
+    /// Returns a new store reading from this repo's backend, with an empty
+    /// cache and all backend calls counted. Only supported for
+    /// `TestRepoBackend::Test`.
+    pub fn instrumented_store(&self) -> (Arc<Store>, Arc<BackendCallCounts>) {
+        let store_path = self.repo.repo_path().join("store");
+        let backend = InstrumentedBackend::new(Box::new(TestBackend::load(&store_path)));
+        backend.into_store(self.repo.store().use_tree_conflict_format())
+    }
+
     pub fn default_store_factories() -> StoreFactories {
         let mut factories = StoreFactories::default();
         factories.add_backend(
@@ -200,19 +215,45 @@ impl TestWorkspace {
         settings: &UserSettings,
         backend: TestRepoBackend,
         signer: Signer,
+    ) -> Self {
+        Self::init_with_backend_initializer(
+            settings,
+            &move |settings, store_path| backend.init_backend(settings, store_path),
+            signer,
+        )
+    }
+
+    /// Initializes a workspace backed by the test backend, with all calls to
+    /// the backend (including those made by the working copy) counted.
+    pub fn init_instrumented(settings: &UserSettings) -> (Self, Arc<BackendCallCounts>) {
+        let counts = Arc::new(BackendCallCounts::default());
+        let backend_counts = counts.clone();
+        let test_workspace = Self::init_with_backend_initializer(
+            settings,
+            &move |_settings, store_path| {
+                Ok(Box::new(InstrumentedBackend::with_counts(
+                    Box::new(TestBackend::init(store_path)),
+                    backend_counts.clone(),
+                )))
+            },
+            Signer::from_settings(settings).unwrap(),
+        );
+        (test_workspace, counts)
+    }
+
+    fn init_with_backend_initializer(
+        settings: &UserSettings,
+        backend_initializer: &BackendInitializer,
+        signer: Signer,
     ) -> Self {
         let temp_dir = new_temp_dir();
 
         let workspace_root = temp_dir.path().join("repo");
         fs::create_dir(&workspace_root).unwrap();
 
-        let (workspace, repo) = Workspace::init_with_backend(
-            settings,
-            &workspace_root,
-            &move |settings, store_path| backend.init_backend(settings, store_path),
-            signer,
-        )
-        .unwrap();
+        let (workspace, repo) =
+            Workspace::init_with_backend(settings, &workspace_root, backend_initializer, signer)
+                .unwrap();
 
         Self {
             temp_dir,
@@ -252,7 +293,7 @@ pub fn commit_transactions(settings: &UserSettings, txs: Vec<Transaction>) -> Ar
     let repo_loader = txs[0].base_repo().loader();
     let mut op_ids = vec![];
     for tx in txs {
-        op_ids.push(tx.commit("test").op_id().clone());
+        op_ids.push(tx.commit("test").unwrap().op_id().clone());
         std::thread::sleep(std::time::Duration::from_millis(1));
     }
     let repo = repo_loader.load_at_head(settings).unwrap();
@@ -314,14 +355,75 @@ pub fn create_single_tree(repo: &Arc<ReadonlyRepo>, path_contents: &[(&RepoPath,
     for (path, contents) in path_contents {
         write_normal_file(&mut tree_builder, path, contents);
     }
-    let id = tree_builder.write_tree();
+    let id = tree_builder.write_tree().unwrap();
     store.get_tree(RepoPath::root(), &id).unwrap()
 }
 
+/// Like `create_single_tree()`, but writes the trees directly to the store
+/// instead of using a `TreeBuilder`, so paths the tree builder rejects, such as
+/// paths under `.jj`, can be written like other tools might.
+pub fn create_single_tree_unchecked(
+    repo: &Arc<ReadonlyRepo>,
+    path_contents: &[(&RepoPath, &str)],
+) -> Tree {
+    let store = repo.store();
+    let mut trees: BTreeMap<RepoPathBuf, backend::Tree> = BTreeMap::new();
+    for (path, contents) in path_contents {
+        let id = write_file(store, path, contents);
+        for dir in iter::successors(path.parent(), |dir| dir.parent()) {
+            trees.entry(dir.to_owned()).or_default();
+        }
+        let (dir, name) = path.split().unwrap();
+        let value = TreeValue::File {
+            id,
+            executable: false,
+        };
+        trees.get_mut(dir).unwrap().set(name.to_owned(), value);
+    }
+    trees.entry(RepoPathBuf::root()).or_default();
+    // Children sort after their parents, so they're written first
+    while let Some((dir, data)) = trees.pop_last() {
+        let tree = store.write_tree(&dir, data).unwrap();
+        let Some((parent, name)) = dir.split() else {
+            return tree;
+        };
+        let value = TreeValue::Tree(tree.id().clone());
+        trees.get_mut(parent).unwrap().set(name.to_owned(), value);
+    }
+    unreachable!("the root tree must be written last");
+}
+
 pub fn create_tree(repo: &Arc<ReadonlyRepo>, path_contents: &[(&RepoPath, &str)]) -> MergedTree {
     MergedTree::legacy(create_single_tree(repo, path_contents))
 }
 
+/// Creates a tree with `num_dirs` directories containing `files_per_dir` files
+/// each. The file contents are derived from `seed`, so trees created with
+/// different seeds differ in every file while keeping the same structure.
+pub fn create_synthetic_tree(
+    repo: &Arc<ReadonlyRepo>,
+    num_dirs: usize,
+    files_per_dir: usize,
+    seed: &str,
+) -> MergedTree {
+    let paths = (0..num_dirs)
+        .flat_map(|dir| {
+            (0..files_per_dir)
+                .map(move |file| RepoPathBuf::from_internal_string(format!("dir{dir}/file{file}")))
+        })
+        .collect_vec();
+    let contents = paths
+        .iter()
+        .map(|path| format!("{seed} {path:?}\n"))
+        .collect_vec();
+    let path_contents: Vec<(&RepoPath, &str)> = paths
+        .iter()
+        .zip(&contents)
+        .map(|(path, contents)| (path.as_ref(), contents.as_str()))
+        .collect();
+    create_tree(repo, &path_contents)
+}
+
 #[must_use]
 pub fn create_random_tree(repo: &Arc<ReadonlyRepo>) -> MergedTreeId {
     let number = rand::random::<u32>();
@@ -362,6 +464,7 @@ pub fn commit_with_tree(store: &Arc<Store>, tree_id: MergedTreeId) -> Commit {
         author: signature.clone(),
         committer: signature,
         secure_sig: None,
+        metadata: BTreeMap::new(),
     };
     store.write_commit(commit, None).unwrap()
 }
@@ -23,7 +23,7 @@ use std::time::SystemTime;
 use async_trait::async_trait;
 use jj_lib::backend::{
     make_root_commit, Backend, BackendError, BackendResult, ChangeId, Commit, CommitId, Conflict,
-    ConflictId, FileId, SecureSig, SigningFn, SymlinkId, Tree, TreeId,
+    ConflictId, FileId, ObjectKind, SecureSig, SigningFn, SymlinkId, Tree, TreeId,
 };
 use jj_lib::index::Index;
 use jj_lib::object_id::ObjectId;
@@ -300,6 +300,34 @@ impl Backend for TestBackend {
         Ok((id, contents))
     }
 
+    fn contains(&self, kind: ObjectKind, id: &[u8]) -> BackendResult<bool> {
+        // Objects are stored per path, so the id may exist at any of them.
+        let data = self.locked_data();
+        let found = match kind {
+            ObjectKind::Commit => {
+                let id = CommitId::from_bytes(id);
+                id == self.root_commit_id || data.commits.contains_key(&id)
+            }
+            ObjectKind::Tree => {
+                let id = TreeId::from_bytes(id);
+                id == self.empty_tree_id || data.trees.values().any(|items| items.contains_key(&id))
+            }
+            ObjectKind::File => {
+                let id = FileId::from_bytes(id);
+                data.files.values().any(|items| items.contains_key(&id))
+            }
+            ObjectKind::Symlink => {
+                let id = SymlinkId::from_bytes(id);
+                data.symlinks.values().any(|items| items.contains_key(&id))
+            }
+            ObjectKind::Conflict => {
+                let id = ConflictId::from_bytes(id);
+                data.conflicts.values().any(|items| items.contains_key(&id))
+            }
+        };
+        Ok(found)
+    }
+
     fn gc(&self, _index: &dyn Index, _keep_newer: SystemTime) -> BackendResult<()> {
         Ok(())
     }
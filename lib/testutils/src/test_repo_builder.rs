@@ -0,0 +1,251 @@
+// Copyright 2024 The Jujutsu Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! A declarative builder for test repos with a given commit graph, tree
+//! contents and branches.
+//!
+//! ```ignore
+//! let fixture = TestRepoBuilder::new()
+//!     .commit("A")
+//!     .file("foo/bar.txt", "1\n")
+//!     .branch("main")
+//!     .commit_on("A", "B")
+//!     .file("foo/bar.txt", "2\n")
+//!     .commit_on("A", "C")
+//!     .file("foo/bar.txt", "3\n")
+//!     .commit_with_parents(&["B", "C"], "D") // conflicted merge
+//!     .build();
+//! let commit_d = fixture.commit("D");
+//! ```
+
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use itertools::Itertools;
+use jj_lib::backend::TreeValue;
+use jj_lib::commit::Commit;
+use jj_lib::merge::Merge;
+use jj_lib::merged_tree::MergedTreeBuilder;
+use jj_lib::op_store::RefTarget;
+use jj_lib::repo::{ReadonlyRepo, Repo};
+use jj_lib::repo_path::RepoPathBuf;
+use jj_lib::rewrite::merge_commit_trees;
+use jj_lib::settings::UserSettings;
+
+use crate::{base_config, write_file, TestRepo, TestRepoBackend};
+
+/// Timestamp used for all commits and operations created by `TestRepoBuilder`,
+/// so their ids don't depend on when the test runs.
+const TIMESTAMP: &str = "2001-02-03T04:05:06+07:00";
+
+#[derive(Clone, Debug)]
+struct CommitSpec {
+    name: String,
+    /// Names of the parent commits. Empty means the root commit.
+    parents: Vec<String>,
+    description: Option<String>,
+    /// `None` contents means the file is removed.
+    files: Vec<(RepoPathBuf, Option<String>)>,
+    branches: Vec<String>,
+}
+
+/// Builds a `TestRepo` from a description of its commits.
+///
+/// Commits are written in the order they're declared, so parents must be
+/// declared before their children. A commit starts out with the (merged) tree
+/// of its parents, and the methods `file()`, `remove_file()`, `description()`
+/// and `branch()` apply to the most recently declared commit. Merging parents
+/// with conflicting changes results in a commit with a conflicted tree.
+#[derive(Clone)]
+pub struct TestRepoBuilder {
+    backend: TestRepoBackend,
+    commits: Vec<CommitSpec>,
+}
+
+impl Default for TestRepoBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl TestRepoBuilder {
+    pub fn new() -> Self {
+        TestRepoBuilder {
+            backend: TestRepoBackend::Test,
+            commits: vec![],
+        }
+    }
+
+    /// Uses the given backend instead of `TestRepoBackend::Test`.
+    pub fn backend(mut self, backend: TestRepoBackend) -> Self {
+        self.backend = backend;
+        self
+    }
+
+    /// Declares a commit on top of the root commit.
+    pub fn commit(self, name: &str) -> Self {
+        self.commit_with_parents(&[], name)
+    }
+
+    /// Declares a commit on top of the commit named `parent`.
+    pub fn commit_on(self, parent: &str, name: &str) -> Self {
+        self.commit_with_parents(&[parent], name)
+    }
+
+    /// Declares a commit with the given parents. No parents means the root
+    /// commit.
+    pub fn commit_with_parents(mut self, parents: &[&str], name: &str) -> Self {
+        assert!(
+            self.commits.iter().all(|spec| spec.name != name),
+            "commit {name:?} is declared twice"
+        );
+        for parent in parents {
+            assert!(
+                self.commits.iter().any(|spec| spec.name == *parent),
+                "parent {parent:?} of {name:?} must be declared first"
+            );
+        }
+        self.commits.push(CommitSpec {
+            name: name.to_owned(),
+            parents: parents.iter().map(|&parent| parent.to_owned()).collect(),
+            description: None,
+            files: vec![],
+            branches: vec![],
+        });
+        self
+    }
+
+    /// Sets the contents of a file in the current commit.
+    pub fn file(mut self, path: &str, contents: &str) -> Self {
+        self.current_commit().files.push((
+            RepoPathBuf::from_internal_string(path),
+            Some(contents.to_owned()),
+        ));
+        self
+    }
+
+    /// Removes a file inherited from the parents of the current commit.
+    pub fn remove_file(mut self, path: &str) -> Self {
+        self.current_commit()
+            .files
+            .push((RepoPathBuf::from_internal_string(path), None));
+        self
+    }
+
+    /// Sets the description of the current commit. Defaults to its name.
+    pub fn description(mut self, description: &str) -> Self {
+        self.current_commit().description = Some(description.to_owned());
+        self
+    }
+
+    /// Points a local branch at the current commit.
+    pub fn branch(mut self, name: &str) -> Self {
+        self.current_commit().branches.push(name.to_owned());
+        self
+    }
+
+    fn current_commit(&mut self) -> &mut CommitSpec {
+        self.commits
+            .last_mut()
+            .expect("a commit must be declared first")
+    }
+
+    /// Settings with fixed commit and operation timestamps. Together with the
+    /// randomness seed from `base_config()`, this makes the commit, change and
+    /// operation ids of a built repo the same on every run.
+    pub fn settings() -> UserSettings {
+        let config = base_config()
+            .set_override("debug.commit-timestamp", TIMESTAMP)
+            .unwrap()
+            .set_override("debug.operation-timestamp", TIMESTAMP)
+            .unwrap()
+            .build()
+            .unwrap();
+        UserSettings::from_config(config)
+    }
+
+    /// Creates the repo and writes all declared commits and branches in a
+    /// single transaction.
+    pub fn build(self) -> TestRepoFixture {
+        let settings = Self::settings();
+        let test_repo = TestRepo::init_with_backend_and_settings(self.backend, &settings);
+        let mut tx = test_repo.repo.start_transaction(&settings);
+        let mut commits: HashMap<String, Commit> = HashMap::new();
+        for spec in self.commits {
+            let mut_repo = tx.mut_repo();
+            let store = mut_repo.store().clone();
+            let parents = if spec.parents.is_empty() {
+                vec![store.root_commit()]
+            } else {
+                spec.parents
+                    .iter()
+                    .map(|name| commits[name].clone())
+                    .collect_vec()
+            };
+            let parent_tree = merge_commit_trees(mut_repo, &parents).unwrap();
+            let mut tree_builder = MergedTreeBuilder::new(parent_tree.id());
+            for (path, contents) in &spec.files {
+                let value = match contents {
+                    Some(contents) => Merge::normal(TreeValue::File {
+                        id: write_file(&store, path, contents),
+                        executable: false,
+                    }),
+                    None => Merge::absent(),
+                };
+                tree_builder.set_or_remove(path.clone(), value);
+            }
+            let tree_id = tree_builder.write_tree(&store).unwrap();
+            let parent_ids = parents
+                .iter()
+                .map(|commit| commit.id().clone())
+                .collect_vec();
+            let commit = mut_repo
+                .new_commit(&settings, parent_ids, tree_id)
+                .set_description(spec.description.unwrap_or_else(|| spec.name.clone()))
+                .write()
+                .unwrap();
+            for branch in &spec.branches {
+                mut_repo.set_local_branch_target(branch, RefTarget::normal(commit.id().clone()));
+            }
+            commits.insert(spec.name, commit);
+        }
+        let repo = tx.commit("build test repo").unwrap();
+        TestRepoFixture {
+            test_repo: TestRepo { repo, ..test_repo },
+            settings,
+            commits,
+        }
+    }
+}
+
+/// A repo built by `TestRepoBuilder`, along with its commits by name.
+pub struct TestRepoFixture {
+    pub test_repo: TestRepo,
+    pub settings: UserSettings,
+    pub commits: HashMap<String, Commit>,
+}
+
+impl TestRepoFixture {
+    pub fn repo(&self) -> &Arc<ReadonlyRepo> {
+        &self.test_repo.repo
+    }
+
+    /// Returns the commit declared with the given name. Panics if there's no
+    /// such commit.
+    pub fn commit(&self, name: &str) -> &Commit {
+        self.commits
+            .get(name)
+            .unwrap_or_else(|| panic!("no commit named {name:?}"))
+    }
+}